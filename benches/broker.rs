@@ -0,0 +1,206 @@
+//! Output fan-out benchmark: the ring-buffer `Broker` against the tokio
+//! broadcast channel it replaced.
+//!
+//! A producer thread publishes 4 KiB chunks, as the PTY reader does, while
+//! subscriber tasks drain them the way raw WebSocket clients do. The
+//! producer publishes in bursts and waits for every subscriber to drain
+//! each one, so both sides deliver every byte and do the same work. Each
+//! run reports delivered throughput, the allocations made while it ran,
+//! and the peak bytes live on the heap.
+//!
+//! ```text
+//! cargo bench --bench broker
+//! ```
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::{Bytes, BytesMut};
+use tokio::sync::broadcast;
+use wsh::broker::Broker;
+
+struct Counting;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+static ALLOC_BYTES: AtomicUsize = AtomicUsize::new(0);
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        ALLOC_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        let live = LIVE.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(live, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const CHUNK: usize = 4096;
+const CHUNKS: usize = 4096; // 16 MiB per run
+const SUBSCRIBERS: usize = 32;
+/// Chunks published before waiting for subscribers to catch up; well
+/// under both the broadcast capacity and half the ring.
+const BURST: usize = 16;
+/// The capacity `Broker` used when it wrapped a broadcast channel.
+const BROADCAST_CAPACITY: usize = 64;
+/// The old per-client coalescing cap.
+const MAX_COALESCED_BYTES: usize = 64 * 1024;
+
+struct Run {
+    elapsed: Duration,
+    delivered: usize,
+    allocs: usize,
+    alloc_bytes: usize,
+    peak: usize,
+}
+
+fn measure(
+    rt: &tokio::runtime::Runtime,
+    setup: impl FnOnce(Arc<AtomicUsize>) -> (Vec<tokio::task::JoinHandle<()>>, std::thread::JoinHandle<()>),
+) -> Run {
+    let delivered = Arc::new(AtomicUsize::new(0));
+    let base_live = LIVE.load(Ordering::Relaxed);
+    PEAK.store(base_live, Ordering::Relaxed);
+    let allocs = ALLOCS.load(Ordering::Relaxed);
+    let alloc_bytes = ALLOC_BYTES.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let (subscribers, producer) = rt.block_on(async { setup(delivered.clone()) });
+    producer.join().unwrap();
+    rt.block_on(async {
+        for sub in subscribers {
+            sub.await.unwrap();
+        }
+    });
+    Run {
+        elapsed: start.elapsed(),
+        delivered: delivered.load(Ordering::Relaxed),
+        allocs: ALLOCS.load(Ordering::Relaxed) - allocs,
+        alloc_bytes: ALLOC_BYTES.load(Ordering::Relaxed) - alloc_bytes,
+        peak: PEAK.load(Ordering::Relaxed).saturating_sub(base_live),
+    }
+}
+
+async fn pace(slow: bool) {
+    if slow {
+        tokio::time::sleep(Duration::from_micros(200)).await;
+    }
+}
+
+/// Publish every chunk, a burst at a time, waiting for all subscribers to
+/// drain each burst.
+fn produce(delivered: Arc<AtomicUsize>, publish: impl Fn(&[u8])) {
+    let buf = [b'x'; CHUNK];
+    for burst in 0..CHUNKS / BURST {
+        for _ in 0..BURST {
+            publish(&buf);
+        }
+        let sent = (burst + 1) * BURST * CHUNK;
+        while delivered.load(Ordering::Relaxed) < sent * SUBSCRIBERS {
+            std::thread::yield_now();
+        }
+    }
+}
+
+fn ring(rt: &tokio::runtime::Runtime, slow: bool) -> Run {
+    measure(rt, |delivered| {
+        let broker = Broker::new();
+        let subscribers = (0..SUBSCRIBERS)
+            .map(|_| {
+                let mut rx = broker.subscribe();
+                let delivered = delivered.clone();
+                tokio::spawn(async move {
+                    while let Ok(data) = rx.recv().await {
+                        delivered.fetch_add(data.len(), Ordering::Relaxed);
+                        pace(slow).await;
+                    }
+                })
+            })
+            .collect();
+        let producer = std::thread::spawn(move || produce(delivered, |buf| broker.publish(buf)));
+        (subscribers, producer)
+    })
+}
+
+fn broadcast_channel(rt: &tokio::runtime::Runtime, slow: bool) -> Run {
+    measure(rt, |delivered| {
+        let (tx, _) = broadcast::channel::<Bytes>(BROADCAST_CAPACITY);
+        let subscribers = (0..SUBSCRIBERS)
+            .map(|_| {
+                let mut rx = tx.subscribe();
+                let delivered = delivered.clone();
+                tokio::spawn(async move {
+                    loop {
+                        // What each raw client did with the broadcast:
+                        // take one chunk, then coalesce whatever was queued.
+                        let first = match rx.recv().await {
+                            Ok(data) => data,
+                            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                            Err(broadcast::error::RecvError::Closed) => break,
+                        };
+                        let mut buf = BytesMut::from(&first[..]);
+                        while buf.len() < MAX_COALESCED_BYTES {
+                            match rx.try_recv() {
+                                Ok(data) => buf.extend_from_slice(&data),
+                                Err(_) => break,
+                            }
+                        }
+                        delivered.fetch_add(buf.len(), Ordering::Relaxed);
+                        pace(slow).await;
+                    }
+                })
+            })
+            .collect();
+        let producer = std::thread::spawn(move || {
+            produce(delivered, |buf| {
+                let _ = tx.send(Bytes::copy_from_slice(buf));
+            })
+        });
+        (subscribers, producer)
+    })
+}
+
+fn report(name: &str, run: &Run) {
+    let secs = run.elapsed.as_secs_f64();
+    println!(
+        "{:<24} {:>9.1} {:>12.1} {:>10} {:>12.1} {:>10.1}",
+        name,
+        secs * 1000.0,
+        run.delivered as f64 / secs / (1024.0 * 1024.0),
+        run.allocs,
+        run.alloc_bytes as f64 / (1024.0 * 1024.0),
+        run.peak as f64 / 1024.0,
+    );
+}
+
+fn main() {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    println!(
+        "{} subscribers, {} x {} byte chunks\n",
+        SUBSCRIBERS, CHUNKS, CHUNK
+    );
+    println!(
+        "{:<24} {:>9} {:>12} {:>10} {:>12} {:>10}",
+        "", "ms", "MiB/s out", "allocs", "alloc MiB", "peak KiB"
+    );
+    for slow in [false, true] {
+        let label = if slow { "slow" } else { "fast" };
+        // Warm up the runtime's worker threads before measuring.
+        ring(&rt, slow);
+        report(&format!("broadcast ({label})"), &broadcast_channel(&rt, slow));
+        report(&format!("ring ({label})"), &ring(&rt, slow));
+    }
+}
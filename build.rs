@@ -10,6 +10,11 @@ fn main() {
     println!("cargo:rerun-if-changed=web/vite.config.ts");
     println!("cargo:rerun-if-changed=web/tsconfig.json");
 
+    // Nothing to embed when the web UI is compiled out.
+    if env::var("CARGO_FEATURE_WEB_UI").is_err() {
+        return;
+    }
+
     if env::var("WSH_SKIP_WEB_BUILD").is_ok() {
         eprintln!("cargo:warning=WSH_SKIP_WEB_BUILD is set — skipping web frontend build");
         ensure_web_dist();
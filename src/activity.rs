@@ -56,6 +56,15 @@ impl ActivityTracker {
         self.tx.borrow().elapsed().as_millis() as u64
     }
 
+    /// Unix time of the last activity, in milliseconds.
+    pub fn last_activity_at(&self) -> u64 {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        now.saturating_sub(self.last_activity_ms())
+    }
+
     /// Wait until `timeout` has elapsed since the last activity.
     ///
     /// If `last_seen` is provided and matches the current generation, the
@@ -146,6 +155,39 @@ impl ActivityTracker {
     }
 }
 
+/// Wait until every tracker has been idle for `timeout` at the same time.
+///
+/// Each tracker is awaited concurrently; once all have settled, the round
+/// only counts if none of them saw new activity in the meantime, otherwise
+/// another round starts. With `fresh`, the first round observes `timeout` of
+/// real silence on each tracker even if it was already idle.
+///
+/// Returns each tracker's generation, in order.
+pub async fn wait_for_all_idle(
+    trackers: &[ActivityTracker],
+    timeout: Duration,
+    mut fresh: bool,
+) -> Vec<u64> {
+    loop {
+        let generations = futures::future::join_all(trackers.iter().map(|tracker| async move {
+            if fresh {
+                tracker.wait_for_fresh_idle(timeout).await
+            } else {
+                tracker.wait_for_idle(timeout, None).await
+            }
+        }))
+        .await;
+        if trackers
+            .iter()
+            .zip(&generations)
+            .all(|(tracker, &generation)| tracker.generation() == generation)
+        {
+            return generations;
+        }
+        fresh = false;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -346,6 +388,42 @@ mod tests {
         assert!(r2 >= Duration::from_millis(50));
     }
 
+    #[tokio::test]
+    async fn all_idle_waits_for_the_busiest_tracker() {
+        let quiet = ActivityTracker::new();
+        let busy = ActivityTracker::new();
+        let busy2 = busy.clone();
+        let ticker = tokio::spawn(async move {
+            for _ in 0..4 {
+                busy2.touch();
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+
+        let start = Instant::now();
+        let generations =
+            wait_for_all_idle(&[quiet.clone(), busy.clone()], Duration::from_millis(100), false).await;
+        // The busy tracker keeps touching for ~30ms, then needs 100ms of quiet.
+        assert!(start.elapsed() >= Duration::from_millis(120));
+        assert_eq!(generations, vec![0, 4]);
+        ticker.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn all_idle_fresh_waits_even_when_already_idle() {
+        let a = ActivityTracker::new();
+        let b = ActivityTracker::new();
+        tokio::time::sleep(Duration::from_millis(60)).await;
+
+        let start = Instant::now();
+        wait_for_all_idle(&[a.clone(), b.clone()], Duration::from_millis(50), false).await;
+        assert!(start.elapsed() < Duration::from_millis(40));
+
+        let start = Instant::now();
+        wait_for_all_idle(&[a, b], Duration::from_millis(50), true).await;
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
     #[tokio::test]
     async fn last_activity_ms_zero_at_start() {
         let tracker = ActivityTracker::new();
@@ -361,4 +439,17 @@ mod tests {
         tokio::time::sleep(Duration::from_millis(60)).await;
         assert!(tracker.last_activity_ms() >= 50);
     }
+
+    #[tokio::test]
+    async fn last_activity_at_is_wall_clock_of_last_touch() {
+        let before = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let tracker = ActivityTracker::new();
+        tracker.touch();
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        let at = tracker.last_activity_at();
+        assert!(at + 1 >= before && at < before + 50, "{} vs {}", at, before);
+    }
 }
@@ -1,10 +1,11 @@
 use std::sync::Arc;
 
-use axum::{extract::Request, middleware::Next, response::Response};
-use subtle::ConstantTimeEq;
+use axum::{extract::Request, http::Method, middleware::Next, response::Response};
 
 use super::error::ApiError;
-use super::ticket::TicketStore;
+use super::ticket::{TicketBinding, TicketStore};
+use super::tokens::{TokenGrant, TokenScope, TokenStore};
+use crate::session::SessionRegistry;
 
 /// Extract a Bearer token from the Authorization header.
 fn extract_bearer(req: &Request) -> Option<String> {
@@ -27,6 +28,15 @@ fn extract_ticket(req: &Request) -> Option<String> {
     })
 }
 
+/// The client a ticket presented with this request must have been issued to.
+fn ticket_binding(req: &Request) -> TicketBinding {
+    let ip = req
+        .extensions()
+        .get::<axum::extract::ConnectInfo<std::net::SocketAddr>>()
+        .map(|info| info.0.ip());
+    TicketBinding::new(ip, req.headers())
+}
+
 /// Check if this request is a WebSocket upgrade.
 fn is_ws_upgrade(req: &Request) -> bool {
     req.headers()
@@ -36,39 +46,172 @@ fn is_ws_upgrade(req: &Request) -> bool {
         .unwrap_or(false)
 }
 
+/// Whether the path is a session's file transfer endpoint.
+fn is_file_path(path: &str) -> bool {
+    path.strip_prefix("/sessions/")
+        .and_then(|rest| rest.strip_suffix("/file"))
+        .is_some_and(|name| !name.is_empty() && !name.contains('/'))
+}
+
+/// Whether the path is a session's approval mode or one of its held
+/// inputs.
+fn is_approvals_path(path: &str) -> bool {
+    let Some((name, rest)) = path.strip_prefix("/sessions/").and_then(|rest| rest.split_once('/')) else {
+        return false;
+    };
+    !name.is_empty()
+        && match rest.strip_prefix("approvals") {
+            Some(rest) => rest.is_empty() || rest.strip_prefix('/').is_some_and(|id| !id.is_empty() && !id.contains('/')),
+            None => false,
+        }
+}
+
+/// Whether the query string names a federated `server`.
+fn has_server_param(req: &Request) -> bool {
+    req.uri().query().is_some_and(|query| {
+        query.split('&').any(|pair| pair.starts_with("server="))
+    })
+}
+
+/// The minimum scope a request needs, by route.
+///
+/// Token management needs `admin`, as do changes to server configuration
+/// and federation, joining as a relayed backend, and reading the server's
+/// logs. WebSockets and MCP can send input, so they need `input` even though
+/// the upgrade itself is a GET, and so does reading a session's files.
+/// Any other read is `read_only` and any other write is `input`.
+fn required_scope(req: &Request) -> TokenScope {
+    let path = req.uri().path();
+    let is_read = req.method() == Method::GET || req.method() == Method::HEAD;
+    if path.starts_with("/auth/tokens") || path == "/server/logs" || path == "/federation/relay" {
+        TokenScope::Admin
+    } else if path == "/auth/ws-ticket" {
+        // The ticket inherits this token's grant.
+        TokenScope::ReadOnly
+    } else if path.starts_with("/server/")
+        || path == "/servers"
+        || path.starts_with("/servers/")
+        || path == "/templates"
+        || path.starts_with("/templates/")
+    {
+        if is_read {
+            TokenScope::ReadOnly
+        } else {
+            TokenScope::Admin
+        }
+    } else if is_approvals_path(path) && !is_read {
+        // Approval is meant to be a human's call, so an agent's input token
+        // can't answer its own held input or switch the mode off.
+        TokenScope::Admin
+    } else if path == "/screens" {
+        // A POST only because the session list travels in the body.
+        TokenScope::ReadOnly
+    } else if is_ws_upgrade(req) || path == "/mcp" || path.starts_with("/mcp/") || is_file_path(path) {
+        TokenScope::Input
+    } else if is_read {
+        TokenScope::ReadOnly
+    } else {
+        TokenScope::Input
+    }
+}
+
+/// Confine a tag-restricted token to the sessions it is tagged for.
+///
+/// Such a token may use per-session routes for matching sessions, list
+/// sessions, fetch screens, broadcast input and run commands in bulk (the
+/// handlers filter the sessions),
+/// create sessions (the handler requires a matching tag), and acquire
+/// WebSocket tickets. Server-wide
+/// routes, MCP, and federated `?server=` requests are refused because they
+/// would reach sessions outside the restriction.
+fn check_tag_restriction(
+    grant: &TokenGrant,
+    req: &Request,
+    sessions: &SessionRegistry,
+) -> Result<(), ApiError> {
+    if !grant.is_tag_restricted() {
+        return Ok(());
+    }
+    if has_server_param(req) {
+        return Err(ApiError::InsufficientScope(
+            "tag-restricted tokens cannot target federated servers".to_string(),
+        ));
+    }
+    let path = req.uri().path();
+    if path == "/sessions/input" && req.method() == Method::POST {
+        // Broadcast input; the handler skips sessions the token can't reach.
+        return Ok(());
+    }
+    if let Some(rest) = path.strip_prefix("/sessions/") {
+        let name = rest.split('/').next().unwrap_or_default();
+        let allowed = sessions
+            .get(name)
+            .is_some_and(|session| grant.allows_session(&session.tags.read()));
+        if allowed {
+            return Ok(());
+        }
+        return Err(ApiError::InsufficientScope(format!(
+            "session '{}' does not carry any of this token's tags",
+            &name[..name.len().min(128)]
+        )));
+    }
+    match path {
+        "/sessions" | "/screens" | "/orchestrate/run" | "/auth/ws-ticket" | "/openapi.yaml" | "/docs" | "/schemas" => Ok(()),
+        _ if path.starts_with("/schemas/") => Ok(()),
+        _ => Err(ApiError::InsufficientScope(
+            "tag-restricted tokens can only access their own sessions".to_string(),
+        )),
+    }
+}
+
+/// Check that `grant` permits `req`.
+fn authorize(grant: &TokenGrant, req: &Request, sessions: &SessionRegistry) -> Result<(), ApiError> {
+    let required = required_scope(req);
+    if !grant.allows(required) {
+        return Err(ApiError::InsufficientScope(format!(
+            "requires {} scope, token '{}' has {}",
+            required, grant.name, grant.scope
+        )));
+    }
+    check_tag_restriction(grant, req, sessions)
+}
+
 /// Auth middleware function.
 ///
 /// Authentication flow:
-/// 1. Try Bearer token from Authorization header
-/// 2. If missing/invalid AND the request is a WebSocket upgrade, try `?ticket=` query param
-///    against the TicketStore (single-use, 30s TTL)
+/// 1. Try Bearer token from Authorization header against the TokenStore
+/// 2. If missing AND the request is a WebSocket upgrade, try `?ticket=` query param
+///    against the TicketStore (single-use, 30s TTL, same client address and
+///    `Origin` as the request that acquired it)
 /// 3. Otherwise reject
+///
+/// The resulting [`TokenGrant`] is checked against the route (scope and tag
+/// restriction) and then inserted into the request extensions for handlers.
 pub async fn require_auth(
-    expected_token: String,
+    tokens: Arc<TokenStore>,
     ticket_store: Option<Arc<TicketStore>>,
-    req: Request,
+    sessions: SessionRegistry,
+    mut req: Request,
     next: Next,
 ) -> Result<Response, ApiError> {
-    // Try Bearer token first
-    if let Some(ref token) = extract_bearer(&req) {
-        if token.as_bytes().ct_eq(expected_token.as_bytes()).into() {
-            return Ok(next.run(req).await);
-        }
-        return Err(ApiError::AuthInvalid);
-    }
-
-    // For WebSocket upgrades, try ticket-based auth
-    if is_ws_upgrade(&req) {
-        if let Some(ref store) = ticket_store {
-            if let Some(ticket) = extract_ticket(&req) {
-                if store.validate(&ticket) {
-                    return Ok(next.run(req).await);
-                }
+    let grant = if let Some(ref token) = extract_bearer(&req) {
+        tokens.authenticate(token).ok_or(ApiError::AuthInvalid)?
+    } else {
+        // For WebSocket upgrades, try ticket-based auth
+        let ticket_grant = if is_ws_upgrade(&req) {
+            match (&ticket_store, extract_ticket(&req)) {
+                (Some(store), Some(ticket)) => store.validate(&ticket, &ticket_binding(&req)),
+                _ => None,
             }
-        }
-    }
+        } else {
+            None
+        };
+        ticket_grant.ok_or(ApiError::AuthRequired)?
+    };
 
-    Err(ApiError::AuthRequired)
+    authorize(&grant, &req, &sessions)?;
+    req.extensions_mut().insert(grant);
+    Ok(next.run(req).await)
 }
 
 #[cfg(test)]
@@ -91,15 +234,50 @@ mod tests {
     }
 
     fn test_app_with_tickets(token: String, store: Option<Arc<TicketStore>>) -> Router {
+        let tokens = Arc::new(TokenStore::new());
+        tokens.set_root(token);
+        test_app_with_store(tokens, store, SessionRegistry::new())
+    }
+
+    fn test_app_with_store(
+        tokens: Arc<TokenStore>,
+        store: Option<Arc<TicketStore>>,
+        sessions: SessionRegistry,
+    ) -> Router {
         Router::new()
             .route("/test", get(ok_handler))
+            .route("/auth/tokens", get(ok_handler))
+            .route("/sessions", get(ok_handler))
+            .route("/sessions/{name}/screen", get(ok_handler))
+            .route("/sessions/{name}/input", axum::routing::post(ok_handler))
+            .route("/sessions/{name}/file", get(ok_handler))
+            .route("/screens", axum::routing::post(ok_handler))
+            .route("/sessions/input", axum::routing::post(ok_handler))
+            .route("/sessions/{name}/approvals", get(ok_handler).patch(ok_handler))
+            .route("/sessions/{name}/approvals/{id}", axum::routing::post(ok_handler))
             .layer(axum::middleware::from_fn(move |req, next| {
-                let t = token.clone();
+                let t = tokens.clone();
                 let s = store.clone();
-                async move { require_auth(t, s, req, next).await }
+                let r = sessions.clone();
+                async move { require_auth(t, s, r, req, next).await }
             }))
     }
 
+    async fn status_for(app: &Router, method: &str, uri: &str, token: &str) -> StatusCode {
+        app.clone()
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("authorization", format!("Bearer {token}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status()
+    }
+
     // ── extract_bearer tests ──────────────────────────────────────
 
     #[test]
@@ -243,13 +421,13 @@ mod tests {
     #[tokio::test]
     async fn ticket_accepted_on_ws_upgrade() {
         let store = Arc::new(TicketStore::new());
-        let ticket = store.create().unwrap();
+        let ticket = store.create(TokenGrant::root(), TicketBinding::default()).unwrap();
         let app = test_app_with_tickets("secret".to_string(), Some(store));
 
         let response = app
             .oneshot(
                 Request::builder()
-                    .uri(&format!("/test?ticket={ticket}"))
+                    .uri(format!("/test?ticket={ticket}"))
                     .header("upgrade", "websocket")
                     .body(Body::empty())
                     .unwrap(),
@@ -263,14 +441,14 @@ mod tests {
     #[tokio::test]
     async fn ticket_rejected_on_non_ws_request() {
         let store = Arc::new(TicketStore::new());
-        let ticket = store.create().unwrap();
+        let ticket = store.create(TokenGrant::root(), TicketBinding::default()).unwrap();
         let app = test_app_with_tickets("secret".to_string(), Some(store));
 
         // Ticket without WS upgrade header should be rejected
         let response = app
             .oneshot(
                 Request::builder()
-                    .uri(&format!("/test?ticket={ticket}"))
+                    .uri(format!("/test?ticket={ticket}"))
                     .body(Body::empty())
                     .unwrap(),
             )
@@ -283,7 +461,7 @@ mod tests {
     #[tokio::test]
     async fn ticket_single_use() {
         let store = Arc::new(TicketStore::new());
-        let ticket = store.create().unwrap();
+        let ticket = store.create(TokenGrant::root(), TicketBinding::default()).unwrap();
         let app = test_app_with_tickets("secret".to_string(), Some(store));
 
         // First use succeeds
@@ -291,7 +469,7 @@ mod tests {
             .clone()
             .oneshot(
                 Request::builder()
-                    .uri(&format!("/test?ticket={ticket}"))
+                    .uri(format!("/test?ticket={ticket}"))
                     .header("upgrade", "websocket")
                     .body(Body::empty())
                     .unwrap(),
@@ -304,7 +482,7 @@ mod tests {
         let response = app
             .oneshot(
                 Request::builder()
-                    .uri(&format!("/test?ticket={ticket}"))
+                    .uri(format!("/test?ticket={ticket}"))
                     .header("upgrade", "websocket")
                     .body(Body::empty())
                     .unwrap(),
@@ -332,4 +510,153 @@ mod tests {
 
         assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
     }
+
+    #[tokio::test]
+    async fn ticket_rejected_from_other_origin() {
+        let store = Arc::new(TicketStore::new());
+        let binding = TicketBinding {
+            ip: None,
+            origin: Some("https://wsh.example".to_string()),
+        };
+        let app = test_app_with_tickets("secret".to_string(), Some(store.clone()));
+        let upgrade = |ticket: String, origin: &str| {
+            Request::builder()
+                .uri(format!("/test?ticket={ticket}"))
+                .header("upgrade", "websocket")
+                .header("origin", origin)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let ticket = store.create(TokenGrant::root(), binding.clone()).unwrap();
+        let response = app.clone().oneshot(upgrade(ticket, "https://evil.example")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let ticket = store.create(TokenGrant::root(), binding).unwrap();
+        let response = app.oneshot(upgrade(ticket, "https://wsh.example")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    // ── Scope and tag restriction tests ──────────────────────────
+
+    #[tokio::test]
+    async fn read_only_token_cannot_send_input() {
+        let tokens = Arc::new(TokenStore::new());
+        tokens.set_root("root-secret".to_string());
+        let (_, reader) = tokens
+            .create("reader".to_string(), TokenScope::ReadOnly, vec![])
+            .unwrap();
+        let app = test_app_with_store(tokens, None, SessionRegistry::new());
+
+        assert_eq!(status_for(&app, "GET", "/test", &reader).await, StatusCode::OK);
+        assert_eq!(
+            status_for(&app, "POST", "/sessions/dev/input", &reader).await,
+            StatusCode::FORBIDDEN
+        );
+        // Files can hold more than the screen shows.
+        assert_eq!(
+            status_for(&app, "GET", "/sessions/dev/file?path=x", &reader).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(status_for(&app, "POST", "/screens", &reader).await, StatusCode::OK);
+        assert_eq!(
+            status_for(&app, "GET", "/auth/tokens", &reader).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status_for(&app, "GET", "/auth/tokens", "root-secret").await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn input_token_cannot_answer_approvals() {
+        let tokens = Arc::new(TokenStore::new());
+        tokens.set_root("root-secret".to_string());
+        let (_, agent) = tokens
+            .create("agent".to_string(), TokenScope::Input, vec![])
+            .unwrap();
+        let app = test_app_with_store(tokens, None, SessionRegistry::new());
+
+        assert_eq!(status_for(&app, "GET", "/sessions/dev/approvals", &agent).await, StatusCode::OK);
+        assert_eq!(
+            status_for(&app, "POST", "/sessions/dev/approvals/1", &agent).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status_for(&app, "PATCH", "/sessions/dev/approvals", &agent).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status_for(&app, "POST", "/sessions/dev/approvals/1", "root-secret").await,
+            StatusCode::OK
+        );
+        assert!(!is_approvals_path("/sessions/approvals"));
+        assert!(!is_approvals_path("/sessions/dev/approvals/1/x"));
+    }
+
+    #[tokio::test]
+    async fn tag_restricted_token_only_reaches_tagged_sessions() {
+        let sessions = SessionRegistry::new();
+        let (build, _) = crate::session::Session::spawn("build".to_string(), Default::default(), 24, 80).unwrap();
+        build.tags.write().insert("ci".to_string());
+        sessions.insert(Some("build".to_string()), build).unwrap();
+        let (other, _) = crate::session::Session::spawn("other".to_string(), Default::default(), 24, 80).unwrap();
+        sessions.insert(Some("other".to_string()), other).unwrap();
+
+        let tokens = Arc::new(TokenStore::new());
+        let (_, secret) = tokens
+            .create("ci-agent".to_string(), TokenScope::Input, vec!["ci".to_string()])
+            .unwrap();
+        let app = test_app_with_store(tokens, None, sessions.clone());
+
+        assert_eq!(
+            status_for(&app, "GET", "/sessions/build/screen", &secret).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status_for(&app, "GET", "/sessions/other/screen", &secret).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status_for(&app, "GET", "/sessions/build/screen?server=remote", &secret).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(status_for(&app, "GET", "/sessions", &secret).await, StatusCode::OK);
+        assert_eq!(status_for(&app, "POST", "/screens", &secret).await, StatusCode::OK);
+        assert_eq!(status_for(&app, "POST", "/sessions/input", &secret).await, StatusCode::OK);
+        assert_eq!(status_for(&app, "GET", "/test", &secret).await, StatusCode::FORBIDDEN);
+
+        for name in sessions.list() {
+            if let Some(s) = sessions.remove(&name) {
+                s.shutdown();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn ticket_inherits_scope_of_requesting_token() {
+        let tokens = Arc::new(TokenStore::new());
+        let store = Arc::new(TicketStore::new());
+        let grant = TokenGrant {
+            name: "reader".to_string(),
+            scope: TokenScope::ReadOnly,
+            tags: vec![],
+        };
+        let ticket = store.create(grant, TicketBinding::default()).unwrap();
+        let app = test_app_with_store(tokens, Some(store), SessionRegistry::new());
+
+        // WebSockets need input scope, so a read-only ticket is refused
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/test?ticket={ticket}"))
+                    .header("upgrade", "websocket")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
 }
@@ -0,0 +1,112 @@
+//! Flow control for raw output WebSocket clients.
+//!
+//! Every raw output client reads the session's output ring at its own pace.
+//! A fast client gets each chunk as it comes. A slow one (a phone on a poor
+//! link) finds more output waiting when it's ready for more; the broker
+//! hands it over as one frame instead of one frame per PTY read. Once a
+//! client is [`SLOW_CLIENT_BACKLOG`] bytes behind, replaying the backlog
+//! would only show it stale output for seconds, so the backlog is dropped
+//! and the client's [`LagPolicy`] decides what happens instead.
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::broker::Subscriber;
+
+/// Unread bytes at which a client counts as behind. Half the ring, so it
+/// trips well before the ring itself overwrites the client's output.
+pub const SLOW_CLIENT_BACKLOG: usize = crate::broker::RING_CAPACITY / 2;
+
+/// What a raw output client that falls behind gets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LagPolicy {
+    /// Skip the missed output and redraw the current screen.
+    #[default]
+    Resync,
+    /// Close the connection with code 1013 so the client reconnects.
+    Disconnect,
+}
+
+/// The next step for a raw output client.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Output {
+    /// Output to send: everything waiting, up to the broker's read cap.
+    Data(Bytes),
+    /// The client fell behind and its backlog was dropped.
+    Behind,
+    /// The session's output ended.
+    Closed,
+}
+
+/// Wait for output and take everything already waiting with it.
+///
+/// Cancel-safe: nothing is taken from `rx` until output has arrived.
+pub async fn recv_coalesced(rx: &mut Subscriber) -> Output {
+    if rx.len() >= SLOW_CLIENT_BACKLOG {
+        return skip_backlog(rx);
+    }
+    match rx.recv().await {
+        Ok(data) => Output::Data(data),
+        Err(RecvError::Closed) => Output::Closed,
+        Err(RecvError::Lagged(_)) => skip_backlog(rx),
+    }
+}
+
+fn skip_backlog(rx: &mut Subscriber) -> Output {
+    let skipped = rx.len();
+    *rx = rx.resubscribe();
+    tracing::warn!(skipped, "raw output client fell behind, dropping its backlog");
+    Output::Behind
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::{Broker, MAX_READ};
+
+    #[tokio::test]
+    async fn waiting_output_is_coalesced() {
+        let broker = Broker::new();
+        let mut rx = broker.subscribe();
+        broker.publish(b"one");
+        assert_eq!(recv_coalesced(&mut rx).await, Output::Data(Bytes::from("one")));
+
+        for chunk in ["a", "b", "c"] {
+            broker.publish(chunk.as_bytes());
+        }
+        assert_eq!(recv_coalesced(&mut rx).await, Output::Data(Bytes::from("abc")));
+    }
+
+    #[tokio::test]
+    async fn coalescing_stops_at_the_read_cap() {
+        let broker = Broker::new();
+        let mut rx = broker.subscribe();
+        let chunk = vec![b'x'; MAX_READ / 2];
+        for _ in 0..3 {
+            broker.publish(&chunk);
+        }
+        let Output::Data(first) = recv_coalesced(&mut rx).await else {
+            panic!("expected data");
+        };
+        assert_eq!(first.len(), MAX_READ);
+        assert_eq!(recv_coalesced(&mut rx).await, Output::Data(Bytes::from(chunk)));
+    }
+
+    #[tokio::test]
+    async fn a_client_far_behind_drops_its_backlog() {
+        let broker = Broker::new();
+        let mut rx = broker.subscribe();
+        broker.publish(&vec![b'x'; SLOW_CLIENT_BACKLOG]);
+        assert_eq!(recv_coalesced(&mut rx).await, Output::Behind);
+        assert!(rx.is_empty());
+
+        // Output after the skip comes through as usual.
+        broker.publish(b"next");
+        assert_eq!(recv_coalesced(&mut rx).await, Output::Data(Bytes::from("next")));
+
+        drop(broker);
+        assert_eq!(recv_coalesced(&mut rx).await, Output::Closed);
+    }
+}
@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -69,6 +69,57 @@ pub enum ApiError {
     ServerAlreadyRegistered(String),
     /// 503 - A backend server is unavailable.
     ServerUnavailable(String),
+    /// 503 - The server is in maintenance mode and not accepting new sessions.
+    ServerMaintenance(String),
+    /// 503 - A subsystem has been switched off through `/server/features`.
+    FeatureDisabled(String),
+    /// 403 - The token is valid but its scope or tags do not permit the request.
+    InsufficientScope(String),
+    /// 404 - A specific API token name was not found.
+    TokenNotFound(String),
+    /// 409 - An API token with this name already exists.
+    TokenNameConflict(String),
+    /// 409 - Secret input refused because the terminal would echo it.
+    EchoEnabled,
+    /// 409 - The session's shell is not ready to read a variable.
+    ShellNotReady(String),
+    /// 409 - A signal was sent to a session whose child has already exited.
+    ChildExited(String),
+    /// 409 - A handoff of keyboard control cannot be requested or answered
+    /// in the session's current state.
+    HandoffConflict(String),
+    /// 403 - A session was requested under another user on a server that
+    /// cannot switch users.
+    UserSwitchNotPermitted(String),
+    /// 502 - An ssh session's connection failed before it came up.
+    SshConnectFailed(String),
+    /// 403 - Input matched a block pattern of the input policy.
+    InputBlocked(String),
+    /// 403 - The server's `on_client_attach` hook failed and its policy
+    /// is to reject the client.
+    HookRejected(String),
+    /// 403 - A file path is outside the directories file transfer may use,
+    /// or file transfer is not enabled.
+    FileNotAllowed(String),
+    /// 404 - A file to read, or the directory of one to write, does not exist.
+    FileNotFound(String),
+    /// 413 - A file is larger than file transfer allows.
+    FileTooLarge(String),
+    /// 404 - The session has no bookmark with this name.
+    BookmarkNotFound(String),
+    /// 404 - No session template with this name.
+    TemplateNotFound(String),
+    /// 404 - Nothing has been copied in the session yet.
+    ClipboardEmpty(String),
+    /// 404 - The session has no inline image with this id.
+    ImageNotFound(String),
+    /// 404 - The session has no pipe with this ID.
+    PipeNotFound(String),
+    /// 404 - The session holds no input with this ID for approval.
+    ApprovalNotFound(u64),
+    /// 429 - The client exceeded the request rate limit; retry after the
+    /// given number of seconds.
+    RateLimited(u64),
     /// 500 - Catch-all internal error.
     InternalError(String),
 }
@@ -106,6 +157,29 @@ impl ApiError {
             ApiError::ServerNotFound(_) => StatusCode::NOT_FOUND,
             ApiError::ServerAlreadyRegistered(_) => StatusCode::CONFLICT,
             ApiError::ServerUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::ServerMaintenance(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::FeatureDisabled(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::InsufficientScope(_) => StatusCode::FORBIDDEN,
+            ApiError::TokenNotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::TokenNameConflict(_) => StatusCode::CONFLICT,
+            ApiError::EchoEnabled => StatusCode::CONFLICT,
+            ApiError::ShellNotReady(_) => StatusCode::CONFLICT,
+            ApiError::ChildExited(_) => StatusCode::CONFLICT,
+            ApiError::HandoffConflict(_) => StatusCode::CONFLICT,
+            ApiError::UserSwitchNotPermitted(_) => StatusCode::FORBIDDEN,
+            ApiError::SshConnectFailed(_) => StatusCode::BAD_GATEWAY,
+            ApiError::InputBlocked(_) => StatusCode::FORBIDDEN,
+            ApiError::HookRejected(_) => StatusCode::FORBIDDEN,
+            ApiError::FileNotAllowed(_) => StatusCode::FORBIDDEN,
+            ApiError::FileNotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::FileTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::BookmarkNotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::TemplateNotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::ClipboardEmpty(_) => StatusCode::NOT_FOUND,
+            ApiError::ImageNotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::PipeNotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::ApprovalNotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
             ApiError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -142,6 +216,29 @@ impl ApiError {
             ApiError::ServerNotFound(_) => "server_not_found",
             ApiError::ServerAlreadyRegistered(_) => "server_already_registered",
             ApiError::ServerUnavailable(_) => "server_unavailable",
+            ApiError::ServerMaintenance(_) => "server_maintenance",
+            ApiError::FeatureDisabled(_) => "feature_disabled",
+            ApiError::InsufficientScope(_) => "insufficient_scope",
+            ApiError::TokenNotFound(_) => "token_not_found",
+            ApiError::TokenNameConflict(_) => "token_name_conflict",
+            ApiError::EchoEnabled => "echo_enabled",
+            ApiError::ShellNotReady(_) => "shell_not_ready",
+            ApiError::ChildExited(_) => "child_exited",
+            ApiError::HandoffConflict(_) => "handoff_conflict",
+            ApiError::UserSwitchNotPermitted(_) => "user_switch_not_permitted",
+            ApiError::SshConnectFailed(_) => "ssh_connect_failed",
+            ApiError::InputBlocked(_) => "input_blocked",
+            ApiError::HookRejected(_) => "hook_rejected",
+            ApiError::FileNotAllowed(_) => "file_not_allowed",
+            ApiError::FileNotFound(_) => "file_not_found",
+            ApiError::FileTooLarge(_) => "file_too_large",
+            ApiError::BookmarkNotFound(_) => "bookmark_not_found",
+            ApiError::TemplateNotFound(_) => "template_not_found",
+            ApiError::ClipboardEmpty(_) => "clipboard_empty",
+            ApiError::ImageNotFound(_) => "image_not_found",
+            ApiError::PipeNotFound(_) => "pipe_not_found",
+            ApiError::ApprovalNotFound(_) => "approval_not_found",
+            ApiError::RateLimited(_) => "rate_limited",
             ApiError::InternalError(_) => "internal_error",
         }
     }
@@ -202,23 +299,161 @@ impl ApiError {
             ApiError::ServerUnavailable(detail) => {
                 format!("Server unavailable: {}.", detail)
             }
+            ApiError::ServerMaintenance(reason) => {
+                format!("Server is in maintenance, not accepting new sessions: {}.", reason)
+            }
+            ApiError::FeatureDisabled(feature) => {
+                format!("{} is switched off on this server.", feature)
+            }
+            ApiError::InsufficientScope(detail) => {
+                format!("Token does not permit this request: {}.", detail)
+            }
+            ApiError::TokenNotFound(name) => {
+                format!("Token not found: {}.", &name[..name.len().min(128)])
+            }
+            ApiError::TokenNameConflict(name) => format!("Token name already exists: {}.", name),
+            ApiError::EchoEnabled => {
+                "Terminal echo is enabled; the secret would be displayed. Pass force to send anyway."
+                    .to_string()
+            }
+            ApiError::ShellNotReady(detail) => format!("Shell not ready: {}.", detail),
+            ApiError::ChildExited(name) => {
+                format!("The process of session {} has already exited.", name)
+            }
+            ApiError::HandoffConflict(detail) => format!("Handoff not possible: {}.", detail),
+            ApiError::UserSwitchNotPermitted(detail) => {
+                format!("Cannot spawn the session as another user: {}.", detail)
+            }
+            ApiError::SshConnectFailed(detail) => format!("SSH connection failed: {}.", detail),
+            ApiError::InputBlocked(pattern) => {
+                format!("Input blocked by policy: matches '{}'.", pattern)
+            }
+            ApiError::HookRejected(detail) => format!("Attach rejected: {}.", detail),
+            ApiError::FileNotAllowed(detail) => format!("File access not allowed: {}.", detail),
+            ApiError::FileNotFound(detail) => format!("File not found: {}.", detail),
+            ApiError::FileTooLarge(detail) => format!("File too large: {}.", detail),
+            ApiError::BookmarkNotFound(name) => format!("Bookmark not found: {}.", name),
+            ApiError::TemplateNotFound(name) => format!("Template not found: {}.", name),
+            ApiError::ClipboardEmpty(name) => format!("Nothing has been copied in session {}.", name),
+            ApiError::ImageNotFound(id) => format!("Image not found: {}.", id),
+            ApiError::PipeNotFound(id) => format!("Pipe not found: {}.", id),
+            ApiError::ApprovalNotFound(id) => format!("No input is waiting for approval with ID {}.", id),
+            ApiError::RateLimited(secs) => {
+                format!("Too many requests. Retry in {} second(s).", secs)
+            }
             ApiError::InternalError(detail) => format!("Internal error: {}.", detail),
         }
     }
+
+    /// Returns how many seconds a client should wait before retrying, for
+    /// errors that clear up on their own (back-pressure, resource limits,
+    /// rate limiting, maintenance). Sent as the `Retry-After` header.
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            ApiError::ChannelFull | ApiError::ParserUnavailable => Some(1),
+            ApiError::MaxSessionsReached
+            | ApiError::ResourceLimitReached(_)
+            | ApiError::ServerUnavailable(_) => Some(5),
+            ApiError::ServerMaintenance(_) => Some(30),
+            ApiError::RateLimited(secs) => Some((*secs).max(1)),
+            _ => None,
+        }
+    }
+}
+
+/// Media type of error responses (RFC 7807).
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Builds an RFC 7807 problem response.
+///
+/// `type` is a `urn:wsh:error:<code>` URI and `title` is derived from the
+/// code, so both are as stable as the code itself. The legacy
+/// `{"error": {"code", "message"}}` object is kept alongside the problem
+/// members so existing clients keep working.
+pub fn problem_response(
+    status: StatusCode,
+    code: &str,
+    detail: &str,
+    retry_after: Option<u64>,
+) -> Response {
+    let mut title: String = code.replace('_', " ");
+    if let Some(first) = title.get_mut(..1) {
+        first.make_ascii_uppercase();
+    }
+    let body = serde_json::json!({
+        "type": format!("urn:wsh:error:{}", code),
+        "title": title,
+        "status": status.as_u16(),
+        "detail": detail,
+        "code": code,
+        "error": {
+            "code": code,
+            "message": detail,
+        }
+    });
+    let mut response = (status, Json(body)).into_response();
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+    if let Some(secs) = retry_after {
+        headers.insert(header::RETRY_AFTER, HeaderValue::from(secs));
+    }
+    response
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        let body = serde_json::json!({
-            "error": {
-                "code": self.code(),
-                "message": self.message(),
-            }
-        });
-        (self.status_code(), Json(body)).into_response()
+        problem_response(self.status_code(), self.code(), &self.message(), self.retry_after())
     }
 }
 
+/// Response middleware that rewrites error responses produced outside the
+/// handlers (unmatched routes, wrong methods, body extractor rejections,
+/// body limits) into problem responses, so every 4xx/5xx from the API has
+/// the same shape. Responses that already carry JSON are left alone.
+pub async fn problem_fallback(response: Response) -> Response {
+    let status = response.status();
+    if !(status.is_client_error() || status.is_server_error()) {
+        return response;
+    }
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok())
+        .is_some_and(|ct| ct.contains("json"));
+    if is_json {
+        return response;
+    }
+    let code = match status {
+        StatusCode::NOT_FOUND => "not_found",
+        StatusCode::METHOD_NOT_ALLOWED => "method_not_allowed",
+        StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+        StatusCode::TOO_MANY_REQUESTS => "rate_limited",
+        s if s.is_server_error() => "internal_error",
+        _ => "invalid_request",
+    };
+    let (parts, body) = response.into_parts();
+    let text = axum::body::to_bytes(body, 64 * 1024)
+        .await
+        .map(|b| String::from_utf8_lossy(&b).trim().to_string())
+        .unwrap_or_default();
+    let detail = if text.is_empty() {
+        status.canonical_reason().unwrap_or("Error").to_string()
+    } else {
+        text
+    };
+    let retry_after = parts
+        .headers
+        .get(header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+    let mut problem = problem_response(status, code, &detail, retry_after);
+    if let Some(allow) = parts.headers.get(header::ALLOW) {
+        problem.headers_mut().insert(header::ALLOW, allow.clone());
+    }
+    problem
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -228,7 +463,11 @@ mod tests {
     /// Helper: convert an ApiError into a response and extract the status and
     /// parsed JSON body.
     async fn response_parts(err: ApiError) -> (StatusCode, serde_json::Value) {
-        let response = err.into_response();
+        body_parts(err.into_response()).await
+    }
+
+    /// Helper: extract the status and parsed JSON body of a response.
+    async fn body_parts(response: Response) -> (StatusCode, serde_json::Value) {
         let status = response.status();
         let body = Body::new(response.into_body())
             .collect()
@@ -516,16 +755,77 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn response_content_type_is_json() {
+    async fn response_content_type_is_problem_json() {
         let response = ApiError::NotFound.into_response();
         let ct = response
             .headers()
             .get("content-type")
             .expect("response must have content-type header");
-        assert!(
-            ct.to_str().unwrap().contains("application/json"),
-            "content-type must be application/json"
-        );
+        assert_eq!(ct.to_str().unwrap(), "application/problem+json");
+    }
+
+    #[tokio::test]
+    async fn response_has_problem_members() {
+        let (_, json) = response_parts(ApiError::SessionNotFound("dev".into())).await;
+        assert_eq!(json["type"], "urn:wsh:error:session_not_found");
+        assert_eq!(json["title"], "Session not found");
+        assert_eq!(json["status"], 404);
+        assert_eq!(json["detail"], "Session not found: dev.");
+        assert_eq!(json["code"], "session_not_found");
+    }
+
+    #[tokio::test]
+    async fn resource_limit_errors_carry_retry_after() {
+        for (err, secs) in [
+            (ApiError::ResourceLimitReached("too many overlays".into()), "5"),
+            (ApiError::MaxSessionsReached, "5"),
+            (ApiError::ChannelFull, "1"),
+            (ApiError::ServerMaintenance("upgrade".into()), "30"),
+            (ApiError::RateLimited(3), "3"),
+        ] {
+            let response = err.into_response();
+            assert_eq!(response.headers()["retry-after"], secs);
+        }
+        let response = ApiError::NotFound.into_response();
+        assert!(response.headers().get("retry-after").is_none());
+    }
+
+    #[tokio::test]
+    async fn rate_limited_is_too_many_requests() {
+        let (status, json) = response_parts(ApiError::RateLimited(0)).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(json["code"], "rate_limited");
+        assert_eq!(ApiError::RateLimited(0).retry_after(), Some(1));
+    }
+
+    #[tokio::test]
+    async fn problem_fallback_rewrites_plain_text_errors() {
+        let plain = (StatusCode::METHOD_NOT_ALLOWED, [(header::ALLOW, "GET")], "").into_response();
+        let response = problem_fallback(plain).await;
+        assert_eq!(response.headers()["content-type"], PROBLEM_JSON);
+        assert_eq!(response.headers()["allow"], "GET");
+        let (status, json) = body_parts(response).await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(json["code"], "method_not_allowed");
+        assert_eq!(json["detail"], "Method Not Allowed");
+
+        let rejection =
+            (StatusCode::UNPROCESSABLE_ENTITY, "Failed to deserialize the JSON body").into_response();
+        let (status, json) = body_parts(problem_fallback(rejection).await).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(json["code"], "invalid_request");
+        assert_eq!(json["detail"], "Failed to deserialize the JSON body");
+    }
+
+    #[tokio::test]
+    async fn problem_fallback_keeps_json_and_success_responses() {
+        let response = problem_fallback(ApiError::NoSessions.into_response()).await;
+        let (_, json) = body_parts(response).await;
+        assert_eq!(json["code"], "no_sessions");
+
+        let ok = (StatusCode::OK, "fine").into_response();
+        let response = problem_fallback(ok).await;
+        assert_eq!(response.status(), StatusCode::OK);
     }
 
     // ── Fixed-message variant tests ────────────────────────────────
@@ -707,4 +1007,150 @@ mod tests {
         let msg = json["error"]["message"].as_str().unwrap();
         assert_eq!(msg, "Server unavailable: connection refused.");
     }
+
+    #[tokio::test]
+    async fn server_maintenance_status_and_code() {
+        let (status, json) =
+            response_parts(ApiError::ServerMaintenance("upgrading".into())).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(json["error"]["code"], "server_maintenance");
+        let msg = json["error"]["message"].as_str().unwrap();
+        assert_eq!(msg, "Server is in maintenance, not accepting new sessions: upgrading.");
+    }
+
+    #[tokio::test]
+    async fn feature_disabled_status_and_code() {
+        let (status, json) = response_parts(ApiError::FeatureDisabled("MCP".into())).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(json["error"]["code"], "feature_disabled");
+        assert_eq!(json["error"]["message"], "MCP is switched off on this server.");
+    }
+
+    #[tokio::test]
+    async fn insufficient_scope_status_and_code() {
+        let (status, json) =
+            response_parts(ApiError::InsufficientScope("requires admin scope".into())).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(json["error"]["code"], "insufficient_scope");
+    }
+
+    #[tokio::test]
+    async fn token_errors_status_and_code() {
+        let (status, json) = response_parts(ApiError::TokenNotFound("ci".into())).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(json["error"]["code"], "token_not_found");
+        let (status, json) = response_parts(ApiError::TokenNameConflict("ci".into())).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(json["error"]["code"], "token_name_conflict");
+    }
+
+    #[tokio::test]
+    async fn echo_enabled_is_conflict() {
+        let (status, json) = response_parts(ApiError::EchoEnabled).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(json["error"]["code"], "echo_enabled");
+    }
+
+    #[tokio::test]
+    async fn shell_not_ready_is_conflict() {
+        let (status, json) = response_parts(ApiError::ShellNotReady("input is hidden".into())).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(json["error"]["code"], "shell_not_ready");
+        assert_eq!(json["error"]["message"], "Shell not ready: input is hidden.");
+    }
+
+    #[tokio::test]
+    async fn handoff_conflict_is_conflict() {
+        let (status, json) =
+            response_parts(ApiError::HandoffConflict("no handoff request is pending".into())).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(json["error"]["code"], "handoff_conflict");
+        assert_eq!(json["error"]["message"], "Handoff not possible: no handoff request is pending.");
+    }
+
+    #[tokio::test]
+    async fn user_switch_not_permitted_is_forbidden() {
+        let (status, json) =
+            response_parts(ApiError::UserSwitchNotPermitted("server is not root".into())).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(json["error"]["code"], "user_switch_not_permitted");
+    }
+
+    #[tokio::test]
+    async fn input_blocked_is_forbidden() {
+        let (status, json) = response_parts(ApiError::InputBlocked("rm -rf".into())).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(json["error"]["code"], "input_blocked");
+        assert_eq!(json["error"]["message"], "Input blocked by policy: matches 'rm -rf'.");
+    }
+
+    #[tokio::test]
+    async fn hook_rejected_is_forbidden() {
+        let (status, json) = response_parts(ApiError::HookRejected("the hook exited with code 1".into())).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(json["error"]["code"], "hook_rejected");
+        assert_eq!(json["error"]["message"], "Attach rejected: the hook exited with code 1.");
+    }
+
+    #[tokio::test]
+    async fn child_exited_is_conflict() {
+        let (status, json) = response_parts(ApiError::ChildExited("build".into())).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(json["code"], "child_exited");
+        assert_eq!(json["detail"], "The process of session build has already exited.");
+    }
+
+    #[tokio::test]
+    async fn template_not_found_is_not_found() {
+        let (status, json) = response_parts(ApiError::TemplateNotFound("build-env".into())).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(json["error"]["code"], "template_not_found");
+        assert_eq!(json["error"]["message"], "Template not found: build-env.");
+    }
+
+    #[tokio::test]
+    async fn clipboard_empty_is_not_found() {
+        let (status, json) = response_parts(ApiError::ClipboardEmpty("dev".into())).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(json["error"]["code"], "clipboard_empty");
+        assert_eq!(json["error"]["message"], "Nothing has been copied in session dev.");
+    }
+
+    #[tokio::test]
+    async fn image_not_found_is_not_found() {
+        let (status, json) = response_parts(ApiError::ImageNotFound("7".into())).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(json["error"]["code"], "image_not_found");
+        assert_eq!(json["error"]["message"], "Image not found: 7.");
+    }
+
+    #[tokio::test]
+    async fn pipe_not_found_is_not_found() {
+        let (status, json) = response_parts(ApiError::PipeNotFound("3".into())).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(json["error"]["code"], "pipe_not_found");
+        assert_eq!(json["error"]["message"], "Pipe not found: 3.");
+    }
+
+    #[tokio::test]
+    async fn approval_not_found_is_not_found() {
+        let (status, json) = response_parts(ApiError::ApprovalNotFound(4)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(json["error"]["code"], "approval_not_found");
+        assert_eq!(json["error"]["message"], "No input is waiting for approval with ID 4.");
+    }
+
+    #[tokio::test]
+    async fn ssh_connect_failed_is_bad_gateway() {
+        let (status, json) = response_parts(ApiError::SshConnectFailed(
+            "connect to host web-1 port 22: Connection refused".into(),
+        ))
+        .await;
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+        assert_eq!(json["error"]["code"], "ssh_connect_failed");
+        assert_eq!(
+            json["error"]["message"],
+            "SSH connection failed: connect to host web-1 port 22: Connection refused."
+        );
+    }
 }
@@ -1,7 +1,7 @@
 use axum::{
     extract::{
         ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade},
-        Path, Query as AxumQuery, State,
+        Extension, Path, Query as AxumQuery, State,
     },
     http::StatusCode,
     response::IntoResponse,
@@ -15,17 +15,24 @@ use futures::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
 use tokio::sync::broadcast;
 
-use crate::input::Mode;
+use crate::input::{ApprovalStatus, HandoffError, HandoffStatus, InputSource, Mode};
 use crate::overlay::{BackgroundStyle, Overlay, OverlaySpan, RegionWrite};
 use crate::panel::{self, Panel, Position};
 use crate::parser::{
+    encoding::Encoding,
     events::EventType,
-    state::{Format, Query},
+    state::{Format, Query, QueryPolicy},
 };
-use crate::pty::SpawnCommand;
-use crate::session::{RegistryError, Session};
+use crate::pty::{Priority, RunAsError, RunAs, SpawnCommand};
+use crate::safe_mode::SafeMode;
+use crate::sandbox::Sandbox;
+use crate::ssh::SshLaunch;
+use crate::session::{Delivery, InputError, RegistryError, RestartPolicy, RestartWhen, Session, SpawnOptions};
+use crate::terminal::ResizePolicy;
 
 use super::error::ApiError;
+use super::tokens::{TokenError, TokenGrant, TokenScope};
+use super::resume::Incoming;
 use super::{get_session, AppState};
 
 // ── Federation: ?server= query parameter support ──────────────────
@@ -60,6 +67,7 @@ pub(super) fn resolve_server_target(
     match server_param {
         None => Ok(SessionTarget::Local),
         Some(server) if server == state.hostname => Ok(SessionTarget::Local),
+        Some(_) if !state.backends.proxying() => Err(federation_disabled()),
         Some(server) => {
             let backend = state
                 .backends
@@ -73,11 +81,123 @@ pub(super) fn resolve_server_target(
     }
 }
 
+/// The error for a request to another server while federation proxying is
+/// switched off.
+pub(super) fn federation_disabled() -> ApiError {
+    ApiError::FeatureDisabled("Federation proxying".to_string())
+}
+
+/// The server named by a `server:session` path segment, recorded by
+/// [`split_qualified_name`] for [`proxy_qualified`].
+#[derive(Clone)]
+pub(super) struct QualifiedServer(String);
+
+/// The query string without its `server=` parameter.
+fn without_server_param(query: Option<&str>) -> Option<String> {
+    let rest: Vec<&str> = query?
+        .split('&')
+        .filter(|pair| !pair.is_empty() && !pair.starts_with("server="))
+        .collect();
+    (!rest.is_empty()).then(|| rest.join("&"))
+}
+
+/// Rewrite `/sessions/{server}:{session}/...` to `/sessions/{session}/...`
+/// before routing.
+///
+/// Session names can't contain `:`, so the qualifier is unambiguous. One
+/// naming this server is dropped. Any other becomes the request's
+/// `?server=`, so auth treats it like any other federated request, and is
+/// recorded for [`proxy_qualified`] to forward the request to that server.
+/// A qualifier that isn't a valid hostname is left alone, so it can't add
+/// parameters to the query string.
+pub(super) fn split_qualified_name(hostname: &str, mut req: axum::extract::Request) -> axum::extract::Request {
+    let uri = req.uri().clone();
+    let Some(rest) = uri.path().strip_prefix("/sessions/") else {
+        return req;
+    };
+    let (segment, tail) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let segment = segment.replace("%3A", ":").replace("%3a", ":");
+    let Some((server, name)) = segment.rsplit_once(':') else {
+        return req;
+    };
+    if name.is_empty() || crate::federation::registry::validate_hostname(server).is_err() {
+        return req;
+    }
+
+    let path = format!("/sessions/{}{}", name, tail);
+    let path_and_query = if server == hostname {
+        match uri.query() {
+            Some(query) => format!("{}?{}", path, query),
+            None => path,
+        }
+    } else {
+        match without_server_param(uri.query()) {
+            Some(query) => format!("{}?{}&server={}", path, query, server),
+            None => format!("{}?server={}", path, server),
+        }
+    };
+    let mut parts = uri.into_parts();
+    let Ok(path_and_query) = path_and_query.parse() else {
+        return req;
+    };
+    parts.path_and_query = Some(path_and_query);
+    let Ok(rewritten) = axum::http::Uri::from_parts(parts) else {
+        return req;
+    };
+    *req.uri_mut() = rewritten;
+    if server != hostname {
+        req.extensions_mut().insert(QualifiedServer(server.to_string()));
+    }
+    req
+}
+
+/// Forward a request for a `server:session` name to that server.
+///
+/// Every session endpoint is reachable this way, whether or not it takes
+/// `?server=` itself. WebSocket endpoints can't be proxied and are refused.
+pub(super) async fn proxy_qualified(
+    State(state): State<AppState>,
+    req: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Result<axum::response::Response, ApiError> {
+    let Some(QualifiedServer(server)) = req.extensions().get::<QualifiedServer>().cloned() else {
+        return Ok(next.run(req).await);
+    };
+    let backend = match resolve_server_target(&state, Some(&server))? {
+        SessionTarget::Local => return Ok(next.run(req).await),
+        SessionTarget::Remote(backend) => backend,
+    };
+    if req.headers().contains_key(axum::http::header::UPGRADE) {
+        return Err(ApiError::InvalidRequest(format!(
+            "WebSocket endpoints are not proxied; connect to {} directly",
+            server
+        )));
+    }
+
+    let path = match without_server_param(req.uri().query()) {
+        Some(query) => format!("{}?{}", req.uri().path(), query),
+        None => req.uri().path().to_string(),
+    };
+    let method = req.method().clone();
+    let content_type = req.headers().get(axum::http::header::CONTENT_TYPE).cloned();
+    let body = axum::body::to_bytes(req.into_body(), 1024 * 1024)
+        .await
+        .map_err(|e| ApiError::InvalidRequest(e.to_string()))?;
+
+    let (status, content_type, body) =
+        super::proxy::proxy_request(&backend, method, &path, content_type, body).await?;
+    let mut response = (status, body).into_response();
+    if let Some(content_type) = content_type {
+        response.headers_mut().insert(axum::http::header::CONTENT_TYPE, content_type);
+    }
+    Ok(response)
+}
+
 /// WebSocket send timeout. If a send takes longer than this, the client is
 /// considered dead and the connection is closed. Kept short (5s) to minimize
 /// the time a slow/stalled client can freeze the handler's select! loop
 /// (blocking ping/pong, idle detection, shutdown, and client messages).
-const WS_SEND_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+pub(super) const WS_SEND_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
 
 /// Timeout for parser query calls from HTTP handlers. Prevents a stalled
 /// parser from hanging an agent's HTTP request indefinitely.
@@ -118,26 +238,308 @@ pub(super) async fn health() -> Json<HealthResponse> {
 
 pub(super) async fn ws_ticket(
     State(state): State<AppState>,
+    grant: Option<Extension<TokenGrant>>,
+    connect_info: Option<Extension<axum::extract::ConnectInfo<std::net::SocketAddr>>>,
+    headers: axum::http::HeaderMap,
 ) -> Result<Json<serde_json::Value>, ApiError> {
+    let grant = grant.map_or_else(TokenGrant::root, |Extension(g)| g);
+    // Only the client that asked for the ticket can redeem it.
+    let binding = super::ticket::TicketBinding::new(
+        connect_info.map(|Extension(info)| info.0.ip()),
+        &headers,
+    );
     let ticket = state
         .ticket_store
-        .create()
+        .create(grant, binding)
         .map_err(|_| ApiError::ResourceLimitReached("too many pending tickets".into()))?;
     Ok(Json(serde_json::json!({ "ticket": ticket })))
 }
 
+#[derive(Deserialize)]
+pub(super) struct CreateTokenRequest {
+    name: String,
+    scope: TokenScope,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+fn token_error(e: TokenError) -> ApiError {
+    match e {
+        TokenError::NameExists(name) => ApiError::TokenNameConflict(name),
+        TokenError::NotFound(name) => ApiError::TokenNotFound(name),
+        TokenError::InvalidName(msg) => ApiError::InvalidRequest(format!("invalid token name: {}", msg)),
+        TokenError::Root => ApiError::InvalidRequest("the root token cannot be created or revoked".into()),
+        TokenError::LimitReached => ApiError::ResourceLimitReached("too many tokens".into()),
+    }
+}
+
+/// GET /auth/tokens -- list named tokens (never their secrets).
+pub(super) async fn token_list(State(state): State<AppState>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "tokens": state.tokens.list() }))
+}
+
+/// POST /auth/tokens -- create a named token. The secret is returned once.
+pub(super) async fn token_create(
+    State(state): State<AppState>,
+    Json(req): Json<CreateTokenRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if !state.tokens.has_root() {
+        return Err(ApiError::InvalidRequest(
+            "server is running without authentication, so scoped tokens would not be enforced".into(),
+        ));
+    }
+    for tag in &req.tags {
+        crate::session::validate_tag(tag).map_err(ApiError::InvalidTag)?;
+    }
+    let (info, secret) = state
+        .tokens
+        .create(req.name, req.scope, req.tags)
+        .map_err(token_error)?;
+    let mut body = serde_json::to_value(info).map_err(|e| ApiError::InternalError(e.to_string()))?;
+    body["token"] = serde_json::Value::String(secret);
+    Ok((StatusCode::CREATED, Json(body)))
+}
+
+/// DELETE /auth/tokens/{name} -- revoke a named token.
+pub(super) async fn token_revoke(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    state.tokens.revoke(&name).map_err(token_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn template_error(e: crate::templates::TemplateError) -> ApiError {
+    use crate::templates::TemplateError;
+    match e {
+        TemplateError::InvalidName(_) | TemplateError::Invalid(_) => ApiError::InvalidRequest(e.to_string()),
+        TemplateError::LimitReached => ApiError::ResourceLimitReached("too many templates".into()),
+        TemplateError::WriteFailed(_) => ApiError::InternalError(e.to_string()),
+    }
+}
+
+/// GET /templates -- list session templates.
+pub(super) async fn template_list(State(state): State<AppState>) -> Json<serde_json::Value> {
+    let templates: Vec<serde_json::Value> = state
+        .server_config
+        .templates()
+        .list()
+        .into_iter()
+        .map(|(name, template)| {
+            let mut entry = serde_json::to_value(template).unwrap_or_default();
+            entry["name"] = serde_json::Value::String(name);
+            entry
+        })
+        .collect();
+    Json(serde_json::json!({ "templates": templates }))
+}
+
+/// GET /templates/{name} -- fetch one template.
+pub(super) async fn template_get(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<crate::templates::SessionTemplate>, ApiError> {
+    state
+        .server_config
+        .templates()
+        .get(&name)
+        .map(Json)
+        .ok_or(ApiError::TemplateNotFound(name))
+}
+
+/// PUT /templates/{name} -- create or replace a template.
+pub(super) async fn template_put(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(template): Json<crate::templates::SessionTemplate>,
+) -> Result<impl IntoResponse, ApiError> {
+    let created = state
+        .server_config
+        .templates()
+        .put(&name, template.clone())
+        .map_err(template_error)?;
+    let status = if created { StatusCode::CREATED } else { StatusCode::OK };
+    Ok((status, Json(template)))
+}
+
+/// DELETE /templates/{name} -- delete a template.
+pub(super) async fn template_delete(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    if state.server_config.templates().remove(&name).map_err(template_error)? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::TemplateNotFound(name))
+    }
+}
+
 pub(super) async fn input(
     State(state): State<AppState>,
     Path(name): Path<String>,
     AxumQuery(query): AxumQuery<ServerQuery>,
     body: Bytes,
+) -> Result<axum::response::Response, ApiError> {
+    match resolve_server_target(&state, query.server.as_deref())? {
+        SessionTarget::Local => {
+            let session = get_session(&state.sessions, &name)?;
+            match session.send_input(InputSource::Http, body).await.map_err(input_error)? {
+                Delivery::Sent => Ok(StatusCode::NO_CONTENT.into_response()),
+                Delivery::Held(pending) => Ok((StatusCode::ACCEPTED, Json(pending)).into_response()),
+            }
+        }
+        SessionTarget::Remote(backend) => {
+            let status = super::proxy::proxy_post_bytes(
+                &backend,
+                &format!("/sessions/{}/input", name),
+                body,
+            )
+            .await?;
+            Ok(status.into_response())
+        }
+    }
+}
+
+fn input_error(e: InputError) -> ApiError {
+    match e {
+        InputError::Blocked(pattern) => ApiError::InputBlocked(pattern),
+        InputError::TooManyPending => ApiError::ResourceLimitReached(format!(
+            "at most {} inputs can wait for approval",
+            crate::input::approval::MAX_PENDING
+        )),
+        InputError::Timeout | InputError::Closed => {
+            tracing::error!("Failed to send input to PTY: {}", e);
+            ApiError::InputSendFailed
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(super) struct BroadcastInputQuery {
+    /// Comma-separated tags; sessions carrying any of them get the input.
+    tag: Option<String>,
+}
+
+/// The outcome of broadcast input for one session.
+#[derive(Serialize)]
+struct BroadcastResult {
+    session: String,
+    sent: bool,
+    /// ID of the held input, when the session holds it for approval.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    approval: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+/// Send the same input to every session carrying one of the tags.
+///
+/// Sessions get the input concurrently, each as from `POST
+/// /sessions/{name}/input`. A session that holds the input for approval
+/// gets an `approval` entry, and one that blocks it or doesn't take it in
+/// time an `error` entry, instead of failing the request.
+pub(super) async fn broadcast_input(
+    State(state): State<AppState>,
+    grant: Option<Extension<TokenGrant>>,
+    AxumQuery(query): AxumQuery<BroadcastInputQuery>,
+    body: Bytes,
+) -> Result<impl IntoResponse, ApiError> {
+    let tags: Vec<String> = query
+        .tag
+        .as_deref()
+        .map(|t| t.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default();
+    if tags.is_empty() {
+        return Err(ApiError::InvalidRequest("tag is required".to_string()));
+    }
+    let mut names = state.sessions.sessions_by_tags(&tags);
+    names.sort();
+
+    let futs = names.into_iter().filter_map(|name| {
+        let session = state.sessions.get(&name).filter(|session| match grant {
+            Some(Extension(ref grant)) => grant.allows_session(&session.tags.read()),
+            None => true,
+        })?;
+        let body = body.clone();
+        Some(async move {
+            let mut result = BroadcastResult { session: name, sent: false, approval: None, error: None, message: None };
+            match session.send_input(InputSource::Http, body).await {
+                Ok(Delivery::Sent) => result.sent = true,
+                Ok(Delivery::Held(pending)) => result.approval = Some(pending.id),
+                Err(e) => {
+                    let e = input_error(e);
+                    result.error = Some(e.code());
+                    result.message = Some(e.message());
+                }
+            }
+            result
+        })
+    });
+    let results = futures::future::join_all(futs).await;
+    let sent = results.iter().filter(|r| r.sent).count();
+    let held = results.iter().filter(|r| r.approval.is_some()).count();
+    Ok(Json(serde_json::json!({
+        "sent": sent,
+        "held": held,
+        "failed": results.len() - sent - held,
+        "results": results,
+    })))
+}
+
+/// `/sessions/input` is also the path of a session named `input`, so the
+/// route for broadcast input serves that session's other methods too.
+pub(super) async fn input_session_get(
+    state: State<AppState>,
+    query: AxumQuery<SessionGetQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    session_get(state, Path("input".to_string()), query).await
+}
+
+pub(super) async fn input_session_update(
+    state: State<AppState>,
+    query: AxumQuery<ServerQuery>,
+    req: Json<UpdateSessionRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    session_update(state, Path("input".to_string()), query, req).await
+}
+
+pub(super) async fn input_session_kill(
+    state: State<AppState>,
+    query: AxumQuery<KillQuery>,
+) -> Result<StatusCode, ApiError> {
+    session_kill(state, Path("input".to_string()), query).await
+}
+
+#[derive(Deserialize)]
+pub(super) struct SendKeysRequest {
+    keys: Vec<String>,
+}
+
+/// Send named keys (e.g. `"ArrowUp"`, `"ctrl+c"`), encoded for the
+/// keyboard modes the session's application has requested.
+pub(super) async fn input_keys(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    AxumQuery(query): AxumQuery<ServerQuery>,
+    body: Bytes,
 ) -> Result<StatusCode, ApiError> {
+    let req: SendKeysRequest =
+        serde_json::from_slice(&body).map_err(|e| ApiError::InvalidRequest(e.to_string()))?;
     match resolve_server_target(&state, query.server.as_deref())? {
         SessionTarget::Local => {
             let session = get_session(&state.sessions, &name)?;
+            let modes = tokio::time::timeout(PARSER_QUERY_TIMEOUT, session.parser.keyboard_modes())
+                .await
+                .map_err(|_| ApiError::ParserTimeout)?
+                .map_err(|_| ApiError::ParserUnavailable)?;
+            let data = crate::input::encode_keys(&req.keys, &modes)
+                .map_err(|e| ApiError::InvalidRequest(e.to_string()))?;
+            session.input_broadcaster.record_input(InputSource::Http, &data);
             tokio::time::timeout(
                 std::time::Duration::from_secs(5),
-                session.input_tx.send(body),
+                session.input_tx.send(Bytes::from(data)),
             )
             .await
             .map_err(|_| ApiError::InputSendFailed)?
@@ -151,7 +553,167 @@ pub(super) async fn input(
         SessionTarget::Remote(backend) => {
             let status = super::proxy::proxy_post_bytes(
                 &backend,
-                &format!("/sessions/{}/input", name),
+                &format!("/sessions/{}/input/keys", name),
+                body,
+            )
+            .await?;
+            Ok(status)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(super) struct SendSecretRequest {
+    data: String,
+    #[serde(default)]
+    force: bool,
+}
+
+/// Send sensitive input such as a password. The content is left out of the
+/// audit log, and the request is refused while the terminal echoes input
+/// unless `force` is set.
+pub(super) async fn input_secret(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    AxumQuery(query): AxumQuery<ServerQuery>,
+    body: Bytes,
+) -> Result<StatusCode, ApiError> {
+    let req: SendSecretRequest =
+        serde_json::from_slice(&body).map_err(|e| ApiError::InvalidRequest(e.to_string()))?;
+    match resolve_server_target(&state, query.server.as_deref())? {
+        SessionTarget::Local => {
+            let session = get_session(&state.sessions, &name)?;
+            if !req.force && session.echoes_input() {
+                return Err(ApiError::EchoEnabled);
+            }
+            let data = Bytes::from(req.data.into_bytes());
+            session
+                .input_broadcaster
+                .record_sensitive_input(InputSource::Http, data.len());
+            tokio::time::timeout(std::time::Duration::from_secs(5), session.input_tx.send(data))
+                .await
+                .map_err(|_| ApiError::InputSendFailed)?
+                .map_err(|_| {
+                    tracing::error!("Failed to send secret input to PTY");
+                    ApiError::InputSendFailed
+                })?;
+            session.activity.touch();
+            Ok(StatusCode::NO_CONTENT)
+        }
+        SessionTarget::Remote(backend) => {
+            let status = super::proxy::proxy_post_bytes(
+                &backend,
+                &format!("/sessions/{}/input/secret", name),
+                body,
+            )
+            .await?;
+            Ok(status)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(super) struct SetEnvRequest {
+    env: std::collections::BTreeMap<String, String>,
+    /// `posix` or `fish`, or a shell name such as `zsh`. Detected from the
+    /// session's command when omitted.
+    #[serde(default)]
+    shell: Option<String>,
+}
+
+/// How long to wait for the shell to start, or finish, a hidden read.
+const ENV_READ_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Poll the terminal until its echo state is `echoes`.
+async fn wait_for_echo(session: &Session, echoes: bool) -> bool {
+    let deadline = tokio::time::Instant::now() + ENV_READ_TIMEOUT;
+    while session.echoes_input() != echoes {
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    true
+}
+
+async fn send_input(session: &Session, data: Bytes) -> Result<(), ApiError> {
+    tokio::time::timeout(std::time::Duration::from_secs(5), session.input_tx.send(data))
+        .await
+        .map_err(|_| ApiError::InputSendFailed)?
+        .map_err(|_| {
+            tracing::error!("Failed to send input to PTY");
+            ApiError::InputSendFailed
+        })
+}
+
+/// Set environment variables in the session's shell without the values
+/// appearing on screen, in scrollback, or in the audit log. See
+/// [`crate::input::env`] for how.
+pub(super) async fn session_env(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    AxumQuery(query): AxumQuery<ServerQuery>,
+    body: Bytes,
+) -> Result<StatusCode, ApiError> {
+    use crate::input::env::{validate_name, validate_value, ShellKind};
+
+    let req: SetEnvRequest =
+        serde_json::from_slice(&body).map_err(|e| ApiError::InvalidRequest(e.to_string()))?;
+    if req.env.is_empty() {
+        return Err(ApiError::InvalidRequest("env must not be empty".to_string()));
+    }
+    for (key, value) in &req.env {
+        validate_name(key).map_err(ApiError::InvalidRequest)?;
+        validate_value(key, value).map_err(ApiError::InvalidRequest)?;
+    }
+    match resolve_server_target(&state, query.server.as_deref())? {
+        SessionTarget::Local => {
+            let session = get_session(&state.sessions, &name)?;
+            let kind = match req.shell.as_deref() {
+                Some(shell) => ShellKind::detect(shell)
+                    .or((shell == "posix").then_some(ShellKind::Posix))
+                    .ok_or_else(|| ApiError::InvalidRequest(format!("unsupported shell: {}", shell)))?,
+                None => ShellKind::detect(&session.command).ok_or_else(|| {
+                    ApiError::InvalidRequest(format!(
+                        "can't tell the shell from the session's command '{}'; pass shell",
+                        session.command
+                    ))
+                })?,
+            };
+            let commands: Vec<String> = req.env.keys().map(|key| kind.read_command(key)).collect();
+            for command in &commands {
+                session.policy.check_input(command.as_bytes()).map_err(ApiError::InputBlocked)?;
+            }
+            // Hidden input now means something other than a shell prompt,
+            // e.g. a password prompt, would read the command.
+            if !session.echoes_input() {
+                return Err(ApiError::ShellNotReady("the terminal is not echoing input".to_string()));
+            }
+            for (command, value) in commands.into_iter().zip(req.env.into_values()) {
+                session.input_broadcaster.record_input(InputSource::Http, command.as_bytes());
+                send_input(&session, Bytes::from(command)).await?;
+                if !wait_for_echo(&session, false).await {
+                    return Err(ApiError::ShellNotReady(
+                        "the shell did not start reading the value; is it at a prompt?".to_string(),
+                    ));
+                }
+                let mut line = value.into_bytes();
+                line.push(b'\n');
+                session.input_broadcaster.record_sensitive_input(InputSource::Http, line.len());
+                send_input(&session, Bytes::from(line)).await?;
+                // Echo comes back on once the value has been read, and only
+                // then is it safe to type the next command.
+                if !wait_for_echo(&session, true).await {
+                    return Err(ApiError::ShellNotReady("the shell did not finish reading the value".to_string()));
+                }
+            }
+            session.activity.touch();
+            Ok(StatusCode::NO_CONTENT)
+        }
+        SessionTarget::Remote(backend) => {
+            let status = super::proxy::proxy_post_bytes(
+                &backend,
+                &format!("/sessions/{}/env", name),
                 body,
             )
             .await?;
@@ -160,17 +722,135 @@ pub(super) async fn input(
     }
 }
 
+/// WebSocket subprotocol for `/ws/raw` clients that want rendered plain text
+/// instead of raw ANSI output.
+const WS_TEXT_SUBPROTOCOL: &str = "wsh-text";
+
+/// How long `wsh-text` connections wait for output to settle before
+/// re-rendering the screen. Coalesces bursts into a single frame.
+const TEXT_RENDER_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// Output encoding for a `/ws/raw` connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RawOutputMode {
+    /// Raw PTY bytes as Binary frames (default).
+    Ansi,
+    /// Parser-rendered plain-text screen as Text frames (`wsh-text`).
+    Text,
+    /// Output, size changes and the session's end as length-prefixed
+    /// frames (`wsh-framed`). See [`super::raw_frames`].
+    Framed,
+}
+
+#[derive(Deserialize)]
+pub(super) struct WsRawQuery {
+    /// Overrides the server's `[websocket] lag_policy` for this client.
+    lag: Option<super::backpressure::LagPolicy>,
+}
+
 pub(super) async fn ws_raw(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
     Path(name): Path<String>,
+    AxumQuery(query): AxumQuery<WsRawQuery>,
 ) -> Result<impl IntoResponse, ApiError> {
     let session = get_session(&state.sessions, &name)?;
+    crate::hooks::check_attach(&state.sessions, &session, "ws_raw", None)
+        .await
+        .map_err(|e| ApiError::HookRejected(e.to_string()))?;
     let client_guard = session.connect().ok_or_else(|| {
         ApiError::ResourceLimitReached("too many clients connected to session".into())
     })?;
-    Ok(ws.max_message_size(MAX_WS_MESSAGE_SIZE)
-        .on_upgrade(|socket| handle_ws_raw(socket, session, state.shutdown, client_guard)))
+    let ws = ws.protocols([WS_TEXT_SUBPROTOCOL, super::raw_frames::SUBPROTOCOL]);
+    let mode = match ws.selected_protocol().and_then(|p| p.to_str().ok()) {
+        Some(WS_TEXT_SUBPROTOCOL) => RawOutputMode::Text,
+        Some(super::raw_frames::SUBPROTOCOL) => RawOutputMode::Framed,
+        _ => RawOutputMode::Ansi,
+    };
+    let lag_policy = query.lag.unwrap_or_else(|| state.server_config.lag_policy());
+    Ok(ws.max_message_size(MAX_WS_MESSAGE_SIZE).on_upgrade(move |socket| {
+        handle_ws_raw(socket, session, state.shutdown, client_guard, mode, lag_policy)
+    }))
+}
+
+/// Render a plain-format screen as newline-separated text, trimming trailing
+/// whitespace on each line and trailing blank lines.
+fn render_plain_screen(screen: &crate::parser::state::ScreenResponse) -> String {
+    use crate::parser::state::FormattedLine;
+    let mut lines: Vec<String> = screen
+        .lines
+        .iter()
+        .map(|line| match line {
+            FormattedLine::Plain(text) => text.trim_end().to_string(),
+            FormattedLine::Styled(spans) => spans
+                .iter()
+                .map(|s| s.text.as_str())
+                .collect::<String>()
+                .trim_end()
+                .to_string(),
+        })
+        .collect();
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// Render the session's current screen as ANSI bytes that clear the
+/// terminal, redraw every line and inline image, and place the cursor. Sent to raw output
+/// clients when they fall behind, so they stay connected with a correct
+/// view instead of a gap in the stream. Also returns the offset in the
+/// session's output that the redraw is current to.
+async fn screen_redraw(session: &Session) -> Option<(Bytes, u64)> {
+    use crate::parser::ansi::line_to_ansi;
+    use crate::parser::state::{Format, Query, QueryResponse};
+    let Ok(Ok(QueryResponse::Screen(screen))) = tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        session.parser.query(Query::Screen { format: Format::Styled, since: None }),
+    )
+    .await
+    else {
+        return None;
+    };
+    let mut buf = String::new();
+    buf.push_str("\x1b[H\x1b[2J");
+    for (i, line) in screen.lines.iter().enumerate() {
+        buf.push_str(&line_to_ansi(line));
+        if i + 1 < screen.lines.len() {
+            buf.push_str("\r\n");
+        }
+    }
+    buf.push_str(&session.parser.redraw_images(&screen));
+    buf.push_str(&format!(
+        "\x1b[{};{}H",
+        screen.cursor.row + 1,
+        screen.cursor.col + 1,
+    ));
+    Some((Bytes::from(buf.into_bytes()), screen.output_offset))
+}
+
+/// Check raw WebSocket input against the session's input policy and write
+/// it to the PTY. Returns `false` if the connection should close.
+async fn forward_raw_input(
+    session: &Session,
+    input_tx: &tokio::sync::mpsc::Sender<Bytes>,
+    source: &InputSource,
+    data: Bytes,
+) -> bool {
+    // There is no reply channel here, so refused input is dropped.
+    if let Err(pattern) = session.policy.check_input(&data) {
+        tracing::warn!(session = %session.name, %pattern, "input blocked by policy");
+        return true;
+    }
+    session.input_broadcaster.record_input(source.clone(), &data);
+    match tokio::time::timeout(std::time::Duration::from_secs(5), input_tx.send(data)).await {
+        Ok(Ok(())) => true,
+        Ok(Err(_)) => false,
+        Err(_) => {
+            tracing::warn!("ws_raw input send timed out, closing");
+            false
+        }
+    }
 }
 
 async fn handle_ws_raw(
@@ -178,6 +858,8 @@ async fn handle_ws_raw(
     session: Session,
     shutdown: crate::shutdown::ShutdownCoordinator,
     _client_guard: crate::session::ClientGuard,
+    mode: RawOutputMode,
+    lag_policy: super::backpressure::LagPolicy,
 ) {
     // Register this connection for graceful shutdown tracking.
     // Check borrow immediately after register to handle the case where
@@ -190,6 +872,7 @@ async fn handle_ws_raw(
     }
 
     let (mut ws_tx, mut ws_rx) = socket.split();
+    let source = InputSource::new_websocket();
 
     let mut output_rx = session.output_rx.subscribe();
     let input_tx = session.input_tx.clone();
@@ -201,14 +884,49 @@ async fn handle_ws_raw(
     let mut ping_sent = false;
     const PONG_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
 
+    // wsh-text: when the next screen render is due, and what was last sent.
+    // Text clients get an initial snapshot immediately.
+    let mut render_at = match mode {
+        RawOutputMode::Text => Some(tokio::time::Instant::now()),
+        RawOutputMode::Ansi | RawOutputMode::Framed => None,
+    };
+    let mut last_text: Option<String> = None;
+
+    // wsh-framed: output goes out as Data frames, and the client hears
+    // about size changes, starting with the current size. Its own size
+    // is registered with the resize policy on its first Resize frame.
+    use super::raw_frames::Frame;
+    let framed = mode == RawOutputMode::Framed;
+    let output_message = |data: Bytes| {
+        Message::Binary(if framed { Frame::Data(data).encode() } else { data })
+    };
+    let mut size_rx = session.terminal_size.subscribe();
+    if framed {
+        let (rows, cols) = *size_rx.borrow_and_update();
+        if ws_tx.send(Message::Binary(Frame::Resize { rows, cols }.encode())).await.is_err() {
+            return;
+        }
+    }
+    let mut client_size: Option<crate::session::ClientSizeGuard> = None;
+
     // Main loop: handle PTY output, WebSocket input, and shutdown signal
     loop {
         tokio::select! {
             // PTY output -> WebSocket
-            result = output_rx.recv() => {
-                match result {
-                    Ok(data) => {
-                        match tokio::time::timeout(WS_SEND_TIMEOUT, ws_tx.send(Message::Binary(data))).await {
+            output = super::backpressure::recv_coalesced(&mut output_rx) => {
+                use super::backpressure::{LagPolicy, Output};
+                if mode == RawOutputMode::Text {
+                    // Output (or lag) only marks the screen dirty; the
+                    // render branch below sends the settled result.
+                    if output == Output::Closed {
+                        break;
+                    }
+                    render_at.get_or_insert_with(|| tokio::time::Instant::now() + TEXT_RENDER_DEBOUNCE);
+                    continue;
+                }
+                match output {
+                    Output::Data(data) => {
+                        match tokio::time::timeout(WS_SEND_TIMEOUT, ws_tx.send(output_message(data))).await {
                             Ok(Ok(())) => {}
                             Ok(Err(_)) => break,
                             Err(_) => {
@@ -217,9 +935,19 @@ async fn handle_ws_raw(
                             }
                         }
                     }
-                    Err(broadcast::error::RecvError::Closed) => break,
-                    Err(broadcast::error::RecvError::Lagged(n)) => {
-                        tracing::warn!(skipped = n, "ws_raw client lagged, sending screen sync");
+                    Output::Closed => break,
+                    Output::Behind if lag_policy == LagPolicy::Disconnect => {
+                        let close_frame = CloseFrame {
+                            code: axum::extract::ws::close_code::AGAIN,
+                            reason: "output lagged, reconnect to re-sync".into(),
+                        };
+                        let _ = tokio::time::timeout(
+                            std::time::Duration::from_secs(2),
+                            ws_tx.send(Message::Close(Some(close_frame))),
+                        ).await;
+                        return;
+                    }
+                    Output::Behind => {
                         // ── Lag recovery: full screen sync ───────────────────
                         //
                         // Matches the socket server and ws_json strategies
@@ -228,28 +956,10 @@ async fn handle_ws_raw(
                         // ANSI bytes, and send as a Binary frame. The client
                         // stays connected with a correct terminal view.
                         // ─────────────────────────────────────────────────────
-                        use crate::parser::ansi::line_to_ansi;
-                        use crate::parser::state::{Format, Query, QueryResponse};
-                        if let Ok(Ok(QueryResponse::Screen(screen))) = tokio::time::timeout(
-                            std::time::Duration::from_secs(5),
-                            session.parser.query(Query::Screen { format: Format::Styled }),
-                        ).await {
-                            let mut buf = String::new();
-                            buf.push_str("\x1b[H\x1b[2J");
-                            for (i, line) in screen.lines.iter().enumerate() {
-                                buf.push_str(&line_to_ansi(line));
-                                if i + 1 < screen.lines.len() {
-                                    buf.push_str("\r\n");
-                                }
-                            }
-                            buf.push_str(&format!(
-                                "\x1b[{};{}H",
-                                screen.cursor.row + 1,
-                                screen.cursor.col + 1,
-                            ));
+                        if let Some((redraw, _)) = screen_redraw(&session).await {
                             match tokio::time::timeout(
                                 WS_SEND_TIMEOUT,
-                                ws_tx.send(Message::Binary(Bytes::from(buf.into_bytes()))),
+                                ws_tx.send(output_message(redraw)),
                             ).await {
                                 Ok(Ok(())) => {}
                                 _ => break,
@@ -259,33 +969,71 @@ async fn handle_ws_raw(
                 }
             }
 
-            // WebSocket input -> PTY
-            msg = ws_rx.next() => {
-                match msg {
-                    Some(Ok(Message::Binary(data))) => {
+            // wsh-text: render the settled screen as plain text
+            _ = tokio::time::sleep_until(render_at.unwrap_or_else(tokio::time::Instant::now)), if render_at.is_some() => {
+                render_at = None;
+                use crate::parser::state::{Format, Query, QueryResponse};
+                if let Ok(Ok(QueryResponse::Screen(screen))) = tokio::time::timeout(
+                    std::time::Duration::from_secs(5),
+                    session.parser.query(Query::Screen { format: Format::Plain, since: None }),
+                ).await {
+                    let text = render_plain_screen(&screen);
+                    if last_text.as_deref() != Some(text.as_str()) {
                         match tokio::time::timeout(
-                            std::time::Duration::from_secs(5),
-                            input_tx.send(data),
+                            WS_SEND_TIMEOUT,
+                            ws_tx.send(Message::Text(text.clone().into())),
                         ).await {
                             Ok(Ok(())) => {}
-                            Ok(Err(_)) => break,
-                            Err(_) => {
-                                tracing::warn!("ws_raw input send timed out, closing");
+                            _ => break,
+                        }
+                        last_text = Some(text);
+                    }
+                }
+            }
+
+            // WebSocket input -> PTY
+            msg = ws_rx.next() => {
+                match msg {
+                    Some(Ok(Message::Binary(data))) if framed => {
+                        let frames = match Frame::decode_all(&data) {
+                            Ok(frames) => frames,
+                            Err(e) => {
+                                tracing::warn!(session = %session.name, error = %e, "malformed wsh-framed message");
+                                continue;
+                            }
+                        };
+                        let mut open = true;
+                        for frame in frames {
+                            match frame {
+                                Frame::Data(data) => {
+                                    open = forward_raw_input(&session, &input_tx, &source, data).await;
+                                }
+                                Frame::Resize { rows, cols } => {
+                                    let (rows, cols) = (rows.clamp(1, 1000), cols.clamp(1, 1000));
+                                    match &client_size {
+                                        Some(guard) => guard.update(rows, cols).await,
+                                        None => client_size = Some(session.track_client_size(rows, cols).await),
+                                    }
+                                }
+                                // Exit is server-to-client only.
+                                Frame::Exit(_) => {}
+                            }
+                            if !open {
                                 break;
                             }
                         }
+                        if !open {
+                            break;
+                        }
+                    }
+                    Some(Ok(Message::Binary(data))) => {
+                        if !forward_raw_input(&session, &input_tx, &source, data).await {
+                            break;
+                        }
                     }
                     Some(Ok(Message::Text(text))) => {
-                        match tokio::time::timeout(
-                            std::time::Duration::from_secs(5),
-                            input_tx.send(Bytes::from(text)),
-                        ).await {
-                            Ok(Ok(())) => {}
-                            Ok(Err(_)) => break,
-                            Err(_) => {
-                                tracing::warn!("ws_raw input send timed out, closing");
-                                break;
-                            }
+                        if !forward_raw_input(&session, &input_tx, &source, Bytes::from(text)).await {
+                            break;
                         }
                     }
                     Some(Ok(Message::Pong(_))) => {
@@ -311,9 +1059,37 @@ async fn handle_ws_raw(
                 ping_sent = true;
             }
 
+            // wsh-framed: tell the client the session's new size
+            Ok(()) = size_rx.changed(), if framed => {
+                let (rows, cols) = *size_rx.borrow_and_update();
+                match tokio::time::timeout(
+                    WS_SEND_TIMEOUT,
+                    ws_tx.send(Message::Binary(Frame::Resize { rows, cols }.encode())),
+                ).await {
+                    Ok(Ok(())) => {}
+                    _ => break,
+                }
+            }
+
             // Session was killed/removed
             _ = session.cancelled.cancelled() => {
                 tracing::debug!("session was killed, closing WebSocket");
+                if framed {
+                    // The server signals shutdown before draining sessions,
+                    // and the child monitor sets child_exited before
+                    // removing one, so both are settled by now.
+                    let reason = if *shutdown_rx.borrow() {
+                        "shutdown"
+                    } else if session.child_exited.load(std::sync::atomic::Ordering::Acquire) {
+                        "exited"
+                    } else {
+                        "killed"
+                    };
+                    let _ = tokio::time::timeout(
+                        WS_SEND_TIMEOUT,
+                        ws_tx.send(Message::Binary(Frame::Exit(reason.to_string()).encode())),
+                    ).await;
+                }
                 break;
             }
 
@@ -321,6 +1097,12 @@ async fn handle_ws_raw(
             _ = shutdown_rx.changed() => {
                 if *shutdown_rx.borrow() {
                     tracing::debug!("WebSocket received shutdown signal, closing");
+                    if framed {
+                        let _ = tokio::time::timeout(
+                            WS_SEND_TIMEOUT,
+                            ws_tx.send(Message::Binary(Frame::Exit("shutdown".to_string()).encode())),
+                        ).await;
+                    }
                     break;
                 }
             }
@@ -344,13 +1126,35 @@ pub(super) async fn ws_json(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<impl IntoResponse, ApiError> {
+    AxumQuery(query): AxumQuery<WsJsonQuery>,
+) -> Result<axum::response::Response, ApiError> {
     let session = get_session(&state.sessions, &name)?;
+    let ws = ws.max_message_size(MAX_WS_MESSAGE_SIZE);
+    // Hand a resuming client's socket to the connection it left; an unknown
+    // or expired token gets a fresh connection with `resumed: false`.
+    if let Some(resume) = query.resume.as_deref().and_then(|token| session.resume_tokens.resume(token)) {
+        return Ok(ws
+            .on_upgrade(move |socket| async move {
+                let _ = resume.send(socket).await;
+            })
+            .into_response());
+    }
+    crate::hooks::check_attach(&state.sessions, &session, "ws_json", None)
+        .await
+        .map_err(|e| ApiError::HookRejected(e.to_string()))?;
     let client_guard = session.connect().ok_or_else(|| {
         ApiError::ResourceLimitReached("too many clients connected to session".into())
     })?;
-    Ok(ws.max_message_size(MAX_WS_MESSAGE_SIZE)
-        .on_upgrade(|socket| handle_ws_json(socket, session, state.shutdown, client_guard)))
+    let resume_requested = query.resume.is_some();
+    Ok(ws
+        .on_upgrade(move |socket| handle_ws_json(socket, session, state.shutdown, client_guard, resume_requested))
+        .into_response())
+}
+
+#[derive(Deserialize)]
+pub(super) struct WsJsonQuery {
+    /// Resume token from an earlier connection's `connected` message.
+    resume: Option<String>,
 }
 
 async fn handle_ws_json(
@@ -358,20 +1162,22 @@ async fn handle_ws_json(
     session: Session,
     shutdown: crate::shutdown::ShutdownCoordinator,
     _client_guard: crate::session::ClientGuard,
+    resume_requested: bool,
 ) {
     let (_guard, mut shutdown_rx) = shutdown.register();
     if *shutdown_rx.borrow_and_update() {
         return;
     }
-    let (mut ws_tx, mut ws_rx) = socket.split();
+    let mut conn = super::resume::ResumableSocket::new(socket, &session.resume_tokens);
+    let source = InputSource::new_websocket();
 
     // Send connected message
-    let connected_msg = serde_json::json!({ "connected": true });
-    if ws_tx
-        .send(Message::Text(connected_msg.to_string().into()))
-        .await
-        .is_err()
-    {
+    let mut connected_msg = serde_json::json!({ "connected": true, "resume_token": conn.token() });
+    if resume_requested {
+        connected_msg["resumed"] = serde_json::Value::Bool(false);
+    }
+    conn.send(Message::Text(connected_msg.to_string().into())).await;
+    if conn.is_parked() {
         return;
     }
 
@@ -398,21 +1204,6 @@ async fn handle_ws_json(
     let mut ping_sent = false;
     const PONG_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
 
-    /// Send a WebSocket message with a timeout. Returns false if the send
-    /// failed or timed out (caller should break out of the loop).
-    macro_rules! ws_send {
-        ($tx:expr, $msg:expr) => {
-            match tokio::time::timeout(WS_SEND_TIMEOUT, $tx.send($msg)).await {
-                Ok(Ok(())) => {}
-                Ok(Err(_)) => break,
-                Err(_) => {
-                    tracing::debug!("ws_json send timed out, closing");
-                    break;
-                }
-            }
-        };
-    }
-
     // Main event loop
     loop {
         tokio::select! {
@@ -432,7 +1223,20 @@ async fn handle_ws_json(
                             crate::parser::events::Event::Diff { .. } => {
                                 subscribed_types.contains(&EventType::Diffs)
                             }
+                            crate::parser::events::Event::Clipboard { .. } => {
+                                subscribed_types.contains(&EventType::Clipboard)
+                            }
+                            crate::parser::events::Event::Title { .. } => {
+                                subscribed_types.contains(&EventType::Title)
+                            }
+                            crate::parser::events::Event::Cwd { .. } => {
+                                subscribed_types.contains(&EventType::Cwd)
+                            }
+                            crate::parser::events::Event::CommandDenied { .. } => {
+                                subscribed_types.contains(&EventType::CommandDenied)
+                            }
                             crate::parser::events::Event::Reset { .. }
+                            | crate::parser::events::Event::ParserRestarted { .. }
                             | crate::parser::events::Event::Sync { .. } => true,
                             crate::parser::events::Event::Idle { .. }
                             | crate::parser::events::Event::Running { .. } => {
@@ -442,7 +1246,7 @@ async fn handle_ws_json(
 
                         if should_send {
                             if let Ok(json) = serde_json::to_string(&event) {
-                                ws_send!(ws_tx, Message::Text(json.into()));
+                                conn.send(Message::Text(json.into())).await;
                             }
                         }
                     }
@@ -450,7 +1254,7 @@ async fn handle_ws_json(
                         tracing::warn!(skipped = n, "parser event subscriber lagged");
                         let lag_msg = serde_json::json!({"type": "lagged", "skipped": n});
                         if let Ok(json) = serde_json::to_string(&lag_msg) {
-                            ws_send!(ws_tx, Message::Text(json.into()));
+                            conn.send(Message::Text(json.into())).await;
                         }
                         // After lag, push a full sync so the client can recover.
                         // Without this, the client has an incomplete view of state.
@@ -458,6 +1262,7 @@ async fn handle_ws_json(
                             std::time::Duration::from_secs(10),
                             session.parser.query(crate::parser::state::Query::Screen {
                                 format: crate::parser::state::Format::default(),
+                                since: None,
                             }),
                         ).await {
                             let scrollback_lines = screen.total_lines;
@@ -467,7 +1272,7 @@ async fn handle_ws_json(
                                 scrollback_lines,
                             };
                             if let Ok(json) = serde_json::to_string(&sync_event) {
-                                ws_send!(ws_tx, Message::Text(json.into()));
+                                conn.send(Message::Text(json.into())).await;
                             }
                         }
                     }
@@ -485,7 +1290,7 @@ async fn handle_ws_json(
                 match input_event {
                     Ok(event) => {
                         if let Ok(json) = serde_json::to_string(&event) {
-                            ws_send!(ws_tx, Message::Text(json.into()));
+                            conn.send(Message::Text(json.into())).await;
                         }
                     }
                     Err(broadcast::error::RecvError::Closed) => {
@@ -495,7 +1300,7 @@ async fn handle_ws_json(
                         tracing::warn!(skipped = n, "input event subscriber lagged");
                         let lag_msg = serde_json::json!({"type": "input_lagged", "skipped": n});
                         if let Ok(json) = serde_json::to_string(&lag_msg) {
-                            ws_send!(ws_tx, Message::Text(json.into()));
+                            conn.send(Message::Text(json.into())).await;
                         }
                     }
                 }
@@ -513,7 +1318,7 @@ async fn handle_ws_json(
                     // Idle — query screen and return (with timeout to avoid blocking the loop)
                     match tokio::time::timeout(
                         std::time::Duration::from_secs(10),
-                        session.parser.query(crate::parser::state::Query::Screen { format }),
+                        session.parser.query(crate::parser::state::Query::Screen { format, since: None }),
                     ).await {
                         Ok(Ok(crate::parser::state::QueryResponse::Screen(screen))) => {
                             let scrollback_lines = screen.total_lines;
@@ -527,7 +1332,7 @@ async fn handle_ws_json(
                                 }),
                             );
                             if let Ok(json) = serde_json::to_string(&resp) {
-                                ws_send!(ws_tx, Message::Text(json.into()));
+                                conn.send(Message::Text(json.into())).await;
                             }
                         }
                         _ => {
@@ -538,7 +1343,7 @@ async fn handle_ws_json(
                                 "Terminal is idle but screen query failed.",
                             );
                             if let Ok(json) = serde_json::to_string(&resp) {
-                                ws_send!(ws_tx, Message::Text(json.into()));
+                                conn.send(Message::Text(json.into())).await;
                             }
                         }
                     }
@@ -551,7 +1356,7 @@ async fn handle_ws_json(
                         "Terminal did not become idle within the deadline.",
                     );
                     if let Ok(json) = serde_json::to_string(&resp) {
-                        ws_send!(ws_tx, Message::Text(json.into()));
+                        conn.send(Message::Text(json.into())).await;
                     }
                 }
             }
@@ -568,7 +1373,7 @@ async fn handle_ws_json(
                         // Emit Idle event with screen snapshot
                         if let Ok(Ok(crate::parser::state::QueryResponse::Screen(screen))) = tokio::time::timeout(
                             std::time::Duration::from_secs(10),
-                            session.parser.query(crate::parser::state::Query::Screen { format: activity_sub_format }),
+                            session.parser.query(crate::parser::state::Query::Screen { format: activity_sub_format, since: None }),
                         ).await {
                             let scrollback_lines = screen.total_lines;
                             let idle_event = crate::parser::events::Event::Idle {
@@ -578,7 +1383,7 @@ async fn handle_ws_json(
                                 scrollback_lines,
                             };
                             if let Ok(json) = serde_json::to_string(&idle_event) {
-                                ws_send!(ws_tx, Message::Text(json.into()));
+                                conn.send(Message::Text(json.into())).await;
                             }
                         }
                     }
@@ -588,7 +1393,7 @@ async fn handle_ws_json(
                             generation,
                         };
                         if let Ok(json) = serde_json::to_string(&running_event) {
-                            ws_send!(ws_tx, Message::Text(json.into()));
+                            conn.send(Message::Text(json.into())).await;
                         }
                     }
                     None => {
@@ -600,21 +1405,25 @@ async fn handle_ws_json(
 
             // Ping keepalive
             _ = ping_interval.tick() => {
+                if conn.is_parked() {
+                    continue;
+                }
                 if ping_sent && last_pong.elapsed() > PONG_TIMEOUT {
-                    tracing::debug!("ws_json client unresponsive (no pong), closing");
-                    break;
+                    tracing::debug!("ws_json client unresponsive (no pong)");
+                    conn.park();
+                    continue;
                 }
-                ws_send!(ws_tx, Message::Ping(Bytes::new()));
+                conn.send(Message::Ping(Bytes::new())).await;
                 ping_sent = true;
             }
 
-            msg = ws_rx.next() => {
-                match msg {
-                    Some(Ok(Message::Pong(_))) => {
+            incoming = conn.recv() => {
+                match incoming {
+                    Incoming::Message(Message::Pong(_)) => {
                         last_pong = tokio::time::Instant::now();
                         ping_sent = false;
                     }
-                    Some(Ok(Message::Text(text))) => {
+                    Incoming::Message(Message::Text(text)) => {
                         // Parse as WsRequest
                         let req = match serde_json::from_str::<super::ws_methods::WsRequest>(&text) {
                             Ok(req) => req,
@@ -624,7 +1433,7 @@ async fn handle_ws_json(
                                     "Invalid JSON or missing 'method' field.",
                                 );
                                 if let Ok(json) = serde_json::to_string(&err) {
-                                    ws_send!(ws_tx, Message::Text(json.into()));
+                                    conn.send(Message::Text(json.into())).await;
                                 }
                                 continue;
                             }
@@ -733,13 +1542,13 @@ async fn handle_ws_json(
                                         serde_json::json!({"events": event_names}),
                                     );
                                     if let Ok(json) = serde_json::to_string(&resp) {
-                                        ws_send!(ws_tx, Message::Text(json.into()));
+                                        conn.send(Message::Text(json.into())).await;
                                     }
 
                                     // Send sync event (with timeout to avoid blocking the loop)
                                     if let Ok(Ok(crate::parser::state::QueryResponse::Screen(screen))) = tokio::time::timeout(
                                         std::time::Duration::from_secs(10),
-                                        session.parser.query(crate::parser::state::Query::Screen { format: sub_format }),
+                                        session.parser.query(crate::parser::state::Query::Screen { format: sub_format, since: None }),
                                     ).await {
                                         let scrollback_lines = screen.total_lines;
                                         let sync_event = crate::parser::events::Event::Sync {
@@ -748,7 +1557,7 @@ async fn handle_ws_json(
                                             scrollback_lines,
                                         };
                                         if let Ok(json) = serde_json::to_string(&sync_event) {
-                                            ws_send!(ws_tx, Message::Text(json.into()));
+                                            conn.send(Message::Text(json.into())).await;
                                         }
                                     }
 
@@ -759,7 +1568,7 @@ async fn handle_ws_json(
                                         if is_idle {
                                             if let Ok(Ok(crate::parser::state::QueryResponse::Screen(screen))) = tokio::time::timeout(
                                                 std::time::Duration::from_secs(10),
-                                                session.parser.query(crate::parser::state::Query::Screen { format: sub_format }),
+                                                session.parser.query(crate::parser::state::Query::Screen { format: sub_format, since: None }),
                                             ).await {
                                                 let scrollback_lines = screen.total_lines;
                                                 let idle_event = crate::parser::events::Event::Idle {
@@ -769,7 +1578,7 @@ async fn handle_ws_json(
                                                     scrollback_lines,
                                                 };
                                                 if let Ok(json) = serde_json::to_string(&idle_event) {
-                                                    ws_send!(ws_tx, Message::Text(json.into()));
+                                                    conn.send(Message::Text(json.into())).await;
                                                 }
                                             }
                                         } else {
@@ -778,7 +1587,7 @@ async fn handle_ws_json(
                                                 generation,
                                             };
                                             if let Ok(json) = serde_json::to_string(&running_event) {
-                                                ws_send!(ws_tx, Message::Text(json.into()));
+                                                conn.send(Message::Text(json.into())).await;
                                             }
                                         }
                                     }
@@ -791,7 +1600,7 @@ async fn handle_ws_json(
                                         "Invalid parameters for this method.",
                                     );
                                     if let Ok(json) = serde_json::to_string(&resp) {
-                                        ws_send!(ws_tx, Message::Text(json.into()));
+                                        conn.send(Message::Text(json.into())).await;
                                     }
                                 }
                             }
@@ -829,7 +1638,7 @@ async fn handle_ws_json(
                                             "A new await_idle request superseded this one.",
                                         );
                                         if let Ok(json) = serde_json::to_string(&resp) {
-                                            ws_send!(ws_tx, Message::Text(json.into()));
+                                            conn.send(Message::Text(json.into())).await;
                                         }
                                     }
                                     pending_idle = Some((req.id.clone(), req.method.clone(), format, fut));
@@ -842,20 +1651,48 @@ async fn handle_ws_json(
                                         "Invalid parameters for this method.",
                                     );
                                     if let Ok(json) = serde_json::to_string(&resp) {
-                                        ws_send!(ws_tx, Message::Text(json.into()));
+                                        conn.send(Message::Text(json.into())).await;
                                     }
                                 }
                             }
                         } else {
                             // Dispatch all other methods
-                            let resp = super::ws_methods::dispatch(&req, &session).await;
+                            let resp = super::ws_methods::dispatch(&req, &session, &source).await;
 
                             if let Ok(json) = serde_json::to_string(&resp) {
-                                ws_send!(ws_tx, Message::Text(json.into()));
+                                conn.send(Message::Text(json.into())).await;
                             }
                         }
                     }
-                    Some(Ok(Message::Close(_))) | None => break,
+                    Incoming::Resume(socket) => {
+                        last_pong = tokio::time::Instant::now();
+                        ping_sent = false;
+                        let dropped = conn.attach(socket).await;
+                        if dropped > 0 {
+                            // Too much was missed to replay; resync the client
+                            // the same way as after lag.
+                            let lag_msg = serde_json::json!({"type": "lagged", "skipped": dropped});
+                            conn.send(Message::Text(lag_msg.to_string().into())).await;
+                            if let Ok(Ok(crate::parser::state::QueryResponse::Screen(screen))) = tokio::time::timeout(
+                                std::time::Duration::from_secs(10),
+                                session.parser.query(crate::parser::state::Query::Screen {
+                                    format: crate::parser::state::Format::default(),
+                                    since: None,
+                                }),
+                            ).await {
+                                let scrollback_lines = screen.total_lines;
+                                let sync_event = crate::parser::events::Event::Sync {
+                                    seq: 0,
+                                    screen,
+                                    scrollback_lines,
+                                };
+                                if let Ok(json) = serde_json::to_string(&sync_event) {
+                                    conn.send(Message::Text(json.into())).await;
+                                }
+                            }
+                        }
+                    }
+                    Incoming::Message(Message::Close(_)) | Incoming::Closed => break,
                     _ => continue,
                 }
             }
@@ -880,10 +1717,7 @@ async fn handle_ws_json(
         code: axum::extract::ws::close_code::NORMAL,
         reason: "session ended".into(),
     };
-    let _ = tokio::time::timeout(
-        std::time::Duration::from_secs(2),
-        ws_tx.send(Message::Close(Some(close_frame))),
-    ).await;
+    conn.close(close_frame).await;
 
     // Clean up activity subscription task
     if let Some(handle) = activity_sub_handle {
@@ -963,11 +1797,29 @@ struct SubHandle {
     idle_timeout_ms: u64,
 }
 
+/// A chunk of raw PTY output from an attached session, forwarded through
+/// the internal mpsc channel.
+struct TaggedOutput {
+    session: String,
+    data: Bytes,
+}
+
+/// Tracks an attached session's output forwarding task. Holds a client
+/// slot on the session for as long as the attachment lasts.
+struct AttachHandle {
+    task: tokio::task::JoinHandle<()>,
+    _client_guard: crate::session::ClientGuard,
+    /// Shared name that the forwarding task reads, updated on rename
+    /// (see `SubHandle::shared_name`).
+    shared_name: std::sync::Arc<parking_lot::Mutex<String>>,
+}
+
 /// Convert a registry-level SessionEvent to a JSON value for the WS protocol.
-/// Also handles cleanup of subscription handles on rename/destroy.
+/// Also handles cleanup of subscription and attach handles on rename/destroy.
 fn format_registry_event(
     event: &crate::session::SessionEvent,
     sub_handles: &mut std::collections::HashMap<String, SubHandle>,
+    attach_handles: &mut std::collections::HashMap<String, AttachHandle>,
 ) -> serde_json::Value {
     match event {
         crate::session::SessionEvent::Created { name } => {
@@ -983,29 +1835,70 @@ fn format_registry_event(
                 *handle.shared_name.lock() = new_name.clone();
                 sub_handles.insert(new_name.clone(), handle);
             }
+            if let Some(handle) = attach_handles.remove(old_name.as_str()) {
+                *handle.shared_name.lock() = new_name.clone();
+                attach_handles.insert(new_name.clone(), handle);
+            }
             serde_json::json!({
                 "event": "session_renamed",
                 "params": { "old_name": old_name, "new_name": new_name }
             })
         }
-        crate::session::SessionEvent::Destroyed { name } => {
+        crate::session::SessionEvent::Destroyed { name, reason, exit } => {
             if let Some(handle) = sub_handles.remove(name) {
                 handle.task.abort();
                 if let Some(at) = handle.activity_task {
                     at.abort();
                 }
             }
+            if let Some(handle) = attach_handles.remove(name) {
+                handle.task.abort();
+            }
+            let mut params = serde_json::json!({ "name": name, "reason": reason });
+            if let Some(exit) = exit {
+                params["exit_code"] = serde_json::json!(exit.exit_code);
+                params["signal"] = serde_json::json!(exit.signal);
+            }
             serde_json::json!({
                 "event": "session_destroyed",
+                "params": params
+            })
+        }
+        crate::session::SessionEvent::Suspended { name, suspended } => {
+            serde_json::json!({
+                "event": if *suspended { "session_suspended" } else { "session_resumed" },
                 "params": { "name": name }
             })
         }
+        crate::session::SessionEvent::Restarted { name, restarts, exit } => {
+            serde_json::json!({
+                "event": "session_restarted",
+                "params": {
+                    "name": name,
+                    "restarts": restarts,
+                    "exit_code": exit.as_ref().and_then(|e| e.exit_code),
+                    "signal": exit.as_ref().and_then(|e| e.signal.clone()),
+                }
+            })
+        }
         crate::session::SessionEvent::TagsChanged { name, added, removed } => {
             serde_json::json!({
                 "event": "session_tags_changed",
                 "params": { "name": name, "added": added, "removed": removed }
             })
         }
+        crate::session::SessionEvent::Maintenance { reason } => {
+            serde_json::json!({
+                "event": "server_maintenance",
+                "params": { "active": reason.is_some(), "reason": reason }
+            })
+        }
+        crate::session::SessionEvent::FeatureChanged { feature, enabled, by } => {
+            serde_json::json!({
+                "event": "server_feature_changed",
+                "params": { "feature": feature, "enabled": enabled, "by": by }
+            })
+        }
     }
 }
 
@@ -1027,7 +1920,20 @@ fn should_forward_session_event(
         crate::parser::events::Event::Diff { .. } => {
             handle.subscribed_types.contains(&EventType::Diffs)
         }
+        crate::parser::events::Event::Clipboard { .. } => {
+            handle.subscribed_types.contains(&EventType::Clipboard)
+        }
+        crate::parser::events::Event::Title { .. } => {
+            handle.subscribed_types.contains(&EventType::Title)
+        }
+        crate::parser::events::Event::Cwd { .. } => {
+            handle.subscribed_types.contains(&EventType::Cwd)
+        }
+        crate::parser::events::Event::CommandDenied { .. } => {
+            handle.subscribed_types.contains(&EventType::CommandDenied)
+        }
         crate::parser::events::Event::Reset { .. }
+        | crate::parser::events::Event::ParserRestarted { .. }
         | crate::parser::events::Event::Sync { .. } => true,
         crate::parser::events::Event::Idle { .. }
         | crate::parser::events::Event::Running { .. } => {
@@ -1042,6 +1948,7 @@ async fn handle_ws_json_server(socket: WebSocket, state: AppState) {
         return;
     }
     let (mut ws_tx, mut ws_rx) = socket.split();
+    let source = InputSource::new_websocket();
 
     // Send connected message
     let connected_msg = serde_json::json!({ "connected": true });
@@ -1064,6 +1971,11 @@ async fn handle_ws_json_server(socket: WebSocket, state: AppState) {
     let mut sub_handles: std::collections::HashMap<String, SubHandle> =
         std::collections::HashMap::new();
 
+    // Raw output from attached sessions funnels through a second channel
+    let (out_tx, mut out_rx) = tokio::sync::mpsc::channel::<TaggedOutput>(256);
+    let mut attach_handles: std::collections::HashMap<String, AttachHandle> =
+        std::collections::HashMap::new();
+
     // Ping/pong keepalive
     let mut ping_interval = tokio::time::interval(std::time::Duration::from_secs(30));
     ping_interval.reset();
@@ -1114,6 +2026,9 @@ async fn handle_ws_json_server(socket: WebSocket, state: AppState) {
                             &state,
                             &mut sub_handles,
                             &sub_tx,
+                            &mut attach_handles,
+                            &out_tx,
+                            &source,
                         )
                         .await;
 
@@ -1137,7 +2052,7 @@ async fn handle_ws_json_server(socket: WebSocket, state: AppState) {
                                         };
                                         if let Ok(Ok(crate::parser::state::QueryResponse::Screen(screen))) = tokio::time::timeout(
                                             std::time::Duration::from_secs(10),
-                                            session.parser.query(crate::parser::state::Query::Screen { format }),
+                                            session.parser.query(crate::parser::state::Query::Screen { format, since: None }),
                                         ).await {
                                             let scrollback_lines = screen.total_lines;
                                             let sync_event = serde_json::json!({
@@ -1169,7 +2084,7 @@ async fn handle_ws_json_server(socket: WebSocket, state: AppState) {
                                                     )) = tokio::time::timeout(
                                                         std::time::Duration::from_secs(10),
                                                         session.parser.query(
-                                                            crate::parser::state::Query::Screen { format },
+                                                            crate::parser::state::Query::Screen { format, since: None },
                                                         ),
                                                     )
                                                     .await
@@ -1233,7 +2148,7 @@ async fn handle_ws_json_server(socket: WebSocket, state: AppState) {
             result = registry_rx.recv() => {
                 match result {
                     Ok(event) => {
-                        let event_json = format_registry_event(&event, &mut sub_handles);
+                        let event_json = format_registry_event(&event, &mut sub_handles, &mut attach_handles);
                         if let Ok(json) = serde_json::to_string(&event_json) {
                             ws_send!(ws_tx, Message::Text(json.into()));
                         }
@@ -1283,6 +2198,7 @@ async fn handle_ws_json_server(socket: WebSocket, state: AppState) {
                                 std::time::Duration::from_secs(10),
                                 session.parser.query(crate::parser::state::Query::Screen {
                                     format: crate::parser::state::Format::default(),
+                                    since: None,
                                 }),
                             ).await {
                                 let scrollback_lines = screen.total_lines;
@@ -1308,6 +2224,19 @@ async fn handle_ws_json_server(socket: WebSocket, state: AppState) {
                 }
             }
 
+            // Raw PTY output forwarded from attach tasks
+            Some(output) = out_rx.recv() => {
+                use base64::Engine;
+                let output_event = serde_json::json!({
+                    "event": "output",
+                    "session": output.session,
+                    "data": base64::engine::general_purpose::STANDARD.encode(&output.data),
+                });
+                if let Ok(json) = serde_json::to_string(&output_event) {
+                    ws_send!(ws_tx, Message::Text(json.into()));
+                }
+            }
+
             // Shutdown signal
             _ = shutdown_rx.changed() => {
                 if *shutdown_rx.borrow() {
@@ -1328,13 +2257,16 @@ async fn handle_ws_json_server(socket: WebSocket, state: AppState) {
         ws_tx.send(Message::Close(Some(close_frame))),
     ).await;
 
-    // Clean up all subscription tasks
+    // Clean up all subscription and attach tasks
     for (_, handle) in sub_handles {
         handle.task.abort();
         if let Some(at) = handle.activity_task {
             at.abort();
         }
     }
+    for (_, handle) in attach_handles {
+        handle.task.abort();
+    }
 }
 
 /// Handle a single server-level WebSocket request.
@@ -1345,6 +2277,9 @@ async fn handle_server_ws_request(
     state: &AppState,
     sub_handles: &mut std::collections::HashMap<String, SubHandle>,
     sub_tx: &tokio::sync::mpsc::Sender<TaggedSessionEvent>,
+    attach_handles: &mut std::collections::HashMap<String, AttachHandle>,
+    out_tx: &tokio::sync::mpsc::Sender<TaggedOutput>,
+    source: &InputSource,
 ) -> Option<super::ws_methods::WsResponse> {
     let id = req.id.clone();
     let method = req.method.as_str();
@@ -1352,18 +2287,7 @@ async fn handle_server_ws_request(
     // Server-level session management methods (no session field required)
     match method {
         "create_session" => {
-            #[derive(Deserialize)]
-            struct CreateParams {
-                name: Option<String>,
-                command: Option<String>,
-                rows: Option<u16>,
-                cols: Option<u16>,
-                cwd: Option<String>,
-                env: Option<std::collections::HashMap<String, String>>,
-                #[serde(default)]
-                tags: Vec<String>,
-            }
-            let params: CreateParams = match &req.params {
+            let params: super::ws_methods::CreateSessionParams = match &req.params {
                 Some(v) => match serde_json::from_value(v.clone()) {
                     Ok(p) => p,
                     Err(_) => {
@@ -1375,23 +2299,60 @@ async fn handle_server_ws_request(
                         ));
                     }
                 },
-                None => CreateParams {
-                    name: None,
-                    command: None,
-                    rows: None,
-                    cols: None,
-                    cwd: None,
-                    env: None,
-                    tags: vec![],
-                },
+                None => super::ws_methods::CreateSessionParams::default(),
             };
 
-            let command = match params.command {
-                Some(cmd) => SpawnCommand::Command {
+            let run_as = match resolve_run_as(params.user.as_deref(), params.uid) {
+                Ok(run_as) => run_as,
+                Err(e) => {
+                    return Some(super::ws_methods::WsResponse::error(
+                        id,
+                        method,
+                        e.code(),
+                        &e.message(),
+                    ));
+                }
+            };
+            let (sandbox, ssh, idle_kill_after, priority, scrollback_lines, restart) = match state
+                .sessions
+                .resolve_sandbox(params.sandbox.as_deref())
+                .and_then(|sandbox| {
+                    let ssh = SshLaunch::from_request(params.command.as_deref(), params.remote.as_deref())?;
+                    let idle_kill_after = params
+                        .idle_kill_after
+                        .as_deref()
+                        .map(crate::config::parse_duration)
+                        .transpose()?;
+                    let priority = Priority::resolve(params.nice, params.ionice.as_deref())?;
+                    let scrollback_lines = crate::session::validate_scrollback_lines(
+                        params.scrollback_lines.unwrap_or_else(|| state.sessions.scrollback_lines()),
+                    )?;
+                    let restart = RestartPolicy::parse(
+                        params.restart,
+                        params.restart_max_retries,
+                        params.restart_backoff_ms,
+                    )?;
+                    Ok((sandbox, ssh, idle_kill_after, priority, scrollback_lines, restart))
+                }) {
+                Ok(resolved) => resolved,
+                Err(msg) => {
+                    let e = ApiError::InvalidRequest(msg);
+                    return Some(super::ws_methods::WsResponse::error(
+                        id,
+                        method,
+                        e.code(),
+                        &e.message(),
+                    ));
+                }
+            };
+
+            let command = match (&ssh, params.command) {
+                (Some(launch), _) => launch.spawn_command(),
+                (None, Some(cmd)) => SpawnCommand::Command {
                     command: cmd,
                     interactive: true,
                 },
-                None => SpawnCommand::Shell {
+                (None, None) => SpawnCommand::Shell {
                     interactive: true,
                     shell: None,
                 },
@@ -1434,6 +2395,12 @@ async fn handle_server_ws_request(
                         "invalid_session_name",
                         &format!("Invalid session name: {}.", msg),
                     ),
+                    RegistryError::Maintenance(reason) => super::ws_methods::WsResponse::error(
+                        id,
+                        method,
+                        "server_maintenance",
+                        &format!("Server is in maintenance, not accepting new sessions: {}.", reason),
+                    ),
                 });
             }
 
@@ -1454,13 +2421,33 @@ async fn handle_server_ws_request(
                 }
             }
 
+            if let Some(ref launch) = ssh {
+                if let Err(e) = launch.create_log(run_as.as_ref()) {
+                    return Some(super::ws_methods::WsResponse::error(
+                        id,
+                        method,
+                        "session_create_failed",
+                        &format!("Failed to create session: ssh log: {}.", e),
+                    ));
+                }
+            }
+
             let param_name = params.name;
-            let cwd = params.cwd;
-            let env = params.env;
+            let options = SpawnOptions {
+                cwd: params.cwd,
+                env: params.env,
+                motd: state.sessions.motd(),
+                run_as,
+                sandbox,
+                safe_mode: None,
+                priority,
+                scrollback_lines,
+                restart,
+            };
             let spawn_result = tokio::task::spawn_blocking(move || {
-                Session::spawn_with_options("".to_string(), command, rows, cols, cwd, env)
+                Session::spawn_with_options("".to_string(), command, rows, cols, options)
             }).await;
-            let (session, child_exit_rx) = match spawn_result {
+            let (session, mut child_exit_rx) = match spawn_result {
                 Ok(Ok(result)) => result,
                 Ok(Err(e)) => {
                     return Some(super::ws_methods::WsResponse::error(
@@ -1480,10 +2467,26 @@ async fn handle_server_ws_request(
                 }
             };
 
+            if let Some(ref launch) = ssh {
+                if let Err(msg) = launch.wait_connected(&session.usage, &mut child_exit_rx).await {
+                    session.shutdown();
+                    let e = ApiError::SshConnectFailed(msg);
+                    return Some(super::ws_methods::WsResponse::error(
+                        id,
+                        method,
+                        e.code(),
+                        &e.message(),
+                    ));
+                }
+            }
+
             // Set initial tags before registry insertion
             if !initial_tags.is_empty() {
                 *session.tags.write() = initial_tags.into_iter().collect();
             }
+            *session.idle_kill_after.lock() = idle_kill_after;
+            *session.query_policy.write() = params.query_policy.unwrap_or_default();
+            session.parser.set_encoding(params.encoding.unwrap_or_default());
 
             match state.sessions.insert_and_get(param_name, session.clone()) {
                 Ok((assigned_name, _session)) => {
@@ -1530,20 +2533,21 @@ async fn handle_server_ws_request(
                             "invalid_session_name",
                             &format!("Invalid session name: {}.", msg),
                         ),
+                        RegistryError::Maintenance(reason) => super::ws_methods::WsResponse::error(
+                            id,
+                            method,
+                            "server_maintenance",
+                            &format!("Server is in maintenance, not accepting new sessions: {}.", reason),
+                        ),
                     });
                 }
             }
         }
 
         "list_sessions" => {
-            #[derive(Deserialize)]
-            struct ListParams {
-                #[serde(default)]
-                tag: Vec<String>,
-            }
-            let params: ListParams = match &req.params {
-                Some(v) => serde_json::from_value(v.clone()).unwrap_or(ListParams { tag: vec![] }),
-                None => ListParams { tag: vec![] },
+            let params: super::ws_methods::ListSessionsParams = match &req.params {
+                Some(v) => serde_json::from_value(v.clone()).unwrap_or_default(),
+                None => Default::default(),
             };
 
             let names = if params.tag.is_empty() {
@@ -1560,7 +2564,7 @@ async fn handle_server_ws_request(
                     tags.sort();
                     Some(serde_json::json!({
                         "name": name,
-                        "pid": session.pid,
+                        "pid": session.pid(),
                         "command": session.command,
                         "rows": rows,
                         "cols": cols,
@@ -1577,11 +2581,7 @@ async fn handle_server_ws_request(
         }
 
         "kill_session" => {
-            #[derive(Deserialize)]
-            struct KillParams {
-                name: String,
-            }
-            let params: KillParams = match &req.params {
+            let params: super::ws_methods::SessionNameParams = match &req.params {
                 Some(v) => match serde_json::from_value(v.clone()) {
                     Ok(p) => p,
                     Err(_) => {
@@ -1628,11 +2628,7 @@ async fn handle_server_ws_request(
         }
 
         "detach_session" => {
-            #[derive(Deserialize)]
-            struct DetachParams {
-                name: String,
-            }
-            let params: DetachParams = match &req.params {
+            let params: super::ws_methods::SessionNameParams = match &req.params {
                 Some(v) => match serde_json::from_value(v.clone()) {
                     Ok(p) => p,
                     Err(_) => {
@@ -1674,13 +2670,8 @@ async fn handle_server_ws_request(
             }
         }
 
-        "rename_session" => {
-            #[derive(Deserialize)]
-            struct RenameParams {
-                name: String,
-                new_name: String,
-            }
-            let params: RenameParams = match &req.params {
+        "resume_session" => {
+            let params: super::ws_methods::SessionNameParams = match &req.params {
                 Some(v) => match serde_json::from_value(v.clone()) {
                     Ok(p) => p,
                     Err(_) => {
@@ -1697,79 +2688,33 @@ async fn handle_server_ws_request(
                         id,
                         method,
                         "invalid_request",
-                        "Missing 'params' with 'name' and 'new_name' fields.",
+                        "Missing 'params' with 'name' field.",
                     ));
                 }
             };
 
-            match state.sessions.rename(&params.name, &params.new_name) {
-                Ok(session) => {
-                    // Update subscription key and shared name if it exists
-                    if let Some(handle) = sub_handles.remove(&params.name) {
-                        *handle.shared_name.lock() = params.new_name.clone();
-                        sub_handles.insert(params.new_name.clone(), handle);
-                    }
-                    let mut tags: Vec<String> = session.tags.read().iter().cloned().collect();
-                    tags.sort();
+            match state.sessions.get(&params.name) {
+                Some(session) => {
+                    session.resume();
                     return Some(super::ws_methods::WsResponse::success(
                         id,
                         method,
-                        serde_json::json!({ "name": params.new_name, "tags": tags }),
-                    ));
-                }
-                Err(RegistryError::NameExists(n)) => {
-                    return Some(super::ws_methods::WsResponse::error(
-                        id,
-                        method,
-                        "session_name_conflict",
-                        &format!("Session name already exists: {}.", n),
+                        serde_json::json!({}),
                     ));
                 }
-                Err(RegistryError::NotFound(n)) => {
+                None => {
                     return Some(super::ws_methods::WsResponse::error(
                         id,
                         method,
                         "session_not_found",
-                        &format!("Session not found: {}.", n),
-                    ));
-                }
-                Err(RegistryError::MaxSessionsReached) => {
-                    return Some(super::ws_methods::WsResponse::error(
-                        id,
-                        method,
-                        "max_sessions_reached",
-                        "Maximum number of sessions reached.",
-                    ));
-                }
-                Err(RegistryError::InvalidTag(msg)) => {
-                    return Some(super::ws_methods::WsResponse::error(
-                        id,
-                        method,
-                        "invalid_tag",
-                        &format!("Invalid tag: {}.", msg),
-                    ));
-                }
-                Err(RegistryError::InvalidName(msg)) => {
-                    return Some(super::ws_methods::WsResponse::error(
-                        id,
-                        method,
-                        "invalid_session_name",
-                        &format!("Invalid session name: {}.", msg),
+                        &format!("Session not found: {}.", params.name),
                     ));
                 }
             }
         }
 
-        "update_tags" => {
-            #[derive(Deserialize)]
-            struct UpdateTagsParams {
-                session: String,
-                #[serde(default)]
-                add: Vec<String>,
-                #[serde(default)]
-                remove: Vec<String>,
-            }
-            let params: UpdateTagsParams = match &req.params {
+        "hibernate_session" => {
+            let params: super::ws_methods::SessionNameParams = match &req.params {
                 Some(v) => match serde_json::from_value(v.clone()) {
                     Ok(p) => p,
                     Err(_) => {
@@ -1786,12 +2731,151 @@ async fn handle_server_ws_request(
                         id,
                         method,
                         "invalid_request",
-                        "Missing params.",
+                        "Missing 'params' with 'name' field.",
                     ));
                 }
             };
 
-            if !params.add.is_empty() {
+            match state.sessions.get(&params.name) {
+                Some(session) => {
+                    if let Err(e) = session.hibernate().await {
+                        return Some(super::ws_methods::WsResponse::error(
+                            id,
+                            method,
+                            "internal_error",
+                            &format!("Hibernation failed: {}.", e),
+                        ));
+                    }
+                    return Some(super::ws_methods::WsResponse::success(
+                        id,
+                        method,
+                        serde_json::json!({}),
+                    ));
+                }
+                None => {
+                    return Some(super::ws_methods::WsResponse::error(
+                        id,
+                        method,
+                        "session_not_found",
+                        &format!("Session not found: {}.", params.name),
+                    ));
+                }
+            }
+        }
+
+        "rename_session" => {
+            let params: super::ws_methods::RenameSessionParams = match &req.params {
+                Some(v) => match serde_json::from_value(v.clone()) {
+                    Ok(p) => p,
+                    Err(_) => {
+                        return Some(super::ws_methods::WsResponse::error(
+                            id,
+                            method,
+                            "invalid_request",
+                            "Invalid parameters for this method.",
+                        ));
+                    }
+                },
+                None => {
+                    return Some(super::ws_methods::WsResponse::error(
+                        id,
+                        method,
+                        "invalid_request",
+                        "Missing 'params' with 'name' and 'new_name' fields.",
+                    ));
+                }
+            };
+
+            match state.sessions.rename(&params.name, &params.new_name) {
+                Ok(session) => {
+                    // Update subscription key and shared name if it exists
+                    if let Some(handle) = sub_handles.remove(&params.name) {
+                        *handle.shared_name.lock() = params.new_name.clone();
+                        sub_handles.insert(params.new_name.clone(), handle);
+                    }
+                    let mut tags: Vec<String> = session.tags.read().iter().cloned().collect();
+                    tags.sort();
+                    return Some(super::ws_methods::WsResponse::success(
+                        id,
+                        method,
+                        serde_json::json!({ "name": params.new_name, "tags": tags }),
+                    ));
+                }
+                Err(RegistryError::NameExists(n)) => {
+                    return Some(super::ws_methods::WsResponse::error(
+                        id,
+                        method,
+                        "session_name_conflict",
+                        &format!("Session name already exists: {}.", n),
+                    ));
+                }
+                Err(RegistryError::NotFound(n)) => {
+                    return Some(super::ws_methods::WsResponse::error(
+                        id,
+                        method,
+                        "session_not_found",
+                        &format!("Session not found: {}.", n),
+                    ));
+                }
+                Err(RegistryError::MaxSessionsReached) => {
+                    return Some(super::ws_methods::WsResponse::error(
+                        id,
+                        method,
+                        "max_sessions_reached",
+                        "Maximum number of sessions reached.",
+                    ));
+                }
+                Err(RegistryError::InvalidTag(msg)) => {
+                    return Some(super::ws_methods::WsResponse::error(
+                        id,
+                        method,
+                        "invalid_tag",
+                        &format!("Invalid tag: {}.", msg),
+                    ));
+                }
+                Err(RegistryError::InvalidName(msg)) => {
+                    return Some(super::ws_methods::WsResponse::error(
+                        id,
+                        method,
+                        "invalid_session_name",
+                        &format!("Invalid session name: {}.", msg),
+                    ));
+                }
+                Err(RegistryError::Maintenance(reason)) => {
+                    return Some(super::ws_methods::WsResponse::error(
+                        id,
+                        method,
+                        "server_maintenance",
+                        &format!("Server is in maintenance, not accepting new sessions: {}.", reason),
+                    ));
+                }
+            }
+        }
+
+        "update_tags" => {
+            let params: super::ws_methods::UpdateTagsParams = match &req.params {
+                Some(v) => match serde_json::from_value(v.clone()) {
+                    Ok(p) => p,
+                    Err(_) => {
+                        return Some(super::ws_methods::WsResponse::error(
+                            id,
+                            method,
+                            "invalid_request",
+                            "Invalid parameters for this method.",
+                        ));
+                    }
+                },
+                None => {
+                    return Some(super::ws_methods::WsResponse::error(
+                        id,
+                        method,
+                        "invalid_request",
+                        "Missing params.",
+                    ));
+                }
+            };
+
+            if !params.add.is_empty() {
                 if let Err(e) = state.sessions.add_tags(&params.session, &params.add) {
                     return Some(super::ws_methods::WsResponse::error(
                         id,
@@ -1832,7 +2916,7 @@ async fn handle_server_ws_request(
                 method,
                 serde_json::json!({
                     "name": session.name,
-                    "pid": session.pid,
+                    "pid": session.pid(),
                     "command": session.command,
                     "rows": rows,
                     "cols": cols,
@@ -1843,10 +2927,12 @@ async fn handle_server_ws_request(
         }
 
         "set_server_mode" => {
-            if let Some(params) = &req.params {
-                if let Some(persistent) = params.get("persistent").and_then(|v| v.as_bool()) {
-                    state.server_config.set_persistent(persistent);
-                }
+            let params = req
+                .params
+                .clone()
+                .and_then(|v| serde_json::from_value::<super::ws_methods::SetServerModeParams>(v).ok());
+            if let Some(persistent) = params.and_then(|p| p.persistent) {
+                state.server_config.set_persistent(persistent);
             }
             return Some(super::ws_methods::WsResponse::success(
                 id,
@@ -1873,6 +2959,43 @@ async fn handle_server_ws_request(
         }
     };
 
+    // Stop forwarding a session's events. Doesn't need the session to
+    // still exist, and is a no-op if it wasn't subscribed.
+    if method == "unsubscribe" {
+        let unsubscribed = match sub_handles.remove(&session_name) {
+            Some(handle) => {
+                handle.task.abort();
+                if let Some(at) = handle.activity_task {
+                    at.abort();
+                }
+                true
+            }
+            None => false,
+        };
+        return Some(super::ws_methods::WsResponse::success(
+            id,
+            method,
+            serde_json::json!({"unsubscribed": unsubscribed}),
+        ));
+    }
+
+    // Stop streaming a session's output and give up its client slot.
+    // Like unsubscribe, a no-op if the session wasn't attached.
+    if method == "detach" {
+        let detached = match attach_handles.remove(&session_name) {
+            Some(handle) => {
+                handle.task.abort();
+                true
+            }
+            None => false,
+        };
+        return Some(super::ws_methods::WsResponse::success(
+            id,
+            method,
+            serde_json::json!({"detached": detached}),
+        ));
+    }
+
     let session = match state.sessions.get(&session_name) {
         Some(s) => s,
         None => {
@@ -1885,6 +3008,79 @@ async fn handle_server_ws_request(
         }
     };
 
+    // Stream the session's raw PTY output as tagged `output` events,
+    // starting with a redraw of the current screen. Counts as a client of
+    // the session, like a raw WebSocket.
+    if method == "attach" {
+        if let Some(old) = attach_handles.remove(&session_name) {
+            old.task.abort();
+        }
+        if let Err(e) = crate::hooks::check_attach(&state.sessions, &session, "ws", None).await {
+            let e = ApiError::HookRejected(e.to_string());
+            return Some(super::ws_methods::WsResponse::error(id, method, e.code(), &e.message()));
+        }
+        let Some(client_guard) = session.connect() else {
+            let e = ApiError::ResourceLimitReached("too many clients connected to session".into());
+            return Some(super::ws_methods::WsResponse::error(id, method, e.code(), &e.message()));
+        };
+
+        let tx = out_tx.clone();
+        let shared_name = std::sync::Arc::new(parking_lot::Mutex::new(session_name.clone()));
+        let task_name = shared_name.clone();
+        let task_session = session.clone();
+        let task = tokio::spawn(async move {
+            // Stream from the first byte the redraw doesn't show, so output
+            // racing the snapshot is neither drawn twice nor missed.
+            let mut output_rx = match screen_redraw(&task_session).await {
+                Some((redraw, offset)) => {
+                    let current_name = task_name.lock().clone();
+                    if tx.send(TaggedOutput { session: current_name, data: redraw }).await.is_err() {
+                        return;
+                    }
+                    task_session
+                        .output_rx
+                        .subscribe_from(offset)
+                        .unwrap_or_else(|| task_session.output_rx.subscribe())
+                }
+                None => task_session.output_rx.subscribe(),
+            };
+            loop {
+                let data = tokio::select! {
+                    output = super::backpressure::recv_coalesced(&mut output_rx) => match output {
+                        super::backpressure::Output::Data(data) => data,
+                        super::backpressure::Output::Closed => break,
+                        // Always resync: the connection is shared with
+                        // other sessions, so it isn't closed for one.
+                        super::backpressure::Output::Behind => match screen_redraw(&task_session).await {
+                            Some((redraw, _)) => redraw,
+                            None => continue,
+                        },
+                    },
+                    _ = task_session.cancelled.cancelled() => break,
+                };
+                let current_name = task_name.lock().clone();
+                if tx.send(TaggedOutput { session: current_name, data }).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let (rows, cols) = session.terminal_size.get();
+        attach_handles.insert(
+            session_name.clone(),
+            AttachHandle {
+                task,
+                _client_guard: client_guard,
+                shared_name,
+            },
+        );
+        return Some(super::ws_methods::WsResponse::success(
+            id,
+            method,
+            serde_json::json!({ "rows": rows, "cols": cols }),
+        ));
+    }
+
     // Handle subscribe specially (needs to set up forwarding task)
     if method == "subscribe" {
         let params_value = req
@@ -1991,7 +3187,7 @@ async fn handle_server_ws_request(
                             )) = tokio::time::timeout(
                                 std::time::Duration::from_secs(10),
                                 activity_parser.query(
-                                    crate::parser::state::Query::Screen { format: activity_format },
+                                    crate::parser::state::Query::Screen { format: activity_format, since: None },
                                 ),
                             )
                             .await
@@ -2051,7 +3247,7 @@ async fn handle_server_ws_request(
                             )) = tokio::time::timeout(
                                 std::time::Duration::from_secs(10),
                                 activity_parser.query(
-                                    crate::parser::state::Query::Screen { format: activity_format },
+                                    crate::parser::state::Query::Screen { format: activity_format, since: None },
                                 ),
                             )
                             .await
@@ -2125,7 +3321,7 @@ async fn handle_server_ws_request(
         params: req.params.clone(),
     };
 
-    Some(super::ws_methods::dispatch(&ws_req, &session).await)
+    Some(super::ws_methods::dispatch(&ws_req, &session, source).await)
 }
 
 // Idle query parameters
@@ -2174,7 +3370,7 @@ pub(super) async fn idle(
             // Idle — query screen state
             let response = tokio::time::timeout(
                 PARSER_QUERY_TIMEOUT,
-                session.parser.query(Query::Screen { format: params.format }),
+                session.parser.query(Query::Screen { format: params.format, since: None }),
             )
             .await
             .map_err(|_| ApiError::ParserTimeout)?
@@ -2222,9 +3418,21 @@ pub(super) struct IdleAnyQuery {
     #[serde(default)]
     fresh: bool,
     /// Comma-separated list of tags (e.g. `?tag=build,test`).
-    /// When provided, only sessions matching all tags are considered.
+    /// When provided, only sessions carrying any of the tags are considered.
     #[serde(default)]
     tag: Option<String>,
+    /// Whether to resolve on the first idle session or only once every
+    /// matching session is idle.
+    #[serde(default)]
+    mode: IdleMode,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum IdleMode {
+    #[default]
+    Any,
+    All,
 }
 
 pub(super) async fn idle_any(
@@ -2236,7 +3444,7 @@ pub(super) async fn idle_any(
         .as_deref()
         .map(|t| t.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
         .unwrap_or_default();
-    let names = if tags.is_empty() {
+    let mut names = if tags.is_empty() {
         state.sessions.list()
     } else {
         state.sessions.sessions_by_tags(&tags)
@@ -2248,6 +3456,29 @@ pub(super) async fn idle_any(
     let timeout = std::time::Duration::from_millis(params.timeout_ms.min(MAX_WAIT_CEILING_MS));
     let deadline = std::time::Duration::from_millis(params.max_wait_ms.min(MAX_WAIT_CEILING_MS));
 
+    if params.mode == IdleMode::All {
+        names.sort();
+        let (names, trackers): (Vec<String>, Vec<_>) = names
+            .into_iter()
+            .filter_map(|name| {
+                let activity = state.sessions.get(&name)?.activity.clone();
+                Some((name, activity))
+            })
+            .unzip();
+        let generations = tokio::time::timeout(
+            deadline,
+            crate::activity::wait_for_all_idle(&trackers, timeout, params.fresh),
+        )
+        .await
+        .map_err(|_| ApiError::IdleTimeout)?;
+        let sessions: Vec<_> = names
+            .into_iter()
+            .zip(generations)
+            .map(|(session, generation)| serde_json::json!({"session": session, "generation": generation}))
+            .collect();
+        return Ok(Json(serde_json::json!({ "sessions": sessions })));
+    }
+
     // Build an idle future for each session, racing them all.
     let mut futs = Vec::with_capacity(names.len());
     for name in &names {
@@ -2288,7 +3519,7 @@ pub(super) async fn idle_any(
             let session = get_session(&state.sessions, &session_name)?;
             let response = tokio::time::timeout(
                 PARSER_QUERY_TIMEOUT,
-                session.parser.query(Query::Screen { format: params.format }),
+                session.parser.query(Query::Screen { format: params.format, since: None }),
             )
             .await
             .map_err(|_| ApiError::ParserTimeout)?
@@ -2304,107 +3535,1217 @@ pub(super) async fn idle_any(
                         "generation": generation,
                     })))
                 }
-                _ => Err(ApiError::ParserUnavailable),
+                _ => Err(ApiError::ParserUnavailable),
+            }
+        }
+        Err(_) => Err(ApiError::IdleTimeout),
+    }
+}
+
+#[derive(Deserialize)]
+pub(super) struct ScreenQuery {
+    #[serde(default)]
+    format: Format,
+    /// Screen generation from an earlier response; only rows changed
+    /// after it are returned.
+    since_generation: Option<u64>,
+    /// Target a specific server in the federation.
+    server: Option<String>,
+}
+
+#[derive(Serialize)]
+struct EnrichedScreen {
+    #[serde(flatten)]
+    screen: crate::parser::state::QueryResponse,
+    last_activity_ms: u64,
+}
+
+pub(super) async fn screen(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<ScreenQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, params.server.as_deref())? {
+        let mut query = Vec::new();
+        if matches!(params.format, Format::Plain) {
+            query.push("format=plain".to_string());
+        }
+        if let Some(since) = params.since_generation {
+            query.push(format!("since_generation={}", since));
+        }
+        let mut path = format!("/sessions/{}/screen", name);
+        if !query.is_empty() {
+            path.push('?');
+            path.push_str(&query.join("&"));
+        }
+        let (status, body) = super::proxy::proxy_get(&backend, &path).await?;
+        return Ok((status, Json(body)).into_response());
+    }
+    let session = get_session(&state.sessions, &name)?;
+    let response = tokio::time::timeout(
+        PARSER_QUERY_TIMEOUT,
+        session.parser.query(Query::Screen {
+            format: params.format,
+            since: params.since_generation,
+        }),
+    )
+    .await
+    .map_err(|_| ApiError::ParserTimeout)?
+    .map_err(|_| ApiError::ParserUnavailable)?;
+
+    let last_activity_ms = session.activity.last_activity_ms();
+    Ok(Json(EnrichedScreen {
+        screen: response,
+        last_activity_ms,
+    })
+    .into_response())
+}
+
+/// GET /sessions/:name/screen.png -- the current screen rendered as an
+/// image, with colors, text attributes and the cursor.
+pub(super) async fn screen_png(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    AxumQuery(params): AxumQuery<ServerQuery>,
+) -> Result<axum::response::Response, ApiError> {
+    let headers = [
+        (axum::http::header::CONTENT_TYPE, "image/png"),
+        (axum::http::header::CACHE_CONTROL, "no-store"),
+    ];
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, params.server.as_deref())? {
+        let path = format!("/sessions/{}/screen.png", name);
+        let (status, body) = super::proxy::proxy_get_bytes(&backend, &path).await?;
+        if !status.is_success() {
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap_or_default();
+            return Ok((status, Json(body)).into_response());
+        }
+        return Ok((status, headers, body).into_response());
+    }
+    let session = get_session(&state.sessions, &name)?;
+    let response = tokio::time::timeout(
+        PARSER_QUERY_TIMEOUT,
+        session.parser.query(Query::Screen { format: Format::Styled, since: None }),
+    )
+    .await
+    .map_err(|_| ApiError::ParserTimeout)?
+    .map_err(|_| ApiError::ParserUnavailable)?;
+    let crate::parser::state::QueryResponse::Screen(screen) = response else {
+        return Err(ApiError::InternalError("unexpected screen query response".into()));
+    };
+    // A 1000x1000 screen is 128M pixels; keep the encoding off the runtime.
+    let png = tokio::task::spawn_blocking(move || crate::parser::image::screen_to_png(&screen))
+        .await
+        .map_err(|e| ApiError::InternalError(format!("screen render task failed: {}", e)))?
+        .map_err(|e| ApiError::InternalError(format!("failed to encode screen: {}", e)))?;
+    Ok((headers, png).into_response())
+}
+
+#[derive(Deserialize, Default)]
+pub(super) struct BulkScreenRequest {
+    /// Sessions to fetch, in order. Mutually exclusive with `tag`.
+    #[serde(default)]
+    sessions: Vec<String>,
+    /// Comma-separated tags; sessions carrying any of them are fetched.
+    #[serde(default)]
+    tag: Option<String>,
+}
+
+/// One entry of a `POST /screens` response: either the session's screen as
+/// plain text or the reason it couldn't be read.
+#[derive(Serialize)]
+pub(super) struct CompactScreen {
+    session: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cursor: Option<crate::parser::state::Cursor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rows: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cols: Option<usize>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    alternate_active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_activity_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'static str>,
+}
+
+impl CompactScreen {
+    fn failed(session: String, error: ApiError) -> Self {
+        Self {
+            session,
+            text: None,
+            cursor: None,
+            rows: None,
+            cols: None,
+            alternate_active: false,
+            last_activity_ms: None,
+            error: Some(error.code()),
+        }
+    }
+}
+
+/// Join screen lines into one string, dropping trailing whitespace on each
+/// line and trailing blank lines.
+fn compact_screen_text(lines: &[crate::parser::state::FormattedLine]) -> String {
+    use crate::parser::state::FormattedLine;
+    let mut text = String::new();
+    for line in lines {
+        match line {
+            FormattedLine::Plain(s) => text.push_str(s.trim_end()),
+            FormattedLine::Styled(spans) => {
+                let joined: String = spans.iter().map(|s| s.text.as_str()).collect();
+                text.push_str(joined.trim_end());
+            }
+        }
+        text.push('\n');
+    }
+    let len = text.trim_end_matches('\n').len();
+    text.truncate(len);
+    text
+}
+
+/// Fetch the screens of many sessions in one request.
+///
+/// Parser queries run concurrently. A session that is missing, not visible
+/// to the token, or whose parser doesn't answer gets an `error` entry
+/// instead of failing the whole request.
+pub(super) async fn screens_bulk(
+    State(state): State<AppState>,
+    grant: Option<Extension<TokenGrant>>,
+    Json(req): Json<BulkScreenRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if !req.sessions.is_empty() && req.tag.is_some() {
+        return Err(ApiError::InvalidRequest(
+            "pass either sessions or tag, not both".to_string(),
+        ));
+    }
+    let tags: Vec<String> = req
+        .tag
+        .as_deref()
+        .map(|t| t.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default();
+    let mut names = if !req.sessions.is_empty() {
+        req.sessions
+    } else {
+        let mut names = if tags.is_empty() {
+            state.sessions.list()
+        } else {
+            state.sessions.sessions_by_tags(&tags)
+        };
+        names.sort();
+        names
+    };
+    let mut seen = std::collections::HashSet::new();
+    names.retain(|n| seen.insert(n.clone()));
+
+    let futs = names.into_iter().map(|name| {
+        let session = state.sessions.get(&name).filter(|session| match grant {
+            Some(Extension(ref grant)) => grant.allows_session(&session.tags.read()),
+            None => true,
+        });
+        async move {
+            let Some(session) = session else {
+                return CompactScreen::failed(name.clone(), ApiError::SessionNotFound(name));
+            };
+            let response = match tokio::time::timeout(
+                PARSER_QUERY_TIMEOUT,
+                session.parser.query(Query::Screen { format: Format::Plain, since: None }),
+            )
+            .await
+            {
+                Ok(Ok(crate::parser::state::QueryResponse::Screen(screen))) => screen,
+                Ok(_) => return CompactScreen::failed(name, ApiError::ParserUnavailable),
+                Err(_) => return CompactScreen::failed(name, ApiError::ParserTimeout),
+            };
+            CompactScreen {
+                session: name,
+                text: Some(compact_screen_text(&response.lines)),
+                cursor: Some(response.cursor),
+                rows: Some(response.rows),
+                cols: Some(response.cols),
+                alternate_active: response.alternate_active,
+                last_activity_ms: Some(session.activity.last_activity_ms()),
+                error: None,
+            }
+        }
+    });
+    let screens = futures::future::join_all(futs).await;
+    Ok(Json(serde_json::json!({ "screens": screens })))
+}
+
+#[derive(Deserialize)]
+pub(super) struct OrchestrateRunRequest {
+    /// Session names, run in this order. Mutually exclusive with `tag`.
+    #[serde(default)]
+    sessions: Vec<String>,
+    /// Comma-separated tags; sessions carrying any of them are run.
+    tag: Option<String>,
+    /// Input sent verbatim. Mutually exclusive with `command`.
+    input: Option<String>,
+    /// A shell command line, run with an exit marker.
+    command: Option<String>,
+    #[serde(default = "default_run_timeout_ms")]
+    timeout_ms: u64,
+    #[serde(default = "default_max_wait")]
+    max_wait_ms: u64,
+}
+
+fn default_run_timeout_ms() -> u64 {
+    2_000
+}
+
+/// The outcome of an orchestrated run for one session.
+#[derive(Serialize)]
+struct RunResult {
+    session: String,
+    /// Whether the session finished before `max_wait_ms`.
+    finished: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    elapsed_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    screen: Option<String>,
+    /// ID of the input, when the session holds it for approval.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    approval: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+impl RunResult {
+    fn failed(session: String, error: ApiError) -> Self {
+        Self {
+            session,
+            finished: false,
+            exit_code: None,
+            elapsed_ms: None,
+            screen: None,
+            approval: None,
+            error: Some(error.code()),
+            message: Some(error.message()),
+        }
+    }
+}
+
+/// Run the same input in many sessions and return their screens.
+///
+/// See [`crate::orchestrate`]. Sessions run concurrently; one that is
+/// missing, not visible to the token, or blocks the input gets an `error`
+/// entry instead of failing the whole request.
+pub(super) async fn orchestrate_run(
+    State(state): State<AppState>,
+    grant: Option<Extension<TokenGrant>>,
+    Json(req): Json<OrchestrateRunRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    use crate::orchestrate::{self, ExitMarker};
+
+    let mut names = match (req.sessions.is_empty(), req.tag.as_deref()) {
+        (false, None) => req.sessions,
+        (true, Some(tag)) => {
+            let tags: Vec<String> =
+                tag.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect();
+            let mut names = state.sessions.sessions_by_tags(&tags);
+            names.sort();
+            names
+        }
+        _ => {
+            return Err(ApiError::InvalidRequest(
+                "pass either sessions or tag".to_string(),
+            ))
+        }
+    };
+    let mut seen = std::collections::HashSet::new();
+    names.retain(|n| seen.insert(n.clone()));
+    if names.len() > orchestrate::MAX_SESSIONS {
+        return Err(ApiError::InvalidRequest(format!(
+            "a run can target at most {} sessions",
+            orchestrate::MAX_SESSIONS
+        )));
+    }
+    let (input, command) = match (req.input, req.command) {
+        (Some(input), None) => (input, false),
+        (None, Some(command)) if !command.trim().is_empty() => (command, true),
+        _ => {
+            return Err(ApiError::InvalidRequest(
+                "pass either input or a non-empty command".to_string(),
+            ))
+        }
+    };
+    let idle = std::time::Duration::from_millis(req.timeout_ms.min(MAX_WAIT_CEILING_MS));
+    let max_wait = std::time::Duration::from_millis(req.max_wait_ms.min(MAX_WAIT_CEILING_MS));
+
+    let started = std::time::Instant::now();
+    let futs = names.into_iter().map(|name| {
+        let session = state.sessions.get(&name).filter(|session| match grant {
+            Some(Extension(ref grant)) => grant.allows_session(&session.tags.read()),
+            None => true,
+        });
+        let input = input.clone();
+        async move {
+            let Some(session) = session else {
+                return RunResult::failed(name.clone(), ApiError::SessionNotFound(name));
+            };
+            let (data, marker) = if command {
+                let marker = ExitMarker::new();
+                (Bytes::from(marker.command_line(&input)), Some(marker))
+            } else {
+                (Bytes::from(input), None)
+            };
+            let finished = match orchestrate::run(&session, data, marker, idle, max_wait).await {
+                Ok(finished) => finished,
+                Err(e) => return RunResult::failed(name, input_error(e)),
+            };
+            let screen = match tokio::time::timeout(
+                PARSER_QUERY_TIMEOUT,
+                session.parser.query(Query::Screen { format: Format::Plain, since: None }),
+            )
+            .await
+            {
+                Ok(Ok(crate::parser::state::QueryResponse::Screen(screen))) => {
+                    Some(compact_screen_text(&screen.lines))
+                }
+                _ => None,
+            };
+            RunResult {
+                session: name,
+                finished: finished.finished,
+                exit_code: finished.exit_code,
+                elapsed_ms: Some(finished.elapsed.as_millis() as u64),
+                screen,
+                approval: finished.approval,
+                error: None,
+                message: None,
+            }
+        }
+    });
+    let results = futures::future::join_all(futs).await;
+    Ok(Json(serde_json::json!({
+        "results": results,
+        "elapsed_ms": started.elapsed().as_millis() as u64,
+    })))
+}
+
+#[derive(Deserialize)]
+pub(super) struct StatsQuery {
+    /// How long to measure CPU usage over.
+    #[serde(default = "default_stats_interval_ms")]
+    interval_ms: u64,
+    /// Target a specific server in the federation.
+    server: Option<String>,
+}
+
+fn default_stats_interval_ms() -> u64 {
+    200
+}
+
+/// Upper bound on `interval_ms` for `/stats`.
+const MAX_STATS_INTERVAL_MS: u64 = 5_000;
+
+pub(super) async fn stats(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<StatsQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, params.server.as_deref())? {
+        let path = format!("/sessions/{}/stats?interval_ms={}", name, params.interval_ms);
+        let (status, body) = super::proxy::proxy_get(&backend, &path).await?;
+        return Ok((status, Json(body)).into_response());
+    }
+    let session = get_session(&state.sessions, &name)?;
+    let interval = std::time::Duration::from_millis(params.interval_ms.clamp(1, MAX_STATS_INTERVAL_MS));
+    let tree = match session.pid() {
+        Some(pid) => tokio::task::spawn_blocking(move || crate::proctree::sample(pid, interval))
+            .await
+            .map_err(|e| ApiError::InternalError(e.to_string()))?,
+        None => crate::proctree::TreeStats::default(),
+    };
+    Ok(Json(serde_json::json!({
+        "session": session.name,
+        "pid": session.pid(),
+        "interval_ms": interval.as_millis() as u64,
+        "cpu_percent": tree.cpu_percent,
+        "rss_bytes": tree.rss_bytes,
+        "open_fds": tree.open_fds,
+        "processes": tree.processes,
+        "usage": session.usage.snapshot(),
+    }))
+    .into_response())
+}
+
+#[derive(Deserialize)]
+pub(super) struct ScrollbackQuery {
+    #[serde(default)]
+    format: Format,
+    #[serde(default)]
+    offset: usize,
+    #[serde(default = "default_limit")]
+    limit: usize,
+    /// Start at this bookmark's line, with `offset` counting from there.
+    since_bookmark: Option<String>,
+    /// Target a specific server in the federation.
+    server: Option<String>,
+}
+
+fn default_limit() -> usize {
+    100
+}
+
+pub(super) async fn scrollback(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<ScrollbackQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, params.server.as_deref())? {
+        let mut path = format!(
+            "/sessions/{}/scrollback?offset={}&limit={}",
+            name, params.offset, params.limit
+        );
+        if matches!(params.format, Format::Plain) {
+            path.push_str("&format=plain");
+        }
+        if let Some(bookmark) = &params.since_bookmark {
+            if !crate::bookmarks::is_valid_name(bookmark) {
+                return Err(ApiError::BookmarkNotFound(bookmark.clone()));
+            }
+            path.push_str(&format!("&since_bookmark={}", bookmark));
+        }
+        let (status, body) = super::proxy::proxy_get(&backend, &path).await?;
+        return Ok((status, Json(body)).into_response());
+    }
+    let session = get_session(&state.sessions, &name)?;
+    let limit = params.limit.min(10_000);
+    let bookmark = match &params.since_bookmark {
+        Some(name) => Some(
+            session
+                .bookmarks
+                .get(name)
+                .ok_or_else(|| ApiError::BookmarkNotFound(name.clone()))?,
+        ),
+        None => None,
+    };
+
+    // A bookmark's offset moves as old lines are dropped. If any are
+    // dropped while the query is in flight, read again with the new offset.
+    let mut attempts = 0;
+    let response = loop {
+        let lines_dropped = session.parser.lines_dropped();
+        let start = bookmark
+            .as_ref()
+            .map_or(0, |b| b.offset(lines_dropped).unwrap_or(0));
+        let response = tokio::time::timeout(
+            PARSER_QUERY_TIMEOUT,
+            session.parser.query(Query::Scrollback {
+                format: params.format,
+                offset: start + params.offset,
+                limit,
+            }),
+        )
+        .await
+        .map_err(|_| ApiError::ParserTimeout)?
+        .map_err(|_| ApiError::ParserUnavailable)?;
+        attempts += 1;
+        if bookmark.is_none() || attempts == 3 || session.parser.lines_dropped() == lines_dropped {
+            break response;
+        }
+    };
+
+    Ok(Json(response).into_response())
+}
+
+#[derive(Deserialize)]
+pub(super) struct OutputQuery {
+    /// Cursor from a previous response. Omitted, output starts at the
+    /// oldest line kept.
+    since: Option<String>,
+    #[serde(default = "default_output_limit")]
+    limit: usize,
+    /// Target a specific server in the federation.
+    server: Option<String>,
+}
+
+fn default_output_limit() -> usize {
+    1000
+}
+
+/// Plain-text output appended since a cursor, for consumers that want a log
+/// rather than screen state. Cursors are line numbers that stay valid as
+/// old lines are dropped, so polling neither repeats nor skips lines; lines
+/// dropped before a slow poller got to them are counted in `missed`.
+pub(super) async fn output(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<OutputQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let since = params
+        .since
+        .as_deref()
+        .map(|cursor| {
+            cursor
+                .parse::<u64>()
+                .map_err(|_| ApiError::InvalidRequest(format!("invalid output cursor '{}'", cursor)))
+        })
+        .transpose()?;
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, params.server.as_deref())? {
+        let mut path = format!("/sessions/{}/output?limit={}", name, params.limit);
+        if let Some(since) = since {
+            path.push_str(&format!("&since={}", since));
+        }
+        let (status, body) = super::proxy::proxy_get(&backend, &path).await?;
+        return Ok((status, Json(body)).into_response());
+    }
+    let session = get_session(&state.sessions, &name)?;
+    let output = tokio::time::timeout(
+        PARSER_QUERY_TIMEOUT,
+        session.parser.output(since, params.limit.min(10_000)),
+    )
+    .await
+    .map_err(|_| ApiError::ParserTimeout)?
+    .map_err(|_| ApiError::ParserUnavailable)?;
+
+    Ok(Json(serde_json::json!({
+        "lines": output.lines,
+        "cursor": output.next.to_string(),
+        "missed": output.missed,
+        "more": output.more,
+    }))
+    .into_response())
+}
+
+// ── Bookmark handlers ─────────────────────────────────────────
+
+#[derive(Deserialize, Serialize)]
+pub(super) struct CreateBookmarkRequest {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+}
+
+/// A bookmark with its current scrollback offset, `null` once its line has
+/// been dropped.
+#[derive(Serialize)]
+struct BookmarkResponse {
+    #[serde(flatten)]
+    bookmark: crate::bookmarks::Bookmark,
+    offset: Option<usize>,
+}
+
+impl BookmarkResponse {
+    fn new(bookmark: crate::bookmarks::Bookmark, session: &Session) -> Self {
+        let offset = bookmark.offset(session.parser.lines_dropped());
+        Self { bookmark, offset }
+    }
+}
+
+pub(super) async fn bookmark_create(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    AxumQuery(query): AxumQuery<ServerQuery>,
+    Json(req): Json<CreateBookmarkRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
+        let body = serde_json::to_value(&req).map_err(|e| ApiError::InternalError(e.to_string()))?;
+        let (status, resp_body) =
+            super::proxy::proxy_post(&backend, &format!("/sessions/{}/bookmarks", name), body).await?;
+        return Ok((status, Json(resp_body)).into_response());
+    }
+    let session = get_session(&state.sessions, &name)?;
+    let line = tokio::time::timeout(PARSER_QUERY_TIMEOUT, session.parser.mark())
+        .await
+        .map_err(|_| ApiError::ParserTimeout)?
+        .map_err(|_| ApiError::ParserUnavailable)?;
+    let bookmark = session
+        .bookmarks
+        .set(&req.name, line, req.note)
+        .map_err(|e| ApiError::InvalidRequest(e.to_string()))?;
+    Ok((StatusCode::CREATED, Json(BookmarkResponse::new(bookmark, &session))).into_response())
+}
+
+pub(super) async fn bookmark_list(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    AxumQuery(query): AxumQuery<ServerQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
+        let (status, body) =
+            super::proxy::proxy_get(&backend, &format!("/sessions/{}/bookmarks", name)).await?;
+        return Ok((status, Json(body)).into_response());
+    }
+    let session = get_session(&state.sessions, &name)?;
+    let bookmarks: Vec<_> = session
+        .bookmarks
+        .list()
+        .into_iter()
+        .map(|b| BookmarkResponse::new(b, &session))
+        .collect();
+    Ok(Json(serde_json::json!({ "bookmarks": bookmarks })).into_response())
+}
+
+pub(super) async fn bookmark_delete(
+    State(state): State<AppState>,
+    Path((name, bookmark)): Path<(String, String)>,
+    AxumQuery(query): AxumQuery<ServerQuery>,
+) -> Result<StatusCode, ApiError> {
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
+        let status = super::proxy::proxy_delete(
+            &backend,
+            &format!("/sessions/{}/bookmarks/{}", name, bookmark),
+        )
+        .await?;
+        return Ok(status);
+    }
+    let session = get_session(&state.sessions, &name)?;
+    if !session.bookmarks.remove(&bookmark) {
+        return Err(ApiError::BookmarkNotFound(bookmark));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// ── Pipe handlers ─────────────────────────────────────────────
+
+/// Where to pipe a session's output: exactly one of `session` and
+/// `command`.
+#[derive(Deserialize, Serialize)]
+pub(super) struct CreatePipeRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    session: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    command: Option<String>,
+    #[serde(default)]
+    filter: crate::pipe::Filter,
+}
+
+pub(super) async fn pipe_create(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    AxumQuery(query): AxumQuery<ServerQuery>,
+    Json(req): Json<CreatePipeRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    use crate::pipe::{PipeError, Sink};
+
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
+        let body = serde_json::to_value(&req).map_err(|e| ApiError::InternalError(e.to_string()))?;
+        let (status, resp_body) =
+            super::proxy::proxy_post(&backend, &format!("/sessions/{}/pipe", name), body).await?;
+        return Ok((status, Json(resp_body)).into_response());
+    }
+    let session = get_session(&state.sessions, &name)?;
+    let sink = match (req.session, req.command) {
+        (Some(target), None) => Sink::Session(Box::new(get_session(&state.sessions, &target)?)),
+        (None, Some(command)) => Sink::Command(command),
+        _ => {
+            return Err(ApiError::InvalidRequest(
+                "set exactly one of 'session' and 'command'".into(),
+            ))
+        }
+    };
+    let pipe = crate::pipe::start(&session, sink, req.filter).map_err(|e| match e {
+        PipeError::TooMany => ApiError::ResourceLimitReached(e.to_string()),
+        PipeError::Spawn(_) => ApiError::InternalError(e.to_string()),
+        PipeError::SameSession | PipeError::EmptyCommand => ApiError::InvalidRequest(e.to_string()),
+    })?;
+    Ok((StatusCode::CREATED, Json(pipe)).into_response())
+}
+
+pub(super) async fn pipe_list(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    AxumQuery(query): AxumQuery<ServerQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
+        let (status, body) =
+            super::proxy::proxy_get(&backend, &format!("/sessions/{}/pipe", name)).await?;
+        return Ok((status, Json(body)).into_response());
+    }
+    let session = get_session(&state.sessions, &name)?;
+    Ok(Json(serde_json::json!({ "pipes": session.pipes.list() })).into_response())
+}
+
+pub(super) async fn pipe_delete(
+    State(state): State<AppState>,
+    Path((name, id)): Path<(String, u64)>,
+    AxumQuery(query): AxumQuery<ServerQuery>,
+) -> Result<StatusCode, ApiError> {
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
+        let status =
+            super::proxy::proxy_delete(&backend, &format!("/sessions/{}/pipe/{}", name, id)).await?;
+        return Ok(status);
+    }
+    let session = get_session(&state.sessions, &name)?;
+    if !session.pipes.stop(id) {
+        return Err(ApiError::PipeNotFound(id.to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub(super) struct RedactRequest {
+    patterns: Vec<String>,
+}
+
+/// Most patterns a session can add to the server's.
+const MAX_SESSION_REDACT_PATTERNS: usize = 64;
+
+fn redact_response(session: &Session) -> serde_json::Value {
+    serde_json::json!({
+        "patterns": session.redactor.patterns(),
+        "server_patterns": session.policy.counts().1,
+    })
+}
+
+/// GET /sessions/{name}/redact -- the session's own redaction patterns.
+pub(super) async fn redact_get(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    AxumQuery(query): AxumQuery<ServerQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
+        let (status, body) =
+            super::proxy::proxy_get(&backend, &format!("/sessions/{}/redact", name)).await?;
+        return Ok((status, Json(body)).into_response());
+    }
+    let session = get_session(&state.sessions, &name)?;
+    Ok(Json(redact_response(&session)).into_response())
+}
+
+/// PUT /sessions/{name}/redact -- replace the session's own redaction
+/// patterns. They apply to output from now on, on top of the server's.
+pub(super) async fn redact_put(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    AxumQuery(query): AxumQuery<ServerQuery>,
+    body: Bytes,
+) -> Result<impl IntoResponse, ApiError> {
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
+        let (status, content_type, body) = super::proxy::proxy_request(
+            &backend,
+            axum::http::Method::PUT,
+            &format!("/sessions/{}/redact", name),
+            Some(axum::http::HeaderValue::from_static("application/json")),
+            body,
+        )
+        .await?;
+        let mut response = (status, body).into_response();
+        if let Some(content_type) = content_type {
+            response.headers_mut().insert(axum::http::header::CONTENT_TYPE, content_type);
+        }
+        return Ok(response);
+    }
+    let req: RedactRequest =
+        serde_json::from_slice(&body).map_err(|e| ApiError::InvalidRequest(e.to_string()))?;
+    if req.patterns.len() > MAX_SESSION_REDACT_PATTERNS {
+        return Err(ApiError::InvalidRequest(format!(
+            "at most {} patterns per session",
+            MAX_SESSION_REDACT_PATTERNS
+        )));
+    }
+    let session = get_session(&state.sessions, &name)?;
+    session
+        .redactor
+        .set_patterns(&req.patterns)
+        .map_err(ApiError::InvalidRequest)?;
+    Ok(Json(redact_response(&session)).into_response())
+}
+
+#[derive(Deserialize)]
+pub(super) struct ClipboardRequest {
+    text: String,
+    /// Selection targets to answer for, as in OSC 52. Defaults to `c`.
+    #[serde(default)]
+    selection: Option<String>,
+}
+
+/// GET /sessions/{name}/clipboard -- what the session last copied.
+pub(super) async fn clipboard_get(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    AxumQuery(query): AxumQuery<ServerQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
+        let (status, body) =
+            super::proxy::proxy_get(&backend, &format!("/sessions/{}/clipboard", name)).await?;
+        return Ok((status, Json(body)).into_response());
+    }
+    let session = get_session(&state.sessions, &name)?;
+    let mut clipboard = session.parser.clipboard().ok_or(ApiError::ClipboardEmpty(name))?;
+    clipboard.text = session.redactor.redact_str(&clipboard.text).into_owned();
+    Ok(Json(clipboard).into_response())
+}
+
+/// POST /sessions/{name}/clipboard -- set the session's clipboard and send
+/// it to the program as the reply to an OSC 52 query.
+pub(super) async fn clipboard_post(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    AxumQuery(query): AxumQuery<ServerQuery>,
+    body: Bytes,
+) -> Result<StatusCode, ApiError> {
+    use crate::parser::clipboard::{response, Clipboard, ClipboardSource};
+
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
+        return super::proxy::proxy_post_bytes(&backend, &format!("/sessions/{}/clipboard", name), body).await;
+    }
+    let req: ClipboardRequest =
+        serde_json::from_slice(&body).map_err(|e| ApiError::InvalidRequest(e.to_string()))?;
+    let selection = req.selection.unwrap_or_else(|| "c".to_string());
+    if !selection.chars().all(|c| matches!(c, 'c' | 'p' | 'q' | 's' | '0'..='7')) {
+        return Err(ApiError::InvalidRequest(format!("invalid selection '{}'", selection)));
+    }
+    let session = get_session(&state.sessions, &name)?;
+    let reply = response(&selection, &req.text);
+    // Clipboard contents are often secrets; audit them by length only.
+    session.input_broadcaster.record_sensitive_input(InputSource::Http, reply.len());
+    send_input(&session, Bytes::from(reply)).await?;
+    session.parser.set_clipboard(Clipboard {
+        selection,
+        text: req.text,
+        source: ClipboardSource::Api,
+    });
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// File formats for `GET /sessions/{name}/scrollback/export`.
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum ExportFormat {
+    /// Text with the ANSI escapes that reproduce its styling.
+    #[default]
+    Ansi,
+    Plain,
+    /// A standalone page with the colors and attributes as CSS.
+    Html,
+}
+
+impl ExportFormat {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Ansi => "ansi",
+            Self::Plain => "plain",
+            Self::Html => "html",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Ansi | Self::Plain => "text/plain; charset=utf-8",
+            Self::Html => "text/html; charset=utf-8",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Ansi => "ans",
+            Self::Plain => "txt",
+            Self::Html => "html",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(super) struct ScrollbackExportQuery {
+    #[serde(default)]
+    format: ExportFormat,
+    /// Target a specific server in the federation.
+    server: Option<String>,
+}
+
+/// Lines fetched from the parser at a time while exporting, so a long
+/// export doesn't hold up other queries.
+const EXPORT_PAGE_LINES: usize = 1000;
+
+/// Fetch one page of lines for an export, with the total line count.
+async fn export_page(
+    session: &Session,
+    styled: bool,
+    offset: usize,
+) -> Result<(Vec<crate::parser::state::FormattedLine>, usize), ApiError> {
+    let response = tokio::time::timeout(
+        PARSER_QUERY_TIMEOUT,
+        session.parser.query(Query::Scrollback {
+            format: if styled { Format::Styled } else { Format::Plain },
+            offset,
+            limit: EXPORT_PAGE_LINES,
+        }),
+    )
+    .await
+    .map_err(|_| ApiError::ParserTimeout)?
+    .map_err(|_| ApiError::ParserUnavailable)?;
+    match response {
+        crate::parser::state::QueryResponse::Scrollback(page) => Ok((page.lines, page.total_lines)),
+        _ => Err(ApiError::InternalError("unexpected scrollback query response".into())),
+    }
+}
+
+fn render_export_lines(format: ExportFormat, lines: &[crate::parser::state::FormattedLine]) -> Bytes {
+    let mut out = String::new();
+    for line in lines {
+        match format {
+            ExportFormat::Ansi | ExportFormat::Plain => out.push_str(&crate::parser::ansi::line_to_ansi(line)),
+            ExportFormat::Html => out.push_str(&crate::parser::html::line_to_html(line)),
+        }
+        out.push('\n');
+    }
+    Bytes::from(out)
+}
+
+/// GET /sessions/:name/scrollback/export -- the whole scrollback and
+/// screen as a downloadable file.
+///
+/// Lines are read from the parser a page at a time and streamed out. The
+/// line count is fixed when the export starts; output the session prints
+/// meanwhile is not included.
+pub(super) async fn scrollback_export(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    AxumQuery(params): AxumQuery<ScrollbackExportQuery>,
+) -> Result<axum::response::Response, ApiError> {
+    let format = params.format;
+    let headers = [
+        (axum::http::header::CONTENT_TYPE, format.content_type().to_string()),
+        (
+            axum::http::header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}-scrollback.{}\"", name, format.extension()),
+        ),
+    ];
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, params.server.as_deref())? {
+        let path = format!("/sessions/{}/scrollback/export?format={}", name, format.as_str());
+        let (status, body) = super::proxy::proxy_get_bytes(&backend, &path).await?;
+        if !status.is_success() {
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap_or_default();
+            return Ok((status, Json(body)).into_response());
+        }
+        return Ok((status, headers, body).into_response());
+    }
+    let session = get_session(&state.sessions, &name)?;
+    let styled = !matches!(format, ExportFormat::Plain);
+
+    // Read the first page up front so a stuck parser is an error status
+    // rather than a truncated download.
+    let (first, total_lines) = export_page(&session, styled, 0).await?;
+    let start = match format {
+        ExportFormat::Html => Some(Bytes::from(crate::parser::html::document_start(&format!(
+            "{} scrollback",
+            name
+        )))),
+        _ => None,
+    };
+    let end = matches!(format, ExportFormat::Html)
+        .then(|| Bytes::from_static(crate::parser::html::DOCUMENT_END.as_bytes()));
+
+    let first_len = first.len();
+    let first = render_export_lines(format, &first);
+    let rest = futures::stream::unfold(first_len, move |offset| {
+        let session = session.clone();
+        async move {
+            if offset >= total_lines {
+                return None;
+            }
+            match export_page(&session, styled, offset).await {
+                Ok((lines, _)) if !lines.is_empty() => {
+                    let lines = &lines[..lines.len().min(total_lines - offset)];
+                    Some((Ok(render_export_lines(format, lines)), offset + lines.len()))
+                }
+                Ok(_) => None,
+                Err(e) => Some((Err(std::io::Error::other(e.message())), total_lines)),
             }
         }
-        Err(_) => Err(ApiError::IdleTimeout),
-    }
+    });
+    let body = futures::stream::iter(start.into_iter().chain(Some(first)).map(Ok))
+        .chain(rest)
+        .chain(futures::stream::iter(end.map(Ok)));
+
+    Ok((headers, axum::body::Body::from_stream(body)).into_response())
 }
 
 #[derive(Deserialize)]
-pub(super) struct ScreenQuery {
-    #[serde(default)]
-    format: Format,
+pub(super) struct AuditQuery {
+    /// Only return entries with a sequence number greater than this.
+    since: Option<u64>,
+    /// Return at most this many of the most recent matching entries.
+    limit: Option<usize>,
     /// Target a specific server in the federation.
     server: Option<String>,
 }
 
 #[derive(Serialize)]
-struct EnrichedScreen {
-    #[serde(flatten)]
-    screen: crate::parser::state::QueryResponse,
-    last_activity_ms: u64,
+struct AuditResponse {
+    entries: Vec<crate::input::AuditEntry>,
 }
 
-pub(super) async fn screen(
+pub(super) async fn audit(
     State(state): State<AppState>,
     Path(name): Path<String>,
-    axum::extract::Query(params): axum::extract::Query<ScreenQuery>,
+    axum::extract::Query(params): axum::extract::Query<AuditQuery>,
 ) -> Result<impl IntoResponse, ApiError> {
     if let SessionTarget::Remote(backend) = resolve_server_target(&state, params.server.as_deref())? {
-        let mut path = format!("/sessions/{}/screen", name);
-        if matches!(params.format, Format::Plain) {
-            path.push_str("?format=plain");
+        let mut query = Vec::new();
+        if let Some(since) = params.since {
+            query.push(format!("since={}", since));
+        }
+        if let Some(limit) = params.limit {
+            query.push(format!("limit={}", limit));
+        }
+        let mut path = format!("/sessions/{}/audit", name);
+        if !query.is_empty() {
+            path.push('?');
+            path.push_str(&query.join("&"));
         }
         let (status, body) = super::proxy::proxy_get(&backend, &path).await?;
         return Ok((status, Json(body)).into_response());
     }
     let session = get_session(&state.sessions, &name)?;
-    let response = tokio::time::timeout(
-        PARSER_QUERY_TIMEOUT,
-        session.parser.query(Query::Screen { format: params.format }),
-    )
-    .await
-    .map_err(|_| ApiError::ParserTimeout)?
-    .map_err(|_| ApiError::ParserUnavailable)?;
-
-    let last_activity_ms = session.activity.last_activity_ms();
-    Ok(Json(EnrichedScreen {
-        screen: response,
-        last_activity_ms,
-    })
-    .into_response())
+    let mut entries = session
+        .input_broadcaster
+        .audit_entries(params.since, params.limit);
+    // Redacted on the way out, so changed rules apply to past entries too.
+    for entry in &mut entries {
+        if let std::borrow::Cow::Owned(text) = session.redactor.redact_str(&entry.text) {
+            entry.text = text;
+        }
+    }
+    Ok(Json(AuditResponse { entries }).into_response())
 }
 
 #[derive(Deserialize)]
-pub(super) struct ScrollbackQuery {
-    #[serde(default)]
-    format: Format,
-    #[serde(default)]
-    offset: usize,
-    #[serde(default = "default_limit")]
+pub(super) struct InsightsQuery {
+    /// How many of the most recent lines to scan.
+    #[serde(default = "default_insight_lines")]
+    lines: usize,
+    /// Return at most this many findings.
+    #[serde(default = "default_insight_limit")]
     limit: usize,
     /// Target a specific server in the federation.
     server: Option<String>,
 }
 
-fn default_limit() -> usize {
-    100
+fn default_insight_lines() -> usize {
+    crate::insights::DEFAULT_SCAN_LINES
 }
 
-pub(super) async fn scrollback(
+fn default_insight_limit() -> usize {
+    crate::insights::DEFAULT_MAX_FINDINGS
+}
+
+pub(super) async fn insights(
     State(state): State<AppState>,
     Path(name): Path<String>,
-    axum::extract::Query(params): axum::extract::Query<ScrollbackQuery>,
+    axum::extract::Query(params): axum::extract::Query<InsightsQuery>,
 ) -> Result<impl IntoResponse, ApiError> {
     if let SessionTarget::Remote(backend) = resolve_server_target(&state, params.server.as_deref())? {
-        let mut path = format!(
-            "/sessions/{}/scrollback?offset={}&limit={}",
-            name, params.offset, params.limit
-        );
-        if matches!(params.format, Format::Plain) {
-            path.push_str("&format=plain");
-        }
+        let path = format!("/sessions/{}/insights?lines={}&limit={}", name, params.lines, params.limit);
         let (status, body) = super::proxy::proxy_get(&backend, &path).await?;
         return Ok((status, Json(body)).into_response());
     }
     let session = get_session(&state.sessions, &name)?;
-    let limit = params.limit.min(10_000);
-    let response = tokio::time::timeout(
+    let report = tokio::time::timeout(
         PARSER_QUERY_TIMEOUT,
-        session.parser.query(Query::Scrollback {
-            format: params.format,
-            offset: params.offset,
-            limit,
-        }),
+        crate::insights::scan_parser(&session.parser, params.lines, params.limit),
     )
     .await
     .map_err(|_| ApiError::ParserTimeout)?
     .map_err(|_| ApiError::ParserUnavailable)?;
+    Ok(Json(report).into_response())
+}
 
-    Ok(Json(response).into_response())
+
+#[derive(Deserialize)]
+pub(super) struct LinksQuery {
+    /// Return at most this many distinct links.
+    #[serde(default = "default_links_limit")]
+    limit: usize,
+    /// Target a specific server in the federation.
+    server: Option<String>,
+}
+
+fn default_links_limit() -> usize {
+    crate::parser::links::DEFAULT_MAX_LINKS
+}
+
+/// GET /sessions/{name}/links -- hyperlinks and URLs in the output.
+pub(super) async fn links(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<LinksQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, params.server.as_deref())? {
+        let path = format!("/sessions/{}/links?limit={}", name, params.limit);
+        let (status, body) = super::proxy::proxy_get(&backend, &path).await?;
+        return Ok((status, Json(body)).into_response());
+    }
+    let session = get_session(&state.sessions, &name)?;
+    let links = tokio::time::timeout(PARSER_QUERY_TIMEOUT, session.parser.links(params.limit))
+        .await
+        .map_err(|_| ApiError::ParserTimeout)?
+        .map_err(|_| ApiError::ParserUnavailable)?;
+    Ok(Json(links).into_response())
+}
+
+/// GET /sessions/{name}/modes -- keyboard, paste, mouse and focus modes
+/// the program has negotiated, for encoding input to it.
+pub(super) async fn modes(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    AxumQuery(query): AxumQuery<ServerQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
+        let (status, body) =
+            super::proxy::proxy_get(&backend, &format!("/sessions/{}/modes", name)).await?;
+        return Ok((status, Json(body)).into_response());
+    }
+    let session = get_session(&state.sessions, &name)?;
+    let modes = tokio::time::timeout(PARSER_QUERY_TIMEOUT, session.parser.modes())
+        .await
+        .map_err(|_| ApiError::ParserTimeout)?
+        .map_err(|_| ApiError::ParserUnavailable)?;
+    Ok(Json(modes).into_response())
+}
+
+/// GET /sessions/{name}/images -- inline images drawn in the session.
+pub(super) async fn images(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    AxumQuery(query): AxumQuery<ServerQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
+        let (status, body) =
+            super::proxy::proxy_get(&backend, &format!("/sessions/{}/images", name)).await?;
+        return Ok((status, Json(body)).into_response());
+    }
+    let session = get_session(&state.sessions, &name)?;
+    Ok(Json(serde_json::json!({ "images": session.parser.images() })).into_response())
 }
 
+/// GET /sessions/{name}/images/{id} -- an inline image's data: the image
+/// file for iTerm2 images, the Sixel sequence for Sixel ones.
+pub(super) async fn image_get(
+    State(state): State<AppState>,
+    Path((name, id)): Path<(String, String)>,
+    AxumQuery(query): AxumQuery<ServerQuery>,
+) -> Result<axum::response::Response, ApiError> {
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
+        let path = format!("/sessions/{}/images/{}", name, id);
+        let (status, body) = super::proxy::proxy_get_bytes(&backend, &path).await?;
+        if !status.is_success() {
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap_or_default();
+            return Ok((status, Json(body)).into_response());
+        }
+        // The backend's content type isn't passed through the proxy.
+        let headers = [(axum::http::header::CONTENT_TYPE, "application/octet-stream")];
+        return Ok((status, headers, body).into_response());
+    }
+    let session = get_session(&state.sessions, &name)?;
+    let image = id
+        .parse()
+        .ok()
+        .and_then(|id| session.parser.image(id))
+        .ok_or(ApiError::ImageNotFound(id))?;
+    let headers = [(axum::http::header::CONTENT_TYPE, image.info.content_type)];
+    Ok((headers, image.data).into_response())
+}
 
 // Overlay request/response types
 #[derive(Deserialize)]
@@ -2416,9 +4757,19 @@ pub(super) struct CreateOverlayRequest {
     height: u16,
     #[serde(default)]
     background: Option<BackgroundStyle>,
+    #[serde(default)]
     spans: Vec<OverlaySpan>,
     #[serde(default)]
     focusable: bool,
+    /// Remove the overlay this many milliseconds after it is created.
+    #[serde(default)]
+    ttl_ms: Option<u64>,
+    /// Show the overlay in both screen modes.
+    #[serde(default)]
+    sticky: bool,
+    /// Render the overlay's spans from this widget instead.
+    #[serde(default)]
+    widget: Option<crate::overlay::Widget>,
 }
 
 #[derive(Serialize)]
@@ -2440,6 +4791,9 @@ pub(super) struct PatchOverlayRequest {
     height: Option<u16>,
     #[serde(default)]
     background: Option<BackgroundStyle>,
+    /// Fields to change in the overlay's widget, or a whole new widget.
+    #[serde(default)]
+    widget: Option<serde_json::Value>,
 }
 
 #[derive(Deserialize)]
@@ -2462,6 +4816,13 @@ pub(super) async fn overlay_create(
     let current_mode = *session.screen_mode.read();
     let id = session.overlays.create(req.x, req.y, req.z, req.width, req.height, req.background, req.spans, req.focusable, current_mode)
         .map_err(|e| ApiError::ResourceLimitReached(e.to_string()))?;
+    if let Some(widget) = req.widget {
+        if let Err(e) = session.overlays.set_widget(&id, widget) {
+            session.overlays.delete(&id);
+            return Err(ApiError::InvalidOverlay(e.into()));
+        }
+    }
+    session.overlays.set_lifetime(&id, req.ttl_ms.map(std::time::Duration::from_millis), req.sticky);
     let _ = session.visual_update_tx.send(crate::protocol::VisualUpdate::OverlaysChanged);
     Ok((StatusCode::CREATED, Json(CreateOverlayResponse { id })))
 }
@@ -2507,6 +4868,11 @@ pub(super) async fn overlay_patch(
     Json(req): Json<PatchOverlayRequest>,
 ) -> Result<StatusCode, ApiError> {
     let session = get_session(&state.sessions, &name)?;
+    if let Some(ref widget) = req.widget {
+        if !session.overlays.patch_widget(&id, widget).map_err(ApiError::InvalidOverlay)? {
+            return Err(ApiError::OverlayNotFound(id));
+        }
+    }
     if session.overlays.move_to(&id, req.x, req.y, req.z, req.width, req.height, req.background) {
         let _ = session.visual_update_tx.send(crate::protocol::VisualUpdate::OverlaysChanged);
         Ok(StatusCode::NO_CONTENT)
@@ -2568,6 +4934,19 @@ pub(super) async fn overlay_region_write(
     }
 }
 
+pub(super) async fn overlay_animate(
+    State(state): State<AppState>,
+    Path((name, id)): Path<(String, String)>,
+    Json(animation): Json<crate::overlay::Animation>,
+) -> Result<StatusCode, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    if session.animate_overlay(&id, animation).map_err(ApiError::InvalidOverlay)? {
+        Ok(StatusCode::ACCEPTED)
+    } else {
+        Err(ApiError::OverlayNotFound(id))
+    }
+}
+
 // Panel request/response types
 
 #[derive(Deserialize)]
@@ -2581,6 +4960,15 @@ pub(super) struct CreatePanelRequest {
     spans: Vec<OverlaySpan>,
     #[serde(default)]
     focusable: bool,
+    /// Remove the panel this many milliseconds after it is created.
+    #[serde(default)]
+    ttl_ms: Option<u64>,
+    /// Show the panel in both screen modes.
+    #[serde(default)]
+    sticky: bool,
+    /// Make the panel scrollable, keeping this many lines of content.
+    #[serde(default)]
+    max_lines: Option<usize>,
 }
 
 #[derive(Serialize)]
@@ -2606,6 +4994,16 @@ pub(super) struct PatchPanelRequest {
     spans: Option<Vec<OverlaySpan>>,
 }
 
+#[derive(Deserialize)]
+pub(super) struct AppendPanelRequest {
+    spans: Vec<OverlaySpan>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct ScrollPanelRequest {
+    offset: usize,
+}
+
 // Panel handlers
 
 pub(super) async fn panel_create(
@@ -2615,10 +5013,17 @@ pub(super) async fn panel_create(
 ) -> Result<(StatusCode, Json<CreatePanelResponse>), ApiError> {
     let session = get_session(&state.sessions, &name)?;
     let current_mode = *session.screen_mode.read();
+    if let Some(max_lines) = req.max_lines {
+        panel::validate_max_lines(max_lines).map_err(|e| ApiError::InvalidOverlay(e.into()))?;
+    }
     let id = session
         .panels
         .create(req.position, req.height, req.z, req.background, req.spans, req.focusable, current_mode)
         .map_err(|e| ApiError::ResourceLimitReached(e.to_string()))?;
+    session.panels.set_lifetime(&id, req.ttl_ms.map(std::time::Duration::from_millis), req.sticky);
+    if req.max_lines.is_some() {
+        let _ = session.panels.set_max_lines(&id, req.max_lines);
+    }
     panel::reconfigure_layout(&session.panels, &session.terminal_size, &session.pty, &session.parser)
         .await;
     let _ = session.visual_update_tx.send(crate::protocol::VisualUpdate::PanelsChanged);
@@ -2759,55 +5164,250 @@ pub(super) async fn panel_update_spans(
     }
 }
 
-pub(super) async fn panel_region_write(
+/// POST /sessions/:name/panel/:id/append -- add lines to a scrollable
+/// panel.
+pub(super) async fn panel_append(
+    State(state): State<AppState>,
+    Path((name, id)): Path<(String, String)>,
+    Json(req): Json<AppendPanelRequest>,
+) -> Result<StatusCode, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    if session.panels.append(&id, req.spans).map_err(|e| ApiError::InvalidOverlay(e.into()))? {
+        panel::flush_panel_content(&session.panels, &id, &session.terminal_size);
+        let _ = session.visual_update_tx.send(crate::protocol::VisualUpdate::PanelsChanged);
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::PanelNotFound(id))
+    }
+}
+
+/// POST /sessions/:name/panel/:id/scroll -- scroll a scrollable panel.
+pub(super) async fn panel_scroll(
+    State(state): State<AppState>,
+    Path((name, id)): Path<(String, String)>,
+    Json(req): Json<ScrollPanelRequest>,
+) -> Result<StatusCode, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    if session.panels.scroll(&id, req.offset).map_err(|e| ApiError::InvalidOverlay(e.into()))? {
+        panel::flush_panel_content(&session.panels, &id, &session.terminal_size);
+        let _ = session.visual_update_tx.send(crate::protocol::VisualUpdate::PanelsChanged);
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::PanelNotFound(id))
+    }
+}
+
+pub(super) async fn panel_region_write(
+    State(state): State<AppState>,
+    Path((name, id)): Path<(String, String)>,
+    Json(req): Json<RegionWriteRequest>,
+) -> Result<StatusCode, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    if session.panels.region_write(&id, req.writes).map_err(|e| ApiError::InvalidOverlay(e.into()))? {
+        panel::flush_panel_content(&session.panels, &id, &session.terminal_size);
+        let _ = session.visual_update_tx.send(crate::protocol::VisualUpdate::PanelsChanged);
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::PanelNotFound(id))
+    }
+}
+
+// Status line
+
+#[derive(Serialize)]
+pub(super) struct StatusLineResponse {
+    #[serde(flatten)]
+    config: crate::status_line::StatusLineConfig,
+    /// The text shown, or null while the status line is disabled.
+    text: Option<String>,
+}
+
+impl StatusLineResponse {
+    pub(super) fn new(session: &Session) -> Self {
+        let text = session
+            .status_line
+            .panel()
+            .and_then(|id| session.panels.get(&id))
+            .map(|panel| panel.spans.into_iter().map(|span| span.text).collect());
+        Self { config: session.status_line.config(), text }
+    }
+}
+
+/// GET /sessions/:name/status_line -- the status line settings and text.
+pub(super) async fn status_line_get(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<StatusLineResponse>, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    Ok(Json(StatusLineResponse::new(&session)))
+}
+
+/// PATCH /sessions/:name/status_line -- change the status line settings
+/// and redraw it.
+pub(super) async fn status_line_patch(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(patch): Json<crate::status_line::StatusLinePatch>,
+) -> Result<Json<StatusLineResponse>, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    session.status_line.patch(patch).map_err(ApiError::InvalidRequest)?;
+    state.sessions.refresh_status_lines(Some(&name)).await;
+    Ok(Json(StatusLineResponse::new(&session)))
+}
+
+// Input mode response type
+#[derive(Serialize)]
+pub(super) struct InputModeResponse {
+    mode: Mode,
+}
+
+// Input mode handlers
+pub(super) async fn input_mode_get(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<InputModeResponse>, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    Ok(Json(InputModeResponse {
+        mode: session.input_mode.get(),
+    }))
+}
+
+pub(super) async fn input_capture(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    session.input_mode.capture();
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub(super) async fn input_release(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    session.input_mode.release();
+    session.focus.unfocus();
+    session.return_control(InputSource::Http);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub(super) struct HandoffRequestBody {
+    /// Name shown to the human. Defaults to the token name.
+    agent: Option<String>,
+    reason: Option<String>,
+}
+
+fn handoff_error(e: HandoffError) -> ApiError {
+    ApiError::HandoffConflict(e.to_string())
+}
+
+pub(super) async fn input_handoff_get(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<HandoffStatus>, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    Ok(Json(session.handoff.status()))
+}
+
+/// POST /sessions/:name/input/handoff -- ask the human for keyboard
+/// control. The human is prompted on their terminal; the answer arrives
+/// as a `handoff` input event.
+pub(super) async fn input_handoff_request(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    grant: Option<Extension<TokenGrant>>,
+    Json(req): Json<HandoffRequestBody>,
+) -> Result<(StatusCode, Json<HandoffStatus>), ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    let agent = req
+        .agent
+        .or_else(|| grant.map(|Extension(grant)| grant.name))
+        .unwrap_or_else(|| "agent".to_string());
+    crate::input::handoff::validate_request(&agent, req.reason.as_deref()).map_err(ApiError::InvalidRequest)?;
+    session
+        .request_handoff(agent, req.reason, InputSource::Http)
+        .map_err(handoff_error)?;
+    Ok((StatusCode::ACCEPTED, Json(session.handoff.status())))
+}
+
+pub(super) async fn input_handoff_approve(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<HandoffStatus>, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    session.answer_handoff(true, InputSource::Http).map_err(handoff_error)?;
+    Ok(Json(session.handoff.status()))
+}
+
+pub(super) async fn input_handoff_deny(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<HandoffStatus>, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    session.answer_handoff(false, InputSource::Http).map_err(handoff_error)?;
+    Ok(Json(session.handoff.status()))
+}
+
+/// POST /sessions/:name/input/handoff/return -- the agent gives control
+/// back to the human, or withdraws its pending request.
+pub(super) async fn input_handoff_return(
     State(state): State<AppState>,
-    Path((name, id)): Path<(String, String)>,
-    Json(req): Json<RegionWriteRequest>,
-) -> Result<StatusCode, ApiError> {
+    Path(name): Path<String>,
+) -> Result<Json<HandoffStatus>, ApiError> {
     let session = get_session(&state.sessions, &name)?;
-    if session.panels.region_write(&id, req.writes).map_err(|e| ApiError::InvalidOverlay(e.into()))? {
-        panel::flush_panel_content(&session.panels, &id, &session.terminal_size);
-        let _ = session.visual_update_tx.send(crate::protocol::VisualUpdate::PanelsChanged);
-        Ok(StatusCode::NO_CONTENT)
-    } else {
-        Err(ApiError::PanelNotFound(id))
-    }
+    session.return_control(InputSource::Http);
+    Ok(Json(session.handoff.status()))
 }
 
-// Input mode response type
-#[derive(Serialize)]
-pub(super) struct InputModeResponse {
-    mode: Mode,
+#[derive(Deserialize)]
+pub(super) struct ApprovalModeRequest {
+    enabled: bool,
 }
 
-// Input mode handlers
-pub(super) async fn input_mode_get(
+#[derive(Deserialize)]
+pub(super) struct ApprovalAnswer {
+    approve: bool,
+}
+
+/// GET /sessions/:name/approvals -- whether approval mode is on, and the
+/// input it holds.
+pub(super) async fn approvals_get(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<InputModeResponse>, ApiError> {
+) -> Result<Json<ApprovalStatus>, ApiError> {
     let session = get_session(&state.sessions, &name)?;
-    Ok(Json(InputModeResponse {
-        mode: session.input_mode.get(),
-    }))
+    Ok(Json(session.approvals.status()))
 }
 
-pub(super) async fn input_capture(
+/// PATCH /sessions/:name/approvals -- turn approval mode on or off. Input
+/// already held stays held until it is answered.
+pub(super) async fn approvals_patch(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<StatusCode, ApiError> {
+    Json(req): Json<ApprovalModeRequest>,
+) -> Result<Json<ApprovalStatus>, ApiError> {
     let session = get_session(&state.sessions, &name)?;
-    session.input_mode.capture();
-    Ok(StatusCode::NO_CONTENT)
+    session.approvals.set_enabled(req.enabled);
+    tracing::info!(session = %name, enabled = req.enabled, "approval mode changed");
+    Ok(Json(session.approvals.status()))
 }
 
-pub(super) async fn input_release(
+/// POST /sessions/:name/approvals/:id -- approve or deny held input.
+/// Approved input is written to the PTY.
+pub(super) async fn approval_answer(
     State(state): State<AppState>,
-    Path(name): Path<String>,
-) -> Result<StatusCode, ApiError> {
+    Path((name, id)): Path<(String, u64)>,
+    Json(req): Json<ApprovalAnswer>,
+) -> Result<impl IntoResponse, ApiError> {
     let session = get_session(&state.sessions, &name)?;
-    session.input_mode.release();
-    session.focus.unfocus();
-    Ok(StatusCode::NO_CONTENT)
+    let pending = session
+        .answer_approval(Some(id), req.approve)
+        .await
+        .map_err(input_error)?
+        .ok_or(ApiError::ApprovalNotFound(id))?;
+    Ok(Json(serde_json::json!({ "approved": req.approve, "input": pending })))
 }
 
 #[derive(Deserialize)]
@@ -2815,9 +5415,16 @@ pub(super) struct FocusRequest {
     pub id: String,
 }
 
+#[derive(Deserialize)]
+pub(super) struct FocusOrderRequest {
+    pub ids: Vec<String>,
+}
+
 #[derive(Serialize)]
 pub(super) struct FocusResponse {
     pub focused: Option<String>,
+    /// The elements focus cycles through, in order.
+    pub order: Vec<String>,
 }
 
 pub(super) async fn input_focus(
@@ -2843,7 +5450,8 @@ pub(super) async fn input_focus(
         return Err(ApiError::NotFocusable(req.id));
     }
 
-    session.focus.focus(req.id);
+    session.focus.focus(req.id.clone());
+    session.input_broadcaster.broadcast_focus(Some(req.id), Some(InputSource::Http));
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -2853,6 +5461,7 @@ pub(super) async fn input_unfocus(
 ) -> Result<StatusCode, ApiError> {
     let session = get_session(&state.sessions, &name)?;
     session.focus.unfocus();
+    session.input_broadcaster.broadcast_focus(None, Some(InputSource::Http));
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -2863,6 +5472,33 @@ pub(super) async fn input_focus_get(
     let session = get_session(&state.sessions, &name)?;
     Ok(Json(FocusResponse {
         focused: session.focus.focused(),
+        order: session.focus_ring(),
+    }))
+}
+
+/// PUT /sessions/:name/input/focus/order -- set the order focus cycles
+/// through.
+pub(super) async fn input_focus_order(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(req): Json<FocusOrderRequest>,
+) -> Result<StatusCode, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    session.set_focus_order(req.ids).map_err(ApiError::InvalidRequest)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /sessions/:name/input/focus/next -- move focus to the next
+/// element in the focus order.
+pub(super) async fn input_focus_next(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<FocusResponse>, ApiError> {
+    let session = get_session(&state.sessions, &name)?;
+    session.focus_next(InputSource::Http);
+    Ok(Json(FocusResponse {
+        focused: session.focus.focused(),
+        order: session.focus_ring(),
     }))
 }
 
@@ -2894,11 +5530,101 @@ pub(super) struct CreateSessionRequest {
     pub env: Option<std::collections::HashMap<String, String>>,
     #[serde(default)]
     pub tags: Vec<String>,
+    /// How sizes from multiple attached clients are reconciled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resize_policy: Option<ResizePolicy>,
+    /// Unix account to run the session as, by name. Needs a root server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// Unix account to run the session as, by uid. Must match `user` if
+    /// both are given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    /// Sandbox profile from the server's `[sandbox]` config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<String>,
+    /// Programs the shell may or may not run. See [`crate::safe_mode`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safe_mode: Option<SafeMode>,
+    /// `[user@]host[:port]` to ssh to; `command` then runs on that host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote: Option<String>,
+    /// Override the server's `[sessions] idle_kill_after` (`"0"` never
+    /// reaps this session).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_kill_after: Option<String>,
+    /// CPU niceness for the session's processes, -20 to 19.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nice: Option<i32>,
+    /// I/O priority: `idle`, `best-effort`, or `realtime`, with an
+    /// optional `:level` (0-7).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ionice: Option<String>,
+    /// Override the server's `[sessions] scrollback_lines`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scrollback_lines: Option<usize>,
+    /// Who answers device and status queries (default: the clients).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query_policy: Option<QueryPolicy>,
+    /// Encoding the program writes (default: UTF-8).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<Encoding>,
+    /// Respawn the command in the same session when it exits:
+    /// `on-failure` or `always`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restart: Option<RestartWhen>,
+    /// Restarts in a row before the session is left to end (default 5).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restart_max_retries: Option<u32>,
+    /// Delay before the first restart in a row, doubled for each one after
+    /// (default 1000).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restart_backoff_ms: Option<u64>,
+    /// Session template to start from; fields set here override it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
     /// Target a specific server in the federation for session creation.
     #[serde(default)]
     pub server: Option<String>,
 }
 
+#[derive(Deserialize, Default)]
+pub(super) struct CreateSessionQuery {
+    /// Validate the request and report what would be created, without
+    /// spawning anything.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Normalized parameters reported by `POST /sessions?dry_run=true`.
+#[derive(Serialize)]
+pub(super) struct CreateSessionPreview {
+    pub dry_run: bool,
+    /// `None` when the server will assign a name.
+    pub name: Option<String>,
+    pub server: String,
+    pub command: Option<String>,
+    /// Absolute path of the program that would run, or `None` when the
+    /// command starts with a shell builtin or expansion.
+    pub program: Option<String>,
+    pub cwd: Option<String>,
+    /// Account the session would run as, when not the server's own.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// Sandbox profile the session would run in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<String>,
+    /// Commands the session's shell would be limited to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub safe_mode: Option<SafeMode>,
+    pub rows: u16,
+    pub cols: u16,
+    pub tags: Vec<String>,
+    pub resize_policy: ResizePolicy,
+    /// Names of the extra environment variables; values are not echoed.
+    pub env: Vec<String>,
+}
+
 #[derive(Serialize)]
 pub(super) struct SessionInfo {
     pub name: String,
@@ -2910,6 +5636,44 @@ pub(super) struct SessionInfo {
     pub clients: usize,
     pub tags: Vec<String>,
     pub last_activity_ms: u64,
+    /// Unix time of the last output or input, in milliseconds.
+    pub last_activity_at: u64,
+    /// Unix time the session was created, in milliseconds.
+    pub created_at: u64,
+    pub resize_policy: ResizePolicy,
+    /// Whether the child is currently stopped by a job-control signal.
+    pub suspended: bool,
+    /// Whether the session is hibernated (stopped, terminal state on disk).
+    pub hibernated: bool,
+    /// Lines of scrollback the session keeps.
+    pub scrollback_lines: usize,
+    /// Who answers device and status queries.
+    pub query_policy: QueryPolicy,
+    /// Encoding the program's output is decoded from.
+    pub encoding: Encoding,
+    /// Window title the program last set (OSC 0/2).
+    pub title: Option<String>,
+    /// Working directory the shell last reported (OSC 7).
+    pub cwd: Option<String>,
+    /// Commands the shell is limited to, when in safe mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub safe_mode: Option<SafeMode>,
+    /// The token or MCP session that created the session, when known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<crate::session::Owner>,
+    /// The restart policy, when the session has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart: Option<RestartInfo>,
+    /// Structured data set by API clients.
+    pub metadata: serde_json::Map<String, serde_json::Value>,
+}
+
+/// A session's restart policy and how often it has restarted the command.
+#[derive(Serialize)]
+pub(super) struct RestartInfo {
+    #[serde(flatten)]
+    pub policy: RestartPolicy,
+    pub restarts: usize,
 }
 
 fn build_session_info(session: &crate::session::Session, hostname: &str) -> SessionInfo {
@@ -2919,13 +5683,30 @@ fn build_session_info(session: &crate::session::Session, hostname: &str) -> Sess
     SessionInfo {
         name: session.name.clone(),
         server: hostname.to_string(),
-        pid: session.pid,
+        pid: session.pid(),
         command: session.command.clone(),
         rows,
         cols,
         clients: session.clients(),
         tags,
         last_activity_ms: session.activity.last_activity_ms(),
+        last_activity_at: session.activity.last_activity_at(),
+        created_at: session.created_at,
+        resize_policy: session.size_arbiter.policy(),
+        suspended: session.is_suspended(),
+        hibernated: session.is_hibernated(),
+        scrollback_lines: session.scrollback_lines.load(std::sync::atomic::Ordering::Acquire),
+        query_policy: *session.query_policy.read(),
+        encoding: session.parser.encoding(),
+        title: session.parser.title(),
+        cwd: session.parser.cwd(),
+        safe_mode: session.safe_mode.clone(),
+        owner: session.owner.lock().clone(),
+        restart: session.restart.as_ref().map(|r| RestartInfo {
+            policy: r.policy,
+            restarts: r.restarts(),
+        }),
+        metadata: session.metadata.read().clone(),
     }
 }
 
@@ -2939,6 +5720,21 @@ pub(super) struct UpdateSessionRequest {
     /// Tags to remove (optional)
     #[serde(default)]
     pub remove_tags: Vec<String>,
+    /// New resize policy (optional)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resize_policy: Option<ResizePolicy>,
+    /// New scrollback size (optional); the oldest lines beyond it are dropped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scrollback_lines: Option<usize>,
+    /// New query policy (optional); replaces the whole policy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query_policy: Option<QueryPolicy>,
+    /// New output encoding (optional); applies to output from now on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<Encoding>,
+    /// Metadata to merge in (optional); `null` values remove keys.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Map<String, serde_json::Value>>,
 }
 
 #[derive(Deserialize)]
@@ -2948,20 +5744,70 @@ pub(super) struct ListSessionsQuery {
     pub tag: Option<String>,
     /// Target a specific server, or omit to aggregate from all servers.
     pub server: Option<String>,
+    /// Set to false to list only this server's sessions when `server` is
+    /// omitted.
+    #[serde(default)]
+    pub federated: Option<bool>,
+    /// Only sessions whose command contains this.
+    pub command: Option<String>,
+    /// Only sessions created with this token name or by this MCP session.
+    pub owner: Option<String>,
+    /// Only sessions idle for at least this many milliseconds.
+    pub min_idle_ms: Option<u64>,
+    /// Only sessions with activity in the last this many milliseconds.
+    pub max_idle_ms: Option<u64>,
+    pub min_clients: Option<u64>,
+    pub max_clients: Option<u64>,
+    /// Only sessions created at or after this Unix time in milliseconds.
+    pub created_after: Option<u64>,
+    /// Only sessions created at or before this Unix time in milliseconds.
+    pub created_before: Option<u64>,
+    /// Field to sort by, `-` first for descending. Defaults to `name`.
+    pub sort: Option<String>,
+    /// Sessions to skip after filtering and sorting.
+    #[serde(default)]
+    pub offset: usize,
+    /// Most sessions to return.
+    pub limit: Option<usize>,
 }
 
 // ── Session management handlers ──────────────────────────────────
 
 pub(super) async fn session_list(
     State(state): State<AppState>,
+    grant: Option<Extension<TokenGrant>>,
     axum::extract::Query(params): axum::extract::Query<ListSessionsQuery>,
+    axum::extract::Query(pairs): axum::extract::Query<Vec<(String, String)>>,
+    axum::extract::RawQuery(raw_query): axum::extract::RawQuery,
 ) -> Result<impl IntoResponse, ApiError> {
+    // Filters forwarded to remote servers: the tags, and `meta.*` pairs as
+    // they were sent.
+    let mut forwarded: Vec<String> = params.tag.iter().map(|tag| format!("tag={}", tag)).collect();
+    forwarded.extend(
+        raw_query
+            .as_deref()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|pair| pair.starts_with("meta."))
+            .map(str::to_string),
+    );
+    let remote_path = if forwarded.is_empty() {
+        "/sessions".to_string()
+    } else {
+        format!("/sessions?{}", forwarded.join("&"))
+    };
+    let meta = metadata_filters(&pairs);
+    let sort = parse_sort(params.sort.as_deref().unwrap_or("name"))?;
+
     // If ?server= names a specific remote backend, proxy to just that server.
     // Note: We inline the server-target resolution here instead of using
     // resolve_server_target() because session_list has special "aggregate all"
     // semantics when ?server= is absent.
     if let Some(ref server) = params.server {
         if server != &state.hostname {
+            if !state.backends.proxying() {
+                return Err(federation_disabled());
+            }
             let backend = state
                 .backends
                 .get_by_hostname(server)
@@ -2969,25 +5815,16 @@ pub(super) async fn session_list(
             if backend.health != crate::federation::registry::BackendHealth::Healthy {
                 return Err(ApiError::ServerUnavailable(server.clone()));
             }
-            let mut path = "/sessions".to_string();
-            if let Some(ref tag) = params.tag {
-                path.push_str(&format!("?tag={}", tag));
-            }
-            let (_, body) = super::proxy::proxy_get(&backend, &path).await?;
+            let (_, body) = super::proxy::proxy_get(&backend, &remote_path).await?;
             let sessions = body.as_array().cloned().unwrap_or_default();
-            return Ok(Json(sessions).into_response());
+            return Ok(session_list_response(sessions, &params, sort));
         }
     }
 
-    // Build the remote proxy path once (preserving tag filter for forwarding).
-    let remote_path = match params.tag {
-        Some(ref tag) => format!("/sessions?tag={}", tag),
-        None => "/sessions".to_string(),
-    };
-
     // Collect local sessions.
     let tags: Vec<String> = params
         .tag
+        .as_deref()
         .map(|t| t.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
         .unwrap_or_default();
     let names = if tags.is_empty() {
@@ -2999,36 +5836,196 @@ pub(super) async fn session_list(
         .into_iter()
         .filter_map(|name| {
             let session = state.sessions.get(&name)?;
+            if let Some(Extension(ref grant)) = grant {
+                if !grant.allows_session(&session.tags.read()) {
+                    return None;
+                }
+            }
+            if !metadata_matches(&session.metadata.read(), &meta) {
+                return None;
+            }
             serde_json::to_value(build_session_info(&session, &state.hostname)).ok()
         })
         .collect();
 
     // If no specific server filter, aggregate from all healthy backends.
+    // Tag-restricted tokens only ever see their own local sessions.
     // TODO: Query backends concurrently with join_all for better latency.
-    if params.server.is_none() {
+    let restricted = grant.as_ref().is_some_and(|Extension(g)| g.is_tag_restricted());
+    let federated = params.federated.unwrap_or(true);
+    if params.server.is_none() && federated && !restricted && state.backends.proxying() {
         for backend in state.backends.healthy() {
             if let Ok((_, body)) = super::proxy::proxy_get(&backend, &remote_path).await {
                 if let Some(arr) = body.as_array() {
-                    all_sessions.extend(arr.iter().cloned());
+                    // Every entry names its server, so clients can address
+                    // it as `server:session`.
+                    all_sessions.extend(arr.iter().cloned().map(|mut entry| {
+                        if let (Some(obj), Some(hostname)) = (entry.as_object_mut(), &backend.hostname) {
+                            obj.entry("server").or_insert_with(|| hostname.clone().into());
+                        }
+                        entry
+                    }));
                 }
             }
             // Silently skip backends that fail to respond.
         }
     }
 
-    Ok(Json(all_sessions).into_response())
+    Ok(session_list_response(all_sessions, &params, sort))
+}
+
+/// Fields `GET /sessions` can sort by.
+const SESSION_SORT_FIELDS: &[&str] = &["name", "command", "clients", "created_at", "last_activity_ms"];
+
+/// Parse a `sort` parameter: a field name, with a leading `-` for
+/// descending order.
+fn parse_sort(sort: &str) -> Result<(&str, bool), ApiError> {
+    let (field, descending) = match sort.strip_prefix('-') {
+        Some(field) => (field, true),
+        None => (sort, false),
+    };
+    if !SESSION_SORT_FIELDS.contains(&field) {
+        return Err(ApiError::InvalidRequest(format!(
+            "cannot sort sessions by '{}'; use one of: {}",
+            field,
+            SESSION_SORT_FIELDS.join(", ")
+        )));
+    }
+    Ok((field, descending))
+}
+
+/// Filter, sort and page a session list. The number of matches before
+/// paging goes in the `X-Total-Count` header. Works on the JSON so that
+/// sessions from other servers are treated alike.
+fn session_list_response(
+    mut sessions: Vec<serde_json::Value>,
+    params: &ListSessionsQuery,
+    (field, descending): (&str, bool),
+) -> axum::response::Response {
+    sessions.retain(|info| session_matches(info, params));
+    sessions.sort_by(|a, b| {
+        let ordering = compare_json(&a[field], &b[field]).then_with(|| compare_json(&a["name"], &b["name"]));
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    let total = sessions.len();
+    let page: Vec<_> = sessions
+        .into_iter()
+        .skip(params.offset)
+        .take(params.limit.unwrap_or(usize::MAX))
+        .collect();
+    ([("x-total-count", total.to_string())], Json(page)).into_response()
+}
+
+/// Whether a session's info passes the list query's filters. Sessions
+/// without a filtered field, as from older servers, don't.
+fn session_matches(info: &serde_json::Value, params: &ListSessionsQuery) -> bool {
+    let within = |field: &str, min: Option<u64>, max: Option<u64>| {
+        if min.is_none() && max.is_none() {
+            return true;
+        }
+        info[field]
+            .as_u64()
+            .is_some_and(|value| min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max))
+    };
+    params
+        .command
+        .as_deref()
+        .is_none_or(|want| info["command"].as_str().is_some_and(|command| command.contains(want)))
+        && params
+            .owner
+            .as_deref()
+            .is_none_or(|want| info["owner"]["token"] == want || info["owner"]["mcp_session"] == want)
+        && within("last_activity_ms", params.min_idle_ms, params.max_idle_ms)
+        && within("clients", params.min_clients, params.max_clients)
+        && within("created_at", params.created_after, params.created_before)
+}
+
+/// Order two JSON values of the same field: numbers and strings by value,
+/// missing values last.
+fn compare_json(a: &serde_json::Value, b: &serde_json::Value) -> std::cmp::Ordering {
+    use serde_json::Value;
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(std::cmp::Ordering::Equal),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        _ => a.is_null().cmp(&b.is_null()),
+    }
+}
+
+/// The `meta.key=value` filters in a session list query.
+fn metadata_filters(pairs: &[(String, String)]) -> Vec<(&str, &str)> {
+    pairs
+        .iter()
+        .filter_map(|(key, value)| Some((key.strip_prefix("meta.")?, value.as_str())))
+        .collect()
+}
+
+/// Whether `metadata` has every `key=value` in `filters`. Strings compare as
+/// they are and other values as JSON, so `meta.attempt=2` matches the
+/// number 2.
+fn metadata_matches(metadata: &serde_json::Map<String, serde_json::Value>, filters: &[(&str, &str)]) -> bool {
+    filters.iter().all(|(key, want)| match metadata.get(*key) {
+        Some(serde_json::Value::String(value)) => value == want,
+        Some(value) => serde_json::from_str::<serde_json::Value>(want).is_ok_and(|want| *value == want),
+        None => false,
+    })
 }
 
 pub(super) async fn session_create(
     State(state): State<AppState>,
-    Json(req): Json<CreateSessionRequest>,
+    grant: Option<Extension<TokenGrant>>,
+    AxumQuery(query): AxumQuery<CreateSessionQuery>,
+    Json(mut req): Json<CreateSessionRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
+    // Templates are looked up on the server that creates the session, so a
+    // request for a federated backend forwards the name untouched.
+    let template = match req.template {
+        Some(ref name) if req.server.as_ref().is_none_or(|s| s == &state.hostname) => {
+            let template = state
+                .server_config
+                .templates()
+                .get(name)
+                .ok_or_else(|| ApiError::TemplateNotFound(name.clone()))?;
+            template.fill(&mut req.command, &mut req.cwd, &mut req.env, &mut req.rows, &mut req.cols, &mut req.tags);
+            Some(template)
+        }
+        _ => None,
+    };
+
+    // A tag-restricted token may only create sessions it will be able to
+    // access afterwards.
+    if let Some(Extension(ref grant)) = grant {
+        if grant.is_tag_restricted()
+            && req.server.as_ref().is_some_and(|s| s != &state.hostname)
+        {
+            return Err(ApiError::InsufficientScope(
+                "tag-restricted tokens cannot target federated servers".to_string(),
+            ));
+        }
+        if grant.is_tag_restricted() && !req.tags.iter().any(|t| grant.tags.contains(t)) {
+            return Err(ApiError::InsufficientScope(format!(
+                "sessions created by token '{}' must carry one of its tags: {}",
+                grant.name,
+                grant.tags.join(", ")
+            )));
+        }
+    }
+
     // If server field names a remote backend, proxy the creation request.
     // Note: We inline the server-target resolution here instead of using
     // resolve_server_target() because the server field comes from the request
     // body rather than a query parameter.
     if let Some(ref server) = req.server {
         if server != &state.hostname {
+            if !state.backends.proxying() {
+                return Err(federation_disabled());
+            }
             let backend = state
                 .backends
                 .get_by_hostname(server)
@@ -3042,20 +6039,61 @@ pub(super) async fn session_create(
             if let Some(obj) = body.as_object_mut() {
                 obj.remove("server");
             }
-            let (status, resp_body) =
-                super::proxy::proxy_post(&backend, "/sessions", body).await?;
+            let path = if query.dry_run { "/sessions?dry_run=true" } else { "/sessions" };
+            let (status, resp_body) = super::proxy::proxy_post(&backend, path, body).await?;
             return Ok((status, Json(resp_body)).into_response());
         }
     }
 
+    let run_as = resolve_run_as(req.user.as_deref(), req.uid)?;
+    let sandbox = state
+        .sessions
+        .resolve_sandbox(req.sandbox.as_deref())
+        .map_err(ApiError::InvalidRequest)?;
+    let ssh = SshLaunch::from_request(req.command.as_deref(), req.remote.as_deref())
+        .map_err(ApiError::InvalidRequest)?;
+    let safe_mode = req.safe_mode.take();
+    if let Some(ref safe_mode) = safe_mode {
+        safe_mode.validate().map_err(ApiError::InvalidRequest)?;
+        if ssh.is_some() {
+            return Err(ApiError::InvalidRequest("safe_mode cannot be used with remote".to_string()));
+        }
+    }
+    let idle_kill_after = req
+        .idle_kill_after
+        .as_deref()
+        .map(crate::config::parse_duration)
+        .transpose()
+        .map_err(ApiError::InvalidRequest)?;
+    let priority = Priority::resolve(req.nice, req.ionice.as_deref()).map_err(ApiError::InvalidRequest)?;
+    let scrollback_lines = crate::session::validate_scrollback_lines(
+        req.scrollback_lines.unwrap_or_else(|| state.sessions.scrollback_lines()),
+    )
+    .map_err(ApiError::InvalidRequest)?;
+    let restart = RestartPolicy::parse(req.restart, req.restart_max_retries, req.restart_backoff_ms)
+        .map_err(ApiError::InvalidRequest)?;
+    // A plain default shell can come from the warm pool, whose shells were
+    // spawned with every default this request leaves unset.
+    let poolable = ssh.is_none()
+        && req.command.is_none()
+        && req.cwd.is_none()
+        && req.env.is_none()
+        && run_as.is_none()
+        && req.sandbox.is_none()
+        && safe_mode.is_none()
+        && priority.is_none()
+        && req.scrollback_lines.is_none()
+        && restart.is_none();
     let req_name = req.name;
     let req_tags = req.tags;
-    let command = match req.command {
-        Some(cmd) => SpawnCommand::Command {
+    let req_resize_policy = req.resize_policy;
+    let command = match (&ssh, req.command) {
+        (Some(launch), _) => launch.spawn_command(),
+        (None, Some(cmd)) => SpawnCommand::Command {
             command: cmd,
             interactive: true,
         },
-        None => SpawnCommand::Shell {
+        (None, None) => SpawnCommand::Shell {
             interactive: true,
             shell: None,
         },
@@ -3072,22 +6110,68 @@ pub(super) async fn session_create(
         RegistryError::MaxSessionsReached => ApiError::MaxSessionsReached,
         RegistryError::InvalidTag(msg) => ApiError::InvalidTag(msg),
         RegistryError::InvalidName(msg) => ApiError::InvalidSessionName(msg),
+        RegistryError::Maintenance(reason) => ApiError::ServerMaintenance(reason),
     })?;
 
+    if query.dry_run {
+        // Preview the ssh invocation itself rather than its `exec` wrapper.
+        let command = match &ssh {
+            Some(launch) => SpawnCommand::Command {
+                command: launch.command_line(),
+                interactive: false,
+            },
+            None => command,
+        };
+        return preview_session_create(&state, req_name, command, rows, cols, req.cwd, req.env, req_tags, req_resize_policy, run_as, sandbox)
+            .map(|preview| Json(CreateSessionPreview { safe_mode, ..preview }).into_response());
+    }
+
     // Use a placeholder name for spawn; registry.insert will assign the real name.
     //
     // spawn_with_options calls fork()/exec() which is a blocking syscall.
     // Under load, fork() on a large-RSS process can take hundreds of ms,
     // so we run it on the blocking thread pool to avoid stalling the
     // async executor.
-    let cwd = req.cwd;
-    let env = req.env;
-    let (session, child_exit_rx) = tokio::task::spawn_blocking(move || {
-        Session::spawn_with_options("".to_string(), command, rows, cols, cwd, env)
-    })
-    .await
-    .map_err(|e| ApiError::SessionCreateFailed(e.to_string()))?
-    .map_err(|e| ApiError::SessionCreateFailed(e.to_string()))?;
+    if let Some(ref launch) = ssh {
+        launch
+            .create_log(run_as.as_ref())
+            .map_err(|e| ApiError::SessionCreateFailed(format!("ssh log: {}", e)))?;
+    }
+    let claimed = if poolable { state.sessions.claim_warm() } else { None };
+    let (session, mut child_exit_rx) = match claimed {
+        Some((session, child_exit_rx)) => {
+            if session.terminal_size.get() != (rows, cols) {
+                session.apply_size(rows, cols).await;
+            }
+            (session, child_exit_rx)
+        }
+        None => {
+            let options = SpawnOptions {
+                cwd: req.cwd,
+                env: req.env,
+                motd: state.sessions.motd(),
+                run_as,
+                sandbox,
+                safe_mode,
+                priority,
+                scrollback_lines,
+                restart,
+            };
+            tokio::task::spawn_blocking(move || {
+                Session::spawn_with_options("".to_string(), command, rows, cols, options)
+            })
+            .await
+            .map_err(|e| ApiError::SessionCreateFailed(e.to_string()))?
+            .map_err(|e| ApiError::SessionCreateFailed(e.to_string()))?
+        }
+    };
+
+    if let Some(ref launch) = ssh {
+        if let Err(e) = launch.wait_connected(&session.usage, &mut child_exit_rx).await {
+            session.shutdown();
+            return Err(ApiError::SshConnectFailed(e));
+        }
+    }
 
     // Validate and set initial tags before inserting into registry,
     // so that insert_and_get() properly indexes them.
@@ -3095,47 +6179,360 @@ pub(super) async fn session_create(
         for tag in &req_tags {
             crate::session::validate_tag(tag).map_err(ApiError::InvalidTag)?;
         }
-        *session.tags.write() = req_tags.into_iter().collect();
+        *session.tags.write() = req_tags.into_iter().collect();
+    }
+    if let Some(policy) = req_resize_policy {
+        let _ = session.size_arbiter.set_policy(policy);
+    }
+    *session.idle_kill_after.lock() = idle_kill_after;
+    *session.query_policy.write() = req.query_policy.unwrap_or_default();
+    session.parser.set_encoding(req.encoding.unwrap_or_default());
+    if let Some(Extension(ref grant)) = grant {
+        *session.owner.lock() = Some(crate::session::Owner {
+            token: Some(grant.name.clone()),
+            ..Default::default()
+        });
+    }
+
+    let (assigned_name, session) = match state.sessions.insert_and_get(req_name, session.clone()) {
+        Ok(result) => result,
+        Err(e) => {
+            session.shutdown();
+            return Err(match e {
+                RegistryError::NameExists(n) => ApiError::SessionNameConflict(n),
+                RegistryError::NotFound(n) => ApiError::SessionNotFound(n),
+                RegistryError::MaxSessionsReached => ApiError::MaxSessionsReached,
+                RegistryError::InvalidTag(msg) => ApiError::InvalidTag(msg),
+                RegistryError::InvalidName(msg) => ApiError::InvalidSessionName(msg),
+                RegistryError::Maintenance(reason) => ApiError::ServerMaintenance(reason),
+            });
+        }
+    };
+
+    // Monitor child exit so the session is auto-removed when the process dies.
+    state.sessions.monitor_child_exit(assigned_name.clone(), session.client_count.clone(), session.child_exited.clone(), child_exit_rx);
+
+    if let Some(template) = template {
+        if let Err(e) = template.decorate(&session).await {
+            tracing::warn!(session = %assigned_name, error = %e, "failed to add template overlays and panels");
+        }
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        Json(build_session_info(&session, &state.hostname)),
+    )
+        .into_response())
+}
+
+/// Resolve the `user`/`uid` fields of a create request.
+fn resolve_run_as(user: Option<&str>, uid: Option<u32>) -> Result<Option<RunAs>, ApiError> {
+    RunAs::resolve(user, uid).map_err(|e| match e {
+        RunAsError::Lookup(msg) => ApiError::InvalidRequest(msg),
+        RunAsError::NotPermitted(msg) => ApiError::UserSwitchNotPermitted(msg),
+    })
+}
+
+/// Check everything a create would need short of spawning: tags, working
+/// directory, and that the program exists. Name and quota have already been
+/// checked by the caller. Host paths aren't checked for a sandbox with its
+/// own root filesystem.
+#[allow(clippy::too_many_arguments)]
+fn preview_session_create(
+    state: &AppState,
+    name: Option<String>,
+    command: SpawnCommand,
+    rows: u16,
+    cols: u16,
+    cwd: Option<String>,
+    env: Option<std::collections::HashMap<String, String>>,
+    tags: Vec<String>,
+    resize_policy: Option<ResizePolicy>,
+    run_as: Option<RunAs>,
+    sandbox: Option<Sandbox>,
+) -> Result<CreateSessionPreview, ApiError> {
+    for tag in &tags {
+        crate::session::validate_tag(tag).map_err(ApiError::InvalidTag)?;
+    }
+    let tags: Vec<String> = tags.into_iter().collect::<std::collections::BTreeSet<_>>().into_iter().collect();
+    let host_paths = !sandbox.as_ref().is_some_and(Sandbox::replaces_root);
+
+    let cwd = match cwd {
+        Some(dir) if !host_paths => Some(std::path::PathBuf::from(dir)),
+        Some(dir) => {
+            let path = std::fs::canonicalize(&dir)
+                .map_err(|e| ApiError::InvalidRequest(format!("cwd {}: {}", dir, e)))?;
+            if !path.is_dir() {
+                return Err(ApiError::InvalidRequest(format!("cwd {} is not a directory", dir)));
+            }
+            Some(path)
+        }
+        None => None,
+    };
+    let (command, cwd) = match run_as {
+        Some(ref account) => (
+            account.default_shell(command),
+            cwd.or_else(|| {
+                let home = std::path::PathBuf::from(&account.home);
+                Some(if home.is_dir() { home } else { std::path::PathBuf::from("/") })
+            }),
+        ),
+        None => (command, cwd),
+    };
+
+    let path_var = env.as_ref().and_then(|e| e.get("PATH")).map(String::as_str);
+    let program = if host_paths {
+        crate::pty::resolve_program(&command, path_var, cwd.as_deref())
+            .map_err(ApiError::InvalidRequest)?
+    } else {
+        None
+    };
+
+    let mut env: Vec<String> = env.map(|e| e.into_keys().collect()).unwrap_or_default();
+    env.sort();
+
+    Ok(CreateSessionPreview {
+        dry_run: true,
+        name,
+        server: state.hostname.clone(),
+        command: match command {
+            SpawnCommand::Command { command, .. } => Some(command),
+            SpawnCommand::Shell { .. } => None,
+        },
+        program: program.map(|p| p.display().to_string()),
+        cwd: cwd.map(|p| p.display().to_string()),
+        user: run_as.map(|account| account.user),
+        sandbox: sandbox.map(|s| s.name),
+        safe_mode: None,
+        rows,
+        cols,
+        tags,
+        resize_policy: resize_policy.unwrap_or_default(),
+        env,
+    })
+}
+
+#[derive(Deserialize, Default)]
+pub(super) struct TmuxImportRequest {
+    /// tmux session names to import; all of them when empty.
+    #[serde(default)]
+    pub sessions: Vec<String>,
+    /// tmux server socket name, as for `tmux -L`.
+    pub socket: Option<String>,
+    #[serde(default)]
+    pub mode: TmuxImportMode,
+    /// Extra tags for the imported sessions, besides `tmux`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub rows: Option<u16>,
+    pub cols: Option<u16>,
+}
+
+/// How imported tmux sessions are reached.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum TmuxImportMode {
+    /// One wsh session per tmux session, running `tmux attach-session`.
+    #[default]
+    Attach,
+    /// One wsh session per pane, mirrored through tmux control mode.
+    Control,
+}
+
+#[derive(Serialize)]
+pub(super) struct TmuxImported {
+    pub tmux: String,
+    /// tmux pane id, for control-mode imports.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pane: Option<String>,
+    pub session: String,
+}
+
+#[derive(Serialize)]
+pub(super) struct TmuxSkipped {
+    pub tmux: String,
+    pub reason: String,
+}
+
+/// One wsh session to create for an import.
+struct TmuxImportTarget {
+    /// What is imported: a tmux session name, or `session:window.pane`.
+    tmux: String,
+    pane: Option<String>,
+    name: String,
+    command: String,
+    rows: u16,
+    cols: u16,
+}
+
+/// Wrap tmux sessions in wsh sessions, each running `tmux attach-session`,
+/// or in control mode mirror each of their panes through the tmux bridge.
+/// Sessions that were already imported, or that can't be, are reported as
+/// skipped rather than failing the whole request.
+pub(super) async fn tmux_import(
+    State(state): State<AppState>,
+    Json(req): Json<TmuxImportRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mut tags = vec![crate::tmux::IMPORT_TAG.to_string()];
+    for tag in req.tags {
+        crate::session::validate_tag(&tag).map_err(ApiError::InvalidTag)?;
+        tags.push(tag);
+    }
+    let rows = req.rows.unwrap_or(24).clamp(1, 1000);
+    let cols = req.cols.unwrap_or(80).clamp(1, 1000);
+
+    let socket = req.socket.clone();
+    let available = tokio::task::spawn_blocking(move || crate::tmux::list_sessions(socket.as_deref()))
+        .await
+        .map_err(|e| ApiError::InternalError(e.to_string()))?
+        .map_err(ApiError::SessionCreateFailed)?;
+    let exe = match req.mode {
+        TmuxImportMode::Attach => None,
+        TmuxImportMode::Control => Some(std::env::current_exe().map_err(|e| ApiError::InternalError(e.to_string()))?),
+    };
+
+    let mut imported = Vec::new();
+    let mut skipped = Vec::new();
+    let wanted: Vec<String> = if req.sessions.is_empty() {
+        available.iter().map(|t| t.name.clone()).collect()
+    } else {
+        req.sessions
+    };
+    // Skips are kept in line with the targets so they're reported in the
+    // order the sessions were asked for.
+    let mut targets = Vec::new();
+    for tmux_name in wanted {
+        if !available.iter().any(|t| t.name == tmux_name) {
+            targets.push(Err(TmuxSkipped { tmux: tmux_name, reason: "no such tmux session".to_string() }));
+            continue;
+        }
+        let Some(exe) = &exe else {
+            targets.push(Ok(TmuxImportTarget {
+                name: crate::tmux::session_name(&tmux_name),
+                command: crate::tmux::attach_command(&tmux_name, req.socket.as_deref()),
+                tmux: tmux_name,
+                pane: None,
+                rows,
+                cols,
+            }));
+            continue;
+        };
+        let (name, socket) = (tmux_name.clone(), req.socket.clone());
+        let panes = tokio::task::spawn_blocking(move || crate::bridge::tmux::list_panes(&name, socket.as_deref()))
+            .await
+            .map_err(|e| ApiError::InternalError(e.to_string()))?;
+        let panes = match panes {
+            Ok(panes) => panes,
+            Err(e) => {
+                targets.push(Err(TmuxSkipped { tmux: tmux_name, reason: e }));
+                continue;
+            }
+        };
+        for pane in panes {
+            targets.push(Ok(TmuxImportTarget {
+                tmux: format!("{}:{}.{}", tmux_name, pane.window_index, pane.pane_index),
+                name: crate::bridge::tmux::session_name(&tmux_name, &pane),
+                command: crate::bridge::tmux::relay_command(exe, &pane.id, req.socket.as_deref()),
+                pane: Some(pane.id),
+                rows: pane.rows.clamp(1, 1000),
+                cols: pane.cols.clamp(1, 1000),
+            }));
+        }
+    }
+
+    for target in targets {
+        let target = match target {
+            Ok(target) => target,
+            Err(skip) => {
+                skipped.push(skip);
+                continue;
+            }
+        };
+        let skip = |reason: &str| TmuxSkipped { tmux: target.tmux.clone(), reason: reason.to_string() };
+        if state.sessions.get(&target.name).is_some() {
+            skipped.push(skip("already imported"));
+            continue;
+        }
+
+        let command = SpawnCommand::Command { command: target.command.clone(), interactive: false };
+        let (rows, cols) = (target.rows, target.cols);
+        let options = SpawnOptions {
+            motd: state.sessions.motd(),
+            scrollback_lines: state.sessions.scrollback_lines(),
+            ..Default::default()
+        };
+        let spawned = tokio::task::spawn_blocking(move || {
+            Session::spawn_with_options(String::new(), command, rows, cols, options)
+        })
+        .await
+        .map_err(|e| ApiError::InternalError(e.to_string()))?;
+        let (session, child_exit_rx) = match spawned {
+            Ok(spawned) => spawned,
+            Err(e) => {
+                skipped.push(skip(&e.to_string()));
+                continue;
+            }
+        };
+        *session.tags.write() = tags.iter().cloned().collect();
+
+        match state.sessions.insert_and_get(Some(target.name.clone()), session.clone()) {
+            Ok((assigned, session)) => {
+                state.sessions.monitor_child_exit(
+                    assigned.clone(),
+                    session.client_count.clone(),
+                    session.child_exited.clone(),
+                    child_exit_rx,
+                );
+                imported.push(TmuxImported { tmux: target.tmux, pane: target.pane, session: assigned });
+            }
+            Err(e) => {
+                session.shutdown();
+                skipped.push(skip(&match e {
+                    RegistryError::NameExists(_) => "already imported".to_string(),
+                    RegistryError::MaxSessionsReached => "maximum number of sessions reached".to_string(),
+                    other => other.to_string(),
+                }));
+            }
+        }
     }
 
-    let (assigned_name, session) = match state.sessions.insert_and_get(req_name, session.clone()) {
-        Ok(result) => result,
-        Err(e) => {
-            session.shutdown();
-            return Err(match e {
-                RegistryError::NameExists(n) => ApiError::SessionNameConflict(n),
-                RegistryError::NotFound(n) => ApiError::SessionNotFound(n),
-                RegistryError::MaxSessionsReached => ApiError::MaxSessionsReached,
-                RegistryError::InvalidTag(msg) => ApiError::InvalidTag(msg),
-                RegistryError::InvalidName(msg) => ApiError::InvalidSessionName(msg),
-            });
-        }
-    };
-
-    // Monitor child exit so the session is auto-removed when the process dies.
-    state.sessions.monitor_child_exit(assigned_name.clone(), session.client_count.clone(), session.child_exited.clone(), child_exit_rx);
+    Ok(Json(serde_json::json!({ "imported": imported, "skipped": skipped })))
+}
 
-    Ok((
-        StatusCode::CREATED,
-        Json(build_session_info(&session, &state.hostname)),
-    )
-        .into_response())
+/// Query parameters of `GET /sessions/{name}`.
+#[derive(Deserialize, Default)]
+pub(super) struct SessionGetQuery {
+    pub server: Option<String>,
+    /// Answer with the session's tombstone if its child has exited.
+    #[serde(default)]
+    pub include_dead: bool,
 }
 
 pub(super) async fn session_get(
     State(state): State<AppState>,
     Path(name): Path<String>,
-    AxumQuery(query): AxumQuery<ServerQuery>,
+    AxumQuery(query): AxumQuery<SessionGetQuery>,
 ) -> Result<impl IntoResponse, ApiError> {
     if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
-        let (status, body) = super::proxy::proxy_get(
-            &backend,
-            &format!("/sessions/{}", name),
-        )
-        .await?;
+        let path = if query.include_dead {
+            format!("/sessions/{}?include_dead=true", name)
+        } else {
+            format!("/sessions/{}", name)
+        };
+        let (status, body) = super::proxy::proxy_get(&backend, &path).await?;
         return Ok((status, Json(body)).into_response());
     }
-    let session = get_session(&state.sessions, &name)?;
+    let session = match get_session(&state.sessions, &name) {
+        Ok(session) => session,
+        Err(err) if query.include_dead => {
+            let tombstone = state.sessions.tombstone(&name).ok_or(err)?;
+            let mut body = serde_json::to_value(&tombstone)
+                .map_err(|e| ApiError::InternalError(e.to_string()))?;
+            body["alive"] = serde_json::json!(false);
+            return Ok(Json(body).into_response());
+        }
+        Err(err) => return Err(err),
+    };
     Ok(Json(build_session_info(&session, &state.hostname)).into_response())
 }
 
@@ -3156,6 +6553,12 @@ pub(super) async fn session_update(
         .await?;
         return Ok((status, Json(resp_body)).into_response());
     }
+    let scrollback_lines = req
+        .scrollback_lines
+        .map(crate::session::validate_scrollback_lines)
+        .transpose()
+        .map_err(ApiError::InvalidRequest)?;
+
     // Handle rename if requested
     let current_name = if let Some(new_name) = req.name {
         state.sessions.rename(&name, &new_name).map_err(|e| match e {
@@ -3164,6 +6567,7 @@ pub(super) async fn session_update(
             RegistryError::MaxSessionsReached => ApiError::MaxSessionsReached,
             RegistryError::InvalidTag(e) => ApiError::InvalidTag(e),
             RegistryError::InvalidName(msg) => ApiError::InvalidSessionName(msg),
+            RegistryError::Maintenance(reason) => ApiError::ServerMaintenance(reason),
         })?;
         new_name
     } else {
@@ -3188,27 +6592,99 @@ pub(super) async fn session_update(
     }
 
     let session = get_session(&state.sessions, &current_name)?;
+
+    if let Some(metadata) = req.metadata {
+        session.merge_metadata(metadata).map_err(ApiError::InvalidRequest)?;
+    }
+
+    // Handle resize policy change; switching to largest_client may resize.
+    if let Some(policy) = req.resize_policy {
+        if let Some((rows, cols)) = session.size_arbiter.set_policy(policy) {
+            session.apply_size(rows, cols).await;
+        }
+    }
+
+    if let Some(lines) = scrollback_lines {
+        session
+            .set_scrollback_lines(lines)
+            .await
+            .map_err(|_| ApiError::ParserUnavailable)?;
+    }
+
+    if let Some(policy) = req.query_policy {
+        *session.query_policy.write() = policy;
+    }
+
+    if let Some(encoding) = req.encoding {
+        session.parser.set_encoding(encoding);
+    }
+
     Ok(Json(build_session_info(&session, &state.hostname)).into_response())
 }
 
+/// Query parameters of `DELETE /sessions/{name}`.
+#[derive(Deserialize, Default)]
+pub(super) struct KillQuery {
+    pub server: Option<String>,
+    /// Signal to send first (`TERM`, `SIGINT`, `15`, ...).
+    pub signal: Option<String>,
+    /// How long to wait for the child to exit before SIGKILL.
+    pub grace_ms: Option<u64>,
+}
+
 pub(super) async fn session_kill(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    AxumQuery(query): AxumQuery<KillQuery>,
+) -> Result<StatusCode, ApiError> {
+    let mode = crate::session::KillMode::parse(query.signal.as_deref(), query.grace_ms)
+        .map_err(ApiError::InvalidRequest)?;
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
+        let mut path = format!("/sessions/{}", name);
+        if mode != crate::session::KillMode::Immediate {
+            path = format!("{}?{}", path, mode.query());
+        }
+        let status = super::proxy::proxy_delete(&backend, &path).await?;
+        return Ok(status);
+    }
+    let session = state
+        .sessions
+        .remove(&name)
+        .ok_or(ApiError::SessionNotFound(name))?;
+    session.kill(mode);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub(super) struct SignalRequest {
+    signal: String,
+}
+
+/// POST /sessions/{name}/signal -- send a signal to the session's process
+/// group without ending the session.
+pub(super) async fn session_signal(
     State(state): State<AppState>,
     Path(name): Path<String>,
     AxumQuery(query): AxumQuery<ServerQuery>,
+    Json(req): Json<SignalRequest>,
 ) -> Result<StatusCode, ApiError> {
+    let signal = crate::session::parse_signal(&req.signal).map_err(ApiError::InvalidRequest)?;
     if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
-        let status = super::proxy::proxy_delete(
+        let (status, _body) = super::proxy::proxy_post(
             &backend,
-            &format!("/sessions/{}", name),
+            &format!("/sessions/{}/signal", name),
+            serde_json::json!({"signal": req.signal.trim()}),
         )
         .await?;
         return Ok(status);
     }
     let session = state
         .sessions
-        .remove(&name)
-        .ok_or(ApiError::SessionNotFound(name))?;
-    session.force_kill();
+        .get(&name)
+        .ok_or_else(|| ApiError::SessionNotFound(name.clone()))?;
+    if !session.send_signal(signal) {
+        return Err(ApiError::ChildExited(name));
+    }
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -3234,6 +6710,205 @@ pub(super) async fn session_detach(
     Ok(StatusCode::NO_CONTENT)
 }
 
+pub(super) async fn session_resume(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    AxumQuery(query): AxumQuery<ServerQuery>,
+) -> Result<StatusCode, ApiError> {
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
+        let (status, _body) = super::proxy::proxy_post(
+            &backend,
+            &format!("/sessions/{}/resume", name),
+            serde_json::Value::Object(Default::default()),
+        )
+        .await?;
+        return Ok(status);
+    }
+    let session = state
+        .sessions
+        .get(&name)
+        .ok_or(ApiError::SessionNotFound(name))?;
+    session.resume();
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub(super) async fn session_hibernate(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    AxumQuery(query): AxumQuery<ServerQuery>,
+) -> Result<StatusCode, ApiError> {
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
+        let (status, _body) = super::proxy::proxy_post(
+            &backend,
+            &format!("/sessions/{}/hibernate", name),
+            serde_json::Value::Object(Default::default()),
+        )
+        .await?;
+        return Ok(status);
+    }
+    let session = state
+        .sessions
+        .get(&name)
+        .ok_or(ApiError::SessionNotFound(name))?;
+    session
+        .hibernate()
+        .await
+        .map_err(|e| ApiError::InternalError(format!("hibernation failed: {}", e)))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// ── File transfer handlers ───────────────────────────────────
+
+#[derive(Deserialize)]
+pub(super) struct FileQuery {
+    path: String,
+    /// Permission mode for a written file, in octal.
+    #[serde(default)]
+    mode: Option<String>,
+    #[serde(default)]
+    server: Option<String>,
+}
+
+fn file_error(e: crate::files::FileError) -> ApiError {
+    use crate::files::FileError;
+    match e {
+        FileError::Disabled => ApiError::FileNotAllowed("file transfer is not enabled on this server".into()),
+        FileError::NotAllowed(_) | FileError::OtherUser(_) => ApiError::FileNotAllowed(e.to_string()),
+        FileError::NotFound(_) => ApiError::FileNotFound(e.to_string()),
+        FileError::TooLarge { .. } => ApiError::FileTooLarge(e.to_string()),
+        FileError::Invalid(detail) => ApiError::InvalidRequest(detail),
+        FileError::Io(detail) => ApiError::InternalError(detail),
+    }
+}
+
+/// Forward a file transfer request to the backend that has the session.
+async fn proxy_file(
+    backend: &crate::federation::registry::BackendEntry,
+    method: axum::http::Method,
+    name: &str,
+    query: Option<&str>,
+    body: Bytes,
+) -> Result<axum::response::Response, ApiError> {
+    let path = match without_server_param(query) {
+        Some(query) => format!("/sessions/{}/file?{}", name, query),
+        None => format!("/sessions/{}/file", name),
+    };
+    let (status, content_type, body) = super::proxy::proxy_request(backend, method, &path, None, body).await?;
+    let mut response = (status, body).into_response();
+    if let Some(content_type) = content_type {
+        response.headers_mut().insert(axum::http::header::CONTENT_TYPE, content_type);
+    }
+    Ok(response)
+}
+
+/// Download a file from the machine the session runs on. Relative paths
+/// are taken from the session shell's working directory.
+pub(super) async fn file_read(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    axum::extract::RawQuery(raw_query): axum::extract::RawQuery,
+    AxumQuery(query): AxumQuery<FileQuery>,
+) -> Result<axum::response::Response, ApiError> {
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
+        return proxy_file(&backend, axum::http::Method::GET, &name, raw_query.as_deref(), Bytes::new()).await;
+    }
+    let session = get_session(&state.sessions, &name)?;
+    let path = state
+        .server_config
+        .files_config()
+        .resolve_for(&session, &query.path, false)
+        .map_err(file_error)?;
+    let data = crate::files::read(path).await.map_err(file_error)?;
+    Ok((
+        [(axum::http::header::CONTENT_TYPE, "application/octet-stream")],
+        data,
+    )
+        .into_response())
+}
+
+/// Upload a file to the machine the session runs on, replacing any file
+/// already at the path. The contents must pass the input policy, as they
+/// would if typed into the session.
+pub(super) async fn file_write(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    axum::extract::RawQuery(raw_query): axum::extract::RawQuery,
+    AxumQuery(query): AxumQuery<FileQuery>,
+    body: Bytes,
+) -> Result<axum::response::Response, ApiError> {
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
+        return proxy_file(&backend, axum::http::Method::PUT, &name, raw_query.as_deref(), body).await;
+    }
+    let session = get_session(&state.sessions, &name)?;
+    let mode = query.mode.as_deref().map(crate::files::parse_mode).transpose().map_err(file_error)?;
+    let path = state
+        .server_config
+        .files_config()
+        .resolve_for(&session, &query.path, true)
+        .map_err(file_error)?;
+    session.policy.check_input(&body).map_err(ApiError::InputBlocked)?;
+    let bytes = body.len();
+    crate::files::write(path.clone(), body, mode).await.map_err(file_error)?;
+    tracing::info!(session = %name, path = %path.display(), bytes, "file written");
+    Ok(Json(serde_json::json!({ "path": path, "bytes": bytes })).into_response())
+}
+
+// ── Resize handlers ──────────────────────────────────────────
+
+#[derive(Deserialize, Serialize)]
+pub(super) struct ResizeRequest {
+    pub rows: u16,
+    pub cols: u16,
+    /// Replace the session's resize policy before applying the size.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<ResizePolicy>,
+}
+
+#[derive(Serialize)]
+pub(super) struct ResizeResponse {
+    pub rows: u16,
+    pub cols: u16,
+    pub policy: ResizePolicy,
+}
+
+pub(super) async fn session_resize(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    AxumQuery(query): AxumQuery<ServerQuery>,
+    Json(req): Json<ResizeRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let SessionTarget::Remote(backend) = resolve_server_target(&state, query.server.as_deref())? {
+        let body = serde_json::to_value(&req)
+            .map_err(|e| ApiError::InternalError(e.to_string()))?;
+        let (status, resp_body) = super::proxy::proxy_post(
+            &backend,
+            &format!("/sessions/{}/resize", name),
+            body,
+        )
+        .await?;
+        return Ok((status, Json(resp_body)).into_response());
+    }
+    if req.rows == 0 || req.cols == 0 || req.rows > 1000 || req.cols > 1000 {
+        return Err(ApiError::InvalidRequest(
+            "rows and cols must be between 1 and 1000".to_string(),
+        ));
+    }
+    let session = get_session(&state.sessions, &name)?;
+    if let Some(policy) = req.policy {
+        // The explicit size below supersedes whatever the new policy
+        // would compute from attached clients.
+        let _ = session.size_arbiter.set_policy(policy);
+    }
+    session.apply_size(req.rows, req.cols).await;
+    let (rows, cols) = session.terminal_size.get();
+    Ok(Json(ResizeResponse {
+        rows,
+        cols,
+        policy: session.size_arbiter.policy(),
+    })
+    .into_response())
+}
+
 // ── Screen mode handlers ──────────────────────────────────────
 
 #[derive(Serialize)]
@@ -3291,13 +6966,37 @@ pub(super) async fn exit_alt_screen(
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// GET /server/info -- identity, lifecycle, load, and federation summary.
 pub(super) async fn server_info(
     State(state): State<AppState>,
 ) -> Json<serde_json::Value> {
+    use crate::federation::registry::BackendHealth;
+    let backends = state.backends.list();
+    let count = |health: BackendHealth| backends.iter().filter(|b| b.health == health).count();
+    let sandbox = state.sessions.sandbox();
     Json(serde_json::json!({
         "hostname": state.hostname,
         "version": env!("CARGO_PKG_VERSION"),
         "server_id": state.server_id,
+        "uptime_secs": state.server_config.uptime().as_secs(),
+        "persistent": state.server_config.is_persistent(),
+        "maintenance": state.sessions.maintenance().is_some(),
+        "sessions": state.sessions.len(),
+        "session_clients": state.sessions.connected_clients(),
+        "ws_clients": state.server_ws_count.load(std::sync::atomic::Ordering::Relaxed),
+        "mcp_sessions": state.mcp_session_count.load(std::sync::atomic::Ordering::Relaxed),
+        "sandbox": {
+            "profiles": sandbox.profile_names(),
+            "default": sandbox.default,
+        },
+        "federation": {
+            "backends": backends.len(),
+            "healthy": count(BackendHealth::Healthy),
+            "connecting": count(BackendHealth::Connecting),
+            "unavailable": count(BackendHealth::Unavailable),
+            "rejected": count(BackendHealth::Rejected),
+        },
+        "resources": super::resources::ResourceUsage::sample(),
     }))
 }
 
@@ -3311,13 +7010,141 @@ pub(super) async fn server_persist_get(
 pub(super) async fn server_persist_set(
     State(state): State<AppState>,
     Json(body): Json<serde_json::Value>,
-) -> impl IntoResponse {
-    if let Some(persistent) = body.get("persistent").and_then(|v| v.as_bool()) {
-        state.server_config.set_persistent(persistent);
-        (StatusCode::OK, Json(serde_json::json!({"persistent": persistent})))
+) -> Result<impl IntoResponse, ApiError> {
+    let persistent = body
+        .get("persistent")
+        .and_then(|v| v.as_bool())
+        .ok_or_else(|| ApiError::InvalidRequest("missing or invalid 'persistent' boolean field".into()))?;
+    state.server_config.set_persistent(persistent);
+    Ok((StatusCode::OK, Json(serde_json::json!({"persistent": persistent}))))
+}
+
+#[derive(Deserialize)]
+pub(super) struct MaintenanceRequest {
+    enabled: bool,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+fn maintenance_json(reason: Option<String>) -> serde_json::Value {
+    serde_json::json!({"maintenance": reason.is_some(), "reason": reason})
+}
+
+/// GET /server/maintenance -- whether the server is refusing new sessions.
+pub(super) async fn server_maintenance_get(
+    State(state): State<AppState>,
+) -> Json<serde_json::Value> {
+    Json(maintenance_json(state.sessions.maintenance()))
+}
+
+/// POST /server/maintenance -- enter or leave maintenance mode.
+///
+/// While enabled, session creation is rejected with 503 but existing
+/// sessions keep running. Attached clients are notified via the registry
+/// event stream.
+pub(super) async fn server_maintenance_set(
+    State(state): State<AppState>,
+    Json(req): Json<MaintenanceRequest>,
+) -> Json<serde_json::Value> {
+    let reason = if req.enabled {
+        Some(
+            req.reason
+                .filter(|r| !r.trim().is_empty())
+                .unwrap_or_else(|| "server maintenance".to_string()),
+        )
     } else {
-        (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": "missing or invalid 'persistent' boolean field"})))
+        None
+    };
+    state.sessions.set_maintenance(reason);
+    Json(maintenance_json(state.sessions.maintenance()))
+}
+
+/// Runtime switches changed by `PATCH /server/features`; omitted fields are
+/// left alone. Unknown fields are rejected so that a typo can't look like
+/// it switched something off.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct FeaturesRequest {
+    mcp: Option<bool>,
+    web_ui: Option<bool>,
+    federation: Option<bool>,
+}
+
+/// Whether each runtime-switchable subsystem is on. Subsystems not built
+/// into this binary are left out.
+fn features_json(state: &AppState) -> serde_json::Value {
+    let mut features = serde_json::Map::new();
+    if cfg!(feature = "mcp") {
+        features.insert("mcp".into(), state.server_config.mcp_enabled().into());
+    }
+    if cfg!(feature = "web-ui") {
+        features.insert("web_ui".into(), state.server_config.web_ui_enabled().into());
+    }
+    features.insert("federation".into(), state.backends.proxying().into());
+    serde_json::Value::Object(features)
+}
+
+#[derive(Deserialize)]
+pub(super) struct ServerLogsQuery {
+    /// Most recent lines to return (default: all that are kept).
+    limit: Option<usize>,
+}
+
+/// GET /server/logs -- the server's most recent log lines, oldest first.
+///
+/// Lines are kept in memory, so this works for servers whose stderr is
+/// discarded. See [`crate::logs`].
+pub(super) async fn server_logs(AxumQuery(query): AxumQuery<ServerLogsQuery>) -> Json<serde_json::Value> {
+    let limit = query.limit.unwrap_or(crate::logs::RECENT_LOG_LINES);
+    Json(serde_json::json!({ "lines": crate::logs::recent().tail(limit) }))
+}
+
+/// GET /server/features -- which subsystems are switched on.
+pub(super) async fn server_features_get(
+    State(state): State<AppState>,
+) -> Json<serde_json::Value> {
+    Json(features_json(&state))
+}
+
+/// PATCH /server/features -- switch MCP, the web UI, or federation
+/// proxying on or off without a restart.
+///
+/// Takes effect for the next request: switching MCP off refuses further
+/// `/mcp` requests, including those of existing MCP sessions. Each change
+/// is logged and sent to `/ws/json` subscribers as a
+/// `server_feature_changed` event naming the token that made it.
+pub(super) async fn server_features_set(
+    State(state): State<AppState>,
+    grant: Option<Extension<TokenGrant>>,
+    Json(req): Json<FeaturesRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let unavailable = [
+        ("mcp", req.mcp.is_some() && !cfg!(feature = "mcp")),
+        ("web_ui", req.web_ui.is_some() && !cfg!(feature = "web-ui")),
+    ];
+    if let Some((feature, _)) = unavailable.iter().find(|(_, missing)| *missing) {
+        return Err(ApiError::InvalidRequest(format!("{} is not built into this server", feature)));
+    }
+
+    let by = grant.map(|Extension(grant)| grant.name);
+    let announce = |feature: &str, enabled: bool| {
+        tracing::warn!(feature, enabled, by = by.as_deref().unwrap_or("-"), "server feature switched");
+        state.sessions.announce_feature_change(feature, enabled, by.clone());
+    };
+    let config = &state.server_config;
+    if let Some(on) = req.mcp.filter(|&on| on != config.mcp_enabled()) {
+        config.set_mcp_enabled(on);
+        announce("mcp", on);
+    }
+    if let Some(on) = req.web_ui.filter(|&on| on != config.web_ui_enabled()) {
+        config.set_web_ui_enabled(on);
+        announce("web_ui", on);
     }
+    if let Some(on) = req.federation.filter(|&on| on != state.backends.proxying()) {
+        state.backends.set_proxying(on);
+        announce("federation", on);
+    }
+    Ok(Json(features_json(&state)))
 }
 
 // ── Federation: /servers endpoints ─────────────────────────────────
@@ -3348,7 +7175,59 @@ pub(super) async fn list_servers(
     Json(serde_json::json!(servers))
 }
 
+/// GET /federation/backends -- connection health and retry state for
+/// each backend, beyond the single `health` flag of `GET /servers`.
+pub(super) async fn federation_backends(
+    State(state): State<AppState>,
+) -> Json<serde_json::Value> {
+    let ms = |d: std::time::Duration| d.as_millis() as u64;
+    let now = std::time::Instant::now();
+    let health_check = state.backends.health_check();
+    let backends: Vec<_> = state
+        .backends
+        .list()
+        .into_iter()
+        .map(|backend| {
+            let stats = state.backends.stats(&backend.address).unwrap_or_default();
+            serde_json::json!({
+                "hostname": backend.hostname,
+                "address": backend.address,
+                "health": backend.health,
+                "server_id": backend.server_id,
+                "latency_ms": stats.latency.map(ms),
+                "consecutive_failures": stats.consecutive_failures,
+                "disconnects": stats.disconnects,
+                "last_error": stats.last_error,
+                "last_error_age_ms": stats.last_error_at.map(|at| ms(now - at)),
+                "connected_for_ms": stats.connected_at.map(|at| ms(now - at)),
+                "backoff_ms": stats.backoff.map(ms),
+                "retry_in_ms": stats.next_retry_at.map(|at| ms(at.saturating_duration_since(now))),
+            })
+        })
+        .collect();
+    Json(serde_json::json!({
+        "health_check": {
+            "interval_ms": ms(health_check.interval),
+            "initial_backoff_ms": ms(health_check.initial_backoff),
+            "max_backoff_ms": ms(health_check.max_backoff),
+        },
+        "backends": backends,
+    }))
+}
+
+/// GET /federation/relay -- WebSocket a backend behind NAT dials to join
+/// this server; see [`crate::federation::relay`].
+#[cfg(feature = "federation")]
+pub(super) async fn federation_relay(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    ws.max_message_size(MAX_WS_MESSAGE_SIZE)
+        .on_upgrade(move |socket| crate::federation::relay::serve_joined(socket, state.federation))
+}
+
 /// POST /servers -- register a new backend server.
+#[cfg(feature = "federation")]
 pub(super) async fn add_server(
     State(state): State<AppState>,
     Json(body): Json<serde_json::Value>,
@@ -3360,13 +7239,13 @@ pub(super) async fn add_server(
 
     // Validate address format before acquiring the lock.
     crate::federation::registry::validate_backend_address(address)
-        .map_err(|detail| ApiError::InvalidRequest(detail))?;
+        .map_err(ApiError::InvalidRequest)?;
 
     // Check resolved IPs against IP access control (if configured).
     if let Some(ref ip_access) = state.ip_access {
         crate::federation::ip_access::check_backend_url(ip_access, address)
             .await
-            .map_err(|detail| ApiError::InvalidRequest(detail))?;
+            .map_err(ApiError::InvalidRequest)?;
     }
 
     let mut manager = state.federation.lock().await;
@@ -3391,6 +7270,7 @@ pub(super) async fn add_server(
 }
 
 /// DELETE /servers/{hostname} -- deregister a backend server.
+#[cfg(feature = "federation")]
 pub(super) async fn remove_server(
     State(state): State<AppState>,
     Path(hostname): Path<String>,
@@ -3420,7 +7300,7 @@ pub(super) async fn get_server(
     }
 
     let backend = state.backends.get_by_hostname(&hostname)
-        .ok_or_else(|| ApiError::ServerNotFound(hostname))?;
+        .ok_or(ApiError::ServerNotFound(hostname))?;
 
     Ok(Json(serde_json::json!({
         "hostname": backend.hostname,
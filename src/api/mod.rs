@@ -1,17 +1,24 @@
 pub mod auth;
+pub mod backpressure;
 pub mod error;
 mod handlers;
 pub mod origin;
 mod proxy;
+pub mod raw_frames;
+mod resources;
+pub mod resume;
+#[cfg(feature = "schemas")]
+pub mod schemas;
 pub mod ticket;
+pub mod tokens;
+#[cfg(feature = "web-ui")]
 mod web;
 pub mod ws_methods;
 
 use axum::{
     extract::DefaultBodyLimit,
     http::{header, HeaderName, HeaderValue, Method},
-    response::Redirect,
-    routing::{get, post},
+    routing::{get, post, put},
     Router,
 };
 use tower_http::cors::CorsLayer;
@@ -31,17 +38,45 @@ use handlers::*;
 /// In ephemeral mode (default, `persistent = false`) the server shuts down
 /// when its last session exits or is destroyed. In persistent mode the server
 /// stays alive indefinitely, waiting for new sessions to be created.
+///
+/// Also holds the runtime switches for the MCP endpoint and the web UI
+/// (`PATCH /server/features`). Federation proxying is switched on the
+/// [`BackendRegistry`](crate::federation::registry::BackendRegistry), which
+/// the socket server shares.
 pub struct ServerConfig {
     persistent: AtomicBool,
+    started_at: std::time::Instant,
+    mcp_enabled: AtomicBool,
+    web_ui_enabled: AtomicBool,
+    /// The `[mcp]` config section, for tool result size caps.
+    mcp: parking_lot::RwLock<crate::config::McpConfig>,
+    /// The `[files]` config section: where file transfer may reach.
+    files: parking_lot::RwLock<crate::files::FilesConfig>,
+    /// The `[websocket]` config section, for slow raw output clients.
+    websocket: parking_lot::RwLock<crate::config::WebSocketConfig>,
+    /// Session templates managed through `/templates`.
+    templates: crate::templates::TemplateStore,
 }
 
 impl ServerConfig {
     pub fn new(persistent: bool) -> Self {
         Self {
             persistent: AtomicBool::new(persistent),
+            started_at: std::time::Instant::now(),
+            mcp_enabled: AtomicBool::new(true),
+            web_ui_enabled: AtomicBool::new(true),
+            mcp: parking_lot::RwLock::new(crate::config::McpConfig::default()),
+            files: parking_lot::RwLock::new(crate::files::FilesConfig::default()),
+            websocket: parking_lot::RwLock::new(crate::config::WebSocketConfig::default()),
+            templates: crate::templates::TemplateStore::new(),
         }
     }
 
+    /// Time since the server started.
+    pub fn uptime(&self) -> std::time::Duration {
+        self.started_at.elapsed()
+    }
+
     pub fn is_persistent(&self) -> bool {
         self.persistent.load(Ordering::Acquire)
     }
@@ -49,6 +84,59 @@ impl ServerConfig {
     pub fn set_persistent(&self, value: bool) {
         self.persistent.store(value, Ordering::Release);
     }
+
+    pub fn mcp_enabled(&self) -> bool {
+        self.mcp_enabled.load(Ordering::Acquire)
+    }
+
+    pub fn set_mcp_enabled(&self, value: bool) {
+        self.mcp_enabled.store(value, Ordering::Release);
+    }
+
+    pub fn web_ui_enabled(&self) -> bool {
+        self.web_ui_enabled.load(Ordering::Acquire)
+    }
+
+    pub fn set_web_ui_enabled(&self, value: bool) {
+        self.web_ui_enabled.store(value, Ordering::Release);
+    }
+
+    pub fn set_mcp_config(&self, config: crate::config::McpConfig) {
+        *self.mcp.write() = config;
+    }
+
+    /// The result size cap for an MCP tool, or `None` for no limit.
+    pub fn mcp_result_limit(&self, tool: &str) -> Option<usize> {
+        self.mcp.read().result_limit(tool)
+    }
+
+    /// What happens to an MCP client's sessions when its MCP session ends,
+    /// unless it asked for something else.
+    pub fn mcp_cleanup_on_disconnect(&self) -> crate::session::DisconnectCleanup {
+        self.mcp.read().cleanup_on_disconnect.unwrap_or(crate::session::DisconnectCleanup::Keep)
+    }
+
+    pub fn set_files_config(&self, config: crate::files::FilesConfig) {
+        *self.files.write() = config;
+    }
+
+    pub fn files_config(&self) -> crate::files::FilesConfig {
+        self.files.read().clone()
+    }
+
+    pub fn set_websocket_config(&self, config: crate::config::WebSocketConfig) {
+        *self.websocket.write() = config;
+    }
+
+    /// What raw output clients that fall behind get, unless they ask for
+    /// something else.
+    pub fn lag_policy(&self) -> backpressure::LagPolicy {
+        self.websocket.read().lag_policy.unwrap_or_default()
+    }
+
+    pub fn templates(&self) -> &crate::templates::TemplateStore {
+        &self.templates
+    }
 }
 
 /// Maximum concurrent server-level WebSocket connections.
@@ -60,6 +148,7 @@ impl ServerConfig {
 const MAX_SERVER_WS_CONNECTIONS: usize = 256;
 
 /// Maximum concurrent MCP sessions allowed via the Streamable HTTP transport.
+#[cfg(feature = "mcp")]
 const MAX_MCP_SESSIONS: usize = 256;
 
 #[derive(Clone)]
@@ -73,11 +162,16 @@ pub struct AppState {
     pub mcp_session_count: Arc<std::sync::atomic::AtomicUsize>,
     /// Short-lived ticket store for WebSocket authentication.
     pub ticket_store: Arc<ticket::TicketStore>,
+    /// Named API tokens and their scopes. The router installs
+    /// `RouterConfig::token` as the root (admin) token.
+    pub tokens: Arc<tokens::TokenStore>,
     /// Registry of known backend servers for federation.
     pub backends: crate::federation::registry::BackendRegistry,
     /// FederationManager for server add/remove operations (behind Mutex for mutation).
+    #[cfg(feature = "federation")]
     pub federation: Arc<tokio::sync::Mutex<crate::federation::manager::FederationManager>>,
     /// IP access control for federation SSRF mitigation (None = unconfigured).
+    #[cfg(feature = "federation")]
     pub ip_access: Option<Arc<crate::federation::ip_access::IpAccessControl>>,
     /// This server's hostname (for federation identity).
     pub hostname: String,
@@ -127,7 +221,14 @@ impl Default for RouterConfig {
     }
 }
 
-pub fn router(state: AppState, config: RouterConfig) -> Router {
+/// The MCP Streamable HTTP service mounted at `/mcp`.
+#[cfg(feature = "mcp")]
+fn mcp_service(
+    state: &AppState,
+) -> rmcp::transport::streamable_http_server::StreamableHttpService<
+    crate::mcp::WshMcpServer,
+    rmcp::transport::streamable_http_server::session::local::LocalSessionManager,
+> {
     use rmcp::transport::streamable_http_server::{
         StreamableHttpService, StreamableHttpServerConfig,
         session::local::LocalSessionManager,
@@ -135,13 +236,12 @@ pub fn router(state: AppState, config: RouterConfig) -> Router {
 
     let mcp_state = state.clone();
     let mcp_counter = state.mcp_session_count.clone();
-    let mcp_service = StreamableHttpService::new(
+    StreamableHttpService::new(
         move || {
             let current = mcp_counter.fetch_add(1, std::sync::atomic::Ordering::AcqRel);
             if current >= MAX_MCP_SESSIONS {
                 mcp_counter.fetch_sub(1, std::sync::atomic::Ordering::Release);
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
+                return Err(std::io::Error::other(
                     "maximum MCP sessions reached",
                 ));
             }
@@ -150,19 +250,67 @@ pub fn router(state: AppState, config: RouterConfig) -> Router {
         },
         Arc::new(LocalSessionManager::default()),
         StreamableHttpServerConfig::default(),
-    );
+    )
+}
+
+/// Refuse requests to a subsystem that has been switched off at runtime.
+#[cfg(any(feature = "mcp", feature = "web-ui"))]
+async fn require_enabled(
+    enabled: bool,
+    feature: &'static str,
+    req: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    if enabled {
+        next.run(req).await
+    } else {
+        use axum::response::IntoResponse;
+        error::ApiError::FeatureDisabled(feature.to_string()).into_response()
+    }
+}
+
+pub fn router(state: AppState, config: RouterConfig) -> Router {
     let session_routes = Router::new()
         .route("/input", post(input))
+        .route("/input/keys", post(input_keys))
+        .route("/input/secret", post(input_secret))
+        .route("/env", post(session_env))
         .route("/input/mode", get(input_mode_get))
         .route("/input/capture", post(input_capture))
         .route("/input/release", post(input_release))
+        .route("/input/handoff", get(input_handoff_get).post(input_handoff_request))
+        .route("/input/handoff/approve", post(input_handoff_approve))
+        .route("/input/handoff/deny", post(input_handoff_deny))
+        .route("/input/handoff/return", post(input_handoff_return))
+        .route("/approvals", get(approvals_get).patch(approvals_patch))
+        .route("/approvals/{id}", post(approval_answer))
         .route("/input/focus", get(input_focus_get).post(input_focus))
+        .route("/input/focus/order", put(input_focus_order))
+        .route("/input/focus/next", post(input_focus_next))
         .route("/input/unfocus", post(input_unfocus))
         .route("/idle", get(idle))
         .route("/ws/raw", get(ws_raw))
         .route("/ws/json", get(ws_json))
         .route("/screen", get(screen))
+        .route("/screen.png", get(screen_png))
         .route("/scrollback", get(scrollback))
+        .route("/output", get(output))
+        .route("/scrollback/export", get(scrollback_export))
+        .route("/stats", get(stats))
+        .route("/audit", get(audit))
+        .route("/insights", get(insights))
+        .route("/links", get(links))
+        .route("/modes", get(modes))
+        .route("/images", get(images))
+        .route("/images/{id}", get(image_get))
+        .route("/bookmarks", get(bookmark_list).post(bookmark_create))
+        .route("/bookmarks/{bookmark}", axum::routing::delete(bookmark_delete))
+        .route("/pipe", get(pipe_list).post(pipe_create))
+        .route("/pipe/{id}", axum::routing::delete(pipe_delete))
+        .route("/redact", get(redact_get).put(redact_put))
+        .route("/clipboard", get(clipboard_get).post(clipboard_post))
+        .route("/resize", post(session_resize))
+        .route("/file", get(file_read).put(file_write))
         .route(
             "/overlay",
             get(overlay_list)
@@ -178,6 +326,7 @@ pub fn router(state: AppState, config: RouterConfig) -> Router {
         )
         .route("/overlay/{id}/spans", post(overlay_update_spans))
         .route("/overlay/{id}/write", post(overlay_region_write))
+        .route("/overlay/{id}/animate", post(overlay_animate))
         .route(
             "/panel",
             get(panel_list)
@@ -193,6 +342,9 @@ pub fn router(state: AppState, config: RouterConfig) -> Router {
         )
         .route("/panel/{id}/spans", post(panel_update_spans))
         .route("/panel/{id}/write", post(panel_region_write))
+        .route("/panel/{id}/append", post(panel_append))
+        .route("/status_line", get(status_line_get).patch(status_line_patch))
+        .route("/panel/{id}/scroll", post(panel_scroll))
         .route("/screen_mode", get(screen_mode_get))
         .route("/screen_mode/enter_alt", post(enter_alt_screen))
         .route("/screen_mode/exit_alt", post(exit_alt_screen));
@@ -208,22 +360,81 @@ pub fn router(state: AppState, config: RouterConfig) -> Router {
                 .patch(session_update)
                 .delete(session_kill),
         )
+        .route(
+            "/sessions/input",
+            post(broadcast_input)
+                .get(input_session_get)
+                .patch(input_session_update)
+                .delete(input_session_kill),
+        )
         .route("/sessions/{name}/detach", post(session_detach))
+        .route("/sessions/{name}/signal", post(session_signal))
+        .route("/sessions/{name}/resume", post(session_resume))
+        .route("/sessions/{name}/hibernate", post(session_hibernate))
         .route("/idle", get(idle_any))
+        .route("/screens", post(screens_bulk))
+        .route("/orchestrate/run", post(orchestrate_run))
+        .route("/tmux/import", post(tmux_import))
+        .route("/templates", get(template_list))
+        .route(
+            "/templates/{name}",
+            get(template_get).put(template_put).delete(template_delete),
+        )
         .route("/server/info", get(server_info))
         .route("/server/persist", get(server_persist_get).put(server_persist_set))
+        .route(
+            "/server/maintenance",
+            get(server_maintenance_get).post(server_maintenance_set),
+        )
+        .route("/server/features", get(server_features_get).patch(server_features_set))
+        .route("/server/logs", get(server_logs))
+        .route("/ws/json", get(ws_json_server));
+    #[cfg(feature = "federation")]
+    let session_mgmt_routes = session_mgmt_routes
         .route("/servers", get(list_servers).post(add_server))
         .route("/servers/{hostname}", get(get_server).delete(remove_server))
-        .route("/ws/json", get(ws_json_server));
+        .route("/federation/backends", get(federation_backends))
+        .route(crate::federation::relay::RELAY_PATH, get(federation_relay));
+    // Without federation the backend list is always empty, but it stays
+    // readable so clients see this server.
+    #[cfg(not(feature = "federation"))]
+    let session_mgmt_routes = session_mgmt_routes
+        .route("/servers", get(list_servers))
+        .route("/servers/{hostname}", get(get_server))
+        .route("/federation/backends", get(federation_backends));
 
     let ticket_store = state.ticket_store.clone();
+    let token_store = state.tokens.clone();
+    let auth_sessions = state.sessions.clone();
     let protected = Router::new()
         .merge(session_mgmt_routes)
         .nest("/sessions/{name}", session_routes)
         .route("/auth/ws-ticket", post(ws_ticket))
+        .route("/auth/tokens", get(token_list).post(token_create))
+        .route("/auth/tokens/{name}", axum::routing::delete(token_revoke))
         .route("/openapi.yaml", get(openapi_spec))
-        .route("/docs", get(docs_index))
-        .nest_service("/mcp", mcp_service)
+        .route("/docs", get(docs_index));
+    #[cfg(feature = "schemas")]
+    let protected = protected
+        .route("/schemas", get(schemas::schemas_index))
+        .route("/schemas/{file}", get(schemas::schema_get));
+    #[cfg(feature = "mcp")]
+    let protected = {
+        let server_config = state.server_config.clone();
+        protected.nest_service(
+            "/mcp",
+            tower::ServiceBuilder::new()
+                .layer(axum::middleware::from_fn(move |req, next| {
+                    require_enabled(server_config.mcp_enabled(), "MCP", req, next)
+                }))
+                .service(mcp_service(&state)),
+        )
+    };
+    #[cfg(feature = "web-ui")]
+    let web_ui_config = state.server_config.clone();
+    let hostname = state.hostname.clone();
+    let protected = protected
+        .layer(axum::middleware::from_fn_with_state(state.clone(), proxy_qualified))
         .with_state(state);
 
     // Auth/origin layer is applied first (inner), then rate limiting (outer).
@@ -232,11 +443,13 @@ pub fn router(state: AppState, config: RouterConfig) -> Router {
     // token guessing is throttled even when auth rejects the request.
     let protected = match config.token {
         Some(token) => {
+            token_store.set_root(token);
             let ts = Some(ticket_store);
             protected.layer(axum::middleware::from_fn(move |req, next| {
-                let t = token.clone();
+                let t = token_store.clone();
                 let ts = ts.clone();
-                async move { auth::require_auth(t, ts, req, next).await }
+                let sessions = auth_sessions.clone();
+                async move { auth::require_auth(t, ts, sessions, req, next).await }
             }))
         }
         None => {
@@ -267,18 +480,33 @@ pub fn router(state: AppState, config: RouterConfig) -> Router {
                 .finish()
                 .unwrap()
         );
-        protected.layer(GovernorLayer::new(governor_conf))
+        protected.layer(GovernorLayer::new(governor_conf).error_handler(|err| {
+            use axum::response::IntoResponse;
+            match err {
+                tower_governor::GovernorError::TooManyRequests { wait_time, .. } => {
+                    error::ApiError::RateLimited(wait_time).into_response()
+                }
+                other => error::ApiError::InternalError(other.to_string()).into_response(),
+            }
+        }))
     } else {
         protected
     };
 
-    let ui = Router::new().fallback(web::web_asset);
+    let router = Router::new().route("/health", get(health)).merge(protected);
+    #[cfg(feature = "web-ui")]
+    let router = router
+        .route("/", get(|| async { axum::response::Redirect::temporary("/ui") }))
+        .nest(
+            "/ui",
+            Router::new()
+                .fallback(web::web_asset)
+                .layer(axum::middleware::from_fn(move |req, next| {
+                    require_enabled(web_ui_config.web_ui_enabled(), "The web UI", req, next)
+                })),
+        );
 
-    let router = Router::new()
-        .route("/", get(|| async { Redirect::temporary("/ui") }))
-        .route("/health", get(health))
-        .merge(protected)
-        .nest("/ui", ui)
+    let router = router
         .layer(DefaultBodyLimit::max(1024 * 1024)) // 1 MB
         .layer(SetResponseHeaderLayer::overriding(
             HeaderName::from_static("x-frame-options"),
@@ -317,19 +545,31 @@ pub fn router(state: AppState, config: RouterConfig) -> Router {
                 .allow_methods([Method::GET, Method::POST, Method::PUT,
                                Method::PATCH, Method::DELETE, Method::OPTIONS])
                 .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE])
+                // Session list paging.
+                .expose_headers([HeaderName::from_static("x-total-count")])
         )
     };
 
+    // `server:session` names are split before routing, so qualified paths
+    // match the ordinary session routes.
+    let router = Router::new().fallback_service(
+        tower::ServiceExt::map_request(router, move |req| split_qualified_name(&hostname, req)),
+    );
+
     // Optionally nest all routes under a base path prefix (for reverse-proxy deployment).
     // /health is duplicated at root so load balancers can probe without the prefix.
-    match config.base_prefix {
+    let router = match config.base_prefix {
         Some(prefix) => {
             Router::new()
                 .route("/health", get(health))
                 .nest(&prefix, router)
         }
         None => router,
-    }
+    };
+
+    // Errors raised outside the handlers (unknown routes, extractor
+    // rejections, body limits) get the same problem+json shape.
+    router.layer(axum::middleware::map_response(error::problem_fallback))
 }
 
 #[cfg(test)]
@@ -357,13 +597,35 @@ mod tests {
         let parser = Parser::spawn(parser_rx, 80, 24, 1000);
         let session = crate::session::Session {
             name: "test".to_string(),
-            pid: None,
+            pid: Default::default(),
             command: "test".to_string(),
             client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
             child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            size_arbiter: crate::terminal::SizeArbiter::default(),
+            usage: crate::usage::UsageCounters::new(),
+            idle_kill_after: Default::default(),
+            suspended: Default::default(),
+            hibernated: Default::default(),
+            exit_status: Default::default(),
+            restart: Default::default(),
+            scrollback_lines: Default::default(),
+            query_policy: Default::default(),
+            handoff: Default::default(),
+            policy: Default::default(),
+            redactor: Default::default(),
+            resume_tokens: Default::default(),
+            bookmarks: Default::default(),
+            status_line: Default::default(),
+            pipes: Default::default(),
+            approvals: Default::default(),
+            metadata: Default::default(),
+            created_at: Default::default(),
+            run_as: None,
+            safe_mode: None,
+            owner: Default::default(),
             input_tx,
-            output_rx: broker.sender(),
+            output_rx: broker.clone(),
             shutdown: ShutdownCoordinator::new(),
             parser,
             overlays: OverlayStore::new(),
@@ -391,8 +653,11 @@ mod tests {
             server_ws_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: Arc::new(ticket::TicketStore::new()),
+            tokens: Arc::new(tokens::TokenStore::new()),
             backends: crate::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(crate::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -520,34 +785,136 @@ mod tests {
             .unwrap();
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
 
-        // Web UI is served under /ui (SPA fallback)
+        #[cfg(feature = "web-ui")]
+        {
+            // Web UI is served under /ui (SPA fallback)
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri("/ui")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            // Root redirects to /ui
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri("/")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+            assert_eq!(
+                response.headers().get("location").unwrap().to_str().unwrap(),
+                "/ui",
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_framework_errors_are_problem_json() {
+        let (state, _input_rx, _name) = create_test_state();
+        let app = router(state, RouterConfig::default());
+
         let response = app
             .clone()
+            .oneshot(Request::builder().uri("/no/such/route").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()["content-type"], "application/problem+json");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "not_found");
+        assert_eq!(json["status"], 404);
+
+        let response = app
             .oneshot(
                 Request::builder()
-                    .uri("/ui")
-                    .body(Body::empty())
+                    .method("POST")
+                    .uri("/sessions")
+                    .header("content-type", "application/json")
+                    .body(Body::from("{not json"))
                     .unwrap(),
             )
             .await
             .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers()["content-type"], "application/problem+json");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "invalid_request");
+        assert!(!json["detail"].as_str().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_has_retry_after() {
+        let (state, _input_rx, _name) = create_test_state();
+        let config = RouterConfig { rate_limit: Some(1), ..RouterConfig::default() };
+        let app = router(state, config);
+        let peer = axum::extract::ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 40000)));
+
+        let mut statuses = Vec::new();
+        for _ in 0..2 {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri("/sessions")
+                        .extension(peer)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            statuses.push(response.status());
+            if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                assert_eq!(response.headers()["content-type"], "application/problem+json");
+                let secs: u64 = response.headers()["retry-after"].to_str().unwrap().parse().unwrap();
+                assert!(secs >= 1);
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                assert_eq!(json["code"], "rate_limited");
+            }
+        }
+        assert_eq!(statuses, [StatusCode::OK, StatusCode::TOO_MANY_REQUESTS]);
+    }
+
+    #[cfg(not(feature = "federation"))]
+    #[tokio::test]
+    async fn test_servers_read_only_without_federation() {
+        let (state, _input_rx, _name) = create_test_state();
+        let app = router(state, RouterConfig::default());
+
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri("/servers").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
         assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let servers: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(servers.as_array().unwrap().len(), 1);
 
-        // Root redirects to /ui
         let response = app
             .oneshot(
                 Request::builder()
-                    .uri("/")
-                    .body(Body::empty())
+                    .method("POST")
+                    .uri("/servers")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"address":"http://10.0.0.1:8080"}"#))
                     .unwrap(),
             )
             .await
             .unwrap();
-        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
-        assert_eq!(
-            response.headers().get("location").unwrap().to_str().unwrap(),
-            "/ui",
-        );
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
     }
 
     #[tokio::test]
@@ -759,6 +1126,182 @@ mod tests {
         assert_eq!(json["mode"], "passthrough");
     }
 
+    #[tokio::test]
+    async fn test_approval_mode_holds_dangerous_input() {
+        let (state, mut input_rx, _name) = create_test_state();
+        let session = state.sessions.get("test").unwrap();
+        let app = router(state, RouterConfig::default());
+        let request = |method: &str, uri: &str, body: &str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        async fn json_of(response: axum::response::Response) -> serde_json::Value {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice(&body).unwrap()
+        }
+
+        // Off by default.
+        let response = app.clone().oneshot(request("POST", "/sessions/test/input", "sudo true\n")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(input_rx.recv().await.unwrap(), Bytes::from("sudo true\n"));
+
+        let response = app
+            .clone()
+            .oneshot(request("PATCH", "/sessions/test/approvals", r#"{"enabled": true}"#))
+            .await
+            .unwrap();
+        assert_eq!(json_of(response).await["enabled"], true);
+
+        // Harmless input still goes straight through.
+        let response = app.clone().oneshot(request("POST", "/sessions/test/input", "ls\n")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(input_rx.recv().await.unwrap(), Bytes::from("ls\n"));
+
+        for input in ["rm -rf build\n", "git push --force\n"] {
+            let response = app.clone().oneshot(request("POST", "/sessions/test/input", input)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+            assert_eq!(json_of(response).await["text"], input);
+        }
+        assert!(input_rx.try_recv().is_err());
+        let prompt = session.overlays.list();
+        assert_eq!(prompt.len(), 1);
+        assert!(prompt[0].spans[0].text.contains("rm -rf build (1 of 2)"));
+
+        let response = app.clone().oneshot(request("GET", "/sessions/test/approvals", "")).await.unwrap();
+        let json = json_of(response).await;
+        assert_eq!(json["pending"][0]["id"], 1);
+        assert_eq!(json["pending"][1]["source"]["kind"], "http");
+
+        let response = app
+            .clone()
+            .oneshot(request("POST", "/sessions/test/approvals/2", r#"{"approve": false}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(input_rx.try_recv().is_err());
+        assert!(session.overlays.list()[0].spans[0].text.contains("rm -rf build  [y]"));
+
+        let response = app
+            .clone()
+            .oneshot(request("POST", "/sessions/test/approvals/1", r#"{"approve": true}"#))
+            .await
+            .unwrap();
+        assert_eq!(json_of(response).await["input"]["text"], "rm -rf build\n");
+        assert_eq!(input_rx.recv().await.unwrap(), Bytes::from("rm -rf build\n"));
+        assert!(session.overlays.list().is_empty());
+
+        let response = app
+            .clone()
+            .oneshot(request("POST", "/sessions/test/approvals/1", r#"{"approve": true}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(json_of(response).await["error"]["code"], "approval_not_found");
+
+        // Approved input is audited under the client that sent it.
+        let response = app.oneshot(request("GET", "/sessions/test/audit", "")).await.unwrap();
+        let json = json_of(response).await;
+        let texts: Vec<_> = json["entries"].as_array().unwrap().iter().map(|e| e["text"].clone()).collect();
+        assert_eq!(texts, ["sudo true\n", "ls\n", "rm -rf build\n"]);
+    }
+
+    #[tokio::test]
+    async fn test_input_handoff_flow() {
+        let (state, _input_rx, _name) = create_test_state();
+        let session = state.sessions.get("test").unwrap();
+        let app = router(state, RouterConfig::default());
+        let post = |uri: &str, body: &str| {
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        async fn json_of(response: axum::response::Response) -> serde_json::Value {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice(&body).unwrap()
+        }
+
+        let response = app
+            .clone()
+            .oneshot(post("/sessions/test/input/handoff", r#"{"agent": "claude", "reason": "deploy"}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let json = json_of(response).await;
+        assert_eq!(json["controller"], "human");
+        assert_eq!(json["pending"]["agent"], "claude");
+        assert_eq!(session.overlays.list().len(), 1);
+
+        // Only one request at a time.
+        let response = app
+            .clone()
+            .oneshot(post("/sessions/test/input/handoff", "{}"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(json_of(response).await["error"]["code"], "handoff_conflict");
+
+        let response = app
+            .clone()
+            .oneshot(post("/sessions/test/input/handoff/approve", ""))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let json = json_of(response).await;
+        assert_eq!(json["controller"], "agent");
+        assert_eq!(json["agent"], "claude");
+        assert!(json["pending"].is_null());
+        assert!(session.input_mode.is_capture());
+        assert!(session.overlays.list().is_empty());
+
+        // A plain release hands control back too.
+        let response = app
+            .clone()
+            .oneshot(post("/sessions/test/input/release", ""))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri("/sessions/test/input/handoff").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(json_of(response).await["controller"], "human");
+
+        let response = app
+            .clone()
+            .oneshot(post("/sessions/test/input/handoff/deny", ""))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = app
+            .oneshot(Request::builder().uri("/sessions/test/audit").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let entries = json_of(response).await["entries"].clone();
+        let steps: Vec<_> = entries
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| (e["handoff"]["action"].clone(), e["handoff"]["to"].clone()))
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                (serde_json::json!("requested"), serde_json::Value::Null),
+                (serde_json::json!("transferred"), serde_json::json!("agent")),
+                (serde_json::json!("transferred"), serde_json::json!("human")),
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn test_openapi_spec_endpoint() {
         let (state, _input_rx, _name) = create_test_state();
@@ -792,6 +1335,42 @@ mod tests {
         assert!(text.contains("/health"));
     }
 
+    #[cfg(feature = "schemas")]
+    #[tokio::test]
+    async fn test_schemas_endpoints() {
+        let (state, _input_rx, _name) = create_test_state();
+        let app = router(state, RouterConfig::default());
+
+        let get = |uri: &'static str| {
+            app.clone()
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        };
+
+        let response = get("/schemas").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let index: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let attach = index["schemas"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|s| s["name"] == "socket.attach_session")
+            .unwrap();
+        assert_eq!(attach["frame_type"], 3);
+        assert_eq!(attach["url"], "/schemas/socket.attach_session.json");
+
+        let response = get("/schemas/ws.params.send_input.json").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/schema+json");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let schema: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(schema["title"], "SendInputParams");
+        assert_eq!(schema["required"], serde_json::json!(["data"]));
+
+        let response = get("/schemas/nope.json").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
     #[tokio::test]
     async fn test_docs_endpoint() {
         let (state, _input_rx, _name) = create_test_state();
@@ -971,6 +1550,107 @@ mod tests {
         assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
     }
 
+    #[tokio::test]
+    async fn test_scoped_token_lifecycle() {
+        let (state, _input_rx, _name) = create_test_state();
+        let app = router(state, RouterConfig { token: Some("secret-token".to_string()), ..Default::default() });
+
+        let send = |method: &str, uri: &str, token: &str, body: &str| {
+            let req = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("authorization", format!("Bearer {token}"))
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            app.clone().oneshot(req)
+        };
+
+        let response = send("POST", "/auth/tokens", "secret-token", r#"{"name": "reader", "scope": "read_only"}"#)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["scope"], "read_only");
+        let reader = json["token"].as_str().unwrap().to_string();
+
+        // Read-only token can read but not send input or manage tokens
+        let response = send("GET", "/sessions/test/input/mode", &reader, "").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send("POST", "/sessions/test/input", &reader, "ls").await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"]["code"], "insufficient_scope");
+        let response = send("GET", "/auth/tokens", &reader, "").await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // Listing never exposes secrets
+        let response = send("GET", "/auth/tokens", "secret-token", "").await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["tokens"][0]["name"], "reader");
+        assert!(json["tokens"][0].get("token").is_none());
+
+        // Revoked tokens stop working
+        let response = send("DELETE", "/auth/tokens/reader", "secret-token", "").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = send("GET", "/sessions/test/input/mode", &reader, "").await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = send("DELETE", "/auth/tokens/reader", "secret-token", "").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_tag_restricted_token_sees_only_tagged_sessions() {
+        let (state, _input_rx, _name) = create_test_state();
+        let (_, secret) = state
+            .tokens
+            .create("ci".to_string(), tokens::TokenScope::Input, vec!["ci".to_string()])
+            .unwrap();
+        let app = router(state.clone(), RouterConfig { token: Some("secret-token".to_string()), ..Default::default() });
+
+        let get_sessions = || {
+            app.clone().oneshot(
+                Request::builder()
+                    .uri("/sessions")
+                    .header("authorization", format!("Bearer {secret}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+
+        // The pre-seeded "test" session is untagged, so it is hidden
+        let response = get_sessions().await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 0);
+
+        state.sessions.add_tags("test", &["ci".to_string()]).unwrap();
+        let response = get_sessions().await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json[0]["name"], "test");
+
+        // Creating a session without one of the token's tags is refused
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/sessions")
+                    .header("authorization", format!("Bearer {secret}"))
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"name": "untagged"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(state.sessions.get("untagged").is_none());
+    }
+
     // ── Session management tests ─────────────────────────────────────
 
     /// Helper: creates a minimal AppState with an empty registry (no pre-seeded sessions).
@@ -982,8 +1662,11 @@ mod tests {
             server_ws_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: Arc::new(ticket::TicketStore::new()),
+            tokens: Arc::new(tokens::TokenStore::new()),
             backends: crate::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(crate::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -1300,14 +1983,206 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_session_create_duplicate_name_returns_409() {
+    async fn test_session_signal_and_graceful_kill() {
         let state = create_empty_state();
+        let sessions = state.sessions.clone();
         let app = router(state, RouterConfig::default());
-
-        // Create a session
-        let body = serde_json::json!({"name": "unique"});
-        let response = app
-            .clone()
+        let request = |method: &str, uri: &str, body: String| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+        let screen_contains = |session: crate::session::Session, needle: &'static str| async move {
+            let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+            loop {
+                let screen = session
+                    .parser
+                    .query(crate::parser::state::Query::Screen {
+                        format: crate::parser::state::Format::Plain,
+                        since: None,
+                    })
+                    .await
+                    .unwrap();
+                let text = serde_json::to_string(&screen).unwrap();
+                if text.contains(needle) {
+                    return true;
+                }
+                if tokio::time::Instant::now() >= deadline {
+                    return false;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        };
+
+        // TERM is ignored, so only the escalation to SIGKILL ends it.
+        let body = serde_json::json!({
+            "name": "stubborn",
+            "command": "trap 'echo got-usr1' USR1; trap '' TERM; echo ready; while :; do sleep 0.1; done",
+        });
+        let response = app.clone().oneshot(request("POST", "/sessions", body.to_string())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let session = sessions.get("stubborn").unwrap();
+        assert!(screen_contains(session.clone(), "ready").await);
+
+        let body = serde_json::json!({"signal": "USR1"});
+        let response = app
+            .clone()
+            .oneshot(request("POST", "/sessions/stubborn/signal", body.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(screen_contains(session.clone(), "got-usr1").await);
+
+        let body = serde_json::json!({"signal": "BOGUS"});
+        let response = app
+            .clone()
+            .oneshot(request("POST", "/sessions/stubborn/signal", body.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .clone()
+            .oneshot(request("DELETE", "/sessions/stubborn?grace_ms=600000", String::new()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(sessions.get("stubborn").is_some());
+
+        let response = app
+            .clone()
+            .oneshot(request("DELETE", "/sessions/stubborn?signal=TERM&grace_ms=300", String::new()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(sessions.get("stubborn").is_none());
+        let exited = session.child_exited.clone();
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+        while !exited.load(std::sync::atomic::Ordering::Acquire) && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(exited.load(std::sync::atomic::Ordering::Acquire), "child should be SIGKILLed after the grace period");
+
+        let body = serde_json::json!({"signal": "TERM"});
+        let response = app
+            .oneshot(request("POST", "/sessions/stubborn/signal", body.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_session_get_include_dead_returns_tombstone() {
+        let state = create_empty_state();
+        let sessions = state.sessions.clone();
+        let mut events = sessions.subscribe_events();
+        let app = router(state, RouterConfig::default());
+        let get = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+        let body = serde_json::json!({"name": "brief", "command": "echo farewell; exit 7"});
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/sessions")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while !matches!(events.recv().await, Ok(crate::session::SessionEvent::Destroyed { ref name, .. }) if name == "brief") {}
+        })
+        .await
+        .expect("session should exit");
+
+        let response = app.clone().oneshot(get("/sessions/brief")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = app.oneshot(get("/sessions/brief?include_dead=true")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["alive"], false);
+        assert_eq!(json["exit_code"], 7);
+        assert!(json["signal"].is_null());
+        assert!(json["screen"].to_string().contains("farewell"), "screen: {}", json["screen"]);
+    }
+
+    #[tokio::test]
+    async fn test_session_create_with_restart_policy() {
+        let state = create_empty_state();
+        let sessions = state.sessions.clone();
+        let mut events = sessions.subscribe_events();
+        let app = router(state, RouterConfig::default());
+        let post = |body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri("/sessions")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(post(serde_json::json!({"name": "x", "restart_max_retries": 2})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = app
+            .clone()
+            .oneshot(post(serde_json::json!({"name": "x", "restart": "sometimes"})))
+            .await
+            .unwrap();
+        assert!(response.status().is_client_error());
+
+        let body = serde_json::json!({
+            "name": "server",
+            "command": "echo up; sleep 0.2; exit 1",
+            "restart": "on-failure",
+            "restart_max_retries": 1,
+            "restart_backoff_ms": 10,
+        });
+        let response = app.clone().oneshot(post(body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            json["restart"],
+            serde_json::json!({"when": "on-failure", "max_retries": 1, "backoff_ms": 10, "restarts": 0})
+        );
+
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while !matches!(events.recv().await, Ok(crate::session::SessionEvent::Restarted { ref name, restarts: 1, .. }) if name == "server") {}
+        })
+        .await
+        .expect("command should be restarted");
+        let response = app
+            .oneshot(Request::builder().uri("/sessions/server").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["restart"]["restarts"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_session_create_duplicate_name_returns_409() {
+        let state = create_empty_state();
+        let app = router(state, RouterConfig::default());
+
+        // Create a session
+        let body = serde_json::json!({"name": "unique"});
+        let response = app
+            .clone()
             .oneshot(
                 Request::builder()
                     .method("POST")
@@ -1418,6 +2293,267 @@ mod tests {
         assert!(!state.server_config.is_persistent());
     }
 
+    #[tokio::test]
+    async fn test_server_maintenance_refuses_new_sessions() {
+        let (state, _input_rx, _) = create_test_state();
+        let app = router(state.clone(), RouterConfig::default());
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/server/maintenance")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"enabled": true, "reason": "upgrading to 1.2"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["maintenance"], true);
+        assert_eq!(json["reason"], "upgrading to 1.2");
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/sessions")
+                    .header("content-type", "application/json")
+                    .body(Body::from("{}"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"]["code"], "server_maintenance");
+        assert!(json["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("upgrading to 1.2"));
+
+        // Existing sessions keep working
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/sessions/test")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/server/maintenance")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"enabled": false}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(state.sessions.maintenance().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_server_maintenance_get_defaults_reason() {
+        let state = create_empty_state();
+        let app = router(state.clone(), RouterConfig::default());
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/server/maintenance")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"enabled": true}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/server/maintenance")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["maintenance"], true);
+        assert_eq!(json["reason"], "server maintenance");
+    }
+
+    #[tokio::test]
+    async fn test_federation_backends_report_stats() {
+        use crate::federation::registry::{BackendEntry, BackendHealth, BackendRole};
+        let (state, _input_rx, _name) = create_test_state();
+        let backends = state.backends.clone();
+        backends
+            .add_unchecked(BackendEntry {
+                address: "http://10.0.1.10:8080".into(),
+                token: Some("secret".into()),
+                hostname: Some("prod-1".into()),
+                health: BackendHealth::Unavailable,
+                role: BackendRole::Member,
+                server_id: None,
+            })
+            .unwrap();
+        backends.record_failure("http://10.0.1.10:8080", "connection refused".into(), false);
+        backends.record_retry("http://10.0.1.10:8080", std::time::Duration::from_secs(4));
+        let app = router(state, RouterConfig::default());
+
+        let response = app
+            .oneshot(Request::builder().uri("/federation/backends").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["health_check"]["interval_ms"], 30_000);
+        let backend = &json["backends"][0];
+        assert_eq!(backend["hostname"], "prod-1");
+        assert_eq!(backend["health"], "unavailable");
+        assert_eq!(backend["consecutive_failures"], 1);
+        assert_eq!(backend["last_error"], "connection refused");
+        assert_eq!(backend["backoff_ms"], 4000);
+        assert!(backend["retry_in_ms"].as_u64().unwrap() <= 4000);
+        assert!(backend["latency_ms"].is_null());
+        assert!(backend.get("token").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_server_logs_require_admin() {
+        let (state, _input_rx, _name) = create_test_state();
+        let tokens = state.tokens.clone();
+        let app = router(state, RouterConfig { token: Some("secret-token".to_string()), ..Default::default() });
+        let (_, reader) = tokens
+            .create("reader".into(), tokens::TokenScope::ReadOnly, vec![])
+            .unwrap();
+        let get = |uri: &str, token: &str| {
+            let req = Request::builder()
+                .uri(uri)
+                .header("authorization", format!("Bearer {token}"))
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(req)
+        };
+
+        crate::logs::recent().push("test_server_logs_require_admin marker\n");
+        let response = get("/server/logs", "secret-token").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(json["lines"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|line| line == "test_server_logs_require_admin marker"));
+
+        let response = get("/server/logs?limit=1", "secret-token").await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["lines"].as_array().unwrap().len(), 1);
+
+        let response = get("/server/logs", &reader).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_server_features_switch_off_subsystems() {
+        let state = create_empty_state();
+        let mut events = state.sessions.subscribe_events();
+        let mut changes = move || {
+            std::iter::from_fn(|| match events.try_recv().ok()? {
+                crate::session::SessionEvent::FeatureChanged { feature, enabled, by } => Some((feature, enabled, by)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+        };
+        let app = router(state.clone(), RouterConfig::default());
+        let patch = |body: &str| {
+            Request::builder()
+                .method("PATCH")
+                .uri("/server/features")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(patch(r#"{"federation": false}"#)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["federation"], false);
+        #[cfg(feature = "web-ui")]
+        assert_eq!(json["web_ui"], true);
+        assert_eq!(changes(), [("federation".to_string(), false, None)]);
+
+        // Requests for another server are refused before looking it up.
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri("/sessions/x/screen?server=other").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"]["code"], "feature_disabled");
+
+        let response = app.clone().oneshot(patch(r#"{"mcp": false}"#)).await.unwrap();
+        #[cfg(feature = "mcp")]
+        {
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(changes(), [("mcp".to_string(), false, None)]);
+            let request = Request::builder()
+                .method("POST")
+                .uri("/mcp")
+                .header("content-type", "application/json")
+                .body(Body::from("{}"))
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        }
+        #[cfg(not(feature = "mcp"))]
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // A field that names no switch is refused rather than ignored.
+        let response = app.clone().oneshot(patch(r#"{"recording": false}"#)).await.unwrap();
+        assert!(response.status().is_client_error());
+
+        // Switching back on restores proxying; repeating a setting is not
+        // announced again.
+        for _ in 0..2 {
+            let response = app.clone().oneshot(patch(r#"{"federation": true}"#)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        assert!(state.backends.proxying());
+        assert_eq!(changes(), [("federation".to_string(), true, None)]);
+    }
+
     // ── ServerConfig unit tests ──────────────────────────────────────
 
     #[test]
@@ -1490,8 +2626,11 @@ mod tests {
                 server_ws_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
                 mcp_session_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
                 ticket_store: Arc::new(ticket::TicketStore::new()),
+                tokens: Arc::new(tokens::TokenStore::new()),
                 backends: crate::federation::registry::BackendRegistry::new(),
+                #[cfg(feature = "federation")]
                 federation: std::sync::Arc::new(tokio::sync::Mutex::new(crate::federation::manager::FederationManager::new())),
+                #[cfg(feature = "federation")]
                 ip_access: None,
                 hostname: "test".to_string(),
                 federation_config_path: None,
@@ -1557,8 +2696,11 @@ mod tests {
                 server_ws_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
                 mcp_session_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
                 ticket_store: Arc::new(ticket::TicketStore::new()),
+                tokens: Arc::new(tokens::TokenStore::new()),
                 backends: crate::federation::registry::BackendRegistry::new(),
+                #[cfg(feature = "federation")]
                 federation: std::sync::Arc::new(tokio::sync::Mutex::new(crate::federation::manager::FederationManager::new())),
+                #[cfg(feature = "federation")]
                 ip_access: None,
                 hostname: "test".to_string(),
                 federation_config_path: None,
@@ -1621,8 +2763,11 @@ mod tests {
                 server_ws_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
                 mcp_session_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
                 ticket_store: Arc::new(ticket::TicketStore::new()),
+                tokens: Arc::new(tokens::TokenStore::new()),
                 backends: crate::federation::registry::BackendRegistry::new(),
+                #[cfg(feature = "federation")]
                 federation: std::sync::Arc::new(tokio::sync::Mutex::new(crate::federation::manager::FederationManager::new())),
+                #[cfg(feature = "federation")]
                 ip_access: None,
                 hostname: "test".to_string(),
                 federation_config_path: None,
@@ -2141,6 +3286,63 @@ mod tests {
         assert_eq!(json["error"]["code"], "invalid_tag");
     }
 
+    #[tokio::test]
+    async fn test_session_create_dry_run_validates_without_spawning() {
+        let state = create_empty_state();
+        let app = router(state.clone(), RouterConfig::default());
+
+        let dry_run = |body: serde_json::Value| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(
+                        Request::builder()
+                            .method("POST")
+                            .uri("/sessions?dry_run=true")
+                            .header("content-type", "application/json")
+                            .body(Body::from(body.to_string()))
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&body).unwrap())
+            }
+        };
+
+        let (status, json) = dry_run(serde_json::json!({
+            "name": "build",
+            "command": "FOO=1 sh -c 'make'",
+            "cwd": "/",
+            "rows": 5000,
+            "tags": ["ci", "build", "ci"],
+            "env": {"PATH": "/bin:/usr/bin", "TOKEN": "secret"},
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["dry_run"], true);
+        assert_eq!(json["name"], "build");
+        assert!(json["program"].as_str().unwrap().ends_with("/sh"));
+        assert_eq!(json["cwd"], "/");
+        assert_eq!(json["rows"], 1000);
+        assert_eq!(json["cols"], 80);
+        assert_eq!(json["tags"], serde_json::json!(["build", "ci"]));
+        assert_eq!(json["env"], serde_json::json!(["PATH", "TOKEN"]));
+        assert!(state.sessions.is_empty());
+
+        let (status, json) = dry_run(serde_json::json!({"command": "no-such-program-wsh"})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json["error"]["code"], "invalid_request");
+
+        let (status, _) = dry_run(serde_json::json!({"cwd": "/no/such/dir"})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, json) = dry_run(serde_json::json!({"name": "bad name"})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json["error"]["code"], "invalid_session_name");
+    }
+
     #[tokio::test]
     async fn test_session_list_with_tag_filter() {
         let state = create_empty_state();
@@ -2566,6 +3768,1729 @@ mod tests {
         assert!(tags.is_empty());
     }
 
+    // ── Resize tests ───────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn test_audit_attributes_http_input() {
+        let (state, mut input_rx, _name) = create_test_state();
+        let app = router(state, RouterConfig::default());
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/sessions/test/input")
+                    .body(Body::from("ls\n"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(input_rx.recv().await.unwrap().as_ref(), b"ls\n");
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/sessions/test/audit?limit=10")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let entries = json["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["source"]["kind"], "http");
+        assert_eq!(entries[0]["text"], "ls\n");
+        assert_eq!(entries[0]["seq"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_input_policy_blocks_and_redacts() {
+        let (state, mut input_rx, _name) = create_test_state();
+        let policy = state.sessions.get("test").unwrap().policy;
+        let app = router(state, RouterConfig::default());
+        let input = |body: &'static str| {
+            Request::builder()
+                .method("POST")
+                .uri("/sessions/test/input")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        // Rules swapped into the shared policy apply to the running session.
+        policy
+            .replace("[input]\nblock = ['rm\\s+-rf\\s+/']\n[redact]\npatterns = ['hunter[0-9]']\n")
+            .unwrap();
+        let response = app.clone().oneshot(input("rm -rf /\n")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"]["code"], "input_blocked");
+
+        let response = app.clone().oneshot(input("echo hunter2\n")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(input_rx.recv().await.unwrap().as_ref(), b"echo hunter2\n");
+        assert!(input_rx.try_recv().is_err());
+
+        let response = app
+            .oneshot(Request::builder().uri("/sessions/test/audit").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let entries = json["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["text"], "echo [redacted]\n");
+    }
+
+    #[tokio::test]
+    async fn test_session_redact_patterns_mask_output() {
+        let (state, _input_rx, _name) = create_test_state();
+        let (session, _child_exit_rx) = crate::session::Session::spawn(
+            "redact".to_string(),
+            crate::pty::SpawnCommand::Shell {
+                interactive: true,
+                shell: Some("/bin/sh".to_string()),
+            },
+            24,
+            80,
+        )
+        .unwrap();
+        state.sessions.insert(Some("redact".into()), session.clone()).unwrap();
+        let app = router(state, RouterConfig::default());
+        let request = |method: &str, uri: &str, body: &'static str| {
+            app.clone().oneshot(Request::builder().method(method).uri(uri).body(Body::from(body)).unwrap())
+        };
+        let json = |response: axum::response::Response| async move {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+
+        let response = request("PUT", "/sessions/redact/redact", r#"{"patterns": ["("]}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = request("PUT", "/sessions/redact/redact", r#"{"patterns": ["tok_[a-z0-9]+"]}"#)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(json(response).await["patterns"][0], "tok_[a-z0-9]+");
+        let response = request("GET", "/sessions/redact/redact", "").await.unwrap();
+        assert_eq!(json(response).await["server_patterns"], 0);
+
+        // The command line doesn't contain the secret; its output does.
+        request("POST", "/sessions/redact/input", "printf 'tok_%s done\\n' abc123\n").await.unwrap();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        let screen = loop {
+            let response = request("GET", "/sessions/redact/screen?format=plain", "").await.unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let screen = String::from_utf8_lossy(&body).to_string();
+            if screen.contains("done") || std::time::Instant::now() > deadline {
+                break screen;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        };
+        assert!(screen.contains("[redacted] done"), "screen: {screen}");
+        assert!(!screen.contains("tok_abc123"), "screen: {screen}");
+        session.kill_child();
+    }
+
+    #[tokio::test]
+    async fn test_session_clipboard_post_answers_and_get_reads_back() {
+        let (state, mut input_rx, _name) = create_test_state();
+        let app = router(state, RouterConfig::default());
+        let request = |method: &str, body: &'static str| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri("/sessions/test/clipboard")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+        };
+
+        let response = request("GET", "").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = request("POST", r#"{"text": "hello", "selection": "x"}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = request("POST", r#"{"text": "hello"}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(&input_rx.recv().await.unwrap()[..], b"\x1b]52;c;aGVsbG8=\x1b\\");
+
+        let response = request("GET", "").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json, serde_json::json!({"selection": "c", "text": "hello", "source": "api"}));
+    }
+
+    #[tokio::test]
+    async fn test_audit_nonexistent_session_404() {
+        let (state, _input_rx, _name) = create_test_state();
+        let app = router(state, RouterConfig::default());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/sessions/nope/audit")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_input_keys_encodes_named_keys() {
+        let (state, _input_rx, _name) = create_test_state();
+        let (session, _child_exit_rx) = crate::session::Session::spawn(
+            "keys".to_string(),
+            crate::pty::SpawnCommand::default(),
+            24,
+            80,
+        )
+        .unwrap();
+        state.sessions.insert(Some("keys".into()), session.clone()).unwrap();
+        let app = router(state, RouterConfig::default());
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/sessions/keys/input/keys")
+                    .body(Body::from(r#"{"keys": ["ctrl+c", "End"]}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let audit = session.input_broadcaster.audit_entries(None, None);
+        assert_eq!(audit.last().unwrap().text, "\x03\x1b[F");
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/sessions/keys/input/keys")
+                    .body(Body::from(r#"{"keys": ["Hyper"]}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        session.kill_child();
+    }
+
+    #[tokio::test]
+    async fn test_input_secret_requires_echo_off() {
+        let (state, _input_rx, _name) = create_test_state();
+        let (session, _child_exit_rx) = crate::session::Session::spawn(
+            "secret".to_string(),
+            crate::pty::SpawnCommand::Command {
+                command: "stty echo icanon; sleep 10".to_string(),
+                interactive: false,
+            },
+            24,
+            80,
+        )
+        .unwrap();
+        state.sessions.insert(Some("secret".into()), session.clone()).unwrap();
+        let app = router(state, RouterConfig::default());
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while !session.echoes_input() && std::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+
+        let send = |body: &'static str| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/sessions/secret/input/secret")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+        };
+
+        let response = send(r#"{"data": "hunter2\n"}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"]["code"], "echo_enabled");
+        assert!(session.input_broadcaster.audit_entries(None, None).is_empty());
+
+        let response = send(r#"{"data": "hunter2\n", "force": true}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let audit = session.input_broadcaster.audit_entries(None, None);
+        assert_eq!(audit.len(), 1);
+        assert!(audit[0].sensitive);
+        assert_eq!(audit[0].len, 8);
+        assert!(audit[0].text.is_empty());
+        session.kill_child();
+    }
+
+    #[tokio::test]
+    async fn test_session_env_sets_variables_without_echo() {
+        let (state, _input_rx, _name) = create_test_state();
+        let (session, _child_exit_rx) = crate::session::Session::spawn(
+            "env".to_string(),
+            crate::pty::SpawnCommand::Shell {
+                interactive: true,
+                shell: Some("/bin/sh".to_string()),
+            },
+            24,
+            80,
+        )
+        .unwrap();
+        state.sessions.insert(Some("env".into()), session.clone()).unwrap();
+        let app = router(state, RouterConfig::default());
+        let post = |uri: &str, body: &'static str| {
+            app.clone().oneshot(Request::builder().method("POST").uri(uri).body(Body::from(body)).unwrap())
+        };
+
+        let response = post("/sessions/env/env", r#"{"env": {"1BAD": "x"}}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = post("/sessions/env/env", r#"{"env": {"A": "x"}, "shell": "tcsh"}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = post("/sessions/env/env", r#"{"env": {"WSH_TOKEN": "s3cret value", "WSH_OTHER": "x"}}"#)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = post("/sessions/env/input", "echo \"[$WSH_TOKEN]\" | tr a-z A-Z\n").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        let screen = loop {
+            let response = app
+                .clone()
+                .oneshot(Request::builder().uri("/sessions/env/screen?format=plain").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let screen = String::from_utf8_lossy(&body).to_string();
+            if screen.contains("[S3CRET VALUE]") || std::time::Instant::now() > deadline {
+                break screen;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        };
+        assert!(screen.contains("[S3CRET VALUE]"), "screen: {screen}");
+        assert!(!screen.contains("s3cret"), "value was echoed: {screen}");
+
+        let audit = session.input_broadcaster.audit_entries(None, None);
+        let sensitive: Vec<_> = audit.iter().filter(|e| e.sensitive).collect();
+        assert_eq!(sensitive.len(), 2);
+        assert!(audit.iter().all(|e| !e.text.contains("s3cret")));
+        session.kill_child();
+    }
+
+    #[tokio::test]
+    async fn test_session_resume_continues_stopped_child() {
+        let (state, _input_rx, _name) = create_test_state();
+        let (session, _child_exit_rx) = crate::session::Session::spawn(
+            "stopped".to_string(),
+            crate::pty::SpawnCommand::Command {
+                command: "kill -STOP $$; sleep 30".to_string(),
+                interactive: false,
+            },
+            24,
+            80,
+        )
+        .unwrap();
+        state.sessions.insert(Some("stopped".into()), session.clone()).unwrap();
+        let app = router(state, RouterConfig::default());
+
+        let suspended = |app: Router| async move {
+            let response = app
+                .oneshot(Request::builder().uri("/sessions/stopped").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            json["suspended"].as_bool().unwrap()
+        };
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while !suspended(app.clone()).await && std::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(session.is_suspended());
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/sessions/stopped/resume")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while suspended(app.clone()).await && std::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(!session.is_suspended());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/sessions/nope/resume")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        session.kill_child();
+    }
+
+    #[tokio::test]
+    async fn test_session_hibernate_and_resume() {
+        let (state, _input_rx, _name) = create_test_state();
+        let (session, _child_exit_rx) = crate::session::Session::spawn(
+            "sleepy".to_string(),
+            crate::pty::SpawnCommand::Command {
+                command: "echo before-hibernate; sleep 30".to_string(),
+                interactive: false,
+            },
+            24,
+            80,
+        )
+        .unwrap();
+        state.sessions.insert(Some("sleepy".into()), session.clone()).unwrap();
+        let app = router(state, RouterConfig::default());
+        let get_json = |app: Router, uri: &'static str| async move {
+            let response = app
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+        let post = |app: Router, uri: &'static str| async move {
+            app.oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(uri)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status()
+        };
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while !get_json(app.clone(), "/sessions/sleepy/screen?format=plain").await["lines"][0]
+            .as_str()
+            .is_some_and(|line| line.contains("before-hibernate"))
+            && std::time::Instant::now() < deadline
+        {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+
+        assert_eq!(post(app.clone(), "/sessions/sleepy/hibernate").await, StatusCode::NO_CONTENT);
+        let info = get_json(app.clone(), "/sessions/sleepy").await;
+        assert_eq!(info["hibernated"], true);
+        assert_eq!(info["suspended"], true);
+        let screen = get_json(app.clone(), "/sessions/sleepy/screen?format=plain").await;
+        assert_eq!(screen["lines"][0], "before-hibernate");
+
+        assert_eq!(post(app.clone(), "/sessions/sleepy/resume").await, StatusCode::NO_CONTENT);
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while session.is_suspended() && std::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let info = get_json(app.clone(), "/sessions/sleepy").await;
+        assert_eq!(info["hibernated"], false);
+        assert_eq!(info["suspended"], false);
+
+        assert_eq!(post(app, "/sessions/nope/hibernate").await, StatusCode::NOT_FOUND);
+        session.kill_child();
+    }
+
+    #[tokio::test]
+    async fn test_screens_bulk_returns_plain_text_per_session() {
+        let state = create_empty_state();
+        let mut spawned = Vec::new();
+        for (name, tag) in [("alpha", "workers"), ("beta", "other")] {
+            let (session, _child_exit_rx) = crate::session::Session::spawn(
+                name.to_string(),
+                crate::pty::SpawnCommand::Command {
+                    command: format!("printf '{}-ready   \\n'; sleep 10", name),
+                    interactive: false,
+                },
+                24,
+                80,
+            )
+            .unwrap();
+            session.tags.write().insert(tag.to_string());
+            state.sessions.insert(Some(name.into()), session.clone()).unwrap();
+            spawned.push(session);
+        }
+        let app = router(state, RouterConfig::default());
+
+        let fetch = |body: serde_json::Value| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(
+                        Request::builder()
+                            .method("POST")
+                            .uri("/screens")
+                            .header("content-type", "application/json")
+                            .body(Body::from(body.to_string()))
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+            }
+        };
+
+        let request = serde_json::json!({"sessions": ["beta", "missing", "alpha", "beta"]});
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        let mut json = fetch(request.clone()).await;
+        while json["screens"][2]["text"] != "alpha-ready" && std::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            json = fetch(request.clone()).await;
+        }
+        let screens = json["screens"].as_array().unwrap();
+        assert_eq!(screens.len(), 3);
+        assert_eq!(screens[0]["session"], "beta");
+        assert_eq!(screens[1]["session"], "missing");
+        assert_eq!(screens[1]["error"], "session_not_found");
+        assert!(screens[1].get("text").is_none());
+        assert_eq!(screens[2]["text"], "alpha-ready");
+        assert_eq!(screens[2]["rows"], 24);
+        assert!(screens[2]["cursor"]["row"].is_number());
+
+        let json = fetch(serde_json::json!({"tag": "workers"})).await;
+        let screens = json["screens"].as_array().unwrap();
+        assert_eq!(screens.len(), 1);
+        assert_eq!(screens[0]["session"], "alpha");
+
+        let json = fetch(serde_json::json!({})).await;
+        assert_eq!(json["screens"].as_array().unwrap().len(), 2);
+
+        for session in spawned {
+            session.kill_child();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_orchestrate_run_returns_exit_codes_and_screens() {
+        let state = create_empty_state();
+        let mut spawned = Vec::new();
+        for name in ["w1", "w2"] {
+            let (session, _child_exit_rx) = crate::session::Session::spawn(
+                name.to_string(),
+                crate::pty::SpawnCommand::Command { command: "sh".to_string(), interactive: false },
+                24,
+                80,
+            )
+            .unwrap();
+            session.tags.write().insert("workers".to_string());
+            state.sessions.insert(Some(name.into()), session.clone()).unwrap();
+            spawned.push(session);
+        }
+        let app = router(state, RouterConfig::default());
+
+        let run = |body: serde_json::Value| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(
+                        Request::builder()
+                            .method("POST")
+                            .uri("/orchestrate/run")
+                            .header("content-type", "application/json")
+                            .body(Body::from(body.to_string()))
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&body).unwrap())
+            }
+        };
+
+        let (status, json) = run(serde_json::json!({
+            "tag": "workers",
+            "command": "echo out-$((1 + 1)); sh -c 'exit 3'",
+            "max_wait_ms": 10_000,
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let results = json["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        for (result, name) in results.iter().zip(["w1", "w2"]) {
+            assert_eq!(result["session"], name);
+            assert_eq!(result["finished"], true, "{result}");
+            assert_eq!(result["exit_code"], 3);
+            assert!(result["screen"].as_str().unwrap().contains("out-2"));
+        }
+
+        let (_, json) = run(serde_json::json!({"sessions": ["w1", "missing"], "input": "\n", "timeout_ms": 200})).await;
+        assert_eq!(json["results"][0]["finished"], true);
+        assert!(json["results"][0].get("exit_code").is_none());
+        assert_eq!(json["results"][1]["error"], "session_not_found");
+
+        let (status, _) = run(serde_json::json!({"tag": "workers", "input": "x", "command": "y"})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        for session in spawned {
+            session.kill_child();
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_stats_reports_process_tree() {
+        let state = create_empty_state();
+        let (session, _child_exit_rx) = crate::session::Session::spawn(
+            "busy".to_string(),
+            crate::pty::SpawnCommand::Command {
+                command: "sleep 30 & wait".to_string(),
+                interactive: false,
+            },
+            24,
+            80,
+        )
+        .unwrap();
+        state.sessions.insert(Some("busy".into()), session.clone()).unwrap();
+        let app = router(state, RouterConfig::default());
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        let json = loop {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri("/sessions/busy/stats?interval_ms=10")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            if json["processes"].as_array().unwrap().len() >= 2 || std::time::Instant::now() > deadline {
+                break json;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        };
+
+        assert_eq!(json["session"], "busy");
+        assert_eq!(json["interval_ms"], 10);
+        let processes = json["processes"].as_array().unwrap();
+        assert_eq!(processes[0]["pid"], session.pid().unwrap());
+        assert!(processes.iter().any(|p| p["command"] == "sleep 30"));
+        assert!(json["rss_bytes"].as_u64().unwrap() > 0);
+        assert!(json["cpu_percent"].is_number());
+        session.kill_child();
+    }
+
+    #[tokio::test]
+    async fn test_tmux_import_wraps_tmux_sessions() {
+        let state = create_empty_state();
+        let sessions = state.sessions.clone();
+        let app = router(state, RouterConfig::default());
+        let post = |body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri("/tmux/import")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(post(serde_json::json!({"tags": ["bad tag"]}))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let tmux_socket = format!("wsh-import-test-{}", std::process::id());
+        let started = std::process::Command::new("tmux")
+            .args(["-L", &tmux_socket, "new-session", "-d", "-s", "work", "sleep 60"])
+            .env_remove("TMUX")
+            .status();
+        if !started.is_ok_and(|s| s.success()) {
+            eprintln!("skipping: tmux unavailable");
+            return;
+        }
+
+        let body = serde_json::json!({"socket": tmux_socket, "sessions": ["work", "missing"], "tags": ["ci"]});
+        let response = app.clone().oneshot(post(body.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["imported"], serde_json::json!([{"tmux": "work", "session": "tmux-work"}]));
+        assert_eq!(json["skipped"][0]["tmux"], "missing");
+        let session = sessions.get("tmux-work").unwrap();
+        let mut tags: Vec<String> = session.tags.read().iter().cloned().collect();
+        tags.sort();
+        assert_eq!(tags, ["ci", "tmux"]);
+
+        // Importing again skips the session that's already wrapped.
+        let response = app.oneshot(post(body)).await.unwrap();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["imported"], serde_json::json!([]));
+        assert_eq!(json["skipped"][0]["reason"], "already imported");
+
+        session.kill_child();
+        let _ = std::process::Command::new("tmux")
+            .args(["-L", &tmux_socket, "kill-server"])
+            .env_remove("TMUX")
+            .status();
+    }
+
+    #[tokio::test]
+    async fn test_tmux_import_control_mode_mirrors_panes() {
+        let state = create_empty_state();
+        let sessions = state.sessions.clone();
+        let app = router(state, RouterConfig::default());
+
+        let tmux_socket = format!("wsh-control-import-test-{}", std::process::id());
+        let tmux = |args: &[&str]| {
+            std::process::Command::new("tmux")
+                .args(["-L", &tmux_socket])
+                .args(args)
+                .env_remove("TMUX")
+                .status()
+                .is_ok_and(|s| s.success())
+        };
+        if !tmux(&["new-session", "-d", "-s", "work", "-x", "80", "-y", "24", "sleep 60"]) {
+            eprintln!("skipping: tmux unavailable");
+            return;
+        }
+        assert!(tmux(&["split-window", "-h", "-t", "=work:", "sleep 60"]));
+
+        let body = serde_json::json!({"socket": tmux_socket, "mode": "control"});
+        let request = Request::builder()
+            .method("POST")
+            .uri("/tmux/import")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let imported = json["imported"].as_array().unwrap();
+        let names: Vec<&str> = imported.iter().map(|i| i["session"].as_str().unwrap()).collect();
+        assert_eq!(names, ["tmux-work-0.0", "tmux-work-0.1"]);
+        assert_eq!(imported[1]["tmux"], "work:0.1");
+        assert!(imported[1]["pane"].as_str().unwrap().starts_with('%'));
+
+        // The sessions run the bridge, so a quick exit removes them; check
+        // whichever are still there.
+        for name in names {
+            if let Some(session) = sessions.get(name) {
+                assert!(session.command.contains("__tmux-bridge"));
+                assert!(session.terminal_size.get().1 < 80);
+                session.kill_child();
+            }
+        }
+        tmux(&["kill-server"]);
+    }
+
+    #[tokio::test]
+    async fn test_create_session_in_sandbox_profile() {
+        let state = create_empty_state();
+        let sessions = state.sessions.clone();
+        sessions.set_sandbox(crate::sandbox::SandboxConfig {
+            default: None,
+            profiles: [(
+                "isolated".to_string(),
+                crate::sandbox::SandboxProfile::Unshare { root: None, network: false },
+            )]
+            .into(),
+        });
+        let app = router(state, RouterConfig::default());
+        let post = |uri: &str, body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(post("/sessions", serde_json::json!({"sandbox": "nope"})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .clone()
+            .oneshot(post("/sessions?dry_run=true", serde_json::json!({"sandbox": "isolated"})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["sandbox"], "isolated");
+
+        let usable = std::process::Command::new("unshare")
+            .args(["--mount", "--pid", "--fork", "true"])
+            .status();
+        if !usable.is_ok_and(|s| s.success()) {
+            eprintln!("skipping: cannot create namespaces here");
+            return;
+        }
+
+        let body = serde_json::json!({
+            "name": "boxed",
+            "sandbox": "isolated",
+            "command": "echo pid=$$; sleep 30",
+        });
+        let response = app.oneshot(post("/sessions", body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let session = sessions.get("boxed").unwrap();
+
+        // The shell is the first process of its own PID namespace.
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+        let text = loop {
+            let screen = session
+                .parser
+                .query(crate::parser::state::Query::Screen {
+                    format: crate::parser::state::Format::Plain,
+                    since: None,
+                })
+                .await
+                .unwrap();
+            let text = serde_json::to_string(&screen).unwrap();
+            if text.contains("pid=") || tokio::time::Instant::now() >= deadline {
+                break text;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        };
+        assert!(text.contains("pid=1"), "screen: {text}");
+        session.kill_child();
+    }
+
+    #[tokio::test]
+    async fn test_create_session_in_safe_mode_refuses_commands() {
+        use tokio_stream::StreamExt;
+
+        let state = create_empty_state();
+        let sessions = state.sessions.clone();
+        let app = router(state, RouterConfig::default());
+        let post = |uri: &str, body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let body = serde_json::json!({"safe_mode": {"allow": ["ls", "rm -rf"]}});
+        let response = app.clone().oneshot(post("/sessions", body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        if std::process::Command::new("bash").arg("-c").arg("true").status().is_err() {
+            eprintln!("skipping: no bash");
+            return;
+        }
+        let body = serde_json::json!({"name": "safe", "safe_mode": {"allow": ["ls"]}});
+        let response = app.clone().oneshot(post("/sessions", body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let info: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(info["safe_mode"], serde_json::json!({"allow": ["ls"]}));
+
+        let session = sessions.get("safe").unwrap();
+        let mut events = std::pin::pin!(session.parser.subscribe());
+        let request = Request::builder()
+            .method("POST")
+            .uri("/sessions/safe/input")
+            .body(Body::from("rm -rf /tmp/wsh-safe-mode-test\n"))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let denied = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while let Some(event) = events.next().await {
+                if let crate::parser::SubscriptionEvent::Event(
+                    crate::parser::events::Event::CommandDenied { program, command, .. },
+                ) = event
+                {
+                    return Some((program, command));
+                }
+            }
+            None
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            denied,
+            Some(("rm".to_string(), "rm -rf /tmp/wsh-safe-mode-test".to_string()))
+        );
+        session.kill_child();
+    }
+
+    #[tokio::test]
+    async fn test_create_session_idle_kill_after_override() {
+        let state = create_empty_state();
+        let sessions = state.sessions.clone();
+        let app = router(state, RouterConfig::default());
+        let post = |body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri("/sessions")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(post(serde_json::json!({"idle_kill_after": "soon"})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = serde_json::json!({"name": "short", "command": "sleep 30", "idle_kill_after": "45m"});
+        let response = app.oneshot(post(body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let session = sessions.get("short").unwrap();
+        assert_eq!(
+            *session.idle_kill_after.lock(),
+            Some(std::time::Duration::from_secs(45 * 60))
+        );
+        session.kill_child();
+    }
+
+    #[tokio::test]
+    async fn test_template_crud_and_create_from_template() {
+        let state = create_empty_state();
+        let sessions = state.sessions.clone();
+        let app = router(state, RouterConfig::default());
+        let request = |method: &str, uri: &str, body: serde_json::Value| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let template = serde_json::json!({
+            "command": "sleep 30",
+            "env": {"WSH_TEMPLATE": "yes", "LEVEL": "info"},
+            "rows": 30,
+            "tags": ["build"],
+            "panels": [{"position": "top", "height": 1, "spans": [{"text": "build-env"}]}],
+        });
+        let response = app.clone().oneshot(request("PUT", "/templates/build-env", template.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let response = app.clone().oneshot(request("PUT", "/templates/build-env", template)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app.clone().oneshot(request("PUT", "/templates/bad%20name", serde_json::json!({}))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app.clone().oneshot(request("GET", "/templates", serde_json::Value::Null)).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["templates"][0]["name"], "build-env");
+        assert_eq!(json["templates"][0]["rows"], 30);
+
+        let body = serde_json::json!({"name": "b1", "template": "build-env", "env": {"LEVEL": "debug"}, "tags": ["mine"]});
+        let response = app.clone().oneshot(request("POST", "/sessions", body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let session = sessions.get("b1").unwrap();
+        // The panel takes one of the template's 30 rows.
+        assert_eq!(session.terminal_size.get(), (30, 80));
+        assert_eq!(session.panels.list().len(), 1);
+        let mut tags: Vec<String> = session.tags.read().iter().cloned().collect();
+        tags.sort();
+        assert_eq!(tags, vec!["build", "mine"]);
+        session.kill_child();
+
+        let body = serde_json::json!({"template": "missing"});
+        let response = app.clone().oneshot(request("POST", "/sessions", body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = app.clone().oneshot(request("DELETE", "/templates/build-env", serde_json::Value::Null)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = app.oneshot(request("GET", "/templates/build-env", serde_json::Value::Null)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_session_scrollback_lines_create_and_update() {
+        let state = create_empty_state();
+        let sessions = state.sessions.clone();
+        sessions.set_scrollback_lines(300);
+        let app = router(state, RouterConfig::default());
+        let request = |method: &str, uri: &str, body: serde_json::Value| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let info = |response: axum::response::Response| async move {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(request("POST", "/sessions", serde_json::json!({"scrollback_lines": 2_000_000})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = serde_json::json!({"name": "default-size", "command": "sleep 30"});
+        let response = app.clone().oneshot(request("POST", "/sessions", body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(info(response).await["scrollback_lines"], 300);
+
+        let body = serde_json::json!({"name": "deep", "command": "seq 1 200; sleep 30", "scrollback_lines": 5000});
+        let response = app.clone().oneshot(request("POST", "/sessions", body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(info(response).await["scrollback_lines"], 5000);
+        let deep = sessions.get("deep").unwrap();
+        let total_lines = || async {
+            match deep.parser.query(crate::parser::state::Query::Screen { format: Default::default(), since: None }).await {
+                Ok(crate::parser::state::QueryResponse::Screen(screen)) => screen.total_lines,
+                _ => 0,
+            }
+        };
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while total_lines().await < 200 && std::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+
+        let response = app
+            .clone()
+            .oneshot(request("PATCH", "/sessions/deep", serde_json::json!({"scrollback_lines": 50})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(info(response).await["scrollback_lines"], 50);
+        assert_eq!(total_lines().await, 50 + 24);
+
+        let response = app
+            .oneshot(request("PATCH", "/sessions/deep", serde_json::json!({"scrollback_lines": 2_000_000})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        for name in ["default-size", "deep"] {
+            sessions.get(name).unwrap().kill_child();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_create_claims_warm_pool_shell() {
+        let state = create_empty_state();
+        let sessions = state.sessions.clone();
+        let app = router(state, RouterConfig::default());
+        let post = |body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri("/sessions")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let wait_ready = || async {
+            let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+            while sessions.warm_pool_ready() < 1 && std::time::Instant::now() < deadline {
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+            assert_eq!(sessions.warm_pool_ready(), 1);
+        };
+        // The message of the day is written when a shell is spawned, so it
+        // tells pooled shells from fresh ones. The parser may not have
+        // caught up with it yet, so wait until `expected` shows up.
+        let first_lines = |name: &'static str, expected: &'static str| {
+            let session = sessions.get(name).unwrap();
+            async move {
+                let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+                loop {
+                    let scrollback = session
+                        .parser
+                        .query(crate::parser::state::Query::Scrollback {
+                            format: crate::parser::state::Format::Plain,
+                            offset: 0,
+                            limit: 3,
+                        })
+                        .await
+                        .unwrap();
+                    let text = serde_json::to_string(&scrollback).unwrap();
+                    if text.contains(expected) || std::time::Instant::now() >= deadline {
+                        return text;
+                    }
+                    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                }
+            }
+        };
+
+        sessions.set_motd(Some("pooled".to_string()));
+        sessions.set_warm_pool_size(1);
+        wait_ready().await;
+        sessions.set_motd(Some("fresh".to_string()));
+
+        let body = serde_json::json!({"name": "warm", "tags": ["agent"], "rows": 30, "cols": 100});
+        let response = app.clone().oneshot(post(body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let warm = sessions.get("warm").unwrap();
+        assert_eq!(warm.name, "warm");
+        assert!(warm.tags.read().contains("agent"));
+        assert_eq!(warm.terminal_size.get(), (30, 100));
+        assert!(first_lines("warm", "pooled").await.contains("pooled"));
+
+        // The pool refills, and requests it can't serve spawn as usual.
+        wait_ready().await;
+        let body = serde_json::json!({"name": "custom", "command": "sleep 30"});
+        let response = app.oneshot(post(body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(first_lines("custom", "fresh").await.contains("fresh"));
+        assert_eq!(sessions.warm_pool_ready(), 1);
+
+        let kill_handle = sessions.drain().unwrap();
+        assert_eq!(sessions.warm_pool_ready(), 0);
+        kill_handle.abort();
+        warm.kill_child();
+    }
+
+    #[tokio::test]
+    async fn test_session_encoding_decodes_parser_input_only() {
+        let state = create_empty_state();
+        let sessions = state.sessions.clone();
+        let app = router(state, RouterConfig::default());
+        let request = |method: &str, uri: &str, body: serde_json::Value| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let json = |response: axum::response::Response| async move {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+
+        let body = serde_json::json!({
+            "name": "legacy",
+            "command": "sleep 0.5; printf 'caf\\351\\n'; sleep 30",
+            "encoding": "latin-1",
+        });
+        let response = app.clone().oneshot(request("POST", "/sessions", body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(json(response).await["encoding"], "latin-1");
+
+        // Attached terminals get the program's bytes unchanged.
+        let session = sessions.get("legacy").unwrap();
+        let mut output = session.output_rx.subscribe();
+        let mut raw = Vec::new();
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while !raw.ends_with(b"caf\xe9\r\n") {
+                raw.extend_from_slice(&output.recv().await.unwrap());
+            }
+        })
+        .await
+        .expect("raw output");
+
+        let screen = || async {
+            let uri = "/sessions/legacy/screen?format=plain";
+            let response = app.clone().oneshot(request("GET", uri, serde_json::Value::Null)).await.unwrap();
+            json(response).await["lines"][0].as_str().unwrap_or_default().to_string()
+        };
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while screen().await != "café" && std::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(screen().await, "café");
+
+        let response = app
+            .clone()
+            .oneshot(request("PATCH", "/sessions/legacy", serde_json::json!({"encoding": "windows-1252"})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(json(response).await["encoding"], "windows-1252");
+        assert_eq!(session.parser.encoding(), crate::parser::encoding::Encoding::Windows1252);
+
+        let body = serde_json::json!({"name": "bad", "command": "true", "encoding": "ebcdic"});
+        let response = app.oneshot(request("POST", "/sessions", body)).await.unwrap();
+        assert!(response.status().is_client_error());
+        assert!(sessions.get("bad").is_none());
+        session.kill_child();
+    }
+
+    #[tokio::test]
+    async fn test_session_metadata_merges_and_filters_list() {
+        let (state, _input_rx, _name) = create_test_state();
+        let app = router(state, RouterConfig::default());
+        let request = |method: &str, uri: &str, body: serde_json::Value| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+        let json = |response: axum::response::Response| async move {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+
+        let patch = serde_json::json!({"metadata": {"run": "run 42", "attempt": 1, "stale": true}});
+        let response = request("PATCH", "/sessions/test", patch).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let patch = serde_json::json!({"metadata": {"attempt": 2, "stale": null}});
+        let response = request("PATCH", "/sessions/test", patch).await.unwrap();
+        assert_eq!(json(response).await["metadata"], serde_json::json!({"run": "run 42", "attempt": 2}));
+
+        let list = |uri: &'static str| async move {
+            let response = request("GET", uri, serde_json::Value::Null).await.unwrap();
+            json(response).await.as_array().unwrap().len()
+        };
+        assert_eq!(list("/sessions?meta.run=run%2042&meta.attempt=2").await, 1);
+        assert_eq!(list("/sessions?meta.attempt=1").await, 0);
+        assert_eq!(list("/sessions?meta.owner=x").await, 0);
+
+        let patch = serde_json::json!({"metadata": {"": 1}});
+        let response = request("PATCH", "/sessions/test", patch).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_session_list_filters_sorts_and_pages() {
+        let (state, _input_rx, _name) = create_test_state();
+        let test = state.sessions.get("test").unwrap();
+        for (name, command, created_at) in [("a", "vim notes", 2000), ("b", "cargo build", 1000)] {
+            let mut session = test.clone();
+            session.command = command.to_string();
+            session.created_at = created_at;
+            session.owner = Default::default();
+            if name == "b" {
+                *session.owner.lock() = Some(crate::session::Owner {
+                    token: Some("ci".to_string()),
+                    ..Default::default()
+                });
+            }
+            state.sessions.insert(Some(name.to_string()), session).unwrap();
+        }
+        let app = router(state, RouterConfig::default());
+        let list = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                    .await
+                    .unwrap();
+                let status = response.status();
+                let total = response
+                    .headers()
+                    .get("x-total-count")
+                    .map(|v| v.to_str().unwrap().to_string());
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                let names: Vec<String> = json
+                    .as_array()
+                    .map(|a| a.iter().map(|s| s["name"].as_str().unwrap().to_string()).collect())
+                    .unwrap_or_default();
+                (status, total, names)
+            }
+        };
+
+        let (_, total, names) = list("/sessions").await;
+        assert_eq!((total.as_deref(), names), (Some("3"), vec!["a".into(), "b".into(), "test".into()]));
+        let (_, _, names) = list("/sessions?sort=-created_at").await;
+        assert_eq!(names, ["a", "b", "test"]);
+        let (_, total, names) = list("/sessions?sort=created_at&offset=1&limit=1").await;
+        assert_eq!((total.as_deref(), names), (Some("3"), vec!["b".into()]));
+        let (_, total, names) = list("/sessions?command=cargo").await;
+        assert_eq!((total.as_deref(), names), (Some("1"), vec!["b".into()]));
+        let (_, _, names) = list("/sessions?owner=ci").await;
+        assert_eq!(names, ["b"]);
+        let (_, _, names) = list("/sessions?created_after=1000&created_before=1500").await;
+        assert_eq!(names, ["b"]);
+        let (_, _, names) = list("/sessions?max_idle_ms=600000&max_clients=0").await;
+        assert_eq!(names.len(), 3);
+        let (_, _, names) = list("/sessions?min_idle_ms=600000").await;
+        assert!(names.is_empty());
+        let (_, _, names) = list("/sessions?min_clients=1").await;
+        assert!(names.is_empty());
+        let (status, _, _) = list("/sessions?sort=pid").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_session_info_reports_title_and_cwd() {
+        let state = create_empty_state();
+        let sessions = state.sessions.clone();
+        let app = router(state, RouterConfig::default());
+        let body = serde_json::json!({
+            "name": "titled",
+            "command": "printf '\\033]0;deploy\\007\\033]7;file://host/srv/my%%20app\\007'; sleep 30",
+        });
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/sessions")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let list = || async {
+            let response = app
+                .clone()
+                .oneshot(Request::builder().uri("/sessions").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            json[0].clone()
+        };
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while list().await["cwd"].is_null() && std::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let info = list().await;
+        assert_eq!(info["title"], "deploy");
+        assert_eq!(info["cwd"], "/srv/my app");
+        sessions.get("titled").unwrap().kill_child();
+    }
+
+    #[tokio::test]
+    async fn test_scrollback_export_formats() {
+        let state = create_empty_state();
+        let sessions = state.sessions.clone();
+        let app = router(state, RouterConfig::default());
+        let body = serde_json::json!({
+            "name": "log",
+            "command": "seq 1 2500; printf '\\033[31mred\\033[0m <done>\\n'; sleep 30",
+        });
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/sessions")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let export = |format: &'static str| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(
+                        Request::builder()
+                            .uri(format!("/sessions/log/scrollback/export?format={format}"))
+                            .body(Body::empty())
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                let status = response.status();
+                let headers = response.headers().clone();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, headers, String::from_utf8(body.to_vec()).unwrap())
+            }
+        };
+
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+        let plain = loop {
+            let (status, headers, body) = export("plain").await;
+            assert_eq!(status, StatusCode::OK);
+            if body.contains("<done>") || tokio::time::Instant::now() >= deadline {
+                assert_eq!(
+                    headers["content-disposition"],
+                    "attachment; filename=\"log-scrollback.txt\""
+                );
+                break body;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        };
+        let numbers: Vec<&str> = plain.lines().take(2500).collect();
+        let expected: Vec<String> = (1..=2500).map(|n| n.to_string()).collect();
+        assert_eq!(numbers, expected);
+        assert!(plain.contains("red <done>\n"));
+
+        let (_, _, ansi) = export("ansi").await;
+        assert!(ansi.contains("\x1b[31mred\x1b[0m <done>\n"), "ansi: {ansi:?}");
+
+        let (_, headers, html) = export("html").await;
+        assert_eq!(headers["content-type"], "text/html; charset=utf-8");
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<span style=\"color:#cd0000\">red</span> &lt;done&gt;\n"));
+        assert!(html.ends_with("</html>\n"));
+
+        let (status, _, _) = export("pdf").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        sessions.get("log").unwrap().kill_child();
+    }
+
+    #[tokio::test]
+    async fn test_screen_png() {
+        let state = create_empty_state();
+        let sessions = state.sessions.clone();
+        let app = router(state, RouterConfig::default());
+        let body = serde_json::json!({
+            "name": "pic",
+            "command": "printf '\\033[41mhello\\033[0m'; sleep 30",
+            "cols": 20,
+            "rows": 5,
+        });
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/sessions")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/sessions/pic/screen.png")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "image/png");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let reader = png::Decoder::new(&body[..]).read_info().unwrap();
+        let info = reader.info();
+        assert_eq!(
+            (info.width, info.height),
+            (20 * crate::parser::CELL_WIDTH_PX as u32, 5 * crate::parser::CELL_HEIGHT_PX as u32)
+        );
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/sessions/nope/screen.png")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        sessions.get("pic").unwrap().kill_child();
+    }
+
+    #[tokio::test]
+    async fn test_session_query_policy_answers_and_strips_replies() {
+        let state = create_empty_state();
+        let sessions = state.sessions.clone();
+        let app = router(state, RouterConfig::default());
+        let request = |method: &str, uri: &str, body: String| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+        let wait_for_screen = |name: &'static str, needle: &'static str| {
+            let session = sessions.get(name).unwrap();
+            async move {
+                let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+                loop {
+                    let screen = session
+                        .parser
+                        .query(crate::parser::state::Query::Screen {
+                            format: crate::parser::state::Format::Plain,
+                            since: None,
+                        })
+                        .await
+                        .unwrap();
+                    let text = serde_json::to_string(&screen).unwrap();
+                    if text.contains(needle) || tokio::time::Instant::now() >= deadline {
+                        break text;
+                    }
+                    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                }
+            }
+        };
+
+        // The parser answers the status query the program prints.
+        let body = serde_json::json!({
+            "name": "answered",
+            "command": "stty -icanon -echo min 1; printf 'ready\\033[5n'; head -c 4 | od -An -c; sleep 30",
+            "query_policy": {"dsr": "synthetic"},
+        });
+        let response = app.clone().oneshot(request("POST", "/sessions", body.to_string())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let info: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(info["query_policy"]["dsr"], "synthetic");
+        assert_eq!(info["query_policy"]["da1"], "passthrough");
+        let text = wait_for_screen("answered", "033").await;
+        assert!(text.contains("033   [   0   n"), "screen: {text}");
+
+        // Client replies are dropped once the policy stops passing them through.
+        let body = serde_json::json!({
+            "name": "stripped",
+            "command": "stty -icanon -echo min 1; printf 'ready'; head -c 3 | od -An -c; sleep 30",
+        });
+        let response = app.clone().oneshot(request("POST", "/sessions", body.to_string())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        wait_for_screen("stripped", "ready").await;
+        let body = serde_json::json!({"query_policy": {"dsr": "block", "da1": "block"}});
+        let response = app.clone().oneshot(request("PATCH", "/sessions/stripped", body.to_string())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let input = "\x1b[?62;22ca\x1b[3;7Rb\x1b[0nc".to_string();
+        let response = app.clone().oneshot(request("POST", "/sessions/stripped/input", input)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let text = wait_for_screen("stripped", "a   b   c").await;
+        assert!(text.contains("a   b   c"), "screen: {text}");
+
+        let body = serde_json::json!({"query_policy": {"dsr": "sometimes"}});
+        let response = app.oneshot(request("PATCH", "/sessions/stripped", body.to_string())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        for name in ["answered", "stripped"] {
+            sessions.get(name).unwrap().kill_child();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ssh_session_connection_failure_is_reported() {
+        let state = create_empty_state();
+        let sessions = state.sessions.clone();
+        let app = router(state, RouterConfig::default());
+        let post = |body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri("/sessions")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(post(serde_json::json!({"remote": "-oProxyCommand=evil"})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        if std::process::Command::new("ssh").arg("-V").output().is_err() {
+            eprintln!("skipping: ssh not installed");
+            return;
+        }
+        let response = app
+            .oneshot(post(serde_json::json!({"name": "web", "command": "ssh://127.0.0.1:1"})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["error"]["code"], "ssh_connect_failed");
+        assert!(
+            json["error"]["message"].as_str().unwrap().contains("Connection refused"),
+            "{json}"
+        );
+        assert!(sessions.get("web").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stats_reports_usage_counters() {
+        let state = create_empty_state();
+        let (session, _child_exit_rx) = crate::session::Session::spawn(
+            "usage".to_string(),
+            crate::pty::SpawnCommand::Command {
+                command: "cat".to_string(),
+                interactive: false,
+            },
+            24,
+            80,
+        )
+        .unwrap();
+        state.sessions.insert(Some("usage".into()), session.clone()).unwrap();
+        let app = router(state, RouterConfig::default());
+
+        let guard = session.connect().unwrap();
+        session.input_tx.send(Bytes::from_static(b"hello\r")).await.unwrap();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while session.usage.snapshot().output_bytes == 0 && std::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        drop(guard);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/sessions/usage/stats?interval_ms=1")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let usage = &json["usage"];
+        assert_eq!(usage["commands_run"], 1);
+        assert_eq!(usage["input_bytes"], 6);
+        assert!(usage["output_bytes"].as_u64().unwrap() > 0);
+        assert!(usage["attached_ms"].is_u64());
+        assert_eq!(usage["mcp_tool_calls"], 0);
+        session.kill_child();
+    }
+
+    #[tokio::test]
+    async fn test_resize_applies_size_and_policy() {
+        let (state, _input_rx, _name) = create_test_state();
+        let sessions = state.sessions.clone();
+        let app = router(state, RouterConfig::default());
+
+        let body = serde_json::json!({"rows": 40, "cols": 120, "policy": "fixed"});
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/sessions/test/resize")
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_string(&body).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["rows"], 40);
+        assert_eq!(json["cols"], 120);
+        assert_eq!(json["policy"], "fixed");
+
+        let session = sessions.get("test").unwrap();
+        assert_eq!(session.terminal_size.get(), (40, 120));
+        assert_eq!(
+            session.size_arbiter.policy(),
+            crate::terminal::ResizePolicy::Fixed
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resize_rejects_zero_size() {
+        let (state, _input_rx, _name) = create_test_state();
+        let app = router(state, RouterConfig::default());
+
+        let body = serde_json::json!({"rows": 0, "cols": 80});
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/sessions/test/resize")
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_string(&body).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_resize_nonexistent_session_404() {
+        let (state, _input_rx, _name) = create_test_state();
+        let app = router(state, RouterConfig::default());
+
+        let body = serde_json::json!({"rows": 24, "cols": 80});
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/sessions/nope/resize")
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_string(&body).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_session_patch_resize_policy() {
+        let (state, _input_rx, _name) = create_test_state();
+        let app = router(state, RouterConfig::default());
+
+        let body = serde_json::json!({"resize_policy": "largest_client"});
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("PATCH")
+                    .uri("/sessions/test")
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_string(&body).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["resize_policy"], "largest_client");
+    }
+
     // ── Base prefix tests ──────────────────────────────────────────
 
     #[tokio::test]
@@ -2632,4 +5557,48 @@ mod tests {
             .unwrap();
         assert_ne!(response.status(), StatusCode::NOT_FOUND, "prefixed session route should be found");
     }
+
+    #[test]
+    fn test_split_qualified_name() {
+        let split = |uri: &str| {
+            let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            let req = split_qualified_name("hub", req);
+            let remote = req.extensions().get::<QualifiedServer>().is_some();
+            (req.uri().to_string(), remote)
+        };
+        assert_eq!(split("/sessions/hub:dev/screen"), ("/sessions/dev/screen".into(), false));
+        assert_eq!(
+            split("/sessions/b1:dev/screen?format=plain&server=x"),
+            ("/sessions/dev/screen?format=plain&server=b1".into(), true)
+        );
+        assert_eq!(split("/sessions/b1%3Adev"), ("/sessions/dev?server=b1".into(), true));
+        assert_eq!(split("/sessions/dev/screen"), ("/sessions/dev/screen".into(), false));
+        assert_eq!(split("/sessions/:dev"), ("/sessions/:dev".into(), false));
+        // Not a hostname: nothing leaks into the query string.
+        assert_eq!(split("/sessions/b1&token=x:dev"), ("/sessions/b1&token=x:dev".into(), false));
+        assert_eq!(split("/sessions/b1%26x=1:dev"), ("/sessions/b1%26x=1:dev".into(), false));
+        assert_eq!(split("/servers/b1:x"), ("/servers/b1:x".into(), false));
+    }
+
+    #[tokio::test]
+    async fn test_qualified_name_is_refused_to_tag_restricted_tokens() {
+        let (state, _input_rx, _) = create_test_state();
+        let tokens = state.tokens.clone();
+        let app = router(state, RouterConfig { token: Some("root".into()), ..Default::default() });
+        let (_, restricted) = tokens
+            .create("ci".into(), crate::api::tokens::TokenScope::ReadOnly, vec!["ci".into()])
+            .unwrap();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/sessions/elsewhere:test/screen")
+                    .header("authorization", format!("Bearer {}", restricted))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
 }
@@ -108,6 +108,35 @@ pub(super) async fn proxy_post_bytes(
     Ok(StatusCode::from_u16(resp.status().as_u16()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR))
 }
 
+/// Proxy a GET request whose response is not JSON, such as a file
+/// download, to a backend server.
+///
+/// Returns the HTTP status code and the raw body from the backend.
+pub(super) async fn proxy_get_bytes(
+    backend: &BackendEntry,
+    path: &str,
+) -> Result<(StatusCode, bytes::Bytes), ApiError> {
+    let url = backend.url_for(path);
+    let client = build_client()?;
+
+    let mut req = client.get(&url);
+    if let Some(ref token) = backend.token {
+        req = req.bearer_auth(token);
+    }
+
+    let resp = req
+        .send()
+        .await
+        .map_err(|e| ApiError::ServerUnavailable(format!("{}: {}", backend.address, e)))?;
+
+    let status = StatusCode::from_u16(resp.status().as_u16()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let body = resp
+        .bytes()
+        .await
+        .map_err(|e| ApiError::ServerUnavailable(format!("{}: {}", backend.address, e)))?;
+    Ok((status, body))
+}
+
 /// Proxy a DELETE request to a backend server.
 ///
 /// Returns the HTTP status code from the backend.
@@ -161,3 +190,40 @@ pub(super) async fn proxy_patch(
 
     Ok((status, body))
 }
+
+/// Forward a request to a backend server as-is: method, path and query,
+/// content type, and body.
+///
+/// Returns the backend's status, content type, and raw body, so any
+/// endpoint can be proxied without knowing its request or response shape.
+pub(super) async fn proxy_request(
+    backend: &BackendEntry,
+    method: axum::http::Method,
+    path: &str,
+    content_type: Option<axum::http::HeaderValue>,
+    body: bytes::Bytes,
+) -> Result<(StatusCode, Option<axum::http::HeaderValue>, bytes::Bytes), ApiError> {
+    let url = backend.url_for(path);
+    let client = build_client()?;
+
+    let mut req = client.request(method, &url).body(body);
+    if let Some(content_type) = content_type {
+        req = req.header(reqwest::header::CONTENT_TYPE, content_type);
+    }
+    if let Some(ref token) = backend.token {
+        req = req.bearer_auth(token);
+    }
+
+    let resp = req
+        .send()
+        .await
+        .map_err(|e| ApiError::ServerUnavailable(format!("{}: {}", backend.address, e)))?;
+
+    let status = StatusCode::from_u16(resp.status().as_u16()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let content_type = resp.headers().get(reqwest::header::CONTENT_TYPE).cloned();
+    let body = resp
+        .bytes()
+        .await
+        .map_err(|e| ApiError::ServerUnavailable(format!("{}: {}", backend.address, e)))?;
+    Ok((status, content_type, body))
+}
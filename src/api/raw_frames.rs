@@ -0,0 +1,138 @@
+//! Framed `/ws/raw` protocol.
+//!
+//! A `/ws/raw` client that negotiates the `wsh-framed` subprotocol gets
+//! length-prefixed frames in Binary messages instead of bare PTY output, so
+//! one socket can also carry the session's size and its end:
+//!
+//! ```text
+//! +------+----------------+-------------+
+//! | type | length (u32 BE)| payload     |
+//! +------+----------------+-------------+
+//! ```
+//!
+//! | Type | Frame  | Payload |
+//! |------|--------|---------|
+//! | 0x01 | Data   | PTY output (server to client) or input (client to server) |
+//! | 0x02 | Resize | rows and cols, each a `u16` BE |
+//! | 0x03 | Exit   | why the session ended, as UTF-8: `exited`, `killed` or `shutdown` |
+//!
+//! The server sends one frame per message: a Resize with the current size
+//! on connect and whenever the size changes, Data for output, and an Exit
+//! before closing. A client sends Data for input and Resize for its own
+//! terminal size, which goes through the session's resize policy like an
+//! attached CLI client's. A message may hold several frames back to back.
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// WebSocket subprotocol that selects framed output on `/ws/raw`.
+pub const SUBPROTOCOL: &str = "wsh-framed";
+
+const DATA: u8 = 0x01;
+const RESIZE: u8 = 0x02;
+const EXIT: u8 = 0x03;
+
+/// Bytes before each frame's payload: the type and the length.
+const HEADER_LEN: usize = 5;
+
+/// One frame of the `wsh-framed` protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    /// Terminal output, or input from the client.
+    Data(Bytes),
+    /// The session's size, or the client's.
+    Resize { rows: u16, cols: u16 },
+    /// The session ended; the payload says why.
+    Exit(String),
+}
+
+impl Frame {
+    /// Encode the frame with its header.
+    pub fn encode(&self) -> Bytes {
+        let (kind, payload) = match self {
+            Frame::Data(data) => (DATA, data.clone()),
+            Frame::Resize { rows, cols } => {
+                let mut buf = BytesMut::with_capacity(4);
+                buf.put_u16(*rows);
+                buf.put_u16(*cols);
+                (RESIZE, buf.freeze())
+            }
+            Frame::Exit(reason) => (EXIT, Bytes::copy_from_slice(reason.as_bytes())),
+        };
+        let mut buf = BytesMut::with_capacity(HEADER_LEN + payload.len());
+        buf.put_u8(kind);
+        buf.put_u32(payload.len() as u32);
+        buf.put_slice(&payload);
+        buf.freeze()
+    }
+
+    /// Decode every frame in a message.
+    pub fn decode_all(mut buf: &[u8]) -> Result<Vec<Frame>, String> {
+        let mut frames = Vec::new();
+        while !buf.is_empty() {
+            if buf.len() < HEADER_LEN {
+                return Err(format!("truncated frame header ({} bytes)", buf.len()));
+            }
+            let kind = buf[0];
+            let len = u32::from_be_bytes([buf[1], buf[2], buf[3], buf[4]]) as usize;
+            let Some(payload) = buf.get(HEADER_LEN..HEADER_LEN + len) else {
+                return Err(format!(
+                    "frame payload is {} bytes, message has {}",
+                    len,
+                    buf.len() - HEADER_LEN
+                ));
+            };
+            frames.push(match kind {
+                DATA => Frame::Data(Bytes::copy_from_slice(payload)),
+                RESIZE => match payload {
+                    [r0, r1, c0, c1] => Frame::Resize {
+                        rows: u16::from_be_bytes([*r0, *r1]),
+                        cols: u16::from_be_bytes([*c0, *c1]),
+                    },
+                    _ => return Err(format!("resize payload must be 4 bytes, got {}", len)),
+                },
+                EXIT => Frame::Exit(String::from_utf8_lossy(payload).into_owned()),
+                other => return Err(format!("unknown frame type 0x{:02x}", other)),
+            });
+            buf = &buf[HEADER_LEN + len..];
+        }
+        Ok(frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_round_trip() {
+        let frames = [
+            Frame::Data(Bytes::from_static(b"\x1b[1mhi\r\n")),
+            Frame::Data(Bytes::new()),
+            Frame::Resize { rows: 50, cols: 300 },
+            Frame::Exit("exited".to_string()),
+        ];
+        let mut message = Vec::new();
+        for frame in &frames {
+            message.extend_from_slice(&frame.encode());
+        }
+        assert_eq!(Frame::decode_all(&message).unwrap(), frames);
+    }
+
+    #[test]
+    fn encoding_is_type_then_big_endian_length() {
+        assert_eq!(
+            &Frame::Resize { rows: 24, cols: 80 }.encode()[..],
+            &[0x02, 0, 0, 0, 4, 0, 24, 0, 80]
+        );
+        assert_eq!(&Frame::Data(Bytes::from_static(b"ok")).encode()[..], b"\x01\0\0\0\x02ok");
+    }
+
+    #[test]
+    fn malformed_messages_are_rejected() {
+        assert!(Frame::decode_all(&[0x01, 0, 0]).is_err());
+        assert!(Frame::decode_all(&[0x01, 0, 0, 0, 9, b'a']).is_err());
+        assert!(Frame::decode_all(&[0x02, 0, 0, 0, 2, 0, 1]).is_err());
+        assert!(Frame::decode_all(&[0x7f, 0, 0, 0, 0]).is_err());
+        assert_eq!(Frame::decode_all(&[]).unwrap(), vec![]);
+    }
+}
@@ -0,0 +1,82 @@
+//! Resource usage of the server process, for `GET /server/info`.
+
+use serde::Serialize;
+
+/// A snapshot of the server process's resource consumption.
+///
+/// Fields the platform can't report are omitted.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResourceUsage {
+    /// CPU time spent in user mode, in milliseconds.
+    pub cpu_user_ms: u64,
+    /// CPU time spent in the kernel, in milliseconds.
+    pub cpu_system_ms: u64,
+    /// Peak resident set size, in bytes.
+    pub max_rss_bytes: u64,
+    /// Current resident set size, in bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rss_bytes: Option<u64>,
+    /// Number of open file descriptors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_fds: Option<usize>,
+    /// Number of OS threads.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threads: Option<usize>,
+}
+
+impl ResourceUsage {
+    /// Sample the current process.
+    pub fn sample() -> Self {
+        let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+        // SAFETY: `usage` is a valid out-pointer; getrusage only writes to it.
+        // On failure the zeroed value is used.
+        unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) };
+        let usage = unsafe { usage.assume_init() };
+        let millis = |tv: libc::timeval| tv.tv_sec as u64 * 1000 + tv.tv_usec as u64 / 1000;
+
+        // ru_maxrss is kilobytes on Linux and bytes on macOS. The two RSS
+        // figures come from different sources, so keep the peak at least as
+        // large as the current value.
+        let max_rss = usage.ru_maxrss.max(0) as u64;
+        let max_rss_bytes = if cfg!(target_os = "macos") { max_rss } else { max_rss * 1024 };
+        let rss_bytes = current_rss();
+
+        Self {
+            cpu_user_ms: millis(usage.ru_utime),
+            cpu_system_ms: millis(usage.ru_stime),
+            max_rss_bytes: max_rss_bytes.max(rss_bytes.unwrap_or(0)),
+            rss_bytes,
+            open_fds: count_dir("/proc/self/fd").or_else(|| count_dir("/dev/fd")),
+            threads: count_dir("/proc/self/task"),
+        }
+    }
+}
+
+/// Resident set size from `/proc/self/statm` (Linux only).
+fn current_rss() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    // SAFETY: sysconf has no preconditions.
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    (page_size > 0).then(|| pages * page_size as u64)
+}
+
+fn count_dir(path: &str) -> Option<usize> {
+    Some(std::fs::read_dir(path).ok()?.count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_reports_memory_and_descriptors() {
+        let usage = ResourceUsage::sample();
+        assert!(usage.max_rss_bytes > 0);
+        if cfg!(target_os = "linux") {
+            assert!(usage.rss_bytes.unwrap() > 0);
+            assert!(usage.open_fds.unwrap() >= 3);
+            assert!(usage.threads.unwrap() >= 1);
+        }
+    }
+}
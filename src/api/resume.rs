@@ -0,0 +1,274 @@
+//! Resumable `ws_json` connections.
+//!
+//! Every per-session `ws_json` connection is issued a resume token in its
+//! `connected` message. If the socket drops without a close frame, the
+//! connection is kept for [`RESUME_GRACE`]: its subscription stays in place
+//! and messages for the client are buffered. Reconnecting with
+//! `?resume=<token>` within that window picks the connection back up. The
+//! client gets `{"connected": true, "resumed": true, ...}` followed by the
+//! messages it missed, or, if more than [`RESUME_BUFFER_BYTES`] piled up,
+//! a `lagged` message and a fresh `sync`, as when a live subscriber falls
+//! behind.
+//!
+//! A resume also takes over a connection the server still thinks is live,
+//! so a client on a half-open socket doesn't have to wait for keepalives to
+//! notice.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
+use futures::stream::{SplitSink, SplitStream};
+use futures::{SinkExt, StreamExt};
+use parking_lot::Mutex;
+use rand::Rng;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use super::handlers::WS_SEND_TIMEOUT;
+
+/// How long a dropped connection waits for its client to come back.
+pub const RESUME_GRACE: Duration = Duration::from_secs(30);
+
+/// Most message bytes buffered for a dropped connection. Past this the
+/// buffer is discarded and the client resyncs from a snapshot on resume.
+pub const RESUME_BUFFER_BYTES: usize = 1024 * 1024;
+
+/// Connections of one session that can be resumed, by token.
+#[derive(Clone, Default)]
+pub struct ResumeStore {
+    inner: Arc<Mutex<HashMap<String, mpsc::Sender<WebSocket>>>>,
+}
+
+impl ResumeStore {
+    /// Issue a token for a new connection. The socket of a client resuming
+    /// with it arrives on the returned receiver. The token stops working
+    /// when the [`Registration`] is dropped.
+    fn register(&self) -> (Registration, mpsc::Receiver<WebSocket>) {
+        let token: String = rand::thread_rng()
+            .sample_iter(&rand::distributions::Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+        let (tx, rx) = mpsc::channel(1);
+        self.inner.lock().insert(token.clone(), tx);
+        let registration = Registration {
+            store: self.clone(),
+            token,
+        };
+        (registration, rx)
+    }
+
+    /// Where to hand the socket of a client resuming with `token`, if the
+    /// connection is still around.
+    pub fn resume(&self, token: &str) -> Option<mpsc::Sender<WebSocket>> {
+        self.inner.lock().get(token).cloned()
+    }
+
+    /// Number of connections that can be resumed.
+    pub fn len(&self) -> usize {
+        self.inner.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Keeps a resume token valid; removes it from the store on drop.
+struct Registration {
+    store: ResumeStore,
+    token: String,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.store.inner.lock().remove(&self.token);
+    }
+}
+
+/// What [`ResumableSocket::recv`] got.
+pub(super) enum Incoming {
+    /// A message from the client.
+    Message(Message),
+    /// The client came back on this socket; pass it to
+    /// [`ResumableSocket::attach`].
+    Resume(Box<WebSocket>),
+    /// The client went away and didn't come back in time.
+    Closed,
+}
+
+enum Wake {
+    Client(Option<Result<Message, axum::Error>>),
+    Resume(Box<WebSocket>),
+    Expired,
+}
+
+/// The client end of a `ws_json` connection, surviving socket drops.
+/// While no socket is attached the connection is "parked": outgoing
+/// messages are buffered until the client resumes or the grace period
+/// runs out.
+pub(super) struct ResumableSocket {
+    registration: Registration,
+    resume_rx: mpsc::Receiver<WebSocket>,
+    tx: Option<SplitSink<WebSocket, Message>>,
+    rx: Option<SplitStream<WebSocket>>,
+    parked_at: Option<Instant>,
+    buffer: Vec<Message>,
+    buffered_bytes: usize,
+    dropped: usize,
+}
+
+impl ResumableSocket {
+    pub(super) fn new(socket: WebSocket, store: &ResumeStore) -> Self {
+        let (registration, resume_rx) = store.register();
+        let (tx, rx) = socket.split();
+        Self {
+            registration,
+            resume_rx,
+            tx: Some(tx),
+            rx: Some(rx),
+            parked_at: None,
+            buffer: Vec::new(),
+            buffered_bytes: 0,
+            dropped: 0,
+        }
+    }
+
+    pub(super) fn token(&self) -> &str {
+        &self.registration.token
+    }
+
+    pub(super) fn is_parked(&self) -> bool {
+        self.parked_at.is_some()
+    }
+
+    /// Detach the socket and start buffering.
+    pub(super) fn park(&mut self) {
+        if self.parked_at.is_none() {
+            tracing::debug!("ws_json client dropped, holding connection for resume");
+            self.parked_at = Some(Instant::now());
+        }
+        self.tx = None;
+        self.rx = None;
+    }
+
+    /// Send a message, or buffer it while parked. A failed send parks the
+    /// connection and buffers the message.
+    pub(super) async fn send(&mut self, msg: Message) {
+        if let Some(tx) = &mut self.tx {
+            match tokio::time::timeout(WS_SEND_TIMEOUT, tx.send(msg.clone())).await {
+                Ok(Ok(())) => return,
+                Ok(Err(_)) => {}
+                Err(_) => tracing::debug!("ws_json send timed out"),
+            }
+            self.park();
+        }
+        self.hold(msg);
+    }
+
+    fn hold(&mut self, msg: Message) {
+        let len = match &msg {
+            Message::Text(text) => text.len(),
+            Message::Binary(data) => data.len(),
+            // Keepalives and close frames belong to the dead socket.
+            _ => return,
+        };
+        if self.dropped > 0 {
+            self.dropped += 1;
+            return;
+        }
+        self.buffered_bytes += len;
+        self.buffer.push(msg);
+        if self.buffered_bytes > RESUME_BUFFER_BYTES {
+            self.dropped = self.buffer.len();
+            self.buffer.clear();
+            self.buffered_bytes = 0;
+        }
+    }
+
+    /// Wait for the next message from the client, riding out socket drops
+    /// for up to [`RESUME_GRACE`]. Cancel safe.
+    pub(super) async fn recv(&mut self) -> Incoming {
+        loop {
+            let wake = match &mut self.rx {
+                Some(rx) => tokio::select! {
+                    msg = rx.next() => Wake::Client(msg),
+                    Some(socket) = self.resume_rx.recv() => Wake::Resume(Box::new(socket)),
+                },
+                None => {
+                    let deadline = self.parked_at.unwrap_or_else(Instant::now) + RESUME_GRACE;
+                    tokio::select! {
+                        Some(socket) = self.resume_rx.recv() => Wake::Resume(Box::new(socket)),
+                        _ = tokio::time::sleep_until(deadline) => Wake::Expired,
+                    }
+                }
+            };
+            match wake {
+                Wake::Client(Some(Ok(msg))) => return Incoming::Message(msg),
+                Wake::Client(_) => self.park(),
+                Wake::Resume(socket) => return Incoming::Resume(socket),
+                Wake::Expired => {
+                    tracing::debug!("ws_json client did not resume, closing");
+                    return Incoming::Closed;
+                }
+            }
+        }
+    }
+
+    /// Swap in a resuming client's socket and replay what it missed.
+    /// Returns how many messages were lost to buffer overflow; unless it is
+    /// zero the client needs a fresh snapshot.
+    pub(super) async fn attach(&mut self, socket: Box<WebSocket>) -> usize {
+        if let Some(mut old) = self.tx.take() {
+            let _ = tokio::time::timeout(Duration::from_secs(1), old.send(Message::Close(None))).await;
+        }
+        let (tx, rx) = (*socket).split();
+        self.tx = Some(tx);
+        self.rx = Some(rx);
+        self.parked_at = None;
+        let buffer = std::mem::take(&mut self.buffer);
+        let dropped = std::mem::take(&mut self.dropped);
+        self.buffered_bytes = 0;
+        tracing::debug!(replayed = buffer.len(), dropped, "ws_json client resumed");
+
+        let connected = serde_json::json!({
+            "connected": true,
+            "resumed": true,
+            "resume_token": self.token(),
+            "replayed": buffer.len(),
+        });
+        self.send(Message::Text(connected.to_string().into())).await;
+        for msg in buffer {
+            self.send(msg).await;
+        }
+        dropped
+    }
+
+    /// Send a close frame, if a socket is attached.
+    pub(super) async fn close(&mut self, frame: CloseFrame) {
+        if let Some(tx) = &mut self.tx {
+            let _ = tokio::time::timeout(Duration::from_secs(2), tx.send(Message::Close(Some(frame)))).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_stop_working_when_the_connection_ends() {
+        let store = ResumeStore::default();
+        let (registration, _rx) = store.register();
+        let token = registration.token.clone();
+        assert_eq!(token.len(), 32);
+        assert!(store.resume(&token).is_some());
+        assert!(store.resume("other").is_none());
+
+        drop(registration);
+        assert!(store.resume(&token).is_none());
+        assert!(store.is_empty());
+    }
+}
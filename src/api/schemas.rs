@@ -0,0 +1,199 @@
+//! JSON Schemas for the WebSocket and Unix socket protocols.
+//!
+//! The schemas are generated from the types the server deserializes and
+//! serializes, so they stay in step with the code. `GET /schemas` lists
+//! them and `GET /schemas/{name}.json` returns one:
+//!
+//! - `ws.request`, `ws.server_request` and `ws.response` are the envelopes
+//!   of `/sessions/{name}/ws/json` and `/ws/json`, and `ws.event` the events
+//!   a subscription pushes.
+//! - `ws.params.{method}` are the `params` of each method.
+//! - `socket.{frame}` are the JSON payloads of the Unix socket control
+//!   frames, listed with their frame type byte.
+
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use schemars::{JsonSchema, Schema};
+
+use super::error::ApiError;
+use super::ws_methods::*;
+use crate::parser::events::Event;
+use crate::protocol::{self, FrameType};
+
+pub struct Entry {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// For Unix socket messages, the frame type that carries them.
+    pub frame_type: Option<FrameType>,
+    pub schema: fn() -> Schema,
+}
+
+fn schema<T: JsonSchema>() -> Schema {
+    schemars::schema_for!(T)
+}
+
+const fn ws(name: &'static str, description: &'static str, schema: fn() -> Schema) -> Entry {
+    Entry { name, description, frame_type: None, schema }
+}
+
+const fn socket(name: &'static str, frame_type: FrameType, schema: fn() -> Schema) -> Entry {
+    Entry { name, description: "", frame_type: Some(frame_type), schema }
+}
+
+/// Every schema served, in the order `GET /schemas` lists them.
+pub const ENTRIES: &[Entry] = &[
+    ws("ws.request", "Request on a per-session /sessions/{name}/ws/json connection", schema::<WsRequest>),
+    ws("ws.server_request", "Request on the server-level /ws/json connection", schema::<ServerWsRequest>),
+    ws("ws.response", "Response to a request, or a protocol error", schema::<WsResponse>),
+    ws("ws.event", "Event pushed to a subscribed connection", schema::<Event>),
+    ws("ws.params.subscribe", "Params of subscribe", schema::<SubscribeParams>),
+    ws("ws.params.await_idle", "Params of await_idle", schema::<AwaitIdleParams>),
+    ws("ws.params.get_screen", "Params of get_screen", schema::<ScreenParams>),
+    ws("ws.params.get_scrollback", "Params of get_scrollback", schema::<ScrollbackParams>),
+    ws("ws.params.send_input", "Params of send_input", schema::<SendInputParams>),
+    ws("ws.params.send_keys", "Params of send_keys", schema::<SendKeysParams>),
+    ws("ws.params.resize", "Params of resize", schema::<ResizeParams>),
+    ws("ws.params.request_handoff", "Params of request_handoff", schema::<RequestHandoffParams>),
+    ws("ws.params.focus", "Params of focus", schema::<FocusParams>),
+    ws("ws.params.set_focus_order", "Params of set_focus_order", schema::<FocusOrderParams>),
+    ws("ws.params.create_overlay", "Params of create_overlay", schema::<CreateOverlayParams>),
+    ws("ws.params.get_overlay", "Params of get_overlay and delete_overlay", schema::<OverlayIdParams>),
+    ws("ws.params.update_overlay", "Params of update_overlay", schema::<UpdateOverlayParams>),
+    ws("ws.params.patch_overlay", "Params of patch_overlay", schema::<PatchOverlayParams>),
+    ws("ws.params.update_overlay_spans", "Params of update_overlay_spans", schema::<UpdateOverlaySpansParams>),
+    ws("ws.params.overlay_region_write", "Params of overlay_region_write", schema::<OverlayRegionWriteParams>),
+    ws("ws.params.animate_overlay", "Params of animate_overlay", schema::<AnimateOverlayParams>),
+    ws("ws.params.create_panel", "Params of create_panel", schema::<CreatePanelParams>),
+    ws("ws.params.get_panel", "Params of get_panel and delete_panel", schema::<PanelIdParams>),
+    ws("ws.params.update_panel", "Params of update_panel", schema::<UpdatePanelParams>),
+    ws("ws.params.patch_panel", "Params of patch_panel", schema::<PatchPanelParams>),
+    ws("ws.params.update_panel_spans", "Params of update_panel_spans", schema::<UpdatePanelSpansParams>),
+    ws("ws.params.panel_region_write", "Params of panel_region_write", schema::<PanelRegionWriteParams>),
+    ws("ws.params.append_panel", "Params of append_panel", schema::<AppendPanelParams>),
+    ws("ws.params.set_status_line", "Params of set_status_line", schema::<crate::status_line::StatusLinePatch>),
+    ws("ws.params.scroll_panel", "Params of scroll_panel", schema::<ScrollPanelParams>),
+    ws("ws.params.batch_update", "Params of batch_update", schema::<BatchUpdateParams>),
+    ws("ws.params.create_session", "Params of create_session (server-level)", schema::<CreateSessionParams>),
+    ws("ws.params.list_sessions", "Params of list_sessions (server-level)", schema::<ListSessionsParams>),
+    ws(
+        "ws.params.kill_session",
+        "Params of kill_session, detach_session, resume_session and hibernate_session (server-level)",
+        schema::<SessionNameParams>,
+    ),
+    ws("ws.params.rename_session", "Params of rename_session (server-level)", schema::<RenameSessionParams>),
+    ws("ws.params.update_tags", "Params of update_tags (server-level)", schema::<UpdateTagsParams>),
+    ws("ws.params.set_server_mode", "Params of set_server_mode (server-level)", schema::<SetServerModeParams>),
+    socket("socket.create_session", FrameType::CreateSession, schema::<protocol::CreateSessionMsg>),
+    socket("socket.create_session_response", FrameType::CreateSessionResponse, schema::<protocol::CreateSessionResponseMsg>),
+    socket("socket.attach_session", FrameType::AttachSession, schema::<protocol::AttachSessionMsg>),
+    socket("socket.attach_session_response", FrameType::AttachSessionResponse, schema::<protocol::AttachSessionResponseMsg>),
+    socket("socket.resize", FrameType::Resize, schema::<protocol::ResizeMsg>),
+    socket("socket.error", FrameType::Error, schema::<protocol::ErrorMsg>),
+    socket("socket.list_sessions", FrameType::ListSessions, schema::<protocol::ListSessionsMsg>),
+    socket("socket.list_sessions_response", FrameType::ListSessionsResponse, schema::<protocol::ListSessionsResponseMsg>),
+    socket("socket.kill_session", FrameType::KillSession, schema::<protocol::KillSessionMsg>),
+    socket("socket.kill_session_response", FrameType::KillSessionResponse, schema::<protocol::KillSessionResponseMsg>),
+    socket("socket.detach_session", FrameType::DetachSession, schema::<protocol::DetachSessionMsg>),
+    socket("socket.detach_session_response", FrameType::DetachSessionResponse, schema::<protocol::DetachSessionResponseMsg>),
+    socket("socket.get_token", FrameType::GetToken, schema::<protocol::GetTokenMsg>),
+    socket("socket.get_token_response", FrameType::GetTokenResponse, schema::<protocol::GetTokenResponseMsg>),
+    socket("socket.overlay_sync", FrameType::OverlaySync, schema::<protocol::OverlaySyncMsg>),
+    socket("socket.panel_sync", FrameType::PanelSync, schema::<protocol::PanelSyncMsg>),
+    socket("socket.manage_tags", FrameType::ManageTags, schema::<protocol::ManageTagsMsg>),
+    socket("socket.manage_tags_response", FrameType::ManageTagsResponse, schema::<protocol::ManageTagsResponseMsg>),
+    socket("socket.shutdown_server", FrameType::ShutdownServer, schema::<protocol::ShutdownServerMsg>),
+    socket("socket.shutdown_server_response", FrameType::ShutdownServerResponse, schema::<protocol::ShutdownServerResponseMsg>),
+    socket("socket.banner", FrameType::Banner, schema::<protocol::BannerMsg>),
+    socket("socket.list_servers", FrameType::ListServers, schema::<protocol::ListServersMsg>),
+    socket("socket.list_servers_response", FrameType::ListServersResponse, schema::<protocol::ListServersResponseMsg>),
+    socket("socket.add_server", FrameType::AddServer, schema::<protocol::AddServerMsg>),
+    socket("socket.add_server_response", FrameType::AddServerResponse, schema::<protocol::AddServerResponseMsg>),
+    socket("socket.remove_server", FrameType::RemoveServer, schema::<protocol::RemoveServerMsg>),
+    socket("socket.remove_server_response", FrameType::RemoveServerResponse, schema::<protocol::RemoveServerResponseMsg>),
+    socket("socket.reload_config", FrameType::ReloadConfig, schema::<protocol::ReloadConfigMsg>),
+    socket("socket.reload_config_response", FrameType::ReloadConfigResponse, schema::<protocol::ReloadConfigResponseMsg>),
+    socket("socket.server_info", FrameType::ServerInfo, schema::<protocol::ServerInfoMsg>),
+    socket("socket.server_info_response", FrameType::ServerInfoResponse, schema::<protocol::ServerInfoResponseMsg>),
+    socket("socket.read_output", FrameType::ReadOutput, schema::<protocol::ReadOutputMsg>),
+    socket("socket.read_output_response", FrameType::ReadOutputResponse, schema::<protocol::ReadOutputResponseMsg>),
+    socket("socket.rename_session", FrameType::RenameSession, schema::<protocol::RenameSessionMsg>),
+    socket("socket.rename_session_response", FrameType::RenameSessionResponse, schema::<protocol::RenameSessionResponseMsg>),
+    socket("socket.session_request", FrameType::SessionRequest, schema::<protocol::SessionRequestMsg>),
+    socket("socket.session_request_response", FrameType::SessionRequestResponse, schema::<protocol::SessionRequestResponseMsg>),
+    socket("socket.set_persist", FrameType::SetPersist, schema::<protocol::SetPersistMsg>),
+    socket("socket.set_persist_response", FrameType::SetPersistResponse, schema::<protocol::SetPersistResponseMsg>),
+];
+
+pub(super) async fn schemas_index() -> impl IntoResponse {
+    let schemas: Vec<_> = ENTRIES
+        .iter()
+        .map(|entry| {
+            let mut item = serde_json::json!({
+                "name": entry.name,
+                "url": format!("/schemas/{}.json", entry.name),
+            });
+            if !entry.description.is_empty() {
+                item["description"] = entry.description.into();
+            }
+            if let Some(frame_type) = entry.frame_type {
+                item["frame_type"] = (frame_type as u8).into();
+            }
+            item
+        })
+        .collect();
+    Json(serde_json::json!({ "schemas": schemas }))
+}
+
+pub(super) async fn schema_get(Path(file): Path<String>) -> Result<Response, ApiError> {
+    let name = file.strip_suffix(".json").unwrap_or(&file);
+    let entry = ENTRIES.iter().find(|entry| entry.name == name).ok_or(ApiError::NotFound)?;
+    Ok((
+        StatusCode::OK,
+        [("content-type", "application/schema+json")],
+        Json((entry.schema)()),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_unique_and_schemas_generate() {
+        let mut names = std::collections::HashSet::new();
+        for entry in ENTRIES {
+            assert!(names.insert(entry.name), "duplicate schema {}", entry.name);
+            let schema = (entry.schema)();
+            assert!(schema.get("$schema").is_some(), "{} has no $schema", entry.name);
+        }
+    }
+
+    #[test]
+    fn every_json_frame_has_a_schema() {
+        // Frames whose payload is raw bytes or empty.
+        let non_json = [FrameType::PtyOutput, FrameType::StdinInput, FrameType::Ping, FrameType::Pong, FrameType::Detach];
+        for byte in 0..=u8::MAX {
+            let Some(frame_type) = FrameType::from_u8(byte) else { continue };
+            if non_json.contains(&frame_type) {
+                continue;
+            }
+            assert!(
+                ENTRIES.iter().any(|entry| entry.frame_type == Some(frame_type)),
+                "no schema for {:?}",
+                frame_type
+            );
+        }
+    }
+
+    #[test]
+    fn request_schema_describes_envelope() {
+        let schema = serde_json::to_value(schema::<WsRequest>()).unwrap();
+        assert_eq!(schema["required"], serde_json::json!(["method"]));
+        assert!(schema["properties"]["params"].is_object());
+    }
+}
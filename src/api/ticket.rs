@@ -1,9 +1,13 @@
 use std::collections::HashMap;
+use std::net::IpAddr;
 use std::time::{Duration, Instant};
 
+use axum::http::HeaderMap;
 use parking_lot::Mutex;
 use rand::Rng;
 
+use super::tokens::TokenGrant;
+
 /// Time-to-live for a pending ticket.
 const TICKET_TTL: Duration = Duration::from_secs(30);
 
@@ -20,28 +24,71 @@ const MAX_PENDING_TICKETS: usize = 1024;
 /// 3. Client opens WebSocket with `?ticket=<nonce>`
 /// 4. Server validates and consumes the ticket on upgrade
 ///
-/// Tickets expire after 30 seconds and can only be used once.
+/// Tickets expire after 30 seconds and can only be used once. A ticket
+/// carries the grant of the token that requested it, so it confers no more
+/// access than that token.
+///
+/// Because tickets travel in URLs they can leak through logs and referrers,
+/// so each one is also bound to the client that requested it (see
+/// [`TicketBinding`]), and consumed tickets are remembered for the length
+/// of a ticket's life so that replays are logged rather than just failing.
 pub struct TicketStore {
-    inner: Mutex<HashMap<String, Instant>>,
+    inner: Mutex<Tickets>,
+}
+
+#[derive(Default)]
+struct Tickets {
+    pending: HashMap<String, (Instant, TokenGrant, TicketBinding)>,
+    /// Recently consumed nonces, by when they were consumed.
+    consumed: HashMap<String, Instant>,
+}
+
+/// The client a ticket was issued to: its address and its `Origin` header.
+/// A ticket is only accepted from a request with the same binding, so a
+/// leaked ticket is useless from another host or another web page.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TicketBinding {
+    pub ip: Option<IpAddr>,
+    pub origin: Option<String>,
+}
+
+impl TicketBinding {
+    pub fn new(ip: Option<IpAddr>, headers: &HeaderMap) -> Self {
+        Self {
+            ip: ip.map(|ip| ip.to_canonical()),
+            origin: headers
+                .get("origin")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string),
+        }
+    }
+}
+
+impl Default for TicketStore {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TicketStore {
     pub fn new() -> Self {
         Self {
-            inner: Mutex::new(HashMap::new()),
+            inner: Mutex::new(Tickets::default()),
         }
     }
 
-    /// Create a new ticket. Returns the nonce on success, or `Err(())` if the
-    /// maximum number of pending tickets has been reached.
-    pub fn create(&self) -> Result<String, ()> {
-        let mut map = self.inner.lock();
+    /// Create a new ticket for `grant`, usable only by a client matching
+    /// `binding`. Returns the nonce on success, or `Err(())` if the maximum
+    /// number of pending tickets has been reached.
+    #[allow(clippy::result_unit_err)]
+    pub fn create(&self, grant: TokenGrant, binding: TicketBinding) -> Result<String, ()> {
+        let mut tickets = self.inner.lock();
 
         // Prune expired tickets first
         let now = Instant::now();
-        map.retain(|_, created| now.duration_since(*created) < TICKET_TTL);
+        tickets.prune(now);
 
-        if map.len() >= MAX_PENDING_TICKETS {
+        if tickets.pending.len() >= MAX_PENDING_TICKETS {
             return Err(());
         }
 
@@ -51,19 +98,57 @@ impl TicketStore {
             .map(char::from)
             .collect();
 
-        map.insert(nonce.clone(), now);
+        tickets.pending.insert(nonce.clone(), (now, grant, binding));
         Ok(nonce)
     }
 
-    /// Validate and consume a ticket. Returns `true` if the ticket was valid
-    /// and has been consumed (removed). Returns `false` if the ticket does not
-    /// exist or has expired.
-    pub fn validate(&self, ticket: &str) -> bool {
-        let mut map = self.inner.lock();
-        match map.remove(ticket) {
-            Some(created) => Instant::now().duration_since(created) < TICKET_TTL,
-            None => false,
+    /// Validate and consume a ticket presented by a client matching
+    /// `binding`. Returns the grant it was issued for if the ticket was
+    /// valid and has been consumed (removed). Returns `None` if the ticket
+    /// does not exist, has expired, or was issued to a different client; a
+    /// ticket presented by the wrong client is consumed all the same.
+    pub fn validate(&self, ticket: &str, binding: &TicketBinding) -> Option<TokenGrant> {
+        let mut tickets = self.inner.lock();
+        let now = Instant::now();
+        tickets.prune(now);
+        let Some((created, grant, issued_to)) = tickets.pending.remove(ticket) else {
+            if tickets.consumed.contains_key(ticket) {
+                tracing::warn!(
+                    ip = ?binding.ip,
+                    origin = ?binding.origin,
+                    "rejected reuse of a consumed WebSocket ticket"
+                );
+            }
+            return None;
+        };
+        if now.duration_since(created) >= TICKET_TTL {
+            return None;
         }
+        if tickets.consumed.len() < MAX_PENDING_TICKETS {
+            tickets.consumed.insert(ticket.to_string(), now);
+        }
+        if issued_to != *binding {
+            tracing::warn!(
+                ip = ?binding.ip,
+                origin = ?binding.origin,
+                issued_ip = ?issued_to.ip,
+                issued_origin = ?issued_to.origin,
+                "rejected WebSocket ticket presented by a different client"
+            );
+            return None;
+        }
+        Some(grant)
+    }
+}
+
+impl Tickets {
+    /// Drop expired pending tickets and consumed nonces past the replay
+    /// window.
+    fn prune(&mut self, now: Instant) {
+        self.pending
+            .retain(|_, (created, _, _)| now.duration_since(*created) < TICKET_TTL);
+        self.consumed
+            .retain(|_, consumed| now.duration_since(*consumed) < TICKET_TTL);
     }
 }
 
@@ -74,32 +159,45 @@ mod tests {
     #[test]
     fn create_returns_nonce() {
         let store = TicketStore::new();
-        let ticket = store.create().unwrap();
+        let ticket = store.create(TokenGrant::root(), TicketBinding::default()).unwrap();
         assert_eq!(ticket.len(), 32);
     }
 
     #[test]
     fn validate_consumes_ticket() {
         let store = TicketStore::new();
-        let ticket = store.create().unwrap();
-        assert!(store.validate(&ticket));
+        let ticket = store.create(TokenGrant::root(), TicketBinding::default()).unwrap();
+        assert_eq!(store.validate(&ticket, &TicketBinding::default()), Some(TokenGrant::root()));
         // Second use should fail (single-use)
-        assert!(!store.validate(&ticket));
+        assert!(store.validate(&ticket, &TicketBinding::default()).is_none());
     }
 
     #[test]
     fn validate_rejects_unknown() {
         let store = TicketStore::new();
-        assert!(!store.validate("nonexistent"));
+        assert!(store.validate("nonexistent", &TicketBinding::default()).is_none());
+    }
+
+    #[test]
+    fn ticket_carries_requesting_grant() {
+        use super::super::tokens::TokenScope;
+        let store = TicketStore::new();
+        let grant = TokenGrant {
+            name: "reader".to_string(),
+            scope: TokenScope::ReadOnly,
+            tags: vec!["ci".to_string()],
+        };
+        let ticket = store.create(grant.clone(), TicketBinding::default()).unwrap();
+        assert_eq!(store.validate(&ticket, &TicketBinding::default()), Some(grant));
     }
 
     #[test]
     fn limit_enforced() {
         let store = TicketStore::new();
         for _ in 0..MAX_PENDING_TICKETS {
-            store.create().unwrap();
+            store.create(TokenGrant::root(), TicketBinding::default()).unwrap();
         }
-        assert!(store.create().is_err());
+        assert!(store.create(TokenGrant::root(), TicketBinding::default()).is_err());
     }
 
     #[test]
@@ -108,18 +206,72 @@ mod tests {
 
         // Insert a ticket with a backdated timestamp
         {
-            let mut map = store.inner.lock();
-            map.insert(
+            let mut tickets = store.inner.lock();
+            tickets.pending.insert(
                 "old-ticket".to_string(),
-                Instant::now() - Duration::from_secs(60),
+                (
+                    Instant::now() - Duration::from_secs(60),
+                    TokenGrant::root(),
+                    TicketBinding::default(),
+                ),
             );
         }
 
         // Expired ticket should not validate
-        assert!(!store.validate("old-ticket"));
+        assert!(store.validate("old-ticket", &TicketBinding::default()).is_none());
 
         // Creating new tickets should succeed (expired ones pruned)
-        let ticket = store.create().unwrap();
-        assert!(store.validate(&ticket));
+        let ticket = store.create(TokenGrant::root(), TicketBinding::default()).unwrap();
+        assert!(store.validate(&ticket, &TicketBinding::default()).is_some());
+    }
+
+    #[test]
+    fn validate_requires_matching_binding() {
+        let store = TicketStore::new();
+        let mut headers = HeaderMap::new();
+        headers.insert("origin", "https://wsh.example".parse().unwrap());
+        let issued_to = TicketBinding::new(Some("10.0.0.5".parse().unwrap()), &headers);
+
+        let ticket = store.create(TokenGrant::root(), issued_to.clone()).unwrap();
+        let other_ip = TicketBinding {
+            ip: Some("10.0.0.6".parse().unwrap()),
+            ..issued_to.clone()
+        };
+        assert!(store.validate(&ticket, &other_ip).is_none());
+        // The mismatched attempt burned the ticket.
+        assert!(store.validate(&ticket, &issued_to).is_none());
+
+        let ticket = store.create(TokenGrant::root(), issued_to.clone()).unwrap();
+        let other_origin = TicketBinding {
+            origin: None,
+            ..issued_to.clone()
+        };
+        assert!(store.validate(&ticket, &other_origin).is_none());
+
+        let ticket = store.create(TokenGrant::root(), issued_to.clone()).unwrap();
+        assert!(store.validate(&ticket, &issued_to).is_some());
+    }
+
+    #[test]
+    fn binding_canonicalizes_mapped_addresses() {
+        let mapped = TicketBinding::new(Some("::ffff:127.0.0.1".parse().unwrap()), &HeaderMap::new());
+        let v4 = TicketBinding::new(Some("127.0.0.1".parse().unwrap()), &HeaderMap::new());
+        assert_eq!(mapped, v4);
+    }
+
+    #[test]
+    fn consumed_tickets_remembered_for_replay_window() {
+        let store = TicketStore::new();
+        let ticket = store.create(TokenGrant::root(), TicketBinding::default()).unwrap();
+        assert!(store.validate(&ticket, &TicketBinding::default()).is_some());
+        assert!(store.inner.lock().consumed.contains_key(&ticket));
+
+        store
+            .inner
+            .lock()
+            .consumed
+            .insert(ticket.clone(), Instant::now() - Duration::from_secs(60));
+        assert!(store.validate(&ticket, &TicketBinding::default()).is_none());
+        assert!(!store.inner.lock().consumed.contains_key(&ticket));
     }
 }
@@ -0,0 +1,302 @@
+use std::collections::HashSet;
+
+use parking_lot::RwLock;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+
+/// Name under which the server's `--token` is registered.
+pub const ROOT_TOKEN_NAME: &str = "root";
+
+/// Maximum number of named tokens (excluding the root token).
+const MAX_TOKENS: usize = 256;
+
+/// What a token is allowed to do. Scopes are ordered: each one includes the
+/// permissions of the scopes below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenScope {
+    /// Read screens, scrollback, and session metadata.
+    ReadOnly,
+    /// Everything `ReadOnly` can do, plus sending input, managing sessions,
+    /// overlays and panels, WebSockets, and MCP.
+    Input,
+    /// Everything, including server configuration, federation, and token
+    /// management.
+    Admin,
+}
+
+impl std::fmt::Display for TokenScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            TokenScope::ReadOnly => "read_only",
+            TokenScope::Input => "input",
+            TokenScope::Admin => "admin",
+        })
+    }
+}
+
+/// The identity and permissions of an authenticated request.
+///
+/// Inserted into request extensions by the auth middleware so handlers can
+/// narrow their results (e.g. session lists for tag-restricted tokens).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenGrant {
+    pub name: String,
+    pub scope: TokenScope,
+    /// When non-empty, the token may only access sessions carrying at least
+    /// one of these tags.
+    pub tags: Vec<String>,
+}
+
+impl TokenGrant {
+    /// An unrestricted grant, used for the root token and for servers
+    /// running without authentication.
+    pub fn root() -> Self {
+        Self {
+            name: ROOT_TOKEN_NAME.to_string(),
+            scope: TokenScope::Admin,
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn allows(&self, scope: TokenScope) -> bool {
+        self.scope >= scope
+    }
+
+    pub fn is_tag_restricted(&self) -> bool {
+        !self.tags.is_empty()
+    }
+
+    /// Whether a session with `session_tags` is visible to this token.
+    pub fn allows_session(&self, session_tags: &HashSet<String>) -> bool {
+        !self.is_tag_restricted() || self.tags.iter().any(|t| session_tags.contains(t))
+    }
+}
+
+/// Public description of a token. Never includes the secret.
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenInfo {
+    pub name: String,
+    pub scope: TokenScope,
+    pub tags: Vec<String>,
+    /// Milliseconds since the Unix epoch.
+    pub created_at_ms: u64,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum TokenError {
+    #[error("token name already exists: {0}")]
+    NameExists(String),
+    #[error("token not found: {0}")]
+    NotFound(String),
+    #[error("invalid token name: {0}")]
+    InvalidName(String),
+    #[error("the root token cannot be created or revoked")]
+    Root,
+    #[error("too many tokens")]
+    LimitReached,
+}
+
+struct StoredToken {
+    secret: String,
+    info: TokenInfo,
+}
+
+/// The set of API tokens accepted by the HTTP server.
+///
+/// Holds the root token (from `--token`, always admin) plus any number of
+/// named tokens created at runtime via `/auth/tokens`. Named tokens live for
+/// the lifetime of the server process.
+pub struct TokenStore {
+    root: RwLock<Option<String>>,
+    tokens: RwLock<Vec<StoredToken>>,
+}
+
+impl Default for TokenStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TokenStore {
+    pub fn new() -> Self {
+        Self {
+            root: RwLock::new(None),
+            tokens: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Install the root (admin) token.
+    pub fn set_root(&self, secret: String) {
+        *self.root.write() = Some(secret);
+    }
+
+    /// Whether authentication is configured at all.
+    pub fn has_root(&self) -> bool {
+        self.root.read().is_some()
+    }
+
+    /// Resolve a bearer secret to its grant. Every stored secret is compared
+    /// in constant time so the lookup does not reveal which token matched.
+    pub fn authenticate(&self, secret: &str) -> Option<TokenGrant> {
+        let mut found = None;
+        if let Some(ref root) = *self.root.read() {
+            if bool::from(secret.as_bytes().ct_eq(root.as_bytes())) {
+                found = Some(TokenGrant::root());
+            }
+        }
+        for token in self.tokens.read().iter() {
+            if bool::from(secret.as_bytes().ct_eq(token.secret.as_bytes())) && found.is_none() {
+                found = Some(TokenGrant {
+                    name: token.info.name.clone(),
+                    scope: token.info.scope,
+                    tags: token.info.tags.clone(),
+                });
+            }
+        }
+        found
+    }
+
+    /// Create a named token and return its description and secret. The
+    /// secret is only available here; it cannot be retrieved later.
+    pub fn create(
+        &self,
+        name: String,
+        scope: TokenScope,
+        tags: Vec<String>,
+    ) -> Result<(TokenInfo, String), TokenError> {
+        if name == ROOT_TOKEN_NAME {
+            return Err(TokenError::Root);
+        }
+        crate::session::validate_session_name(&name).map_err(TokenError::InvalidName)?;
+
+        let mut tokens = self.tokens.write();
+        if tokens.iter().any(|t| t.info.name == name) {
+            return Err(TokenError::NameExists(name));
+        }
+        if tokens.len() >= MAX_TOKENS {
+            return Err(TokenError::LimitReached);
+        }
+
+        let secret: String = rand::thread_rng()
+            .sample_iter(&rand::distributions::Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+        let info = TokenInfo {
+            name,
+            scope,
+            tags,
+            created_at_ms: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+        };
+        tokens.push(StoredToken {
+            secret: secret.clone(),
+            info: info.clone(),
+        });
+        Ok((info, secret))
+    }
+
+    /// All named tokens, oldest first.
+    pub fn list(&self) -> Vec<TokenInfo> {
+        self.tokens.read().iter().map(|t| t.info.clone()).collect()
+    }
+
+    /// Revoke a named token. Requests using it fail from then on.
+    pub fn revoke(&self, name: &str) -> Result<(), TokenError> {
+        if name == ROOT_TOKEN_NAME {
+            return Err(TokenError::Root);
+        }
+        let mut tokens = self.tokens.write();
+        let before = tokens.len();
+        tokens.retain(|t| t.info.name != name);
+        if tokens.len() == before {
+            return Err(TokenError::NotFound(name.to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes_are_ordered() {
+        assert!(TokenScope::Admin > TokenScope::Input);
+        assert!(TokenScope::Input > TokenScope::ReadOnly);
+        let grant = TokenGrant {
+            name: "t".to_string(),
+            scope: TokenScope::Input,
+            tags: vec![],
+        };
+        assert!(grant.allows(TokenScope::ReadOnly));
+        assert!(grant.allows(TokenScope::Input));
+        assert!(!grant.allows(TokenScope::Admin));
+    }
+
+    #[test]
+    fn authenticate_root_and_named_tokens() {
+        let store = TokenStore::new();
+        store.set_root("root-secret".to_string());
+        let (info, secret) = store
+            .create("reader".to_string(), TokenScope::ReadOnly, vec![])
+            .unwrap();
+        assert_eq!(info.name, "reader");
+
+        assert_eq!(store.authenticate("root-secret"), Some(TokenGrant::root()));
+        let grant = store.authenticate(&secret).unwrap();
+        assert_eq!(grant.name, "reader");
+        assert_eq!(grant.scope, TokenScope::ReadOnly);
+        assert!(store.authenticate("wrong").is_none());
+    }
+
+    #[test]
+    fn revoked_token_no_longer_authenticates() {
+        let store = TokenStore::new();
+        let (_, secret) = store
+            .create("agent".to_string(), TokenScope::Input, vec![])
+            .unwrap();
+        store.revoke("agent").unwrap();
+        assert!(store.authenticate(&secret).is_none());
+        assert_eq!(store.revoke("agent"), Err(TokenError::NotFound("agent".to_string())));
+    }
+
+    #[test]
+    fn create_rejects_duplicates_and_root() {
+        let store = TokenStore::new();
+        store
+            .create("a".to_string(), TokenScope::Input, vec![])
+            .unwrap();
+        assert_eq!(
+            store.create("a".to_string(), TokenScope::Input, vec![]).unwrap_err(),
+            TokenError::NameExists("a".to_string())
+        );
+        assert_eq!(
+            store.create(ROOT_TOKEN_NAME.to_string(), TokenScope::Admin, vec![]).unwrap_err(),
+            TokenError::Root
+        );
+        assert!(matches!(
+            store.create("bad name".to_string(), TokenScope::Input, vec![]),
+            Err(TokenError::InvalidName(_))
+        ));
+        assert_eq!(store.revoke(ROOT_TOKEN_NAME), Err(TokenError::Root));
+    }
+
+    #[test]
+    fn tag_restriction_matches_any_tag() {
+        let grant = TokenGrant {
+            name: "t".to_string(),
+            scope: TokenScope::Input,
+            tags: vec!["build".to_string(), "ci".to_string()],
+        };
+        let tags = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<HashSet<_>>();
+        assert!(grant.allows_session(&tags(&["ci"])));
+        assert!(!grant.allows_session(&tags(&["prod"])));
+        assert!(!grant.allows_session(&tags(&[])));
+        assert!(TokenGrant::root().allows_session(&tags(&[])));
+    }
+}
@@ -14,6 +14,7 @@ const PARSER_QUERY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs
 
 /// Incoming WebSocket request (JSON-RPC-ish).
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct WsRequest {
     /// Optional request id, echoed back in the response.
     pub id: Option<serde_json::Value>,
@@ -28,6 +29,7 @@ pub struct WsRequest {
 /// Used by the multiplexed `/ws/json` endpoint where a single WebSocket
 /// connection can interact with multiple sessions.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ServerWsRequest {
     /// Optional request id, echoed back in the response.
     pub id: Option<serde_json::Value>,
@@ -41,6 +43,7 @@ pub struct ServerWsRequest {
 
 /// Outgoing WebSocket response.
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct WsResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<serde_json::Value>,
@@ -101,6 +104,7 @@ impl WsResponse {
 
 /// Error payload inside a [`WsResponse`].
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct WsError {
     pub code: String,
     pub message: String,
@@ -112,6 +116,7 @@ pub struct WsError {
 
 /// Parameters for the `subscribe` method.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct SubscribeParams {
     pub events: Vec<EventType>,
     #[serde(default = "default_interval")]
@@ -127,6 +132,7 @@ pub struct SubscribeParams {
 
 /// Parameters for the `await_idle` WebSocket method.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct AwaitIdleParams {
     pub timeout_ms: u64,
     #[serde(default)]
@@ -152,13 +158,19 @@ fn default_interval() -> u64 {
 
 /// Parameters for the `get_screen` method.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ScreenParams {
     #[serde(default)]
     pub format: Format,
+    /// Screen generation from an earlier response; only rows changed
+    /// after it are returned.
+    #[serde(default)]
+    pub since_generation: Option<u64>,
 }
 
 /// Parameters for the `get_scrollback` method.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ScrollbackParams {
     #[serde(default)]
     pub format: Format,
@@ -174,6 +186,7 @@ fn default_scrollback_limit() -> usize {
 
 /// Parameters for the `resize` method.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ResizeParams {
     pub cols: u16,
     pub rows: u16,
@@ -181,14 +194,23 @@ pub struct ResizeParams {
 
 /// Parameters for the `send_input` method.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct SendInputParams {
     pub data: String,
     #[serde(default)]
     pub encoding: InputEncoding,
 }
 
+/// Parameters for the `send_keys` method.
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct SendKeysParams {
+    pub keys: Vec<String>,
+}
+
 /// Encoding used for [`SendInputParams::data`].
 #[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum InputEncoding {
     #[default]
@@ -196,18 +218,94 @@ pub enum InputEncoding {
     Base64,
 }
 
+// ---------------------------------------------------------------------------
+// Server-level param types (multiplexed `/ws/json`)
+// ---------------------------------------------------------------------------
+
+/// Parameters for the `create_session` method.
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct CreateSessionParams {
+    pub name: Option<String>,
+    pub command: Option<String>,
+    pub rows: Option<u16>,
+    pub cols: Option<u16>,
+    pub cwd: Option<String>,
+    pub env: Option<std::collections::HashMap<String, String>>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub user: Option<String>,
+    pub uid: Option<u32>,
+    pub sandbox: Option<String>,
+    pub remote: Option<String>,
+    pub idle_kill_after: Option<String>,
+    pub nice: Option<i32>,
+    pub ionice: Option<String>,
+    pub scrollback_lines: Option<usize>,
+    pub query_policy: Option<crate::parser::state::QueryPolicy>,
+    pub encoding: Option<crate::parser::encoding::Encoding>,
+    pub restart: Option<crate::session::RestartWhen>,
+    pub restart_max_retries: Option<u32>,
+    pub restart_backoff_ms: Option<u64>,
+}
+
+/// Parameters for the `list_sessions` method.
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct ListSessionsParams {
+    #[serde(default)]
+    pub tag: Vec<String>,
+}
+
+/// Parameters for the methods that act on one session by name:
+/// `kill_session`, `detach_session`, `resume_session` and
+/// `hibernate_session`.
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct SessionNameParams {
+    pub name: String,
+}
+
+/// Parameters for the `rename_session` method.
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct RenameSessionParams {
+    pub name: String,
+    pub new_name: String,
+}
+
+/// Parameters for the `update_tags` method.
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct UpdateTagsParams {
+    pub session: String,
+    #[serde(default)]
+    pub add: Vec<String>,
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+/// Parameters for the `set_server_mode` method.
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct SetServerModeParams {
+    pub persistent: Option<bool>,
+}
+
 // ---------------------------------------------------------------------------
 // Overlay param types
 // ---------------------------------------------------------------------------
 
 /// Parameters that identify an overlay by id (get / delete).
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct OverlayIdParams {
     pub id: String,
 }
 
 /// Parameters for creating a new overlay.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct CreateOverlayParams {
     pub x: u16,
     pub y: u16,
@@ -216,13 +314,23 @@ pub struct CreateOverlayParams {
     pub height: u16,
     #[serde(default)]
     pub background: Option<crate::overlay::BackgroundStyle>,
+    #[serde(default)]
     pub spans: Vec<OverlaySpan>,
     #[serde(default)]
     pub focusable: bool,
+    /// Remove the overlay this many milliseconds after it is created.
+    #[serde(default)]
+    pub ttl_ms: Option<u64>,
+    /// Show the overlay in both screen modes.
+    #[serde(default)]
+    pub sticky: bool,
+    #[serde(default)]
+    pub widget: Option<crate::overlay::Widget>,
 }
 
 /// Parameters for replacing an overlay's spans.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct UpdateOverlayParams {
     pub id: String,
     pub spans: Vec<OverlaySpan>,
@@ -230,6 +338,7 @@ pub struct UpdateOverlayParams {
 
 /// Parameters for patching overlay position / z-order / background.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct PatchOverlayParams {
     pub id: String,
     pub x: Option<u16>,
@@ -239,6 +348,9 @@ pub struct PatchOverlayParams {
     pub height: Option<u16>,
     #[serde(default)]
     pub background: Option<crate::overlay::BackgroundStyle>,
+    /// Fields to change in the overlay's widget, or a whole new widget.
+    #[serde(default)]
+    pub widget: Option<serde_json::Value>,
 }
 
 // ---------------------------------------------------------------------------
@@ -249,12 +361,14 @@ use crate::panel::Position;
 
 /// Parameters that identify a panel by id (get / delete).
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct PanelIdParams {
     pub id: String,
 }
 
 /// Parameters for creating a new panel.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct CreatePanelParams {
     pub position: Position,
     pub height: u16,
@@ -265,10 +379,20 @@ pub struct CreatePanelParams {
     pub spans: Vec<OverlaySpan>,
     #[serde(default)]
     pub focusable: bool,
+    /// Remove the panel this many milliseconds after it is created.
+    #[serde(default)]
+    pub ttl_ms: Option<u64>,
+    /// Show the panel in both screen modes.
+    #[serde(default)]
+    pub sticky: bool,
+    /// Make the panel scrollable, keeping this many lines of content.
+    #[serde(default)]
+    pub max_lines: Option<usize>,
 }
 
 /// Parameters for fully replacing a panel.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct UpdatePanelParams {
     pub id: String,
     pub position: Position,
@@ -279,6 +403,7 @@ pub struct UpdatePanelParams {
 
 /// Parameters for patching panel properties.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct PatchPanelParams {
     pub id: String,
     pub position: Option<Position>,
@@ -295,6 +420,7 @@ pub struct PatchPanelParams {
 
 /// Parameters for updating specific named spans on an overlay.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct UpdateOverlaySpansParams {
     pub id: String,
     pub spans: Vec<OverlaySpan>,
@@ -302,20 +428,51 @@ pub struct UpdateOverlaySpansParams {
 
 /// Parameters for region writes on an overlay.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct OverlayRegionWriteParams {
     pub id: String,
     pub writes: Vec<RegionWrite>,
 }
 
+/// Parameters for animating an overlay.
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct AnimateOverlayParams {
+    pub id: String,
+    pub keyframes: Vec<crate::overlay::Keyframe>,
+    /// Delete the overlay once the last keyframe is reached.
+    #[serde(default)]
+    pub delete: bool,
+}
+
 /// Parameters for updating specific named spans on a panel.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct UpdatePanelSpansParams {
     pub id: String,
     pub spans: Vec<OverlaySpan>,
 }
 
+/// Parameters for appending lines to a scrollable panel.
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct AppendPanelParams {
+    pub id: String,
+    pub spans: Vec<OverlaySpan>,
+}
+
+/// Parameters for scrolling a scrollable panel.
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct ScrollPanelParams {
+    pub id: String,
+    /// Lines up from the newest line; 0 follows new lines.
+    pub offset: usize,
+}
+
 /// Parameters for region writes on a panel.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct PanelRegionWriteParams {
     pub id: String,
     pub writes: Vec<RegionWrite>,
@@ -323,6 +480,7 @@ pub struct PanelRegionWriteParams {
 
 /// Parameters for the `batch_update` method.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct BatchUpdateParams {
     pub id: String,
     #[serde(rename = "type")]
@@ -333,6 +491,7 @@ pub struct BatchUpdateParams {
 
 /// Target type for batch updates.
 #[derive(Debug, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum BatchTargetType {
     Overlay,
@@ -341,14 +500,33 @@ pub enum BatchTargetType {
 
 /// Parameters for the `focus` method.
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct FocusParams {
     pub id: String,
 }
 
+/// Parameters for the `set_focus_order` method.
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct FocusOrderParams {
+    pub ids: Vec<String>,
+}
+
+/// Parameters for the `request_handoff` method.
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct RequestHandoffParams {
+    #[serde(default)]
+    pub agent: Option<String>,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
 // ---------------------------------------------------------------------------
 // Dispatch
 // ---------------------------------------------------------------------------
 
+use crate::input::InputSource;
 use crate::session::Session;
 
 /// Parse params from a WsRequest, returning a WsResponse error on failure.
@@ -369,8 +547,13 @@ fn parse_params<T: serde::de::DeserializeOwned>(req: &WsRequest) -> Result<T, Ws
     })
 }
 
+/// Error response for a handoff that cannot be requested or answered.
+fn handoff_error(id: Option<serde_json::Value>, method: &str, e: crate::input::HandoffError) -> WsResponse {
+    WsResponse::error(id, method, "handoff_conflict", &format!("Handoff not possible: {}.", e))
+}
+
 /// Dispatch a WebSocket request to the appropriate handler.
-pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
+pub async fn dispatch(req: &WsRequest, session: &Session, source: &InputSource) -> WsResponse {
     let id = req.id.clone();
     let method = req.method.as_str();
 
@@ -386,8 +569,34 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
         "release_input" => {
             session.input_mode.release();
             session.focus.unfocus();
+            session.return_control(source.clone());
             WsResponse::success(id, method, serde_json::json!({}))
         }
+        "get_handoff" => WsResponse::success(id, method, serde_json::json!(session.handoff.status())),
+        "request_handoff" => {
+            let params: RequestHandoffParams = match parse_params(req) {
+                Ok(p) => p,
+                Err(e) => return e,
+            };
+            let agent = params.agent.unwrap_or_else(|| "agent".to_string());
+            if let Err(e) = crate::input::handoff::validate_request(&agent, params.reason.as_deref()) {
+                return WsResponse::error(id, method, "invalid_request", &format!("Invalid request: {}.", e));
+            }
+            match session.request_handoff(agent, params.reason, source.clone()) {
+                Ok(_) => WsResponse::success(id, method, serde_json::json!(session.handoff.status())),
+                Err(e) => handoff_error(id, method, e),
+            }
+        }
+        "approve_handoff" | "deny_handoff" => {
+            match session.answer_handoff(method == "approve_handoff", source.clone()) {
+                Ok(_) => WsResponse::success(id, method, serde_json::json!(session.handoff.status())),
+                Err(e) => handoff_error(id, method, e),
+            }
+        }
+        "return_control" => {
+            session.return_control(source.clone());
+            WsResponse::success(id, method, serde_json::json!(session.handoff.status()))
+        }
         "focus" => {
             let params: FocusParams = match parse_params(req) {
                 Ok(p) => p,
@@ -414,16 +623,33 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
                     &format!("Target '{}' is not focusable.", params.id),
                 );
             }
-            session.focus.focus(params.id);
+            session.focus.focus(params.id.clone());
+            session.input_broadcaster.broadcast_focus(Some(params.id), Some(source.clone()));
             WsResponse::success(id, method, serde_json::json!({}))
         }
         "unfocus" => {
             session.focus.unfocus();
+            session.input_broadcaster.broadcast_focus(None, Some(source.clone()));
             WsResponse::success(id, method, serde_json::json!({}))
         }
         "get_focus" => {
             let focused = session.focus.focused();
-            WsResponse::success(id, method, serde_json::json!({ "focused": focused }))
+            WsResponse::success(id, method, serde_json::json!({ "focused": focused, "order": session.focus_ring() }))
+        }
+        "set_focus_order" => {
+            let params: FocusOrderParams = match parse_params(req) {
+                Ok(p) => p,
+                Err(e) => return e,
+            };
+            match session.set_focus_order(params.ids) {
+                Ok(()) => WsResponse::success(id, method, serde_json::json!({})),
+                Err(e) => WsResponse::error(id, method, "invalid_request", &e),
+            }
+        }
+        "focus_next" => {
+            session.focus_next(source.clone());
+            let focused = session.focus.focused();
+            WsResponse::success(id, method, serde_json::json!({ "focused": focused, "order": session.focus_ring() }))
         }
         "list_overlays" => {
             let mode = *session.screen_mode.read();
@@ -446,6 +672,13 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
                 Ok(id) => id,
                 Err(e) => return WsResponse::error(id, method, "resource_limit_reached", e),
             };
+            if let Some(widget) = params.widget {
+                if let Err(e) = session.overlays.set_widget(&overlay_id, widget) {
+                    session.overlays.delete(&overlay_id);
+                    return WsResponse::error(id, method, "invalid_overlay", e);
+                }
+            }
+            session.overlays.set_lifetime(&overlay_id, params.ttl_ms.map(std::time::Duration::from_millis), params.sticky);
             let _ = session.visual_update_tx.send(crate::protocol::VisualUpdate::OverlaysChanged);
             WsResponse::success(id, method, serde_json::json!({ "id": overlay_id }))
         }
@@ -492,6 +725,18 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
                 Ok(p) => p,
                 Err(e) => return e,
             };
+            if let Some(ref widget) = params.widget {
+                match session.overlays.patch_widget(&params.id, widget) {
+                    Err(e) => return WsResponse::error(id, method, "invalid_overlay", &e),
+                    Ok(false) => return WsResponse::error(
+                        id,
+                        method,
+                        "overlay_not_found",
+                        &format!("No overlay exists with id '{}'.", params.id),
+                    ),
+                    Ok(true) => {}
+                }
+            }
             if session.overlays.move_to(&params.id, params.x, params.y, params.z, params.width, params.height, params.background) {
                 let _ = session.visual_update_tx.send(crate::protocol::VisualUpdate::OverlaysChanged);
                 WsResponse::success(id, method, serde_json::json!({}))
@@ -529,7 +774,7 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
             };
             match tokio::time::timeout(
                 PARSER_QUERY_TIMEOUT,
-                session.parser.query(Query::Screen { format: params.format }),
+                session.parser.query(Query::Screen { format: params.format, since: params.since_generation }),
             ).await {
                 Ok(Ok(resp)) => WsResponse::success(
                     id,
@@ -550,6 +795,27 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
                 ),
             }
         }
+        "get_modes" => {
+            match tokio::time::timeout(PARSER_QUERY_TIMEOUT, session.parser.query(Query::Modes)).await {
+                Ok(Ok(resp)) => WsResponse::success(
+                    id,
+                    method,
+                    serde_json::to_value(&resp).unwrap(),
+                ),
+                Ok(Err(_)) => WsResponse::error(
+                    id,
+                    method,
+                    "parser_unavailable",
+                    "Terminal parser is unavailable.",
+                ),
+                Err(_) => WsResponse::error(
+                    id,
+                    method,
+                    "parser_timeout",
+                    "Parser query timed out.",
+                ),
+            }
+        }
         "get_scrollback" => {
             let params: ScrollbackParams = match parse_params(req) {
                 Ok(p) => p,
@@ -604,6 +870,81 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
                     }
                 }
             };
+            if let Err(pattern) = session.policy.check_input(&bytes) {
+                return WsResponse::error(
+                    id,
+                    method,
+                    "input_blocked",
+                    &format!("Input blocked by policy: matches '{}'.", pattern),
+                );
+            }
+            match session.hold_input(source, &bytes) {
+                Ok(Some(pending)) => {
+                    return WsResponse::success(id, method, serde_json::json!({ "approval": pending }));
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    return WsResponse::error(id, method, "resource_limit_reached", &format!("{}.", e));
+                }
+            }
+            session.input_broadcaster.record_input(source.clone(), &bytes);
+            match tokio::time::timeout(
+                std::time::Duration::from_secs(5),
+                session.input_tx.send(bytes),
+            ).await {
+                Ok(Ok(())) => {
+                    session.activity.touch();
+                    WsResponse::success(id, method, serde_json::json!({}))
+                }
+                Ok(Err(_)) => WsResponse::error(
+                    id,
+                    method,
+                    "input_send_failed",
+                    "Failed to send input to terminal.",
+                ),
+                Err(_) => WsResponse::error(
+                    id,
+                    method,
+                    "input_send_timeout",
+                    "Input send timed out.",
+                ),
+            }
+        }
+        "send_keys" => {
+            let params: SendKeysParams = match parse_params(req) {
+                Ok(p) => p,
+                Err(e) => return e,
+            };
+            let modes = match tokio::time::timeout(
+                std::time::Duration::from_secs(2),
+                session.parser.keyboard_modes(),
+            ).await {
+                Ok(Ok(modes)) => modes,
+                Ok(Err(_)) => return WsResponse::error(
+                    id,
+                    method,
+                    "parser_unavailable",
+                    "Terminal parser is unavailable.",
+                ),
+                Err(_) => return WsResponse::error(
+                    id,
+                    method,
+                    "parser_timeout",
+                    "Parser query timed out.",
+                ),
+            };
+            let bytes = match crate::input::encode_keys(&params.keys, &modes) {
+                Ok(data) => bytes::Bytes::from(data),
+                Err(e) => {
+                    return WsResponse::error(
+                        id,
+                        method,
+                        "invalid_request",
+                        &format!("Invalid key: {}.", e.0),
+                    );
+                }
+            };
+            session.input_broadcaster.record_input(source.clone(), &bytes);
             match tokio::time::timeout(
                 std::time::Duration::from_secs(5),
                 session.input_tx.send(bytes),
@@ -633,13 +974,7 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
             };
             let rows = params.rows.clamp(1, 1000);
             let cols = params.cols.clamp(1, 1000);
-            if let Err(e) = session.pty.lock().resize(rows, cols) {
-                tracing::warn!(?e, "failed to resize PTY via WS");
-            }
-            if let Err(e) = session.parser.resize(cols as usize, rows as usize).await {
-                tracing::warn!(?e, "failed to resize parser via WS");
-            }
-            session.terminal_size.set(rows, cols);
+            session.apply_size(rows, cols).await;
             WsResponse::success(id, method, serde_json::json!({}))
         }
         "list_panels" => {
@@ -666,12 +1001,19 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
                 Err(e) => return e,
             };
             let current_mode = *session.screen_mode.read();
+            if let Some(Err(e)) = params.max_lines.map(crate::panel::validate_max_lines) {
+                return WsResponse::error(id, method, "invalid_overlay", e);
+            }
             let panel_id = match session
                 .panels
                 .create(params.position, params.height, params.z, params.background, params.spans, params.focusable, current_mode) {
                 Ok(id) => id,
                 Err(e) => return WsResponse::error(id, method, "resource_limit_reached", e),
             };
+            session.panels.set_lifetime(&panel_id, params.ttl_ms.map(std::time::Duration::from_millis), params.sticky);
+            if params.max_lines.is_some() {
+                let _ = session.panels.set_max_lines(&panel_id, params.max_lines);
+            }
             crate::panel::reconfigure_layout(
                 &session.panels,
                 &session.terminal_size,
@@ -875,6 +1217,23 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
                 )
             }
         }
+        "animate_overlay" => {
+            let params: AnimateOverlayParams = match parse_params(req) {
+                Ok(p) => p,
+                Err(e) => return e,
+            };
+            let animation = crate::overlay::Animation { keyframes: params.keyframes, delete: params.delete };
+            match session.animate_overlay(&params.id, animation) {
+                Err(e) => WsResponse::error(id, method, "invalid_overlay", &e),
+                Ok(true) => WsResponse::success(id, method, serde_json::json!({})),
+                Ok(false) => WsResponse::error(
+                    id,
+                    method,
+                    "overlay_not_found",
+                    &format!("No overlay exists with id '{}'.", params.id),
+                )
+            }
+        }
         "update_panel_spans" => {
             let params: UpdatePanelSpansParams = match parse_params(req) {
                 Ok(p) => p,
@@ -899,6 +1258,56 @@ pub async fn dispatch(req: &WsRequest, session: &Session) -> WsResponse {
                 )
             }
         }
+        "get_status_line" => {
+            let status_line = super::handlers::StatusLineResponse::new(session);
+            WsResponse::success(id, method, serde_json::to_value(status_line).unwrap())
+        }
+        "set_status_line" => {
+            let params: crate::status_line::StatusLinePatch = match parse_params(req) {
+                Ok(p) => p,
+                Err(e) => return e,
+            };
+            // The server's refresher redraws it within a second.
+            match session.status_line.patch(params) {
+                Ok(config) => WsResponse::success(id, method, serde_json::to_value(config).unwrap()),
+                Err(e) => WsResponse::error(id, method, "invalid_request", &e),
+            }
+        }
+        "append_panel" | "scroll_panel" => {
+            let (panel_id, result) = if method == "append_panel" {
+                let params: AppendPanelParams = match parse_params(req) {
+                    Ok(p) => p,
+                    Err(e) => return e,
+                };
+                let result = session.panels.append(&params.id, params.spans);
+                (params.id, result)
+            } else {
+                let params: ScrollPanelParams = match parse_params(req) {
+                    Ok(p) => p,
+                    Err(e) => return e,
+                };
+                let result = session.panels.scroll(&params.id, params.offset);
+                (params.id, result)
+            };
+            match result {
+                Err(e) => WsResponse::error(id, method, "invalid_overlay", e),
+                Ok(true) => {
+                    crate::panel::flush_panel_content(
+                        &session.panels,
+                        &panel_id,
+                        &session.terminal_size,
+                    );
+                    let _ = session.visual_update_tx.send(crate::protocol::VisualUpdate::PanelsChanged);
+                    WsResponse::success(id, method, serde_json::json!({}))
+                }
+                Ok(false) => WsResponse::error(
+                    id,
+                    method,
+                    "panel_not_found",
+                    &format!("No panel exists with id '{}'.", panel_id),
+                )
+            }
+        }
         "panel_region_write" => {
             let params: PanelRegionWriteParams = match parse_params(req) {
                 Ok(p) => p,
@@ -1174,13 +1583,35 @@ mod tests {
         let parser = Parser::spawn(parser_rx, 80, 24, 1000);
         let session = Session {
             name: "test".to_string(),
-            pid: None,
+            pid: Default::default(),
             command: "test".to_string(),
             client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
             child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            size_arbiter: crate::terminal::SizeArbiter::default(),
+            usage: crate::usage::UsageCounters::new(),
+            idle_kill_after: Default::default(),
+            suspended: Default::default(),
+            hibernated: Default::default(),
+            exit_status: Default::default(),
+            restart: Default::default(),
+            scrollback_lines: Default::default(),
+            query_policy: Default::default(),
+            handoff: Default::default(),
+            policy: Default::default(),
+            redactor: Default::default(),
+            resume_tokens: Default::default(),
+            bookmarks: Default::default(),
+            status_line: Default::default(),
+            pipes: Default::default(),
+            approvals: Default::default(),
+            metadata: Default::default(),
+            created_at: Default::default(),
+            run_as: None,
+            safe_mode: None,
+            owner: Default::default(),
             input_tx,
-            output_rx: broker.sender(),
+            output_rx: broker.clone(),
             shutdown: ShutdownCoordinator::new(),
             parser,
             overlays: OverlayStore::new(),
@@ -1199,6 +1630,12 @@ mod tests {
         (session, input_rx, parser_tx)
     }
 
+    fn test_source() -> crate::input::InputSource {
+        crate::input::InputSource::WebSocket {
+            connection: "ws-test".to_string(),
+        }
+    }
+
     #[tokio::test]
     async fn dispatch_unknown_method() {
         let (session, _rx, _parser_tx) = create_test_session();
@@ -1207,7 +1644,7 @@ mod tests {
             method: "do_magic".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["error"]["code"], "unknown_method");
         assert_eq!(json["method"], "do_magic");
@@ -1221,7 +1658,7 @@ mod tests {
             method: "get_input_mode".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["id"], 1);
         assert_eq!(json["method"], "get_input_mode");
@@ -1238,7 +1675,7 @@ mod tests {
             method: "capture_input".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         assert!(serde_json::to_value(&resp).unwrap()["result"].is_object());
 
         // Verify mode changed
@@ -1247,7 +1684,7 @@ mod tests {
             method: "get_input_mode".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["result"]["mode"], "capture");
 
@@ -1257,7 +1694,7 @@ mod tests {
             method: "release_input".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         assert!(serde_json::to_value(&resp).unwrap()["result"].is_object());
 
         // Verify
@@ -1266,11 +1703,55 @@ mod tests {
             method: "get_input_mode".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["result"]["mode"], "passthrough");
     }
 
+    #[tokio::test]
+    async fn dispatch_handoff_round_trip() {
+        let (session, _rx, _parser_tx) = create_test_session();
+        let call = |method: &str, params: Option<serde_json::Value>| WsRequest {
+            id: None,
+            method: method.to_string(),
+            params,
+        };
+
+        let resp = dispatch(
+            &call("request_handoff", Some(json!({"agent": "claude", "reason": "deploy"}))),
+            &session,
+            &test_source(),
+        )
+        .await;
+        let json = serde_json::to_value(&resp).unwrap();
+        assert_eq!(json["result"]["controller"], "human");
+        assert_eq!(json["result"]["pending"]["agent"], "claude");
+        assert_eq!(json["result"]["pending"]["reason"], "deploy");
+
+        let resp = dispatch(&call("request_handoff", None), &session, &test_source()).await;
+        let json = serde_json::to_value(&resp).unwrap();
+        assert_eq!(json["error"]["code"], "handoff_conflict");
+
+        let resp = dispatch(&call("approve_handoff", None), &session, &test_source()).await;
+        let json = serde_json::to_value(&resp).unwrap();
+        assert_eq!(json["result"]["controller"], "agent");
+        assert_eq!(json["result"]["agent"], "claude");
+        assert!(session.input_mode.is_capture());
+
+        let resp = dispatch(&call("return_control", None), &session, &test_source()).await;
+        let json = serde_json::to_value(&resp).unwrap();
+        assert_eq!(json["result"]["controller"], "human");
+        assert!(!session.input_mode.is_capture());
+
+        let resp = dispatch(&call("deny_handoff", None), &session, &test_source()).await;
+        let json = serde_json::to_value(&resp).unwrap();
+        assert_eq!(json["error"]["code"], "handoff_conflict");
+
+        let audit = session.input_broadcaster.audit_entries(None, None);
+        assert_eq!(audit.len(), 3);
+        assert_eq!(audit[2].source, test_source());
+    }
+
     #[tokio::test]
     async fn dispatch_list_overlays_empty() {
         let (session, _rx, _parser_tx) = create_test_session();
@@ -1279,7 +1760,7 @@ mod tests {
             method: "list_overlays".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["result"], serde_json::json!([]));
     }
@@ -1295,7 +1776,7 @@ mod tests {
             method: "clear_overlays".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         assert!(serde_json::to_value(&resp).unwrap()["result"].is_object());
         assert_eq!(session.overlays.list().len(), 0);
     }
@@ -1308,7 +1789,7 @@ mod tests {
             method: "get_screen".to_string(),
             params: Some(serde_json::json!({"format": "plain"})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"]["cols"].is_number());
         assert!(json["result"]["rows"].is_number());
@@ -1323,11 +1804,34 @@ mod tests {
             method: "get_screen".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"]["cols"].is_number());
     }
 
+    #[tokio::test]
+    async fn dispatch_get_modes() {
+        let (session, _rx, parser_tx) = create_test_session();
+        parser_tx.send(bytes::Bytes::from_static(b"\x1b[?2004h\x1b[>4;2m")).await.unwrap();
+        let req = WsRequest {
+            id: None,
+            method: "get_modes".to_string(),
+            params: None,
+        };
+        let mut json = serde_json::Value::Null;
+        for _ in 0..50 {
+            let resp = dispatch(&req, &session, &test_source()).await;
+            json = serde_json::to_value(&resp).unwrap();
+            if json["result"]["bracketed_paste"] == true {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(json["result"]["bracketed_paste"], true);
+        assert_eq!(json["result"]["modify_other_keys"], 2);
+        assert_eq!(json["result"]["mouse_tracking"], "off");
+    }
+
     #[tokio::test]
     async fn dispatch_get_scrollback() {
         let (session, _rx, _parser_tx) = create_test_session();
@@ -1336,7 +1840,7 @@ mod tests {
             method: "get_scrollback".to_string(),
             params: Some(serde_json::json!({"format": "plain", "offset": 0, "limit": 10})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"]["total_lines"].is_number());
         assert!(json["result"]["lines"].is_array());
@@ -1350,12 +1854,17 @@ mod tests {
             method: "send_input".to_string(),
             params: Some(serde_json::json!({"data": "hello"})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
 
         let received = rx.try_recv().unwrap();
         assert_eq!(received.as_ref(), b"hello");
+
+        let audit = session.input_broadcaster.audit_entries(None, None);
+        assert_eq!(audit.len(), 1);
+        assert_eq!(audit[0].source, test_source());
+        assert_eq!(audit[0].text, "hello");
     }
 
     #[tokio::test]
@@ -1368,7 +1877,7 @@ mod tests {
             method: "send_input".to_string(),
             params: Some(serde_json::json!({"data": encoded, "encoding": "base64"})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
 
@@ -1376,6 +1885,44 @@ mod tests {
         assert_eq!(received.as_ref(), b"\x03");
     }
 
+    #[tokio::test]
+    async fn dispatch_send_keys_follows_application_cursor_mode() {
+        let (session, mut rx, parser_tx) = create_test_session();
+        let req = WsRequest {
+            id: None,
+            method: "send_keys".to_string(),
+            params: Some(serde_json::json!({"keys": ["ArrowUp", "ctrl+c"]})),
+        };
+        let resp = dispatch(&req, &session, &test_source()).await;
+        assert!(serde_json::to_value(&resp).unwrap()["result"].is_object());
+        assert_eq!(rx.try_recv().unwrap().as_ref(), b"\x1b[A\x03");
+
+        // Once the application enables DECCKM, arrows switch to SS3 form
+        parser_tx.send(Bytes::from_static(b"\x1b[?1h")).await.unwrap();
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+        while !session.parser.keyboard_modes().await.unwrap().application_cursor {
+            assert!(tokio::time::Instant::now() < deadline, "DECCKM never applied");
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let resp = dispatch(&req, &session, &test_source()).await;
+        assert!(serde_json::to_value(&resp).unwrap()["result"].is_object());
+        assert_eq!(rx.try_recv().unwrap().as_ref(), b"\x1bOA\x03");
+    }
+
+    #[tokio::test]
+    async fn dispatch_send_keys_unknown_key() {
+        let (session, mut rx, _parser_tx) = create_test_session();
+        let req = WsRequest {
+            id: None,
+            method: "send_keys".to_string(),
+            params: Some(serde_json::json!({"keys": ["Enter", "Hyper"]})),
+        };
+        let resp = dispatch(&req, &session, &test_source()).await;
+        let json = serde_json::to_value(&resp).unwrap();
+        assert_eq!(json["error"]["code"], "invalid_request");
+        assert!(rx.try_recv().is_err());
+    }
+
     #[tokio::test]
     async fn dispatch_send_input_bad_base64() {
         let (session, _rx, _parser_tx) = create_test_session();
@@ -1384,7 +1931,7 @@ mod tests {
             method: "send_input".to_string(),
             params: Some(serde_json::json!({"data": "!!!not-base64!!!", "encoding": "base64"})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["error"]["code"], "invalid_request");
     }
@@ -1397,7 +1944,7 @@ mod tests {
             method: "resize".to_string(),
             params: Some(json!({"cols": 120, "rows": 40})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         assert!(json.get("error").is_none());
@@ -1416,7 +1963,7 @@ mod tests {
             method: "resize".to_string(),
             params: Some(json!({"cols": 0, "rows": 0})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
 
@@ -1436,7 +1983,7 @@ mod tests {
                 "spans": [{"text": "Hello"}]
             })),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"]["id"].is_string());
         assert_eq!(session.overlays.list().len(), 1);
@@ -1454,7 +2001,7 @@ mod tests {
             method: "get_overlay".to_string(),
             params: Some(serde_json::json!({"id": id})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["result"]["x"], 5);
         assert_eq!(json["result"]["y"], 10);
@@ -1468,7 +2015,7 @@ mod tests {
             method: "get_overlay".to_string(),
             params: Some(serde_json::json!({"id": "nonexistent"})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["error"]["code"], "overlay_not_found");
     }
@@ -1485,7 +2032,7 @@ mod tests {
             method: "update_overlay".to_string(),
             params: Some(serde_json::json!({"id": id, "spans": [{"text": "New"}]})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         let overlay = session.overlays.get(&id).unwrap();
@@ -1501,7 +2048,7 @@ mod tests {
             method: "patch_overlay".to_string(),
             params: Some(serde_json::json!({"id": id, "x": 20, "y": 30})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         let overlay = session.overlays.get(&id).unwrap();
@@ -1518,7 +2065,7 @@ mod tests {
             method: "delete_overlay".to_string(),
             params: Some(serde_json::json!({"id": id})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         assert!(session.overlays.get(&id).is_none());
@@ -1532,7 +2079,7 @@ mod tests {
             method: "delete_overlay".to_string(),
             params: Some(serde_json::json!({"id": "nonexistent"})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["error"]["code"], "overlay_not_found");
     }
@@ -1549,7 +2096,7 @@ mod tests {
             method: "list_panels".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["result"], serde_json::json!([]));
     }
@@ -1566,7 +2113,7 @@ mod tests {
                 "spans": [{"text": "Status"}]
             })),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"]["id"].is_string());
         assert_eq!(json["id"], 1);
@@ -1593,7 +2140,7 @@ mod tests {
             method: "get_panel".to_string(),
             params: Some(json!({"id": panel_id})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["result"]["position"], "top");
         assert_eq!(json["result"]["height"], 1);
@@ -1608,7 +2155,7 @@ mod tests {
             method: "get_panel".to_string(),
             params: Some(json!({"id": "nonexistent"})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["error"]["code"], "panel_not_found");
     }
@@ -1637,7 +2184,7 @@ mod tests {
                 "spans": [{"text": "Updated"}]
             })),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         let updated = session.panels.get(&panel_id).unwrap();
@@ -1660,7 +2207,7 @@ mod tests {
                 "spans": []
             })),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["error"]["code"], "panel_not_found");
     }
@@ -1685,7 +2232,7 @@ mod tests {
                 "height": 5
             })),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         let patched = session.panels.get(&panel_id).unwrap();
@@ -1701,7 +2248,7 @@ mod tests {
             method: "patch_panel".to_string(),
             params: Some(json!({"id": "nonexistent", "height": 2})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["error"]["code"], "panel_not_found");
     }
@@ -1724,7 +2271,7 @@ mod tests {
             method: "delete_panel".to_string(),
             params: Some(json!({"id": panel_id})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         assert!(session.panels.get(&panel_id).is_none());
@@ -1738,7 +2285,7 @@ mod tests {
             method: "delete_panel".to_string(),
             params: Some(json!({"id": "nonexistent"})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["error"]["code"], "panel_not_found");
     }
@@ -1755,7 +2302,7 @@ mod tests {
             method: "clear_panels".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         assert_eq!(session.panels.list().len(), 0);
@@ -1781,7 +2328,7 @@ mod tests {
             method: "list_panels".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         let panels = json["result"].as_array().unwrap();
         assert_eq!(panels.len(), 1);
@@ -1810,7 +2357,7 @@ mod tests {
                 "spans": [{"id": "lbl", "text": "New"}]
             })),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         let overlay = session.overlays.get(&oid).unwrap();
@@ -1825,7 +2372,7 @@ mod tests {
             method: "update_overlay_spans".to_string(),
             params: Some(json!({"id": "nonexistent", "spans": []})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["error"]["code"], "overlay_not_found");
     }
@@ -1842,7 +2389,7 @@ mod tests {
                 "writes": [{"row": 0, "col": 0, "text": "X"}]
             })),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         let overlay = session.overlays.get(&oid).unwrap();
@@ -1858,7 +2405,7 @@ mod tests {
             method: "overlay_region_write".to_string(),
             params: Some(json!({"id": "nonexistent", "writes": []})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["error"]["code"], "overlay_not_found");
     }
@@ -1887,7 +2434,7 @@ mod tests {
                 "spans": [{"id": "tag", "text": "Updated"}]
             })),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         let panel = session.panels.get(&pid).unwrap();
@@ -1902,7 +2449,7 @@ mod tests {
             method: "update_panel_spans".to_string(),
             params: Some(json!({"id": "nonexistent", "spans": []})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["error"]["code"], "panel_not_found");
     }
@@ -1927,7 +2474,7 @@ mod tests {
                 "writes": [{"row": 1, "col": 2, "text": "Y"}]
             })),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         let panel = session.panels.get(&pid).unwrap();
@@ -1945,7 +2492,7 @@ mod tests {
             method: "panel_region_write".to_string(),
             params: Some(json!({"id": "nonexistent", "writes": []})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["error"]["code"], "panel_not_found");
     }
@@ -1970,7 +2517,7 @@ mod tests {
                 "writes": [{"row": 2, "col": 0, "text": "Cell"}]
             })),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         let overlay = session.overlays.get(&oid).unwrap();
@@ -2005,7 +2552,7 @@ mod tests {
                 "writes": [{"row": 1, "col": 0, "text": "Row data"}]
             })),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         let panel = session.panels.get(&pid).unwrap();
@@ -2026,7 +2573,7 @@ mod tests {
                 "spans": []
             })),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["error"]["code"], "overlay_not_found");
     }
@@ -2043,7 +2590,7 @@ mod tests {
                 "spans": []
             })),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["error"]["code"], "panel_not_found");
     }
@@ -2067,7 +2614,7 @@ mod tests {
                 "spans": [{"id": "a", "text": "B"}]
             })),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         let overlay = session.overlays.get(&oid).unwrap();
@@ -2088,7 +2635,7 @@ mod tests {
                 "writes": [{"row": 0, "col": 0, "text": "Z"}]
             })),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         let overlay = session.overlays.get(&oid).unwrap();
@@ -2108,7 +2655,7 @@ mod tests {
             method: "get_focus".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"]["focused"].is_null());
     }
@@ -2122,7 +2669,7 @@ mod tests {
             method: "focus".to_string(),
             params: Some(json!({"id": oid})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         assert_eq!(session.focus.focused(), Some(oid));
@@ -2137,7 +2684,7 @@ mod tests {
             method: "focus".to_string(),
             params: Some(json!({"id": oid})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["error"]["code"], "not_focusable");
     }
@@ -2150,7 +2697,7 @@ mod tests {
             method: "focus".to_string(),
             params: Some(json!({"id": "nonexistent"})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["error"]["code"], "invalid_request");
     }
@@ -2172,7 +2719,7 @@ mod tests {
             method: "focus".to_string(),
             params: Some(json!({"id": pid})),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         assert_eq!(session.focus.focused(), Some(pid));
@@ -2190,7 +2737,7 @@ mod tests {
             method: "unfocus".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         assert!(session.focus.focused().is_none());
@@ -2207,7 +2754,7 @@ mod tests {
             method: "get_focus".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["result"]["focused"], oid);
     }
@@ -2224,7 +2771,7 @@ mod tests {
             method: "get_screen_mode".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["result"]["mode"], "normal");
     }
@@ -2237,7 +2784,7 @@ mod tests {
             method: "enter_alt_screen".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         assert_eq!(*session.screen_mode.read(), crate::overlay::ScreenMode::Alt);
@@ -2253,7 +2800,7 @@ mod tests {
             method: "enter_alt_screen".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["error"]["code"], "already_in_alt_screen");
     }
@@ -2268,7 +2815,7 @@ mod tests {
             method: "exit_alt_screen".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert!(json["result"].is_object());
         assert_eq!(*session.screen_mode.read(), crate::overlay::ScreenMode::Normal);
@@ -2283,7 +2830,7 @@ mod tests {
             method: "exit_alt_screen".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["error"]["code"], "not_in_alt_screen");
     }
@@ -2298,7 +2845,7 @@ mod tests {
             method: "get_screen_mode".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["result"]["mode"], "normal");
 
@@ -2308,7 +2855,7 @@ mod tests {
             method: "enter_alt_screen".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         assert!(serde_json::to_value(&resp).unwrap()["result"].is_object());
 
         // Verify mode is alt
@@ -2317,7 +2864,7 @@ mod tests {
             method: "get_screen_mode".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["result"]["mode"], "alt");
 
@@ -2327,7 +2874,7 @@ mod tests {
             method: "exit_alt_screen".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         assert!(serde_json::to_value(&resp).unwrap()["result"].is_object());
 
         // Verify mode is normal again
@@ -2336,7 +2883,7 @@ mod tests {
             method: "get_screen_mode".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         assert_eq!(json["result"]["mode"], "normal");
     }
@@ -2360,7 +2907,7 @@ mod tests {
             method: "list_overlays".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         let overlays = json["result"].as_array().unwrap();
         assert_eq!(overlays.len(), 1);
@@ -2369,7 +2916,7 @@ mod tests {
         *session.screen_mode.write() = crate::overlay::ScreenMode::Normal;
 
         // list_overlays should only return normal-mode overlays
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         let overlays = json["result"].as_array().unwrap();
         assert_eq!(overlays.len(), 1);
@@ -2410,7 +2957,7 @@ mod tests {
             method: "list_panels".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         let panels = json["result"].as_array().unwrap();
         assert_eq!(panels.len(), 1);
@@ -2419,7 +2966,7 @@ mod tests {
         *session.screen_mode.write() = crate::overlay::ScreenMode::Normal;
 
         // list_panels should only return normal-mode panels
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         let panels = json["result"].as_array().unwrap();
         assert_eq!(panels.len(), 1);
@@ -2441,7 +2988,7 @@ mod tests {
                 "spans": [{"text": "Alt overlay"}]
             })),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         let overlay_id = json["result"]["id"].as_str().unwrap().to_string();
 
@@ -2467,7 +3014,7 @@ mod tests {
                 "spans": [{"text": "Alt panel"}]
             })),
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         let json = serde_json::to_value(&resp).unwrap();
         let panel_id = json["result"]["id"].as_str().unwrap().to_string();
 
@@ -2500,7 +3047,7 @@ mod tests {
             method: "exit_alt_screen".to_string(),
             params: None,
         };
-        let resp = dispatch(&req, &session).await;
+        let resp = dispatch(&req, &session, &test_source()).await;
         assert!(serde_json::to_value(&resp).unwrap()["result"].is_object());
 
         // Alt elements should be deleted, normal elements preserved
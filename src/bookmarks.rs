@@ -0,0 +1,144 @@
+//! Named markers in a session's output.
+//!
+//! `POST /sessions/{name}/bookmarks` records the line the cursor is on, and
+//! `GET /sessions/{name}/scrollback?since_bookmark=...` reads from there, so
+//! an orchestrator can get "the output since I started the tests" without
+//! keeping track of offsets itself. Bookmarks hold line numbers from
+//! [`Parser::mark`](crate::parser::Parser::mark), which stay put as old
+//! lines are dropped from the scrollback; once a bookmarked line has been
+//! dropped, reading since it starts at the oldest line left.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+/// Most bookmarks a session keeps.
+pub const MAX_BOOKMARKS: usize = 256;
+
+/// Longest bookmark name, in bytes.
+pub const MAX_NAME_LEN: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Bookmark {
+    pub name: String,
+    /// Line number from [`Parser::mark`](crate::parser::Parser::mark).
+    pub line: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Unix time the bookmark was set, in milliseconds.
+    pub created_at: u64,
+}
+
+impl Bookmark {
+    /// The bookmark's current scrollback offset, or `None` if its line has
+    /// been dropped from the scrollback.
+    pub fn offset(&self, lines_dropped: u64) -> Option<usize> {
+        self.line.checked_sub(lines_dropped).map(|offset| offset as usize)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BookmarkError {
+    #[error("bookmark names must be 1 to {MAX_NAME_LEN} letters, digits, '.', '_' or '-'")]
+    InvalidName,
+    #[error("a session can have at most {MAX_BOOKMARKS} bookmarks")]
+    TooMany,
+}
+
+/// A session's bookmarks, by name. Clones share the same set.
+#[derive(Clone, Default)]
+pub struct Bookmarks {
+    inner: Arc<Mutex<BTreeMap<String, Bookmark>>>,
+}
+
+impl Bookmarks {
+    /// Set a bookmark, moving it if one with that name exists.
+    pub fn set(&self, name: &str, line: u64, note: Option<String>) -> Result<Bookmark, BookmarkError> {
+        validate_name(name)?;
+        let mut inner = self.inner.lock();
+        if !inner.contains_key(name) && inner.len() >= MAX_BOOKMARKS {
+            return Err(BookmarkError::TooMany);
+        }
+        let bookmark = Bookmark {
+            name: name.to_string(),
+            line,
+            note,
+            created_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+        };
+        inner.insert(name.to_string(), bookmark.clone());
+        Ok(bookmark)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Bookmark> {
+        self.inner.lock().get(name).cloned()
+    }
+
+    /// All bookmarks, in output order.
+    pub fn list(&self) -> Vec<Bookmark> {
+        let mut list: Vec<_> = self.inner.lock().values().cloned().collect();
+        list.sort_by_key(|b| (b.line, b.created_at));
+        list
+    }
+
+    /// Remove a bookmark, returning whether it existed.
+    pub fn remove(&self, name: &str) -> bool {
+        self.inner.lock().remove(name).is_some()
+    }
+}
+
+/// Whether `name` can name a bookmark. Valid names need no escaping in
+/// URLs.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+fn validate_name(name: &str) -> Result<(), BookmarkError> {
+    if is_valid_name(name) { Ok(()) } else { Err(BookmarkError::InvalidName) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_moves_existing_bookmarks() {
+        let bookmarks = Bookmarks::default();
+        bookmarks.set("build-start", 10, None).unwrap();
+        bookmarks.set("deploy", 4, Some("v2".into())).unwrap();
+        bookmarks.set("build-start", 20, None).unwrap();
+
+        let names: Vec<_> = bookmarks.list().into_iter().map(|b| (b.name, b.line)).collect();
+        assert_eq!(names, [("deploy".to_string(), 4), ("build-start".to_string(), 20)]);
+        assert_eq!(bookmarks.get("deploy").unwrap().note.as_deref(), Some("v2"));
+        assert!(bookmarks.remove("deploy"));
+        assert!(!bookmarks.remove("deploy"));
+    }
+
+    #[test]
+    fn names_and_count_are_limited() {
+        let bookmarks = Bookmarks::default();
+        assert!(matches!(bookmarks.set("", 0, None), Err(BookmarkError::InvalidName)));
+        assert!(matches!(bookmarks.set("a b", 0, None), Err(BookmarkError::InvalidName)));
+        for i in 0..MAX_BOOKMARKS {
+            bookmarks.set(&format!("b{i}"), 0, None).unwrap();
+        }
+        assert!(matches!(bookmarks.set("extra", 0, None), Err(BookmarkError::TooMany)));
+        // Moving an existing one is still allowed.
+        bookmarks.set("b0", 5, None).unwrap();
+    }
+
+    #[test]
+    fn offsets_follow_dropped_lines() {
+        let bookmark = Bookmarks::default().set("x", 100, None).unwrap();
+        assert_eq!(bookmark.offset(0), Some(100));
+        assert_eq!(bookmark.offset(40), Some(60));
+        assert_eq!(bookmark.offset(101), None);
+    }
+}
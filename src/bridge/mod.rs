@@ -0,0 +1,3 @@
+//! Bridges that mirror sessions of other terminal multiplexers into wsh.
+
+pub mod tmux;
@@ -0,0 +1,494 @@
+//! Mirroring tmux panes through tmux control mode.
+//!
+//! The attach import in [`crate::tmux`] runs a whole tmux client inside the
+//! wsh session, status line and prefix key included. A control-mode import
+//! gives each tmux pane its own wsh session instead, whose child is the
+//! `wsh __tmux-bridge` relay: it attaches to the tmux server as a control
+//! client (`tmux -C`), writes the pane's `%output` to its terminal, and
+//! turns input typed into the wsh session into `send-keys`. The wsh screen
+//! shows exactly what the program in the pane draws, and API clients can
+//! read and drive every pane separately.
+//!
+//! The relay attaches with `ignore-size`, so it never resizes tmux windows;
+//! the pane keeps the size tmux gives it. The relay exits, ending its wsh
+//! session, when the pane or the tmux server goes away. Ending the wsh
+//! session leaves the pane running.
+
+use std::ffi::OsString;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::process::Stdio;
+
+use serde::Serialize;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+use crate::pty::shell_quote;
+
+/// First argument that starts wsh as a control-mode relay:
+/// `wsh __tmux-bridge <pane-id> [<socket>]`.
+pub const BRIDGE_ARG: &str = "__tmux-bridge";
+
+/// A pane on a tmux server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TmuxPane {
+    /// tmux's pane id, like `%3`; unique on the server for the pane's lifetime.
+    pub id: String,
+    pub window_index: u32,
+    pub pane_index: u32,
+    /// Number of panes in the pane's window.
+    pub window_panes: u32,
+    pub cols: u16,
+    pub rows: u16,
+}
+
+const PANE_FORMAT: &str =
+    "#{pane_id}:#{window_index}:#{pane_index}:#{window_panes}:#{pane_width}:#{pane_height}";
+
+/// Marker printed after a capture to report the cursor and window; see
+/// [`sync_command`].
+const CURSOR_MARKER: &str = "wsh-cursor ";
+
+/// Bytes of input per `send-keys` command.
+const SEND_KEYS_CHUNK: usize = 256;
+
+/// List the panes of every window of tmux session `tmux_name`.
+pub fn list_panes(tmux_name: &str, socket: Option<&str>) -> Result<Vec<TmuxPane>, String> {
+    let output = crate::tmux::command(socket)
+        .args(["list-panes", "-s", "-t", &format!("={}", tmux_name), "-F", PANE_FORMAT])
+        .output()
+        .map_err(crate::tmux::spawn_error)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("tmux list-panes failed: {}", stderr.trim()));
+    }
+    Ok(parse_panes(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn parse_panes(output: &str) -> Vec<TmuxPane> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let id = fields.next().filter(|id| id.starts_with('%'))?;
+            Some(TmuxPane {
+                id: id.to_string(),
+                window_index: fields.next()?.parse().ok()?,
+                pane_index: fields.next()?.parse().ok()?,
+                window_panes: fields.next()?.parse().ok()?,
+                cols: fields.next()?.parse().ok()?,
+                rows: fields.next()?.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// The wsh session name for a pane of `tmux_name`: the attach import's name
+/// plus `-<window>`, and `.<pane>` when the window is split.
+pub fn session_name(tmux_name: &str, pane: &TmuxPane) -> String {
+    let suffix = if pane.window_panes > 1 {
+        format!("-{}.{}", pane.window_index, pane.pane_index)
+    } else {
+        format!("-{}", pane.window_index)
+    };
+    // Cut the tmux name rather than the suffix so panes stay distinct.
+    let mut name = crate::tmux::session_name(tmux_name);
+    name.truncate(64usize.saturating_sub(suffix.len()));
+    name + &suffix
+}
+
+/// Shell command that runs the relay for `pane_id` from the wsh binary at
+/// `exe`.
+pub fn relay_command(exe: &Path, pane_id: &str, socket: Option<&str>) -> String {
+    let mut command = format!(
+        "unset TMUX; exec {} {} {}",
+        shell_quote(&exe.to_string_lossy()),
+        BRIDGE_ARG,
+        shell_quote(pane_id)
+    );
+    if let Some(socket) = socket {
+        command.push(' ');
+        command.push_str(&shell_quote(socket));
+    }
+    command
+}
+
+/// Commands that capture `pane` and then print [`CURSOR_MARKER`] with the
+/// cursor position and window id. tmux answers each with its own output
+/// block.
+fn sync_command(pane: &str) -> String {
+    format!(
+        "capture-pane -p -e -t {pane} ; display-message -p -t {pane} '{CURSOR_MARKER}#{{cursor_x}} #{{cursor_y}} #{{window_id}}'\n"
+    )
+}
+
+/// `send-keys` commands that type `input` into `pane` byte for byte.
+fn send_keys_commands(pane: &str, input: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in input.chunks(SEND_KEYS_CHUNK) {
+        out.push_str("send-keys -t ");
+        out.push_str(pane);
+        out.push_str(" -H");
+        for byte in chunk {
+            out.push_str(&format!(" {:02x}", byte));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Undo control mode's escaping of `%output` data: bytes below space and
+/// backslashes arrive as three-digit octal escapes.
+fn decode_output(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        let octal = data.get(i + 1..i + 4).filter(|d| d.iter().all(|b| (b'0'..=b'7').contains(b)));
+        match (data[i], octal) {
+            (b'\\', Some(digits)) => {
+                let value = digits.iter().fold(0u32, |acc, d| acc * 8 + u32::from(d - b'0'));
+                out.push(value as u8);
+                i += 4;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Screen contents that reproduce a capture: the visible lines, keeping at
+/// most `rows` from the bottom, then the cursor.
+fn redraw(lines: &[Vec<u8>], cursor_x: u16, cursor_y: u16, rows: u16) -> Vec<u8> {
+    let skip = lines.len().saturating_sub(usize::from(rows.max(1)));
+    let mut out = b"\x1b[0m\x1b[H\x1b[2J".to_vec();
+    for (i, line) in lines[skip..].iter().enumerate() {
+        if i > 0 {
+            out.extend_from_slice(b"\r\n");
+        }
+        out.extend_from_slice(line);
+        out.extend_from_slice(b"\x1b[0m");
+    }
+    let row = usize::from(cursor_y).saturating_sub(skip) + 1;
+    let _ = write!(out, "\x1b[{};{}H", row, cursor_x + 1);
+    out
+}
+
+/// What the relay does about a line from tmux.
+#[derive(Debug, PartialEq, Eq)]
+enum Event {
+    /// Bytes to write to the terminal.
+    Output(Vec<u8>),
+    /// The pane's window changed layout; capture the pane again.
+    Resync,
+    /// The pane is gone or tmux is exiting.
+    Exit,
+}
+
+/// Control-mode protocol state for following one pane.
+struct ControlParser {
+    pane: String,
+    /// Rows of the relay's terminal, for fitting captures.
+    rows: u16,
+    /// Whether tmux has finished attaching the relay. Pane output that
+    /// comes before then isn't sent to the relay at all.
+    attached: bool,
+    /// The pane's window id (`@N`), learned from the first capture.
+    window: Option<String>,
+    /// Whether the latest capture has been drawn. `%output` that arrives
+    /// before then is already part of the capture and is dropped.
+    synced: bool,
+    /// Lines of the output block being read.
+    block: Option<Vec<Vec<u8>>>,
+    /// The last non-empty output block, which is the capture once the
+    /// cursor marker follows it.
+    capture: Vec<Vec<u8>>,
+}
+
+impl ControlParser {
+    fn new(pane: &str, rows: u16) -> Self {
+        Self {
+            pane: pane.to_string(),
+            rows,
+            attached: false,
+            window: None,
+            synced: false,
+            block: None,
+            capture: Vec::new(),
+        }
+    }
+
+    /// Handle one line from tmux, without its newline.
+    fn line(&mut self, line: &[u8]) -> Option<Event> {
+        if let Some(block) = self.block.as_mut() {
+            let ok = line.starts_with(b"%end ");
+            if ok || line.starts_with(b"%error ") {
+                let block = self.block.take().unwrap_or_default();
+                return self.finish_block(block, ok);
+            }
+            block.push(line.to_vec());
+            return None;
+        }
+
+        let (kind, rest) = split_word(line);
+        match kind {
+            b"%begin" => {
+                self.block = Some(Vec::new());
+                None
+            }
+            b"%output" => {
+                let (pane, data) = split_word(rest);
+                (self.synced && pane == self.pane.as_bytes()).then(|| Event::Output(decode_output(data)))
+            }
+            b"%session-changed" if !self.attached => {
+                self.attached = true;
+                Some(Event::Resync)
+            }
+            b"%layout-change" => self.is_our_window(rest).then_some(Event::Resync),
+            b"%window-close" | b"%unlinked-window-close" => self.is_our_window(rest).then_some(Event::Exit),
+            b"%exit" => Some(Event::Exit),
+            _ => None,
+        }
+    }
+
+    /// Mark the pane as needing a new capture.
+    fn resync(&mut self) {
+        self.synced = false;
+    }
+
+    /// Whether the pane has been drawn once, so input can be sent.
+    fn ready(&self) -> bool {
+        self.window.is_some()
+    }
+
+    fn is_our_window(&self, rest: &[u8]) -> bool {
+        let (window, _) = split_word(rest);
+        self.window.as_deref().is_some_and(|w| w.as_bytes() == window)
+    }
+
+    fn finish_block(&mut self, lines: Vec<Vec<u8>>, ok: bool) -> Option<Event> {
+        if !ok {
+            // Until the first capture succeeds, an error means the pane
+            // doesn't exist. Later ones come from commands racing the
+            // pane's removal, and the close notification ends the relay.
+            return self.window.is_none().then_some(Event::Exit);
+        }
+        let marker = match lines.as_slice() {
+            [line] => line.strip_prefix(CURSOR_MARKER.as_bytes()),
+            _ => None,
+        };
+        let Some(marker) = marker else {
+            if !lines.is_empty() {
+                self.capture = lines;
+            }
+            return None;
+        };
+        let marker = String::from_utf8_lossy(marker);
+        let mut fields = marker.split(' ');
+        let x = fields.next().and_then(|f| f.parse().ok()).unwrap_or(0);
+        let y = fields.next().and_then(|f| f.parse().ok()).unwrap_or(0);
+        if let Some(window) = fields.next() {
+            self.window = Some(window.to_string());
+        }
+        self.synced = true;
+        let capture = std::mem::take(&mut self.capture);
+        Some(Event::Output(redraw(&capture, x, y, self.rows)))
+    }
+}
+
+fn split_word(line: &[u8]) -> (&[u8], &[u8]) {
+    match line.iter().position(|&b| b == b' ') {
+        Some(i) => (&line[..i], &line[i + 1..]),
+        None => (line, &[]),
+    }
+}
+
+/// Run the relay with `args`, the arguments after [`BRIDGE_ARG`], until the
+/// pane goes away or the terminal closes.
+pub async fn run(mut args: impl Iterator<Item = OsString>) -> Result<(), String> {
+    let pane = args
+        .next()
+        .and_then(|a| a.into_string().ok())
+        .filter(|p| p.starts_with('%'))
+        .ok_or("expected a pane id like %3")?;
+    let socket = args.next().and_then(|a| a.into_string().ok());
+
+    // Raw mode so that keys like Ctrl+C reach the pane instead of
+    // signalling the relay.
+    let _raw = match std::io::stdin().is_terminal() {
+        true => Some(crate::terminal::RawModeGuard::new().map_err(|e| e.to_string())?),
+        false => None,
+    };
+    let mut rows = crate::terminal::terminal_size().map(|(rows, _)| rows).unwrap_or(24);
+
+    let mut tmux = crate::tmux::command(socket.as_deref());
+    tmux.args(["-C", "attach-session", "-f", "ignore-size", "-t", &pane]);
+    let mut child = tokio::process::Command::from(tmux)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(crate::tmux::spawn_error)?;
+    let (Some(mut commands), Some(notifications)) = (child.stdin.take(), child.stdout.take()) else {
+        return Err("tmux control client has no pipes".to_string());
+    };
+    let mut notifications = BufReader::new(notifications);
+
+    let mut sigwinch = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::window_change())
+        .map_err(|e| e.to_string())?;
+    let mut stdin = tokio::io::stdin();
+    let mut stdout = tokio::io::stdout();
+    let mut parser = ControlParser::new(&pane, rows);
+    let mut line = Vec::new();
+    let mut input = [0u8; 4096];
+
+    // tmux runs commands it reads before it has finished attaching, so the
+    // first capture waits for `%session-changed` (see `ControlParser`).
+    let sync = sync_command(&pane);
+    loop {
+        tokio::select! {
+            read = notifications.read_until(b'\n', &mut line) => {
+                if read.map_err(|e| e.to_string())? == 0 {
+                    break;
+                }
+                if line.last() == Some(&b'\n') {
+                    line.pop();
+                }
+                let event = parser.line(&line);
+                line.clear();
+                match event {
+                    Some(Event::Output(bytes)) => {
+                        stdout.write_all(&bytes).await.map_err(|e| e.to_string())?;
+                        stdout.flush().await.map_err(|e| e.to_string())?;
+                    }
+                    Some(Event::Resync) => {
+                        parser.resync();
+                        commands.write_all(sync.as_bytes()).await.map_err(|e| e.to_string())?;
+                    }
+                    Some(Event::Exit) => break,
+                    None => {}
+                }
+            }
+            read = stdin.read(&mut input), if parser.ready() => {
+                let n = read.map_err(|e| e.to_string())?;
+                if n == 0 {
+                    break;
+                }
+                let keys = send_keys_commands(&pane, &input[..n]);
+                commands.write_all(keys.as_bytes()).await.map_err(|e| e.to_string())?;
+            }
+            _ = sigwinch.recv() => {
+                // Redraw the capture to fit the new height.
+                rows = crate::terminal::terminal_size().map(|(rows, _)| rows).unwrap_or(rows);
+                parser.rows = rows;
+                if parser.ready() {
+                    parser.resync();
+                    commands.write_all(sync.as_bytes()).await.map_err(|e| e.to_string())?;
+                }
+            }
+        }
+    }
+
+    // Closing its input detaches the control client.
+    drop(commands);
+    let _ = child.wait().await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(parser: &mut ControlParser, text: &str) -> Vec<Event> {
+        text.lines().filter_map(|line| parser.line(line.as_bytes())).collect()
+    }
+
+    #[test]
+    fn parse_panes_reads_fields() {
+        let out = "%0:0:0:2:40:12\n%4:1:0:1:80:24\nbroken\n";
+        assert_eq!(
+            parse_panes(out),
+            vec![
+                TmuxPane { id: "%0".into(), window_index: 0, pane_index: 0, window_panes: 2, cols: 40, rows: 12 },
+                TmuxPane { id: "%4".into(), window_index: 1, pane_index: 0, window_panes: 1, cols: 80, rows: 24 },
+            ]
+        );
+    }
+
+    #[test]
+    fn session_name_marks_split_panes() {
+        let mut pane = TmuxPane { id: "%0".into(), window_index: 2, pane_index: 1, window_panes: 1, cols: 80, rows: 24 };
+        assert_eq!(session_name("work", &pane), "tmux-work-2");
+        pane.window_panes = 2;
+        assert_eq!(session_name("work", &pane), "tmux-work-2.1");
+        let long = session_name(&"x".repeat(100), &pane);
+        assert_eq!(long.len(), 64);
+        assert!(long.ends_with("-2.1"));
+        assert!(crate::session::validate_session_name(&long).is_ok());
+    }
+
+    #[test]
+    fn relay_command_quotes_arguments() {
+        assert_eq!(
+            relay_command(Path::new("/opt/my wsh"), "%3", Some("it's")),
+            r"unset TMUX; exec '/opt/my wsh' __tmux-bridge '%3' 'it'\''s'"
+        );
+    }
+
+    #[test]
+    fn send_keys_is_hex_encoded_in_chunks() {
+        assert_eq!(send_keys_commands("%1", b"hi\r"), "send-keys -t %1 -H 68 69 0d\n");
+        assert_eq!(send_keys_commands("%1", &[b'a'; 300]).lines().count(), 2);
+    }
+
+    #[test]
+    fn decode_output_unescapes_octal() {
+        assert_eq!(decode_output(br"hi\015\012\134x\9"), b"hi\r\n\\x\\9");
+    }
+
+    #[test]
+    fn capture_is_drawn_before_output() {
+        let mut parser = ControlParser::new("%0", 24);
+        let events = feed(
+            &mut parser,
+            "%begin 1 1 0\n%end 1 1 0\n%output %0 early\n%session-changed $0 work\n\
+             %begin 1 2 1\n\x1b[1mhi\x1b[0m\n$\n%end 1 2 1\n\
+             %begin 1 3 1\nwsh-cursor 2 1 @4\n%end 1 3 1\n\
+             %output %1 other\n%output %0 late\\015\\012\n",
+        );
+        assert_eq!(
+            events,
+            vec![
+                Event::Resync,
+                Event::Output(b"\x1b[0m\x1b[H\x1b[2J\x1b[1mhi\x1b[0m\x1b[0m\r\n$\x1b[0m\x1b[2;3H".to_vec()),
+                Event::Output(b"late\r\n".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn capture_keeps_bottom_rows() {
+        let lines: Vec<Vec<u8>> = ["a", "b", "c"].iter().map(|l| l.as_bytes().to_vec()).collect();
+        assert_eq!(redraw(&lines, 0, 2, 2), b"\x1b[0m\x1b[H\x1b[2Jb\x1b[0m\r\nc\x1b[0m\x1b[2;1H");
+    }
+
+    #[test]
+    fn window_notifications_follow_the_pane() {
+        let mut parser = ControlParser::new("%0", 24);
+        feed(&mut parser, "%begin 1 2 1\nx\n%end 1 2 1\n%begin 1 3 1\nwsh-cursor 0 0 @4\n%end 1 3 1\n");
+        assert_eq!(parser.line(b"%layout-change @5 abcd,80x24,0,0,1"), None);
+        assert_eq!(parser.line(b"%layout-change @4 abcd,80x24,0,0,0"), Some(Event::Resync));
+        assert_eq!(parser.line(b"%window-close @5"), None);
+        assert_eq!(parser.line(b"%window-close @4"), Some(Event::Exit));
+        assert_eq!(parser.line(b"%exit"), Some(Event::Exit));
+    }
+
+    #[test]
+    fn missing_pane_exits() {
+        let mut parser = ControlParser::new("%9", 24);
+        let events = feed(&mut parser, "%begin 1 2 1\ncan't find pane: %9\n%error 1 2 1\n");
+        assert_eq!(events, vec![Event::Exit]);
+    }
+}
@@ -1,14 +1,32 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
 use bytes::Bytes;
-use tokio::sync::broadcast;
+use parking_lot::RwLock;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+use tokio::sync::watch;
+
+/// Bytes of output the ring keeps for subscribers that haven't read it yet.
+pub const RING_CAPACITY: usize = 256 * 1024;
 
-pub const BROADCAST_CAPACITY: usize = 64;
+/// Most bytes a subscriber takes in one read.
+pub const MAX_READ: usize = 64 * 1024;
 
-/// Distributes PTY output to streaming API clients via a broadcast channel.
+/// Distributes PTY output to streaming API clients through a shared ring
+/// buffer.
+///
+/// Output is copied once into a fixed-size byte ring. Each subscriber keeps
+/// its own cursor into it and, when it reads, takes everything it hasn't
+/// seen yet (up to [`MAX_READ`]) as one chunk. Memory per session stays at
+/// [`RING_CAPACITY`] however many clients watch it, publishing doesn't
+/// allocate, and a subscriber that reads less often than output arrives
+/// gets fewer, larger chunks instead of one per PTY read.
 ///
-/// The broadcast channel is lossy by design: if a subscriber falls behind,
-/// it receives a `Lagged` error and must re-query state. This is fine for
-/// streaming clients (WebSocket, socket) because they can recover by
-/// re-fetching the current screen.
+/// The ring is lossy by design: a subscriber that falls more than
+/// [`RING_CAPACITY`] bytes behind gets [`RecvError::Lagged`] with the
+/// number of bytes it missed, skips to the newest output, and must
+/// re-query state. This is fine for streaming clients (WebSocket, socket)
+/// because they can recover by re-fetching the current screen.
 ///
 /// # Parser channel is NOT here
 ///
@@ -17,32 +35,137 @@ pub const BROADCAST_CAPACITY: usize = 64;
 /// the full rationale. The short version: the parser channel uses `blocking_send()`
 /// for PTY backpressure, which requires the sender to be in the PTY reader's
 /// blocking thread — not in a method called from arbitrary contexts.
-#[derive(Clone)]
 pub struct Broker {
-    tx: broadcast::Sender<Bytes>,
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    ring: RwLock<Ring>,
+    /// Total bytes ever published. Subscribers wait on changes.
+    head: watch::Sender<u64>,
+    /// Live `Broker` handles. Subscribers see the end of output once the
+    /// last one is dropped, as with a broadcast channel's senders.
+    brokers: AtomicUsize,
+    closed: AtomicBool,
+    subscribers: AtomicUsize,
+}
+
+struct Ring {
+    buf: Box<[u8]>,
+    /// Total bytes ever written; `head % capacity` is the next write.
+    head: u64,
+}
+
+impl Ring {
+    fn write(&mut self, mut data: &[u8]) {
+        let cap = self.buf.len();
+        let total = data.len() as u64;
+        // Only the newest `cap` bytes of an oversized write can be kept.
+        if data.len() > cap {
+            data = &data[data.len() - cap..];
+        }
+        let start = ((self.head + total - data.len() as u64) % cap as u64) as usize;
+        let first = data.len().min(cap - start);
+        self.buf[start..start + first].copy_from_slice(&data[..first]);
+        self.buf[..data.len() - first].copy_from_slice(&data[first..]);
+        self.head += total;
+    }
+
+    /// Copy up to `max` bytes from `cursor`, which must still be in the ring.
+    fn read(&self, cursor: u64, max: usize) -> Bytes {
+        let cap = self.buf.len();
+        let len = ((self.head - cursor) as usize).min(max);
+        let start = (cursor % cap as u64) as usize;
+        let first = len.min(cap - start);
+        let mut out = Vec::with_capacity(len);
+        out.extend_from_slice(&self.buf[start..start + first]);
+        out.extend_from_slice(&self.buf[..len - first]);
+        Bytes::from(out)
+    }
 }
 
 impl Broker {
     pub fn new() -> Self {
-        let (tx, _) = broadcast::channel(BROADCAST_CAPACITY);
-        Self { tx }
+        Self::with_capacity(RING_CAPACITY)
+    }
+
+    /// A broker whose ring holds `capacity` bytes.
+    pub fn with_capacity(capacity: usize) -> Self {
+        assert!(capacity > 0, "broker capacity must be non-zero");
+        let (head, _) = watch::channel(0);
+        Self {
+            shared: Arc::new(Shared {
+                ring: RwLock::new(Ring {
+                    buf: vec![0; capacity].into_boxed_slice(),
+                    head: 0,
+                }),
+                head,
+                brokers: AtomicUsize::new(1),
+                closed: AtomicBool::new(false),
+                subscribers: AtomicUsize::new(0),
+            }),
+        }
     }
 
     /// Publish PTY output to streaming clients.
     ///
-    /// This is non-blocking and lossy: lagged subscribers get dropped messages.
-    /// The parser receives data through a separate bounded channel with
-    /// backpressure (see session.rs), NOT through this broadcast.
-    pub fn publish(&self, data: Bytes) {
-        let _ = self.tx.send(data);
+    /// This is non-blocking and lossy: subscribers that fall too far behind
+    /// lose output. The parser receives data through a separate bounded
+    /// channel with backpressure (see session.rs), NOT through this ring.
+    pub fn publish(&self, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        let head = {
+            let mut ring = self.shared.ring.write();
+            ring.write(data);
+            ring.head
+        };
+        self.shared.head.send_replace(head);
+    }
+
+    /// Subscribe to output published from now on.
+    pub fn subscribe(&self) -> Subscriber {
+        Subscriber::new(self.shared.clone())
     }
 
-    pub fn subscribe(&self) -> broadcast::Receiver<Bytes> {
-        self.tx.subscribe()
+    /// Subscribe to output from byte `offset` on, counting every byte ever
+    /// published, such as a parser's
+    /// [`output_offset`](crate::parser::state::ScreenResponse::output_offset).
+    /// `None` if that output has already left the ring. Offsets past the
+    /// newest output start at the newest output.
+    pub fn subscribe_from(&self, offset: u64) -> Option<Subscriber> {
+        let mut subscriber = Subscriber::new(self.shared.clone());
+        let oldest = subscriber.cursor.saturating_sub(self.shared.ring.read().buf.len() as u64);
+        if offset < oldest {
+            return None;
+        }
+        subscriber.cursor = subscriber.cursor.min(offset);
+        Some(subscriber)
     }
 
-    pub fn sender(&self) -> broadcast::Sender<Bytes> {
-        self.tx.clone()
+    /// Number of live subscribers.
+    pub fn receiver_count(&self) -> usize {
+        self.shared.subscribers.load(Ordering::Acquire)
+    }
+}
+
+impl Clone for Broker {
+    fn clone(&self) -> Self {
+        self.shared.brokers.fetch_add(1, Ordering::AcqRel);
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for Broker {
+    fn drop(&mut self) {
+        if self.shared.brokers.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.closed.store(true, Ordering::Release);
+            // Wake waiting subscribers so they see the end.
+            self.shared.head.send_modify(|_| {});
+        }
     }
 }
 
@@ -52,10 +175,114 @@ impl Default for Broker {
     }
 }
 
+/// A reader of a [`Broker`]'s output with its own cursor.
+///
+/// The methods mirror a broadcast receiver's: `recv` waits, `try_recv`
+/// doesn't, and both report [`Lagged`](RecvError::Lagged) (in bytes) and
+/// [`Closed`](RecvError::Closed) the same way.
+pub struct Subscriber {
+    shared: Arc<Shared>,
+    cursor: u64,
+    head_rx: watch::Receiver<u64>,
+}
+
+impl Subscriber {
+    fn new(shared: Arc<Shared>) -> Self {
+        shared.subscribers.fetch_add(1, Ordering::AcqRel);
+        let cursor = shared.ring.read().head;
+        let head_rx = shared.head.subscribe();
+        Self {
+            shared,
+            cursor,
+            head_rx,
+        }
+    }
+
+    /// Wait for output and take everything not yet read, up to
+    /// [`MAX_READ`] bytes.
+    ///
+    /// Cancel-safe: nothing is consumed unless this returns.
+    pub async fn recv(&mut self) -> Result<Bytes, RecvError> {
+        loop {
+            // Mark the current head as seen before looking, so a publish
+            // after the look always wakes the wait below.
+            self.head_rx.borrow_and_update();
+            match self.try_recv() {
+                Ok(data) => return Ok(data),
+                Err(TryRecvError::Lagged(n)) => return Err(RecvError::Lagged(n)),
+                Err(TryRecvError::Closed) => return Err(RecvError::Closed),
+                Err(TryRecvError::Empty) => {}
+            }
+            if self.head_rx.changed().await.is_err() {
+                return Err(RecvError::Closed);
+            }
+        }
+    }
+
+    /// Take everything not yet read, up to [`MAX_READ`] bytes, without
+    /// waiting.
+    pub fn try_recv(&mut self) -> Result<Bytes, TryRecvError> {
+        // Read the closed flag first: output published before the last
+        // broker dropped is still delivered.
+        let closed = self.shared.closed.load(Ordering::Acquire);
+        let ring = self.shared.ring.read();
+        let behind = ring.head - self.cursor;
+        if behind > ring.buf.len() as u64 {
+            self.cursor = ring.head;
+            return Err(TryRecvError::Lagged(behind));
+        }
+        if behind == 0 {
+            return Err(if closed { TryRecvError::Closed } else { TryRecvError::Empty });
+        }
+        let data = ring.read(self.cursor, MAX_READ);
+        self.cursor += data.len() as u64;
+        Ok(data)
+    }
+
+    /// Bytes published but not yet read, including any already lost to
+    /// lag.
+    pub fn len(&self) -> usize {
+        // Read the ring's head, not the watch: `publish` updates the watch
+        // after releasing the ring, and a read in between can move the
+        // cursor past the watched value.
+        (self.shared.ring.read().head - self.cursor) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// A new subscriber for the same broker, starting at the newest output.
+    pub fn resubscribe(&self) -> Self {
+        Self::new(self.shared.clone())
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        self.shared.subscribers.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_subscribe_from_replays_output_still_in_the_ring() {
+        let broker = Broker::with_capacity(8);
+        broker.publish(b"abcdef");
+
+        let mut rx = broker.subscribe_from(2).unwrap();
+        assert_eq!(&rx.recv().await.unwrap()[..], b"cdef");
+        assert!(broker.subscribe_from(100).unwrap().is_empty());
+
+        broker.publish(b"ghijkl");
+        assert!(broker.subscribe_from(2).is_none());
+        let mut rx = broker.subscribe_from(4).unwrap();
+        assert_eq!(&rx.recv().await.unwrap()[..], b"efghijkl");
+    }
+
     #[tokio::test]
     async fn test_new_creates_broker() {
         let broker = Broker::new();
@@ -67,7 +294,7 @@ mod tests {
     async fn test_publish_with_no_subscribers() {
         let broker = Broker::new();
         // Should not panic even with no subscribers
-        broker.publish(Bytes::from("hello"));
+        broker.publish(b"hello");
     }
 
     #[tokio::test]
@@ -75,7 +302,7 @@ mod tests {
         let broker = Broker::new();
         let mut rx = broker.subscribe();
 
-        broker.publish(Bytes::from("hello"));
+        broker.publish(b"hello");
 
         let received = rx.recv().await.expect("should receive message");
         assert_eq!(received, Bytes::from("hello"));
@@ -88,7 +315,7 @@ mod tests {
         let mut rx2 = broker.subscribe();
         let mut rx3 = broker.subscribe();
 
-        broker.publish(Bytes::from("broadcast"));
+        broker.publish(b"broadcast");
 
         let received1 = rx1.recv().await.expect("rx1 should receive message");
         let received2 = rx2.recv().await.expect("rx2 should receive message");
@@ -100,52 +327,145 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_subscriber_receives_multiple_messages() {
+    async fn test_subscriber_receives_published_output_in_order() {
         let broker = Broker::new();
         let mut rx = broker.subscribe();
 
-        broker.publish(Bytes::from("first"));
-        broker.publish(Bytes::from("second"));
-        broker.publish(Bytes::from("third"));
-
+        broker.publish(b"first");
         let msg1 = rx.recv().await.expect("should receive first message");
-        let msg2 = rx.recv().await.expect("should receive second message");
-        let msg3 = rx.recv().await.expect("should receive third message");
+        broker.publish(b"second");
+        broker.publish(b"third");
+        // Output published since the last read arrives as one chunk.
+        let msg2 = rx.recv().await.expect("should receive the rest");
 
         assert_eq!(msg1, Bytes::from("first"));
-        assert_eq!(msg2, Bytes::from("second"));
-        assert_eq!(msg3, Bytes::from("third"));
+        assert_eq!(msg2, Bytes::from("secondthird"));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
     }
 
     #[tokio::test]
-    async fn test_default_creates_broker() {
-        let broker = Broker::default();
-        // Can subscribe without error
-        let _rx = broker.subscribe();
+    async fn test_subscriber_only_sees_output_after_subscribing() {
+        let broker = Broker::new();
+        broker.publish(b"before");
+        let mut rx = broker.subscribe();
+        broker.publish(b"after");
+        assert_eq!(rx.recv().await.unwrap(), Bytes::from("after"));
+    }
+
+    #[tokio::test]
+    async fn test_reads_wrap_around_the_ring() {
+        let broker = Broker::with_capacity(8);
+        let mut rx = broker.subscribe();
+        for chunk in [&b"abcde"[..], b"fghij", b"klm"] {
+            broker.publish(chunk);
+            assert_eq!(rx.recv().await.unwrap(), Bytes::copy_from_slice(chunk));
+        }
+        broker.publish(b"nopqrstu");
+        assert_eq!(rx.recv().await.unwrap(), Bytes::from("nopqrstu"));
     }
 
     #[tokio::test]
-    async fn test_sender_can_be_used_directly() {
+    async fn test_reads_are_capped_at_max_read() {
         let broker = Broker::new();
         let mut rx = broker.subscribe();
-        let sender = broker.sender();
+        broker.publish(&vec![b'x'; MAX_READ + 10]);
+        assert_eq!(rx.recv().await.unwrap().len(), MAX_READ);
+        assert_eq!(rx.len(), 10);
+        assert_eq!(rx.recv().await.unwrap().len(), 10);
+        assert!(rx.is_empty());
+    }
 
-        // Send via the sender directly
-        sender.send(Bytes::from("via sender")).expect("send should succeed");
+    #[tokio::test]
+    async fn test_len_after_read_between_ring_write_and_head_update() {
+        let broker = Broker::new();
+        let mut rx = broker.subscribe();
+        // The first half of a publish: the ring has the output but the
+        // watched head doesn't yet.
+        broker.shared.ring.write().write(b"racing");
+        assert_eq!(rx.try_recv().unwrap(), Bytes::from("racing"));
+        assert_eq!(rx.len(), 0);
+        assert!(rx.is_empty());
+        // The second half.
+        let head = broker.shared.ring.read().head;
+        broker.shared.head.send_replace(head);
+        assert!(rx.is_empty());
+    }
 
-        let received = rx.recv().await.expect("should receive message");
-        assert_eq!(received, Bytes::from("via sender"));
+    #[tokio::test]
+    async fn test_lagged_subscriber_skips_to_newest_output() {
+        let broker = Broker::with_capacity(8);
+        let mut slow = broker.subscribe();
+        let mut fast = broker.subscribe();
+        broker.publish(b"12345");
+        assert_eq!(fast.recv().await.unwrap(), Bytes::from("12345"));
+        broker.publish(b"6789");
+
+        assert_eq!(slow.recv().await, Err(RecvError::Lagged(9)));
+        assert!(slow.is_empty());
+        assert_eq!(fast.recv().await.unwrap(), Bytes::from("6789"));
+
+        broker.publish(b"next");
+        assert_eq!(slow.recv().await.unwrap(), Bytes::from("next"));
+    }
+
+    #[tokio::test]
+    async fn test_output_is_delivered_before_closed() {
+        let broker = Broker::new();
+        let mut rx = broker.subscribe();
+        let clone = broker.clone();
+        broker.publish(b"last words");
+        drop(broker);
+        // A remaining clone keeps the output open.
+        assert_eq!(rx.recv().await.unwrap(), Bytes::from("last words"));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+
+        drop(clone);
+        assert_eq!(rx.recv().await, Err(RecvError::Closed));
+    }
+
+    #[tokio::test]
+    async fn test_recv_wakes_on_publish_from_another_thread() {
+        let broker = Broker::new();
+        let mut rx = broker.subscribe();
+        let publisher = broker.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            publisher.publish(b"from the pty thread");
+        });
+        let received = tokio::time::timeout(std::time::Duration::from_secs(2), rx.recv())
+            .await
+            .expect("recv should wake");
+        assert_eq!(received.unwrap(), Bytes::from("from the pty thread"));
+    }
+
+    #[tokio::test]
+    async fn test_receiver_count_tracks_subscribers() {
+        let broker = Broker::new();
+        let clone = broker.clone();
+        let rx = broker.subscribe();
+        let again = rx.resubscribe();
+        assert_eq!(clone.receiver_count(), 2);
+        drop(rx);
+        drop(again);
+        assert_eq!(broker.receiver_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_default_creates_broker() {
+        let broker = Broker::default();
+        // Can subscribe without error
+        let _rx = broker.subscribe();
     }
 
     #[tokio::test]
-    async fn test_clone_shares_channel() {
+    async fn test_clone_shares_ring() {
         let broker1 = Broker::new();
         let broker2 = broker1.clone();
 
         let mut rx = broker1.subscribe();
 
         // Publish via the clone
-        broker2.publish(Bytes::from("from clone"));
+        broker2.publish(b"from clone");
 
         let received = rx.recv().await.expect("should receive message from clone");
         assert_eq!(received, Bytes::from("from clone"));
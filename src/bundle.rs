@@ -0,0 +1,205 @@
+//! Debug bundles: a gzipped tarball of server state for bug reports.
+//!
+//! `wsh debug-bundle` fetches what it can from a running server, redacts
+//! credentials, and writes everything under one directory in the archive.
+//! The archive is written by hand in ustar format; bundles are a handful of
+//! small text files, so nothing beyond regular files is needed.
+
+use std::io::{self, Write};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+/// Keys whose values are replaced by [`REDACTED`] in JSON and TOML.
+const SECRET_KEY_PARTS: &[&str] = &["token", "secret", "password", "passphrase", "authorization"];
+
+pub const REDACTED: &str = "[redacted]";
+
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SECRET_KEY_PARTS.iter().any(|part| key.contains(part))
+}
+
+/// Replace the values of credential-looking keys, at any depth.
+pub fn redact_json(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                if is_secret_key(key) && !v.is_null() {
+                    *v = serde_json::Value::String(REDACTED.into());
+                } else {
+                    redact_json(v);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact_json),
+        _ => {}
+    }
+}
+
+/// Replace the values of credential-looking keys in a config file, at any
+/// depth. Fails if `text` is not valid TOML.
+pub fn redact_toml(text: &str) -> Result<String, toml::de::Error> {
+    fn walk(value: &mut toml::Value) {
+        match value {
+            toml::Value::Table(table) => {
+                for (key, v) in table.iter_mut() {
+                    if is_secret_key(key) {
+                        *v = toml::Value::String(REDACTED.into());
+                    } else {
+                        walk(v);
+                    }
+                }
+            }
+            toml::Value::Array(items) => items.iter_mut().for_each(walk),
+            _ => {}
+        }
+    }
+    let mut value: toml::Value = text.parse()?;
+    walk(&mut value);
+    Ok(toml::to_string_pretty(&value).unwrap_or_default())
+}
+
+/// Writes regular files into a gzipped ustar archive.
+pub struct TarWriter<W: Write> {
+    out: GzEncoder<W>,
+    mtime: u64,
+}
+
+const BLOCK: usize = 512;
+
+impl<W: Write> TarWriter<W> {
+    /// Start an archive whose entries all carry `mtime` (Unix seconds).
+    pub fn new(out: W, mtime: u64) -> Self {
+        Self { out: GzEncoder::new(out, Compression::default()), mtime }
+    }
+
+    /// Add a file. `path` must fit the 100-byte ustar name field.
+    pub fn append(&mut self, path: &str, data: &[u8]) -> io::Result<()> {
+        if path.is_empty() || path.len() > 100 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("archive path must be 1-100 bytes: {}", path),
+            ));
+        }
+        let mut header = [0u8; BLOCK];
+        header[..path.len()].copy_from_slice(path.as_bytes());
+        header[100..108].copy_from_slice(b"0000644\0");
+        header[108..116].copy_from_slice(b"0000000\0");
+        header[116..124].copy_from_slice(b"0000000\0");
+        header[124..136].copy_from_slice(format!("{:011o}\0", data.len()).as_bytes());
+        header[136..148].copy_from_slice(format!("{:011o}\0", self.mtime).as_bytes());
+        header[148..156].copy_from_slice(b"        ");
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+
+        self.out.write_all(&header)?;
+        self.out.write_all(data)?;
+        let padding = (BLOCK - data.len() % BLOCK) % BLOCK;
+        self.out.write_all(&[0u8; BLOCK][..padding])
+    }
+
+    /// Write the end-of-archive marker and flush the compressor.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.write_all(&[0u8; BLOCK * 2])?;
+        self.out.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    /// Read back (path, contents) pairs from a gzipped ustar archive.
+    fn entries(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut tar = Vec::new();
+        flate2::read::GzDecoder::new(archive).read_to_end(&mut tar).unwrap();
+        assert_eq!(tar.len() % BLOCK, 0);
+        let mut out = Vec::new();
+        let mut pos = 0;
+        while tar[pos..pos + BLOCK].iter().any(|&b| b != 0) {
+            let header = &tar[pos..pos + BLOCK];
+            let stored: u32 = u32::from_str_radix(std::str::from_utf8(&header[148..154]).unwrap(), 8).unwrap();
+            let sum: u32 = header
+                .iter()
+                .enumerate()
+                .map(|(i, &b)| if (148..156).contains(&i) { b' ' as u32 } else { b as u32 })
+                .sum();
+            assert_eq!(stored, sum);
+            let name_len = header[..100].iter().position(|&b| b == 0).unwrap_or(100);
+            let name = String::from_utf8(header[..name_len].to_vec()).unwrap();
+            let size = usize::from_str_radix(std::str::from_utf8(&header[124..135]).unwrap(), 8).unwrap();
+            pos += BLOCK;
+            out.push((name, tar[pos..pos + size].to_vec()));
+            pos += size.div_ceil(BLOCK) * BLOCK;
+        }
+        assert!(tar[pos..].iter().all(|&b| b == 0));
+        out
+    }
+
+    #[test]
+    fn tar_round_trips_files() {
+        let mut tar = TarWriter::new(Vec::new(), 1_700_000_000);
+        tar.append("bundle/empty.txt", b"").unwrap();
+        tar.append("bundle/info.json", b"{\"ok\":true}").unwrap();
+        let big = vec![b'x'; BLOCK + 1];
+        tar.append("bundle/big.txt", &big).unwrap();
+        let archive = tar.finish().unwrap();
+        assert_eq!(
+            entries(&archive),
+            vec![
+                ("bundle/empty.txt".to_string(), vec![]),
+                ("bundle/info.json".to_string(), b"{\"ok\":true}".to_vec()),
+                ("bundle/big.txt".to_string(), big),
+            ]
+        );
+    }
+
+    #[test]
+    fn tar_rejects_long_paths() {
+        let mut tar = TarWriter::new(Vec::new(), 0);
+        assert!(tar.append(&"a".repeat(101), b"").is_err());
+        assert!(tar.append("", b"").is_err());
+    }
+
+    #[test]
+    fn json_secrets_are_redacted_at_any_depth() {
+        let mut value = serde_json::json!({
+            "hostname": "h",
+            "servers": [{"address": "b:8080", "token": "t1", "auth_token": null}],
+            "default_token": "t2",
+            "nested": {"client_secret": 5},
+        });
+        redact_json(&mut value);
+        assert_eq!(value["hostname"], "h");
+        assert_eq!(value["servers"][0]["address"], "b:8080");
+        assert_eq!(value["servers"][0]["token"], REDACTED);
+        assert!(value["servers"][0]["auth_token"].is_null());
+        assert_eq!(value["default_token"], REDACTED);
+        assert_eq!(value["nested"]["client_secret"], REDACTED);
+    }
+
+    #[test]
+    fn toml_secrets_are_redacted() {
+        let config = r#"
+            default_token = "shared-secret"
+
+            [sessions]
+            scrollback_lines = 500
+
+            [[servers]]
+            address = "backend:8080"
+            token = "per-server-token"
+        "#;
+        let redacted = redact_toml(config).unwrap();
+        assert!(!redacted.contains("shared-secret"));
+        assert!(!redacted.contains("per-server-token"));
+        assert!(redacted.contains("backend:8080"));
+        assert!(redacted.contains("scrollback_lines = 500"));
+        assert!(redact_toml("not = [valid").is_err());
+    }
+}
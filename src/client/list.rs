@@ -0,0 +1,316 @@
+//! Output formats for `wsh list`.
+//!
+//! The default is a table of the columns most people want. `--wide` shows
+//! every column, `--columns` picks them, `--format` prints one line per
+//! session from a template such as `{name}\t{cwd}`, and `--json` prints the
+//! sessions as the server sent them. Columns and template fields share one
+//! set of names, plus `meta.<key>` for a key of the session's metadata.
+
+use crate::protocol::SessionInfoMsg;
+
+/// Every column, in `--wide` order.
+pub const COLUMNS: &[&str] = &[
+    "name",
+    "server",
+    "pid",
+    "command",
+    "title",
+    "cwd",
+    "size",
+    "rows",
+    "cols",
+    "clients",
+    "state",
+    "age",
+    "idle",
+    "created_at",
+    "last_activity_at",
+    "tags",
+];
+
+/// Columns shown when none are chosen.
+pub const DEFAULT_COLUMNS: &[&str] = &[
+    "name", "pid", "command", "title", "size", "clients", "age", "idle", "tags",
+];
+
+/// Check a comma-separated column list such as `name,cwd,meta.run`.
+pub fn parse_columns(names: &[String]) -> Result<Vec<String>, String> {
+    names
+        .iter()
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            if is_field(name) {
+                Ok(name.to_string())
+            } else {
+                Err(unknown_field(name))
+            }
+        })
+        .collect()
+}
+
+/// Lay `sessions` out as a table of `columns`, one row per session.
+/// Missing values show as `-`. `now_ms` is the current Unix time, for the
+/// age column.
+pub fn table(sessions: &[SessionInfoMsg], columns: &[String], now_ms: u64) -> String {
+    let header: Vec<String> = columns.iter().map(|c| c.to_uppercase()).collect();
+    let rows: Vec<Vec<String>> = sessions
+        .iter()
+        .map(|s| {
+            columns
+                .iter()
+                .map(|c| {
+                    // In the table a missing title falls back to the
+                    // directory, so the column always says what a
+                    // long-lived shell is doing.
+                    let value = match c.as_str() {
+                        "title" => field(s, "title", now_ms).or_else(|| field(s, "cwd", now_ms)),
+                        _ => field(s, c, now_ms),
+                    };
+                    value.filter(|v| !v.is_empty()).unwrap_or_else(|| "-".to_string())
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut out = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let last = row.len().saturating_sub(1);
+        for (i, cell) in row.iter().enumerate() {
+            if i == last {
+                out.push_str(cell);
+            } else {
+                out.push_str(&format!("{:<width$}  ", cell, width = widths[i]));
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// A parsed `--format` template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    Field(String),
+}
+
+impl Template {
+    /// Parse `template`. `{field}` is replaced by the field's value, `{{`
+    /// and `}}` are literal braces, and `\t`, `\n` and `\\` are a tab, a
+    /// newline and a backslash, so templates work without shell quoting
+    /// tricks.
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('t') => text.push('\t'),
+                    Some('n') => text.push('\n'),
+                    Some('\\') => text.push('\\'),
+                    Some(other) => {
+                        text.push('\\');
+                        text.push(other);
+                    }
+                    None => text.push('\\'),
+                },
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("unclosed '{{' in format: {}", template)),
+                        }
+                    }
+                    if !is_field(&name) {
+                        return Err(unknown_field(&name));
+                    }
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(Piece::Field(name));
+                }
+                '}' => return Err(format!("unmatched '}}' in format: {}", template)),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        Ok(Self { pieces })
+    }
+
+    /// Render one session. Missing values are empty.
+    pub fn render(&self, session: &SessionInfoMsg, now_ms: u64) -> String {
+        self.pieces
+            .iter()
+            .map(|piece| match piece {
+                Piece::Text(text) => text.clone(),
+                Piece::Field(name) => field(session, name, now_ms).unwrap_or_default(),
+            })
+            .collect()
+    }
+}
+
+fn is_field(name: &str) -> bool {
+    COLUMNS.contains(&name) || name.strip_prefix("meta.").is_some_and(|key| !key.is_empty())
+}
+
+fn unknown_field(name: &str) -> String {
+    format!(
+        "unknown field '{}'; use one of: {}, meta.<key>",
+        name,
+        COLUMNS.join(", ")
+    )
+}
+
+/// The value of `name` for `s`, or `None` if the session doesn't have one.
+fn field(s: &SessionInfoMsg, name: &str, now_ms: u64) -> Option<String> {
+    if let Some(key) = name.strip_prefix("meta.") {
+        return s.metadata.get(key).map(|value| match value {
+            serde_json::Value::String(text) => text.clone(),
+            other => other.to_string(),
+        });
+    }
+    Some(match name {
+        "name" => s.name.clone(),
+        "server" => s.server.clone(),
+        "pid" => s.pid?.to_string(),
+        "command" => s.command.clone(),
+        "title" => s.title.clone()?,
+        "cwd" => s.cwd.clone()?,
+        "size" => format!("{}x{}", s.cols, s.rows),
+        "rows" => s.rows.to_string(),
+        "cols" => s.cols.to_string(),
+        "clients" => s.clients.to_string(),
+        "state" => if s.hibernated {
+            "hibernated"
+        } else if s.suspended {
+            "suspended"
+        } else {
+            "running"
+        }
+        .to_string(),
+        // Older servers don't report a creation time.
+        "age" if s.created_at == 0 => return None,
+        "age" => format_uptime(now_ms.saturating_sub(s.created_at) / 1000),
+        "idle" => format_uptime(s.last_activity_ms / 1000),
+        "created_at" if s.created_at == 0 => return None,
+        "created_at" => s.created_at.to_string(),
+        "last_activity_at" if s.last_activity_at == 0 => return None,
+        "last_activity_at" => s.last_activity_at.to_string(),
+        "tags" => s.tags.join(","),
+        _ => return None,
+    })
+}
+
+/// A short, human-readable duration such as `3m 12s` or `2d 4h`.
+pub fn format_uptime(secs: u64) -> String {
+    let (days, hours, mins) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60);
+    match (days, hours, mins) {
+        (0, 0, 0) => format!("{}s", secs),
+        (0, 0, m) => format!("{}m {}s", m, secs % 60),
+        (0, h, m) => format!("{}h {}m", h, m),
+        (d, h, _) => format!("{}d {}h", d, h),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000_000;
+
+    fn session(name: &str) -> SessionInfoMsg {
+        SessionInfoMsg {
+            name: name.to_string(),
+            server: "box".to_string(),
+            pid: Some(42),
+            command: "bash".to_string(),
+            rows: 24,
+            cols: 80,
+            clients: 1,
+            tags: vec!["build".to_string(), "ci".to_string()],
+            last_activity_ms: 90_000,
+            last_activity_at: NOW - 90_000,
+            created_at: NOW - 7_200_000,
+            suspended: false,
+            hibernated: false,
+            title: None,
+            cwd: Some("/src/app".to_string()),
+            metadata: serde_json::json!({"run": "r-1", "attempt": 2})
+                .as_object()
+                .unwrap()
+                .clone(),
+        }
+    }
+
+    #[test]
+    fn table_aligns_columns_and_falls_back_to_cwd_for_title() {
+        let mut other = session("a-much-longer-name");
+        other.pid = None;
+        other.created_at = 0;
+        let columns: Vec<String> = ["name", "pid", "title", "age", "idle"]
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        assert_eq!(
+            table(&[session("dev"), other], &columns, NOW),
+            "NAME                PID  TITLE     AGE    IDLE\n\
+             dev                 42   /src/app  2h 0m  1m 30s\n\
+             a-much-longer-name  -    /src/app  -      1m 30s\n"
+        );
+    }
+
+    #[test]
+    fn template_renders_fields_escapes_and_metadata() {
+        let template = Template::parse(r"{name}\t{cwd}\t{meta.run}/{meta.attempt} {{{tags}}}{title}").unwrap();
+        assert_eq!(template.render(&session("dev"), NOW), "dev\t/src/app\tr-1/2 {build,ci}");
+    }
+
+    #[test]
+    fn unknown_fields_and_bad_braces_are_rejected() {
+        assert!(Template::parse("{nmae}").unwrap_err().contains("unknown field 'nmae'"));
+        assert!(Template::parse("{name").is_err());
+        assert!(Template::parse("name}").is_err());
+        assert!(Template::parse("{meta.}").is_err());
+        assert!(parse_columns(&["name".to_string(), "bogus".to_string()]).is_err());
+        assert_eq!(
+            parse_columns(&["name".to_string(), " meta.run".to_string()]).unwrap(),
+            vec!["name", "meta.run"]
+        );
+    }
+
+    #[test]
+    fn state_reports_hibernated_over_suspended() {
+        let mut s = session("dev");
+        assert_eq!(field(&s, "state", NOW).as_deref(), Some("running"));
+        s.suspended = true;
+        assert_eq!(field(&s, "state", NOW).as_deref(), Some("suspended"));
+        s.hibernated = true;
+        assert_eq!(field(&s, "state", NOW).as_deref(), Some("hibernated"));
+    }
+}
@@ -4,8 +4,14 @@
 //! sends control frames (CreateSession / AttachSession), and then enters
 //! a streaming I/O proxy loop forwarding stdin/stdout over the socket.
 
+pub mod list;
+pub mod output;
+pub mod palette;
+pub mod picker;
+pub mod top;
+
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use bytes::Bytes;
 use tokio::io::{AsyncWriteExt, ReadHalf, WriteHalf};
@@ -15,6 +21,27 @@ use crate::overlay::{self, Overlay};
 use crate::panel::{self, Panel};
 use crate::protocol::*;
 
+/// Render parsed scrollback lines as ANSI bytes laid out for a local
+/// terminal `cols` wide.
+///
+/// Lines are wrapped at the local width (rather than relying on terminal
+/// soft-wrap of lines sized for the session), so scrollback from a wider
+/// session reads correctly in a narrower window.
+pub fn render_scrollback_lines(
+    lines: &[crate::parser::state::FormattedLine],
+    cols: u16,
+) -> Vec<u8> {
+    use crate::parser::ansi::{line_to_ansi, wrap_line};
+    let mut buf = String::new();
+    for line in lines {
+        for row in wrap_line(line, cols as usize) {
+            buf.push_str(&line_to_ansi(&row));
+            buf.push_str("\r\n");
+        }
+    }
+    buf.into_bytes()
+}
+
 /// Render the panel sync update, writing ANSI escape sequences to `w`.
 ///
 /// Handles scroll region transitions carefully: DECSTBM (`\x1b[r`) moves the
@@ -77,16 +104,252 @@ fn render_panel_sync(
     w.flush()
 }
 
+/// Render a server banner as an inverse-video line across the top row of a
+/// terminal `cols` wide, leaving the cursor where it was.
+///
+/// The banner is drawn once; the session's own output will paint over it
+/// as the top row changes.
+pub fn render_banner(message: &str, cols: u16) -> String {
+    use unicode_width::UnicodeWidthChar;
+    let mut text = String::new();
+    let mut width = 0;
+    for c in format!(" wsh: {} ", message).chars() {
+        let w = c.width().unwrap_or(0);
+        if width + w > cols as usize {
+            break;
+        }
+        text.push(c);
+        width += w;
+    }
+    text.push_str(&" ".repeat((cols as usize).saturating_sub(width)));
+    format!(
+        "{}{}\x1b[1;1H\x1b[7m{}\x1b[0m{}{}",
+        overlay::begin_sync(),
+        overlay::save_cursor(),
+        text,
+        overlay::restore_cursor(),
+        overlay::end_sync(),
+    )
+}
+
+/// Whether a session of `session` size (rows, cols) overflows a local
+/// terminal of `local` size and must be shown through a [`Viewport`].
+pub fn needs_viewport(session: (u16, u16), local: (u16, u16)) -> bool {
+    session.0 > local.0 || session.1 > local.1
+}
+
+/// Local rendering of a session that is larger than the local terminal.
+///
+/// Writing session-sized output straight to a smaller terminal lets the
+/// terminal soft-wrap and scroll it, garbling the display. Instead, output
+/// is fed into a session-sized virtual terminal and a local-sized window of
+/// it is redrawn. The window follows the cursor until the user pans with
+/// Shift+Alt+Arrow; any other key resumes following.
+///
+/// While the local terminal is large enough, output passes through
+/// unchanged and the virtual terminal is only kept in sync.
+pub struct Viewport {
+    vt: avt::Vt,
+    session: (u16, u16),
+    local: (u16, u16),
+    /// Top row and left column of the visible window.
+    offset: (usize, usize),
+    follow: bool,
+    /// Incomplete UTF-8 sequence held back from the previous chunk.
+    pending: Vec<u8>,
+}
+
+/// Pan direction for [`Viewport::pan`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pan {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Viewport {
+    pub fn new(session: (u16, u16), local: (u16, u16)) -> Self {
+        Self {
+            vt: avt::Vt::new(session.1.max(1) as usize, session.0.max(1) as usize),
+            session,
+            local,
+            offset: (0, 0),
+            follow: true,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Whether output is currently rendered through the window.
+    pub fn is_active(&self) -> bool {
+        needs_viewport(self.session, self.local)
+    }
+
+    /// Feed session output into the virtual terminal.
+    pub fn feed(&mut self, data: &[u8]) {
+        self.pending.extend_from_slice(data);
+        let valid = match std::str::from_utf8(&self.pending) {
+            Ok(_) => self.pending.len(),
+            // Hold back a truncated sequence at the end for the next chunk
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.pending.len(),
+        };
+        let rest = self.pending.split_off(valid);
+        let text = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending = rest;
+        self.vt.feed_str(&text);
+        if self.follow {
+            self.follow_cursor();
+        }
+    }
+
+    /// The session was resized.
+    pub fn resize_session(&mut self, rows: u16, cols: u16) {
+        self.session = (rows, cols);
+        self.vt.resize(cols.max(1) as usize, rows.max(1) as usize);
+        self.clamp();
+    }
+
+    /// The local terminal was resized.
+    pub fn resize_local(&mut self, rows: u16, cols: u16) {
+        self.local = (rows, cols);
+        self.clamp();
+    }
+
+    /// Move the window by half its height or width.
+    pub fn pan(&mut self, dir: Pan) {
+        self.follow = false;
+        let (rows, cols) = (self.local.0.max(2) as usize / 2, self.local.1.max(2) as usize / 2);
+        match dir {
+            Pan::Up => self.offset.0 = self.offset.0.saturating_sub(rows),
+            Pan::Down => self.offset.0 += rows,
+            Pan::Left => self.offset.1 = self.offset.1.saturating_sub(cols),
+            Pan::Right => self.offset.1 += cols,
+        }
+        self.clamp();
+    }
+
+    /// Handle local input while active: returns `true` if `data` was a pan
+    /// key and must not be forwarded to the session.
+    pub fn handle_input(&mut self, data: &[u8]) -> bool {
+        let dir = match data {
+            b"\x1b[1;4A" => Pan::Up,
+            b"\x1b[1;4B" => Pan::Down,
+            b"\x1b[1;4C" => Pan::Right,
+            b"\x1b[1;4D" => Pan::Left,
+            _ => {
+                if !self.follow {
+                    self.follow = true;
+                    self.follow_cursor();
+                }
+                return false;
+            }
+        };
+        self.pan(dir);
+        true
+    }
+
+    /// Scroll the window the minimum amount needed to show the cursor.
+    fn follow_cursor(&mut self) {
+        let cursor = self.vt.cursor();
+        let (rows, cols) = (self.local.0.max(1) as usize, self.local.1.max(1) as usize);
+        if cursor.row < self.offset.0 {
+            self.offset.0 = cursor.row;
+        } else if cursor.row >= self.offset.0 + rows {
+            self.offset.0 = cursor.row + 1 - rows;
+        }
+        if cursor.col < self.offset.1 {
+            self.offset.1 = cursor.col;
+        } else if cursor.col >= self.offset.1 + cols {
+            self.offset.1 = cursor.col + 1 - cols;
+        }
+        self.clamp();
+    }
+
+    fn clamp(&mut self) {
+        let max_top = (self.session.0 as usize).saturating_sub(self.local.0 as usize);
+        let max_left = (self.session.1 as usize).saturating_sub(self.local.1 as usize);
+        self.offset = (self.offset.0.min(max_top), self.offset.1.min(max_left));
+    }
+
+    /// Redraw the whole local terminal from the virtual terminal.
+    pub fn render(&self) -> String {
+        use crate::parser::ansi::{crop_line, line_to_ansi};
+        use crate::parser::format::format_line;
+
+        let (local_rows, local_cols) = (self.local.0 as usize, self.local.1 as usize);
+        let (top, left) = self.offset;
+        let mut buf = String::new();
+        buf.push_str(overlay::begin_sync());
+        buf.push_str("\x1b[?25l");
+        let mut lines = self.vt.view().skip(top);
+        for row in 0..local_rows {
+            buf.push_str(&format!("\x1b[{};1H", row + 1));
+            if let Some(line) = lines.next() {
+                let cropped = crop_line(&format_line(line, true), left, local_cols);
+                buf.push_str(&line_to_ansi(&cropped));
+            }
+            buf.push_str("\x1b[0m\x1b[K");
+        }
+        let cursor = self.vt.cursor();
+        if cursor.row >= top
+            && cursor.row < top + local_rows
+            && cursor.col >= left
+            && cursor.col < left + local_cols
+        {
+            buf.push_str(&format!(
+                "\x1b[{};{}H",
+                cursor.row - top + 1,
+                cursor.col - left + 1,
+            ));
+            if cursor.visible {
+                buf.push_str("\x1b[?25h");
+            }
+        }
+        buf.push_str(overlay::end_sync());
+        buf
+    }
+}
+
 /// A client connection to the wsh server daemon over a Unix socket.
 pub struct Client {
     stream: UnixStream,
+    /// Session size from the last create/attach response, used to set up
+    /// the local [`Viewport`] when streaming.
+    session_size: Option<(u16, u16)>,
+    /// Screen contents from the last attach response, replayed into the
+    /// viewport's virtual terminal.
+    initial_screen: Vec<u8>,
+    /// Key handling policy from the last create/attach response.
+    policy: ClientPolicy,
+    /// Socket the client connected to, for the command palette's own
+    /// requests while streaming.
+    socket_path: PathBuf,
+    /// Session from the last create/attach response.
+    session_name: Option<String>,
+}
+
+/// How a streaming session ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamEnd {
+    /// Detached, or the session or server went away.
+    Detached,
+    /// The user picked another session to attach to from the command palette.
+    Switch(String),
 }
 
 impl Client {
     /// Connect to the server's Unix domain socket.
     pub async fn connect(socket_path: &Path) -> io::Result<Self> {
         let stream = UnixStream::connect(socket_path).await?;
-        Ok(Self { stream })
+        Ok(Self {
+            stream,
+            session_size: None,
+            initial_screen: Vec::new(),
+            policy: ClientPolicy::default(),
+            socket_path: socket_path.to_path_buf(),
+            session_name: None,
+        })
     }
 
     /// Send a CreateSession control frame and read the response.
@@ -101,9 +364,13 @@ impl Client {
         let resp_frame = Frame::read_from(&mut self.stream).await?;
         match resp_frame.frame_type {
             FrameType::CreateSessionResponse => {
-                resp_frame
+                let resp: CreateSessionResponseMsg = resp_frame
                     .parse_json()
-                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                self.session_size = Some((resp.rows, resp.cols));
+                self.policy = resp.client_policy.clone();
+                self.session_name = Some(resp.name.clone());
+                Ok(resp)
             }
             FrameType::Error => {
                 let err: ErrorMsg = resp_frame
@@ -130,9 +397,14 @@ impl Client {
         let resp_frame = Frame::read_from(&mut self.stream).await?;
         match resp_frame.frame_type {
             FrameType::AttachSessionResponse => {
-                resp_frame
+                let resp: AttachSessionResponseMsg = resp_frame
                     .parse_json()
-                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                self.session_size = Some((resp.rows, resp.cols));
+                self.initial_screen = resp.screen.clone();
+                self.policy = resp.client_policy.clone();
+                self.session_name = Some(resp.name.clone());
+                Ok(resp)
             }
             FrameType::Error => {
                 let err: ErrorMsg = resp_frame
@@ -194,7 +466,13 @@ impl Client {
         name: &str,
         server: Option<String>,
     ) -> io::Result<()> {
-        let msg = KillSessionMsg { name: name.to_string(), server };
+        self.kill_session_with(KillSessionMsg { name: name.to_string(), server, signal: None, grace_ms: None })
+            .await
+    }
+
+    /// Kill a session as `msg` describes: at once, or by sending a signal
+    /// and escalating to SIGKILL after a grace period.
+    pub async fn kill_session_with(&mut self, msg: KillSessionMsg) -> io::Result<()> {
         let frame = Frame::control(FrameType::KillSession, &msg)
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
         frame.write_to(&mut self.stream).await?;
@@ -215,6 +493,138 @@ impl Client {
         }
     }
 
+    /// Read a session's output lines, calling `on_batch` with each batch the
+    /// server sends. Returns once the server has sent everything (or, when
+    /// following, once the session ends).
+    pub async fn read_output(
+        &mut self,
+        msg: ReadOutputMsg,
+        mut on_batch: impl FnMut(ReadOutputResponseMsg),
+    ) -> io::Result<()> {
+        let frame = Frame::control(FrameType::ReadOutput, &msg)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        frame.write_to(&mut self.stream).await?;
+
+        loop {
+            let resp_frame = match Frame::read_from(&mut self.stream).await {
+                Ok(frame) => frame,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            match resp_frame.frame_type {
+                FrameType::ReadOutputResponse => {
+                    let resp: ReadOutputResponseMsg = resp_frame
+                        .parse_json()
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    on_batch(resp);
+                }
+                FrameType::Error => {
+                    let err: ErrorMsg = resp_frame
+                        .parse_json()
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    return Err(io::Error::other(format!("{}: {}", err.code, err.message)));
+                }
+                other => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("unexpected response frame type: {:?}", other),
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Rename a session via the server's Unix socket.
+    pub async fn rename_session(&mut self, name: &str, new_name: &str) -> io::Result<()> {
+        let msg = RenameSessionMsg { name: name.to_string(), new_name: new_name.to_string() };
+        let frame = Frame::control(FrameType::RenameSession, &msg)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        frame.write_to(&mut self.stream).await?;
+
+        let resp_frame = Frame::read_from(&mut self.stream).await?;
+        match resp_frame.frame_type {
+            FrameType::RenameSessionResponse => Ok(()),
+            FrameType::Error => {
+                let err: ErrorMsg = resp_frame
+                    .parse_json()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                Err(io::Error::other(format!("{}: {}", err.code, err.message)))
+            }
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unexpected response frame type: {:?}", other),
+            )),
+        }
+    }
+
+    /// Run a per-session WebSocket JSON method (e.g. `get_screen`,
+    /// `resize`, `create_overlay`) via the server's Unix socket and return
+    /// its result.
+    pub async fn session_request(
+        &mut self,
+        name: &str,
+        method: &str,
+        params: Option<serde_json::Value>,
+    ) -> io::Result<serde_json::Value> {
+        let msg = SessionRequestMsg {
+            name: name.to_string(),
+            method: method.to_string(),
+            params,
+        };
+        let frame = Frame::control(FrameType::SessionRequest, &msg)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        frame.write_to(&mut self.stream).await?;
+
+        let resp_frame = Frame::read_from(&mut self.stream).await?;
+        match resp_frame.frame_type {
+            FrameType::SessionRequestResponse => {
+                let resp: SessionRequestResponseMsg = resp_frame
+                    .parse_json()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                Ok(resp.result)
+            }
+            FrameType::Error => {
+                let err: ErrorMsg = resp_frame
+                    .parse_json()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                Err(io::Error::other(format!("{}: {}", err.code, err.message)))
+            }
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unexpected response frame type: {:?}", other),
+            )),
+        }
+    }
+
+    /// Query, or with `Some` set, the server's persistence mode via the
+    /// Unix socket. Returns the mode after the request.
+    pub async fn set_persist(&mut self, persistent: Option<bool>) -> io::Result<bool> {
+        let msg = SetPersistMsg { persistent };
+        let frame = Frame::control(FrameType::SetPersist, &msg)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        frame.write_to(&mut self.stream).await?;
+
+        let resp_frame = Frame::read_from(&mut self.stream).await?;
+        match resp_frame.frame_type {
+            FrameType::SetPersistResponse => {
+                let resp: SetPersistResponseMsg = resp_frame
+                    .parse_json()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                Ok(resp.persistent)
+            }
+            FrameType::Error => {
+                let err: ErrorMsg = resp_frame
+                    .parse_json()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                Err(io::Error::other(format!("{}: {}", err.code, err.message)))
+            }
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unexpected response frame type: {:?}", other),
+            )),
+        }
+    }
+
     /// Detach all attached clients from a session via the server's Unix socket.
     ///
     /// Unlike `kill_session`, this keeps the session alive — it only disconnects
@@ -499,12 +909,81 @@ impl Client {
     /// - Reads from stdin (via `spawn_blocking`) and forwards as StdinInput frames
     /// - Reads PtyOutput frames from the server and writes to stdout
     /// - Handles SIGWINCH signals and sends Resize frames
+    /// - Renders through a [`Viewport`] while the session is larger than
+    ///   the local terminal
     /// - Exits on stdin EOF or server disconnect
-    pub async fn run_streaming(self) -> io::Result<()> {
+    pub async fn run_streaming(self) -> io::Result<StreamEnd> {
+        let viewport = self.session_size.map(|size| {
+            let local = crate::terminal::terminal_size().unwrap_or((24, 80));
+            let mut viewport = Viewport::new(size, local);
+            viewport.feed(&self.initial_screen);
+            viewport
+        });
+        // An unusable command key was already reported by the server when
+        // it loaded the config, so the palette is just left off.
+        let commands = match (self.policy.command_sequence(), self.session_name) {
+            (Ok(Some(key)), Some(session)) => Some(Commands {
+                palette: palette::Palette::new(key),
+                socket_path: self.socket_path,
+                session,
+            }),
+            _ => None,
+        };
         let (reader, writer) = tokio::io::split(self.stream);
+        let mut stdin = StdinReader::spawn()?;
+        let mut sigwinch_rx = watch_window_size();
 
-        // Channel for stdin data from the blocking reader
-        let (stdin_tx, mut stdin_rx) = tokio::sync::mpsc::channel::<Bytes>(64);
+        let mut stdout = std::io::stdout();
+        let result = streaming_loop(reader, writer, &mut stdin.rx, &mut sigwinch_rx, &mut stdout, viewport, &self.policy, commands).await;
+
+        stdin.stop().await;
+        result
+    }
+
+}
+
+/// Show the session picker on the local terminal, which must already be
+/// in raw mode, and return the session picked, or `None` if the picker was
+/// closed or there are no sessions.
+pub async fn pick_session(socket_path: &Path) -> io::Result<Option<String>> {
+    use std::io::Write;
+
+    let sessions = Client::connect(socket_path).await?.list_sessions().await?;
+    let mut names: Vec<String> = sessions.into_iter().map(|s| s.name).collect();
+    if names.is_empty() {
+        return Ok(None);
+    }
+    names.sort();
+    let mut picker = picker::Picker::new(names, None);
+    let mut stdin = StdinReader::spawn()?;
+    let mut stdout = std::io::stdout();
+    let picked = loop {
+        let (rows, cols) = crate::terminal::terminal_size().unwrap_or((24, 80));
+        let _ = stdout.write_all(overlay::render_all_overlays(&[picker.overlay(rows, cols)]).as_bytes());
+        let _ = stdout.flush();
+        let Some(data) = stdin.rx.recv().await else {
+            break None;
+        };
+        match picker.handle(&data) {
+            picker::PickerEvent::Update => {}
+            picker::PickerEvent::Pick(name) => break Some(name),
+            picker::PickerEvent::Cancel => break None,
+        }
+    };
+    stdin.stop().await;
+    Ok(picked)
+}
+
+/// Local stdin, read on a blocking thread and delivered through `rx`.
+pub(crate) struct StdinReader {
+    pub(crate) rx: tokio::sync::mpsc::Receiver<Bytes>,
+    cancel_wr: std::os::unix::io::OwnedFd,
+    handle: tokio::task::JoinHandle<()>,
+}
+
+impl StdinReader {
+    pub(crate) fn spawn() -> io::Result<Self> {
+        let (stdin_tx, rx) = tokio::sync::mpsc::channel::<Bytes>(64);
 
         // Self-pipe for stdin reader cancellation. poll() blocks on both
         // stdin and the read end of this pipe. To cancel, we drop the write
@@ -524,8 +1003,7 @@ impl Client {
         };
         let cancel_rd_raw = std::os::unix::io::AsRawFd::as_raw_fd(&cancel_rd);
 
-        // Spawn stdin reader in a blocking thread.
-        let stdin_handle = tokio::task::spawn_blocking(move || {
+        let handle = tokio::task::spawn_blocking(move || {
             use std::io::Read;
             use std::os::unix::io::AsRawFd;
 
@@ -562,38 +1040,40 @@ impl Client {
             }
         });
 
-        // Channel for SIGWINCH signals
-        let (sigwinch_tx, mut sigwinch_rx) = tokio::sync::mpsc::channel::<(u16, u16)>(4);
-        tokio::spawn(async move {
-            use tokio::signal::unix::{signal, SignalKind};
-            let mut sigwinch = match signal(SignalKind::window_change()) {
-                Ok(s) => s,
-                Err(_) => return,
-            };
-            loop {
-                sigwinch.recv().await;
-                if let Ok((rows, cols)) = crate::terminal::terminal_size() {
-                    if sigwinch_tx.send((rows, cols)).await.is_err() {
-                        break;
-                    }
-                }
-            }
-        });
-
-        let mut stdout = std::io::stdout();
-        let result = streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut stdout).await;
+        Ok(Self { rx, cancel_wr, handle })
+    }
 
+    /// Stop the reader thread and wait for it to exit.
+    pub(crate) async fn stop(self) {
         // Close the cancel pipe write end — poll() in the reader wakes
         // instantly with POLLHUP and the reader exits. Then we join it
         // to ensure it's fully stopped before the caller restores the
         // terminal.
-        drop(cancel_wr);
-        drop(stdin_rx);
-        let _ = stdin_handle.await;
-
-        result
+        drop(self.cancel_wr);
+        drop(self.rx);
+        let _ = self.handle.await;
     }
+}
 
+/// Local terminal size after each SIGWINCH.
+pub(crate) fn watch_window_size() -> tokio::sync::mpsc::Receiver<(u16, u16)> {
+    let (sigwinch_tx, sigwinch_rx) = tokio::sync::mpsc::channel::<(u16, u16)>(4);
+    tokio::spawn(async move {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut sigwinch = match signal(SignalKind::window_change()) {
+            Ok(s) => s,
+            Err(_) => return,
+        };
+        loop {
+            sigwinch.recv().await;
+            if let Ok((rows, cols)) = crate::terminal::terminal_size() {
+                if sigwinch_tx.send((rows, cols)).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    sigwinch_rx
 }
 
 /// The main streaming loop, factored out of `run_streaming` for testability.
@@ -601,43 +1081,118 @@ impl Client {
 /// Reads stdin data from `stdin_rx`, reads frames from the server via `reader`,
 /// writes frames to the server via `writer`, and handles resize signals from
 /// `sigwinch_rx`. Terminal output (PTY data, overlays, panels) is written to
-/// `output`, which is `stdout` in production and a buffer in tests.
+/// `output`, which is `stdout` in production and a buffer in tests. When a
+/// `viewport` is given, PTY output is rendered through it while the session
+/// is larger than the local terminal. `policy` decides which keys detach and
+/// whether Ctrl+\ is left to the server as the capture toggle. With
+/// `commands`, local input goes through the command palette first.
+#[allow(clippy::too_many_arguments)]
 async fn streaming_loop(
     reader: ReadHalf<UnixStream>,
     mut writer: WriteHalf<UnixStream>,
     stdin_rx: &mut tokio::sync::mpsc::Receiver<Bytes>,
     sigwinch_rx: &mut tokio::sync::mpsc::Receiver<(u16, u16)>,
     output: &mut impl std::io::Write,
-) -> io::Result<()> {
+    mut viewport: Option<Viewport>,
+    policy: &ClientPolicy,
+    mut commands: Option<Commands>,
+) -> io::Result<StreamEnd> {
     // BufReader preserves partially-read bytes across select! cancellation,
     // making Frame::read_from cancellation-safe. Without this, if a select!
     // branch cancels read_from between the header and payload reads, the
     // next read would interpret payload bytes as a header, corrupting the stream.
     let mut reader = tokio::io::BufReader::new(reader);
-    // Ctrl+\ double-tap detection for detach.
-    // Each Ctrl+\ is forwarded to the server immediately (the server toggles
-    // input capture mode). If a second Ctrl+\ arrives within the timeout,
-    // we also detach. Two rapid toggles cancel out, leaving capture mode
-    // unchanged after re-attach.
+    // Detach key double-tap detection.
+    // With the default policy the detach key is Ctrl+\, which is forwarded
+    // to the server immediately (the server toggles input capture mode). If
+    // a second press arrives within the timeout, we also detach. Two rapid
+    // toggles cancel out, leaving capture mode unchanged after re-attach.
+    // Any other detach key is held back until the timeout expires, and only
+    // then delivered to the application, so a double-tap never reaches it.
+    let detach_keys = policy.detach_sequences().unwrap_or_default();
     let mut pending_detach = false;
+    let mut withheld: Option<Bytes> = None;
     let detach_timer = tokio::time::sleep(std::time::Duration::from_millis(500));
     tokio::pin!(detach_timer);
 
     // Local caches of visual state for erase-before-render
     let mut cached_overlays: Vec<Overlay> = Vec::new();
     let mut cached_panels: Vec<Panel> = Vec::new();
+    // The session picker as last drawn, to erase it when it closes and
+    // draw it again over new output.
+    let mut picker_overlay: Option<Overlay> = None;
+
+    if let Some(ref viewport) = viewport {
+        if viewport.is_active() {
+            let _ = output.write_all(viewport.render().as_bytes());
+            let _ = output.flush();
+        }
+    }
+
+    let mut end = StreamEnd::Detached;
 
-    loop {
+    'stream: loop {
         tokio::select! {
             // Stdin data → StdinInput frame to server
             data = stdin_rx.recv() => {
                 match data {
                     Some(data) => {
-                        if crate::input::is_ctrl_backslash(&data) {
-                            // Always forward immediately — server handles the toggle
-                            let frame = Frame::data(FrameType::StdinInput, data);
-                            if frame.write_to(&mut writer).await.is_err() {
-                                break;
+                        let data = match commands.as_mut() {
+                            Some(commands) => {
+                                let mut forward = None;
+                                for action in commands.palette.handle(&data) {
+                                    match commands.run(action).await {
+                                        CommandOutcome::Forward(data) => forward = Some(data),
+                                        CommandOutcome::Draw(text) => {
+                                            let (rows, cols) = crate::terminal::terminal_size().unwrap_or((24, 80));
+                                            let _ = output.write_all(palette::render_status(&text, rows, cols).as_bytes());
+                                        }
+                                        CommandOutcome::Redraw => {
+                                            if let Some(picker) = picker_overlay.take() {
+                                                let _ = output.write_all(overlay::erase_all_overlays(&[picker]).as_bytes());
+                                            }
+                                            redraw_screen(output, viewport.as_ref(), &cached_overlays);
+                                        }
+                                        CommandOutcome::DrawPicker => {
+                                            if let Some(picker) = commands.palette.picker() {
+                                                let (rows, cols) = crate::terminal::terminal_size().unwrap_or((24, 80));
+                                                let picker = picker.overlay(rows, cols);
+                                                let _ = output.write_all(overlay::render_all_overlays(std::slice::from_ref(&picker)).as_bytes());
+                                                picker_overlay = Some(picker);
+                                            }
+                                        }
+                                        CommandOutcome::End(stream_end) => {
+                                            let detach = Frame::new(FrameType::Detach, Bytes::new());
+                                            let _ = detach.write_to(&mut writer).await;
+                                            end = stream_end;
+                                            break 'stream;
+                                        }
+                                    }
+                                }
+                                let _ = output.flush();
+                                match forward {
+                                    Some(data) => data,
+                                    None => continue,
+                                }
+                            }
+                            None => data,
+                        };
+                        if let Some(ref mut viewport) = viewport {
+                            if viewport.is_active() && viewport.handle_input(&data) {
+                                let _ = output.write_all(viewport.render().as_bytes());
+                                let _ = output.flush();
+                                continue;
+                            }
+                        }
+                        if detach_keys.iter().any(|key| key[..] == data[..]) {
+                            if policy.allow_capture_toggle && crate::input::is_ctrl_backslash(&data) {
+                                // Forward immediately — server handles the toggle
+                                let frame = Frame::data(FrameType::StdinInput, data);
+                                if frame.write_to(&mut writer).await.is_err() {
+                                    break;
+                                }
+                            } else if !pending_detach {
+                                withheld = Some(data);
                             }
 
                             if pending_detach {
@@ -654,6 +1209,12 @@ async fn streaming_loop(
                             }
                         } else {
                             pending_detach = false;
+                            if let Some(key) = withheld.take() {
+                                let frame = Frame::data(FrameType::StdinInput, key);
+                                if frame.write_to(&mut writer).await.is_err() {
+                                    break;
+                                }
+                            }
                             let frame = Frame::data(FrameType::StdinInput, data);
                             if frame.write_to(&mut writer).await.is_err() {
                                 break;
@@ -675,15 +1236,40 @@ async fn streaming_loop(
                     Ok(frame) => {
                         match frame.frame_type {
                             FrameType::PtyOutput => {
-                                if !cached_overlays.is_empty() {
+                                if let Some(ref mut viewport) = viewport {
+                                    viewport.feed(&frame.payload);
+                                }
+                                let payload = clipboard_passthrough(frame.payload, policy.clipboard);
+                                if let Some(viewport) = viewport.as_ref().filter(|v| v.is_active()) {
+                                    let _ = output.write_all(viewport.render().as_bytes());
+                                    // The viewport redraws from its own screen, which
+                                    // drops the clipboard writes; pass them on as is.
+                                    if policy.clipboard {
+                                        for range in crate::parser::clipboard::sequences(&payload) {
+                                            let _ = output.write_all(&payload[range]);
+                                        }
+                                    }
+                                    if !cached_overlays.is_empty() {
+                                        let _ = output.write_all(overlay::save_cursor().as_bytes());
+                                        let _ = output.write_all(overlay::render_all_overlays(&cached_overlays).as_bytes());
+                                        let _ = output.write_all(overlay::restore_cursor().as_bytes());
+                                    }
+                                } else if !cached_overlays.is_empty() {
                                     // Erase overlays, write PTY output, re-render overlays
                                     let _ = output.write_all(overlay::begin_sync().as_bytes());
                                     let _ = output.write_all(overlay::erase_all_overlays(&cached_overlays).as_bytes());
-                                    let _ = output.write_all(&frame.payload);
+                                    let _ = output.write_all(&payload);
                                     let _ = output.write_all(overlay::render_all_overlays(&cached_overlays).as_bytes());
                                     let _ = output.write_all(overlay::end_sync().as_bytes());
                                 } else {
-                                    let _ = output.write_all(&frame.payload);
+                                    let _ = output.write_all(&payload);
+                                }
+                                if let Some(text) = commands.as_ref().and_then(|c| c.palette.status()) {
+                                    let (rows, cols) = crate::terminal::terminal_size().unwrap_or((24, 80));
+                                    let _ = output.write_all(palette::render_status(text, rows, cols).as_bytes());
+                                }
+                                if let Some(ref picker) = picker_overlay {
+                                    let _ = output.write_all(overlay::render_all_overlays(std::slice::from_ref(picker)).as_bytes());
                                 }
                                 let _ = output.flush();
                             }
@@ -724,6 +1310,24 @@ async fn streaming_loop(
                                 let pong = Frame::new(FrameType::Pong, Bytes::new());
                                 let _ = pong.write_to(&mut writer).await;
                             }
+                            FrameType::Resize => {
+                                // The session itself was resized
+                                if let (Some(viewport), Ok(msg)) = (viewport.as_mut(), frame.parse_json::<ResizeMsg>()) {
+                                    let was_active = viewport.is_active();
+                                    viewport.resize_session(msg.rows, msg.cols);
+                                    if was_active || viewport.is_active() {
+                                        let _ = output.write_all(viewport.render().as_bytes());
+                                        let _ = output.flush();
+                                    }
+                                }
+                            }
+                            FrameType::Banner => {
+                                if let Ok(BannerMsg { message: Some(message) }) = frame.parse_json::<BannerMsg>() {
+                                    let (_, cols) = crate::terminal::terminal_size().unwrap_or((24, 80));
+                                    let _ = output.write_all(render_banner(&message, cols).as_bytes());
+                                    let _ = output.flush();
+                                }
+                            }
                             FrameType::Detach => {
                                 break;
                             }
@@ -739,6 +1343,14 @@ async fn streaming_loop(
             // SIGWINCH → Resize frame to server
             size = sigwinch_rx.recv() => {
                 if let Some((rows, cols)) = size {
+                    if let Some(ref mut viewport) = viewport {
+                        let was_active = viewport.is_active();
+                        viewport.resize_local(rows, cols);
+                        if was_active || viewport.is_active() {
+                            let _ = output.write_all(viewport.render().as_bytes());
+                            let _ = output.flush();
+                        }
+                    }
                     let msg = ResizeMsg { rows, cols };
                     if let Ok(frame) = Frame::control(FrameType::Resize, &msg) {
                         let _ = frame.write_to(&mut writer).await;
@@ -746,9 +1358,15 @@ async fn streaming_loop(
                 }
             }
 
-            // Ctrl+\ double-tap timeout expired — no detach
+            // Detach key double-tap timeout expired — no detach
             () = &mut detach_timer, if pending_detach => {
                 pending_detach = false;
+                if let Some(key) = withheld.take() {
+                    let frame = Frame::data(FrameType::StdinInput, key);
+                    if frame.write_to(&mut writer).await.is_err() {
+                        break;
+                    }
+                }
             }
         }
     }
@@ -769,7 +1387,140 @@ async fn streaming_loop(
 
     // Ensure the writer half is cleanly shut down
     let _ = writer.shutdown().await;
-    Ok(())
+    Ok(end)
+}
+
+/// The command palette and the session its commands act on.
+struct Commands {
+    palette: palette::Palette,
+    socket_path: PathBuf,
+    session: String,
+}
+
+/// What the streaming loop does after a palette action.
+enum CommandOutcome {
+    /// Send these bytes to the session.
+    Forward(Bytes),
+    /// Draw this on the status line.
+    Draw(String),
+    /// Redraw the screen under the status line or picker.
+    Redraw,
+    /// Draw the palette's session picker.
+    DrawPicker,
+    End(StreamEnd),
+}
+
+impl Commands {
+    /// Carry out a palette action. Commands that talk to the server do so
+    /// over their own connection and report back on the status line.
+    async fn run(&mut self, action: palette::Action) -> CommandOutcome {
+        use palette::Action;
+        match action {
+            Action::Forward(data) => CommandOutcome::Forward(data),
+            Action::Show(text) => CommandOutcome::Draw(text),
+            Action::Hide => CommandOutcome::Redraw,
+            Action::ShowPicker => CommandOutcome::DrawPicker,
+            Action::Detach => CommandOutcome::End(StreamEnd::Detached),
+            Action::ListSessions => {
+                let text = match self.session_names().await {
+                    Ok(names) => {
+                        let names: Vec<String> = names
+                            .into_iter()
+                            .map(|name| if name == self.session { format!("*{}", name) } else { name })
+                            .collect();
+                        format!("sessions: {}", names.join("  "))
+                    }
+                    Err(e) => format!("list failed: {}", e),
+                };
+                self.message(text)
+            }
+            Action::OpenPicker => match self.session_names().await {
+                Ok(names) => {
+                    self.palette.open_picker(names, &self.session);
+                    CommandOutcome::DrawPicker
+                }
+                Err(e) => self.message(format!("list failed: {}", e)),
+            },
+            Action::Switch(name) => {
+                let text = match self.session_names().await {
+                    Ok(_) if name == self.session => format!("already attached to '{}'", name),
+                    Ok(names) if names.contains(&name) => return CommandOutcome::End(StreamEnd::Switch(name)),
+                    Ok(_) => format!("no session named '{}'", name),
+                    Err(e) => format!("switch failed: {}", e),
+                };
+                self.message(text)
+            }
+            Action::Rename(new_name) => {
+                let result = async {
+                    Client::connect(&self.socket_path)
+                        .await?
+                        .rename_session(&self.session, &new_name)
+                        .await
+                }
+                .await;
+                let text = match result {
+                    Ok(()) => {
+                        let text = format!("renamed '{}' to '{}'", self.session, new_name);
+                        self.session = new_name;
+                        text
+                    }
+                    Err(e) => format!("rename failed: {}", e),
+                };
+                self.message(text)
+            }
+        }
+    }
+
+    fn message(&mut self, text: String) -> CommandOutcome {
+        self.palette.message(text.clone());
+        CommandOutcome::Draw(text)
+    }
+
+    async fn session_names(&self) -> io::Result<Vec<String>> {
+        let sessions = Client::connect(&self.socket_path).await?.list_sessions().await?;
+        let mut names: Vec<String> = sessions.into_iter().map(|s| s.name).collect();
+        names.sort();
+        Ok(names)
+    }
+}
+
+/// Redraw the local terminal from the viewport's copy of the session,
+/// e.g. after the status line is removed.
+/// Output as it should reach the local terminal: with its clipboard
+/// writes (OSC 52) removed unless `clipboard` allows them.
+fn clipboard_passthrough(payload: Bytes, clipboard: bool) -> Bytes {
+    if clipboard {
+        return payload;
+    }
+    let ranges = crate::parser::clipboard::sequences(&payload);
+    if ranges.is_empty() {
+        return payload;
+    }
+    let mut out = Vec::with_capacity(payload.len());
+    let mut last = 0;
+    for range in ranges {
+        out.extend_from_slice(&payload[last..range.start]);
+        last = range.end;
+    }
+    out.extend_from_slice(&payload[last..]);
+    Bytes::from(out)
+}
+
+fn redraw_screen(output: &mut impl std::io::Write, viewport: Option<&Viewport>, overlays: &[Overlay]) {
+    match viewport {
+        Some(viewport) => {
+            let _ = output.write_all(viewport.render().as_bytes());
+        }
+        None => {
+            let (rows, _) = crate::terminal::terminal_size().unwrap_or((24, 80));
+            let _ = output.write_all(format!("\x1b7\x1b[{};1H\x1b[2K\x1b8", rows.max(1)).as_bytes());
+        }
+    }
+    if !overlays.is_empty() {
+        let _ = output.write_all(overlay::save_cursor().as_bytes());
+        let _ = output.write_all(overlay::render_all_overlays(overlays).as_bytes());
+        let _ = output.write_all(overlay::restore_cursor().as_bytes());
+    }
 }
 
 #[cfg(test)]
@@ -863,6 +1614,7 @@ mod tests {
         let msg = AttachSessionMsg {
             name: "attach-me".to_string(),
             scrollback: ScrollbackRequest::None,
+            scrollback_format: None,
             rows: 30,
             cols: 120,
         };
@@ -890,6 +1642,7 @@ mod tests {
         let msg = AttachSessionMsg {
             name: "no-such-session".to_string(),
             scrollback: ScrollbackRequest::None,
+            scrollback_format: None,
             rows: 24,
             cols: 80,
         };
@@ -1021,7 +1774,7 @@ mod tests {
 
         // Spawn the streaming loop
         let loop_handle = tokio::spawn(async move {
-            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink()).await
+            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink(), None, &ClientPolicy::default(), None).await
         });
 
         // Send data through stdin channel
@@ -1066,7 +1819,7 @@ mod tests {
 
         // Spawn the streaming loop
         let loop_handle = tokio::spawn(async move {
-            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink()).await
+            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink(), None, &ClientPolicy::default(), None).await
         });
 
         // Send a PtyOutput frame from the "server"
@@ -1092,7 +1845,7 @@ mod tests {
         let (sigwinch_tx, mut sigwinch_rx) = tokio::sync::mpsc::channel::<(u16, u16)>(4);
 
         let loop_handle = tokio::spawn(async move {
-            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink()).await
+            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink(), None, &ClientPolicy::default(), None).await
         });
 
         // Send a resize signal
@@ -1243,7 +1996,7 @@ mod tests {
         let (_sigwinch_tx, mut sigwinch_rx) = tokio::sync::mpsc::channel::<(u16, u16)>(4);
 
         let loop_handle = tokio::spawn(async move {
-            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink()).await
+            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink(), None, &ClientPolicy::default(), None).await
         });
 
         // Send Ctrl+\ twice in quick succession
@@ -1283,6 +2036,133 @@ mod tests {
         loop_handle.await.unwrap().unwrap();
     }
 
+    #[tokio::test]
+    async fn test_command_palette_detaches_and_switches() {
+        let sessions = SessionRegistry::new();
+        let (path, _dir) = start_test_server(sessions.clone()).await;
+        let mut creator = Client::connect(&path).await.unwrap();
+        creator
+            .create_session(CreateSessionMsg {
+                name: Some("other".to_string()),
+                command: None,
+                cwd: None,
+                env: None,
+                rows: 24,
+                cols: 80,
+                tags: vec![],
+                server: None,
+            })
+            .await
+            .unwrap();
+
+        let run = |keys: Vec<&'static [u8]>| {
+            let path = path.clone();
+            async move {
+                let (client_stream, mut server_stream) = TokioUnixStream::pair().unwrap();
+                let (reader, writer) = tokio::io::split(client_stream);
+                let (stdin_tx, mut stdin_rx) = tokio::sync::mpsc::channel::<Bytes>(64);
+                let (_sigwinch_tx, mut sigwinch_rx) = tokio::sync::mpsc::channel::<(u16, u16)>(4);
+                let commands = Commands {
+                    palette: palette::Palette::new(vec![0x01]),
+                    socket_path: path,
+                    session: "current".to_string(),
+                };
+                let loop_handle = tokio::spawn(async move {
+                    streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink(), None, &ClientPolicy::default(), Some(commands)).await
+                });
+                for key in keys {
+                    stdin_tx.send(Bytes::from_static(key)).await.unwrap();
+                }
+                let mut forwarded = Vec::new();
+                loop {
+                    let frame = tokio::time::timeout(
+                        std::time::Duration::from_secs(2),
+                        Frame::read_from(&mut server_stream),
+                    )
+                    .await
+                    .unwrap()
+                    .unwrap();
+                    match frame.frame_type {
+                        FrameType::StdinInput => forwarded.extend_from_slice(&frame.payload),
+                        FrameType::Detach => break,
+                        other => panic!("unexpected frame {:?}", other),
+                    }
+                }
+                (forwarded, loop_handle.await.unwrap().unwrap())
+            }
+        };
+
+        // The command key twice sends it; Ctrl+A d detaches.
+        let (forwarded, end) = run(vec![b"x", &[0x01], &[0x01], &[0x01], b"d"]).await;
+        assert_eq!(forwarded, b"x\x01");
+        assert_eq!(end, StreamEnd::Detached);
+
+        // The picker closes when the filter matches nothing, and the next
+        // key goes to the session; filtering down to a session picks it.
+        let (forwarded, end) = run(vec![&[0x01], b"s", b"nope\r", b"y", &[0x01], b"s", b"oth", b"\r"]).await;
+        assert_eq!(forwarded, b"y");
+        assert_eq!(end, StreamEnd::Switch("other".to_string()));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_policy_detach_key_is_withheld_then_forwarded() {
+        let (client_stream, mut server_stream) = TokioUnixStream::pair().unwrap();
+
+        let (reader, writer) = tokio::io::split(client_stream);
+        let (stdin_tx, mut stdin_rx) = tokio::sync::mpsc::channel::<Bytes>(64);
+        let (_sigwinch_tx, mut sigwinch_rx) = tokio::sync::mpsc::channel::<(u16, u16)>(4);
+        let policy = ClientPolicy {
+            detach_keys: vec!["ctrl+]".to_string()],
+            allow_capture_toggle: false,
+            command_key: None,
+            clipboard: true,
+            focus_key: "tab".to_string(),
+        };
+
+        let loop_handle = tokio::spawn(async move {
+            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink(), None, &policy, None).await
+        });
+
+        // Ctrl+\ is no longer special: forwarded right away.
+        stdin_tx.send(Bytes::from_static(&[0x1c])).await.unwrap();
+        let frame = Frame::read_from(&mut server_stream).await.unwrap();
+        assert_eq!(frame.payload.as_ref(), &[0x1c]);
+
+        // A single Ctrl+] is delivered once the double-tap window closes.
+        stdin_tx.send(Bytes::from_static(&[0x1d])).await.unwrap();
+        assert!(tokio::time::timeout(
+            std::time::Duration::from_millis(200),
+            Frame::read_from(&mut server_stream),
+        )
+        .await
+        .is_err());
+        let frame = tokio::time::timeout(
+            std::time::Duration::from_secs(2),
+            Frame::read_from(&mut server_stream),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(frame.frame_type, FrameType::StdinInput);
+        assert_eq!(frame.payload.as_ref(), &[0x1d]);
+
+        // A double-tap detaches without sending either press.
+        stdin_tx.send(Bytes::from_static(&[0x1d])).await.unwrap();
+        stdin_tx.send(Bytes::from_static(&[0x1d])).await.unwrap();
+        let frame = tokio::time::timeout(
+            std::time::Duration::from_secs(2),
+            Frame::read_from(&mut server_stream),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(frame.frame_type, FrameType::Detach);
+
+        loop_handle.await.unwrap().unwrap();
+    }
+
     #[tokio::test]
     async fn test_ctrl_backslash_single_tap_forwarded_immediately() {
         let (client_stream, mut server_stream) = TokioUnixStream::pair().unwrap();
@@ -1292,7 +2172,7 @@ mod tests {
         let (_sigwinch_tx, mut sigwinch_rx) = tokio::sync::mpsc::channel::<(u16, u16)>(4);
 
         let loop_handle = tokio::spawn(async move {
-            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink()).await
+            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink(), None, &ClientPolicy::default(), None).await
         });
 
         // Send a single Ctrl+\ — should be forwarded immediately (no delay)
@@ -1331,7 +2211,7 @@ mod tests {
         let (_sigwinch_tx, mut sigwinch_rx) = tokio::sync::mpsc::channel::<(u16, u16)>(4);
 
         let loop_handle = tokio::spawn(async move {
-            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink()).await
+            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink(), None, &ClientPolicy::default(), None).await
         });
 
         // Send Ctrl+\ followed by 'a'
@@ -1379,7 +2259,7 @@ mod tests {
         let (_sigwinch_tx, mut sigwinch_rx) = tokio::sync::mpsc::channel::<(u16, u16)>(4);
 
         let loop_handle = tokio::spawn(async move {
-            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink()).await
+            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut std::io::sink(), None, &ClientPolicy::default(), None).await
         });
 
         // Send Ctrl+\ then immediately close stdin
@@ -1424,6 +2304,10 @@ mod tests {
             visible: true,
             focusable: false,
             screen_mode: crate::overlay::ScreenMode::Normal,
+            sticky: false,
+            expires_at: None,
+            max_lines: None,
+            scroll: 0,
         }
     }
 
@@ -1538,7 +2422,7 @@ mod tests {
 
         let loop_handle = tokio::spawn(async move {
             let mut out = output_buf_clone;
-            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut out).await
+            streaming_loop(reader, writer, &mut stdin_rx, &mut sigwinch_rx, &mut out, None, &ClientPolicy::default(), None).await
         });
 
         // Send a PanelSync frame with empty panels (simulates server visual
@@ -1567,4 +2451,103 @@ mod tests {
             output,
         );
     }
+
+    #[test]
+    fn test_render_scrollback_lines_wraps_to_local_width() {
+        use crate::parser::state::FormattedLine;
+        let lines = vec![
+            FormattedLine::Plain("abcdefgh".to_string()),
+            FormattedLine::Plain(String::new()),
+        ];
+        let out = String::from_utf8(render_scrollback_lines(&lines, 5)).unwrap();
+        assert!(out.contains("abcde\r\n"), "got {:?}", out);
+        assert!(out.contains("fgh"), "got {:?}", out);
+        // Two rows for the wrapped line plus one for the empty line
+        assert_eq!(out.matches("\r\n").count(), 3);
+    }
+
+    #[test]
+    fn test_clipboard_passthrough_strips_only_when_disabled() {
+        let payload = Bytes::from_static(b"a\x1b]52;c;aGk=\x07b\x1b]0;t\x07");
+        assert_eq!(clipboard_passthrough(payload.clone(), true), payload);
+        assert_eq!(&clipboard_passthrough(payload, false)[..], b"ab\x1b]0;t\x07");
+    }
+
+    #[test]
+    fn test_render_banner_fits_top_row() {
+        let out = render_banner("draining", 20);
+        assert!(out.contains("\x1b[1;1H\x1b[7m wsh: draining      \x1b[0m"), "got {:?}", out);
+
+        let out = render_banner("a very long maintenance reason", 10);
+        assert!(out.contains("\x1b[7m wsh: a ve\x1b[0m"), "got {:?}", out);
+    }
+
+    #[test]
+    fn test_needs_viewport() {
+        assert!(!needs_viewport((24, 80), (24, 80)));
+        assert!(!needs_viewport((24, 80), (40, 120)));
+        assert!(needs_viewport((40, 80), (24, 80)));
+        assert!(needs_viewport((24, 120), (24, 80)));
+    }
+
+    #[test]
+    fn test_viewport_follows_cursor() {
+        let mut viewport = Viewport::new((10, 40), (5, 20));
+        assert!(viewport.is_active());
+        for i in 0..9 {
+            viewport.feed(format!("line {}\r\n", i).as_bytes());
+        }
+        // Cursor is on row 9, so the 5-row window shows rows 5..10
+        let out = viewport.render();
+        assert!(out.contains("line 8"), "got {:?}", out);
+        assert!(out.contains("line 5"), "got {:?}", out);
+        assert!(!out.contains("line 4"), "got {:?}", out);
+    }
+
+    #[test]
+    fn test_viewport_pan_keys_and_resume_follow() {
+        let mut viewport = Viewport::new((10, 40), (5, 20));
+        for i in 0..9 {
+            viewport.feed(format!("line {}\r\n", i).as_bytes());
+        }
+        // Shift+Alt+Up is consumed and pans away from the cursor
+        assert!(viewport.handle_input(b"\x1b[1;4A"));
+        assert!(viewport.handle_input(b"\x1b[1;4A"));
+        let out = viewport.render();
+        assert!(out.contains("line 1"), "got {:?}", out);
+        assert!(!out.contains("line 8"), "got {:?}", out);
+
+        // Any other input is forwarded and snaps back to the cursor
+        assert!(!viewport.handle_input(b"x"));
+        assert!(viewport.render().contains("line 8"));
+    }
+
+    #[test]
+    fn test_viewport_crops_columns() {
+        let mut viewport = Viewport::new((5, 40), (5, 10));
+        viewport.feed(b"0123456789abcdefghij");
+        // Cursor at col 20 pushes the window right to cols 11..21
+        let out = viewport.render();
+        assert!(out.contains("bcdefghij"), "got {:?}", out);
+        assert!(!out.contains("0123"), "got {:?}", out);
+    }
+
+    #[test]
+    fn test_viewport_buffers_split_utf8() {
+        let mut viewport = Viewport::new((5, 40), (2, 20));
+        let bytes = "héllo".as_bytes();
+        viewport.feed(&bytes[..2]);
+        viewport.feed(&bytes[2..]);
+        assert!(viewport.render().contains("héllo"));
+    }
+
+    #[test]
+    fn test_viewport_inactive_when_local_fits() {
+        let mut viewport = Viewport::new((24, 80), (24, 80));
+        assert!(!viewport.is_active());
+        viewport.resize_session(40, 80);
+        assert!(viewport.is_active());
+        viewport.resize_local(50, 100);
+        assert!(!viewport.is_active());
+    }
 }
@@ -0,0 +1,136 @@
+//! Output format of the CLI subcommands (`--output`).
+//!
+//! In `text` mode results are printed for people and errors go to stderr
+//! as `wsh <command>: <message>`. In `json` mode every subcommand prints
+//! one JSON document to stdout: its result, or on failure
+//!
+//! ```json
+//! {"error": {"command": "kill", "code": "session_not_found", "message": "..."}, "exit_code": 1}
+//! ```
+//!
+//! so scripts read a single stream whatever happens. `code` is the server's
+//! error code when it sent one, `server_unreachable` when the server could
+//! not be reached, and `error` otherwise.
+
+use std::fmt::Display;
+
+use serde::Serialize;
+
+/// Exit status of a failed subcommand.
+pub const EXIT_FAILURE: i32 = 1;
+
+/// How subcommands print results and errors.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable text.
+    #[default]
+    Text,
+    /// One JSON document on stdout.
+    Json,
+}
+
+/// Printer for one subcommand's result or error.
+#[derive(Debug, Clone, Copy)]
+pub struct Output {
+    pub format: OutputFormat,
+    /// Subcommand name, as in `wsh servers add`'s `servers add`.
+    pub command: &'static str,
+}
+
+impl Output {
+    pub fn new(format: OutputFormat, command: &'static str) -> Self {
+        Self { format, command }
+    }
+
+    pub fn is_json(&self) -> bool {
+        self.format == OutputFormat::Json
+    }
+
+    /// Print a result: `value` as JSON, or whatever `text` prints.
+    pub fn print<T: Serialize + ?Sized>(&self, value: &T, text: impl FnOnce()) {
+        match self.format {
+            OutputFormat::Json => {
+                println!("{}", serde_json::to_string_pretty(value).unwrap_or_default())
+            }
+            OutputFormat::Text => text(),
+        }
+    }
+
+    /// Report an error and exit. The code is taken from a `code: message`
+    /// error as the socket client returns them.
+    pub fn fail(&self, err: impl Display) -> ! {
+        let message = err.to_string();
+        match split_code(&message) {
+            Some((code, rest)) => {
+                let (code, rest) = (code.to_string(), rest.to_string());
+                self.report(&code, &message, &rest)
+            }
+            None => self.report("error", &message, &message),
+        }
+    }
+
+    /// Report an error with the given code and exit.
+    pub fn fail_with(&self, code: &str, message: impl Display) -> ! {
+        let message = message.to_string();
+        self.report(code, &message, &message)
+    }
+
+    /// Report that the server at `socket_path` could not be reached and exit.
+    pub fn unreachable(&self, socket_path: &std::path::Path, err: impl Display) -> ! {
+        self.fail_with(
+            "server_unreachable",
+            format!("failed to connect to server at {}: {}", socket_path.display(), err),
+        )
+    }
+
+    /// Text mode prints `text`, JSON mode the bare `message`.
+    fn report(&self, code: &str, text: &str, message: &str) -> ! {
+        match self.format {
+            OutputFormat::Text => eprintln!("wsh {}: {}", self.command, text),
+            OutputFormat::Json => println!("{}", error_json(self.command, code, message, EXIT_FAILURE)),
+        }
+        std::process::exit(EXIT_FAILURE)
+    }
+}
+
+/// The JSON document printed for a failed subcommand.
+fn error_json(command: &str, code: &str, message: &str, exit_code: i32) -> serde_json::Value {
+    serde_json::json!({
+        "error": {"command": command, "code": code, "message": message},
+        "exit_code": exit_code,
+    })
+}
+
+/// Split `code: message` where `code` is a snake_case error code.
+fn split_code(message: &str) -> Option<(&str, &str)> {
+    let (code, rest) = message.split_once(": ")?;
+    let is_code = !code.is_empty()
+        && code.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_')
+        && code.as_bytes()[0].is_ascii_lowercase();
+    is_code.then_some((code, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_server_error_codes() {
+        assert_eq!(
+            split_code("session_not_found: session not found: dev"),
+            Some(("session_not_found", "session not found: dev"))
+        );
+        assert_eq!(split_code("Connection refused (os error 111)"), None);
+        assert_eq!(split_code("no code here"), None);
+        assert_eq!(split_code("io error: broken pipe"), None);
+    }
+
+    #[test]
+    fn error_document_carries_code_and_exit_code() {
+        let doc = error_json("kill", "session_not_found", "session not found: dev", EXIT_FAILURE);
+        assert_eq!(doc["error"]["command"], "kill");
+        assert_eq!(doc["error"]["code"], "session_not_found");
+        assert_eq!(doc["error"]["message"], "session not found: dev");
+        assert_eq!(doc["exit_code"], 1);
+    }
+}
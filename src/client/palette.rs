@@ -0,0 +1,267 @@
+//! Command palette for the attach client.
+//!
+//! Pressing the command key (`command_key` in the `[client]` config, like
+//! tmux's prefix key) shows a menu on the bottom line of the terminal, and
+//! the next key picks a command. Renaming prompts for the new name on the
+//! same line, and switching opens the session [`Picker`]. The palette only
+//! decides what each key means; the streaming loop carries out the
+//! resulting [`Action`]s.
+
+use bytes::Bytes;
+
+use super::picker::{Picker, PickerEvent};
+
+const MENU: &str = "d detach  s switch  l list  r rename  (command key again to send it)";
+
+/// What the streaming loop should do in response to a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Send these bytes to the session.
+    Forward(Bytes),
+    /// Draw this text on the status line.
+    Show(String),
+    /// Remove the status line or picker and redraw what it covered.
+    Hide,
+    /// Draw the session picker returned by [`Palette::picker`].
+    ShowPicker,
+    Detach,
+    ListSessions,
+    /// Fetch the session list and open the picker with it.
+    OpenPicker,
+    Switch(String),
+    Rename(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Prompt {
+    Rename,
+}
+
+impl Prompt {
+    fn label(self) -> &'static str {
+        match self {
+            Prompt::Rename => "rename to: ",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum State {
+    Idle,
+    Menu,
+    Prompt(Prompt, String),
+    Picker(Picker),
+    /// A message is shown until the next key, which is then handled as if
+    /// the palette were closed.
+    Message,
+}
+
+/// Key dispatch for the command palette.
+pub struct Palette {
+    key: Vec<u8>,
+    state: State,
+    status: Option<String>,
+}
+
+impl Palette {
+    /// A palette opened by the key that produces `key`.
+    pub fn new(key: Vec<u8>) -> Self {
+        Self { key, state: State::Idle, status: None }
+    }
+
+    /// Text currently on the status line, to redraw after output covers it.
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+
+    /// The open session picker, to redraw after output covers it.
+    pub fn picker(&self) -> Option<&Picker> {
+        match &self.state {
+            State::Picker(picker) => Some(picker),
+            _ => None,
+        }
+    }
+
+    /// Open the session picker over `names`, with `current` marked.
+    pub fn open_picker(&mut self, names: Vec<String>, current: &str) -> Action {
+        self.status = None;
+        self.state = State::Picker(Picker::new(names, Some(current.to_string())));
+        Action::ShowPicker
+    }
+
+    /// Show `text` until the next key.
+    pub fn message(&mut self, text: String) {
+        self.state = State::Message;
+        self.status = Some(text);
+    }
+
+    /// Handle one chunk of local input.
+    pub fn handle(&mut self, data: &[u8]) -> Vec<Action> {
+        match std::mem::replace(&mut self.state, State::Idle) {
+            State::Idle if data == self.key => {
+                self.state = State::Menu;
+                vec![self.show(MENU.to_string())]
+            }
+            State::Idle => vec![Action::Forward(Bytes::copy_from_slice(data))],
+            State::Message => {
+                let mut actions = vec![self.hide()];
+                actions.extend(self.handle(data));
+                actions
+            }
+            State::Menu => {
+                let action = match data {
+                    d if d == self.key => Action::Forward(Bytes::copy_from_slice(d)),
+                    b"d" => Action::Detach,
+                    b"l" => Action::ListSessions,
+                    b"s" => Action::OpenPicker,
+                    b"r" => return self.prompt(Prompt::Rename, String::new()),
+                    _ => return vec![self.hide()],
+                };
+                vec![self.hide(), action]
+            }
+            State::Prompt(prompt, mut input) => {
+                // Arrow keys and other escape sequences aren't editing keys.
+                if data.len() > 1 && data[0] == 0x1b {
+                    return self.prompt(prompt, input);
+                }
+                for c in String::from_utf8_lossy(data).chars() {
+                    match c {
+                        '\r' | '\n' => {
+                            let mut actions = vec![self.hide()];
+                            if !input.is_empty() {
+                                actions.push(match prompt {
+                                    Prompt::Rename => Action::Rename(input),
+                                });
+                            }
+                            return actions;
+                        }
+                        '\x1b' | '\x03' => return vec![self.hide()],
+                        '\x7f' | '\x08' => {
+                            input.pop();
+                        }
+                        c if !c.is_control() => input.push(c),
+                        _ => {}
+                    }
+                }
+                self.prompt(prompt, input)
+            }
+            State::Picker(mut picker) => match picker.handle(data) {
+                PickerEvent::Update => {
+                    self.state = State::Picker(picker);
+                    vec![Action::ShowPicker]
+                }
+                PickerEvent::Pick(name) => vec![self.hide(), Action::Switch(name)],
+                PickerEvent::Cancel => vec![self.hide()],
+            },
+        }
+    }
+
+    fn prompt(&mut self, prompt: Prompt, input: String) -> Vec<Action> {
+        let text = format!("{}{}", prompt.label(), input);
+        self.state = State::Prompt(prompt, input);
+        vec![self.show(text)]
+    }
+
+    fn show(&mut self, text: String) -> Action {
+        self.status = Some(text.clone());
+        Action::Show(text)
+    }
+
+    fn hide(&mut self) -> Action {
+        self.status = None;
+        Action::Hide
+    }
+}
+
+/// Draw `text` in reverse video on the bottom row of a `rows` x `cols`
+/// terminal, leaving the cursor where it was.
+pub fn render_status(text: &str, rows: u16, cols: u16) -> String {
+    let text: String = format!(" {}", text).chars().take(cols as usize).collect();
+    format!("\x1b7\x1b[{};1H\x1b[0;7m{}\x1b[K\x1b[0m\x1b8", rows.max(1), text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = &[0x01]; // ctrl+a
+
+    #[test]
+    fn keys_pass_through_until_the_command_key() {
+        let mut palette = Palette::new(KEY.to_vec());
+        assert_eq!(palette.handle(b"ls\r"), vec![Action::Forward(Bytes::from_static(b"ls\r"))]);
+        assert!(matches!(palette.handle(KEY)[..], [Action::Show(_)]));
+        assert_eq!(palette.handle(b"d"), vec![Action::Hide, Action::Detach]);
+        assert_eq!(palette.handle(b"x"), vec![Action::Forward(Bytes::from_static(b"x"))]);
+    }
+
+    #[test]
+    fn command_key_twice_sends_it() {
+        let mut palette = Palette::new(KEY.to_vec());
+        palette.handle(KEY);
+        assert_eq!(palette.handle(KEY), vec![Action::Hide, Action::Forward(Bytes::from_static(KEY))]);
+    }
+
+    #[test]
+    fn unknown_command_closes_the_menu() {
+        let mut palette = Palette::new(KEY.to_vec());
+        palette.handle(KEY);
+        assert_eq!(palette.handle(b"\x1b"), vec![Action::Hide]);
+        assert!(palette.status().is_none());
+    }
+
+    #[test]
+    fn prompt_edits_and_submits_a_name() {
+        let mut palette = Palette::new(KEY.to_vec());
+        palette.handle(KEY);
+        assert_eq!(palette.handle(b"r"), vec![Action::Show("rename to: ".to_string())]);
+        palette.handle(b"bulid");
+        palette.handle(b"\x7f\x7f\x7f");
+        palette.handle(b"\x1b[D");
+        assert_eq!(palette.handle(b"ild"), vec![Action::Show("rename to: build".to_string())]);
+        assert_eq!(palette.handle(b"\r"), vec![Action::Hide, Action::Rename("build".to_string())]);
+    }
+
+    #[test]
+    fn switch_opens_the_picker_and_picks_a_session() {
+        let mut palette = Palette::new(KEY.to_vec());
+        palette.handle(KEY);
+        assert_eq!(palette.handle(b"s"), vec![Action::Hide, Action::OpenPicker]);
+        assert_eq!(
+            palette.open_picker(vec!["a".to_string(), "b".to_string()], "a"),
+            Action::ShowPicker
+        );
+        assert_eq!(palette.picker().and_then(|p| p.selected()), Some("a"));
+        assert_eq!(palette.handle(b"\x1b[B"), vec![Action::ShowPicker]);
+        assert_eq!(palette.handle(b"\r"), vec![Action::Hide, Action::Switch("b".to_string())]);
+        assert!(palette.picker().is_none());
+
+        palette.open_picker(vec!["a".to_string()], "a");
+        assert_eq!(palette.handle(b"\x1b"), vec![Action::Hide]);
+        assert_eq!(palette.handle(b"x"), vec![Action::Forward(Bytes::from_static(b"x"))]);
+    }
+
+    #[test]
+    fn prompt_cancels_on_escape_and_ignores_empty_names() {
+        let mut palette = Palette::new(KEY.to_vec());
+        palette.handle(KEY);
+        palette.handle(b"r");
+        palette.handle(b"new");
+        assert_eq!(palette.handle(b"\x1b"), vec![Action::Hide]);
+
+        palette.handle(KEY);
+        palette.handle(b"r");
+        assert_eq!(palette.handle(b"\r"), vec![Action::Hide]);
+    }
+
+    #[test]
+    fn message_is_dismissed_by_the_next_key() {
+        let mut palette = Palette::new(KEY.to_vec());
+        palette.message("sessions: a b".to_string());
+        assert_eq!(palette.status(), Some("sessions: a b"));
+        assert_eq!(
+            palette.handle(b"q"),
+            vec![Action::Hide, Action::Forward(Bytes::from_static(b"q"))]
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! Session picker for the attach client.
+//!
+//! Lists the sessions on the server in a box drawn through the overlay
+//! renderer. Typing filters the list, the arrow keys (or Ctrl+P / Ctrl+N)
+//! move the selection, Enter picks the selected session and Escape or
+//! Ctrl+C closes the picker. Used by the command palette's switch command
+//! and by `wsh switch`.
+
+use crate::overlay::{BackgroundStyle, Color, NamedColor, Overlay, OverlaySpan};
+
+/// Id of the picker's overlay. It is only ever rendered locally.
+const OVERLAY_ID: &str = "wsh-session-picker";
+
+const HINT: &str = "type to filter, enter to switch, esc to close";
+
+/// What happened in response to a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PickerEvent {
+    /// The filter or selection changed; draw the picker again.
+    Update,
+    Pick(String),
+    Cancel,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Picker {
+    names: Vec<String>,
+    current: Option<String>,
+    filter: String,
+    selected: usize,
+}
+
+impl Picker {
+    /// A picker over `names`, marking `current` (the attached session) and
+    /// starting with it selected.
+    pub fn new(names: Vec<String>, current: Option<String>) -> Self {
+        let selected = current
+            .as_ref()
+            .and_then(|c| names.iter().position(|n| n == c))
+            .unwrap_or(0);
+        Self { names, current, filter: String::new(), selected }
+    }
+
+    /// Sessions whose name contains the filter, in list order.
+    pub fn matches(&self) -> Vec<&str> {
+        let filter = self.filter.to_lowercase();
+        self.names
+            .iter()
+            .filter(|name| name.to_lowercase().contains(&filter))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// The session Enter would pick.
+    pub fn selected(&self) -> Option<&str> {
+        let matches = self.matches();
+        matches.get(self.selected.min(matches.len().saturating_sub(1))).copied()
+    }
+
+    /// Handle one chunk of local input.
+    pub fn handle(&mut self, data: &[u8]) -> PickerEvent {
+        match data {
+            b"\x1b[A" | b"\x1bOA" | b"\x10" => {
+                self.clamp();
+                self.selected = self.selected.saturating_sub(1);
+                return PickerEvent::Update;
+            }
+            b"\x1b[B" | b"\x1bOB" | b"\x0e" => {
+                self.clamp();
+                if self.selected + 1 < self.matches().len() {
+                    self.selected += 1;
+                }
+                return PickerEvent::Update;
+            }
+            // Other escape sequences aren't editing keys.
+            d if d.len() > 1 && d[0] == 0x1b => return PickerEvent::Update,
+            _ => {}
+        }
+        for c in String::from_utf8_lossy(data).chars() {
+            match c {
+                '\r' | '\n' => {
+                    return match self.selected() {
+                        Some(name) => PickerEvent::Pick(name.to_string()),
+                        None => PickerEvent::Cancel,
+                    };
+                }
+                '\x1b' | '\x03' => return PickerEvent::Cancel,
+                '\x7f' | '\x08' => {
+                    self.filter.pop();
+                    self.selected = 0;
+                }
+                c if !c.is_control() => {
+                    self.filter.push(c);
+                    self.selected = 0;
+                }
+                _ => {}
+            }
+        }
+        PickerEvent::Update
+    }
+
+    fn clamp(&mut self) {
+        self.selected = self.selected.min(self.matches().len().saturating_sub(1));
+    }
+
+    /// The picker as an overlay centered on a `rows` x `cols` terminal.
+    ///
+    /// The box is sized for the whole session list so that it doesn't
+    /// shrink, and leave stale cells behind, as the filter narrows it.
+    pub fn overlay(&self, rows: u16, cols: u16) -> Overlay {
+        let longest = self.names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+        let width = (longest + 4).max(HINT.len() + 2).min(cols.max(1) as usize);
+        // Title, filter and hint lines plus one per session.
+        let list_rows = (rows as usize).saturating_sub(5).max(1);
+        let height = (self.names.len().clamp(1, list_rows) + 3).min(rows.max(1) as usize);
+
+        let matches = self.matches();
+        let selected = self.selected.min(matches.len().saturating_sub(1));
+        // Scroll so the selection stays visible.
+        let first = (selected + 1).saturating_sub(height.saturating_sub(3));
+
+        let fit = |text: String| -> String {
+            let text: String = text.chars().take(width).collect();
+            let pad = width - text.chars().count();
+            format!("{}{}", text, " ".repeat(pad))
+        };
+
+        let mut spans = vec![
+            span(fit(" switch session".to_string()), true, false),
+            span(format!("\n{}", fit(format!(" > {}", self.filter))), false, false),
+        ];
+        if matches.is_empty() {
+            spans.push(span(format!("\n{}", fit("   (no matches)".to_string())), false, false));
+        }
+        for (i, name) in matches.iter().enumerate().skip(first).take(height.saturating_sub(3)) {
+            let marker = if Some(*name) == self.current.as_deref() { '*' } else { ' ' };
+            spans.push(span(format!("\n{}", fit(format!(" {} {}", marker, name))), false, i == selected));
+        }
+        let shown = matches.len().saturating_sub(first).clamp(1, height.saturating_sub(3).max(1));
+        for _ in shown..height.saturating_sub(3) {
+            spans.push(span(format!("\n{}", fit(String::new())), false, false));
+        }
+        spans.push(span(format!("\n{}", fit(format!(" {}", HINT))), false, false));
+
+        let width = width as u16;
+        let height = height as u16;
+        Overlay {
+            id: OVERLAY_ID.to_string(),
+            x: cols.saturating_sub(width) / 2,
+            y: rows.saturating_sub(height) / 2,
+            z: i32::MAX,
+            width,
+            height,
+            background: Some(BackgroundStyle { bg: Color::Named(NamedColor::Black) }),
+            spans,
+            region_writes: Vec::new(),
+            focusable: false,
+            screen_mode: Default::default(),
+            widget: None,
+            sticky: false,
+            expires_at: None,
+        }
+    }
+}
+
+fn span(text: String, bold: bool, highlight: bool) -> OverlaySpan {
+    OverlaySpan {
+        text,
+        id: None,
+        fg: Some(Color::Named(if highlight { NamedColor::Black } else { NamedColor::White })),
+        bg: Some(Color::Named(if highlight { NamedColor::Cyan } else { NamedColor::Black })),
+        bold,
+        italic: false,
+        underline: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn picker() -> Picker {
+        Picker::new(
+            vec!["api".to_string(), "build".to_string(), "builder".to_string()],
+            Some("build".to_string()),
+        )
+    }
+
+    #[test]
+    fn starts_on_the_current_session() {
+        assert_eq!(picker().selected(), Some("build"));
+        assert_eq!(Picker::new(vec!["a".to_string()], None).selected(), Some("a"));
+        assert_eq!(Picker::new(Vec::new(), None).selected(), None);
+    }
+
+    #[test]
+    fn arrows_move_the_selection_within_the_list() {
+        let mut picker = picker();
+        assert_eq!(picker.handle(b"\x1b[B"), PickerEvent::Update);
+        assert_eq!(picker.selected(), Some("builder"));
+        picker.handle(b"\x1b[B");
+        assert_eq!(picker.selected(), Some("builder"));
+        picker.handle(b"\x1b[A");
+        picker.handle(b"\x10");
+        picker.handle(b"\x10");
+        assert_eq!(picker.selected(), Some("api"));
+    }
+
+    #[test]
+    fn typing_filters_and_enter_picks() {
+        let mut picker = picker();
+        picker.handle(b"BUI");
+        assert_eq!(picker.matches(), vec!["build", "builder"]);
+        picker.handle(b"\x0e");
+        assert_eq!(picker.handle(b"\r"), PickerEvent::Pick("builder".to_string()));
+    }
+
+    #[test]
+    fn enter_with_no_matches_cancels() {
+        let mut picker = picker();
+        picker.handle(b"zzz");
+        assert!(picker.matches().is_empty());
+        assert_eq!(picker.handle(b"\r"), PickerEvent::Cancel);
+        assert_eq!(picker.handle(b"\x7f\x7f\x7f\x1b"), PickerEvent::Cancel);
+    }
+
+    #[test]
+    fn overlay_fits_the_terminal_and_keeps_its_size() {
+        let mut picker = picker();
+        let full = picker.overlay(24, 80);
+        assert!(full.x + full.width <= 80 && full.y + full.height <= 24);
+        assert_eq!(full.height, 6);
+        picker.handle(b"api");
+        let filtered = picker.overlay(24, 80);
+        assert_eq!((filtered.width, filtered.height), (full.width, full.height));
+
+        let small = picker.overlay(4, 10);
+        assert!(small.width <= 10 && small.height <= 4);
+    }
+}
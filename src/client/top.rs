@@ -0,0 +1,507 @@
+//! `wsh top`: a dashboard of the sessions on the server.
+//!
+//! The left pane lists every session with its client count, idle time,
+//! tags and a sparkline of when it was active; the right pane shows the
+//! last lines of the selected session's output. Both refresh every second.
+//! The arrow keys (or j/k) move the selection, Enter attaches to the
+//! selected session, `K` kills it after asking, `t` adds or removes tags
+//! and `q` or Escape quits.
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Paragraph, Row, Table, TableState};
+use ratatui::{Frame, Terminal};
+
+use super::{Client, StdinReader};
+use crate::protocol::{ReadOutputMsg, SessionInfoMsg};
+
+/// How often the session list and preview are refreshed.
+const REFRESH: Duration = Duration::from_secs(1);
+
+/// Activity samples kept per session, one per refresh.
+const HISTORY: usize = 60;
+
+/// Output lines fetched for the preview pane.
+const PREVIEW_LINES: usize = 100;
+
+const HINT: &str = "↑/↓ select  enter attach  K kill  t tags  q quit";
+
+/// What the caller should do after a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TopEvent {
+    /// Draw the dashboard again.
+    Update,
+    Attach(String),
+    Kill { name: String, server: Option<String> },
+    Tag { name: String, server: Option<String>, add: Vec<String>, remove: Vec<String> },
+    Quit,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Mode {
+    Browse,
+    ConfirmKill,
+    /// Editing a tag change such as `+build -ci`.
+    Tags(String),
+}
+
+pub struct Dashboard {
+    /// Hostname of the server we're connected to. Sessions on other
+    /// servers come from federation.
+    local: String,
+    sessions: Vec<SessionInfoMsg>,
+    /// Whether each session was active in each refresh, oldest first,
+    /// keyed by server and name.
+    activity: HashMap<(String, String), VecDeque<bool>>,
+    selected: usize,
+    preview: Vec<String>,
+    mode: Mode,
+    status: Option<String>,
+}
+
+impl Dashboard {
+    pub fn new(local: String) -> Self {
+        Self {
+            local,
+            sessions: Vec::new(),
+            activity: HashMap::new(),
+            selected: 0,
+            preview: Vec::new(),
+            mode: Mode::Browse,
+            status: None,
+        }
+    }
+
+    /// Replace the session list, keeping the selection on the same session
+    /// and recording which sessions were active in the last `interval`.
+    pub fn update(&mut self, mut sessions: Vec<SessionInfoMsg>, interval: Duration) {
+        sessions.sort_by(|a, b| (&a.server, &a.name).cmp(&(&b.server, &b.name)));
+        let current = self.selected_session().map(key);
+        self.selected = current
+            .and_then(|current| sessions.iter().position(|s| key(s) == current))
+            .unwrap_or(self.selected)
+            .min(sessions.len().saturating_sub(1));
+
+        let mut activity = HashMap::new();
+        for s in &sessions {
+            let mut history = self.activity.remove(&key(s)).unwrap_or_default();
+            history.push_back(s.last_activity_ms < interval.as_millis() as u64);
+            if history.len() > HISTORY {
+                history.pop_front();
+            }
+            activity.insert(key(s), history);
+        }
+        self.activity = activity;
+        self.sessions = sessions;
+    }
+
+    pub fn selected_session(&self) -> Option<&SessionInfoMsg> {
+        self.sessions.get(self.selected)
+    }
+
+    /// Whether `s` is on the server we're connected to, so its output can
+    /// be previewed and it can be attached to.
+    pub fn is_local(&self, s: &SessionInfoMsg) -> bool {
+        s.server.is_empty() || s.server == self.local
+    }
+
+    pub fn set_preview(&mut self, lines: Vec<String>) {
+        self.preview = lines;
+    }
+
+    pub fn set_status(&mut self, status: impl Into<String>) {
+        self.status = Some(status.into());
+    }
+
+    /// Handle one chunk of local input.
+    pub fn handle(&mut self, data: &[u8]) -> TopEvent {
+        match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Browse => self.handle_browse(data),
+            Mode::ConfirmKill => match (data, self.selected_session()) {
+                (b"y" | b"Y", Some(s)) => TopEvent::Kill {
+                    name: s.name.clone(),
+                    server: server_of(s),
+                },
+                _ => TopEvent::Update,
+            },
+            Mode::Tags(input) => self.handle_tags(input, data),
+        }
+    }
+
+    fn handle_browse(&mut self, data: &[u8]) -> TopEvent {
+        self.status = None;
+        match data {
+            b"\x1b[A" | b"\x1bOA" | b"\x10" | b"k" => {
+                self.selected = self.selected.saturating_sub(1);
+                TopEvent::Update
+            }
+            b"\x1b[B" | b"\x1bOB" | b"\x0e" | b"j" => {
+                if self.selected + 1 < self.sessions.len() {
+                    self.selected += 1;
+                }
+                TopEvent::Update
+            }
+            b"\r" | b"\n" => match self.selected_session() {
+                Some(s) if self.is_local(s) => TopEvent::Attach(s.name.clone()),
+                Some(s) => {
+                    let status = format!("{} is on {}; attach with wsh attach --url", s.name, s.server);
+                    self.set_status(status);
+                    TopEvent::Update
+                }
+                None => TopEvent::Update,
+            },
+            b"K" if self.selected_session().is_some() => {
+                self.mode = Mode::ConfirmKill;
+                TopEvent::Update
+            }
+            b"t" if self.selected_session().is_some() => {
+                self.mode = Mode::Tags(String::new());
+                TopEvent::Update
+            }
+            b"q" | b"\x1b" | b"\x03" => TopEvent::Quit,
+            _ => TopEvent::Update,
+        }
+    }
+
+    fn handle_tags(&mut self, mut input: String, data: &[u8]) -> TopEvent {
+        // Other escape sequences aren't editing keys.
+        if data.len() > 1 && data[0] == 0x1b {
+            self.mode = Mode::Tags(input);
+            return TopEvent::Update;
+        }
+        for c in String::from_utf8_lossy(data).chars() {
+            match c {
+                '\r' | '\n' => {
+                    let Some(s) = self.selected_session() else {
+                        return TopEvent::Update;
+                    };
+                    let (add, remove) = parse_tags(&input);
+                    if add.is_empty() && remove.is_empty() {
+                        return TopEvent::Update;
+                    }
+                    return TopEvent::Tag { name: s.name.clone(), server: server_of(s), add, remove };
+                }
+                '\x1b' | '\x03' => return TopEvent::Update,
+                '\x7f' | '\x08' => {
+                    input.pop();
+                }
+                c if !c.is_control() => input.push(c),
+                _ => {}
+            }
+        }
+        self.mode = Mode::Tags(input);
+        TopEvent::Update
+    }
+
+    pub fn render(&self, frame: &mut Frame) {
+        let [main, footer] = Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.size());
+        let [list, preview] =
+            Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)]).areas(main);
+        self.render_list(frame, list);
+        self.render_preview(frame, preview);
+
+        let footer_text = match &self.mode {
+            Mode::ConfirmKill => match self.selected_session() {
+                Some(s) => format!("kill {}? (y/n)", s.name),
+                None => String::new(),
+            },
+            Mode::Tags(input) => format!("tags (+add -remove): {}", input),
+            Mode::Browse => self.status.clone().unwrap_or_else(|| HINT.to_string()),
+        };
+        frame.render_widget(Paragraph::new(footer_text), footer);
+    }
+
+    fn render_list(&self, frame: &mut Frame, area: Rect) {
+        // A server column only when federation brings in other servers.
+        let federated = self.sessions.iter().any(|s| !self.is_local(s));
+        let spark_width = area.width.saturating_sub(2).saturating_sub(60).clamp(8, HISTORY as u16);
+
+        let mut header = vec!["NAME"];
+        if federated {
+            header.push("SERVER");
+        }
+        header.extend(["CLIENTS", "IDLE", "ACTIVITY", "TAGS"]);
+
+        let rows = self.sessions.iter().map(|s| {
+            let mut cells = vec![s.name.clone()];
+            if federated {
+                cells.push(s.server.clone());
+            }
+            cells.push(s.clients.to_string());
+            cells.push(super::list::format_uptime(s.last_activity_ms / 1000));
+            cells.push(self.sparkline(s, spark_width as usize));
+            cells.push(s.tags.join(","));
+            Row::new(cells)
+        });
+
+        let mut widths = vec![Constraint::Min(12)];
+        if federated {
+            widths.push(Constraint::Length(14));
+        }
+        widths.extend([
+            Constraint::Length(7),
+            Constraint::Length(8),
+            Constraint::Length(spark_width),
+            Constraint::Min(6),
+        ]);
+
+        let title = format!(" wsh top: {} session{} ", self.sessions.len(), if self.sessions.len() == 1 { "" } else { "s" });
+        let table = Table::new(rows, widths)
+            .header(Row::new(header).style(Style::new().add_modifier(Modifier::BOLD)))
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        let mut state = TableState::default().with_selected((!self.sessions.is_empty()).then_some(self.selected));
+        frame.render_stateful_widget(table, area, &mut state);
+    }
+
+    fn render_preview(&self, frame: &mut Frame, area: Rect) {
+        let Some(s) = self.selected_session() else {
+            let block = Block::default().borders(Borders::ALL).title(" no sessions ");
+            frame.render_widget(block, area);
+            return;
+        };
+        let mut title = format!(" {} ", s.name);
+        if let Some(what) = s.title.as_deref().or(s.cwd.as_deref()) {
+            title = format!(" {}: {} ", s.name, what);
+        }
+        let block = Block::default().borders(Borders::ALL).title(title);
+        let lines: Vec<Line> = if self.is_local(s) {
+            // The most recent lines that fit.
+            let height = area.height.saturating_sub(2) as usize;
+            let first = self.preview.len().saturating_sub(height);
+            self.preview[first..].iter().map(|l| Line::raw(l.replace('\t', "    "))).collect()
+        } else {
+            vec![Line::raw(format!("(output of sessions on {} isn't previewed)", s.server))]
+        };
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    /// The last `width` activity samples of `s`: `█` where it was active
+    /// and `▁` where it was idle.
+    fn sparkline(&self, s: &SessionInfoMsg, width: usize) -> String {
+        let Some(history) = self.activity.get(&key(s)) else {
+            return String::new();
+        };
+        history
+            .iter()
+            .skip(history.len().saturating_sub(width))
+            .map(|&active| if active { '█' } else { '▁' })
+            .collect()
+    }
+}
+
+fn key(s: &SessionInfoMsg) -> (String, String) {
+    (s.server.clone(), s.name.clone())
+}
+
+fn server_of(s: &SessionInfoMsg) -> Option<String> {
+    (!s.server.is_empty()).then(|| s.server.clone())
+}
+
+/// Split `+build -ci test` into tags to add (`build`, `test`) and remove
+/// (`ci`).
+fn parse_tags(input: &str) -> (Vec<String>, Vec<String>) {
+    let mut add = Vec::new();
+    let mut remove = Vec::new();
+    for word in input.split_whitespace() {
+        if let Some(tag) = word.strip_prefix('-') {
+            remove.push(tag.to_string());
+        } else {
+            add.push(word.trim_start_matches('+').to_string());
+        }
+    }
+    add.retain(|t| !t.is_empty());
+    remove.retain(|t| !t.is_empty());
+    (add, remove)
+}
+
+/// Run the dashboard on the local terminal, which must already be in raw
+/// mode on the alternate screen. Returns the session to attach to, or
+/// `None` when the user quits.
+pub async fn run(socket_path: &Path) -> io::Result<Option<String>> {
+    let local = Client::connect(socket_path).await?.server_info().await?.hostname;
+    let mut dashboard = Dashboard::new(local);
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    terminal.clear()?;
+
+    let mut stdin = StdinReader::spawn()?;
+    let mut tick = tokio::time::interval(REFRESH);
+    let result = loop {
+        tokio::select! {
+            _ = tick.tick() => {
+                if let Err(e) = refresh(socket_path, &mut dashboard).await {
+                    break Err(e);
+                }
+            }
+            data = stdin.rx.recv() => {
+                let Some(data) = data else { break Ok(None) };
+                match dashboard.handle(&data) {
+                    TopEvent::Update => {}
+                    TopEvent::Quit => break Ok(None),
+                    TopEvent::Attach(name) => break Ok(Some(name)),
+                    TopEvent::Kill { name, server } => {
+                        let result = async { Client::connect(socket_path).await?.kill_session_on(&name, server).await }.await;
+                        dashboard.set_status(match result {
+                            Ok(()) => format!("killed {}", name),
+                            Err(e) => format!("kill {}: {}", name, e),
+                        });
+                    }
+                    TopEvent::Tag { name, server, add, remove } => {
+                        let result = async {
+                            Client::connect(socket_path).await?.manage_tags_on(&name, add, remove, server).await
+                        }
+                        .await;
+                        dashboard.set_status(match result {
+                            Ok(tags) => format!("{} tags: {}", name, tags.join(",")),
+                            Err(e) => format!("tag {}: {}", name, e),
+                        });
+                    }
+                }
+                // Show the effect of the key, and the new selection's
+                // output, without waiting for the next tick.
+                if let Err(e) = refresh(socket_path, &mut dashboard).await {
+                    break Err(e);
+                }
+            }
+        }
+        if let Err(e) = terminal.draw(|frame| dashboard.render(frame)) {
+            break Err(e);
+        }
+    };
+    stdin.stop().await;
+    result
+}
+
+/// Fetch the session list, and the selected session's output if it's
+/// local.
+async fn refresh(socket_path: &Path, dashboard: &mut Dashboard) -> io::Result<()> {
+    let sessions = Client::connect(socket_path).await?.list_sessions().await?;
+    dashboard.update(sessions, REFRESH);
+
+    let mut lines = Vec::new();
+    if let Some(s) = dashboard.selected_session().filter(|s| dashboard.is_local(s)) {
+        let msg = ReadOutputMsg { name: s.name.clone(), since: None, last: Some(PREVIEW_LINES), follow: false };
+        // A session can end between listing and reading; show it empty.
+        let mut c = Client::connect(socket_path).await?;
+        let _ = c.read_output(msg, |batch| lines.extend(batch.lines)).await;
+    }
+    dashboard.set_preview(lines);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+
+    fn session(server: &str, name: &str, idle_ms: u64) -> SessionInfoMsg {
+        SessionInfoMsg {
+            name: name.to_string(),
+            server: server.to_string(),
+            pid: Some(1),
+            command: "bash".to_string(),
+            rows: 24,
+            cols: 80,
+            clients: 2,
+            tags: vec!["agent".to_string()],
+            last_activity_ms: idle_ms,
+            last_activity_at: 0,
+            created_at: 0,
+            suspended: false,
+            hibernated: false,
+            title: Some("cargo test".to_string()),
+            cwd: None,
+            metadata: Default::default(),
+        }
+    }
+
+    fn dashboard() -> Dashboard {
+        let mut d = Dashboard::new("box".to_string());
+        d.update(
+            vec![session("box", "build", 100), session("box", "agent-1", 5000), session("far", "agent-2", 0)],
+            REFRESH,
+        );
+        d
+    }
+
+    fn screen(d: &Dashboard) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(120, 12)).unwrap();
+        terminal.draw(|frame| d.render(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        (0..buffer.area.height)
+            .map(|y| (0..buffer.area.width).map(|x| buffer.get(x, y).symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn update_sorts_keeps_the_selection_and_records_activity() {
+        let mut d = dashboard();
+        assert_eq!(d.selected_session().unwrap().name, "agent-1");
+        d.handle(b"j");
+        assert_eq!(d.selected_session().unwrap().name, "build");
+
+        d.update(vec![session("box", "build", 0), session("box", "aaa", 0)], REFRESH);
+        assert_eq!(d.selected_session().unwrap().name, "build");
+        assert_eq!(d.sparkline(&session("box", "build", 0), 10), "██");
+        assert_eq!(d.sparkline(&session("box", "aaa", 0), 10), "█");
+        assert!(!d.activity.contains_key(&("box".to_string(), "agent-1".to_string())));
+    }
+
+    #[test]
+    fn keys_attach_kill_and_tag_the_selected_session() {
+        let mut d = dashboard();
+        assert_eq!(d.handle(b"\r"), TopEvent::Attach("agent-1".to_string()));
+
+        assert_eq!(d.handle(b"K"), TopEvent::Update);
+        assert_eq!(d.handle(b"n"), TopEvent::Update);
+        assert_eq!(d.handle(b"y"), TopEvent::Update);
+        d.handle(b"K");
+        assert_eq!(d.handle(b"y"), TopEvent::Kill { name: "agent-1".to_string(), server: Some("box".to_string()) });
+
+        d.handle(b"t");
+        d.handle(b"+gpu -agent x");
+        d.handle(b"\x7f");
+        assert_eq!(
+            d.handle(b"fleet\r"),
+            TopEvent::Tag {
+                name: "agent-1".to_string(),
+                server: Some("box".to_string()),
+                add: vec!["gpu".to_string(), "fleet".to_string()],
+                remove: vec!["agent".to_string()],
+            }
+        );
+        assert_eq!(d.handle(b"q"), TopEvent::Quit);
+    }
+
+    #[test]
+    fn remote_sessions_are_not_attached_or_previewed() {
+        let mut d = dashboard();
+        d.handle(b"j");
+        d.handle(b"j");
+        assert_eq!(d.selected_session().unwrap().name, "agent-2");
+        assert_eq!(d.handle(b"\r"), TopEvent::Update);
+        let screen = screen(&d);
+        assert!(screen.contains("agent-2 is on far"), "{}", screen);
+        assert!(screen.contains("(output of sessions on far isn't previewed)"), "{}", screen);
+    }
+
+    #[test]
+    fn render_shows_sessions_and_the_preview() {
+        let mut d = dashboard();
+        d.set_preview(vec!["running 3 tests".to_string(), "test result: ok".to_string()]);
+        let screen = screen(&d);
+        assert!(screen.contains("wsh top: 3 sessions"), "{}", screen);
+        assert!(screen.contains("SERVER"), "{}", screen);
+        assert!(screen.contains("agent-1: cargo test"), "{}", screen);
+        assert!(screen.contains("test result: ok"), "{}", screen);
+        assert!(screen.contains(HINT), "{}", screen);
+    }
+}
@@ -0,0 +1,130 @@
+//! Shell completion scripts.
+//!
+//! `wsh completions <shell>` prints clap's script for the shell. For bash,
+//! zsh and fish it adds a hook that completes session names after
+//! subcommands like `attach` and `kill` by running the hidden
+//! `wsh __complete sessions`, which asks the running server over its Unix
+//! socket. Other shells get flags and subcommands only.
+
+use std::io::{self, Write};
+
+use clap::Command;
+use clap_complete::Shell;
+
+/// Subcommands whose first positional argument is a session name.
+pub fn session_commands(cmd: &Command) -> Vec<String> {
+    cmd.get_subcommands()
+        .filter(|sub| !sub.is_hide_set())
+        .filter(|sub| sub.get_positionals().next().is_some_and(|arg| arg.get_id() == "name"))
+        .map(|sub| sub.get_name().to_string())
+        .collect()
+}
+
+/// Write the completion script for `shell` to `out`.
+pub fn generate(shell: Shell, cmd: &mut Command, out: &mut dyn Write) -> io::Result<()> {
+    let bin = cmd.get_name().to_string();
+    clap_complete::generate(shell, cmd, &bin, out);
+    let commands = session_commands(cmd);
+    match shell {
+        Shell::Bash => write!(out, "{}", bash_hook(&bin, &commands)),
+        Shell::Zsh => write!(out, "{}", zsh_hook(&bin, &commands)),
+        Shell::Fish => write!(out, "{}", fish_hook(&bin, &commands)),
+        _ => Ok(()),
+    }
+}
+
+// Each hook completes a session name when the word before the cursor is
+// one of `commands`, so it still works after global flags such as `-L`.
+
+fn bash_hook(bin: &str, commands: &[String]) -> String {
+    format!(
+        r#"
+_{bin}_sessions() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    if [[ $COMP_CWORD -ge 2 && "$cur" != -* ]]; then
+        case "${{COMP_WORDS[COMP_CWORD-1]}}" in
+            {cases})
+                COMPREPLY=( $(compgen -W "$({bin} __complete sessions 2>/dev/null)" -- "$cur") )
+                return 0
+                ;;
+        esac
+    fi
+    _{bin} "$@"
+}}
+complete -F _{bin}_sessions -o nosort -o bashdefault -o default {bin}
+"#,
+        cases = commands.join("|"),
+    )
+}
+
+fn zsh_hook(bin: &str, commands: &[String]) -> String {
+    format!(
+        r#"
+_{bin}_sessions() {{
+    if (( CURRENT > 2 )) && [[ $PREFIX != -* && ${{words[CURRENT-1]}} == ({cases}) ]]; then
+        local -a sessions
+        sessions=(${{(f)"$({bin} __complete sessions 2>/dev/null)"}})
+        compadd -a sessions
+        return
+    fi
+    _{bin} "$@"
+}}
+compdef _{bin}_sessions {bin}
+"#,
+        cases = commands.join("|"),
+    )
+}
+
+fn fish_hook(bin: &str, commands: &[String]) -> String {
+    format!(
+        "\ncomplete -c {bin} -n 'contains -- (commandline -opc)[-1] {cases}' -f -a '({bin} __complete sessions 2>/dev/null)'\n",
+        cases = commands.join(" "),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Arg;
+
+    fn command() -> Command {
+        Command::new("wsh")
+            .subcommand(Command::new("attach").arg(Arg::new("name")))
+            .subcommand(Command::new("kill").arg(Arg::new("name")))
+            .subcommand(Command::new("list"))
+            .subcommand(Command::new("import-tmux").arg(Arg::new("sessions")))
+            .subcommand(Command::new("__complete").hide(true).arg(Arg::new("name")))
+    }
+
+    fn script(shell: Shell) -> String {
+        let mut out = Vec::new();
+        generate(shell, &mut command(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn session_commands_are_those_taking_a_name() {
+        assert_eq!(session_commands(&command()), vec!["attach", "kill"]);
+    }
+
+    #[test]
+    fn bash_zsh_and_fish_scripts_complete_session_names() {
+        let bash = script(Shell::Bash);
+        assert!(bash.contains("            attach|kill)\n"));
+        assert!(bash.contains("wsh __complete sessions"));
+        assert!(bash.ends_with("complete -F _wsh_sessions -o nosort -o bashdefault -o default wsh\n"));
+
+        let zsh = script(Shell::Zsh);
+        assert!(zsh.contains("== (attach|kill) ]]"));
+        assert!(zsh.ends_with("compdef _wsh_sessions wsh\n"));
+
+        let fish = script(Shell::Fish);
+        assert!(fish.contains("(commandline -opc)[-1] attach kill'"));
+        assert!(fish.contains("-a '(wsh __complete sessions 2>/dev/null)'"));
+    }
+
+    #[test]
+    fn other_shells_get_the_static_script_only() {
+        assert!(!script(Shell::Elvish).contains("__complete sessions"));
+    }
+}
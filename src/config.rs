@@ -12,6 +12,198 @@ pub struct FederationConfig {
     pub servers: Vec<BackendServerConfig>,
     /// IP access control for backend connections (SSRF mitigation).
     pub ip_access: Option<IpAccessConfig>,
+    /// Key handling policy advertised to `wsh attach` clients.
+    ///
+    /// ```toml
+    /// [client]
+    /// detach_keys = ["ctrl+]"]
+    /// allow_capture_toggle = false
+    /// command_key = "ctrl+a"
+    /// focus_key = "ctrl+o"
+    /// ```
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<crate::protocol::ClientPolicy>,
+    /// Sandbox profiles sessions can be created in. See
+    /// [`SandboxConfig`](crate::sandbox::SandboxConfig).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<crate::sandbox::SandboxConfig>,
+    /// Defaults applied to every session.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sessions: Option<SessionsConfig>,
+    /// MCP server behavior.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mcp: Option<McpConfig>,
+    /// How backend connections are health-checked and retried.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<HealthCheckConfig>,
+    /// Where input policy and redaction rules are loaded from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<PolicyConfig>,
+    /// Directories the file transfer endpoints and tools may use. See
+    /// [`FilesConfig`](crate::files::FilesConfig).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub files: Option<crate::files::FilesConfig>,
+    /// WebSocket client behavior.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub websocket: Option<WebSocketConfig>,
+    /// The status line sessions start with. See
+    /// [`StatusLineConfig`](crate::status_line::StatusLineConfig).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_line: Option<crate::status_line::StatusLineConfig>,
+    /// Commands run when sessions are created or end, and before clients
+    /// attach. See [`HooksConfig`](crate::hooks::HooksConfig).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<crate::hooks::HooksConfig>,
+}
+
+/// Input policy section. The rules themselves live in a separate file,
+/// reloaded when it changes; see [`crate::policy`].
+///
+/// ```toml
+/// [policy]
+/// file = "/etc/wsh/policy.toml"
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PolicyConfig {
+    /// Path to the rules file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<std::path::PathBuf>,
+}
+
+/// Backend health-check section. Durations use [`parse_duration`]; unset
+/// fields keep their defaults.
+///
+/// ```toml
+/// [health_check]
+/// interval = "10s"        # ping connected backends (default 30s)
+/// initial_backoff = "2s"  # first reconnect delay (default 1s)
+/// max_backoff = "5m"      # reconnect delay cap (default 60s)
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HealthCheckConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initial_backoff: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_backoff: Option<String>,
+}
+
+impl HealthCheckConfig {
+    /// Resolve against the defaults, failing on an unparseable or
+    /// inconsistent value.
+    pub fn resolve(&self) -> Result<crate::federation::registry::HealthCheck, String> {
+        let mut health_check = crate::federation::registry::HealthCheck::default();
+        for (field, spec, target) in [
+            ("interval", &self.interval, &mut health_check.interval),
+            ("initial_backoff", &self.initial_backoff, &mut health_check.initial_backoff),
+            ("max_backoff", &self.max_backoff, &mut health_check.max_backoff),
+        ] {
+            if let Some(spec) = spec {
+                *target = parse_duration(spec).map_err(|e| format!("{}: {}", field, e))?;
+            }
+        }
+        health_check.validate()?;
+        Ok(health_check)
+    }
+}
+
+/// MCP section.
+///
+/// ```toml
+/// [mcp]
+/// unhealthy_backend_wait = "15s"
+/// max_result_bytes = 50000
+/// cleanup_on_disconnect = "kill"
+///
+/// [mcp.tool_result_bytes]
+/// wsh_get_scrollback = 200000
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct McpConfig {
+    /// When a tool call targets a backend that is not currently healthy,
+    /// wait up to this long (see [`parse_duration`]) for it to recover
+    /// before failing. Unset or `"0"` fails at once. Capped at 5 minutes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unhealthy_backend_wait: Option<String>,
+    /// Largest screen or scrollback tool result, in bytes of JSON. Longer
+    /// results drop lines from the end and say where to continue. Unset
+    /// means [`DEFAULT_MCP_RESULT_BYTES`]; `0` means no limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_result_bytes: Option<usize>,
+    /// Per-tool overrides of `max_result_bytes`, by tool name.
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub tool_result_bytes: std::collections::BTreeMap<String, usize>,
+    /// What happens to the sessions an MCP client created when its MCP
+    /// session ends, unless `wsh_create_session` says otherwise. Unset
+    /// keeps them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cleanup_on_disconnect: Option<crate::session::DisconnectCleanup>,
+}
+
+/// Result size cap for MCP tools when `max_result_bytes` is unset: about
+/// 25k tokens, which MCP hosts commonly accept from a single tool.
+pub const DEFAULT_MCP_RESULT_BYTES: usize = 100_000;
+
+impl McpConfig {
+    /// The result size cap for `tool`, or `None` for no limit.
+    pub fn result_limit(&self, tool: &str) -> Option<usize> {
+        let limit = self
+            .tool_result_bytes
+            .get(tool)
+            .copied()
+            .or(self.max_result_bytes)
+            .unwrap_or(DEFAULT_MCP_RESULT_BYTES);
+        Some(limit).filter(|&limit| limit > 0)
+    }
+}
+
+/// Session defaults section.
+///
+/// ```toml
+/// [sessions]
+/// idle_kill_after = "2h"
+/// scrollback_lines = 50000
+/// warm_pool = 4
+/// tombstone_retention = "10m"
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionsConfig {
+    /// Kill sessions that have had no client attached and no terminal
+    /// input or output for this long (see [`parse_duration`]). Unset or
+    /// `"0"` keeps idle sessions forever. Sessions can override it when
+    /// created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_kill_after: Option<String>,
+    /// Lines of scrollback each session keeps (default 10000). Sessions can
+    /// set their own when created, and change it later.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scrollback_lines: Option<usize>,
+    /// Number of idle default shells to keep pre-spawned (default 0, off).
+    /// A create request for a plain shell claims one instead of waiting
+    /// for a new shell to start.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warm_pool: Option<usize>,
+    /// How long to keep the tombstone (exit status, final screen) of a
+    /// session whose process exited (default `"5m"`). `"0"` keeps none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tombstone_retention: Option<String>,
+}
+
+/// WebSocket section.
+///
+/// ```toml
+/// [websocket]
+/// lag_policy = "disconnect"
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WebSocketConfig {
+    /// What a `/ws/raw` client that falls too far behind on output gets:
+    /// `"resync"` (default) skips ahead and redraws the screen,
+    /// `"disconnect"` closes the connection. Clients can choose for
+    /// themselves with `?lag=`. See [`crate::api::backpressure`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lag_policy: Option<crate::api::backpressure::LagPolicy>,
 }
 
 /// IP access control configuration for SSRF mitigation.
@@ -40,6 +232,11 @@ pub struct IpAccessConfig {
 pub struct ServerIdentityConfig {
     /// Override system hostname.
     pub hostname: Option<String>,
+    /// Message of the day written into every new session's scrollback and
+    /// shown to attaching clients. `{hostname}` expands to the server
+    /// hostname.
+    #[serde(default)]
+    pub motd: Option<String>,
 }
 
 /// A single backend server entry.
@@ -77,13 +274,122 @@ impl FederationConfig {
                 .map_err(|e| ConfigError::WriteFailed(path.to_path_buf(), e))?;
         }
         let contents =
-            toml::to_string_pretty(self).map_err(|e| ConfigError::SerializeFailed(e))?;
+            toml::to_string_pretty(self).map_err(ConfigError::SerializeFailed)?;
         std::fs::write(path, contents)
             .map_err(|e| ConfigError::WriteFailed(path.to_path_buf(), e))?;
         Ok(())
     }
 }
 
+/// Add a backend to the `[[servers]]` of the config file at `path`,
+/// creating the file if needed. Returns whether the file changed; an
+/// address that is already listed is left alone.
+///
+/// See [`edit_servers`] for how concurrent edits are handled.
+pub fn persist_server_added(
+    path: &std::path::Path,
+    address: &str,
+    token: Option<&str>,
+) -> Result<bool, ConfigError> {
+    edit_servers(path, |servers| {
+        if servers.iter().any(|s| s.get("address").and_then(|a| a.as_str()) == Some(address)) {
+            return false;
+        }
+        let mut server = toml_edit::Table::new();
+        server["address"] = toml_edit::value(address);
+        if let Some(token) = token {
+            server["token"] = toml_edit::value(token);
+        }
+        servers.push(server);
+        true
+    })
+}
+
+/// Remove a backend from the `[[servers]]` of the config file at `path`.
+/// Returns whether the file changed.
+pub fn persist_server_removed(path: &std::path::Path, address: &str) -> Result<bool, ConfigError> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    edit_servers(path, |servers| {
+        let before = servers.len();
+        servers.retain(|s| s.get("address").and_then(|a| a.as_str()) != Some(address));
+        servers.len() != before
+    })
+}
+
+/// Apply `edit` to the `[[servers]]` array of the config file at `path`
+/// and write it back if `edit` reports a change.
+///
+/// Only that array is touched: the rest of the file, comments and
+/// formatting included, is kept as it is on disk. The file is read and
+/// written while holding an exclusive lock on `<path>.lock`, so two servers
+/// sharing a config file don't lose each other's changes, and it is
+/// replaced atomically, keeping its permissions (new files are `0600`
+/// since they may hold tokens).
+fn edit_servers(
+    path: &std::path::Path,
+    edit: impl FnOnce(&mut toml_edit::ArrayOfTables) -> bool,
+) -> Result<bool, ConfigError> {
+    let write_failed = |e| ConfigError::WriteFailed(path.to_path_buf(), e);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(write_failed)?;
+    }
+    let _lock = lock_file(&sibling(path, "lock")).map_err(write_failed)?;
+
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(ConfigError::ReadFailed(path.to_path_buf(), e)),
+    };
+    let mut doc: toml_edit::DocumentMut = contents
+        .parse()
+        .map_err(|e: toml_edit::TomlError| ConfigError::EditFailed(path.to_path_buf(), e.to_string()))?;
+    let servers = doc
+        .entry("servers")
+        .or_insert_with(|| toml_edit::Item::ArrayOfTables(toml_edit::ArrayOfTables::new()))
+        .as_array_of_tables_mut()
+        .ok_or_else(|| {
+            ConfigError::EditFailed(path.to_path_buf(), "`servers` is not a [[servers]] array of tables".to_string())
+        })?;
+    if !edit(servers) {
+        return Ok(false);
+    }
+
+    let tmp = sibling(path, "tmp");
+    std::fs::write(&tmp, doc.to_string()).map_err(write_failed)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(path).map(|m| m.permissions().mode()).unwrap_or(0o600);
+        std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(mode)).map_err(write_failed)?;
+    }
+    std::fs::rename(&tmp, path).map_err(write_failed)?;
+    Ok(true)
+}
+
+/// `path` with `.suffix` appended to its file name.
+fn sibling(path: &std::path::Path, suffix: &str) -> std::path::PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Open `path` and take an exclusive `flock` on it, released when the
+/// returned file is dropped.
+fn lock_file(path: &std::path::Path) -> std::io::Result<std::fs::File> {
+    let file = std::fs::OpenOptions::new().create(true).truncate(false).write(true).open(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::AsRawFd;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(file)
+}
+
 /// Errors that can occur when loading or saving config.
 #[derive(Debug)]
 pub enum ConfigError {
@@ -91,6 +397,8 @@ pub enum ConfigError {
     ParseFailed(std::path::PathBuf, toml::de::Error),
     WriteFailed(std::path::PathBuf, std::io::Error),
     SerializeFailed(toml::ser::Error),
+    /// The file on disk couldn't be edited in place.
+    EditFailed(std::path::PathBuf, String),
 }
 
 impl std::fmt::Display for ConfigError {
@@ -106,6 +414,9 @@ impl std::fmt::Display for ConfigError {
                 write!(f, "Failed to write config {}: {}", path.display(), e)
             }
             Self::SerializeFailed(e) => write!(f, "Failed to serialize config: {}", e),
+            Self::EditFailed(path, e) => {
+                write!(f, "Failed to edit config {}: {}", path.display(), e)
+            }
         }
     }
 }
@@ -148,6 +459,28 @@ pub fn is_world_readable(mode: u32) -> bool {
     mode & 0o004 != 0
 }
 
+/// Parse a duration such as `"90s"`, `"30m"`, `"2h"`, or `"1d"`. A bare
+/// number is seconds.
+pub fn parse_duration(s: &str) -> Result<std::time::Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (digits, unit) = s.split_at(split);
+    let value: u64 = digits
+        .parse()
+        .map_err(|_| format!("invalid duration {:?}: expected a number with s, m, h, or d", s))?;
+    let scale = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        other => return Err(format!("invalid duration unit {:?} in {:?}: expected s, m, h, or d", other, s)),
+    };
+    value
+        .checked_mul(scale)
+        .map(std::time::Duration::from_secs)
+        .ok_or_else(|| format!("duration {:?} is too large", s))
+}
+
 /// Resolve the server's hostname. Uses config override if present,
 /// otherwise falls back to system hostname.
 pub fn resolve_hostname(server_config: Option<&ServerIdentityConfig>) -> String {
@@ -208,6 +541,153 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_server_motd() {
+        let toml = r#"
+            [server]
+            motd = """
+Authorized use only.
+Sessions on {hostname} are recorded."""
+        "#;
+        let config: FederationConfig = toml::from_str(toml).unwrap();
+        let server = config.server.unwrap();
+        assert!(server.hostname.is_none());
+        assert_eq!(
+            server.motd.as_deref(),
+            Some("Authorized use only.\nSessions on {hostname} are recorded.")
+        );
+    }
+
+    #[test]
+    fn parse_client_policy() {
+        let toml = r#"
+            [client]
+            detach_keys = ["ctrl+]"]
+            allow_capture_toggle = false
+            command_key = "ctrl+a"
+        "#;
+        let config: FederationConfig = toml::from_str(toml).unwrap();
+        let policy = config.client.unwrap();
+        assert_eq!(policy.detach_keys, vec!["ctrl+]"]);
+        assert!(!policy.allow_capture_toggle);
+        assert_eq!(policy.command_key.as_deref(), Some("ctrl+a"));
+
+        let config: FederationConfig = toml::from_str("[client]\nallow_capture_toggle = false\n").unwrap();
+        let policy = config.client.unwrap();
+        assert_eq!(policy.detach_keys, vec!["ctrl+\\"]);
+        assert_eq!(policy.command_key, None);
+    }
+
+    #[test]
+    fn parse_sandbox_section() {
+        let toml = r#"
+            [sandbox]
+            default = "jail"
+
+            [sandbox.profiles.jail]
+            backend = "unshare"
+            root = "/srv/agent-root"
+        "#;
+        let config: FederationConfig = toml::from_str(toml).unwrap();
+        let sandbox = config.sandbox.unwrap();
+        assert_eq!(sandbox.default.as_deref(), Some("jail"));
+        assert_eq!(
+            sandbox.profiles["jail"],
+            crate::sandbox::SandboxProfile::Unshare {
+                root: Some("/srv/agent-root".into()),
+                network: false,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_sessions_section() {
+        let config: FederationConfig =
+            toml::from_str("[sessions]\nidle_kill_after = \"2h\"\nscrollback_lines = 500\ntombstone_retention = \"10m\"\n").unwrap();
+        let sessions = config.sessions.unwrap();
+        assert_eq!(sessions.idle_kill_after.as_deref(), Some("2h"));
+        assert_eq!(sessions.scrollback_lines, Some(500));
+        assert_eq!(sessions.tombstone_retention.as_deref(), Some("10m"));
+    }
+
+    #[test]
+    fn parse_mcp_section() {
+        let config: FederationConfig =
+            toml::from_str("[mcp]\nunhealthy_backend_wait = \"15s\"\n").unwrap();
+        let mcp = config.mcp.unwrap();
+        assert_eq!(mcp.unhealthy_backend_wait.as_deref(), Some("15s"));
+        assert_eq!(mcp.result_limit("wsh_get_screen"), Some(DEFAULT_MCP_RESULT_BYTES));
+    }
+
+    #[test]
+    fn parse_websocket_section() {
+        use crate::api::backpressure::LagPolicy;
+        let config: FederationConfig =
+            toml::from_str("[websocket]\nlag_policy = \"disconnect\"\n").unwrap();
+        assert_eq!(config.websocket.unwrap().lag_policy, Some(LagPolicy::Disconnect));
+        assert!(toml::from_str::<FederationConfig>("[websocket]\nlag_policy = \"drop\"\n").is_err());
+    }
+
+    #[test]
+    fn parse_files_section() {
+        let config: FederationConfig = toml::from_str("[files]\nallow = [\"/tmp\"]\n").unwrap();
+        let files = config.files.unwrap();
+        assert_eq!(files.allow, vec![std::path::PathBuf::from("/tmp")]);
+        assert!(files.enabled());
+    }
+
+    #[test]
+    fn mcp_result_limits() {
+        let toml = r#"
+            [mcp]
+            max_result_bytes = 50000
+            cleanup_on_disconnect = "detach"
+
+            [mcp.tool_result_bytes]
+            wsh_get_scrollback = 200000
+            wsh_run_command = 0
+        "#;
+        let mcp = toml::from_str::<FederationConfig>(toml).unwrap().mcp.unwrap();
+        assert_eq!(mcp.result_limit("wsh_get_screen"), Some(50_000));
+        assert_eq!(mcp.result_limit("wsh_get_scrollback"), Some(200_000));
+        assert_eq!(mcp.result_limit("wsh_run_command"), None);
+        assert_eq!(mcp.cleanup_on_disconnect, Some(crate::session::DisconnectCleanup::Detach));
+    }
+
+    #[test]
+    fn parse_health_check_section() {
+        use std::time::Duration;
+        let config: FederationConfig =
+            toml::from_str("[health_check]\ninterval = \"10s\"\nmax_backoff = \"5m\"\n").unwrap();
+        let health_check = config.health_check.unwrap().resolve().unwrap();
+        assert_eq!(health_check.interval, Duration::from_secs(10));
+        assert_eq!(health_check.initial_backoff, Duration::from_secs(1));
+        assert_eq!(health_check.max_backoff, Duration::from_secs(300));
+
+        let bad = HealthCheckConfig { interval: Some("soon".into()), ..Default::default() };
+        assert!(bad.resolve().unwrap_err().starts_with("interval:"));
+        let inverted = HealthCheckConfig {
+            initial_backoff: Some("2m".into()),
+            max_backoff: Some("1m".into()),
+            ..Default::default()
+        };
+        assert!(inverted.resolve().is_err());
+    }
+
+    #[test]
+    fn parse_duration_units() {
+        use std::time::Duration;
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("45s").unwrap(), Duration::from_secs(45));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(1800));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert_eq!(parse_duration("1d").unwrap(), Duration::from_secs(86400));
+        assert_eq!(parse_duration("0").unwrap(), Duration::ZERO);
+        for bad in ["", "h", "2w", "-1h", "1.5h", "99999999999999999999d"] {
+            assert!(parse_duration(bad).is_err(), "{bad}");
+        }
+    }
+
     #[test]
     fn parse_empty_config() {
         let toml = "";
@@ -220,6 +700,7 @@ mod tests {
         let config = FederationConfig {
             server: Some(ServerIdentityConfig {
                 hostname: Some("my-custom-host".into()),
+                motd: None,
             }),
             ..Default::default()
         };
@@ -302,6 +783,7 @@ mod tests {
         let config = FederationConfig {
             server: Some(ServerIdentityConfig {
                 hostname: Some("my-host".into()),
+                motd: Some("Sessions on {hostname} are recorded.".into()),
             }),
             default_token: Some("tok".into()),
             servers: vec![
@@ -315,6 +797,16 @@ mod tests {
                 },
             ],
             ip_access: None,
+            client: None,
+            sandbox: None,
+            sessions: None,
+            mcp: None,
+            health_check: None,
+            policy: None,
+            files: None,
+            websocket: None,
+            status_line: None,
+            hooks: None,
         };
         let serialized = toml::to_string_pretty(&config).unwrap();
         let reparsed: FederationConfig = toml::from_str(&serialized).unwrap();
@@ -345,4 +837,57 @@ mod tests {
         let config: FederationConfig = toml::from_str(toml).unwrap();
         assert!(config.ip_access.is_none());
     }
+
+    #[test]
+    fn persisted_servers_keep_the_rest_of_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let original = "# my cluster\n[server]\nhostname = \"hub\"  # keep me\n\n[[servers]]\naddress = \"http://10.0.1.10:8080\"\n";
+        std::fs::write(&path, original).unwrap();
+
+        assert!(persist_server_added(&path, "http://10.0.1.11:8080", Some("tok")).unwrap());
+        assert!(!persist_server_added(&path, "http://10.0.1.11:8080", None).unwrap());
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.starts_with(original), "{contents}");
+        let config = FederationConfig::load(&path).unwrap().unwrap();
+        assert_eq!(config.servers.len(), 2);
+        assert_eq!(config.servers[1].token.as_deref(), Some("tok"));
+
+        assert!(persist_server_removed(&path, "http://10.0.1.10:8080").unwrap());
+        assert!(!persist_server_removed(&path, "http://10.0.1.10:8080").unwrap());
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("hostname = \"hub\"  # keep me"), "{contents}");
+        let config = FederationConfig::load(&path).unwrap().unwrap();
+        assert_eq!(config.servers.len(), 1);
+        assert_eq!(config.servers[0].address, "http://10.0.1.11:8080");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn persisted_servers_create_a_private_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wsh").join("config.toml");
+        assert!(!persist_server_removed(&path, "http://10.0.1.10:8080").unwrap());
+        assert!(!path.exists());
+
+        assert!(persist_server_added(&path, "http://10.0.1.10:8080", None).unwrap());
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let config = FederationConfig::load(&path).unwrap().unwrap();
+        assert_eq!(config.servers.len(), 1);
+    }
+
+    #[test]
+    fn persisted_servers_refuse_an_unexpected_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "servers = \"none\"\n").unwrap();
+        assert!(matches!(
+            persist_server_added(&path, "http://10.0.1.10:8080", None),
+            Err(ConfigError::EditFailed(..))
+        ));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "servers = \"none\"\n");
+    }
 }
@@ -11,8 +11,12 @@ type WsStream = tokio_tungstenite::WebSocketStream<MaybeTlsStream<tokio::net::Tc
 /// Spawns a tokio task that:
 /// - Connects to `ws://{address}/ws/json` (with optional Bearer token)
 /// - On success: queries `GET /server/info` for hostname, updates registry health to Healthy
-/// - Runs a select! loop: ping timer (30s), incoming messages, shutdown signal
-/// - On disconnect: marks health Unavailable, retries with exponential backoff (1s..60s)
+/// - Runs a select! loop: ping timer, incoming messages, shutdown signal
+/// - On disconnect: marks health Unavailable, retries with exponential backoff
+///
+/// Ping interval and backoff come from the registry's
+/// [`HealthCheck`](crate::federation::registry::HealthCheck) at spawn time.
+/// Latency, failures and retries are recorded in the registry's stats.
 pub struct BackendConnection {
     shutdown_tx: tokio::sync::watch::Sender<bool>,
     task: tokio::task::JoinHandle<()>,
@@ -55,8 +59,8 @@ async fn connection_loop(
     mut shutdown_rx: tokio::sync::watch::Receiver<bool>,
     local_server_id: String,
 ) {
-    let mut backoff = Duration::from_secs(1);
-    let max_backoff = Duration::from_secs(60);
+    let health_check = registry.health_check();
+    let mut backoff = health_check.initial_backoff;
 
     loop {
         if *shutdown_rx.borrow() {
@@ -107,7 +111,7 @@ async fn connection_loop(
 
         match connect_result {
             Ok((ws_stream, _)) => {
-                backoff = Duration::from_secs(1);
+                backoff = health_check.initial_backoff;
 
                 // Query server info (hostname + server_id) via HTTP endpoint.
                 let server_info = fetch_server_info(&address, token.as_deref()).await;
@@ -141,6 +145,7 @@ async fn connection_loop(
                 }
 
                 // Not a self-loop — mark healthy.
+                registry.record_connected(&address);
                 registry.set_health(&address, BackendHealth::Healthy);
                 tracing::info!(backend = %address, "backend connected");
 
@@ -155,60 +160,85 @@ async fn connection_loop(
                 }
 
                 // Run until disconnect or shutdown.
-                run_connection(ws_stream, &mut shutdown_rx).await;
+                let reason = run_connection(
+                    ws_stream,
+                    &mut shutdown_rx,
+                    &registry,
+                    &address,
+                    health_check.interval,
+                )
+                .await;
 
                 if *shutdown_rx.borrow() {
                     return;
                 }
+                registry.record_failure(&address, reason, true);
                 registry.set_health(&address, BackendHealth::Unavailable);
                 tracing::warn!(backend = %address, "backend disconnected");
             }
             Err(e) => {
                 tracing::debug!(backend = %address, error = %e, "connection failed");
+                registry.record_failure(&address, e.to_string(), false);
                 registry.set_health(&address, BackendHealth::Unavailable);
             }
         }
 
         // Wait before retry with exponential backoff.
+        registry.record_retry(&address, backoff);
         tokio::select! {
             _ = tokio::time::sleep(backoff) => {}
             _ = shutdown_rx.changed() => { return; }
         }
-        backoff = (backoff * 2).min(max_backoff);
+        backoff = (backoff * 2).min(health_check.max_backoff);
     }
 }
 
+/// Run a connected backend's socket until it drops or shutdown is
+/// signalled. Returns why the connection ended.
 async fn run_connection(
     ws_stream: WsStream,
     shutdown_rx: &mut tokio::sync::watch::Receiver<bool>,
-) {
+    registry: &BackendRegistry,
+    address: &str,
+    interval: Duration,
+) -> String {
     let (mut sink, mut stream) = ws_stream.split();
-    let mut ping_interval = tokio::time::interval(Duration::from_secs(30));
+    let mut ping_interval = tokio::time::interval(interval);
     ping_interval.tick().await; // Skip the first immediate tick.
+    // When the outstanding ping was sent, if any.
+    let mut ping_sent: Option<tokio::time::Instant> = None;
 
     loop {
         tokio::select! {
             msg = stream.next() => {
                 match msg {
-                    Some(Ok(Message::Pong(_))) => {}
+                    Some(Ok(Message::Pong(_))) => {
+                        if let Some(sent) = ping_sent.take() {
+                            registry.record_latency(address, sent.elapsed());
+                        }
+                    }
                     Some(Ok(Message::Ping(data))) => {
-                        if sink.send(Message::Pong(data)).await.is_err() {
-                            break;
+                        if let Err(e) = sink.send(Message::Pong(data)).await {
+                            return format!("send failed: {}", e);
                         }
                     }
-                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Ok(Message::Close(_))) | None => return "closed by backend".into(),
                     Some(Ok(_)) => {} // Ignore other messages for now.
-                    Some(Err(_)) => break,
+                    Some(Err(e)) => return format!("read failed: {}", e),
                 }
             }
             _ = ping_interval.tick() => {
-                if sink.send(Message::Ping(vec![].into())).await.is_err() {
-                    break;
+                if ping_sent.is_some() {
+                    return format!("no pong within {}s", interval.as_secs_f64());
                 }
+                if let Err(e) = sink.send(Message::Ping(vec![].into())).await {
+                    return format!("send failed: {}", e);
+                }
+                ping_sent = Some(tokio::time::Instant::now());
             }
             _ = shutdown_rx.changed() => {
                 let _ = sink.send(Message::Close(None)).await;
-                break;
+                return "shut down".into();
             }
         }
     }
@@ -253,7 +283,7 @@ async fn fetch_server_info(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::federation::registry::{BackendEntry, BackendHealth, BackendRegistry, BackendRole};
+    use crate::federation::registry::{BackendEntry, BackendHealth, BackendRegistry, BackendRole, HealthCheck};
     use tokio::time::timeout;
 
     /// Spawn a minimal WebSocket server that accepts connections and stays open.
@@ -560,4 +590,84 @@ mod tests {
         conn.shutdown();
         conn.join().await;
     }
+
+    #[tokio::test]
+    async fn records_ping_latency() {
+        let addr = spawn_mock_backend("remote-uuid", "pinged").await;
+        let address = format!("http://{}", addr);
+
+        let registry = BackendRegistry::new();
+        registry.set_health_check(HealthCheck {
+            interval: Duration::from_millis(50),
+            ..Default::default()
+        });
+        registry
+            .add_unchecked(BackendEntry {
+                address: address.clone(),
+                token: None,
+                hostname: None,
+                health: BackendHealth::Connecting,
+                role: BackendRole::Member,
+                server_id: None,
+            })
+            .unwrap();
+
+        let conn = BackendConnection::spawn(address.clone(), None, registry.clone(), "local".into());
+
+        timeout(Duration::from_secs(5), async {
+            while registry.stats(&address).and_then(|s| s.latency).is_none() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("should record a ping round trip");
+        let stats = registry.stats(&address).unwrap();
+        assert_eq!(stats.consecutive_failures, 0);
+        assert!(stats.connected_at.is_some());
+
+        conn.shutdown();
+        conn.join().await;
+    }
+
+    #[tokio::test]
+    async fn failed_attempts_back_off_as_configured() {
+        // Bind then drop, so the port refuses connections.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let registry = BackendRegistry::new();
+        registry.set_health_check(HealthCheck {
+            interval: Duration::from_secs(30),
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(40),
+        });
+        registry
+            .add_unchecked(BackendEntry {
+                address: address.clone(),
+                token: None,
+                hostname: None,
+                health: BackendHealth::Connecting,
+                role: BackendRole::Member,
+                server_id: None,
+            })
+            .unwrap();
+
+        let conn = BackendConnection::spawn(address.clone(), None, registry.clone(), "local".into());
+
+        timeout(Duration::from_secs(5), async {
+            while registry.stats(&address).unwrap().consecutive_failures < 5 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("should keep retrying quickly");
+        let stats = registry.stats(&address).unwrap();
+        assert!(stats.last_error.is_some());
+        assert_eq!(stats.disconnects, 0);
+        assert!(stats.backoff.unwrap() <= Duration::from_millis(40));
+
+        conn.shutdown();
+        conn.join().await;
+    }
 }
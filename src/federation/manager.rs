@@ -1,9 +1,10 @@
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 use crate::config::FederationConfig;
 use crate::federation::auth::resolve_backend_token;
 use crate::federation::connection::BackendConnection;
-use crate::federation::registry::{BackendEntry, BackendHealth, BackendRegistry, BackendRole};
+use crate::federation::registry::{BackendEntry, BackendHealth, BackendRegistry, BackendRole, HealthCheck};
 use uuid::Uuid;
 
 /// Owns the backend registry and all active WebSocket connections.
@@ -20,6 +21,15 @@ pub struct FederationManager {
     local_token: Option<String>,
     /// This server's UUID, passed to connections for self-loop detection.
     server_id: String,
+    /// Config file that runtime adds and removes are written back to, so
+    /// they survive a restart. `None` keeps them in memory only.
+    config_path: Option<PathBuf>,
+}
+
+impl Default for FederationManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl FederationManager {
@@ -34,17 +44,21 @@ impl FederationManager {
             default_token: None,
             local_token: None,
             server_id: Uuid::new_v4().to_string(),
+            config_path: None,
         }
     }
 
     /// Create from config, spawning connections for each configured backend.
+    /// `health_check` applies to these and to backends added later.
     pub fn from_config(
         config: FederationConfig,
         local_token: Option<String>,
         default_token: Option<String>,
         server_id: String,
+        health_check: HealthCheck,
     ) -> Self {
         let registry = BackendRegistry::new();
+        registry.set_health_check(health_check);
         let mut connections = HashMap::new();
 
         for backend_config in &config.servers {
@@ -80,6 +94,26 @@ impl FederationManager {
             default_token: default_token.or_else(|| config.default_token.clone()),
             local_token,
             server_id,
+            config_path: None,
+        }
+    }
+
+    /// Write runtime adds and removes back to the `[[servers]]` of the
+    /// config file at `path`, or stop with `None`.
+    pub fn set_config_path(&mut self, path: Option<PathBuf>) {
+        self.config_path = path;
+    }
+
+    /// Record a change in the config file, if there is one. A failure is
+    /// logged but doesn't undo the change in memory.
+    fn persist(&self, write: impl FnOnce(&Path) -> Result<bool, crate::config::ConfigError>) {
+        let Some(ref path) = self.config_path else {
+            return;
+        };
+        match write(path) {
+            Ok(true) => tracing::info!(path = %path.display(), "federation config updated"),
+            Ok(false) => {}
+            Err(e) => tracing::warn!(error = %e, "failed to write federation change to config"),
         }
     }
 
@@ -104,9 +138,40 @@ impl FederationManager {
             self.local_token.as_deref(),
         );
 
+        self.connect(address, resolved_token)?;
+        self.persist(|path| crate::config::persist_server_added(path, address, token));
+
+        Ok(())
+    }
+
+    /// Add a backend that joined through the relay endpoint and is reached
+    /// at `address` on this server. It is not written to the config file,
+    /// since it only exists while the backend stays joined.
+    pub fn add_relay(
+        &mut self,
+        address: &str,
+        token: Option<String>,
+    ) -> Result<(), crate::federation::registry::RegistryError> {
+        self.connect(address, token)
+    }
+
+    /// Remove a relayed backend whose relay connection ended.
+    pub fn remove_relay(&mut self, address: &str) -> bool {
+        if let Some(conn) = self.connections.remove(address) {
+            conn.shutdown();
+        }
+        self.registry.remove_by_address(address)
+    }
+
+    /// Register a backend and spawn its connection.
+    fn connect(
+        &mut self,
+        address: &str,
+        token: Option<String>,
+    ) -> Result<(), crate::federation::registry::RegistryError> {
         let entry = BackendEntry {
             address: address.to_string(),
-            token: resolved_token.clone(),
+            token: token.clone(),
             hostname: None,
             health: BackendHealth::Connecting,
             role: BackendRole::Member,
@@ -117,12 +182,11 @@ impl FederationManager {
 
         let conn = BackendConnection::spawn(
             address.to_string(),
-            resolved_token,
+            token,
             self.registry.clone(),
             self.server_id.clone(),
         );
         self.connections.insert(address.to_string(), conn);
-
         Ok(())
     }
 
@@ -131,18 +195,27 @@ impl FederationManager {
         if let Some(conn) = self.connections.remove(address) {
             conn.shutdown();
         }
-        self.registry.remove_by_address(address)
+        let removed = self.registry.remove_by_address(address);
+        if removed {
+            self.persist(|path| crate::config::persist_server_removed(path, address));
+        }
+        removed
     }
 
     /// Remove a backend by hostname. Shuts down its connection.
     pub fn remove_backend_by_hostname(&mut self, hostname: &str) -> bool {
         // Find the address for this hostname first.
-        if let Some(entry) = self.registry.get_by_hostname(hostname) {
-            if let Some(conn) = self.connections.remove(&entry.address) {
-                conn.shutdown();
-            }
+        let Some(entry) = self.registry.get_by_hostname(hostname) else {
+            return false;
+        };
+        if let Some(conn) = self.connections.remove(&entry.address) {
+            conn.shutdown();
+        }
+        let removed = self.registry.remove_by_hostname(hostname);
+        if removed {
+            self.persist(|path| crate::config::persist_server_removed(path, &entry.address));
         }
-        self.registry.remove_by_hostname(hostname)
+        removed
     }
 
     /// Shut down all backend connections.
@@ -163,7 +236,7 @@ mod tests {
     #[tokio::test]
     async fn manager_from_empty_config() {
         let config = FederationConfig::default();
-        let manager = FederationManager::from_config(config, None, None, "test-id".into());
+        let manager = FederationManager::from_config(config, None, None, "test-id".into(), HealthCheck::default());
         assert!(manager.registry().list().is_empty());
     }
 
@@ -183,8 +256,18 @@ mod tests {
                 },
             ],
             ip_access: None,
+            client: None,
+            sandbox: None,
+            sessions: None,
+            mcp: None,
+            health_check: None,
+            policy: None,
+            files: None,
+            websocket: None,
+            status_line: None,
+            hooks: None,
         };
-        let mut manager = FederationManager::from_config(config, None, None, "test-id".into());
+        let mut manager = FederationManager::from_config(config, None, None, "test-id".into(), HealthCheck::default());
         let backends = manager.registry().list();
         assert_eq!(backends.len(), 2);
         // All start in Connecting state
@@ -219,4 +302,24 @@ mod tests {
         assert!(result.is_ok());
         manager.shutdown_all().await;
     }
+
+    #[tokio::test]
+    async fn manager_writes_changes_to_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let mut manager = FederationManager::new();
+        manager.add_backend("http://10.0.99.1:9999", None).unwrap();
+        assert!(!path.exists());
+
+        manager.set_config_path(Some(path.clone()));
+        manager.add_backend("http://10.0.99.2:9999", Some("tok")).unwrap();
+        let config = FederationConfig::load(&path).unwrap().unwrap();
+        assert_eq!(config.servers.len(), 1);
+        assert_eq!(config.servers[0].address, "http://10.0.99.2:9999");
+
+        assert!(manager.remove_backend_by_address("http://10.0.99.2:9999"));
+        let config = FederationConfig::load(&path).unwrap().unwrap();
+        assert!(config.servers.is_empty());
+        manager.shutdown_all().await;
+    }
 }
@@ -1,6 +1,19 @@
+//! Federation: routing requests to sessions on other wsh servers.
+//!
+//! The registry, sanitization and request proxying are always built, so
+//! handlers can resolve a `server` parameter either way. Connecting to
+//! backends, managing them at runtime and relaying for backends behind NAT
+//! need the `federation` feature; without it the registry stays empty.
+
+#[cfg(feature = "federation")]
 pub mod auth;
+#[cfg(feature = "federation")]
 pub mod connection;
+#[cfg(feature = "federation")]
 pub mod ip_access;
+#[cfg(feature = "federation")]
 pub mod manager;
 pub mod registry;
+#[cfg(feature = "federation")]
+pub mod relay;
 pub mod sanitize;
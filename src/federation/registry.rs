@@ -1,6 +1,9 @@
 use parking_lot::RwLock;
 use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "snake_case")]
@@ -13,6 +16,18 @@ pub enum BackendHealth {
     Rejected,
 }
 
+impl BackendHealth {
+    /// The name used in serialized output and error messages.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Connecting => "connecting",
+            Self::Healthy => "healthy",
+            Self::Unavailable => "unavailable",
+            Self::Rejected => "rejected",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum BackendRole {
@@ -49,10 +64,10 @@ impl BackendEntry {
     /// Converts `http://` → `ws://` and `https://` → `wss://`.
     pub fn ws_url_for(&self, path: &str) -> String {
         let base = self.address.trim_end_matches('/');
-        let ws_base = if base.starts_with("https://") {
-            format!("wss://{}", &base["https://".len()..])
-        } else if base.starts_with("http://") {
-            format!("ws://{}", &base["http://".len()..])
+        let ws_base = if let Some(rest) = base.strip_prefix("https://") {
+            format!("wss://{}", rest)
+        } else if let Some(rest) = base.strip_prefix("http://") {
+            format!("ws://{}", rest)
         } else {
             // Shouldn't happen after validation, but be defensive
             format!("ws://{}", base)
@@ -61,6 +76,72 @@ impl BackendEntry {
     }
 }
 
+/// How backend connections are health-checked and retried.
+///
+/// ```toml
+/// [health_check]
+/// interval = "10s"
+/// initial_backoff = "2s"
+/// max_backoff = "5m"
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HealthCheck {
+    /// How often a connected backend is pinged. A ping still unanswered
+    /// when the next one is due drops the connection.
+    pub interval: Duration,
+    /// Delay before the first reconnect attempt.
+    pub initial_backoff: Duration,
+    /// The delay doubles after each failed attempt, up to this.
+    pub max_backoff: Duration,
+}
+
+impl Default for HealthCheck {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+impl HealthCheck {
+    /// Check that the durations are usable together.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval.is_zero() {
+            return Err("interval must be greater than zero".into());
+        }
+        if self.initial_backoff.is_zero() {
+            return Err("initial_backoff must be greater than zero".into());
+        }
+        if self.max_backoff < self.initial_backoff {
+            return Err("max_backoff must not be less than initial_backoff".into());
+        }
+        Ok(())
+    }
+}
+
+/// Connection history for one backend, kept alongside its entry.
+#[derive(Debug, Clone, Default)]
+pub struct BackendStats {
+    /// Round trip of the last answered ping.
+    pub latency: Option<Duration>,
+    /// Failed connection attempts and dropped connections since the
+    /// backend was last connected.
+    pub consecutive_failures: u32,
+    /// Connections that dropped after the backend was healthy.
+    pub disconnects: u64,
+    /// Why the last attempt failed or the last connection dropped.
+    pub last_error: Option<String>,
+    pub last_error_at: Option<Instant>,
+    /// When the current connection was established.
+    pub connected_at: Option<Instant>,
+    /// Delay before the pending reconnect attempt, or the last one made.
+    pub backoff: Option<Duration>,
+    /// When the pending reconnect attempt is due.
+    pub next_retry_at: Option<Instant>,
+}
+
 #[derive(Debug)]
 pub enum RegistryError {
     DuplicateAddress(String),
@@ -188,8 +269,8 @@ pub fn validate_backend_address(address: &str) -> Result<(), String> {
         if rest.is_empty() {
             // No port specified — use scheme default
             (host, None)
-        } else if rest.starts_with(':') {
-            (host, Some(&rest[1..]))
+        } else if let Some(port) = rest.strip_prefix(':') {
+            (host, Some(port))
         } else {
             return Err("invalid characters after IPv6 address".into());
         }
@@ -230,6 +311,17 @@ pub fn validate_backend_address(address: &str) -> Result<(), String> {
 #[derive(Clone)]
 pub struct BackendRegistry {
     inner: Arc<RwLock<Vec<BackendEntry>>>,
+    /// Woken whenever a backend's health changes.
+    health_changed: Arc<tokio::sync::Notify>,
+    /// How long MCP tool calls wait for an unhealthy backend to recover
+    /// before failing. Zero fails at once.
+    unhealthy_wait: Arc<RwLock<Duration>>,
+    /// Whether requests may be proxied to backends. Switched off at runtime
+    /// through `PATCH /server/features`.
+    proxying: Arc<AtomicBool>,
+    /// Connection history by backend address.
+    stats: Arc<RwLock<HashMap<String, BackendStats>>>,
+    health_check: Arc<RwLock<HealthCheck>>,
 }
 
 impl BackendRegistry {
@@ -237,9 +329,43 @@ impl BackendRegistry {
     pub fn new() -> Self {
         Self {
             inner: Arc::new(RwLock::new(Vec::new())),
+            health_changed: Arc::new(tokio::sync::Notify::new()),
+            unhealthy_wait: Arc::new(RwLock::new(Duration::ZERO)),
+            proxying: Arc::new(AtomicBool::new(true)),
+            stats: Arc::new(RwLock::new(HashMap::new())),
+            health_check: Arc::new(RwLock::new(HealthCheck::default())),
         }
     }
 
+    /// Set the health-check settings used for newly spawned connections.
+    pub fn set_health_check(&self, health_check: HealthCheck) {
+        *self.health_check.write() = health_check;
+    }
+
+    pub fn health_check(&self) -> HealthCheck {
+        *self.health_check.read()
+    }
+
+    /// Set how long [`wait_healthy`](Self::wait_healthy) callers should
+    /// wait for an unhealthy backend.
+    pub fn set_unhealthy_wait(&self, wait: Duration) {
+        *self.unhealthy_wait.write() = wait;
+    }
+
+    pub fn unhealthy_wait(&self) -> Duration {
+        *self.unhealthy_wait.read()
+    }
+
+    /// Allow or refuse proxying requests to backends. Backends stay
+    /// registered and health-checked either way.
+    pub fn set_proxying(&self, enabled: bool) {
+        self.proxying.store(enabled, Ordering::Release);
+    }
+
+    pub fn proxying(&self) -> bool {
+        self.proxying.load(Ordering::Acquire)
+    }
+
     /// Add a backend entry. Validates the address (and hostname if present),
     /// then rejects duplicate addresses and hostname collisions.
     pub fn add(&self, entry: BackendEntry) -> Result<(), RegistryError> {
@@ -269,6 +395,7 @@ impl BackendRegistry {
             }
         }
 
+        self.stats.write().insert(entry.address.clone(), BackendStats::default());
         backends.push(entry);
         Ok(())
     }
@@ -278,6 +405,7 @@ impl BackendRegistry {
         let mut backends = self.inner.write();
         let len_before = backends.len();
         backends.retain(|b| b.address != addr);
+        self.stats.write().remove(addr);
         backends.len() < len_before
     }
 
@@ -286,6 +414,7 @@ impl BackendRegistry {
         let mut backends = self.inner.write();
         let len_before = backends.len();
         backends.retain(|b| b.hostname.as_deref() != Some(hostname));
+        self.stats.write().retain(|addr, _| backends.iter().any(|b| &b.address == addr));
         backends.len() < len_before
     }
 
@@ -351,6 +480,28 @@ impl BackendRegistry {
         if let Some(entry) = backends.iter_mut().find(|b| b.address == address) {
             entry.health = health;
         }
+        drop(backends);
+        self.health_changed.notify_waiters();
+    }
+
+    /// Look up a backend by hostname, waiting up to `timeout` for it to
+    /// become healthy. Returns the entry as last seen, which may still be
+    /// unhealthy, or `None` if no backend has that hostname. A rejected
+    /// backend never recovers, so it is returned without waiting.
+    pub async fn wait_healthy(&self, hostname: &str, timeout: Duration) -> Option<BackendEntry> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            // Register for the wakeup before checking, so a change between
+            // the check and the wait isn't missed.
+            let changed = self.health_changed.notified();
+            let backend = self.get_by_hostname(hostname)?;
+            if matches!(backend.health, BackendHealth::Healthy | BackendHealth::Rejected) {
+                return Some(backend);
+            }
+            if tokio::time::timeout_at(deadline, changed).await.is_err() {
+                return Some(backend);
+            }
+        }
     }
 
     /// Set the server_id for a backend identified by address.
@@ -361,6 +512,55 @@ impl BackendRegistry {
         }
     }
 
+    /// Connection history for a backend, by address.
+    pub fn stats(&self, address: &str) -> Option<BackendStats> {
+        self.stats.read().get(address).cloned()
+    }
+
+    /// Update a backend's stats, if it is still registered.
+    fn update_stats(&self, address: &str, f: impl FnOnce(&mut BackendStats)) {
+        if let Some(stats) = self.stats.write().get_mut(address) {
+            f(stats);
+        }
+    }
+
+    /// Record a successful connection.
+    pub fn record_connected(&self, address: &str) {
+        self.update_stats(address, |stats| {
+            stats.consecutive_failures = 0;
+            stats.connected_at = Some(Instant::now());
+            stats.next_retry_at = None;
+        });
+    }
+
+    /// Record a failed connection attempt, or a dropped connection if
+    /// `disconnected` is set.
+    pub fn record_failure(&self, address: &str, error: String, disconnected: bool) {
+        self.update_stats(address, |stats| {
+            stats.consecutive_failures = stats.consecutive_failures.saturating_add(1);
+            if disconnected {
+                stats.disconnects += 1;
+            }
+            stats.last_error = Some(error);
+            stats.last_error_at = Some(Instant::now());
+            stats.connected_at = None;
+            stats.latency = None;
+        });
+    }
+
+    /// Record the round trip of an answered ping.
+    pub fn record_latency(&self, address: &str, latency: Duration) {
+        self.update_stats(address, |stats| stats.latency = Some(latency));
+    }
+
+    /// Record that a reconnect attempt is due after `backoff`.
+    pub fn record_retry(&self, address: &str, backoff: Duration) {
+        self.update_stats(address, |stats| {
+            stats.backoff = Some(backoff);
+            stats.next_retry_at = Some(Instant::now() + backoff);
+        });
+    }
+
     /// Add a backend entry WITHOUT validating the address or hostname.
     ///
     /// This is intended for internal use (e.g., tests that need to register
@@ -382,6 +582,7 @@ impl BackendRegistry {
             }
         }
 
+        self.stats.write().insert(entry.address.clone(), BackendStats::default());
         backends.push(entry);
         Ok(())
     }
@@ -534,6 +735,103 @@ mod tests {
         assert_eq!(list[0].health, BackendHealth::Unavailable);
     }
 
+    #[test]
+    fn stats_track_failures_and_reset_on_connect() {
+        let reg = BackendRegistry::new();
+        let addr = "http://10.0.1.10:8080";
+        reg.add(BackendEntry {
+            address: addr.into(),
+            token: None,
+            hostname: Some("prod-1".into()),
+            health: BackendHealth::Connecting,
+            role: BackendRole::Member,
+            server_id: None,
+        })
+        .unwrap();
+        assert_eq!(reg.stats(addr).unwrap().consecutive_failures, 0);
+
+        reg.record_failure(addr, "connection refused".into(), false);
+        reg.record_retry(addr, Duration::from_secs(1));
+        reg.record_failure(addr, "connection refused".into(), false);
+        reg.record_retry(addr, Duration::from_secs(2));
+        let stats = reg.stats(addr).unwrap();
+        assert_eq!(stats.consecutive_failures, 2);
+        assert_eq!(stats.disconnects, 0);
+        assert_eq!(stats.last_error.as_deref(), Some("connection refused"));
+        assert_eq!(stats.backoff, Some(Duration::from_secs(2)));
+        assert!(stats.next_retry_at.is_some());
+
+        reg.record_connected(addr);
+        reg.record_latency(addr, Duration::from_millis(3));
+        let stats = reg.stats(addr).unwrap();
+        assert_eq!(stats.consecutive_failures, 0);
+        assert_eq!(stats.latency, Some(Duration::from_millis(3)));
+        assert!(stats.connected_at.is_some());
+        assert!(stats.next_retry_at.is_none());
+        // The last error stays visible after recovery.
+        assert_eq!(stats.last_error.as_deref(), Some("connection refused"));
+
+        reg.record_failure(addr, "closed by backend".into(), true);
+        let stats = reg.stats(addr).unwrap();
+        assert_eq!((stats.consecutive_failures, stats.disconnects), (1, 1));
+        assert!(stats.latency.is_none() && stats.connected_at.is_none());
+
+        assert!(reg.remove_by_hostname("prod-1"));
+        assert!(reg.stats(addr).is_none());
+        // Late updates from a shut-down connection are dropped.
+        reg.record_failure(addr, "gone".into(), false);
+        assert!(reg.stats(addr).is_none());
+    }
+
+    #[test]
+    fn health_check_validation() {
+        assert!(HealthCheck::default().validate().is_ok());
+        let zero = HealthCheck { interval: Duration::ZERO, ..Default::default() };
+        assert!(zero.validate().is_err());
+        let inverted = HealthCheck {
+            initial_backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(5),
+            ..Default::default()
+        };
+        assert!(inverted.validate().is_err());
+    }
+
+    fn unavailable_backend(reg: &BackendRegistry) {
+        reg.add(BackendEntry {
+            address: "http://10.0.1.10:8080".into(),
+            token: None,
+            hostname: Some("prod-1".into()),
+            health: BackendHealth::Unavailable,
+            role: BackendRole::Member,
+            server_id: None,
+        })
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn wait_healthy_returns_when_backend_recovers() {
+        let reg = BackendRegistry::new();
+        unavailable_backend(&reg);
+        let flipper = reg.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            flipper.set_health("http://10.0.1.10:8080", BackendHealth::Healthy);
+        });
+        let entry = reg.wait_healthy("prod-1", Duration::from_secs(5)).await.unwrap();
+        assert_eq!(entry.health, BackendHealth::Healthy);
+    }
+
+    #[tokio::test]
+    async fn wait_healthy_gives_up_after_timeout() {
+        let reg = BackendRegistry::new();
+        unavailable_backend(&reg);
+        let started = std::time::Instant::now();
+        let entry = reg.wait_healthy("prod-1", Duration::from_millis(100)).await.unwrap();
+        assert_eq!(entry.health, BackendHealth::Unavailable);
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        assert!(reg.wait_healthy("nope", Duration::from_secs(5)).await.is_none());
+    }
+
     #[test]
     fn get_by_hostname() {
         let reg = BackendRegistry::new();
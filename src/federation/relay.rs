@@ -0,0 +1,471 @@
+//! Relay mode: federation with backends the hub can't reach.
+//!
+//! A backend behind NAT runs `wsh server --join wss://hub/federation/relay`
+//! and dials out to the hub instead of waiting to be dialed. The hub
+//! registers it like any other backend, at a loopback address of its own,
+//! and carries every connection made to that address over the backend's
+//! WebSocket, where the backend serves it with its own router. Proxying,
+//! health checks and the backend's authentication work unchanged.
+//!
+//! Connections are multiplexed as binary frames: a 4-byte big-endian stream
+//! id, a 1-byte kind (open, data or close), and for data the payload. The
+//! hub opens streams; a close frame ends one direction of a stream, like a
+//! TCP half-close. Before any frames the backend sends a [`Hello`] as a text
+//! message.
+
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_util::sync::CancellationToken;
+
+use crate::federation::manager::FederationManager;
+
+/// The hub's relay endpoint.
+pub const RELAY_PATH: &str = "/federation/relay";
+
+const OPEN: u8 = 0;
+const DATA: u8 = 1;
+const CLOSE: u8 = 2;
+
+/// Most bytes read from a stream into one frame.
+const CHUNK: usize = 32 * 1024;
+/// Frames queued for the socket, and per stream, before senders wait.
+const QUEUE: usize = 64;
+/// Most streams open at once over one relay.
+const MAX_STREAMS: usize = 256;
+/// How long the hub waits for a joining backend's [`Hello`].
+const HELLO_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often a joined backend pings the hub to keep NAT mappings alive.
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// What a joining backend tells the hub before any streams are opened.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Hello {
+    /// Token the hub should present to the backend, if it requires one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// The backend's `--base-prefix`, if it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_prefix: Option<String>,
+}
+
+fn frame(id: u32, kind: u8, payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(5 + payload.len());
+    out.extend_from_slice(&id.to_be_bytes());
+    out.push(kind);
+    out.extend_from_slice(payload);
+    out
+}
+
+fn parse_frame(frame: &[u8]) -> Option<(u32, u8, &[u8])> {
+    let (header, payload) = frame.split_at_checked(5)?;
+    let id = u32::from_be_bytes(header[..4].try_into().ok()?);
+    Some((id, header[4], payload))
+}
+
+/// The relay URL for a `--join` argument: `ws(s)://` or `http(s)://`, with
+/// the relay path added when only a host is given.
+pub fn join_url(arg: &str) -> Result<String, String> {
+    let rest = if let Some(rest) = arg.strip_prefix("https://") {
+        format!("wss://{}", rest)
+    } else if let Some(rest) = arg.strip_prefix("http://") {
+        format!("ws://{}", rest)
+    } else if arg.starts_with("wss://") || arg.starts_with("ws://") {
+        arg.to_string()
+    } else {
+        return Err(format!(
+            "--join must be a ws://, wss://, http:// or https:// URL, got '{}'",
+            arg
+        ));
+    };
+    let (scheme, after) = rest.split_once("://").unwrap_or(("ws", &rest));
+    match after.split_once('/') {
+        Some((host, path)) if !host.is_empty() && !path.trim_matches('/').is_empty() => Ok(rest),
+        Some((host, _)) if !host.is_empty() => Ok(format!("{}://{}{}", scheme, host, RELAY_PATH)),
+        None if !after.is_empty() => Ok(format!("{}://{}{}", scheme, after, RELAY_PATH)),
+        _ => Err(format!("--join URL has no host: '{}'", arg)),
+    }
+}
+
+/// Streams multiplexed over one WebSocket. Outgoing frames are queued on
+/// `out`, which the owner drains into the socket; incoming ones are handed
+/// to [`dispatch`](Self::dispatch).
+#[derive(Clone)]
+struct Mux {
+    out: mpsc::Sender<Vec<u8>>,
+    streams: Arc<Mutex<HashMap<u32, mpsc::Sender<Bytes>>>>,
+    next_id: Arc<AtomicU32>,
+}
+
+impl Mux {
+    fn new() -> (Self, mpsc::Receiver<Vec<u8>>) {
+        let (out, out_rx) = mpsc::channel(QUEUE);
+        let mux = Self {
+            out,
+            streams: Arc::new(Mutex::new(HashMap::new())),
+            next_id: Arc::new(AtomicU32::new(1)),
+        };
+        (mux, out_rx)
+    }
+
+    /// Open a new stream to the peer and carry `io` over it.
+    async fn open<S>(&self, io: S)
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        if self.out.send(frame(id, OPEN, &[])).await.is_ok() {
+            self.attach(id, io);
+        }
+    }
+
+    /// Carry `io` as stream `id`: what is read from it goes to the peer, and
+    /// what the peer sends on `id` is written to it.
+    fn attach<S>(&self, id: u32, io: S)
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (tx, mut rx) = mpsc::channel::<Bytes>(QUEUE);
+        self.streams.lock().insert(id, tx);
+        let (mut reader, mut writer) = tokio::io::split(io);
+
+        let out = self.out.clone();
+        tokio::spawn(async move {
+            let mut buf = vec![0u8; CHUNK];
+            loop {
+                match reader.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        if out.send(frame(id, DATA, &buf[..n])).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+            let _ = out.send(frame(id, CLOSE, &[])).await;
+        });
+
+        tokio::spawn(async move {
+            while let Some(data) = rx.recv().await {
+                if writer.write_all(&data).await.is_err() {
+                    return;
+                }
+            }
+            let _ = writer.shutdown().await;
+        });
+    }
+
+    /// Route an incoming frame to its stream. Returns the id of a stream the
+    /// peer asked to open, for the caller to [`attach`](Self::attach).
+    async fn dispatch(&self, data: &[u8]) -> Option<u32> {
+        let (id, kind, payload) = parse_frame(data)?;
+        match kind {
+            OPEN => {
+                if self.streams.lock().len() < MAX_STREAMS {
+                    return Some(id);
+                }
+                tracing::warn!(id, "relay stream limit reached, refusing stream");
+                let _ = self.out.send(frame(id, CLOSE, &[])).await;
+            }
+            DATA => {
+                let tx = self.streams.lock().get(&id).cloned();
+                if let Some(tx) = tx {
+                    if tx.send(Bytes::copy_from_slice(payload)).await.is_err() {
+                        self.streams.lock().remove(&id);
+                    }
+                }
+            }
+            CLOSE => {
+                self.streams.lock().remove(&id);
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+// ── Hub side ───────────────────────────────────────────────────────
+
+/// Serve a backend that joined over `socket`: register it at a fresh
+/// loopback address and carry connections made there over the socket.
+/// The backend is removed again when the socket closes.
+pub async fn serve_joined(
+    socket: axum::extract::ws::WebSocket,
+    federation: Arc<tokio::sync::Mutex<FederationManager>>,
+) {
+    use axum::extract::ws::Message as AxumMessage;
+
+    let (mut sink, mut stream) = socket.split();
+    let hello = match tokio::time::timeout(HELLO_TIMEOUT, stream.next()).await {
+        Ok(Some(Ok(AxumMessage::Text(text)))) => match serde_json::from_str::<Hello>(&text) {
+            Ok(hello) => hello,
+            Err(e) => {
+                tracing::warn!(error = %e, "relay: invalid hello from joining backend");
+                return;
+            }
+        },
+        _ => {
+            tracing::warn!("relay: joining backend sent no hello");
+            return;
+        }
+    };
+
+    let listener = match tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::warn!(error = %e, "relay: failed to bind loopback listener");
+            return;
+        }
+    };
+    let Ok(local) = listener.local_addr() else {
+        return;
+    };
+    let address = format!("http://{}{}", local, hello.base_prefix.as_deref().unwrap_or(""));
+    if let Err(e) = federation.lock().await.add_relay(&address, hello.token) {
+        tracing::warn!(error = %e, "relay: failed to register joining backend");
+        return;
+    }
+    tracing::info!(backend = %address, "relay: backend joined");
+
+    let (mux, mut out_rx) = Mux::new();
+    let writer = tokio::spawn(async move {
+        while let Some(frame) = out_rx.recv().await {
+            if sink.send(AxumMessage::Binary(frame.into())).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                if let Ok((conn, _)) = accepted {
+                    mux.open(conn).await;
+                }
+            }
+            msg = stream.next() => match msg {
+                Some(Ok(AxumMessage::Binary(data))) => {
+                    // The hub opens every stream, so a backend asking to
+                    // open one is ignored.
+                    let _ = mux.dispatch(&data).await;
+                }
+                Some(Ok(AxumMessage::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+
+    writer.abort();
+    federation.lock().await.remove_relay(&address);
+    tracing::info!(backend = %address, "relay: backend left");
+}
+
+// ── Backend side ───────────────────────────────────────────────────
+
+/// Keep this server joined to the hub at `url`, reconnecting with backoff,
+/// until `cancel` fires. `hub_token` authenticates to the hub; streams the
+/// hub opens are served by `app`.
+pub async fn join(
+    url: String,
+    hub_token: Option<String>,
+    hello: Hello,
+    app: axum::Router,
+    cancel: CancellationToken,
+) {
+    let hello = match serde_json::to_string(&hello) {
+        Ok(hello) => hello,
+        Err(_) => return,
+    };
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        match join_once(&url, hub_token.as_deref(), &hello, &app, &cancel).await {
+            Ok(()) => return,
+            Err(JoinError::Connect(e)) => {
+                tracing::warn!(hub = %url, error = %e, retry_in = ?backoff, "relay: failed to join hub");
+            }
+            Err(JoinError::Dropped(e)) => {
+                backoff = INITIAL_BACKOFF;
+                tracing::warn!(hub = %url, error = %e, retry_in = ?backoff, "relay: connection to hub dropped");
+            }
+        }
+        tokio::select! {
+            _ = cancel.cancelled() => return,
+            _ = tokio::time::sleep(backoff) => {}
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+enum JoinError {
+    /// The hub couldn't be reached or refused the connection.
+    Connect(String),
+    /// An established connection ended.
+    Dropped(String),
+}
+
+/// One connection to the hub. Returns `Ok` only when cancelled.
+async fn join_once(
+    url: &str,
+    hub_token: Option<&str>,
+    hello: &str,
+    app: &axum::Router,
+    cancel: &CancellationToken,
+) -> Result<(), JoinError> {
+    let mut request = url
+        .into_client_request()
+        .map_err(|e| JoinError::Connect(e.to_string()))?;
+    if let Some(token) = hub_token {
+        let value = format!("Bearer {}", token)
+            .parse()
+            .map_err(|_| JoinError::Connect("invalid hub token".into()))?;
+        request.headers_mut().insert("Authorization", value);
+    }
+    let (ws, _) = tokio_tungstenite::connect_async(request)
+        .await
+        .map_err(|e| JoinError::Connect(e.to_string()))?;
+    let (mut sink, mut stream) = ws.split();
+    sink.send(Message::Text(hello.into()))
+        .await
+        .map_err(|e| JoinError::Connect(e.to_string()))?;
+    tracing::info!(hub = %url, "relay: joined hub");
+
+    let (mux, mut out_rx) = Mux::new();
+    let (ping_tx, mut ping_rx) = mpsc::channel::<()>(1);
+    let writer = tokio::spawn(async move {
+        loop {
+            let msg = tokio::select! {
+                frame = out_rx.recv() => match frame {
+                    Some(frame) => Message::Binary(frame.into()),
+                    None => break,
+                },
+                Some(()) = ping_rx.recv() => Message::Ping(Bytes::new()),
+            };
+            if sink.send(msg).await.is_err() {
+                break;
+            }
+        }
+        let _ = sink.send(Message::Close(None)).await;
+    });
+
+    let mut ping = tokio::time::interval(PING_INTERVAL);
+    ping.tick().await;
+    let result = loop {
+        tokio::select! {
+            _ = cancel.cancelled() => break Ok(()),
+            _ = ping.tick() => {
+                let _ = ping_tx.try_send(());
+            }
+            msg = stream.next() => match msg {
+                Some(Ok(Message::Binary(data))) => {
+                    if let Some(id) = mux.dispatch(&data).await {
+                        let (hub_end, server_end) = tokio::io::duplex(2 * CHUNK);
+                        mux.attach(id, hub_end);
+                        tokio::spawn(serve_stream(server_end, app.clone()));
+                    }
+                }
+                Some(Ok(Message::Close(_))) | None => break Err(JoinError::Dropped("closed by hub".into())),
+                Some(Err(e)) => break Err(JoinError::Dropped(e.to_string())),
+                Some(Ok(_)) => {}
+            },
+        }
+    };
+
+    drop(mux);
+    drop(ping_tx);
+    if result.is_ok() {
+        let _ = tokio::time::timeout(Duration::from_secs(1), writer).await;
+    } else {
+        writer.abort();
+    }
+    result
+}
+
+/// Serve one relayed connection with the server's router, as the HTTP
+/// listener would.
+async fn serve_stream(io: tokio::io::DuplexStream, app: axum::Router) {
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+
+    let service = hyper_util::service::TowerToHyperService::new(app);
+    let builder = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new());
+    if let Err(e) = builder.serve_connection_with_upgrades(TokioIo::new(io), service).await {
+        tracing::debug!(?e, "relay: connection error");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_round_trip() {
+        let encoded = frame(7, DATA, b"hello");
+        assert_eq!(parse_frame(&encoded), Some((7, DATA, &b"hello"[..])));
+        assert_eq!(parse_frame(&frame(1, CLOSE, &[])), Some((1, CLOSE, &b""[..])));
+        assert_eq!(parse_frame(b"\0\0\0"), None);
+    }
+
+    #[test]
+    fn join_url_adds_the_relay_path() {
+        assert_eq!(join_url("wss://hub.example.com").unwrap(), "wss://hub.example.com/federation/relay");
+        assert_eq!(join_url("https://hub.example.com/").unwrap(), "wss://hub.example.com/federation/relay");
+        assert_eq!(join_url("http://10.0.0.1:8080").unwrap(), "ws://10.0.0.1:8080/federation/relay");
+        assert_eq!(
+            join_url("wss://hub.example.com/wsh/federation/relay").unwrap(),
+            "wss://hub.example.com/wsh/federation/relay"
+        );
+        assert!(join_url("hub.example.com").is_err());
+        assert!(join_url("wss://").is_err());
+    }
+
+    /// Two muxes wired back to back, as the hub and backend would be.
+    #[tokio::test]
+    async fn streams_carry_bytes_both_ways() {
+        let (hub, mut hub_out) = Mux::new();
+        let (backend, mut backend_out) = Mux::new();
+
+        let (mut client, hub_end) = tokio::io::duplex(1024);
+        hub.open(hub_end).await;
+        let open = hub_out.recv().await.unwrap();
+        let id = backend.dispatch(&open).await.unwrap();
+        let (backend_end, mut server) = tokio::io::duplex(1024);
+        backend.attach(id, backend_end);
+
+        // Forward frames in both directions.
+        let to_backend = backend.clone();
+        tokio::spawn(async move {
+            while let Some(frame) = hub_out.recv().await {
+                to_backend.dispatch(&frame).await;
+            }
+        });
+        let to_hub = hub.clone();
+        tokio::spawn(async move {
+            while let Some(frame) = backend_out.recv().await {
+                to_hub.dispatch(&frame).await;
+            }
+        });
+
+        client.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        server.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+
+        server.write_all(b"pong").await.unwrap();
+        server.shutdown().await.unwrap();
+        let mut reply = Vec::new();
+        client.read_to_end(&mut reply).await.unwrap();
+        assert_eq!(reply, b"pong");
+    }
+}
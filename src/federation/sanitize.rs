@@ -22,6 +22,11 @@ const ALLOWED_SESSION_FIELDS: &[&str] = &[
     "tags",
     "server",
     "last_activity_ms",
+    "suspended",
+    "hibernated",
+    "scrollback_lines",
+    "query_policy",
+    "encoding",
 ];
 
 /// Validate a session name received from a remote backend.
@@ -206,11 +211,16 @@ mod tests {
             "clients": 2,
             "tags": ["web"],
             "server": "host-1",
-            "last_activity_ms": 500
+            "last_activity_ms": 500,
+            "suspended": false,
+            "hibernated": false,
+            "scrollback_lines": 10000,
+            "query_policy": {"da1": "passthrough", "dsr": "passthrough", "xtgettcap": "passthrough"},
+            "encoding": "utf-8"
         }]);
         let result = sanitize_session_list(&input).unwrap();
         let obj = result.as_array().unwrap()[0].as_object().unwrap();
-        assert_eq!(obj.len(), 9);
+        assert_eq!(obj.len(), 14);
         for field in ALLOWED_SESSION_FIELDS {
             assert!(obj.contains_key(*field), "missing allowed field: {}", field);
         }
@@ -0,0 +1,285 @@
+//! Reading and writing files on the machine a session runs on.
+//!
+//! `GET`/`PUT /sessions/{name}/file` and the `wsh_read_file` and
+//! `wsh_write_file` MCP tools move file contents without going through the
+//! PTY, so an agent can drop a script in place and run it instead of typing
+//! it out as a heredoc. File transfer is off unless the config names the
+//! directories it may touch:
+//!
+//! ```toml
+//! [files]
+//! allow = ["/home/agent/work", "/tmp"]
+//! ```
+//!
+//! Relative paths resolve against the working directory of the session's
+//! shell. Symlinks are followed before the allowlist is checked, so a link
+//! inside an allowed directory can't reach outside it. Paths are as the
+//! server sees them: a sandboxed session's chroot or private mounts don't
+//! apply. Files are limited to [`MAX_FILE_BYTES`], and only regular files
+//! are read or written.
+//!
+//! Transfers run as the server's user. Sessions started as another user
+//! (`user`/`uid` on create) are refused, since their clients would otherwise
+//! reach files the session's own account can't.
+
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// Largest file that can be read or written, matching the request body
+/// limit of the HTTP API.
+pub const MAX_FILE_BYTES: usize = 1024 * 1024;
+
+/// The `[files]` config section.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilesConfig {
+    /// Directories files may be read from and written to, including their
+    /// subdirectories. Empty turns file transfer off.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<PathBuf>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FileError {
+    #[error("file transfer is not enabled on this server")]
+    Disabled,
+    #[error("{0} is outside the directories files may be transferred in")]
+    NotAllowed(PathBuf),
+    #[error("no such file or directory: {0}")]
+    NotFound(PathBuf),
+    #[error("file is {size} bytes, more than the limit of {limit}")]
+    TooLarge { size: u64, limit: usize },
+    #[error("session runs as user '{0}'; file transfer is only available for sessions running as the server's user")]
+    OtherUser(String),
+    #[error("{0}")]
+    Invalid(String),
+    #[error("{0}")]
+    Io(String),
+}
+
+impl FilesConfig {
+    pub fn enabled(&self) -> bool {
+        !self.allow.is_empty()
+    }
+
+    /// [`resolve`](Self::resolve) a path for a client of `session`,
+    /// refusing sessions that run as another user.
+    pub fn resolve_for(&self, session: &crate::session::Session, path: &str, for_write: bool) -> Result<PathBuf, FileError> {
+        if let Some(ref account) = session.run_as {
+            return Err(FileError::OtherUser(account.user.clone()));
+        }
+        self.resolve(session.pid(), path, for_write)
+    }
+
+    /// Resolve `path` as given by a client of the session whose shell is
+    /// `pid`: relative paths are taken from the shell's working directory,
+    /// symlinks are followed, and the result must lie in an allowed
+    /// directory. A path being written may name a file that doesn't exist
+    /// yet, but its directory must.
+    pub fn resolve(&self, pid: Option<u32>, path: &str, for_write: bool) -> Result<PathBuf, FileError> {
+        if !self.enabled() {
+            return Err(FileError::Disabled);
+        }
+        if path.is_empty() {
+            return Err(FileError::Invalid("path is empty".into()));
+        }
+        let path = Path::new(path);
+        let full = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            let cwd = pid
+                .and_then(|pid| std::fs::read_link(format!("/proc/{pid}/cwd")).ok())
+                .ok_or_else(|| {
+                    FileError::Invalid("the session's working directory is unknown; use an absolute path".into())
+                })?;
+            cwd.join(path)
+        };
+
+        let resolved = match std::fs::canonicalize(&full) {
+            Ok(resolved) => resolved,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && for_write => {
+                let name = match full.components().next_back() {
+                    Some(Component::Normal(name)) => name.to_os_string(),
+                    _ => return Err(FileError::Invalid(format!("{} does not name a file", full.display()))),
+                };
+                let parent = full.parent().unwrap_or(Path::new("/"));
+                canonicalize(parent)?.join(name)
+            }
+            Err(e) => return Err(io_error(&full, e)),
+        };
+
+        let allowed = self
+            .allow
+            .iter()
+            .filter_map(|dir| std::fs::canonicalize(dir).ok())
+            .any(|dir| resolved.starts_with(dir));
+        if !allowed {
+            return Err(FileError::NotAllowed(resolved));
+        }
+        Ok(resolved)
+    }
+}
+
+fn canonicalize(path: &Path) -> Result<PathBuf, FileError> {
+    std::fs::canonicalize(path).map_err(|e| io_error(path, e))
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> FileError {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => FileError::NotFound(path.to_path_buf()),
+        _ => FileError::Io(format!("{}: {}", path.display(), e)),
+    }
+}
+
+/// Open `path` without blocking, so a FIFO or device can't hang the
+/// caller, and refuse anything that isn't a regular file.
+fn open_regular(path: &Path, options: &mut std::fs::OpenOptions) -> Result<(std::fs::File, std::fs::Metadata), FileError> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let file = options
+        .custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK)
+        .open(path)
+        .map_err(|e| io_error(path, e))?;
+    let meta = file.metadata().map_err(|e| io_error(path, e))?;
+    if !meta.is_file() {
+        return Err(FileError::Invalid(format!("{} is not a regular file", path.display())));
+    }
+    Ok((file, meta))
+}
+
+/// Read a file resolved by [`FilesConfig::resolve`].
+pub async fn read(path: PathBuf) -> Result<Vec<u8>, FileError> {
+    use std::io::Read;
+
+    tokio::task::spawn_blocking(move || {
+        let (file, meta) = open_regular(&path, std::fs::OpenOptions::new().read(true))?;
+        if meta.len() > MAX_FILE_BYTES as u64 {
+            return Err(FileError::TooLarge { size: meta.len(), limit: MAX_FILE_BYTES });
+        }
+        let mut data = Vec::with_capacity(meta.len() as usize);
+        // Read one byte past the limit to catch a file that grew.
+        file.take(MAX_FILE_BYTES as u64 + 1)
+            .read_to_end(&mut data)
+            .map_err(|e| io_error(&path, e))?;
+        if data.len() > MAX_FILE_BYTES {
+            return Err(FileError::TooLarge { size: data.len() as u64, limit: MAX_FILE_BYTES });
+        }
+        Ok(data)
+    })
+    .await
+    .map_err(|e| FileError::Io(e.to_string()))?
+}
+
+/// Write `data` to a file resolved by [`FilesConfig::resolve`], replacing
+/// its contents. A new file gets `mode`, or 0644; an existing one keeps its
+/// permissions unless `mode` is given. The final path component is not
+/// followed if it has become a symlink since it was resolved, and anything
+/// but a regular file is refused.
+pub async fn write(path: PathBuf, data: bytes::Bytes, mode: Option<u32>) -> Result<(), FileError> {
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    if data.len() > MAX_FILE_BYTES {
+        return Err(FileError::TooLarge { size: data.len() as u64, limit: MAX_FILE_BYTES });
+    }
+    tokio::task::spawn_blocking(move || {
+        // Truncate only once the file is known to be regular.
+        let (mut file, _) = open_regular(
+            &path,
+            std::fs::OpenOptions::new().write(true).create(true).mode(mode.unwrap_or(0o644)),
+        )?;
+        file.set_len(0).map_err(|e| io_error(&path, e))?;
+        if let Some(mode) = mode {
+            file.set_permissions(std::fs::Permissions::from_mode(mode))
+                .map_err(|e| io_error(&path, e))?;
+        }
+        file.write_all(&data).map_err(|e| io_error(&path, e))
+    })
+    .await
+    .map_err(|e| FileError::Io(e.to_string()))?
+}
+
+/// Parse a permission mode given in octal, such as `755`.
+pub fn parse_mode(mode: &str) -> Result<u32, FileError> {
+    u32::from_str_radix(mode, 8)
+        .ok()
+        .filter(|&mode| mode <= 0o7777)
+        .ok_or_else(|| FileError::Invalid(format!("invalid file mode '{}', expected octal such as 644", mode)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(dir: &Path) -> FilesConfig {
+        FilesConfig { allow: vec![dir.to_path_buf()] }
+    }
+
+    #[test]
+    fn off_without_an_allowlist() {
+        let err = FilesConfig::default().resolve(None, "/tmp/x", false).unwrap_err();
+        assert!(matches!(err, FileError::Disabled));
+    }
+
+    #[test]
+    fn relative_paths_use_the_shell_working_directory() {
+        let cwd = std::env::current_dir().unwrap();
+        let config = config(&cwd);
+        let resolved = config.resolve(Some(std::process::id()), "Cargo.toml", false).unwrap();
+        assert_eq!(resolved, cwd.join("Cargo.toml").canonicalize().unwrap());
+        assert!(matches!(config.resolve(None, "Cargo.toml", false), Err(FileError::Invalid(_))));
+    }
+
+    #[test]
+    fn paths_outside_the_allowlist_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let allowed = dir.path().join("work");
+        std::fs::create_dir(&allowed).unwrap();
+        std::fs::write(dir.path().join("secret"), "x").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("secret"), allowed.join("link")).unwrap();
+        let config = config(&allowed);
+
+        let escape = format!("{}/../secret", allowed.display());
+        assert!(matches!(config.resolve(None, &escape, false), Err(FileError::NotAllowed(_))));
+        let link = allowed.join("link");
+        assert!(matches!(config.resolve(None, link.to_str().unwrap(), true), Err(FileError::NotAllowed(_))));
+        let missing = allowed.join("missing/new.sh");
+        assert!(matches!(config.resolve(None, missing.to_str().unwrap(), true), Err(FileError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn writes_and_reads_back() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path());
+        let path = config
+            .resolve(None, dir.path().join("run.sh").to_str().unwrap(), true)
+            .unwrap();
+        write(path.clone(), bytes::Bytes::from("#!/bin/sh\necho hi\n"), Some(parse_mode("755").unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o755);
+        assert_eq!(read(path.clone()).await.unwrap(), b"#!/bin/sh\necho hi\n");
+
+        let big = bytes::Bytes::from(vec![0u8; MAX_FILE_BYTES + 1]);
+        assert!(matches!(write(path.clone(), big, None).await, Err(FileError::TooLarge { .. })));
+        assert!(matches!(read(dir.path().to_path_buf()).await, Err(FileError::Invalid(_))));
+        assert!(parse_mode("9").is_err());
+    }
+
+    #[tokio::test]
+    async fn fifos_are_refused_without_blocking() {
+        let dir = tempfile::tempdir().unwrap();
+        let fifo = dir.path().join("fifo");
+        let c_path = std::ffi::CString::new(fifo.to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+
+        // With no reader, a blocking open would wait forever; a non-blocking
+        // write open fails with ENXIO instead.
+        let written = tokio::time::timeout(std::time::Duration::from_secs(5), write(fifo.clone(), bytes::Bytes::from("x"), None));
+        assert!(written.await.expect("write to a FIFO hung").is_err());
+        let read_back = tokio::time::timeout(std::time::Duration::from_secs(5), read(fifo));
+        assert!(matches!(read_back.await.expect("read from a FIFO hung"), Err(FileError::Invalid(_))));
+    }
+}
@@ -0,0 +1,562 @@
+//! Operator hook scripts around the session lifecycle.
+//!
+//! The `[hooks]` config section names a shell command to run when a
+//! session is created, when it ends, and before a client attaches to it,
+//! so that an operator can register sessions in an inventory system or
+//! gate who watches them. Each command runs through `/bin/sh -c` on the
+//! server with the session's details as a JSON object on stdin and as
+//! `WSH_*` environment variables (see [`Payload`]).
+//!
+//! The create and exit hooks run in the background from the registry's
+//! lifecycle events, in order for any one session: a session's exit hook
+//! waits for its create hook to finish. The attach hook runs before the
+//! client is connected, and with `on_failure = "reject"` a failure refuses
+//! the client.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use crate::session::{DestroyReason, Session, SessionEvent, SessionRegistry};
+
+/// How long a hook may run when its `timeout` is unset.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest `timeout` a hook may have.
+pub const MAX_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Most bytes of a failed hook's stderr kept for its error message.
+const MAX_STDERR_LEN: usize = 512;
+
+/// The `[hooks]` config section.
+///
+/// ```toml
+/// [hooks.on_session_create]
+/// command = "/usr/local/bin/inventory register"
+/// timeout = "5s"
+/// on_failure = "reject"
+///
+/// [hooks.on_session_exit]
+/// command = "/usr/local/bin/inventory deregister"
+///
+/// [hooks.on_client_attach]
+/// command = "/usr/local/bin/may-attach"
+/// on_failure = "reject"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HooksConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_session_create: Option<HookConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_session_exit: Option<HookConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_client_attach: Option<HookConfig>,
+}
+
+/// One hook of the `[hooks]` section.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HookConfig {
+    /// Shell command, run with `/bin/sh -c`.
+    pub command: String,
+    /// How long the command may run before it is killed and counted as
+    /// failed (see [`parse_duration`](crate::config::parse_duration)).
+    /// Unset means [`DEFAULT_TIMEOUT`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+    /// What a failure (non-zero exit, timeout, or a command that can't be
+    /// started) does.
+    #[serde(default)]
+    pub on_failure: FailurePolicy,
+}
+
+/// What a failed hook does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailurePolicy {
+    /// Log the failure and carry on.
+    #[default]
+    Ignore,
+    /// Kill the new session (`on_session_create`) or refuse the client
+    /// (`on_client_attach`). Not allowed for `on_session_exit`.
+    Reject,
+}
+
+/// The lifecycle points a hook can run at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    SessionCreate,
+    SessionExit,
+    ClientAttach,
+}
+
+impl HookEvent {
+    /// The hook's name in the config, and its `hook` in the [`Payload`].
+    pub fn name(self) -> &'static str {
+        match self {
+            HookEvent::SessionCreate => "on_session_create",
+            HookEvent::SessionExit => "on_session_exit",
+            HookEvent::ClientAttach => "on_client_attach",
+        }
+    }
+}
+
+/// A configured hook, ready to run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hook {
+    pub command: String,
+    pub timeout: Duration,
+    pub on_failure: FailurePolicy,
+}
+
+/// The hooks a server runs, resolved from [`HooksConfig`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hooks {
+    pub on_session_create: Option<Hook>,
+    pub on_session_exit: Option<Hook>,
+    pub on_client_attach: Option<Hook>,
+}
+
+impl HooksConfig {
+    /// Resolve against the defaults, failing on an empty command or an
+    /// unparseable or out-of-range timeout.
+    pub fn resolve(&self) -> Result<Hooks, String> {
+        let resolve = |event: HookEvent, config: &Option<HookConfig>| -> Result<Option<Hook>, String> {
+            let Some(config) = config else {
+                return Ok(None);
+            };
+            let name = event.name();
+            if config.command.trim().is_empty() {
+                return Err(format!("{name}: command is empty"));
+            }
+            let timeout = match config.timeout.as_deref() {
+                Some(spec) => crate::config::parse_duration(spec).map_err(|e| format!("{name}: timeout: {e}"))?,
+                None => DEFAULT_TIMEOUT,
+            };
+            if timeout.is_zero() || timeout > MAX_TIMEOUT {
+                return Err(format!("{name}: timeout must be more than 0 and at most {}s", MAX_TIMEOUT.as_secs()));
+            }
+            if event == HookEvent::SessionExit && config.on_failure == FailurePolicy::Reject {
+                return Err(format!("{name}: on_failure = \"reject\" only applies to on_session_create and on_client_attach"));
+            }
+            Ok(Some(Hook { command: config.command.clone(), timeout, on_failure: config.on_failure }))
+        };
+        Ok(Hooks {
+            on_session_create: resolve(HookEvent::SessionCreate, &self.on_session_create)?,
+            on_session_exit: resolve(HookEvent::SessionExit, &self.on_session_exit)?,
+            on_client_attach: resolve(HookEvent::ClientAttach, &self.on_client_attach)?,
+        })
+    }
+}
+
+/// Why a hook failed.
+#[derive(Debug, thiserror::Error)]
+pub enum HookError {
+    #[error("could not start the hook: {0}")]
+    Spawn(#[from] std::io::Error),
+    #[error("the hook timed out after {}s", .0.as_secs_f64())]
+    TimedOut(Duration),
+    #[error("the hook {status}{}", if .stderr.is_empty() { String::new() } else { format!(": {}", .stderr) })]
+    Failed {
+        /// `exited with code N` or `was killed by signal N`.
+        status: String,
+        /// The end of what the hook wrote to stderr, trimmed.
+        stderr: String,
+    },
+}
+
+/// What a hook is told about the session, written to its stdin as one
+/// line of JSON. Each field that is set is also in the environment as
+/// `WSH_` and its name in capitals (`WSH_SESSION`, `WSH_EXIT_CODE`, ...),
+/// with `tags` comma-separated; `metadata` is only in the JSON.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Payload {
+    /// Which hook this is, such as `on_session_create`.
+    pub hook: &'static str,
+    /// The server's host name.
+    pub host: String,
+    pub session: String,
+    pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    pub tags: Vec<String>,
+    /// Unix time the session was created, in milliseconds.
+    pub created_at: u64,
+    /// The session's metadata, as set by API clients.
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    pub metadata: serde_json::Map<String, serde_json::Value>,
+    /// Why the session ended (`on_session_exit`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<DestroyReason>,
+    /// How the command ended, when it exited on its own (`on_session_exit`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signal: Option<String>,
+    /// How the client connects (`on_client_attach`): `ws_raw`, `ws_json`,
+    /// `ws` (the `attach` method of `/ws/json`), or `socket`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client: Option<&'static str>,
+    /// The client's peer process, for a Unix socket client.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peer: Option<String>,
+}
+
+impl Payload {
+    /// The details of `session` for the `event` hook.
+    pub fn new(event: HookEvent, host: String, session: &Session) -> Self {
+        let mut tags: Vec<String> = session.tags.read().iter().cloned().collect();
+        tags.sort();
+        Self {
+            hook: event.name(),
+            host,
+            session: session.name.clone(),
+            command: session.command.clone(),
+            pid: session.pid(),
+            tags,
+            created_at: session.created_at,
+            metadata: session.metadata.read().clone(),
+            reason: None,
+            exit_code: None,
+            signal: None,
+            client: None,
+            peer: None,
+        }
+    }
+
+    /// The `WSH_*` environment variables for the fields that are set.
+    pub fn env(&self) -> Vec<(String, String)> {
+        let serde_json::Value::Object(fields) = serde_json::to_value(self).unwrap_or_default() else {
+            return Vec::new();
+        };
+        fields
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let value = match value {
+                    serde_json::Value::String(s) => s,
+                    serde_json::Value::Number(n) => n.to_string(),
+                    serde_json::Value::Bool(b) => b.to_string(),
+                    serde_json::Value::Array(items) => items
+                        .iter()
+                        .filter_map(|item| item.as_str())
+                        .collect::<Vec<_>>()
+                        .join(","),
+                    _ => return None,
+                };
+                Some((format!("WSH_{}", key.to_ascii_uppercase()), value))
+            })
+            .collect()
+    }
+}
+
+impl Hook {
+    /// Run the command with `payload`, waiting up to the hook's timeout.
+    /// On timeout the command's whole process group is killed.
+    pub async fn run(&self, payload: &Payload) -> Result<(), HookError> {
+        let mut child = tokio::process::Command::new("/bin/sh")
+            .arg("-c")
+            .arg(&self.command)
+            .envs(payload.env())
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped())
+            .process_group(0)
+            .kill_on_drop(true)
+            .spawn()?;
+        let pid = child.id();
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let mut input = serde_json::to_vec(payload).unwrap_or_default();
+        input.push(b'\n');
+        let finished = tokio::time::timeout(self.timeout, async move {
+            // A hook that doesn't read its input may exit before we're done
+            // writing it; that's not a failure.
+            let _ = stdin.write_all(&input).await;
+            drop(stdin);
+            child.wait_with_output().await
+        })
+        .await;
+        let output = match finished {
+            Ok(output) => output?,
+            Err(_) => {
+                if let Some(pid) = pid {
+                    // SAFETY: kill only sends a signal; the group is the
+                    // hook's own, made by process_group(0).
+                    unsafe { libc::kill(-(pid as i32), libc::SIGKILL) };
+                }
+                return Err(HookError::TimedOut(self.timeout));
+            }
+        };
+        if output.status.success() {
+            return Ok(());
+        }
+        use std::os::unix::process::ExitStatusExt;
+        let status = match (output.status.code(), output.status.signal()) {
+            (Some(code), _) => format!("exited with code {code}"),
+            (None, Some(signal)) => format!("was killed by signal {signal}"),
+            (None, None) => "failed".to_string(),
+        };
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stderr = stderr.trim();
+        let mut start = stderr.len().saturating_sub(MAX_STDERR_LEN);
+        while !stderr.is_char_boundary(start) {
+            start += 1;
+        }
+        Err(HookError::Failed { status, stderr: stderr[start..].to_string() })
+    }
+}
+
+/// Run the server's `on_client_attach` hook, if it has one, before a
+/// `client` (see [`Payload::client`]) attaches to `session`. Fails only
+/// when the hook fails and its policy is to reject the client.
+pub async fn check_attach(
+    registry: &SessionRegistry,
+    session: &Session,
+    client: &'static str,
+    peer: Option<String>,
+) -> Result<(), HookError> {
+    let Some(hook) = registry.hooks().on_client_attach else {
+        return Ok(());
+    };
+    let mut payload = Payload::new(HookEvent::ClientAttach, registry.hostname(), session);
+    payload.client = Some(client);
+    payload.peer = peer;
+    match hook.run(&payload).await {
+        Ok(()) => Ok(()),
+        Err(e) if hook.on_failure == FailurePolicy::Reject => {
+            tracing::warn!(session = %session.name, client, error = %e, "on_client_attach hook rejected client");
+            Err(e)
+        }
+        Err(e) => {
+            tracing::warn!(session = %session.name, client, error = %e, "on_client_attach hook failed");
+            Ok(())
+        }
+    }
+}
+
+/// A session the runner has seen created, and its create hook while that
+/// is still running.
+struct Known {
+    payload: Payload,
+    create: Option<tokio::task::JoinHandle<()>>,
+}
+
+/// Spawn a task that runs the `on_session_create` and `on_session_exit`
+/// hooks from the registry's lifecycle events. Hooks are read from the
+/// registry as each event arrives.
+pub fn spawn_runner(registry: SessionRegistry) -> tokio::task::JoinHandle<()> {
+    let mut events = registry.subscribe_events();
+    tokio::spawn(async move {
+        // The exit hook runs after the session is gone from the registry,
+        // so it gets the details seen at creation, kept up to date here.
+        let mut known: HashMap<String, Known> = HashMap::new();
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!(missed, "hook runner fell behind; hooks for some session events did not run");
+                    continue;
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            };
+            match event {
+                SessionEvent::Created { name } => {
+                    let Some(session) = registry.get(&name) else {
+                        continue;
+                    };
+                    let payload = Payload::new(HookEvent::SessionCreate, registry.hostname(), &session);
+                    let create = registry.hooks().on_session_create.map(|hook| {
+                        let registry = registry.clone();
+                        let payload = payload.clone();
+                        tokio::spawn(async move {
+                            let Err(e) = hook.run(&payload).await else {
+                                return;
+                            };
+                            if hook.on_failure == FailurePolicy::Reject {
+                                tracing::warn!(session = %payload.session, error = %e, "on_session_create hook failed, killing session");
+                                registry.reject(&session);
+                            } else {
+                                tracing::warn!(session = %payload.session, error = %e, "on_session_create hook failed");
+                            }
+                        })
+                    });
+                    known.insert(name, Known { payload, create });
+                }
+                SessionEvent::Renamed { old_name, new_name } => {
+                    if let Some(mut entry) = known.remove(&old_name) {
+                        entry.payload.session = new_name.clone();
+                        known.insert(new_name, entry);
+                    }
+                }
+                SessionEvent::TagsChanged { name, added, removed } => {
+                    if let Some(entry) = known.get_mut(&name) {
+                        let tags = &mut entry.payload.tags;
+                        tags.retain(|tag| !removed.contains(tag));
+                        tags.extend(added.into_iter().filter(|tag| !tags.contains(tag)).collect::<Vec<_>>());
+                        tags.sort();
+                    }
+                }
+                SessionEvent::Destroyed { name, reason, exit } => {
+                    let Some(entry) = known.remove(&name) else {
+                        continue;
+                    };
+                    let Some(hook) = registry.hooks().on_session_exit else {
+                        continue;
+                    };
+                    let mut payload = entry.payload;
+                    payload.hook = HookEvent::SessionExit.name();
+                    payload.reason = Some(reason);
+                    if let Some(exit) = exit {
+                        payload.exit_code = exit.exit_code;
+                        payload.signal = exit.signal;
+                    }
+                    tokio::spawn(async move {
+                        if let Some(create) = entry.create {
+                            let _ = create.await;
+                        }
+                        if let Err(e) = hook.run(&payload).await {
+                            tracing::warn!(session = %payload.session, error = %e, "on_session_exit hook failed");
+                        }
+                    });
+                }
+                _ => {}
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hook(command: &str) -> Hook {
+        Hook { command: command.to_string(), timeout: DEFAULT_TIMEOUT, on_failure: FailurePolicy::Ignore }
+    }
+
+    fn payload() -> Payload {
+        Payload {
+            hook: "on_session_exit",
+            host: "box".into(),
+            session: "build".into(),
+            command: "make".into(),
+            pid: Some(42),
+            tags: vec!["ci".into(), "nightly".into()],
+            created_at: 1,
+            metadata: serde_json::Map::new(),
+            reason: Some(DestroyReason::Exited),
+            exit_code: Some(2),
+            signal: None,
+            client: None,
+            peer: None,
+        }
+    }
+
+    #[test]
+    fn resolve_applies_defaults_and_rejects_bad_values() {
+        let config: HooksConfig = toml::from_str(
+            r#"
+            [on_session_create]
+            command = "register"
+            on_failure = "reject"
+
+            [on_client_attach]
+            command = "check"
+            timeout = "2s"
+            "#,
+        )
+        .unwrap();
+        let hooks = config.resolve().unwrap();
+        let create = hooks.on_session_create.unwrap();
+        assert_eq!(create.timeout, DEFAULT_TIMEOUT);
+        assert_eq!(create.on_failure, FailurePolicy::Reject);
+        assert_eq!(hooks.on_client_attach.unwrap().timeout, Duration::from_secs(2));
+        assert!(hooks.on_session_exit.is_none());
+
+        let bad = |toml_text: &str| toml::from_str::<HooksConfig>(toml_text).unwrap().resolve().unwrap_err();
+        assert!(bad("[on_session_exit]\ncommand = \"x\"\non_failure = \"reject\"").contains("only applies"));
+        assert!(bad("[on_session_create]\ncommand = \" \"").contains("empty"));
+        assert!(bad("[on_session_create]\ncommand = \"x\"\ntimeout = \"1h\"").contains("at most"));
+    }
+
+    #[test]
+    fn payload_env_flattens_fields() {
+        let env: HashMap<String, String> = payload().env().into_iter().collect();
+        assert_eq!(env["WSH_HOOK"], "on_session_exit");
+        assert_eq!(env["WSH_SESSION"], "build");
+        assert_eq!(env["WSH_PID"], "42");
+        assert_eq!(env["WSH_TAGS"], "ci,nightly");
+        assert_eq!(env["WSH_REASON"], "exited");
+        assert_eq!(env["WSH_EXIT_CODE"], "2");
+        assert!(!env.contains_key("WSH_SIGNAL"));
+    }
+
+    #[tokio::test]
+    async fn run_passes_json_on_stdin_and_reports_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("payload.json");
+        let write = hook(&format!("cat > {} && [ \"$WSH_SESSION\" = build ]", out.display()));
+        write.run(&payload()).await.unwrap();
+        let written: serde_json::Value = serde_json::from_slice(&std::fs::read(&out).unwrap()).unwrap();
+        assert_eq!(written["session"], "build");
+        assert_eq!(written["exit_code"], 2);
+
+        let err = hook("echo nope >&2; exit 3").run(&payload()).await.unwrap_err();
+        assert_eq!(err.to_string(), "the hook exited with code 3: nope");
+
+        let slow = Hook { timeout: Duration::from_millis(200), ..hook("sleep 30") };
+        let started = std::time::Instant::now();
+        assert!(matches!(slow.run(&payload()).await, Err(HookError::TimedOut(_))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn check_attach_follows_failure_policy() {
+        let registry = SessionRegistry::new();
+        let (session, _rx) = Session::spawn("gated".into(), crate::pty::SpawnCommand::default(), 24, 80).unwrap();
+        assert!(check_attach(&registry, &session, "ws_raw", None).await.is_ok());
+
+        let only_socket = Hook { on_failure: FailurePolicy::Reject, ..hook("[ \"$WSH_CLIENT\" = socket ]") };
+        registry.set_hooks(Hooks { on_client_attach: Some(only_socket.clone()), ..Default::default() });
+        assert!(check_attach(&registry, &session, "socket", None).await.is_ok());
+        assert!(check_attach(&registry, &session, "ws_raw", None).await.is_err());
+
+        let ignored = Hook { on_failure: FailurePolicy::Ignore, ..only_socket };
+        registry.set_hooks(Hooks { on_client_attach: Some(ignored), ..Default::default() });
+        assert!(check_attach(&registry, &session, "ws_raw", None).await.is_ok());
+        session.force_kill();
+    }
+
+    #[tokio::test]
+    async fn rejecting_create_hook_kills_the_session_and_exit_hook_follows() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("log");
+        let registry = SessionRegistry::new();
+        registry.set_hooks(Hooks {
+            on_session_create: Some(Hook {
+                on_failure: FailurePolicy::Reject,
+                ..hook(&format!("echo \"create $WSH_SESSION\" >> {}; exit 1", log.display()))
+            }),
+            on_session_exit: Some(hook(&format!("echo \"exit $WSH_SESSION $WSH_REASON\" >> {}", log.display()))),
+            on_client_attach: None,
+        });
+        let runner = spawn_runner(registry.clone());
+        let (session, _rx) =
+            Session::spawn("".into(), crate::pty::SpawnCommand::Command { command: "sleep 30".into(), interactive: false }, 24, 80)
+                .unwrap();
+        registry.insert(Some("doomed".into()), session.clone()).unwrap();
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        loop {
+            let text = std::fs::read_to_string(&log).unwrap_or_default();
+            if text.contains("exit doomed hook_rejected") {
+                assert!(text.starts_with("create doomed\n"), "create hook runs first: {text}");
+                break;
+            }
+            assert!(std::time::Instant::now() < deadline, "exit hook did not run: {text}");
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(registry.get("doomed").is_none());
+        assert!(session.cancelled.is_cancelled());
+        runner.abort();
+    }
+}
@@ -0,0 +1,236 @@
+//! Human approval of dangerous programmatic input.
+//!
+//! With approval mode on, input from the API, WebSocket or MCP that
+//! matches one of the policy's approve patterns (by default `rm -rf`,
+//! `sudo` and `git push --force`) is not written to the PTY. It is held in
+//! a queue instead, and a prompt is shown on the human's terminal. The
+//! oldest held input is answered with a key press at an attached terminal
+//! (`y`/`n`), and any of them through `POST /sessions/{name}/approvals/{id}`.
+//! Approved input is then delivered and recorded in the audit log under
+//! the client that sent it; denied input is dropped.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use super::InputSource;
+
+/// Most inputs one session holds at a time.
+pub const MAX_PENDING: usize = 32;
+
+/// Longest held input shown in the prompt, in characters.
+const MAX_PROMPT_TEXT: usize = 60;
+
+/// Input waiting for approval.
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingInput {
+    /// Sequence number of the input within the session.
+    pub id: u64,
+    /// The input as text (invalid UTF-8 replaced).
+    pub text: String,
+    /// The approve pattern it matched.
+    pub pattern: String,
+    /// Client that sent it.
+    pub source: InputSource,
+    /// Unix time it was held, in milliseconds.
+    pub created_at: u64,
+    #[serde(skip)]
+    pub data: Bytes,
+}
+
+/// Whether approval mode is on, and the input it is holding.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApprovalStatus {
+    pub enabled: bool,
+    /// Held input, oldest first.
+    pub pending: Vec<PendingInput>,
+}
+
+#[derive(Default)]
+struct ApprovalState {
+    enabled: bool,
+    next_id: u64,
+    pending: VecDeque<PendingInput>,
+    /// The held input the prompt overlay asks about, and the overlay's ID.
+    prompt: Option<(u64, String)>,
+}
+
+/// Per-session approval mode and queue of held input.
+#[derive(Clone, Default)]
+pub struct Approvals {
+    inner: Arc<Mutex<ApprovalState>>,
+}
+
+impl Approvals {
+    pub fn is_enabled(&self) -> bool {
+        self.inner.lock().enabled
+    }
+
+    /// Turn approval mode on or off. Input already held stays held until
+    /// it is answered.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.inner.lock().enabled = enabled;
+    }
+
+    /// Hold `data`, which matched `pattern`. Returns `None` when the queue
+    /// is full.
+    pub fn hold(&self, source: InputSource, data: Bytes, pattern: String) -> Option<PendingInput> {
+        let mut state = self.inner.lock();
+        if state.pending.len() >= MAX_PENDING {
+            return None;
+        }
+        state.next_id += 1;
+        let pending = PendingInput {
+            id: state.next_id,
+            text: String::from_utf8_lossy(&data).into_owned(),
+            pattern,
+            source,
+            created_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            data,
+        };
+        state.pending.push_back(pending.clone());
+        Some(pending)
+    }
+
+    /// Remove held input `id`, or the oldest when `id` is `None`, to answer
+    /// it.
+    pub fn take(&self, id: Option<u64>) -> Option<PendingInput> {
+        let mut state = self.inner.lock();
+        let index = match id {
+            Some(id) => state.pending.iter().position(|p| p.id == id)?,
+            None => 0,
+        };
+        state.pending.remove(index)
+    }
+
+    /// The oldest held input, which the prompt should ask about.
+    pub fn oldest(&self) -> Option<PendingInput> {
+        self.inner.lock().pending.front().cloned()
+    }
+
+    pub fn is_pending(&self) -> bool {
+        !self.inner.lock().pending.is_empty()
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.inner.lock().pending.len()
+    }
+
+    /// The held input the prompt overlay asks about, and the overlay.
+    pub fn prompt(&self) -> Option<(u64, String)> {
+        self.inner.lock().prompt.clone()
+    }
+
+    /// Remember the overlay prompting for held input `id`, returning the
+    /// previous prompt overlay to remove. Returns `Err` with `overlay` back
+    /// when `id` was answered in the meantime.
+    pub fn set_prompt(&self, id: u64, overlay: String) -> Result<Option<String>, String> {
+        let mut state = self.inner.lock();
+        if !state.pending.iter().any(|p| p.id == id) {
+            return Err(overlay);
+        }
+        Ok(state.prompt.replace((id, overlay)).map(|(_, overlay)| overlay))
+    }
+
+    /// Forget the prompt overlay, returning it to remove.
+    pub fn take_prompt(&self) -> Option<String> {
+        self.inner.lock().prompt.take().map(|(_, overlay)| overlay)
+    }
+
+    pub fn status(&self) -> ApprovalStatus {
+        let state = self.inner.lock();
+        ApprovalStatus {
+            enabled: state.enabled,
+            pending: state.pending.iter().cloned().collect(),
+        }
+    }
+}
+
+/// Text of the prompt overlay for `pending`, with `count` inputs held in
+/// all. Control characters are shown as `^X` and long input is cut short.
+pub fn prompt_text(pending: &PendingInput, count: usize) -> String {
+    let mut shown = String::new();
+    for c in pending.text.trim_end_matches(['\r', '\n']).chars() {
+        match c {
+            '\x00'..='\x1f' => {
+                shown.push('^');
+                shown.push((b'@' + c as u8) as char);
+            }
+            c if c.is_control() => shown.push('?'),
+            c => shown.push(c),
+        }
+    }
+    if shown.chars().count() > MAX_PROMPT_TEXT {
+        shown = shown.chars().take(MAX_PROMPT_TEXT - 1).collect();
+        shown.push('…');
+    }
+    let mut text = format!(" Approve input: {}", shown);
+    if count > 1 {
+        text.push_str(&format!(" (1 of {})", count));
+    }
+    text.push_str("  [y] allow  [n] deny ");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hold(approvals: &Approvals, text: &'static str) -> PendingInput {
+        approvals
+            .hold(InputSource::Http, Bytes::from_static(text.as_bytes()), "sudo".into())
+            .unwrap()
+    }
+
+    #[test]
+    fn answers_oldest_first_or_by_id() {
+        let approvals = Approvals::default();
+        let first = hold(&approvals, "sudo a\n");
+        let second = hold(&approvals, "sudo b\n");
+        let third = hold(&approvals, "sudo c\n");
+        assert_eq!((first.id, second.id, third.id), (1, 2, 3));
+
+        assert_eq!(approvals.take(Some(2)).unwrap().text, "sudo b\n");
+        assert!(approvals.take(Some(2)).is_none());
+        assert_eq!(approvals.take(None).unwrap().id, 1);
+        assert_eq!(approvals.oldest().unwrap().id, 3);
+        assert_eq!(approvals.status().pending.len(), 1);
+    }
+
+    #[test]
+    fn queue_is_bounded() {
+        let approvals = Approvals::default();
+        for _ in 0..MAX_PENDING {
+            hold(&approvals, "sudo x\n");
+        }
+        assert!(approvals.hold(InputSource::Http, Bytes::new(), "sudo".into()).is_none());
+    }
+
+    #[test]
+    fn prompt_is_replaced_and_ignored_once_answered() {
+        let approvals = Approvals::default();
+        let first = hold(&approvals, "sudo a\n");
+        assert_eq!(approvals.set_prompt(first.id, "overlay-1".into()), Ok(None));
+        let second = hold(&approvals, "sudo b\n");
+        assert_eq!(approvals.set_prompt(second.id, "overlay-2".into()), Ok(Some("overlay-1".into())));
+        approvals.take(Some(second.id));
+        assert_eq!(approvals.set_prompt(second.id, "overlay-3".into()), Err("overlay-3".into()));
+        assert_eq!(approvals.take_prompt().as_deref(), Some("overlay-2"));
+    }
+
+    #[test]
+    fn prompt_text_shows_control_characters() {
+        let approvals = Approvals::default();
+        let pending = hold(&approvals, "sudo rm -rf /tmp/x\x1b[A\r\n");
+        assert_eq!(
+            prompt_text(&pending, 2),
+            " Approve input: sudo rm -rf /tmp/x^[[A (1 of 2)  [y] allow  [n] deny "
+        );
+    }
+}
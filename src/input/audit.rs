@@ -0,0 +1,243 @@
+//! Per-session audit log of input delivered to the PTY.
+//!
+//! Every path that writes to a session's PTY records who sent the input, so
+//! that when several agents share a session it is possible to tell who typed
+//! what. Handoffs of keyboard control are recorded in the same log.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use super::HandoffAction;
+
+/// Number of audit entries retained per session. Older entries are dropped.
+pub const AUDIT_LOG_CAPACITY: usize = 1000;
+
+/// Identity of the client that sent a piece of input.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum InputSource {
+    /// A Unix socket client (e.g. `wsh attach`), identified by its peer process.
+    Socket { peer: String },
+    /// An HTTP `POST /sessions/{name}/input` request.
+    Http,
+    /// A WebSocket connection (`ws/raw` or `ws/json`).
+    WebSocket { connection: String },
+    /// An MCP tool call, identified by the MCP session ID when available.
+    Mcp {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        session: Option<String>,
+    },
+    /// A pipe of another session's output (`POST /sessions/{name}/pipe`),
+    /// identified by that session's name.
+    Pipe { from: String },
+}
+
+impl InputSource {
+    /// A source for a new WebSocket connection with a process-unique ID.
+    pub fn new_websocket() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        Self::WebSocket {
+            connection: format!("ws-{}", NEXT_ID.fetch_add(1, Ordering::Relaxed)),
+        }
+    }
+}
+
+/// A single recorded input.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    /// Monotonically increasing sequence number within the session.
+    pub seq: u64,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    pub source: InputSource,
+    /// Number of bytes delivered.
+    pub len: usize,
+    /// The input as text (invalid UTF-8 replaced). Empty for sensitive
+    /// input.
+    pub text: String,
+    /// Set for input sent as a secret, whose content is never recorded.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub sensitive: bool,
+    /// Set for a handoff step rather than input; `len` is then 0.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handoff: Option<HandoffAction>,
+}
+
+struct AuditState {
+    next_seq: u64,
+    entries: VecDeque<AuditEntry>,
+}
+
+/// Bounded, shareable log of input sent to a session.
+#[derive(Clone)]
+pub struct InputAuditLog {
+    inner: Arc<Mutex<AuditState>>,
+}
+
+impl InputAuditLog {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(AuditState {
+                next_seq: 1,
+                entries: VecDeque::new(),
+            })),
+        }
+    }
+
+    /// Record input from `source`.
+    pub fn record(&self, source: InputSource, data: &[u8]) {
+        self.push(source, data.len(), String::from_utf8_lossy(data).into_owned(), false, None);
+    }
+
+    /// Record that `len` bytes of sensitive input were sent by `source`,
+    /// without keeping the content.
+    pub fn record_sensitive(&self, source: InputSource, len: usize) {
+        self.push(source, len, String::new(), true, None);
+    }
+
+    /// Record a step in a handoff of keyboard control, taken by `source`.
+    pub fn record_handoff(&self, source: InputSource, action: HandoffAction) {
+        self.push(source, 0, String::new(), false, Some(action));
+    }
+
+    fn push(
+        &self,
+        source: InputSource,
+        len: usize,
+        text: String,
+        sensitive: bool,
+        handoff: Option<HandoffAction>,
+    ) {
+        let timestamp_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let mut state = self.inner.lock();
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        if state.entries.len() == AUDIT_LOG_CAPACITY {
+            state.entries.pop_front();
+        }
+        state.entries.push_back(AuditEntry {
+            seq,
+            timestamp_ms,
+            source,
+            len,
+            text,
+            sensitive,
+            handoff,
+        });
+    }
+
+    /// Entries with a sequence number greater than `since`, oldest first.
+    /// With a `limit`, only the most recent `limit` of those are returned.
+    pub fn entries(&self, since: Option<u64>, limit: Option<usize>) -> Vec<AuditEntry> {
+        let state = self.inner.lock();
+        let since = since.unwrap_or(0);
+        let matching: Vec<&AuditEntry> = state.entries.iter().filter(|e| e.seq > since).collect();
+        let skip = limit.map_or(0, |l| matching.len().saturating_sub(l));
+        matching.into_iter().skip(skip).cloned().collect()
+    }
+}
+
+impl Default for InputAuditLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_source_and_text() {
+        let log = InputAuditLog::new();
+        log.record(InputSource::Http, b"ls\n");
+        log.record(
+            InputSource::Mcp {
+                session: Some("abc".to_string()),
+            },
+            b"pwd\n",
+        );
+
+        let entries = log.entries(None, None);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].seq, 1);
+        assert_eq!(entries[0].source, InputSource::Http);
+        assert_eq!(entries[0].text, "ls\n");
+        assert_eq!(entries[1].len, 4);
+    }
+
+    #[test]
+    fn since_and_limit_filter_entries() {
+        let log = InputAuditLog::new();
+        for i in 0..5 {
+            log.record(InputSource::Http, format!("{}", i).as_bytes());
+        }
+        let after = log.entries(Some(3), None);
+        assert_eq!(after.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![4, 5]);
+        let last = log.entries(None, Some(2));
+        assert_eq!(last.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![4, 5]);
+    }
+
+    #[test]
+    fn oldest_entries_are_dropped_at_capacity() {
+        let log = InputAuditLog::new();
+        for _ in 0..AUDIT_LOG_CAPACITY + 3 {
+            log.record(InputSource::Http, b"x");
+        }
+        let entries = log.entries(None, None);
+        assert_eq!(entries.len(), AUDIT_LOG_CAPACITY);
+        assert_eq!(entries[0].seq, 4);
+    }
+
+    #[test]
+    fn sensitive_entries_omit_content() {
+        let log = InputAuditLog::new();
+        log.record_sensitive(InputSource::Http, 9);
+        let entries = log.entries(None, None);
+        assert_eq!(entries[0].len, 9);
+        assert!(entries[0].text.is_empty());
+        let json = serde_json::to_value(&entries[0]).unwrap();
+        assert_eq!(json["sensitive"], true);
+
+        log.record(InputSource::Http, b"ls\n");
+        let json = serde_json::to_value(&log.entries(None, None)[1]).unwrap();
+        assert!(json.get("sensitive").is_none());
+    }
+
+    #[test]
+    fn handoff_entries_carry_the_action() {
+        let log = InputAuditLog::new();
+        log.record_handoff(
+            InputSource::Http,
+            HandoffAction::Denied {
+                id: 1,
+                agent: "claude".to_string(),
+            },
+        );
+        let json = serde_json::to_value(&log.entries(None, None)[0]).unwrap();
+        assert_eq!(json["len"], 0);
+        assert_eq!(json["handoff"]["action"], "denied");
+        assert_eq!(json["handoff"]["agent"], "claude");
+    }
+
+    #[test]
+    fn websocket_sources_are_unique() {
+        assert_ne!(InputSource::new_websocket(), InputSource::new_websocket());
+    }
+
+    #[test]
+    fn source_serializes_with_kind_tag() {
+        let json = serde_json::to_value(InputSource::Socket {
+            peer: "pid:42".to_string(),
+        })
+        .unwrap();
+        assert_eq!(json, serde_json::json!({"kind": "socket", "peer": "pid:42"}));
+    }
+}
@@ -0,0 +1,116 @@
+//! Setting environment variables in a running shell.
+//!
+//! Typing `export KEY=value` at a prompt would leave the value on screen and
+//! in scrollback. Instead, for each variable the shell is given a command
+//! that reads the value from the terminal with echo turned off:
+//!
+//! ```text
+//!  export API_KEY="$(sh -c 'stty -echo; IFS= read -r v; stty echo; printf %s "$v"')"
+//! ```
+//!
+//! The value is only sent once the terminal has stopped echoing, so it is
+//! never displayed, and it is recorded in the audit log by length only. The
+//! leading space keeps the command out of history in shells configured to
+//! ignore such lines (`HISTCONTROL=ignorespace`, `setopt histignorespace`,
+//! and fish by default).
+
+/// Reads one line from the terminal without echo and prints it.
+const READ_HIDDEN: &str = r#"sh -c 'stty -echo; IFS= read -r v; stty echo; printf %s "$v"'"#;
+
+/// Longest variable name accepted.
+const MAX_NAME_LEN: usize = 128;
+
+/// Command syntax family of a shell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellKind {
+    /// bash, zsh, and other `sh`-compatible shells.
+    Posix,
+    Fish,
+}
+
+impl ShellKind {
+    /// The kind of shell `program` is, from its file name. Accepts a full
+    /// command line and a login shell's leading `-`.
+    pub fn detect(program: &str) -> Option<Self> {
+        let first = program.split_whitespace().next()?;
+        let name = first.rsplit('/').next()?.trim_start_matches('-');
+        match name {
+            "bash" | "zsh" | "sh" | "dash" | "ash" | "ksh" | "mksh" | "yash" => Some(Self::Posix),
+            "fish" => Some(Self::Fish),
+            _ => None,
+        }
+    }
+
+    /// The line to type to set `name` from a hidden read.
+    pub fn read_command(self, name: &str) -> String {
+        match self {
+            Self::Posix => format!(" export {}=\"$({})\"\n", name, READ_HIDDEN),
+            Self::Fish => format!(" set -gx {} ({})\n", name, READ_HIDDEN),
+        }
+    }
+}
+
+/// Check that `name` can be used unquoted as a variable name in any shell.
+pub fn validate_name(name: &str) -> Result<(), String> {
+    let mut chars = name.chars();
+    let valid = name.len() <= MAX_NAME_LEN
+        && chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "invalid variable name '{}': use letters, digits and '_', not starting with a digit",
+            name.chars().take(MAX_NAME_LEN).collect::<String>()
+        ))
+    }
+}
+
+/// Check that `value` can be sent as one line of input. Control
+/// characters other than tab are refused: the terminal's line discipline
+/// would act on them (^C, ^U, ESC sequences) instead of passing them on.
+pub fn validate_value(name: &str, value: &str) -> Result<(), String> {
+    if value.chars().any(|c| c.is_control() && c != '\t') {
+        return Err(format!("value of {} must not contain control characters", name));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_shells_from_paths_and_command_lines() {
+        assert_eq!(ShellKind::detect("/bin/bash"), Some(ShellKind::Posix));
+        assert_eq!(ShellKind::detect("-zsh"), Some(ShellKind::Posix));
+        assert_eq!(ShellKind::detect("/usr/bin/fish -l"), Some(ShellKind::Fish));
+        assert_eq!(ShellKind::detect("vim notes.txt"), None);
+        assert_eq!(ShellKind::detect(""), None);
+    }
+
+    #[test]
+    fn read_commands_never_contain_a_value() {
+        let posix = ShellKind::Posix.read_command("API_KEY");
+        assert!(posix.starts_with(" export API_KEY=\"$(sh -c 'stty -echo;"));
+        assert!(posix.ends_with(")\"\n"));
+        let fish = ShellKind::Fish.read_command("API_KEY");
+        assert!(fish.starts_with(" set -gx API_KEY (sh -c "));
+        assert!(fish.ends_with(")\n"));
+    }
+
+    #[test]
+    fn names_and_values_are_checked() {
+        assert!(validate_name("API_KEY").is_ok());
+        assert!(validate_name("_x1").is_ok());
+        assert!(validate_name("1X").is_err());
+        assert!(validate_name("A-B").is_err());
+        assert!(validate_name("A;rm").is_err());
+        assert!(validate_name("").is_err());
+        assert!(validate_value("A", "p@ss 'word\" $x").is_ok());
+        assert!(validate_value("A", "tab\tseparated").is_ok());
+        assert!(validate_value("A", "two\nlines").is_err());
+        assert!(validate_value("A", "esc\x1b[2J").is_err());
+        assert!(validate_value("A", "del\x7f").is_err());
+    }
+}
@@ -6,7 +6,7 @@
 use serde::Serialize;
 use tokio::sync::broadcast;
 
-use super::{parse_key, Mode, ParsedKey};
+use super::{parse_key, AuditEntry, HandoffAction, InputAuditLog, InputSource, Mode, ParsedKey};
 
 /// Input event broadcast to subscribers
 #[derive(Debug, Clone, Serialize)]
@@ -18,25 +18,69 @@ pub enum InputEvent {
         parsed: Option<ParsedKey>,
         #[serde(skip_serializing_if = "Option::is_none")]
         target: Option<String>,
+        /// Client that sent the input, if known.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<InputSource>,
     },
     Mode {
         mode: Mode,
     },
+    /// Focus moved to another overlay or panel, or was cleared.
+    Focus {
+        target: Option<String>,
+        /// Client that moved focus.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<InputSource>,
+    },
+    /// A form widget in the focused overlay was submitted.
+    Submit {
+        target: String,
+        /// The text entered, the option selected, or the answer given.
+        value: serde_json::Value,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<InputSource>,
+    },
+    /// A form widget in the focused overlay was dismissed with Escape.
+    Cancel {
+        target: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<InputSource>,
+    },
+    /// A step in a handoff of keyboard control between the human and an agent.
+    Handoff {
+        #[serde(flatten)]
+        action: HandoffAction,
+        /// Client that took the step.
+        source: InputSource,
+    },
 }
 
 /// Broadcaster for input events
+///
+/// Also keeps the session's input audit log, so every input path can
+/// record attribution through the same handle.
 #[derive(Clone)]
 pub struct InputBroadcaster {
     tx: broadcast::Sender<InputEvent>,
+    audit: InputAuditLog,
 }
 
 impl InputBroadcaster {
     pub fn new() -> Self {
         let (tx, _) = broadcast::channel(256);
-        Self { tx }
+        Self {
+            tx,
+            audit: InputAuditLog::new(),
+        }
     }
 
-    pub fn broadcast_input(&self, data: &[u8], mode: Mode, target: Option<String>) {
+    pub fn broadcast_input(
+        &self,
+        data: &[u8],
+        mode: Mode,
+        target: Option<String>,
+        source: Option<InputSource>,
+    ) {
         let parsed = parse_key(data);
         let parsed = if parsed.key.is_some() {
             Some(parsed)
@@ -48,9 +92,46 @@ impl InputBroadcaster {
             raw: data.to_vec(),
             parsed,
             target,
+            source,
         });
     }
 
+    /// Announce that focus moved to `target`, or was cleared.
+    pub fn broadcast_focus(&self, target: Option<String>, source: Option<InputSource>) {
+        let _ = self.tx.send(InputEvent::Focus { target, source });
+    }
+
+    /// Announce that the form widget in overlay `target` was submitted.
+    pub fn broadcast_submit(&self, target: String, value: serde_json::Value, source: Option<InputSource>) {
+        let _ = self.tx.send(InputEvent::Submit { target, value, source });
+    }
+
+    /// Announce that the form widget in overlay `target` was cancelled.
+    pub fn broadcast_cancel(&self, target: String, source: Option<InputSource>) {
+        let _ = self.tx.send(InputEvent::Cancel { target, source });
+    }
+
+    /// Record input delivered to the PTY in the audit log.
+    pub fn record_input(&self, source: InputSource, data: &[u8]) {
+        self.audit.record(source, data);
+    }
+
+    /// Record sensitive input in the audit log by length only.
+    pub fn record_sensitive_input(&self, source: InputSource, len: usize) {
+        self.audit.record_sensitive(source, len);
+    }
+
+    /// Audit log entries newer than `since`, limited to the latest `limit`.
+    pub fn audit_entries(&self, since: Option<u64>, limit: Option<usize>) -> Vec<AuditEntry> {
+        self.audit.entries(since, limit)
+    }
+
+    /// Broadcast a handoff step and record it in the audit log.
+    pub fn announce_handoff(&self, source: InputSource, action: HandoffAction) {
+        self.audit.record_handoff(source.clone(), action.clone());
+        let _ = self.tx.send(InputEvent::Handoff { action, source });
+    }
+
     pub fn broadcast_mode(&self, mode: Mode) {
         let _ = self.tx.send(InputEvent::Mode { mode });
     }
@@ -81,11 +162,11 @@ mod tests {
         let broadcaster = InputBroadcaster::new();
         let mut rx = broadcaster.subscribe();
 
-        broadcaster.broadcast_input(b"a", Mode::Passthrough, None);
+        broadcaster.broadcast_input(b"a", Mode::Passthrough, None, None);
 
         let event = rx.try_recv().unwrap();
         match event {
-            InputEvent::Input { mode, raw, parsed, target } => {
+            InputEvent::Input { mode, raw, parsed, target, .. } => {
                 assert_eq!(mode, Mode::Passthrough);
                 assert_eq!(raw, vec![b'a']);
                 assert!(parsed.is_some());
@@ -103,11 +184,11 @@ mod tests {
         let mut rx = broadcaster.subscribe();
 
         // Unknown sequence
-        broadcaster.broadcast_input(&[0x80, 0x81], Mode::Capture, None);
+        broadcaster.broadcast_input(&[0x80, 0x81], Mode::Capture, None, None);
 
         let event = rx.try_recv().unwrap();
         match event {
-            InputEvent::Input { mode, raw, parsed, target } => {
+            InputEvent::Input { mode, raw, parsed, target, .. } => {
                 assert_eq!(mode, Mode::Capture);
                 assert_eq!(raw, vec![0x80, 0x81]);
                 assert!(parsed.is_none());
@@ -117,6 +198,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_broadcast_input_carries_source() {
+        let broadcaster = InputBroadcaster::new();
+        let mut rx = broadcaster.subscribe();
+
+        broadcaster.broadcast_input(b"a", Mode::Passthrough, None, Some(InputSource::Http));
+
+        match rx.try_recv().unwrap() {
+            InputEvent::Input { source, .. } => assert_eq!(source, Some(InputSource::Http)),
+            _ => panic!("Expected Input event"),
+        }
+    }
+
+    #[test]
+    fn test_record_input_is_shared_across_clones() {
+        let broadcaster = InputBroadcaster::new();
+        broadcaster.clone().record_input(InputSource::Http, b"ls\n");
+        let entries = broadcaster.audit_entries(None, None);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].text, "ls\n");
+    }
+
     #[test]
     fn test_broadcast_mode() {
         let broadcaster = InputBroadcaster::new();
@@ -133,6 +236,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_announce_handoff_broadcasts_and_audits() {
+        let broadcaster = InputBroadcaster::new();
+        let mut rx = broadcaster.subscribe();
+
+        broadcaster.announce_handoff(
+            InputSource::Http,
+            HandoffAction::Transferred {
+                to: crate::input::Controller::Agent,
+                agent: "claude".to_string(),
+            },
+        );
+
+        let json = serde_json::to_value(rx.try_recv().unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "event": "handoff",
+                "action": "transferred",
+                "to": "agent",
+                "agent": "claude",
+                "source": {"kind": "http"},
+            })
+        );
+        assert!(broadcaster.audit_entries(None, None)[0].handoff.is_some());
+    }
+
     #[test]
     fn test_input_event_serialization() {
         let event = InputEvent::Input {
@@ -140,6 +270,7 @@ mod tests {
             raw: vec![b'a'],
             parsed: Some(ParsedKey::new(Some("a".to_string()))),
             target: None,
+            source: None,
         };
 
         let json = serde_json::to_string(&event).unwrap();
@@ -6,43 +6,78 @@ use parking_lot::RwLock;
 /// At most one element has focus at a time. Focus requires input capture
 /// mode to be active -- the FocusTracker doesn't enforce this itself;
 /// the API layer checks capture mode before routing input.
+///
+/// The tracker also keeps the tab order that focus cycles through when
+/// the human presses the focus key. An empty order means the session's
+/// focusable elements in stacking order.
 #[derive(Clone)]
 pub struct FocusTracker {
-    inner: Arc<RwLock<Option<String>>>,
+    inner: Arc<RwLock<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    focused: Option<String>,
+    order: Vec<String>,
 }
 
 impl FocusTracker {
     pub fn new() -> Self {
         Self {
-            inner: Arc::new(RwLock::new(None)),
+            inner: Arc::new(RwLock::new(Inner::default())),
         }
     }
 
     /// Set focus to a specific element by ID.
     pub fn focus(&self, id: String) {
         let mut inner = self.inner.write();
-        *inner = Some(id);
+        inner.focused = Some(id);
     }
 
     /// Remove focus from any element.
     pub fn unfocus(&self) {
         let mut inner = self.inner.write();
-        *inner = None;
+        inner.focused = None;
     }
 
     /// Get the currently focused element's ID, if any.
     pub fn focused(&self) -> Option<String> {
         let inner = self.inner.read();
-        inner.clone()
+        inner.focused.clone()
     }
 
     /// Clear focus only if the given ID currently has focus.
     /// Used when an element is deleted -- only unfocus if it was the focused one.
+    /// The element is also dropped from the tab order.
     pub fn clear_if_focused(&self, id: &str) {
         let mut inner = self.inner.write();
-        if inner.as_deref() == Some(id) {
-            *inner = None;
+        if inner.focused.as_deref() == Some(id) {
+            inner.focused = None;
         }
+        inner.order.retain(|o| o != id);
+    }
+
+    /// Set the tab order. Empty restores the default order.
+    pub fn set_order(&self, order: Vec<String>) {
+        self.inner.write().order = order;
+    }
+
+    /// The tab order set with [`set_order`](Self::set_order).
+    pub fn order(&self) -> Vec<String> {
+        self.inner.read().order.clone()
+    }
+
+    /// Move focus to the element after the focused one in `ring`, wrapping
+    /// around, or to the first element if none of `ring` has focus.
+    /// Returns the newly focused element, or `None` if `ring` is empty.
+    pub fn focus_next(&self, ring: &[String]) -> Option<String> {
+        let mut inner = self.inner.write();
+        let next = match inner.focused.as_ref().and_then(|f| ring.iter().position(|id| id == f)) {
+            Some(i) => ring[(i + 1) % ring.len()].clone(),
+            None => ring.first()?.clone(),
+        };
+        inner.focused = Some(next.clone());
+        Some(next)
     }
 }
 
@@ -123,6 +158,27 @@ mod tests {
         assert_eq!(cloned.focused(), Some("overlay-1".to_string()));
     }
 
+    #[test]
+    fn test_focus_next_cycles_through_the_ring() {
+        let tracker = FocusTracker::new();
+        let ring = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        assert_eq!(tracker.focus_next(&[]), None);
+        assert_eq!(tracker.focus_next(&ring).as_deref(), Some("a"));
+        assert_eq!(tracker.focus_next(&ring).as_deref(), Some("b"));
+        tracker.focus("c".to_string());
+        assert_eq!(tracker.focus_next(&ring).as_deref(), Some("a"));
+        tracker.focus("elsewhere".to_string());
+        assert_eq!(tracker.focus_next(&ring).as_deref(), Some("a"));
+    }
+
+    #[test]
+    fn test_clear_if_focused_drops_the_element_from_the_order() {
+        let tracker = FocusTracker::new();
+        tracker.set_order(vec!["a".to_string(), "b".to_string()]);
+        tracker.clear_if_focused("a");
+        assert_eq!(tracker.order(), vec!["b".to_string()]);
+    }
+
     #[test]
     fn test_default_has_no_focus() {
         let tracker = FocusTracker::default();
@@ -0,0 +1,327 @@
+//! Coordinated handoff of keyboard control between the human at the
+//! terminal and an agent.
+//!
+//! Capture and release (see `mode.rs`) stay owner-less and silent. A
+//! handoff layers consent on top of them: an agent asks for control, the
+//! human answers a prompt shown on their terminal, and only an approval
+//! puts the session into capture mode on the agent's behalf. Each step,
+//! including control going back to the human, is broadcast as an input
+//! event and written to the session's audit log.
+
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+/// Longest agent name accepted in a request, in characters.
+pub const MAX_AGENT_LEN: usize = 64;
+
+/// Longest reason accepted in a request, in bytes.
+pub const MAX_REASON_LEN: usize = 1024;
+
+/// Longest reason shown in the prompt, in characters.
+const MAX_PROMPT_REASON: usize = 80;
+
+/// Who drives the session's keyboard after a transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Controller {
+    Human,
+    Agent,
+}
+
+/// An agent's request for control, waiting for the human's answer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HandoffRequest {
+    /// Sequence number of the request within the session.
+    pub id: u64,
+    /// Name the agent is shown under in the prompt.
+    pub agent: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// A step in a handoff, as broadcast to input subscribers and recorded in
+/// the audit log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum HandoffAction {
+    /// An agent asked for control and the human is being prompted.
+    Requested {
+        id: u64,
+        agent: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// The human refused the request.
+    Denied { id: u64, agent: String },
+    /// The agent gave up its request before it was answered.
+    Withdrawn { id: u64, agent: String },
+    /// Control moved to `to`; `agent` is the agent gaining or giving it up.
+    Transferred { to: Controller, agent: String },
+}
+
+/// Who has keyboard control, and any request awaiting an answer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HandoffStatus {
+    pub controller: Controller,
+    /// Agent holding control, when `controller` is `agent`.
+    pub agent: Option<String>,
+    pub pending: Option<HandoffRequest>,
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum HandoffError {
+    #[error("a request from {0} is already waiting for an answer")]
+    Pending(String),
+    #[error("{0} already has control")]
+    AgentInControl(String),
+    #[error("no handoff request is pending")]
+    NothingPending,
+}
+
+#[derive(Default)]
+struct HandoffState {
+    next_id: u64,
+    pending: Option<HandoffRequest>,
+    /// ID of the overlay prompting the human about `pending`.
+    prompt: Option<String>,
+    /// Agent holding control through an approved handoff.
+    holder: Option<String>,
+}
+
+/// Per-session handoff state: at most one pending request and at most one
+/// agent holding control.
+#[derive(Clone, Default)]
+pub struct Handoff {
+    inner: Arc<Mutex<HandoffState>>,
+}
+
+impl Handoff {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open a request for control on behalf of `agent`.
+    pub fn request(&self, agent: String, reason: Option<String>) -> Result<HandoffRequest, HandoffError> {
+        let mut state = self.inner.lock();
+        if let Some(pending) = &state.pending {
+            return Err(HandoffError::Pending(pending.agent.clone()));
+        }
+        if let Some(holder) = &state.holder {
+            return Err(HandoffError::AgentInControl(holder.clone()));
+        }
+        state.next_id += 1;
+        let request = HandoffRequest {
+            id: state.next_id,
+            agent,
+            reason,
+        };
+        state.pending = Some(request.clone());
+        Ok(request)
+    }
+
+    /// Remember the overlay prompting for request `id`, if it is still
+    /// pending. Returns false when the request was answered in the meantime.
+    pub fn set_prompt(&self, id: u64, overlay: String) -> bool {
+        let mut state = self.inner.lock();
+        if state.pending.as_ref().is_some_and(|p| p.id == id) {
+            state.prompt = Some(overlay);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Answer the pending request. On approval its agent becomes the
+    /// holder. Returns the request and the ID of its prompt overlay.
+    pub fn answer(&self, approve: bool) -> Result<(HandoffRequest, Option<String>), HandoffError> {
+        let mut state = self.inner.lock();
+        let request = state.pending.take().ok_or(HandoffError::NothingPending)?;
+        if approve {
+            state.holder = Some(request.agent.clone());
+        }
+        Ok((request, state.prompt.take()))
+    }
+
+    /// Drop the pending request without answering it.
+    pub fn withdraw(&self) -> Option<(HandoffRequest, Option<String>)> {
+        let mut state = self.inner.lock();
+        let request = state.pending.take()?;
+        Some((request, state.prompt.take()))
+    }
+
+    /// Clear the holder, returning the agent that had control.
+    pub fn release(&self) -> Option<String> {
+        self.inner.lock().holder.take()
+    }
+
+    pub fn pending(&self) -> Option<HandoffRequest> {
+        self.inner.lock().pending.clone()
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.inner.lock().pending.is_some()
+    }
+
+    pub fn holder(&self) -> Option<String> {
+        self.inner.lock().holder.clone()
+    }
+
+    pub fn status(&self) -> HandoffStatus {
+        let state = self.inner.lock();
+        HandoffStatus {
+            controller: if state.holder.is_some() {
+                Controller::Agent
+            } else {
+                Controller::Human
+            },
+            agent: state.holder.clone(),
+            pending: state.pending.clone(),
+        }
+    }
+}
+
+/// Check the agent name and reason of a request before opening it.
+pub fn validate_request(agent: &str, reason: Option<&str>) -> Result<(), String> {
+    if agent.trim().is_empty() || agent.chars().count() > MAX_AGENT_LEN {
+        return Err(format!("agent must be 1-{} characters", MAX_AGENT_LEN));
+    }
+    if reason.is_some_and(|r| r.len() > MAX_REASON_LEN) {
+        return Err(format!("reason must be at most {} bytes", MAX_REASON_LEN));
+    }
+    Ok(())
+}
+
+/// How a key pressed at the terminal answers a pending prompt: `y`
+/// approves; `n`, Escape or Ctrl+C deny. Anything else is not an answer.
+pub fn answer_key(data: &[u8]) -> Option<bool> {
+    match data {
+        b"y" | b"Y" => Some(true),
+        b"n" | b"N" | b"\x1b" | b"\x03" => Some(false),
+        _ => None,
+    }
+}
+
+/// Text of the prompt overlay for `request`. Control characters in the
+/// agent name and reason are replaced, and long reasons are cut short.
+pub fn prompt_text(request: &HandoffRequest) -> String {
+    let clean = |s: &str| -> String {
+        s.chars()
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .collect()
+    };
+    let mut text = format!(" {} asks for keyboard control", clean(&request.agent));
+    if let Some(reason) = &request.reason {
+        let mut reason = clean(reason);
+        if reason.chars().count() > MAX_PROMPT_REASON {
+            reason = reason.chars().take(MAX_PROMPT_REASON - 1).collect();
+            reason.push('…');
+        }
+        text.push_str(": ");
+        text.push_str(&reason);
+    }
+    text.push_str("  [y] allow  [n] deny ");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn approved_request_makes_agent_holder() {
+        let handoff = Handoff::new();
+        let request = handoff.request("claude".into(), Some("run migrations".into())).unwrap();
+        assert_eq!(request.id, 1);
+        assert!(handoff.set_prompt(1, "overlay-1".into()));
+        assert_eq!(handoff.pending(), Some(request.clone()));
+
+        let (answered, prompt) = handoff.answer(true).unwrap();
+        assert_eq!(answered, request);
+        assert_eq!(prompt.as_deref(), Some("overlay-1"));
+        assert!(!handoff.is_pending());
+        assert_eq!(handoff.holder().as_deref(), Some("claude"));
+        assert_eq!(handoff.status().controller, Controller::Agent);
+
+        assert_eq!(handoff.release().as_deref(), Some("claude"));
+        assert_eq!(handoff.holder(), None);
+    }
+
+    #[test]
+    fn denied_request_leaves_no_holder() {
+        let handoff = Handoff::new();
+        handoff.request("claude".into(), None).unwrap();
+        handoff.answer(false).unwrap();
+        assert_eq!(handoff.holder(), None);
+        assert_eq!(handoff.answer(false), Err(HandoffError::NothingPending));
+    }
+
+    #[test]
+    fn one_request_at_a_time() {
+        let handoff = Handoff::new();
+        handoff.request("a".into(), None).unwrap();
+        assert_eq!(
+            handoff.request("b".into(), None),
+            Err(HandoffError::Pending("a".into()))
+        );
+        handoff.answer(true).unwrap();
+        assert_eq!(
+            handoff.request("b".into(), None),
+            Err(HandoffError::AgentInControl("a".into()))
+        );
+    }
+
+    #[test]
+    fn stale_prompt_is_not_attached() {
+        let handoff = Handoff::new();
+        let request = handoff.request("a".into(), None).unwrap();
+        handoff.withdraw().unwrap();
+        assert!(!handoff.set_prompt(request.id, "overlay".into()));
+        assert!(handoff.withdraw().is_none());
+    }
+
+    #[test]
+    fn requests_are_validated() {
+        assert!(validate_request("claude", Some("deploy")).is_ok());
+        assert!(validate_request(" ", None).is_err());
+        assert!(validate_request(&"a".repeat(MAX_AGENT_LEN + 1), None).is_err());
+        assert!(validate_request("claude", Some(&"x".repeat(MAX_REASON_LEN + 1))).is_err());
+    }
+
+    #[test]
+    fn answer_keys() {
+        assert_eq!(answer_key(b"y"), Some(true));
+        assert_eq!(answer_key(b"N"), Some(false));
+        assert_eq!(answer_key(b"\x1b"), Some(false));
+        assert_eq!(answer_key(b"\x03"), Some(false));
+        assert_eq!(answer_key(b"yes"), None);
+        assert_eq!(answer_key(b"\x1b[A"), None);
+    }
+
+    #[test]
+    fn prompt_text_is_sanitized_and_bounded() {
+        let request = HandoffRequest {
+            id: 1,
+            agent: "bot\x1b".into(),
+            reason: Some("x".repeat(200)),
+        };
+        let text = prompt_text(&request);
+        assert!(text.starts_with(" bot  asks for keyboard control: xxx"));
+        assert!(text.ends_with("…  [y] allow  [n] deny "));
+        assert!(!text.chars().any(char::is_control));
+    }
+
+    #[test]
+    fn actions_serialize_with_action_tag() {
+        let json = serde_json::to_value(HandoffAction::Transferred {
+            to: Controller::Agent,
+            agent: "claude".into(),
+        })
+        .unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"action": "transferred", "to": "agent", "agent": "claude"})
+        );
+    }
+}
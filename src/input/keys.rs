@@ -1,10 +1,13 @@
-//! Key parsing for input events.
+//! Key parsing and encoding for input events.
 //!
-//! Parses raw bytes into structured key events with modifiers.
-//! Used for interpreting keyboard input from various sources.
+//! Parses raw bytes into structured key events with modifiers, and encodes
+//! named keys into the bytes an application expects given its current
+//! keyboard modes.
 
 use serde::Serialize;
 
+use crate::parser::state::KeyboardModes;
+
 /// A parsed key event with optional modifiers.
 #[derive(Debug, Clone, Serialize, PartialEq, Eq)]
 pub struct ParsedKey {
@@ -56,14 +59,15 @@ pub fn is_ctrl_backslash(data: &[u8]) -> bool {
 /// - ESC [ D -> "ArrowLeft"
 /// - ESC [ H -> "Home"
 /// - ESC [ F -> "End"
+/// - ESC O A..D, H, F -> the same keys in application cursor mode
 /// - Unknown -> key: None
 pub fn parse_key(data: &[u8]) -> ParsedKey {
     if data.is_empty() {
         return ParsedKey::new(None);
     }
 
-    // Check for escape sequences (ESC [ ...)
-    if data.len() >= 3 && data[0] == 0x1b && data[1] == b'[' {
+    // Check for escape sequences (ESC [ ... or ESC O ...)
+    if data.len() >= 3 && data[0] == 0x1b && (data[1] == b'[' || data[1] == b'O') {
         let key = match data[2] {
             b'A' => Some("ArrowUp".to_string()),
             b'B' => Some("ArrowDown".to_string()),
@@ -121,6 +125,223 @@ pub fn parse_key(data: &[u8]) -> ParsedKey {
     }
 }
 
+/// Error returned by [`encode_key`] for a key it cannot encode.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown key: {0}")]
+pub struct UnknownKey(pub String);
+
+#[derive(Clone, Copy)]
+enum NamedKey {
+    /// Cursor keys, Home and End: `CSI <final>` or `SS3 <final>`.
+    Cursor(u8),
+    /// Keys encoded as `CSI <n> ~`.
+    Tilde(u8),
+    /// F1-F4: `SS3 <final>`.
+    Function(u8),
+    Enter,
+    Tab,
+    Backspace,
+    Escape,
+    /// Numeric keypad key: its character and application-mode final byte.
+    Keypad(char, u8),
+    Char(char),
+}
+
+fn named_key(name: &str) -> Option<NamedKey> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(NamedKey::Char(c));
+    }
+    let key = match name.to_ascii_lowercase().as_str() {
+        "arrowup" | "up" => NamedKey::Cursor(b'A'),
+        "arrowdown" | "down" => NamedKey::Cursor(b'B'),
+        "arrowright" | "right" => NamedKey::Cursor(b'C'),
+        "arrowleft" | "left" => NamedKey::Cursor(b'D'),
+        "home" => NamedKey::Cursor(b'H'),
+        "end" => NamedKey::Cursor(b'F'),
+        "insert" => NamedKey::Tilde(2),
+        "delete" => NamedKey::Tilde(3),
+        "pageup" => NamedKey::Tilde(5),
+        "pagedown" => NamedKey::Tilde(6),
+        "f1" => NamedKey::Function(b'P'),
+        "f2" => NamedKey::Function(b'Q'),
+        "f3" => NamedKey::Function(b'R'),
+        "f4" => NamedKey::Function(b'S'),
+        "f5" => NamedKey::Tilde(15),
+        "f6" => NamedKey::Tilde(17),
+        "f7" => NamedKey::Tilde(18),
+        "f8" => NamedKey::Tilde(19),
+        "f9" => NamedKey::Tilde(20),
+        "f10" => NamedKey::Tilde(21),
+        "f11" => NamedKey::Tilde(23),
+        "f12" => NamedKey::Tilde(24),
+        "enter" | "return" => NamedKey::Enter,
+        "tab" => NamedKey::Tab,
+        "backspace" => NamedKey::Backspace,
+        "escape" | "esc" => NamedKey::Escape,
+        "space" => NamedKey::Char(' '),
+        "numpadenter" => NamedKey::Keypad('\r', b'M'),
+        "numpadadd" => NamedKey::Keypad('+', b'k'),
+        "numpadsubtract" => NamedKey::Keypad('-', b'm'),
+        "numpadmultiply" => NamedKey::Keypad('*', b'j'),
+        "numpaddivide" => NamedKey::Keypad('/', b'o'),
+        "numpaddecimal" => NamedKey::Keypad('.', b'n'),
+        other => {
+            let digit = other.strip_prefix("numpad")?;
+            let d = digit.parse::<u8>().ok().filter(|d| *d <= 9)?;
+            NamedKey::Keypad((b'0' + d) as char, b'p' + d)
+        }
+    };
+    Some(key)
+}
+
+/// Encode a key such as `"ArrowUp"`, `"ctrl+c"` or `"shift+F5"` into the
+/// bytes the application expects.
+///
+/// Key names follow the DOM `KeyboardEvent.key` spelling (`ArrowUp`,
+/// `PageDown`, `Escape`, `F1`..`F12`) plus `Space` and `Numpad0`..`Numpad9`,
+/// `NumpadEnter`, `NumpadAdd` etc. for the keypad; any single character is
+/// sent as itself. Modifiers (`ctrl`, `alt`, `shift`) are joined with `+`.
+///
+/// The encoding honors the application's keyboard modes: arrow keys use SS3
+/// sequences under DECCKM, the keypad sends application sequences under
+/// DECKPAM, the kitty keyboard protocol's disambiguation flags switch
+/// ambiguous keys to `CSI ... u`, and xterm's modifyOtherKeys switches
+/// modified keys to `CSI 27 ; ... ~` when kitty's protocol is off.
+pub fn encode_key(spec: &str, modes: &KeyboardModes) -> Result<Vec<u8>, UnknownKey> {
+    let unknown = || UnknownKey(spec.to_string());
+    let (modifiers, name) = match spec.strip_suffix("++") {
+        Some(mods) => (mods, "+"),
+        None => match spec.rsplit_once('+') {
+            Some((mods, name)) if !name.is_empty() => (mods, name),
+            _ => ("", spec),
+        },
+    };
+    let mut mods = Modifiers::default();
+    for m in modifiers.split('+').filter(|m| !m.is_empty()) {
+        match m.to_ascii_lowercase().as_str() {
+            "shift" => mods.shift = true,
+            "alt" | "meta" | "option" => mods.alt = true,
+            "ctrl" | "control" => mods.ctrl = true,
+            _ => return Err(unknown()),
+        }
+    }
+    let key = named_key(name).ok_or_else(unknown)?;
+    Ok(encode(key, mods, modes))
+}
+
+/// Encode a sequence of keys with [`encode_key`], concatenating the bytes.
+pub fn encode_keys(keys: &[String], modes: &KeyboardModes) -> Result<Vec<u8>, UnknownKey> {
+    let mut out = Vec::new();
+    for key in keys {
+        out.extend(encode_key(key, modes)?);
+    }
+    Ok(out)
+}
+
+#[derive(Clone, Copy, Default)]
+struct Modifiers {
+    shift: bool,
+    alt: bool,
+    ctrl: bool,
+}
+
+impl Modifiers {
+    /// xterm modifier parameter: 1 + shift + 2*alt + 4*ctrl.
+    fn param(self) -> u8 {
+        1 + u8::from(self.shift) + 2 * u8::from(self.alt) + 4 * u8::from(self.ctrl)
+    }
+}
+
+fn encode(key: NamedKey, mods: Modifiers, modes: &KeyboardModes) -> Vec<u8> {
+    let m = mods.param();
+    let disambiguate = modes.kitty_flags & 1 != 0;
+    let all_escapes = modes.kitty_flags & 8 != 0;
+    // The kitty protocol supersedes modifyOtherKeys when both are on.
+    let other_keys = if modes.kitty_flags == 0 { modes.modify_other_keys } else { 0 };
+    let csi_u = |code: u32| {
+        if m == 1 {
+            format!("\x1b[{code}u").into_bytes()
+        } else {
+            format!("\x1b[{code};{m}u").into_bytes()
+        }
+    };
+    let modified = |code: u32| format!("\x1b[27;{m};{code}~").into_bytes();
+    let with_alt = |bytes: &[u8]| {
+        let mut out = Vec::with_capacity(bytes.len() + 1);
+        if mods.alt {
+            out.push(0x1b);
+        }
+        out.extend_from_slice(bytes);
+        out
+    };
+
+    match key {
+        NamedKey::Cursor(f) if m == 1 => {
+            let intro = if modes.application_cursor { b'O' } else { b'[' };
+            vec![0x1b, intro, f]
+        }
+        NamedKey::Cursor(f) => format!("\x1b[1;{m}{}", f as char).into_bytes(),
+        NamedKey::Tilde(n) if m == 1 => format!("\x1b[{n}~").into_bytes(),
+        NamedKey::Tilde(n) => format!("\x1b[{n};{m}~").into_bytes(),
+        NamedKey::Function(f) if m == 1 => vec![0x1b, b'O', f],
+        // CSI R is a cursor position report, so kitty moves F3 to CSI 13 ~
+        NamedKey::Function(b'R') if disambiguate => format!("\x1b[13;{m}~").into_bytes(),
+        NamedKey::Function(f) => format!("\x1b[1;{m}{}", f as char).into_bytes(),
+        NamedKey::Keypad(_, f) if modes.application_keypad && m == 1 => vec![0x1b, b'O', f],
+        NamedKey::Keypad('\r', _) => encode(NamedKey::Enter, mods, modes),
+        NamedKey::Keypad(c, _) => encode(NamedKey::Char(c), mods, modes),
+        NamedKey::Escape if disambiguate || all_escapes => csi_u(27),
+        NamedKey::Enter if all_escapes || (disambiguate && m > 1) => csi_u(13),
+        NamedKey::Tab if all_escapes || (disambiguate && m > 1) => csi_u(9),
+        NamedKey::Backspace if all_escapes || (disambiguate && m > 1) => csi_u(127),
+        NamedKey::Char(c) if all_escapes || (disambiguate && (mods.ctrl || mods.alt)) => {
+            csi_u(c.to_lowercase().next().unwrap_or(c) as u32)
+        }
+        NamedKey::Escape if other_keys > 0 && m > 1 => modified(27),
+        NamedKey::Enter if other_keys > 0 && m > 1 => modified(13),
+        // Shift+Tab keeps its own CSI Z encoding
+        NamedKey::Tab if other_keys > 0 && m > 2 => modified(9),
+        NamedKey::Backspace if other_keys > 0 && m > 1 => modified(127),
+        // Level 1 covers only the keys Ctrl has no control byte for
+        NamedKey::Char(c)
+            if (other_keys >= 2 && (mods.ctrl || mods.alt))
+                || (other_keys == 1 && mods.ctrl && control_byte(c).is_none()) =>
+        {
+            let c = if mods.shift { c.to_uppercase().next().unwrap_or(c) } else { c };
+            modified(c as u32)
+        }
+        NamedKey::Escape => with_alt(b"\x1b"),
+        NamedKey::Enter => with_alt(b"\r"),
+        NamedKey::Tab if mods.shift => with_alt(b"\x1b[Z"),
+        NamedKey::Tab => with_alt(b"\t"),
+        NamedKey::Backspace if mods.ctrl => with_alt(b"\x08"),
+        NamedKey::Backspace => with_alt(b"\x7f"),
+        NamedKey::Char(c) => {
+            let c = if mods.shift { c.to_uppercase().next().unwrap_or(c) } else { c };
+            match control_byte(c).filter(|_| mods.ctrl) {
+                Some(b) => with_alt(&[b]),
+                None => with_alt(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            }
+        }
+    }
+}
+
+/// The C0 control byte produced by Ctrl plus `c`, if any.
+fn control_byte(c: char) -> Option<u8> {
+    match c {
+        'a'..='z' | 'A'..='Z' => Some((c.to_ascii_lowercase() as u8) & 0x1f),
+        '@' | ' ' | '2' => Some(0x00),
+        '[' | '3' => Some(0x1b),
+        '\\' | '4' => Some(0x1c),
+        ']' | '5' => Some(0x1d),
+        '^' | '6' => Some(0x1e),
+        '_' | '/' | '7' => Some(0x1f),
+        '?' | '8' => Some(0x7f),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -171,4 +392,127 @@ mod tests {
         assert!(!is_ctrl_backslash(&[0x1c, 0x00])); // Extra byte
         assert!(!is_ctrl_backslash(&[])); // Empty
     }
+
+    #[test]
+    fn test_parse_application_cursor_arrow() {
+        let result = parse_key(b"\x1bOA");
+        assert_eq!(result.key, Some("ArrowUp".to_string()));
+    }
+
+    fn enc(spec: &str, modes: KeyboardModes) -> Vec<u8> {
+        encode_key(spec, &modes).unwrap()
+    }
+
+    #[test]
+    fn encode_arrows_follow_decckm() {
+        let normal = KeyboardModes::default();
+        let app = KeyboardModes {
+            application_cursor: true,
+            ..Default::default()
+        };
+        assert_eq!(enc("ArrowUp", normal), b"\x1b[A");
+        assert_eq!(enc("ArrowUp", app), b"\x1bOA");
+        assert_eq!(enc("End", app), b"\x1bOF");
+        // Modified arrows use the CSI form in both modes
+        assert_eq!(enc("ctrl+ArrowLeft", app), b"\x1b[1;5D");
+    }
+
+    #[test]
+    fn encode_function_and_editing_keys() {
+        let modes = KeyboardModes::default();
+        assert_eq!(enc("F1", modes), b"\x1bOP");
+        assert_eq!(enc("shift+F1", modes), b"\x1b[1;2P");
+        assert_eq!(enc("F5", modes), b"\x1b[15~");
+        assert_eq!(enc("alt+F12", modes), b"\x1b[24;3~");
+        assert_eq!(enc("PageDown", modes), b"\x1b[6~");
+        assert_eq!(enc("shift+Tab", modes), b"\x1b[Z");
+    }
+
+    #[test]
+    fn encode_characters_with_modifiers() {
+        let modes = KeyboardModes::default();
+        assert_eq!(enc("ctrl+c", modes), [0x03]);
+        assert_eq!(enc("CTRL+[", modes), [0x1b]);
+        assert_eq!(enc("alt+x", modes), b"\x1bx");
+        assert_eq!(enc("shift+a", modes), b"A");
+        assert_eq!(enc("+", modes), b"+");
+        assert_eq!(enc("alt++", modes), b"\x1b+");
+        assert_eq!(enc("Space", modes), b" ");
+        assert_eq!(enc("Enter", modes), b"\r");
+    }
+
+    #[test]
+    fn encode_keypad_follows_application_keypad() {
+        let normal = KeyboardModes::default();
+        let app = KeyboardModes {
+            application_keypad: true,
+            ..Default::default()
+        };
+        assert_eq!(enc("Numpad5", normal), b"5");
+        assert_eq!(enc("Numpad5", app), b"\x1bOu");
+        assert_eq!(enc("NumpadEnter", normal), b"\r");
+        assert_eq!(enc("NumpadEnter", app), b"\x1bOM");
+    }
+
+    #[test]
+    fn encode_kitty_disambiguates() {
+        let kitty = KeyboardModes {
+            kitty_flags: 1,
+            ..Default::default()
+        };
+        assert_eq!(enc("Escape", kitty), b"\x1b[27u");
+        assert_eq!(enc("ctrl+c", kitty), b"\x1b[99;5u");
+        assert_eq!(enc("shift+Enter", kitty), b"\x1b[13;2u");
+        assert_eq!(enc("Enter", kitty), b"\r");
+        assert_eq!(enc("a", kitty), b"a");
+        assert_eq!(enc("ctrl+F3", kitty), b"\x1b[13;5~");
+
+        let all = KeyboardModes {
+            kitty_flags: 1 | 8,
+            ..Default::default()
+        };
+        assert_eq!(enc("Enter", all), b"\x1b[13u");
+        assert_eq!(enc("a", all), b"\x1b[97u");
+    }
+
+    #[test]
+    fn encode_modify_other_keys() {
+        let level1 = KeyboardModes {
+            modify_other_keys: 1,
+            ..Default::default()
+        };
+        assert_eq!(enc("ctrl+c", level1), [0x03]);
+        assert_eq!(enc("ctrl+1", level1), b"\x1b[27;5;49~");
+        assert_eq!(enc("ctrl+Enter", level1), b"\x1b[27;5;13~");
+        assert_eq!(enc("shift+Tab", level1), b"\x1b[Z");
+
+        let level2 = KeyboardModes {
+            modify_other_keys: 2,
+            ..Default::default()
+        };
+        assert_eq!(enc("ctrl+c", level2), b"\x1b[27;5;99~");
+        assert_eq!(enc("alt+x", level2), b"\x1b[27;3;120~");
+        assert_eq!(enc("ctrl+shift+a", level2), b"\x1b[27;6;65~");
+        assert_eq!(enc("a", level2), b"a");
+        assert_eq!(enc("Escape", level2), b"\x1b");
+
+        // The kitty protocol takes precedence
+        let both = KeyboardModes {
+            kitty_flags: 1,
+            modify_other_keys: 2,
+            ..Default::default()
+        };
+        assert_eq!(enc("ctrl+c", both), b"\x1b[99;5u");
+    }
+
+    #[test]
+    fn encode_rejects_unknown_keys_and_modifiers() {
+        let modes = KeyboardModes::default();
+        assert_eq!(
+            encode_key("Hyper", &modes),
+            Err(UnknownKey("Hyper".to_string()))
+        );
+        assert!(encode_key("super+a", &modes).is_err());
+        assert!(encode_key("Numpad12", &modes).is_err());
+    }
 }
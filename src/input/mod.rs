@@ -1,9 +1,16 @@
+pub mod approval;
+pub mod audit;
+pub mod env;
 pub mod events;
 pub mod focus;
+pub mod handoff;
 pub mod keys;
 pub mod mode;
 
+pub use approval::{ApprovalStatus, Approvals, PendingInput};
+pub use audit::{AuditEntry, InputAuditLog, InputSource};
 pub use events::{InputBroadcaster, InputEvent};
 pub use focus::FocusTracker;
-pub use keys::{is_ctrl_backslash, parse_key, ParsedKey};
+pub use handoff::{Controller, Handoff, HandoffAction, HandoffError, HandoffRequest, HandoffStatus};
+pub use keys::{encode_key, encode_keys, is_ctrl_backslash, parse_key, ParsedKey, UnknownKey};
 pub use mode::{InputMode, Mode};
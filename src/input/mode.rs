@@ -39,6 +39,7 @@ use parking_lot::RwLock;
 
 /// The current input routing mode.
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum Mode {
     /// Input goes to both API subscribers and PTY
@@ -0,0 +1,233 @@
+//! Error and warning extraction from session output.
+//!
+//! `GET /sessions/{name}/insights` and the `wsh_get_insights` MCP tool scan
+//! the tail of a session's scrollback with a built-in pack of patterns for
+//! common failure signatures -- compiler errors and warnings, panics,
+//! tracebacks, "permission denied" and the like -- and return each matching
+//! line with its position. It is a cheap pre-filter: an agent can jump
+//! straight to the lines that matter instead of rereading the whole log.
+//!
+//! Each line yields at most one finding, for the leftmost match; when two
+//! patterns match at the same place the earlier one in [`RULES`] wins.
+
+use std::sync::LazyLock;
+
+use regex_automata::meta::Regex;
+use serde::Serialize;
+
+use crate::parser::state::{Format, FormattedLine, Query, QueryResponse};
+use crate::parser::{Parser, ParserError};
+
+/// Lines scanned when the caller doesn't say.
+pub const DEFAULT_SCAN_LINES: usize = 2_000;
+
+/// Most lines a single scan reads, matching the scrollback page limit.
+pub const MAX_SCAN_LINES: usize = 10_000;
+
+/// Findings returned when the caller doesn't say.
+pub const DEFAULT_MAX_FINDINGS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// One signature in the built-in pattern pack.
+pub struct Rule {
+    /// Stable name for the kind of problem, reported as the finding's `kind`.
+    pub kind: &'static str,
+    pub severity: Severity,
+    pub pattern: &'static str,
+}
+
+/// The built-in pattern pack, most specific first.
+pub const RULES: &[Rule] = &[
+    // rustc / cargo: "error[E0308]: mismatched types", "error: could not compile"
+    Rule { kind: "compiler_error", severity: Severity::Error, pattern: r"^error(\[E\d+\])?: " },
+    // gcc, clang, go, javac style: "src/main.c:12:5: error: ..."
+    Rule { kind: "compiler_error", severity: Severity::Error, pattern: r"^\S+:\d+(:\d+)?: (fatal )?error: " },
+    // tsc: "src/app.ts(3,7): error TS2322: ..."
+    Rule { kind: "compiler_error", severity: Severity::Error, pattern: r"\berror TS\d+: " },
+    Rule { kind: "compiler_warning", severity: Severity::Warning, pattern: r"^warning(\[\w+\])?: " },
+    Rule { kind: "compiler_warning", severity: Severity::Warning, pattern: r"^\S+:\d+(:\d+)?: warning: " },
+    // Rust and Go
+    Rule { kind: "panic", severity: Severity::Error, pattern: r"thread '[^']*' panicked at" },
+    Rule { kind: "panic", severity: Severity::Error, pattern: r"^panic: " },
+    Rule { kind: "traceback", severity: Severity::Error, pattern: r"^Traceback \(most recent call last\):" },
+    // Python's last traceback line, Java and JavaScript exceptions
+    Rule { kind: "exception", severity: Severity::Error, pattern: r#"^Exception in thread ""# },
+    Rule { kind: "exception", severity: Severity::Error, pattern: r"^(Uncaught )?([\w.]+\.)?[A-Z]\w*(Error|Exception): " },
+    Rule { kind: "test_failure", severity: Severity::Error, pattern: r"^test .+ \.\.\. FAILED$|^FAILED |^--- FAIL: |^test result: FAILED" },
+    Rule { kind: "permission_denied", severity: Severity::Error, pattern: r"(?i)permission denied|operation not permitted" },
+    Rule { kind: "command_not_found", severity: Severity::Error, pattern: r"command not found|: not found$" },
+    Rule { kind: "no_such_file", severity: Severity::Error, pattern: r"(?i)no such file or directory" },
+    Rule { kind: "crash", severity: Severity::Error, pattern: r"(?i)segmentation fault|core dumped|out of memory" },
+    // git, npm, make
+    Rule { kind: "fatal", severity: Severity::Error, pattern: r"^fatal: |^npm ERR! |^make(\[\d+\])?: \*\*\* " },
+];
+
+static PACK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new_many(&RULES.iter().map(|r| r.pattern).collect::<Vec<_>>()).expect("built-in insight patterns compile"));
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub kind: &'static str,
+    pub severity: Severity,
+    /// Index of the line in the scrollback, as used by `/scrollback`'s
+    /// `offset`.
+    pub line: usize,
+    /// Character offsets of the match within the line.
+    pub start: usize,
+    pub end: usize,
+    /// The whole line, as plain text.
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Report {
+    /// Index of the first line scanned.
+    pub first_line: usize,
+    /// Lines in the scrollback when the scan ran.
+    pub total_lines: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    /// The first findings in scrollback order, up to the requested limit.
+    pub findings: Vec<Finding>,
+    /// Whether findings past the limit were left out. `errors` and
+    /// `warnings` count them all.
+    pub truncated: bool,
+}
+
+/// Scan `lines`, numbered from `first_line`, keeping at most `max_findings`
+/// findings.
+pub fn scan<'a>(
+    first_line: usize,
+    lines: impl IntoIterator<Item = &'a str>,
+    max_findings: usize,
+) -> Report {
+    let mut report = Report { first_line, ..Default::default() };
+    for (i, text) in lines.into_iter().enumerate() {
+        let Some(m) = PACK.find(text) else { continue };
+        let rule = &RULES[m.pattern().as_usize()];
+        match rule.severity {
+            Severity::Error => report.errors += 1,
+            Severity::Warning => report.warnings += 1,
+        }
+        if report.findings.len() == max_findings {
+            report.truncated = true;
+            continue;
+        }
+        report.findings.push(Finding {
+            kind: rule.kind,
+            severity: rule.severity,
+            line: first_line + i,
+            start: text[..m.start()].chars().count(),
+            end: text[..m.end()].chars().count(),
+            text: text.to_string(),
+        });
+    }
+    report
+}
+
+/// Scan the last `lines` lines of a session's scrollback.
+pub async fn scan_parser(parser: &Parser, lines: usize, max_findings: usize) -> Result<Report, ParserError> {
+    let lines = lines.min(MAX_SCAN_LINES);
+    let page = |offset, limit| async move {
+        match parser.query(Query::Scrollback { format: Format::Plain, offset, limit }).await? {
+            QueryResponse::Scrollback(page) => Ok(page),
+            _ => Err(ParserError::InvalidQuery("unexpected scrollback query response".into())),
+        }
+    };
+    let total = page(0, 0).await?.total_lines;
+    let first_line = total.saturating_sub(lines);
+    let page = page(first_line, lines).await?;
+    let texts = page.lines.iter().map(|line| match line {
+        FormattedLine::Plain(text) => text.as_str(),
+        FormattedLine::Styled(_) => "",
+    });
+    let mut report = scan(first_line, texts, max_findings);
+    report.total_lines = page.total_lines;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(text: &str) -> Vec<&'static str> {
+        scan(0, text.lines(), usize::MAX).findings.iter().map(|f| f.kind).collect()
+    }
+
+    #[test]
+    fn pattern_pack_compiles() {
+        assert_eq!(PACK.pattern_len(), RULES.len());
+    }
+
+    #[test]
+    fn recognizes_common_signatures() {
+        let output = "\
+   Compiling app v0.1.0
+error[E0308]: mismatched types
+warning: unused variable: `x`
+src/main.c:12:5: error: expected ';'
+thread 'main' panicked at src/main.rs:4:5:
+Traceback (most recent call last):
+  File \"x.py\", line 1, in <module>
+ValueError: bad value
+bash: foo: command not found
+cp: cannot open 'x' for reading: Permission denied
+test tests::it_works ... FAILED
+fatal: not a git repository
+all good here";
+        assert_eq!(
+            kinds(output),
+            [
+                "compiler_error",
+                "compiler_warning",
+                "compiler_error",
+                "panic",
+                "traceback",
+                "exception",
+                "command_not_found",
+                "permission_denied",
+                "test_failure",
+                "fatal",
+            ]
+        );
+    }
+
+    #[test]
+    fn findings_carry_positions_and_counts() {
+        let lines = ["ok", "héllo: Permission denied", "warning: unused", "ok"];
+        let report = scan(40, lines, 1);
+        assert_eq!((report.errors, report.warnings), (1, 1));
+        assert!(report.truncated);
+        let finding = &report.findings[0];
+        assert_eq!(finding.line, 41);
+        assert_eq!((finding.start, finding.end), (7, 24));
+        assert_eq!(finding.severity, Severity::Error);
+        assert_eq!(finding.text, "héllo: Permission denied");
+    }
+
+    #[tokio::test]
+    async fn scans_the_tail_of_the_scrollback() {
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let parser = Parser::spawn(rx, 40, 5, 1000);
+        tx.send(bytes::Bytes::from("error: early\r\n$ make\r\nsrc/a.c:3:1: error: oops\r\n$ "))
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        // The scrollback includes the whole 5-row screen.
+        let report = scan_parser(&parser, 3, 10).await.unwrap();
+        assert_eq!(report.total_lines, 5);
+        assert_eq!(report.first_line, 2);
+        assert_eq!(report.findings.len(), 1);
+        assert_eq!(report.findings[0].line, 2);
+        assert_eq!(report.findings[0].kind, "compiler_error");
+
+        assert_eq!(scan_parser(&parser, 100, 10).await.unwrap().errors, 2);
+    }
+}
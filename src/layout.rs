@@ -0,0 +1,116 @@
+//! Layout files: several sessions created by one `wsh --layout` invocation.
+//!
+//! ```toml
+//! [[session]]
+//! name = "planner"
+//! command = "claude"
+//! cwd = "/home/me/src/app"
+//! tags = ["agent"]
+//!
+//! [[session]]
+//! name = "build"
+//! command = "cargo watch -x test"
+//! env = { RUST_LOG = "info" }
+//! ```
+//!
+//! Sessions are created in file order and the client attaches to the first.
+//! Sessions that already exist on the server are left as they are, so
+//! running the same layout again just reattaches.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Layout {
+    #[serde(default, rename = "session")]
+    pub sessions: Vec<LayoutSession>,
+}
+
+/// One session in a layout file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutSession {
+    pub name: String,
+    /// Command to run instead of the shell.
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub env: Option<HashMap<String, String>>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl Layout {
+    /// Read and check a layout file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read layout {}: {}", path.display(), e))?;
+        Self::parse(&contents).map_err(|e| format!("invalid layout {}: {}", path.display(), e))
+    }
+
+    /// Parse a layout, requiring at least one session and distinct,
+    /// non-empty names.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let layout: Layout = toml::from_str(contents).map_err(|e| e.message().to_string())?;
+        if layout.sessions.is_empty() {
+            return Err("no [[session]] entries".to_string());
+        }
+        let mut seen = std::collections::HashSet::new();
+        for session in &layout.sessions {
+            if session.name.trim().is_empty() {
+                return Err("session names must not be empty".to_string());
+            }
+            if !seen.insert(session.name.as_str()) {
+                return Err(format!("session '{}' is listed more than once", session.name));
+            }
+        }
+        Ok(layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sessions_in_order() {
+        let layout = Layout::parse(
+            r#"
+            [[session]]
+            name = "planner"
+            command = "claude"
+            tags = ["agent", "lead"]
+
+            [[session]]
+            name = "build"
+            cwd = "/tmp"
+            env = { RUST_LOG = "info" }
+            "#,
+        )
+        .unwrap();
+        let names: Vec<&str> = layout.sessions.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["planner", "build"]);
+        assert_eq!(layout.sessions[0].command.as_deref(), Some("claude"));
+        assert_eq!(layout.sessions[0].tags, vec!["agent", "lead"]);
+        assert_eq!(layout.sessions[1].cwd.as_deref(), Some("/tmp"));
+        assert_eq!(layout.sessions[1].env.as_ref().unwrap()["RUST_LOG"], "info");
+    }
+
+    #[test]
+    fn rejects_empty_and_duplicate_layouts() {
+        assert!(Layout::parse("").unwrap_err().contains("no [[session]]"));
+        let dup = "[[session]]\nname = \"a\"\n[[session]]\nname = \"a\"\n";
+        assert!(Layout::parse(dup).unwrap_err().contains("more than once"));
+        assert!(Layout::parse("[[session]]\nname = \" \"\n").is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        assert!(Layout::parse("[[session]]\nname = \"a\"\ncmd = \"ls\"\n").is_err());
+    }
+}
@@ -1,18 +1,41 @@
 pub mod activity;
 pub mod config;
 pub mod api;
+pub mod bridge;
+pub mod bookmarks;
+pub mod bundle;
 pub mod federation;
+pub mod files;
+pub mod hooks;
 pub mod broker;
 pub mod client;
+pub mod completions;
 pub mod input;
+pub mod insights;
+pub mod layout;
+pub mod logs;
+#[cfg(feature = "mcp")]
 pub mod mcp;
+pub mod orchestrate;
 pub mod overlay;
 pub mod panel;
 pub mod parser;
+pub mod pipe;
+pub mod policy;
+pub mod proctree;
 pub mod protocol;
 pub mod pty;
+#[cfg(feature = "remote-attach")]
+pub mod remote;
+pub mod safe_mode;
+pub mod sandbox;
 pub mod server;
 pub mod session;
+pub mod status_line;
 pub mod shutdown;
+pub mod ssh;
 pub mod terminal;
+pub mod templates;
 pub mod tls;
+pub mod tmux;
+pub mod usage;
@@ -0,0 +1,82 @@
+//! The most recent log lines, kept in memory.
+//!
+//! A server started in the background has its stderr discarded, so its log
+//! output is otherwise lost. Tracing also writes every line to a bounded
+//! buffer, served by `GET /server/logs` and collected by
+//! `wsh debug-bundle`.
+
+use std::collections::VecDeque;
+use std::io;
+use std::sync::LazyLock;
+
+use parking_lot::Mutex;
+
+/// Lines kept; older lines are dropped first.
+pub const RECENT_LOG_LINES: usize = 2000;
+
+static RECENT: LazyLock<RecentLogs> = LazyLock::new(|| RecentLogs::new(RECENT_LOG_LINES));
+
+/// A bounded buffer of log lines.
+pub struct RecentLogs {
+    lines: Mutex<VecDeque<String>>,
+    capacity: usize,
+}
+
+impl RecentLogs {
+    pub fn new(capacity: usize) -> Self {
+        Self { lines: Mutex::new(VecDeque::with_capacity(capacity)), capacity }
+    }
+
+    /// Append the lines in `text`, dropping the oldest beyond capacity.
+    pub fn push(&self, text: &str) {
+        let mut lines = self.lines.lock();
+        for line in text.lines().filter(|l| !l.is_empty()) {
+            if lines.len() == self.capacity {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_string());
+        }
+    }
+
+    /// The last `limit` lines, oldest first.
+    pub fn tail(&self, limit: usize) -> Vec<String> {
+        let lines = self.lines.lock();
+        lines.iter().skip(lines.len().saturating_sub(limit)).cloned().collect()
+    }
+}
+
+/// The process-wide buffer that [`Writer`] appends to.
+pub fn recent() -> &'static RecentLogs {
+    &RECENT
+}
+
+/// An [`io::Write`] sink for `tracing_subscriber::fmt` that appends to
+/// [`recent()`]. Each formatted event arrives in a single write.
+pub struct Writer;
+
+impl io::Write for Writer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        RECENT.push(&String::from_utf8_lossy(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_newest_lines() {
+        let logs = RecentLogs::new(3);
+        logs.push("one\n");
+        logs.push("two\nthree\n\n");
+        logs.push("four\n");
+        assert_eq!(logs.tail(10), vec!["two", "three", "four"]);
+        assert_eq!(logs.tail(1), vec!["four"]);
+        assert!(logs.tail(0).is_empty());
+    }
+}
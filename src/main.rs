@@ -18,6 +18,7 @@ use std::path::PathBuf;
 use std::sync::Arc;
 use thiserror::Error;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use wsh::client::output::{Output, OutputFormat};
 use wsh::{
     api, client, protocol,
     protocol::{AttachSessionMsg, ScrollbackRequest},
@@ -65,6 +66,10 @@ struct Cli {
     #[arg(long = "tag")]
     tags: Vec<String>,
 
+    /// Create the sessions listed in a layout file and attach to the first
+    #[arg(long, conflicts_with_all = ["cmd", "name", "tags", "shell"])]
+    layout: Option<PathBuf>,
+
     /// Use alternate screen buffer (restores previous screen on exit, but
     /// disables native terminal scrollback while wsh is running)
     #[arg(long)]
@@ -77,6 +82,11 @@ struct Cli {
     /// Path to the Unix domain socket (overrides -L)
     #[arg(long, global = true)]
     socket: Option<PathBuf>,
+
+    /// How subcommands print results and errors: `text`, or `json` for one
+    /// JSON document on stdout, errors included
+    #[arg(long, value_enum, global = true, default_value = "text")]
+    output: OutputFormat,
 }
 
 #[derive(Subcommand, Debug)]
@@ -117,10 +127,30 @@ enum Commands {
         #[arg(long, env = "WSH_CONFIG")]
         config: Option<PathBuf>,
 
+        /// Keep servers added or removed at runtime in memory only, instead
+        /// of writing them back to the config file's [[servers]]
+        #[arg(long, env = "WSH_NO_PERSIST")]
+        no_persist: bool,
+
+        /// Join the hub at this URL as a relayed backend (e.g.
+        /// wss://hub.example.com), dialing out to it instead of waiting for
+        /// it to connect. For servers behind NAT.
+        #[arg(long, env = "WSH_JOIN")]
+        join: Option<String>,
+
+        /// Token for the hub named by --join
+        #[arg(long, env = "WSH_JOIN_TOKEN", requires = "join")]
+        join_token: Option<String>,
+
         /// Override system hostname for server identity
         #[arg(long, env = "WSH_HOSTNAME")]
         hostname: Option<String>,
 
+        /// Message of the day written into every new session and shown to
+        /// attaching clients. `{hostname}` expands to the server hostname.
+        #[arg(long, env = "WSH_MOTD")]
+        motd: Option<String>,
+
         /// Base path prefix for all API routes (e.g. "/wsh" for reverse-proxy deployment).
         /// Must start with "/" and must NOT end with "/".
         #[arg(long, env = "WSH_BASE_PREFIX")]
@@ -148,13 +178,56 @@ enum Commands {
         /// disables native terminal scrollback while wsh is running)
         #[arg(long)]
         alt_screen: bool,
+
+        /// Attach over HTTP to the server at this URL (e.g.
+        /// http://host:8080) instead of through the local Unix socket
+        #[arg(long)]
+        url: Option<String>,
+
+        /// Bearer token for the server given with --url
+        #[arg(long, env = "WSH_TOKEN")]
+        token: Option<String>,
+    },
+
+    /// Pick a session from a list and attach to it
+    Switch {
+        /// Session to attach to, skipping the picker
+        name: Option<String>,
+
+        /// Use alternate screen buffer (restores previous screen on exit, but
+        /// disables native terminal scrollback while wsh is running)
+        #[arg(long)]
+        alt_screen: bool,
     },
 
+    /// Show a live dashboard of the sessions on the server, with a preview
+    /// of each one's output. Enter attaches, K kills, t edits tags
+    Top,
+
     /// List active sessions on the server
     List {
         /// Target a specific federated server by hostname
         #[arg(short, long)]
         server: Option<String>,
+
+        /// Print the sessions as JSON
+        #[arg(long, conflicts_with_all = ["format", "columns", "wide"])]
+        json: bool,
+
+        /// Print one line per session from a template, e.g. '{name}\t{cwd}'.
+        /// Fields are the column names and meta.<key>
+        #[arg(long, conflicts_with_all = ["columns", "wide"])]
+        format: Option<String>,
+
+        /// Comma-separated columns to show: name, server, pid, command,
+        /// title, cwd, size, rows, cols, clients, state, age, idle,
+        /// created_at, last_activity_at, tags, or meta.<key>
+        #[arg(short = 'o', long, value_delimiter = ',', conflicts_with = "wide")]
+        columns: Vec<String>,
+
+        /// Show every column
+        #[arg(short, long)]
+        wide: bool,
     },
 
     /// Kill (destroy) a session on the server
@@ -165,6 +238,30 @@ enum Commands {
         /// Target a specific federated server by hostname
         #[arg(short, long)]
         server: Option<String>,
+
+        /// Send this signal (TERM, INT, HUP, ...) and SIGKILL only if the
+        /// process outlives the grace period
+        #[arg(long)]
+        signal: Option<String>,
+
+        /// Grace period before SIGKILL (e.g. 5s, 1m); defaults to 5s with
+        /// --signal, which defaults to TERM with --grace
+        #[arg(long, value_parser = wsh::config::parse_duration)]
+        grace: Option<std::time::Duration>,
+    },
+
+    /// Print a session's output, optionally following new output
+    Logs {
+        /// Session name to read
+        name: String,
+
+        /// Keep printing new output until the session ends
+        #[arg(short, long)]
+        follow: bool,
+
+        /// Start this many lines before the end
+        #[arg(short = 'n', long)]
+        lines: Option<usize>,
     },
 
     /// Detach all clients from a session (session stays alive)
@@ -185,18 +282,111 @@ enum Commands {
     Persist {
         /// "on" or "off". Omit to query without changing.
         value: Option<String>,
+    },
+
+    /// Run a session method of the WebSocket JSON API over the Unix socket
+    /// and print its result as JSON, e.g. `wsh call build get_screen`,
+    /// `wsh call build resize '{"rows":40,"cols":120}'`
+    Call {
+        /// Session name
+        name: String,
+
+        /// Method name, as for the WebSocket JSON API (get_screen,
+        /// get_scrollback, resize, create_overlay, update_panel, ...)
+        method: String,
+
+        /// Method parameters as a JSON object
+        params: Option<String>,
+    },
 
+    /// Show a running server's version, uptime, load, and resource usage
+    Status {
         /// Address of the HTTP/WebSocket API server
         #[arg(long, default_value = "127.0.0.1:8080")]
         bind: SocketAddr,
 
-        /// Authentication token
+        /// Authentication token (defaults to the server's root token,
+        /// retrieved via the Unix socket)
+        #[arg(long, env = "WSH_TOKEN")]
+        token: Option<String>,
+
+        /// Print the raw JSON from GET /server/info
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Write server status, recent logs, session metadata, config and
+    /// version info to a tarball for bug reports. Credentials are redacted,
+    /// and terminal contents are left out unless `--include-output` is given.
+    DebugBundle {
+        /// Where to write the bundle (default: wsh-debug-<timestamp>.tar.gz)
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+
+        /// Also include each session's screen, scrollback and input audit log
+        #[arg(long)]
+        include_output: bool,
+
+        /// Server config file to include (default: the server's default path)
+        #[arg(long, env = "WSH_CONFIG")]
+        config: Option<PathBuf>,
+
+        /// Address of the HTTP/WebSocket API server
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        bind: SocketAddr,
+
+        /// Authentication token (defaults to the server's root token,
+        /// retrieved via the Unix socket)
+        #[arg(long, env = "WSH_TOKEN")]
+        token: Option<String>,
+    },
+
+    /// Wrap existing tmux sessions in wsh sessions so they can be reached
+    /// through the API. Each runs `tmux attach-session` and is named
+    /// `tmux-<name>`; with `--control`, each pane gets its own session
+    /// instead, named `tmux-<name>-<window>[.<pane>]`.
+    ImportTmux {
+        /// tmux sessions to import (all if omitted)
+        sessions: Vec<String>,
+
+        /// tmux server socket name (like `tmux -L`)
+        #[arg(long)]
+        tmux_socket: Option<String>,
+
+        /// Mirror each pane through tmux control mode instead of running a
+        /// tmux client (needs tmux 3.2 or later)
+        #[arg(long)]
+        control: bool,
+
+        /// Extra tags for the imported sessions (can be specified multiple times)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+
+        /// Address of the HTTP/WebSocket API server
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        bind: SocketAddr,
+
+        /// Authentication token (defaults to the server's root token,
+        /// retrieved via the Unix socket)
         #[arg(long, env = "WSH_TOKEN")]
         token: Option<String>,
     },
 
-    /// Print the server's auth token (retrieved via Unix socket)
-    Token {},
+    /// Print the server's auth token (retrieved via Unix socket), or manage
+    /// scoped API tokens
+    Token {
+        #[command(subcommand)]
+        action: Option<TokenAction>,
+
+        /// Address of the HTTP/WebSocket API server
+        #[arg(long, global = true, default_value = "127.0.0.1:8080")]
+        bind: SocketAddr,
+
+        /// Admin token to authenticate with (defaults to the server's root
+        /// token, retrieved via the Unix socket)
+        #[arg(long, global = true, env = "WSH_TOKEN")]
+        token: Option<String>,
+    },
 
     /// Manage tags on a session
     Tag {
@@ -219,6 +409,31 @@ enum Commands {
     /// Stop the running wsh server
     Stop {},
 
+    /// Find auto-spawned servers that can no longer be reached and stop them
+    Gc {
+        /// Report what would be cleaned up without doing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Print a shell completion script
+    ///
+    /// In bash, zsh and fish, session names complete from the running
+    /// server. Add `source <(wsh completions bash)` (or zsh) to your shell's
+    /// rc file, or `wsh completions fish | source` to config.fish.
+    Completions {
+        /// Shell to generate the script for
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+
+    /// Print completion candidates for the scripts from `wsh completions`
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        /// What to complete: "sessions"
+        kind: String,
+    },
+
     /// Manage federated backend servers
     Servers {
         /// Action to perform
@@ -227,6 +442,7 @@ enum Commands {
     },
 
     /// Start an MCP server over stdio (for AI hosts like Claude Desktop)
+    #[cfg(feature = "mcp")]
     Mcp {
         /// Address to bind the HTTP/WebSocket API server (for auto-spawn)
         #[arg(long, default_value = "127.0.0.1:8080")]
@@ -238,6 +454,32 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand, Debug)]
+enum TokenAction {
+    /// Create a scoped API token and print its secret
+    Create {
+        /// Name identifying the token
+        name: String,
+
+        /// What the token may do: "read-only", "input", or "admin"
+        #[arg(long, default_value = "read-only")]
+        scope: String,
+
+        /// Restrict the token to sessions carrying this tag (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+    },
+
+    /// List scoped API tokens
+    List,
+
+    /// Revoke a scoped API token
+    Revoke {
+        /// Name of the token to revoke
+        name: String,
+    },
+}
+
 #[derive(Subcommand, Debug)]
 enum ServersAction {
     /// List all servers (local + federated backends)
@@ -299,6 +541,14 @@ fn resolve_socket_path(socket: Option<PathBuf>, server_name: &str) -> PathBuf {
 /// are rejected to prevent accidental auth bypass (e.g. `WSH_TOKEN=""`).
 const MIN_TOKEN_LENGTH: usize = 16;
 
+/// Upper bound on `[mcp] unhealthy_backend_wait`, so a typo can't stall tool
+/// calls indefinitely.
+const MAX_UNHEALTHY_BACKEND_WAIT: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// Upper bound on `[sessions] warm_pool`. Each pooled shell holds a PTY and
+/// three blocking threads without counting against `--max-sessions`.
+const MAX_WARM_POOL: usize = 32;
+
 fn resolve_token(bind: &SocketAddr, user_token: &Option<String>, no_auth: bool) -> Result<Option<String>, WshError> {
     if is_loopback(bind) {
         return Ok(None);
@@ -333,11 +583,45 @@ fn resolve_token(bind: &SocketAddr, user_token: &Option<String>, no_auth: bool)
 
 #[tokio::main]
 async fn main() -> Result<(), WshError> {
+    // Sessions spawned as another user start as `wsh __run-as ...`, which
+    // drops privileges and execs the real program.
+    // Sessions with a `nice`/`ionice` setting likewise start as
+    // `wsh __priority ...`, and panes imported from tmux in control mode
+    // as `wsh __tmux-bridge ...`.
+    let mut args = std::env::args_os().skip(1);
+    match args.next() {
+        Some(arg) if arg == wsh::pty::RUN_AS_ARG => {
+            let err = wsh::pty::exec_as(args);
+            eprintln!("wsh: cannot start session as another user: {err}");
+            std::process::exit(127);
+        }
+        Some(arg) if arg == wsh::pty::PRIORITY_ARG => {
+            let err = wsh::pty::exec_with_priority(args);
+            eprintln!("wsh: cannot set session priority: {err}");
+            std::process::exit(127);
+        }
+        Some(arg) if arg == wsh::bridge::tmux::BRIDGE_ARG => {
+            // Exit outright: the runtime would otherwise wait on the
+            // relay's pending stdin read before shutting down.
+            if let Err(err) = wsh::bridge::tmux::run(args).await {
+                eprintln!("wsh: tmux bridge: {err}");
+                std::process::exit(1);
+            }
+            std::process::exit(0);
+        }
+        _ => {}
+    }
+
     let cli = Cli::parse();
 
     // MCP mode: tracing must use stderr since stdout is for MCP protocol
+    #[cfg(feature = "mcp")]
     let is_mcp = matches!(cli.command, Some(Commands::Mcp { .. }));
-    if is_mcp {
+    #[cfg(not(feature = "mcp"))]
+    let is_mcp = false;
+    // Completion output is read by the shell, so it must be clean too.
+    let is_completion = matches!(cli.command, Some(Commands::Completions { .. } | Commands::Complete { .. }));
+    if is_mcp || is_completion {
         init_tracing_stderr();
     } else {
         init_tracing();
@@ -347,38 +631,89 @@ async fn main() -> Result<(), WshError> {
     // passed before or after the subcommand (e.g. `wsh -L foo list`).
     let socket = cli.socket.clone();
     let server_name = cli.server_name.clone();
+    let output = cli.output;
+    let out = |command| Output::new(output, command);
 
     match cli.command {
-        Some(Commands::Server { bind, token, no_auth, ephemeral, max_sessions, cors_origins, rate_limit, config, hostname, base_prefix, tls_cert, tls_key }) => {
-            run_server(bind, token, no_auth, socket, ephemeral, max_sessions, server_name, cors_origins, rate_limit, config, hostname, base_prefix, tls_cert, tls_key).await
+        Some(Commands::Server { bind, token, no_auth, ephemeral, max_sessions, cors_origins, rate_limit, config, no_persist, join, join_token, hostname, motd, base_prefix, tls_cert, tls_key }) => {
+            run_server(bind, token, no_auth, socket, ephemeral, max_sessions, server_name, cors_origins, rate_limit, config, no_persist, join, join_token, hostname, motd, base_prefix, tls_cert, tls_key).await
+        }
+        Some(Commands::Attach { name, scrollback, alt_screen, url, token }) => match url {
+            #[cfg(feature = "remote-attach")]
+            Some(url) => run_remote_attach(name, scrollback, alt_screen, url, token).await,
+            #[cfg(not(feature = "remote-attach"))]
+            Some(_) => {
+                let _ = token;
+                Err(WshError::Config("wsh was built without remote attach support".into()))
+            }
+            None => run_attach(name, scrollback, socket, alt_screen, server_name).await,
+        },
+        Some(Commands::Switch { name, alt_screen }) => {
+            run_switch(name, socket, alt_screen, server_name).await
+        }
+        Some(Commands::Top) => {
+            run_top(socket, server_name).await
+        }
+        Some(Commands::List { server, json, format, columns, wide }) => {
+            let out = if json { Output::new(OutputFormat::Json, "list") } else { out("list") };
+            run_list(socket, server_name, server, format, columns, wide, out).await
+        }
+        Some(Commands::Kill { name, server, signal, grace }) => {
+            let msg = protocol::KillSessionMsg {
+                name,
+                server,
+                signal,
+                grace_ms: grace.map(|g| g.as_millis() as u64),
+            };
+            run_kill(msg, socket, server_name, out("kill")).await
         }
-        Some(Commands::Attach { name, scrollback, alt_screen }) => {
-            run_attach(name, scrollback, socket, alt_screen, server_name).await
+        Some(Commands::Logs { name, follow, lines }) => {
+            run_logs(name, follow, lines, socket, server_name, out("logs")).await
         }
-        Some(Commands::List { server }) => {
-            run_list(socket, server_name, server).await
+        Some(Commands::Detach { name, server }) => {
+            run_detach(name, socket, server_name, server, out("detach")).await
         }
-        Some(Commands::Kill { name, server }) => {
-            run_kill(name, socket, server_name, server).await
+        Some(Commands::Token { action: None, .. }) => {
+            run_token(socket, server_name, out("token")).await
         }
-        Some(Commands::Detach { name, server }) => {
-            run_detach(name, socket, server_name, server).await
+        Some(Commands::Token { action: Some(action), bind, token }) => {
+            run_token_action(action, bind, token, socket, server_name, out("token")).await
+        }
+        Some(Commands::Persist { value }) => {
+            run_persist(value, socket, server_name, out("persist")).await
+        }
+        Some(Commands::Call { name, method, params }) => {
+            run_call(name, method, params, socket, server_name, out("call")).await
         }
-        Some(Commands::Token {}) => {
-            run_token(socket, server_name).await
+        Some(Commands::Status { bind, token, json }) => {
+            run_status(bind, token, json, socket, server_name, out("status")).await
         }
-        Some(Commands::Persist { value, bind, token }) => {
-            run_persist(value, bind, token).await
+        Some(Commands::DebugBundle { output, include_output, config, bind, token }) => {
+            run_debug_bundle(output, include_output, config, bind, token, socket, server_name).await
+        }
+        Some(Commands::ImportTmux { sessions, tmux_socket, control, tags, bind, token }) => {
+            run_import_tmux(sessions, tmux_socket, control, tags, bind, token, socket, server_name).await
         }
         Some(Commands::Tag { name, add, remove, server }) => {
-            run_tag(name, add, remove, server, socket, server_name).await
+            run_tag(name, add, remove, server, socket, server_name, out("tag")).await
         }
         Some(Commands::Stop {}) => {
-            run_stop(socket, server_name).await
+            run_stop(socket, server_name, out("stop")).await
+        }
+        Some(Commands::Gc { dry_run }) => {
+            run_gc(dry_run, out("gc")).await
         }
         Some(Commands::Servers { action }) => {
-            run_servers(action, socket, server_name).await
+            run_servers(action, socket, server_name, output).await
+        }
+        Some(Commands::Completions { shell }) => {
+            let mut cmd = <Cli as clap::CommandFactory>::command();
+            wsh::completions::generate(shell, &mut cmd, &mut std::io::stdout()).map_err(WshError::Io)
+        }
+        Some(Commands::Complete { kind }) => {
+            run_complete(kind, socket, server_name).await
         }
+        #[cfg(feature = "mcp")]
         Some(Commands::Mcp { bind, token }) => {
             run_mcp(bind, socket, token, server_name).await
         }
@@ -394,6 +729,8 @@ fn init_tracing() {
             std::env::var("RUST_LOG").unwrap_or_else(|_| "wsh=info,tower_http=info".into()),
         ))
         .with(tracing_subscriber::fmt::layer())
+        // Also keep recent lines in memory for GET /server/logs.
+        .with(tracing_subscriber::fmt::layer().with_ansi(false).with_writer(|| wsh::logs::Writer))
         .init();
 }
 
@@ -412,7 +749,22 @@ fn init_tracing_stderr() {
 
 // ── Server mode ────────────────────────────────────────────────────
 
+/// The server's config file when `--config` is not given.
+fn default_config_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| {
+            // dirs::config_dir() returns None only in minimal environments
+            // (no HOME, no XDG_CONFIG_HOME). Fall back to $HOME/.config.
+            std::env::var("HOME")
+                .map(|h| PathBuf::from(h).join(".config"))
+                .unwrap_or_else(|_| PathBuf::from("/etc"))
+        })
+        .join("wsh")
+        .join("config.toml")
+}
+
 /// Run the wsh server daemon: HTTP/WS + Unix socket, no local terminal.
+#[allow(clippy::too_many_arguments)]
 async fn run_server(
     bind: SocketAddr,
     token: Option<String>,
@@ -424,7 +776,11 @@ async fn run_server(
     cors_origins: Vec<String>,
     rate_limit: Option<u32>,
     config_arg: Option<PathBuf>,
+    no_persist: bool,
+    join: Option<String>,
+    join_token: Option<String>,
     hostname_arg: Option<String>,
+    motd_arg: Option<String>,
     base_prefix: Option<String>,
     tls_cert: Option<PathBuf>,
     tls_key: Option<PathBuf>,
@@ -441,6 +797,17 @@ async fn run_server(
         }
     }
 
+    #[cfg(feature = "federation")]
+    let join = join
+        .map(|url| wsh::federation::relay::join_url(&url))
+        .transpose()
+        .map_err(WshError::Config)?;
+    #[cfg(not(feature = "federation"))]
+    if join.is_some() {
+        let _ = join_token;
+        return Err(WshError::Config("wsh was built without federation support".into()));
+    }
+
     // Load TLS configuration if cert + key are provided.
     let tls_acceptor = match (tls_cert, tls_key) {
         (Some(cert), Some(key)) => {
@@ -477,18 +844,7 @@ async fn run_server(
     };
 
     // Resolve config path: CLI arg, else platform config dir
-    let config_path = config_arg.unwrap_or_else(|| {
-        dirs::config_dir()
-            .unwrap_or_else(|| {
-                // dirs::config_dir() returns None only in minimal environments
-                // (no HOME, no XDG_CONFIG_HOME). Fall back to $HOME/.config.
-                std::env::var("HOME")
-                    .map(|h| PathBuf::from(h).join(".config"))
-                    .unwrap_or_else(|_| PathBuf::from("/etc"))
-            })
-            .join("wsh")
-            .join("config.toml")
-    });
+    let config_path = config_arg.unwrap_or_else(default_config_path);
 
     // Load federation config (optional — missing file is fine)
     let fed_config = wsh::config::FederationConfig::load(&config_path)
@@ -500,14 +856,143 @@ async fn run_server(
     let hostname = hostname_arg
         .or_else(|| fed_config.as_ref()?.server.as_ref()?.hostname.clone())
         .unwrap_or_else(|| wsh::config::resolve_hostname(None));
+    // MOTD: CLI arg > config file
+    let motd = motd_arg
+        .or_else(|| fed_config.as_ref()?.server.as_ref()?.motd.clone())
+        .map(|text| text.replace("{hostname}", &hostname));
+    let client_policy = match fed_config.as_ref().and_then(|c| c.client.clone()) {
+        Some(mut policy) => match policy.detach_sequences() {
+            Ok(_) => {
+                if let Err(e) = policy.command_sequence() {
+                    eprintln!("Warning: invalid [client] command_key ({}), the command palette is disabled", e);
+                    policy.command_key = None;
+                }
+                if let Err(e) = policy.focus_sequence() {
+                    eprintln!("Warning: invalid [client] focus_key ({}), focus cycling is disabled", e);
+                    policy.focus_key.clear();
+                }
+                policy
+            }
+            Err(e) => {
+                eprintln!("Warning: invalid [client] detach_keys ({}), using the default policy", e);
+                wsh::protocol::ClientPolicy::default()
+            }
+        },
+        None => wsh::protocol::ClientPolicy::default(),
+    };
+    // A bad default is kept rather than dropped: sessions then fail to
+    // create instead of quietly running unsandboxed.
+    let sandbox = fed_config.as_ref().and_then(|c| c.sandbox.clone()).unwrap_or_default();
+    let status_line = match fed_config.as_ref().and_then(|c| c.status_line.clone()) {
+        Some(status_line) => match status_line.validate() {
+            Ok(()) => status_line,
+            Err(e) => {
+                eprintln!("Warning: invalid [status_line] config ({}), the status line is disabled", e);
+                wsh::status_line::StatusLineConfig::default()
+            }
+        },
+        None => wsh::status_line::StatusLineConfig::default(),
+    };
+    if let Err(e) = sandbox.validate() {
+        eprintln!("Warning: invalid [sandbox] config: {}", e);
+    }
+    let hooks = match fed_config.as_ref().and_then(|c| c.hooks.as_ref()) {
+        Some(hooks) => hooks.resolve().unwrap_or_else(|e| {
+            eprintln!("Warning: invalid [hooks] config ({}), hooks are disabled", e);
+            wsh::hooks::Hooks::default()
+        }),
+        None => wsh::hooks::Hooks::default(),
+    };
+    let idle_kill_after = fed_config
+        .as_ref()
+        .and_then(|c| c.sessions.as_ref()?.idle_kill_after.as_deref())
+        .and_then(|spec| match wsh::config::parse_duration(spec) {
+            Ok(after) => Some(after).filter(|after| !after.is_zero()),
+            Err(e) => {
+                eprintln!("Warning: invalid [sessions] idle_kill_after ({}), idle sessions will not be reaped", e);
+                None
+            }
+        });
+    let tombstone_retention = fed_config
+        .as_ref()
+        .and_then(|c| c.sessions.as_ref()?.tombstone_retention.as_deref())
+        .and_then(|spec| match wsh::config::parse_duration(spec) {
+            Ok(retention) => Some(retention),
+            Err(e) => {
+                eprintln!("Warning: invalid [sessions] tombstone_retention ({}), using the default", e);
+                None
+            }
+        })
+        .unwrap_or(wsh::session::DEFAULT_TOMBSTONE_RETENTION);
+    let scrollback_lines = fed_config
+        .as_ref()
+        .and_then(|c| c.sessions.as_ref()?.scrollback_lines)
+        .and_then(|lines| match wsh::session::validate_scrollback_lines(lines) {
+            Ok(lines) => Some(lines),
+            Err(e) => {
+                eprintln!("Warning: invalid [sessions] scrollback_lines ({}), using the default", e);
+                None
+            }
+        })
+        .unwrap_or(wsh::session::DEFAULT_SCROLLBACK_LINES);
+    let warm_pool = fed_config
+        .as_ref()
+        .and_then(|c| c.sessions.as_ref()?.warm_pool)
+        .map(|size| {
+            if size > MAX_WARM_POOL {
+                eprintln!("Warning: [sessions] warm_pool ({}) is larger than {}, using {}", size, MAX_WARM_POOL, MAX_WARM_POOL);
+            }
+            size.min(MAX_WARM_POOL)
+        })
+        .unwrap_or(0);
+    // Unlike the settings above, broken rules stop the server: starting
+    // without the guardrails someone configured is worse than not starting.
+    let policy = wsh::policy::Policy::default();
+    let policy_file = fed_config.as_ref().and_then(|c| c.policy.as_ref()?.file.clone());
+    if let Some(ref path) = policy_file {
+        policy
+            .load(path)
+            .map_err(|e| WshError::Config(format!("invalid [policy] file: {}", e)))?;
+        let (block, redact) = policy.counts();
+        tracing::info!(path = %path.display(), block, redact, "policy rules loaded");
+    }
+    let unhealthy_backend_wait = fed_config
+        .as_ref()
+        .and_then(|c| c.mcp.as_ref()?.unhealthy_backend_wait.as_deref())
+        .and_then(|spec| match wsh::config::parse_duration(spec) {
+            Ok(wait) => Some(wait.min(MAX_UNHEALTHY_BACKEND_WAIT)),
+            Err(e) => {
+                eprintln!("Warning: invalid [mcp] unhealthy_backend_wait ({}), failing at once", e);
+                None
+            }
+        })
+        .unwrap_or_default();
+    let health_check = fed_config
+        .as_ref()
+        .and_then(|c| c.health_check.as_ref())
+        .and_then(|section| match section.resolve() {
+            Ok(health_check) => Some(health_check),
+            Err(e) => {
+                eprintln!("Warning: invalid [health_check] config ({}), using the defaults", e);
+                None
+            }
+        })
+        .unwrap_or_default();
 
     let fed_config = fed_config.unwrap_or_default();
+    let mcp_config = fed_config.mcp.clone().unwrap_or_default();
+    let files_config = fed_config.files.clone().unwrap_or_default();
+    let websocket_config = fed_config.websocket.clone().unwrap_or_default();
+    if files_config.enabled() {
+        tracing::info!(allow = ?files_config.allow, "file transfer enabled");
+    }
     tracing::info!(hostname = %hostname, config = %config_path.display(), "server identity resolved");
 
     // Save default_token before fed_config is consumed by FederationManager.
     let fed_default_token = fed_config.default_token.clone();
 
     // Build IP access control from config (if configured).
+    #[cfg(feature = "federation")]
     let ip_access_control = fed_config.ip_access.as_ref().map(|cfg| {
         let ctrl = wsh::federation::ip_access::IpAccessControl::from_config(cfg);
         if ctrl.is_unconfigured() {
@@ -525,6 +1010,7 @@ async fn run_server(
     });
 
     // Warn if non-loopback with no ip_access config at all.
+    #[cfg(feature = "federation")]
     if !is_loopback(&bind) && ip_access_control.is_none() {
         tracing::warn!(
             "Binding to non-loopback address without IP access control. \
@@ -537,14 +1023,33 @@ async fn run_server(
 
     // Create the FederationManager: spawns persistent WebSocket connections
     // for each configured backend server.
-    let federation_manager = Arc::new(tokio::sync::Mutex::new(
-        wsh::federation::manager::FederationManager::from_config(
+    #[cfg(feature = "federation")]
+    let federation_manager = {
+        let mut manager = wsh::federation::manager::FederationManager::from_config(
             fed_config,
             token.clone(),
             fed_default_token.clone(),
             server_id.clone(),
-        ),
-    ));
+            health_check,
+        );
+        if !no_persist {
+            manager.set_config_path(Some(config_path.clone()));
+        }
+        Arc::new(tokio::sync::Mutex::new(manager))
+    };
+    #[cfg(feature = "federation")]
+    let backends = federation_manager.lock().await.registry().clone();
+    #[cfg(not(feature = "federation"))]
+    let backends = {
+        let _ = no_persist;
+        if !fed_config.servers.is_empty() || fed_config.ip_access.is_some() {
+            eprintln!("Warning: wsh was built without federation support, ignoring [[servers]] and [ip_access]");
+        }
+        let backends = wsh::federation::registry::BackendRegistry::new();
+        backends.set_health_check(health_check);
+        backends
+    };
+    backends.set_unhealthy_wait(unhealthy_backend_wait);
 
     let persistent = !ephemeral;
     // When --max-sessions is explicitly provided, use that value.
@@ -556,8 +1061,36 @@ async fn run_server(
         }
         None => SessionRegistry::new(),
     };
+    sessions.set_motd(motd);
+    sessions.set_client_policy(client_policy);
+    sessions.set_sandbox(sandbox);
+    sessions.set_idle_kill_after(idle_kill_after);
+    sessions.set_tombstone_retention(tombstone_retention);
+    sessions.set_scrollback_lines(scrollback_lines);
+    sessions.set_warm_pool_size(warm_pool);
+    if let Some(path) = policy_file {
+        policy.watch(path, wsh::policy::POLICY_POLL_INTERVAL);
+    }
+    sessions.set_policy(policy);
+    // Runs even without a default: sessions can set their own timeout.
+    sessions.spawn_idle_reaper(std::time::Duration::from_secs(30));
+    sessions.set_status_line(status_line, hostname.clone());
+    sessions.spawn_status_line_refresher();
+    if hooks.on_session_create.is_some() || hooks.on_session_exit.is_some() {
+        wsh::hooks::spawn_runner(sessions.clone());
+    }
+    sessions.set_hooks(hooks);
     let shutdown = ShutdownCoordinator::new();
     let server_config = std::sync::Arc::new(api::ServerConfig::new(persistent));
+    server_config.set_mcp_config(mcp_config);
+    server_config.set_files_config(files_config);
+    server_config.set_websocket_config(websocket_config);
+    if let Some(dir) = config_path.parent() {
+        let loaded = server_config.templates().load_dir(&dir.join("templates"));
+        if loaded > 0 {
+            tracing::info!(count = loaded, "loaded session templates");
+        }
+    }
     let state = api::AppState {
         sessions: sessions.clone(),
         shutdown: shutdown.clone(),
@@ -565,8 +1098,11 @@ async fn run_server(
         server_ws_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         mcp_session_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         ticket_store: Arc::new(api::ticket::TicketStore::new()),
-        backends: federation_manager.lock().await.registry().clone(),
+        tokens: Arc::new(api::tokens::TokenStore::new()),
+        backends,
+        #[cfg(feature = "federation")]
         federation: federation_manager.clone(),
+        #[cfg(feature = "federation")]
         ip_access: ip_access_control,
         hostname,
         federation_config_path: if config_path.exists() { Some(config_path) } else { None },
@@ -585,22 +1121,35 @@ async fn run_server(
     let socket_token = token.clone();
     let socket_hostname = state.hostname.clone();
     let socket_fed_state = server::FederationState {
+        #[cfg(feature = "federation")]
         federation: state.federation.clone(),
         backends: state.backends.clone(),
         config_path: state.federation_config_path.clone(),
         local_token: state.local_token.clone(),
         default_backend_token: state.default_backend_token.clone(),
+        #[cfg(feature = "federation")]
         ip_access: state.ip_access.clone(),
         server_id: state.server_id.clone(),
+        server_config: Some(server_config.clone()),
     };
     if let Some(ref prefix) = base_prefix {
         tracing::info!(prefix = %prefix, "base path prefix configured");
     }
+    #[cfg(feature = "federation")]
+    let join_hello = wsh::federation::relay::Hello { token: token.clone(), base_prefix: base_prefix.clone() };
     let app = api::router(state, api::RouterConfig { token, bind, cors_origins, rate_limit, base_prefix: base_prefix.clone() });
 
     // Cancellation token for HTTP server shutdown (supports multiple listeners)
     let http_cancel = tokio_util::sync::CancellationToken::new();
 
+    // Relay mode: dial out to the hub and serve the streams it opens with
+    // the same router as the HTTP listener.
+    #[cfg(feature = "federation")]
+    if let Some(url) = join {
+        tracing::info!(hub = %url, "joining hub as a relayed backend");
+        tokio::spawn(wsh::federation::relay::join(url, join_token, join_hello, app.clone(), http_cancel.clone()));
+    }
+
     let listener = tokio::net::TcpListener::bind(bind)
         .await
         .map_err(WshError::Io)?;
@@ -644,13 +1193,13 @@ async fn run_server(
         let cancel4 = http_cancel.clone();
         let app4 = app.clone();
         let acceptor4 = acceptor.clone();
-        http_handle = tokio::spawn(serve_tls(listener, acceptor4, app4, cancel4));
+        http_handle = tokio::spawn(wsh::tls::serve(listener, acceptor4, app4, cancel4));
 
         http6_handle = ipv6_listener.map(|l| {
             let cancel6 = http_cancel.clone();
             let app6 = app.clone();
             let acceptor6 = acceptor.clone();
-            tokio::spawn(serve_tls(l, acceptor6, app6, cancel6))
+            tokio::spawn(wsh::tls::serve(l, acceptor6, app6, cancel6))
         });
     } else {
         let cancel4 = http_cancel.clone();
@@ -814,6 +1363,11 @@ async fn run_server(
         let _ = std::fs::remove_file(&socket_path_for_cleanup);
         tracing::debug!(path = %socket_path_for_cleanup.display(), "removed socket file");
     }
+    // Likewise the record left by the client that spawned us, if any.
+    let daemon_state_path = server::daemon_state_path_for_instance(&server_name);
+    if server::DaemonState::read(&daemon_state_path).is_ok_and(|s| s.pid == std::process::id()) {
+        let _ = std::fs::remove_file(&daemon_state_path);
+    }
 
     // 2. Signal existing WS handlers to close
     shutdown.shutdown();
@@ -832,6 +1386,7 @@ async fn run_server(
     }
 
     // 4. Shut down federation backend connections
+    #[cfg(feature = "federation")]
     federation_manager.lock().await.shutdown_all().await;
 
     // 5. Drain sessions (detach clients, SIGHUP children, schedule SIGKILL)
@@ -871,72 +1426,11 @@ async fn run_server(
     Ok(())
 }
 
-/// Manual TLS accept loop for HTTPS serving.
-///
-/// `axum::serve()` only accepts `TcpListener` (sealed `Listener` trait), so
-/// TLS requires a manual loop: accept TCP → TLS handshake → hyper-util
-/// `serve_connection`. Each connection is handled in its own spawned task.
-async fn serve_tls(
-    listener: tokio::net::TcpListener,
-    acceptor: tokio_rustls::TlsAcceptor,
-    app: axum::Router,
-    cancel: tokio_util::sync::CancellationToken,
-) {
-    use hyper_util::rt::TokioIo;
-
-    loop {
-        let (tcp_stream, peer_addr) = tokio::select! {
-            _ = cancel.cancelled() => break,
-            result = listener.accept() => {
-                match result {
-                    Ok(conn) => conn,
-                    Err(e) => {
-                        tracing::debug!(?e, "TCP accept error");
-                        continue;
-                    }
-                }
-            }
-        };
-
-        let acceptor = acceptor.clone();
-        let app = app.clone();
-        let cancel = cancel.clone();
-
-        tokio::spawn(async move {
-            let tls_stream = match acceptor.accept(tcp_stream).await {
-                Ok(s) => s,
-                Err(e) => {
-                    tracing::debug!(?e, %peer_addr, "TLS handshake failed");
-                    return;
-                }
-            };
-
-            let io = TokioIo::new(tls_stream);
-            let service = hyper_util::service::TowerToHyperService::new(app);
-            let builder = hyper_util::server::conn::auto::Builder::new(hyper_util::rt::TokioExecutor::new());
-            let conn = builder.serve_connection_with_upgrades(io, service);
-            tokio::pin!(conn);
-
-            tokio::select! {
-                result = &mut conn => {
-                    if let Err(e) = result {
-                        tracing::debug!(?e, %peer_addr, "connection error");
-                    }
-                }
-                _ = cancel.cancelled() => {
-                    // Graceful shutdown: signal the connection and give it time to finish
-                    conn.as_mut().graceful_shutdown();
-                    let _ = tokio::time::timeout(std::time::Duration::from_secs(5), conn).await;
-                }
-            }
-        });
-    }
-}
-
 // ── MCP stdio mode ─────────────────────────────────────────────────
 
 /// Run the MCP stdio bridge: connect to (or spawn) a server, then bridge
 /// stdin/stdout JSON-RPC ↔ the server's `/mcp` Streamable HTTP endpoint.
+#[cfg(feature = "mcp")]
 async fn run_mcp(
     bind: SocketAddr,
     socket: Option<PathBuf>,
@@ -1012,6 +1506,9 @@ async fn run_mcp(
     // ─────────────────────────────────────────────────────────────
     let mut in_flight = tokio::task::JoinSet::new();
     let concurrency = Arc::new(tokio::sync::Semaphore::new(64));
+    // Whether the standalone notification stream is open. See
+    // `mcp_bridge_listen`.
+    let listening = Arc::new(std::sync::atomic::AtomicBool::new(false));
 
     let mut line = String::new();
     loop {
@@ -1036,11 +1533,12 @@ async fn run_mcp(
         let tok = token.clone();
         let out = stdout.clone();
         let sem = concurrency.clone();
+        let listening = listening.clone();
 
         in_flight.spawn(async move {
             // Acquire permit before dispatching; dropped when the task completes.
             let _permit = sem.acquire().await;
-            mcp_bridge_dispatch(body_str, client, url, sid, tok, out).await;
+            mcp_bridge_dispatch(body_str, client, url, sid, tok, out, listening).await;
         });
     }
 
@@ -1086,6 +1584,7 @@ async fn run_mcp(
 
 /// Dispatch a single MCP JSON-RPC request to the server and write the
 /// response to stdout. Called from a spawned task for concurrency.
+#[cfg(feature = "mcp")]
 async fn mcp_bridge_dispatch(
     body_str: String,
     http_client: reqwest::Client,
@@ -1093,12 +1592,17 @@ async fn mcp_bridge_dispatch(
     session_id: Arc<tokio::sync::Mutex<Option<String>>>,
     token: Option<String>,
     stdout: Arc<tokio::sync::Mutex<tokio::io::Stdout>>,
+    listening: Arc<std::sync::atomic::AtomicBool>,
 ) {
     // Extract the JSON-RPC request ID so we can echo it in error responses
-    let request_id = serde_json::from_str::<serde_json::Value>(&body_str)
-        .ok()
+    let request = serde_json::from_str::<serde_json::Value>(&body_str).ok();
+    let request_id = request
+        .as_ref()
         .and_then(|v| v.get("id").cloned())
         .unwrap_or(serde_json::Value::Null);
+    let subscribing = request
+        .as_ref()
+        .is_some_and(|v| v.get("method").and_then(|m| m.as_str()) == Some("resources/subscribe"));
 
     // Build HTTP request
     let mut req = http_client
@@ -1177,6 +1681,25 @@ async fn mcp_bridge_dispatch(
         }
     }
 
+    if content_type.contains("text/event-stream") && status.is_success() {
+        // Forward each event as it arrives, so progress notifications sent
+        // while a tool runs reach the host before its result.
+        let mut resp = resp;
+        let mut buf: Vec<u8> = Vec::new();
+        while let Ok(Some(chunk)) = resp.chunk().await {
+            buf.extend_from_slice(&chunk);
+            while let Some(end) = buf.windows(2).position(|w| w == b"\n\n") {
+                let event: Vec<u8> = buf.drain(..end + 2).collect();
+                write_sse_event(&String::from_utf8_lossy(&event), &stdout).await;
+            }
+        }
+        write_sse_event(&String::from_utf8_lossy(&buf), &stdout).await;
+        if subscribing && !listening.swap(true, std::sync::atomic::Ordering::AcqRel) {
+            tokio::spawn(mcp_bridge_listen(http_client, mcp_url, session_id, token, stdout, listening));
+        }
+        return;
+    }
+
     let body = resp.text().await.unwrap_or_default();
 
     if !status.is_success() && !status.is_informational() {
@@ -1190,33 +1713,67 @@ async fn mcp_bridge_dispatch(
         return;
     }
 
-    // Parse SSE response based on content-type only.
     let mut out = stdout.lock().await;
-    if content_type.contains("text/event-stream") {
-        for event in body.split("\n\n") {
-            let event = event.trim();
-            if event.is_empty() {
-                continue;
-            }
-            for event_line in event.lines() {
-                if let Some(data) = event_line.strip_prefix("data:") {
-                    let json_str = data.trim();
-                    if !json_str.is_empty() {
-                        let out_line = format!("{}\n", json_str);
-                        let _ = tokio::io::AsyncWriteExt::write_all(
-                            &mut *out,
-                            out_line.as_bytes(),
-                        )
-                        .await;
+    let trimmed_body = body.trim();
+    if !trimmed_body.is_empty() {
+        let out_line = format!("{}\n", trimmed_body);
+        let _ = tokio::io::AsyncWriteExt::write_all(&mut *out, out_line.as_bytes()).await;
+    }
+    let _ = tokio::io::AsyncWriteExt::flush(&mut *out).await;
+}
+
+/// Forward server-initiated notifications, such as resource updates, from
+/// the session's standalone SSE stream to stdout until the stream ends.
+/// Opened once the host first subscribes to a resource.
+#[cfg(feature = "mcp")]
+async fn mcp_bridge_listen(
+    http_client: reqwest::Client,
+    mcp_url: String,
+    session_id: Arc<tokio::sync::Mutex<Option<String>>>,
+    token: Option<String>,
+    stdout: Arc<tokio::sync::Mutex<tokio::io::Stdout>>,
+    listening: Arc<std::sync::atomic::AtomicBool>,
+) {
+    let sid = session_id.lock().await.clone();
+    if let Some(sid) = sid {
+        let mut req = http_client
+            .get(&mcp_url)
+            .header("Accept", "text/event-stream")
+            .header("Mcp-Session-Id", sid);
+        if let Some(ref t) = token {
+            req = req.bearer_auth(t);
+        }
+        match req.send().await {
+            Ok(mut resp) if resp.status().is_success() => {
+                let mut buf: Vec<u8> = Vec::new();
+                while let Ok(Some(chunk)) = resp.chunk().await {
+                    buf.extend_from_slice(&chunk);
+                    while let Some(end) = buf.windows(2).position(|w| w == b"\n\n") {
+                        let event: Vec<u8> = buf.drain(..end + 2).collect();
+                        write_sse_event(&String::from_utf8_lossy(&event), &stdout).await;
                     }
                 }
             }
+            Ok(resp) => tracing::warn!(status = %resp.status(), "MCP notification stream refused"),
+            Err(e) => tracing::warn!(?e, "MCP notification stream failed"),
         }
-    } else {
-        let trimmed_body = body.trim();
-        if !trimmed_body.is_empty() {
-            let out_line = format!("{}\n", trimmed_body);
-            let _ = tokio::io::AsyncWriteExt::write_all(&mut *out, out_line.as_bytes()).await;
+    }
+    // Let the next subscribe reopen it.
+    listening.store(false, std::sync::atomic::Ordering::Release);
+}
+
+/// Write the JSON-RPC messages in the `data:` lines of one SSE event to
+/// stdout, one per line.
+#[cfg(feature = "mcp")]
+async fn write_sse_event(event: &str, stdout: &tokio::sync::Mutex<tokio::io::Stdout>) {
+    let mut out = stdout.lock().await;
+    for event_line in event.trim().lines() {
+        if let Some(data) = event_line.strip_prefix("data:") {
+            let json_str = data.trim();
+            if !json_str.is_empty() {
+                let out_line = format!("{}\n", json_str);
+                let _ = tokio::io::AsyncWriteExt::write_all(&mut *out, out_line.as_bytes()).await;
+            }
         }
     }
     let _ = tokio::io::AsyncWriteExt::flush(&mut *out).await;
@@ -1301,7 +1858,21 @@ fn spawn_server_daemon(
     }
 
     let child = cmd.spawn().map_err(WshError::Io)?;
-    tracing::debug!("spawned wsh server daemon");
+    tracing::debug!(pid = child.id(), "spawned wsh server daemon");
+
+    // Record the daemon so `wsh gc` can find it if it ends up orphaned.
+    let state = server::DaemonState {
+        pid: child.id(),
+        instance: server_name.to_string(),
+        socket: socket_path.to_path_buf(),
+        spawned_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+    };
+    if let Err(e) = state.write(&server::daemon_state_path_for_instance(server_name)) {
+        tracing::warn!(?e, "failed to write daemon state file");
+    }
 
     // Reap the child in a background thread to prevent zombie accumulation.
     std::thread::spawn(move || {
@@ -1340,6 +1911,12 @@ async fn run_default(cli: Cli) -> Result<(), WshError> {
     let server_name = &cli.server_name;
     let socket_path = resolve_socket_path(cli.socket.clone(), server_name);
 
+    // Check the layout before spawning a server for it.
+    let layout = match cli.layout {
+        Some(ref path) => Some(wsh::layout::Layout::load(path).map_err(WshError::Config)?),
+        None => None,
+    };
+
     // Try connecting to an existing server; if none, spawn one.
     // Uses an advisory file lock to prevent two clients from racing to spawn
     // duplicate daemons (TOCTOU between connect-fail and spawn).
@@ -1390,6 +1967,11 @@ async fn run_default(cli: Cli) -> Result<(), WshError> {
     let (rows, cols) = terminal::terminal_size().unwrap_or((24, 80));
     tracing::debug!(rows, cols, "terminal size");
 
+    if let Some(layout) = layout {
+        let first = create_layout(c, &socket_path, layout, rows, cols).await?;
+        return run_attach(first, "all".to_string(), cli.socket, cli.alt_screen, cli.server_name).await;
+    }
+
     // Determine what command to pass to the server
     let command = match &cli.cmd {
         Some(cmd) => Some(cmd.clone()),
@@ -1426,6 +2008,15 @@ async fn run_default(cli: Cli) -> Result<(), WshError> {
     };
     let screen_guard = terminal::ScreenGuard::new(screen_mode)?;
 
+    // The MOTD was written into the session before this client subscribed
+    // to its output, so display it locally.
+    if let Some(ref motd) = resp.motd {
+        use std::io::Write;
+        let mut stdout = std::io::stdout().lock();
+        let _ = stdout.write_all(&wsh::session::format_motd(motd));
+        let _ = stdout.flush();
+    }
+
     // Enter the streaming I/O loop
     let result = c.run_streaming().await;
 
@@ -1433,9 +2024,15 @@ async fn run_default(cli: Cli) -> Result<(), WshError> {
     drop(screen_guard);
     drop(raw_guard);
 
-    if let Err(e) = result {
-        eprintln!("wsh: streaming error: {}", e);
-        return Err(WshError::Io(e));
+    match result {
+        Ok(client::StreamEnd::Detached) => {}
+        Ok(client::StreamEnd::Switch(name)) => {
+            return run_attach(name, "all".to_string(), cli.socket, cli.alt_screen, cli.server_name).await;
+        }
+        Err(e) => {
+            eprintln!("wsh: streaming error: {}", e);
+            return Err(WshError::Io(e));
+        }
     }
 
     eprintln!("[detached from session '{}']", resp.name);
@@ -1443,6 +2040,54 @@ async fn run_default(cli: Cli) -> Result<(), WshError> {
     Ok(())
 }
 
+/// Create the sessions in `layout` that don't exist yet, and return the
+/// name of the first one. `c` is used to list the existing sessions.
+async fn create_layout(
+    mut c: client::Client,
+    socket_path: &std::path::Path,
+    layout: wsh::layout::Layout,
+    rows: u16,
+    cols: u16,
+) -> Result<String, WshError> {
+    let existing: Vec<String> = c
+        .list_sessions()
+        .await
+        .map_err(|e| {
+            eprintln!("wsh: failed to list sessions: {}", e);
+            WshError::Io(e)
+        })?
+        .into_iter()
+        .map(|s| s.name)
+        .collect();
+
+    let first = layout.sessions[0].name.clone();
+    for session in layout.sessions {
+        if existing.contains(&session.name) {
+            eprintln!("wsh: session '{}' already exists, leaving it as is", session.name);
+            continue;
+        }
+        let msg = protocol::CreateSessionMsg {
+            name: Some(session.name.clone()),
+            command: session.command,
+            cwd: session.cwd,
+            env: session.env,
+            rows,
+            cols,
+            tags: session.tags,
+            server: None,
+        };
+        // Each create takes its own connection, which detaches again when
+        // it's dropped.
+        let result = async { client::Client::connect(socket_path).await?.create_session(msg).await }.await;
+        if let Err(e) = result {
+            eprintln!("wsh: failed to create session '{}': {}", session.name, e);
+            return Err(WshError::Io(e));
+        }
+        tracing::info!(session = %session.name, "session created from layout");
+    }
+    Ok(first)
+}
+
 // ── Client subcommands ─────────────────────────────────────────────
 
 async fn run_attach(
@@ -1453,22 +2098,79 @@ async fn run_attach(
     server_name: String,
 ) -> Result<(), WshError> {
     let socket_path = resolve_socket_path(socket, &server_name);
-
-    let scrollback_req = match scrollback.as_str() {
-        "none" => ScrollbackRequest::None,
-        "all" => ScrollbackRequest::All,
-        s => match s.parse::<usize>() {
-            Ok(n) => ScrollbackRequest::Lines(n),
-            Err(_) => {
-                eprintln!("wsh attach: invalid scrollback value: {}", s);
-                std::process::exit(1);
+    let mut name = name;
+    let mut scrollback = scrollback;
+    // Switching sessions from the command palette attaches to the next one.
+    loop {
+        match attach_once(&socket_path, name, &scrollback, alt_screen).await? {
+            client::StreamEnd::Detached => return Ok(()),
+            client::StreamEnd::Switch(next) => {
+                name = next;
+                scrollback = "all".to_string();
             }
-        },
+        }
+    }
+}
+
+async fn run_switch(
+    name: Option<String>,
+    socket: Option<PathBuf>,
+    alt_screen: bool,
+    server_name: String,
+) -> Result<(), WshError> {
+    let name = match name {
+        Some(name) => name,
+        None => {
+            let socket_path = resolve_socket_path(socket.clone(), &server_name);
+            let picked = {
+                let _raw_guard = terminal::RawModeGuard::new()?;
+                let _screen_guard = terminal::ScreenGuard::new(terminal::ScreenMode::AltScreen)?;
+                client::pick_session(&socket_path).await
+            };
+            match picked {
+                Ok(Some(name)) => name,
+                Ok(None) => return Ok(()),
+                Err(e) => {
+                    eprintln!("wsh switch: failed to list sessions on {}: {}", socket_path.display(), e);
+                    return Err(WshError::Io(e));
+                }
+            }
+        }
     };
+    run_attach(name, "all".to_string(), socket, alt_screen, server_name).await
+}
+
+async fn run_top(socket: Option<PathBuf>, server_name: String) -> Result<(), WshError> {
+    let socket_path = resolve_socket_path(socket.clone(), &server_name);
+    // Detaching from a session attached from the dashboard comes back to it.
+    loop {
+        let picked = {
+            let _raw_guard = terminal::RawModeGuard::new()?;
+            let _screen_guard = terminal::ScreenGuard::new(terminal::ScreenMode::AltScreen)?;
+            client::top::run(&socket_path).await
+        };
+        match picked {
+            Ok(Some(name)) => run_attach(name, "all".to_string(), socket.clone(), false, server_name.clone()).await?,
+            Ok(None) => return Ok(()),
+            Err(e) => {
+                eprintln!("wsh top: {}: {}", socket_path.display(), e);
+                return Err(WshError::Io(e));
+            }
+        }
+    }
+}
+
+async fn attach_once(
+    socket_path: &std::path::Path,
+    name: String,
+    scrollback: &str,
+    alt_screen: bool,
+) -> Result<client::StreamEnd, WshError> {
+    let scrollback_req = parse_scrollback(scrollback);
 
     let (rows, cols) = terminal::terminal_size().unwrap_or((24, 80));
 
-    let mut c = client::Client::connect(&socket_path).await.map_err(|e| {
+    let mut c = client::Client::connect(socket_path).await.map_err(|e| {
         eprintln!("wsh attach: failed to connect to server at {}: {}", socket_path.display(), e);
         WshError::Io(e)
     })?;
@@ -1476,6 +2178,7 @@ async fn run_attach(
     let msg = AttachSessionMsg {
         name: name.clone(),
         scrollback: scrollback_req,
+        scrollback_format: Some(wsh::parser::state::Format::Styled),
         rows,
         cols,
     };
@@ -1501,10 +2204,19 @@ async fn run_attach(
     {
         use std::io::Write;
         let mut stdout = std::io::stdout().lock();
-        if !resp.scrollback.is_empty() {
+        if let Some(ref motd) = resp.motd {
+            let _ = stdout.write_all(&wsh::session::format_motd(motd));
+        }
+        // Parsed scrollback lines are re-wrapped to the local width; older
+        // servers only send raw bytes, which are replayed verbatim.
+        if let Some(ref lines) = resp.scrollback_lines {
+            let _ = stdout.write_all(&client::render_scrollback_lines(lines, cols));
+        } else if !resp.scrollback.is_empty() {
             let _ = stdout.write_all(&resp.scrollback);
         }
-        if !resp.screen.is_empty() {
+        // A session larger than the local terminal is drawn through the
+        // client's viewport once streaming starts.
+        if !resp.screen.is_empty() && !client::needs_viewport((resp.rows, resp.cols), (rows, cols)) {
             let _ = stdout.write_all(&resp.screen);
         }
         let _ = stdout.flush();
@@ -1517,104 +2229,226 @@ async fn run_attach(
     drop(screen_guard);
     drop(raw_guard);
 
+    match result {
+        Ok(client::StreamEnd::Detached) => {
+            eprintln!("[detached from session '{}']", resp.name);
+            Ok(client::StreamEnd::Detached)
+        }
+        Ok(end) => Ok(end),
+        Err(e) => {
+            eprintln!("wsh attach: streaming error: {}", e);
+            Err(WshError::Io(e))
+        }
+    }
+}
+
+/// Parse `wsh attach --scrollback`, exiting on an invalid value.
+fn parse_scrollback(scrollback: &str) -> ScrollbackRequest {
+    match scrollback {
+        "none" => ScrollbackRequest::None,
+        "all" => ScrollbackRequest::All,
+        s => match s.parse::<usize>() {
+            Ok(n) => ScrollbackRequest::Lines(n),
+            Err(_) => {
+                eprintln!("wsh attach: invalid scrollback value: {}", s);
+                std::process::exit(1);
+            }
+        },
+    }
+}
+
+#[cfg(feature = "remote-attach")]
+async fn run_remote_attach(
+    name: String,
+    scrollback: String,
+    alt_screen: bool,
+    url: String,
+    token: Option<String>,
+) -> Result<(), WshError> {
+    let scrollback_req = parse_scrollback(&scrollback);
+    let (_, cols) = terminal::terminal_size().unwrap_or((24, 80));
+
+    let remote = wsh::remote::RemoteSession::new(&url, &name, token).map_err(|e| {
+        eprintln!("wsh attach: {}", e);
+        WshError::Io(e)
+    })?;
+    let fetch = async {
+        let lines = remote.scrollback(scrollback_req).await?;
+        let screen = remote.screen().await?;
+        Ok::<_, std::io::Error>((lines, screen))
+    };
+    let (lines, screen) = fetch.await.map_err(|e| {
+        eprintln!("wsh attach: {}", e);
+        WshError::Io(e)
+    })?;
+    // Output between the screen snapshot and the stream opening is not
+    // replayed; the resize on entry makes the application redraw.
+    let ws = remote.connect().await.map_err(|e| {
+        eprintln!("wsh attach: failed to connect to {}: {}", remote.ws_url(), e);
+        WshError::Io(e)
+    })?;
+
+    let raw_guard = terminal::RawModeGuard::new()?;
+    let screen_mode = if alt_screen {
+        terminal::ScreenMode::AltScreen
+    } else {
+        terminal::ScreenMode::Clear
+    };
+    let screen_guard = terminal::ScreenGuard::new(screen_mode)?;
+
+    {
+        use std::io::Write;
+        let mut stdout = std::io::stdout().lock();
+        let _ = stdout.write_all(&client::render_scrollback_lines(&lines, cols));
+        let _ = stdout.write_all(&screen);
+        let _ = stdout.flush();
+    }
+
+    let result = remote.run_streaming(ws).await;
+
+    drop(screen_guard);
+    drop(raw_guard);
+
     if let Err(e) = result {
         eprintln!("wsh attach: streaming error: {}", e);
         return Err(WshError::Io(e));
     }
 
-    eprintln!("[detached from session '{}']", resp.name);
+    eprintln!("[detached from session '{}']", name);
     Ok(())
 }
 
-async fn run_list(socket: Option<PathBuf>, server_name: String, server: Option<String>) -> Result<(), WshError> {
+async fn run_list(
+    socket: Option<PathBuf>,
+    server_name: String,
+    server: Option<String>,
+    format: Option<String>,
+    columns: Vec<String>,
+    wide: bool,
+    out: Output,
+) -> Result<(), WshError> {
+    use wsh::client::list;
+
+    // Check the output options before talking to the server.
+    let template = match format.as_deref().map(list::Template::parse).transpose() {
+        Ok(template) => template,
+        Err(e) => out.fail_with("invalid_argument", e),
+    };
+    let columns = if wide {
+        list::COLUMNS.iter().map(|c| c.to_string()).collect()
+    } else if columns.is_empty() {
+        list::DEFAULT_COLUMNS.iter().map(|c| c.to_string()).collect()
+    } else {
+        match list::parse_columns(&columns) {
+            Ok(columns) => columns,
+            Err(e) => out.fail_with("invalid_argument", e),
+        }
+    };
+
     let socket_path = resolve_socket_path(socket, &server_name);
     let mut c = match client::Client::connect(&socket_path).await {
         Ok(c) => c,
-        Err(e) => {
-            eprintln!(
-                "wsh list: failed to connect to server at {}: {}",
-                socket_path.display(),
-                e
-            );
-            std::process::exit(1);
-        }
+        Err(e) => out.unreachable(&socket_path, e),
     };
 
     let sessions = match c.list_sessions_on(server).await {
         Ok(s) => s,
-        Err(e) => {
-            eprintln!("wsh list: {}", e);
-            std::process::exit(1);
-        }
+        Err(e) => out.fail(e),
     };
 
-    if sessions.is_empty() {
-        println!("No active sessions.");
-    } else {
-        println!(
-            "{:<20} {:<8} {:<20} {:<12} {:<8} {}",
-            "NAME", "PID", "COMMAND", "SIZE", "CLIENTS", "TAGS"
-        );
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    if out.is_json() {
+        println!("{}", serde_json::to_string_pretty(&sessions).unwrap_or_default());
+    } else if let Some(template) = template {
         for s in &sessions {
-            let pid_str = match s.pid {
-                Some(pid) => pid.to_string(),
-                None => "-".to_string(),
-            };
-            let size = format!("{}x{}", s.cols, s.rows);
-            let tags_str = s.tags.join(", ");
-            println!(
-                "{:<20} {:<8} {:<20} {:<12} {:<8} {}",
-                s.name, pid_str, s.command, size, s.clients, tags_str
-            );
+            println!("{}", template.render(s, now));
         }
+    } else if sessions.is_empty() {
+        println!("No active sessions.");
+    } else {
+        print!("{}", list::table(&sessions, &columns, now));
     }
 
     Ok(())
 }
 
-async fn run_kill(name: String, socket: Option<PathBuf>, server_name: String, server: Option<String>) -> Result<(), WshError> {
+async fn run_kill(msg: protocol::KillSessionMsg, socket: Option<PathBuf>, server_name: String, out: Output) -> Result<(), WshError> {
     let socket_path = resolve_socket_path(socket, &server_name);
     let mut c = match client::Client::connect(&socket_path).await {
         Ok(c) => c,
-        Err(e) => {
-            eprintln!(
-                "wsh kill: failed to connect to server at {}: {}",
-                socket_path.display(),
-                e
-            );
-            std::process::exit(1);
-        }
+        Err(e) => out.unreachable(&socket_path, e),
     };
 
-    if let Err(e) = c.kill_session_on(&name, server).await {
-        eprintln!("wsh kill: {}", e);
-        std::process::exit(1);
+    let name = msg.name.clone();
+    if let Err(e) = c.kill_session_with(msg).await {
+        out.fail(e);
     }
 
-    println!("Session '{}' killed.", name);
+    out.print(&serde_json::json!({"name": name, "killed": true}), || {
+        println!("Session '{}' killed.", name);
+    });
     Ok(())
 }
 
-async fn run_detach(name: String, socket: Option<PathBuf>, server_name: String, server: Option<String>) -> Result<(), WshError> {
+async fn run_logs(
+    name: String,
+    follow: bool,
+    lines: Option<usize>,
+    socket: Option<PathBuf>,
+    server_name: String,
+    out: Output,
+) -> Result<(), WshError> {
+    use std::io::Write;
+
     let socket_path = resolve_socket_path(socket, &server_name);
     let mut c = match client::Client::connect(&socket_path).await {
         Ok(c) => c,
-        Err(e) => {
-            eprintln!(
-                "wsh detach: failed to connect to server at {}: {}",
-                socket_path.display(),
-                e
-            );
-            std::process::exit(1);
-        }
+        Err(e) => out.unreachable(&socket_path, e),
+    };
+
+    let msg = protocol::ReadOutputMsg { name, since: None, last: lines, follow };
+    let mut stdout = std::io::stdout().lock();
+    let result = c
+        .read_output(msg, |batch| {
+            // One JSON object per batch, so a follow can be read line by line.
+            if out.is_json() {
+                let _ = writeln!(stdout, "{}", serde_json::to_string(&batch).unwrap_or_default());
+                let _ = stdout.flush();
+                return;
+            }
+            if batch.missed > 0 {
+                eprintln!("wsh logs: {} lines dropped from scrollback before they were read", batch.missed);
+            }
+            for line in &batch.lines {
+                let _ = writeln!(stdout, "{}", line);
+            }
+            let _ = stdout.flush();
+        })
+        .await;
+    if let Err(e) = result {
+        drop(stdout);
+        out.fail(e);
+    }
+    Ok(())
+}
+
+async fn run_detach(name: String, socket: Option<PathBuf>, server_name: String, server: Option<String>, out: Output) -> Result<(), WshError> {
+    let socket_path = resolve_socket_path(socket, &server_name);
+    let mut c = match client::Client::connect(&socket_path).await {
+        Ok(c) => c,
+        Err(e) => out.unreachable(&socket_path, e),
     };
 
     if let Err(e) = c.detach_session_on(&name, server).await {
-        eprintln!("wsh detach: {}", e);
-        std::process::exit(1);
+        out.fail(e);
     }
 
-    println!("Session '{}' detached.", name);
+    out.print(&serde_json::json!({"name": name, "detached": true}), || {
+        println!("Session '{}' detached.", name);
+    });
     Ok(())
 }
 
@@ -1625,33 +2459,25 @@ async fn run_tag(
     server: Option<String>,
     socket: Option<PathBuf>,
     server_name: String,
+    out: Output,
 ) -> Result<(), WshError> {
     let socket_path = resolve_socket_path(socket, &server_name);
     let mut c = match client::Client::connect(&socket_path).await {
         Ok(c) => c,
-        Err(e) => {
-            eprintln!(
-                "wsh tag: failed to connect to server at {}: {}",
-                socket_path.display(),
-                e
-            );
-            std::process::exit(1);
-        }
+        Err(e) => out.unreachable(&socket_path, e),
     };
 
-    match c.manage_tags_on(&name, add, remove, server).await {
-        Ok(tags) => {
-            if tags.is_empty() {
-                println!("Session '{}': no tags", name);
-            } else {
-                println!("Session '{}': {}", name, tags.join(", "));
-            }
-        }
-        Err(e) => {
-            eprintln!("wsh tag: {}", e);
-            std::process::exit(1);
+    let tags = match c.manage_tags_on(&name, add, remove, server).await {
+        Ok(tags) => tags,
+        Err(e) => out.fail(e),
+    };
+    out.print(&serde_json::json!({"name": name, "tags": tags}), || {
+        if tags.is_empty() {
+            println!("Session '{}': no tags", name);
+        } else {
+            println!("Session '{}': {}", name, tags.join(", "));
         }
-    }
+    });
 
     Ok(())
 }
@@ -1660,35 +2486,38 @@ async fn run_servers(
     action: ServersAction,
     socket: Option<PathBuf>,
     server_name: String,
+    format: OutputFormat,
 ) -> Result<(), WshError> {
+    let out = Output::new(
+        format,
+        match action {
+            ServersAction::List => "servers list",
+            ServersAction::Add { .. } => "servers add",
+            ServersAction::Remove { .. } => "servers remove",
+            ServersAction::Info => "servers info",
+            ServersAction::Reload => "servers reload",
+        },
+    );
     let socket_path = resolve_socket_path(socket, &server_name);
     let mut c = match client::Client::connect(&socket_path).await {
         Ok(c) => c,
-        Err(e) => {
-            eprintln!(
-                "wsh servers: failed to connect to server at {}: {}",
-                socket_path.display(),
-                e
-            );
-            std::process::exit(1);
-        }
+        Err(e) => out.unreachable(&socket_path, e),
     };
 
     match action {
         ServersAction::List => {
             let resp = match c.list_servers().await {
                 Ok(r) => r,
-                Err(e) => {
-                    eprintln!("wsh servers list: {}", e);
-                    std::process::exit(1);
-                }
+                Err(e) => out.fail(e),
             };
-            if resp.servers.is_empty() {
-                println!("No servers.");
-            } else {
+            out.print(&resp, || {
+                if resp.servers.is_empty() {
+                    println!("No servers.");
+                    return;
+                }
                 println!(
-                    "{:<20} {:<25} {:<12} {:<10} {}",
-                    "HOSTNAME", "ADDRESS", "HEALTH", "ROLE", "SESSIONS"
+                    "{:<20} {:<25} {:<12} {:<10} SESSIONS",
+                    "HOSTNAME", "ADDRESS", "HEALTH", "ROLE"
                 );
                 for s in &resp.servers {
                     let hostname = s.hostname.as_deref().unwrap_or("-");
@@ -1701,202 +2530,776 @@ async fn run_servers(
                         hostname, s.address, s.health, s.role, sessions_str
                     );
                 }
-            }
+            });
         }
         ServersAction::Add { address, token } => {
-            match c.add_server(&address, token).await {
-                Ok(resp) => {
-                    println!(
-                        "Server added: {} (health: {})",
-                        resp.address, resp.health
-                    );
-                }
-                Err(e) => {
-                    eprintln!("wsh servers add: {}", e);
-                    std::process::exit(1);
-                }
-            }
+            let resp = match c.add_server(&address, token).await {
+                Ok(resp) => resp,
+                Err(e) => out.fail(e),
+            };
+            out.print(&resp, || {
+                println!(
+                    "Server added: {} (health: {})",
+                    resp.address, resp.health
+                );
+            });
         }
         ServersAction::Remove { hostname } => {
             if let Err(e) = c.remove_server(&hostname).await {
-                eprintln!("wsh servers remove: {}", e);
-                std::process::exit(1);
+                out.fail(e);
             }
-            println!("Server '{}' removed.", hostname);
+            out.print(&serde_json::json!({"hostname": hostname, "removed": true}), || {
+                println!("Server '{}' removed.", hostname);
+            });
         }
         ServersAction::Info => {
-            match c.server_info().await {
-                Ok(info) => {
-                    println!("Hostname: {}", info.hostname);
-                    println!("Version:  {}", info.version);
-                }
-                Err(e) => {
-                    eprintln!("wsh servers info: {}", e);
-                    std::process::exit(1);
-                }
-            }
+            let info = match c.server_info().await {
+                Ok(info) => info,
+                Err(e) => out.fail(e),
+            };
+            out.print(&info, || {
+                println!("Hostname: {}", info.hostname);
+                println!("Version:  {}", info.version);
+            });
         }
         ServersAction::Reload => {
-            match c.reload_config().await {
-                Ok(resp) => {
-                    println!(
-                        "Config reloaded: {} added, {} removed.",
-                        resp.added, resp.removed
-                    );
-                }
-                Err(e) => {
-                    eprintln!("wsh servers reload: {}", e);
-                    std::process::exit(1);
-                }
-            }
+            let resp = match c.reload_config().await {
+                Ok(resp) => resp,
+                Err(e) => out.fail(e),
+            };
+            out.print(&resp, || {
+                println!(
+                    "Config reloaded: {} added, {} removed.",
+                    resp.added, resp.removed
+                );
+            });
         }
     }
 
     Ok(())
 }
 
-async fn run_stop(socket: Option<PathBuf>, server_name: String) -> Result<(), WshError> {
+/// Print completion candidates, one per line. Prints nothing if the server
+/// can't be reached quickly, so a completion never hangs or shows an error.
+async fn run_complete(kind: String, socket: Option<PathBuf>, server_name: String) -> Result<(), WshError> {
+    if kind != "sessions" {
+        return Ok(());
+    }
+    let socket_path = resolve_socket_path(socket, &server_name);
+    let list = async {
+        let mut c = client::Client::connect(&socket_path).await.ok()?;
+        c.list_sessions_on(None).await.ok()
+    };
+    if let Ok(Some(sessions)) = tokio::time::timeout(std::time::Duration::from_secs(2), list).await {
+        for s in sessions {
+            println!("{}", s.name);
+        }
+    }
+    Ok(())
+}
+
+async fn run_stop(socket: Option<PathBuf>, server_name: String, out: Output) -> Result<(), WshError> {
     let socket_path = resolve_socket_path(socket, &server_name);
     let mut c = match client::Client::connect(&socket_path).await {
         Ok(c) => c,
         Err(e) => {
             match e.kind() {
                 std::io::ErrorKind::ConnectionRefused | std::io::ErrorKind::NotFound => {
-                    println!("No server running.");
+                    out.print(&serde_json::json!({"stopped": false, "running": false}), || {
+                        println!("No server running.");
+                    });
                     return Ok(());
                 }
-                _ => {
-                    eprintln!(
-                        "wsh stop: failed to connect to server at {}: {}",
-                        socket_path.display(),
-                        e
-                    );
-                    std::process::exit(1);
-                }
+                _ => out.unreachable(&socket_path, e),
             }
         }
     };
 
     if let Err(e) = c.shutdown_server().await {
-        eprintln!("wsh stop: {}", e);
-        std::process::exit(1);
+        out.fail(e);
     }
 
     // Wait for the socket file to disappear (server cleanup)
     let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(10);
     while socket_path.exists() {
         if tokio::time::Instant::now() > deadline {
-            eprintln!("wsh stop: server acknowledged shutdown but socket file still exists after 10s");
-            std::process::exit(1);
+            out.fail_with(
+                "shutdown_timeout",
+                "server acknowledged shutdown but socket file still exists after 10s",
+            );
         }
         tokio::time::sleep(std::time::Duration::from_millis(50)).await;
     }
 
-    println!("Server stopped.");
+    out.print(&serde_json::json!({"stopped": true}), || println!("Server stopped."));
+    Ok(())
+}
+
+/// How long after spawning a daemon `wsh gc` leaves it alone, so one that
+/// is still starting up isn't mistaken for an orphan.
+const GC_STARTUP_GRACE_SECS: u64 = 30;
+
+/// Clean up after auto-spawned servers, across all instances.
+///
+/// A daemon whose socket is gone or refusing connections can't be reached
+/// by clients or stopped with `wsh stop`, yet may still hold its TCP port.
+/// Such daemons are sent SIGTERM, then SIGKILL if they don't exit. Records
+/// of daemons that have died, and socket files no server is listening on,
+/// are removed.
+async fn run_gc(dry_run: bool, out: Output) -> Result<(), WshError> {
+    let dir = server::instance_dir();
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries.filter_map(Result::ok).map(|e| e.path()).collect::<Vec<_>>(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => out.fail_with("io_error", e),
+    };
+    let action = |done: &str| if dry_run { "would be ".to_string() + done } else { done.to_string() };
+    // What was (or would be) cleaned up, for `--output json`.
+    let mut cleaned = Vec::new();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    for path in entries.iter().filter(|p| p.to_string_lossy().ends_with(".daemon.json")) {
+        let state = match server::DaemonState::read(path) {
+            Ok(state) => state,
+            Err(e) => {
+                if !out.is_json() {
+                    println!("{}: unreadable ({}), {}", path.display(), e, action("removed"));
+                }
+                if !dry_run {
+                    let _ = std::fs::remove_file(path);
+                }
+                cleaned.push(serde_json::json!({"path": path, "problem": "unreadable", "action": "removed"}));
+                continue;
+            }
+        };
+        match state.health() {
+            server::DaemonHealth::Running => continue,
+            // A daemon that was just spawned may not have bound its socket yet.
+            server::DaemonHealth::Orphaned if now.saturating_sub(state.spawned_at) < GC_STARTUP_GRACE_SECS => continue,
+            server::DaemonHealth::Orphaned => {
+                if !out.is_json() {
+                    println!(
+                        "{}: pid {} is running but its socket {} is unreachable, {}",
+                        state.instance,
+                        state.pid,
+                        state.socket.display(),
+                        action("stopped")
+                    );
+                }
+                if !dry_run {
+                    if !terminate(&state).await {
+                        eprintln!("wsh gc: pid {} did not exit", state.pid);
+                        continue;
+                    }
+                    let _ = std::fs::remove_file(path);
+                    if !server::instance_lock_held(&state.instance) {
+                        let _ = std::fs::remove_file(&state.socket);
+                    }
+                }
+                cleaned.push(serde_json::json!({
+                    "instance": state.instance, "pid": state.pid, "problem": "orphaned", "action": "stopped",
+                }));
+            }
+            server::DaemonHealth::Dead => {
+                if !out.is_json() {
+                    println!("{}: pid {} has exited, record {}", state.instance, state.pid, action("removed"));
+                }
+                if !dry_run {
+                    let _ = std::fs::remove_file(path);
+                    if !server::instance_lock_held(&state.instance)
+                        && std::os::unix::net::UnixStream::connect(&state.socket).is_err()
+                    {
+                        let _ = std::fs::remove_file(&state.socket);
+                    }
+                }
+                cleaned.push(serde_json::json!({
+                    "instance": state.instance, "pid": state.pid, "problem": "dead", "action": "removed",
+                }));
+            }
+        }
+    }
+
+    // Sockets left behind by servers that died without a record, such as
+    // ones started with `wsh server` that were killed.
+    for path in entries.iter().filter(|p| p.extension().is_some_and(|e| e == "sock")) {
+        let Some(name) = path.file_stem().and_then(|s| s.to_str()) else { continue };
+        if !path.exists()
+            || server::instance_lock_held(name)
+            || std::os::unix::net::UnixStream::connect(path).is_ok()
+        {
+            continue;
+        }
+        if !out.is_json() {
+            println!("{}: stale socket {}, {}", name, path.display(), action("removed"));
+        }
+        if !dry_run {
+            let _ = std::fs::remove_file(path);
+        }
+        cleaned.push(serde_json::json!({"instance": name, "path": path, "problem": "stale_socket", "action": "removed"}));
+    }
+
+    out.print(&serde_json::json!({"dry_run": dry_run, "cleaned": cleaned}), || {
+        if cleaned.is_empty() {
+            println!("Nothing to clean up.");
+        }
+    });
     Ok(())
 }
 
-async fn run_token(socket: Option<PathBuf>, server_name: String) -> Result<(), WshError> {
+/// Stop an orphaned daemon: SIGTERM, then SIGKILL after 5 seconds. Returns
+/// whether it exited.
+async fn terminate(state: &server::DaemonState) -> bool {
+    let pid = state.pid as libc::pid_t;
+    for signal in [libc::SIGTERM, libc::SIGKILL] {
+        unsafe { libc::kill(pid, signal) };
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+        while tokio::time::Instant::now() < deadline {
+            if !state.is_alive() {
+                return true;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    }
+    false
+}
+
+async fn run_token(socket: Option<PathBuf>, server_name: String, out: Output) -> Result<(), WshError> {
     let socket_path = resolve_socket_path(socket, &server_name);
     let mut c = match client::Client::connect(&socket_path).await {
         Ok(c) => c,
-        Err(e) => {
-            eprintln!(
-                "wsh token: failed to connect to server at {}: {}",
-                socket_path.display(),
-                e
-            );
-            std::process::exit(1);
-        }
+        Err(e) => out.unreachable(&socket_path, e),
     };
 
     match c.get_token().await {
         Ok(Some(token)) => {
-            println!("{}", token);
-        }
-        Ok(None) => {
-            eprintln!("wsh token: no auth token configured (server is on localhost)");
-            std::process::exit(1);
-        }
-        Err(e) => {
-            eprintln!("wsh token: {}", e);
-            std::process::exit(1);
+            out.print(&serde_json::json!({"token": token}), || println!("{}", token));
         }
+        Ok(None) => out.fail_with("no_token", "no auth token configured (server is on localhost)"),
+        Err(e) => out.fail(e),
     }
 
     Ok(())
 }
 
-async fn run_persist(
-    value: Option<String>,
+async fn run_token_action(
+    action: TokenAction,
     bind: SocketAddr,
     token: Option<String>,
+    socket: Option<PathBuf>,
+    server_name: String,
+    out: Output,
 ) -> Result<(), WshError> {
-    let url = format!("http://{}/server/persist", bind);
+    // Fall back to the root token from the local server
+    let token = match token {
+        Some(t) => Some(t),
+        None => {
+            let socket_path = resolve_socket_path(socket, &server_name);
+            match client::Client::connect(&socket_path).await {
+                Ok(mut c) => c.get_token().await.ok().flatten(),
+                Err(_) => None,
+            }
+        }
+    };
+
+    let base = format!("http://{}/auth/tokens", bind);
     let client = reqwest::Client::new();
+    let req = match &action {
+        TokenAction::Create { name, scope, tags } => client.post(&base).json(&serde_json::json!({
+            "name": name,
+            "scope": scope.replace('-', "_"),
+            "tags": tags,
+        })),
+        TokenAction::List => client.get(&base),
+        TokenAction::Revoke { name } => client.delete(format!("{}/{}", base, name)),
+    };
+    let req = match &token {
+        Some(t) => req.bearer_auth(t),
+        None => req,
+    };
+    let resp = match req.send().await {
+        Ok(r) => r,
+        Err(e) => fail_http(out, bind, e),
+    };
+    if !resp.status().is_success() {
+        fail_status(out, resp).await;
+    }
+    let body: serde_json::Value = resp.json().await.unwrap_or_default();
+
+    if out.is_json() {
+        let body = match action {
+            TokenAction::Revoke { name } => serde_json::json!({"name": name, "revoked": true}),
+            _ => body,
+        };
+        out.print(&body, || {});
+        return Ok(());
+    }
+    match action {
+        TokenAction::Create { .. } => {
+            println!("{}", body["token"].as_str().unwrap_or_default());
+        }
+        TokenAction::List => {
+            let tokens = body["tokens"].as_array().cloned().unwrap_or_default();
+            if tokens.is_empty() {
+                println!("No tokens.");
+            } else {
+                println!("{:<24} {:<10} TAGS", "NAME", "SCOPE");
+                for t in &tokens {
+                    let tags: Vec<&str> = t["tags"]
+                        .as_array()
+                        .map(|a| a.iter().filter_map(|v| v.as_str()).collect())
+                        .unwrap_or_default();
+                    println!(
+                        "{:<24} {:<10} {}",
+                        t["name"].as_str().unwrap_or("-"),
+                        t["scope"].as_str().unwrap_or("-"),
+                        if tags.is_empty() { "-".to_string() } else { tags.join(",") }
+                    );
+                }
+            }
+        }
+        TokenAction::Revoke { name } => {
+            println!("Revoked token '{}'.", name);
+        }
+    }
+    Ok(())
+}
 
-    // Determine whether to GET (query) or PUT (set)
+async fn run_persist(
+    value: Option<String>,
+    socket: Option<PathBuf>,
+    server_name: String,
+    out: Output,
+) -> Result<(), WshError> {
     let persistent_value = match value.as_deref() {
         None => None,
         Some("on") => Some(true),
         Some("off") => Some(false),
-        Some(other) => {
-            eprintln!("wsh persist: expected 'on' or 'off', got '{}'", other);
+        Some(other) => out.fail_with("invalid_argument", format!("expected 'on' or 'off', got '{}'", other)),
+    };
+
+    let socket_path = resolve_socket_path(socket, &server_name);
+    let mut c = match client::Client::connect(&socket_path).await {
+        Ok(c) => c,
+        Err(e) => out.unreachable(&socket_path, e),
+    };
+
+    let is_persistent = match c.set_persist(persistent_value).await {
+        Ok(persistent) => persistent,
+        Err(e) => out.fail(e),
+    };
+    out.print(&serde_json::json!({"persistent": is_persistent}), || {
+        if is_persistent {
+            println!("Server is in persistent mode (will stay alive when sessions end).");
+        } else {
+            println!("Server is in ephemeral mode (will exit when last session ends).");
+        }
+    });
+    Ok(())
+}
+
+async fn run_call(
+    name: String,
+    method: String,
+    params: Option<String>,
+    socket: Option<PathBuf>,
+    server_name: String,
+    out: Output,
+) -> Result<(), WshError> {
+    let params = match params.as_deref().map(serde_json::from_str::<serde_json::Value>) {
+        None => None,
+        Some(Ok(value)) => Some(value),
+        Some(Err(e)) => out.fail_with("invalid_argument", format!("params are not valid JSON: {}", e)),
+    };
+
+    let socket_path = resolve_socket_path(socket, &server_name);
+    let mut c = match client::Client::connect(&socket_path).await {
+        Ok(c) => c,
+        Err(e) => out.unreachable(&socket_path, e),
+    };
+
+    match c.session_request(&name, &method, params).await {
+        Ok(result) => {
+            println!("{}", serde_json::to_string_pretty(&result).unwrap_or_default());
+        }
+        Err(e) => out.fail(e),
+    }
+
+    Ok(())
+}
+
+/// Report a failed request to the HTTP API and exit.
+fn fail_http(out: Output, bind: SocketAddr, e: reqwest::Error) -> ! {
+    if e.is_connect() {
+        out.fail_with(
+            "server_unreachable",
+            format!("could not connect to wsh server at {} — is the server running?", bind),
+        )
+    }
+    out.fail(e)
+}
+
+/// Report an error response from the HTTP API, with the code and message
+/// from its body when it has them, and exit.
+async fn fail_status(out: Output, resp: reqwest::Response) -> ! {
+    let status = resp.status();
+    let body: serde_json::Value = resp.json().await.unwrap_or_default();
+    let code = body["error"]["code"].as_str().unwrap_or("error");
+    match body["error"]["message"].as_str() {
+        Some(msg) => out.fail_with(code, msg),
+        None => out.fail_with(code, format!("server returned status {}", status)),
+    }
+}
+
+async fn run_status(
+    bind: SocketAddr,
+    token: Option<String>,
+    json: bool,
+    socket: Option<PathBuf>,
+    server_name: String,
+    out: Output,
+) -> Result<(), WshError> {
+    let token = match token {
+        Some(t) => Some(t),
+        None => {
+            let socket_path = resolve_socket_path(socket, &server_name);
+            match client::Client::connect(&socket_path).await {
+                Ok(mut c) => c.get_token().await.ok().flatten(),
+                Err(_) => None,
+            }
+        }
+    };
+
+    let mut req = reqwest::Client::new().get(format!("http://{}/server/info", bind));
+    if let Some(t) = &token {
+        req = req.bearer_auth(t);
+    }
+    let resp = match req.send().await {
+        Ok(r) => r,
+        Err(e) => fail_http(out, bind, e),
+    };
+    if !resp.status().is_success() {
+        fail_status(out, resp).await;
+    }
+    let info: serde_json::Value = resp.json().await.unwrap_or_default();
+    if json || out.is_json() {
+        println!("{}", serde_json::to_string_pretty(&info).unwrap_or_default());
+        return Ok(());
+    }
+
+    let n = |v: &serde_json::Value| v.as_u64().unwrap_or(0);
+    println!(
+        "Server:      {} (wsh {})",
+        info["hostname"].as_str().unwrap_or("-"),
+        info["version"].as_str().unwrap_or("-")
+    );
+    println!("Uptime:      {}", wsh::client::list::format_uptime(n(&info["uptime_secs"])));
+    let mode = if info["persistent"].as_bool().unwrap_or(false) { "persistent" } else { "ephemeral" };
+    let maintenance = if info["maintenance"].as_bool().unwrap_or(false) { ", in maintenance" } else { "" };
+    println!("Mode:        {}{}", mode, maintenance);
+    println!(
+        "Sessions:    {} ({} clients attached)",
+        n(&info["sessions"]),
+        n(&info["session_clients"])
+    );
+    println!("WebSockets:  {}", n(&info["ws_clients"]));
+    println!("MCP:         {}", n(&info["mcp_sessions"]));
+    let fed = &info["federation"];
+    if n(&fed["backends"]) > 0 {
+        println!(
+            "Federation:  {} backends ({} healthy, {} connecting, {} unavailable)",
+            n(&fed["backends"]),
+            n(&fed["healthy"]),
+            n(&fed["connecting"]),
+            n(&fed["unavailable"])
+        );
+    }
+    let res = &info["resources"];
+    match res["rss_bytes"].as_u64() {
+        Some(rss) => println!(
+            "Memory:      {} (peak {})",
+            format_bytes(rss),
+            format_bytes(n(&res["max_rss_bytes"]))
+        ),
+        None => println!("Memory:      peak {}", format_bytes(n(&res["max_rss_bytes"]))),
+    }
+    println!(
+        "CPU:         {:.2}s user, {:.2}s system",
+        n(&res["cpu_user_ms"]) as f64 / 1000.0,
+        n(&res["cpu_system_ms"]) as f64 / 1000.0
+    );
+    if let Some(fds) = res["open_fds"].as_u64() {
+        println!("Open files:  {}", fds);
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_import_tmux(
+    sessions: Vec<String>,
+    tmux_socket: Option<String>,
+    control: bool,
+    tags: Vec<String>,
+    bind: SocketAddr,
+    token: Option<String>,
+    socket: Option<PathBuf>,
+    server_name: String,
+) -> Result<(), WshError> {
+    let token = match token {
+        Some(t) => Some(t),
+        None => {
+            let socket_path = resolve_socket_path(socket, &server_name);
+            match client::Client::connect(&socket_path).await {
+                Ok(mut c) => c.get_token().await.ok().flatten(),
+                Err(_) => None,
+            }
+        }
+    };
+
+    let body = serde_json::json!({
+        "sessions": sessions,
+        "socket": tmux_socket,
+        "mode": if control { "control" } else { "attach" },
+        "tags": tags,
+    });
+    let mut req = reqwest::Client::new()
+        .post(format!("http://{}/tmux/import", bind))
+        .json(&body);
+    if let Some(t) = &token {
+        req = req.bearer_auth(t);
+    }
+    let resp = match req.send().await {
+        Ok(r) => r,
+        Err(e) => {
+            if e.is_connect() {
+                eprintln!("wsh import-tmux: could not connect to wsh server at {} — is the server running?", bind);
+            } else {
+                eprintln!("wsh import-tmux: {}", e);
+            }
             std::process::exit(1);
         }
     };
+    let status = resp.status();
+    let result: serde_json::Value = resp.json().await.unwrap_or_default();
+    if !status.is_success() {
+        let message = result["error"]["message"].as_str().unwrap_or_default();
+        eprintln!("wsh import-tmux: server returned status {} {}", status, message);
+        std::process::exit(1);
+    }
+
+    let empty = Vec::new();
+    let imported = result["imported"].as_array().unwrap_or(&empty);
+    let skipped = result["skipped"].as_array().unwrap_or(&empty);
+    for entry in imported {
+        println!(
+            "imported {} as {}",
+            entry["tmux"].as_str().unwrap_or("-"),
+            entry["session"].as_str().unwrap_or("-")
+        );
+    }
+    for entry in skipped {
+        println!(
+            "skipped {}: {}",
+            entry["tmux"].as_str().unwrap_or("-"),
+            entry["reason"].as_str().unwrap_or("-")
+        );
+    }
+    if imported.is_empty() && skipped.is_empty() {
+        println!("No tmux sessions found.");
+    }
+    Ok(())
+}
 
-    let resp = match persistent_value {
+/// GET `path` from the server for a debug bundle. Errors are returned as
+/// text for the bundle's `errors.txt`.
+async fn bundle_fetch(
+    http: &reqwest::Client,
+    bind: SocketAddr,
+    token: Option<&str>,
+    path: &str,
+) -> Result<Vec<u8>, String> {
+    let mut req = http.get(format!("http://{}{}", bind, path));
+    if let Some(t) = token {
+        req = req.bearer_auth(t);
+    }
+    let resp = req.send().await.map_err(|e| e.to_string())?;
+    let status = resp.status();
+    let body = resp.bytes().await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        return Err(format!("{} {}", status, String::from_utf8_lossy(&body).trim()));
+    }
+    Ok(body.to_vec())
+}
+
+/// Collect a debug bundle from the server at `bind`.
+///
+/// Whatever can't be collected is listed in `errors.txt` instead of
+/// failing the command: bundles matter most when the server is misbehaving
+/// or not running at all.
+async fn run_debug_bundle(
+    output: Option<PathBuf>,
+    include_output: bool,
+    config: Option<PathBuf>,
+    bind: SocketAddr,
+    token: Option<String>,
+    socket: Option<PathBuf>,
+    server_name: String,
+) -> Result<(), WshError> {
+    let token = match token {
+        Some(t) => Some(t),
         None => {
-            // Query current state
-            let mut req = client.get(&url);
-            if let Some(t) = &token {
-                req = req.bearer_auth(t);
+            let socket_path = resolve_socket_path(socket, &server_name);
+            match client::Client::connect(&socket_path).await {
+                Ok(mut c) => c.get_token().await.ok().flatten(),
+                Err(_) => None,
             }
-            match req.send().await {
-                Ok(r) => r,
-                Err(e) => {
-                    if e.is_connect() {
-                        eprintln!("wsh persist: could not connect to wsh server at {} — is the server running?", bind);
-                    } else {
-                        eprintln!("wsh persist: {}", e);
-                    }
-                    std::process::exit(1);
+        }
+    };
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let dir = format!("wsh-debug-{}", now);
+    let output = output.unwrap_or_else(|| PathBuf::from(format!("{}.tar.gz", dir)));
+    let http = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .build()
+        .map_err(|e| WshError::Config(e.to_string()))?;
+
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut errors: Vec<String> = Vec::new();
+    let pretty = |mut value: serde_json::Value| {
+        wsh::bundle::redact_json(&mut value);
+        serde_json::to_vec_pretty(&value).unwrap_or_default()
+    };
+
+    let config_path = config.unwrap_or_else(default_config_path);
+    files.push((
+        "version.json".into(),
+        pretty(serde_json::json!({
+            "client_version": env!("CARGO_PKG_VERSION"),
+            "os": std::env::consts::OS,
+            "arch": std::env::consts::ARCH,
+            "server_name": server_name,
+            "bind": bind.to_string(),
+            "config_path": config_path,
+            "include_output": include_output,
+            "created_unix": now,
+        })),
+    ));
+
+    let mut session_names = Vec::new();
+    for (file, path) in [
+        ("server/info.json", "/server/info"),
+        ("server/features.json", "/server/features"),
+        ("servers.json", "/servers"),
+        ("sessions.json", "/sessions?federated=false"),
+    ] {
+        match bundle_fetch(&http, bind, token.as_deref(), path).await {
+            Ok(body) => {
+                let value: serde_json::Value = serde_json::from_slice(&body).unwrap_or_default();
+                if file == "sessions.json" {
+                    session_names = value
+                        .as_array()
+                        .map(|list| list.iter().filter_map(|s| s["name"].as_str().map(String::from)).collect())
+                        .unwrap_or_default();
                 }
+                files.push((file.into(), pretty(value)));
             }
+            Err(e) => errors.push(format!("GET {}: {}", path, e)),
         }
-        Some(val) => {
-            // Set new state
-            let mut req = client.put(&url).json(&serde_json::json!({"persistent": val}));
-            if let Some(t) = &token {
-                req = req.bearer_auth(t);
+    }
+    match bundle_fetch(&http, bind, token.as_deref(), "/server/logs").await {
+        Ok(body) => {
+            let value: serde_json::Value = serde_json::from_slice(&body).unwrap_or_default();
+            let mut logs = String::new();
+            for line in value["lines"].as_array().into_iter().flatten() {
+                logs.push_str(line.as_str().unwrap_or_default());
+                logs.push('\n');
             }
-            match req.send().await {
-                Ok(r) => r,
-                Err(e) => {
-                    if e.is_connect() {
-                        eprintln!("wsh persist: could not connect to wsh server at {} — is the server running?", bind);
-                    } else {
-                        eprintln!("wsh persist: {}", e);
+            files.push(("server/logs.txt".into(), logs.into_bytes()));
+        }
+        Err(e) => errors.push(format!("GET /server/logs: {}", e)),
+    }
+
+    match std::fs::read_to_string(&config_path) {
+        Ok(text) => match wsh::bundle::redact_toml(&text) {
+            Ok(redacted) => files.push(("config.toml".into(), redacted.into_bytes())),
+            // Left out rather than risk copying credentials unredacted.
+            Err(e) => errors.push(format!("{}: not valid TOML: {}", config_path.display(), e)),
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => errors.push(format!("{}: {}", config_path.display(), e)),
+    }
+
+    if include_output {
+        for name in &session_names {
+            let screen = format!("/sessions/{}/screen?format=plain", name);
+            match bundle_fetch(&http, bind, token.as_deref(), &screen).await {
+                Ok(body) => {
+                    let value: serde_json::Value = serde_json::from_slice(&body).unwrap_or_default();
+                    let mut text = String::new();
+                    for line in value["lines"].as_array().into_iter().flatten() {
+                        text.push_str(line.as_str().unwrap_or_default());
+                        text.push('\n');
                     }
-                    std::process::exit(1);
+                    files.push((format!("sessions/{}/screen.txt", name), text.into_bytes()));
                 }
+                Err(e) => errors.push(format!("GET {}: {}", screen, e)),
+            }
+            let scrollback = format!("/sessions/{}/scrollback/export?format=plain", name);
+            match bundle_fetch(&http, bind, token.as_deref(), &scrollback).await {
+                Ok(body) => files.push((format!("sessions/{}/scrollback.txt", name), body)),
+                Err(e) => errors.push(format!("GET {}: {}", scrollback, e)),
+            }
+            let audit = format!("/sessions/{}/audit", name);
+            match bundle_fetch(&http, bind, token.as_deref(), &audit).await {
+                Ok(body) => {
+                    let value = serde_json::from_slice(&body).unwrap_or_default();
+                    files.push((format!("sessions/{}/audit.json", name), pretty(value)));
+                }
+                Err(e) => errors.push(format!("GET {}: {}", audit, e)),
             }
         }
-    };
+    }
 
-    if !resp.status().is_success() {
-        eprintln!("wsh persist: server returned status {}", resp.status());
-        std::process::exit(1);
+    if !errors.is_empty() {
+        let mut text = errors.join("\n");
+        text.push('\n');
+        files.push(("errors.txt".into(), text.into_bytes()));
     }
 
-    let body: serde_json::Value = resp.json().await.unwrap_or_default();
-    let is_persistent = body["persistent"].as_bool().unwrap_or(false);
-    if is_persistent {
-        println!("Server is in persistent mode (will stay alive when sessions end).");
-    } else {
-        println!("Server is in ephemeral mode (will exit when last session ends).");
+    // Owner-only: even redacted, the bundle describes the machine.
+    use std::os::unix::fs::OpenOptionsExt;
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&output)?;
+    let mut tar = wsh::bundle::TarWriter::new(std::io::BufWriter::new(file), now);
+    for (name, data) in &files {
+        tar.append(&format!("{}/{}", dir, name), data)?;
+    }
+    tar.finish()?.into_inner().map_err(|e| e.into_error())?;
+
+    println!("Wrote {}", output.display());
+    if !errors.is_empty() {
+        println!("{} item(s) could not be collected; see errors.txt in the bundle.", errors.len());
+    }
+    if !include_output {
+        println!("Terminal contents are not included; pass --include-output to add them.");
     }
     Ok(())
 }
 
+fn format_bytes(bytes: u64) -> String {
+    const MIB: f64 = 1024.0 * 1024.0;
+    if bytes as f64 >= 1024.0 * MIB {
+        format!("{:.1} GiB", bytes as f64 / (1024.0 * MIB))
+    } else {
+        format!("{:.1} MiB", bytes as f64 / MIB)
+    }
+}
@@ -0,0 +1,131 @@
+//! Size caps on tool results.
+//!
+//! A full scrollback page can run to megabytes of styled JSON, more than an
+//! MCP host can put in front of a model. Screen and scrollback results are
+//! cut to a byte budget (see [`McpConfig`](crate::config::McpConfig)) by
+//! dropping lines from the end, and marked with `truncated: true` and a
+//! `next_offset` to pass as `offset` to `wsh_get_scrollback` to continue.
+
+use rmcp::model::{CallToolResult, Content};
+use serde_json::Value;
+
+/// Room left for the `truncated` and `next_offset` fields.
+const METADATA_BYTES: usize = 64;
+
+fn json_len(value: &Value) -> usize {
+    serde_json::to_vec(value).map(|v| v.len()).unwrap_or(0)
+}
+
+/// Drop lines from the end of a screen or scrollback result until it
+/// serializes to at most `limit` bytes. At least one line is kept so that
+/// paging always makes progress. Returns whether anything was dropped.
+pub fn truncate_lines(result: &mut Value, limit: usize) -> bool {
+    let size = json_len(result);
+    if size <= limit {
+        return false;
+    }
+    // Line numbers are absolute: scrollback results start at `offset`,
+    // screens at `first_line_index`.
+    let start = result["offset"]
+        .as_u64()
+        .or_else(|| result["first_line_index"].as_u64())
+        .unwrap_or(0);
+    let Some(lines) = result.get_mut("lines").and_then(Value::as_array_mut) else {
+        return false;
+    };
+    let mut excess = size + METADATA_BYTES - limit;
+    let mut keep = lines.len();
+    while keep > 1 && excess > 0 {
+        keep -= 1;
+        // The line plus its separating comma.
+        excess = excess.saturating_sub(json_len(&lines[keep]) + 1);
+    }
+    if keep == lines.len() {
+        return false;
+    }
+    lines.truncate(keep);
+    result["truncated"] = Value::Bool(true);
+    result["next_offset"] = Value::from(start + keep as u64);
+    true
+}
+
+/// Apply [`truncate_lines`] to a result's JSON text, leaving text that
+/// isn't a JSON object untouched.
+pub fn fit_text(text: String, limit: Option<usize>) -> String {
+    let Some(limit) = limit.filter(|&limit| text.len() > limit) else {
+        return text;
+    };
+    let Ok(mut value) = serde_json::from_str::<Value>(&text) else {
+        return text;
+    };
+    if truncate_lines(&mut value, limit) {
+        serde_json::to_string(&value).unwrap_or(text)
+    } else {
+        text
+    }
+}
+
+/// Apply [`fit_text`] to a proxied tool result.
+pub fn fit_call_result(mut result: CallToolResult, limit: Option<usize>) -> CallToolResult {
+    if let Some(text) = result.content.first().and_then(|c| c.as_text()).map(|t| t.text.clone()) {
+        result.content[0] = Content::text(fit_text(text, limit));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scrollback(offset: usize, count: usize) -> Value {
+        let lines: Vec<String> = (0..count).map(|i| format!("line {:04} {}", offset + i, "x".repeat(80))).collect();
+        serde_json::json!({ "epoch": 1, "lines": lines, "total_lines": 10_000, "offset": offset })
+    }
+
+    #[test]
+    fn small_results_are_untouched() {
+        let mut value = scrollback(0, 3);
+        let before = value.clone();
+        assert!(!truncate_lines(&mut value, 100_000));
+        assert_eq!(value, before);
+    }
+
+    #[test]
+    fn large_results_fit_the_budget_and_say_where_to_continue() {
+        let mut value = scrollback(500, 1000);
+        assert!(truncate_lines(&mut value, 10_000));
+        assert!(json_len(&value) <= 10_000);
+        let kept = value["lines"].as_array().unwrap().len();
+        assert!(kept > 50 && kept < 1000, "kept {kept}");
+        assert_eq!(value["truncated"], true);
+        assert_eq!(value["next_offset"], 500 + kept as u64);
+        assert_eq!(value["lines"][kept - 1], format!("line {:04} {}", 500 + kept - 1, "x".repeat(80)));
+    }
+
+    #[test]
+    fn screens_continue_from_their_first_line_index() {
+        let lines: Vec<String> = (0..40).map(|_| "y".repeat(200)).collect();
+        let mut value = serde_json::json!({ "first_line_index": 9000, "lines": lines, "cols": 200, "rows": 40 });
+        assert!(truncate_lines(&mut value, 2_000));
+        let kept = value["lines"].as_array().unwrap().len();
+        assert_eq!(value["next_offset"], 9000 + kept as u64);
+    }
+
+    #[test]
+    fn one_line_is_always_kept() {
+        let mut value = scrollback(7, 2);
+        assert!(truncate_lines(&mut value, 10));
+        assert_eq!(value["lines"].as_array().unwrap().len(), 1);
+        assert_eq!(value["next_offset"], 8);
+    }
+
+    #[test]
+    fn text_without_lines_is_left_alone() {
+        let text = format!("{{\"error\":\"{}\"}}", "e".repeat(100));
+        assert_eq!(fit_text(text.clone(), Some(10)), text);
+        assert_eq!(fit_text("not json".repeat(10), Some(10)), "not json".repeat(10));
+        let big = serde_json::to_string(&scrollback(0, 100)).unwrap();
+        assert_eq!(fit_text(big.clone(), None), big);
+        assert!(fit_text(big, Some(1000)).len() <= 1000);
+    }
+}
@@ -1,7 +1,10 @@
 pub mod tools;
 pub mod resources;
 pub mod prompts;
+pub mod budget;
+pub mod progress;
 
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -19,8 +22,51 @@ use rmcp::{
 use crate::api::AppState;
 use crate::federation::registry::{BackendEntry, BackendHealth};
 use crate::parser::state::Query;
-use crate::pty::SpawnCommand;
-use crate::session::{RegistryError, Session};
+use crate::pty::{Priority, RunAs, SpawnCommand};
+use crate::ssh::SshLaunch;
+use crate::session::{Delivery, InputError, RegistryError, RestartPolicy, Session, SpawnOptions};
+
+/// The client's MCP session, taken from the `Mcp-Session-Id` header of
+/// the HTTP request carrying the tool call.
+fn mcp_session_id(extensions: &Extensions) -> Option<String> {
+    extensions
+        .get::<axum::http::request::Parts>()
+        .and_then(|parts| parts.headers.get("mcp-session-id"))
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Attribute MCP input to the client's MCP session.
+fn mcp_input_source(extensions: &Extensions) -> crate::input::InputSource {
+    crate::input::InputSource::Mcp { session: mcp_session_id(extensions) }
+}
+
+/// The error for input refused by the session's input policy.
+fn input_blocked(pattern: String) -> ErrorData {
+    ErrorData::invalid_params(format!("input blocked by policy: matches '{pattern}'"), None)
+}
+
+fn input_held(e: InputError) -> ErrorData {
+    ErrorData::invalid_request(e.to_string(), None)
+}
+
+/// The result of a tool whose input the session holds for approval.
+fn held_result(pending: &crate::input::PendingInput) -> CallToolResult {
+    let result = serde_json::json!({
+        "status": "held",
+        "approval": pending.id,
+        "pattern": pending.pattern,
+        "hint": "The input matches an approve pattern and waits for a human to approve it. Check GET /sessions/{name}/approvals or the screen later.",
+    });
+    CallToolResult::success(vec![Content::text(serde_json::to_string(&result).unwrap_or_default())])
+}
+
+fn file_error(e: crate::files::FileError) -> ErrorData {
+    match e {
+        crate::files::FileError::Io(_) => ErrorData::internal_error(e.to_string(), None),
+        _ => ErrorData::invalid_params(e.to_string(), None),
+    }
+}
 
 /// Maximum allowed value for timeout_ms and max_wait_ms parameters.
 const MAX_WAIT_CEILING_MS: u64 = 300_000; // 5 minutes
@@ -33,11 +79,12 @@ const PROXY_IDLE_TIMEOUT: Duration = Duration::from_secs(330);
 
 use tools::{
     CreateSessionParams, ListSessionsParams, ManageSessionParams, ManageAction,
-    SendInputParams, Encoding, GetScreenParams, GetScrollbackParams,
-    AwaitIdleParams, RunCommandParams,
+    SendInputParams, BroadcastInputParams, SendKeysParams, SendSecretParams, Encoding, GetScreenParams, GetScrollbackParams,
+    GetInsightsParams, GetLinksParams,
+    AwaitIdleParams, AwaitIdleGroupParams, GroupIdleMode, RunCommandParams,
     OverlayParams, RemoveOverlayParams, PanelParams, RemovePanelParams,
     InputModeParams, InputModeAction, ScreenModeParams, ScreenModeAction,
-    ListServersParams, AddServerParams, RemoveServerParams, ServerStatusParams,
+    ListServersParams, ServerStatusParams, ReadFileParams, WriteFileParams,
 };
 
 // ── Federation helpers ─────────────────────────────────────────────
@@ -242,14 +289,43 @@ pub struct WshMcpServer {
     tool_router: ToolRouter<WshMcpServer>,
     /// Shared counter for active MCP sessions. Decremented on Drop.
     session_counter: Option<Arc<std::sync::atomic::AtomicUsize>>,
+    /// Resources the host has subscribed to.
+    subscriptions: Arc<resources::Subscriptions>,
+    /// Cleans up the sessions this MCP session created once it ends.
+    owned: Arc<OwnedSessions>,
+}
+
+/// The MCP session a [`WshMcpServer`] serves, learnt from the first
+/// session it creates. Dropped with the last clone of the server, when
+/// the MCP session ends, and then applies each owned session's
+/// `cleanup_on_disconnect`.
+struct OwnedSessions {
+    sessions: crate::session::SessionRegistry,
+    mcp_session: std::sync::OnceLock<String>,
+}
+
+impl Drop for OwnedSessions {
+    fn drop(&mut self) {
+        if let Some(mcp_session) = self.mcp_session.get() {
+            self.sessions.release_owner(mcp_session);
+        }
+    }
 }
 
 impl WshMcpServer {
     pub fn new(state: AppState) -> Self {
+        let tool_router = Self::tool_router();
+        #[cfg(feature = "federation")]
+        let tool_router = tool_router + Self::federation_tool_router();
         Self {
+            owned: Arc::new(OwnedSessions {
+                sessions: state.sessions.clone(),
+                mcp_session: std::sync::OnceLock::new(),
+            }),
             state,
-            tool_router: Self::tool_router(),
+            tool_router,
             session_counter: None,
+            subscriptions: Arc::default(),
         }
     }
 
@@ -259,30 +335,72 @@ impl WshMcpServer {
         self
     }
 
+    /// The result size cap for `tool`. See [`budget`].
+    fn result_limit(&self, tool: &str) -> Option<usize> {
+        self.state.server_config.mcp_result_limit(tool)
+    }
+
+    /// Serialize a screen or scrollback result, cut to `tool`'s budget.
+    fn lines_result(&self, tool: &str, response: &impl serde::Serialize) -> CallToolResult {
+        let mut value = serde_json::to_value(response).unwrap_or_default();
+        if let Some(limit) = self.result_limit(tool) {
+            budget::truncate_lines(&mut value, limit);
+        }
+        CallToolResult::success(vec![Content::text(value.to_string())])
+    }
+
+    /// Look up a session for a tool call, counting the call in the
+    /// session's usage.
     fn get_session(&self, name: &str) -> Result<crate::session::Session, ErrorData> {
-        self.state
+        let session = self
+            .state
             .sessions
             .get(name)
-            .ok_or_else(|| ErrorData::invalid_params(format!("session not found: {name}"), None))
+            .ok_or_else(|| ErrorData::invalid_params(format!("session not found: {name}"), None))?;
+        session.usage.record_mcp_call();
+        Ok(session)
     }
 
     /// Resolve whether a request targets the local server or a remote backend.
     ///
     /// - `None` always means local.
     /// - Matching the local hostname means local.
-    /// - Otherwise, looks up the hostname in the backend registry.
-    fn resolve_server(&self, server: Option<&str>) -> Result<McpSessionTarget, ErrorData> {
+    /// - Otherwise, looks up the hostname in the backend registry. If the
+    ///   backend is unhealthy, waits up to the configured unhealthy-backend
+    ///   wait for it to recover before failing.
+    async fn resolve_server(&self, server: Option<&str>) -> Result<McpSessionTarget, ErrorData> {
         match server {
             None => Ok(McpSessionTarget::Local),
             Some(s) if s == self.state.hostname => Ok(McpSessionTarget::Local),
+            Some(_) if !self.state.backends.proxying() => Err(ErrorData::invalid_request(
+                "federation proxying is switched off on this server",
+                None,
+            )),
             Some(s) => {
-                let backend = self.state.backends.get_by_hostname(s).ok_or_else(|| {
-                    ErrorData::invalid_params(format!("server not found: {s}"), None)
-                })?;
+                let wait = self.state.backends.unhealthy_wait();
+                let started = std::time::Instant::now();
+                let backend = self
+                    .state
+                    .backends
+                    .wait_healthy(s, wait)
+                    .await
+                    .ok_or_else(|| {
+                        ErrorData::invalid_params(format!("server not found: {s}"), None)
+                    })?;
                 if backend.health != BackendHealth::Healthy {
+                    let waited = started.elapsed();
                     return Err(ErrorData::internal_error(
-                        format!("server unavailable: {s}"),
-                        None,
+                        format!(
+                            "server unavailable: {s} (health: {}, waited {}ms)",
+                            backend.health.as_str(),
+                            waited.as_millis()
+                        ),
+                        Some(serde_json::json!({
+                            "server": s,
+                            "address": backend.address,
+                            "health": backend.health,
+                            "waited_ms": waited.as_millis() as u64,
+                        })),
                     ));
                 }
                 Ok(McpSessionTarget::Remote(backend))
@@ -307,6 +425,7 @@ impl ServerHandler for WshMcpServer {
             capabilities: ServerCapabilities::builder()
                 .enable_tools()
                 .enable_resources()
+                .enable_resources_subscribe()
                 .enable_prompts()
                 .build(),
             server_info: Implementation {
@@ -357,6 +476,23 @@ impl ServerHandler for WshMcpServer {
         resources::read_resource(&self.state, request).await
     }
 
+    async fn subscribe(
+        &self,
+        request: SubscribeRequestParams,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), ErrorData> {
+        self.subscriptions.subscribe(&self.state, &request.uri, context.peer)
+    }
+
+    async fn unsubscribe(
+        &self,
+        request: UnsubscribeRequestParams,
+        _: RequestContext<RoleServer>,
+    ) -> Result<(), ErrorData> {
+        self.subscriptions.unsubscribe(&request.uri);
+        Ok(())
+    }
+
     async fn list_prompts(
         &self,
         _request: Option<PaginatedRequestParams>,
@@ -380,11 +516,19 @@ impl WshMcpServer {
     #[tool(description = "Create a new terminal session. Spawns an interactive shell by default, or runs a specific command. Returns the assigned session name and terminal dimensions. Use 'server' to target a remote federated server.")]
     async fn wsh_create_session(
         &self,
-        Parameters(params): Parameters<CreateSessionParams>,
+        Parameters(mut params): Parameters<CreateSessionParams>,
+        extensions: Extensions,
     ) -> Result<CallToolResult, ErrorData> {
         // Federation: proxy to remote if server is specified.
-        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref())? {
+        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref()).await? {
+            if params.cleanup_on_disconnect.is_some() {
+                return Err(ErrorData::invalid_params(
+                    "cleanup_on_disconnect only applies to sessions on this server".to_string(),
+                    None,
+                ));
+            }
             let mut body = serde_json::json!({});
+            if let Some(template) = &params.template { body["template"] = serde_json::json!(template); }
             if let Some(name) = &params.name { body["name"] = serde_json::json!(name); }
             if let Some(cmd) = &params.command { body["command"] = serde_json::json!(cmd); }
             if let Some(rows) = params.rows { body["rows"] = serde_json::json!(rows); }
@@ -392,17 +536,72 @@ impl WshMcpServer {
             if let Some(cwd) = &params.cwd { body["cwd"] = serde_json::json!(cwd); }
             if let Some(env) = &params.env { body["env"] = serde_json::json!(env); }
             if !params.tags.is_empty() { body["tags"] = serde_json::json!(params.tags); }
+            if let Some(user) = &params.user { body["user"] = serde_json::json!(user); }
+            if let Some(uid) = params.uid { body["uid"] = serde_json::json!(uid); }
+            if let Some(sandbox) = &params.sandbox { body["sandbox"] = serde_json::json!(sandbox); }
+            if let Some(remote) = &params.remote { body["remote"] = serde_json::json!(remote); }
+            if let Some(after) = &params.idle_kill_after { body["idle_kill_after"] = serde_json::json!(after); }
+            if let Some(nice) = params.nice { body["nice"] = serde_json::json!(nice); }
+            if let Some(ionice) = &params.ionice { body["ionice"] = serde_json::json!(ionice); }
+            if let Some(lines) = params.scrollback_lines { body["scrollback_lines"] = serde_json::json!(lines); }
+            if let Some(policy) = params.query_policy { body["query_policy"] = serde_json::json!(policy); }
+            if let Some(encoding) = params.encoding { body["encoding"] = serde_json::json!(encoding); }
+            if let Some(restart) = params.restart { body["restart"] = serde_json::json!(restart); }
+            if let Some(max) = params.restart_max_retries { body["restart_max_retries"] = serde_json::json!(max); }
+            if let Some(ms) = params.restart_backoff_ms { body["restart_backoff_ms"] = serde_json::json!(ms); }
             return proxy_post_json(&backend, "/sessions", body).await;
         }
 
+        let template = match params.template {
+            Some(ref name) => {
+                let template = self.state.server_config.templates().get(name).ok_or_else(|| {
+                    ErrorData::invalid_params(format!("template not found: {name}"), None)
+                })?;
+                template.fill(
+                    &mut params.command,
+                    &mut params.cwd,
+                    &mut params.env,
+                    &mut params.rows,
+                    &mut params.cols,
+                    &mut params.tags,
+                );
+                Some(template)
+            }
+            None => None,
+        };
+
+        let run_as = RunAs::resolve(params.user.as_deref(), params.uid)
+            .map_err(|e| ErrorData::invalid_params(e.to_string(), None))?;
+        let sandbox = self
+            .state
+            .sessions
+            .resolve_sandbox(params.sandbox.as_deref())
+            .map_err(|e| ErrorData::invalid_params(e, None))?;
+        let ssh = SshLaunch::from_request(params.command.as_deref(), params.remote.as_deref())
+            .map_err(|e| ErrorData::invalid_params(e, None))?;
+        let idle_kill_after = params
+            .idle_kill_after
+            .as_deref()
+            .map(crate::config::parse_duration)
+            .transpose()
+            .map_err(|e| ErrorData::invalid_params(e, None))?;
+        let priority = Priority::resolve(params.nice, params.ionice.as_deref())
+            .map_err(|e| ErrorData::invalid_params(e, None))?;
+        let scrollback_lines = crate::session::validate_scrollback_lines(
+            params.scrollback_lines.unwrap_or_else(|| self.state.sessions.scrollback_lines()),
+        )
+        .map_err(|e| ErrorData::invalid_params(e, None))?;
+        let restart = RestartPolicy::parse(params.restart, params.restart_max_retries, params.restart_backoff_ms)
+            .map_err(|e| ErrorData::invalid_params(e, None))?;
         let param_name = params.name;
         let tags = params.tags;
-        let command = match params.command {
-            Some(cmd) => SpawnCommand::Command {
+        let command = match (&ssh, params.command) {
+            (Some(launch), _) => launch.spawn_command(),
+            (None, Some(cmd)) => SpawnCommand::Command {
                 command: cmd,
                 interactive: true,
             },
-            None => SpawnCommand::Shell {
+            (None, None) => SpawnCommand::Shell {
                 interactive: true,
                 shell: None,
             },
@@ -426,6 +625,10 @@ impl WshMcpServer {
                 "maximum number of sessions reached".to_string(),
                 None,
             ),
+            RegistryError::Maintenance(reason) => ErrorData::internal_error(
+                format!("server is in maintenance, not accepting new sessions: {reason}"),
+                None,
+            ),
             RegistryError::InvalidTag(msg) => ErrorData::invalid_params(
                 format!("invalid tag: {msg}"),
                 None,
@@ -436,12 +639,27 @@ impl WshMcpServer {
             ),
         })?;
 
+        if let Some(ref launch) = ssh {
+            launch.create_log(run_as.as_ref()).map_err(|e| {
+                ErrorData::internal_error(format!("failed to create ssh log: {e}"), None)
+            })?;
+        }
+
         // spawn_with_options calls fork()/exec() -- run on blocking pool.
-        let cwd = params.cwd;
-        let env = params.env;
-        let (session, child_exit_rx) =
+        let options = SpawnOptions {
+            cwd: params.cwd,
+            env: params.env,
+            motd: self.state.sessions.motd(),
+            run_as,
+            sandbox,
+            safe_mode: None,
+            priority,
+            scrollback_lines,
+            restart,
+        };
+        let (session, mut child_exit_rx) =
             tokio::task::spawn_blocking(move || {
-                Session::spawn_with_options("".to_string(), command, rows, cols, cwd, env)
+                Session::spawn_with_options("".to_string(), command, rows, cols, options)
             })
             .await
             .map_err(|e| ErrorData::internal_error(format!("spawn task failed: {e}"), None))?
@@ -452,6 +670,13 @@ impl WshMcpServer {
                 )
             })?;
 
+        if let Some(ref launch) = ssh {
+            if let Err(e) = launch.wait_connected(&session.usage, &mut child_exit_rx).await {
+                session.shutdown();
+                return Err(ErrorData::internal_error(format!("ssh connection failed: {e}"), None));
+            }
+        }
+
         // Validate and set initial tags before registry insertion
         if !tags.is_empty() {
             for tag in &tags {
@@ -461,6 +686,25 @@ impl WshMcpServer {
             }
             *session.tags.write() = tags.into_iter().collect();
         }
+        *session.idle_kill_after.lock() = idle_kill_after;
+        *session.query_policy.write() = params.query_policy.unwrap_or_default();
+        session.parser.set_encoding(params.encoding.unwrap_or_default());
+        let mcp_session = mcp_session_id(&extensions);
+        if let Some(ref id) = mcp_session {
+            self.owned.mcp_session.get_or_init(|| id.clone());
+        }
+        *session.owner.lock() = Some(crate::session::Owner {
+            token: extensions
+                .get::<axum::http::request::Parts>()
+                .and_then(|parts| parts.extensions.get::<crate::api::tokens::TokenGrant>())
+                .map(|grant| grant.name.clone()),
+            cleanup_on_disconnect: mcp_session.as_ref().map(|_| {
+                params
+                    .cleanup_on_disconnect
+                    .unwrap_or_else(|| self.state.server_config.mcp_cleanup_on_disconnect())
+            }),
+            mcp_session,
+        });
 
         let (assigned_name, session) =
             match self.state.sessions.insert_and_get(param_name, session.clone()) {
@@ -480,6 +724,10 @@ impl WshMcpServer {
                             "maximum number of sessions reached".to_string(),
                             None,
                         ),
+                        RegistryError::Maintenance(reason) => ErrorData::internal_error(
+                            format!("server is in maintenance, not accepting new sessions: {reason}"),
+                            None,
+                        ),
                         RegistryError::InvalidTag(msg) => ErrorData::invalid_params(
                             format!("invalid tag: {msg}"),
                             None,
@@ -497,11 +745,17 @@ impl WshMcpServer {
             .sessions
             .monitor_child_exit(assigned_name.clone(), session.client_count.clone(), session.child_exited.clone(), child_exit_rx);
 
+        if let Some(template) = template {
+            if let Err(e) = template.decorate(&session).await {
+                tracing::warn!(session = %assigned_name, error = %e, "failed to add template overlays and panels");
+            }
+        }
+
         let mut result_tags: Vec<String> = session.tags.read().iter().cloned().collect();
         result_tags.sort();
         let result = serde_json::json!({
             "name": assigned_name,
-            "pid": session.pid,
+            "pid": session.pid(),
             "rows": rows,
             "cols": cols,
             "tags": result_tags,
@@ -513,13 +767,13 @@ impl WshMcpServer {
     }
 
     /// List all sessions or get details for a specific session.
-    #[tool(description = "List all terminal sessions, or get details for a specific session by name. Returns session names and terminal dimensions. Use 'server' to target a remote federated server.")]
+    #[tool(description = "List all terminal sessions, or get details for a specific session by name. Returns session names, terminal dimensions, metadata, creation and last-activity times (Unix ms), and the window title and working directory the program last reported. Use 'server' to target a remote federated server.")]
     async fn wsh_list_sessions(
         &self,
         Parameters(params): Parameters<ListSessionsParams>,
     ) -> Result<CallToolResult, ErrorData> {
         // Federation: proxy to remote if server is specified.
-        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref())? {
+        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref()).await? {
             let mut path = "/sessions".to_string();
             if let Some(ref name) = params.session {
                 // Single session detail
@@ -538,12 +792,19 @@ impl WshMcpServer {
             tags.sort();
             let result = serde_json::json!({
                 "name": session.name,
-                "pid": session.pid,
+                "pid": session.pid(),
                 "command": session.command,
                 "rows": rows,
                 "cols": cols,
                 "clients": session.clients(),
                 "tags": tags,
+                "created_at": session.created_at,
+                "last_activity_at": session.activity.last_activity_at(),
+                "last_activity_ms": session.activity.last_activity_ms(),
+                "title": session.parser.title(),
+                "cwd": session.parser.cwd(),
+                "owner": *session.owner.lock(),
+                "metadata": *session.metadata.read(),
             });
             Ok(CallToolResult::success(vec![Content::text(
                 serde_json::to_string(&result).unwrap_or_default(),
@@ -564,12 +825,19 @@ impl WshMcpServer {
                     tags.sort();
                     Some(serde_json::json!({
                         "name": name,
-                        "pid": session.pid,
+                        "pid": session.pid(),
                         "command": session.command.clone(),
                         "rows": rows,
                         "cols": cols,
                         "clients": session.clients(),
                         "tags": tags,
+                        "created_at": session.created_at,
+                        "last_activity_at": session.activity.last_activity_at(),
+                        "last_activity_ms": session.activity.last_activity_ms(),
+                        "title": session.parser.title(),
+                        "cwd": session.parser.cwd(),
+                        "owner": *session.owner.lock(),
+                        "metadata": *session.metadata.read(),
                     }))
                 })
                 .collect();
@@ -582,13 +850,13 @@ impl WshMcpServer {
     }
 
     /// Manage an existing session: kill, rename, detach, add_tags, or remove_tags.
-    #[tool(description = "Manage a terminal session. Actions: 'kill' destroys the session, 'rename' changes its name (requires new_name), 'detach' disconnects all streaming clients, 'add_tags' adds tags (requires tags), 'remove_tags' removes tags (requires tags). Use 'server' to target a remote federated server.")]
+    #[tool(description = "Manage a terminal session. Actions: 'kill' destroys the session, 'rename' changes its name (requires new_name), 'detach' disconnects all streaming clients, 'resume' continues a child stopped by SIGTSTP/SIGSTOP or a hibernated session, 'hibernate' stops the child and moves its screen and scrollback to disk, 'add_tags' adds tags (requires tags), 'remove_tags' removes tags (requires tags). Use 'server' to target a remote federated server.")]
     async fn wsh_manage_session(
         &self,
         Parameters(params): Parameters<ManageSessionParams>,
     ) -> Result<CallToolResult, ErrorData> {
         // Federation: proxy to remote if server is specified.
-        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref())? {
+        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref()).await? {
             return match params.action {
                 ManageAction::Kill => {
                     proxy_delete(&backend, &format!("/sessions/{}", params.session)).await
@@ -610,6 +878,20 @@ impl WshMcpServer {
                         serde_json::json!({}),
                     ).await
                 }
+                ManageAction::Resume => {
+                    proxy_post_json(
+                        &backend,
+                        &format!("/sessions/{}/resume", params.session),
+                        serde_json::json!({}),
+                    ).await
+                }
+                ManageAction::Hibernate => {
+                    proxy_post_json(
+                        &backend,
+                        &format!("/sessions/{}/hibernate", params.session),
+                        serde_json::json!({}),
+                    ).await
+                }
                 ManageAction::AddTags => {
                     if params.tags.is_empty() {
                         return Err(ErrorData::invalid_params(
@@ -685,6 +967,10 @@ impl WshMcpServer {
                             "maximum number of sessions reached".to_string(),
                             None,
                         ),
+                        RegistryError::Maintenance(reason) => ErrorData::internal_error(
+                            format!("server is in maintenance, not accepting new sessions: {reason}"),
+                            None,
+                        ),
                         RegistryError::InvalidTag(msg) => ErrorData::invalid_params(
                             format!("invalid tag: {msg}"),
                             None,
@@ -718,6 +1004,35 @@ impl WshMcpServer {
                 )]))
             }
 
+            ManageAction::Hibernate => {
+                let session = self.get_session(&params.session)?;
+                session
+                    .hibernate()
+                    .await
+                    .map_err(|e| ErrorData::internal_error(format!("hibernation failed: {e}"), None))?;
+
+                let result = serde_json::json!({
+                    "status": "hibernated",
+                    "session": params.session,
+                });
+                Ok(CallToolResult::success(vec![Content::text(
+                    serde_json::to_string(&result).unwrap_or_default(),
+                )]))
+            }
+
+            ManageAction::Resume => {
+                let session = self.get_session(&params.session)?;
+                session.resume();
+
+                let result = serde_json::json!({
+                    "status": "resumed",
+                    "session": params.session,
+                });
+                Ok(CallToolResult::success(vec![Content::text(
+                    serde_json::to_string(&result).unwrap_or_default(),
+                )]))
+            }
+
             ManageAction::AddTags => {
                 if params.tags.is_empty() {
                     return Err(ErrorData::invalid_params(
@@ -790,10 +1105,11 @@ impl WshMcpServer {
     #[tool(description = "Send input to a terminal session. Supports UTF-8 text (default) or base64-encoded binary data. The input is delivered to the PTY exactly as provided -- no newline is appended automatically. Use 'server' to target a remote federated server.")]
     async fn wsh_send_input(
         &self,
+        extensions: Extensions,
         Parameters(params): Parameters<SendInputParams>,
     ) -> Result<CallToolResult, ErrorData> {
         // Federation: proxy to remote if server is specified.
-        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref())? {
+        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref()).await? {
             // Decode the input to raw bytes for the remote HTTP endpoint.
             let data = match params.encoding {
                 Encoding::Utf8 => Bytes::from(params.input.into_bytes()),
@@ -833,6 +1149,12 @@ impl WshMcpServer {
         };
 
         let len = data.len();
+        session.policy.check_input(&data).map_err(input_blocked)?;
+        let source = mcp_input_source(&extensions);
+        if let Some(pending) = session.hold_input(&source, &data).map_err(input_held)? {
+            return Ok(held_result(&pending));
+        }
+        session.input_broadcaster.record_input(source, &data);
         tokio::time::timeout(
             Duration::from_secs(5),
             session.input_tx.send(data),
@@ -856,6 +1178,158 @@ impl WshMcpServer {
         )]))
     }
 
+    /// Send the same input to every session carrying one of the tags.
+    #[tool(description = "Send the same input to every local session carrying any of the given tags, e.g. to run one command in all worker shells at once. Sessions get the input concurrently; the result lists, per session, whether it was sent or why not. Supports UTF-8 text (default) or base64-encoded binary data. No newline is appended automatically.")]
+    async fn wsh_broadcast_input(
+        &self,
+        extensions: Extensions,
+        Parameters(params): Parameters<BroadcastInputParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        if params.tag.is_empty() {
+            return Err(ErrorData::invalid_params("at least one tag is required", None));
+        }
+        let data = match params.encoding {
+            Encoding::Utf8 => Bytes::from(params.input.into_bytes()),
+            Encoding::Base64 => {
+                use base64::Engine;
+                let decoded = base64::engine::general_purpose::STANDARD
+                    .decode(&params.input)
+                    .map_err(|e| {
+                        ErrorData::invalid_params(format!("invalid base64 input: {e}"), None)
+                    })?;
+                Bytes::from(decoded)
+            }
+        };
+
+        let mut names = self.state.sessions.sessions_by_tags(&params.tag);
+        names.sort();
+        let source = mcp_input_source(&extensions);
+        let futs = names.into_iter().filter_map(|name| {
+            let session = self.state.sessions.get(&name)?;
+            let (source, data) = (source.clone(), data.clone());
+            Some(async move {
+                match session.send_input(source, data).await {
+                    Ok(Delivery::Sent) => serde_json::json!({ "session": name, "sent": true }),
+                    Ok(Delivery::Held(pending)) => {
+                        serde_json::json!({ "session": name, "sent": false, "approval": pending.id })
+                    }
+                    Err(e) => serde_json::json!({ "session": name, "sent": false, "error": e.to_string() }),
+                }
+            })
+        });
+        let results = futures::future::join_all(futs).await;
+        let sent = results.iter().filter(|r| r["sent"] == true).count();
+        let held = results.iter().filter(|r| r.get("approval").is_some()).count();
+
+        let result = serde_json::json!({
+            "sent": sent,
+            "held": held,
+            "failed": results.len() - sent - held,
+            "bytes": data.len(),
+            "results": results,
+        });
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string(&result).unwrap_or_default(),
+        )]))
+    }
+
+    /// Press named keys in a terminal session.
+    #[tool(description = "Press keys in a terminal session by name, e.g. ['ctrl+c'], ['ArrowUp', 'Enter'], ['Escape', ':', 'w', 'q', 'Enter']. Keys are encoded for the keyboard modes the running application has enabled (application cursor keys, application keypad, kitty keyboard protocol), so prefer this over wsh_send_input for arrows, function keys, and control combinations. Use 'server' to target a remote federated server.")]
+    async fn wsh_send_keys(
+        &self,
+        extensions: Extensions,
+        Parameters(params): Parameters<SendKeysParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        // Federation: the remote server encodes for its own session's modes.
+        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref()).await? {
+            let body = serde_json::json!({ "keys": params.keys });
+            return proxy_post_bytes(
+                &backend,
+                &format!("/sessions/{}/input/keys", params.session),
+                Bytes::from(body.to_string()),
+            ).await;
+        }
+
+        let session = self.get_session(&params.session)?;
+        let modes = session.parser.keyboard_modes().await.map_err(|e| {
+            ErrorData::internal_error(format!("parser error: {e}"), None)
+        })?;
+        let data = crate::input::encode_keys(&params.keys, &modes)
+            .map_err(|e| ErrorData::invalid_params(e.to_string(), None))?;
+
+        let len = data.len();
+        session.input_broadcaster.record_input(mcp_input_source(&extensions), &data);
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            session.input_tx.send(Bytes::from(data)),
+        )
+        .await
+        .map_err(|_| ErrorData::internal_error("input send timed out", None))?
+        .map_err(|e| {
+            ErrorData::internal_error(
+                format!("failed to send input: {e}"),
+                None,
+            )
+        })?;
+        session.activity.touch();
+
+        let result = serde_json::json!({
+            "status": "sent",
+            "bytes": len,
+            "keyboard": modes,
+        });
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string(&result).unwrap_or_default(),
+        )]))
+    }
+
+    /// Send sensitive input that is kept out of the audit log.
+    #[tool(description = "Send a secret such as a password or token to a terminal session. The content is never written to the session's audit log and is not echoed back in the result. Refused while the terminal would display input (e.g. at a shell prompt rather than a password prompt) unless force is true; wait for the password prompt, then send the secret followed by '\\n'. Use 'server' to target a remote federated server.")]
+    async fn wsh_send_secret(
+        &self,
+        extensions: Extensions,
+        Parameters(params): Parameters<SendSecretParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref()).await? {
+            let body = serde_json::json!({ "data": params.input, "force": params.force });
+            return proxy_post_bytes(
+                &backend,
+                &format!("/sessions/{}/input/secret", params.session),
+                Bytes::from(body.to_string()),
+            ).await;
+        }
+
+        let session = self.get_session(&params.session)?;
+        if !params.force && session.echoes_input() {
+            let result = serde_json::json!({
+                "error": "terminal echo is enabled; the secret would be displayed",
+                "hint": "wait for a password prompt, or pass force: true",
+            });
+            return Ok(CallToolResult::error(vec![Content::text(
+                serde_json::to_string(&result).unwrap_or_default(),
+            )]));
+        }
+
+        let data = Bytes::from(params.input.into_bytes());
+        let len = data.len();
+        session
+            .input_broadcaster
+            .record_sensitive_input(mcp_input_source(&extensions), len);
+        tokio::time::timeout(Duration::from_secs(5), session.input_tx.send(data))
+            .await
+            .map_err(|_| ErrorData::internal_error("input send timed out", None))?
+            .map_err(|_| ErrorData::internal_error("failed to send input", None))?;
+        session.activity.touch();
+
+        let result = serde_json::json!({
+            "status": "sent",
+            "bytes": len,
+        });
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string(&result).unwrap_or_default(),
+        )]))
+    }
+
     /// Get the current visible screen contents of a terminal session.
     #[tool(description = "Get the current visible screen contents of a terminal session. Returns the screen grid with text, colors, cursor position, and terminal dimensions. Use 'server' to target a remote federated server.")]
     async fn wsh_get_screen(
@@ -863,12 +1337,13 @@ impl WshMcpServer {
         Parameters(params): Parameters<GetScreenParams>,
     ) -> Result<CallToolResult, ErrorData> {
         // Federation: proxy to remote if server is specified.
-        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref())? {
+        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref()).await? {
             let mut path = format!("/sessions/{}/screen", params.session);
             if matches!(params.format, tools::ScreenFormat::Plain) {
                 path.push_str("?format=plain");
             }
-            return proxy_get(&backend, &path).await;
+            let result = proxy_get(&backend, &path).await?;
+            return Ok(budget::fit_call_result(result, self.result_limit("wsh_get_screen")));
         }
 
         let session = self.get_session(&params.session)?;
@@ -876,15 +1351,13 @@ impl WshMcpServer {
 
         let response = session
             .parser
-            .query(Query::Screen { format })
+            .query(Query::Screen { format, since: None })
             .await
             .map_err(|e| {
                 ErrorData::internal_error(format!("parser error: {e}"), None)
             })?;
 
-        Ok(CallToolResult::success(vec![Content::text(
-            serde_json::to_string(&response).unwrap_or_default(),
-        )]))
+        Ok(self.lines_result("wsh_get_screen", &response))
     }
 
     /// Get scrollback buffer contents from a terminal session.
@@ -894,7 +1367,7 @@ impl WshMcpServer {
         Parameters(params): Parameters<GetScrollbackParams>,
     ) -> Result<CallToolResult, ErrorData> {
         // Federation: proxy to remote if server is specified.
-        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref())? {
+        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref()).await? {
             let mut path = format!(
                 "/sessions/{}/scrollback?offset={}&limit={}",
                 params.session, params.offset, params.limit,
@@ -902,7 +1375,8 @@ impl WshMcpServer {
             if matches!(params.format, tools::ScreenFormat::Plain) {
                 path.push_str("&format=plain");
             }
-            return proxy_get(&backend, &path).await;
+            let result = proxy_get(&backend, &path).await?;
+            return Ok(budget::fit_call_result(result, self.result_limit("wsh_get_scrollback")));
         }
 
         let session = self.get_session(&params.session)?;
@@ -921,8 +1395,51 @@ impl WshMcpServer {
                 ErrorData::internal_error(format!("parser error: {e}"), None)
             })?;
 
+        Ok(self.lines_result("wsh_get_scrollback", &response))
+    }
+
+    /// Find errors and warnings in a session's recent output.
+    #[tool(description = "Scan a terminal session's recent output for errors and warnings: compiler errors and warnings, panics, tracebacks, exceptions, test failures, 'permission denied', 'command not found' and similar. Returns each matching line with its scrollback line index (usable as the offset for wsh_get_scrollback), its kind and severity, plus error and warning counts. Much cheaper than reading the whole scrollback to check whether something failed. Use 'server' to target a remote federated server.")]
+    async fn wsh_get_insights(
+        &self,
+        Parameters(params): Parameters<GetInsightsParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref()).await? {
+            let path = format!(
+                "/sessions/{}/insights?lines={}&limit={}",
+                params.session, params.lines, params.limit,
+            );
+            return proxy_get(&backend, &path).await;
+        }
+
+        let session = self.get_session(&params.session)?;
+        let report = crate::insights::scan_parser(&session.parser, params.lines, params.limit)
+            .await
+            .map_err(|e| ErrorData::internal_error(format!("parser error: {e}"), None))?;
         Ok(CallToolResult::success(vec![Content::text(
-            serde_json::to_string(&response).unwrap_or_default(),
+            serde_json::to_string(&report).unwrap_or_default(),
+        )]))
+    }
+
+    /// List the links in a session's output.
+    #[tool(description = "List the links in a terminal session's screen and scrollback: OSC 8 hyperlinks (where the shown text can differ from the URL) and URLs printed as text, such as CI run pages or docs. Each distinct URL is listed once with every place it appears (scrollback line index, usable as the offset for wsh_get_scrollback, and character offsets). Use 'server' to target a remote federated server.")]
+    async fn wsh_get_links(
+        &self,
+        Parameters(params): Parameters<GetLinksParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref()).await? {
+            let path = format!("/sessions/{}/links?limit={}", params.session, params.limit);
+            return proxy_get(&backend, &path).await;
+        }
+
+        let session = self.get_session(&params.session)?;
+        let links = session
+            .parser
+            .links(params.limit)
+            .await
+            .map_err(|e| ErrorData::internal_error(format!("parser error: {e}"), None))?;
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string(&links).unwrap_or_default(),
         )]))
     }
 
@@ -930,10 +1447,11 @@ impl WshMcpServer {
     #[tool(description = "Wait for a terminal session to become idle (no output for timeout_ms). Returns the activity generation number on success. Returns an error result if max_wait_ms is exceeded before idle is reached. Use 'server' to target a remote federated server.")]
     async fn wsh_await_idle(
         &self,
+        context: RequestContext<RoleServer>,
         Parameters(params): Parameters<AwaitIdleParams>,
     ) -> Result<CallToolResult, ErrorData> {
         // Federation: proxy to remote if server is specified.
-        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref())? {
+        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref()).await? {
             let path = format!(
                 "/sessions/{}/idle?timeout_ms={}&max_wait_ms={}",
                 params.session, params.timeout_ms, params.max_wait_ms,
@@ -946,12 +1464,15 @@ impl WshMcpServer {
         let timeout = Duration::from_millis(params.timeout_ms.min(MAX_WAIT_CEILING_MS));
         let max_wait = Duration::from_millis(params.max_wait_ms.min(MAX_WAIT_CEILING_MS));
 
-        match tokio::time::timeout(
+        let streaming = progress::stream_output(&session, &context).await;
+        let idle_result = tokio::time::timeout(
             max_wait,
             session.activity.wait_for_idle(timeout, None),
         )
-        .await
-        {
+        .await;
+        drop(streaming);
+
+        match idle_result {
             Ok(generation) => {
                 let result = serde_json::json!({
                     "status": "idle",
@@ -974,17 +1495,123 @@ impl WshMcpServer {
         }
     }
 
+    /// Wait for a group of sessions to become idle.
+    #[tool(description = "Wait until all sessions (or those carrying any of the given tags) are idle at the same time, or with mode 'any' until the first one is. A synchronization point for parallel workers: start work in each session, then wait for the whole group to settle. Returns the session names and generations. Returns an error result listing the still-busy sessions if max_wait_ms is exceeded. Use 'server' to target a remote federated server.")]
+    async fn wsh_await_idle_group(
+        &self,
+        Parameters(params): Parameters<AwaitIdleGroupParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let mode = match params.mode {
+            GroupIdleMode::Any => "any",
+            GroupIdleMode::All => "all",
+        };
+        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref()).await? {
+            let mut path = format!(
+                "/idle?timeout_ms={}&max_wait_ms={}&mode={}&format=plain&fresh={}",
+                params.timeout_ms, params.max_wait_ms, mode, params.fresh,
+            );
+            if !params.tag.is_empty() {
+                path.push_str(&format!("&tag={}", params.tag.join(",")));
+            }
+            return proxy_get_long(&backend, &path).await;
+        }
+
+        let mut names = if params.tag.is_empty() {
+            self.state.sessions.list()
+        } else {
+            self.state.sessions.sessions_by_tags(&params.tag)
+        };
+        names.sort();
+        let (names, trackers): (Vec<String>, Vec<_>) = names
+            .into_iter()
+            .filter_map(|name| {
+                let activity = self.state.sessions.get(&name)?.activity.clone();
+                Some((name, activity))
+            })
+            .unzip();
+        if names.is_empty() {
+            return Ok(CallToolResult::error(vec![Content::text(
+                serde_json::json!({"error": "no matching sessions"}).to_string(),
+            )]));
+        }
+
+        let timeout = Duration::from_millis(params.timeout_ms.min(MAX_WAIT_CEILING_MS));
+        let max_wait = Duration::from_millis(params.max_wait_ms.min(MAX_WAIT_CEILING_MS));
+        let fresh = params.fresh;
+        let wait = async {
+            match params.mode {
+                GroupIdleMode::All => {
+                    let generations =
+                        crate::activity::wait_for_all_idle(&trackers, timeout, fresh).await;
+                    names
+                        .iter()
+                        .zip(generations)
+                        .map(|(session, generation)| {
+                            serde_json::json!({"session": session, "generation": generation})
+                        })
+                        .collect::<Vec<_>>()
+                }
+                GroupIdleMode::Any => {
+                    let waits = trackers.iter().enumerate().map(|(i, tracker)| {
+                        Box::pin(async move {
+                            let generation = if fresh {
+                                tracker.wait_for_fresh_idle(timeout).await
+                            } else {
+                                tracker.wait_for_idle(timeout, None).await
+                            };
+                            (i, generation)
+                        })
+                    });
+                    let ((i, generation), _, _) = futures::future::select_all(waits).await;
+                    vec![serde_json::json!({"session": names[i], "generation": generation})]
+                }
+            }
+        };
+
+        match tokio::time::timeout(max_wait, wait).await {
+            Ok(sessions) => {
+                let result = serde_json::json!({
+                    "status": "idle",
+                    "mode": mode,
+                    "sessions": sessions,
+                });
+                Ok(CallToolResult::success(vec![Content::text(
+                    serde_json::to_string(&result).unwrap_or_default(),
+                )]))
+            }
+            Err(_) => {
+                let busy: Vec<&String> = names
+                    .iter()
+                    .zip(&trackers)
+                    .filter(|(_, tracker)| tracker.last_activity_ms() < params.timeout_ms)
+                    .map(|(name, _)| name)
+                    .collect();
+                let result = serde_json::json!({
+                    "error": "idle timeout exceeded max_wait_ms",
+                    "busy": busy,
+                    "timeout_ms": params.timeout_ms,
+                    "max_wait_ms": params.max_wait_ms,
+                });
+                Ok(CallToolResult::error(vec![Content::text(
+                    serde_json::to_string(&result).unwrap_or_default(),
+                )]))
+            }
+        }
+    }
+
     /// Send input and wait for the terminal to become idle, then return the screen.
     #[tool(description = "Send input to a terminal session, wait for idle, then return the screen contents. This is the primary 'run a command' primitive: send input, wait for output to settle, read the result. If idle is not reached within max_wait_ms, the screen is still returned but marked as an error. Use 'server' to target a remote federated server.")]
     async fn wsh_run_command(
         &self,
+        extensions: Extensions,
+        context: RequestContext<RoleServer>,
         Parameters(params): Parameters<RunCommandParams>,
     ) -> Result<CallToolResult, ErrorData> {
         // Federation: proxy to remote if server is specified.
         // For run_command on a remote, we execute the three steps (send input,
         // await idle, get screen) as separate proxied HTTP calls so the remote
         // server's activity tracker handles the timing.
-        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref())? {
+        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref()).await? {
             // 1. Send input
             let input_bytes = Bytes::from(params.input.into_bytes());
             proxy_post_bytes(
@@ -1006,6 +1633,7 @@ impl WshMcpServer {
                 screen_path.push_str("?format=plain");
             }
             let screen_result = proxy_get(&backend, &screen_path).await?;
+            let screen_result = budget::fit_call_result(screen_result, self.result_limit("wsh_run_command"));
 
             // Combine results
             match idle_result {
@@ -1033,6 +1661,13 @@ impl WshMcpServer {
 
             // 1. Send input
             let data = Bytes::from(params.input.into_bytes());
+            session.policy.check_input(&data).map_err(input_blocked)?;
+            let source = mcp_input_source(&extensions);
+            if let Some(pending) = session.hold_input(&source, &data).map_err(input_held)? {
+                return Ok(held_result(&pending));
+            }
+            let streaming = progress::stream_output(&session, &context).await;
+            session.input_broadcaster.record_input(source, &data);
             tokio::time::timeout(
                 Duration::from_secs(5),
                 session.input_tx.send(data),
@@ -1059,16 +1694,21 @@ impl WshMcpServer {
                 session.activity.wait_for_idle(timeout, None),
             )
             .await;
+            drop(streaming);
 
             // 3. Get screen regardless of idle outcome
             let format = params.format.into_parser_format();
             let screen = session
                 .parser
-                .query(Query::Screen { format })
+                .query(Query::Screen { format, since: None })
                 .await
                 .map_err(|e| {
                     ErrorData::internal_error(format!("parser error: {e}"), None)
                 })?;
+            let mut screen = serde_json::to_value(&screen).unwrap_or_default();
+            if let Some(limit) = self.result_limit("wsh_run_command") {
+                budget::truncate_lines(&mut screen, limit);
+            }
 
             match idle_result {
                 Ok(generation) => {
@@ -1093,6 +1733,134 @@ impl WshMcpServer {
         }
     }
 
+    // ── File transfer tools ──────────────────────────────────────
+
+    /// Read a file from the machine a session runs on.
+    #[tool(description = "Read a file from the machine a terminal session runs on, without going through the terminal. Relative paths start at the session shell's current directory. Returns the resolved path, size and contents: text for UTF-8 files, base64 otherwise. Only works in directories the server allows; files are limited to 1 MiB. Use 'server' to target a remote federated server.")]
+    async fn wsh_read_file(
+        &self,
+        Parameters(params): Parameters<ReadFileParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let (path, data) = match self.resolve_server(params.server.as_deref()).await? {
+            McpSessionTarget::Remote(backend) => {
+                let client = build_proxy_client()?;
+                let mut req = client
+                    .get(backend.url_for(&format!("/sessions/{}/file", params.session)))
+                    .query(&[("path", &params.path)]);
+                if let Some(ref token) = backend.token {
+                    req = req.bearer_auth(token);
+                }
+                let resp = req.send().await.map_err(|e| {
+                    ErrorData::internal_error(format!("proxy request failed: {e}"), None)
+                })?;
+                let status = resp.status();
+                if !status.is_success() {
+                    let text = resp.text().await.unwrap_or_default();
+                    return Err(ErrorData::internal_error(
+                        format!("remote server returned {}: {}", status, text),
+                        None,
+                    ));
+                }
+                let data = resp.bytes().await.map_err(|e| {
+                    ErrorData::internal_error(format!("proxy request failed: {e}"), None)
+                })?;
+                (PathBuf::from(&params.path), data.to_vec())
+            }
+            McpSessionTarget::Local => {
+                let session = self.get_session(&params.session)?;
+                let path = self
+                    .state
+                    .server_config
+                    .files_config()
+                    .resolve_for(&session, &params.path, false)
+                    .map_err(file_error)?;
+                let data = crate::files::read(path.clone()).await.map_err(file_error)?;
+                (path, data)
+            }
+        };
+
+        let bytes = data.len();
+        let (encoding, content) = match String::from_utf8(data) {
+            Ok(text) => ("utf8", text),
+            Err(e) => {
+                use base64::Engine;
+                ("base64", base64::engine::general_purpose::STANDARD.encode(e.into_bytes()))
+            }
+        };
+        let result = serde_json::json!({
+            "path": path,
+            "bytes": bytes,
+            "encoding": encoding,
+            "content": content,
+        });
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string(&result).unwrap_or_default(),
+        )]))
+    }
+
+    /// Write a file on the machine a session runs on.
+    #[tool(description = "Write a file on the machine a terminal session runs on, without going through the terminal -- e.g. put a script in place, then run it with wsh_run_command. Relative paths start at the session shell's current directory. Replaces any existing file. Set mode (octal, e.g. '755') to make it executable. Only works in directories the server allows; files are limited to 1 MiB, and contents must pass the server's input policy. Use 'server' to target a remote federated server.")]
+    async fn wsh_write_file(
+        &self,
+        Parameters(params): Parameters<WriteFileParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let data = match params.encoding {
+            Encoding::Utf8 => Bytes::from(params.content.into_bytes()),
+            Encoding::Base64 => {
+                use base64::Engine;
+                let decoded = base64::engine::general_purpose::STANDARD
+                    .decode(&params.content)
+                    .map_err(|e| {
+                        ErrorData::invalid_params(format!("invalid base64 content: {e}"), None)
+                    })?;
+                Bytes::from(decoded)
+            }
+        };
+
+        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref()).await? {
+            let client = build_proxy_client()?;
+            let mut query = vec![("path", params.path.clone())];
+            query.extend(params.mode.clone().map(|mode| ("mode", mode)));
+            let mut req = client
+                .put(backend.url_for(&format!("/sessions/{}/file", params.session)))
+                .query(&query)
+                .body(data);
+            if let Some(ref token) = backend.token {
+                req = req.bearer_auth(token);
+            }
+            let resp = req.send().await.map_err(|e| {
+                ErrorData::internal_error(format!("proxy request failed: {e}"), None)
+            })?;
+            return response_to_call_result(resp).await;
+        }
+
+        let session = self.get_session(&params.session)?;
+        let mode = params
+            .mode
+            .as_deref()
+            .map(crate::files::parse_mode)
+            .transpose()
+            .map_err(file_error)?;
+        let path = self
+            .state
+            .server_config
+            .files_config()
+            .resolve_for(&session, &params.path, true)
+            .map_err(file_error)?;
+        session.policy.check_input(&data).map_err(input_blocked)?;
+        let bytes = data.len();
+        crate::files::write(path.clone(), data, mode).await.map_err(file_error)?;
+        tracing::info!(session = %params.session, path = %path.display(), bytes, "file written");
+
+        let result = serde_json::json!({
+            "path": path,
+            "bytes": bytes,
+        });
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string(&result).unwrap_or_default(),
+        )]))
+    }
+
     // ── Visual feedback tools ────────────────────────────────────
 
     /// Create, update, or list overlays on a terminal session.
@@ -1102,7 +1870,7 @@ impl WshMcpServer {
         Parameters(params): Parameters<OverlayParams>,
     ) -> Result<CallToolResult, ErrorData> {
         // Federation: proxy to remote if server is specified.
-        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref())? {
+        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref()).await? {
             if params.list {
                 return proxy_get(&backend, &format!("/sessions/{}/overlay", params.session)).await;
             }
@@ -1117,6 +1885,7 @@ impl WshMcpServer {
                     if let Some(h) = params.height { body["height"] = serde_json::json!(h); }
                     if let Some(bg) = &params.background { body["background"] = bg.clone(); }
                     if let Some(sp) = &params.spans { body["spans"] = serde_json::json!(sp); }
+                    if let Some(w) = &params.widget { body["widget"] = w.clone(); }
                     return proxy_patch_json(
                         &backend,
                         &format!("/sessions/{}/overlay/{}", params.session, id),
@@ -1134,6 +1903,9 @@ impl WshMcpServer {
                     if let Some(bg) = &params.background { body["background"] = bg.clone(); }
                     if let Some(sp) = &params.spans { body["spans"] = serde_json::json!(sp); }
                     if params.focusable { body["focusable"] = serde_json::json!(true); }
+                    if let Some(ttl) = params.ttl_ms { body["ttl_ms"] = serde_json::json!(ttl); }
+                    if params.sticky { body["sticky"] = serde_json::json!(true); }
+                    if let Some(w) = &params.widget { body["widget"] = w.clone(); }
                     return proxy_post_json(
                         &backend,
                         &format!("/sessions/{}/overlay", params.session),
@@ -1204,6 +1976,12 @@ impl WshMcpServer {
                     Ok(false) => return Err(ErrorData::invalid_params(format!("overlay not found: {id}"), None)),
                     Ok(true) => {}
                 }
+                if let Some(ref widget) = params.widget {
+                    session
+                        .overlays
+                        .patch_widget(&id, widget)
+                        .map_err(|e| ErrorData::invalid_params(e, None))?;
+                }
 
                 let _ = session
                     .visual_update_tx
@@ -1220,6 +1998,14 @@ impl WshMcpServer {
 
             // CREATE new overlay
             None => {
+                let widget = match &params.widget {
+                    Some(raw) => Some(
+                        serde_json::from_value::<crate::overlay::Widget>(raw.clone()).map_err(|e| {
+                            ErrorData::invalid_params(format!("invalid widget: {e}"), None)
+                        })?,
+                    ),
+                    None => None,
+                };
                 let x = params.x.ok_or_else(|| {
                     ErrorData::invalid_params("x is required when creating an overlay", None)
                 })?;
@@ -1244,6 +2030,13 @@ impl WshMcpServer {
                     params.focusable,
                     current_mode,
                 ).map_err(|e| ErrorData::invalid_params(e, None))?;
+                if let Some(widget) = widget {
+                    if let Err(e) = session.overlays.set_widget(&id, widget) {
+                        session.overlays.delete(&id);
+                        return Err(ErrorData::invalid_params(e, None));
+                    }
+                }
+                session.overlays.set_lifetime(&id, params.ttl_ms.map(std::time::Duration::from_millis), params.sticky);
 
                 let _ = session
                     .visual_update_tx
@@ -1267,7 +2060,7 @@ impl WshMcpServer {
         Parameters(params): Parameters<RemoveOverlayParams>,
     ) -> Result<CallToolResult, ErrorData> {
         // Federation: proxy to remote if server is specified.
-        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref())? {
+        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref()).await? {
             return match params.id {
                 Some(id) => proxy_delete(
                     &backend,
@@ -1322,17 +2115,28 @@ impl WshMcpServer {
     }
 
     /// Create, update, or list panels on a terminal session.
-    #[tool(description = "Create, update, or list panels on a terminal session. Panels carve out dedicated rows at the top or bottom of the terminal, shrinking the PTY viewport. Modes: set list=true to list all panels; omit id to create (position and height required); provide id to update. Use 'server' to target a remote federated server.")]
+    #[tool(description = "Create, update, or list panels on a terminal session. Panels carve out dedicated rows at the top or bottom of the terminal, shrinking the PTY viewport. Modes: set list=true to list all panels; omit id to create (position and height required); provide id to update. For a status log, create with max_lines and then provide id with append to add lines without re-sending the rest, or scroll to look back. Use 'server' to target a remote federated server.")]
     async fn wsh_panel(
         &self,
         Parameters(params): Parameters<PanelParams>,
     ) -> Result<CallToolResult, ErrorData> {
         // Federation: proxy to remote if server is specified.
-        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref())? {
+        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref()).await? {
             if params.list {
                 return proxy_get(&backend, &format!("/sessions/{}/panel", params.session)).await;
             }
             match params.id {
+                Some(ref id) if params.append.is_some() || params.scroll.is_some() => {
+                    let path = format!("/sessions/{}/panel/{}", params.session, id);
+                    let mut result = None;
+                    if let Some(sp) = &params.append {
+                        result = Some(proxy_post_json(&backend, &format!("{path}/append"), serde_json::json!({ "spans": sp })).await?);
+                    }
+                    if let Some(offset) = params.scroll {
+                        result = Some(proxy_post_json(&backend, &format!("{path}/scroll"), serde_json::json!({ "offset": offset })).await?);
+                    }
+                    return Ok(result.unwrap());
+                }
                 Some(ref id) => {
                     let mut body = serde_json::json!({});
                     if let Some(pos) = &params.position { body["position"] = serde_json::json!(pos); }
@@ -1354,6 +2158,9 @@ impl WshMcpServer {
                     if let Some(bg) = &params.background { body["background"] = bg.clone(); }
                     if let Some(sp) = &params.spans { body["spans"] = serde_json::json!(sp); }
                     if params.focusable { body["focusable"] = serde_json::json!(true); }
+                    if let Some(ttl) = params.ttl_ms { body["ttl_ms"] = serde_json::json!(ttl); }
+                    if params.sticky { body["sticky"] = serde_json::json!(true); }
+                    if let Some(n) = params.max_lines { body["max_lines"] = serde_json::json!(n); }
                     return proxy_post_json(
                         &backend,
                         &format!("/sessions/{}/panel", params.session),
@@ -1423,6 +2230,41 @@ impl WshMcpServer {
         };
 
         match params.id {
+            // APPEND to or SCROLL a scrollable panel
+            Some(id) if params.append.is_some() || params.scroll.is_some() => {
+                if let Some(raw) = &params.append {
+                    let spans: Vec<crate::overlay::OverlaySpan> = raw
+                        .iter()
+                        .map(|v| serde_json::from_value(v.clone()))
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|e| ErrorData::invalid_params(format!("invalid span: {e}"), None))?;
+                    match session.panels.append(&id, spans) {
+                        Err(e) => return Err(ErrorData::invalid_params(e.to_string(), None)),
+                        Ok(false) => return Err(ErrorData::invalid_params(format!("panel not found: {id}"), None)),
+                        Ok(true) => {}
+                    }
+                }
+                if let Some(offset) = params.scroll {
+                    match session.panels.scroll(&id, offset) {
+                        Err(e) => return Err(ErrorData::invalid_params(e.to_string(), None)),
+                        Ok(false) => return Err(ErrorData::invalid_params(format!("panel not found: {id}"), None)),
+                        Ok(true) => {}
+                    }
+                }
+
+                let _ = session
+                    .visual_update_tx
+                    .send(crate::protocol::VisualUpdate::PanelsChanged);
+
+                let result = serde_json::json!({
+                    "status": "updated",
+                    "id": id,
+                });
+                Ok(CallToolResult::success(vec![Content::text(
+                    serde_json::to_string(&result).unwrap_or_default(),
+                )]))
+            }
+
             // UPDATE existing panel
             Some(id) => {
                 match session.panels.patch(
@@ -1470,6 +2312,10 @@ impl WshMcpServer {
                 let height = params.height.ok_or_else(|| {
                     ErrorData::invalid_params("height is required when creating a panel", None)
                 })?;
+                if let Some(max_lines) = params.max_lines {
+                    crate::panel::validate_max_lines(max_lines)
+                        .map_err(|e| ErrorData::invalid_params(e, None))?;
+                }
 
                 let id = session.panels.create(
                     position,
@@ -1480,6 +2326,10 @@ impl WshMcpServer {
                     params.focusable,
                     current_mode,
                 ).map_err(|e| ErrorData::invalid_params(e, None))?;
+                session.panels.set_lifetime(&id, params.ttl_ms.map(std::time::Duration::from_millis), params.sticky);
+                if params.max_lines.is_some() {
+                    let _ = session.panels.set_max_lines(&id, params.max_lines);
+                }
 
                 crate::panel::reconfigure_layout(
                     &session.panels,
@@ -1511,7 +2361,7 @@ impl WshMcpServer {
         Parameters(params): Parameters<RemovePanelParams>,
     ) -> Result<CallToolResult, ErrorData> {
         // Federation: proxy to remote if server is specified.
-        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref())? {
+        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref()).await? {
             return match params.id {
                 Some(id) => proxy_delete(
                     &backend,
@@ -1586,9 +2436,10 @@ impl WshMcpServer {
     // ── Input & screen mode tools ────────────────────────────────
 
     /// Query or change the input mode and focus state of a terminal session.
-    #[tool(description = "Query or change the input mode and focus state of a terminal session. Without arguments, returns the current mode and focused element. Set mode to 'capture' (input goes to API only) or 'release' (input goes to both API and PTY). Set focus to an overlay/panel ID (must be focusable), or unfocus=true to clear focus. Use 'server' to target a remote federated server.")]
+    #[tool(description = "Query or change the input mode and focus state of a terminal session. Without arguments, returns the current mode, focused element and handoff state. Set mode to 'capture' (input goes to API only) or 'release' (input goes to both API and PTY). To take the keyboard from a human who is using the session, set mode to 'request_handoff' with a 'reason': they are prompted on their terminal and, once they approve, the session switches to capture mode for you; set mode to 'return_control' when done. Set focus to an overlay/panel ID (must be focusable), or unfocus=true to clear focus. Use 'server' to target a remote federated server.")]
     async fn wsh_input_mode(
         &self,
+        extensions: Extensions,
         Parameters(params): Parameters<InputModeParams>,
     ) -> Result<CallToolResult, ErrorData> {
         // Federation: proxy to remote if server is specified.
@@ -1600,7 +2451,13 @@ impl WshMcpServer {
             ));
         }
 
-        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref())? {
+        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref()).await? {
+            if params.await_form_ms.is_some() {
+                return Err(ErrorData::invalid_params(
+                    "await_form_ms is not available for remote sessions",
+                    None,
+                ));
+            }
             // Query-only: just GET
             if params.mode.is_none() && params.focus.is_none() && !params.unfocus {
                 return proxy_get(&backend, &format!("/sessions/{}/input/mode", params.session)).await;
@@ -1622,6 +2479,23 @@ impl WshMcpServer {
                             serde_json::json!({}),
                         ).await?;
                     }
+                    InputModeAction::RequestHandoff => {
+                        proxy_post_json(
+                            &backend,
+                            &format!("/sessions/{}/input/handoff", params.session),
+                            serde_json::json!({
+                                "agent": params.agent.as_deref().unwrap_or("agent"),
+                                "reason": params.reason,
+                            }),
+                        ).await?;
+                    }
+                    InputModeAction::ReturnControl => {
+                        proxy_post_json(
+                            &backend,
+                            &format!("/sessions/{}/input/handoff/return", params.session),
+                            serde_json::json!({}),
+                        ).await?;
+                    }
                 }
             }
             // Apply focus
@@ -1645,6 +2519,8 @@ impl WshMcpServer {
         }
 
         let session = self.get_session(&params.session)?;
+        // Subscribe before focusing, so a quick answer isn't missed.
+        let mut form_rx = params.await_form_ms.map(|_| session.input_broadcaster.subscribe());
 
         // Apply mode change if requested
         if let Some(ref action) = params.mode {
@@ -1655,6 +2531,18 @@ impl WshMcpServer {
                 InputModeAction::Release => {
                     session.input_mode.release();
                     session.focus.unfocus();
+                    session.return_control(mcp_input_source(&extensions));
+                }
+                InputModeAction::RequestHandoff => {
+                    let agent = params.agent.clone().unwrap_or_else(|| "agent".to_string());
+                    crate::input::handoff::validate_request(&agent, params.reason.as_deref())
+                        .map_err(|e| ErrorData::invalid_params(e, None))?;
+                    session
+                        .request_handoff(agent, params.reason.clone(), mcp_input_source(&extensions))
+                        .map_err(|e| ErrorData::invalid_request(e.to_string(), None))?;
+                }
+                InputModeAction::ReturnControl => {
+                    session.return_control(mcp_input_source(&extensions));
                 }
             }
         }
@@ -1681,11 +2569,13 @@ impl WshMcpServer {
             }
 
             session.focus.focus(id.clone());
+            session.input_broadcaster.broadcast_focus(Some(id.clone()), Some(mcp_input_source(&extensions)));
         }
 
         // Apply unfocus if requested
         if params.unfocus {
             session.focus.unfocus();
+            session.input_broadcaster.broadcast_focus(None, Some(mcp_input_source(&extensions)));
         }
 
         // Return current state
@@ -1696,10 +2586,31 @@ impl WshMcpServer {
         };
         let focused_element = session.focus.focused();
 
-        let result = serde_json::json!({
+        let mut result = serde_json::json!({
             "mode": mode_str,
             "focused_element": focused_element,
+            "handoff": session.handoff.status(),
         });
+        if let (Some(rx), Some(ms)) = (form_rx.as_mut(), params.await_form_ms) {
+            let wait = std::time::Duration::from_millis(ms.min(MAX_WAIT_CEILING_MS));
+            let form = tokio::time::timeout(wait, async {
+                loop {
+                    match rx.recv().await {
+                        Ok(crate::input::InputEvent::Submit { target, value, .. }) => {
+                            return serde_json::json!({"target": target, "submitted": true, "value": value});
+                        }
+                        Ok(crate::input::InputEvent::Cancel { target, .. }) => {
+                            return serde_json::json!({"target": target, "submitted": false});
+                        }
+                        Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => std::future::pending().await,
+                    }
+                }
+            })
+            .await
+            .unwrap_or(serde_json::Value::Null);
+            result["form"] = form;
+        }
         Ok(CallToolResult::success(vec![Content::text(
             serde_json::to_string(&result).unwrap_or_default(),
         )]))
@@ -1712,7 +2623,7 @@ impl WshMcpServer {
         Parameters(params): Parameters<ScreenModeParams>,
     ) -> Result<CallToolResult, ErrorData> {
         // Federation: proxy to remote if server is specified.
-        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref())? {
+        if let McpSessionTarget::Remote(backend) = self.resolve_server(params.server.as_deref()).await? {
             if let Some(ref action) = params.action {
                 match action {
                     ScreenModeAction::EnterAlt => {
@@ -1830,11 +2741,39 @@ impl WshMcpServer {
         )]))
     }
 
+    /// Get detailed status for a specific federated backend server.
+    #[tool(description = "Get detailed status for a specific federated backend server by hostname. Returns address, health, role, and hostname.")]
+    async fn wsh_server_status(
+        &self,
+        Parameters(params): Parameters<ServerStatusParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let entry = self.state.backends.get_by_hostname(&params.hostname).ok_or_else(|| {
+            ErrorData::invalid_params(format!("server not found: {}", params.hostname), None)
+        })?;
+
+        let result = serde_json::json!({
+            "address": entry.address,
+            "hostname": entry.hostname,
+            "health": entry.health,
+            "role": entry.role,
+        });
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string(&result).unwrap_or_default(),
+        )]))
+    }
+}
+
+/// Tools that change the set of backends. Only built with the
+/// `federation` feature; without it the server has no way to connect to
+/// backends, so they are not offered.
+#[cfg(feature = "federation")]
+#[tool_router(router = federation_tool_router)]
+impl WshMcpServer {
     /// Add a new backend server to the federation.
     #[tool(description = "Add a new backend server to the federation. Provide the address (host:port) and optionally a token. The server will be probed for health and hostname.")]
     async fn wsh_add_server(
         &self,
-        Parameters(params): Parameters<AddServerParams>,
+        Parameters(params): Parameters<tools::AddServerParams>,
     ) -> Result<CallToolResult, ErrorData> {
         // Check resolved IPs against IP access control (if configured).
         if let Some(ref ip_access) = self.state.ip_access {
@@ -1861,7 +2800,7 @@ impl WshMcpServer {
     #[tool(description = "Remove a backend server from the federation by hostname. Its connection will be shut down.")]
     async fn wsh_remove_server(
         &self,
-        Parameters(params): Parameters<RemoveServerParams>,
+        Parameters(params): Parameters<tools::RemoveServerParams>,
     ) -> Result<CallToolResult, ErrorData> {
         let mut federation = self.state.federation.lock().await;
         let removed = federation.remove_backend_by_hostname(&params.hostname);
@@ -1881,25 +2820,4 @@ impl WshMcpServer {
             serde_json::to_string(&result).unwrap_or_default(),
         )]))
     }
-
-    /// Get detailed status for a specific federated backend server.
-    #[tool(description = "Get detailed status for a specific federated backend server by hostname. Returns address, health, role, and hostname.")]
-    async fn wsh_server_status(
-        &self,
-        Parameters(params): Parameters<ServerStatusParams>,
-    ) -> Result<CallToolResult, ErrorData> {
-        let entry = self.state.backends.get_by_hostname(&params.hostname).ok_or_else(|| {
-            ErrorData::invalid_params(format!("server not found: {}", params.hostname), None)
-        })?;
-
-        let result = serde_json::json!({
-            "address": entry.address,
-            "hostname": entry.hostname,
-            "health": entry.health,
-            "role": entry.role,
-        });
-        Ok(CallToolResult::success(vec![Content::text(
-            serde_json::to_string(&result).unwrap_or_default(),
-        )]))
-    }
 }
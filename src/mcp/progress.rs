@@ -0,0 +1,176 @@
+//! Streaming output from long-running tools.
+//!
+//! A host that sends a `progressToken` in the `_meta` of a `wsh_run_command`
+//! or `wsh_await_idle` call gets the session's output while the tool waits,
+//! instead of only the final screen. About once a second, lines completed
+//! since the last notification are sent as `notifications/progress`:
+//! `progress` counts the lines streamed so far and `message` carries the new
+//! ones as plain text. Hosts that don't send a token see no difference.
+
+use std::time::Duration;
+
+use rmcp::model::{ProgressNotificationParam, ProgressToken};
+use rmcp::service::RequestContext;
+use rmcp::RoleServer;
+
+use crate::parser::state::{Format, FormattedLine, Query, QueryResponse};
+use crate::parser::Parser;
+use crate::session::Session;
+
+/// How often new output is sent.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Most lines sent in one notification. When more than this scrolled by
+/// since the last one, the oldest are left out and the message says so.
+const MAX_LINES: usize = 200;
+
+/// Streams a session's output as progress notifications until dropped.
+pub struct OutputStream {
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for OutputStream {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Start streaming `session`'s output to the host, if the request carries a
+/// progress token. Output already on the screen is not sent, so call this
+/// before sending the input whose output should be streamed.
+pub async fn stream_output(session: &Session, context: &RequestContext<RoleServer>) -> Option<OutputStream> {
+    let token = context.meta.get_progress_token()?;
+    let parser = session.parser.clone();
+    let start = completed_end(&parser).await?;
+    let peer = context.peer.clone();
+    let task = tokio::spawn(async move {
+        let mut streamer = Streamer { parser, token, position: start, sent: 0 };
+        let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let Some(param) = streamer.next().await else {
+                continue;
+            };
+            if peer.notify_progress(param).await.is_err() {
+                // The host has gone away.
+                return;
+            }
+        }
+    });
+    Some(OutputStream { task })
+}
+
+struct Streamer {
+    parser: Parser,
+    token: ProgressToken,
+    /// Epoch and absolute index of the first line not yet sent.
+    position: (u64, usize),
+    /// Lines streamed so far, including any left out.
+    sent: usize,
+}
+
+impl Streamer {
+    /// The notification for lines completed since the last one, if any.
+    async fn next(&mut self) -> Option<ProgressNotificationParam> {
+        let (epoch, end) = completed_end(&self.parser).await?;
+        let (last_epoch, next) = self.position;
+        self.position = (epoch, end);
+        // After a reset line indices start over, so pick up from here.
+        if epoch != last_epoch || end <= next {
+            return None;
+        }
+        let skipped = (end - next).saturating_sub(MAX_LINES);
+        let offset = next + skipped;
+        let query = Query::Scrollback { format: Format::Plain, offset, limit: end - offset };
+        let QueryResponse::Scrollback(page) = self.parser.query(query).await.ok()? else {
+            return None;
+        };
+        let mut message = String::new();
+        if skipped > 0 {
+            message.push_str(&format!("[{} lines omitted]\n", skipped));
+        }
+        for line in &page.lines {
+            message.push_str(line_text(line).trim_end());
+            message.push('\n');
+        }
+        self.sent += skipped + page.lines.len();
+        Some(ProgressNotificationParam {
+            progress_token: self.token.clone(),
+            progress: self.sent as f64,
+            total: None,
+            message: Some(message),
+        })
+    }
+}
+
+/// The parser epoch and the absolute index of the cursor's line. Lines
+/// before it are complete; the cursor's own may still be written to.
+async fn completed_end(parser: &Parser) -> Option<(u64, usize)> {
+    match parser.query(Query::Screen { format: Format::Plain, since: None }).await.ok()? {
+        QueryResponse::Screen(screen) => Some((screen.epoch, screen.first_line_index + screen.cursor.row)),
+        _ => None,
+    }
+}
+
+fn line_text(line: &FormattedLine) -> String {
+    match line {
+        FormattedLine::Plain(text) => text.clone(),
+        FormattedLine::Styled(spans) => spans.iter().map(|s| s.text.as_str()).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::model::NumberOrString;
+
+    async fn streamer() -> (Streamer, tokio::sync::mpsc::Sender<bytes::Bytes>) {
+        let (raw_tx, raw_rx) = tokio::sync::mpsc::channel(16);
+        let parser = Parser::spawn(raw_rx, 80, 24, 1000);
+        let position = completed_end(&parser).await.unwrap();
+        let token = ProgressToken(NumberOrString::Number(1));
+        (Streamer { parser, token, position, sent: 0 }, raw_tx)
+    }
+
+    #[tokio::test]
+    async fn streams_completed_lines_once() {
+        let (mut streamer, raw_tx) = streamer().await;
+
+        raw_tx.send(bytes::Bytes::from("one\r\ntwo\r\nthr")).await.unwrap();
+        let param = wait_for(&mut streamer).await;
+        assert_eq!(param.message.as_deref(), Some("one\ntwo\n"));
+        assert_eq!(param.progress, 2.0);
+
+        // The partial line is sent once it's finished, and nothing twice.
+        raw_tx.send(bytes::Bytes::from("ee\r\n")).await.unwrap();
+        let param = wait_for(&mut streamer).await;
+        assert_eq!(param.message.as_deref(), Some("three\n"));
+        assert_eq!(param.progress, 3.0);
+        assert!(streamer.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn floods_are_cut_to_the_newest_lines() {
+        let (mut streamer, raw_tx) = streamer().await;
+
+        let output: String = (0..250).map(|i| format!("line {i}\r\n")).collect();
+        raw_tx.send(bytes::Bytes::from(output)).await.unwrap();
+        let param = wait_for(&mut streamer).await;
+        let message = param.message.unwrap();
+        assert!(message.starts_with("[50 lines omitted]\nline 50\n"), "{message}");
+        assert!(message.ends_with("line 249\n"));
+        assert_eq!(param.progress, 250.0);
+    }
+
+    async fn wait_for(streamer: &mut Streamer) -> ProgressNotificationParam {
+        for _ in 0..100 {
+            if let Some(param) = streamer.next().await {
+                return param;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("no output streamed");
+    }
+}
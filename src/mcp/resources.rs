@@ -4,15 +4,33 @@
 // - wsh://sessions              -> list all sessions with dimensions
 // - wsh://sessions/{name}/screen    -> current visible screen contents
 // - wsh://sessions/{name}/scrollback -> scrollback buffer contents
+//
+// Screen resources can be subscribed to: the host then gets a
+// `notifications/resources/updated` when the screen changes, at most once
+// per SCREEN_UPDATE_INTERVAL, and re-reads it when it wants.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
 
+use futures::{FutureExt, StreamExt};
+use parking_lot::Mutex;
 use rmcp::model::*;
+use rmcp::service::Peer;
+use rmcp::RoleServer;
+use tokio_util::sync::CancellationToken;
 
 use crate::api::AppState;
 use crate::parser::state::{Format, Query};
+use crate::parser::Parser;
 
 /// The URI prefix for all wsh resources.
 const URI_PREFIX: &str = "wsh://sessions";
 
+/// Minimum time between update notifications for one subscribed screen.
+/// Changes within it are coalesced into one notification.
+pub const SCREEN_UPDATE_INTERVAL: Duration = Duration::from_millis(500);
+
 /// List all available resources.
 ///
 /// Returns the fixed `wsh://sessions` resource plus dynamic per-session
@@ -118,7 +136,7 @@ pub async fn read_resource(
                     let (rows, cols) = session.terminal_size.get();
                     Some(serde_json::json!({
                         "name": name,
-                        "pid": session.pid,
+                        "pid": session.pid(),
                         "command": session.command.clone(),
                         "rows": rows,
                         "cols": cols,
@@ -146,6 +164,7 @@ pub async fn read_resource(
                 .parser
                 .query(Query::Screen {
                     format: Format::Styled,
+                    since: None,
                 })
                 .await
                 .map_err(|e| {
@@ -192,6 +211,88 @@ pub async fn read_resource(
     }
 }
 
+/// An MCP session's resource subscriptions, by URI. Dropping it (when the
+/// MCP session ends) stops them all.
+#[derive(Default)]
+pub struct Subscriptions {
+    watchers: Mutex<HashMap<String, tokio::task::JoinHandle<()>>>,
+}
+
+impl Subscriptions {
+    /// Subscribe `peer` to updates of the resource at `uri`. Only session
+    /// screens can be subscribed to. Subscribing again is a no-op.
+    pub fn subscribe(&self, state: &AppState, uri: &str, peer: Peer<RoleServer>) -> Result<(), ErrorData> {
+        let (Some(name), ResourceType::Screen) = parse_resource_uri(uri)? else {
+            return Err(ErrorData::invalid_params(
+                format!("only screen resources can be subscribed to: {uri}"),
+                None,
+            ));
+        };
+        let session = state.sessions.get(&name).ok_or_else(|| {
+            ErrorData::resource_not_found(format!("session not found: {name}"), None)
+        })?;
+        let mut watchers = self.watchers.lock();
+        if watchers.get(uri).is_some_and(|task| !task.is_finished()) {
+            return Ok(());
+        }
+        let uri = uri.to_string();
+        let param = ResourceUpdatedNotificationParam { uri: uri.clone() };
+        let task = tokio::spawn(watch_screen(session.parser, session.cancelled, SCREEN_UPDATE_INTERVAL, move || {
+            let peer = peer.clone();
+            let param = param.clone();
+            async move { peer.notify_resource_updated(param).await.is_ok() }
+        }));
+        watchers.insert(uri, task);
+        Ok(())
+    }
+
+    /// Stop updates of the resource at `uri`, if subscribed.
+    pub fn unsubscribe(&self, uri: &str) {
+        if let Some(task) = self.watchers.lock().remove(uri) {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for Subscriptions {
+    fn drop(&mut self) {
+        for task in self.watchers.get_mut().values() {
+            task.abort();
+        }
+    }
+}
+
+/// Call `notify` after the screen changes, at most once per `interval`,
+/// until the session ends or `notify` returns false.
+async fn watch_screen<F, Fut>(parser: Parser, cancelled: CancellationToken, interval: Duration, mut notify: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    let mut events = Box::pin(parser.subscribe());
+    loop {
+        tokio::select! {
+            event = events.next() => {
+                if event.is_none() {
+                    return;
+                }
+            }
+            _ = cancelled.cancelled() => return,
+        }
+        // Let the rest of the burst arrive, then drain it so it is covered
+        // by this one notification.
+        tokio::time::sleep(interval).await;
+        while let Some(event) = events.next().now_or_never() {
+            if event.is_none() {
+                return;
+            }
+        }
+        if !notify().await {
+            return;
+        }
+    }
+}
+
 /// Resource type parsed from a URI.
 #[derive(Debug, PartialEq)]
 enum ResourceType {
@@ -348,8 +449,11 @@ mod tests {
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(crate::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(crate::api::tokens::TokenStore::new()),
             backends: crate::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(crate::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -376,8 +480,11 @@ mod tests {
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(crate::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(crate::api::tokens::TokenStore::new()),
             backends: crate::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(crate::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -404,8 +511,11 @@ mod tests {
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(crate::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(crate::api::tokens::TokenStore::new()),
             backends: crate::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(crate::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -442,8 +552,11 @@ mod tests {
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(crate::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(crate::api::tokens::TokenStore::new()),
             backends: crate::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(crate::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -472,8 +585,11 @@ mod tests {
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(crate::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(crate::api::tokens::TokenStore::new()),
             backends: crate::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(crate::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -487,4 +603,33 @@ mod tests {
         assert_eq!(result.resources.len(), 1);
         assert_eq!(result.resources[0].raw.uri, "wsh://sessions");
     }
+
+    // ── Subscriptions ───────────────────────────────────────────
+
+    #[tokio::test]
+    async fn screen_watch_coalesces_bursts() {
+        let (parser_tx, parser_rx) = tokio::sync::mpsc::channel(64);
+        let parser = Parser::spawn(parser_rx, 80, 24, 1000);
+        let cancelled = CancellationToken::new();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let watcher = tokio::spawn(watch_screen(parser, cancelled.clone(), Duration::from_millis(100), move || {
+            let tx = tx.clone();
+            async move { tx.send(()).is_ok() }
+        }));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        for i in 0..20 {
+            parser_tx.send(bytes::Bytes::from(format!("line {i}\r\n"))).await.unwrap();
+        }
+        tokio::time::timeout(Duration::from_secs(2), rx.recv()).await.unwrap().unwrap();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(rx.try_recv().is_err(), "one burst should produce one notification");
+
+        parser_tx.send(bytes::Bytes::from("more\r\n")).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(2), rx.recv()).await.unwrap().unwrap();
+
+        // The watch ends with the session.
+        cancelled.cancel();
+        tokio::time::timeout(Duration::from_secs(2), watcher).await.unwrap().unwrap();
+    }
 }
@@ -35,6 +35,66 @@ pub struct CreateSessionParams {
     #[schemars(description = "Tags to assign to the session at creation time.")]
     pub tags: Vec<String>,
 
+    /// Unix account to run the session as, by name.
+    #[schemars(description = "Run the session as this Unix user. Only servers running as root can switch users.")]
+    pub user: Option<String>,
+
+    /// Unix account to run the session as, by uid.
+    #[schemars(description = "Run the session as this Unix uid. Must match 'user' if both are given.")]
+    pub uid: Option<u32>,
+
+    /// Sandbox profile to run the session in.
+    #[schemars(description = "Run the session in this sandbox profile, as configured on the server. The server may apply a default profile when omitted.")]
+    pub sandbox: Option<String>,
+
+    /// ssh target for the session.
+    #[schemars(description = "Run the session over ssh on this host, given as [user@]host[:port]. 'command', if set, runs on that host instead of a login shell. A 'command' of the form ssh://[user@]host[:port] does the same.")]
+    pub remote: Option<String>,
+
+    /// Idle timeout override for the session.
+    #[schemars(description = "Kill the session after it has had no client attached and no terminal input or output for this long, e.g. '30m' or '2h'. Overrides the server default; '0' never kills it.")]
+    pub idle_kill_after: Option<String>,
+
+    /// CPU niceness for the session.
+    #[schemars(description = "CPU niceness for the session's processes, from -20 (highest priority) to 19 (lowest). Use a positive value to keep background jobs from slowing interactive sessions; negative values need a server running as root.")]
+    pub nice: Option<i32>,
+
+    /// I/O priority for the session.
+    #[schemars(description = "I/O scheduling class for the session (Linux only): 'idle', 'best-effort', or 'realtime', optionally followed by ':level' with 0 highest and 7 lowest, e.g. 'best-effort:7'. 'realtime' needs a server running as root.")]
+    pub ionice: Option<String>,
+
+    /// Scrollback size for the session.
+    #[schemars(description = "Lines of scrollback the session keeps, up to 1000000. Overrides the server default (10000 unless configured). Raise it for long build logs; lower it to save memory.")]
+    pub scrollback_lines: Option<usize>,
+
+    /// Who answers the application's device and status queries.
+    #[schemars(description = "Who answers the device and status queries the program sends its terminal: an object with 'da1' (device attributes), 'dsr' (status and cursor position reports) and 'xtgettcap' (termcap lookups), each 'passthrough' (attached clients answer; the default), 'synthetic' (wsh answers and client replies are dropped) or 'block' (nobody answers). Use 'synthetic' when several clients are attached so the program doesn't get duplicate replies in its input.")]
+    pub query_policy: Option<crate::parser::state::QueryPolicy>,
+
+    /// Encoding the program writes.
+    #[schemars(description = "Character encoding the program writes: 'utf-8' (the default), 'latin-1' or 'windows-1252'. Set it for legacy programs that don't write UTF-8; screen and scrollback are then decoded to UTF-8, while attached terminals get the program's bytes unchanged.")]
+    pub encoding: Option<crate::parser::encoding::Encoding>,
+
+    /// Restart policy for the session's command.
+    #[schemars(description = "Respawn the command in the same session when it exits: 'on-failure' after a non-zero exit status or a fatal signal, 'always' after any exit. Scrollback is kept, with a separator line between runs. Use it to keep a dev server or watcher running.")]
+    pub restart: Option<crate::session::RestartWhen>,
+
+    /// Restarts in a row before giving up.
+    #[schemars(description = "With 'restart', how many restarts in a row are allowed before the session is left to end, up to 1000 (default 5). A run of a minute or more starts the count over.")]
+    pub restart_max_retries: Option<u32>,
+
+    /// Delay before the first restart.
+    #[schemars(description = "With 'restart', milliseconds to wait before the first restart in a row (default 1000); each further one waits twice as long, up to 5 minutes.")]
+    pub restart_backoff_ms: Option<u64>,
+
+    /// What happens to the session when this MCP session ends.
+    #[schemars(description = "What happens to the session when this MCP session ends, for example because the agent exits or crashes: 'kill' kills it, 'detach' disconnects its streaming clients and leaves it running, 'keep' leaves it as it is. Defaults to the server's [mcp] cleanup_on_disconnect, which is 'keep' unless configured. Only for sessions on this server.")]
+    pub cleanup_on_disconnect: Option<crate::session::DisconnectCleanup>,
+
+    /// Session template to start from.
+    #[schemars(description = "Name of a session template stored on the server. The template supplies command, cwd, env, size and tags, and pre-creates its overlays and panels; parameters given here override its values, env is merged and tags are added to the template's.")]
+    pub template: Option<String>,
+
     /// Target a specific federated server by hostname. Omit for local.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     #[schemars(description = "Target a specific federated server by hostname. Omit to target the local server.")]
@@ -69,6 +129,10 @@ pub enum ManageAction {
     Rename,
     /// Detach all streaming clients from the session.
     Detach,
+    /// Send `SIGCONT` to a session whose child was stopped or hibernated.
+    Resume,
+    /// Stop the child and move the terminal state to disk.
+    Hibernate,
     /// Add tags to the session. Requires `tags`.
     AddTags,
     /// Remove tags from the session. Requires `tags`.
@@ -83,7 +147,7 @@ pub struct ManageSessionParams {
     pub session: String,
 
     /// The action to perform on the session.
-    #[schemars(description = "The action to perform: kill, rename, detach, add_tags, or remove_tags.")]
+    #[schemars(description = "The action to perform: kill, rename, detach, resume, hibernate, add_tags, or remove_tags.")]
     pub action: ManageAction,
 
     /// New name for the session (required when action is 'rename').
@@ -139,6 +203,73 @@ pub struct SendInputParams {
     pub server: Option<String>,
 }
 
+/// Parameters for the `wsh_broadcast_input` tool.
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct BroadcastInputParams {
+    /// Sessions carrying any of these tags get the input.
+    #[schemars(description = "Send to every session carrying any of these tags (union/OR semantics). At least one is required.")]
+    pub tag: Vec<String>,
+
+    /// The input data to send. Interpretation depends on `encoding`.
+    #[schemars(description = "The input data to send. For utf8 encoding, this is plain text. For base64 encoding, this is base64-encoded binary data.")]
+    pub input: String,
+
+    /// How to interpret the `input` field. Defaults to `utf8`.
+    #[serde(default = "default_encoding")]
+    #[schemars(description = "Input encoding: 'utf8' (default) for plain text, 'base64' for binary data.")]
+    pub encoding: Encoding,
+}
+
+/// Parameters for the `wsh_send_keys` tool.
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct SendKeysParams {
+    /// The name of the target session.
+    #[schemars(description = "The name of the target session.")]
+    pub session: String,
+
+    /// Keys to press, in order.
+    #[schemars(description = "Keys to press, in order. Use key names like 'Enter', 'Escape', 'Tab', 'Backspace', 'ArrowUp', 'PageDown', 'F5', 'Space', 'Numpad0', or any single character, optionally with modifiers joined by '+': 'ctrl+c', 'alt+x', 'shift+Tab', 'ctrl+ArrowLeft'.")]
+    pub keys: Vec<String>,
+
+    /// Target a specific federated server by hostname. Omit for local.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Target a specific federated server by hostname. Omit to target the local server.")]
+    pub server: Option<String>,
+}
+
+/// Parameters for the `wsh_send_secret` tool.
+#[derive(Deserialize, schemars::JsonSchema)]
+pub struct SendSecretParams {
+    /// The name of the target session.
+    #[schemars(description = "The name of the target session.")]
+    pub session: String,
+
+    /// The secret to send. Include a trailing newline to submit it.
+    #[schemars(description = "The secret to send, e.g. a password. Include a trailing newline to submit it.")]
+    pub input: String,
+
+    /// Send even if the terminal would echo the input.
+    #[serde(default)]
+    #[schemars(description = "Send even if the terminal currently echoes input. Defaults to false.")]
+    pub force: bool,
+
+    /// Target a specific federated server by hostname. Omit for local.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Target a specific federated server by hostname. Omit to target the local server.")]
+    pub server: Option<String>,
+}
+
+impl std::fmt::Debug for SendSecretParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SendSecretParams")
+            .field("session", &self.session)
+            .field("input", &"<redacted>")
+            .field("force", &self.force)
+            .field("server", &self.server)
+            .finish()
+    }
+}
+
 /// Screen content format for query results.
 #[derive(Debug, Default, Deserialize, schemars::JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -210,6 +341,59 @@ pub struct GetScrollbackParams {
     pub server: Option<String>,
 }
 
+fn default_insight_lines() -> usize {
+    crate::insights::DEFAULT_SCAN_LINES
+}
+
+fn default_insight_limit() -> usize {
+    crate::insights::DEFAULT_MAX_FINDINGS
+}
+
+/// Parameters for the `wsh_get_insights` tool.
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct GetInsightsParams {
+    /// The name of the target session.
+    #[schemars(description = "The name of the target session.")]
+    pub session: String,
+
+    /// How many of the most recent lines to scan. Defaults to 2000.
+    #[serde(default = "default_insight_lines")]
+    #[schemars(description = "How many of the most recent lines to scan. Defaults to 2000, at most 10000.")]
+    pub lines: usize,
+
+    /// Maximum number of findings to return. Defaults to 100.
+    #[serde(default = "default_insight_limit")]
+    #[schemars(description = "Maximum number of findings to return. Defaults to 100.")]
+    pub limit: usize,
+
+    /// Target a specific federated server by hostname. Omit for local.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Target a specific federated server by hostname. Omit to target the local server.")]
+    pub server: Option<String>,
+}
+
+fn default_links_limit() -> usize {
+    crate::parser::links::DEFAULT_MAX_LINKS
+}
+
+/// Parameters for the `wsh_get_links` tool.
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct GetLinksParams {
+    /// The name of the target session.
+    #[schemars(description = "The name of the target session.")]
+    pub session: String,
+
+    /// Maximum number of distinct links to return. Defaults to 200.
+    #[serde(default = "default_links_limit")]
+    #[schemars(description = "Maximum number of distinct links to return. Defaults to 200, at most 1000.")]
+    pub limit: usize,
+
+    /// Target a specific federated server by hostname. Omit for local.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Target a specific federated server by hostname. Omit to target the local server.")]
+    pub server: Option<String>,
+}
+
 fn default_timeout_ms() -> u64 {
     2000
 }
@@ -243,6 +427,51 @@ pub struct AwaitIdleParams {
     pub server: Option<String>,
 }
 
+/// When a group idle wait resolves.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupIdleMode {
+    /// As soon as any session is idle.
+    Any,
+    /// Once every session is idle at the same time (default).
+    #[default]
+    All,
+}
+
+/// Parameters for the `wsh_await_idle_group` tool.
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct AwaitIdleGroupParams {
+    /// Sessions carrying any of these tags are watched; empty means all.
+    #[serde(default)]
+    #[schemars(description = "Watch sessions carrying any of these tags. Omit to watch every session.")]
+    pub tag: Vec<String>,
+
+    /// Whether to wait for all sessions or just the first.
+    #[serde(default)]
+    #[schemars(description = "'all' (default) waits until every session is idle; 'any' returns the first idle session.")]
+    pub mode: GroupIdleMode,
+
+    /// Idle timeout in milliseconds. Defaults to 2000.
+    #[serde(default = "default_timeout_ms")]
+    #[schemars(description = "Idle timeout in milliseconds. A session must be idle for this long. Defaults to 2000.")]
+    pub timeout_ms: u64,
+
+    /// Maximum wall-clock time to wait in milliseconds. Defaults to 30000.
+    #[serde(default = "default_max_wait_ms")]
+    #[schemars(description = "Maximum wall-clock time to wait in milliseconds. Defaults to 30000.")]
+    pub max_wait_ms: u64,
+
+    /// Observe real silence even if sessions are already idle.
+    #[serde(default)]
+    #[schemars(description = "If true, observe a full timeout_ms of silence even when sessions are already idle.")]
+    pub fresh: bool,
+
+    /// Target a specific federated server by hostname. Omit for local.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Target a specific federated server by hostname. Omit to target the local server.")]
+    pub server: Option<String>,
+}
+
 /// Parameters for the `wsh_run_command` tool.
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct RunCommandParams {
@@ -275,6 +504,56 @@ pub struct RunCommandParams {
     pub server: Option<String>,
 }
 
+// ── File transfer parameter types ───────────────────────────────
+
+/// Parameters for the `wsh_read_file` tool.
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ReadFileParams {
+    /// The name of the session whose machine holds the file.
+    #[schemars(description = "The name of the session whose machine holds the file.")]
+    pub session: String,
+
+    /// Path of the file; relative paths start at the shell's directory.
+    #[schemars(description = "Path of the file. Relative paths are resolved against the session shell's current directory.")]
+    pub path: String,
+
+    /// Target a specific federated server by hostname. Omit for local.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Target a specific federated server by hostname. Omit to target the local server.")]
+    pub server: Option<String>,
+}
+
+/// Parameters for the `wsh_write_file` tool.
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct WriteFileParams {
+    /// The name of the session whose machine the file is written on.
+    #[schemars(description = "The name of the session whose machine the file is written on.")]
+    pub session: String,
+
+    /// Path of the file; relative paths start at the shell's directory.
+    #[schemars(description = "Path of the file. Relative paths are resolved against the session shell's current directory. The directory must exist; an existing file is replaced.")]
+    pub path: String,
+
+    /// The file contents. Interpretation depends on `encoding`.
+    #[schemars(description = "The file contents. For utf8 encoding, this is plain text. For base64 encoding, this is base64-encoded binary data.")]
+    pub content: String,
+
+    /// How to interpret the `content` field. Defaults to `utf8`.
+    #[serde(default = "default_encoding")]
+    #[schemars(description = "Content encoding: 'utf8' (default) for plain text, 'base64' for binary data.")]
+    pub encoding: Encoding,
+
+    /// Permission mode in octal, e.g. "755".
+    #[serde(default)]
+    #[schemars(description = "Permission mode in octal, e.g. '755' to make a script executable. New files default to '644'; existing files keep their mode.")]
+    pub mode: Option<String>,
+
+    /// Target a specific federated server by hostname. Omit for local.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Target a specific federated server by hostname. Omit to target the local server.")]
+    pub server: Option<String>,
+}
+
 // ── Visual feedback parameter types ─────────────────────────────
 
 /// Parameters for the `wsh_overlay` tool (create, update, or list overlays).
@@ -321,6 +600,19 @@ pub struct OverlayParams {
     #[schemars(description = "Whether this overlay can receive input focus. Defaults to false.")]
     pub focusable: bool,
 
+    /// Milliseconds after creation at which the server removes the overlay.
+    #[schemars(description = "Remove the overlay this many milliseconds after creating it, e.g. 3000 for a toast. Only used when creating.")]
+    pub ttl_ms: Option<u64>,
+
+    /// Show the overlay in both screen modes.
+    #[serde(default)]
+    #[schemars(description = "Show the overlay in both the normal and alternate screen, and keep it when a full-screen program exits. Only used when creating. Defaults to false.")]
+    pub sticky: bool,
+
+    /// Widget the server renders the overlay's spans from.
+    #[schemars(description = "Widget to render instead of spans, kept up to date by the server. Object with 'type' and its data: {\"type\":\"progress\",\"label\":\"Tests\",\"value\":42,\"max\":100}, {\"type\":\"spinner\",\"label\":\"Building...\"}, {\"type\":\"table\",\"rows\":[{\"key\":\"Branch\",\"value\":\"main\"}]} or {\"type\":\"markdown\",\"text\":\"# Title\"}. Form widgets the human fills in while the overlay is focused and input captured (see wsh_input_mode await_form_ms): {\"type\":\"text_input\",\"label\":\"Name:\"}, {\"type\":\"select\",\"options\":[\"a\",\"b\"]} or {\"type\":\"confirm\",\"message\":\"Proceed?\"}; give them focusable=true. When updating, give only the fields that change, e.g. {\"value\": 60}.")]
+    pub widget: Option<serde_json::Value>,
+
     /// If true, list all overlays for the current screen mode instead of creating/updating.
     #[serde(default)]
     #[schemars(description = "If true, list all overlays for the current screen mode. All other parameters are ignored.")]
@@ -385,6 +677,27 @@ pub struct PanelParams {
     #[schemars(description = "Whether this panel can receive input focus. Defaults to false.")]
     pub focusable: bool,
 
+    /// Milliseconds after creation at which the server removes the panel.
+    #[schemars(description = "Remove the panel this many milliseconds after creating it, e.g. 3000 for a toast. Only used when creating.")]
+    pub ttl_ms: Option<u64>,
+
+    /// Show the panel in both screen modes.
+    #[serde(default)]
+    #[schemars(description = "Show the panel in both the normal and alternate screen, and keep it when a full-screen program exits. Only used when creating. Defaults to false.")]
+    pub sticky: bool,
+
+    /// Make the panel scrollable, keeping this many lines of content.
+    #[schemars(description = "Make the panel a scrollable log that keeps up to this many lines, showing the newest. Only used when creating.")]
+    pub max_lines: Option<usize>,
+
+    /// Spans to append to a scrollable panel.
+    #[schemars(description = "With id, append these spans to a scrollable panel, starting on a new line. Other update fields are ignored.")]
+    pub append: Option<Vec<serde_json::Value>>,
+
+    /// Lines to scroll a scrollable panel up from its newest line.
+    #[schemars(description = "With id, scroll a scrollable panel this many lines up from its newest line; 0 follows new lines. Other update fields are ignored.")]
+    pub scroll: Option<usize>,
+
     /// If true, list all panels for the current screen mode instead of creating/updating.
     #[serde(default)]
     #[schemars(description = "If true, list all panels for the current screen mode. All other parameters are ignored.")]
@@ -423,6 +736,10 @@ pub enum InputModeAction {
     Capture,
     /// Switch to passthrough mode (input goes to both API subscribers and PTY).
     Release,
+    /// Ask the human for keyboard control; they approve or deny on their terminal.
+    RequestHandoff,
+    /// Give keyboard control back to the human, or withdraw a pending request.
+    ReturnControl,
 }
 
 /// Parameters for the `wsh_input_mode` tool.
@@ -433,10 +750,20 @@ pub struct InputModeParams {
     pub session: String,
 
     /// Action to change the input mode. Omit to query the current mode without changing it.
-    #[schemars(description = "Action to change the input mode: 'capture' or 'release'. Omit to query without changing.")]
+    #[schemars(description = "Action to change the input mode: 'capture' or 'release', or 'request_handoff' to ask the human for control and 'return_control' to give it back. Omit to query without changing.")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mode: Option<InputModeAction>,
 
+    /// Name shown to the human with a handoff request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Name the human sees in the handoff prompt. Only used with mode 'request_handoff'; defaults to 'agent'.")]
+    pub agent: Option<String>,
+
+    /// Why the agent wants control, shown with a handoff request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "Why you need keyboard control, shown in the handoff prompt. Only used with mode 'request_handoff'.")]
+    pub reason: Option<String>,
+
     /// ID of an overlay or panel to focus. The target must have focusable=true.
     #[schemars(description = "ID of an overlay or panel to focus. The target must have focusable=true.")]
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -447,6 +774,11 @@ pub struct InputModeParams {
     #[schemars(description = "If true, remove focus from any currently focused element.")]
     pub unfocus: bool,
 
+    /// Wait this long for a form widget to be submitted or cancelled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "After applying any changes, wait up to this many milliseconds (at most 300000) for the human to submit or cancel a form widget (text_input, select or confirm) in a focused overlay. The result's 'form' field then holds the overlay 'target', 'submitted' and the submitted 'value', or is null on timeout. Not available with 'server'.")]
+    pub await_form_ms: Option<u64>,
+
     /// Target a specific federated server by hostname. Omit for local.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     #[schemars(description = "Target a specific federated server by hostname. Omit to target the local server.")]
@@ -532,6 +864,13 @@ mod tests {
         assert!(params.cwd.is_none());
         assert!(params.env.is_none());
         assert!(params.tags.is_empty());
+        assert!(params.user.is_none());
+        assert!(params.uid.is_none());
+        assert!(params.sandbox.is_none());
+        assert!(params.remote.is_none());
+        assert!(params.idle_kill_after.is_none());
+        assert!(params.nice.is_none());
+        assert!(params.ionice.is_none());
     }
 
     #[test]
@@ -542,7 +881,14 @@ mod tests {
             "rows": 30,
             "cols": 120,
             "cwd": "/tmp",
-            "env": {"FOO": "bar"}
+            "env": {"FOO": "bar"},
+            "user": "alice",
+            "uid": 1001,
+            "sandbox": "jail",
+            "remote": "ops@web-1",
+            "idle_kill_after": "30m",
+            "nice": 10,
+            "ionice": "idle"
         });
         let params: CreateSessionParams = serde_json::from_value(json).unwrap();
         assert_eq!(params.name.as_deref(), Some("my-session"));
@@ -550,6 +896,13 @@ mod tests {
         assert_eq!(params.rows, Some(30));
         assert_eq!(params.cols, Some(120));
         assert_eq!(params.cwd.as_deref(), Some("/tmp"));
+        assert_eq!(params.user.as_deref(), Some("alice"));
+        assert_eq!(params.uid, Some(1001));
+        assert_eq!(params.sandbox.as_deref(), Some("jail"));
+        assert_eq!(params.remote.as_deref(), Some("ops@web-1"));
+        assert_eq!(params.idle_kill_after.as_deref(), Some("30m"));
+        assert_eq!(params.nice, Some(10));
+        assert_eq!(params.ionice.as_deref(), Some("idle"));
         let env = params.env.unwrap();
         assert_eq!(env.get("FOO").map(|s| s.as_str()), Some("bar"));
     }
@@ -766,6 +1119,15 @@ mod tests {
         assert!(matches!(params.encoding, Encoding::Utf8));
     }
 
+    #[test]
+    fn broadcast_input_params_require_tags() {
+        let json = serde_json::json!({"tag": ["workers"], "input": "make\n"});
+        let params: BroadcastInputParams = serde_json::from_value(json).unwrap();
+        assert_eq!(params.tag, vec!["workers"]);
+        assert!(matches!(params.encoding, Encoding::Utf8));
+        assert!(serde_json::from_value::<BroadcastInputParams>(serde_json::json!({"input": "x"})).is_err());
+    }
+
     #[test]
     fn send_input_params_with_base64() {
         let json = serde_json::json!({
@@ -799,6 +1161,30 @@ mod tests {
 
     // ── GetScreenParams ─────────────────────────────────────────
 
+    #[test]
+    fn send_keys_params_parse() {
+        let json = serde_json::json!({"session": "s", "keys": ["ctrl+c", "ArrowUp"]});
+        let params: SendKeysParams = serde_json::from_value(json).unwrap();
+        assert_eq!(params.keys, vec!["ctrl+c", "ArrowUp"]);
+        assert!(params.server.is_none());
+    }
+
+    #[test]
+    fn send_keys_params_missing_keys() {
+        let json = serde_json::json!({"session": "s"});
+        assert!(serde_json::from_value::<SendKeysParams>(json).is_err());
+    }
+
+    #[test]
+    fn send_secret_params_default_and_debug_redacts() {
+        let json = serde_json::json!({"session": "s", "input": "hunter2\n"});
+        let params: SendSecretParams = serde_json::from_value(json).unwrap();
+        assert!(!params.force);
+        assert_eq!(params.input, "hunter2\n");
+        let debug = format!("{:?}", params);
+        assert!(!debug.contains("hunter2"));
+    }
+
     #[test]
     fn get_screen_params_defaults_to_styled() {
         let json = serde_json::json!({"session": "my-session"});
@@ -881,6 +1267,23 @@ mod tests {
         assert_eq!(params.max_wait_ms, 10000);
     }
 
+    // ── AwaitIdleGroupParams ─────────────────────────────────
+
+    #[test]
+    fn await_idle_group_params_defaults() {
+        let params: AwaitIdleGroupParams = serde_json::from_value(serde_json::json!({})).unwrap();
+        assert!(params.tag.is_empty());
+        assert_eq!(params.mode, GroupIdleMode::All);
+        assert_eq!(params.timeout_ms, 2000);
+        assert_eq!(params.max_wait_ms, 30000);
+        assert!(!params.fresh);
+
+        let params: AwaitIdleGroupParams =
+            serde_json::from_value(serde_json::json!({"tag": ["workers"], "mode": "any"})).unwrap();
+        assert_eq!(params.tag, vec!["workers"]);
+        assert_eq!(params.mode, GroupIdleMode::Any);
+    }
+
     // ── RunCommandParams ────────────────────────────────────────
 
     #[test]
@@ -1129,6 +1532,20 @@ mod tests {
         assert!(matches!(action, InputModeAction::Release));
     }
 
+    #[test]
+    fn input_mode_params_request_handoff() {
+        let json = serde_json::json!({
+            "session": "s",
+            "mode": "request_handoff",
+            "agent": "claude",
+            "reason": "restart the service"
+        });
+        let params: InputModeParams = serde_json::from_value(json).unwrap();
+        assert!(matches!(params.mode, Some(InputModeAction::RequestHandoff)));
+        assert_eq!(params.agent.as_deref(), Some("claude"));
+        assert_eq!(params.reason.as_deref(), Some("restart the service"));
+    }
+
     #[test]
     fn input_mode_action_invalid() {
         let json = serde_json::json!("toggle");
@@ -0,0 +1,169 @@
+//! Running one command in many sessions at once.
+//!
+//! `POST /orchestrate/run` sends the same input to a set of sessions, waits
+//! for each to finish, and returns all their screens in one response, so an
+//! agent driving N worker shells makes one request per step instead of a
+//! send, a wait and a read per session.
+//!
+//! A session has finished when its output has been quiet for the idle
+//! timeout or, for a shell `command`, when the command's exit marker
+//! appears. The marker is a line `[wsh-exit:<nonce>:<status>]` the shell
+//! prints after the command, which also yields its exit status.
+
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::input::InputSource;
+use crate::session::{Delivery, InputError, Session};
+
+/// Most sessions one run may target.
+pub const MAX_SESSIONS: usize = 64;
+
+const MARKER_PREFIX: &str = "[wsh-exit:";
+
+/// Finds a command's exit marker in output that may split it across
+/// chunks.
+pub struct ExitMarker {
+    /// `[wsh-exit:<nonce>:`, unique to one run so that markers from
+    /// earlier runs still in the output don't match.
+    needle: Vec<u8>,
+    /// End of the output so far, in case a marker is split.
+    tail: Vec<u8>,
+}
+
+impl Default for ExitMarker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExitMarker {
+    pub fn new() -> Self {
+        let nonce = uuid::Uuid::new_v4().simple().to_string();
+        Self {
+            needle: format!("{MARKER_PREFIX}{}:", &nonce[..12]).into_bytes(),
+            tail: Vec::new(),
+        }
+    }
+
+    /// `command` followed by a `printf` of the marker with its exit status,
+    /// as one line of shell input.
+    pub fn command_line(&self, command: &str) -> String {
+        let needle = String::from_utf8_lossy(&self.needle);
+        format!("{}; printf '\\n{}%s]\\n' \"$?\"\n", command.trim_end(), needle)
+    }
+
+    /// Look for the marker in the next chunk of output, returning the exit
+    /// status once it has appeared.
+    pub fn scan(&mut self, chunk: &[u8]) -> Option<i32> {
+        self.tail.extend_from_slice(chunk);
+        let found = self.find();
+        // The echoed command line holds the needle followed by `%s`, so the
+        // tail only has to hold a needle and a status.
+        let keep = self.needle.len() + 12;
+        if self.tail.len() > keep {
+            self.tail.drain(..self.tail.len() - keep);
+        }
+        found
+    }
+
+    fn find(&self) -> Option<i32> {
+        let mut rest = &self.tail[..];
+        while let Some(at) = rest.windows(self.needle.len()).position(|w| w == self.needle) {
+            rest = &rest[at + self.needle.len()..];
+            let digits = rest.iter().take_while(|b| b.is_ascii_digit()).count();
+            if digits > 0 && rest.get(digits) == Some(&b']') {
+                return std::str::from_utf8(&rest[..digits]).ok()?.parse().ok();
+            }
+        }
+        None
+    }
+}
+
+/// How a session's run ended.
+pub struct Finished {
+    /// Whether the session finished before `max_wait`.
+    pub finished: bool,
+    /// Exit status of the command, when run with an [`ExitMarker`] that
+    /// appeared.
+    pub exit_code: Option<i32>,
+    pub elapsed: Duration,
+    /// ID of the input when the session holds it for approval instead of
+    /// running it. See [`crate::input::approval`].
+    pub approval: Option<u64>,
+}
+
+/// Send `input` to `session` and wait until it finishes: until the
+/// marker appears when there is one, otherwise until its output has been
+/// quiet for `idle`. Gives up after `max_wait`. Returns at once, not
+/// finished, when the session holds the input for approval.
+pub async fn run(
+    session: &Session,
+    input: Bytes,
+    mut marker: Option<ExitMarker>,
+    idle: Duration,
+    max_wait: Duration,
+) -> Result<Finished, InputError> {
+    // Subscribe first so output that follows the input at once isn't missed.
+    let mut output = session.output_rx.subscribe();
+    let start = Instant::now();
+    if let Delivery::Held(pending) = session.send_input(InputSource::Http, input).await? {
+        return Ok(Finished { finished: false, exit_code: None, elapsed: start.elapsed(), approval: Some(pending.id) });
+    }
+    let deadline = tokio::time::Instant::from_std(start + max_wait);
+
+    let (finished, exit_code) = match marker.as_mut() {
+        Some(marker) => loop {
+            let chunk = tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => break (false, None),
+                _ = session.cancelled.cancelled() => break (false, None),
+                chunk = output.recv() => chunk,
+            };
+            match chunk {
+                Ok(chunk) => {
+                    if let Some(code) = marker.scan(&chunk) {
+                        break (true, Some(code));
+                    }
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break (false, None),
+            }
+        },
+        None => {
+            let idle = tokio::time::timeout_at(deadline, session.activity.wait_for_idle(idle, None)).await;
+            (idle.is_ok(), None)
+        }
+    };
+    Ok(Finished { finished, exit_code, elapsed: start.elapsed(), approval: None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_marker_split_across_chunks_but_not_its_echo() {
+        let mut marker = ExitMarker::new();
+        let line = marker.command_line("make test\n");
+        assert!(line.starts_with("make test; printf '\\n[wsh-exit:"));
+        assert!(line.ends_with("%s]\\n' \"$?\"\n"));
+
+        // The echo of the command line has `%s` where the status goes.
+        assert_eq!(marker.scan(line.as_bytes()), None);
+        let needle = String::from_utf8(marker.needle.clone()).unwrap();
+        let output = format!("ok\r\n{needle}2]\r\n$ ");
+        let (a, b) = output.split_at(output.len() - 8);
+        assert_eq!(marker.scan(a.as_bytes()), None);
+        assert_eq!(marker.scan(b.as_bytes()), Some(2));
+    }
+
+    #[test]
+    fn markers_of_other_runs_do_not_match() {
+        let earlier = ExitMarker::new();
+        let needle = String::from_utf8(earlier.needle.clone()).unwrap();
+        let mut marker = ExitMarker::new();
+        assert_eq!(marker.scan(format!("{needle}0]\r\n").as_bytes()), None);
+    }
+}
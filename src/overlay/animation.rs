@@ -0,0 +1,129 @@
+//! Server-driven overlay animation.
+//!
+//! An animation is a list of keyframes, each giving the overlay's position,
+//! background or spans at some time after the animation starts. The server
+//! moves the overlay smoothly between keyframe positions and swaps in each
+//! keyframe's background and spans as it is reached, so a client can slide
+//! a toast in and out with one request instead of a stream of PATCH calls.
+
+use serde::{Deserialize, Serialize};
+
+use super::types::{BackgroundStyle, OverlaySpan};
+
+/// Time between animation frames.
+pub const FRAME: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// Most keyframes an animation may have.
+pub const MAX_KEYFRAMES: usize = 256;
+
+/// Longest an animation may run, in milliseconds.
+pub const MAX_DURATION_MS: u64 = 10 * 60 * 1000;
+
+/// A sequence of keyframes the server plays on an overlay.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct Animation {
+    /// Keyframes in order of `at_ms`.
+    pub keyframes: Vec<Keyframe>,
+    /// Delete the overlay once the last keyframe is reached.
+    #[serde(default)]
+    pub delete: bool,
+}
+
+/// The state of an overlay at a point in an animation. Fields left out
+/// keep their previous value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct Keyframe {
+    /// Milliseconds after the animation starts.
+    pub at_ms: u64,
+    /// Column, reached by moving steadily from the previous keyframe.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x: Option<u16>,
+    /// Row, reached by moving steadily from the previous keyframe.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<u16>,
+    /// Background, applied when the keyframe is reached.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<BackgroundStyle>,
+    /// Spans, applied when the keyframe is reached.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spans: Option<Vec<OverlaySpan>>,
+}
+
+impl Animation {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.keyframes.is_empty() {
+            return Err("animation has no keyframes".into());
+        }
+        if self.keyframes.len() > MAX_KEYFRAMES {
+            return Err(format!("animation has more than {} keyframes", MAX_KEYFRAMES));
+        }
+        if self.keyframes.windows(2).any(|pair| pair[1].at_ms < pair[0].at_ms) {
+            return Err("keyframes must be in order of at_ms".into());
+        }
+        if self.duration_ms() > MAX_DURATION_MS {
+            return Err(format!("animation is longer than {} ms", MAX_DURATION_MS));
+        }
+        Ok(())
+    }
+
+    /// Time of the last keyframe.
+    pub fn duration_ms(&self) -> u64 {
+        self.keyframes.last().map_or(0, |k| k.at_ms)
+    }
+
+    /// Position of an overlay that started at `start`, `elapsed_ms` into
+    /// the animation.
+    pub fn position_at(&self, start: (u16, u16), elapsed_ms: u64) -> (u16, u16) {
+        let (mut from_at, mut from) = (0, start);
+        for keyframe in &self.keyframes {
+            let to = (keyframe.x.unwrap_or(from.0), keyframe.y.unwrap_or(from.1));
+            if elapsed_ms < keyframe.at_ms {
+                let progress = (elapsed_ms - from_at) as f64 / (keyframe.at_ms - from_at) as f64;
+                return (lerp(from.0, to.0, progress), lerp(from.1, to.1, progress));
+            }
+            (from_at, from) = (keyframe.at_ms, to);
+        }
+        from
+    }
+}
+
+fn lerp(from: u16, to: u16, progress: f64) -> u16 {
+    (from as f64 + (to as f64 - from as f64) * progress).round() as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframe(at_ms: u64, x: Option<u16>, y: Option<u16>) -> Keyframe {
+        Keyframe { at_ms, x, y, background: None, spans: None }
+    }
+
+    #[test]
+    fn positions_move_steadily_between_keyframes() {
+        let animation = Animation {
+            keyframes: vec![keyframe(100, Some(10), None), keyframe(300, None, Some(4)), keyframe(300, Some(0), None)],
+            delete: false,
+        };
+        assert_eq!(animation.position_at((0, 0), 0), (0, 0));
+        assert_eq!(animation.position_at((0, 0), 50), (5, 0));
+        assert_eq!(animation.position_at((0, 0), 100), (10, 0));
+        assert_eq!(animation.position_at((0, 0), 200), (10, 2));
+        // Keyframes at the same time jump straight to the last one.
+        assert_eq!(animation.position_at((0, 0), 300), (0, 4));
+        assert_eq!(animation.position_at((0, 0), 1000), (0, 4));
+    }
+
+    #[test]
+    fn validation() {
+        let animation = |keyframes| Animation { keyframes, delete: false };
+        assert!(animation(vec![]).validate().is_err());
+        assert!(animation(vec![keyframe(200, None, None), keyframe(100, None, None)]).validate().is_err());
+        assert!(animation(vec![keyframe(MAX_DURATION_MS + 1, None, None)]).validate().is_err());
+        assert!(animation(vec![keyframe(0, Some(1), None), keyframe(100, None, None)]).validate().is_ok());
+    }
+}
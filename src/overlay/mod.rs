@@ -1,7 +1,10 @@
+pub mod animation;
 pub mod render;
 pub mod store;
 pub mod types;
+pub mod widget;
 
+pub use animation::{Animation, Keyframe};
 pub use render::{
     begin_sync, cursor_position, end_sync, erase_all_overlays, erase_overlay,
     overlay_line_extents, render_all_overlays, render_overlay, render_spans, reset,
@@ -9,3 +12,4 @@ pub use render::{
 };
 pub use store::OverlayStore;
 pub use types::{BackgroundStyle, Color, NamedColor, Overlay, OverlayId, OverlaySpan, RegionWrite, ScreenMode, Style, is_normal_mode};
+pub use widget::{FormKey, Widget};
@@ -416,6 +416,9 @@ mod tests {
             region_writes: vec![],
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            widget: None,
+            sticky: false,
+            expires_at: None,
         };
         let result = render_overlay(&overlay);
         // y=5, x=10 (0-indexed) -> row=6, col=11 (1-indexed)
@@ -533,6 +536,9 @@ mod tests {
             region_writes: vec![],
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            widget: None,
+            sticky: false,
+            expires_at: None,
         }];
         let result = render_all_overlays(&overlays);
         assert!(
@@ -626,6 +632,9 @@ mod tests {
             region_writes: vec![],
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            widget: None,
+            sticky: false,
+            expires_at: None,
         };
         let extents = overlay_line_extents(&overlay);
         assert_eq!(extents, vec![(3, 5, 5)]);
@@ -653,6 +662,9 @@ mod tests {
             region_writes: vec![],
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            widget: None,
+            sticky: false,
+            expires_at: None,
         };
         let extents = overlay_line_extents(&overlay);
         assert_eq!(extents, vec![(0, 0, 2), (1, 0, 3), (2, 0, 1)]);
@@ -691,6 +703,9 @@ mod tests {
             region_writes: vec![],
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            widget: None,
+            sticky: false,
+            expires_at: None,
         };
         let extents = overlay_line_extents(&overlay);
         // Two spans on same line: width = 2 + 2 = 4
@@ -730,6 +745,9 @@ mod tests {
             region_writes: vec![],
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            widget: None,
+            sticky: false,
+            expires_at: None,
         };
         let extents = overlay_line_extents(&overlay);
         // First span: "ab\n" -> line "ab" (width 2), then newline
@@ -759,6 +777,9 @@ mod tests {
             region_writes: vec![],
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            widget: None,
+            sticky: false,
+            expires_at: None,
         };
         let result = erase_overlay(&overlay);
         // Should erase full width=10 rectangle at (3,5) -> \x1b[4;6H then 10 spaces
@@ -787,6 +808,9 @@ mod tests {
             region_writes: vec![],
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            widget: None,
+            sticky: false,
+            expires_at: None,
         };
         let result = erase_overlay(&overlay);
         let spaces = " ".repeat(20);
@@ -823,6 +847,9 @@ mod tests {
             region_writes: vec![],
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            widget: None,
+            sticky: false,
+            expires_at: None,
         };
         let result = render_overlay(&overlay);
         // Should contain background color
@@ -855,6 +882,9 @@ mod tests {
             }],
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            widget: None,
+            sticky: false,
+            expires_at: None,
         };
         let result = render_overlay(&overlay);
         // Region write at (1, 5) within overlay at (10, 5)
@@ -3,7 +3,9 @@ use std::sync::Arc;
 use parking_lot::RwLock;
 use uuid::Uuid;
 
+use super::animation::Animation;
 use super::types::{BackgroundStyle, Overlay, OverlayId, OverlaySpan, RegionWrite, ScreenMode};
+use super::widget::{FormKey, Widget};
 
 const MAX_OVERLAYS: usize = 256;
 const MAX_SPANS_PER_OVERLAY: usize = 4096;
@@ -22,6 +24,17 @@ fn validate_spans(spans: &[OverlaySpan]) -> Result<(), &'static str> {
     Ok(())
 }
 
+fn validate_widget(widget: &Widget) -> Result<(), &'static str> {
+    widget.validate(MAX_TEXT_BYTES)
+}
+
+/// Re-render an overlay's spans from its widget, if it has one.
+fn render_widget(overlay: &mut Overlay, tick: u64) {
+    if let Some(ref widget) = overlay.widget {
+        overlay.spans = widget.render(overlay.width, overlay.height, tick);
+    }
+}
+
 fn validate_region_writes(writes: &[RegionWrite]) -> Result<(), &'static str> {
     if writes.len() > MAX_REGION_WRITES {
         return Err("too many region writes");
@@ -38,11 +51,22 @@ fn validate_region_writes(writes: &[RegionWrite]) -> Result<(), &'static str> {
 #[derive(Clone)]
 pub struct OverlayStore {
     inner: Arc<RwLock<StoreInner>>,
+    /// Woken when an animated widget is installed. See
+    /// [`animation_started`](Self::animation_started).
+    animated: Arc<tokio::sync::Notify>,
+    /// Woken when an overlay is given an expiry. See
+    /// [`expiry_set`](Self::expiry_set).
+    expiry: Arc<tokio::sync::Notify>,
 }
 
 struct StoreInner {
     overlays: HashMap<OverlayId, Overlay>,
     next_z: i32,
+    /// Widget ticks so far, which pick animation frames.
+    tick: u64,
+    /// Token of the animation playing on each animated overlay.
+    animations: HashMap<OverlayId, u64>,
+    next_animation: u64,
 }
 
 impl OverlayStore {
@@ -51,7 +75,12 @@ impl OverlayStore {
             inner: Arc::new(RwLock::new(StoreInner {
                 overlays: HashMap::new(),
                 next_z: 0,
+                tick: 0,
+                animations: HashMap::new(),
+                next_animation: 0,
             })),
+            animated: Arc::new(tokio::sync::Notify::new()),
+            expiry: Arc::new(tokio::sync::Notify::new()),
         }
     }
 
@@ -96,6 +125,9 @@ impl OverlayStore {
             region_writes: vec![],
             focusable,
             screen_mode,
+            widget: None,
+            sticky: false,
+            expires_at: None,
         };
         inner.overlays.insert(id.clone(), overlay);
         Ok(id)
@@ -115,12 +147,14 @@ impl OverlayStore {
         overlays
     }
 
-    /// Update an overlay's spans (full replacement)
+    /// Update an overlay's spans (full replacement). An overlay's widget
+    /// gives way to spans set this way.
     pub fn update(&self, id: &str, spans: Vec<OverlaySpan>) -> Result<bool, &'static str> {
         validate_spans(&spans)?;
         let mut inner = self.inner.write();
         if let Some(overlay) = inner.overlays.get_mut(id) {
             overlay.spans = spans;
+            overlay.widget = None;
             Ok(true)
         } else {
             Ok(false)
@@ -140,6 +174,7 @@ impl OverlayStore {
         background: Option<BackgroundStyle>,
     ) -> bool {
         let mut inner = self.inner.write();
+        let tick = inner.tick;
         let overlay = match inner.overlays.get_mut(id) {
             Some(o) => o,
             None => return false,
@@ -162,6 +197,7 @@ impl OverlayStore {
         if let Some(background) = background {
             overlay.background = Some(background);
         }
+        render_widget(overlay, tick);
         // Update next_z tracking
         if let Some(z) = z {
             if z >= inner.next_z {
@@ -237,6 +273,7 @@ impl OverlayStore {
         if !inner.overlays.contains_key(id) {
             return Ok(false);
         }
+        let tick = inner.tick;
         // Apply all mutations under a single lock. We use two separate
         // get_mut calls to satisfy the borrow checker (next_z update
         // needs &mut inner while overlay ref also borrows &mut inner).
@@ -262,7 +299,9 @@ impl OverlayStore {
             }
             if let Some(spans) = spans {
                 overlay.spans = spans;
+                overlay.widget = None;
             }
+            render_widget(overlay, tick);
         }
         // Update next_z tracking after dropping the overlay reference
         if let Some(z) = z {
@@ -273,29 +312,212 @@ impl OverlayStore {
         Ok(true)
     }
 
+    /// Give an overlay a widget, replacing its spans with the widget's
+    /// rendering. Returns false if the overlay does not exist.
+    pub fn set_widget(&self, id: &str, widget: Widget) -> Result<bool, &'static str> {
+        validate_widget(&widget)?;
+        let mut inner = self.inner.write();
+        let tick = inner.tick;
+        if let Some(overlay) = inner.overlays.get_mut(id) {
+            if widget.animated() {
+                self.animated.notify_one();
+            }
+            overlay.widget = Some(widget);
+            render_widget(overlay, tick);
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Update an overlay's widget with the fields in `patch` (see
+    /// [`Widget::merge`]) and re-render it. Returns false if the overlay
+    /// does not exist.
+    pub fn patch_widget(&self, id: &str, patch: &serde_json::Value) -> Result<bool, String> {
+        let mut inner = self.inner.write();
+        let tick = inner.tick;
+        let Some(overlay) = inner.overlays.get_mut(id) else {
+            return Ok(false);
+        };
+        let widget = Widget::merge(overlay.widget.as_ref(), patch)?;
+        validate_widget(&widget)?;
+        if widget.animated() {
+            self.animated.notify_one();
+        }
+        overlay.widget = Some(widget);
+        render_widget(overlay, tick);
+        Ok(true)
+    }
+
+    /// Pass a key typed into a focused overlay to its form widget,
+    /// re-rendering the widget if it changed. Returns `None` if the overlay
+    /// does not exist or has no form widget.
+    pub fn form_key(&self, id: &str, data: &[u8]) -> Option<FormKey> {
+        let mut inner = self.inner.write();
+        let tick = inner.tick;
+        let overlay = inner.overlays.get_mut(id)?;
+        let widget = overlay.widget.as_mut().filter(|w| w.is_form())?;
+        let result = widget.handle_key(data, MAX_TEXT_BYTES);
+        if result == FormKey::Changed {
+            render_widget(overlay, tick);
+        }
+        Some(result)
+    }
+
+    /// Wait until an animated widget is installed. Callers tick widgets
+    /// until [`tick_widgets`](Self::tick_widgets) returns false, then wait
+    /// here again.
+    pub async fn animation_started(&self) {
+        self.animated.notified().await;
+    }
+
+    /// Advance animated widgets by a frame. Returns true if any overlay
+    /// changed and needs redrawing.
+    pub fn tick_widgets(&self) -> bool {
+        if !self.inner.read().overlays.values().any(|o| o.widget.as_ref().is_some_and(Widget::animated)) {
+            return false;
+        }
+        let mut inner = self.inner.write();
+        inner.tick = inner.tick.wrapping_add(1);
+        let tick = inner.tick;
+        let mut changed = false;
+        for overlay in inner.overlays.values_mut() {
+            if overlay.widget.as_ref().is_some_and(Widget::animated) {
+                render_widget(overlay, tick);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Start playing `animation` on an overlay, replacing any animation
+    /// already playing on it. Returns a token for
+    /// [`apply_frame`](Self::apply_frame) and the overlay's `x` and `y`, or
+    /// `None` if the overlay does not exist.
+    pub fn start_animation(&self, id: &str, animation: &Animation) -> Result<Option<(u64, u16, u16)>, String> {
+        animation.validate()?;
+        for keyframe in &animation.keyframes {
+            if let Some(ref spans) = keyframe.spans {
+                validate_spans(spans)?;
+            }
+        }
+        let mut inner = self.inner.write();
+        let Some((x, y)) = inner.overlays.get(id).map(|o| (o.x, o.y)) else {
+            return Ok(None);
+        };
+        inner.next_animation += 1;
+        let token = inner.next_animation;
+        inner.animations.insert(id.to_string(), token);
+        Ok(Some((token, x, y)))
+    }
+
+    /// Apply a frame of the animation `token`: move the overlay to
+    /// `position` and set whichever of `background` and `spans` are given.
+    /// Returns false, changing nothing, if the overlay is gone or another
+    /// animation has replaced this one.
+    pub fn apply_frame(
+        &self,
+        id: &str,
+        token: u64,
+        position: (u16, u16),
+        background: Option<BackgroundStyle>,
+        spans: Option<Vec<OverlaySpan>>,
+    ) -> bool {
+        let mut inner = self.inner.write();
+        if inner.animations.get(id) != Some(&token) {
+            return false;
+        }
+        let Some(overlay) = inner.overlays.get_mut(id) else {
+            return false;
+        };
+        (overlay.x, overlay.y) = position;
+        if background.is_some() {
+            overlay.background = background;
+        }
+        if let Some(spans) = spans {
+            overlay.spans = spans;
+            overlay.widget = None;
+        }
+        true
+    }
+
+    /// End the animation `token`. Returns false if another animation had
+    /// replaced it.
+    pub fn finish_animation(&self, id: &str, token: u64) -> bool {
+        let mut inner = self.inner.write();
+        if inner.animations.get(id) != Some(&token) {
+            return false;
+        }
+        inner.animations.remove(id);
+        true
+    }
+
+    /// Set whether an overlay is sticky and when it expires: `ttl` from
+    /// now, or never. Returns false if the overlay does not exist.
+    pub fn set_lifetime(&self, id: &str, ttl: Option<std::time::Duration>, sticky: bool) -> bool {
+        let mut inner = self.inner.write();
+        let Some(overlay) = inner.overlays.get_mut(id) else {
+            return false;
+        };
+        overlay.sticky = sticky;
+        overlay.expires_at = ttl.map(|ttl| crate::session::unix_millis().saturating_add(ttl.as_millis() as u64));
+        if overlay.expires_at.is_some() {
+            self.expiry.notify_one();
+        }
+        true
+    }
+
+    /// Unix time in milliseconds at which the next overlay expires.
+    pub fn next_expiry(&self) -> Option<u64> {
+        self.inner.read().overlays.values().filter_map(|o| o.expires_at).min()
+    }
+
+    /// Delete the overlays that have expired by `now` (Unix milliseconds)
+    /// and return their IDs.
+    pub fn remove_expired(&self, now: u64) -> Vec<OverlayId> {
+        let mut inner = self.inner.write();
+        let expired: Vec<OverlayId> = inner
+            .overlays
+            .values()
+            .filter(|o| o.expires_at.is_some_and(|at| at <= now))
+            .map(|o| o.id.clone())
+            .collect();
+        for id in &expired {
+            inner.overlays.remove(id);
+        }
+        expired
+    }
+
+    /// Wait until an overlay is given an expiry, which may be sooner than
+    /// the one [`next_expiry`](Self::next_expiry) returned before.
+    pub async fn expiry_set(&self) {
+        self.expiry.notified().await;
+    }
+
     /// Delete an overlay by ID, returns true if it existed
     pub fn delete(&self, id: &str) -> bool {
         let mut inner = self.inner.write();
         inner.overlays.remove(id).is_some()
     }
 
-    /// List overlays for a specific screen mode, sorted by z-index (ascending)
+    /// List overlays for a specific screen mode, and sticky ones, sorted by
+    /// z-index (ascending)
     pub fn list_by_mode(&self, mode: ScreenMode) -> Vec<Overlay> {
         let inner = self.inner.read();
         let mut overlays: Vec<_> = inner
             .overlays
             .values()
-            .filter(|o| o.screen_mode == mode)
+            .filter(|o| o.sticky || o.screen_mode == mode)
             .cloned()
             .collect();
         overlays.sort_by_key(|o| o.z);
         overlays
     }
 
-    /// Delete all overlays for a specific screen mode
+    /// Delete all overlays for a specific screen mode, except sticky ones
     pub fn delete_by_mode(&self, mode: ScreenMode) {
         let mut inner = self.inner.write();
-        inner.overlays.retain(|_, o| o.screen_mode != mode);
+        inner.overlays.retain(|_, o| o.sticky || o.screen_mode != mode);
     }
 
     /// Clear all overlays
@@ -611,4 +833,125 @@ mod tests {
         let overlay = store.get(&id).unwrap();
         assert_eq!(overlay.background.unwrap().bg, Color::Named(NamedColor::Red));
     }
+
+    #[test]
+    fn test_widget_renders_and_follows_patches() {
+        let store = OverlayStore::new();
+        let id = store.create(0, 0, None, 12, 1, None, vec![], false, ScreenMode::Normal).unwrap();
+        let widget = Widget::Progress { label: None, value: 0.0, max: 10.0, color: None };
+        assert!(store.set_widget(&id, widget).unwrap());
+        let text = |store: &OverlayStore| store.get(&id).unwrap().spans.iter().map(|s| s.text.clone()).collect::<String>();
+        assert_eq!(text(&store), "░░░░░░░   0%");
+
+        assert!(store.patch_widget(&id, &serde_json::json!({"value": 5})).unwrap());
+        assert_eq!(text(&store), "████░░░  50%");
+
+        // Resizing re-renders at the new width
+        assert!(store.move_to(&id, None, None, None, Some(9), None, None));
+        assert_eq!(text(&store), "██░░  50%");
+
+        assert!(store.patch_widget(&id, &serde_json::json!({"max": 0})).is_err());
+        assert!(!store.patch_widget("nonexistent", &serde_json::json!({"value": 1})).unwrap());
+
+        // Replacing the spans drops the widget
+        store.update(&id, vec![]).unwrap();
+        assert!(store.get(&id).unwrap().widget.is_none());
+    }
+
+    #[test]
+    fn test_tick_widgets_only_when_animated() {
+        let store = OverlayStore::new();
+        let id = store.create(0, 0, None, 20, 1, None, vec![], false, ScreenMode::Normal).unwrap();
+        assert!(!store.tick_widgets());
+        let spinner = Widget::Spinner { label: "wait".into(), color: None };
+        store.set_widget(&id, spinner).unwrap();
+        let first = store.get(&id).unwrap().spans[0].text.clone();
+        assert!(store.tick_widgets());
+        assert_ne!(store.get(&id).unwrap().spans[0].text, first);
+    }
+
+    #[tokio::test]
+    async fn test_animation_started_waits_for_animated_widget() {
+        let store = OverlayStore::new();
+        let id = store.create(0, 0, None, 20, 1, None, vec![], false, ScreenMode::Normal).unwrap();
+        let progress = Widget::Progress { label: None, value: 1.0, max: 10.0, color: None };
+        store.set_widget(&id, progress).unwrap();
+        let wait = tokio::time::timeout(std::time::Duration::from_millis(50), store.animation_started());
+        assert!(wait.await.is_err(), "a progress bar should not start the ticker");
+
+        assert!(store.patch_widget(&id, &serde_json::json!({"type": "spinner"})).unwrap());
+        let wait = tokio::time::timeout(std::time::Duration::from_secs(1), store.animation_started());
+        assert!(wait.await.is_ok(), "a spinner should start the ticker");
+    }
+
+    #[test]
+    fn test_sticky_overlays_survive_mode_switches() {
+        let store = OverlayStore::new();
+        let sticky = store.create(0, 0, None, 80, 1, None, vec![], false, ScreenMode::Alt).unwrap();
+        store.create(0, 0, None, 80, 1, None, vec![], false, ScreenMode::Alt).unwrap();
+        assert!(store.set_lifetime(&sticky, None, true));
+        assert!(!store.set_lifetime("nonexistent", None, true));
+
+        assert_eq!(store.list_by_mode(ScreenMode::Normal).len(), 1);
+        store.delete_by_mode(ScreenMode::Alt);
+        assert_eq!(store.list().len(), 1);
+        assert_eq!(store.list()[0].id, sticky);
+    }
+
+    #[tokio::test]
+    async fn test_remove_expired() {
+        let store = OverlayStore::new();
+        let short = store.create(0, 0, None, 80, 1, None, vec![], false, ScreenMode::Normal).unwrap();
+        let long = store.create(0, 0, None, 80, 1, None, vec![], false, ScreenMode::Normal).unwrap();
+        store.create(0, 0, None, 80, 1, None, vec![], false, ScreenMode::Normal).unwrap();
+        assert_eq!(store.next_expiry(), None);
+
+        store.set_lifetime(&long, Some(std::time::Duration::from_secs(60)), false);
+        store.set_lifetime(&short, Some(std::time::Duration::from_millis(10)), false);
+        let wait = tokio::time::timeout(std::time::Duration::from_secs(1), store.expiry_set());
+        assert!(wait.await.is_ok(), "setting a TTL should wake the expiry task");
+
+        let at = store.next_expiry().unwrap();
+        assert_eq!(at, store.get(&short).unwrap().expires_at.unwrap());
+        assert!(store.remove_expired(at - 1).is_empty());
+        assert_eq!(store.remove_expired(at), vec![short]);
+        assert_eq!(store.list().len(), 2);
+        assert_eq!(store.next_expiry(), store.get(&long).unwrap().expires_at);
+    }
+
+    #[test]
+    fn test_new_animation_replaces_old() {
+        let store = OverlayStore::new();
+        let id = store.create(3, 4, None, 20, 1, None, vec![], false, ScreenMode::Normal).unwrap();
+        let animation = Animation {
+            keyframes: vec![crate::overlay::Keyframe { at_ms: 100, x: Some(10), y: None, background: None, spans: None }],
+            delete: false,
+        };
+        let (first, x, y) = store.start_animation(&id, &animation).unwrap().unwrap();
+        assert_eq!((x, y), (3, 4));
+        assert!(store.apply_frame(&id, first, (5, 4), None, None));
+        assert_eq!(store.get(&id).unwrap().x, 5);
+
+        let (second, _, _) = store.start_animation(&id, &animation).unwrap().unwrap();
+        assert!(!store.apply_frame(&id, first, (6, 4), None, None));
+        assert!(!store.finish_animation(&id, first));
+        assert!(store.finish_animation(&id, second));
+        assert_eq!(store.get(&id).unwrap().x, 5);
+
+        assert!(store.start_animation("nonexistent", &animation).unwrap().is_none());
+        let empty = Animation { keyframes: vec![], delete: false };
+        assert!(store.start_animation(&id, &empty).is_err());
+    }
+
+    #[test]
+    fn test_form_key_edits_form_widgets_only() {
+        let store = OverlayStore::new();
+        let id = store.create(0, 0, None, 20, 1, None, vec![], true, ScreenMode::Normal).unwrap();
+        assert_eq!(store.form_key(&id, b"a"), None);
+        store.set_widget(&id, Widget::TextInput { label: String::new(), value: String::new(), cursor: None }).unwrap();
+        assert_eq!(store.form_key(&id, b"a"), Some(FormKey::Changed));
+        assert_eq!(store.get(&id).unwrap().spans[0].text, "a");
+        assert_eq!(store.form_key(&id, b"\r"), Some(FormKey::Submit("a".into())));
+        assert_eq!(store.form_key("nonexistent", b"a"), None);
+    }
 }
@@ -5,6 +5,7 @@ use serde::{Deserialize, Serialize};
 /// Elements tagged with a particular mode are only visible (and returned by list
 /// endpoints) when the session is in that mode.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum ScreenMode {
     #[default]
@@ -22,6 +23,7 @@ pub type OverlayId = String;
 
 /// Background fill style for an overlay's bounding rectangle.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct BackgroundStyle {
     pub bg: Color,
 }
@@ -31,6 +33,7 @@ pub struct BackgroundStyle {
 /// Enables freeform cell-level drawing for charts, visualizations, and other
 /// non-linear content.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct RegionWrite {
     pub row: u16,
     pub col: u16,
@@ -49,6 +52,7 @@ pub struct RegionWrite {
 
 /// An overlay displayed on top of terminal content
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct Overlay {
     pub id: OverlayId,
     pub x: u16,
@@ -65,10 +69,21 @@ pub struct Overlay {
     pub focusable: bool,
     #[serde(default, skip_serializing_if = "is_normal_mode")]
     pub screen_mode: ScreenMode,
+    /// Structured content `spans` are rendered from, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub widget: Option<super::widget::Widget>,
+    /// Shown in both screen modes, and kept when the alternate screen is
+    /// left.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sticky: bool,
+    /// Unix time in milliseconds at which the server removes the overlay.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
 }
 
 /// A styled text span within an overlay
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct OverlaySpan {
     pub text: String,
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -87,6 +102,7 @@ pub struct OverlaySpan {
 
 /// Color specification for overlay styling
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 #[serde(untagged)]
 pub enum Color {
     Named(NamedColor),
@@ -94,6 +110,7 @@ pub enum Color {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum NamedColor {
     Black,
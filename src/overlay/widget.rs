@@ -0,0 +1,750 @@
+//! Widgets: overlay content described as structured data.
+//!
+//! Instead of laying out spans by hand, a client can give an overlay a
+//! widget -- a progress bar, a spinner, a key-value table or a block of
+//! markdown -- and the server renders it into spans that fit the overlay.
+//! Updating the widget's data (say, a progress bar's `value`) re-renders
+//! the spans, so a client keeps an indicator current with one small patch.
+//!
+//! Form widgets -- a text field, a select menu and a yes/no confirmation --
+//! also take keys. While input is captured and their overlay has focus,
+//! the human's keystrokes edit them, and submitting or cancelling one is
+//! announced to input subscribers as a `submit` or `cancel` event.
+
+use serde::{Deserialize, Serialize};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use super::types::{Color, NamedColor, OverlaySpan};
+
+/// Frames of the spinner animation, advanced on every widget tick.
+const SPINNER_FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// Most rows a table widget may have.
+pub const MAX_TABLE_ROWS: usize = 1024;
+
+/// Most options a select widget may have.
+pub const MAX_SELECT_OPTIONS: usize = 1024;
+
+/// Structured overlay content the server renders into spans.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Widget {
+    /// A bar filled to `value` out of `max`, after an optional label and
+    /// followed by the percentage.
+    Progress {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+        value: f64,
+        #[serde(default = "default_max")]
+        max: f64,
+        /// Color of the filled part. Defaults to green.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        color: Option<Color>,
+    },
+    /// A spinner the server animates, followed by a label.
+    Spinner {
+        #[serde(default)]
+        label: String,
+        /// Color of the spinner. Defaults to cyan.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        color: Option<Color>,
+    },
+    /// Rows of keys and values, with the keys aligned in a column.
+    Table { rows: Vec<TableRow> },
+    /// Markdown text wrapped to the overlay's width. Headings, bold,
+    /// italic, inline code and bullet lists are styled; other markup is
+    /// shown as written.
+    Markdown { text: String },
+    /// A one-line text field. Enter submits `value`; Escape cancels.
+    TextInput {
+        #[serde(default)]
+        label: String,
+        #[serde(default)]
+        value: String,
+        /// Cursor position in characters. Defaults to the end of `value`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cursor: Option<usize>,
+    },
+    /// Options listed one per line. The arrow keys move the selection and
+    /// Enter submits the selected option; Escape cancels.
+    Select {
+        #[serde(default)]
+        label: String,
+        options: Vec<String>,
+        /// Index of the selected option.
+        #[serde(default)]
+        selected: usize,
+    },
+    /// A yes/no question. The arrow keys move between the answers
+    /// and Enter submits the highlighted one; y and n answer directly and
+    /// Escape cancels.
+    Confirm {
+        message: String,
+        /// Whether "Yes" is highlighted.
+        #[serde(default)]
+        yes: bool,
+    },
+}
+
+/// What a key did to a form widget.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormKey {
+    /// The key means nothing to the widget.
+    Ignored,
+    /// The widget's data changed and it needs re-rendering.
+    Changed,
+    /// The widget was submitted with this value: the text of a text field,
+    /// the selected option of a select menu, or the answer to a
+    /// confirmation.
+    Submit(serde_json::Value),
+    /// The widget was dismissed with Escape.
+    Cancel,
+}
+
+/// A row of a table widget.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct TableRow {
+    pub key: String,
+    pub value: String,
+}
+
+fn default_max() -> f64 {
+    100.0
+}
+
+impl Widget {
+    /// Whether the widget changes on its own, and so needs ticking.
+    pub fn animated(&self) -> bool {
+        matches!(self, Widget::Spinner { .. })
+    }
+
+    /// Whether the widget takes keys while its overlay has focus.
+    pub fn is_form(&self) -> bool {
+        matches!(self, Widget::TextInput { .. } | Widget::Select { .. } | Widget::Confirm { .. })
+    }
+
+    /// Check the widget's data. Text lengths are limited like span text.
+    pub fn validate(&self, max_text: usize) -> Result<(), &'static str> {
+        match self {
+            Widget::Progress { label, value, max, .. } => {
+                if !value.is_finite() || !max.is_finite() || *max <= 0.0 {
+                    return Err("progress value and max must be finite, with max above zero");
+                }
+                if label.as_ref().is_some_and(|l| l.len() > max_text) {
+                    return Err("widget text too large");
+                }
+            }
+            Widget::Spinner { label, .. } => {
+                if label.len() > max_text {
+                    return Err("widget text too large");
+                }
+            }
+            Widget::Table { rows } => {
+                if rows.len() > MAX_TABLE_ROWS {
+                    return Err("too many table rows");
+                }
+                if rows.iter().any(|r| r.key.len() + r.value.len() > max_text) {
+                    return Err("widget text too large");
+                }
+            }
+            Widget::Markdown { text } | Widget::Confirm { message: text, .. } => {
+                if text.len() > max_text {
+                    return Err("widget text too large");
+                }
+            }
+            Widget::TextInput { label, value, .. } => {
+                if label.len() + value.len() > max_text {
+                    return Err("widget text too large");
+                }
+            }
+            Widget::Select { label, options, selected } => {
+                if options.is_empty() || options.len() > MAX_SELECT_OPTIONS {
+                    return Err("a select widget needs between 1 and 1024 options");
+                }
+                if *selected >= options.len() {
+                    return Err("selected option out of range");
+                }
+                if label.len() + options.iter().map(String::len).sum::<usize>() > max_text {
+                    return Err("widget text too large");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply a key typed into a form widget. Text entered into a text
+    /// field stops growing at `max_text` bytes.
+    pub fn handle_key(&mut self, data: &[u8], max_text: usize) -> FormKey {
+        let parsed = crate::input::parse_key(data);
+        let ctrl = parsed.modifiers.iter().any(|m| m == "ctrl");
+        match (parsed.key.as_deref(), ctrl) {
+            (Some("Escape"), _) => return FormKey::Cancel,
+            (Some("Enter"), _) => {
+                return match self {
+                    Widget::TextInput { value, .. } => FormKey::Submit(value.clone().into()),
+                    Widget::Select { options, selected, .. } => FormKey::Submit(options[*selected].clone().into()),
+                    Widget::Confirm { yes, .. } => FormKey::Submit((*yes).into()),
+                    _ => FormKey::Ignored,
+                };
+            }
+            _ => {}
+        }
+        match self {
+            Widget::TextInput { label, value, cursor } => {
+                let len = value.chars().count();
+                let at = cursor.unwrap_or(len).min(len);
+                let byte = |i: usize| value.char_indices().nth(i).map_or(value.len(), |(b, _)| b);
+                let moved = match (parsed.key.as_deref(), ctrl) {
+                    (Some("ArrowLeft"), _) | (Some("b"), true) => at.saturating_sub(1),
+                    (Some("ArrowRight"), _) | (Some("f"), true) => (at + 1).min(len),
+                    (Some("Home"), _) | (Some("a"), true) => 0,
+                    (Some("End"), _) | (Some("e"), true) => len,
+                    (Some("Backspace"), _) | (Some("h"), true) if at > 0 => {
+                        value.remove(byte(at - 1));
+                        at - 1
+                    }
+                    (Some("u"), true) => {
+                        value.replace_range(..byte(at), "");
+                        0
+                    }
+                    (Some("k"), true) => {
+                        value.truncate(byte(at));
+                        at
+                    }
+                    _ if data == b"\x1b[3~" && at < len => {
+                        value.remove(byte(at));
+                        at
+                    }
+                    _ => match std::str::from_utf8(data) {
+                        Ok(text) if !text.is_empty() && !text.chars().any(char::is_control) => {
+                            if label.len() + value.len() + text.len() > max_text {
+                                return FormKey::Ignored;
+                            }
+                            value.insert_str(byte(at), text);
+                            at + text.chars().count()
+                        }
+                        _ => return FormKey::Ignored,
+                    },
+                };
+                *cursor = Some(moved);
+                FormKey::Changed
+            }
+            Widget::Select { options, selected, .. } => {
+                let moved = match parsed.key.as_deref() {
+                    Some("ArrowUp") => selected.saturating_sub(1),
+                    Some("ArrowDown") => (*selected + 1).min(options.len() - 1),
+                    Some("Home") => 0,
+                    Some("End") => options.len() - 1,
+                    _ => return FormKey::Ignored,
+                };
+                if moved == *selected {
+                    return FormKey::Ignored;
+                }
+                *selected = moved;
+                FormKey::Changed
+            }
+            Widget::Confirm { yes, .. } => match parsed.key.as_deref() {
+                Some("y") | Some("Y") => FormKey::Submit(true.into()),
+                Some("n") | Some("N") => FormKey::Submit(false.into()),
+                Some("ArrowLeft") | Some("ArrowRight") => {
+                    *yes = !*yes;
+                    FormKey::Changed
+                }
+                _ => FormKey::Ignored,
+            },
+            _ => FormKey::Ignored,
+        }
+    }
+
+    /// Apply `patch` to the widget's data. Fields in `patch` replace the
+    /// widget's; a patch with a different `type` replaces the widget.
+    pub fn merge(current: Option<&Widget>, patch: &serde_json::Value) -> Result<Widget, String> {
+        let serde_json::Value::Object(fields) = patch else {
+            return Err("widget must be an object".into());
+        };
+        let mut merged = match current.map(serde_json::to_value) {
+            Some(Ok(serde_json::Value::Object(current)))
+                if fields.get("type").is_none_or(|t| Some(t) == current.get("type")) =>
+            {
+                current
+            }
+            _ => serde_json::Map::new(),
+        };
+        for (key, value) in fields {
+            merged.insert(key.clone(), value.clone());
+        }
+        serde_json::from_value(serde_json::Value::Object(merged)).map_err(|e| format!("invalid widget: {e}"))
+    }
+
+    /// Render the widget into spans for an overlay of `width` x `height`.
+    /// `tick` picks the animation frame of animated widgets.
+    pub fn render(&self, width: u16, height: u16, tick: u64) -> Vec<OverlaySpan> {
+        let width = usize::from(width);
+        let height = usize::from(height.max(1));
+        match self {
+            Widget::Progress { label, value, max, color } => render_progress(label.as_deref(), *value / *max, color, width),
+            Widget::Spinner { label, color } => {
+                let frame = SPINNER_FRAMES[(tick % SPINNER_FRAMES.len() as u64) as usize];
+                let mut spans = vec![span(frame, Style {
+                    fg: Some(color.clone().unwrap_or(Color::Named(NamedColor::Cyan))),
+                    ..Style::default()
+                })];
+                if !label.is_empty() && width > 2 {
+                    spans.push(span(&format!(" {}", truncate(label, width - 2)), Style::default()));
+                }
+                spans
+            }
+            Widget::Table { rows } => render_table(rows, width, height),
+            Widget::Markdown { text } => render_markdown(text, width, height),
+            Widget::TextInput { label, value, cursor } => render_text_input(label, value, *cursor, width),
+            Widget::Select { label, options, selected } => render_select(label, options, *selected, width, height),
+            Widget::Confirm { message, yes } => render_confirm(message, *yes, width, height),
+        }
+    }
+}
+
+#[derive(Clone, Default, PartialEq)]
+struct Style {
+    fg: Option<Color>,
+    bg: Option<Color>,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+}
+
+fn span(text: &str, style: Style) -> OverlaySpan {
+    OverlaySpan {
+        text: text.to_string(),
+        id: None,
+        fg: style.fg,
+        bg: style.bg,
+        bold: style.bold,
+        italic: style.italic,
+        underline: style.underline,
+    }
+}
+
+/// The longest prefix of `text` that fits in `width` columns.
+fn truncate(text: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (i, c) in text.char_indices() {
+        used += c.width().unwrap_or(0);
+        if used > width {
+            return &text[..i];
+        }
+    }
+    text
+}
+
+fn render_progress(label: Option<&str>, fraction: f64, color: &Option<Color>, width: usize) -> Vec<OverlaySpan> {
+    let fraction = fraction.clamp(0.0, 1.0);
+    let percent = format!(" {:>3}%", (fraction * 100.0).floor() as u32);
+    let mut spans = Vec::new();
+    let mut used = percent.len();
+    if let Some(label) = label.filter(|l| !l.is_empty()) {
+        let label = format!("{} ", truncate(label, width.saturating_sub(used + 1)));
+        used += label.width();
+        spans.push(span(&label, Style {
+            bold: true,
+            ..Style::default()
+        }));
+    }
+    let bar = width.saturating_sub(used);
+    let filled = (fraction * bar as f64).round() as usize;
+    if filled > 0 {
+        spans.push(span(&"█".repeat(filled), Style {
+            fg: Some(color.clone().unwrap_or(Color::Named(NamedColor::Green))),
+            ..Style::default()
+        }));
+    }
+    if bar > filled {
+        spans.push(span(&"░".repeat(bar - filled), Style::default()));
+    }
+    spans.push(span(&percent, Style::default()));
+    spans
+}
+
+/// Style of the text field cursor and the highlighted answer of a
+/// confirmation.
+fn highlight() -> Style {
+    Style {
+        fg: Some(Color::Named(NamedColor::Black)),
+        bg: Some(Color::Named(NamedColor::White)),
+        ..Style::default()
+    }
+}
+
+fn render_text_input(label: &str, value: &str, cursor: Option<usize>, width: usize) -> Vec<OverlaySpan> {
+    let mut spans = Vec::new();
+    let label = if label.is_empty() { String::new() } else { format!("{} ", truncate(label, width.saturating_sub(2))) };
+    if !label.is_empty() {
+        spans.push(span(&label, Style {
+            bold: true,
+            ..Style::default()
+        }));
+    }
+    let field = width.saturating_sub(label.width()).max(1);
+    let chars: Vec<char> = value.chars().collect();
+    let at = cursor.unwrap_or(chars.len()).min(chars.len());
+    // Scroll the text so the cursor stays in view.
+    let mut start = 0;
+    while chars[start..at].iter().map(|c| c.width().unwrap_or(0)).sum::<usize>() + 1 > field && start < at {
+        start += 1;
+    }
+    let before: String = chars[start..at].iter().collect();
+    let under = chars.get(at).map_or(" ".to_string(), |c| c.to_string());
+    let after: String = chars.get(at + 1..).unwrap_or_default().iter().collect();
+    if !before.is_empty() {
+        spans.push(span(&before, Style::default()));
+    }
+    spans.push(span(&under, highlight()));
+    let room = field.saturating_sub(before.width() + under.width());
+    let after = truncate(&after, room);
+    if !after.is_empty() {
+        spans.push(span(after, Style::default()));
+    }
+    spans
+}
+
+fn render_select(label: &str, options: &[String], selected: usize, width: usize, height: usize) -> Vec<OverlaySpan> {
+    let mut lines: Vec<OverlaySpan> = Vec::new();
+    if !label.is_empty() {
+        lines.push(span(truncate(label, width), Style {
+            bold: true,
+            ..Style::default()
+        }));
+    }
+    // Scroll the list so the selected option stays in view.
+    let rows = height.saturating_sub(lines.len()).max(1);
+    let first = (selected + 1).saturating_sub(rows);
+    for (i, option) in options.iter().enumerate().skip(first).take(rows) {
+        let text = truncate(option, width.saturating_sub(2));
+        lines.push(if i == selected {
+            span(&format!("> {text}"), Style {
+                fg: Some(Color::Named(NamedColor::Cyan)),
+                bold: true,
+                ..Style::default()
+            })
+        } else {
+            span(&format!("  {text}"), Style::default())
+        });
+    }
+    let mut spans = Vec::new();
+    for (i, mut line) in lines.into_iter().enumerate() {
+        if i > 0 {
+            line.text.insert(0, '\n');
+        }
+        spans.push(line);
+    }
+    spans
+}
+
+fn render_confirm(message: &str, yes: bool, width: usize, height: usize) -> Vec<OverlaySpan> {
+    let mut spans = Vec::new();
+    let answer = |label: &str, on: bool| span(label, if on { highlight() } else { Style::default() });
+    let buttons = [answer("[ Yes ]", yes), span("  ", Style::default()), answer("[ No ]", !yes)];
+    let buttons_width = "[ Yes ]  [ No ]".width();
+    if height == 1 {
+        let message = truncate(message, width.saturating_sub(buttons_width + 2));
+        if !message.is_empty() {
+            spans.push(span(&format!("{message}  "), Style::default()));
+        }
+    } else {
+        for line in message.lines().take(height - 1) {
+            spans.push(span(&format!("{}\n", truncate(line, width)), Style::default()));
+        }
+    }
+    spans.extend(buttons);
+    spans
+}
+
+fn render_table(rows: &[TableRow], width: usize, height: usize) -> Vec<OverlaySpan> {
+    // Keys take at most half the width, so long keys don't hide values.
+    let key_width = rows.iter().map(|r| r.key.width()).max().unwrap_or(0).min(width / 2);
+    let value_width = width.saturating_sub(key_width + 2);
+    let mut spans = Vec::new();
+    for (i, row) in rows.iter().take(height).enumerate() {
+        let key = truncate(&row.key, key_width);
+        let pad = " ".repeat(key_width - key.width());
+        let newline = if i > 0 { "\n" } else { "" };
+        spans.push(span(&format!("{newline}{key}{pad}"), Style {
+            bold: true,
+            ..Style::default()
+        }));
+        spans.push(span(&format!("  {}", truncate(&row.value, value_width)), Style::default()));
+    }
+    spans
+}
+
+/// Split a line of markdown into styled runs of text.
+fn inline_runs(text: &str, base: &Style) -> Vec<(String, Style)> {
+    let mut runs: Vec<(String, Style)> = Vec::new();
+    let mut style = base.clone();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+    let flush = |current: &mut String, style: &Style, runs: &mut Vec<(String, Style)>| {
+        if !current.is_empty() {
+            runs.push((std::mem::take(current), style.clone()));
+        }
+    };
+    while let Some(c) = chars.next() {
+        match c {
+            '`' => {
+                // Inline code runs to the closing backtick, unstyled within.
+                let code: String = chars.by_ref().take_while(|&c| c != '`').collect();
+                flush(&mut current, &style, &mut runs);
+                runs.push((code, Style {
+                    fg: Some(Color::Named(NamedColor::Cyan)),
+                    ..base.clone()
+                }));
+            }
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                flush(&mut current, &style, &mut runs);
+                style.bold = !style.bold;
+            }
+            '*' | '_' => {
+                flush(&mut current, &style, &mut runs);
+                style.italic = !style.italic;
+            }
+            c => current.push(c),
+        }
+    }
+    flush(&mut current, &style, &mut runs);
+    runs
+}
+
+fn render_markdown(text: &str, width: usize, height: usize) -> Vec<OverlaySpan> {
+    let width = width.max(1);
+    let mut lines: Vec<Vec<(String, Style)>> = Vec::new();
+    for source in text.lines() {
+        let trimmed = source.trim_start();
+        let (prefix, body, base) = if let Some(heading) = trimmed.strip_prefix('#') {
+            let level = 1 + heading.chars().take_while(|&c| c == '#').count();
+            let body = heading.trim_start_matches('#').trim_start();
+            let base = Style {
+                bold: true,
+                underline: level == 1,
+                ..Style::default()
+            };
+            ("", body, base)
+        } else if let Some(item) = trimmed.strip_prefix("- ").or_else(|| trimmed.strip_prefix("* ")) {
+            ("• ", item, Style::default())
+        } else {
+            ("", trimmed, Style::default())
+        };
+        if body.is_empty() && prefix.is_empty() {
+            lines.push(Vec::new());
+            continue;
+        }
+        // Wrap word by word; list items hang under their first word.
+        let indent = prefix.width();
+        let mut line: Vec<(String, Style)> = vec![(prefix.to_string(), Style::default())];
+        let mut used = indent;
+        for (run, style) in inline_runs(body, &base) {
+            for word in run.split_inclusive(' ') {
+                let w = word.trim_end().width();
+                if used + w > width && used > indent {
+                    if let Some((text, _)) = line.last_mut() {
+                        text.truncate(text.trim_end().len());
+                    }
+                    lines.push(std::mem::take(&mut line));
+                    line.push((" ".repeat(indent), Style::default()));
+                    used = indent;
+                }
+                let word = truncate(word, width - used);
+                used += word.width();
+                match line.last_mut() {
+                    Some((text, last)) if *last == style => text.push_str(word),
+                    _ => line.push((word.to_string(), style.clone())),
+                }
+            }
+        }
+        lines.push(line);
+    }
+
+    let mut spans = Vec::new();
+    for (i, line) in lines.into_iter().take(height).enumerate() {
+        if i > 0 {
+            spans.push(span("\n", Style::default()));
+        }
+        for (text, style) in line {
+            if !text.is_empty() {
+                spans.push(span(&text, style));
+            }
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(spans: &[OverlaySpan]) -> String {
+        spans.iter().map(|s| s.text.as_str()).collect()
+    }
+
+    #[test]
+    fn progress_fills_the_width() {
+        let widget = Widget::Progress {
+            label: Some("Tests".into()),
+            value: 50.0,
+            max: 100.0,
+            color: None,
+        };
+        let spans = widget.render(20, 1, 0);
+        assert_eq!(text(&spans), "Tests █████░░░░  50%");
+        assert_eq!(text(&spans).width(), 20);
+        assert!(spans[0].bold);
+        assert_eq!(spans[1].fg, Some(Color::Named(NamedColor::Green)));
+    }
+
+    #[test]
+    fn progress_clamps_out_of_range_values() {
+        let widget = Widget::Progress {
+            label: None,
+            value: 7.0,
+            max: 5.0,
+            color: None,
+        };
+        assert_eq!(text(&widget.render(10, 1, 0)), "█████ 100%");
+    }
+
+    #[test]
+    fn spinner_advances_with_the_tick() {
+        let widget = Widget::Spinner {
+            label: "Running tests".into(),
+            color: None,
+        };
+        assert_eq!(text(&widget.render(40, 1, 0)), "⠋ Running tests");
+        assert_eq!(text(&widget.render(40, 1, 1)), "⠙ Running tests");
+        assert_eq!(text(&widget.render(8, 1, 0)), "⠋ Runnin");
+        assert!(widget.animated());
+    }
+
+    #[test]
+    fn table_aligns_keys() {
+        let widget = Widget::Table {
+            rows: vec![
+                TableRow { key: "Branch".into(), value: "main".into() },
+                TableRow { key: "CI".into(), value: "passing".into() },
+                TableRow { key: "Extra".into(), value: "cut off".into() },
+            ],
+        };
+        assert_eq!(text(&widget.render(30, 2, 0)), "Branch  main\nCI      passing");
+    }
+
+    #[test]
+    fn markdown_styles_and_wraps() {
+        let widget = Widget::Markdown {
+            text: "# Build\nThe **release** build uses `cargo` and takes a while\n- one item".into(),
+        };
+        let spans = widget.render(20, 10, 0);
+        assert_eq!(
+            text(&spans),
+            "Build\nThe release build\nuses cargo and takes\na while\n• one item"
+        );
+        let heading = &spans[0];
+        assert!(heading.bold && heading.underline);
+        assert!(spans.iter().any(|s| s.text == "release" && s.bold));
+        assert!(spans.iter().any(|s| s.text == "cargo" && s.fg == Some(Color::Named(NamedColor::Cyan))));
+    }
+
+    #[test]
+    fn merge_updates_fields_or_replaces() {
+        let widget = Widget::Progress {
+            label: Some("Build".into()),
+            value: 10.0,
+            max: 100.0,
+            color: None,
+        };
+        let merged = Widget::merge(Some(&widget), &serde_json::json!({"value": 60})).unwrap();
+        assert_eq!(
+            merged,
+            Widget::Progress {
+                label: Some("Build".into()),
+                value: 60.0,
+                max: 100.0,
+                color: None,
+            }
+        );
+        let replaced = Widget::merge(Some(&widget), &serde_json::json!({"type": "spinner", "label": "x"})).unwrap();
+        assert!(replaced.animated());
+        assert!(Widget::merge(None, &serde_json::json!({"value": 1})).is_err());
+    }
+
+    #[test]
+    fn validate_rejects_bad_progress() {
+        let widget = Widget::Progress {
+            label: None,
+            value: 1.0,
+            max: 0.0,
+            color: None,
+        };
+        assert!(widget.validate(100).is_err());
+    }
+
+    #[test]
+    fn text_input_edits_and_submits() {
+        let mut widget = Widget::TextInput { label: "Name:".into(), value: String::new(), cursor: None };
+        for key in ["a", "c", "\x1b[D", "b", "\x1b[F", "é", "\x7f"] {
+            assert_eq!(widget.handle_key(key.as_bytes(), 1024), FormKey::Changed);
+        }
+        assert_eq!(widget, Widget::TextInput { label: "Name:".into(), value: "abc".into(), cursor: Some(3) });
+        assert_eq!(text(&widget.render(12, 1, 0)), "Name: abc ");
+        assert_eq!(widget.handle_key(b"\x01", 1024), FormKey::Changed);
+        assert_eq!(widget.handle_key(b"\x1b[3~", 1024), FormKey::Changed);
+        let spans = widget.render(12, 1, 0);
+        assert_eq!(text(&spans), "Name: bc");
+        assert_eq!(spans[1].text, "b");
+        assert_eq!(spans[1].bg, Some(Color::Named(NamedColor::White)));
+
+        assert_eq!(widget.handle_key(b"x", 7), FormKey::Ignored);
+        assert_eq!(widget.handle_key(b"\x1b[A", 1024), FormKey::Ignored);
+        assert_eq!(widget.handle_key(b"\r", 1024), FormKey::Submit("bc".into()));
+        assert_eq!(widget.handle_key(b"\x1b", 1024), FormKey::Cancel);
+    }
+
+    #[test]
+    fn text_input_scrolls_to_the_cursor() {
+        let widget = Widget::TextInput { label: String::new(), value: "abcdefghij".into(), cursor: None };
+        assert_eq!(text(&widget.render(5, 1, 0)), "ghij ");
+        let widget = Widget::TextInput { label: String::new(), value: "abcdefghij".into(), cursor: Some(0) };
+        assert_eq!(text(&widget.render(5, 1, 0)), "abcde");
+    }
+
+    #[test]
+    fn select_moves_and_submits() {
+        let mut widget = Widget::Select {
+            label: "Deploy to".into(),
+            options: vec!["staging".into(), "production".into(), "dev".into()],
+            selected: 0,
+        };
+        assert_eq!(text(&widget.render(20, 3, 0)), "Deploy to\n> staging\n  production");
+        assert_eq!(widget.handle_key(b"\x1b[A", 1024), FormKey::Ignored);
+        assert_eq!(widget.handle_key(b"\x1b[B", 1024), FormKey::Changed);
+        assert_eq!(widget.handle_key(b"\x1b[B", 1024), FormKey::Changed);
+        assert_eq!(text(&widget.render(20, 3, 0)), "Deploy to\n  production\n> dev");
+        assert_eq!(widget.handle_key(b"\r", 1024), FormKey::Submit("dev".into()));
+
+        let empty = Widget::Select { label: String::new(), options: vec![], selected: 0 };
+        assert!(empty.validate(1024).is_err());
+        let out_of_range = Widget::Select { label: String::new(), options: vec!["a".into()], selected: 1 };
+        assert!(out_of_range.validate(1024).is_err());
+    }
+
+    #[test]
+    fn confirm_answers() {
+        let mut widget = Widget::Confirm { message: "Delete the branch?".into(), yes: false };
+        assert_eq!(text(&widget.render(30, 2, 0)), "Delete the branch?\n[ Yes ]  [ No ]");
+        assert_eq!(text(&widget.render(40, 1, 0)), "Delete the branch?  [ Yes ]  [ No ]");
+        assert_eq!(widget.handle_key(b"\x1b[C", 1024), FormKey::Changed);
+        assert_eq!(widget.handle_key(b"\r", 1024), FormKey::Submit(true.into()));
+        assert_eq!(widget.handle_key(b"n", 1024), FormKey::Submit(false.into()));
+        assert!(widget.is_form());
+        assert!(!Widget::Markdown { text: String::new() }.is_form());
+    }
+}
@@ -36,8 +36,8 @@ pub fn compute_layout(panels: &[Panel], terminal_rows: u16, terminal_cols: u16)
         .collect();
 
     // Sort by z descending (highest priority first)
-    top_panels.sort_by(|a, b| b.z.cmp(&a.z));
-    bottom_panels.sort_by(|a, b| b.z.cmp(&a.z));
+    top_panels.sort_by_key(|p| std::cmp::Reverse(p.z));
+    bottom_panels.sort_by_key(|p| std::cmp::Reverse(p.z));
 
     let mut remaining_rows = terminal_rows;
     let mut visible_top: Vec<Panel> = Vec::new();
@@ -51,7 +51,7 @@ pub fn compute_layout(panels: &[Panel], terminal_rows: u16, terminal_cols: u16)
         .chain(bottom_panels.iter())
         .cloned()
         .collect();
-    all_panels.sort_by(|a, b| b.z.cmp(&a.z));
+    all_panels.sort_by_key(|p| std::cmp::Reverse(p.z));
 
     for panel in &all_panels {
         if remaining_rows == 0 {
@@ -74,8 +74,8 @@ pub fn compute_layout(panels: &[Panel], terminal_rows: u16, terminal_cols: u16)
     }
 
     // Re-sort visible panels: top by z descending, bottom by z descending
-    visible_top.sort_by(|a, b| b.z.cmp(&a.z));
-    visible_bottom.sort_by(|a, b| b.z.cmp(&a.z));
+    visible_top.sort_by_key(|p| std::cmp::Reverse(p.z));
+    visible_bottom.sort_by_key(|p| std::cmp::Reverse(p.z));
 
     let top_height: u16 = visible_top.iter().map(|p| p.height).sum();
     let bottom_height: u16 = visible_bottom.iter().map(|p| p.height).sum();
@@ -122,6 +122,10 @@ mod tests {
             visible: true,
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            sticky: false,
+            expires_at: None,
+            max_lines: None,
+            scroll: 0,
         }
     }
 
@@ -291,6 +295,10 @@ mod tests {
             visible: true,
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            sticky: false,
+            expires_at: None,
+            max_lines: None,
+            scroll: 0,
         }];
         let layout = compute_layout(&panels, 24, 80);
         assert_eq!(layout.bottom_panels[0].spans[0].text, "hello");
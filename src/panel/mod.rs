@@ -9,5 +9,5 @@ pub use layout::{compute_layout, Layout};
 pub use render::{
     erase_all_panels, render_all_panels, render_panel, reset_scroll_region, set_scroll_region,
 };
-pub use store::PanelStore;
+pub use store::{validate_max_lines, PanelStore};
 pub use types::{Panel, PanelId, Position};
@@ -24,7 +24,9 @@ pub fn reset_scroll_region() -> &'static str {
 ///
 /// Rendering pipeline:
 /// 1. Fill background (if background is set) for all rows
-/// 2. Render span content for each row (spans are split on `\n`)
+/// 2. Render span content for each row (spans are split on `\n`). A
+///    scrollable panel shows the lines ending `scroll` lines above its
+///    newest one; any other panel shows its first lines
 /// 3. Clear remaining columns with spaces up to `terminal_cols`
 /// 4. Render region writes on top of everything
 pub fn render_panel(panel: &Panel, start_row: u16, terminal_cols: u16) -> String {
@@ -62,6 +64,15 @@ pub fn render_panel(panel: &Panel, start_row: u16, terminal_cols: u16) -> String
         }
     }
 
+    let height = panel.height as usize;
+    let first_line = if panel.max_lines.is_some() {
+        let end = text_lines.len().saturating_sub(panel.scroll).max(height.min(text_lines.len()));
+        end.saturating_sub(height)
+    } else {
+        0
+    };
+    let text_lines = &text_lines[first_line..];
+
     for (row_offset, segments) in text_lines.iter().enumerate() {
         if row_offset as u16 >= panel.height {
             break; // Don't render beyond panel height
@@ -279,6 +290,10 @@ mod tests {
             visible: true,
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            sticky: false,
+            expires_at: None,
+            max_lines: None,
+            scroll: 0,
         }
     }
 
@@ -305,6 +320,10 @@ mod tests {
             visible: true,
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            sticky: false,
+            expires_at: None,
+            max_lines: None,
+            scroll: 0,
         };
         let result = render_panel(&panel, 23, 10);
         // Should position at row 23, col 0 (0-indexed -> \x1b[24;1H)
@@ -327,6 +346,10 @@ mod tests {
             visible: true,
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            sticky: false,
+            expires_at: None,
+            max_lines: None,
+            scroll: 0,
         };
         let result = render_panel(&panel, 0, 10);
         // Row 0: line1
@@ -337,6 +360,26 @@ mod tests {
         assert!(result.contains("line2"));
     }
 
+    #[test]
+    fn test_render_scrollable_panel_shows_a_window_of_its_lines() {
+        let mut panel = make_panel("t", Position::Bottom, 2, 0);
+        panel.spans = vec![span("a1\nb2\nc3\nd4")];
+
+        // A fixed panel shows its first lines.
+        let result = render_panel(&panel, 0, 4);
+        assert!(result.contains("a1") && result.contains("b2") && !result.contains("c3"));
+
+        // A scrollable panel follows its newest lines...
+        panel.max_lines = Some(100);
+        let result = render_panel(&panel, 0, 4);
+        assert!(result.contains("c3") && result.contains("d4") && !result.contains("b2"));
+
+        // ...unless scrolled up.
+        panel.scroll = 1;
+        let result = render_panel(&panel, 0, 4);
+        assert!(result.contains("b2") && result.contains("c3") && !result.contains("d4"));
+    }
+
     #[test]
     fn test_render_panel_clears_empty_rows() {
         let panel = Panel {
@@ -350,6 +393,10 @@ mod tests {
             visible: true,
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            sticky: false,
+            expires_at: None,
+            max_lines: None,
+            scroll: 0,
         };
         let result = render_panel(&panel, 0, 20);
         // Should render content on row 0, then clear rows 1 and 2
@@ -378,6 +425,10 @@ mod tests {
             visible: true,
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            sticky: false,
+            expires_at: None,
+            max_lines: None,
+            scroll: 0,
         };
         let result = render_panel(&panel, 23, 10);
         assert!(result.contains("\x1b[1m")); // bold
@@ -436,6 +487,10 @@ mod tests {
             region_writes: vec![],
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            sticky: false,
+            expires_at: None,
+            max_lines: None,
+            scroll: 0,
         };
         let result = render_panel(&panel, 23, 10);
         assert!(result.contains("\x1b[44m")); // blue bg
@@ -463,6 +518,10 @@ mod tests {
             }],
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            sticky: false,
+            expires_at: None,
+            max_lines: None,
+            scroll: 0,
         };
         let result = render_panel(&panel, 10, 20);
         // row=10+1=11, col=2, 1-indexed: \x1b[12;3H
@@ -5,12 +5,24 @@ use uuid::Uuid;
 
 use crate::overlay::{BackgroundStyle, OverlaySpan, RegionWrite, ScreenMode};
 
-use super::types::{Panel, PanelId, Position};
+use super::types::{line_count, Panel, PanelId, Position};
 
 const MAX_PANELS: usize = 256;
 const MAX_SPANS_PER_PANEL: usize = 4096;
 const MAX_REGION_WRITES: usize = 4096;
 const MAX_TEXT_BYTES: usize = 65_536; // 64 KB per span/write
+const MAX_PANEL_LINES: usize = 10_000;
+
+/// Check the `max_lines` of a scrollable panel.
+pub fn validate_max_lines(max_lines: usize) -> Result<(), &'static str> {
+    if max_lines == 0 {
+        return Err("max_lines must be at least 1");
+    }
+    if max_lines > MAX_PANEL_LINES {
+        return Err("max_lines too large");
+    }
+    Ok(())
+}
 
 fn validate_spans(spans: &[OverlaySpan]) -> Result<(), &'static str> {
     if spans.len() > MAX_SPANS_PER_PANEL {
@@ -24,6 +36,30 @@ fn validate_spans(spans: &[OverlaySpan]) -> Result<(), &'static str> {
     Ok(())
 }
 
+/// Drop the oldest lines of a scrollable panel beyond its `max_lines`, and
+/// keep its scroll offset within its content.
+fn trim_lines(panel: &mut Panel) {
+    let Some(max_lines) = panel.max_lines else {
+        return;
+    };
+    let mut excess = line_count(&panel.spans).saturating_sub(max_lines);
+    while excess > 0 {
+        let span = &mut panel.spans[0];
+        match span.text.match_indices('\n').nth(excess - 1) {
+            Some((i, _)) => {
+                span.text.drain(..=i);
+                excess = 0;
+            }
+            None => {
+                excess -= span.text.matches('\n').count();
+                panel.spans.remove(0);
+            }
+        }
+    }
+    let lines = line_count(&panel.spans);
+    panel.scroll = panel.scroll.min(lines.saturating_sub(panel.height as usize));
+}
+
 fn validate_region_writes(writes: &[RegionWrite]) -> Result<(), &'static str> {
     if writes.len() > MAX_REGION_WRITES {
         return Err("too many region writes");
@@ -40,6 +76,9 @@ fn validate_region_writes(writes: &[RegionWrite]) -> Result<(), &'static str> {
 #[derive(Clone)]
 pub struct PanelStore {
     inner: Arc<RwLock<StoreInner>>,
+    /// Woken when a panel is given an expiry. See
+    /// [`expiry_set`](Self::expiry_set).
+    expiry: Arc<tokio::sync::Notify>,
 }
 
 struct StoreInner {
@@ -54,6 +93,7 @@ impl PanelStore {
                 panels: HashMap::new(),
                 next_z: 0,
             })),
+            expiry: Arc::new(tokio::sync::Notify::new()),
         }
     }
 
@@ -94,6 +134,10 @@ impl PanelStore {
             visible: true,
             focusable,
             screen_mode,
+            sticky: false,
+            expires_at: None,
+            max_lines: None,
+            scroll: 0,
         };
         inner.panels.insert(id.clone(), panel);
         Ok(id)
@@ -126,6 +170,7 @@ impl PanelStore {
         let mut inner = self.inner.write();
         if let Some(panel) = inner.panels.get_mut(id) {
             panel.spans = spans;
+            trim_lines(panel);
             Ok(true)
         } else {
             Ok(false)
@@ -172,6 +217,7 @@ impl PanelStore {
         if let Some(spans) = spans {
             panel.spans = spans;
         }
+        trim_lines(panel);
         Ok(true)
     }
 
@@ -197,6 +243,7 @@ impl PanelStore {
                     }
                 }
             }
+            trim_lines(panel);
             Ok(true)
         } else {
             Ok(false)
@@ -217,6 +264,90 @@ impl PanelStore {
         }
     }
 
+    /// Make a panel scrollable, keeping up to `max_lines` lines of content,
+    /// or show only its first lines again. Returns false if the panel does
+    /// not exist.
+    pub fn set_max_lines(&self, id: &str, max_lines: Option<usize>) -> Result<bool, &'static str> {
+        if let Some(max_lines) = max_lines {
+            validate_max_lines(max_lines)?;
+        }
+        let mut inner = self.inner.write();
+        let Some(panel) = inner.panels.get_mut(id) else {
+            return Ok(false);
+        };
+        panel.max_lines = max_lines;
+        if max_lines.is_none() {
+            panel.scroll = 0;
+        }
+        trim_lines(panel);
+        Ok(true)
+    }
+
+    /// Append `spans` to a scrollable panel, starting on a new line, and
+    /// drop the oldest lines beyond its `max_lines`. A panel scrolled up
+    /// stays on the lines it was showing. Returns false if the panel does
+    /// not exist.
+    pub fn append(&self, id: &str, spans: Vec<OverlaySpan>) -> Result<bool, &'static str> {
+        validate_spans(&spans)?;
+        let mut inner = self.inner.write();
+        let Some(panel) = inner.panels.get_mut(id) else {
+            return Ok(false);
+        };
+        if panel.max_lines.is_none() {
+            return Err("panel is not scrollable; set max_lines");
+        }
+        let before = line_count(&panel.spans);
+        let mut appended = panel.spans.clone();
+        if !appended.is_empty() {
+            appended.push(OverlaySpan {
+                text: "\n".to_string(),
+                id: None,
+                fg: None,
+                bg: None,
+                bold: false,
+                italic: false,
+                underline: false,
+            });
+        }
+        appended.extend(spans);
+        let added = line_count(&appended) - before;
+        let mut updated = Panel { spans: appended, ..panel.clone() };
+        if updated.scroll > 0 {
+            updated.scroll += added;
+        }
+        trim_lines(&mut updated);
+        validate_spans(&updated.spans)?;
+        *panel = updated;
+        Ok(true)
+    }
+
+    /// Scroll a scrollable panel to `offset` lines up from its newest line;
+    /// 0 follows new lines as they are appended. The offset is limited to
+    /// the panel's content. Returns false if the panel does not exist.
+    pub fn scroll(&self, id: &str, offset: usize) -> Result<bool, &'static str> {
+        let mut inner = self.inner.write();
+        let Some(panel) = inner.panels.get_mut(id) else {
+            return Ok(false);
+        };
+        if panel.max_lines.is_none() {
+            return Err("panel is not scrollable; set max_lines");
+        }
+        panel.scroll = offset;
+        trim_lines(panel);
+        Ok(true)
+    }
+
+    /// Set or remove a panel's background. Returns false if the panel does
+    /// not exist.
+    pub fn set_background(&self, id: &str, background: Option<BackgroundStyle>) -> bool {
+        let mut inner = self.inner.write();
+        let Some(panel) = inner.panels.get_mut(id) else {
+            return false;
+        };
+        panel.background = background;
+        true
+    }
+
     /// Set visibility for a panel (called by layout engine)
     pub fn set_visible(&self, id: &str, visible: bool) {
         let mut inner = self.inner.write();
@@ -225,19 +356,62 @@ impl PanelStore {
         }
     }
 
+    /// Set whether a panel is sticky and when it expires: `ttl` from now,
+    /// or never. Returns false if the panel does not exist.
+    pub fn set_lifetime(&self, id: &str, ttl: Option<std::time::Duration>, sticky: bool) -> bool {
+        let mut inner = self.inner.write();
+        let Some(panel) = inner.panels.get_mut(id) else {
+            return false;
+        };
+        panel.sticky = sticky;
+        panel.expires_at = ttl.map(|ttl| crate::session::unix_millis().saturating_add(ttl.as_millis() as u64));
+        if panel.expires_at.is_some() {
+            self.expiry.notify_one();
+        }
+        true
+    }
+
+    /// Unix time in milliseconds at which the next panel expires.
+    pub fn next_expiry(&self) -> Option<u64> {
+        self.inner.read().panels.values().filter_map(|p| p.expires_at).min()
+    }
+
+    /// Delete the panels that have expired by `now` (Unix milliseconds)
+    /// and return their IDs.
+    pub fn remove_expired(&self, now: u64) -> Vec<PanelId> {
+        let mut inner = self.inner.write();
+        let expired: Vec<PanelId> = inner
+            .panels
+            .values()
+            .filter(|p| p.expires_at.is_some_and(|at| at <= now))
+            .map(|p| p.id.clone())
+            .collect();
+        for id in &expired {
+            inner.panels.remove(id);
+        }
+        expired
+    }
+
+    /// Wait until a panel is given an expiry, which may be sooner than the
+    /// one [`next_expiry`](Self::next_expiry) returned before.
+    pub async fn expiry_set(&self) {
+        self.expiry.notified().await;
+    }
+
     /// Delete a panel by ID, returns true if it existed
     pub fn delete(&self, id: &str) -> bool {
         let mut inner = self.inner.write();
         inner.panels.remove(id).is_some()
     }
 
-    /// List panels for a specific screen mode, sorted by position then z descending
+    /// List panels for a specific screen mode, and sticky ones, sorted by
+    /// position then z descending
     pub fn list_by_mode(&self, mode: ScreenMode) -> Vec<Panel> {
         let inner = self.inner.read();
         let mut panels: Vec<_> = inner
             .panels
             .values()
-            .filter(|p| p.screen_mode == mode)
+            .filter(|p| p.sticky || p.screen_mode == mode)
             .cloned()
             .collect();
         panels.sort_by(|a, b| {
@@ -251,10 +425,10 @@ impl PanelStore {
         panels
     }
 
-    /// Delete all panels for a specific screen mode
+    /// Delete all panels for a specific screen mode, except sticky ones
     pub fn delete_by_mode(&self, mode: ScreenMode) {
         let mut inner = self.inner.write();
-        inner.panels.retain(|_, p| p.screen_mode != mode);
+        inner.panels.retain(|_, p| p.sticky || p.screen_mode != mode);
     }
 
     /// Clear all panels
@@ -625,4 +799,81 @@ mod tests {
         assert!(panel.background.is_some());
         assert_eq!(panel.background.unwrap().bg, Color::Named(NamedColor::Blue));
     }
+
+    #[test]
+    fn test_sticky_panels_survive_mode_switches() {
+        let store = PanelStore::new();
+        let sticky = store.create(Position::Top, 1, None, None, vec![], false, ScreenMode::Alt).unwrap();
+        store.create(Position::Bottom, 1, None, None, vec![], false, ScreenMode::Alt).unwrap();
+        assert!(store.set_lifetime(&sticky, None, true));
+
+        assert_eq!(store.list_by_mode(ScreenMode::Normal).len(), 1);
+        store.delete_by_mode(ScreenMode::Alt);
+        assert_eq!(store.list().len(), 1);
+        assert_eq!(store.list()[0].id, sticky);
+    }
+
+    #[test]
+    fn test_remove_expired() {
+        let store = PanelStore::new();
+        let id = store.create(Position::Bottom, 1, None, None, vec![], false, ScreenMode::Normal).unwrap();
+        store.create(Position::Top, 1, None, None, vec![], false, ScreenMode::Normal).unwrap();
+        assert!(store.set_lifetime(&id, Some(std::time::Duration::from_millis(10)), false));
+
+        let at = store.next_expiry().unwrap();
+        assert!(store.remove_expired(at - 1).is_empty());
+        assert_eq!(store.remove_expired(at), vec![id]);
+        assert_eq!(store.list().len(), 1);
+        assert_eq!(store.next_expiry(), None);
+    }
+
+    fn text_span(text: &str) -> OverlaySpan {
+        OverlaySpan {
+            text: text.to_string(),
+            id: None,
+            fg: None,
+            bg: None,
+            bold: false,
+            italic: false,
+            underline: false,
+        }
+    }
+
+    fn text(panel: &Panel) -> String {
+        panel.spans.iter().map(|s| s.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_append_keeps_the_newest_lines() {
+        let store = PanelStore::new();
+        let id = store.create(Position::Bottom, 2, None, None, vec![], false, ScreenMode::Normal).unwrap();
+        assert!(store.append(&id, vec![text_span("one")]).is_err(), "not scrollable yet");
+        assert!(store.set_max_lines(&id, Some(3)).unwrap());
+
+        store.append(&id, vec![text_span("one")]).unwrap();
+        store.append(&id, vec![text_span("two\nthree")]).unwrap();
+        assert_eq!(text(&store.get(&id).unwrap()), "one\ntwo\nthree");
+        store.append(&id, vec![text_span("four")]).unwrap();
+        assert_eq!(text(&store.get(&id).unwrap()), "two\nthree\nfour");
+        assert!(store.set_max_lines(&id, Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_scrolled_panel_stays_on_its_lines() {
+        let store = PanelStore::new();
+        let id = store.create(Position::Bottom, 2, None, None, vec![], false, ScreenMode::Normal).unwrap();
+        store.set_max_lines(&id, Some(100)).unwrap();
+        store.append(&id, vec![text_span("1\n2\n3\n4")]).unwrap();
+
+        // Scrolling is limited to the lines above the panel's height.
+        store.scroll(&id, 10).unwrap();
+        assert_eq!(store.get(&id).unwrap().scroll, 2);
+        store.scroll(&id, 1).unwrap();
+        store.append(&id, vec![text_span("5")]).unwrap();
+        assert_eq!(store.get(&id).unwrap().scroll, 2);
+
+        store.scroll(&id, 0).unwrap();
+        store.append(&id, vec![text_span("6")]).unwrap();
+        assert_eq!(store.get(&id).unwrap().scroll, 0);
+    }
 }
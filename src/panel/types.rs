@@ -7,6 +7,7 @@ pub type PanelId = String;
 
 /// Edge of the terminal where a panel is anchored
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 #[serde(rename_all = "lowercase")]
 pub enum Position {
     Top,
@@ -18,6 +19,7 @@ pub enum Position {
 /// Unlike overlays (which draw on top of PTY content), panels shrink the PTY
 /// viewport so that programs never write into panel space.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct Panel {
     pub id: PanelId,
     pub position: Position,
@@ -33,6 +35,34 @@ pub struct Panel {
     pub focusable: bool,
     #[serde(default, skip_serializing_if = "is_normal_mode")]
     pub screen_mode: ScreenMode,
+    /// Shown in both screen modes, and kept when the alternate screen is
+    /// left.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sticky: bool,
+    /// Unix time in milliseconds at which the server removes the panel.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Lines of content kept by a scrollable panel. Its content may be
+    /// taller than the panel, and the oldest lines are dropped once there
+    /// are more than this. Unset for a panel that shows its first lines.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_lines: Option<usize>,
+    /// How many lines a scrollable panel is scrolled up from its newest
+    /// line.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub scroll: usize,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+/// Number of lines in `spans`, which are split on `\n`.
+pub fn line_count(spans: &[OverlaySpan]) -> usize {
+    if spans.is_empty() {
+        return 0;
+    }
+    1 + spans.iter().map(|s| s.text.matches('\n').count()).sum::<usize>()
 }
 
 #[cfg(test)]
@@ -77,6 +107,10 @@ mod tests {
             visible: true,
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            sticky: false,
+            expires_at: None,
+            max_lines: None,
+            scroll: 0,
         };
         let json = serde_json::to_string(&panel).unwrap();
         let deserialized: Panel = serde_json::from_str(&json).unwrap();
@@ -102,6 +136,10 @@ mod tests {
             visible: false,
             focusable: false,
             screen_mode: ScreenMode::Normal,
+            sticky: false,
+            expires_at: None,
+            max_lines: None,
+            scroll: 0,
         };
         let json = serde_json::to_string(&panel).unwrap();
         assert!(json.contains("\"visible\":false"));
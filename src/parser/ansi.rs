@@ -3,6 +3,8 @@
 //! Used by the attach handler to replay styled screen/scrollback content
 //! to reconnecting clients.
 
+use unicode_width::UnicodeWidthChar;
+
 use super::state::{Color, FormattedLine, Span, Style};
 
 /// Convert a `FormattedLine` to a string containing ANSI escape sequences.
@@ -13,6 +15,120 @@ pub fn line_to_ansi(line: &FormattedLine) -> String {
     }
 }
 
+/// Split a line into rows of at most `width` terminal columns.
+///
+/// Span styles are preserved across the split. Trailing blank cells with no
+/// visible background are dropped first, so a short line from a wide session
+/// does not wrap into empty rows. Always returns at least one row.
+pub fn wrap_line(line: &FormattedLine, width: usize) -> Vec<FormattedLine> {
+    let width = width.max(1);
+    match line {
+        FormattedLine::Plain(text) => {
+            let mut rows = Vec::new();
+            let mut current = String::new();
+            let mut used = 0;
+            for ch in text.trim_end().chars() {
+                let w = ch.width().unwrap_or(0);
+                if used + w > width && used > 0 {
+                    rows.push(FormattedLine::Plain(std::mem::take(&mut current)));
+                    used = 0;
+                }
+                current.push(ch);
+                used += w;
+            }
+            rows.push(FormattedLine::Plain(current));
+            rows
+        }
+        FormattedLine::Styled(spans) => {
+            let spans = trim_trailing_blank(spans);
+            let mut rows = Vec::new();
+            let mut current: Vec<Span> = Vec::new();
+            let mut used = 0;
+            for span in &spans {
+                let mut text = String::new();
+                for ch in span.text.chars() {
+                    let w = ch.width().unwrap_or(0);
+                    if used + w > width && used > 0 {
+                        if !text.is_empty() {
+                            current.push(Span {
+                                text: std::mem::take(&mut text),
+                                style: span.style.clone(),
+                            });
+                        }
+                        rows.push(FormattedLine::Styled(std::mem::take(&mut current)));
+                        used = 0;
+                    }
+                    text.push(ch);
+                    used += w;
+                }
+                if !text.is_empty() {
+                    current.push(Span {
+                        text,
+                        style: span.style.clone(),
+                    });
+                }
+            }
+            rows.push(FormattedLine::Styled(current));
+            rows
+        }
+    }
+}
+
+/// Cut out the columns `start..start + width` of a line.
+///
+/// Span styles are preserved. A wide character straddling either edge is
+/// replaced by spaces for the columns that fall inside the window.
+pub fn crop_line(line: &FormattedLine, start: usize, width: usize) -> FormattedLine {
+    let end = start + width;
+    let crop = |text: &str, col: &mut usize| -> String {
+        let mut out = String::new();
+        for ch in text.chars() {
+            let w = ch.width().unwrap_or(0);
+            let (from, to) = (*col, *col + w);
+            *col = to;
+            if from >= start && to <= end {
+                out.push(ch);
+            } else if from < end && to > start {
+                let visible = to.min(end) - from.max(start);
+                out.extend(std::iter::repeat_n(' ', visible));
+            }
+        }
+        out
+    };
+    let mut col = 0;
+    match line {
+        FormattedLine::Plain(text) => FormattedLine::Plain(crop(text, &mut col)),
+        FormattedLine::Styled(spans) => FormattedLine::Styled(
+            spans
+                .iter()
+                .map(|span| Span {
+                    text: crop(&span.text, &mut col),
+                    style: span.style.clone(),
+                })
+                .filter(|span| !span.text.is_empty())
+                .collect(),
+        ),
+    }
+}
+
+/// Drop trailing whitespace that would render as empty cells.
+fn trim_trailing_blank(spans: &[Span]) -> Vec<Span> {
+    let mut spans = spans.to_vec();
+    while let Some(last) = spans.last_mut() {
+        if last.style.bg.is_some() || last.style.inverse {
+            break;
+        }
+        let trimmed = last.text.trim_end();
+        if trimmed.is_empty() {
+            spans.pop();
+        } else {
+            last.text.truncate(trimmed.len());
+            break;
+        }
+    }
+    spans
+}
+
 /// Convert a slice of `Span`s to an ANSI-styled string.
 fn spans_to_ansi(spans: &[Span]) -> String {
     let mut buf = String::new();
@@ -102,6 +218,100 @@ fn color_to_sgr(color: &Color, is_fg: bool) -> String {
 mod tests {
     use super::*;
 
+    fn span(text: &str, style: Style) -> Span {
+        Span {
+            text: text.to_string(),
+            style,
+        }
+    }
+
+    fn row_text(line: &FormattedLine) -> String {
+        match line {
+            FormattedLine::Plain(t) => t.clone(),
+            FormattedLine::Styled(spans) => spans.iter().map(|s| s.text.as_str()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_wrap_plain_line() {
+        let rows = wrap_line(&FormattedLine::Plain("abcdefgh   ".to_string()), 3);
+        let texts: Vec<String> = rows.iter().map(row_text).collect();
+        assert_eq!(texts, vec!["abc", "def", "gh"]);
+    }
+
+    #[test]
+    fn test_wrap_empty_line_yields_one_row() {
+        let rows = wrap_line(&FormattedLine::Styled(vec![span("    ", Style::default())]), 10);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(row_text(&rows[0]), "");
+    }
+
+    #[test]
+    fn test_wrap_styled_line_preserves_styles_across_split() {
+        let bold = Style { bold: true, ..Style::default() };
+        let line = FormattedLine::Styled(vec![
+            span("ab", Style::default()),
+            span("cdef", bold.clone()),
+        ]);
+        let rows = wrap_line(&line, 4);
+        assert_eq!(rows.len(), 2);
+        match &rows[1] {
+            FormattedLine::Styled(spans) => {
+                assert_eq!(spans.len(), 1);
+                assert_eq!(spans[0].text, "ef");
+                assert_eq!(spans[0].style, bold);
+            }
+            _ => panic!("expected styled row"),
+        }
+    }
+
+    #[test]
+    fn test_wrap_keeps_trailing_background() {
+        let bg = Style { bg: Some(Color::Indexed(4)), ..Style::default() };
+        let line = FormattedLine::Styled(vec![span("x", Style::default()), span("   ", bg)]);
+        let rows = wrap_line(&line, 80);
+        assert_eq!(row_text(&rows[0]), "x   ");
+    }
+
+    #[test]
+    fn test_wrap_counts_wide_chars() {
+        let rows = wrap_line(&FormattedLine::Plain("日本語".to_string()), 4);
+        let texts: Vec<String> = rows.iter().map(row_text).collect();
+        assert_eq!(texts, vec!["日本", "語"]);
+    }
+
+    #[test]
+    fn test_crop_plain_line() {
+        let line = FormattedLine::Plain("0123456789".to_string());
+        assert_eq!(row_text(&crop_line(&line, 3, 4)), "3456");
+        assert_eq!(row_text(&crop_line(&line, 8, 4)), "89");
+        assert_eq!(row_text(&crop_line(&line, 20, 4)), "");
+    }
+
+    #[test]
+    fn test_crop_styled_line_keeps_styles() {
+        let bold = Style { bold: true, ..Style::default() };
+        let line = FormattedLine::Styled(vec![
+            span("abc", Style::default()),
+            span("def", bold.clone()),
+        ]);
+        match crop_line(&line, 2, 3) {
+            FormattedLine::Styled(spans) => {
+                assert_eq!(spans.len(), 2);
+                assert_eq!(spans[0].text, "c");
+                assert_eq!(spans[1].text, "de");
+                assert_eq!(spans[1].style, bold);
+            }
+            other => panic!("expected styled line, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_crop_pads_straddling_wide_char() {
+        let line = FormattedLine::Plain("日本語".to_string());
+        assert_eq!(row_text(&crop_line(&line, 1, 4)), " 本 ");
+    }
+
     #[test]
     fn test_default_style_produces_no_sgr() {
         let span = Span {
@@ -0,0 +1,157 @@
+//! OSC 52 clipboard sequences.
+//!
+//! Programs like vim and tmux copy by writing `OSC 52 ; Pc ; Pd ST` to the
+//! terminal, where `Pc` names the selection (`c` for the clipboard, `p`
+//! for the primary selection, ...) and `Pd` is the base64 of the text. A
+//! `Pd` of `?` asks the terminal to reply with the selection's contents.
+//!
+//! The parser keeps the last write so API clients can read it, and
+//! [`response`] builds the reply an API client sends to a program that
+//! asked.
+
+use base64::Engine;
+use serde::Serialize;
+
+use super::osc::OscScanner;
+
+/// Longest OSC 52 payload we buffer before giving up on a sequence.
+const MAX_OSC: usize = 1024 * 1024;
+
+/// Contents of a session's clipboard.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct Clipboard {
+    /// Selection targets as sent, e.g. `c` or `pc`.
+    pub selection: String,
+    pub text: String,
+    pub source: ClipboardSource,
+}
+
+/// Who last set the clipboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ClipboardSource {
+    /// The program in the session, with OSC 52.
+    Application,
+    /// An API client, with `POST /sessions/{name}/clipboard`.
+    Api,
+}
+
+/// Scanner for OSC 52 writes. Carries partial sequences across chunk
+/// boundaries.
+pub(super) struct ClipboardScanner {
+    osc: OscScanner,
+}
+
+impl ClipboardScanner {
+    pub(super) fn new() -> Self {
+        Self {
+            osc: OscScanner::new(&["52;"], MAX_OSC),
+        }
+    }
+
+    /// Feed a chunk of output. Returns the clipboard writes completed in it,
+    /// oldest first. Queries (`Pd` of `?`) and malformed payloads are
+    /// ignored.
+    pub(super) fn feed(&mut self, text: &str) -> Vec<Clipboard> {
+        self.osc
+            .feed(text)
+            .into_iter()
+            .filter_map(|(_, body)| parse(&body))
+            .collect()
+    }
+}
+
+fn parse(body: &[u8]) -> Option<Clipboard> {
+    let (selection, data) = std::str::from_utf8(body.strip_prefix(b"52;")?)
+        .ok()?
+        .split_once(';')?;
+    if data == "?" {
+        return None;
+    }
+    let text = base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .ok()?;
+    Some(Clipboard {
+        selection: selection.to_string(),
+        text: String::from_utf8_lossy(&text).into_owned(),
+        source: ClipboardSource::Application,
+    })
+}
+
+/// The reply a terminal sends to an OSC 52 query: `text` as the contents
+/// of `selection`.
+pub fn response(selection: &str, text: &str) -> Vec<u8> {
+    let data = base64::engine::general_purpose::STANDARD.encode(text);
+    format!("\x1b]52;{};{}\x1b\\", selection, data).into_bytes()
+}
+
+/// Byte ranges of the complete OSC 52 sequences in `data`. Used by attach
+/// clients, which see raw output rather than the parser's view of it.
+pub fn sequences(data: &[u8]) -> Vec<std::ops::Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while let Some(pos) = data[i..].windows(5).position(|w| w == b"\x1b]52;") {
+        let start = i + pos;
+        let body = start + 5;
+        let end = data[body..].iter().enumerate().find_map(|(j, &b)| match b {
+            0x07 => Some(body + j + 1),
+            0x1b if data.get(body + j + 1) == Some(&b'\\') => Some(body + j + 2),
+            _ => None,
+        });
+        let Some(end) = end else { break };
+        ranges.push(start..end);
+        i = end;
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(selection: &str, text: &str) -> Clipboard {
+        Clipboard {
+            selection: selection.to_string(),
+            text: text.to_string(),
+            source: ClipboardSource::Application,
+        }
+    }
+
+    #[test]
+    fn scanner_finds_writes_across_chunks() {
+        let mut s = ClipboardScanner::new();
+        // "hello" and "wsh"
+        assert_eq!(
+            s.feed("a\x1b]52;c;aGVsbG8=\x07b"),
+            vec![write("c", "hello")]
+        );
+        assert!(s.feed("\x1b]52;pc;d3").is_empty());
+        assert_eq!(s.feed("No\x1b\\"), vec![write("pc", "wsh")]);
+    }
+
+    #[test]
+    fn scanner_ignores_queries_titles_and_bad_payloads() {
+        let mut s = ClipboardScanner::new();
+        assert!(s.feed("\x1b]52;c;?\x07").is_empty());
+        assert!(s.feed("\x1b]0;52;c;aGVsbG8=\x07").is_empty());
+        assert!(s.feed("\x1b]52;c;not base64!\x07").is_empty());
+        assert!(s.feed("\x1b]52;c\x07").is_empty());
+        assert_eq!(s.feed("\x1b]52;c;\x07"), vec![write("c", "")]);
+    }
+
+    #[test]
+    fn response_encodes_text() {
+        assert_eq!(response("c", "hello"), b"\x1b]52;c;aGVsbG8=\x1b\\");
+    }
+
+    #[test]
+    fn finds_sequences_in_raw_output() {
+        let data = b"x\x1b]52;c;aGk=\x07y\x1b]0;title\x07\x1b]52;p;aGk=\x1b\\\x1b]52;c;aG";
+        let ranges = sequences(data);
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(&data[ranges[0].clone()], b"\x1b]52;c;aGk=\x07");
+        assert_eq!(&data[ranges[1].clone()], b"\x1b]52;p;aGk=\x1b\\");
+    }
+}
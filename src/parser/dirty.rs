@@ -0,0 +1,102 @@
+//! Which screen rows changed since a client last looked.
+//!
+//! Every batch of output that changes the screen starts a new screen
+//! generation and stamps the rows it touched with it. A client that last
+//! saw generation `g` only needs the rows stamped after `g`, so polling
+//! agents get a few rows instead of the whole grid. Anything that moves or
+//! replaces the whole screen (a resize, an alternate screen switch, a
+//! restore) stamps every row.
+
+/// Generation stamps for the visible rows.
+pub(super) struct DirtyRows {
+    generation: u64,
+    rows: Vec<u64>,
+}
+
+impl DirtyRows {
+    /// Rows of a new screen, all stamped with `generation`.
+    pub(super) fn new(rows: usize, generation: u64) -> Self {
+        Self {
+            generation,
+            rows: vec![generation; rows],
+        }
+    }
+
+    /// The current screen generation.
+    pub(super) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Start a new generation if any of `changed` rows changed, and stamp
+    /// them with it.
+    pub(super) fn touch(&mut self, changed: &[usize]) {
+        if changed.is_empty() {
+            return;
+        }
+        self.generation += 1;
+        for &row in changed {
+            if let Some(stamp) = self.rows.get_mut(row) {
+                *stamp = self.generation;
+            }
+        }
+    }
+
+    /// Start a new generation that changed all of a screen of `rows` rows.
+    pub(super) fn touch_all(&mut self, rows: usize) {
+        self.generation += 1;
+        self.rows = vec![self.generation; rows];
+    }
+
+    /// Rows changed after generation `since`, or `None` if `since` isn't
+    /// one this screen has reached and only the whole screen will do.
+    pub(super) fn changed_since(&self, since: u64) -> Option<Vec<usize>> {
+        if since > self.generation {
+            return None;
+        }
+        Some(
+            self.rows
+                .iter()
+                .enumerate()
+                .filter(|(_, &stamp)| stamp > since)
+                .map(|(row, _)| row)
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_rows_touched_after_a_generation_are_changed() {
+        let mut dirty = DirtyRows::new(4, 10);
+        assert_eq!(dirty.changed_since(10), Some(vec![]));
+        assert_eq!(dirty.changed_since(9), Some(vec![0, 1, 2, 3]));
+
+        dirty.touch(&[1]);
+        dirty.touch(&[]);
+        dirty.touch(&[3, 1]);
+        assert_eq!(dirty.generation(), 12);
+        assert_eq!(dirty.changed_since(10), Some(vec![1, 3]));
+        assert_eq!(dirty.changed_since(11), Some(vec![1, 3]));
+        assert_eq!(dirty.changed_since(12), Some(vec![]));
+    }
+
+    #[test]
+    fn whole_screen_changes_stamp_every_row() {
+        let mut dirty = DirtyRows::new(2, 0);
+        dirty.touch(&[0]);
+        dirty.touch_all(3);
+        assert_eq!(dirty.changed_since(1), Some(vec![0, 1, 2]));
+        // Rows past the screen's end are ignored.
+        dirty.touch(&[7]);
+        assert_eq!(dirty.changed_since(2), Some(vec![]));
+    }
+
+    #[test]
+    fn unknown_generations_need_the_whole_screen() {
+        let dirty = DirtyRows::new(2, 5);
+        assert_eq!(dirty.changed_since(6), None);
+    }
+}
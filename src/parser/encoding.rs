@@ -0,0 +1,98 @@
+//! Character encodings for programs that don't write UTF-8.
+//!
+//! The parser decodes each chunk of output before feeding the terminal, so
+//! screen, scrollback and events are always UTF-8. Attached terminals get
+//! the program's bytes unchanged.
+
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+/// Encoding of a session's output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub enum Encoding {
+    /// UTF-8; invalid sequences become U+FFFD.
+    #[default]
+    #[serde(rename = "utf-8", alias = "utf8")]
+    Utf8,
+    /// ISO-8859-1: each byte is the code point of the same value.
+    #[serde(rename = "latin-1", alias = "latin1", alias = "iso-8859-1")]
+    Latin1,
+    /// Windows-1252: Latin-1 with printable characters in 0x80-0x9F.
+    #[serde(rename = "windows-1252", alias = "cp1252")]
+    Windows1252,
+}
+
+/// Windows-1252 characters for bytes 0x80-0x9F. The five bytes it leaves
+/// undefined map to the C1 control of the same value, as in Latin-1.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+impl Encoding {
+    /// Decode a chunk of output to UTF-8. Single-byte encodings never
+    /// split a character across chunks; UTF-8 is decoded per chunk.
+    pub fn decode(self, bytes: &[u8]) -> Cow<'_, str> {
+        match self {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes),
+            _ if bytes.is_ascii() => String::from_utf8_lossy(bytes),
+            Encoding::Latin1 => Cow::Owned(bytes.iter().map(|&b| b as char).collect()),
+            Encoding::Windows1252 => Cow::Owned(
+                bytes
+                    .iter()
+                    .map(|&b| match b {
+                        0x80..=0x9F => WINDOWS_1252_HIGH[(b - 0x80) as usize],
+                        _ => b as char,
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf8_is_decoded_lossily() {
+        assert_eq!(Encoding::Utf8.decode("héllo".as_bytes()), "héllo");
+        assert_eq!(Encoding::Utf8.decode(b"caf\xe9"), "caf\u{FFFD}");
+    }
+
+    #[test]
+    fn ascii_is_borrowed() {
+        assert!(matches!(Encoding::Latin1.decode(b"\x1b[1mplain"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn latin1_maps_bytes_to_code_points() {
+        assert_eq!(Encoding::Latin1.decode(b"caf\xe9 \xa9"), "café ©");
+        assert_eq!(Encoding::Latin1.decode(b"\x80"), "\u{80}");
+    }
+
+    #[test]
+    fn windows_1252_maps_the_high_controls() {
+        assert_eq!(Encoding::Windows1252.decode(b"\x80 \x93q\x94 caf\xe9"), "€ “q” café");
+        assert_eq!(Encoding::Windows1252.decode(b"\x81"), "\u{81}");
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for (name, encoding) in [
+            ("utf-8", Encoding::Utf8),
+            ("latin-1", Encoding::Latin1),
+            ("windows-1252", Encoding::Windows1252),
+        ] {
+            assert_eq!(serde_json::to_value(encoding).unwrap(), name);
+            assert_eq!(serde_json::from_value::<Encoding>(name.into()).unwrap(), encoding);
+        }
+        let alias: Encoding = serde_json::from_value("iso-8859-1".into()).unwrap();
+        assert_eq!(alias, Encoding::Latin1);
+        assert!(serde_json::from_value::<Encoding>("shift-jis".into()).is_err());
+    }
+}
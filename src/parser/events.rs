@@ -1,8 +1,9 @@
 use serde::{Deserialize, Serialize};
 
-use super::state::{FormattedLine, ScreenResponse};
+use super::state::{FormattedLine, InputModes, KeyboardModes, ScreenResponse};
 
 #[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 #[serde(tag = "event", rename_all = "snake_case")]
 pub enum Event {
     Line {
@@ -20,11 +21,46 @@ pub enum Event {
     Mode {
         seq: u64,
         alternate_active: bool,
+        keyboard: KeyboardModes,
+        input: InputModes,
     },
     Reset {
         seq: u64,
         reason: ResetReason,
     },
+    /// The program wrote to the clipboard with OSC 52.
+    Clipboard {
+        seq: u64,
+        selection: String,
+        text: String,
+    },
+    /// The program set the window title with OSC 0 or 2. `None` when it
+    /// cleared it.
+    Title {
+        seq: u64,
+        title: Option<String>,
+    },
+    /// The shell reported a new working directory with OSC 7.
+    Cwd {
+        seq: u64,
+        cwd: String,
+    },
+    /// The shell of a session in safe mode refused to run `program`, or
+    /// a redirection (`>`), in `command`.
+    CommandDenied {
+        seq: u64,
+        program: String,
+        command: String,
+    },
+    /// The parser task panicked and was started again. `restored` says
+    /// whether it resumed from its last recovery checkpoint or from a
+    /// cleared screen; `restarts` counts restarts over the session's life.
+    /// Followed by a `parser_restart` reset.
+    ParserRestarted {
+        seq: u64,
+        restarts: u64,
+        restored: bool,
+    },
     Sync {
         seq: u64,
         screen: ScreenResponse,
@@ -48,6 +84,7 @@ pub enum Event {
 }
 
 #[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum ResetReason {
     ClearScreen,
@@ -56,12 +93,16 @@ pub enum ResetReason {
     AlternateScreenEnter,
     AlternateScreenExit,
     Resize,
-    /// The parser task panicked and was restarted with fresh VT state.
-    /// Clients should re-query screen/scrollback as all prior state is lost.
+    /// The parser task panicked and was restarted from its last recovery
+    /// checkpoint, or with fresh VT state if it had none. Clients should
+    /// re-query screen/scrollback as recent output may be lost.
     ParserRestart,
+    /// The terminal state was replaced from a checkpoint.
+    Restore,
 }
 
 #[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct Subscribe {
     pub events: Vec<EventType>,
     #[serde(default = "default_interval")]
@@ -75,6 +116,7 @@ fn default_interval() -> u64 {
 }
 
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum EventType {
     Lines,
@@ -85,6 +127,10 @@ pub enum EventType {
     Input,
     Overlay,
     Activity,
+    Clipboard,
+    Title,
+    Cwd,
+    CommandDenied,
 }
 
 #[cfg(test)]
@@ -99,6 +145,8 @@ mod tests {
             generation: 7,
             screen: ScreenResponse {
                 epoch: 0,
+                generation: 0,
+                changed_rows: None,
                 first_line_index: 0,
                 total_lines: 100,
                 lines: vec![],
@@ -110,6 +158,9 @@ mod tests {
                 cols: 80,
                 rows: 24,
                 alternate_active: false,
+                keyboard: Default::default(),
+                images: vec![],
+                output_offset: 0,
             },
             scrollback_lines: 100,
         };
@@ -133,6 +184,19 @@ mod tests {
         assert_eq!(json["generation"], 3);
     }
 
+    #[test]
+    fn parser_restarted_event_serializes_correctly() {
+        let event = Event::ParserRestarted {
+            seq: 0,
+            restarts: 2,
+            restored: true,
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "parser_restarted");
+        assert_eq!(json["restarts"], 2);
+        assert_eq!(json["restored"], true);
+    }
+
     #[test]
     fn activity_event_type_deserializes() {
         let json = r#""activity""#;
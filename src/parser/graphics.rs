@@ -0,0 +1,722 @@
+//! Inline images: Sixel and the iTerm2 image protocol.
+//!
+//! Programs draw images with a Sixel DCS sequence (`ESC P Ps q <sixels>
+//! ST`) or with iTerm2's `OSC 1337 ; File=<args> : <base64> ST`. avt
+//! skips both, so on its own the grid would keep the cursor where the
+//! image started and later text would land on different rows than on the
+//! attached terminal. The parser picks the images out itself: it moves the
+//! cursor past each one as a graphical terminal does, and keeps the
+//! sequence so the image can be listed, downloaded and redrawn for clients
+//! that attach later.
+//!
+//! A terminal's cell size in pixels isn't known, so sizes in cells assume
+//! cells of [`CELL_WIDTH_PX`] by [`CELL_HEIGHT_PX`], the size screenshots
+//! draw them at.
+//!
+//! Images are positioned by line number, like hyperlinks (see
+//! [`super::Parser::mark`]), so they scroll with the text. An image is
+//! forgotten when its lines drop off the scrollback, when the screen it
+//! is on is erased or left, or when newer images push it out of the
+//! store's budget.
+
+use std::collections::VecDeque;
+
+use base64::Engine;
+use bytes::Bytes;
+use serde::Serialize;
+
+use super::links::Position;
+use super::osc::OscScanner;
+use super::{CELL_HEIGHT_PX, CELL_WIDTH_PX};
+
+/// Longest image sequence buffered; longer ones are skipped.
+const MAX_SEQUENCE: usize = 8 * 1024 * 1024;
+
+/// Most images kept, oldest dropped first.
+const MAX_IMAGES: usize = 64;
+
+/// Most bytes of image sequences kept, oldest dropped first.
+const MAX_STORED_BYTES: usize = 32 * 1024 * 1024;
+
+/// How an image was sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ImageProtocol {
+    /// A Sixel DCS sequence.
+    Sixel,
+    /// iTerm2's `OSC 1337 ; File=`.
+    Iterm2,
+}
+
+/// An image drawn in the terminal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct ImageInfo {
+    pub id: u64,
+    pub protocol: ImageProtocol,
+    /// Media type of the image's data, as served by
+    /// `GET /sessions/{name}/images/{id}`.
+    pub content_type: String,
+    /// File name the program gave, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Line number of the image's top row, counted like
+    /// [`super::Parser::mark`].
+    pub line: u64,
+    /// Column of the image's left edge.
+    pub col: usize,
+    /// Rows and columns of cells the image covers.
+    pub rows: usize,
+    pub cols: usize,
+    /// Size of the image in pixels, when it could be read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width_px: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height_px: Option<u32>,
+    /// Whether it was drawn on the alternate screen.
+    pub alternate: bool,
+}
+
+/// An image and its data: the decoded file for iTerm2 images, the Sixel
+/// sequence itself for Sixel.
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub info: ImageInfo,
+    pub data: Bytes,
+}
+
+/// Where an image sits on the screen, in a screen response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct ImagePlacement {
+    pub id: u64,
+    pub protocol: ImageProtocol,
+    /// Screen row of the image's top edge. Negative when its top has
+    /// scrolled off the screen.
+    pub row: i64,
+    pub col: usize,
+    pub rows: usize,
+    pub cols: usize,
+}
+
+/// An image sequence found in the output, not yet placed.
+#[derive(Debug, Clone)]
+pub(super) struct ScannedImage {
+    protocol: ImageProtocol,
+    content_type: &'static str,
+    name: Option<String>,
+    width_px: Option<u32>,
+    height_px: Option<u32>,
+    /// Size asked for by an iTerm2 image, per dimension.
+    width: Dimension,
+    height: Dimension,
+    preserve_aspect_ratio: bool,
+    sequence: Bytes,
+}
+
+/// A size from iTerm2's `width` and `height` arguments.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Dimension {
+    Auto,
+    Cells(usize),
+    Pixels(u32),
+    Percent(u32),
+}
+
+impl ScannedImage {
+    /// Rows and columns the image covers on a terminal of `cols` by
+    /// `rows`. Images wider than the terminal are scaled down to fit, as
+    /// terminals do.
+    pub(super) fn cells(&self, cols: usize, rows: usize) -> (usize, usize) {
+        let cells = |dim: Dimension, px: Option<u32>, cell_px: u32, of: usize| match dim {
+            Dimension::Cells(n) => Some(n),
+            Dimension::Pixels(n) => Some(n.div_ceil(cell_px) as usize),
+            Dimension::Percent(p) => Some(of * p as usize / 100),
+            Dimension::Auto => px.map(|px| px.div_ceil(cell_px) as usize),
+        };
+        let mut width = cells(self.width, self.width_px, CELL_WIDTH_PX as u32, cols);
+        let mut height = cells(self.height, self.height_px, CELL_HEIGHT_PX as u32, rows);
+        // One dimension given: scale the other to keep the aspect ratio.
+        if let (Some(w_px), Some(h_px)) = (self.width_px, self.height_px) {
+            if self.preserve_aspect_ratio && w_px > 0 && h_px > 0 {
+                let aspect = (h_px as f64 / CELL_HEIGHT_PX as f64) / (w_px as f64 / CELL_WIDTH_PX as f64);
+                match (self.width, self.height, width, height) {
+                    (Dimension::Auto, d, _, Some(h)) if d != Dimension::Auto => {
+                        width = Some((h as f64 / aspect).ceil() as usize);
+                    }
+                    (d, Dimension::Auto, Some(w), _) if d != Dimension::Auto => {
+                        height = Some((w as f64 * aspect).ceil() as usize);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let mut width = width.unwrap_or(1).max(1);
+        let mut height = height.unwrap_or(1).max(1);
+        if width > cols && cols > 0 {
+            if self.preserve_aspect_ratio {
+                height = (height * cols).div_ceil(width).max(1);
+            }
+            width = cols;
+        }
+        height = height.min(rows.max(1) * 4);
+        (height, width)
+    }
+
+    /// Output that moves the cursor past an image of `rows` by `cols`
+    /// cells, as a graphical terminal does after drawing it: below the
+    /// image for Sixel, just right of its last row for iTerm2.
+    pub(super) fn cursor_motion(&self, rows: usize, cols: usize) -> String {
+        match self.protocol {
+            ImageProtocol::Sixel => "\n".repeat(rows),
+            ImageProtocol::Iterm2 => format!("{}\x1b[{}C", "\n".repeat(rows - 1), cols),
+        }
+    }
+}
+
+/// Scanner for Sixel and iTerm2 image sequences. Carries partial
+/// sequences across chunk boundaries.
+pub(super) struct ImageScanner {
+    osc: OscScanner,
+    dcs: DcsScanner,
+}
+
+impl ImageScanner {
+    pub(super) fn new() -> Self {
+        Self {
+            osc: OscScanner::new(&["1337;File="], MAX_SEQUENCE),
+            dcs: DcsScanner::new(),
+        }
+    }
+
+    /// Feed a chunk of output. Returns each image completed in it with the
+    /// byte offset just past its sequence, in order.
+    pub(super) fn feed(&mut self, text: &str) -> Vec<(usize, ScannedImage)> {
+        let mut found: Vec<_> = self
+            .osc
+            .feed(text)
+            .into_iter()
+            .filter_map(|(offset, body)| Some((offset, parse_iterm2(&body)?)))
+            .collect();
+        found.extend(
+            self.dcs
+                .feed(text)
+                .into_iter()
+                .filter_map(|(offset, body)| Some((offset, parse_sixel(&body)?))),
+        );
+        found.sort_by_key(|(offset, _)| *offset);
+        found
+    }
+}
+
+#[derive(Clone, Copy)]
+enum DcsState {
+    Ground,
+    Esc,
+    Dcs,
+    DcsEsc,
+}
+
+/// Scanner for Sixel DCS bodies (everything between `ESC P` and `ST`).
+struct DcsScanner {
+    state: DcsState,
+    buf: Vec<u8>,
+    /// The DCS being read isn't Sixel or is too long.
+    skip: bool,
+}
+
+impl DcsScanner {
+    fn new() -> Self {
+        Self {
+            state: DcsState::Ground,
+            buf: Vec::new(),
+            skip: false,
+        }
+    }
+
+    fn feed(&mut self, text: &str) -> Vec<(usize, Vec<u8>)> {
+        let mut found = Vec::new();
+        for (i, &byte) in text.as_bytes().iter().enumerate() {
+            self.state = match self.state {
+                DcsState::Ground | DcsState::Esc if byte == 0x1b => DcsState::Esc,
+                DcsState::Ground => DcsState::Ground,
+                DcsState::Esc if byte == b'P' => {
+                    self.buf.clear();
+                    self.skip = false;
+                    DcsState::Dcs
+                }
+                DcsState::Esc => DcsState::Ground,
+                DcsState::Dcs if byte == 0x1b => DcsState::DcsEsc,
+                DcsState::Dcs => {
+                    if !self.skip {
+                        self.buf.push(byte);
+                        self.skip = self.buf.len() > MAX_SEQUENCE || !is_sixel_prefix(&self.buf);
+                        if self.skip {
+                            self.buf = Vec::new();
+                        }
+                    }
+                    DcsState::Dcs
+                }
+                DcsState::DcsEsc if byte == b'\\' => {
+                    let body = std::mem::take(&mut self.buf);
+                    if !self.skip && body.contains(&b'q') {
+                        found.push((i + 1, body));
+                    }
+                    DcsState::Ground
+                }
+                // An ESC that doesn't end the string starts a new sequence.
+                DcsState::DcsEsc if byte == b'P' => {
+                    self.buf.clear();
+                    self.skip = false;
+                    DcsState::Dcs
+                }
+                DcsState::DcsEsc => {
+                    self.buf.clear();
+                    DcsState::Ground
+                }
+            };
+        }
+        found
+    }
+}
+
+/// Whether a DCS body read so far may be Sixel: parameters, then `q`.
+fn is_sixel_prefix(body: &[u8]) -> bool {
+    match body.iter().position(|&b| !(b.is_ascii_digit() || b == b';')) {
+        None => true,
+        Some(i) => body[i] == b'q',
+    }
+}
+
+fn parse_sixel(body: &[u8]) -> Option<ScannedImage> {
+    let q = body.iter().position(|&b| b == b'q')?;
+    let data = &body[q + 1..];
+    let (width_px, height_px) = sixel_size(data);
+    let mut sequence = Vec::with_capacity(body.len() + 4);
+    sequence.extend_from_slice(b"\x1bP");
+    sequence.extend_from_slice(body);
+    sequence.extend_from_slice(b"\x1b\\");
+    Some(ScannedImage {
+        protocol: ImageProtocol::Sixel,
+        content_type: "image/x-sixel",
+        name: None,
+        width_px: Some(width_px),
+        height_px: Some(height_px),
+        width: Dimension::Auto,
+        height: Dimension::Auto,
+        preserve_aspect_ratio: false,
+        sequence: Bytes::from(sequence),
+    })
+}
+
+/// Size in pixels of Sixel data: from its raster attributes
+/// (`" Pan ; Pad ; Ph ; Pv`) if it has them, otherwise from the sixels
+/// themselves.
+fn sixel_size(data: &[u8]) -> (u32, u32) {
+    if let Some(raster) = data.strip_prefix(b"\"") {
+        let end = raster
+            .iter()
+            .position(|&b| !(b.is_ascii_digit() || b == b';'))
+            .unwrap_or(raster.len());
+        let params: Vec<u32> = std::str::from_utf8(&raster[..end])
+            .unwrap_or_default()
+            .split(';')
+            .map(|p| p.parse().unwrap_or(0))
+            .collect();
+        if let [_, _, width, height] = params[..] {
+            if width > 0 && height > 0 {
+                return (width, height);
+            }
+        }
+    }
+    let (mut width, mut x, mut bands) = (0u32, 0u32, 1u32);
+    let mut i = 0;
+    while i < data.len() {
+        match data[i] {
+            // Repeat: `! Pn <sixel>`.
+            b'!' => {
+                let digits = data[i + 1..].iter().take_while(|b| b.is_ascii_digit()).count();
+                let count: u32 = std::str::from_utf8(&data[i + 1..i + 1 + digits])
+                    .ok()
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or(1);
+                x += count;
+                i += digits + 1;
+            }
+            // Color introducer and raster attributes: skip their parameters.
+            b'#' | b'"' => {
+                i += data[i + 1..]
+                    .iter()
+                    .take_while(|b| b.is_ascii_digit() || **b == b';')
+                    .count();
+            }
+            b'$' => x = 0,
+            b'-' => {
+                x = 0;
+                bands += 1;
+            }
+            0x3f..=0x7e => x += 1,
+            _ => {}
+        }
+        width = width.max(x);
+        i += 1;
+    }
+    // A trailing graphics new line doesn't start a band of its own.
+    if data.ends_with(b"-") {
+        bands -= 1;
+    }
+    (width, bands.max(1) * 6)
+}
+
+fn parse_iterm2(body: &[u8]) -> Option<ScannedImage> {
+    let rest = std::str::from_utf8(body.strip_prefix(b"1337;File=")?).ok()?;
+    let (args, data) = rest.split_once(':')?;
+    let mut name = None;
+    let mut inline = false;
+    let mut width = Dimension::Auto;
+    let mut height = Dimension::Auto;
+    let mut preserve_aspect_ratio = true;
+    for arg in args.split(';') {
+        let Some((key, value)) = arg.split_once('=') else { continue };
+        match key {
+            "name" => {
+                name = base64::engine::general_purpose::STANDARD
+                    .decode(value)
+                    .ok()
+                    .map(|n| String::from_utf8_lossy(&n).into_owned());
+            }
+            "inline" => inline = value == "1",
+            "width" => width = dimension(value),
+            "height" => height = dimension(value),
+            "preserveAspectRatio" => preserve_aspect_ratio = value != "0",
+            _ => {}
+        }
+    }
+    // Without inline=1 the file is a download, not shown.
+    if !inline {
+        return None;
+    }
+    let file = base64::engine::general_purpose::STANDARD.decode(data.trim()).ok()?;
+    let (content_type, size) = sniff(&file);
+    let mut sequence = Vec::with_capacity(body.len() + 4);
+    sequence.extend_from_slice(b"\x1b]");
+    sequence.extend_from_slice(body);
+    sequence.extend_from_slice(b"\x1b\\");
+    Some(ScannedImage {
+        protocol: ImageProtocol::Iterm2,
+        content_type,
+        name,
+        width_px: size.map(|(w, _)| w),
+        height_px: size.map(|(_, h)| h),
+        width,
+        height,
+        preserve_aspect_ratio,
+        sequence: Bytes::from(sequence),
+    })
+}
+
+fn dimension(value: &str) -> Dimension {
+    let number = |s: &str| s.parse::<u32>().ok();
+    if value == "auto" {
+        Dimension::Auto
+    } else if let Some(px) = value.strip_suffix("px").and_then(number) {
+        Dimension::Pixels(px)
+    } else if let Some(percent) = value.strip_suffix('%').and_then(number) {
+        Dimension::Percent(percent.min(100))
+    } else {
+        number(value).map_or(Dimension::Auto, |n| Dimension::Cells(n as usize))
+    }
+}
+
+/// Media type and pixel size of an image file, from its header.
+fn sniff(file: &[u8]) -> (&'static str, Option<(u32, u32)>) {
+    let be32 = |b: &[u8]| u32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+    if file.starts_with(b"\x89PNG\r\n\x1a\n") && file.len() >= 24 {
+        return ("image/png", Some((be32(&file[16..20]), be32(&file[20..24]))));
+    }
+    if file.starts_with(b"GIF8") && file.len() >= 10 {
+        let w = u16::from_le_bytes([file[6], file[7]]) as u32;
+        let h = u16::from_le_bytes([file[8], file[9]]) as u32;
+        return ("image/gif", Some((w, h)));
+    }
+    if file.starts_with(b"\xff\xd8") {
+        return ("image/jpeg", jpeg_size(file));
+    }
+    ("application/octet-stream", None)
+}
+
+/// Pixel size from a JPEG's start-of-frame segment.
+fn jpeg_size(file: &[u8]) -> Option<(u32, u32)> {
+    let mut i = 2;
+    while i + 9 < file.len() {
+        if file[i] != 0xff {
+            return None;
+        }
+        let marker = file[i + 1];
+        let len = u16::from_be_bytes([file[i + 2], file[i + 3]]) as usize;
+        if matches!(marker, 0xc0..=0xcf) && !matches!(marker, 0xc4 | 0xc8 | 0xcc) {
+            let h = u16::from_be_bytes([file[i + 5], file[i + 6]]) as u32;
+            let w = u16::from_be_bytes([file[i + 7], file[i + 8]]) as u32;
+            return Some((w, h));
+        }
+        i += 2 + len;
+    }
+    None
+}
+
+struct Stored {
+    info: ImageInfo,
+    sequence: Bytes,
+}
+
+/// Images drawn in a session, oldest first.
+#[derive(Default)]
+pub struct ImageStore {
+    images: VecDeque<Stored>,
+    bytes: usize,
+    next_id: u64,
+}
+
+impl ImageStore {
+    /// Record an image drawn at `at`, covering `rows` by `cols` cells.
+    pub(super) fn insert(
+        &mut self,
+        image: ScannedImage,
+        at: Position,
+        (rows, cols): (usize, usize),
+        alternate: bool,
+    ) -> u64 {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.bytes += image.sequence.len();
+        self.images.push_back(Stored {
+            info: ImageInfo {
+                id,
+                protocol: image.protocol,
+                content_type: image.content_type.to_string(),
+                name: image.name,
+                line: at.line,
+                col: at.col,
+                rows,
+                cols,
+                width_px: image.width_px,
+                height_px: image.height_px,
+                alternate,
+            },
+            sequence: image.sequence,
+        });
+        while self.images.len() > MAX_IMAGES || self.bytes > MAX_STORED_BYTES {
+            let Some(old) = self.images.pop_front() else { break };
+            self.bytes -= old.sequence.len();
+        }
+        id
+    }
+
+    fn retain(&mut self, mut keep: impl FnMut(&ImageInfo) -> bool) {
+        let mut bytes = self.bytes;
+        self.images.retain(|image| {
+            let kept = keep(&image.info);
+            if !kept {
+                bytes -= image.sequence.len();
+            }
+            kept
+        });
+        self.bytes = bytes;
+    }
+
+    /// Forget every image, as when the terminal is replaced.
+    pub(super) fn clear(&mut self) {
+        self.images.clear();
+        self.bytes = 0;
+    }
+
+    /// Forget images whose lines have all dropped off the scrollback.
+    pub(super) fn forget_dropped(&mut self, dropped: u64) {
+        self.retain(|image| image.line + image.rows as u64 > dropped);
+    }
+
+    /// Forget images on the screen starting at line `first_line`, which
+    /// was erased, or above it too if the scrollback was.
+    pub(super) fn erase(&mut self, first_line: u64, alternate: bool, scrollback: bool) {
+        self.retain(|image| {
+            image.alternate != alternate || (!scrollback && image.line + image.rows as u64 <= first_line)
+        });
+    }
+
+    /// Forget images drawn on the alternate screen, which was left.
+    pub(super) fn leave_alternate(&mut self) {
+        self.retain(|image| !image.alternate);
+    }
+
+    /// Every image kept, oldest first.
+    pub fn list(&self) -> Vec<ImageInfo> {
+        self.images.iter().map(|image| image.info.clone()).collect()
+    }
+
+    /// The image with `id` and its data.
+    pub fn get(&self, id: u64) -> Option<Image> {
+        let image = self.images.iter().find(|image| image.info.id == id)?;
+        let data = match image.info.protocol {
+            ImageProtocol::Sixel => image.sequence.clone(),
+            ImageProtocol::Iterm2 => {
+                // The body is `1337;File=<args>:<base64>` between `ESC ]` and ST.
+                let body = &image.sequence[2..image.sequence.len() - 2];
+                let colon = body.iter().position(|&b| b == b':')?;
+                let decoded = base64::engine::general_purpose::STANDARD
+                    .decode(body[colon + 1..].trim_ascii())
+                    .ok()?;
+                Bytes::from(decoded)
+            }
+        };
+        Some(Image {
+            info: image.info.clone(),
+            data,
+        })
+    }
+
+    /// Images showing on a screen of `rows` rows whose top row is line
+    /// `first_line`.
+    pub(super) fn placements(&self, first_line: u64, rows: usize, alternate: bool) -> Vec<ImagePlacement> {
+        self.images
+            .iter()
+            .map(|image| &image.info)
+            .filter(|image| {
+                image.alternate == alternate
+                    && image.line + (image.rows as u64) > first_line
+                    && image.line < first_line + rows as u64
+            })
+            .map(|image| ImagePlacement {
+                id: image.id,
+                protocol: image.protocol,
+                row: image.line as i64 - first_line as i64,
+                col: image.col,
+                rows: image.rows,
+                cols: image.cols,
+            })
+            .collect()
+    }
+
+    /// Output that draws the `placements` that fit on the screen whole,
+    /// each at its place. Images partly scrolled off are left out, since
+    /// drawing one would scroll the screen.
+    pub fn redraw(&self, placements: &[ImagePlacement], screen_rows: usize) -> String {
+        let mut out = String::new();
+        for placement in placements {
+            if placement.row < 0 || placement.row as usize + placement.rows > screen_rows {
+                continue;
+            }
+            let Some(image) = self.images.iter().find(|image| image.info.id == placement.id) else {
+                continue;
+            };
+            out.push_str(&format!("\x1b[{};{}H", placement.row + 1, placement.col + 1));
+            // Sequences are cut from decoded output, so they are UTF-8.
+            out.push_str(&String::from_utf8_lossy(&image.sequence));
+        }
+        out
+    }
+}
+
+/// End offsets of the sequences in `text` that erase the screen (ED 2,
+/// ED 3 and RIS), and whether each also erases the scrollback.
+pub(super) fn erases(text: &str) -> Vec<(usize, bool)> {
+    let mut found = Vec::new();
+    for (pattern, scrollback) in [("\x1b[2J", false), ("\x1b[3J", true), ("\x1bc", true)] {
+        found.extend(text.match_indices(pattern).map(|(i, _)| (i + pattern.len(), scrollback)));
+    }
+    found.sort();
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG_2X1: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x28\0\0\0\x14";
+
+    fn iterm2(args: &str, file: &[u8]) -> String {
+        let data = base64::engine::general_purpose::STANDARD.encode(file);
+        format!("\x1b]1337;File={}:{}\x07", args, data)
+    }
+
+    #[test]
+    fn scanner_finds_sixel_and_iterm2_across_chunks() {
+        let mut s = ImageScanner::new();
+        let sixel = "\x1bP0;1q\"1;1;20;12#0~~~~-~~\x1b\\";
+        assert!(s.feed(&format!("a{}", &sixel[..9])).is_empty());
+        let found = s.feed(&format!("{}c", &sixel[9..]));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, sixel.len() - 9);
+        assert_eq!(found[0].1.protocol, ImageProtocol::Sixel);
+        assert_eq!((found[0].1.width_px, found[0].1.height_px), (Some(20), Some(12)));
+        assert_eq!(&found[0].1.sequence[..], sixel.as_bytes());
+
+        let found = s.feed(&iterm2("name=aS5wbmc=;inline=1", PNG_2X1));
+        assert_eq!(found.len(), 1);
+        let image = &found[0].1;
+        assert_eq!(image.protocol, ImageProtocol::Iterm2);
+        assert_eq!(image.content_type, "image/png");
+        assert_eq!(image.name.as_deref(), Some("i.png"));
+        assert_eq!((image.width_px, image.height_px), (Some(40), Some(20)));
+    }
+
+    #[test]
+    fn scanner_ignores_downloads_and_other_sequences() {
+        let mut s = ImageScanner::new();
+        assert!(s.feed(&iterm2("name=eA==", PNG_2X1)).is_empty());
+        assert!(s.feed("\x1bP1$r0m\x1b\\\x1b]0;title\x07\x1b]1337;SetMark\x07").is_empty());
+    }
+
+    #[test]
+    fn sixel_size_without_raster_attributes() {
+        // Two bands; the first is 3 + 4 pixels wide.
+        assert_eq!(sixel_size(b"#0~~~!4~$#1~-~~"), (7, 12));
+        assert_eq!(sixel_size(b"~~-"), (2, 6));
+    }
+
+    #[test]
+    fn cells_follow_size_arguments() {
+        let mut s = ImageScanner::new();
+        let cells = |s: &mut ImageScanner, args: &str| s.feed(&iterm2(args, PNG_2X1))[0].1.cells(80, 24);
+        // 40x20 pixels is 5x2 cells.
+        assert_eq!(cells(&mut s, "inline=1"), (2, 5));
+        assert_eq!(cells(&mut s, "inline=1;width=10;height=3"), (3, 10));
+        assert_eq!(cells(&mut s, "inline=1;width=50%;height=100px"), (7, 40));
+        // Height follows width to keep the aspect ratio.
+        assert_eq!(cells(&mut s, "inline=1;width=20"), (5, 20));
+        // Too wide for the terminal: scaled down to fit.
+        assert_eq!(cells(&mut s, "inline=1;width=160;height=8"), (4, 80));
+    }
+
+    #[test]
+    fn store_keeps_placements_until_dropped_or_erased() {
+        let mut s = ImageScanner::new();
+        let image = s.feed(&iterm2("inline=1", PNG_2X1)).remove(0).1;
+        let mut store = ImageStore::default();
+        let id = store.insert(image, Position { line: 10, col: 2 }, (3, 4), false);
+        assert_eq!(store.get(id).unwrap().data, PNG_2X1);
+
+        let on_screen = store.placements(11, 5, false);
+        assert_eq!(on_screen.len(), 1);
+        assert_eq!((on_screen[0].row, on_screen[0].col), (-1, 2));
+        assert!(store.placements(11, 5, true).is_empty());
+        assert!(store.placements(13, 5, false).is_empty());
+
+        // Partly scrolled off, so it isn't redrawn.
+        assert!(store.redraw(&on_screen, 5).is_empty());
+        let redraw = store.redraw(&store.placements(10, 5, false), 5);
+        assert!(redraw.starts_with("\x1b[1;3H\x1b]1337;File=inline=1:"));
+
+        store.forget_dropped(12);
+        assert_eq!(store.list().len(), 1);
+        store.erase(20, false, false);
+        assert_eq!(store.list().len(), 1);
+        store.erase(11, false, false);
+        assert!(store.list().is_empty());
+    }
+
+    #[test]
+    fn erases_are_found_in_order() {
+        assert_eq!(erases("a\x1b[3J\x1b[H\x1b[2Jb"), vec![(5, true), (12, false)]);
+    }
+}
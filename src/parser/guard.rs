@@ -0,0 +1,76 @@
+//! Commands refused by the safe mode guard.
+//!
+//! The bash guard of a session in safe mode (see [`crate::safe_mode`])
+//! writes `OSC 7771 ; program ; command BEL` before its error line when it
+//! skips a command. The parser reports each as a `command_denied` event.
+//! Like any OSC, the sequence can also be printed by a program in the
+//! session, so the events are a record for clients, not an audit trail.
+
+use super::osc::OscScanner;
+
+/// Longest sequence we buffer before giving up on it.
+const MAX_OSC: usize = 4096;
+
+/// A command the guard refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Denied {
+    /// The program refused, or `>` for a redirection.
+    pub program: String,
+    /// The command it appeared in, as the shell saw it.
+    pub command: String,
+}
+
+/// Scanner for OSC 7771. Carries partial sequences across chunk boundaries.
+pub(super) struct GuardScanner {
+    osc: OscScanner,
+}
+
+impl GuardScanner {
+    pub(super) fn new() -> Self {
+        Self {
+            osc: OscScanner::new(&["7771;"], MAX_OSC),
+        }
+    }
+
+    /// Feed a chunk of output. Returns the refusals completed in it,
+    /// oldest first.
+    pub(super) fn feed(&mut self, text: &str) -> Vec<Denied> {
+        self.osc
+            .feed(text)
+            .into_iter()
+            .filter_map(|(_, body)| parse(&body))
+            .collect()
+    }
+}
+
+fn parse(body: &[u8]) -> Option<Denied> {
+    let body = String::from_utf8_lossy(body.strip_prefix(b"7771;")?);
+    let (program, command) = body.split_once(';')?;
+    if program.is_empty() {
+        return None;
+    }
+    let clean = |s: &str| s.chars().filter(|c| !c.is_control()).collect::<String>();
+    Some(Denied {
+        program: clean(program),
+        command: clean(command),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scanner_finds_refusals_across_chunks() {
+        let mut s = GuardScanner::new();
+        assert!(s.feed("$ \x1b]7771;rm;rm -rf /tmp/x; echo a").is_empty());
+        assert_eq!(
+            s.feed(";b\x07wsh: rm: not allowed in safe mode\r\n"),
+            vec![Denied {
+                program: "rm".to_string(),
+                command: "rm -rf /tmp/x; echo a;b".to_string(),
+            }]
+        );
+        assert!(s.feed("\x1b]7771;\x07\x1b]7771;;ls\x07\x1b]777;rm;x\x07").is_empty());
+    }
+}
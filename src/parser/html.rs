@@ -0,0 +1,147 @@
+//! Convert parser state types (Span, Style, Color) to HTML.
+//!
+//! Used by the scrollback export to produce a standalone transcript that
+//! keeps the terminal's colors and text attributes.
+
+use std::fmt::Write;
+
+use super::palette::{self, DEFAULT_BG, DEFAULT_FG};
+use super::state::{Color, FormattedLine, Span, Style};
+
+/// Opening of a standalone HTML document, up to and including the `<pre>`
+/// that holds the lines. `title` is escaped.
+pub fn document_start(title: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>body{{margin:0;background:{bg};color:{fg}}}\
+         pre{{margin:0;padding:1em;font-family:ui-monospace,Menlo,Consolas,monospace;line-height:1.2}}</style>\n\
+         </head>\n<body>\n<pre>",
+        escape(title),
+        bg = palette::hex(DEFAULT_BG),
+        fg = palette::hex(DEFAULT_FG),
+    )
+}
+
+/// Closing of the document opened by [`document_start`].
+pub const DOCUMENT_END: &str = "</pre>\n</body>\n</html>\n";
+
+/// Convert a `FormattedLine` to HTML, without a trailing newline.
+pub fn line_to_html(line: &FormattedLine) -> String {
+    match line {
+        FormattedLine::Plain(text) => escape(text),
+        FormattedLine::Styled(spans) => spans_to_html(spans),
+    }
+}
+
+fn spans_to_html(spans: &[Span]) -> String {
+    let mut out = String::new();
+    for span in spans {
+        let css = style_to_css(&span.style);
+        if css.is_empty() {
+            out.push_str(&escape(&span.text));
+        } else {
+            let _ = write!(out, "<span style=\"{}\">{}</span>", css, escape(&span.text));
+        }
+    }
+    out
+}
+
+fn style_to_css(style: &Style) -> String {
+    let mut fg = style.fg.as_ref().map(color_to_css);
+    let mut bg = style.bg.as_ref().map(color_to_css);
+    if style.inverse {
+        let old_fg = fg.take().unwrap_or_else(|| palette::hex(DEFAULT_FG));
+        fg = Some(bg.take().unwrap_or_else(|| palette::hex(DEFAULT_BG)));
+        bg = Some(old_fg);
+    }
+
+    let mut css = Vec::new();
+    if let Some(fg) = fg {
+        css.push(format!("color:{}", fg));
+    }
+    if let Some(bg) = bg {
+        css.push(format!("background:{}", bg));
+    }
+    if style.bold {
+        css.push("font-weight:bold".to_string());
+    }
+    if style.faint {
+        css.push("opacity:0.6".to_string());
+    }
+    if style.italic {
+        css.push("font-style:italic".to_string());
+    }
+    let decorations: Vec<&str> = [
+        (style.underline, "underline"),
+        (style.strikethrough, "line-through"),
+    ]
+    .into_iter()
+    .filter_map(|(on, name)| on.then_some(name))
+    .collect();
+    if !decorations.is_empty() {
+        css.push(format!("text-decoration:{}", decorations.join(" ")));
+    }
+    css.join(";")
+}
+
+fn color_to_css(color: &Color) -> String {
+    palette::hex(palette::rgb(color))
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_text_is_escaped() {
+        let line = FormattedLine::Plain("a <b> & \"c\"".to_string());
+        assert_eq!(line_to_html(&line), "a &lt;b&gt; &amp; &quot;c&quot;");
+    }
+
+    #[test]
+    fn styled_spans_render_css() {
+        let line = FormattedLine::Styled(vec![
+            Span {
+                text: "ok ".to_string(),
+                style: Style::default(),
+            },
+            Span {
+                text: "FAIL".to_string(),
+                style: Style {
+                    fg: Some(Color::Indexed(1)),
+                    bold: true,
+                    underline: true,
+                    ..Default::default()
+                },
+            },
+        ]);
+        assert_eq!(
+            line_to_html(&line),
+            "ok <span style=\"color:#cd0000;font-weight:bold;text-decoration:underline\">FAIL</span>"
+        );
+    }
+
+    #[test]
+    fn inverse_swaps_colors() {
+        let style = Style {
+            bg: Some(Color::Rgb { r: 1, g: 2, b: 3 }),
+            inverse: true,
+            ..Default::default()
+        };
+        assert_eq!(style_to_css(&style), "color:#010203;background:#d4d4d4");
+    }
+}
@@ -0,0 +1,234 @@
+//! Render a screen snapshot to PNG.
+//!
+//! Glyphs come from an embedded 8x8 bitmap font with each row drawn twice,
+//! giving 8x16 cells. Characters the font lacks are drawn as a hollow box.
+
+use std::io::Write;
+
+use font8x8::UnicodeFonts;
+use unicode_width::UnicodeWidthChar;
+
+use super::palette::{self, DEFAULT_BG, DEFAULT_FG};
+use super::state::{FormattedLine, ScreenResponse, Style};
+use super::{CELL_HEIGHT_PX, CELL_WIDTH_PX};
+
+/// Drawn for characters the font has no glyph for.
+const MISSING_GLYPH: [u8; 8] = [0x00, 0x7e, 0x42, 0x42, 0x42, 0x42, 0x7e, 0x00];
+
+#[derive(Clone, Copy)]
+struct Cell {
+    glyph: [u8; 8],
+    fg: [u8; 3],
+    bg: [u8; 3],
+    bold: bool,
+    underline: bool,
+    strikethrough: bool,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            glyph: [0; 8],
+            fg: DEFAULT_FG,
+            bg: DEFAULT_BG,
+            bold: false,
+            underline: false,
+            strikethrough: false,
+        }
+    }
+}
+
+/// Encode the screen as an RGB PNG of `cols * CELL_WIDTH_PX` by
+/// `rows * CELL_HEIGHT_PX` pixels. Styled lines keep their colors and
+/// attributes; a visible cursor is drawn as a block.
+pub fn screen_to_png(screen: &ScreenResponse) -> Result<Vec<u8>, png::EncodingError> {
+    let cols = screen.cols.max(1);
+    let rows = screen.rows.max(1);
+    let width = cols * CELL_WIDTH_PX;
+
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, width as u32, (rows * CELL_HEIGHT_PX) as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    let mut stream = writer.stream_writer()?;
+
+    // Encode one text row at a time so a large screen never needs the
+    // whole bitmap in memory.
+    let mut pixels = vec![0u8; width * 3];
+    for row in 0..rows {
+        let mut cells = screen.lines.get(row).map(|l| line_cells(l, cols)).unwrap_or_default();
+        cells.resize(cols, Cell::default());
+        if screen.cursor.visible && screen.cursor.row == row && screen.cursor.col < cols {
+            let cell = &mut cells[screen.cursor.col];
+            std::mem::swap(&mut cell.fg, &mut cell.bg);
+        }
+        for y in 0..CELL_HEIGHT_PX {
+            for (col, cell) in cells.iter().enumerate() {
+                let bits = cell.glyph[y / 2];
+                for x in 0..CELL_WIDTH_PX {
+                    let mut on = bits & (1 << x) != 0;
+                    if cell.bold && x > 0 {
+                        on |= bits & (1 << (x - 1)) != 0;
+                    }
+                    on |= cell.underline && y == CELL_HEIGHT_PX - 2;
+                    on |= cell.strikethrough && y == CELL_HEIGHT_PX / 2;
+                    let i = (col * CELL_WIDTH_PX + x) * 3;
+                    pixels[i..i + 3].copy_from_slice(if on { &cell.fg } else { &cell.bg });
+                }
+            }
+            stream.write_all(&pixels)?;
+        }
+    }
+    stream.finish()?;
+    drop(writer);
+    Ok(out)
+}
+
+/// Lay a line out into at most `cols` cells. Wide characters take two
+/// cells, the second drawn as blank background.
+fn line_cells(line: &FormattedLine, cols: usize) -> Vec<Cell> {
+    let default = Style::default();
+    let spans: Vec<(&str, &Style)> = match line {
+        FormattedLine::Plain(text) => vec![(text.as_str(), &default)],
+        FormattedLine::Styled(spans) => spans.iter().map(|s| (s.text.as_str(), &s.style)).collect(),
+    };
+
+    let mut cells = Vec::with_capacity(cols);
+    for (text, style) in spans {
+        let cell = style_cell(style);
+        for ch in text.chars() {
+            let width = ch.width().unwrap_or(0);
+            if width == 0 {
+                continue;
+            }
+            if cells.len() >= cols {
+                return cells;
+            }
+            cells.push(Cell {
+                glyph: glyph(ch),
+                ..cell
+            });
+            if width == 2 && cells.len() < cols {
+                cells.push(Cell {
+                    underline: false,
+                    strikethrough: false,
+                    ..cell
+                });
+            }
+        }
+    }
+    cells
+}
+
+fn style_cell(style: &Style) -> Cell {
+    let mut fg = style.fg.as_ref().map_or(DEFAULT_FG, palette::rgb);
+    let mut bg = style.bg.as_ref().map_or(DEFAULT_BG, palette::rgb);
+    if style.inverse {
+        std::mem::swap(&mut fg, &mut bg);
+    }
+    if style.faint {
+        fg = [0, 1, 2].map(|i| ((fg[i] as u16 + bg[i] as u16) / 2) as u8);
+    }
+    Cell {
+        glyph: [0; 8],
+        fg,
+        bg,
+        bold: style.bold,
+        underline: style.underline,
+        strikethrough: style.strikethrough,
+    }
+}
+
+fn glyph(ch: char) -> [u8; 8] {
+    font8x8::BASIC_FONTS
+        .get(ch)
+        .or_else(|| font8x8::LATIN_FONTS.get(ch))
+        .or_else(|| font8x8::BOX_FONTS.get(ch))
+        .or_else(|| font8x8::BLOCK_FONTS.get(ch))
+        .or_else(|| font8x8::GREEK_FONTS.get(ch))
+        .or_else(|| font8x8::MISC_FONTS.get(ch))
+        .or_else(|| font8x8::HIRAGANA_FONTS.get(ch))
+        .unwrap_or(MISSING_GLYPH)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::state::{Color, Cursor, KeyboardModes, Span};
+
+    fn screen(lines: Vec<FormattedLine>, cursor: Cursor) -> ScreenResponse {
+        ScreenResponse {
+            epoch: 0,
+            generation: 0,
+            changed_rows: None,
+            first_line_index: 0,
+            total_lines: lines.len(),
+            lines,
+            cursor,
+            cols: 4,
+            rows: 2,
+            alternate_active: false,
+            keyboard: KeyboardModes::default(),
+            images: vec![],
+            output_offset: 0,
+        }
+    }
+
+    fn decode(data: &[u8]) -> (png::OutputInfo, Vec<u8>) {
+        let mut reader = png::Decoder::new(data).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        (info, buf)
+    }
+
+    fn pixel(buf: &[u8], width: usize, x: usize, y: usize) -> [u8; 3] {
+        let i = (y * width + x) * 3;
+        [buf[i], buf[i + 1], buf[i + 2]]
+    }
+
+    #[test]
+    fn renders_colors_and_cursor() {
+        let line = FormattedLine::Styled(vec![Span {
+            text: " ".to_string(),
+            style: Style {
+                bg: Some(Color::Indexed(1)),
+                ..Default::default()
+            },
+        }]);
+        let cursor = Cursor {
+            row: 1,
+            col: 2,
+            visible: true,
+        };
+        let (info, buf) = decode(&screen_to_png(&screen(vec![line], cursor)).unwrap());
+        assert_eq!((info.width, info.height), (32, 32));
+        let width = info.width as usize;
+        // Red background in the first cell, default background after it.
+        assert_eq!(pixel(&buf, width, 0, 0), [0xcd, 0x00, 0x00]);
+        assert_eq!(pixel(&buf, width, CELL_WIDTH_PX, 0), DEFAULT_BG);
+        // The cursor cell is inverted.
+        assert_eq!(pixel(&buf, width, 2 * CELL_WIDTH_PX, CELL_HEIGHT_PX), DEFAULT_FG);
+    }
+
+    #[test]
+    fn draws_glyphs_and_clips_to_width() {
+        let line = FormattedLine::Plain("||||||".to_string());
+        let cursor = Cursor {
+            row: 0,
+            col: 0,
+            visible: false,
+        };
+        let (info, buf) = decode(&screen_to_png(&screen(vec![line], cursor)).unwrap());
+        let width = info.width as usize;
+        // Some pixel of the bar glyph in the last column is lit.
+        let lit = (0..CELL_WIDTH_PX).any(|x| pixel(&buf, width, 3 * CELL_WIDTH_PX + x, 4) == DEFAULT_FG);
+        assert!(lit);
+    }
+
+    #[test]
+    fn unknown_characters_get_a_box() {
+        assert_eq!(glyph('\u{1F600}'), MISSING_GLYPH);
+        assert_ne!(glyph('A'), MISSING_GLYPH);
+    }
+}
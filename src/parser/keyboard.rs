@@ -0,0 +1,362 @@
+//! Tracking of the keyboard modes an application has requested.
+//!
+//! Applications choose how they want keys encoded: DECCKM switches the
+//! arrow keys to SS3 (`ESC O A`) form, DECKPAM/DECNKM switch the numeric
+//! keypad to application sequences, and the kitty keyboard protocol
+//! replaces ambiguous legacy encodings with `CSI ... u`, as xterm's
+//! modifyOtherKeys does with `CSI 27 ; ... ~`. They likewise turn on
+//! bracketed paste, mouse reporting and focus events. The parser watches
+//! for these sequences so that key-sending APIs and clients can encode
+//! input the way the running application expects.
+
+use super::state::{InputModes, KeyboardModes, MouseEncoding, MouseTracking};
+
+/// Maximum depth of the kitty keyboard flags stack. Pushing beyond this
+/// evicts the oldest entry, as the protocol specifies.
+const KITTY_STACK_LIMIT: usize = 16;
+
+/// Longest CSI parameter string we buffer before giving up on a sequence.
+const MAX_PARAMS: usize = 32;
+
+#[derive(Clone, Copy)]
+enum ScanState {
+    Ground,
+    Esc,
+    Csi,
+}
+
+/// Stateful scanner for keyboard and input mode sequences.
+///
+/// Like the alternate screen detector, this carries partial sequences
+/// across chunk boundaries.
+pub(super) struct KeyboardModeTracker {
+    scan: ScanState,
+    /// Private marker and parameter bytes of the CSI sequence being read.
+    params: Vec<u8>,
+    application_cursor: bool,
+    application_keypad: bool,
+    kitty_flags: u32,
+    kitty_stack: Vec<u32>,
+    modify_other_keys: u8,
+    input: InputModes,
+}
+
+impl KeyboardModeTracker {
+    pub(super) fn new() -> Self {
+        Self {
+            scan: ScanState::Ground,
+            params: Vec::new(),
+            application_cursor: false,
+            application_keypad: false,
+            kitty_flags: 0,
+            kitty_stack: Vec::new(),
+            modify_other_keys: 0,
+            input: InputModes::default(),
+        }
+    }
+
+    pub(super) fn modes(&self) -> KeyboardModes {
+        KeyboardModes {
+            application_cursor: self.application_cursor,
+            application_keypad: self.application_keypad,
+            kitty_flags: self.kitty_flags,
+            modify_other_keys: self.modify_other_keys,
+        }
+    }
+
+    pub(super) fn input(&self) -> InputModes {
+        self.input
+    }
+
+    /// Start over from `modes` and `input`, as restored from a checkpoint.
+    /// The kitty flags stack isn't part of a checkpoint, so it starts out
+    /// empty.
+    pub(super) fn restore(&mut self, modes: KeyboardModes, input: InputModes) {
+        *self = Self::new();
+        self.application_cursor = modes.application_cursor;
+        self.application_keypad = modes.application_keypad;
+        self.kitty_flags = modes.kitty_flags;
+        self.modify_other_keys = modes.modify_other_keys;
+        self.input = input;
+    }
+
+    /// Feed a chunk of output. Returns true if any mode changed.
+    pub(super) fn feed(&mut self, text: &str) -> bool {
+        let before = (self.modes(), self.input);
+        for &byte in text.as_bytes() {
+            self.scan = match self.scan {
+                ScanState::Ground => {
+                    if byte == 0x1b {
+                        ScanState::Esc
+                    } else {
+                        ScanState::Ground
+                    }
+                }
+                ScanState::Esc => match byte {
+                    b'[' => {
+                        self.params.clear();
+                        ScanState::Csi
+                    }
+                    b'=' => {
+                        self.application_keypad = true;
+                        ScanState::Ground
+                    }
+                    b'>' => {
+                        self.application_keypad = false;
+                        ScanState::Ground
+                    }
+                    b'c' => {
+                        self.reset();
+                        ScanState::Ground
+                    }
+                    0x1b => ScanState::Esc,
+                    _ => ScanState::Ground,
+                },
+                ScanState::Csi => match byte {
+                    0x20..=0x3f if self.params.len() < MAX_PARAMS => {
+                        self.params.push(byte);
+                        ScanState::Csi
+                    }
+                    0x40..=0x7e => {
+                        self.dispatch_csi(byte);
+                        ScanState::Ground
+                    }
+                    0x1b => ScanState::Esc,
+                    _ => ScanState::Ground,
+                },
+            };
+        }
+        (self.modes(), self.input) != before
+    }
+
+    fn reset(&mut self) {
+        self.application_cursor = false;
+        self.application_keypad = false;
+        self.kitty_flags = 0;
+        self.kitty_stack.clear();
+        self.modify_other_keys = 0;
+        self.input = InputModes::default();
+    }
+
+    fn set_mouse_tracking(&mut self, tracking: MouseTracking, enable: bool) {
+        if enable {
+            self.input.mouse_tracking = tracking;
+        } else if self.input.mouse_tracking == tracking {
+            self.input.mouse_tracking = MouseTracking::Off;
+        }
+    }
+
+    fn set_mouse_encoding(&mut self, encoding: MouseEncoding, enable: bool) {
+        if enable {
+            self.input.mouse_encoding = encoding;
+        } else if self.input.mouse_encoding == encoding {
+            self.input.mouse_encoding = MouseEncoding::Default;
+        }
+    }
+
+    fn dispatch_csi(&mut self, final_byte: u8) {
+        let (marker, rest) = match self.params.first() {
+            Some(&m @ (b'?' | b'>' | b'<' | b'=' | b'!')) => (Some(m), &self.params[1..]),
+            _ => (None, &self.params[..]),
+        };
+        let Ok(rest) = std::str::from_utf8(rest) else {
+            return;
+        };
+        let numbers: Vec<Option<u32>> = rest
+            .split(';')
+            .map(|p| if p.is_empty() { None } else { p.parse().ok() })
+            .collect();
+        let arg = |i: usize, default: u32| numbers.get(i).copied().flatten().unwrap_or(default);
+
+        match (marker, final_byte) {
+            // DECSET / DECRST
+            (Some(b'?'), b'h' | b'l') => {
+                let enable = final_byte == b'h';
+                for mode in numbers.iter().flatten() {
+                    match mode {
+                        1 => self.application_cursor = enable,
+                        66 => self.application_keypad = enable,
+                        9 => self.set_mouse_tracking(MouseTracking::X10, enable),
+                        1000 => self.set_mouse_tracking(MouseTracking::Normal, enable),
+                        1002 => self.set_mouse_tracking(MouseTracking::Button, enable),
+                        1003 => self.set_mouse_tracking(MouseTracking::Any, enable),
+                        1004 => self.input.focus_events = enable,
+                        1005 => self.set_mouse_encoding(MouseEncoding::Utf8, enable),
+                        1006 => self.set_mouse_encoding(MouseEncoding::Sgr, enable),
+                        1015 => self.set_mouse_encoding(MouseEncoding::Urxvt, enable),
+                        1016 => self.set_mouse_encoding(MouseEncoding::SgrPixels, enable),
+                        2004 => self.input.bracketed_paste = enable,
+                        _ => {}
+                    }
+                }
+            }
+            // xterm: set modifyOtherKeys (resource 4) to a level, or reset
+            // it when the level is left out
+            (Some(b'>'), b'm') if arg(0, 0) == 4 => {
+                self.modify_other_keys = arg(1, 0).min(2) as u8;
+            }
+            // xterm: reset modifyOtherKeys
+            (Some(b'>'), b'n') if arg(0, 0) == 4 => self.modify_other_keys = 0,
+            // DECSTR soft reset
+            (Some(b'!'), b'p') => {
+                self.application_cursor = false;
+                self.application_keypad = false;
+            }
+            // Kitty: push flags
+            (Some(b'>'), b'u') => {
+                if self.kitty_stack.len() == KITTY_STACK_LIMIT {
+                    self.kitty_stack.remove(0);
+                }
+                self.kitty_stack.push(self.kitty_flags);
+                self.kitty_flags = arg(0, 0);
+            }
+            // Kitty: pop flags
+            (Some(b'<'), b'u') => {
+                for _ in 0..arg(0, 1).max(1) {
+                    match self.kitty_stack.pop() {
+                        Some(flags) => self.kitty_flags = flags,
+                        None => {
+                            self.kitty_flags = 0;
+                            break;
+                        }
+                    }
+                }
+            }
+            // Kitty: set flags (1 = replace, 2 = add, 3 = remove)
+            (Some(b'='), b'u') => {
+                let flags = arg(0, 0);
+                match arg(1, 1) {
+                    1 => self.kitty_flags = flags,
+                    2 => self.kitty_flags |= flags,
+                    3 => self.kitty_flags &= !flags,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyboardModeTracker, MouseEncoding, MouseTracking};
+
+    #[test]
+    fn decckm_toggles_application_cursor() {
+        let mut t = KeyboardModeTracker::new();
+        assert!(t.feed("\x1b[?1h"));
+        assert!(t.modes().application_cursor);
+        assert!(t.feed("\x1b[?1l"));
+        assert!(!t.modes().application_cursor);
+    }
+
+    #[test]
+    fn keypad_modes() {
+        let mut t = KeyboardModeTracker::new();
+        t.feed("\x1b=");
+        assert!(t.modes().application_keypad);
+        t.feed("\x1b>");
+        assert!(!t.modes().application_keypad);
+        t.feed("\x1b[?66h");
+        assert!(t.modes().application_keypad);
+    }
+
+    #[test]
+    fn combined_dec_modes_and_unrelated_output() {
+        let mut t = KeyboardModeTracker::new();
+        assert!(!t.feed("hello \x1b[1;31mred\x1b[0m \x1b[?25h"));
+        t.feed("\x1b[?1049;1h");
+        assert!(t.modes().application_cursor);
+    }
+
+    #[test]
+    fn kitty_push_pop_and_set() {
+        let mut t = KeyboardModeTracker::new();
+        t.feed("\x1b[>1u");
+        assert_eq!(t.modes().kitty_flags, 1);
+        t.feed("\x1b[>9u");
+        assert_eq!(t.modes().kitty_flags, 9);
+        t.feed("\x1b[=2;2u");
+        assert_eq!(t.modes().kitty_flags, 11);
+        t.feed("\x1b[=8;3u");
+        assert_eq!(t.modes().kitty_flags, 3);
+        t.feed("\x1b[<u");
+        assert_eq!(t.modes().kitty_flags, 1);
+        t.feed("\x1b[<5u");
+        assert_eq!(t.modes().kitty_flags, 0);
+    }
+
+    #[test]
+    fn kitty_query_is_ignored() {
+        let mut t = KeyboardModeTracker::new();
+        assert!(!t.feed("\x1b[?u"));
+    }
+
+    #[test]
+    fn sequences_split_across_chunks() {
+        let mut t = KeyboardModeTracker::new();
+        assert!(!t.feed("abc\x1b"));
+        assert!(!t.feed("[?"));
+        assert!(t.feed("1h"));
+        assert!(t.modes().application_cursor);
+        t.feed("\x1b[>");
+        t.feed("1");
+        t.feed("u");
+        assert_eq!(t.modes().kitty_flags, 1);
+    }
+
+    #[test]
+    fn modify_other_keys_levels() {
+        let mut t = KeyboardModeTracker::new();
+        assert!(t.feed("\x1b[>4;2m"));
+        assert_eq!(t.modes().modify_other_keys, 2);
+        t.feed("\x1b[>4;1m");
+        assert_eq!(t.modes().modify_other_keys, 1);
+        t.feed("\x1b[>4m");
+        assert_eq!(t.modes().modify_other_keys, 0);
+        t.feed("\x1b[>4;2m\x1b[>4n");
+        assert_eq!(t.modes().modify_other_keys, 0);
+        // Other resources are left alone
+        assert!(!t.feed("\x1b[>1;2m"));
+    }
+
+    #[test]
+    fn bracketed_paste_and_focus_events() {
+        let mut t = KeyboardModeTracker::new();
+        assert!(t.feed("\x1b[?2004h"));
+        assert!(t.input().bracketed_paste);
+        t.feed("\x1b[?1004h");
+        assert!(t.input().focus_events);
+        t.feed("\x1b[?2004;1004l");
+        assert!(!t.input().bracketed_paste);
+        assert!(!t.input().focus_events);
+    }
+
+    #[test]
+    fn mouse_tracking_and_encoding() {
+        let mut t = KeyboardModeTracker::new();
+        t.feed("\x1b[?1000h\x1b[?1006h");
+        assert_eq!(t.input().mouse_tracking, MouseTracking::Normal);
+        assert_eq!(t.input().mouse_encoding, MouseEncoding::Sgr);
+        t.feed("\x1b[?1003h");
+        assert_eq!(t.input().mouse_tracking, MouseTracking::Any);
+        // Resetting a mode that isn't the active one changes nothing
+        assert!(!t.feed("\x1b[?1000l"));
+        t.feed("\x1b[?1003l\x1b[?1006l");
+        assert_eq!(t.input().mouse_tracking, MouseTracking::Off);
+        assert_eq!(t.input().mouse_encoding, MouseEncoding::Default);
+    }
+
+    #[test]
+    fn hard_reset_clears_everything() {
+        let mut t = KeyboardModeTracker::new();
+        t.feed("\x1b[?1h\x1b=\x1b[>1u\x1b[>4;2m\x1b[?2004;1002h");
+        t.feed("\x1bc");
+        let modes = t.modes();
+        assert!(!modes.application_cursor);
+        assert!(!modes.application_keypad);
+        assert_eq!(modes.kitty_flags, 0);
+        assert_eq!(modes.modify_other_keys, 0);
+        assert_eq!(t.input(), Default::default());
+    }
+}
@@ -0,0 +1,453 @@
+//! Links in terminal output.
+//!
+//! Programs mark hyperlinks with OSC 8 (`OSC 8 ; params ; URI ST`, then
+//! the link text, then `OSC 8 ; ; ST`); the terminal shows the text and
+//! opens the URI when it is clicked. The parser records where each
+//! hyperlink's text was printed. Plain URLs that were printed as text are
+//! found by scanning the lines when links are asked for.
+//!
+//! Positions are kept as line numbers that don't change as old lines drop
+//! off the scrollback (see [`super::Parser::mark`]). The text a hyperlink
+//! covered is kept too, and a hyperlink whose text has since been
+//! overwritten or cleared is no longer reported.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::LazyLock;
+
+use regex_automata::meta::Regex;
+use serde::Serialize;
+
+use super::osc::OscScanner;
+
+/// Links returned when the caller doesn't say.
+pub const DEFAULT_MAX_LINKS: usize = 200;
+
+/// Most links a single request returns.
+pub const MAX_LINKS: usize = 1_000;
+
+/// Hyperlinks remembered, oldest dropped first.
+const MAX_HYPERLINKS: usize = 1024;
+
+/// Longest URI we buffer; longer ones are ignored, as other terminals do.
+const MAX_URI: usize = 2048;
+
+/// URLs printed as plain text. Trailing punctuation is trimmed after
+/// matching.
+static URL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\b(?:https?|ftp|file)://[^\s<>"'`]+"#).expect("URL pattern compiles")
+});
+
+/// A link found in the output.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct Link {
+    pub url: String,
+    pub source: LinkSource,
+    /// Where the link appears, in scrollback order.
+    pub locations: Vec<LinkLocation>,
+}
+
+/// How a link got into the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum LinkSource {
+    /// An OSC 8 hyperlink. Its text may differ from the URL.
+    Hyperlink,
+    /// A URL printed as text.
+    Text,
+}
+
+/// One place a link appears. A hyperlink that wraps onto the next line has
+/// a location on each line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct LinkLocation {
+    /// Index of the line in the scrollback, as used by `/scrollback`'s
+    /// `offset`.
+    pub line: usize,
+    /// Character offsets of the link's text within the line.
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct LinksResponse {
+    /// Lines in the scrollback, including the screen.
+    pub total_lines: usize,
+    /// Scrollback index of the screen's first row.
+    pub first_line_index: usize,
+    /// One entry per distinct URL, in order of first appearance.
+    pub links: Vec<Link>,
+    /// Whether links past the requested limit were left out.
+    pub truncated: bool,
+}
+
+/// An OSC 8 sequence: the start of a hyperlink, or the end of one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum LinkMark {
+    Open(String),
+    Close,
+}
+
+/// Scanner for OSC 8 sequences. Carries partial sequences across chunk
+/// boundaries.
+pub(super) struct LinkScanner {
+    osc: OscScanner,
+}
+
+impl LinkScanner {
+    pub(super) fn new() -> Self {
+        Self {
+            osc: OscScanner::new(&["8;"], MAX_URI),
+        }
+    }
+
+    /// Feed a chunk of output. Returns each OSC 8 sequence completed in it
+    /// with the byte offset just past its end, so the caller can tell
+    /// where in the chunk the link starts or ends.
+    pub(super) fn feed(&mut self, text: &str) -> Vec<(usize, LinkMark)> {
+        self.osc
+            .feed(text)
+            .into_iter()
+            .filter_map(|(offset, body)| Some((offset, parse(&body)?)))
+            .collect()
+    }
+}
+
+fn parse(body: &[u8]) -> Option<LinkMark> {
+    let (_params, uri) = std::str::from_utf8(body.strip_prefix(b"8;")?)
+        .ok()?
+        .split_once(';')?;
+    Some(if uri.is_empty() {
+        LinkMark::Close
+    } else {
+        LinkMark::Open(uri.to_string())
+    })
+}
+
+/// A place in the output: a line number as counted by
+/// [`super::Parser::mark`] and a column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Position {
+    pub(super) line: u64,
+    pub(super) col: usize,
+}
+
+struct Hyperlink {
+    url: String,
+    start: Position,
+    end: Position,
+    /// The text covered when the hyperlink ended.
+    text: String,
+}
+
+/// The hyperlinks printed so far.
+pub(super) struct Hyperlinks {
+    open: Option<(String, Position)>,
+    spans: VecDeque<Hyperlink>,
+}
+
+impl Hyperlinks {
+    pub(super) fn new() -> Self {
+        Self {
+            open: None,
+            spans: VecDeque::new(),
+        }
+    }
+
+    /// Forget every hyperlink, as when the terminal is replaced.
+    pub(super) fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Apply an OSC 8 sequence seen with the cursor at `at`. Opening a
+    /// hyperlink ends the one before it.
+    pub(super) fn mark(&mut self, mark: LinkMark, at: Position, vt: &avt::Vt, dropped: u64) {
+        if let Some((url, start)) = self.open.take() {
+            let text: String = segments(vt, dropped, start, at)
+                .map(|(_, _, _, text)| text)
+                .collect();
+            if !text.trim().is_empty() {
+                if self.spans.len() == MAX_HYPERLINKS {
+                    self.spans.pop_front();
+                }
+                self.spans.push_back(Hyperlink {
+                    url,
+                    start,
+                    end: at,
+                    text,
+                });
+            }
+        }
+        if let LinkMark::Open(url) = mark {
+            self.open = Some((url, at));
+        }
+    }
+
+    /// The links in the terminal, hyperlinks and plain URLs, deduplicated by
+    /// URL. At most `limit` are returned.
+    pub(super) fn collect(&self, vt: &avt::Vt, dropped: u64, limit: usize) -> LinksResponse {
+        let (_, rows) = vt.size();
+        let lines: Vec<&avt::Line> = vt.lines().collect();
+        let mut found: Vec<(usize, usize, String, LinkSource, LinkLocation)> = Vec::new();
+
+        for span in &self.spans {
+            let segments: Vec<_> = segments(vt, dropped, span.start, span.end).collect();
+            let text: String = segments
+                .iter()
+                .map(|(_, _, _, text)| text.as_str())
+                .collect();
+            if segments.is_empty() || text != span.text {
+                continue;
+            }
+            for (line, start, end, text) in segments {
+                found.push((
+                    line,
+                    start,
+                    span.url.clone(),
+                    LinkSource::Hyperlink,
+                    LinkLocation {
+                        line,
+                        start,
+                        end,
+                        text,
+                    },
+                ));
+            }
+        }
+        for (index, line) in lines.iter().enumerate() {
+            let text = line.text();
+            for m in URL.find_iter(&text) {
+                let url = trim_url(&text[m.start()..m.end()]);
+                let start = text[..m.start()].chars().count();
+                let location = LinkLocation {
+                    line: index,
+                    start,
+                    end: start + url.chars().count(),
+                    text: url.to_string(),
+                };
+                found.push((index, start, url.to_string(), LinkSource::Text, location));
+            }
+        }
+        found.sort_by_key(|(line, start, ..)| (*line, *start));
+
+        let mut links: Vec<Link> = Vec::new();
+        let mut by_url: HashMap<String, usize> = HashMap::new();
+        let mut truncated = false;
+        for (_, _, url, source, location) in found {
+            let index = match by_url.get(&url) {
+                Some(&index) => index,
+                None if links.len() == limit => {
+                    truncated = true;
+                    continue;
+                }
+                None => {
+                    by_url.insert(url.clone(), links.len());
+                    links.push(Link {
+                        url,
+                        source,
+                        locations: Vec::new(),
+                    });
+                    links.len() - 1
+                }
+            };
+            let link = &mut links[index];
+            if source == LinkSource::Hyperlink {
+                link.source = source;
+            }
+            // A hyperlink whose text is its URL is also found as text.
+            if !link.locations.contains(&location) {
+                link.locations.push(location);
+            }
+        }
+
+        LinksResponse {
+            total_lines: lines.len(),
+            first_line_index: lines.len().saturating_sub(rows),
+            links,
+            truncated,
+        }
+    }
+}
+
+/// The text between `start` and `end`, one piece per line: scrollback
+/// index, character offsets within the line, and the text. Empty if the
+/// lines are no longer in the terminal.
+fn segments(
+    vt: &avt::Vt,
+    dropped: u64,
+    start: Position,
+    end: Position,
+) -> impl Iterator<Item = (usize, usize, usize, String)> + '_ {
+    let first = start.line.checked_sub(dropped).map(|l| l as usize);
+    let last = end.line.checked_sub(dropped).map(|l| l as usize);
+    let range = match (first, last) {
+        (Some(first), Some(last)) if first <= last => first..last + 1,
+        _ => 0..0,
+    };
+    vt.lines()
+        .enumerate()
+        .skip(range.start)
+        .take(range.len())
+        .filter_map(move |(index, line)| {
+            let from = if index == range.start { start.col } else { 0 };
+            let to = if index + 1 == range.end {
+                end.col
+            } else {
+                usize::MAX
+            };
+            let mut chars = 0;
+            let mut first_char = None;
+            let mut text = String::new();
+            for (col, cell) in line.cells().iter().enumerate() {
+                if cell.width() == 0 {
+                    continue;
+                }
+                if col >= from && col < to {
+                    first_char.get_or_insert(chars);
+                    text.push(cell.char());
+                }
+                chars += 1;
+            }
+            let start = first_char?;
+            Some((index, start, start + text.chars().count(), text))
+        })
+}
+
+/// Drop punctuation that ends the sentence around a URL rather than the
+/// URL, keeping closing brackets that have a match inside it.
+fn trim_url(url: &str) -> &str {
+    let mut url = url;
+    loop {
+        let Some(last) = url.chars().last() else {
+            return url;
+        };
+        let open = match last {
+            ')' => '(',
+            ']' => '[',
+            '}' => '{',
+            '.' | ',' | ';' | ':' | '!' | '?' => {
+                url = &url[..url.len() - 1];
+                continue;
+            }
+            _ => return url,
+        };
+        if url.matches(open).count() >= url.matches(last).count() {
+            return url;
+        }
+        url = &url[..url.len() - 1];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scanner_finds_osc8_across_chunks() {
+        let mut s = LinkScanner::new();
+        let text = "see \x1b]8;id=1;https://example.com\x1b\\docs\x1b]8;;\x07.";
+        let marks = s.feed(text);
+        assert_eq!(marks.len(), 2);
+        assert_eq!(marks[0].1, LinkMark::Open("https://example.com".into()));
+        assert_eq!(&text[marks[0].0..marks[0].0 + 4], "docs");
+        assert_eq!(marks[1].1, LinkMark::Close);
+        assert_eq!(&text[marks[1].0..], ".");
+
+        assert!(s.feed("\x1b]8;;https://ex").is_empty());
+        assert_eq!(
+            s.feed("ample.org\x07"),
+            vec![(10, LinkMark::Open("https://example.org".into()))]
+        );
+    }
+
+    #[test]
+    fn scanner_skips_other_osc() {
+        let mut s = LinkScanner::new();
+        assert!(s
+            .feed("\x1b]0;8;;title\x07\x1b]80;;x\x07\x1b]52;c;aGk=\x07")
+            .is_empty());
+    }
+
+    #[test]
+    fn trims_sentence_punctuation() {
+        assert_eq!(trim_url("https://a.io/x."), "https://a.io/x");
+        assert_eq!(trim_url("https://a.io/x),"), "https://a.io/x");
+        assert_eq!(
+            trim_url("https://en.wikipedia.org/wiki/Rust_(language)"),
+            "https://en.wikipedia.org/wiki/Rust_(language)"
+        );
+    }
+
+    fn vt_with(text: &str) -> (avt::Vt, Hyperlinks) {
+        let mut vt = avt::Vt::builder().size(30, 4).scrollback_limit(100).build();
+        let mut links = Hyperlinks::new();
+        let mut scanner = LinkScanner::new();
+        let mut start = 0;
+        for (offset, mark) in scanner.feed(text) {
+            vt.feed_str(&text[start..offset]);
+            start = offset;
+            let (_, rows) = vt.size();
+            let cursor = vt.cursor();
+            let line = (vt.lines().count() - rows + cursor.row) as u64;
+            links.mark(
+                mark,
+                Position {
+                    line,
+                    col: cursor.col,
+                },
+                &vt,
+                0,
+            );
+        }
+        vt.feed_str(&text[start..]);
+        (vt, links)
+    }
+
+    #[test]
+    fn collects_hyperlinks_and_text_urls() {
+        let (vt, links) = vt_with(
+            "CI: \x1b]8;;https://ci.example/run/7\x1b\\run 7\x1b]8;;\x1b\\ done\r\n\
+             see https://docs.rs/avt.\r\n\
+             again https://docs.rs/avt\r\n",
+        );
+        let response = links.collect(&vt, 0, 10);
+        assert_eq!(response.links.len(), 2);
+        let ci = &response.links[0];
+        assert_eq!(ci.url, "https://ci.example/run/7");
+        assert_eq!(ci.source, LinkSource::Hyperlink);
+        assert_eq!(
+            ci.locations,
+            vec![LinkLocation {
+                line: 0,
+                start: 4,
+                end: 9,
+                text: "run 7".into()
+            }]
+        );
+        let docs = &response.links[1];
+        assert_eq!(docs.source, LinkSource::Text);
+        assert_eq!(
+            docs.locations
+                .iter()
+                .map(|l| (l.line, l.start))
+                .collect::<Vec<_>>(),
+            vec![(1, 4), (2, 6)]
+        );
+
+        let response = links.collect(&vt, 0, 1);
+        assert_eq!(response.links.len(), 1);
+        assert!(response.truncated);
+    }
+
+    #[test]
+    fn overwritten_hyperlinks_are_dropped() {
+        let (mut vt, links) = vt_with("\x1b]8;;https://a.example\x1b\\click\x1b]8;;\x1b\\");
+        assert_eq!(links.collect(&vt, 0, 10).links.len(), 1);
+        vt.feed_str("\rxxxxx");
+        assert!(links.collect(&vt, 0, 10).links.is_empty());
+    }
+}
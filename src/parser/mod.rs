@@ -1,12 +1,25 @@
 pub mod ansi;
+pub mod clipboard;
+pub mod encoding;
 pub mod events;
 pub mod format;
+pub mod graphics;
+pub mod guard;
+pub mod html;
+pub mod image;
+pub mod links;
+pub mod palette;
+pub mod queries;
 pub mod state;
+pub mod title;
 
+mod dirty;
+mod keyboard;
+mod osc;
 mod task;
 
 use std::panic::AssertUnwindSafe;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use futures::FutureExt;
@@ -17,8 +30,17 @@ use tokio_stream::{Stream, StreamExt};
 
 use bytes::Bytes;
 
+use encoding::Encoding;
 use events::Event;
-use state::{Query, QueryResponse};
+use state::{
+    Checkpoint, KeyboardModes, OutputResponse, Query, QueryPolicy, QueryResponse, ScreenResponse, TerminalModes,
+};
+
+/// Width of a terminal cell in pixels. Screenshots draw cells this size,
+/// and image sizes given in pixels are turned into cells with it.
+pub const CELL_WIDTH_PX: usize = 8;
+/// Height of a terminal cell in pixels; see [`CELL_WIDTH_PX`].
+pub const CELL_HEIGHT_PX: usize = 16;
 
 /// Wrapper for parser subscription events that includes lag notifications.
 #[derive(Debug, Clone)]
@@ -42,12 +64,21 @@ pub enum ParserError {
 
     #[error("invalid query parameters: {0}")]
     InvalidQuery(String),
+
+    #[error("failed to write hibernation snapshot: {0}")]
+    Hibernate(String),
 }
 
 #[derive(Clone)]
 pub struct Parser {
     query_tx: mpsc::Sender<(Query, oneshot::Sender<QueryResponse>)>,
     event_tx: broadcast::Sender<Event>,
+    responder: Arc<parking_lot::Mutex<Option<queries::Responder>>>,
+    encoding: Arc<parking_lot::RwLock<Encoding>>,
+    clipboard: Arc<parking_lot::Mutex<Option<clipboard::Clipboard>>>,
+    title: Arc<parking_lot::RwLock<title::TitleState>>,
+    images: Arc<parking_lot::Mutex<graphics::ImageStore>>,
+    lines_dropped: Arc<AtomicU64>,
 }
 
 impl Parser {
@@ -70,11 +101,32 @@ impl Parser {
         // from session creation time.
         let current_cols = Arc::new(AtomicUsize::new(cols));
         let current_rows = Arc::new(AtomicUsize::new(rows));
+        let current_scrollback = Arc::new(AtomicUsize::new(scrollback_limit));
         let task_cols = current_cols.clone();
         let task_rows = current_rows.clone();
+        let task_scrollback = current_scrollback.clone();
+        let responder = Arc::new(parking_lot::Mutex::new(None));
+        let task_responder = Arc::clone(&responder);
+        let encoding = Arc::new(parking_lot::RwLock::new(Encoding::default()));
+        let task_encoding = Arc::clone(&encoding);
+        let clipboard = Arc::new(parking_lot::Mutex::new(None));
+        let task_clipboard = Arc::clone(&clipboard);
+        let title = Arc::new(parking_lot::RwLock::new(title::TitleState::default()));
+        let task_title = Arc::clone(&title);
+        let images = Arc::new(parking_lot::Mutex::new(graphics::ImageStore::default()));
+        let task_images = Arc::clone(&images);
+        let lines_dropped = Arc::new(AtomicU64::new(0));
+        let task_lines_dropped = Arc::clone(&lines_dropped);
+        // Last screen generation, which a restarted task continues from.
+        let screen_generation = AtomicU64::new(0);
+        // Bytes of output taken from `raw_rx`, kept across restarts.
+        let output_offset = AtomicU64::new(0);
+        // Latest state saved by the task, which a restarted task resumes from.
+        let recovery = parking_lot::Mutex::new(None);
 
         tokio::spawn(async move {
             let mut query_rx = query_rx;
+            let mut restarts = 0u64;
             // On first iteration use the initial dimensions; on restart
             // read the latest values from the shared atomics.
             let mut first = true;
@@ -94,9 +146,19 @@ impl Parser {
                     event_tx_clone.clone(),
                     c,
                     r,
-                    scrollback_limit,
+                    task_scrollback.load(Ordering::Acquire),
                     &task_cols,
                     &task_rows,
+                    &task_scrollback,
+                    &task_lines_dropped,
+                    &screen_generation,
+                    &output_offset,
+                    &task_responder,
+                    &task_encoding,
+                    &task_clipboard,
+                    &task_title,
+                    &task_images,
+                    &recovery,
                 ))
                 .catch_unwind()
                 .await;
@@ -107,11 +169,24 @@ impl Parser {
                         break;
                     }
                     Err(e) => {
-                        tracing::error!("parser task panicked, restarting with fresh state: {:?}", e);
-                        // Emit a reset event so clients know to re-query state.
-                        // The VT state is lost, but the channels survive across
-                        // the panic boundary because they're owned by this outer
-                        // scope, not by the panicking task::run function.
+                        restarts += 1;
+                        let restored = recovery.lock().is_some();
+                        tracing::error!(
+                            restored,
+                            restarts,
+                            "parser task panicked, restarting from last checkpoint: {:?}",
+                            e
+                        );
+                        // Tell clients about the restart, then emit a reset so
+                        // they know to re-query state. Output since the last
+                        // checkpoint is lost, but the channels survive across
+                        // the panic boundary because they're owned by this
+                        // outer scope, not by the panicking task::run function.
+                        let _ = event_tx_clone.send(events::Event::ParserRestarted {
+                            seq: 0,
+                            restarts,
+                            restored,
+                        });
                         let _ = event_tx_clone.send(events::Event::Reset {
                             seq: 0,
                             reason: events::ResetReason::ParserRestart,
@@ -124,6 +199,12 @@ impl Parser {
         Self {
             query_tx,
             event_tx,
+            responder,
+            encoding,
+            clipboard,
+            title,
+            images,
+            lines_dropped,
         }
     }
 
@@ -150,6 +231,158 @@ impl Parser {
         Ok(())
     }
 
+    /// Write the terminal state to `path` and drop it from memory. It is
+    /// read back transparently; see [`Query::Hibernate`].
+    pub async fn hibernate(&self, path: std::path::PathBuf) -> Result<(), ParserError> {
+        match self.query(Query::Hibernate { path }).await? {
+            QueryResponse::Ok => Ok(()),
+            QueryResponse::Failed(e) => Err(ParserError::Hibernate(e)),
+            _ => Err(ParserError::InvalidQuery("unexpected hibernate query response".into())),
+        }
+    }
+
+    /// Change how many lines of scrollback the terminal keeps, dropping the
+    /// oldest lines if there are more than `lines`.
+    pub async fn set_scrollback_limit(&self, lines: usize) -> Result<(), ParserError> {
+        self.query(Query::SetScrollbackLimit { lines }).await?;
+        Ok(())
+    }
+
+    /// Capture the full emulator state; see [`Checkpoint`].
+    pub async fn checkpoint(&self) -> Result<Checkpoint, ParserError> {
+        match self.query(Query::Checkpoint).await? {
+            QueryResponse::Checkpoint(checkpoint) => Ok(checkpoint),
+            _ => Err(ParserError::InvalidQuery("unexpected checkpoint query response".into())),
+        }
+    }
+
+    /// Replace the emulator state with `checkpoint`. Subscribers get a
+    /// reset with reason `restore`.
+    pub async fn restore(&self, checkpoint: Checkpoint) -> Result<(), ParserError> {
+        match self.query(Query::Restore { checkpoint }).await? {
+            QueryResponse::Ok => Ok(()),
+            QueryResponse::Failed(e) => Err(ParserError::InvalidQuery(e)),
+            _ => Err(ParserError::InvalidQuery("unexpected restore query response".into())),
+        }
+    }
+
+    /// Answer the terminal queries `policy` marks synthetic by writing the
+    /// replies to `reply_tx`, which should feed the PTY. The policy is read
+    /// on every chunk of output, so later changes to it apply right away.
+    pub fn answer_queries(
+        &self,
+        policy: Arc<parking_lot::RwLock<QueryPolicy>>,
+        reply_tx: mpsc::WeakSender<Bytes>,
+    ) {
+        *self.responder.lock() = Some(queries::Responder { policy, reply_tx });
+    }
+
+    /// Decode output from `encoding` from the next chunk on. Only what
+    /// the parser sees is decoded; attached terminals get the raw bytes.
+    pub fn set_encoding(&self, encoding: Encoding) {
+        *self.encoding.write() = encoding;
+    }
+
+    /// Encoding the parser decodes output from.
+    pub fn encoding(&self) -> Encoding {
+        *self.encoding.read()
+    }
+
+    /// The last clipboard write, by the program with OSC 52 or through
+    /// [`set_clipboard`](Self::set_clipboard).
+    pub fn clipboard(&self) -> Option<clipboard::Clipboard> {
+        self.clipboard.lock().clone()
+    }
+
+    /// Replace the clipboard contents, as when an API client answers a
+    /// program's OSC 52 query.
+    pub fn set_clipboard(&self, clipboard: clipboard::Clipboard) {
+        *self.clipboard.lock() = Some(clipboard);
+    }
+
+    /// Window title the program last set with OSC 0 or 2, if any.
+    pub fn title(&self) -> Option<String> {
+        self.title.read().title.clone()
+    }
+
+    /// Working directory the shell last reported with OSC 7, if any.
+    pub fn cwd(&self) -> Option<String> {
+        self.title.read().cwd.clone()
+    }
+
+    /// Inline images kept from the output, oldest first. See [`graphics`].
+    pub fn images(&self) -> Vec<graphics::ImageInfo> {
+        self.images.lock().list()
+    }
+
+    /// The inline image with `id` and its data, if it is still kept.
+    pub fn image(&self, id: u64) -> Option<graphics::Image> {
+        self.images.lock().get(id)
+    }
+
+    /// Output that draws the images on `screen` over a replay of its
+    /// lines, for a client that didn't see them drawn. Moves the cursor.
+    pub fn redraw_images(&self, screen: &ScreenResponse) -> String {
+        if screen.images.is_empty() {
+            return String::new();
+        }
+        self.images.lock().redraw(&screen.images, screen.rows)
+    }
+
+    /// Keyboard modes the application has requested, for encoding keys.
+    pub async fn keyboard_modes(&self) -> Result<KeyboardModes, ParserError> {
+        match self.query(Query::Keyboard).await? {
+            QueryResponse::Keyboard(modes) => Ok(modes),
+            _ => Err(ParserError::InvalidQuery("unexpected keyboard query response".into())),
+        }
+    }
+
+    /// Keyboard, paste, mouse and focus modes the application has
+    /// negotiated, and whether the alternate screen is active.
+    pub async fn modes(&self) -> Result<TerminalModes, ParserError> {
+        match self.query(Query::Modes).await? {
+            QueryResponse::Modes(modes) => Ok(modes),
+            _ => Err(ParserError::InvalidQuery("unexpected modes query response".into())),
+        }
+    }
+
+    /// Lines dropped off the top of the scrollback since the parser
+    /// started, by the scrollback limit or by shrinking it. Scrollback
+    /// offsets shift down by this much as old lines go; [`mark`](Self::mark)
+    /// numbers lines from before the first one, so they don't.
+    pub fn lines_dropped(&self) -> u64 {
+        self.lines_dropped.load(Ordering::Acquire)
+    }
+
+    /// Line number of the cursor's line, counted from the first line the
+    /// session ever printed. Subtract [`lines_dropped`](Self::lines_dropped)
+    /// to get its current scrollback offset.
+    pub async fn mark(&self) -> Result<u64, ParserError> {
+        match self.query(Query::Mark).await? {
+            QueryResponse::Mark(line) => Ok(line),
+            _ => Err(ParserError::InvalidQuery("unexpected mark query response".into())),
+        }
+    }
+
+    /// Complete lines of output from line number `since`, or from the oldest
+    /// line kept. See [`Query::Output`].
+    pub async fn output(&self, since: Option<u64>, limit: usize) -> Result<OutputResponse, ParserError> {
+        match self.query(Query::Output { since, limit }).await? {
+            QueryResponse::Output(output) => Ok(output),
+            _ => Err(ParserError::InvalidQuery("unexpected output query response".into())),
+        }
+    }
+
+    /// Hyperlinks and URLs in the screen and scrollback, at most `limit`
+    /// (capped at [`links::MAX_LINKS`]) distinct ones. See [`links`].
+    pub async fn links(&self, limit: usize) -> Result<links::LinksResponse, ParserError> {
+        let limit = limit.min(links::MAX_LINKS);
+        match self.query(Query::Links { limit }).await? {
+            QueryResponse::Links(links) => Ok(links),
+            _ => Err(ParserError::InvalidQuery("unexpected links query response".into())),
+        }
+    }
+
     /// Subscribe to events (returns async Stream).
     ///
     /// The stream yields `SubscriptionEvent::Event` for normal events and
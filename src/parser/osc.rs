@@ -0,0 +1,131 @@
+//! Scanning output for OSC (operating system command) sequences.
+//!
+//! An OSC is `ESC ]`, a body of the form `Ps ; Pt`, and a terminator: BEL
+//! or `ESC \` (ST). avt ignores them, so the parser picks out the ones it
+//! cares about itself.
+
+#[derive(Clone, Copy)]
+enum ScanState {
+    Ground,
+    Esc,
+    Osc,
+    OscEsc,
+}
+
+/// Stateful scanner for OSC sequences whose body starts with one of a set
+/// of prefixes, such as `"52;"`. Carries partial sequences across chunk
+/// boundaries. Other sequences are skipped without buffering them.
+pub(super) struct OscScanner {
+    prefixes: &'static [&'static str],
+    /// Longest body buffered; longer sequences are skipped.
+    max_len: usize,
+    scan: ScanState,
+    buf: Vec<u8>,
+    /// The OSC being read isn't one we want.
+    skip: bool,
+}
+
+impl OscScanner {
+    pub(super) fn new(prefixes: &'static [&'static str], max_len: usize) -> Self {
+        Self {
+            prefixes,
+            max_len,
+            scan: ScanState::Ground,
+            buf: Vec::new(),
+            skip: false,
+        }
+    }
+
+    /// Feed a chunk of output. Returns the body of each wanted sequence
+    /// completed in it, with the byte offset just past its terminator.
+    pub(super) fn feed(&mut self, text: &str) -> Vec<(usize, Vec<u8>)> {
+        let mut found = Vec::new();
+        for (i, &byte) in text.as_bytes().iter().enumerate() {
+            self.scan = match self.scan {
+                ScanState::Ground => {
+                    if byte == 0x1b {
+                        ScanState::Esc
+                    } else {
+                        ScanState::Ground
+                    }
+                }
+                ScanState::Esc => match byte {
+                    b']' => self.start(),
+                    0x1b => ScanState::Esc,
+                    _ => ScanState::Ground,
+                },
+                ScanState::Osc => match byte {
+                    0x07 => {
+                        found.extend(self.finish().map(|body| (i + 1, body)));
+                        ScanState::Ground
+                    }
+                    0x1b => ScanState::OscEsc,
+                    _ => {
+                        if !self.skip {
+                            self.buf.push(byte);
+                            self.skip = self.buf.len() > self.max_len || !self.wants();
+                            if self.skip {
+                                self.buf = Vec::new();
+                            }
+                        }
+                        ScanState::Osc
+                    }
+                },
+                ScanState::OscEsc => match byte {
+                    b'\\' => {
+                        found.extend(self.finish().map(|body| (i + 1, body)));
+                        ScanState::Ground
+                    }
+                    // An ESC that doesn't end the string starts a new sequence.
+                    b']' => self.start(),
+                    _ => ScanState::Ground,
+                },
+            };
+        }
+        found
+    }
+
+    fn start(&mut self) -> ScanState {
+        self.buf.clear();
+        self.skip = false;
+        ScanState::Osc
+    }
+
+    /// Whether the body read so far may still start with a wanted prefix.
+    fn wants(&self) -> bool {
+        self.prefixes.iter().any(|prefix| {
+            let n = self.buf.len().min(prefix.len());
+            self.buf[..n] == prefix.as_bytes()[..n]
+        })
+    }
+
+    fn finish(&mut self) -> Option<Vec<u8>> {
+        let body = std::mem::take(&mut self.buf);
+        let complete = self
+            .prefixes
+            .iter()
+            .any(|prefix| body.starts_with(prefix.as_bytes()));
+        (!self.skip && complete).then_some(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_wanted_sequences_across_chunks() {
+        let mut s = OscScanner::new(&["0;", "52;"], 64);
+        let text = "a\x1b]0;title\x07b\x1b]2;other\x1b\\\x1b]52;c;x";
+        assert_eq!(s.feed(text), vec![(11, b"0;title".to_vec())]);
+        assert_eq!(s.feed("y\x1b\\z"), vec![(3, b"52;c;xy".to_vec())]);
+    }
+
+    #[test]
+    fn skips_short_and_oversized_sequences() {
+        let mut s = OscScanner::new(&["52;"], 8);
+        assert!(s.feed("\x1b]52\x07\x1b]5\x07").is_empty());
+        assert!(s.feed("\x1b]52;c;aGVsbG8=\x07").is_empty());
+        assert_eq!(s.feed("\x1b]52;c;aG\x07"), vec![(10, b"52;c;aG".to_vec())]);
+    }
+}
@@ -0,0 +1,73 @@
+//! The RGB values used when parser colors have to be drawn rather than
+//! passed on as escape codes (HTML export, PNG snapshots).
+
+use super::state::Color;
+
+/// Default foreground and background, used when a cell sets no color (and
+/// so for inverse video without explicit colors).
+pub const DEFAULT_FG: [u8; 3] = [0xd4, 0xd4, 0xd4];
+pub const DEFAULT_BG: [u8; 3] = [0x1e, 0x1e, 0x1e];
+
+/// The 16 standard colors, as xterm renders them.
+const ANSI_COLORS: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00],
+    [0xcd, 0x00, 0x00],
+    [0x00, 0xcd, 0x00],
+    [0xcd, 0xcd, 0x00],
+    [0x00, 0x00, 0xee],
+    [0xcd, 0x00, 0xcd],
+    [0x00, 0xcd, 0xcd],
+    [0xe5, 0xe5, 0xe5],
+    [0x7f, 0x7f, 0x7f],
+    [0xff, 0x00, 0x00],
+    [0x00, 0xff, 0x00],
+    [0xff, 0xff, 0x00],
+    [0x5c, 0x5c, 0xff],
+    [0xff, 0x00, 0xff],
+    [0x00, 0xff, 0xff],
+    [0xff, 0xff, 0xff],
+];
+
+pub fn rgb(color: &Color) -> [u8; 3] {
+    match color {
+        Color::Indexed(idx) => indexed(*idx),
+        Color::Rgb { r, g, b } => [*r, *g, *b],
+    }
+}
+
+/// Resolve a 256-color palette index: the 16 standard colors, then a
+/// 6x6x6 color cube, then a 24-step grayscale ramp.
+pub fn indexed(idx: u8) -> [u8; 3] {
+    match idx {
+        0..=15 => ANSI_COLORS[idx as usize],
+        16..=231 => {
+            let i = idx - 16;
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            [level(i / 36), level((i / 6) % 6), level(i % 6)]
+        }
+        232..=255 => {
+            let v = 8 + (idx - 232) * 10;
+            [v, v, v]
+        }
+    }
+}
+
+/// Format a color as `#rrggbb`.
+pub fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palette() {
+        assert_eq!(hex(indexed(1)), "#cd0000");
+        assert_eq!(hex(indexed(16)), "#000000");
+        assert_eq!(hex(indexed(196)), "#ff0000");
+        assert_eq!(hex(indexed(231)), "#ffffff");
+        assert_eq!(hex(indexed(232)), "#080808");
+        assert_eq!(hex(indexed(255)), "#eeeeee");
+    }
+}
@@ -0,0 +1,345 @@
+//! Device and status queries an application sends to its terminal.
+//!
+//! Programs probe the terminal with DA1 (`CSI c`), DSR (`CSI 5 n`, and
+//! `CSI 6 n` for the cursor position) and XTGETTCAP (`DCS + q ... ST`), and
+//! read the answer from their input. Every client attached to a session runs
+//! its own terminal, so each one answers every query it sees. The session's
+//! [`QueryPolicy`] decides per kind whether those client replies reach the
+//! PTY, or whether they are dropped and the parser answers instead.
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use parking_lot::RwLock;
+use tokio::sync::mpsc;
+
+use super::state::{QueryMode, QueryPolicy};
+
+/// Longest CSI parameter string we buffer before giving up on a sequence.
+const MAX_PARAMS: usize = 32;
+
+/// Longest XTGETTCAP request we buffer before giving up on it.
+const MAX_DCS: usize = 1024;
+
+/// Reply to DA1: a VT220 with ANSI color.
+const DA1_REPLY: &[u8] = b"\x1b[?62;22c";
+
+/// Capabilities answered for XTGETTCAP, by name.
+const TERMCAP: &[(&str, &str)] = &[("TN", "xterm-256color"), ("name", "xterm-256color"), ("Co", "256"), ("colors", "256")];
+
+/// A query found in the application's output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum TerminalQuery {
+    /// `CSI c` / `CSI 0 c`.
+    PrimaryDeviceAttributes,
+    /// `CSI 5 n`.
+    DeviceStatus,
+    /// `CSI 6 n`.
+    CursorPosition,
+    /// `DCS + q` with the hex-encoded capability names.
+    Termcap(Vec<String>),
+}
+
+impl TerminalQuery {
+    fn mode(&self, policy: &QueryPolicy) -> QueryMode {
+        match self {
+            Self::PrimaryDeviceAttributes => policy.da1,
+            Self::DeviceStatus | Self::CursorPosition => policy.dsr,
+            Self::Termcap(_) => policy.xtgettcap,
+        }
+    }
+
+    /// The reply a terminal would send. `row` and `col` are the 0-based
+    /// cursor position.
+    fn answer(&self, row: usize, col: usize) -> Vec<u8> {
+        match self {
+            Self::PrimaryDeviceAttributes => DA1_REPLY.to_vec(),
+            Self::DeviceStatus => b"\x1b[0n".to_vec(),
+            Self::CursorPosition => format!("\x1b[{};{}R", row + 1, col + 1).into_bytes(),
+            Self::Termcap(names) => {
+                let mut reply = Vec::new();
+                for hex_name in names {
+                    let value = decode_hex(hex_name).and_then(|name| {
+                        TERMCAP.iter().find(|(cap, _)| *cap == name).map(|(_, v)| *v)
+                    });
+                    match value {
+                        Some(value) => reply.extend_from_slice(
+                            format!("\x1bP1+r{}={}\x1b\\", hex_name, encode_hex(value)).as_bytes(),
+                        ),
+                        None => reply.extend_from_slice(format!("\x1bP0+r{}\x1b\\", hex_name).as_bytes()),
+                    }
+                }
+                reply
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum ScanState {
+    Ground,
+    Esc,
+    Csi,
+    Dcs,
+    DcsEsc,
+}
+
+/// Stateful scanner for queries in PTY output.
+///
+/// Like the keyboard mode tracker, this carries partial sequences across
+/// chunk boundaries.
+pub(super) struct QueryScanner {
+    scan: ScanState,
+    /// Parameter bytes of the CSI sequence, or payload of the DCS string,
+    /// being read.
+    buf: Vec<u8>,
+}
+
+impl QueryScanner {
+    pub(super) fn new() -> Self {
+        Self {
+            scan: ScanState::Ground,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Feed a chunk of output, returning the queries it completes.
+    pub(super) fn feed(&mut self, text: &str) -> Vec<TerminalQuery> {
+        let mut found = Vec::new();
+        for &byte in text.as_bytes() {
+            self.scan = match self.scan {
+                ScanState::Ground => {
+                    if byte == 0x1b {
+                        ScanState::Esc
+                    } else {
+                        ScanState::Ground
+                    }
+                }
+                ScanState::Esc => match byte {
+                    b'[' | b'P' => {
+                        self.buf.clear();
+                        if byte == b'[' {
+                            ScanState::Csi
+                        } else {
+                            ScanState::Dcs
+                        }
+                    }
+                    0x1b => ScanState::Esc,
+                    _ => ScanState::Ground,
+                },
+                ScanState::Csi => match byte {
+                    0x20..=0x3f if self.buf.len() < MAX_PARAMS => {
+                        self.buf.push(byte);
+                        ScanState::Csi
+                    }
+                    0x40..=0x7e => {
+                        match (self.buf.as_slice(), byte) {
+                            (b"" | b"0", b'c') => found.push(TerminalQuery::PrimaryDeviceAttributes),
+                            (b"5", b'n') => found.push(TerminalQuery::DeviceStatus),
+                            (b"6", b'n') => found.push(TerminalQuery::CursorPosition),
+                            _ => {}
+                        }
+                        ScanState::Ground
+                    }
+                    0x1b => ScanState::Esc,
+                    _ => ScanState::Ground,
+                },
+                ScanState::Dcs => match byte {
+                    0x1b => ScanState::DcsEsc,
+                    _ if self.buf.len() < MAX_DCS => {
+                        self.buf.push(byte);
+                        ScanState::Dcs
+                    }
+                    _ => ScanState::Ground,
+                },
+                ScanState::DcsEsc => {
+                    if byte == b'\\' {
+                        if let Some(names) = self.buf.strip_prefix(b"+q") {
+                            let names = String::from_utf8_lossy(names);
+                            found.push(TerminalQuery::Termcap(
+                                names.split(';').filter(|n| !n.is_empty()).map(str::to_string).collect(),
+                            ));
+                        }
+                        ScanState::Ground
+                    } else if byte == b'[' || byte == b'P' {
+                        self.buf.clear();
+                        if byte == b'[' {
+                            ScanState::Csi
+                        } else {
+                            ScanState::Dcs
+                        }
+                    } else {
+                        ScanState::Ground
+                    }
+                }
+            };
+        }
+        found
+    }
+}
+
+/// Where the parser sends its own answers: the session's query policy and
+/// the channel feeding the PTY.
+pub(super) struct Responder {
+    pub(super) policy: Arc<RwLock<QueryPolicy>>,
+    pub(super) reply_tx: mpsc::WeakSender<Bytes>,
+}
+
+impl Responder {
+    /// Answer the queries the policy marks synthetic. Replies are dropped
+    /// rather than waited for if the PTY input is backed up.
+    ///
+    /// The cursor is read after the whole chunk is fed, which matches the
+    /// position at the query for the usual case of a program printing a
+    /// query and then waiting for the answer.
+    pub(super) fn answer(&self, queries: &[TerminalQuery], row: usize, col: usize) {
+        let policy = *self.policy.read();
+        let Some(tx) = self.reply_tx.upgrade() else {
+            return;
+        };
+        for query in queries {
+            if query.mode(&policy) == QueryMode::Synthetic {
+                let _ = tx.try_send(Bytes::from(query.answer(row, col)));
+            }
+        }
+    }
+}
+
+/// Remove the replies to queries that `policy` doesn't pass through from
+/// client input. Returns `None` when nothing was removed.
+///
+/// Clients write a reply in one piece, so sequences split across writes
+/// are left alone rather than holding back input (a lone Escape keypress
+/// must not wait for the next chunk).
+pub fn strip_replies(data: &[u8], policy: &QueryPolicy) -> Option<Vec<u8>> {
+    if policy.is_passthrough() || !data.contains(&0x1b) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    let mut stripped = false;
+    while i < data.len() {
+        if data[i] == 0x1b {
+            if let Some(len) = reply_len(&data[i..], policy) {
+                i += len;
+                stripped = true;
+                continue;
+            }
+        }
+        out.push(data[i]);
+        i += 1;
+    }
+    stripped.then_some(out)
+}
+
+/// Length of the blocked reply at the start of `data`, if there is one.
+fn reply_len(data: &[u8], policy: &QueryPolicy) -> Option<usize> {
+    match data.get(1)? {
+        b'[' => {
+            let end = data[2..].iter().position(|b| !(0x30..=0x3f).contains(b))? + 2;
+            let params = &data[2..end];
+            let blocked = match data[end] {
+                // DA1 reply: CSI ? Ps ; ... c
+                b'c' => policy.da1 != QueryMode::Passthrough && params.first() == Some(&b'?'),
+                // Cursor position report: CSI row ; col R
+                b'R' => policy.dsr != QueryMode::Passthrough && is_cursor_report(params),
+                // Status report: CSI 0 n (ok) or CSI 3 n (malfunction)
+                b'n' => policy.dsr != QueryMode::Passthrough && matches!(params, b"0" | b"3"),
+                _ => false,
+            };
+            blocked.then_some(end + 1)
+        }
+        // XTGETTCAP reply: DCS 1 + r ... ST or DCS 0 + r ... ST
+        b'P' if policy.xtgettcap != QueryMode::Passthrough => {
+            let body = &data[2..];
+            if !(body.starts_with(b"1+r") || body.starts_with(b"0+r")) {
+                return None;
+            }
+            let end = body.windows(2).position(|w| w == b"\x1b\\")?;
+            Some(2 + end + 2)
+        }
+        _ => None,
+    }
+}
+
+fn is_cursor_report(params: &[u8]) -> bool {
+    let mut parts = params.split(|&b| b == b';');
+    let is_number = |p: Option<&[u8]>| p.is_some_and(|p| !p.is_empty() && p.iter().all(u8::is_ascii_digit));
+    is_number(parts.next()) && is_number(parts.next()) && parts.next().is_none()
+}
+
+fn decode_hex(hex: &str) -> Option<String> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+fn encode_hex(s: &str) -> String {
+    s.bytes().map(|b| format!("{:02X}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(mode: QueryMode) -> QueryPolicy {
+        QueryPolicy {
+            da1: mode,
+            dsr: mode,
+            xtgettcap: mode,
+        }
+    }
+
+    #[test]
+    fn scanner_finds_queries_across_chunks() {
+        let mut s = QueryScanner::new();
+        assert_eq!(s.feed("hi \x1b[c \x1b[1;31mred"), vec![TerminalQuery::PrimaryDeviceAttributes]);
+        assert!(s.feed("\x1b[").is_empty());
+        assert_eq!(s.feed("6n\x1b[5n"), vec![TerminalQuery::CursorPosition, TerminalQuery::DeviceStatus]);
+        assert!(s.feed("\x1b[>c\x1b[?6n").is_empty());
+        assert!(s.feed("\x1bP+q544E;").is_empty());
+        assert_eq!(
+            s.feed("436F\x1b\\"),
+            vec![TerminalQuery::Termcap(vec!["544E".into(), "436F".into()])]
+        );
+    }
+
+    #[test]
+    fn answers() {
+        assert_eq!(TerminalQuery::PrimaryDeviceAttributes.answer(0, 0), DA1_REPLY);
+        assert_eq!(TerminalQuery::CursorPosition.answer(4, 9), b"\x1b[5;10R");
+        assert_eq!(
+            TerminalQuery::Termcap(vec!["436F".into(), "6B6931".into()]).answer(0, 0),
+            b"\x1bP1+r436F=323536\x1b\\\x1bP0+r6B6931\x1b\\"
+        );
+    }
+
+    #[test]
+    fn strip_replies_by_policy() {
+        let input = b"ls\x1b[?62;22c\x1b[12;40R\x1b[0n\x1bP1+r544E=78\x1b\\\r";
+        assert_eq!(strip_replies(input, &policy(QueryMode::Passthrough)), None);
+        assert_eq!(strip_replies(input, &policy(QueryMode::Block)).unwrap(), b"ls\r");
+
+        let only_dsr = QueryPolicy {
+            dsr: QueryMode::Synthetic,
+            ..Default::default()
+        };
+        assert_eq!(
+            strip_replies(input, &only_dsr).unwrap(),
+            b"ls\x1b[?62;22c\x1bP1+r544E=78\x1b\\\r"
+        );
+    }
+
+    #[test]
+    fn strip_replies_keeps_keys() {
+        let block = policy(QueryMode::Block);
+        for keys in [&b"\x1b"[..], b"\x1b[A", b"\x1b[1;5C", b"\x1b[?", b"\x1bP1+r", b"\x1b[5~"] {
+            assert_eq!(strip_replies(keys, &block), None, "{:?}", keys);
+        }
+    }
+}
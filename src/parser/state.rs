@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum Format {
     Plain,
@@ -10,24 +11,66 @@ pub enum Format {
 
 #[derive(Debug, Clone)]
 pub enum Query {
-    Screen { format: Format },
+    /// The visible screen. With `since`, a screen generation from an
+    /// earlier response, only the rows changed after it.
+    Screen { format: Format, since: Option<u64> },
     Scrollback { format: Format, offset: usize, limit: usize },
     Cursor,
+    Keyboard,
+    /// Every input mode the application has negotiated; see [`TerminalModes`].
+    Modes,
     Resize { cols: usize, rows: usize },
+    /// Move the terminal state to a snapshot file at `path` and free it from
+    /// memory. Queries are then answered from the file; raw output or a
+    /// resize loads it back.
+    Hibernate { path: std::path::PathBuf },
+    /// Keep at most `lines` lines of scrollback, dropping the oldest.
+    SetScrollbackLimit { lines: usize },
+    /// Capture the full emulator state as a [`Checkpoint`].
+    Checkpoint,
+    /// Replace the emulator state with a [`Checkpoint`], resized to the
+    /// current terminal size if it was taken at another.
+    Restore { checkpoint: Checkpoint },
+    /// Line number of the cursor's line that stays valid as old lines are
+    /// dropped from the scrollback. See [`super::Parser::lines_dropped`].
+    Mark,
+    /// Up to `limit` complete lines of the primary screen, as plain text,
+    /// starting at line number `since` (see [`Query::Mark`]), or at the
+    /// oldest line kept if `since` is `None`.
+    Output { since: Option<u64>, limit: usize },
+    /// Hyperlinks and URLs in the screen and scrollback, at most `limit`
+    /// distinct ones.
+    Links { limit: usize },
 }
 
 #[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 #[serde(untagged)]
 pub enum QueryResponse {
     Screen(ScreenResponse),
     Scrollback(ScrollbackResponse),
     Cursor(CursorResponse),
+    Keyboard(KeyboardModes),
+    Modes(TerminalModes),
+    Checkpoint(Checkpoint),
+    Mark(u64),
+    Output(OutputResponse),
+    Links(super::links::LinksResponse),
     Ok,
+    Failed(String),
 }
 
 #[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ScreenResponse {
     pub epoch: u64,
+    /// Screen generation these lines are from. Pass it back as `since` to
+    /// get only the rows that change after it.
+    pub generation: u64,
+    /// For a `since` query, the rows `lines` holds, in order; no other row
+    /// changed. Absent when `lines` is the whole screen.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changed_rows: Option<Vec<usize>>,
     pub first_line_index: usize,
     pub total_lines: usize,
     pub lines: Vec<FormattedLine>,
@@ -35,9 +78,21 @@ pub struct ScreenResponse {
     pub cols: usize,
     pub rows: usize,
     pub alternate_active: bool,
+    pub keyboard: KeyboardModes,
+    /// Inline images showing on the screen. Their cells read as blank in
+    /// `lines`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<super::graphics::ImagePlacement>,
+    /// Bytes of output the parser had taken in when the screen was read.
+    /// Output from this offset on follows the screen; see
+    /// [`Broker::subscribe_from`](crate::broker::Broker::subscribe_from).
+    #[serde(skip)]
+    #[cfg_attr(feature = "schemas", schemars(skip))]
+    pub output_offset: u64,
 }
 
 #[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ScrollbackResponse {
     pub epoch: u64,
     pub lines: Vec<FormattedLine>,
@@ -46,33 +101,194 @@ pub struct ScrollbackResponse {
 }
 
 #[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct OutputResponse {
+    pub lines: Vec<String>,
+    /// Line number to read from next.
+    pub next: u64,
+    /// Lines between `since` and the first line returned that had already
+    /// been dropped from the scrollback.
+    pub missed: u64,
+    /// Whether more complete lines follow `next`.
+    pub more: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct CursorResponse {
     pub epoch: u64,
     pub cursor: Cursor,
 }
 
+/// Full emulator state: screens, scrollback, cursor, terminal modes and
+/// the modes wsh tracks alongside them. Serializable, so it can be stored
+/// and restored into another parser.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct Checkpoint {
+    pub cols: usize,
+    pub rows: usize,
+    /// Lines in scrollback and on screen when the checkpoint was taken.
+    pub total_lines: usize,
+    pub alternate_active: bool,
+    pub keyboard: KeyboardModes,
+    #[serde(default)]
+    pub input: InputModes,
+    /// Byte stream that rebuilds the grid when fed to an empty terminal of
+    /// `cols` x `rows`.
+    pub data: String,
+}
+
+/// Keyboard modes requested by the application, which determine how keys
+/// must be encoded when sent to it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct KeyboardModes {
+    /// DECCKM: arrow keys, Home and End use SS3 (`ESC O A`) sequences.
+    pub application_cursor: bool,
+    /// DECKPAM: the numeric keypad sends application sequences.
+    pub application_keypad: bool,
+    /// Active kitty keyboard protocol flags (0 when not in use).
+    pub kitty_flags: u32,
+    /// xterm modifyOtherKeys level (`CSI > 4 ; n m`): 0 off, 1 for keys
+    /// with no legacy encoding, 2 for every modified key.
+    #[serde(default)]
+    pub modify_other_keys: u8,
+}
+
+/// How the application wants pastes, mouse events and focus changes
+/// delivered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[serde(default)]
+pub struct InputModes {
+    /// Mode 2004: pastes are wrapped in `ESC [ 200 ~` and `ESC [ 201 ~`.
+    pub bracketed_paste: bool,
+    /// Which mouse events are reported.
+    pub mouse_tracking: MouseTracking,
+    /// How mouse reports are encoded.
+    pub mouse_encoding: MouseEncoding,
+    /// Mode 1004: focus changes are reported as `CSI I` and `CSI O`.
+    pub focus_events: bool,
+}
+
+/// Mouse tracking mode, by the DEC private mode that enables it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum MouseTracking {
+    #[default]
+    Off,
+    /// Mode 9: button presses only.
+    X10,
+    /// Mode 1000: presses and releases.
+    Normal,
+    /// Mode 1002: presses, releases and drags.
+    Button,
+    /// Mode 1003: every motion, buttons held or not.
+    Any,
+}
+
+/// Mouse report encoding, by the DEC private mode that selects it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum MouseEncoding {
+    /// `CSI M` followed by three bytes.
+    #[default]
+    Default,
+    /// Mode 1005: coordinates as UTF-8 characters.
+    Utf8,
+    /// Mode 1006: `CSI < b ; x ; y M` or `m`.
+    Sgr,
+    /// Mode 1015: `CSI b ; x ; y M`.
+    Urxvt,
+    /// Mode 1016: SGR with pixel coordinates.
+    SgrPixels,
+}
+
+/// Everything the application has negotiated about its input, as served
+/// at `GET /sessions/{name}/modes`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct TerminalModes {
+    pub alternate_active: bool,
+    #[serde(flatten)]
+    pub keyboard: KeyboardModes,
+    #[serde(flatten)]
+    pub input: InputModes,
+}
+
+/// Who answers a kind of device or status query the application sends to
+/// its terminal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMode {
+    /// Attached clients' terminals answer; their replies reach the PTY.
+    #[default]
+    Passthrough,
+    /// The parser answers; replies from clients are dropped.
+    Synthetic,
+    /// Nobody answers; replies from clients are dropped.
+    Block,
+}
+
+/// Per-session handling of the queries an application uses to probe its
+/// terminal. With several clients attached, each client's terminal answers
+/// every query it sees, so `passthrough` can put duplicate replies in the
+/// application's input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[serde(default, deny_unknown_fields)]
+pub struct QueryPolicy {
+    /// Primary device attributes (`CSI c`).
+    pub da1: QueryMode,
+    /// Device status and cursor position reports (`CSI 5 n`, `CSI 6 n`).
+    pub dsr: QueryMode,
+    /// Termcap capability lookups (`DCS + q ... ST`).
+    pub xtgettcap: QueryMode,
+}
+
+impl QueryPolicy {
+    /// Whether any query kind is answered by the parser.
+    pub fn any_synthetic(&self) -> bool {
+        [self.da1, self.dsr, self.xtgettcap].contains(&QueryMode::Synthetic)
+    }
+
+    /// Whether every query kind is left to the clients.
+    pub fn is_passthrough(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct Cursor {
     pub row: usize,
     pub col: usize,
     pub visible: bool,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 #[serde(untagged)]
 pub enum FormattedLine {
     Plain(String),
     Styled(Vec<Span>),
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct Span {
     pub text: String,
     #[serde(flatten)]
     pub style: Style,
 }
 
-#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[serde(default)]
 pub struct Style {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fg: Option<Color>,
@@ -109,7 +325,8 @@ impl Style {
     }
 }
 
-#[derive(Debug, Clone, Serialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum Color {
     Indexed(u8),
@@ -1,14 +1,33 @@
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use tokio::sync::{broadcast, mpsc, oneshot};
 
+use super::ansi::line_to_ansi;
+use super::clipboard::{Clipboard, ClipboardScanner};
+use super::dirty::DirtyRows;
+use super::encoding::Encoding;
 use super::events::{Event, ResetReason};
 use super::format::format_line;
+use super::graphics::{self, ImageScanner, ImageStore, ScannedImage};
+use super::keyboard::KeyboardModeTracker;
+use super::links::{Hyperlinks, LinkMark, LinkScanner, Position};
+use super::queries::{QueryScanner, Responder};
 use super::state::{
-    Cursor, CursorResponse, Format, Query, QueryResponse, ScreenResponse, ScrollbackResponse,
+    Checkpoint, Cursor, CursorResponse, Format, InputModes, KeyboardModes, OutputResponse, Query,
+    QueryResponse, ScreenResponse, ScrollbackResponse, TerminalModes,
 };
+use super::guard::GuardScanner;
+use super::title::{TitleChange, TitleScanner, TitleState};
+
+/// How often a changed terminal is checkpointed for recovery after a panic.
+const RECOVERY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Most bytes of queued output fed to the terminal as one batch.
+const MAX_BATCH: usize = 64 * 1024;
 
 #[allow(clippy::too_many_arguments)]
 pub async fn run(
@@ -20,7 +39,18 @@ pub async fn run(
     scrollback_limit: usize,
     shared_cols: &Arc<AtomicUsize>,
     shared_rows: &Arc<AtomicUsize>,
+    shared_scrollback: &Arc<AtomicUsize>,
+    lines_dropped: &Arc<AtomicU64>,
+    screen_generation: &AtomicU64,
+    output_offset: &AtomicU64,
+    responder: &parking_lot::Mutex<Option<Responder>>,
+    encoding: &parking_lot::RwLock<Encoding>,
+    clipboard: &parking_lot::Mutex<Option<Clipboard>>,
+    title: &parking_lot::RwLock<TitleState>,
+    images: &parking_lot::Mutex<ImageStore>,
+    recovery: &parking_lot::Mutex<Option<Checkpoint>>,
 ) {
+    let mut scrollback_limit = scrollback_limit;
     let mut vt = avt::Vt::builder()
         .size(cols, rows)
         .scrollback_limit(scrollback_limit)
@@ -28,35 +58,117 @@ pub async fn run(
 
     let mut seq: u64 = 0;
     let epoch: u64 = 0;
-    let mut last_cursor = vt.cursor();
     let mut alternate_active = false;
     let mut alt_detect = AlternateScreenDetector::new();
+    let mut keyboard = KeyboardModeTracker::new();
+    let mut queries = QueryScanner::new();
+    let mut clipboard_scan = ClipboardScanner::new();
+    let mut link_scan = LinkScanner::new();
+    let mut title_scan = TitleScanner::new();
+    let mut guard_scan = GuardScanner::new();
+    let mut image_scan = ImageScanner::new();
+    let mut hyperlinks = Hyperlinks::new();
+    // Snapshot file holding the terminal state while hibernated.
+    let mut hibernated: Option<PathBuf> = None;
+
+    // After a panic, pick up from the last recovery checkpoint. It is taken
+    // rather than copied so that state which makes the parser panic again
+    // isn't restored a second time.
+    if let Some(checkpoint) = recovery.lock().take() {
+        restore(&mut vt, &checkpoint, scrollback_limit);
+        alternate_active = checkpoint.alternate_active;
+        keyboard.restore(checkpoint.keyboard, checkpoint.input);
+    }
+    let mut last_cursor = vt.cursor();
 
+    // A restarted task continues the generations of the one before, with
+    // every row changed, so clients' deltas stay valid across the restart.
+    let mut dirty_rows = DirtyRows::new(vt.size().1, screen_generation.load(Ordering::Acquire) + 1);
+
+    // Whether the terminal changed since the last recovery checkpoint.
+    let mut dirty = false;
+    let mut recovery_tick = tokio::time::interval_at(
+        tokio::time::Instant::now() + RECOVERY_INTERVAL,
+        RECOVERY_INTERVAL,
+    );
+    recovery_tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    // Once the PTY output ends, queries are still answered, so the final
+    // screen can be read, until every handle to the parser is dropped.
+    let mut output_open = true;
     loop {
         tokio::select! {
-            result = raw_rx.recv() => {
+            result = raw_rx.recv(), if output_open => {
                 match result {
                     Some(bytes) => {
-                        let text = String::from_utf8_lossy(&bytes);
-
-                        // Detect alternate screen transitions before feeding to avt
-                        let new_alternate = alt_detect.feed(&text, alternate_active);
-
-                        let changes = vt.feed_str(&text);
-
-                        // Extract changed line indices before dropping the Changes struct
-                        // (Changes contains a reference to vt via its scrollback iterator)
-                        let changed_lines: Vec<usize> = changes.lines.clone();
-                        drop(changes);
+                        let bytes = batch(bytes, raw_rx);
+                        output_offset.fetch_add(bytes.len() as u64, Ordering::Release);
+                        dirty = true;
+                        wake(&mut vt, &mut hibernated, scrollback_limit, &mut seq, &event_tx);
+                        let text = encoding.read().decode(&bytes);
+
+                        let keyboard_changed = keyboard.feed(&text);
+                        let found_queries = queries.feed(&text);
+                        let clipboard_writes = clipboard_scan.feed(&text);
+                        let title_changes = title_scan.feed(&text);
+                        let denied = guard_scan.feed(&text);
+
+                        // Feed up to each hyperlink start or end, image and
+                        // screen erase, to see where the cursor is at that point.
+                        let mut stops: Vec<(usize, Stop)> = link_scan
+                            .feed(&text)
+                            .into_iter()
+                            .map(|(offset, mark)| (offset, Stop::Link(mark)))
+                            .collect();
+                        stops.extend(image_scan.feed(&text).into_iter().map(|(offset, image)| (offset, Stop::Image(image))));
+                        stops.extend(graphics::erases(&text).into_iter().map(|(offset, scrollback)| (offset, Stop::Erase { scrollback })));
+                        stops.sort_by_key(|(offset, _)| *offset);
+                        // Alternate screen switches are tracked along the way,
+                        // so each image is put on the screen it was drawn on.
+                        let mut changed_lines = Vec::new();
+                        let mut fed = 0;
+                        let mut new_alternate = alternate_active;
+                        for (offset, stop) in stops {
+                            new_alternate = switch_screen(&mut alt_detect, &text[fed..offset], new_alternate, images);
+                            feed(&mut vt, &text[fed..offset], &mut changed_lines, lines_dropped);
+                            fed = offset;
+                            let dropped = lines_dropped.load(Ordering::Acquire);
+                            let at = cursor_position(&vt, dropped);
+                            match stop {
+                                Stop::Link(mark) => hyperlinks.mark(mark, at, &vt, dropped),
+                                Stop::Image(image) => {
+                                    // avt skipped the image, so move the cursor
+                                    // past it as the attached terminal did.
+                                    let (cols, rows) = vt.size();
+                                    let (image_rows, image_cols) = image.cells(cols, rows);
+                                    let motion = image.cursor_motion(image_rows, image_cols);
+                                    images.lock().insert(image, at, (image_rows, image_cols), new_alternate);
+                                    feed(&mut vt, &motion, &mut changed_lines, lines_dropped);
+                                }
+                                Stop::Erase { scrollback } => {
+                                    let first_line = at.line - vt.cursor().row as u64;
+                                    images.lock().erase(first_line, new_alternate, scrollback);
+                                }
+                            }
+                        }
+                        new_alternate = switch_screen(&mut alt_detect, &text[fed..], new_alternate, images);
+                        feed(&mut vt, &text[fed..], &mut changed_lines, lines_dropped);
+                        images.lock().forget_dropped(lines_dropped.load(Ordering::Acquire));
 
-                        // Emit mode/reset events if alternate screen state changed
-                        if new_alternate != alternate_active {
-                            alternate_active = new_alternate;
+                        // Emit a mode event if the alternate screen or keyboard
+                        // modes changed, and a reset on alternate screen switches
+                        if new_alternate != alternate_active || keyboard_changed {
                             seq = seq.wrapping_add(1);
                             let _ = event_tx.send(Event::Mode {
                                 seq,
-                                alternate_active,
+                                alternate_active: new_alternate,
+                                keyboard: keyboard.modes(),
+                                input: keyboard.input(),
                             });
+                        }
+                        if new_alternate != alternate_active {
+                            alternate_active = new_alternate;
+                            dirty_rows.touch_all(vt.size().1);
                             seq = seq.wrapping_add(1);
                             let _ = event_tx.send(Event::Reset {
                                 seq,
@@ -73,6 +185,7 @@ pub async fn run(
                         // Use vt.view() to get the correct visible line content.
                         // Only collect when there are actual changes to avoid
                         // O(n) iteration on every PTY chunk.
+                        dirty_rows.touch(&changed_lines);
                         if !changed_lines.is_empty() {
                             let total_lines = vt.lines().count();
                             let view_lines: Vec<_> = vt.view().collect();
@@ -83,7 +196,7 @@ pub async fn run(
                                         seq,
                                         index: line_idx,
                                         total_lines,
-                                        line: format_line(*line, true),
+                                        line: format_line(line, true),
                                     });
                                 }
                             }
@@ -104,16 +217,369 @@ pub async fn run(
                             });
                             last_cursor = cursor;
                         }
+
+                        for write in clipboard_writes {
+                            seq = seq.wrapping_add(1);
+                            let _ = event_tx.send(Event::Clipboard {
+                                seq,
+                                selection: write.selection.clone(),
+                                text: write.text.clone(),
+                            });
+                            *clipboard.lock() = Some(write);
+                        }
+
+                        for change in title_changes {
+                            if !title.write().apply(change.clone()) {
+                                continue;
+                            }
+                            seq = seq.wrapping_add(1);
+                            let _ = event_tx.send(match change {
+                                TitleChange::Title(title) => Event::Title { seq, title },
+                                TitleChange::Cwd(cwd) => Event::Cwd { seq, cwd },
+                            });
+                        }
+
+                        for denied in denied {
+                            seq = seq.wrapping_add(1);
+                            let _ = event_tx.send(Event::CommandDenied {
+                                seq,
+                                program: denied.program,
+                                command: denied.command,
+                            });
+                        }
+
+                        if !found_queries.is_empty() {
+                            if let Some(responder) = responder.lock().as_ref() {
+                                let (cols, _) = vt.size();
+                                let col = last_cursor.col.min(cols.saturating_sub(1));
+                                responder.answer(&found_queries, last_cursor.row, col);
+                            }
+                        }
                     }
-                    None => break,
+                    None => output_open = false,
                 }
             }
 
             Some((query, response_tx)) = query_rx.recv() => {
-                let response = handle_query(&mut vt, query, epoch, alternate_active, &mut seq, &event_tx, shared_cols, shared_rows);
+                // These replace or reflow the whole screen.
+                let whole_screen = matches!(query, Query::Resize { .. } | Query::SetScrollbackLimit { .. } | Query::Restore { .. });
+                if whole_screen {
+                    dirty = true;
+                }
+                let response = match query {
+                    Query::Hibernate { path } => {
+                        if hibernated.is_some() {
+                            QueryResponse::Ok
+                        } else {
+                            match hibernate(&mut vt, &path, scrollback_limit) {
+                                Ok(()) => {
+                                    hibernated = Some(path);
+                                    QueryResponse::Ok
+                                }
+                                Err(e) => QueryResponse::Failed(e.to_string()),
+                            }
+                        }
+                    }
+                    Query::SetScrollbackLimit { lines } => {
+                        wake(&mut vt, &mut hibernated, scrollback_limit, &mut seq, &event_tx);
+                        let (cols, rows) = vt.size();
+                        let before = vt.lines().count();
+                        let snapshot = snapshot(&mut vt);
+                        vt = from_snapshot(&snapshot, cols, rows, lines);
+                        scrollback_limit = lines;
+                        shared_scrollback.store(lines, Ordering::Release);
+                        if vt.lines().count() < before {
+                            lines_dropped.fetch_add((before - vt.lines().count()) as u64, Ordering::Release);
+                            images.lock().forget_dropped(lines_dropped.load(Ordering::Acquire));
+                            seq = seq.wrapping_add(1);
+                            let _ = event_tx.send(Event::Reset {
+                                seq,
+                                reason: ResetReason::ClearScrollback,
+                            });
+                        }
+                        QueryResponse::Ok
+                    }
+                    Query::Modes => QueryResponse::Modes(TerminalModes {
+                        alternate_active,
+                        keyboard: keyboard.modes(),
+                        input: keyboard.input(),
+                    }),
+                    Query::Checkpoint => {
+                        // Like reads, taken from a copy while hibernated.
+                        let mut copy;
+                        let target = match hibernated.as_deref() {
+                            Some(path) => {
+                                let (cols, rows) = vt.size();
+                                copy = load_snapshot(path, cols, rows, scrollback_limit, &mut seq, &event_tx);
+                                &mut copy
+                            }
+                            None => &mut vt,
+                        };
+                        QueryResponse::Checkpoint(checkpoint(target, scrollback_limit, alternate_active, keyboard.modes(), keyboard.input()))
+                    }
+                    Query::Restore { checkpoint } => {
+                        if checkpoint.cols == 0 || checkpoint.rows == 0 {
+                            QueryResponse::Failed("checkpoint has an empty terminal size".into())
+                        } else {
+                            if let Some(path) = hibernated.take() {
+                                let _ = std::fs::remove_file(path);
+                            }
+                            restore(&mut vt, &checkpoint, scrollback_limit);
+                            alternate_active = checkpoint.alternate_active;
+                            alt_detect = AlternateScreenDetector::new();
+                            keyboard.restore(checkpoint.keyboard, checkpoint.input);
+                            hyperlinks.clear();
+                            images.lock().clear();
+                            last_cursor = vt.cursor();
+                            seq = seq.wrapping_add(1);
+                            let _ = event_tx.send(Event::Reset {
+                                seq,
+                                reason: ResetReason::Restore,
+                            });
+                            QueryResponse::Ok
+                        }
+                    }
+                    // Reads are served from a throwaway copy of the snapshot
+                    // so polling a hibernated session doesn't undo it.
+                    query @ (Query::Screen { .. } | Query::Scrollback { .. } | Query::Cursor | Query::Mark | Query::Output { .. } | Query::Links { .. })
+                        if hibernated.is_some() =>
+                    {
+                        let (cols, rows) = vt.size();
+                        let path = hibernated.as_deref().unwrap_or(Path::new(""));
+                        let mut snapshot_vt = load_snapshot(path, cols, rows, scrollback_limit, &mut seq, &event_tx);
+                        handle_query(&mut snapshot_vt, query, epoch, alternate_active, keyboard.modes(), &hyperlinks, &images.lock(), &dirty_rows, output_offset.load(Ordering::Acquire), &mut seq, &event_tx, shared_cols, shared_rows, lines_dropped)
+                    }
+                    query => {
+                        wake(&mut vt, &mut hibernated, scrollback_limit, &mut seq, &event_tx);
+                        handle_query(&mut vt, query, epoch, alternate_active, keyboard.modes(), &hyperlinks, &images.lock(), &dirty_rows, output_offset.load(Ordering::Acquire), &mut seq, &event_tx, shared_cols, shared_rows, lines_dropped)
+                    }
+                };
+                if whole_screen {
+                    dirty_rows.touch_all(vt.size().1);
+                }
                 let _ = response_tx.send(response);
             }
+
+            _ = recovery_tick.tick(), if output_open => {
+                // A hibernated terminal is already safe on disk, and
+                // checkpointing it would load it back.
+                if dirty && hibernated.is_none() {
+                    *recovery.lock() = Some(checkpoint(&mut vt, scrollback_limit, alternate_active, keyboard.modes(), keyboard.input()));
+                    dirty = false;
+                }
+            }
+
+            else => break,
         }
+        screen_generation.store(dirty_rows.generation(), Ordering::Release);
+    }
+
+    if let Some(path) = hibernated {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// A point in a chunk of output where the parser needs to know where the
+/// cursor is.
+enum Stop {
+    Link(LinkMark),
+    Image(ScannedImage),
+    /// The screen, and maybe the scrollback, was erased.
+    Erase { scrollback: bool },
+}
+
+/// Track alternate screen switches in `text`, output about to be fed with
+/// the alternate screen `active` or not. Leaving the alternate screen drops
+/// the images drawn on it. Returns whether it's active after `text`.
+fn switch_screen(
+    alt_detect: &mut AlternateScreenDetector,
+    text: &str,
+    active: bool,
+    images: &parking_lot::Mutex<ImageStore>,
+) -> bool {
+    let now = alt_detect.feed(text, active);
+    if active && !now {
+        images.lock().leave_alternate();
+    }
+    now
+}
+
+/// Append the output already queued behind `first`, up to [`MAX_BATCH`]
+/// bytes, so a burst is fed, diffed and reported once rather than once per
+/// PTY read. A row rewritten by every chunk of the burst gets one line
+/// event with its final contents.
+fn batch(first: Bytes, raw_rx: &mut mpsc::Receiver<Bytes>) -> Bytes {
+    if first.len() >= MAX_BATCH || raw_rx.is_empty() {
+        return first;
+    }
+    let mut buf = BytesMut::from(&first[..]);
+    while buf.len() < MAX_BATCH {
+        match raw_rx.try_recv() {
+            Ok(more) => buf.extend_from_slice(&more),
+            Err(_) => break,
+        }
+    }
+    buf.freeze()
+}
+
+/// Feed `text` to `vt`, adding the view rows it changed to `changed_lines`
+/// and counting the lines it pushed out of the scrollback.
+fn feed(vt: &mut avt::Vt, text: &str, changed_lines: &mut Vec<usize>, lines_dropped: &AtomicU64) {
+    if text.is_empty() {
+        return;
+    }
+    let mut changes = vt.feed_str(text);
+    // Changes borrows vt through its scrollback iterator, so take what is
+    // needed before dropping it.
+    for line in &changes.lines {
+        if !changed_lines.contains(line) {
+            changed_lines.push(*line);
+        }
+    }
+    let dropped = changes.scrollback.by_ref().count();
+    drop(changes);
+    lines_dropped.fetch_add(dropped as u64, Ordering::Release);
+}
+
+/// The cursor's position, with its line numbered as by [`Query::Mark`].
+fn cursor_position(vt: &avt::Vt, dropped: u64) -> Position {
+    let (_, rows) = vt.size();
+    let cursor = vt.cursor();
+    let first_line_index = vt.lines().count().saturating_sub(rows);
+    Position {
+        line: dropped + (first_line_index + cursor.row) as u64,
+        col: cursor.col,
+    }
+}
+
+/// Serialize the terminal as a byte stream that rebuilds it when fed to a
+/// fresh `Vt` of the same size: the scrollback as styled lines, scrolled off
+/// the top, followed by avt's dump of the screens, modes and cursor.
+///
+/// Switches `vt` to the primary screen to reach its scrollback, so `vt`
+/// must be discarded (or rebuilt from the result) afterwards.
+fn snapshot(vt: &mut avt::Vt) -> String {
+    let mut out = String::new();
+    let screens = vt.dump();
+    vt.feed_str("\x1b[?1047l");
+
+    let (_, rows) = vt.size();
+    let lines: Vec<_> = vt.lines().collect();
+    let history = lines.len().saturating_sub(rows);
+    for line in &lines[..history] {
+        out.push_str(&line_to_ansi(&format_line(line, true)));
+        out.push_str("\r\n");
+    }
+    if history > 0 {
+        out.push_str(&"\n".repeat(rows.saturating_sub(1)));
+    }
+    out.push_str("\x1b[H");
+    out.push_str(&screens);
+    out
+}
+
+fn from_snapshot(snapshot: &str, cols: usize, rows: usize, scrollback_limit: usize) -> avt::Vt {
+    let mut vt = avt::Vt::builder()
+        .size(cols, rows)
+        .scrollback_limit(scrollback_limit)
+        .build();
+    vt.feed_str(snapshot);
+    vt
+}
+
+/// Capture `vt` and the modes tracked alongside it. `vt` is rebuilt from
+/// the capture, so it is left as it was.
+fn checkpoint(
+    vt: &mut avt::Vt,
+    scrollback_limit: usize,
+    alternate_active: bool,
+    keyboard: KeyboardModes,
+    input: InputModes,
+) -> Checkpoint {
+    let (cols, rows) = vt.size();
+    let total_lines = vt.lines().count();
+    let data = snapshot(vt);
+    *vt = from_snapshot(&data, cols, rows, scrollback_limit);
+    Checkpoint {
+        cols,
+        rows,
+        total_lines,
+        alternate_active,
+        keyboard,
+        input,
+        data,
+    }
+}
+
+/// Replace `vt` with the terminal in `checkpoint`, keeping the size of `vt`.
+fn restore(vt: &mut avt::Vt, checkpoint: &Checkpoint, scrollback_limit: usize) {
+    let size = vt.size();
+    *vt = from_snapshot(&checkpoint.data, checkpoint.cols, checkpoint.rows, scrollback_limit);
+    if vt.size() != size {
+        let _ = vt.resize(size.0, size.1);
+    }
+}
+
+/// Write `vt` to `path` (mode 0600; it holds terminal contents) and replace
+/// it with an empty terminal of the same size.
+fn hibernate(vt: &mut avt::Vt, path: &Path, scrollback_limit: usize) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let (cols, rows) = vt.size();
+    let snapshot = snapshot(vt);
+    let written = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .and_then(|mut file| file.write_all(snapshot.as_bytes()));
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(path);
+        *vt = from_snapshot(&snapshot, cols, rows, scrollback_limit);
+        return Err(e);
+    }
+    *vt = avt::Vt::builder().size(cols, rows).scrollback_limit(0).build();
+    Ok(())
+}
+
+/// Rebuild a terminal from the snapshot at `path`. If it can't be read the
+/// state is lost: clients get a reset and an empty terminal.
+fn load_snapshot(
+    path: &Path,
+    cols: usize,
+    rows: usize,
+    scrollback_limit: usize,
+    seq: &mut u64,
+    event_tx: &broadcast::Sender<Event>,
+) -> avt::Vt {
+    match std::fs::read_to_string(path) {
+        Ok(snapshot) => from_snapshot(&snapshot, cols, rows, scrollback_limit),
+        Err(e) => {
+            tracing::warn!(?e, path = %path.display(), "failed to read hibernation snapshot");
+            *seq = seq.wrapping_add(1);
+            let _ = event_tx.send(Event::Reset {
+                seq: *seq,
+                reason: ResetReason::ParserRestart,
+            });
+            from_snapshot("", cols, rows, scrollback_limit)
+        }
+    }
+}
+
+/// Load a hibernated terminal back into memory and delete its snapshot.
+fn wake(
+    vt: &mut avt::Vt,
+    hibernated: &mut Option<PathBuf>,
+    scrollback_limit: usize,
+    seq: &mut u64,
+    event_tx: &broadcast::Sender<Event>,
+) {
+    if let Some(path) = hibernated.take() {
+        let (cols, rows) = vt.size();
+        *vt = load_snapshot(&path, cols, rows, scrollback_limit, seq, event_tx);
+        let _ = std::fs::remove_file(&path);
     }
 }
 
@@ -123,23 +589,45 @@ fn handle_query(
     query: Query,
     epoch: u64,
     alternate_active: bool,
+    keyboard: KeyboardModes,
+    hyperlinks: &Hyperlinks,
+    images: &ImageStore,
+    dirty_rows: &DirtyRows,
+    output_offset: u64,
     seq: &mut u64,
     event_tx: &broadcast::Sender<Event>,
     shared_cols: &Arc<AtomicUsize>,
     shared_rows: &Arc<AtomicUsize>,
+    lines_dropped: &Arc<AtomicU64>,
 ) -> QueryResponse {
     match query {
-        Query::Screen { format } => {
+        Query::Screen { format, since } => {
             let styled = matches!(format, Format::Styled);
             let (cols, rows) = vt.size();
             let cursor = vt.cursor();
 
             let total_lines = vt.lines().count();
             let first_line_index = total_lines.saturating_sub(rows);
-            let lines: Vec<_> = vt.view().map(|l| format_line(l, styled)).collect();
+            // Only the rows changed since the client's generation are
+            // formatted; that is most of the cost of answering.
+            let changed_rows = since.and_then(|since| dirty_rows.changed_since(since));
+            let lines: Vec<_> = match &changed_rows {
+                Some(changed) => {
+                    let view: Vec<_> = vt.view().collect();
+                    changed
+                        .iter()
+                        .filter_map(|&row| view.get(row))
+                        .map(|l| format_line(l, styled))
+                        .collect()
+                }
+                None => vt.view().map(|l| format_line(l, styled)).collect(),
+            };
 
+            let first_line = lines_dropped.load(Ordering::Acquire) + first_line_index as u64;
             QueryResponse::Screen(ScreenResponse {
                 epoch,
+                generation: dirty_rows.generation(),
+                changed_rows,
                 first_line_index,
                 total_lines,
                 lines,
@@ -151,6 +639,9 @@ fn handle_query(
                 cols,
                 rows,
                 alternate_active,
+                keyboard,
+                images: images.placements(first_line, rows, alternate_active),
+                output_offset,
             })
         }
 
@@ -170,7 +661,7 @@ fn handle_query(
                 .into_iter()
                 .skip(offset)
                 .take(limit)
-                .map(|l| format_line(&l, styled))
+                .map(|l| format_line(l, styled))
                 .collect();
 
             QueryResponse::Scrollback(ScrollbackResponse {
@@ -193,8 +684,60 @@ fn handle_query(
             })
         }
 
+        Query::Keyboard => QueryResponse::Keyboard(keyboard),
+
+        Query::Mark => {
+            let (_, rows) = vt.size();
+            let first_line_index = vt.lines().count().saturating_sub(rows);
+            let line = first_line_index + vt.cursor().row;
+            QueryResponse::Mark(lines_dropped.load(Ordering::Acquire) + line as u64)
+        }
+
+        Query::Output { since, limit } => {
+            let dropped = lines_dropped.load(Ordering::Acquire);
+            let (_, rows) = vt.size();
+            let all_lines: Vec<_> = vt.lines().collect();
+            // The cursor's line may still be written to, so output ends
+            // before it. A full-screen application's output isn't a log, and
+            // while one runs the primary screen is out of reach.
+            let end = if alternate_active {
+                since.unwrap_or(dropped)
+            } else {
+                dropped + (all_lines.len().saturating_sub(rows) + vt.cursor().row) as u64
+            };
+            let since = since.unwrap_or(dropped);
+            let start = since.clamp(dropped, end.max(dropped));
+            let count = (end.saturating_sub(start) as usize).min(limit);
+            let first = (start - dropped) as usize;
+            let lines = all_lines[first.min(all_lines.len())..]
+                .iter()
+                .take(count)
+                .map(|line| line.text().trim_end().to_string())
+                .collect();
+            QueryResponse::Output(OutputResponse {
+                lines,
+                next: start + count as u64,
+                missed: start.saturating_sub(since),
+                more: start + (count as u64) < end,
+            })
+        }
+
+        Query::Links { limit } => {
+            QueryResponse::Links(hyperlinks.collect(vt, lines_dropped.load(Ordering::Acquire), limit))
+        }
+
+        // Handled by `run`, which owns the terminal and its settings.
+        Query::Hibernate { .. }
+        | Query::SetScrollbackLimit { .. }
+        | Query::Checkpoint
+        | Query::Restore { .. }
+        | Query::Modes => QueryResponse::Ok,
+
         Query::Resize { cols, rows } => {
-            let _changes = vt.resize(cols, rows);
+            let mut changes = vt.resize(cols, rows);
+            let dropped = changes.scrollback.by_ref().count();
+            drop(changes);
+            lines_dropped.fetch_add(dropped as u64, Ordering::Release);
             // Update shared dimensions so the restart loop uses current
             // values instead of stale spawn-time dimensions.
             shared_cols.store(cols, Ordering::Release);
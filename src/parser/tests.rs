@@ -16,6 +16,24 @@ async fn spawn_test_parser(cols: usize, rows: usize, scrollback_limit: usize) ->
     (tx, parser)
 }
 
+/// Helper: poll the plain screen until some line contains `needle`, which
+/// shows the parser has processed the input that wrote it.
+async fn wait_for_screen(parser: &Parser, needle: &str) {
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+    loop {
+        let lines = match parser.query(Query::Screen { format: Format::Plain, since: None }).await.unwrap() {
+            QueryResponse::Screen(screen) => screen.lines,
+            _ => panic!("expected Screen response"),
+        };
+        let text = serde_json::to_string(&lines).unwrap();
+        if text.contains(needle) {
+            return;
+        }
+        assert!(tokio::time::Instant::now() < deadline, "screen never showed {needle:?}: {text}");
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    }
+}
+
 #[tokio::test]
 async fn test_parser_spawn() {
     let (_tx, parser) = spawn_test_parser(80, 24, 1000).await;
@@ -24,6 +42,7 @@ async fn test_parser_spawn() {
     let response = parser
         .query(Query::Screen {
             format: Format::Plain,
+            since: None,
         })
         .await
         .unwrap();
@@ -65,6 +84,7 @@ async fn test_parser_processes_input() {
     let response = parser
         .query(Query::Screen {
             format: Format::Plain,
+            since: None,
         })
         .await
         .unwrap();
@@ -91,6 +111,7 @@ async fn test_parser_resize() {
     let response = parser
         .query(Query::Screen {
             format: Format::Plain,
+            since: None,
         })
         .await
         .unwrap();
@@ -148,6 +169,7 @@ async fn test_scrollback_includes_all_lines() {
     let screen_response = parser
         .query(Query::Screen {
             format: Format::Plain,
+            since: None,
         })
         .await
         .unwrap();
@@ -278,6 +300,7 @@ async fn test_scrollback_when_in_alternate_screen() {
 
     // Exit alternate screen mode (DECRST 1049 or rmcup)
     tx.send(bytes::Bytes::from("\x1b[?1049l")).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
     tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
 
     // Query scrollback after exiting alternate screen
@@ -307,6 +330,7 @@ async fn test_alternate_active_in_screen_response() {
     let response = parser
         .query(Query::Screen {
             format: Format::Plain,
+            since: None,
         })
         .await
         .unwrap();
@@ -325,6 +349,7 @@ async fn test_alternate_active_in_screen_response() {
     let response = parser
         .query(Query::Screen {
             format: Format::Plain,
+            since: None,
         })
         .await
         .unwrap();
@@ -338,11 +363,13 @@ async fn test_alternate_active_in_screen_response() {
 
     // Exit alternate screen mode
     tx.send(bytes::Bytes::from("\x1b[?1049l")).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
     tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
 
     let response = parser
         .query(Query::Screen {
             format: Format::Plain,
+            since: None,
         })
         .await
         .unwrap();
@@ -379,6 +406,7 @@ async fn test_alternate_screen_emits_mode_event() {
 
     // Exit alternate screen
     tx.send(bytes::Bytes::from("\x1b[?1049l")).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
 
     let mode_event = tokio::time::timeout(tokio::time::Duration::from_millis(200), async {
         loop {
@@ -393,6 +421,157 @@ async fn test_alternate_screen_emits_mode_event() {
     assert!(!mode_event, "Mode event should indicate alternate_active = false");
 }
 
+#[tokio::test]
+async fn test_osc52_write_sets_clipboard_and_emits_event() {
+    let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
+    assert!(parser.clipboard().is_none());
+
+    let mut events = parser.subscribe();
+    tx.send(bytes::Bytes::from("yank\x1b]52;c;aGVsbG8=\x07")).await.unwrap();
+
+    let (selection, text) = tokio::time::timeout(tokio::time::Duration::from_millis(200), async {
+        loop {
+            if let Some(SubscriptionEvent::Event(Event::Clipboard { selection, text, .. })) = events.next().await {
+                return (selection, text);
+            }
+        }
+    })
+    .await
+    .expect("should receive Clipboard event");
+
+    assert_eq!((selection.as_str(), text.as_str()), ("c", "hello"));
+    let clipboard = parser.clipboard().unwrap();
+    assert_eq!(clipboard.text, "hello");
+    assert_eq!(clipboard.source, clipboard::ClipboardSource::Application);
+}
+
+#[tokio::test]
+async fn test_title_and_cwd_changes_emit_events() {
+    let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
+    assert_eq!((parser.title(), parser.cwd()), (None, None));
+
+    let mut events = parser.subscribe();
+    tx.send(bytes::Bytes::from("\x1b]7;file://box/srv/app\x07\x1b]2;make\x07\x1b]2;make\x07"))
+        .await
+        .unwrap();
+
+    let changes = tokio::time::timeout(tokio::time::Duration::from_millis(200), async {
+        let mut changes = Vec::new();
+        while changes.len() < 2 {
+            match events.next().await {
+                Some(SubscriptionEvent::Event(Event::Cwd { cwd, .. })) => changes.push(cwd),
+                Some(SubscriptionEvent::Event(Event::Title { title, .. })) => changes.push(title.unwrap()),
+                _ => {}
+            }
+        }
+        changes
+    })
+    .await
+    .expect("should receive Cwd and Title events");
+
+    assert_eq!(changes, vec!["/srv/app", "make"]);
+    assert_eq!(parser.title().as_deref(), Some("make"));
+    assert_eq!(parser.cwd().as_deref(), Some("/srv/app"));
+    // The repeated title isn't a change.
+    tx.send(bytes::Bytes::from("x")).await.unwrap();
+    let next = tokio::time::timeout(tokio::time::Duration::from_millis(100), async {
+        loop {
+            if let Some(SubscriptionEvent::Event(event @ (Event::Title { .. } | Event::Line { .. }))) = events.next().await {
+                return event;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert!(matches!(next, Event::Line { .. }));
+}
+
+#[tokio::test]
+async fn test_links_track_hyperlinks_split_across_chunks() {
+    let (tx, parser) = spawn_test_parser(40, 5, 1000).await;
+    tx.send(bytes::Bytes::from("build: \x1b]8;;https://ci.example/1\x1b\\")).await.unwrap();
+    tx.send(bytes::Bytes::from("run 1\x1b]8;;\x1b")).await.unwrap();
+    tx.send(bytes::Bytes::from("\\ ok\r\nlog at http://logs.example/1\r\n")).await.unwrap();
+    for _ in 0..8 {
+        tx.send(bytes::Bytes::from("more\r\n")).await.unwrap();
+    }
+    tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+
+    let links = parser.links(10).await.unwrap();
+    assert_eq!(links.total_lines, 11);
+    assert_eq!(links.first_line_index, 6);
+    let found: Vec<_> = links
+        .links
+        .iter()
+        .map(|l| (l.url.as_str(), l.locations[0].line, l.locations[0].text.as_str()))
+        .collect();
+    assert_eq!(found, vec![("https://ci.example/1", 0, "run 1"), ("http://logs.example/1", 1, "http://logs.example/1")]);
+}
+
+#[tokio::test]
+async fn test_keyboard_mode_change_emits_mode_event() {
+    let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
+
+    let mut events = parser.subscribe();
+
+    // Enable application cursor keys and kitty disambiguation
+    tx.send(bytes::Bytes::from("\x1b[?1h\x1b[>1u")).await.unwrap();
+
+    let keyboard = tokio::time::timeout(tokio::time::Duration::from_millis(200), async {
+        loop {
+            if let Some(SubscriptionEvent::Event(Event::Mode { keyboard, alternate_active, .. })) = events.next().await {
+                assert!(!alternate_active);
+                return keyboard;
+            }
+        }
+    })
+    .await
+    .expect("should receive Mode event");
+
+    assert!(keyboard.application_cursor);
+    assert_eq!(keyboard.kitty_flags, 1);
+
+    let response = parser
+        .query(Query::Screen { format: Format::Plain, since: None })
+        .await
+        .unwrap();
+    match response {
+        QueryResponse::Screen(screen) => assert_eq!(screen.keyboard, keyboard),
+        _ => panic!("expected Screen response"),
+    }
+    assert_eq!(parser.keyboard_modes().await.unwrap(), keyboard);
+}
+
+#[tokio::test]
+async fn test_input_modes_reported_and_checkpointed() {
+    let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
+    let mut events = parser.subscribe();
+
+    tx.send(bytes::Bytes::from("\x1b[?2004h\x1b[?1002;1006h\x1b[>4;2m")).await.unwrap();
+    let input = tokio::time::timeout(tokio::time::Duration::from_millis(200), async {
+        loop {
+            if let Some(SubscriptionEvent::Event(Event::Mode { input, .. })) = events.next().await {
+                return input;
+            }
+        }
+    })
+    .await
+    .expect("should receive Mode event");
+    assert!(input.bracketed_paste);
+
+    let modes = parser.modes().await.unwrap();
+    assert_eq!(modes.input, input);
+    assert_eq!(modes.input.mouse_tracking, state::MouseTracking::Button);
+    assert_eq!(modes.input.mouse_encoding, state::MouseEncoding::Sgr);
+    assert_eq!(modes.keyboard.modify_other_keys, 2);
+    assert!(!modes.alternate_active);
+
+    let checkpoint = parser.checkpoint().await.unwrap();
+    let (_other_tx, other) = spawn_test_parser(80, 24, 1000).await;
+    other.restore(checkpoint).await.unwrap();
+    assert_eq!(other.modes().await.unwrap(), modes);
+}
+
 #[tokio::test]
 async fn test_screen_response_includes_line_indices() {
     let (tx, parser) = spawn_test_parser(80, 5, 100).await;
@@ -404,7 +583,7 @@ async fn test_screen_response_includes_line_indices() {
     tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
 
     let response = parser
-        .query(Query::Screen { format: Format::Plain })
+        .query(Query::Screen { format: Format::Plain, since: None })
         .await
         .unwrap();
 
@@ -419,6 +598,19 @@ async fn test_screen_response_includes_line_indices() {
     }
 }
 
+#[tokio::test]
+async fn test_screen_response_reports_output_offset() {
+    let (tx, parser) = spawn_test_parser(80, 24, 100).await;
+    tx.send(bytes::Bytes::from("hello ")).await.unwrap();
+    tx.send(bytes::Bytes::from("world")).await.unwrap();
+    wait_for_screen(&parser, "hello world").await;
+
+    match parser.query(Query::Screen { format: Format::Plain, since: None }).await.unwrap() {
+        QueryResponse::Screen(screen) => assert_eq!(screen.output_offset, 11),
+        _ => panic!("expected Screen response"),
+    }
+}
+
 #[tokio::test]
 async fn test_parser_channel_does_not_lose_data() {
     let (tx, parser) = spawn_test_parser(80, 24, 1000).await;
@@ -431,7 +623,7 @@ async fn test_parser_channel_does_not_lose_data() {
     tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
 
     let response = parser
-        .query(Query::Screen { format: Format::Plain })
+        .query(Query::Screen { format: Format::Plain, since: None })
         .await
         .unwrap();
 
@@ -444,3 +636,361 @@ async fn test_parser_channel_does_not_lose_data() {
         _ => panic!("expected Screen response"),
     }
 }
+
+/// Screen and full scrollback as JSON, for comparing terminal states.
+async fn dump_state(parser: &Parser) -> (serde_json::Value, serde_json::Value) {
+    let screen = parser.query(Query::Screen { format: Format::Styled, since: None }).await.unwrap();
+    let scrollback = parser
+        .query(Query::Scrollback { format: Format::Styled, offset: 0, limit: usize::MAX })
+        .await
+        .unwrap();
+    // The screen generation counts changes, not contents.
+    let mut screen = serde_json::to_value(screen).unwrap();
+    screen.as_object_mut().unwrap().remove("generation");
+    (screen, serde_json::to_value(scrollback).unwrap())
+}
+
+#[tokio::test]
+async fn test_parser_hibernate_round_trips_state() {
+    let (tx, parser) = spawn_test_parser(40, 5, 1000).await;
+    for i in 0..12 {
+        tx.send(bytes::Bytes::from(format!("\x1b[1;3{}mline {i}\x1b[0m plain\r\n", i % 8)))
+            .await
+            .unwrap();
+    }
+    tx.send(bytes::Bytes::from("$ par")).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    let before = dump_state(&parser).await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("snapshot");
+    parser.hibernate(path.clone()).await.unwrap();
+    assert!(path.exists());
+
+    // Reads come from the snapshot without loading it back.
+    assert_eq!(dump_state(&parser).await, before);
+    assert!(path.exists());
+
+    // Output wakes the parser and carries on where it left off.
+    tx.send(bytes::Bytes::from("tial")).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    let response = parser.query(Query::Screen { format: Format::Plain, since: None }).await.unwrap();
+    assert!(!path.exists());
+    match response {
+        QueryResponse::Screen(screen) => {
+            assert_eq!(serde_json::to_value(&screen.lines[4]).unwrap(), "$ partial");
+            assert_eq!(screen.cursor.col, 9);
+            assert_eq!(screen.total_lines, before.0["total_lines"]);
+        }
+        _ => panic!("expected Screen response"),
+    }
+}
+
+#[tokio::test]
+async fn test_parser_hibernate_keeps_primary_scrollback_under_alternate_screen() {
+    let (tx, parser) = spawn_test_parser(40, 5, 1000).await;
+    for i in 0..8 {
+        tx.send(bytes::Bytes::from(format!("history {i}\r\n"))).await.unwrap();
+    }
+    tx.send(bytes::Bytes::from("\x1b[?1049h\x1b[Hfull screen app")).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    let before = dump_state(&parser).await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("snapshot");
+    parser.hibernate(path.clone()).await.unwrap();
+    assert_eq!(dump_state(&parser).await, before);
+
+    // Leaving the alternate screen after waking shows the old history.
+    tx.send(bytes::Bytes::from("\x1b[?1049l")).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    let response = parser
+        .query(Query::Scrollback { format: Format::Plain, offset: 0, limit: 1 })
+        .await
+        .unwrap();
+    match response {
+        QueryResponse::Scrollback(scrollback) => {
+            assert_eq!(serde_json::to_value(&scrollback.lines[0]).unwrap(), "history 0");
+        }
+        _ => panic!("expected Scrollback response"),
+    }
+}
+
+#[tokio::test]
+async fn test_parser_checkpoint_restores_into_another_parser() {
+    let (tx, parser) = spawn_test_parser(40, 5, 1000).await;
+    for i in 0..12 {
+        tx.send(bytes::Bytes::from(format!("\x1b[1;3{}mline {i}\x1b[0m plain\r\n", i % 8)))
+            .await
+            .unwrap();
+    }
+    tx.send(bytes::Bytes::from("\x1b[?1h\x1b[>5u$ par")).await.unwrap();
+    wait_for_screen(&parser, "$ par").await;
+    let before = dump_state(&parser).await;
+
+    let checkpoint = parser.checkpoint().await.unwrap();
+    assert_eq!((checkpoint.cols, checkpoint.rows), (40, 5));
+    assert_eq!(checkpoint.total_lines, before.0["total_lines"]);
+    assert!(checkpoint.keyboard.application_cursor);
+    assert_eq!(checkpoint.keyboard.kitty_flags, 5);
+    // Taking a checkpoint leaves the terminal as it was.
+    assert_eq!(dump_state(&parser).await, before);
+
+    // Checkpoints survive serialization.
+    let json = serde_json::to_string(&checkpoint).unwrap();
+    let checkpoint: state::Checkpoint = serde_json::from_str(&json).unwrap();
+
+    let (other_tx, other) = spawn_test_parser(40, 5, 1000).await;
+    other_tx.send(bytes::Bytes::from("something else")).await.unwrap();
+    // The restore must not race the input above.
+    wait_for_screen(&other, "something else").await;
+    let mut events = other.subscribe();
+    other.restore(checkpoint).await.unwrap();
+    assert_eq!(dump_state(&other).await, before);
+    assert_eq!(other.keyboard_modes().await.unwrap().kitty_flags, 5);
+    loop {
+        match events.next().await {
+            Some(SubscriptionEvent::Event(events::Event::Reset { reason, .. })) => {
+                assert!(matches!(reason, events::ResetReason::Restore));
+                break;
+            }
+            Some(_) => continue,
+            None => panic!("expected a reset"),
+        }
+    }
+
+    // Output carries on from the restored cursor.
+    other_tx.send(bytes::Bytes::from("tial")).await.unwrap();
+    wait_for_screen(&other, "partial").await;
+    match other.query(Query::Screen { format: Format::Plain, since: None }).await.unwrap() {
+        QueryResponse::Screen(screen) => {
+            assert_eq!(serde_json::to_value(&screen.lines[4]).unwrap(), "$ partial");
+        }
+        _ => panic!("expected Screen response"),
+    }
+}
+
+#[tokio::test]
+async fn test_parser_restore_keeps_current_size() {
+    let (tx, parser) = spawn_test_parser(40, 5, 1000).await;
+    tx.send(bytes::Bytes::from("\x1b[?1049h\x1b[Hfull screen app")).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    let checkpoint = parser.checkpoint().await.unwrap();
+    assert!(checkpoint.alternate_active);
+
+    let (_other_tx, other) = spawn_test_parser(60, 10, 1000).await;
+    other.restore(checkpoint.clone()).await.unwrap();
+    match other.query(Query::Screen { format: Format::Plain, since: None }).await.unwrap() {
+        QueryResponse::Screen(screen) => {
+            assert_eq!((screen.cols, screen.rows), (60, 10));
+            assert!(screen.alternate_active);
+            assert_eq!(serde_json::to_value(&screen.lines[0]).unwrap(), "full screen app");
+        }
+        _ => panic!("expected Screen response"),
+    }
+
+    let empty = state::Checkpoint { cols: 0, ..checkpoint };
+    assert!(matches!(other.restore(empty).await, Err(ParserError::InvalidQuery(_))));
+}
+
+#[tokio::test]
+async fn test_parser_mark_survives_dropped_lines() {
+    let (tx, parser) = spawn_test_parser(20, 3, 10).await;
+
+    for i in 0..4 {
+        tx.send(bytes::Bytes::from(format!("before {}\r\n", i))).await.unwrap();
+    }
+    tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+    let mark = parser.mark().await.unwrap();
+    tx.send(bytes::Bytes::from("marked\r\n")).await.unwrap();
+    for i in 0..8 {
+        tx.send(bytes::Bytes::from(format!("after {}\r\n", i))).await.unwrap();
+    }
+    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+    let dropped = parser.lines_dropped();
+    assert!(dropped > 0, "the scrollback limit should have dropped lines");
+    let response = parser
+        .query(Query::Scrollback {
+            format: Format::Plain,
+            offset: (mark - dropped) as usize,
+            limit: 1,
+        })
+        .await
+        .unwrap();
+    match response {
+        QueryResponse::Scrollback(scrollback) => {
+            assert!(
+                matches!(scrollback.lines.as_slice(), [state::FormattedLine::Plain(text)] if text == "marked"),
+                "unexpected lines: {:?}",
+                scrollback.lines
+            );
+        }
+        _ => panic!("expected Scrollback response"),
+    }
+}
+
+#[tokio::test]
+async fn test_parser_output_follows_cursor() {
+    let (tx, parser) = spawn_test_parser(20, 3, 4).await;
+
+    tx.send(bytes::Bytes::from("one\r\ntwo\r\nthr")).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+
+    // The line being written isn't returned until it is finished.
+    let output = parser.output(None, 100).await.unwrap();
+    assert_eq!(output.lines, ["one", "two"]);
+    assert_eq!((output.missed, output.more), (0, false));
+    let partial = parser.output(Some(output.next), 100).await.unwrap();
+    assert!(partial.lines.is_empty());
+    assert_eq!(partial.next, output.next);
+
+    tx.send(bytes::Bytes::from("ee\r\nfour\r\n")).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+    let output = parser.output(Some(output.next), 1).await.unwrap();
+    assert_eq!(output.lines, ["three"]);
+    assert!(output.more);
+    let output = parser.output(Some(output.next), 100).await.unwrap();
+    assert_eq!(output.lines, ["four"]);
+
+    // Lines dropped before they were read are counted, not repeated.
+    for i in 0..10 {
+        tx.send(bytes::Bytes::from(format!("line {}\r\n", i))).await.unwrap();
+    }
+    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    let output = parser.output(Some(output.next), 100).await.unwrap();
+    assert!(output.missed > 0);
+    assert_eq!(output.lines.last().map(String::as_str), Some("line 9"));
+    assert_eq!(output.lines.len() as u64 + output.missed, 10);
+}
+
+async fn screen_since(parser: &Parser, since: Option<u64>) -> state::ScreenResponse {
+    match parser.query(Query::Screen { format: Format::Plain, since }).await.unwrap() {
+        QueryResponse::Screen(screen) => screen,
+        _ => panic!("expected Screen response"),
+    }
+}
+
+#[tokio::test]
+async fn test_parser_screen_since_generation_returns_changed_rows() {
+    let (tx, parser) = spawn_test_parser(20, 4, 1000).await;
+    tx.send(bytes::Bytes::from("a\r\nb\r\nc")).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    let full = screen_since(&parser, None).await;
+    assert_eq!(full.lines.len(), 4);
+    assert_eq!(full.changed_rows, None);
+
+    tx.send(bytes::Bytes::from("\x1b[2;1Hbee")).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    let delta = screen_since(&parser, Some(full.generation)).await;
+    assert_eq!(delta.changed_rows, Some(vec![1]));
+    assert_eq!(serde_json::to_value(&delta.lines).unwrap(), serde_json::json!(["bee"]));
+    assert_eq!(delta.cursor.col, 3);
+
+    // Nothing changed since the delta; moving the cursor changes no rows.
+    tx.send(bytes::Bytes::from("\x1b[H")).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    let unchanged = screen_since(&parser, Some(delta.generation)).await;
+    assert_eq!(unchanged.generation, delta.generation);
+    assert_eq!(unchanged.changed_rows, Some(vec![]));
+    assert!(unchanged.lines.is_empty());
+    assert_eq!(unchanged.cursor.col, 0);
+
+    // A resize changes every row.
+    parser.resize(20, 3).await.unwrap();
+    let resized = screen_since(&parser, Some(delta.generation)).await;
+    assert_eq!(resized.changed_rows, Some(vec![0, 1, 2]));
+
+    // A generation the screen hasn't reached gets the whole screen.
+    let unknown = screen_since(&parser, Some(resized.generation + 1)).await;
+    assert_eq!(unknown.changed_rows, None);
+    assert_eq!(unknown.lines.len(), 3);
+}
+
+#[tokio::test]
+async fn test_parser_batches_queued_output() {
+    let (tx, parser) = spawn_test_parser(20, 4, 1000).await;
+    let mut events = parser.subscribe();
+    // Queue a burst before the parser task gets to run.
+    for i in 0..50 {
+        tx.try_send(bytes::Bytes::from(format!("\rframe {i:02}"))).unwrap();
+    }
+    tx.send(bytes::Bytes::from("\x1b[?25l")).await.unwrap();
+
+    let mut frames = Vec::new();
+    tokio::time::timeout(tokio::time::Duration::from_secs(2), async {
+        while let Some(SubscriptionEvent::Event(event)) = events.next().await {
+            match event {
+                Event::Line { index: 0, line, .. } => frames.push(serde_json::to_value(&line).unwrap()),
+                Event::Cursor { visible: false, .. } => break,
+                _ => {}
+            }
+        }
+    })
+    .await
+    .expect("cursor hidden");
+    // The row was rewritten 50 times but reported with its final contents.
+    assert_eq!(frames, vec![serde_json::json!([{ "text": "frame 49" }])]);
+}
+
+/// An iTerm2 inline image of a 40x20 pixel PNG, drawn `width` cells wide.
+fn iterm2_image(width: usize) -> String {
+    use base64::Engine;
+    let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x28\0\0\0\x14";
+    let data = base64::engine::general_purpose::STANDARD.encode(png);
+    format!("\x1b]1337;File=inline=1;width={width}:{data}\x07")
+}
+
+#[tokio::test]
+async fn test_parser_inline_images_move_the_cursor_and_are_placed() {
+    let (tx, parser) = spawn_test_parser(40, 10, 1000).await;
+    // 8 cells wide keeps the 2:1 aspect ratio at 2 rows high.
+    let sixel = "\x1bPq\"1;1;24;32#0~~~\x1b\\";
+    let output = format!("top\r\n  {}after\r\n{}below", iterm2_image(8), sixel);
+    tx.send(bytes::Bytes::from(output)).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+    let screen = screen_since(&parser, None).await;
+    let lines = serde_json::to_value(&screen.lines).unwrap();
+    // The iTerm2 image leaves the cursor right of its last row; the Sixel
+    // one (2 rows of 16 pixels) below it, at the same column.
+    assert_eq!(lines[2], "          after");
+    assert_eq!(lines[5], "below");
+    let placed: Vec<_> = screen.images.iter().map(|i| (i.row, i.col, i.rows, i.cols)).collect();
+    assert_eq!(placed, vec![(1, 2, 2, 8), (3, 0, 2, 3)]);
+
+    let images = parser.images();
+    assert_eq!(images.len(), 2);
+    assert_eq!(images[0].content_type, "image/png");
+    assert_eq!((images[0].width_px, images[0].height_px), (Some(40), Some(20)));
+    let sixel_image = parser.image(images[1].id).unwrap();
+    assert_eq!(&sixel_image.data[..], sixel.as_bytes());
+
+    let redraw = parser.redraw_images(&screen);
+    assert!(redraw.starts_with("\x1b[2;3H\x1b]1337;File=inline=1;width=8:"));
+    assert!(redraw.ends_with(&format!("\x1b[4;1H{}", sixel)));
+}
+
+#[tokio::test]
+async fn test_parser_inline_images_are_forgotten_with_their_screen() {
+    let (tx, parser) = spawn_test_parser(40, 10, 1000).await;
+    tx.send(bytes::Bytes::from(iterm2_image(4))).await.unwrap();
+    tx.send(bytes::Bytes::from(format!("\x1b[?1049h{}", iterm2_image(6)))).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    let screen = screen_since(&parser, None).await;
+    assert_eq!(screen.images.len(), 1);
+    assert_eq!(screen.images[0].cols, 6);
+    assert_eq!(parser.images().len(), 2);
+
+    // Leaving the alternate screen drops its image; the primary one shows again.
+    tx.send(bytes::Bytes::from("\x1b[?1049l")).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    let screen = screen_since(&parser, None).await;
+    assert_eq!(screen.images.len(), 1);
+    assert_eq!(screen.images[0].cols, 4);
+
+    tx.send(bytes::Bytes::from("\x1b[H\x1b[2J")).await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    assert!(screen_since(&parser, None).await.images.is_empty());
+    assert!(parser.images().is_empty());
+}
@@ -0,0 +1,149 @@
+//! Window title and working directory sequences.
+//!
+//! Shells and full-screen programs name the terminal window with OSC 0 or
+//! OSC 2 (`OSC 2 ; title ST`), and many shells report their directory
+//! after each command with OSC 7 (`OSC 7 ; file://host/path ST`). The
+//! parser keeps the latest of each so session listings can show what a
+//! long-lived shell is doing rather than the command it was started with.
+
+use super::osc::OscScanner;
+
+/// Longest title or `file://` URL we buffer before giving up on a sequence.
+const MAX_OSC: usize = 4096;
+
+/// The title and working directory last reported by the program.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TitleState {
+    pub title: Option<String>,
+    pub cwd: Option<String>,
+}
+
+/// A title or directory change found in the output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum TitleChange {
+    /// OSC 0 or 2. An empty title clears it.
+    Title(Option<String>),
+    /// OSC 7, as a local path.
+    Cwd(String),
+}
+
+impl TitleState {
+    /// Apply `change`, returning whether anything changed.
+    pub(super) fn apply(&mut self, change: TitleChange) -> bool {
+        let (slot, value) = match change {
+            TitleChange::Title(title) => (&mut self.title, title),
+            TitleChange::Cwd(cwd) => (&mut self.cwd, Some(cwd)),
+        };
+        if *slot == value {
+            return false;
+        }
+        *slot = value;
+        true
+    }
+}
+
+/// Scanner for OSC 0, 2 and 7. Carries partial sequences across chunk
+/// boundaries.
+pub(super) struct TitleScanner {
+    osc: OscScanner,
+}
+
+impl TitleScanner {
+    pub(super) fn new() -> Self {
+        Self {
+            osc: OscScanner::new(&["0;", "2;", "7;"], MAX_OSC),
+        }
+    }
+
+    /// Feed a chunk of output. Returns the changes completed in it, oldest
+    /// first. OSC 7 URLs that aren't `file://` are ignored.
+    pub(super) fn feed(&mut self, text: &str) -> Vec<TitleChange> {
+        self.osc
+            .feed(text)
+            .into_iter()
+            .filter_map(|(_, body)| parse(&body))
+            .collect()
+    }
+}
+
+fn parse(body: &[u8]) -> Option<TitleChange> {
+    let body = String::from_utf8_lossy(body);
+    let (ps, pt) = body.split_once(';')?;
+    match ps {
+        "0" | "2" => {
+            let title: String = pt.chars().filter(|c| !c.is_control()).collect();
+            Some(TitleChange::Title((!title.is_empty()).then_some(title)))
+        }
+        "7" => file_url_path(pt).map(TitleChange::Cwd),
+        _ => None,
+    }
+}
+
+/// The path of a `file://host/path` URL, percent-decoded.
+fn file_url_path(url: &str) -> Option<String> {
+    let rest = url.strip_prefix("file://")?;
+    let path = &rest[rest.find('/')?..];
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut iter = path.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    let path = String::from_utf8_lossy(&bytes);
+    if path.chars().any(char::is_control) {
+        return None;
+    }
+    Some(path.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn title(t: &str) -> TitleChange {
+        TitleChange::Title(Some(t.to_string()))
+    }
+
+    #[test]
+    fn scanner_finds_titles_and_directories_across_chunks() {
+        let mut s = TitleScanner::new();
+        assert_eq!(s.feed("a\x1b]0;vim notes.txt\x07b"), vec![title("vim notes.txt")]);
+        assert!(s.feed("\x1b]7;file://box/home/al%20ice").is_empty());
+        assert_eq!(
+            s.feed("\x1b\\\x1b]2;\x07"),
+            vec![TitleChange::Cwd("/home/al ice".to_string()), TitleChange::Title(None)]
+        );
+    }
+
+    #[test]
+    fn scanner_ignores_other_sequences_and_bad_urls() {
+        let mut s = TitleScanner::new();
+        assert!(s.feed("\x1b]1;icon\x07\x1b]52;c;aGk=\x07").is_empty());
+        assert!(s.feed("\x1b]7;https://example.com/x\x07").is_empty());
+        assert!(s.feed("\x1b]7;file://box\x07").is_empty());
+        assert!(s.feed("\x1b]7;file://box/a%2\x07").is_empty());
+        assert!(s.feed("\x1b]7;file://box/a%0Ab\x07").is_empty());
+        assert_eq!(s.feed("\x1b]7;file:///tmp\x07"), vec![TitleChange::Cwd("/tmp".to_string())]);
+    }
+
+    #[test]
+    fn apply_reports_only_changes() {
+        let mut state = TitleState::default();
+        assert!(state.apply(title("bash")));
+        assert!(!state.apply(title("bash")));
+        assert!(state.apply(TitleChange::Cwd("/tmp".to_string())));
+        assert!(state.apply(TitleChange::Title(None)));
+        assert_eq!(
+            state,
+            TitleState {
+                title: None,
+                cwd: Some("/tmp".to_string()),
+            }
+        );
+    }
+}
@@ -0,0 +1,387 @@
+//! Pipes that tee a session's output elsewhere.
+//!
+//! `POST /sessions/{name}/pipe` copies everything the session outputs from
+//! then on into another session's input, or into the stdin of a command
+//! the server starts, such as piping a build session into one running a
+//! log filter. A pipe is one more subscriber of the source session's
+//! [`Broker`](crate::broker::Broker), so like a streaming client it loses
+//! output when it falls more than the ring's capacity behind; the bytes
+//! lost are counted in [`PipeInfo::dropped`].
+//!
+//! A pipe ends when it is deleted, when the source or target session ends,
+//! or when the command exits. A command's stdin is closed when its pipe
+//! ends, and it is killed if it hasn't exited [`EXIT_GRACE`] later.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio_util::sync::CancellationToken;
+
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::broker::Subscriber;
+use crate::input::InputSource;
+use crate::session::Session;
+
+/// Most pipes out of one session.
+pub const MAX_PIPES: usize = 8;
+
+/// How long a command gets to exit after its stdin is closed.
+pub const EXIT_GRACE: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// What a pipe does to the output before passing it on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    /// The output as it is.
+    #[default]
+    Raw,
+    /// Escape sequences removed; control characters kept.
+    StripAnsi,
+    /// Text only: escape sequences and control characters other than
+    /// newline and tab removed, so lines end in `\n`.
+    Plain,
+}
+
+/// Where a pipe sends the output.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PipeTarget {
+    Session { session: String },
+    Command { command: String },
+}
+
+/// A running pipe.
+#[derive(Debug, Clone, Serialize)]
+pub struct PipeInfo {
+    pub id: u64,
+    pub target: PipeTarget,
+    pub filter: Filter,
+    /// Bytes passed on, after filtering.
+    pub bytes: u64,
+    /// Bytes of output lost because the pipe fell behind.
+    pub dropped: u64,
+    /// Unix time the pipe started, in milliseconds.
+    pub created_at: u64,
+}
+
+/// Where to start a pipe to.
+pub enum Sink {
+    Session(Box<Session>),
+    /// A command line, run with `/bin/sh -c`.
+    Command(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PipeError {
+    #[error("a session can't be piped into itself")]
+    SameSession,
+    #[error("a session can have at most {MAX_PIPES} pipes")]
+    TooMany,
+    #[error("command must not be empty")]
+    EmptyCommand,
+    #[error("failed to start command: {0}")]
+    Spawn(#[from] std::io::Error),
+}
+
+/// The pipes out of a session, by ID. Clones share the same set.
+#[derive(Clone, Default)]
+pub struct Pipes {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_id: u64,
+    pipes: BTreeMap<u64, Entry>,
+}
+
+struct Entry {
+    target: PipeTarget,
+    filter: Filter,
+    created_at: u64,
+    counters: Arc<Counters>,
+    stop: CancellationToken,
+}
+
+#[derive(Default)]
+struct Counters {
+    bytes: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl Entry {
+    fn info(&self, id: u64) -> PipeInfo {
+        PipeInfo {
+            id,
+            target: self.target.clone(),
+            filter: self.filter,
+            bytes: self.counters.bytes.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+            created_at: self.created_at,
+        }
+    }
+}
+
+impl Pipes {
+    /// Running pipes, oldest first.
+    pub fn list(&self) -> Vec<PipeInfo> {
+        self.inner.lock().pipes.iter().map(|(&id, entry)| entry.info(id)).collect()
+    }
+
+    /// Stop a pipe. Returns `false` if there is none with this ID.
+    pub fn stop(&self, id: u64) -> bool {
+        match self.inner.lock().pipes.remove(&id) {
+            Some(entry) => {
+                entry.stop.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stop every pipe.
+    pub fn stop_all(&self) {
+        for (_, entry) in std::mem::take(&mut self.inner.lock().pipes) {
+            entry.stop.cancel();
+        }
+    }
+}
+
+/// Start piping `source`'s output from now on into `sink`.
+pub fn start(source: &Session, sink: Sink, filter: Filter) -> Result<PipeInfo, PipeError> {
+    let mut inner = source.pipes.inner.lock();
+    if inner.pipes.len() >= MAX_PIPES {
+        return Err(PipeError::TooMany);
+    }
+    // Cancelled when the pipe ends, for whatever reason.
+    let stop = CancellationToken::new();
+    let (target, writer, gone) = match sink {
+        Sink::Session(target) => {
+            if target.name == source.name {
+                return Err(PipeError::SameSession);
+            }
+            let gone = target.cancelled.clone();
+            let name = target.name.clone();
+            let writer = Writer::Session { target, from: source.name.clone() };
+            (PipeTarget::Session { session: name }, writer, gone)
+        }
+        Sink::Command(command) => {
+            if command.trim().is_empty() {
+                return Err(PipeError::EmptyCommand);
+            }
+            let mut child = tokio::process::Command::new("/bin/sh")
+                .arg("-c")
+                .arg(&command)
+                .stdin(std::process::Stdio::piped())
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .kill_on_drop(true)
+                .spawn()?;
+            let stdin = child.stdin.take().expect("stdin is piped");
+            let gone = CancellationToken::new();
+            tokio::spawn(reap(child, stop.clone(), gone.clone()));
+            (PipeTarget::Command { command }, Writer::Command(stdin), gone)
+        }
+    };
+
+    inner.next_id += 1;
+    let id = inner.next_id;
+    let entry = Entry {
+        target,
+        filter,
+        created_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+        counters: Arc::default(),
+        stop: stop.clone(),
+    };
+    let info = entry.info(id);
+    let pipe = Pipe {
+        output: source.output_rx.subscribe(),
+        pipes: source.pipes.clone(),
+        id,
+        writer,
+        stripper: (filter != Filter::Raw).then(|| Stripper::new(filter == Filter::Plain)),
+        counters: entry.counters.clone(),
+    };
+    inner.pipes.insert(id, entry);
+    tokio::spawn(pipe.run(stop, gone, source.cancelled.clone()));
+    Ok(info)
+}
+
+/// Wait for a command to exit, or once its pipe ends and its stdin is
+/// closed, give it [`EXIT_GRACE`] to exit before killing it.
+async fn reap(mut child: tokio::process::Child, stop: CancellationToken, gone: CancellationToken) {
+    tokio::select! {
+        _ = child.wait() => {}
+        _ = stop.cancelled() => {
+            if tokio::time::timeout(EXIT_GRACE, child.wait()).await.is_err() {
+                let _ = child.kill().await;
+            }
+        }
+    }
+    gone.cancel();
+}
+
+struct Pipe {
+    output: Subscriber,
+    pipes: Pipes,
+    id: u64,
+    writer: Writer,
+    stripper: Option<Stripper>,
+    counters: Arc<Counters>,
+}
+
+enum Writer {
+    Session { target: Box<Session>, from: String },
+    Command(tokio::process::ChildStdin),
+}
+
+impl Writer {
+    /// Pass `data` on. `false` if the target can't take any more.
+    async fn write(&mut self, data: Bytes) -> bool {
+        match self {
+            Writer::Session { target, from } => {
+                if let Err(e) = target.policy.check_input(&data) {
+                    tracing::warn!(session = %target.name, from = %from, "pipe input blocked: {e}");
+                    return false;
+                }
+                target.input_broadcaster.record_input(InputSource::Pipe { from: from.clone() }, &data);
+                if target.input_tx.send(data).await.is_err() {
+                    return false;
+                }
+                target.activity.touch();
+                true
+            }
+            Writer::Command(stdin) => stdin.write_all(&data).await.is_ok(),
+        }
+    }
+}
+
+impl Pipe {
+    /// Copy output until the pipe is stopped, the source ends, or the
+    /// target goes away.
+    async fn run(mut self, stop: CancellationToken, gone: CancellationToken, source_gone: CancellationToken) {
+        loop {
+            let data = tokio::select! {
+                _ = stop.cancelled() => break,
+                _ = gone.cancelled() => break,
+                _ = source_gone.cancelled() => break,
+                data = self.output.recv() => match data {
+                    Ok(data) => data,
+                    Err(RecvError::Lagged(n)) => {
+                        self.counters.dropped.fetch_add(n, Ordering::Relaxed);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            };
+            let data = match &mut self.stripper {
+                Some(stripper) => Bytes::from(stripper.strip(&data)),
+                None => data,
+            };
+            if data.is_empty() {
+                continue;
+            }
+            let len = data.len() as u64;
+            let written = tokio::select! {
+                _ = stop.cancelled() => break,
+                _ = gone.cancelled() => break,
+                written = self.writer.write(data) => written,
+            };
+            if !written {
+                break;
+            }
+            self.counters.bytes.fetch_add(len, Ordering::Relaxed);
+        }
+        // Close a command's stdin before `reap` waits for it.
+        drop(self.writer);
+        stop.cancel();
+        self.pipes.inner.lock().pipes.remove(&self.id);
+    }
+}
+
+/// Removes escape sequences from output that may split them across
+/// chunks.
+struct Stripper {
+    state: State,
+    /// Also drop control characters other than newline and tab.
+    plain: bool,
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Ground,
+    /// After ESC, or ESC and intermediate bytes.
+    Escape,
+    /// In a CSI sequence, until its final byte.
+    Csi,
+    /// In an OSC, DCS, SOS, PM or APC string, until BEL or ST.
+    Str,
+    /// After ESC inside a string.
+    StrEscape,
+}
+
+impl Stripper {
+    fn new(plain: bool) -> Self {
+        Self { state: State::Ground, plain }
+    }
+
+    fn strip(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len());
+        for &b in data {
+            self.state = match (self.state, b) {
+                (_, 0x1b) if !matches!(self.state, State::Str) => State::Escape,
+                (State::Ground, _) => {
+                    let control = (b < 0x20 && b != b'\n' && b != b'\t') || b == 0x7f;
+                    if !(self.plain && control) {
+                        out.push(b);
+                    }
+                    State::Ground
+                }
+                (State::Escape, b'[') => State::Csi,
+                (State::Escape, b']' | b'P' | b'X' | b'^' | b'_') => State::Str,
+                (State::Escape, 0x20..=0x2f) => State::Escape,
+                (State::Escape, _) => State::Ground,
+                (State::Csi, 0x40..=0x7e) => State::Ground,
+                (State::Csi, _) => State::Csi,
+                (State::Str, 0x07) => State::Ground,
+                (State::Str, 0x1b) => State::StrEscape,
+                (State::Str, _) => State::Str,
+                (State::StrEscape, b'\\') => State::Ground,
+                (State::StrEscape, _) => State::Str,
+            };
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(plain: bool, chunks: &[&[u8]]) -> Vec<u8> {
+        let mut stripper = Stripper::new(plain);
+        chunks.iter().flat_map(|chunk| stripper.strip(chunk)).collect()
+    }
+
+    #[test]
+    fn strips_sequences_split_across_chunks() {
+        let out = strip(false, &[b"\x1b[1;3", b"1mred\x1b", b"[0m\r\n\x1b]0;ti", b"tle\x07ok\x1b(Bx"]);
+        assert_eq!(out, b"red\r\nokx");
+    }
+
+    #[test]
+    fn plain_drops_control_characters() {
+        let out = strip(true, &[b"a\tb\r\n\x08c\x1bP1$r\x1b\\d"]);
+        assert_eq!(out, b"a\tb\ncd");
+    }
+}
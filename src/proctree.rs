@@ -0,0 +1,241 @@
+//! Resource usage of a session's process tree, read from `/proc`.
+//!
+//! A session's child is usually a shell, and the interesting work happens
+//! in its descendants (a build, a test runner, a server). [`sample`] walks
+//! the tree below the session's PID and reports CPU, memory, and file
+//! descriptors for each process, so a runaway one can be found without
+//! shelling into the session. Only Linux is supported; elsewhere the tree
+//! is always empty.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// Longest command line reported per process.
+const MAX_COMMAND_LEN: usize = 512;
+
+/// One process in a session's tree.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub ppid: u32,
+    /// Executable name as the kernel reports it (at most 15 bytes).
+    pub name: String,
+    /// Full command line, space-separated. Empty for kernel threads and
+    /// zombies.
+    pub command: String,
+    /// Single-letter scheduler state (`R`, `S`, `D`, `Z`, ...).
+    pub state: String,
+    /// CPU usage over the sampling interval; 100 is one full core.
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+    /// `None` if the descriptor table can't be read (e.g. another user).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_fds: Option<usize>,
+    pub threads: u32,
+}
+
+/// Totals across a process tree, plus the processes themselves (root
+/// first, then descendants in breadth-first order).
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TreeStats {
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+    pub open_fds: usize,
+    pub processes: Vec<ProcessInfo>,
+}
+
+/// Fields of `/proc/<pid>/stat` we use.
+struct Stat {
+    name: String,
+    state: String,
+    ppid: u32,
+    /// User plus system time, in clock ticks.
+    cpu_ticks: u64,
+    threads: u32,
+    rss_pages: u64,
+}
+
+/// Sample the tree rooted at `root` over `interval`.
+///
+/// CPU usage needs two readings, so this blocks for `interval`; call it
+/// from a blocking context.
+pub fn sample(root: u32, interval: Duration) -> TreeStats {
+    let before: HashMap<u32, u64> = descendants(root)
+        .into_iter()
+        .filter_map(|pid| Some((pid, read_stat(pid)?.cpu_ticks)))
+        .collect();
+    let start = Instant::now();
+    std::thread::sleep(interval);
+    let elapsed = start.elapsed().as_secs_f64();
+
+    // SAFETY: sysconf has no preconditions.
+    let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as f64;
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(0) as u64;
+
+    let mut stats = TreeStats::default();
+    for pid in descendants(root) {
+        let Some(stat) = read_stat(pid) else {
+            continue;
+        };
+        // A process that started during the interval used all of its
+        // ticks within it.
+        let used = stat.cpu_ticks.saturating_sub(before.get(&pid).copied().unwrap_or(0));
+        let cpu_percent = if elapsed > 0.0 {
+            (used as f64 / ticks_per_sec / elapsed * 1000.0).round() / 10.0
+        } else {
+            0.0
+        };
+        let open_fds = std::fs::read_dir(format!("/proc/{pid}/fd")).ok().map(|d| d.count());
+        let info = ProcessInfo {
+            pid,
+            ppid: stat.ppid,
+            name: stat.name,
+            command: read_cmdline(pid),
+            state: stat.state,
+            cpu_percent,
+            rss_bytes: stat.rss_pages * page_size,
+            open_fds,
+            threads: stat.threads,
+        };
+        stats.cpu_percent += info.cpu_percent;
+        stats.rss_bytes += info.rss_bytes;
+        stats.open_fds += info.open_fds.unwrap_or(0);
+        stats.processes.push(info);
+    }
+    stats.cpu_percent = (stats.cpu_percent * 10.0).round() / 10.0;
+    stats
+}
+
+/// `root` and every live process below it, breadth-first.
+fn descendants(root: u32) -> Vec<u32> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    let mut root_alive = false;
+    for entry in entries.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse::<u32>().ok()) else {
+            continue;
+        };
+        let Some(stat) = read_stat(pid) else {
+            continue;
+        };
+        root_alive |= pid == root;
+        children.entry(stat.ppid).or_default().push(pid);
+    }
+    if !root_alive {
+        return Vec::new();
+    }
+
+    let mut tree = vec![root];
+    let mut i = 0;
+    while i < tree.len() {
+        if let Some(kids) = children.get_mut(&tree[i]) {
+            kids.sort_unstable();
+            tree.extend(kids.iter().copied());
+        }
+        i += 1;
+    }
+    tree
+}
+
+fn read_stat(pid: u32) -> Option<Stat> {
+    let raw = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    parse_stat(&raw)
+}
+
+/// Parse `/proc/<pid>/stat`. The name is parenthesized and may itself
+/// contain spaces and parentheses, so split at the last `)`.
+fn parse_stat(raw: &str) -> Option<Stat> {
+    let open = raw.find('(')?;
+    let close = raw.rfind(')')?;
+    let name = raw.get(open + 1..close)?.to_string();
+    let fields: Vec<&str> = raw.get(close + 1..)?.split_whitespace().collect();
+    // Field numbers from proc(5), offset so that `state` (field 3) is 0.
+    let field = |n: usize| fields.get(n - 3).copied();
+    let num = |n: usize| field(n)?.parse::<u64>().ok();
+    Some(Stat {
+        name,
+        state: field(3)?.to_string(),
+        ppid: num(4)? as u32,
+        cpu_ticks: num(14)? + num(15)?,
+        threads: num(20)? as u32,
+        rss_pages: num(24)?,
+    })
+}
+
+fn read_cmdline(pid: u32) -> String {
+    let Ok(raw) = std::fs::read(format!("/proc/{pid}/cmdline")) else {
+        return String::new();
+    };
+    let mut command = raw
+        .split(|&b| b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(String::from_utf8_lossy)
+        .collect::<Vec<_>>()
+        .join(" ");
+    if command.len() > MAX_COMMAND_LEN {
+        let mut end = MAX_COMMAND_LEN;
+        while !command.is_char_boundary(end) {
+            end -= 1;
+        }
+        command.truncate(end);
+    }
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_stat_handles_odd_names() {
+        let raw = "4242 (my (odd) name) S 1 4242 4242 0 -1 4194560 100 0 0 0 \
+                   7 3 0 0 20 0 2 0 12345 1000000 250 18446744073709551615";
+        let stat = parse_stat(raw).unwrap();
+        assert_eq!(stat.name, "my (odd) name");
+        assert_eq!(stat.state, "S");
+        assert_eq!(stat.ppid, 1);
+        assert_eq!(stat.cpu_ticks, 10);
+        assert_eq!(stat.threads, 2);
+        assert_eq!(stat.rss_pages, 250);
+        assert!(parse_stat("garbage").is_none());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn sample_finds_descendants() {
+        let mut child = std::process::Command::new("/bin/sh")
+            .args(["-c", "sleep 30 & wait"])
+            .spawn()
+            .unwrap();
+        let root = child.id();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut stats = sample(root, Duration::from_millis(10));
+        while stats.processes.len() < 2 && Instant::now() < deadline {
+            stats = sample(root, Duration::from_millis(10));
+        }
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        assert_eq!(stats.processes[0].pid, root);
+        let sleep = stats.processes.iter().find(|p| p.name == "sleep").expect("sleep in tree");
+        assert_eq!(sleep.ppid, root);
+        assert_eq!(sleep.command, "sleep 30");
+        assert!(stats.rss_bytes > 0);
+        assert!(stats.open_fds >= 3);
+
+        // Reap the orphaned sleep.
+        let _ = std::process::Command::new("kill").arg(sleep.pid.to_string()).status();
+    }
+
+    #[test]
+    fn sample_of_missing_process_is_empty() {
+        let stats = sample(u32::MAX, Duration::ZERO);
+        assert!(stats.processes.is_empty());
+        assert_eq!(stats.rss_bytes, 0);
+    }
+}
@@ -46,6 +46,9 @@ pub enum FrameType {
     ShutdownServer = 0x18,
     ShutdownServerResponse = 0x19,
 
+    // Server notice shown to attached clients (JSON payload, server → client)
+    Banner = 0x1A,
+
     // Keepalive frames (empty payload)
     Ping = 0x14,
     Pong = 0x15,
@@ -61,6 +64,22 @@ pub enum FrameType {
     ReloadConfigResponse = 0x27,
     ServerInfo = 0x28,
     ServerInfoResponse = 0x29,
+
+    // Session output frames (JSON payload)
+    ReadOutput = 0x2A,
+    ReadOutputResponse = 0x2B,
+
+    // Session rename frames (JSON payload)
+    RenameSession = 0x2C,
+    RenameSessionResponse = 0x2D,
+
+    // Session management frames (JSON payload)
+    SessionRequest = 0x2E,
+    SessionRequestResponse = 0x2F,
+
+    // Server persistence frames (JSON payload)
+    SetPersist = 0x30,
+    SetPersistResponse = 0x31,
 }
 
 impl FrameType {
@@ -89,6 +108,7 @@ impl FrameType {
             0x17 => Some(Self::ManageTagsResponse),
             0x18 => Some(Self::ShutdownServer),
             0x19 => Some(Self::ShutdownServerResponse),
+            0x1A => Some(Self::Banner),
             0x14 => Some(Self::Ping),
             0x15 => Some(Self::Pong),
             0x20 => Some(Self::ListServers),
@@ -101,6 +121,14 @@ impl FrameType {
             0x27 => Some(Self::ReloadConfigResponse),
             0x28 => Some(Self::ServerInfo),
             0x29 => Some(Self::ServerInfoResponse),
+            0x2A => Some(Self::ReadOutput),
+            0x2B => Some(Self::ReadOutputResponse),
+            0x2C => Some(Self::RenameSession),
+            0x2D => Some(Self::RenameSessionResponse),
+            0x2E => Some(Self::SessionRequest),
+            0x2F => Some(Self::SessionRequestResponse),
+            0x30 => Some(Self::SetPersist),
+            0x31 => Some(Self::SetPersistResponse),
             _ => None,
         }
     }
@@ -251,6 +279,7 @@ impl Frame {
 
 /// Client → Server: request to create a new session.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct CreateSessionMsg {
     pub name: Option<String>,
     pub command: Option<String>,
@@ -268,6 +297,7 @@ pub struct CreateSessionMsg {
 
 /// Server → Client: response after session creation.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct CreateSessionResponseMsg {
     pub name: String,
     #[serde(default)]
@@ -275,19 +305,32 @@ pub struct CreateSessionResponseMsg {
     pub pid: Option<u32>,
     pub rows: u16,
     pub cols: u16,
+    /// Server message of the day, for the client to display.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motd: Option<String>,
+    /// How the client should handle its local keys. Older servers omit
+    /// this, and clients fall back to the default policy.
+    #[serde(default)]
+    pub client_policy: ClientPolicy,
 }
 
 /// Client → Server: request to attach to an existing session.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct AttachSessionMsg {
     pub name: String,
     pub scrollback: ScrollbackRequest,
+    /// Deliver scrollback as parsed lines in this format instead of raw ANSI
+    /// bytes. `None` keeps the raw replay.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scrollback_format: Option<crate::parser::state::Format>,
     pub rows: u16,
     pub cols: u16,
 }
 
 /// How much scrollback to replay on attach.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 #[serde(rename_all = "snake_case")]
 pub enum ScrollbackRequest {
     None,
@@ -297,15 +340,18 @@ pub enum ScrollbackRequest {
 
 /// Server → Client: response after attaching to a session.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct AttachSessionResponseMsg {
     pub name: String,
     pub rows: u16,
     pub cols: u16,
     /// Raw terminal bytes for scrollback replay (base64-encoded in JSON).
     #[serde(with = "base64_bytes")]
+    #[cfg_attr(feature = "schemas", schemars(with = "String"))]
     pub scrollback: Vec<u8>,
     /// Raw terminal bytes for current screen state (base64-encoded in JSON).
     #[serde(with = "base64_bytes")]
+    #[cfg_attr(feature = "schemas", schemars(with = "String"))]
     pub screen: Vec<u8>,
     /// Current input routing mode (passthrough or capture).
     #[serde(default)]
@@ -316,17 +362,112 @@ pub struct AttachSessionResponseMsg {
     /// ID of the currently focused overlay/panel, if any.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub focused_id: Option<String>,
+    /// Parsed scrollback lines, present when the request set
+    /// `scrollback_format`. `scrollback` is empty in that case.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scrollback_lines: Option<Vec<crate::parser::state::FormattedLine>>,
+    /// Server message of the day, for the client to display.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motd: Option<String>,
+    /// How the client should handle its local keys.
+    #[serde(default)]
+    pub client_policy: ClientPolicy,
+}
+
+/// Local key handling that the server asks attach clients to follow, so
+/// that every client version behaves the same way against this server.
+///
+/// Also used as the `[client]` section of the server config file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[serde(default)]
+pub struct ClientPolicy {
+    /// Keys that detach the client when pressed twice in quick succession,
+    /// named as for `POST /sessions/:name/input/keys` (e.g. `"ctrl+\\"`).
+    /// Empty disables the detach shortcut.
+    pub detach_keys: Vec<String>,
+    /// Whether Ctrl+\ toggles input capture mode. When false, Ctrl+\ is
+    /// delivered to the application like any other key.
+    pub allow_capture_toggle: bool,
+    /// Key that opens the client's command palette (e.g. `"ctrl+a"`), like
+    /// tmux's prefix key. None disables the palette.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command_key: Option<String>,
+    /// Whether clipboard writes (OSC 52) from the session reach the local
+    /// terminal, and so the local clipboard.
+    pub clipboard: bool,
+    /// Key that moves focus to the next focusable overlay or panel while
+    /// input is captured and there are two or more to choose from. Handled
+    /// by the server; empty disables it.
+    pub focus_key: String,
+}
+
+impl Default for ClientPolicy {
+    fn default() -> Self {
+        Self {
+            detach_keys: vec!["ctrl+\\".to_string()],
+            allow_capture_toggle: true,
+            command_key: None,
+            clipboard: true,
+            focus_key: "tab".to_string(),
+        }
+    }
+}
+
+impl ClientPolicy {
+    /// The byte sequence each detach key produces at a plain terminal.
+    ///
+    /// Fails on the first key name that can't be encoded.
+    pub fn detach_sequences(&self) -> Result<Vec<Vec<u8>>, crate::input::UnknownKey> {
+        let modes = crate::parser::state::KeyboardModes::default();
+        self.detach_keys
+            .iter()
+            .map(|key| crate::input::encode_key(key, &modes))
+            .collect()
+    }
+
+    /// The byte sequence the focus key produces at a plain terminal, or
+    /// `None` if it is disabled.
+    pub fn focus_sequence(&self) -> Result<Option<Vec<u8>>, crate::input::UnknownKey> {
+        if self.focus_key.is_empty() {
+            return Ok(None);
+        }
+        let modes = crate::parser::state::KeyboardModes::default();
+        crate::input::encode_key(&self.focus_key, &modes).map(Some)
+    }
+
+    /// The byte sequence the command key produces at a plain terminal.
+    pub fn command_sequence(&self) -> Result<Option<Vec<u8>>, crate::input::UnknownKey> {
+        let modes = crate::parser::state::KeyboardModes::default();
+        self.command_key
+            .as_deref()
+            .map(|key| crate::input::encode_key(key, &modes))
+            .transpose()
+    }
 }
 
-/// Client → Server: resize notification.
+/// Resize notification.
+///
+/// Client → Server: the client's local terminal size changed.
+/// Server → Client: the session's terminal size changed.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ResizeMsg {
     pub rows: u16,
     pub cols: u16,
 }
 
+/// Server → Client: a server-wide notice to display, e.g. that the server
+/// is in maintenance. `message: None` clears a previously shown banner.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct BannerMsg {
+    pub message: Option<String>,
+}
+
 /// Server → Client: error response.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ErrorMsg {
     pub code: String,
     pub message: String,
@@ -334,6 +475,7 @@ pub struct ErrorMsg {
 
 /// Client → Server: request to list all sessions.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ListSessionsMsg {
     /// Target server for federation routing (None = aggregate all).
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -342,12 +484,14 @@ pub struct ListSessionsMsg {
 
 /// Server → Client: response with the list of sessions.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ListSessionsResponseMsg {
     pub sessions: Vec<SessionInfoMsg>,
 }
 
 /// Info about a single session, used in list responses.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct SessionInfoMsg {
     pub name: String,
     #[serde(default)]
@@ -361,25 +505,58 @@ pub struct SessionInfoMsg {
     pub tags: Vec<String>,
     #[serde(default)]
     pub last_activity_ms: u64,
+    /// Unix time of the last output or input, in milliseconds. Zero from
+    /// servers that don't report it.
+    #[serde(default)]
+    pub last_activity_at: u64,
+    /// Unix time the session was created, in milliseconds. Zero from
+    /// servers that don't report it.
+    #[serde(default)]
+    pub created_at: u64,
+    /// Whether the child is stopped by a job-control signal.
+    #[serde(default)]
+    pub suspended: bool,
+    /// Whether the session is hibernated.
+    #[serde(default)]
+    pub hibernated: bool,
+    /// Window title the program last set (OSC 0/2).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Working directory the shell last reported (OSC 7).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// Structured data set by API clients.
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub metadata: serde_json::Map<String, serde_json::Value>,
 }
 
 /// Client → Server: request to kill (destroy) a session.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct KillSessionMsg {
     pub name: String,
     /// Target server for federation routing (None = local).
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub server: Option<String>,
+    /// Signal to send before SIGKILL (`TERM`, `INT`, ...). With neither
+    /// this nor `grace_ms`, the session is SIGKILLed at once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal: Option<String>,
+    /// How long to wait for the child to exit before SIGKILL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grace_ms: Option<u64>,
 }
 
 /// Server → Client: confirmation that a session was killed.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct KillSessionResponseMsg {
     pub name: String,
 }
 
 /// Client → Server: request to detach (signal) a session without destroying it.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct DetachSessionMsg {
     pub name: String,
     /// Target server for federation routing (None = local).
@@ -389,22 +566,41 @@ pub struct DetachSessionMsg {
 
 /// Server → Client: confirmation that a session was detached.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct DetachSessionResponseMsg {
     pub name: String,
 }
 
+/// Client → Server: rename a session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct RenameSessionMsg {
+    pub name: String,
+    pub new_name: String,
+}
+
+/// Server → Client: confirmation that a session was renamed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct RenameSessionResponseMsg {
+    pub name: String,
+}
+
 /// Client → Server: request the server's auth token.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct GetTokenMsg {}
 
 /// Server → Client: response with the auth token (if configured).
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct GetTokenResponseMsg {
     pub token: Option<String>,
 }
 
 /// Client → Server: request to manage tags on a session.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ManageTagsMsg {
     pub session: String,
     #[serde(default)]
@@ -418,32 +614,38 @@ pub struct ManageTagsMsg {
 
 /// Server → Client: response with the current tags after management.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ManageTagsResponseMsg {
     pub tags: Vec<String>,
 }
 
 /// Client → Server: request to shut down the server.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ShutdownServerMsg {}
 
 /// Server → Client: acknowledgment before shutdown.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ShutdownServerResponseMsg {}
 
 // ── Federation / server management messages ────────────────────────
 
 /// Client → Server: request to list all servers in the federation.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ListServersMsg {}
 
 /// Server → Client: response with the list of servers.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ListServersResponseMsg {
     pub servers: Vec<ServerInfoEntry>,
 }
 
 /// Info about a single server in the federation.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ServerInfoEntry {
     pub hostname: Option<String>,
     pub address: String,
@@ -457,6 +659,7 @@ pub struct ServerInfoEntry {
 
 /// Client → Server: request to add a backend server.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct AddServerMsg {
     pub address: String,
     pub token: Option<String>,
@@ -464,6 +667,7 @@ pub struct AddServerMsg {
 
 /// Server → Client: response after adding a backend server.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct AddServerResponseMsg {
     pub address: String,
     pub health: String,
@@ -471,20 +675,24 @@ pub struct AddServerResponseMsg {
 
 /// Client → Server: request to remove a backend server by hostname.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct RemoveServerMsg {
     pub hostname: String,
 }
 
 /// Server → Client: confirmation that a backend server was removed.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct RemoveServerResponseMsg {}
 
 /// Client → Server: request to reload federation config from file.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ReloadConfigMsg {}
 
 /// Server → Client: response after reloading federation config.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ReloadConfigResponseMsg {
     pub added: usize,
     pub removed: usize,
@@ -492,21 +700,96 @@ pub struct ReloadConfigResponseMsg {
 
 /// Client → Server: request for server identity info.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ServerInfoMsg {}
 
 /// Server → Client: response with server identity info.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct ServerInfoResponseMsg {
     pub hostname: String,
     pub version: String,
     pub server_id: String,
 }
 
+/// Client → Server: read a session's output as plain-text lines.
+///
+/// The server answers with [`ReadOutputResponseMsg`] frames until it has
+/// sent every complete line, then closes the connection. With `follow` it
+/// keeps sending new lines as they are printed, until the session ends or
+/// the client disconnects.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct ReadOutputMsg {
+    pub name: String,
+    /// Line number to start at, from a previous response's `next`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<u64>,
+    /// Start this many lines before the end. With neither this nor
+    /// `since`, start at the oldest line kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last: Option<usize>,
+    #[serde(default)]
+    pub follow: bool,
+}
+
+/// Server → Client: a batch of output lines.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct ReadOutputResponseMsg {
+    pub lines: Vec<String>,
+    /// Line number to read from next.
+    pub next: u64,
+    /// Lines dropped from the scrollback before they could be sent.
+    #[serde(default)]
+    pub missed: u64,
+}
+
+/// Client → Server: run one of the per-session methods of the WebSocket
+/// JSON API (`get_screen`, `get_scrollback`, `resize`, `create_overlay`,
+/// `update_panel`, ...) against a session. Lets local automation do over
+/// the socket what it would otherwise need the HTTP port and token for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct SessionRequestMsg {
+    pub name: String,
+    pub method: String,
+    /// Method parameters, as for the WebSocket method.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<serde_json::Value>,
+}
+
+/// Server → Client: the method's result. Failures come back as an Error
+/// frame carrying the method's error code.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct SessionRequestResponseMsg {
+    pub result: serde_json::Value,
+}
+
+/// Client → Server: query or set whether the server outlives its last
+/// session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct SetPersistMsg {
+    /// New mode; `None` only queries it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persistent: Option<bool>,
+}
+
+/// Server → Client: the persistence mode after the request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct SetPersistResponseMsg {
+    pub persistent: bool,
+}
+
 /// Server → Client: full overlay state sync.
 ///
 /// Sent when any overlay changes, contains ALL current overlays.
 /// Full-state sync is simpler than delta updates and overlay counts are small.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct OverlaySyncMsg {
     pub overlays: Vec<crate::overlay::Overlay>,
 }
@@ -515,6 +798,7 @@ pub struct OverlaySyncMsg {
 ///
 /// Sent when any panel changes, contains ALL current panels plus layout info.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
 pub struct PanelSyncMsg {
     pub panels: Vec<crate::panel::Panel>,
     pub scroll_region_top: u16,
@@ -588,6 +872,15 @@ mod tests {
             FrameType::ReloadConfigResponse,
             FrameType::ServerInfo,
             FrameType::ServerInfoResponse,
+            FrameType::Banner,
+            FrameType::ReadOutput,
+            FrameType::ReadOutputResponse,
+            FrameType::RenameSession,
+            FrameType::RenameSessionResponse,
+            FrameType::SessionRequest,
+            FrameType::SessionRequestResponse,
+            FrameType::SetPersist,
+            FrameType::SetPersistResponse,
         ];
         for ft in types {
             let byte = ft as u8;
@@ -600,7 +893,7 @@ mod tests {
     fn frame_type_invalid_byte() {
         assert!(FrameType::from_u8(0xFF).is_none());
         assert!(FrameType::from_u8(0x00).is_none());
-        assert!(FrameType::from_u8(0x1A).is_none());
+        assert!(FrameType::from_u8(0x1B).is_none());
     }
 
     #[test]
@@ -707,6 +1000,19 @@ mod tests {
         assert_eq!(decoded.cols, 80);
     }
 
+    #[test]
+    fn control_frame_banner() {
+        let msg = BannerMsg {
+            message: Some("draining for upgrade".to_string()),
+        };
+        let frame = Frame::control(FrameType::Banner, &msg).unwrap();
+        let decoded: BannerMsg = frame.parse_json().unwrap();
+        assert_eq!(decoded.message.as_deref(), Some("draining for upgrade"));
+
+        let cleared: BannerMsg = serde_json::from_str(r#"{"message":null}"#).unwrap();
+        assert!(cleared.message.is_none());
+    }
+
     #[test]
     fn control_frame_create_session_response() {
         let msg = CreateSessionResponseMsg {
@@ -715,6 +1021,8 @@ mod tests {
             pid: None,
             rows: 40,
             cols: 120,
+            motd: None,
+            client_policy: ClientPolicy::default(),
         };
         let frame = Frame::control(FrameType::CreateSessionResponse, &msg).unwrap();
         let decoded: CreateSessionResponseMsg = frame.parse_json().unwrap();
@@ -725,11 +1033,45 @@ mod tests {
         assert_eq!(decoded.cols, 120);
     }
 
+    #[test]
+    fn client_policy_defaults_when_absent() {
+        let decoded: CreateSessionResponseMsg =
+            serde_json::from_str(r#"{"name":"s","pid":null,"rows":24,"cols":80}"#).unwrap();
+        assert_eq!(decoded.client_policy, ClientPolicy::default());
+        assert_eq!(
+            decoded.client_policy.detach_sequences().unwrap(),
+            vec![vec![0x1c]]
+        );
+
+        let policy: ClientPolicy =
+            serde_json::from_str(r#"{"detach_keys":["ctrl+]","F12"]}"#).unwrap();
+        assert!(policy.allow_capture_toggle);
+        assert_eq!(
+            policy.detach_sequences().unwrap(),
+            vec![vec![0x1d], b"\x1b[24~".to_vec()]
+        );
+
+        assert_eq!(policy.command_sequence().unwrap(), None);
+
+        let bad = ClientPolicy {
+            detach_keys: vec!["Hyper".to_string()],
+            allow_capture_toggle: true,
+            command_key: Some("ctrl+a".to_string()),
+            clipboard: true,
+            focus_key: String::new(),
+        };
+        assert!(bad.detach_sequences().is_err());
+        assert_eq!(bad.command_sequence().unwrap(), Some(vec![0x01]));
+        assert_eq!(bad.focus_sequence().unwrap(), None);
+        assert_eq!(policy.focus_sequence().unwrap(), Some(vec![b'\t']));
+    }
+
     #[test]
     fn control_frame_attach_session() {
         let msg = AttachSessionMsg {
             name: "my-session".to_string(),
             scrollback: ScrollbackRequest::Lines(100),
+            scrollback_format: None,
             rows: 24,
             cols: 80,
         };
@@ -750,6 +1092,9 @@ mod tests {
             input_mode: crate::input::mode::Mode::Passthrough,
             screen_mode: crate::overlay::ScreenMode::Normal,
             focused_id: None,
+            scrollback_lines: None,
+            motd: None,
+            client_policy: ClientPolicy::default(),
         };
         let frame = Frame::control(FrameType::AttachSessionResponse, &msg).unwrap();
         let decoded: AttachSessionResponseMsg = frame.parse_json().unwrap();
@@ -772,6 +1117,9 @@ mod tests {
             input_mode: crate::input::mode::Mode::Capture,
             screen_mode: crate::overlay::ScreenMode::Alt,
             focused_id: Some("overlay-123".to_string()),
+            scrollback_lines: None,
+            motd: None,
+            client_policy: ClientPolicy::default(),
         };
         let frame = Frame::control(FrameType::AttachSessionResponse, &msg).unwrap();
         let decoded: AttachSessionResponseMsg = frame.parse_json().unwrap();
@@ -780,6 +1128,53 @@ mod tests {
         assert_eq!(decoded.focused_id, Some("overlay-123".to_string()));
     }
 
+    #[test]
+    fn control_frame_attach_session_response_with_scrollback_lines() {
+        use crate::parser::state::{FormattedLine, Span, Style};
+        let msg = AttachSessionResponseMsg {
+            name: "test".to_string(),
+            rows: 24,
+            cols: 80,
+            scrollback: Vec::new(),
+            screen: Vec::new(),
+            input_mode: crate::input::mode::Mode::Passthrough,
+            screen_mode: crate::overlay::ScreenMode::Normal,
+            focused_id: None,
+            scrollback_lines: Some(vec![
+                FormattedLine::Plain("plain".to_string()),
+                FormattedLine::Styled(vec![Span {
+                    text: "bold".to_string(),
+                    style: Style {
+                        bold: true,
+                        ..Default::default()
+                    },
+                }]),
+            ]),
+            motd: Some("Sessions are recorded.".to_string()),
+            client_policy: ClientPolicy::default(),
+        };
+        let frame = Frame::control(FrameType::AttachSessionResponse, &msg).unwrap();
+        let decoded: AttachSessionResponseMsg = frame.parse_json().unwrap();
+        assert_eq!(decoded.motd.as_deref(), Some("Sessions are recorded."));
+        let lines = decoded.scrollback_lines.unwrap();
+        assert_eq!(lines.len(), 2);
+        assert!(matches!(&lines[0], FormattedLine::Plain(s) if s == "plain"));
+        match &lines[1] {
+            FormattedLine::Styled(spans) => {
+                assert_eq!(spans[0].text, "bold");
+                assert!(spans[0].style.bold);
+            }
+            other => panic!("expected styled line, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn attach_session_msg_scrollback_format_defaults_to_none() {
+        let json = r#"{"name":"s","scrollback":"none","rows":24,"cols":80}"#;
+        let msg: AttachSessionMsg = serde_json::from_str(json).unwrap();
+        assert!(msg.scrollback_format.is_none());
+    }
+
     #[test]
     fn control_frame_resize() {
         let msg = ResizeMsg {
@@ -866,6 +1261,13 @@ mod tests {
                     clients: 1,
                     tags: vec![],
                     last_activity_ms: 0,
+                    last_activity_at: 1_700_000_000_000,
+                    created_at: 1_699_999_000_000,
+                    suspended: true,
+                    hibernated: false,
+                    title: Some("vim".to_string()),
+                    cwd: Some("/tmp".to_string()),
+                    metadata: serde_json::json!({"run": "r-1"}).as_object().unwrap().clone(),
                 },
                 SessionInfoMsg {
                     name: "beta".to_string(),
@@ -877,6 +1279,13 @@ mod tests {
                     clients: 0,
                     tags: vec![],
                     last_activity_ms: 0,
+                    last_activity_at: 0,
+                    created_at: 0,
+                    suspended: false,
+                    hibernated: true,
+                    title: None,
+                    cwd: None,
+                    metadata: Default::default(),
                 },
             ],
         };
@@ -889,6 +1298,14 @@ mod tests {
         assert_eq!(decoded.sessions[0].rows, 24);
         assert_eq!(decoded.sessions[0].cols, 80);
         assert_eq!(decoded.sessions[0].clients, 1);
+        assert_eq!(decoded.sessions[0].title.as_deref(), Some("vim"));
+        assert_eq!(decoded.sessions[0].cwd.as_deref(), Some("/tmp"));
+        assert_eq!(decoded.sessions[0].metadata["run"], "r-1");
+        assert_eq!(decoded.sessions[0].last_activity_at, 1_700_000_000_000);
+        assert_eq!(decoded.sessions[0].created_at, 1_699_999_000_000);
+        assert!(decoded.sessions[0].suspended && !decoded.sessions[0].hibernated);
+        assert!(decoded.sessions[1].hibernated);
+        assert!(decoded.sessions[1].metadata.is_empty());
         assert_eq!(decoded.sessions[1].name, "beta");
         assert_eq!(decoded.sessions[1].pid, None);
     }
@@ -903,7 +1320,12 @@ mod tests {
 
     #[test]
     fn control_frame_kill_session() {
-        let msg = KillSessionMsg { name: "my-session".to_string(), server: None };
+        let msg = KillSessionMsg {
+            name: "my-session".to_string(),
+            server: None,
+            signal: Some("TERM".to_string()),
+            grace_ms: Some(2000),
+        };
         let frame = Frame::control(FrameType::KillSession, &msg).unwrap();
         let decoded: KillSessionMsg = frame.parse_json().unwrap();
         assert_eq!(decoded.name, "my-session");
@@ -933,6 +1355,15 @@ mod tests {
         assert_eq!(decoded.name, "detached-session");
     }
 
+    #[test]
+    fn control_frame_rename_session() {
+        let msg = RenameSessionMsg { name: "old".to_string(), new_name: "new".to_string() };
+        let frame = Frame::control(FrameType::RenameSession, &msg).unwrap();
+        let decoded: RenameSessionMsg = frame.parse_json().unwrap();
+        assert_eq!(decoded.name, "old");
+        assert_eq!(decoded.new_name, "new");
+    }
+
     #[tokio::test]
     async fn multiple_frames_sequential() {
         let frames = vec![
@@ -1161,6 +1592,8 @@ mod tests {
         let json = serde_json::json!({"name": "s1"});
         let msg: KillSessionMsg = serde_json::from_value(json).unwrap();
         assert!(msg.server.is_none());
+        assert!(msg.signal.is_none());
+        assert!(msg.grace_ms.is_none());
     }
 
     #[test]
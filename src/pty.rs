@@ -48,6 +48,10 @@ pub struct Pty {
     // open as long as any Pty clone is alive.
     master: Box<dyn portable_pty::MasterPty + Send>,
     child: Option<Box<dyn portable_pty::Child + Send + Sync>>,
+    // Kept only by `spawn_respawnable`, so another command can be spawned
+    // once the child exits. Reads of the master don't see EOF until it is
+    // closed with `close_slave`.
+    slave: Option<Box<dyn portable_pty::SlavePty + Send>>,
 }
 
 
@@ -70,6 +74,16 @@ impl Pty {
     /// are dropped. Dropping the slave here ensures the reader thread
     /// exits promptly when the child dies.
     pub fn spawn_with_cmd(rows: u16, cols: u16, cmd: CommandBuilder) -> Result<Self, PtyError> {
+        let mut pty = Self::spawn_respawnable(rows, cols, cmd)?;
+        // Drop slave — only the child process should hold it open.
+        pty.close_slave();
+        Ok(pty)
+    }
+
+    /// Like [`spawn_with_cmd`](Self::spawn_with_cmd), but keep the slave
+    /// side open so [`respawn`](Self::respawn) can start another command on
+    /// the same terminal after the child exits.
+    pub fn spawn_respawnable(rows: u16, cols: u16, cmd: CommandBuilder) -> Result<Self, PtyError> {
         let pty_system = native_pty_system();
 
         let size = PtySize {
@@ -83,10 +97,23 @@ impl Pty {
 
         let child = pair.slave.spawn_command(cmd).map_err(PtyError::SpawnCommand)?;
 
-        // Drop slave — only the child process should hold it open.
-        drop(pair.slave);
+        Ok(Self { master: pair.master, child: Some(child), slave: Some(pair.slave) })
+    }
 
-        Ok(Self { master: pair.master, child: Some(child) })
+    /// Spawn `cmd` on the kept slave side, replacing the child. Fails once
+    /// the slave has been closed.
+    pub fn respawn(&mut self, cmd: CommandBuilder) -> Result<(), PtyError> {
+        let slave = self.slave.as_ref().ok_or_else(|| {
+            PtyError::SpawnCommand(anyhow::anyhow!("the terminal has been closed"))
+        })?;
+        self.child = Some(slave.spawn_command(cmd).map_err(PtyError::SpawnCommand)?);
+        Ok(())
+    }
+
+    /// Close the kept slave side, so reads of the master see EOF once the
+    /// child exits.
+    pub fn close_slave(&mut self) {
+        self.slave = None;
     }
 
     /// Build a CommandBuilder from the spawn configuration.
@@ -138,6 +165,25 @@ impl Pty {
         }).map_err(PtyError::Resize)
     }
 
+    /// Whether input written to the PTY would be shown on screen.
+    ///
+    /// True when the line discipline echoes (`ECHO`) or when canonical mode
+    /// is off, since raw-mode programs such as readline-based shells and
+    /// editors echo input themselves. A password prompt (`getpass`, `sudo`,
+    /// `ssh`) clears `ECHO` while staying in canonical mode, so it reports
+    /// false. Returns `None` if the terminal attributes can't be read.
+    pub fn echoes_input(&self) -> Option<bool> {
+        let fd = self.master.as_raw_fd()?;
+        let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
+        // SAFETY: `fd` is the open PTY master and `termios` is a valid
+        // out-pointer; tcgetattr initializes it on success.
+        if unsafe { libc::tcgetattr(fd, termios.as_mut_ptr()) } != 0 {
+            return None;
+        }
+        let lflag = unsafe { termios.assume_init() }.c_lflag;
+        Some(lflag & libc::ECHO != 0 || lflag & libc::ICANON == 0)
+    }
+
     pub fn take_child(&mut self) -> Option<Box<dyn portable_pty::Child + Send + Sync>> {
         self.child.take()
     }
@@ -150,6 +196,489 @@ impl Pty {
     }
 }
 
+/// Wait for the child `pid` to exit, calling `on_stop(true)` each time it is
+/// stopped (`SIGTSTP`, `SIGSTOP`) and `on_stop(false)` when it continues.
+/// Returns the `waitpid` status it exited or was killed with.
+///
+/// This reaps the child, so the `portable_pty` handle must not be waited on
+/// as well.
+pub fn wait_reporting_stops(pid: u32, mut on_stop: impl FnMut(bool)) -> std::io::Result<libc::c_int> {
+    loop {
+        let mut status = 0;
+        // SAFETY: `status` is a valid out-pointer for the duration of the call.
+        let rc = unsafe { libc::waitpid(pid as libc::pid_t, &mut status, libc::WUNTRACED | libc::WCONTINUED) };
+        if rc < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        if libc::WIFSTOPPED(status) {
+            on_stop(true);
+        } else if libc::WIFCONTINUED(status) {
+            on_stop(false);
+        } else {
+            return Ok(status);
+        }
+    }
+}
+
+/// Words `sh` handles itself; a command starting with one of these can't be
+/// checked against the filesystem.
+const SHELL_BUILTINS: &[&str] = &[
+    "!", ".", ":", "[", "[[", "{", "alias", "break", "builtin", "case", "cd", "command",
+    "continue", "echo", "eval", "exec", "exit", "export", "false", "for", "function", "if",
+    "local", "printf", "pwd", "read", "readonly", "return", "set", "shift", "source", "test",
+    "time", "trap", "true", "type", "ulimit", "umask", "unset", "until", "wait", "while",
+];
+
+/// Locate the program a spawn command would run, without running it.
+///
+/// For a shell this is the shell binary; for a command it is the first word
+/// after any leading `VAR=value` assignments. Bare names are looked up in
+/// `path_var` (a `PATH`-style list, defaulting to the server's own `PATH`),
+/// and names containing `/` are resolved against `cwd`.
+///
+/// Returns `Ok(None)` when the command starts with a shell builtin or an
+/// expansion that only the shell can evaluate, and `Err` with a
+/// human-readable reason when the program is missing or not executable.
+pub fn resolve_program(
+    spawn_cmd: &SpawnCommand,
+    path_var: Option<&str>,
+    cwd: Option<&std::path::Path>,
+) -> Result<Option<std::path::PathBuf>, String> {
+    let program = match spawn_cmd {
+        SpawnCommand::Shell { shell, .. } => match shell {
+            Some(s) => s.clone(),
+            None => std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string()),
+        },
+        SpawnCommand::Command { command, .. } => match first_command_word(command)? {
+            Some(word) => word,
+            None => return Ok(None),
+        },
+    };
+
+    if program.contains('/') {
+        let path = match cwd {
+            Some(dir) => dir.join(&program),
+            None => std::path::PathBuf::from(&program),
+        };
+        return if is_executable(&path) {
+            Ok(Some(path))
+        } else if path.exists() {
+            Err(format!("{} is not executable", program))
+        } else {
+            Err(format!("{} does not exist", program))
+        };
+    }
+
+    let path_var = match path_var {
+        Some(p) => p.to_string(),
+        None => std::env::var("PATH").unwrap_or_default(),
+    };
+    std::env::split_paths(&path_var)
+        .map(|dir| dir.join(&program))
+        .find(|candidate| is_executable(candidate))
+        .map(Some)
+        .ok_or_else(|| format!("{}: command not found in PATH", program))
+}
+
+/// The program word of a shell command line, or `None` if it can't be known
+/// without running the shell.
+fn first_command_word(command: &str) -> Result<Option<String>, String> {
+    let mut rest = command.trim_start();
+    loop {
+        if rest.is_empty() {
+            return Err("command is empty".to_string());
+        }
+        let (word, tail) = match rest.as_bytes()[0] {
+            quote @ (b'\'' | b'"') => {
+                let end = rest[1..]
+                    .find(quote as char)
+                    .ok_or_else(|| "command has an unterminated quote".to_string())?;
+                (&rest[1..=end], &rest[end + 2..])
+            }
+            _ => {
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || ";|&<>()".contains(c))
+                    .unwrap_or(rest.len());
+                if end == 0 {
+                    // Starts with an operator such as a subshell.
+                    return Ok(None);
+                }
+                (&rest[..end], &rest[end..])
+            }
+        };
+        let is_assignment = word.split_once('=').is_some_and(|(var, _)| {
+            !var.is_empty()
+                && !var.starts_with(|c: char| c.is_ascii_digit())
+                && var.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+        if is_assignment {
+            rest = tail.trim_start();
+            continue;
+        }
+        if SHELL_BUILTINS.contains(&word) || word.contains(['$', '`', '*', '?', '~']) {
+            return Ok(None);
+        }
+        return Ok(Some(word.to_string()));
+    }
+}
+
+/// Quote `s` as a single POSIX shell word.
+pub(crate) fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+fn is_executable(path: &std::path::Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+/// First argument that makes the `wsh` binary act as the privilege-dropping
+/// trampoline for [`RunAs`] instead of parsing its usual command line.
+pub const RUN_AS_ARG: &str = "__run-as";
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum RunAsError {
+    /// The account doesn't exist, or `user` and `uid` disagree.
+    #[error("{0}")]
+    Lookup(String),
+    /// The server lacks the privileges to switch to the account.
+    #[error("{0}")]
+    NotPermitted(String),
+}
+
+/// A Unix account to run a session's child under.
+///
+/// `portable_pty` forks and execs the child itself and offers no hook to run
+/// code in between, so the child is started as the `wsh` binary in
+/// trampoline mode ([`RUN_AS_ARG`]). The trampoline calls [`exec_as`], which
+/// sets the supplementary groups, gid, and uid, then execs the real program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunAs {
+    pub user: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub home: String,
+    pub shell: String,
+}
+
+impl RunAs {
+    /// Look up an account by name, uid, or both (which must agree).
+    ///
+    /// Returns `Ok(None)` when neither is given.
+    pub fn lookup(user: Option<&str>, uid: Option<u32>) -> Result<Option<Self>, String> {
+        let run_as = match (user, uid) {
+            (None, None) => return Ok(None),
+            (Some(name), _) => {
+                passwd_by_name(name).ok_or_else(|| format!("no such user: {}", name))?
+            }
+            (None, Some(uid)) => passwd_by_uid(uid).ok_or_else(|| format!("no such uid: {}", uid))?,
+        };
+        if let Some(uid) = uid {
+            if uid != run_as.uid {
+                return Err(format!("user {} has uid {}, not {}", run_as.user, run_as.uid, uid));
+            }
+        }
+        Ok(Some(run_as))
+    }
+
+    /// Resolve the account a new session was asked to run as.
+    ///
+    /// Asking for the server's own account is a no-op and returns
+    /// `Ok(None)`; any other account needs a server running as root.
+    pub fn resolve(user: Option<&str>, uid: Option<u32>) -> Result<Option<Self>, RunAsError> {
+        let Some(account) = Self::lookup(user, uid).map_err(RunAsError::Lookup)? else {
+            return Ok(None);
+        };
+        // SAFETY: geteuid has no preconditions and cannot fail.
+        let euid = unsafe { libc::geteuid() };
+        if account.uid == euid {
+            Ok(None)
+        } else if euid == 0 {
+            Ok(Some(account))
+        } else {
+            Err(RunAsError::NotPermitted(format!(
+                "the server is not running as root, so it cannot run sessions as {}",
+                account.user
+            )))
+        }
+    }
+
+    /// A shell session with no explicit shell gets the account's login
+    /// shell rather than the server's `$SHELL`.
+    pub fn default_shell(&self, command: SpawnCommand) -> SpawnCommand {
+        match command {
+            SpawnCommand::Shell { interactive, shell: None } => SpawnCommand::Shell {
+                interactive,
+                shell: Some(self.shell.clone()),
+            },
+            command => command,
+        }
+    }
+
+    /// Rewrite `cmd` to run through the trampoline as this account, with
+    /// `HOME`, `USER`, and `LOGNAME` set for it and its home directory as
+    /// the default working directory (`/` if the home doesn't exist, as
+    /// `login` does).
+    pub fn wrap(&self, cmd: &mut CommandBuilder) -> Result<(), PtyError> {
+        let exe = std::env::current_exe().map_err(|e| PtyError::SpawnCommand(e.into()))?;
+        let argv = cmd.get_argv_mut();
+        let prefix = [
+            exe.into_os_string(),
+            RUN_AS_ARG.into(),
+            self.uid.to_string().into(),
+            self.gid.to_string().into(),
+            self.user.clone().into(),
+        ];
+        argv.splice(0..0, prefix);
+        cmd.env("HOME", &self.home);
+        cmd.env("USER", &self.user);
+        cmd.env("LOGNAME", &self.user);
+        // portable_pty falls back to $HOME for the working directory, so a
+        // missing home (e.g. nobody's /nonexistent) needs an explicit one.
+        if cmd.get_cwd().is_none() {
+            if std::path::Path::new(&self.home).is_dir() {
+                cmd.cwd(&self.home);
+            } else {
+                cmd.cwd("/");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Trampoline body: drop to `uid`/`gid` with `user`'s groups, then exec the
+/// remaining arguments. Takes the arguments following [`RUN_AS_ARG`] and only
+/// returns on failure.
+pub fn exec_as(mut args: impl Iterator<Item = std::ffi::OsString>) -> std::io::Error {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::process::CommandExt;
+
+    let mut id = || -> Option<u32> { args.next()?.to_str()?.parse().ok() };
+    let (Some(uid), Some(gid)) = (id(), id()) else {
+        return std::io::Error::new(std::io::ErrorKind::InvalidInput, "expected uid and gid");
+    };
+    let (Some(user), Some(program)) = (args.next(), args.next()) else {
+        return std::io::Error::new(std::io::ErrorKind::InvalidInput, "expected user and program");
+    };
+    let Ok(user) = std::ffi::CString::new(user.as_bytes()) else {
+        return std::io::Error::new(std::io::ErrorKind::InvalidInput, "user contains a NUL byte");
+    };
+
+    // SAFETY: plain syscalls on values we own; `user` outlives the call.
+    // Order matters: groups and gid can only be changed while still root.
+    unsafe {
+        if libc::initgroups(user.as_ptr(), gid as _) != 0
+            || libc::setgid(gid) != 0
+            || libc::setuid(uid) != 0
+        {
+            return std::io::Error::last_os_error();
+        }
+        if uid != 0 && libc::setuid(0) == 0 {
+            return std::io::Error::other("privileges could not be dropped");
+        }
+    }
+    std::process::Command::new(program).args(args).exec()
+}
+
+/// First argument that makes the `wsh` binary set a [`Priority`] and exec the
+/// session's program.
+pub const PRIORITY_ARG: &str = "__priority";
+
+/// Linux I/O scheduling class, as used by `ionice(1)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoClass {
+    Realtime,
+    BestEffort,
+    Idle,
+}
+
+/// CPU niceness and I/O priority for a session's child, so background jobs
+/// can yield to interactive sessions on the same machine.
+///
+/// Like [`RunAs`], this is applied by the `wsh` trampoline ([`PRIORITY_ARG`])
+/// just before exec, since `portable_pty` gives no hook in the forked child.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Priority {
+    /// `nice` value, -20 (highest) to 19 (lowest).
+    pub nice: Option<i32>,
+    /// I/O class and level (0 highest to 7 lowest; unused for `Idle`).
+    pub ionice: Option<(IoClass, u8)>,
+}
+
+impl Priority {
+    /// Validate the `nice` and `ionice` options of a create request.
+    ///
+    /// `ionice` is `idle`, `best-effort`, or `realtime`, optionally followed
+    /// by `:level` (default 4). Raising priority above the default (negative
+    /// `nice`, `realtime`) needs a root server. Returns `Ok(None)` when
+    /// neither is given.
+    pub fn resolve(nice: Option<i32>, ionice: Option<&str>) -> Result<Option<Self>, String> {
+        if nice.is_none() && ionice.is_none() {
+            return Ok(None);
+        }
+        if let Some(n) = nice {
+            if !(-20..=19).contains(&n) {
+                return Err(format!("nice must be between -20 and 19, not {}", n));
+            }
+        }
+        let ionice = ionice.map(parse_ionice).transpose()?;
+        if ionice.is_some() && !cfg!(target_os = "linux") {
+            return Err("ionice is only supported on Linux".to_string());
+        }
+        // SAFETY: geteuid has no preconditions and cannot fail.
+        let root = unsafe { libc::geteuid() } == 0;
+        if !root && (nice.is_some_and(|n| n < 0) || matches!(ionice, Some((IoClass::Realtime, _)))) {
+            return Err("raising a session's priority needs a server running as root".to_string());
+        }
+        Ok(Some(Self { nice, ionice }))
+    }
+
+    /// Rewrite `cmd` to run through the trampoline at this priority.
+    ///
+    /// Must be applied after [`RunAs::wrap`], so a root server sets the
+    /// priority before dropping privileges.
+    pub fn wrap(&self, cmd: &mut CommandBuilder) -> Result<(), PtyError> {
+        let exe = std::env::current_exe().map_err(|e| PtyError::SpawnCommand(e.into()))?;
+        let (class, level) = match self.ionice {
+            Some((IoClass::Realtime, level)) => (1, level),
+            Some((IoClass::BestEffort, level)) => (2, level),
+            Some((IoClass::Idle, _)) => (3, 0),
+            None => (0, 0),
+        };
+        let prefix = [
+            exe.into_os_string(),
+            PRIORITY_ARG.into(),
+            self.nice.map_or_else(|| "-".to_string(), |n| n.to_string()).into(),
+            class.to_string().into(),
+            level.to_string().into(),
+        ];
+        cmd.get_argv_mut().splice(0..0, prefix);
+        Ok(())
+    }
+}
+
+fn parse_ionice(spec: &str) -> Result<(IoClass, u8), String> {
+    let (class, level) = match spec.split_once(':') {
+        Some((class, level)) => (class, Some(level)),
+        None => (spec, None),
+    };
+    let class = match class {
+        "realtime" => IoClass::Realtime,
+        "best-effort" => IoClass::BestEffort,
+        "idle" => IoClass::Idle,
+        _ => return Err(format!("unknown ionice class {:?}: expected idle, best-effort, or realtime", class)),
+    };
+    let level = match level {
+        Some(l) => l
+            .parse::<u8>()
+            .ok()
+            .filter(|&l| l <= 7)
+            .ok_or_else(|| format!("ionice level must be 0-7, not {:?}", l))?,
+        None => 4,
+    };
+    Ok((class, level))
+}
+
+/// Trampoline body: set the niceness and I/O priority passed by
+/// [`Priority::wrap`], then exec the remaining arguments. Takes the arguments
+/// following [`PRIORITY_ARG`] and only returns on failure.
+pub fn exec_with_priority(mut args: impl Iterator<Item = std::ffi::OsString>) -> std::io::Error {
+    use std::os::unix::process::CommandExt;
+
+    let (Some(nice), Some(class), Some(level), Some(program)) =
+        (args.next(), args.next(), args.next(), args.next())
+    else {
+        return std::io::Error::new(std::io::ErrorKind::InvalidInput, "expected nice, class, level, and program");
+    };
+    let nice = match nice.to_str() {
+        Some("-") => None,
+        Some(n) => match n.parse::<i32>() {
+            Ok(n) => Some(n),
+            Err(_) => return std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid nice value"),
+        },
+        None => return std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid nice value"),
+    };
+    let (Some(class), Some(level)) = (
+        class.to_str().and_then(|c| c.parse::<i64>().ok()),
+        level.to_str().and_then(|l| l.parse::<i64>().ok()),
+    ) else {
+        return std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid ionice class or level");
+    };
+
+    if let Some(nice) = nice {
+        // SAFETY: plain syscall; `who` 0 is the calling process.
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+            return std::io::Error::last_os_error();
+        }
+    }
+    if class != 0 {
+        #[cfg(target_os = "linux")]
+        {
+            const IOPRIO_WHO_PROCESS: i64 = 1;
+            const IOPRIO_CLASS_SHIFT: i64 = 13;
+            // SAFETY: plain syscall on the calling process (`who` 0).
+            let rc = unsafe {
+                libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, class << IOPRIO_CLASS_SHIFT | level)
+            };
+            if rc != 0 {
+                return std::io::Error::last_os_error();
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = level;
+            return std::io::Error::new(std::io::ErrorKind::Unsupported, "ionice is only supported on Linux");
+        }
+    }
+    std::process::Command::new(program).args(args).exec()
+}
+
+fn passwd_by_name(name: &str) -> Option<RunAs> {
+    let name = std::ffi::CString::new(name).ok()?;
+    // SAFETY: getpwnam_r writes into `pwd` and `buf`, which outlive the call.
+    lookup_passwd(|pwd, buf, len, result| unsafe {
+        libc::getpwnam_r(name.as_ptr(), pwd, buf, len, result)
+    })
+}
+
+fn passwd_by_uid(uid: u32) -> Option<RunAs> {
+    // SAFETY: as above.
+    lookup_passwd(|pwd, buf, len, result| unsafe { libc::getpwuid_r(uid, pwd, buf, len, result) })
+}
+
+fn lookup_passwd(
+    call: impl Fn(*mut libc::passwd, *mut libc::c_char, usize, *mut *mut libc::passwd) -> libc::c_int,
+) -> Option<RunAs> {
+    let mut buf = vec![0 as libc::c_char; 4096];
+    loop {
+        // SAFETY: passwd is plain old data; zeroed is a valid value.
+        let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut result = std::ptr::null_mut();
+        match call(&mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) {
+            libc::ERANGE if buf.len() < 1 << 20 => buf.resize(buf.len() * 2, 0),
+            0 if !result.is_null() => {
+                // SAFETY: on success the string fields point into `buf`.
+                let field = |p: *const libc::c_char| unsafe {
+                    std::ffi::CStr::from_ptr(p).to_string_lossy().into_owned()
+                };
+                let shell = field(pwd.pw_shell);
+                return Some(RunAs {
+                    user: field(pwd.pw_name),
+                    uid: pwd.pw_uid,
+                    gid: pwd.pw_gid,
+                    home: field(pwd.pw_dir),
+                    shell: if shell.is_empty() { "/bin/sh".to_string() } else { shell },
+                });
+            }
+            _ => return None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -190,6 +719,151 @@ mod tests {
         rx.recv_timeout(timeout).unwrap_or_default()
     }
 
+    fn command(command: &str) -> SpawnCommand {
+        SpawnCommand::Command { command: command.to_string(), interactive: false }
+    }
+
+    #[test]
+    fn test_resolve_program_searches_path() {
+        let resolved = resolve_program(&command("FOO=1 BAR=2 sh -c true"), Some("/nonexistent:/bin"), None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(resolved, std::path::PathBuf::from("/bin/sh"));
+
+        let err = resolve_program(&command("no-such-program-wsh --flag"), None, None).unwrap_err();
+        assert!(err.contains("no-such-program-wsh"), "{}", err);
+        assert!(resolve_program(&command("sh"), Some("/nonexistent"), None).is_err());
+    }
+
+    #[test]
+    fn test_run_as_lookup() {
+        let root = RunAs::lookup(Some("root"), None).unwrap().unwrap();
+        assert_eq!((root.uid, root.gid), (0, 0));
+        assert_eq!(RunAs::lookup(None, Some(0)).unwrap(), Some(root.clone()));
+        assert_eq!(RunAs::lookup(Some("root"), Some(0)).unwrap(), Some(root));
+        assert_eq!(RunAs::lookup(None, None).unwrap(), None);
+        assert!(RunAs::lookup(Some("root"), Some(4_000_000)).unwrap_err().contains("has uid 0"));
+        assert_eq!(
+            RunAs::lookup(Some("no-such-wsh-user"), None).unwrap_err(),
+            "no such user: no-such-wsh-user"
+        );
+    }
+
+    #[test]
+    fn test_run_as_resolve_own_account_is_noop() {
+        // SAFETY: geteuid cannot fail.
+        let euid = unsafe { libc::geteuid() };
+        assert_eq!(RunAs::resolve(None, Some(euid)), Ok(None));
+        assert!(matches!(RunAs::resolve(Some("no-such-wsh-user"), None), Err(RunAsError::Lookup(_))));
+        if euid != 0 {
+            assert!(matches!(RunAs::resolve(Some("root"), None), Err(RunAsError::NotPermitted(_))));
+        }
+    }
+
+    #[test]
+    fn test_run_as_wraps_command() {
+        let run_as = RunAs {
+            user: "alice".to_string(),
+            uid: 1001,
+            gid: 1002,
+            home: "/home/alice".to_string(),
+            shell: "/bin/bash".to_string(),
+        };
+        let mut cmd = Pty::build_command(&command("id -u"));
+        run_as.wrap(&mut cmd).unwrap();
+        // No such home here, so the session starts in `/`.
+        assert_eq!(cmd.get_env("HOME").unwrap(), "/home/alice");
+        assert_eq!(cmd.get_cwd().unwrap(), "/");
+
+        let mut cmd = Pty::build_command(&command("id -u"));
+        cmd.cwd("/tmp");
+        run_as.wrap(&mut cmd).unwrap();
+        assert_eq!(cmd.get_cwd().unwrap(), "/tmp");
+
+        let argv: Vec<_> = cmd.get_argv().iter().map(|a| a.to_string_lossy().into_owned()).collect();
+        assert_eq!(argv[1..], [RUN_AS_ARG, "1001", "1002", "alice", "/bin/sh", "-c", "id -u"]);
+        assert_eq!(cmd.get_env("USER").unwrap(), "alice");
+    }
+
+    #[test]
+    fn test_priority_resolve() {
+        assert_eq!(Priority::resolve(None, None), Ok(None));
+        assert_eq!(
+            Priority::resolve(Some(10), None),
+            Ok(Some(Priority { nice: Some(10), ionice: None }))
+        );
+        assert!(Priority::resolve(Some(20), None).is_err());
+        assert!(Priority::resolve(Some(-21), None).is_err());
+        assert!(Priority::resolve(None, Some("low")).is_err());
+        assert!(Priority::resolve(None, Some("best-effort:8")).is_err());
+        if cfg!(target_os = "linux") {
+            assert_eq!(
+                Priority::resolve(None, Some("best-effort")).unwrap().unwrap().ionice,
+                Some((IoClass::BestEffort, 4))
+            );
+            assert_eq!(
+                Priority::resolve(Some(5), Some("idle")).unwrap().unwrap().ionice,
+                Some((IoClass::Idle, 4))
+            );
+        }
+        // SAFETY: geteuid cannot fail.
+        if unsafe { libc::geteuid() } != 0 {
+            assert!(Priority::resolve(Some(-5), None).unwrap_err().contains("root"));
+        }
+    }
+
+    #[test]
+    fn test_priority_wraps_command() {
+        let priority = Priority { nice: Some(10), ionice: Some((IoClass::BestEffort, 7)) };
+        let mut cmd = Pty::build_command(&command("true"));
+        priority.wrap(&mut cmd).unwrap();
+        let argv: Vec<_> = cmd.get_argv().iter().map(|a| a.to_string_lossy().into_owned()).collect();
+        assert_eq!(argv[1..], [PRIORITY_ARG, "10", "2", "7", "/bin/sh", "-c", "true"]);
+
+        let mut cmd = Pty::build_command(&command("true"));
+        Priority { nice: None, ionice: Some((IoClass::Idle, 4)) }.wrap(&mut cmd).unwrap();
+        let argv: Vec<_> = cmd.get_argv().iter().map(|a| a.to_string_lossy().into_owned()).collect();
+        assert_eq!(argv[1..5], [PRIORITY_ARG, "-", "3", "0"]);
+    }
+
+    #[test]
+    fn test_resolve_program_paths_and_builtins() {
+        assert!(resolve_program(&command("'/bin/sh' -c true"), None, None).unwrap().is_some());
+        assert_eq!(
+            resolve_program(&command("./sh"), None, Some(std::path::Path::new("/bin"))).unwrap(),
+            Some(std::path::PathBuf::from("/bin/./sh"))
+        );
+        assert!(resolve_program(&command("/etc/passwd"), None, None).is_err());
+        for cmd in ["cd /tmp && ls", "exit 3", "(echo hi)", "$EDITOR file", "{ true; }"] {
+            assert_eq!(resolve_program(&command(cmd), Some(""), None), Ok(None), "{}", cmd);
+        }
+        assert!(resolve_program(&command("   "), None, None).is_err());
+        assert!(resolve_program(&command("'unterminated"), None, None).is_err());
+
+        let shell = SpawnCommand::Shell { interactive: true, shell: Some("/bin/sh".to_string()) };
+        assert!(resolve_program(&shell, None, None).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_wait_reporting_stops_sees_stop_and_continue() {
+        // The child waits for a line after it is continued, so it can't
+        // exit before the continue is reaped.
+        let mut pty = Pty::spawn(24, 80, command("kill -STOP $$; read _")).expect("Failed to spawn PTY");
+        let pid = pty.take_child().unwrap().process_id().unwrap();
+        let mut writer = pty.take_writer().unwrap();
+        let (tx, rx) = mpsc::channel();
+        let waiter = thread::spawn(move || wait_reporting_stops(pid, |stopped| tx.send(stopped).unwrap()));
+
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(true));
+        // SAFETY: the child is stopped, not reaped, so `pid` is still ours.
+        unsafe { libc::kill(pid as i32, libc::SIGCONT) };
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(false));
+        writer.write_all(b"\n").unwrap();
+        let status = waiter.join().unwrap().unwrap();
+        assert!(libc::WIFEXITED(status));
+        assert_eq!(libc::WEXITSTATUS(status), 0);
+    }
+
     #[test]
     fn test_spawn_creates_pty_with_shell() {
         let pty = Pty::spawn(24, 80, SpawnCommand::default());
@@ -285,6 +959,35 @@ mod tests {
         assert!(result.is_ok(), "Failed to resize PTY second time: {:?}", result.err());
     }
 
+    #[test]
+    fn test_echoes_input_follows_terminal_attributes() {
+        let pty = Pty::spawn(
+            24,
+            80,
+            SpawnCommand::Command {
+                command: "stty -echo icanon; echo ready; sleep 5".to_string(),
+                interactive: false,
+            },
+        )
+        .expect("Failed to spawn PTY");
+        let output = read_with_timeout(pty.take_reader().unwrap(), Duration::from_secs(5));
+        assert!(String::from_utf8_lossy(&output).contains("ready"));
+        assert_eq!(pty.echoes_input(), Some(false));
+
+        let pty = Pty::spawn(
+            24,
+            80,
+            SpawnCommand::Command {
+                command: "stty echo icanon; echo ready; sleep 5".to_string(),
+                interactive: false,
+            },
+        )
+        .expect("Failed to spawn PTY");
+        let output = read_with_timeout(pty.take_reader().unwrap(), Duration::from_secs(5));
+        assert!(String::from_utf8_lossy(&output).contains("ready"));
+        assert_eq!(pty.echoes_input(), Some(true));
+    }
+
     #[test]
     fn test_multiple_readers_can_be_cloned() {
         let pty = Pty::spawn(24, 80, SpawnCommand::default()).expect("Failed to spawn PTY");
@@ -0,0 +1,385 @@
+//! Attaching to a session on a remote server over its HTTP API.
+//!
+//! `wsh attach --url` talks to a server that isn't reachable through a
+//! local Unix socket. Scrollback and the current screen are fetched from
+//! `/sessions/{name}/scrollback` and `/sessions/{name}/screen`, terminal
+//! I/O streams over `/sessions/{name}/ws/raw`, and local window size
+//! changes are sent to `/sessions/{name}/resize`.
+
+use std::io;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::parser::state::FormattedLine;
+use crate::protocol::ScrollbackRequest;
+
+/// Most lines the server returns per scrollback request.
+const SCROLLBACK_PAGE: usize = 10_000;
+
+/// How long a second press of the detach key may follow the first.
+const DETACH_WINDOW: Duration = Duration::from_millis(500);
+
+/// The detach key, Ctrl+\. There is no capture toggle over `/ws/raw`, so a
+/// single press is held back and delivered to the application once the
+/// double-tap window expires.
+const DETACH_KEY: &[u8] = b"\x1c";
+
+pub type RemoteStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// A session on a remote wsh server, addressed by the server's base URL.
+#[derive(Clone)]
+pub struct RemoteSession {
+    http: reqwest::Client,
+    /// `http(s)://host:port`, plus the server's `--base-prefix` if any.
+    base: String,
+    name: String,
+    token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ScrollbackPage {
+    lines: Vec<FormattedLine>,
+    total_lines: usize,
+}
+
+#[derive(Deserialize)]
+struct ScreenCursor {
+    row: usize,
+    col: usize,
+}
+
+#[derive(Deserialize)]
+struct ScreenPage {
+    lines: Vec<FormattedLine>,
+    cursor: ScreenCursor,
+}
+
+impl RemoteSession {
+    /// Address session `name` on the server at `url`, which must be an
+    /// `http://` or `https://` URL.
+    pub fn new(url: &str, name: &str, token: Option<String>) -> io::Result<Self> {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("server URL must start with http:// or https://: {}", url),
+            ));
+        }
+        let http = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(5))
+            .timeout(Duration::from_secs(30))
+            .build()
+            .map_err(io::Error::other)?;
+        Ok(Self {
+            http,
+            base: url.trim_end_matches('/').to_string(),
+            name: name.to_string(),
+            token,
+        })
+    }
+
+    fn url_for(&self, path: &str) -> String {
+        format!("{}/sessions/{}{}", self.base, self.name, path)
+    }
+
+    /// The `/ws/raw` URL, with the scheme switched to `ws://` or `wss://`.
+    pub fn ws_url(&self) -> String {
+        let url = self.url_for("/ws/raw");
+        match url.strip_prefix("https://") {
+            Some(rest) => format!("wss://{}", rest),
+            None => format!("ws://{}", url.trim_start_matches("http://")),
+        }
+    }
+
+    async fn send(&self, req: reqwest::RequestBuilder) -> io::Result<reqwest::Response> {
+        let req = match &self.token {
+            Some(token) => req.bearer_auth(token),
+            None => req,
+        };
+        let resp = req.send().await.map_err(io::Error::other)?;
+        if resp.status().is_success() {
+            return Ok(resp);
+        }
+        let status = resp.status();
+        let body: serde_json::Value = resp.json().await.unwrap_or_default();
+        match (body["error"]["code"].as_str(), body["error"]["message"].as_str()) {
+            (Some(code), Some(message)) => Err(io::Error::other(format!("{}: {}", code, message))),
+            _ => Err(io::Error::other(format!("server returned {}", status))),
+        }
+    }
+
+    /// Scrollback lines to replay, oldest first: none, all, or the first
+    /// `n`, matching what the Unix socket attach sends.
+    pub async fn scrollback(&self, request: ScrollbackRequest) -> io::Result<Vec<FormattedLine>> {
+        let wanted = match request {
+            ScrollbackRequest::None => return Ok(Vec::new()),
+            ScrollbackRequest::All => usize::MAX,
+            ScrollbackRequest::Lines(n) => n,
+        };
+        let mut lines = Vec::new();
+        while lines.len() < wanted {
+            let limit = (wanted - lines.len()).min(SCROLLBACK_PAGE);
+            let url = self.url_for(&format!("/scrollback?format=styled&offset={}&limit={}", lines.len(), limit));
+            let page: ScrollbackPage = self
+                .send(self.http.get(url))
+                .await?
+                .json()
+                .await
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let done = page.lines.is_empty() || lines.len() + page.lines.len() >= page.total_lines;
+            lines.extend(page.lines);
+            if done {
+                break;
+            }
+        }
+        lines.truncate(wanted);
+        Ok(lines)
+    }
+
+    /// The current screen as ANSI bytes that clear the local terminal,
+    /// redraw it, and put the cursor back.
+    pub async fn screen(&self) -> io::Result<Vec<u8>> {
+        use crate::parser::ansi::line_to_ansi;
+        let screen: ScreenPage = self
+            .send(self.http.get(self.url_for("/screen?format=styled")))
+            .await?
+            .json()
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut buf = String::from("\x1b[H\x1b[2J");
+        for (i, line) in screen.lines.iter().enumerate() {
+            buf.push_str(&line_to_ansi(line));
+            if i + 1 < screen.lines.len() {
+                buf.push_str("\r\n");
+            }
+        }
+        buf.push_str(&format!("\x1b[{};{}H", screen.cursor.row + 1, screen.cursor.col + 1));
+        Ok(buf.into_bytes())
+    }
+
+    /// Resize the remote session to the local terminal.
+    pub async fn resize(&self, rows: u16, cols: u16) -> io::Result<()> {
+        let body = serde_json::json!({ "rows": rows, "cols": cols });
+        self.send(self.http.post(self.url_for("/resize")).json(&body)).await?;
+        Ok(())
+    }
+
+    /// Open the `/ws/raw` stream, authenticating with the bearer token.
+    pub async fn connect(&self) -> io::Result<RemoteStream> {
+        let mut req = self
+            .ws_url()
+            .into_client_request()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        if let Some(token) = &self.token {
+            let value = format!("Bearer {}", token)
+                .parse()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            req.headers_mut().insert("Authorization", value);
+        }
+        let (ws, _) = tokio_tungstenite::connect_async(req).await.map_err(io::Error::other)?;
+        Ok(ws)
+    }
+
+    /// Proxy the local terminal to `ws` until the user detaches, stdin
+    /// closes, or the server ends the stream.
+    ///
+    /// The remote session is resized to the local terminal on entry and
+    /// after every SIGWINCH; the application's redraw then arrives over
+    /// the stream.
+    pub async fn run_streaming(&self, ws: RemoteStream) -> io::Result<()> {
+        let mut stdin = crate::client::StdinReader::spawn()?;
+        let mut sigwinch_rx = crate::client::watch_window_size();
+        if let Ok((rows, cols)) = crate::terminal::terminal_size() {
+            self.spawn_resize(rows, cols);
+        }
+
+        let mut stdout = std::io::stdout();
+        let result = streaming_loop(ws, &mut stdin.rx, &mut sigwinch_rx, &mut stdout, |rows, cols| {
+            self.spawn_resize(rows, cols)
+        })
+        .await;
+
+        stdin.stop().await;
+        result
+    }
+
+    /// Resize in the background so output keeps flowing meanwhile.
+    fn spawn_resize(&self, rows: u16, cols: u16) {
+        let session = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = session.resize(rows, cols).await {
+                tracing::warn!(error = %e, "failed to resize remote session");
+            }
+        });
+    }
+}
+
+/// The `/ws/raw` streaming loop, factored out of
+/// [`RemoteSession::run_streaming`] for testability.
+///
+/// Stdin chunks from `stdin_rx` are sent as binary messages; binary and
+/// text messages from the server are written to `output`. Sizes from
+/// `sigwinch_rx` are passed to `resize`.
+async fn streaming_loop<S>(
+    ws: WebSocketStream<S>,
+    stdin_rx: &mut tokio::sync::mpsc::Receiver<Bytes>,
+    sigwinch_rx: &mut tokio::sync::mpsc::Receiver<(u16, u16)>,
+    output: &mut impl std::io::Write,
+    mut resize: impl FnMut(u16, u16),
+) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (mut ws_tx, mut ws_rx) = ws.split();
+    let mut pending_detach = false;
+    let detach_timer = tokio::time::sleep(DETACH_WINDOW);
+    tokio::pin!(detach_timer);
+
+    loop {
+        tokio::select! {
+            data = stdin_rx.recv() => {
+                match data {
+                    Some(data) if data[..] == *DETACH_KEY => {
+                        if pending_detach {
+                            break;
+                        }
+                        pending_detach = true;
+                        detach_timer.as_mut().reset(tokio::time::Instant::now() + DETACH_WINDOW);
+                    }
+                    Some(data) => {
+                        if std::mem::take(&mut pending_detach)
+                            && ws_tx.send(Message::Binary(Bytes::from_static(DETACH_KEY))).await.is_err()
+                        {
+                            break;
+                        }
+                        if ws_tx.send(Message::Binary(data)).await.is_err() {
+                            break;
+                        }
+                    }
+                    // Stdin closed
+                    None => break,
+                }
+            }
+
+            msg = ws_rx.next() => {
+                match msg {
+                    Some(Ok(Message::Binary(data))) => {
+                        let _ = output.write_all(&data);
+                        let _ = output.flush();
+                    }
+                    Some(Ok(Message::Text(text))) => {
+                        let _ = output.write_all(text.as_bytes());
+                        let _ = output.flush();
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    // Pings are answered by tungstenite itself.
+                    Some(Ok(_)) => {}
+                }
+            }
+
+            size = sigwinch_rx.recv() => {
+                if let Some((rows, cols)) = size {
+                    resize(rows, cols);
+                }
+            }
+
+            // Detach key double-tap timeout expired — deliver the key
+            () = &mut detach_timer, if pending_detach => {
+                pending_detach = false;
+                if ws_tx.send(Message::Binary(Bytes::from_static(DETACH_KEY))).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    let _ = ws_tx.send(Message::Close(None)).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A client/server WebSocket pair over an in-memory duplex pipe.
+    async fn ws_pair() -> (
+        WebSocketStream<tokio::io::DuplexStream>,
+        WebSocketStream<tokio::io::DuplexStream>,
+    ) {
+        use tokio_tungstenite::tungstenite::protocol::Role;
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        (
+            WebSocketStream::from_raw_socket(a, Role::Client, None).await,
+            WebSocketStream::from_raw_socket(b, Role::Server, None).await,
+        )
+    }
+
+    #[test]
+    fn ws_url_follows_the_http_scheme() {
+        let plain = RemoteSession::new("http://host:8080/", "dev", None).unwrap();
+        assert_eq!(plain.ws_url(), "ws://host:8080/sessions/dev/ws/raw");
+        let tls = RemoteSession::new("https://host/wsh", "dev", None).unwrap();
+        assert_eq!(tls.ws_url(), "wss://host/wsh/sessions/dev/ws/raw");
+        assert!(RemoteSession::new("host:8080", "dev", None).is_err());
+    }
+
+    #[tokio::test]
+    async fn stdin_and_output_are_proxied() {
+        let (client, mut server) = ws_pair().await;
+        let (stdin_tx, mut stdin_rx) = tokio::sync::mpsc::channel(4);
+        let (sigwinch_tx, mut sigwinch_rx) = tokio::sync::mpsc::channel(4);
+        let (resized_tx, mut resized_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut output = Vec::new();
+
+        let server_side = async {
+            server.send(Message::Binary(Bytes::from_static(b"hello"))).await.unwrap();
+            stdin_tx.send(Bytes::from_static(b"ls\r")).await.unwrap();
+            let msg = server.next().await.unwrap().unwrap();
+            assert_eq!(msg, Message::Binary(Bytes::from_static(b"ls\r")));
+            sigwinch_tx.send((40, 120)).await.unwrap();
+            assert_eq!(resized_rx.recv().await, Some((40, 120)));
+            drop(stdin_tx);
+            // The client closes once stdin does.
+            while let Some(Ok(msg)) = server.next().await {
+                if msg.is_close() {
+                    break;
+                }
+            }
+        };
+        let client_side = streaming_loop(client, &mut stdin_rx, &mut sigwinch_rx, &mut output, |rows, cols| {
+            resized_tx.send((rows, cols)).unwrap()
+        });
+        let (result, ()) = tokio::join!(client_side, server_side);
+
+        result.unwrap();
+        assert_eq!(output, b"hello");
+    }
+
+    #[tokio::test]
+    async fn double_tap_detaches_and_single_tap_is_delivered() {
+        let (client, mut server) = ws_pair().await;
+        let (stdin_tx, mut stdin_rx) = tokio::sync::mpsc::channel(4);
+        let (_sigwinch_tx, mut sigwinch_rx) = tokio::sync::mpsc::channel(4);
+        let mut output = Vec::new();
+
+        let server_side = async {
+            stdin_tx.send(Bytes::from_static(DETACH_KEY)).await.unwrap();
+            let msg = server.next().await.unwrap().unwrap();
+            assert_eq!(msg, Message::Binary(Bytes::from_static(DETACH_KEY)));
+
+            stdin_tx.send(Bytes::from_static(DETACH_KEY)).await.unwrap();
+            stdin_tx.send(Bytes::from_static(DETACH_KEY)).await.unwrap();
+            let msg = server.next().await.unwrap().unwrap();
+            assert!(msg.is_close(), "expected close, got {:?}", msg);
+        };
+        let client_side = streaming_loop(client, &mut stdin_rx, &mut sigwinch_rx, &mut output, |_, _| {});
+        let (result, ()) = tokio::join!(client_side, server_side);
+        result.unwrap();
+    }
+}
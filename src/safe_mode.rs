@@ -0,0 +1,281 @@
+//! Safe mode: sessions whose shell only runs allowed commands.
+//!
+//! A create request can give a session an allowlist and/or a denylist of
+//! programs. The session's shell is then bash, started with a guard that
+//! checks each simple command before it runs, including those in
+//! pipelines, subshells and command substitutions. A refused command is
+//! skipped: the guard writes an error line to the terminal and an
+//! `OSC 7771 ; <program> ; <command> BEL` sequence, which the parser turns
+//! into a `command_denied` event on the JSON stream (see
+//! [`crate::parser::guard`]).
+//!
+//! Unless the session allows it, output redirection to files is refused
+//! too, so an allowlist of read-only tools gives a session that can
+//! inspect but not change the host.
+//!
+//! The guard runs inside the shell, like [`RunAs`](crate::pty::RunAs) and
+//! [`Sandbox`](crate::sandbox::Sandbox) by prefixing argv, so it is a
+//! guardrail for agents that drive the shell as asked, not a boundary
+//! against one trying to escape: an allowed program that runs others
+//! (`env`, `xargs`, `find -exec`, an interpreter) is not checked inside.
+//! Pair it with a sandbox profile for untrusted code.
+
+use portable_pty::CommandBuilder;
+use serde::{Deserialize, Serialize};
+
+use crate::pty::SpawnCommand;
+
+/// Most programs in each list.
+pub const MAX_PROGRAMS: usize = 256;
+
+/// Environment variable that carries the guard to the shell, which unsets
+/// it once read.
+const RC_VAR: &str = "WSH_SAFE_MODE_RC";
+
+/// Builtins that are always allowed when there is an allowlist, since they
+/// neither run other programs nor change anything outside the shell.
+const ALWAYS_ALLOWED: &[&str] = &[
+    "cd", "pwd", "echo", "printf", "test", "[", "true", "false", ":", "exit", "logout", "type", "help", "history",
+];
+
+/// Always refused: builtins that could switch the guard off or run a
+/// command it doesn't see, and shells, which would start without it.
+const ALWAYS_DENIED: &[&str] = &[
+    "trap", "shopt", "set", "enable", "builtin", "command", "exec", "eval", "source", ".", "bash", "sh", "dash",
+    "zsh", "ksh", "fish", "busybox",
+];
+
+/// A session's command rules.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SafeMode {
+    /// Programs the shell may run; when not empty, nothing else runs
+    /// apart from a few harmless builtins.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+    /// Programs the shell refuses, even when allowed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+    /// Let output be redirected to files.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub allow_redirects: bool,
+}
+
+impl SafeMode {
+    /// Check the lists: at least one program, and names a guard can match
+    /// literally.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.allow.is_empty() && self.deny.is_empty() {
+            return Err("safe_mode needs an allow or deny list".to_string());
+        }
+        for (field, list) in [("allow", &self.allow), ("deny", &self.deny)] {
+            if list.len() > MAX_PROGRAMS {
+                return Err(format!("safe_mode.{} can list at most {} programs", field, MAX_PROGRAMS));
+            }
+            if let Some(name) = list.iter().find(|name| !valid_name(name)) {
+                return Err(format!(
+                    "safe_mode.{}: '{}' is not a program name (letters, digits, '.', '_', '+', '-' and '[')",
+                    field, name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Run `command` under the guard: replace the argv of `cmd`, built
+    /// for `command` by [`Pty::build_command`](crate::pty::Pty::build_command),
+    /// with an interactive bash that reads the guard as its startup file.
+    /// Must be applied before any sandbox or account wrapping.
+    pub fn wrap(&self, cmd: &mut CommandBuilder, command: &SpawnCommand) {
+        // The guard comes in through a process substitution so that no
+        // file is left behind; `exec` keeps its descriptor open for the
+        // shell that reads it.
+        let launch = format!("exec bash --noprofile --rcfile <(printf '%s\\n' \"${RC_VAR}\") -i");
+        let argv = cmd.get_argv_mut();
+        argv.clear();
+        argv.extend(["bash".into(), "-c".into()]);
+        match command {
+            SpawnCommand::Shell { .. } => argv.push(launch.into()),
+            SpawnCommand::Command { command, .. } => {
+                argv.push(format!("{launch}c \"$0\"").into());
+                argv.push(command.into());
+            }
+        }
+        cmd.env(RC_VAR, self.script());
+    }
+
+    /// The bash startup file that installs the guard.
+    fn script(&self) -> String {
+        let quote = |list: &[String]| -> String {
+            list.iter().map(|name| format!("'{}'", name)).collect::<Vec<_>>().join(" ")
+        };
+        let fixed = |list: &[&str]| -> String {
+            list.iter().map(|name| format!("'{}'", name)).collect::<Vec<_>>().join(" ")
+        };
+        GUARD
+            .replace("@ALLOW@", &quote(&self.allow))
+            .replace("@DENY@", &quote(&self.deny))
+            .replace("@ALWAYS_ALLOWED@", &fixed(ALWAYS_ALLOWED))
+            .replace("@ALWAYS_DENIED@", &fixed(ALWAYS_DENIED))
+            .replace("@REDIRECTS@", if self.allow_redirects { "1" } else { "0" })
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 255
+        && name.bytes().all(|b| b.is_ascii_alphanumeric() || b"._+-[".contains(&b))
+}
+
+/// The guard, with its lists filled in by [`SafeMode::script`].
+///
+/// `extdebug` makes bash skip a command when the DEBUG trap returns
+/// non-zero. It is turned on by the first check rather than here, since
+/// set in a startup file it makes `bash -c` look for a debugger.
+const GUARD: &str = r#"
+__wsh_allow=(@ALLOW@)
+__wsh_deny=(@DENY@)
+__wsh_always_allowed=(@ALWAYS_ALLOWED@)
+__wsh_always_denied=(@ALWAYS_DENIED@)
+__wsh_redirects=@REDIRECTS@
+readonly __wsh_allow __wsh_deny __wsh_always_allowed __wsh_always_denied __wsh_redirects PATH
+
+__wsh_refuse() {
+    local line=${2//[$'\001'-$'\037']/ }
+    printf '\033]7771;%s;%s\007wsh: %s: not allowed in safe mode\n' "$1" "$line" "$1" >&2
+    return 1
+}
+
+__wsh_listed() {
+    local name=$1 w
+    shift
+    for w in "$@"; do
+        [ "$w" = "$name" ] && return 0
+    done
+    return 1
+}
+
+__wsh_guard() {
+    shopt -q extdebug || shopt -s extdebug
+    local IFS=$' \t\n' w cmd= target i
+    local -a words
+    read -r -a words <<< "$1"
+    for w in "${words[@]}"; do
+        case $w in
+            [A-Za-z_]*=*) continue ;;
+        esac
+        cmd=$w
+        break
+    done
+    [ -n "$cmd" ] || return 0
+    case $cmd in
+        for|select|case|'[['|'(('*) return 0 ;;
+    esac
+    if [ "$__wsh_redirects" = 0 ]; then
+        for ((i = 0; i < ${#words[@]}; i++)); do
+            w=${words[i]}
+            case $w in
+                *'>'*) ;;
+                *) continue ;;
+            esac
+            target=${w##*>}
+            [ -n "$target" ] || target=${words[i+1]}
+            case $target in
+                '&'*|/dev/null|/dev/stdout|/dev/stderr|/dev/tty) ;;
+                *) __wsh_refuse '>' "$1"; return 1 ;;
+            esac
+        done
+    fi
+    case $cmd in
+        *'$'*|*'`'*) __wsh_refuse "$cmd" "$1"; return 1 ;;
+    esac
+    local name=${cmd##*/}
+    name=${name//[\\\"\']/}
+    if __wsh_listed "$name" "${__wsh_always_denied[@]}" || __wsh_listed "$name" "${__wsh_deny[@]}"; then
+        __wsh_refuse "$name" "$1"
+        return 1
+    fi
+    # A function's body is checked command by command.
+    declare -F -- "$cmd" > /dev/null && return 0
+    if [ ${#__wsh_allow[@]} -gt 0 ] \
+        && ! __wsh_listed "$name" "${__wsh_always_allowed[@]}" \
+        && ! __wsh_listed "$name" "${__wsh_allow[@]}"; then
+        __wsh_refuse "$name" "$1"
+        return 1
+    fi
+    return 0
+}
+readonly -f __wsh_refuse __wsh_listed __wsh_guard
+
+unset WSH_SAFE_MODE_RC
+set -T
+trap '__wsh_guard "$BASH_COMMAND"' DEBUG
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn safe_mode(allow: &[&str], deny: &[&str]) -> SafeMode {
+        SafeMode {
+            allow: allow.iter().map(|s| s.to_string()).collect(),
+            deny: deny.iter().map(|s| s.to_string()).collect(),
+            allow_redirects: false,
+        }
+    }
+
+    #[test]
+    fn validates_lists() {
+        assert!(SafeMode::default().validate().is_err());
+        assert!(safe_mode(&["ls", "g++", "["], &[]).validate().is_ok());
+        let err = safe_mode(&[], &["rm -rf"]).validate().unwrap_err();
+        assert!(err.contains("'rm -rf'"), "{err}");
+        assert!(safe_mode(&["'"], &[]).validate().is_err());
+    }
+
+    /// Run `script` under the guard in `dir` and return its output.
+    fn run_guarded(mode: &SafeMode, script: &str, dir: &std::path::Path) -> String {
+        let mut cmd = CommandBuilder::new("sh");
+        mode.wrap(&mut cmd, &SpawnCommand::Command { command: script.to_string(), interactive: false });
+        let argv: Vec<_> = cmd.get_argv().iter().collect();
+        let output = std::process::Command::new(argv[0])
+            .args(&argv[1..])
+            .env(RC_VAR, cmd.get_env(RC_VAR).unwrap())
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        text
+    }
+
+    #[test]
+    fn guard_skips_refused_commands() {
+        if std::process::Command::new("bash").arg("-c").arg("true").status().is_err() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("z"), "").unwrap();
+        let mode = safe_mode(&["ls", "printf"], &[]);
+        let out = run_guarded(
+            &mode,
+            "printf 'a\\n'; rm -f x; echo $(touch y; printf b); printf c > f; trap - DEBUG; /bin/rm z; printf 'd\\n' 2>&1",
+            dir.path(),
+        );
+        assert!(out.contains("a\n"), "{out}");
+        assert!(out.contains("\x1b]7771;rm;rm -f x\x07wsh: rm: not allowed in safe mode\n"), "{out}");
+        assert!(out.contains("wsh: touch: not allowed"), "{out}");
+        assert!(out.contains("\x1b]7771;>;printf c > f\x07"), "{out}");
+        assert!(out.contains("wsh: trap: not allowed"), "{out}");
+        assert!(out.contains("\x1b]7771;rm;/bin/rm z\x07"), "{out}");
+        assert!(out.contains("b\nd\n"), "{out}");
+        assert!(!dir.path().join("y").exists());
+        assert!(!dir.path().join("f").exists());
+        assert!(dir.path().join("z").exists());
+
+        // With only a denylist, everything else runs.
+        let out = run_guarded(&safe_mode(&[], &["rm"]), "rm z; ls", dir.path());
+        assert!(out.contains("wsh: rm: not allowed"), "{out}");
+        assert!(out.contains("z\n"), "{out}");
+    }
+}
@@ -0,0 +1,270 @@
+//! Sandbox profiles for sessions.
+//!
+//! An operator defines named profiles under `[sandbox]` in the config file;
+//! a create request can only pick one of them by name, so what an untrusted
+//! agent's session may see is decided by the config, not the agent.
+//!
+//! Like [`RunAs`](crate::pty::RunAs), a profile works by prefixing the
+//! child's argv, since `portable_pty` gives no hook between fork and exec:
+//!
+//! - `unshare` runs the child through util-linux `unshare(1)` in fresh
+//!   mount, PID, and network namespaces, optionally chrooted.
+//! - `bwrap` runs it through bubblewrap with operator-supplied arguments.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+
+use portable_pty::CommandBuilder;
+use serde::{Deserialize, Serialize};
+
+/// The `[sandbox]` config section.
+///
+/// ```toml
+/// [sandbox]
+/// default = "jail"
+///
+/// [sandbox.profiles.jail]
+/// backend = "unshare"
+/// root = "/srv/agent-root"
+///
+/// [sandbox.profiles.ro-host]
+/// backend = "bwrap"
+/// args = ["--ro-bind", "/", "/", "--dev", "/dev", "--tmpfs", "/tmp",
+///         "--unshare-all", "--die-with-parent"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SandboxConfig {
+    /// Profile for API and MCP sessions that don't ask for one. Requests
+    /// cannot opt out of it, only pick another profile. Sessions created by
+    /// the local `wsh` CLI are not sandboxed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, SandboxProfile>,
+}
+
+/// How a sandboxed session's child is isolated.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum SandboxProfile {
+    /// New mount, PID, and network namespaces via `unshare(1)`.
+    Unshare {
+        /// Directory to chroot into. Without one the child still sees the
+        /// host filesystem; only its mounts are private.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        root: Option<String>,
+        /// Share the host's network instead of getting an empty namespace.
+        #[serde(default)]
+        network: bool,
+    },
+    /// `bwrap` with the configured arguments, which decide everything
+    /// about the sandbox.
+    Bwrap {
+        #[serde(default = "default_bwrap_program")]
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+fn default_bwrap_program() -> String {
+    "bwrap".to_string()
+}
+
+/// A profile picked for a new session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sandbox {
+    pub name: String,
+    pub profile: SandboxProfile,
+}
+
+impl SandboxConfig {
+    /// Check that `default`, if set, names a configured profile.
+    pub fn validate(&self) -> Result<(), String> {
+        match &self.default {
+            Some(name) if !self.profiles.contains_key(name) => {
+                Err(format!("default profile '{}' is not defined", name))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Names of the configured profiles, sorted.
+    pub fn profile_names(&self) -> Vec<String> {
+        self.profiles.keys().cloned().collect()
+    }
+
+    /// The sandbox for a new session: the `requested` profile, else the
+    /// default, else none. An unknown name is an error, including a
+    /// misconfigured default, so sessions are never silently unsandboxed.
+    pub fn resolve(&self, requested: Option<&str>) -> Result<Option<Sandbox>, String> {
+        let Some(name) = requested.or(self.default.as_deref()) else {
+            return Ok(None);
+        };
+        match self.profiles.get(name) {
+            Some(profile) => Ok(Some(Sandbox {
+                name: name.to_string(),
+                profile: profile.clone(),
+            })),
+            None => Err(format!("unknown sandbox profile: {}", name)),
+        }
+    }
+}
+
+impl Sandbox {
+    /// Whether the child sees a filesystem other than the host's, so host
+    /// paths (program, working directory) can't be checked up front.
+    pub fn replaces_root(&self) -> bool {
+        match &self.profile {
+            SandboxProfile::Unshare { root, .. } => root.is_some(),
+            SandboxProfile::Bwrap { .. } => true,
+        }
+    }
+
+    /// Rewrite `cmd` to run inside the sandbox. `as_root` says whether the
+    /// sandbox program itself will run as root; if not, `unshare` also
+    /// creates a user namespace, which unprivileged users may do.
+    ///
+    /// Must be applied before [`RunAs::wrap`](crate::pty::RunAs::wrap), so
+    /// privileges are dropped before entering the sandbox and the
+    /// trampoline is still reachable on the host filesystem.
+    pub fn wrap(&self, cmd: &mut CommandBuilder, as_root: bool) {
+        let mut prefix: Vec<OsString> = Vec::new();
+        match &self.profile {
+            SandboxProfile::Unshare { root, network } => {
+                prefix.push("unshare".into());
+                if !as_root {
+                    prefix.extend(["--user".into(), "--map-root-user".into()]);
+                }
+                // --kill-child takes the whole namespace down with unshare
+                // when the session hangs up.
+                prefix.extend(
+                    ["--mount", "--pid", "--fork", "--kill-child", "--mount-proc"].map(OsString::from),
+                );
+                if !network {
+                    prefix.push("--net".into());
+                }
+                if let Some(root) = root {
+                    prefix.extend(["--root".into(), root.into()]);
+                    // A working directory is a path inside the new root.
+                    if let Some(cwd) = cmd.get_cwd() {
+                        prefix.extend(["--wd".into(), cwd.clone()]);
+                    }
+                }
+            }
+            SandboxProfile::Bwrap { program, args } => {
+                prefix.push(program.into());
+                prefix.extend(args.iter().map(OsString::from));
+            }
+        }
+        prefix.push("--".into());
+        cmd.get_argv_mut().splice(0..0, prefix);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> SandboxConfig {
+        toml::from_str(
+            r#"
+            default = "jail"
+
+            [profiles.jail]
+            backend = "unshare"
+            root = "/srv/root"
+
+            [profiles.net]
+            backend = "unshare"
+            network = true
+
+            [profiles.bw]
+            backend = "bwrap"
+            args = ["--unshare-all", "--die-with-parent"]
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn argv(cmd: &CommandBuilder) -> Vec<String> {
+        cmd.get_argv().iter().map(|a| a.to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn parse_profiles() {
+        let config = config();
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(config.profile_names(), ["bw", "jail", "net"]);
+        assert_eq!(
+            config.profiles["net"],
+            SandboxProfile::Unshare { root: None, network: true }
+        );
+        assert_eq!(
+            config.profiles["bw"],
+            SandboxProfile::Bwrap {
+                program: "bwrap".into(),
+                args: vec!["--unshare-all".into(), "--die-with-parent".into()],
+            }
+        );
+    }
+
+    #[test]
+    fn resolve_uses_requested_then_default() {
+        let config = config();
+        assert_eq!(config.resolve(Some("net")).unwrap().unwrap().name, "net");
+        assert_eq!(config.resolve(None).unwrap().unwrap().name, "jail");
+        assert_eq!(
+            config.resolve(Some("nope")),
+            Err("unknown sandbox profile: nope".to_string())
+        );
+        assert_eq!(SandboxConfig::default().resolve(None), Ok(None));
+
+        // A default naming a missing profile fails closed.
+        let broken = SandboxConfig { default: Some("gone".into()), ..Default::default() };
+        assert!(broken.validate().is_err());
+        assert!(broken.resolve(None).is_err());
+    }
+
+    #[test]
+    fn wrap_unshare() {
+        let config = config();
+        let jail = config.resolve(Some("jail")).unwrap().unwrap();
+        let mut cmd = CommandBuilder::new("/bin/sh");
+        cmd.cwd("/work");
+        jail.wrap(&mut cmd, true);
+        assert_eq!(
+            argv(&cmd),
+            [
+                "unshare", "--mount", "--pid", "--fork", "--kill-child", "--mount-proc", "--net",
+                "--root", "/srv/root", "--wd", "/work", "--", "/bin/sh",
+            ]
+        );
+        assert!(jail.replaces_root());
+
+        let net = config.resolve(Some("net")).unwrap().unwrap();
+        let mut cmd = CommandBuilder::new("/bin/sh");
+        net.wrap(&mut cmd, false);
+        assert_eq!(
+            argv(&cmd),
+            [
+                "unshare", "--user", "--map-root-user", "--mount", "--pid", "--fork",
+                "--kill-child", "--mount-proc", "--", "/bin/sh",
+            ]
+        );
+        assert!(!net.replaces_root());
+    }
+
+    #[test]
+    fn wrap_bwrap() {
+        let bw = config().resolve(Some("bw")).unwrap().unwrap();
+        let mut cmd = CommandBuilder::new("/bin/sh");
+        cmd.arg("-c");
+        cmd.arg("id");
+        bw.wrap(&mut cmd, true);
+        assert_eq!(
+            argv(&cmd),
+            ["bwrap", "--unshare-all", "--die-with-parent", "--", "/bin/sh", "-c", "id"]
+        );
+    }
+}
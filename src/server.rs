@@ -9,18 +9,21 @@ use bytes::Bytes;
 use std::fs::File;
 use std::io;
 use std::path::{Path, PathBuf};
+#[cfg(feature = "federation")]
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
-use tokio::net::UnixListener;
+use tokio::net::{UnixListener, UnixStream};
 use tracing;
 
+#[cfg(feature = "federation")]
 use crate::federation::manager::FederationManager;
 use crate::federation::registry::{BackendEntry, BackendHealth, BackendRegistry};
+use crate::input::InputSource;
 use crate::panel::layout::compute_layout;
 use crate::protocol::*;
 use crate::pty::SpawnCommand;
-use crate::session::{Session, SessionRegistry};
+use crate::session::{RegistryError, Session, SessionEvent, SessionRegistry, SpawnOptions};
 
 /// Acquire an exclusive flock on the server instance lock file.
 ///
@@ -56,29 +59,20 @@ pub fn acquire_instance_lock(lock_path: &Path) -> io::Result<File> {
 /// Additional federation state passed into the socket server.
 ///
 /// Grouped to avoid bloating function signatures further.
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct FederationState {
+    #[cfg(feature = "federation")]
     pub federation: Arc<tokio::sync::Mutex<FederationManager>>,
     pub backends: BackendRegistry,
     pub config_path: Option<PathBuf>,
     pub local_token: Option<String>,
     pub default_backend_token: Option<String>,
+    #[cfg(feature = "federation")]
     pub ip_access: Option<Arc<crate::federation::ip_access::IpAccessControl>>,
     pub server_id: String,
-}
-
-impl Default for FederationState {
-    fn default() -> Self {
-        Self {
-            federation: Arc::new(tokio::sync::Mutex::new(FederationManager::new())),
-            backends: BackendRegistry::new(),
-            config_path: None,
-            local_token: None,
-            default_backend_token: None,
-            ip_access: None,
-            server_id: String::new(),
-        }
-    }
+    /// Lifecycle config shared with the HTTP server, for the persistence
+    /// toggle. `None` when the socket server runs on its own.
+    pub server_config: Option<std::sync::Arc<crate::api::ServerConfig>>,
 }
 
 /// Start the Unix socket server, listening for CLI client connections.
@@ -126,13 +120,14 @@ pub async fn serve(
             result = listener.accept() => {
                 match result {
                     Ok((stream, _addr)) => {
+                        let peer = socket_peer_source(&stream);
                         let sessions = sessions.clone();
                         let token = token.clone();
                         let shutdown_request = shutdown_request.clone();
                         let hostname = hostname.clone();
                         let fed_state = federation_state.clone();
                         tokio::spawn(async move {
-                            if let Err(e) = handle_client(stream, sessions, token, shutdown_request, hostname, fed_state).await {
+                            if let Err(e) = handle_client(stream, peer, sessions, token, shutdown_request, hostname, fed_state).await {
                                 tracing::debug!(?e, "client connection ended");
                             }
                         });
@@ -149,6 +144,18 @@ pub async fn serve(
     Ok(())
 }
 
+/// Identify a socket client by its peer process for input attribution.
+fn socket_peer_source(stream: &UnixStream) -> InputSource {
+    let peer = match stream.peer_cred() {
+        Ok(cred) => match cred.pid() {
+            Some(pid) => format!("pid:{}", pid),
+            None => format!("uid:{}", cred.uid()),
+        },
+        Err(_) => "unknown".to_string(),
+    };
+    InputSource::Socket { peer }
+}
+
 /// Base directory for all wsh instance files (sockets, locks).
 ///
 /// Returns `$XDG_RUNTIME_DIR/wsh/` or `/tmp/wsh-$USER/wsh/` as fallback.
@@ -182,6 +189,87 @@ pub fn spawn_lock_path_for_instance(name: &str) -> PathBuf {
     instance_dir().join(format!("{}.spawn.lock", name))
 }
 
+/// Compute the daemon state file path for a named instance.
+///
+/// The client that auto-spawns a server records the daemon here, so that
+/// `wsh gc` can find it again if it is orphaned. See [`DaemonState`].
+pub fn daemon_state_path_for_instance(name: &str) -> PathBuf {
+    instance_dir().join(format!("{}.daemon.json", name))
+}
+
+/// What `wsh gc` makes of a recorded daemon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DaemonHealth {
+    /// Running and accepting connections on its socket.
+    Running,
+    /// Running, but its socket is gone or refuses connections, so no
+    /// client can reach it or stop it.
+    Orphaned,
+    /// The process is gone, or its PID now belongs to another program.
+    Dead,
+}
+
+/// Record of an auto-spawned server daemon.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct DaemonState {
+    pub pid: u32,
+    pub instance: String,
+    pub socket: PathBuf,
+    /// Unix time the daemon was spawned, in seconds.
+    pub spawned_at: u64,
+}
+
+impl DaemonState {
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_vec(self).map_err(io::Error::other)?)
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let data = std::fs::read(path)?;
+        serde_json::from_slice(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Check whether the recorded daemon is still running and reachable.
+    pub fn health(&self) -> DaemonHealth {
+        if !self.is_alive() {
+            return DaemonHealth::Dead;
+        }
+        match std::os::unix::net::UnixStream::connect(&self.socket) {
+            Ok(_) => DaemonHealth::Running,
+            Err(_) => DaemonHealth::Orphaned,
+        }
+    }
+
+    /// Whether the PID is still the daemon. Where `/proc` is available the
+    /// command line must name the recorded socket, so a recycled PID is not
+    /// mistaken for it.
+    pub fn is_alive(&self) -> bool {
+        if unsafe { libc::kill(self.pid as libc::pid_t, 0) } != 0 {
+            return false;
+        }
+        match std::fs::read(format!("/proc/{}/cmdline", self.pid)) {
+            Ok(cmdline) => {
+                let socket = self.socket.as_os_str().as_encoded_bytes();
+                cmdline.split(|&b| b == 0).any(|arg| arg == socket)
+            }
+            Err(_) => true,
+        }
+    }
+}
+
+/// Whether some server holds the instance lock for `name`, checked by
+/// briefly trying to take it.
+pub fn instance_lock_held(name: &str) -> bool {
+    let path = lock_path_for_instance(name);
+    if !path.exists() {
+        return false;
+    }
+    matches!(acquire_instance_lock(&path), Err(e) if e.kind() == io::ErrorKind::AddrInUse)
+}
+
 /// Compute the default Unix socket path for this user.
 ///
 /// Equivalent to `socket_path_for_instance("default")`.
@@ -208,6 +296,10 @@ fn resolve_remote_target(
     match server {
         None => Ok(None),
         Some(s) if s == hostname => Ok(None),
+        Some(_) if !fed_state.backends.proxying() => Err(ErrorMsg {
+            code: "feature_disabled".to_string(),
+            message: "federation proxying is switched off".to_string(),
+        }),
         Some(s) => {
             let backend = fed_state.backends.get_by_hostname(s).ok_or_else(|| ErrorMsg {
                 code: "server_not_found".to_string(),
@@ -290,12 +382,13 @@ async fn send_error_frame<S: AsyncWrite + Unpin>(
 ) -> io::Result<()> {
     let frame = Frame::control(FrameType::Error, &err).map_err(io::Error::other)?;
     frame.write_to(stream).await?;
-    Err(io::Error::new(io::ErrorKind::Other, err.message))
+    Err(io::Error::other(err.message))
 }
 
 /// Handle a single client connection.
 async fn handle_client<S: AsyncRead + AsyncWrite + Unpin>(
     mut stream: S,
+    peer: InputSource,
     sessions: SessionRegistry,
     token: Option<String>,
     shutdown_request: tokio_util::sync::CancellationToken,
@@ -317,7 +410,7 @@ async fn handle_client<S: AsyncRead + AsyncWrite + Unpin>(
                 io::Error::new(io::ErrorKind::InvalidData, e)
             })?;
             match resolve_remote_target(msg.server.as_deref(), &hostname, &federation_state) {
-                Ok(None) => handle_create_session(&mut stream, sessions, msg, &hostname).await,
+                Ok(None) => handle_create_session(&mut stream, peer, sessions, msg, &hostname).await,
                 Ok(Some(backend)) => {
                     proxy_create_session(&mut stream, &backend, &msg).await
                 }
@@ -328,7 +421,7 @@ async fn handle_client<S: AsyncRead + AsyncWrite + Unpin>(
             let msg: AttachSessionMsg = frame.parse_json().map_err(|e| {
                 io::Error::new(io::ErrorKind::InvalidData, e)
             })?;
-            handle_attach_session(&mut stream, sessions, msg).await
+            handle_attach_session(&mut stream, peer, sessions, msg).await
         }
         FrameType::ListSessions => {
             let msg: ListSessionsMsg = frame.parse_json().map_err(|e| {
@@ -351,9 +444,7 @@ async fn handle_client<S: AsyncRead + AsyncWrite + Unpin>(
             })?;
             match resolve_remote_target(msg.server.as_deref(), &hostname, &federation_state) {
                 Ok(None) => handle_kill_session(&mut stream, sessions, msg).await,
-                Ok(Some(backend)) => {
-                    proxy_kill_session(&mut stream, &backend, &msg.name).await
-                }
+                Ok(Some(backend)) => proxy_kill_session(&mut stream, &backend, &msg).await,
                 Err(err) => send_error_frame(&mut stream, err).await,
             }
         }
@@ -390,31 +481,63 @@ async fn handle_client<S: AsyncRead + AsyncWrite + Unpin>(
         FrameType::ListServers => {
             handle_list_servers(&mut stream, sessions, &hostname, &federation_state).await
         }
+        #[cfg(feature = "federation")]
         FrameType::AddServer => {
             let msg: AddServerMsg = frame.parse_json().map_err(|e| {
                 io::Error::new(io::ErrorKind::InvalidData, e)
             })?;
             handle_add_server(&mut stream, msg, &federation_state).await
         }
+        #[cfg(feature = "federation")]
         FrameType::RemoveServer => {
             let msg: RemoveServerMsg = frame.parse_json().map_err(|e| {
                 io::Error::new(io::ErrorKind::InvalidData, e)
             })?;
             handle_remove_server(&mut stream, msg, &federation_state).await
         }
+        #[cfg(feature = "federation")]
         FrameType::ReloadConfig => {
             handle_reload_config(&mut stream, &federation_state).await
         }
+        #[cfg(not(feature = "federation"))]
+        FrameType::AddServer | FrameType::RemoveServer | FrameType::ReloadConfig => {
+            handle_federation_unavailable(&mut stream).await
+        }
         FrameType::ServerInfo => {
             handle_server_info(&mut stream, &hostname, &federation_state.server_id).await
         }
+        FrameType::RenameSession => {
+            let msg: RenameSessionMsg = frame.parse_json().map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, e)
+            })?;
+            handle_rename_session(&mut stream, sessions, msg).await
+        }
+        FrameType::ReadOutput => {
+            let msg: ReadOutputMsg = frame.parse_json().map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, e)
+            })?;
+            handle_read_output(&mut stream, sessions, msg).await
+        }
+        FrameType::SessionRequest => {
+            let msg: SessionRequestMsg = frame.parse_json().map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, e)
+            })?;
+            handle_session_request(&mut stream, &peer, sessions, msg).await
+        }
+        FrameType::SetPersist => {
+            let msg: SetPersistMsg = frame.parse_json().map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, e)
+            })?;
+            handle_set_persist(&mut stream, msg, &federation_state).await
+        }
         other => {
             let err = ErrorMsg {
                 code: "invalid_initial_frame".to_string(),
                 message: format!(
                     "expected CreateSession, AttachSession, ListSessions, KillSession, \
                      DetachSession, GetToken, ManageTags, ShutdownServer, ListServers, \
-                     AddServer, RemoveServer, ReloadConfig, or ServerInfo, got {:?}",
+                     AddServer, RemoveServer, ReloadConfig, ServerInfo, RenameSession, ReadOutput, \
+                     SessionRequest, or SetPersist, got {:?}",
                     other
                 ),
             };
@@ -432,37 +555,72 @@ async fn handle_client<S: AsyncRead + AsyncWrite + Unpin>(
 /// Handle a CreateSession request: spawn a new session and enter streaming.
 async fn handle_create_session<S: AsyncRead + AsyncWrite + Unpin>(
     stream: &mut S,
+    peer: InputSource,
     sessions: SessionRegistry,
     msg: CreateSessionMsg,
     hostname: &str,
 ) -> io::Result<()> {
-    let command = match &msg.command {
-        Some(cmd) => SpawnCommand::Command {
+    let ssh = match crate::ssh::SshLaunch::from_request(msg.command.as_deref(), None) {
+        Ok(ssh) => ssh,
+        Err(message) => {
+            return send_error_frame(
+                stream,
+                ErrorMsg { code: "invalid_request".to_string(), message },
+            )
+            .await;
+        }
+    };
+    let command = match (&ssh, &msg.command) {
+        (Some(launch), _) => launch.spawn_command(),
+        (None, Some(cmd)) => SpawnCommand::Command {
             command: cmd.clone(),
             interactive: true,
         },
-        None => SpawnCommand::default(),
+        (None, None) => SpawnCommand::default(),
     };
 
     // Advisory pre-check — see name_available() doc for TOCTOU rationale.
     // The authoritative check is insert() below.
-    sessions.name_available(&msg.name).map_err(|e| {
-        io::Error::new(io::ErrorKind::AlreadyExists, e.to_string())
-    })?;
+    if let Err(e) = sessions.name_available(&msg.name) {
+        return registry_error_response(stream, e).await;
+    }
 
     let rows = msg.rows.max(1);
     let cols = msg.cols.max(1);
 
+    if let Some(ref launch) = ssh {
+        launch.create_log(None)?;
+    }
     let name_for_spawn = msg.name.clone().unwrap_or_default();
-    let cwd = msg.cwd;
-    let env = msg.env;
-    let (session, child_exit_rx) = tokio::task::spawn_blocking(move || {
-        Session::spawn_with_options(name_for_spawn, command, rows, cols, cwd, env)
+    let motd = sessions.motd();
+    let options = SpawnOptions {
+        cwd: msg.cwd,
+        env: msg.env,
+        motd: motd.clone(),
+        scrollback_lines: sessions.scrollback_lines(),
+        ..Default::default()
+    };
+    let (session, mut child_exit_rx) = tokio::task::spawn_blocking(move || {
+        Session::spawn_with_options(name_for_spawn, command, rows, cols, options)
     })
     .await
     .map_err(io::Error::other)?
     .map_err(io::Error::other)?;
 
+    if let Some(ref launch) = ssh {
+        if let Err(e) = launch.wait_connected(&session.usage, &mut child_exit_rx).await {
+            session.shutdown();
+            return send_error_frame(
+                stream,
+                ErrorMsg {
+                    code: "ssh_connect_failed".to_string(),
+                    message: format!("ssh connection failed: {}", e),
+                },
+            )
+            .await;
+        }
+    }
+
     // Validate and set tags before inserting into registry
     if !msg.tags.is_empty() {
         for tag in &msg.tags {
@@ -477,7 +635,7 @@ async fn handle_create_session<S: AsyncRead + AsyncWrite + Unpin>(
         Ok(name) => name,
         Err(e) => {
             session.shutdown();
-            return Err(io::Error::new(io::ErrorKind::AlreadyExists, e.to_string()));
+            return registry_error_response(stream, e).await;
         }
     };
 
@@ -487,9 +645,11 @@ async fn handle_create_session<S: AsyncRead + AsyncWrite + Unpin>(
     let resp = CreateSessionResponseMsg {
         name: name.clone(),
         server: hostname.to_string(),
-        pid: session.pid,
+        pid: session.pid(),
         rows,
         cols,
+        motd,
+        client_policy: sessions.client_policy(),
     };
     let resp_frame = Frame::control(FrameType::CreateSessionResponse, &resp)
         .map_err(io::Error::other)?;
@@ -497,16 +657,44 @@ async fn handle_create_session<S: AsyncRead + AsyncWrite + Unpin>(
 
     tracing::info!(session = %name, "client created session");
 
+    let client_size = session.track_client_size(rows, cols).await;
+
     // Send initial visual state before streaming
     send_initial_visual_state(stream, &session).await?;
 
     // Enter streaming loop
-    run_streaming(stream, &session).await
+    run_streaming(stream, &session, &sessions, client_size, peer).await
+}
+
+/// Report a failed session creation. Maintenance mode is surfaced to the
+/// client as an Error frame so it can show the reason; other registry
+/// errors end the connection.
+async fn registry_error_response<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    err: RegistryError,
+) -> io::Result<()> {
+    match err {
+        RegistryError::Maintenance(reason) => {
+            send_error_frame(
+                stream,
+                ErrorMsg {
+                    code: "server_maintenance".to_string(),
+                    message: format!(
+                        "server is in maintenance, not accepting new sessions: {}",
+                        reason
+                    ),
+                },
+            )
+            .await
+        }
+        other => Err(io::Error::new(io::ErrorKind::AlreadyExists, other.to_string())),
+    }
 }
 
 /// Handle an AttachSession request: look up session and enter streaming.
 async fn handle_attach_session<S: AsyncRead + AsyncWrite + Unpin>(
     stream: &mut S,
+    peer: InputSource,
     sessions: SessionRegistry,
     msg: AttachSessionMsg,
 ) -> io::Result<()> {
@@ -527,20 +715,32 @@ async fn handle_attach_session<S: AsyncRead + AsyncWrite + Unpin>(
         }
     };
 
-    // Resize session to match client terminal (clamp to min 1 to avoid zero-size PTY)
-    let rows = msg.rows.max(1);
-    let cols = msg.cols.max(1);
-    if let Err(e) = session.pty.lock().resize(rows, cols) {
-        tracing::warn!(?e, "failed to resize PTY on attach");
-    }
-    if let Err(e) = session.parser.resize(cols as usize, rows as usize).await {
-        tracing::warn!(?e, "failed to resize parser on attach");
+    let peer_name = match &peer {
+        InputSource::Socket { peer } => Some(peer.clone()),
+        _ => None,
+    };
+    if let Err(e) = crate::hooks::check_attach(&sessions, &session, "socket", peer_name).await {
+        return send_error_frame(
+            stream,
+            ErrorMsg {
+                code: "hook_rejected".to_string(),
+                message: format!("attach rejected: {}", e),
+            },
+        )
+        .await;
     }
 
+    // Register the client's terminal size; the session's resize policy
+    // decides whether (and to what) the PTY is resized.
+    let client_size = session.track_client_size(msg.rows, msg.cols).await;
+
     // Build scrollback/screen data for replay (using Styled format to
     // preserve colors and attributes for the reconnecting client).
     use crate::parser::ansi::line_to_ansi;
     use crate::parser::state::{Format, Query, QueryResponse};
+    // When the client asks for parsed lines, hand them over as-is so it can
+    // lay them out for its own terminal width; otherwise replay raw ANSI.
+    let mut scrollback_lines = None;
     let scrollback_data = match msg.scrollback {
         ScrollbackRequest::None => Vec::new(),
         ScrollbackRequest::All | ScrollbackRequest::Lines(_) => {
@@ -551,11 +751,15 @@ async fn handle_attach_session<S: AsyncRead + AsyncWrite + Unpin>(
             match tokio::time::timeout(
                 Duration::from_secs(10),
                 session.parser.query(Query::Scrollback {
-                    format: Format::Styled,
+                    format: msg.scrollback_format.unwrap_or(Format::Styled),
                     offset: 0,
                     limit,
                 }),
             ).await {
+                Ok(Ok(QueryResponse::Scrollback(sb))) if msg.scrollback_format.is_some() => {
+                    scrollback_lines = Some(sb.lines);
+                    Vec::new()
+                }
                 Ok(Ok(QueryResponse::Scrollback(sb))) => {
                     let mut buf = String::new();
                     for line in &sb.lines {
@@ -573,6 +777,7 @@ async fn handle_attach_session<S: AsyncRead + AsyncWrite + Unpin>(
         Duration::from_secs(10),
         session.parser.query(Query::Screen {
             format: Format::Styled,
+            since: None,
         }),
     ).await {
         Ok(Ok(QueryResponse::Screen(screen))) => {
@@ -585,6 +790,7 @@ async fn handle_attach_session<S: AsyncRead + AsyncWrite + Unpin>(
                     buf.push_str("\r\n");
                 }
             }
+            buf.push_str(&session.parser.redraw_images(&screen));
             // Restore cursor position
             buf.push_str(&format!(
                 "\x1b[{};{}H",
@@ -596,15 +802,19 @@ async fn handle_attach_session<S: AsyncRead + AsyncWrite + Unpin>(
         _ => Vec::new(),
     };
 
+    let (rows, cols) = session.terminal_size.get();
     let resp = AttachSessionResponseMsg {
         name: msg.name.clone(),
-        rows: msg.rows,
-        cols: msg.cols,
+        rows,
+        cols,
         scrollback: scrollback_data,
         screen: screen_data,
         input_mode: session.input_mode.get(),
         screen_mode: *session.screen_mode.read(),
         focused_id: session.focus.focused(),
+        scrollback_lines,
+        motd: sessions.motd(),
+        client_policy: sessions.client_policy(),
     };
     let resp_frame = Frame::control(FrameType::AttachSessionResponse, &resp)
         .map_err(io::Error::other)?;
@@ -616,7 +826,7 @@ async fn handle_attach_session<S: AsyncRead + AsyncWrite + Unpin>(
     send_initial_visual_state(stream, &session).await?;
 
     // Enter streaming loop
-    run_streaming(stream, &session).await
+    run_streaming(stream, &session, &sessions, client_size, peer).await
 }
 
 /// Handle a KillSession request: remove the session or return an error.
@@ -625,9 +835,15 @@ async fn handle_kill_session<S: AsyncRead + AsyncWrite + Unpin>(
     sessions: SessionRegistry,
     msg: KillSessionMsg,
 ) -> io::Result<()> {
+    let mode = match crate::session::KillMode::parse(msg.signal.as_deref(), msg.grace_ms) {
+        Ok(mode) => mode,
+        Err(e) => {
+            return send_error_frame(stream, ErrorMsg { code: "invalid_request".to_string(), message: e }).await;
+        }
+    };
     match sessions.remove(&msg.name) {
         Some(session) => {
-            session.force_kill();
+            session.kill(mode);
             tracing::info!(session = %msg.name, "session killed via socket");
             let resp = KillSessionResponseMsg { name: msg.name };
             let resp_frame = Frame::control(FrameType::KillSessionResponse, &resp)
@@ -651,6 +867,187 @@ async fn handle_kill_session<S: AsyncRead + AsyncWrite + Unpin>(
     }
 }
 
+/// Handle a RenameSession request.
+async fn handle_rename_session<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    sessions: SessionRegistry,
+    msg: RenameSessionMsg,
+) -> io::Result<()> {
+    match sessions.rename(&msg.name, &msg.new_name) {
+        Ok(_) => {
+            tracing::info!(session = %msg.name, new_name = %msg.new_name, "session renamed via socket");
+            let resp = RenameSessionResponseMsg { name: msg.new_name };
+            let resp_frame = Frame::control(FrameType::RenameSessionResponse, &resp)
+                .map_err(io::Error::other)?;
+            resp_frame.write_to(stream).await?;
+            Ok(())
+        }
+        Err(e) => {
+            let code = match e {
+                RegistryError::NotFound(_) => "session_not_found",
+                RegistryError::NameExists(_) => "session_name_conflict",
+                RegistryError::InvalidName(_) => "invalid_session_name",
+                _ => "invalid_request",
+            };
+            send_error_frame(stream, ErrorMsg { code: code.to_string(), message: e.to_string() }).await
+        }
+    }
+}
+
+/// Handle a SessionRequest: run a per-session WebSocket JSON method and
+/// send back its result.
+async fn handle_session_request<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    peer: &InputSource,
+    sessions: SessionRegistry,
+    msg: SessionRequestMsg,
+) -> io::Result<()> {
+    let Some(session) = sessions.get(&msg.name) else {
+        let err = ErrorMsg {
+            code: "session_not_found".to_string(),
+            message: format!("session not found: {}", msg.name),
+        };
+        return send_error_frame(stream, err).await;
+    };
+    let req = crate::api::ws_methods::WsRequest {
+        id: None,
+        method: msg.method,
+        params: msg.params,
+    };
+    let resp = crate::api::ws_methods::dispatch(&req, &session, peer).await;
+    if let Some(err) = resp.error {
+        return send_error_frame(stream, ErrorMsg { code: err.code, message: err.message }).await;
+    }
+    let resp = SessionRequestResponseMsg {
+        result: resp.result.unwrap_or_default(),
+    };
+    let resp_frame = Frame::control(FrameType::SessionRequestResponse, &resp)
+        .map_err(io::Error::other)?;
+    resp_frame.write_to(stream).await
+}
+
+/// Handle a SetPersist request: report, and optionally change, whether
+/// the server outlives its last session.
+async fn handle_set_persist<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    msg: SetPersistMsg,
+    federation_state: &FederationState,
+) -> io::Result<()> {
+    let Some(config) = federation_state.server_config.as_ref() else {
+        let err = ErrorMsg {
+            code: "persist_unavailable".to_string(),
+            message: "this server has no persistence setting".to_string(),
+        };
+        return send_error_frame(stream, err).await;
+    };
+    if let Some(persistent) = msg.persistent {
+        config.set_persistent(persistent);
+        tracing::info!(persistent, "persistence set via socket");
+    }
+    let resp = SetPersistResponseMsg {
+        persistent: config.is_persistent(),
+    };
+    let resp_frame = Frame::control(FrameType::SetPersistResponse, &resp)
+        .map_err(io::Error::other)?;
+    resp_frame.write_to(stream).await
+}
+
+/// Lines per ReadOutputResponse frame.
+const READ_OUTPUT_BATCH: usize = 1000;
+
+/// Handle a ReadOutput request: send the session's complete output lines,
+/// then with `follow` keep sending new ones until the session ends or the
+/// client hangs up.
+async fn handle_read_output<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    sessions: SessionRegistry,
+    msg: ReadOutputMsg,
+) -> io::Result<()> {
+    let Some(session) = sessions.get(&msg.name) else {
+        let err = ErrorMsg {
+            code: "session_not_found".to_string(),
+            message: format!("session not found: {}", msg.name),
+        };
+        let err_frame = Frame::control(FrameType::Error, &err)
+            .map_err(io::Error::other)?;
+        err_frame.write_to(stream).await?;
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("session not found: {}", msg.name),
+        ));
+    };
+
+    // Subscribe before the first read so no output slips in between.
+    let mut events = Box::pin(session.parser.subscribe());
+
+    let mut since = msg.since;
+    if since.is_none() {
+        if let Some(last) = msg.last {
+            let mark = session.parser.mark().await.map_err(io::Error::other)?;
+            since = Some(mark.saturating_sub(last as u64));
+        }
+    }
+    let mut next = send_output(stream, &session, since, true).await?;
+    if !msg.follow {
+        return Ok(());
+    }
+
+    // The client sends nothing more; a read returning means it went away.
+    let (mut reader, mut writer) = tokio::io::split(&mut *stream);
+    let mut probe = [0u8; 1];
+    loop {
+        tokio::select! {
+            event = tokio_stream::StreamExt::next(&mut events) => {
+                if event.is_none() {
+                    return Ok(());
+                }
+                next = send_output(&mut writer, &session, Some(next), false).await?;
+            }
+            _ = session.cancelled.cancelled() => {
+                send_output(&mut writer, &session, Some(next), false).await?;
+                return Ok(());
+            }
+            _ = tokio::io::AsyncReadExt::read(&mut reader, &mut probe) => {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Send the output from line `since` in ReadOutputResponse frames and
+/// return the line number to continue from. Unless `always_send`, nothing
+/// is sent when there's no new output.
+async fn send_output<W: AsyncWrite + Unpin>(
+    stream: &mut W,
+    session: &Session,
+    mut since: Option<u64>,
+    always_send: bool,
+) -> io::Result<u64> {
+    let mut first = always_send;
+    loop {
+        let output = session
+            .parser
+            .output(since, READ_OUTPUT_BATCH)
+            .await
+            .map_err(io::Error::other)?;
+        if first || !output.lines.is_empty() || output.missed > 0 {
+            let resp = ReadOutputResponseMsg {
+                lines: output.lines,
+                next: output.next,
+                missed: output.missed,
+            };
+            let frame = Frame::control(FrameType::ReadOutputResponse, &resp)
+                .map_err(io::Error::other)?;
+            frame.write_to(stream).await?;
+        }
+        first = false;
+        since = Some(output.next);
+        if !output.more {
+            return Ok(output.next);
+        }
+    }
+}
+
 /// Handle a DetachSession request: signal the session to detach attached clients.
 async fn handle_detach_session<S: AsyncRead + AsyncWrite + Unpin>(
     stream: &mut S,
@@ -818,6 +1215,7 @@ async fn handle_list_servers<S: AsyncRead + AsyncWrite + Unpin>(
 }
 
 /// Handle an AddServer request: register a new backend.
+#[cfg(feature = "federation")]
 async fn handle_add_server<S: AsyncRead + AsyncWrite + Unpin>(
     stream: &mut S,
     msg: AddServerMsg,
@@ -864,6 +1262,7 @@ async fn handle_add_server<S: AsyncRead + AsyncWrite + Unpin>(
 }
 
 /// Handle a RemoveServer request: unregister a backend by hostname.
+#[cfg(feature = "federation")]
 async fn handle_remove_server<S: AsyncRead + AsyncWrite + Unpin>(
     stream: &mut S,
     msg: RemoveServerMsg,
@@ -893,6 +1292,7 @@ async fn handle_remove_server<S: AsyncRead + AsyncWrite + Unpin>(
 }
 
 /// Handle a ReloadConfig request: diff config file with current backends.
+#[cfg(feature = "federation")]
 async fn handle_reload_config<S: AsyncRead + AsyncWrite + Unpin>(
     stream: &mut S,
     fed_state: &FederationState,
@@ -954,24 +1354,22 @@ async fn handle_reload_config<S: AsyncRead + AsyncWrite + Unpin>(
     // Add new backends
     let mut added = 0usize;
     for server_config in &new_config.servers {
-        if !current_addresses.contains(&server_config.address) {
-            if manager
+        if !current_addresses.contains(&server_config.address)
+            && manager
                 .add_backend(&server_config.address, server_config.token.as_deref())
                 .is_ok()
             {
                 added += 1;
             }
-        }
     }
 
     // Remove backends no longer in config
     let mut removed = 0usize;
     for backend in &current_backends {
-        if !new_addresses.contains(&backend.address) {
-            if manager.remove_backend_by_address(&backend.address) {
+        if !new_addresses.contains(&backend.address)
+            && manager.remove_backend_by_address(&backend.address) {
                 removed += 1;
             }
-        }
     }
 
     let resp = ReloadConfigResponseMsg { added, removed };
@@ -982,6 +1380,19 @@ async fn handle_reload_config<S: AsyncRead + AsyncWrite + Unpin>(
     Ok(())
 }
 
+/// Reply to a server management request this build can't serve.
+#[cfg(not(feature = "federation"))]
+async fn handle_federation_unavailable<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S) -> io::Result<()> {
+    let err = ErrorMsg {
+        code: "federation_unavailable".to_string(),
+        message: "this server was built without federation support".to_string(),
+    };
+    let err_frame = Frame::control(FrameType::Error, &err)
+        .map_err(io::Error::other)?;
+    err_frame.write_to(stream).await?;
+    Err(io::Error::new(io::ErrorKind::Unsupported, "federation not built in"))
+}
+
 /// Handle a ServerInfo request: return local hostname and version.
 async fn handle_server_info<S: AsyncRead + AsyncWrite + Unpin>(
     stream: &mut S,
@@ -1015,22 +1426,35 @@ async fn handle_list_sessions_aggregated<S: AsyncRead + AsyncWrite + Unpin>(
             let (rows, cols) = session.terminal_size.get();
             let mut tags: Vec<String> = session.tags.read().iter().cloned().collect();
             tags.sort();
+            let metadata = session.metadata.read().clone();
             Some(SessionInfoMsg {
                 name,
                 server: hostname.to_string(),
-                pid: session.pid,
+                pid: session.pid(),
                 command: session.command.clone(),
                 rows,
                 cols,
                 clients: session.clients(),
                 tags,
                 last_activity_ms: session.activity.last_activity_ms(),
+                last_activity_at: session.activity.last_activity_at(),
+                created_at: session.created_at,
+                suspended: session.is_suspended(),
+                hibernated: session.is_hibernated(),
+                title: session.parser.title(),
+                cwd: session.parser.cwd(),
+                metadata,
             })
         })
         .collect();
 
-    // Fetch sessions from all healthy backends in parallel
-    let healthy_backends = fed_state.backends.healthy();
+    // Fetch sessions from all healthy backends in parallel, unless
+    // federation proxying is switched off.
+    let healthy_backends = if fed_state.backends.proxying() {
+        fed_state.backends.healthy()
+    } else {
+        Vec::new()
+    };
     let mut join_set = tokio::task::JoinSet::new();
     for backend in healthy_backends {
         join_set.spawn(async move {
@@ -1079,7 +1503,15 @@ async fn proxy_create_session<S: AsyncRead + AsyncWrite + Unpin>(
             let pid = resp_body["pid"].as_u64().map(|p| p as u32);
             let rows = resp_body["rows"].as_u64().unwrap_or(24) as u16;
             let cols = resp_body["cols"].as_u64().unwrap_or(80) as u16;
-            let resp = CreateSessionResponseMsg { name, server, pid, rows, cols };
+            let resp = CreateSessionResponseMsg {
+                name,
+                server,
+                pid,
+                rows,
+                cols,
+                motd: None,
+                client_policy: ClientPolicy::default(),
+            };
             let resp_frame = Frame::control(FrameType::CreateSessionResponse, &resp)
                 .map_err(io::Error::other)?;
             resp_frame.write_to(stream).await?;
@@ -1130,12 +1562,21 @@ async fn proxy_list_sessions<S: AsyncRead + AsyncWrite + Unpin>(
 async fn proxy_kill_session<S: AsyncRead + AsyncWrite + Unpin>(
     stream: &mut S,
     backend: &BackendEntry,
-    name: &str,
+    msg: &KillSessionMsg,
 ) -> io::Result<()> {
-    let path = format!("/sessions/{}", name);
+    let mode = match crate::session::KillMode::parse(msg.signal.as_deref(), msg.grace_ms) {
+        Ok(mode) => mode,
+        Err(e) => {
+            return send_error_frame(stream, ErrorMsg { code: "invalid_request".to_string(), message: e }).await;
+        }
+    };
+    let mut path = format!("/sessions/{}", msg.name);
+    if mode != crate::session::KillMode::Immediate {
+        path = format!("{}?{}", path, mode.query());
+    }
     match proxy_delete(backend, &path).await {
         Ok(_) => {
-            let resp = KillSessionResponseMsg { name: name.to_string() };
+            let resp = KillSessionResponseMsg { name: msg.name.clone() };
             let resp_frame = Frame::control(FrameType::KillSessionResponse, &resp)
                 .map_err(io::Error::other)?;
             resp_frame.write_to(stream).await?;
@@ -1270,11 +1711,21 @@ async fn write_frame_with_timeout<W: AsyncWriteExt + Unpin>(
 ///
 /// - Client → Server: StdinInput frames are forwarded to session.input_tx
 /// - Server → Client: Session broker output is forwarded as PtyOutput frames
-/// - Client → Server: Resize frames resize the PTY and parser
+/// - Client → Server: Resize frames are reported to the session's resize
+///   policy via `client_size`, which resizes the PTY and parser as needed
+/// - Server → Client: Resize frames announce the session's new size whenever
+///   it changes
+/// - Server → Client: Banner frames announce server maintenance mode, both
+///   on entry and when it changes
+/// - Client → Server: while a handoff request is pending, StdinInput answers
+///   its prompt (`y` approves, `n` denies) instead of reaching the PTY
 /// - Client → Server: Detach frame ends the loop cleanly
 async fn run_streaming<S: AsyncRead + AsyncWrite + Unpin>(
     stream: &mut S,
     session: &Session,
+    sessions: &SessionRegistry,
+    client_size: crate::session::ClientSizeGuard,
+    peer: InputSource,
 ) -> io::Result<()> {
     let _client_guard = match session.connect() {
         Some(guard) => guard,
@@ -1295,7 +1746,6 @@ async fn run_streaming<S: AsyncRead + AsyncWrite + Unpin>(
     let mut output_rx = session.output_rx.subscribe();
 
     let input_tx = session.input_tx.clone();
-    let pty = session.pty.clone();
     let parser = session.parser.clone();
     let activity = session.activity.clone();
     let terminal_size = session.terminal_size.clone();
@@ -1304,6 +1754,10 @@ async fn run_streaming<S: AsyncRead + AsyncWrite + Unpin>(
     let focus = session.focus.clone();
     let mut detach_rx = session.detach_signal.subscribe();
     let mut visual_update_rx = session.visual_update_tx.subscribe();
+    let mut size_rx = terminal_size.subscribe();
+    let mut registry_rx = sessions.subscribe_events();
+    let allow_capture_toggle = sessions.client_policy().allow_capture_toggle;
+    let focus_sequence = sessions.client_policy().focus_sequence().ok().flatten();
 
     // Keepalive: server sends Ping every 30s, expects Pong within 10s.
     // Without this, idle sessions would rely on a hard read timeout to
@@ -1314,6 +1768,13 @@ async fn run_streaming<S: AsyncRead + AsyncWrite + Unpin>(
     let mut last_pong = tokio::time::Instant::now();
     let pong_timeout = Duration::from_secs(10);
 
+    if let Some(reason) = sessions.maintenance() {
+        let msg = BannerMsg { message: Some(reason) };
+        if let Ok(frame) = Frame::control(FrameType::Banner, &msg) {
+            let _ = write_frame_with_timeout(&frame, &mut writer).await;
+        }
+    }
+
     // Main loop: read from client and session output concurrently
     loop {
         tokio::select! {
@@ -1369,6 +1830,31 @@ async fn run_streaming<S: AsyncRead + AsyncWrite + Unpin>(
                 }
             }
 
+            // Session size changed (by any client or the API) → tell the
+            // client so it can render a viewport when its terminal is smaller
+            Ok(()) = size_rx.changed() => {
+                let (rows, cols) = *size_rx.borrow_and_update();
+                if let Ok(frame) = Frame::control(FrameType::Resize, &ResizeMsg { rows, cols }) {
+                    if !write_frame_with_timeout(&frame, &mut writer).await {
+                        break;
+                    }
+                }
+            }
+
+            // Maintenance mode entered or left → Banner frame
+            // (other lifecycle events are ignored; the channel cannot close
+            // while `sessions`, which owns the sender, is borrowed here)
+            result = registry_rx.recv() => {
+                if let Ok(SessionEvent::Maintenance { reason }) = result {
+                    let msg = BannerMsg { message: reason };
+                    if let Ok(frame) = Frame::control(FrameType::Banner, &msg) {
+                        if !write_frame_with_timeout(&frame, &mut writer).await {
+                            break;
+                        }
+                    }
+                }
+            }
+
             // Output from session → client
             result = output_rx.recv() => {
                 match result {
@@ -1418,7 +1904,7 @@ async fn run_streaming<S: AsyncRead + AsyncWrite + Unpin>(
                         use crate::parser::state::{Format, Query, QueryResponse};
                         if let Ok(Ok(QueryResponse::Screen(screen))) = tokio::time::timeout(
                             Duration::from_secs(5),
-                            parser.query(Query::Screen { format: Format::Styled }),
+                            parser.query(Query::Screen { format: Format::Styled, since: None }),
                         ).await {
                             let mut buf = String::new();
                             buf.push_str("\x1b[H\x1b[2J");
@@ -1428,6 +1914,7 @@ async fn run_streaming<S: AsyncRead + AsyncWrite + Unpin>(
                                     buf.push_str("\r\n");
                                 }
                             }
+                            buf.push_str(&parser.redraw_images(&screen));
                             buf.push_str(&format!(
                                 "\x1b[{};{}H",
                                 screen.cursor.row + 1,
@@ -1468,22 +1955,71 @@ async fn run_streaming<S: AsyncRead + AsyncWrite + Unpin>(
                                 let data = &f.payload;
                                 let mode = input_mode.get();
                                 let target = focus.focused();
-                                input_broadcaster.broadcast_input(data, mode, target);
+                                input_broadcaster.broadcast_input(data, mode, target, Some(peer.clone()));
                                 activity.touch();
 
+                                // While an agent's handoff request is pending, the
+                                // terminal answers the prompt; nothing reaches the PTY.
+                                if session.handoff.is_pending() {
+                                    if let Some(approve) = crate::input::handoff::answer_key(data) {
+                                        let _ = session.answer_handoff(approve, peer.clone());
+                                    }
+                                    continue;
+                                }
+
+                                // Likewise while input is held for approval, for the
+                                // oldest held input.
+                                if session.approvals.is_pending() {
+                                    if let Some(approve) = crate::input::handoff::answer_key(data) {
+                                        let session = session.clone();
+                                        tokio::spawn(async move {
+                                            if let Err(e) = session.answer_approval(None, approve).await {
+                                                tracing::warn!(session = %session.name, "failed to deliver approved input: {e}");
+                                            }
+                                        });
+                                    }
+                                    continue;
+                                }
+
                                 // Ctrl+\ toggles input capture; never forwarded to PTY
-                                if crate::input::is_ctrl_backslash(data) {
+                                // unless the client policy disables toggling. If an
+                                // agent holds control through a handoff, it takes
+                                // control back instead.
+                                if allow_capture_toggle && crate::input::is_ctrl_backslash(data) {
+                                    if session.return_control(peer.clone()) {
+                                        continue;
+                                    }
                                     let new_mode = input_mode.toggle();
                                     input_broadcaster.broadcast_mode(new_mode);
                                     tracing::debug!("Ctrl+\\ pressed, toggled to {new_mode:?} mode");
                                     continue;
                                 }
 
-                                // In capture mode, don't forward to PTY
+                                // In capture mode, don't forward to PTY. Keys go
+                                // to the form widget of the focused overlay, if any.
                                 if mode == crate::input::Mode::Capture {
+                                    if focus_sequence.as_deref() == Some(&data[..]) && session.focus_ring().len() >= 2 {
+                                        session.focus_next(peer.clone());
+                                        continue;
+                                    }
+                                    if let Some(target) = focus.focused() {
+                                        match session.overlays.form_key(&target, data) {
+                                            Some(crate::overlay::FormKey::Changed) => {
+                                                let _ = session.visual_update_tx.send(VisualUpdate::OverlaysChanged);
+                                            }
+                                            Some(crate::overlay::FormKey::Submit(value)) => {
+                                                input_broadcaster.broadcast_submit(target, value, Some(peer.clone()));
+                                            }
+                                            Some(crate::overlay::FormKey::Cancel) => {
+                                                input_broadcaster.broadcast_cancel(target, Some(peer.clone()));
+                                            }
+                                            Some(crate::overlay::FormKey::Ignored) | None => {}
+                                        }
+                                    }
                                     continue;
                                 }
 
+                                input_broadcaster.record_input(peer.clone(), &f.payload);
                                 match tokio::time::timeout(
                                     std::time::Duration::from_secs(5),
                                     input_tx.send(f.payload),
@@ -1498,18 +2034,7 @@ async fn run_streaming<S: AsyncRead + AsyncWrite + Unpin>(
                             }
                             FrameType::Resize => {
                                 if let Ok(msg) = f.parse_json::<ResizeMsg>() {
-                                    let rows = msg.rows.max(1);
-                                    let cols = msg.cols.max(1);
-                                    terminal_size.set(rows, cols);
-                                    if let Err(e) = pty.lock().resize(rows, cols) {
-                                        tracing::warn!(?e, "failed to resize PTY");
-                                    }
-                                    if let Err(e) = parser.resize(
-                                        cols as usize,
-                                        rows as usize,
-                                    ).await {
-                                        tracing::warn!(?e, "failed to resize parser");
-                                    }
+                                    client_size.update(msg.rows, msg.cols).await;
                                 }
                             }
                             FrameType::Detach => {
@@ -1565,6 +2090,47 @@ mod tests {
         (path, dir)
     }
 
+    #[test]
+    fn daemon_state_health() {
+        let dir = TempDir::new().unwrap();
+        let socket = dir.path().join("gc.sock");
+        let path = dir.path().join("gc.daemon.json");
+        let mut state = DaemonState {
+            pid: std::process::id(),
+            instance: "gc".to_string(),
+            socket: socket.clone(),
+            spawned_at: 0,
+        };
+        state.write(&path).unwrap();
+        assert_eq!(DaemonState::read(&path).unwrap(), state);
+
+        // Our own PID, but the command line doesn't name the socket.
+        assert_eq!(state.health(), DaemonHealth::Dead);
+
+        // `; :` keeps the shell from exec'ing sleep, so its command line,
+        // naming the socket, stays in place.
+        let mut child = std::process::Command::new("sh")
+            .arg("-c")
+            .arg("sleep 30; :")
+            .arg(&socket)
+            .spawn()
+            .unwrap();
+        state.pid = child.id();
+        // Until the exec completes the child still has our command line.
+        for _ in 0..100 {
+            if state.is_alive() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(state.health(), DaemonHealth::Orphaned);
+        let _listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+        assert_eq!(state.health(), DaemonHealth::Running);
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert_eq!(state.health(), DaemonHealth::Dead);
+    }
+
     #[tokio::test]
     async fn test_create_session_via_socket() {
         let sessions = SessionRegistry::new();
@@ -1626,6 +2192,7 @@ mod tests {
         let msg = AttachSessionMsg {
             name: "attach-target".to_string(),
             scrollback: ScrollbackRequest::None,
+            scrollback_format: None,
             rows: 30,
             cols: 120,
         };
@@ -1652,6 +2219,7 @@ mod tests {
         let msg = AttachSessionMsg {
             name: "nonexistent".to_string(),
             scrollback: ScrollbackRequest::None,
+            scrollback_format: None,
             rows: 24,
             cols: 80,
         };
@@ -1667,6 +2235,43 @@ mod tests {
         std::fs::remove_file(&path).ok();
     }
 
+    #[tokio::test]
+    async fn test_attach_rejected_by_hook_returns_error() {
+        let sessions = SessionRegistry::new();
+        sessions.set_hooks(crate::hooks::Hooks {
+            on_client_attach: Some(crate::hooks::Hook {
+                command: "[ \"$WSH_CLIENT\" != socket ]".to_string(),
+                timeout: Duration::from_secs(10),
+                on_failure: crate::hooks::FailurePolicy::Reject,
+            }),
+            ..Default::default()
+        });
+        let (session, _child_exit_rx) =
+            Session::spawn("gated".to_string(), SpawnCommand::default(), 24, 80).unwrap();
+        sessions.insert(Some("gated".to_string()), session.clone()).unwrap();
+        let (path, _dir) = start_test_server(sessions).await;
+
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+        let msg = AttachSessionMsg {
+            name: "gated".to_string(),
+            scrollback: ScrollbackRequest::None,
+            scrollback_format: None,
+            rows: 24,
+            cols: 80,
+        };
+        let frame = Frame::control(FrameType::AttachSession, &msg).unwrap();
+        frame.write_to(&mut stream).await.unwrap();
+
+        let resp = Frame::read_from(&mut stream).await.unwrap();
+        assert_eq!(resp.frame_type, FrameType::Error);
+        let err: ErrorMsg = resp.parse_json().unwrap();
+        assert_eq!(err.code, "hook_rejected");
+        assert_eq!(session.client_count.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+        session.force_kill();
+        std::fs::remove_file(&path).ok();
+    }
+
     #[tokio::test]
     async fn test_stdin_forwarding() {
         let sessions = SessionRegistry::new();
@@ -1712,6 +2317,45 @@ mod tests {
         std::fs::remove_file(&path).ok();
     }
 
+    #[tokio::test]
+    async fn test_stdin_is_attributed_to_socket_peer() {
+        let sessions = SessionRegistry::new();
+        let (path, _dir) = start_test_server(sessions.clone()).await;
+
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+
+        let msg = CreateSessionMsg {
+            name: Some("audit-test".to_string()),
+            command: None,
+            cwd: None,
+            env: None,
+            rows: 24,
+            cols: 80,
+            tags: vec![],
+            server: None,
+        };
+        let frame = Frame::control(FrameType::CreateSession, &msg).unwrap();
+        frame.write_to(&mut stream).await.unwrap();
+        let _resp = Frame::read_from(&mut stream).await.unwrap();
+
+        let input_frame = Frame::data(FrameType::StdinInput, Bytes::from("true\n"));
+        input_frame.write_to(&mut stream).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let session = sessions.get("audit-test").unwrap();
+        let entries = session.input_broadcaster.audit_entries(None, None);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].text, "true\n");
+        assert_eq!(
+            entries[0].source,
+            InputSource::Socket {
+                peer: format!("pid:{}", std::process::id()),
+            }
+        );
+
+        std::fs::remove_file(&path).ok();
+    }
+
     #[tokio::test]
     async fn test_detach_ends_streaming() {
         let sessions = SessionRegistry::new();
@@ -1788,7 +2432,7 @@ mod tests {
         // Query the parser to verify resize took effect
         let session = sessions.get("resize-test").unwrap();
         use crate::parser::state::{Format, Query, QueryResponse};
-        let resp = session.parser.query(Query::Screen { format: Format::Plain }).await.unwrap();
+        let resp = session.parser.query(Query::Screen { format: Format::Plain, since: None }).await.unwrap();
         if let QueryResponse::Screen(screen) = resp {
             assert_eq!(screen.cols, 120);
             assert_eq!(screen.rows, 40);
@@ -1800,7 +2444,444 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_invalid_initial_frame() {
+    async fn test_resize_ignored_under_fixed_policy() {
+        let sessions = SessionRegistry::new();
+        let (path, _dir) = start_test_server(sessions.clone()).await;
+
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+
+        let msg = CreateSessionMsg {
+            name: Some("fixed-test".to_string()),
+            command: None,
+            cwd: None,
+            env: None,
+            rows: 24,
+            cols: 80,
+            tags: vec![],
+            server: None,
+        };
+        Frame::control(FrameType::CreateSession, &msg)
+            .unwrap()
+            .write_to(&mut stream)
+            .await
+            .unwrap();
+        let _resp = Frame::read_from(&mut stream).await.unwrap();
+
+        let session = sessions.get("fixed-test").unwrap();
+        session
+            .size_arbiter
+            .set_policy(crate::terminal::ResizePolicy::Fixed);
+
+        let resize_msg = ResizeMsg { rows: 40, cols: 120 };
+        Frame::control(FrameType::Resize, &resize_msg)
+            .unwrap()
+            .write_to(&mut stream)
+            .await
+            .unwrap();
+
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        assert_eq!(session.terminal_size.get(), (24, 80));
+        assert_eq!(session.size_arbiter.client_count(), 1);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_session_resize_is_announced_to_client() {
+        let sessions = SessionRegistry::new();
+        let (path, _dir) = start_test_server(sessions.clone()).await;
+
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+
+        let msg = CreateSessionMsg {
+            name: Some("announce-test".to_string()),
+            command: None,
+            cwd: None,
+            env: None,
+            rows: 24,
+            cols: 80,
+            tags: vec![],
+            server: None,
+        };
+        Frame::control(FrameType::CreateSession, &msg)
+            .unwrap()
+            .write_to(&mut stream)
+            .await
+            .unwrap();
+        let _resp = Frame::read_from(&mut stream).await.unwrap();
+
+        // Give the streaming loop time to subscribe
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let session = sessions.get("announce-test").unwrap();
+        session.apply_size(50, 160).await;
+
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+        loop {
+            let frame = tokio::time::timeout_at(deadline, Frame::read_from(&mut stream))
+                .await
+                .expect("timed out waiting for Resize frame")
+                .unwrap();
+            if frame.frame_type == FrameType::Resize {
+                let msg: ResizeMsg = frame.parse_json().unwrap();
+                assert_eq!((msg.rows, msg.cols), (50, 160));
+                break;
+            }
+        }
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_create_and_attach_responses_carry_motd() {
+        let sessions = SessionRegistry::new();
+        sessions.set_motd(Some("Sessions are recorded.".to_string()));
+        let (path, _dir) = start_test_server(sessions.clone()).await;
+
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+        let msg = CreateSessionMsg {
+            name: Some("motd-test".to_string()),
+            command: None,
+            cwd: None,
+            env: None,
+            rows: 24,
+            cols: 80,
+            tags: vec![],
+            server: None,
+        };
+        Frame::control(FrameType::CreateSession, &msg)
+            .unwrap()
+            .write_to(&mut stream)
+            .await
+            .unwrap();
+        let resp = Frame::read_from(&mut stream).await.unwrap();
+        assert_eq!(resp.frame_type, FrameType::CreateSessionResponse);
+        let created: CreateSessionResponseMsg = resp.parse_json().unwrap();
+        assert_eq!(created.motd.as_deref(), Some("Sessions are recorded."));
+
+        let mut other = UnixStream::connect(&path).await.unwrap();
+        let msg = AttachSessionMsg {
+            name: "motd-test".to_string(),
+            scrollback: ScrollbackRequest::None,
+            scrollback_format: None,
+            rows: 24,
+            cols: 80,
+        };
+        Frame::control(FrameType::AttachSession, &msg)
+            .unwrap()
+            .write_to(&mut other)
+            .await
+            .unwrap();
+        let resp = Frame::read_from(&mut other).await.unwrap();
+        assert_eq!(resp.frame_type, FrameType::AttachSessionResponse);
+        let attached: AttachSessionResponseMsg = resp.parse_json().unwrap();
+        assert_eq!(attached.motd.as_deref(), Some("Sessions are recorded."));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_client_policy_is_advertised_and_enforced() {
+        let sessions = SessionRegistry::new();
+        let policy = ClientPolicy {
+            detach_keys: vec!["ctrl+]".to_string()],
+            allow_capture_toggle: false,
+            command_key: None,
+            clipboard: true,
+            focus_key: "tab".to_string(),
+        };
+        sessions.set_client_policy(policy.clone());
+        let (path, _dir) = start_test_server(sessions.clone()).await;
+
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+        let msg = CreateSessionMsg {
+            name: Some("policy-test".to_string()),
+            command: None,
+            cwd: None,
+            env: None,
+            rows: 24,
+            cols: 80,
+            tags: vec![],
+            server: None,
+        };
+        Frame::control(FrameType::CreateSession, &msg)
+            .unwrap()
+            .write_to(&mut stream)
+            .await
+            .unwrap();
+        let resp = Frame::read_from(&mut stream).await.unwrap();
+        let created: CreateSessionResponseMsg = resp.parse_json().unwrap();
+        assert_eq!(created.client_policy, policy);
+
+        // With toggling disabled, Ctrl+\ goes to the PTY instead of
+        // switching to capture mode.
+        Frame::data(FrameType::StdinInput, Bytes::from_static(&[0x1c]))
+            .write_to(&mut stream)
+            .await
+            .unwrap();
+        let session = sessions.get("policy-test").unwrap();
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
+        while session.input_broadcaster.audit_entries(None, None).is_empty()
+            && tokio::time::Instant::now() < deadline
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(session.input_broadcaster.audit_entries(None, None)[0].text, "\x1c");
+        assert_eq!(session.input_mode.get(), crate::input::Mode::Passthrough);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_handoff_is_answered_from_the_terminal() {
+        let sessions = SessionRegistry::new();
+        let (path, _dir) = start_test_server(sessions.clone()).await;
+
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+        let msg = CreateSessionMsg {
+            name: Some("handoff-test".to_string()),
+            command: None,
+            cwd: None,
+            env: None,
+            rows: 24,
+            cols: 80,
+            tags: vec![],
+            server: None,
+        };
+        Frame::control(FrameType::CreateSession, &msg)
+            .unwrap()
+            .write_to(&mut stream)
+            .await
+            .unwrap();
+        let _resp = Frame::read_from(&mut stream).await.unwrap();
+
+        let session = sessions.get("handoff-test").unwrap();
+        let mut events = session.input_broadcaster.subscribe();
+        session
+            .request_handoff("claude".to_string(), Some("run the migration".to_string()), InputSource::Http)
+            .unwrap();
+        assert_eq!(session.overlays.list().len(), 1);
+
+        // Keys other than an answer are held back while the prompt is up.
+        for key in [&b"x"[..], &b"y"[..]] {
+            Frame::data(FrameType::StdinInput, Bytes::copy_from_slice(key))
+                .write_to(&mut stream)
+                .await
+                .unwrap();
+        }
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
+        while session.handoff.holder().is_none() && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(session.handoff.holder().as_deref(), Some("claude"));
+        assert_eq!(session.input_mode.get(), crate::input::Mode::Capture);
+        assert!(session.overlays.list().is_empty());
+
+        // Ctrl+\ takes control back from the agent.
+        Frame::data(FrameType::StdinInput, Bytes::from_static(&[0x1c]))
+            .write_to(&mut stream)
+            .await
+            .unwrap();
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
+        while session.handoff.holder().is_some() && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(session.input_mode.get(), crate::input::Mode::Passthrough);
+
+        let mut actions = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let crate::input::InputEvent::Handoff { action, .. } = event {
+                actions.push(serde_json::to_value(action).unwrap()["action"].clone());
+            }
+        }
+        assert_eq!(actions, vec!["requested", "transferred", "transferred"]);
+
+        let audit = session.input_broadcaster.audit_entries(None, None);
+        assert_eq!(audit.len(), 3);
+        assert!(audit.iter().all(|e| e.handoff.is_some()));
+        assert!(matches!(audit[1].source, InputSource::Socket { .. }));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_captured_keys_fill_in_a_focused_form() {
+        let sessions = SessionRegistry::new();
+        let (path, _dir) = start_test_server(sessions.clone()).await;
+
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+        let msg = CreateSessionMsg {
+            name: Some("form-test".to_string()),
+            command: None,
+            cwd: None,
+            env: None,
+            rows: 24,
+            cols: 80,
+            tags: vec![],
+            server: None,
+        };
+        Frame::control(FrameType::CreateSession, &msg)
+            .unwrap()
+            .write_to(&mut stream)
+            .await
+            .unwrap();
+        let _resp = Frame::read_from(&mut stream).await.unwrap();
+
+        let session = sessions.get("form-test").unwrap();
+        let id = session
+            .overlays
+            .create(0, 0, None, 20, 1, None, vec![], true, crate::overlay::ScreenMode::Normal)
+            .unwrap();
+        let field = crate::overlay::Widget::TextInput { label: String::new(), value: String::new(), cursor: None };
+        session.overlays.set_widget(&id, field).unwrap();
+        session.input_mode.capture();
+        session.focus.focus(id.clone());
+        let mut events = session.input_broadcaster.subscribe();
+
+        for key in [&b"o"[..], &b"k"[..], &b"\r"[..]] {
+            Frame::data(FrameType::StdinInput, Bytes::copy_from_slice(key))
+                .write_to(&mut stream)
+                .await
+                .unwrap();
+        }
+        let submitted = tokio::time::timeout(Duration::from_secs(2), async {
+            loop {
+                if let Ok(crate::input::InputEvent::Submit { target, value, source }) = events.recv().await {
+                    return (target, value, source);
+                }
+            }
+        })
+        .await
+        .expect("no submit event");
+        assert_eq!(submitted.0, id);
+        assert_eq!(submitted.1, serde_json::json!("ok"));
+        assert!(matches!(submitted.2, Some(InputSource::Socket { .. })));
+        // Captured keys never reach the PTY.
+        assert!(session.input_broadcaster.audit_entries(None, None).is_empty());
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_focus_key_cycles_focus_between_overlays() {
+        let sessions = SessionRegistry::new();
+        let (path, _dir) = start_test_server(sessions.clone()).await;
+
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+        let msg = CreateSessionMsg {
+            name: Some("focus-test".to_string()),
+            command: None,
+            cwd: None,
+            env: None,
+            rows: 24,
+            cols: 80,
+            tags: vec![],
+            server: None,
+        };
+        Frame::control(FrameType::CreateSession, &msg)
+            .unwrap()
+            .write_to(&mut stream)
+            .await
+            .unwrap();
+        let _resp = Frame::read_from(&mut stream).await.unwrap();
+
+        let session = sessions.get("focus-test").unwrap();
+        let ids: Vec<String> = (0..2)
+            .map(|_| {
+                session
+                    .overlays
+                    .create(0, 0, None, 20, 1, None, vec![], true, crate::overlay::ScreenMode::Normal)
+                    .unwrap()
+            })
+            .collect();
+        session.input_mode.capture();
+        let mut events = session.input_broadcaster.subscribe();
+
+        for _ in 0..3 {
+            Frame::data(FrameType::StdinInput, Bytes::from_static(b"\t"))
+                .write_to(&mut stream)
+                .await
+                .unwrap();
+        }
+        let mut targets = Vec::new();
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while targets.len() < 3 {
+                if let Ok(crate::input::InputEvent::Focus { target, .. }) = events.recv().await {
+                    targets.push(target.unwrap());
+                }
+            }
+        })
+        .await
+        .expect("no focus events");
+        assert_eq!(targets, [ids[0].clone(), ids[1].clone(), ids[0].clone()]);
+        assert_eq!(session.focus.focused(), Some(ids[0].clone()));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_banner_and_create_rejection() {
+        let sessions = SessionRegistry::new();
+        let (path, _dir) = start_test_server(sessions.clone()).await;
+
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+        let msg = CreateSessionMsg {
+            name: Some("drain-test".to_string()),
+            command: None,
+            cwd: None,
+            env: None,
+            rows: 24,
+            cols: 80,
+            tags: vec![],
+            server: None,
+        };
+        Frame::control(FrameType::CreateSession, &msg)
+            .unwrap()
+            .write_to(&mut stream)
+            .await
+            .unwrap();
+        let _resp = Frame::read_from(&mut stream).await.unwrap();
+
+        // Give the streaming loop time to subscribe
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        sessions.set_maintenance(Some("upgrading".to_string()));
+
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+        loop {
+            let frame = tokio::time::timeout_at(deadline, Frame::read_from(&mut stream))
+                .await
+                .expect("timed out waiting for Banner frame")
+                .unwrap();
+            if frame.frame_type == FrameType::Banner {
+                let msg: BannerMsg = frame.parse_json().unwrap();
+                assert_eq!(msg.message.as_deref(), Some("upgrading"));
+                break;
+            }
+        }
+
+        // New sessions are refused with a maintenance error
+        let mut other = UnixStream::connect(&path).await.unwrap();
+        let msg = CreateSessionMsg {
+            name: Some("refused".to_string()),
+            ..msg
+        };
+        Frame::control(FrameType::CreateSession, &msg)
+            .unwrap()
+            .write_to(&mut other)
+            .await
+            .unwrap();
+        let resp = Frame::read_from(&mut other).await.unwrap();
+        assert_eq!(resp.frame_type, FrameType::Error);
+        let err: ErrorMsg = resp.parse_json().unwrap();
+        assert_eq!(err.code, "server_maintenance");
+        assert!(sessions.get("refused").is_none());
+        assert!(sessions.get("drain-test").is_some());
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_invalid_initial_frame() {
         let sessions = SessionRegistry::new();
         let (path, _dir) = start_test_server(sessions).await;
 
@@ -1941,7 +3022,7 @@ mod tests {
         let (path, _dir) = start_test_server(sessions.clone()).await;
 
         let mut stream = UnixStream::connect(&path).await.unwrap();
-        let msg = KillSessionMsg { name: "kill-me".to_string(), server: None };
+        let msg = KillSessionMsg { name: "kill-me".to_string(), server: None, signal: None, grace_ms: None };
         Frame::control(FrameType::KillSession, &msg)
             .unwrap()
             .write_to(&mut stream)
@@ -1965,7 +3046,7 @@ mod tests {
         let (path, _dir) = start_test_server(sessions).await;
 
         let mut stream = UnixStream::connect(&path).await.unwrap();
-        let msg = KillSessionMsg { name: "nonexistent".to_string(), server: None };
+        let msg = KillSessionMsg { name: "nonexistent".to_string(), server: None, signal: None, grace_ms: None };
         Frame::control(FrameType::KillSession, &msg)
             .unwrap()
             .write_to(&mut stream)
@@ -1980,6 +3061,91 @@ mod tests {
         std::fs::remove_file(&path).ok();
     }
 
+    #[tokio::test]
+    async fn test_session_request_runs_session_methods() {
+        let sessions = SessionRegistry::new();
+        let (session, _rx) = Session::spawn(
+            "managed".to_string(),
+            SpawnCommand::default(),
+            24, 80,
+        ).unwrap();
+        sessions.insert(Some("managed".to_string()), session.clone()).unwrap();
+
+        let (path, _dir) = start_test_server(sessions).await;
+
+        async fn request(path: &Path, method: &str, params: serde_json::Value) -> Frame {
+            let mut stream = UnixStream::connect(path).await.unwrap();
+            let msg = SessionRequestMsg {
+                name: "managed".to_string(),
+                method: method.to_string(),
+                params: Some(params),
+            };
+            Frame::control(FrameType::SessionRequest, &msg)
+                .unwrap()
+                .write_to(&mut stream)
+                .await
+                .unwrap();
+            Frame::read_from(&mut stream).await.unwrap()
+        }
+
+        let resp = request(&path, "resize", serde_json::json!({"rows": 30, "cols": 100})).await;
+        assert_eq!(resp.frame_type, FrameType::SessionRequestResponse);
+        assert_eq!(session.terminal_size.get(), (30, 100));
+
+        let resp = request(&path, "create_overlay", serde_json::json!({
+            "x": 0, "y": 0, "width": 10, "height": 1,
+            "spans": [{"text": "hello"}],
+        })).await;
+        assert_eq!(resp.frame_type, FrameType::SessionRequestResponse);
+        assert_eq!(session.overlays.list().len(), 1);
+
+        let resp = request(&path, "get_screen", serde_json::json!({"format": "plain"})).await;
+        let screen: SessionRequestResponseMsg = resp.parse_json().unwrap();
+        assert_eq!(screen.result["cols"], 100);
+
+        let resp = request(&path, "no_such_method", serde_json::json!({})).await;
+        assert_eq!(resp.frame_type, FrameType::Error);
+        let err: ErrorMsg = resp.parse_json().unwrap();
+        assert_eq!(err.code, "unknown_method");
+    }
+
+    #[tokio::test]
+    async fn test_set_persist_toggles_server_config() {
+        let dir = TempDir::new().unwrap();
+        let socket_path = dir.path().join("test.sock");
+        let config = std::sync::Arc::new(crate::api::ServerConfig::new(false));
+        let federation_state = FederationState {
+            server_config: Some(config.clone()),
+            ..Default::default()
+        };
+        let path = socket_path.clone();
+        tokio::spawn(async move {
+            let cancel = tokio_util::sync::CancellationToken::new();
+            let shutdown_request = tokio_util::sync::CancellationToken::new();
+            serve(SessionRegistry::new(), &path, cancel, None, shutdown_request, "test".to_string(), federation_state).await.unwrap();
+        });
+        for _ in 0..50 {
+            if socket_path.exists() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        for (persistent, expected) in [(None, false), (Some(true), true), (None, true)] {
+            let mut stream = UnixStream::connect(&socket_path).await.unwrap();
+            Frame::control(FrameType::SetPersist, &SetPersistMsg { persistent })
+                .unwrap()
+                .write_to(&mut stream)
+                .await
+                .unwrap();
+            let resp = Frame::read_from(&mut stream).await.unwrap();
+            assert_eq!(resp.frame_type, FrameType::SetPersistResponse);
+            let resp: SetPersistResponseMsg = resp.parse_json().unwrap();
+            assert_eq!(resp.persistent, expected);
+        }
+        assert!(config.is_persistent());
+    }
+
     #[tokio::test]
     async fn test_get_token_returns_none() {
         let sessions = SessionRegistry::new();
@@ -2088,6 +3254,7 @@ mod tests {
         let attach_msg = AttachSessionMsg {
             name: "sb-none-test".to_string(),
             scrollback: ScrollbackRequest::None,
+            scrollback_format: None,
             rows: 24,
             cols: 80,
         };
@@ -2123,6 +3290,7 @@ mod tests {
         let attach_msg = AttachSessionMsg {
             name: "sb-all-test".to_string(),
             scrollback: ScrollbackRequest::All,
+            scrollback_format: None,
             rows: 24,
             cols: 80,
         };
@@ -2158,6 +3326,52 @@ mod tests {
         std::fs::remove_file(&path).ok();
     }
 
+    #[tokio::test]
+    async fn test_attach_scrollback_format_returns_parsed_lines() {
+        let sessions = SessionRegistry::new();
+        let (path, _dir) = start_test_server(sessions.clone()).await;
+
+        let _creator = create_session_with_output(&sessions, &path, "sb-format-test").await;
+
+        let mut stream2 = UnixStream::connect(&path).await.unwrap();
+        let attach_msg = AttachSessionMsg {
+            name: "sb-format-test".to_string(),
+            scrollback: ScrollbackRequest::All,
+            scrollback_format: Some(crate::parser::state::Format::Plain),
+            rows: 24,
+            cols: 80,
+        };
+        Frame::control(FrameType::AttachSession, &attach_msg)
+            .unwrap()
+            .write_to(&mut stream2)
+            .await
+            .unwrap();
+
+        let resp_frame = Frame::read_from(&mut stream2).await.unwrap();
+        assert_eq!(resp_frame.frame_type, FrameType::AttachSessionResponse);
+        let resp: AttachSessionResponseMsg = resp_frame.parse_json().unwrap();
+
+        // Structured lines replace the raw ANSI replay
+        assert!(resp.scrollback.is_empty());
+        let lines = resp.scrollback_lines.expect("expected parsed scrollback lines");
+        let text: String = lines
+            .iter()
+            .map(|l| match l {
+                crate::parser::state::FormattedLine::Plain(s) => s.clone(),
+                crate::parser::state::FormattedLine::Styled(_) => panic!("expected plain lines"),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let combined = format!("{}{}", text, String::from_utf8_lossy(&resp.screen));
+        assert!(
+            combined.contains("scrollback_line_1"),
+            "expected 'scrollback_line_1' in parsed scrollback or screen: {:?}",
+            combined,
+        );
+
+        std::fs::remove_file(&path).ok();
+    }
+
     #[tokio::test]
     async fn test_attach_scrollback_lines_limits_output() {
         let sessions = SessionRegistry::new();
@@ -2205,6 +3419,7 @@ mod tests {
         let attach_msg = AttachSessionMsg {
             name: "sb-lines-test".to_string(),
             scrollback: ScrollbackRequest::Lines(5),
+            scrollback_format: None,
             rows: 24,
             cols: 80,
         };
@@ -2223,6 +3438,7 @@ mod tests {
         let attach_all_msg = AttachSessionMsg {
             name: "sb-lines-test".to_string(),
             scrollback: ScrollbackRequest::All,
+            scrollback_format: None,
             rows: 24,
             cols: 80,
         };
@@ -2275,6 +3491,7 @@ mod tests {
         let attach_msg = AttachSessionMsg {
             name: "screen-test".to_string(),
             scrollback: ScrollbackRequest::None,
+            scrollback_format: None,
             rows: 24,
             cols: 80,
         };
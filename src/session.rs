@@ -7,14 +7,21 @@ use tokio::sync::{broadcast, mpsc};
 use tokio::sync::broadcast as tokio_broadcast;
 
 use crate::activity::ActivityTracker;
-use crate::input::{FocusTracker, InputBroadcaster, InputMode};
-use crate::overlay::{OverlayStore, ScreenMode};
+use crate::input::{
+    Controller, FocusTracker, Handoff, HandoffAction, HandoffError, HandoffRequest, InputBroadcaster,
+    InputMode, InputSource, Mode, PendingInput,
+};
+use crate::overlay::{BackgroundStyle, Color, NamedColor, OverlaySpan, OverlayStore, ScreenMode};
 use crate::panel::PanelStore;
+use crate::parser::state::QueryPolicy;
 use crate::parser::Parser;
 use crate::protocol::VisualUpdate;
-use crate::pty::{Pty, PtyError, SpawnCommand};
+use crate::pty::{Priority, Pty, PtyError, RunAs, SpawnCommand};
+use crate::safe_mode::SafeMode;
+use crate::sandbox::Sandbox;
 use crate::shutdown::ShutdownCoordinator;
-use crate::terminal::TerminalSize;
+use crate::terminal::{SizeArbiter, TerminalSize};
+use crate::usage::UsageCounters;
 
 /// Validate a session name. Names must be 1-64 chars, alphanumeric/hyphens/underscores/dots.
 pub fn validate_session_name(name: &str) -> Result<(), String> {
@@ -31,6 +38,72 @@ pub fn validate_session_name(name: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// Wait for `child` on the blocking pool, recording stops in `suspended`
+/// and how it ended in `exit_status`. The returned receiver fires once it
+/// has exited, or at once without a child.
+fn watch_child(
+    child: Option<Box<dyn portable_pty::Child + Send + Sync>>,
+    suspended: Arc<tokio::sync::watch::Sender<bool>>,
+    exit_status: Arc<parking_lot::Mutex<Option<ExitStatus>>>,
+) -> tokio::sync::oneshot::Receiver<()> {
+    let (child_exit_tx, child_exit_rx) = tokio::sync::oneshot::channel::<()>();
+    let Some(mut child) = child else {
+        // No child to wait on; signal immediately.
+        let _ = child_exit_tx.send(());
+        return child_exit_rx;
+    };
+    let pid = child.process_id();
+    tokio::task::spawn_blocking(move || {
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            // Waiting on the pid directly also reports stops, which
+            // the portable_pty handle can't.
+            let result = match pid {
+                Some(pid) => crate::pty::wait_reporting_stops(pid, |stopped| {
+                    suspended.send_replace(stopped);
+                })
+                .map(ExitStatus::from_wait_status),
+                None => child.wait().map(|status| ExitStatus {
+                    exit_code: Some(status.exit_code() as i32),
+                    signal: None,
+                }),
+            };
+            match result {
+                Ok(status) => {
+                    tracing::debug!(?status, "session child exited");
+                    *exit_status.lock() = Some(status);
+                }
+                Err(e) => tracing::error!(?e, "error waiting for session child"),
+            }
+        }));
+        if let Err(e) = result {
+            tracing::error!("child exit monitor task panicked: {:?}", e);
+        }
+        let _ = child_exit_tx.send(());
+    });
+    child_exit_rx
+}
+
+/// The line written into the scrollback between one run of a restarted
+/// command and the next.
+pub fn format_restart_separator(exit: Option<&ExitStatus>, restarts: usize) -> Vec<u8> {
+    let status = match exit {
+        Some(ExitStatus { signal: Some(signal), .. }) => format!("killed by SIG{}", signal),
+        Some(ExitStatus { exit_code: Some(code), .. }) => format!("exited with code {}", code),
+        _ => "exited".to_string(),
+    };
+    format!("\x1b[0m\r\n\x1b[7m--- {}, restart {} ---\x1b[0m\r\n", status, restarts).into_bytes()
+}
+
+/// Render a message of the day as terminal output: bare `\n` line endings
+/// become `\r\n` and the message always ends on a fresh line.
+pub fn format_motd(text: &str) -> Vec<u8> {
+    let mut out = text.replace("\r\n", "\n").replace('\n', "\r\n");
+    if !out.ends_with("\r\n") {
+        out.push_str("\r\n");
+    }
+    out.into_bytes()
+}
+
 /// Validate a tag string. Tags must be 1-64 chars, alphanumeric/hyphens/underscores/dots.
 pub fn validate_tag(tag: &str) -> Result<(), String> {
     if tag.is_empty() {
@@ -54,8 +127,9 @@ pub fn validate_tag(tag: &str) -> Result<(), String> {
 pub struct Session {
     /// Human-readable session name (displayed in UI, used in URLs).
     pub name: String,
-    /// PID of the child process spawned in the PTY, if available.
-    pub pid: Option<u32>,
+    /// PID of the child process spawned in the PTY, if available. A
+    /// [restart](RestartPolicy) replaces it; read it with [`Session::pid`].
+    pub pid: Arc<parking_lot::Mutex<Option<u32>>>,
     /// Human-readable display of the command being run (e.g. shell path or command string).
     pub command: String,
     /// Number of currently connected streaming clients (WebSocket, socket, etc.).
@@ -63,7 +137,7 @@ pub struct Session {
     /// User-defined tags for organizing and filtering sessions.
     pub tags: Arc<RwLock<HashSet<String>>>,
     pub input_tx: mpsc::Sender<Bytes>,
-    pub output_rx: broadcast::Sender<Bytes>,
+    pub output_rx: crate::broker::Broker,
     pub shutdown: ShutdownCoordinator,
     pub parser: Parser,
     pub overlays: OverlayStore,
@@ -94,13 +168,96 @@ pub struct Session {
     /// Checked by `send_sighup()` and `kill_child()` to avoid signaling a
     /// potentially-recycled PID.
     pub child_exited: Arc<AtomicBool>,
+    /// Resize policy and the sizes reported by attached clients.
+    pub size_arbiter: SizeArbiter,
+    /// Usage counters (commands, bytes, attached time, MCP calls).
+    pub usage: UsageCounters,
+    /// Per-session override of the server's idle kill timeout. `None`
+    /// uses the server default; `Some(Duration::ZERO)` never reaps.
+    pub idle_kill_after: Arc<parking_lot::Mutex<Option<std::time::Duration>>>,
+    /// Whether the child process is stopped (`SIGTSTP`/`SIGSTOP`). Updated
+    /// by the child monitor; [`resume`](Self::resume) continues it.
+    pub suspended: Arc<tokio::sync::watch::Sender<bool>>,
+    /// Set by [`hibernate`](Self::hibernate), cleared by
+    /// [`resume`](Self::resume).
+    pub hibernated: Arc<AtomicBool>,
+    /// How the child process ended, once it has. Set by the child monitor
+    /// before it raises [`child_exited`](Self::child_exited).
+    pub exit_status: Arc<parking_lot::Mutex<Option<ExitStatus>>>,
+    /// Respawns the command when it exits, if the session was created with
+    /// a [`RestartPolicy`].
+    pub restart: Option<Arc<Restarter>>,
+    /// How many lines of scrollback the parser keeps.
+    pub scrollback_lines: Arc<AtomicUsize>,
+    /// Who answers the device and status queries the application sends.
+    /// Read for every chunk of input and output, so writes apply at once.
+    pub query_policy: Arc<RwLock<QueryPolicy>>,
+    /// Pending request for, and current holder of, keyboard control
+    /// handed over by the human. See [`request_handoff`](Self::request_handoff).
+    pub handoff: Handoff,
+    /// Input policy and redaction rules, shared with the registry the
+    /// session is inserted into so that reloads apply at once.
+    pub policy: crate::policy::Policy,
+    /// Masks secrets in the output before the parser and streaming clients
+    /// see it.
+    pub redactor: crate::policy::OutputRedactor,
+    /// `ws_json` connections that a client can reconnect to, by resume
+    /// token. See [`crate::api::resume`].
+    pub resume_tokens: crate::api::resume::ResumeStore,
+    /// Named markers in the session's output. See [`crate::bookmarks`].
+    pub bookmarks: crate::bookmarks::Bookmarks,
+    /// The server-rendered status line. See [`crate::status_line`].
+    pub status_line: crate::status_line::StatusLine,
+    /// Pipes copying the session's output elsewhere. See [`crate::pipe`].
+    pub pipes: crate::pipe::Pipes,
+    /// Approval mode and the input it holds. See [`crate::input::approval`].
+    pub approvals: crate::input::Approvals,
+    /// Structured data API clients attach to the session, such as the ID
+    /// of the agent run that owns it. See [`merge_metadata`](Self::merge_metadata).
+    pub metadata: Arc<RwLock<serde_json::Map<String, serde_json::Value>>>,
+    /// Unix time the session was created, in milliseconds. For a shell
+    /// from the warm pool, when it was claimed.
+    pub created_at: u64,
+    /// The account the child runs under, when not the server's own.
+    pub run_as: Option<RunAs>,
+    /// The commands the shell may run, when limited. See [`crate::safe_mode`].
+    pub safe_mode: Option<SafeMode>,
+    /// The client that created the session, when known.
+    pub owner: Arc<parking_lot::Mutex<Option<Owner>>>,
+}
+
+/// The client that created a session, so that a session left behind by a
+/// crashed agent can be traced to it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct Owner {
+    /// Name of the API token it was created with, on servers with tokens.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// ID of the MCP session whose client created it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mcp_session: Option<String>,
+    /// What happens to the session when that MCP session ends.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cleanup_on_disconnect: Option<DisconnectCleanup>,
+}
+
+/// What to do with a session when the MCP session that owns it ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DisconnectCleanup {
+    /// Leave the session as it is.
+    Keep,
+    /// Kill the session.
+    Kill,
+    /// Detach its streaming clients and leave it running.
+    Detach,
 }
 
 impl std::fmt::Debug for Session {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Session")
             .field("name", &self.name)
-            .field("pid", &self.pid)
+            .field("pid", &self.pid())
             .field("command", &self.command)
             .finish_non_exhaustive()
     }
@@ -112,14 +269,347 @@ impl std::fmt::Debug for Session {
 /// socket connections to a single session.
 const MAX_CLIENTS_PER_SESSION: usize = 64;
 
+/// How long [`Session::send_input`] waits for room in the input channel.
+pub const INPUT_SEND_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How often animated overlay widgets advance a frame.
+const WIDGET_TICK: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Scrollback lines kept per session unless the server's `[sessions]
+/// scrollback_lines` or the session itself says otherwise.
+pub const DEFAULT_SCROLLBACK_LINES: usize = 10_000;
+
+/// Upper bound on a session's scrollback, to keep one session from taking
+/// the host's memory.
+pub const MAX_SCROLLBACK_LINES: usize = 1_000_000;
+
+/// Optional settings for [`Session::spawn_with_options`].
+#[derive(Debug, Clone)]
+pub struct SpawnOptions {
+    /// Working directory for the child.
+    pub cwd: Option<String>,
+    /// Environment variables set for the child.
+    pub env: Option<std::collections::HashMap<String, String>>,
+    /// Written into the terminal, and so the scrollback, before any output
+    /// from the child.
+    pub motd: Option<String>,
+    /// Account the child runs under. A shell session defaults to the
+    /// account's login shell.
+    pub run_as: Option<RunAs>,
+    /// Sandbox profile the child runs inside.
+    pub sandbox: Option<Sandbox>,
+    /// Commands the shell may run. The child becomes a guarded bash.
+    pub safe_mode: Option<SafeMode>,
+    /// CPU and I/O scheduling priority of the child.
+    pub priority: Option<Priority>,
+    /// Lines of scrollback the terminal keeps.
+    pub scrollback_lines: usize,
+    /// Respawn the command in the same terminal when it exits.
+    pub restart: Option<RestartPolicy>,
+}
+
+impl Default for SpawnOptions {
+    fn default() -> Self {
+        Self {
+            cwd: None,
+            env: None,
+            motd: None,
+            run_as: None,
+            sandbox: None,
+            safe_mode: None,
+            priority: None,
+            scrollback_lines: DEFAULT_SCROLLBACK_LINES,
+            restart: None,
+        }
+    }
+}
+
+/// Check a requested scrollback size against [`MAX_SCROLLBACK_LINES`].
+pub fn validate_scrollback_lines(lines: usize) -> Result<usize, String> {
+    if lines > MAX_SCROLLBACK_LINES {
+        return Err(format!("scrollback_lines must be at most {}", MAX_SCROLLBACK_LINES));
+    }
+    Ok(lines)
+}
+
+/// Signals a session's child can be sent by name.
+const SIGNAL_NAMES: &[(&str, libc::c_int)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("USR2", libc::SIGUSR2),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("WINCH", libc::SIGWINCH),
+];
+
+/// Parse a signal given by name (`TERM`, `SIGTERM`, `term`) or number.
+pub fn parse_signal(signal: &str) -> Result<libc::c_int, String> {
+    let signal = signal.trim();
+    if let Ok(number) = signal.parse::<libc::c_int>() {
+        if (1..=64).contains(&number) {
+            return Ok(number);
+        }
+        return Err(format!("signal number out of range: {}", number));
+    }
+    let upper = signal.to_ascii_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    SIGNAL_NAMES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, sig)| *sig)
+        .ok_or_else(|| format!("unknown signal: {}", &signal[..signal.len().min(32)]))
+}
+
+/// The name of `signal` as [`parse_signal`] accepts it (`TERM`), or its
+/// number when it has no name here.
+pub fn signal_name(signal: libc::c_int) -> String {
+    SIGNAL_NAMES
+        .iter()
+        .find(|(_, sig)| *sig == signal)
+        .map(|(name, _)| name.to_string())
+        .unwrap_or_else(|| signal.to_string())
+}
+
+/// How a session's child process ended.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ExitStatus {
+    /// Exit code, when the child exited on its own.
+    pub exit_code: Option<i32>,
+    /// The signal that killed it, as named by [`signal_name`].
+    pub signal: Option<String>,
+}
+
+impl ExitStatus {
+    /// Decode the `waitpid` status of a child that has exited or was killed.
+    pub fn from_wait_status(status: libc::c_int) -> Self {
+        if libc::WIFSIGNALED(status) {
+            ExitStatus { exit_code: None, signal: Some(signal_name(libc::WTERMSIG(status))) }
+        } else {
+            ExitStatus { exit_code: Some(libc::WEXITSTATUS(status)), signal: None }
+        }
+    }
+}
+
+/// Grace period of a graceful kill that names a signal but no grace.
+pub const DEFAULT_KILL_GRACE: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Longest grace period a graceful kill may ask for.
+pub const MAX_KILL_GRACE: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How long the registry keeps the tombstone of a session whose child
+/// exited, unless configured otherwise.
+pub const DEFAULT_TOMBSTONE_RETENTION: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// Most tombstones the registry keeps, however recent.
+const MAX_TOMBSTONES: usize = 256;
+
+/// How an explicit kill ends a session's child.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KillMode {
+    /// SIGKILL the process group at once.
+    Immediate,
+    /// Send `signal` to the process group, then SIGKILL it if the child is
+    /// still running after `grace`.
+    Graceful { signal: libc::c_int, grace: std::time::Duration },
+}
+
+impl KillMode {
+    /// Build a kill mode from the optional `signal` and `grace_ms` of a kill
+    /// request. Neither means an immediate SIGKILL; either alone means a
+    /// graceful kill, with SIGTERM and [`DEFAULT_KILL_GRACE`] filling in
+    /// the one left out.
+    pub fn parse(signal: Option<&str>, grace_ms: Option<u64>) -> Result<Self, String> {
+        if signal.is_none() && grace_ms.is_none() {
+            return Ok(KillMode::Immediate);
+        }
+        let signal = signal.map(parse_signal).transpose()?.unwrap_or(libc::SIGTERM);
+        if signal == libc::SIGKILL {
+            return Ok(KillMode::Immediate);
+        }
+        let grace = grace_ms
+            .map(std::time::Duration::from_millis)
+            .unwrap_or(DEFAULT_KILL_GRACE);
+        if grace > MAX_KILL_GRACE {
+            return Err(format!("grace_ms must be at most {}", MAX_KILL_GRACE.as_millis()));
+        }
+        Ok(KillMode::Graceful { signal, grace })
+    }
+
+    /// The query string (without `?`) that asks a federated backend for this
+    /// kill mode on `DELETE /sessions/{name}`. Empty for an immediate kill.
+    pub fn query(&self) -> String {
+        match self {
+            KillMode::Immediate => String::new(),
+            KillMode::Graceful { signal, grace } => {
+                format!("signal={}&grace_ms={}", signal_name(*signal), grace.as_millis())
+            }
+        }
+    }
+}
+
+/// When a session's command is respawned after it exits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartWhen {
+    /// Only after a non-zero exit status or a fatal signal.
+    OnFailure,
+    /// After every exit.
+    Always,
+}
+
+/// Restarts in a row a restart policy allows unless it says otherwise.
+pub const DEFAULT_RESTART_MAX_RETRIES: u32 = 5;
+
+/// Most restarts in a row a restart policy may allow.
+pub const MAX_RESTART_MAX_RETRIES: u32 = 1000;
+
+/// Delay before the first restart unless the policy says otherwise. Each
+/// further restart in a row waits twice as long as the one before.
+pub const DEFAULT_RESTART_BACKOFF: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Longest delay before a restart, however many came before it.
+pub const MAX_RESTART_BACKOFF: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// A command that runs at least this long before exiting starts the count of
+/// restarts in a row, and the backoff, over.
+pub const RESTART_RESET_AFTER: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Whether, how often and how soon a session's command is respawned in the
+/// same terminal after it exits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct RestartPolicy {
+    pub when: RestartWhen,
+    /// Restarts in a row before the session is left to end.
+    pub max_retries: u32,
+    /// Delay before the first restart in a row, in milliseconds.
+    pub backoff_ms: u64,
+}
+
+impl RestartPolicy {
+    /// Build a policy from the `restart`, `restart_max_retries` and
+    /// `restart_backoff_ms` of a create request. `None` without `when`.
+    pub fn parse(
+        when: Option<RestartWhen>,
+        max_retries: Option<u32>,
+        backoff_ms: Option<u64>,
+    ) -> Result<Option<Self>, String> {
+        let Some(when) = when else {
+            if max_retries.is_some() || backoff_ms.is_some() {
+                return Err("restart_max_retries and restart_backoff_ms need restart".to_string());
+            }
+            return Ok(None);
+        };
+        let max_retries = max_retries.unwrap_or(DEFAULT_RESTART_MAX_RETRIES);
+        if max_retries > MAX_RESTART_MAX_RETRIES {
+            return Err(format!("restart_max_retries must be at most {}", MAX_RESTART_MAX_RETRIES));
+        }
+        let backoff_ms = backoff_ms.unwrap_or(DEFAULT_RESTART_BACKOFF.as_millis() as u64);
+        if backoff_ms > MAX_RESTART_BACKOFF.as_millis() as u64 {
+            return Err(format!("restart_backoff_ms must be at most {}", MAX_RESTART_BACKOFF.as_millis()));
+        }
+        Ok(Some(Self { when, max_retries, backoff_ms }))
+    }
+
+    /// Whether a command that ended with `exit` should be restarted. An
+    /// unknown status counts as a failure.
+    pub fn applies(&self, exit: Option<&ExitStatus>) -> bool {
+        match self.when {
+            RestartWhen::Always => true,
+            RestartWhen::OnFailure => exit.is_none_or(|e| e.exit_code != Some(0)),
+        }
+    }
+
+    /// Delay before restart number `attempt` in a row, counting from 1.
+    pub fn delay(&self, attempt: u32) -> std::time::Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(20);
+        std::time::Duration::from_millis(self.backoff_ms.saturating_mul(factor)).min(MAX_RESTART_BACKOFF)
+    }
+}
+
+/// What a session with a [`RestartPolicy`] keeps to respawn its command.
+pub struct Restarter {
+    pub policy: RestartPolicy,
+    /// The command as first spawned, with its working directory,
+    /// environment, account and sandbox.
+    cmd: portable_pty::CommandBuilder,
+    /// Writes the separator between runs into the scrollback.
+    parser_tx: mpsc::Sender<Bytes>,
+    /// Restarts in a row, reset by a run of [`RESTART_RESET_AFTER`].
+    attempts: AtomicUsize,
+    /// Restarts since the session was created.
+    restarts: AtomicUsize,
+    /// When the current child was spawned.
+    started: parking_lot::Mutex<std::time::Instant>,
+}
+
+impl Restarter {
+    /// Restarts since the session was created.
+    pub fn restarts(&self) -> usize {
+        self.restarts.load(Ordering::Acquire)
+    }
+}
+
+/// Upper bound on a session's metadata, serialized as JSON.
+pub const MAX_METADATA_BYTES: usize = 64 * 1024;
+
+/// The current Unix time in milliseconds.
+pub(crate) fn unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
 /// RAII guard that decrements the session client count on drop.
 pub struct ClientGuard {
     counter: Arc<AtomicUsize>,
+    usage: UsageCounters,
 }
 
 impl Drop for ClientGuard {
     fn drop(&mut self) {
         self.counter.fetch_sub(1, Ordering::Release);
+        self.usage.sync_attached(&self.counter);
+    }
+}
+
+/// RAII guard for a client's entry in the session's [`SizeArbiter`].
+///
+/// Dropping the guard unregisters the client. If the remaining clients call
+/// for a different size (e.g. the largest client left), the resize is applied
+/// on a background task.
+pub struct ClientSizeGuard {
+    session: Session,
+    id: u64,
+}
+
+impl ClientSizeGuard {
+    /// Report a new terminal size for this client.
+    pub async fn update(&self, rows: u16, cols: u16) {
+        let target = self
+            .session
+            .size_arbiter
+            .report(self.id, rows.max(1), cols.max(1));
+        if let Some((rows, cols)) = target {
+            self.session.apply_size(rows, cols).await;
+        }
+    }
+}
+
+impl Drop for ClientSizeGuard {
+    fn drop(&mut self) {
+        if let Some((rows, cols)) = self.session.size_arbiter.unregister(self.id) {
+            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                let session = self.session.clone();
+                handle.spawn(async move { session.apply_size(rows, cols).await });
+            }
+        }
     }
 }
 
@@ -140,8 +630,10 @@ impl Session {
                 .compare_exchange(current, current + 1, Ordering::AcqRel, Ordering::Acquire)
                 .is_ok()
             {
+                self.usage.sync_attached(&self.client_count);
                 return Some(ClientGuard {
                     counter: Arc::clone(&self.client_count),
+                    usage: self.usage.clone(),
                 });
             }
         }
@@ -152,6 +644,128 @@ impl Session {
         self.client_count.load(Ordering::Acquire)
     }
 
+    /// How long the session has been idle: no client attached and no
+    /// terminal input or output. `None` while a client is attached.
+    pub fn idle_for(&self) -> Option<std::time::Duration> {
+        let unattached = self.usage.unattached_for()?;
+        let silent = std::time::Duration::from_millis(self.activity.last_activity_ms());
+        Some(unattached.min(silent))
+    }
+
+    /// PID of the current child process, if available.
+    pub fn pid(&self) -> Option<u32> {
+        *self.pid.lock()
+    }
+
+    /// Whether the child process is currently stopped.
+    pub fn is_suspended(&self) -> bool {
+        *self.suspended.borrow()
+    }
+
+    /// Change the scrollback size, dropping the oldest lines if the
+    /// terminal holds more than `lines`.
+    pub async fn set_scrollback_lines(&self, lines: usize) -> Result<(), crate::parser::ParserError> {
+        self.parser.set_scrollback_limit(lines).await?;
+        self.scrollback_lines.store(lines, Ordering::Release);
+        Ok(())
+    }
+
+    /// Merge `patch` into the session's metadata: keys set to `null` are
+    /// removed and the rest replace what was there. Nothing changes if the
+    /// result would be larger than [`MAX_METADATA_BYTES`].
+    pub fn merge_metadata(&self, patch: serde_json::Map<String, serde_json::Value>) -> Result<(), String> {
+        if patch.keys().any(|key| key.is_empty()) {
+            return Err("metadata keys must not be empty".to_string());
+        }
+        let mut metadata = self.metadata.write();
+        let mut merged = metadata.clone();
+        for (key, value) in patch {
+            if value.is_null() {
+                merged.remove(&key);
+            } else {
+                merged.insert(key, value);
+            }
+        }
+        let size = serde_json::to_vec(&merged).map_or(usize::MAX, |json| json.len());
+        if size > MAX_METADATA_BYTES {
+            return Err(format!("metadata must be at most {} bytes as JSON", MAX_METADATA_BYTES));
+        }
+        *metadata = merged;
+        Ok(())
+    }
+
+    /// Whether the session is hibernated: its child stopped and its
+    /// terminal state moved to disk.
+    pub fn is_hibernated(&self) -> bool {
+        self.hibernated.load(Ordering::Acquire) && self.is_suspended()
+    }
+
+    /// Hibernate the session: detach streaming clients, stop the child's
+    /// process group with `SIGSTOP`, and move the parser's screen and
+    /// scrollback to a file under the instance directory, freeing them.
+    ///
+    /// Reads are served from the file. [`resume`](Self::resume) continues
+    /// the child, and the parser loads its state back on the next output.
+    pub async fn hibernate(&self) -> Result<(), String> {
+        let Some(pid) = self.pid().filter(|&pid| pid != 0 && pid <= i32::MAX as u32) else {
+            return Err("session has no child process".to_string());
+        };
+        if self.child_exited.load(Ordering::Acquire) {
+            return Err("child process has exited".to_string());
+        }
+        let dir = crate::server::instance_dir().join("hibernate");
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            std::fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(&dir)
+                .map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+        }
+
+        self.detach();
+        let mut suspended = self.suspended.subscribe();
+        unsafe {
+            libc::kill(-(pid as i32), libc::SIGSTOP);
+        }
+        // Let the stop land so output the child had in flight reaches the
+        // parser before it hibernates, rather than immediately waking it.
+        let _ = tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            suspended.wait_for(|stopped| *stopped),
+        )
+        .await;
+
+        let path = dir.join(format!("{}-{}", std::process::id(), pid));
+        if let Err(e) = self.parser.hibernate(path).await {
+            unsafe {
+                libc::kill(-(pid as i32), libc::SIGCONT);
+            }
+            return Err(e.to_string());
+        }
+        self.hibernated.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Send `SIGCONT` to the child's process group, resuming a stopped
+    /// or hibernated child. Harmless if it isn't stopped.
+    pub fn resume(&self) {
+        self.hibernated.store(false, Ordering::Release);
+        if let Some(pid) = self.pid() {
+            if pid == 0 || pid > i32::MAX as u32 {
+                tracing::warn!(pid, "PID is 0 or exceeds i32::MAX, cannot send signal");
+                return;
+            }
+            if self.child_exited.load(Ordering::Acquire) {
+                return;
+            }
+            #[cfg(unix)]
+            unsafe {
+                libc::kill(-(pid as i32), libc::SIGCONT);
+            }
+        }
+    }
+
     /// Signal all attached streaming clients to detach.
     ///
     /// The session remains alive — only the streaming connections are closed.
@@ -159,6 +773,119 @@ impl Session {
         let _ = self.detach_signal.send(());
     }
 
+    /// Ask the human at the terminal to hand keyboard control to `agent`.
+    ///
+    /// Shows a prompt overlay across the top row. The request stays
+    /// pending until [`answer_handoff`](Self::answer_handoff) is called,
+    /// from an attached terminal (`y`/`n`) or through the API.
+    pub fn request_handoff(
+        &self,
+        agent: String,
+        reason: Option<String>,
+        source: InputSource,
+    ) -> Result<HandoffRequest, HandoffError> {
+        let request = self.handoff.request(agent, reason)?;
+        let text = crate::input::handoff::prompt_text(&request);
+        let (_, cols) = self.terminal_size.get();
+        let width = (text.chars().count() as u16).min(cols).max(1);
+        let span = OverlaySpan {
+            text,
+            id: None,
+            fg: Some(Color::Named(NamedColor::Black)),
+            bg: None,
+            bold: true,
+            italic: false,
+            underline: false,
+        };
+        let background = Some(BackgroundStyle {
+            bg: Color::Named(NamedColor::Yellow),
+        });
+        let mode = *self.screen_mode.read();
+        match self.overlays.create(0, 0, Some(i32::MAX), width, 1, background, vec![span], false, mode) {
+            Ok(overlay) => {
+                if !self.handoff.set_prompt(request.id, overlay.clone()) {
+                    self.overlays.delete(&overlay);
+                }
+                let _ = self.visual_update_tx.send(VisualUpdate::OverlaysChanged);
+            }
+            Err(e) => tracing::warn!(session = %self.name, "could not show handoff prompt: {e}"),
+        }
+        tracing::info!(session = %self.name, agent = %request.agent, "agent requested keyboard control");
+        self.input_broadcaster.announce_handoff(
+            source,
+            HandoffAction::Requested {
+                id: request.id,
+                agent: request.agent.clone(),
+                reason: request.reason.clone(),
+            },
+        );
+        Ok(request)
+    }
+
+    /// Approve or deny the pending handoff request. Approval puts the
+    /// session into capture mode on the agent's behalf.
+    pub fn answer_handoff(&self, approve: bool, source: InputSource) -> Result<HandoffRequest, HandoffError> {
+        let (request, prompt) = self.handoff.answer(approve)?;
+        self.remove_prompt(prompt);
+        let action = if approve {
+            self.input_mode.capture();
+            self.input_broadcaster.broadcast_mode(Mode::Capture);
+            HandoffAction::Transferred {
+                to: Controller::Agent,
+                agent: request.agent.clone(),
+            }
+        } else {
+            HandoffAction::Denied {
+                id: request.id,
+                agent: request.agent.clone(),
+            }
+        };
+        tracing::info!(session = %self.name, agent = %request.agent, approve, "keyboard handoff answered");
+        self.input_broadcaster.announce_handoff(source, action);
+        Ok(request)
+    }
+
+    /// Give keyboard control back to the human: withdraws a pending
+    /// request and, if an agent holds control through a handoff, releases
+    /// capture mode. Returns false when there was nothing to give back.
+    pub fn return_control(&self, source: InputSource) -> bool {
+        let mut changed = false;
+        if let Some((request, prompt)) = self.handoff.withdraw() {
+            self.remove_prompt(prompt);
+            self.input_broadcaster.announce_handoff(
+                source.clone(),
+                HandoffAction::Withdrawn {
+                    id: request.id,
+                    agent: request.agent,
+                },
+            );
+            changed = true;
+        }
+        if let Some(agent) = self.handoff.release() {
+            self.input_mode.release();
+            self.focus.unfocus();
+            self.input_broadcaster.broadcast_mode(Mode::Passthrough);
+            tracing::info!(session = %self.name, %agent, "keyboard control returned to the human");
+            self.input_broadcaster.announce_handoff(
+                source,
+                HandoffAction::Transferred {
+                    to: Controller::Human,
+                    agent,
+                },
+            );
+            changed = true;
+        }
+        changed
+    }
+
+    fn remove_prompt(&self, prompt: Option<String>) {
+        if let Some(id) = prompt {
+            if self.overlays.delete(&id) {
+                let _ = self.visual_update_tx.send(VisualUpdate::OverlaysChanged);
+            }
+        }
+    }
+
     /// Explicitly shut down this session's background tasks.
     ///
     /// Called when a spawned session cannot be registered in the registry
@@ -167,6 +894,7 @@ impl Session {
     pub fn shutdown(&self) {
         self.cancelled.cancel();
         self.detach();
+        self.pty.lock().close_slave();
     }
 
     /// Forcefully kill this session: cancel all watchers, detach all
@@ -178,9 +906,138 @@ impl Session {
     pub fn force_kill(&self) {
         self.cancelled.cancel();
         self.detach();
+        // A restartable session's terminal closes with this child.
+        self.pty.lock().close_slave();
         self.kill_child();
     }
 
+    /// How long to wait before respawning the command that just exited, or
+    /// `None` if it should stay ended: the session has no restart policy,
+    /// the policy doesn't cover this exit, its retries are used up, or the
+    /// session is being killed.
+    pub fn restart_delay(&self) -> Option<std::time::Duration> {
+        let restart = self.restart.as_ref()?;
+        if self.cancelled.is_cancelled() || !restart.policy.applies(self.exit_status.lock().as_ref()) {
+            return None;
+        }
+        if restart.started.lock().elapsed() >= RESTART_RESET_AFTER {
+            restart.attempts.store(0, Ordering::Release);
+        }
+        let attempt = restart.attempts.load(Ordering::Acquire) + 1;
+        if attempt > restart.policy.max_retries as usize {
+            return None;
+        }
+        Some(restart.policy.delay(attempt as u32))
+    }
+
+    /// Spawn the command again in the same terminal, after a separator
+    /// line in the scrollback. Returns the receiver that fires when the new
+    /// child exits, like the one [`spawn_with_options`](Self::spawn_with_options)
+    /// returns.
+    pub async fn respawn(&self) -> Result<tokio::sync::oneshot::Receiver<()>, PtyError> {
+        let Some(restart) = self.restart.as_ref() else {
+            return Err(PtyError::SpawnCommand(anyhow::anyhow!("session has no restart policy")));
+        };
+        let restarts = restart.restarts.fetch_add(1, Ordering::AcqRel) + 1;
+        restart.attempts.fetch_add(1, Ordering::AcqRel);
+        let separator = Bytes::from(format_restart_separator(self.exit_status.lock().as_ref(), restarts));
+        self.output_rx.publish(&separator);
+        let _ = restart.parser_tx.send(separator).await;
+
+        let child = {
+            let mut pty = self.pty.lock();
+            pty.respawn(restart.cmd.clone())?;
+            pty.take_child()
+        };
+        *restart.started.lock() = std::time::Instant::now();
+        *self.pid.lock() = child.as_ref().and_then(|c| c.process_id());
+        *self.exit_status.lock() = None;
+        self.suspended.send_if_modified(|stopped| std::mem::replace(stopped, false));
+        self.child_exited.store(false, Ordering::Release);
+        Ok(watch_child(child, self.suspended.clone(), self.exit_status.clone()))
+    }
+
+    /// Plain-text lines of the screen once the child's last output has
+    /// been parsed, without trailing blank lines. Waits briefly for output
+    /// to settle; empty if the parser doesn't answer.
+    async fn final_screen(&self) -> Vec<String> {
+        use crate::parser::state::{Format, FormattedLine, Query, QueryResponse};
+        let settle = self.activity.wait_for_fresh_idle(std::time::Duration::from_millis(100));
+        let _ = tokio::time::timeout(std::time::Duration::from_millis(500), settle).await;
+        let query = self.parser.query(Query::Screen { format: Format::Plain, since: None });
+        let screen = match tokio::time::timeout(std::time::Duration::from_secs(1), query).await {
+            Ok(Ok(QueryResponse::Screen(screen))) => screen,
+            _ => return Vec::new(),
+        };
+        let mut lines: Vec<String> = screen
+            .lines
+            .iter()
+            .map(|line| match line {
+                FormattedLine::Plain(text) => text.trim_end().to_string(),
+                FormattedLine::Styled(spans) => {
+                    spans.iter().map(|s| s.text.as_str()).collect::<String>().trim_end().to_string()
+                }
+            })
+            .collect();
+        while lines.last().is_some_and(|line| line.is_empty()) {
+            lines.pop();
+        }
+        lines
+    }
+
+    /// Kill this session as `mode` asks. A graceful kill detaches streaming
+    /// clients and signals the child at once, then escalates to
+    /// [`force_kill`](Self::force_kill) in the background if the child is
+    /// still running when the grace period ends.
+    pub fn kill(&self, mode: KillMode) {
+        let (signal, grace) = match mode {
+            KillMode::Immediate => return self.force_kill(),
+            KillMode::Graceful { signal, grace } => (signal, grace),
+        };
+        self.detach();
+        if !self.send_signal(signal) {
+            return self.force_kill();
+        }
+        let session = self.clone();
+        tokio::spawn(async move {
+            let deadline = tokio::time::Instant::now() + grace;
+            while tokio::time::Instant::now() < deadline
+                && !session.child_exited.load(Ordering::Acquire)
+            {
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+            if !session.child_exited.load(Ordering::Acquire) {
+                tracing::info!(session = %session.name, "child outlived its kill grace period, sending SIGKILL");
+            }
+            session.force_kill();
+        });
+    }
+
+    /// Send `signal` to the child's process group. Terminating signals are
+    /// followed by SIGCONT, since a stopped child only acts on them once
+    /// continued. Returns false when there is no running child to signal.
+    pub fn send_signal(&self, signal: libc::c_int) -> bool {
+        let Some(pid) = self.pid() else {
+            return false;
+        };
+        if pid == 0 || pid > i32::MAX as u32 {
+            tracing::warn!(pid, "PID is 0 or exceeds i32::MAX, cannot send signal");
+            return false;
+        }
+        if self.child_exited.load(Ordering::Acquire) {
+            tracing::debug!(pid, signal, "child already exited, skipping signal");
+            return false;
+        }
+        #[cfg(unix)]
+        unsafe {
+            libc::kill(-(pid as i32), signal);
+            if matches!(signal, libc::SIGHUP | libc::SIGINT | libc::SIGQUIT | libc::SIGTERM) {
+                libc::kill(-(pid as i32), libc::SIGCONT);
+            }
+        }
+        true
+    }
+
     /// Send SIGHUP to the child's process group.
     ///
     /// Used during drain to explicitly request graceful termination,
@@ -192,7 +1049,7 @@ impl Session {
     /// portable_pty calls setsid() when spawning, so the child is the
     /// leader of its own process group.
     pub fn send_sighup(&self) {
-        if let Some(pid) = self.pid {
+        if let Some(pid) = self.pid() {
             if pid == 0 || pid > i32::MAX as u32 {
                 tracing::warn!(pid, "PID is 0 or exceeds i32::MAX, cannot send signal");
                 return;
@@ -201,13 +1058,122 @@ impl Session {
                 tracing::debug!(pid, "child already exited, skipping SIGHUP");
                 return;
             }
+            // A stopped child only acts on SIGHUP once continued.
             #[cfg(unix)]
             unsafe {
                 libc::kill(-(pid as i32), libc::SIGHUP);
+                libc::kill(-(pid as i32), libc::SIGCONT);
             }
         }
     }
 
+    /// Deliver `data` to the PTY as input from `source`: check it against
+    /// the input policy, hold it for approval if it needs it, record it in
+    /// the audit log, and wait up to [`INPUT_SEND_TIMEOUT`] for room in the
+    /// input channel.
+    pub async fn send_input(&self, source: InputSource, data: Bytes) -> Result<Delivery, InputError> {
+        self.policy.check_input(&data).map_err(InputError::Blocked)?;
+        if let Some(pending) = self.hold_input(&source, &data)? {
+            return Ok(Delivery::Held(pending));
+        }
+        self.deliver(source, data).await?;
+        Ok(Delivery::Sent)
+    }
+
+    async fn deliver(&self, source: InputSource, data: Bytes) -> Result<(), InputError> {
+        self.input_broadcaster.record_input(source, &data);
+        tokio::time::timeout(INPUT_SEND_TIMEOUT, self.input_tx.send(data))
+            .await
+            .map_err(|_| InputError::Timeout)?
+            .map_err(|_| InputError::Closed)?;
+        self.activity.touch();
+        Ok(())
+    }
+
+    /// In approval mode, hold input matching an approve pattern of the
+    /// policy instead of delivering it, and prompt the human about it.
+    /// Returns the held input, or `None` when it can be delivered now.
+    pub fn hold_input(&self, source: &InputSource, data: &Bytes) -> Result<Option<PendingInput>, InputError> {
+        if !self.approvals.is_enabled() {
+            return Ok(None);
+        }
+        let Some(pattern) = self.policy.needs_approval(data) else {
+            return Ok(None);
+        };
+        let pending = self
+            .approvals
+            .hold(source.clone(), data.clone(), pattern)
+            .ok_or(InputError::TooManyPending)?;
+        tracing::info!(session = %self.name, id = pending.id, pattern = %pending.pattern, "input held for approval");
+        self.update_approval_prompt();
+        Ok(Some(pending))
+    }
+
+    /// Approve or deny held input `id`, or the oldest when `id` is `None`.
+    /// Approved input is delivered as if its client had just sent it.
+    /// Returns `None` when no such input is held.
+    pub async fn answer_approval(&self, id: Option<u64>, approve: bool) -> Result<Option<PendingInput>, InputError> {
+        let Some(pending) = self.approvals.take(id) else {
+            return Ok(None);
+        };
+        self.update_approval_prompt();
+        tracing::info!(session = %self.name, id = pending.id, approve, "held input answered");
+        if approve {
+            self.deliver(pending.source.clone(), pending.data.clone()).await?;
+        }
+        Ok(Some(pending))
+    }
+
+    /// Show the prompt for the oldest held input across the second row,
+    /// below any handoff prompt, or remove it when nothing is held.
+    fn update_approval_prompt(&self) {
+        let Some(oldest) = self.approvals.oldest() else {
+            if let Some(overlay) = self.approvals.take_prompt() {
+                self.remove_prompt(Some(overlay));
+            }
+            return;
+        };
+        let text = crate::input::approval::prompt_text(&oldest, self.approvals.pending_count());
+        let (rows, cols) = self.terminal_size.get();
+        let width = (text.chars().count() as u16).min(cols).max(1);
+        let span = OverlaySpan {
+            text,
+            id: None,
+            fg: Some(Color::Named(NamedColor::Black)),
+            bg: None,
+            bold: true,
+            italic: false,
+            underline: false,
+        };
+        let background = Some(BackgroundStyle {
+            bg: Color::Named(NamedColor::Yellow),
+        });
+        let mode = *self.screen_mode.read();
+        let row = 1.min(rows.saturating_sub(1));
+        match self.overlays.create(0, row, Some(i32::MAX), width, 1, background, vec![span], false, mode) {
+            Ok(overlay) => {
+                match self.approvals.set_prompt(oldest.id, overlay) {
+                    Ok(previous) => {
+                        if let Some(previous) = previous {
+                            self.overlays.delete(&previous);
+                        }
+                    }
+                    Err(overlay) => {
+                        self.overlays.delete(&overlay);
+                    }
+                }
+                let _ = self.visual_update_tx.send(VisualUpdate::OverlaysChanged);
+            }
+            Err(e) => tracing::warn!(session = %self.name, "could not show approval prompt: {e}"),
+        }
+    }
+
+    /// Whether input sent now would be displayed. Treated as true when the
+    /// terminal attributes can't be read, so secrets are never sent blind.
+    pub fn echoes_input(&self) -> bool {
+        self.pty.lock().echoes_input().unwrap_or(true)
+    }
+
     /// Send SIGKILL to the child's process group.
     ///
     /// Used as an escalation path when the child ignores SIGHUP during
@@ -218,7 +1184,7 @@ impl Session {
     /// potentially-recycled PID. The flag is set by `monitor_child_exit`
     /// when the child process exits.
     pub fn kill_child(&self) {
-        if let Some(pid) = self.pid {
+        if let Some(pid) = self.pid() {
             if pid == 0 || pid > i32::MAX as u32 {
                 tracing::warn!(pid, "PID is 0 or exceeds i32::MAX, cannot send signal");
                 return;
@@ -234,6 +1200,192 @@ impl Session {
         }
     }
 
+    /// Resize the PTY and parser and record the new terminal size.
+    ///
+    /// This bypasses the [`ResizePolicy`](crate::terminal::ResizePolicy);
+    /// callers reconciling client-reported sizes go through
+    /// [`track_client_size`](Self::track_client_size) instead.
+    pub async fn apply_size(&self, rows: u16, cols: u16) {
+        let rows = rows.max(1);
+        let cols = cols.max(1);
+        self.terminal_size.set(rows, cols);
+        if let Err(e) = self.pty.lock().resize(rows, cols) {
+            tracing::warn!(?e, session = %self.name, "failed to resize PTY");
+        }
+        if let Err(e) = self.parser.resize(cols as usize, rows as usize).await {
+            tracing::warn!(?e, session = %self.name, "failed to resize parser");
+        }
+    }
+
+    /// Register an attached client's terminal size with the session's
+    /// resize policy, applying whatever size the policy settles on.
+    ///
+    /// The returned guard forwards later size changes and unregisters the
+    /// client on drop.
+    pub async fn track_client_size(&self, rows: u16, cols: u16) -> ClientSizeGuard {
+        let (id, target) = self.size_arbiter.register(rows.max(1), cols.max(1));
+        if let Some((rows, cols)) = target {
+            self.apply_size(rows, cols).await;
+        }
+        ClientSizeGuard {
+            session: self.clone(),
+            id,
+        }
+    }
+
+    /// The elements focus cycles through: the tab order set on the focus
+    /// tracker, or else the focusable overlays and then panels of the
+    /// current screen mode in stacking order. Elements that are gone or
+    /// not focusable are skipped.
+    pub fn focus_ring(&self) -> Vec<String> {
+        let mode = *self.screen_mode.read();
+        let overlays = self.overlays.list_by_mode(mode).into_iter().filter(|o| o.focusable).map(|o| o.id);
+        let panels = self.panels.list_by_mode(mode).into_iter().filter(|p| p.focusable).map(|p| p.id);
+        let focusable: Vec<String> = overlays.chain(panels).collect();
+        let order = self.focus.order();
+        if order.is_empty() {
+            return focusable;
+        }
+        order.into_iter().filter(|id| focusable.contains(id)).collect()
+    }
+
+    /// Set the order focus cycles through. Every element must be a
+    /// focusable overlay or panel; empty restores the default order.
+    pub fn set_focus_order(&self, ids: Vec<String>) -> Result<(), String> {
+        for id in &ids {
+            let focusable = match self.overlays.get(id) {
+                Some(overlay) => overlay.focusable,
+                None => self.panels.get(id).ok_or_else(|| format!("no overlay or panel with id '{id}'"))?.focusable,
+            };
+            if !focusable {
+                return Err(format!("target '{id}' is not focusable"));
+            }
+        }
+        self.focus.set_order(ids);
+        Ok(())
+    }
+
+    /// Move focus to the next element of the [focus ring](Self::focus_ring)
+    /// and announce it to input subscribers.
+    pub fn focus_next(&self, source: InputSource) -> Option<String> {
+        let next = self.focus.focus_next(&self.focus_ring())?;
+        self.input_broadcaster.broadcast_focus(Some(next.clone()), Some(source));
+        Some(next)
+    }
+
+    /// Bring the status line panel up to date: create it when the status
+    /// line is enabled (or it was deleted), delete it when disabled, and
+    /// otherwise update its text and position.
+    pub async fn refresh_status_line(&self, vars: &crate::status_line::Vars<'_>) {
+        let config = self.status_line.config();
+        let panel = self.status_line.panel().and_then(|id| self.panels.get(&id));
+        if !config.enabled {
+            if let Some(panel) = panel {
+                self.status_line.set_panel(None);
+                self.panels.delete(&panel.id);
+                self.focus.clear_if_focused(&panel.id);
+                crate::panel::reconfigure_layout(&self.panels, &self.terminal_size, &self.pty, &self.parser).await;
+                let _ = self.visual_update_tx.send(VisualUpdate::PanelsChanged);
+            }
+            return;
+        }
+        let cols = self.terminal_size.get().1 as usize;
+        let text: String = crate::status_line::expand(&config.format, vars, &config.segments)
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(cols)
+            .collect();
+        let spans = vec![crate::overlay::OverlaySpan {
+            text,
+            id: None,
+            fg: config.fg.clone(),
+            bg: None,
+            bold: false,
+            italic: false,
+            underline: false,
+        }];
+        let background = config.bg.clone().map(|bg| crate::overlay::BackgroundStyle { bg });
+        match panel {
+            None => {
+                let mode = *self.screen_mode.read();
+                // The lowest z keeps it next to the terminal, inside any
+                // panels clients add on the same edge.
+                let Ok(id) = self.panels.create(config.position, 1, Some(i32::MIN), background, spans, false, mode) else {
+                    return;
+                };
+                self.panels.set_lifetime(&id, None, true);
+                self.status_line.set_panel(Some(id));
+                crate::panel::reconfigure_layout(&self.panels, &self.terminal_size, &self.pty, &self.parser).await;
+                let _ = self.visual_update_tx.send(VisualUpdate::PanelsChanged);
+            }
+            Some(panel) => {
+                let moved = panel.position != config.position;
+                let unchanged = !moved
+                    && panel.spans[..] == spans[..]
+                    && panel.background == background;
+                if unchanged {
+                    return;
+                }
+                let _ = self.panels.patch(&panel.id, Some(config.position), None, None, None, Some(spans));
+                self.panels.set_background(&panel.id, background);
+                if moved {
+                    crate::panel::reconfigure_layout(&self.panels, &self.terminal_size, &self.pty, &self.parser).await;
+                }
+                let _ = self.visual_update_tx.send(VisualUpdate::PanelsChanged);
+            }
+        }
+    }
+
+    /// Play `animation` on an overlay, replacing any animation already
+    /// playing on it. Returns false if the overlay does not exist.
+    pub fn animate_overlay(&self, id: &str, animation: crate::overlay::Animation) -> Result<bool, String> {
+        let Some((token, x, y)) = self.overlays.start_animation(id, &animation)? else {
+            return Ok(false);
+        };
+        let id = id.to_string();
+        let overlays = self.overlays.clone();
+        let focus = self.focus.clone();
+        let visual_update_tx = self.visual_update_tx.clone();
+        let cancelled = self.cancelled.clone();
+        tokio::spawn(async move {
+            let started = tokio::time::Instant::now();
+            let mut frame = tokio::time::interval(crate::overlay::animation::FRAME);
+            frame.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            let mut position = None;
+            let mut reached = 0;
+            loop {
+                tokio::select! {
+                    _ = frame.tick() => {}
+                    _ = cancelled.cancelled() => return,
+                }
+                let elapsed = started.elapsed().as_millis() as u64;
+                let next = animation.position_at((x, y), elapsed);
+                let (mut background, mut spans) = (None, None);
+                while let Some(keyframe) = animation.keyframes.get(reached).filter(|k| k.at_ms <= elapsed) {
+                    background = keyframe.background.clone().or(background);
+                    spans = keyframe.spans.clone().or(spans);
+                    reached += 1;
+                }
+                if position != Some(next) || background.is_some() || spans.is_some() {
+                    if !overlays.apply_frame(&id, token, next, background, spans) {
+                        overlays.finish_animation(&id, token);
+                        return;
+                    }
+                    position = Some(next);
+                    let _ = visual_update_tx.send(VisualUpdate::OverlaysChanged);
+                }
+                if reached == animation.keyframes.len() {
+                    break;
+                }
+            }
+            if overlays.finish_animation(&id, token) && animation.delete && overlays.delete(&id) {
+                focus.clear_if_focused(&id);
+                let _ = visual_update_tx.send(VisualUpdate::OverlaysChanged);
+            }
+        });
+        Ok(true)
+    }
+
     /// Spawn a new session with a PTY and all associated I/O tasks.
     ///
     /// The PTY reader only publishes to the broker (no stdout -- server mode).
@@ -248,18 +1400,23 @@ impl Session {
         rows: u16,
         cols: u16,
     ) -> Result<(Self, tokio::sync::oneshot::Receiver<()>), PtyError> {
-        Self::spawn_with_options(name, command, rows, cols, None, None)
+        Self::spawn_with_options(name, command, rows, cols, SpawnOptions::default())
     }
 
-    /// Spawn a new session with optional cwd and environment overrides.
+    /// Spawn a new session with the working directory, environment, account
+    /// and other settings in `options`. See [`SpawnOptions`].
     pub fn spawn_with_options(
         name: String,
         command: SpawnCommand,
         rows: u16,
         cols: u16,
-        cwd: Option<String>,
-        env: Option<std::collections::HashMap<String, String>>,
+        options: SpawnOptions,
     ) -> Result<(Self, tokio::sync::oneshot::Receiver<()>), PtyError> {
+        let SpawnOptions { cwd, env, motd, run_as, sandbox, safe_mode, priority, scrollback_lines, restart } = options;
+        let command = match run_as {
+            Some(ref account) => account.default_shell(command),
+            None => command,
+        };
         let command_display = match &command {
             SpawnCommand::Shell { shell, .. } => {
                 shell.clone().unwrap_or_else(|| {
@@ -269,6 +1426,9 @@ impl Session {
             SpawnCommand::Command { command, .. } => command.clone(),
         };
         let mut cmd = Pty::build_command(&command);
+        if let Some(ref safe_mode) = safe_mode {
+            safe_mode.wrap(&mut cmd, &command);
+        }
         if let Some(ref dir) = cwd {
             cmd.cwd(dir);
         }
@@ -277,7 +1437,24 @@ impl Session {
                 cmd.env(k, v);
             }
         }
-        let mut pty = Pty::spawn_with_cmd(rows, cols, cmd)?;
+        if let Some(ref sandbox) = sandbox {
+            // SAFETY: geteuid has no preconditions and cannot fail.
+            let as_root = run_as.is_none() && unsafe { libc::geteuid() } == 0;
+            sandbox.wrap(&mut cmd, as_root);
+        }
+        if let Some(ref account) = run_as {
+            account.wrap(&mut cmd)?;
+        }
+        if let Some(ref priority) = priority {
+            priority.wrap(&mut cmd)?;
+        }
+        // A restartable session keeps the command to spawn it again, and
+        // the terminal open for it.
+        let restart_cmd = restart.map(|policy| (policy, cmd.clone()));
+        let mut pty = match restart_cmd {
+            Some(_) => Pty::spawn_respawnable(rows, cols, cmd)?,
+            None => Pty::spawn_with_cmd(rows, cols, cmd)?,
+        };
         let pty_reader = pty.take_reader()?;
         let pty_writer = pty.take_writer()?;
         let pty_child = pty.take_child();
@@ -296,24 +1473,9 @@ impl Session {
         //   2. The tokio runtime does not abort blocking tasks on shutdown —
         //      they run to completion on the blocking thread pool.
         //   3. drain() already ensures children are killed within 3 seconds.
-        let (child_exit_tx, child_exit_rx) = tokio::sync::oneshot::channel::<()>();
-        if let Some(mut child) = pty_child {
-            tokio::task::spawn_blocking(move || {
-                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-                    match child.wait() {
-                        Ok(status) => tracing::debug!(?status, "session child exited"),
-                        Err(e) => tracing::error!(?e, "error waiting for session child"),
-                    }
-                }));
-                if let Err(e) = result {
-                    tracing::error!("child exit monitor task panicked: {:?}", e);
-                }
-                let _ = child_exit_tx.send(());
-            });
-        } else {
-            // No child to wait on; signal immediately.
-            let _ = child_exit_tx.send(());
-        }
+        let suspended: Arc<tokio::sync::watch::Sender<bool>> = Default::default();
+        let exit_status: Arc<parking_lot::Mutex<Option<ExitStatus>>> = Default::default();
+        let child_exit_rx = watch_child(pty_child, suspended.clone(), exit_status.clone());
 
         let broker = crate::broker::Broker::new();
 
@@ -371,9 +1533,44 @@ impl Session {
         // ────────────────────────────────────────────────────────────────────
         const PARSER_CHANNEL_CAPACITY: usize = 256;
         let (parser_tx, parser_rx) = mpsc::channel::<Bytes>(PARSER_CHANNEL_CAPACITY);
-        let parser = Parser::spawn(parser_rx, cols as usize, rows as usize, 10_000);
+        let parser = Parser::spawn(parser_rx, cols as usize, rows as usize, scrollback_lines);
+
+        let restart = restart_cmd.map(|(policy, cmd)| {
+            Arc::new(Restarter {
+                policy,
+                cmd,
+                parser_tx: parser_tx.clone(),
+                attempts: AtomicUsize::new(0),
+                restarts: AtomicUsize::new(0),
+                started: parking_lot::Mutex::new(std::time::Instant::now()),
+            })
+        });
+
+        let (input_tx, mut input_rx) = mpsc::channel::<Bytes>(64);
+
+        // Client input passes through a filter that drops replies to the
+        // queries the policy doesn't pass through. The parser's own replies
+        // skip the filter by going straight to `pty_tx`. It only holds a weak
+        // sender, so the writer still stops once `input_tx` is gone.
+        let query_policy = Arc::new(RwLock::new(QueryPolicy::default()));
+        let (pty_tx, pty_rx) = mpsc::channel::<Bytes>(64);
+        parser.answer_queries(query_policy.clone(), pty_tx.downgrade());
+        {
+            let query_policy = query_policy.clone();
+            tokio::spawn(async move {
+                while let Some(data) = input_rx.recv().await {
+                    let data = match crate::parser::queries::strip_replies(&data, &query_policy.read()) {
+                        Some(rest) if rest.is_empty() => continue,
+                        Some(rest) => Bytes::from(rest),
+                        None => data,
+                    };
+                    if pty_tx.send(data).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
 
-        let (input_tx, input_rx) = mpsc::channel::<Bytes>(64);
         let shutdown = ShutdownCoordinator::new();
         let overlays = OverlayStore::new();
         let panels = PanelStore::new();
@@ -383,6 +1580,14 @@ impl Session {
         let focus = FocusTracker::new();
         let terminal_size = TerminalSize::new(rows, cols);
 
+        // The PTY reader hasn't started yet, so the MOTD is guaranteed to
+        // precede anything the child prints.
+        if let Some(text) = motd.as_deref() {
+            let data = Bytes::from(format_motd(text));
+            broker.publish(&data);
+            let _ = parser_tx.try_send(data);
+        }
+
         // Spawn PTY reader (server mode -- no stdout, only broker + parser)
         //
         // Order matters: broadcast first (non-blocking, lossy for streaming
@@ -391,6 +1596,10 @@ impl Session {
         // blocked by parser throughput.
         let broker_clone = broker.clone();
         let activity_clone = activity.clone();
+        let usage = UsageCounters::new();
+        let usage_reader = usage.clone();
+        let redactor = crate::policy::OutputRedactor::default();
+        let mut redact_stream = crate::policy::RedactStream::new(redactor.clone());
         tokio::task::spawn_blocking(move || {
             let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
                 use std::io::Read;
@@ -400,9 +1609,13 @@ impl Session {
                     match reader.read(&mut buf) {
                         Ok(0) => break,
                         Ok(n) => {
-                            let data = Bytes::copy_from_slice(&buf[..n]);
+                            usage_reader.record_output(n);
+                            let data = Bytes::from(redact_stream.push(&buf[..n], n == buf.len()));
+                            if data.is_empty() {
+                                continue;
+                            }
                             // 1. Broadcast to streaming clients (non-blocking, lossy)
-                            broker_clone.publish(data.clone());
+                            broker_clone.publish(&data);
                             // 2. Send to parser (blocks if channel full → PTY backpressure)
                             if parser_tx.blocking_send(data).is_err() {
                                 // Parser channel closed — session is shutting down
@@ -413,6 +1626,12 @@ impl Session {
                         Err(_) => break,
                     }
                 }
+                let rest = redact_stream.flush();
+                if !rest.is_empty() {
+                    let data = Bytes::from(rest);
+                    broker_clone.publish(&data);
+                    let _ = parser_tx.blocking_send(data);
+                }
             }));
             if let Err(e) = result {
                 tracing::error!("PTY reader task panicked: {:?}", e);
@@ -423,7 +1642,7 @@ impl Session {
         //
         // ── REVIEWED: blocking thread pool saturation during shutdown ───
         //
-        // This task blocks on `input_rx.blocking_recv()`, which occupies a
+        // This task blocks on `pty_rx.blocking_recv()`, which occupies a
         // tokio blocking thread until the channel closes or write_all fails.
         // During drain(), Session clones held by other tasks keep input_tx
         // alive, so the channel doesn't close immediately. This has been
@@ -449,12 +1668,14 @@ impl Session {
         //    latency to normal input handling for negligible shutdown
         //    benefit. The current design is the right tradeoff.
         // ────────────────────────────────────────────────────────────────
+        let usage_writer = usage.clone();
         tokio::task::spawn_blocking(move || {
             let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
                 use std::io::Write;
                 let mut writer = pty_writer;
-                let mut rx = input_rx;
+                let mut rx = pty_rx;
                 while let Some(data) = rx.blocking_recv() {
+                    usage_writer.record_input(&data);
                     if writer.write_all(&data).is_err() {
                         break;
                     }
@@ -468,12 +1689,12 @@ impl Session {
 
         let session = Session {
             name,
-            pid,
+            pid: Arc::new(parking_lot::Mutex::new(pid)),
             command: command_display,
             client_count: Arc::new(AtomicUsize::new(0)),
             tags: Arc::new(RwLock::new(HashSet::new())),
             input_tx,
-            output_rx: broker.sender(),
+            output_rx: broker.clone(),
             shutdown,
             parser,
             overlays,
@@ -489,6 +1710,28 @@ impl Session {
             screen_mode: Arc::new(RwLock::new(ScreenMode::Normal)),
             cancelled: tokio_util::sync::CancellationToken::new(),
             child_exited: Arc::new(AtomicBool::new(false)),
+            size_arbiter: SizeArbiter::default(),
+            usage,
+            idle_kill_after: Default::default(),
+            suspended,
+            hibernated: Default::default(),
+            exit_status,
+            restart,
+            scrollback_lines: Arc::new(AtomicUsize::new(scrollback_lines)),
+            query_policy,
+            handoff: Default::default(),
+            policy: Default::default(),
+            redactor,
+            resume_tokens: Default::default(),
+            bookmarks: Default::default(),
+            status_line: Default::default(),
+            pipes: Default::default(),
+            approvals: Default::default(),
+            metadata: Default::default(),
+            created_at: unix_millis(),
+            run_as,
+            safe_mode,
+            owner: Default::default(),
         };
 
         // Watch for alternate screen mode changes from the parser and
@@ -532,12 +1775,91 @@ impl Session {
                                         let _ = visual_update_tx.send(VisualUpdate::PanelsChanged);
                                     }
                                 }
-                                Some(_) => {} // other events, ignore
-                                None => break, // channel closed
+                                Some(_) => {} // other events, ignore
+                                None => break, // channel closed
+                            }
+                        }
+                        _ = cancelled.cancelled() => break,
+                    }
+                }
+            });
+        }
+
+        // Animate overlay widgets (spinners). The ticker only runs while
+        // some overlay has an animated widget; otherwise the task sleeps
+        // until one is installed.
+        {
+            let overlays = session.overlays.clone();
+            let visual_update_tx = session.visual_update_tx.clone();
+            let cancelled = session.cancelled.clone();
+            tokio::spawn(async move {
+                loop {
+                    tokio::select! {
+                        _ = overlays.animation_started() => {}
+                        _ = cancelled.cancelled() => break,
+                    }
+                    let mut tick = tokio::time::interval(WIDGET_TICK);
+                    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+                    tick.tick().await;
+                    loop {
+                        tokio::select! {
+                            _ = tick.tick() => {
+                                if !overlays.tick_widgets() {
+                                    break;
+                                }
+                                let _ = visual_update_tx.send(VisualUpdate::OverlaysChanged);
+                            }
+                            _ = cancelled.cancelled() => return,
+                        }
+                    }
+                }
+            });
+        }
+
+        // Remove overlays and panels created with a TTL once it runs out.
+        // The task sleeps until the earliest expiry, waking early when a
+        // new one is set.
+        {
+            let overlays = session.overlays.clone();
+            let panels = session.panels.clone();
+            let focus = session.focus.clone();
+            let terminal_size = session.terminal_size.clone();
+            let pty = session.pty.clone();
+            let parser = session.parser.clone();
+            let visual_update_tx = session.visual_update_tx.clone();
+            let cancelled = session.cancelled.clone();
+            tokio::spawn(async move {
+                loop {
+                    let next = [overlays.next_expiry(), panels.next_expiry()].into_iter().flatten().min();
+                    let sleep = async {
+                        match next {
+                            Some(at) => {
+                                let wait = at.saturating_sub(unix_millis());
+                                tokio::time::sleep(std::time::Duration::from_millis(wait)).await
                             }
+                            None => std::future::pending().await,
                         }
+                    };
+                    tokio::select! {
+                        _ = sleep => {}
+                        _ = overlays.expiry_set() => continue,
+                        _ = panels.expiry_set() => continue,
                         _ = cancelled.cancelled() => break,
                     }
+
+                    let now = unix_millis();
+                    let expired_overlays = overlays.remove_expired(now);
+                    let expired_panels = panels.remove_expired(now);
+                    for id in expired_overlays.iter().chain(&expired_panels) {
+                        focus.clear_if_focused(id);
+                    }
+                    if !expired_overlays.is_empty() {
+                        let _ = visual_update_tx.send(VisualUpdate::OverlaysChanged);
+                    }
+                    if !expired_panels.is_empty() {
+                        crate::panel::reconfigure_layout(&panels, &terminal_size, &pty, &parser).await;
+                        let _ = visual_update_tx.send(VisualUpdate::PanelsChanged);
+                    }
                 }
             });
         }
@@ -546,13 +1868,83 @@ impl Session {
     }
 }
 
+/// Why a session was removed from the registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DestroyReason {
+    /// Killed through the API, socket, or MCP.
+    Killed,
+    /// The child process exited.
+    Exited,
+    /// The server shut down.
+    Shutdown,
+    /// Reaped after sitting unattached and silent past its idle timeout.
+    Idle,
+    /// Killed because the MCP session that created it ended.
+    OwnerDisconnected,
+    /// Killed because the server's `on_session_create` hook failed. See
+    /// [`crate::hooks`].
+    HookRejected,
+}
+
 /// Server-level session lifecycle events.
 #[derive(Debug, Clone)]
 pub enum SessionEvent {
     Created { name: String },
     Renamed { old_name: String, new_name: String },
-    Destroyed { name: String },
+    /// `exit` is how the child ended, when it exited on its own.
+    Destroyed { name: String, reason: DestroyReason, exit: Option<ExitStatus> },
     TagsChanged { name: String, added: Vec<String>, removed: Vec<String> },
+    /// Maintenance mode was entered (`reason` is `Some`) or left (`None`).
+    Maintenance { reason: Option<String> },
+    /// The child process was stopped (`suspended` is true) or continued.
+    Suspended { name: String, suspended: bool },
+    /// The command exited with `exit` and was respawned by the session's
+    /// restart policy, for the `restarts`th time.
+    Restarted { name: String, restarts: usize, exit: Option<ExitStatus> },
+    /// A server feature was switched on or off at runtime. `by` names the
+    /// token that did it, when the server uses authentication.
+    FeatureChanged { feature: String, enabled: bool, by: Option<String> },
+}
+
+/// What is left of a session whose child exited: kept by the registry for
+/// its tombstone retention window so callers can see how the command ended.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Tombstone {
+    pub name: String,
+    pub command: String,
+    pub tags: Vec<String>,
+    /// How the child ended. Both fields are `None` if the server could not
+    /// tell.
+    #[serde(flatten)]
+    pub exit: ExitStatus,
+    /// Unix time in milliseconds at which the session ended.
+    pub ended_at: u64,
+    /// Plain-text lines of the final screen, without trailing blank lines.
+    pub screen: Vec<String>,
+}
+
+/// What [`Session::send_input`] did with input it accepted.
+#[derive(Debug)]
+pub enum Delivery {
+    /// Written to the PTY.
+    Sent,
+    /// Held until a human approves it. See [`crate::input::approval`].
+    Held(PendingInput),
+}
+
+/// Why [`Session::send_input`] didn't deliver input.
+#[derive(Debug, thiserror::Error)]
+pub enum InputError {
+    /// The input matches this block pattern of the input policy.
+    #[error("input blocked by policy: matches '{0}'")]
+    Blocked(String),
+    #[error("input send timed out")]
+    Timeout,
+    #[error("session input channel closed")]
+    Closed,
+    #[error("too many inputs are waiting for approval")]
+    TooManyPending,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -567,6 +1959,8 @@ pub enum RegistryError {
     InvalidTag(String),
     #[error("invalid session name: {0}")]
     InvalidName(String),
+    #[error("server is in maintenance: {0}")]
+    Maintenance(String),
 }
 
 struct RegistryInner {
@@ -574,6 +1968,46 @@ struct RegistryInner {
     next_id: u64,
     max_sessions: Option<usize>,
     tags_index: HashMap<String, HashSet<String>>,
+    /// Reason new sessions are being refused, while in maintenance mode.
+    maintenance: Option<String>,
+    /// Message of the day written into each new session.
+    motd: Option<String>,
+    /// Local key handling advertised to attach clients.
+    client_policy: crate::protocol::ClientPolicy,
+    /// Sandbox profiles new sessions may be created in.
+    sandbox: crate::sandbox::SandboxConfig,
+    /// Default idle timeout after which unattached, silent sessions are
+    /// killed. `None` disables reaping unless a session sets its own.
+    idle_kill_after: Option<std::time::Duration>,
+    /// Scrollback lines kept by new sessions that don't ask for a size.
+    scrollback_lines: usize,
+    /// Idle default shells waiting to be claimed by
+    /// [`claim_warm`](SessionRegistry::claim_warm), oldest first. They are
+    /// not listed, named, or counted against `max_sessions`.
+    warm_pool: std::collections::VecDeque<PooledSession>,
+    /// How many shells the warm pool keeps ready. Zero disables it.
+    warm_pool_size: usize,
+    /// Whether a refill task is running.
+    warm_pool_filling: bool,
+    /// Input policy and redaction rules handed to every inserted session.
+    policy: crate::policy::Policy,
+    /// Status line settings inserted sessions start with.
+    status_line: crate::status_line::StatusLineConfig,
+    /// Host name shown by status lines.
+    hostname: String,
+    /// Tombstones of sessions whose child exited, oldest first.
+    tombstones: std::collections::VecDeque<Tombstone>,
+    /// How long tombstones are kept. Zero keeps none.
+    tombstone_retention: std::time::Duration,
+    /// Operator hook scripts run around the session lifecycle.
+    hooks: crate::hooks::Hooks,
+}
+
+/// A pre-spawned shell in the warm pool, with the receiver that fires when
+/// it exits.
+struct PooledSession {
+    session: Session,
+    child_exit_rx: tokio::sync::oneshot::Receiver<()>,
 }
 
 /// Manages multiple sessions by name.
@@ -612,6 +2046,21 @@ impl SessionRegistry {
                 next_id: 0,
                 max_sessions,
                 tags_index: HashMap::new(),
+                maintenance: None,
+                motd: None,
+                client_policy: crate::protocol::ClientPolicy::default(),
+                sandbox: crate::sandbox::SandboxConfig::default(),
+                idle_kill_after: None,
+                scrollback_lines: DEFAULT_SCROLLBACK_LINES,
+                warm_pool: std::collections::VecDeque::new(),
+                warm_pool_size: 0,
+                warm_pool_filling: false,
+                policy: crate::policy::Policy::default(),
+                status_line: crate::status_line::StatusLineConfig::default(),
+                hostname: crate::config::resolve_hostname(None),
+                tombstones: std::collections::VecDeque::new(),
+                tombstone_retention: DEFAULT_TOMBSTONE_RETENTION,
+                hooks: crate::hooks::Hooks::default(),
             })),
             events_tx,
         }
@@ -632,6 +2081,10 @@ impl SessionRegistry {
     ) -> Result<String, RegistryError> {
         let mut inner = self.inner.write();
 
+        if let Some(ref reason) = inner.maintenance {
+            return Err(RegistryError::Maintenance(reason.clone()));
+        }
+
         if let Some(max) = inner.max_sessions {
             if inner.sessions.len() >= max {
                 return Err(RegistryError::MaxSessionsReached);
@@ -660,6 +2113,9 @@ impl SessionRegistry {
         };
 
         session.name = assigned_name.clone();
+        session.policy = inner.policy.clone();
+        session.redactor.set_policy(inner.policy.clone());
+        session.status_line.set_config(inner.status_line.clone());
         // Index initial tags
         {
             let session_tags = session.tags.read();
@@ -690,6 +2146,10 @@ impl SessionRegistry {
     ) -> Result<(String, Session), RegistryError> {
         let mut inner = self.inner.write();
 
+        if let Some(ref reason) = inner.maintenance {
+            return Err(RegistryError::Maintenance(reason.clone()));
+        }
+
         if let Some(max) = inner.max_sessions {
             if inner.sessions.len() >= max {
                 return Err(RegistryError::MaxSessionsReached);
@@ -718,6 +2178,8 @@ impl SessionRegistry {
         };
 
         session.name = assigned_name.clone();
+        session.policy = inner.policy.clone();
+        session.redactor.set_policy(inner.policy.clone());
         let cloned = session.clone();
         // Index initial tags
         {
@@ -747,6 +2209,15 @@ impl SessionRegistry {
     /// Also cleans up the tags_index for any tags the session had.
     pub fn remove(&self, name: &str) -> Option<Session> {
         let mut inner = self.inner.write();
+        Self::remove_locked(&mut inner, &self.events_tx, name, DestroyReason::Killed)
+    }
+
+    fn remove_locked(
+        inner: &mut RegistryInner,
+        events_tx: &tokio_broadcast::Sender<SessionEvent>,
+        name: &str,
+        reason: DestroyReason,
+    ) -> Option<Session> {
         let removed = inner.sessions.remove(name);
         if let Some(ref session) = removed {
             // Clean up tags_index while still holding the write lock
@@ -761,8 +2232,11 @@ impl SessionRegistry {
             }
             drop(session_tags);
             session.cancelled.cancel();
-            let _ = self.events_tx.send(SessionEvent::Destroyed {
+            session.usage.log_final(name);
+            let _ = events_tx.send(SessionEvent::Destroyed {
                 name: name.to_string(),
+                reason,
+                exit: None,
             });
         }
         removed
@@ -801,112 +2275,587 @@ impl SessionRegistry {
         }
         drop(session_tags);
 
-        inner.sessions.insert(new_name.to_string(), session);
+        inner.sessions.insert(new_name.to_string(), session);
+
+        let _ = self.events_tx.send(SessionEvent::Renamed {
+            old_name: old_name.to_string(),
+            new_name: new_name.to_string(),
+        });
+
+        Ok(cloned)
+    }
+
+    /// Return all session names.
+    pub fn list(&self) -> Vec<String> {
+        let inner = self.inner.read();
+        inner.sessions.keys().cloned().collect()
+    }
+
+    /// Return the number of sessions.
+    pub fn len(&self) -> usize {
+        let inner = self.inner.read();
+        inner.sessions.len()
+    }
+
+    /// Total streaming clients (socket attach and per-session WebSocket)
+    /// connected across all sessions.
+    pub fn connected_clients(&self) -> usize {
+        let inner = self.inner.read();
+        inner.sessions.values().map(|s| s.clients()).sum()
+    }
+
+    /// Return true if the registry contains no sessions.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// **Advisory** check for whether a session name is available.
+    ///
+    /// Returns `Ok(())` if `name` is `None` (auto-assign) or the name is free.
+    /// Returns `Err(RegistryError::NameExists)` if the name is taken, and
+    /// `Err(RegistryError::MaxSessionsReached)` if the registry is full.
+    ///
+    /// # Important: this is a fast-fail optimization, NOT a correctness guard
+    ///
+    /// Session creation follows a two-phase pattern:
+    ///
+    ///   1. `name_available()` — advisory pre-check (this method)
+    ///   2. `spawn_blocking(Session::spawn_with_options(...))` — expensive fork/exec
+    ///   3. `insert_and_get()` / `insert()` — **authoritative** atomic insert
+    ///
+    /// There is a TOCTOU window between step 1 and step 3: a concurrent request
+    /// can claim the same name after the pre-check passes. This is by design.
+    /// The pre-check exists solely to avoid the expensive fork/exec in step 2
+    /// when the name is *obviously* already taken. The authoritative uniqueness
+    /// check is `insert_and_get()` / `insert()`, which operates under a write
+    /// lock. If the name was claimed between steps 1 and 3, the insert fails
+    /// and the caller shuts down the just-spawned session (`session.shutdown()`).
+    ///
+    /// The consequence of losing the TOCTOU race is one wasted fork/exec that
+    /// is immediately cleaned up — not a resource leak, not a correctness bug.
+    /// This only happens under concurrent creates with the *same* name, which
+    /// is a degenerate case.
+    ///
+    /// **Do not attempt to "fix" this TOCTOU by holding a lock across the
+    /// spawn.** That would block all session operations for the duration of
+    /// fork/exec (potentially hundreds of ms under memory pressure), which is
+    /// far worse than the occasional wasted spawn.
+    pub fn name_available(&self, name: &Option<String>) -> Result<(), RegistryError> {
+        if let Some(reason) = self.maintenance() {
+            return Err(RegistryError::Maintenance(reason));
+        }
+        let inner = self.inner.read();
+        if let Some(max) = inner.max_sessions {
+            if inner.sessions.len() >= max {
+                return Err(RegistryError::MaxSessionsReached);
+            }
+        }
+        if let Some(n) = name {
+            validate_session_name(n).map_err(RegistryError::InvalidName)?;
+            if inner.sessions.contains_key(n) {
+                return Err(RegistryError::NameExists(n.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove all sessions atomically, detaching streaming clients first.
+    ///
+    /// Called during server shutdown to ensure child processes are cleaned up
+    /// promptly. Sends explicit SIGHUP to each child (rather than relying on
+    /// PTY fd closure, which requires all Session Arc clones to be dropped).
+    /// Returns a `JoinHandle` for the SIGKILL escalation task if any sessions
+    /// were drained, so the caller can await it.
+    ///
+    /// Uses a single write lock for the entire operation to prevent in-flight
+    /// session create requests from inserting new sessions between the
+    /// snapshot and the cleanup. Without atomicity, sessions created by
+    /// requests that were already past the HTTP accept layer (but not yet
+    /// completed) could escape drain and leave orphaned child processes.
+    pub fn drain(&self) -> Option<tokio::task::JoinHandle<()>> {
+        let sessions: Vec<Session> = {
+            let mut inner = self.inner.write();
+            let drained: Vec<(String, Session)> = inner.sessions.drain().collect();
+            inner.tags_index.clear();
+            // Stop refilling the warm pool and hang up its shells too.
+            inner.warm_pool_size = 0;
+            let pooled: Vec<Session> = inner.warm_pool.drain(..).map(|p| p.session).collect();
+            for session in &pooled {
+                session.cancelled.cancel();
+                session.send_sighup();
+            }
+            for (name, ref session) in &drained {
+                session.cancelled.cancel();
+                session.detach();
+                session.send_sighup();
+                session.usage.log_final(name);
+                let _ = self.events_tx.send(SessionEvent::Destroyed {
+                    name: name.clone(),
+                    reason: DestroyReason::Shutdown,
+                    exit: None,
+                });
+            }
+            drained.into_iter().map(|(_, s)| s).chain(pooled).collect()
+        };
+        if sessions.is_empty() {
+            return None;
+        }
+        // Give children 3 seconds to exit from SIGHUP, then escalate to SIGKILL
+        Some(tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+            for session in &sessions {
+                session.kill_child();
+            }
+        }))
+    }
+
+    /// Enter maintenance mode with a reason shown to clients, or leave it
+    /// with `None`. While in maintenance, new sessions are refused with
+    /// `RegistryError::Maintenance`; existing sessions are unaffected.
+    /// Emits `SessionEvent::Maintenance` when the state changes.
+    pub fn set_maintenance(&self, reason: Option<String>) {
+        let changed = {
+            let mut inner = self.inner.write();
+            let changed = inner.maintenance != reason;
+            inner.maintenance = reason.clone();
+            changed
+        };
+        if changed {
+            let _ = self.events_tx.send(SessionEvent::Maintenance { reason });
+        }
+    }
+
+    /// Tell event subscribers that a server feature was switched on or off.
+    pub fn announce_feature_change(&self, feature: &str, enabled: bool, by: Option<String>) {
+        let _ = self.events_tx.send(SessionEvent::FeatureChanged {
+            feature: feature.to_string(),
+            enabled,
+            by,
+        });
+    }
+
+    /// The maintenance reason, if the registry is in maintenance mode.
+    pub fn maintenance(&self) -> Option<String> {
+        self.inner.read().maintenance.clone()
+    }
+
+    /// Set the message of the day written into each new session's
+    /// scrollback and shown to clients on create and attach.
+    pub fn set_motd(&self, motd: Option<String>) {
+        self.inner.write().motd = motd;
+    }
+
+    /// The configured message of the day, if any.
+    pub fn motd(&self) -> Option<String> {
+        self.inner.read().motd.clone()
+    }
+
+    /// Set the client policy sent to socket clients on create and attach.
+    pub fn set_client_policy(&self, policy: crate::protocol::ClientPolicy) {
+        self.inner.write().client_policy = policy;
+    }
+
+    /// The client policy sent to socket clients.
+    pub fn client_policy(&self) -> crate::protocol::ClientPolicy {
+        self.inner.read().client_policy.clone()
+    }
+
+    /// Set the sandbox profiles available to new sessions.
+    pub fn set_sandbox(&self, sandbox: crate::sandbox::SandboxConfig) {
+        self.inner.write().sandbox = sandbox;
+    }
+
+    /// The configured sandbox profiles.
+    pub fn sandbox(&self) -> crate::sandbox::SandboxConfig {
+        self.inner.read().sandbox.clone()
+    }
+
+    /// The sandbox a new session asking for `requested` gets. See
+    /// [`SandboxConfig::resolve`](crate::sandbox::SandboxConfig::resolve).
+    pub fn resolve_sandbox(
+        &self,
+        requested: Option<&str>,
+    ) -> Result<Option<crate::sandbox::Sandbox>, String> {
+        self.inner.read().sandbox.resolve(requested)
+    }
+
+    /// Set the scrollback size for new sessions that don't ask for one.
+    pub fn set_scrollback_lines(&self, lines: usize) {
+        self.inner.write().scrollback_lines = lines;
+    }
+
+    /// Scrollback size for new sessions that don't ask for one.
+    pub fn scrollback_lines(&self) -> usize {
+        self.inner.read().scrollback_lines
+    }
+
+    /// Set how many idle default shells to keep pre-spawned for
+    /// [`claim_warm`](Self::claim_warm), killing any surplus and starting
+    /// a refill if the pool is short. Zero disables the pool.
+    pub fn set_warm_pool_size(&self, size: usize) {
+        let surplus: Vec<PooledSession> = {
+            let mut inner = self.inner.write();
+            inner.warm_pool_size = size;
+            let keep = inner.warm_pool.len().min(size);
+            inner.warm_pool.drain(keep..).collect()
+        };
+        for pooled in surplus {
+            pooled.session.force_kill();
+        }
+        self.refill_warm_pool();
+    }
+
+    /// How many shells the warm pool keeps ready.
+    pub fn warm_pool_size(&self) -> usize {
+        self.inner.read().warm_pool_size
+    }
+
+    /// How many shells are in the warm pool right now.
+    pub fn warm_pool_ready(&self) -> usize {
+        self.inner.read().warm_pool.len()
+    }
+
+    /// Take the oldest live shell from the warm pool, if there is one, and
+    /// start spawning its replacement. The session is unnamed and not yet
+    /// in the registry: the caller sets it up like a freshly spawned
+    /// session, then inserts it and monitors its exit.
+    ///
+    /// Pooled shells run the default shell at 24x80 in the default sandbox
+    /// with the default scrollback, and have already written the message
+    /// of the day.
+    pub fn claim_warm(&self) -> Option<(Session, tokio::sync::oneshot::Receiver<()>)> {
+        let claimed = {
+            let mut inner = self.inner.write();
+            loop {
+                let Some(mut pooled) = inner.warm_pool.pop_front() else {
+                    break None;
+                };
+                // A shell that died while waiting is no use to anyone.
+                match pooled.child_exit_rx.try_recv() {
+                    Err(tokio::sync::oneshot::error::TryRecvError::Empty) => {
+                        // Time spent in the pool doesn't count against the
+                        // new session: not as idle time, nor as usage.
+                        pooled.session.created_at = unix_millis();
+                        pooled.session.activity.touch();
+                        pooled.session.usage.reset();
+                        break Some((pooled.session, pooled.child_exit_rx));
+                    }
+                    _ => pooled.session.shutdown(),
+                }
+            }
+        };
+        self.refill_warm_pool();
+        claimed
+    }
+
+    /// Spawn shells in the background until the warm pool is full. Does
+    /// nothing if a refill is already running. Gives up on the first spawn
+    /// failure; the next claim tries again.
+    fn refill_warm_pool(&self) {
+        {
+            let mut inner = self.inner.write();
+            if inner.warm_pool_filling || inner.warm_pool.len() >= inner.warm_pool_size {
+                return;
+            }
+            inner.warm_pool_filling = true;
+        }
+        let registry = self.clone();
+        tokio::spawn(async move {
+            loop {
+                // The flag is cleared under the same lock as the check, so
+                // a claim racing with the last spawn still gets a refill.
+                let (motd, sandbox, scrollback_lines) = {
+                    let mut inner = registry.inner.write();
+                    if inner.warm_pool.len() >= inner.warm_pool_size {
+                        inner.warm_pool_filling = false;
+                        return;
+                    }
+                    (inner.motd.clone(), inner.sandbox.resolve(None), inner.scrollback_lines)
+                };
+                let command = SpawnCommand::Shell {
+                    interactive: true,
+                    shell: None,
+                };
+                // fork()/exec() blocks; keep it off the async executor.
+                let spawned = match sandbox {
+                    Ok(sandbox) => tokio::task::spawn_blocking(move || {
+                        Session::spawn_with_options(
+                            "".to_string(),
+                            command,
+                            24,
+                            80,
+                            SpawnOptions { motd, sandbox, scrollback_lines, ..Default::default() },
+                        )
+                            .map_err(|e| e.to_string())
+                    })
+                    .await
+                    .unwrap_or_else(|e| Err(e.to_string())),
+                    Err(e) => Err(e),
+                };
+                let mut inner = registry.inner.write();
+                match spawned {
+                    // The pool may have been shrunk or drained meanwhile.
+                    Ok((session, _)) if inner.warm_pool.len() >= inner.warm_pool_size => {
+                        inner.warm_pool_filling = false;
+                        drop(inner);
+                        session.force_kill();
+                        return;
+                    }
+                    Ok((session, child_exit_rx)) => {
+                        inner.warm_pool.push_back(PooledSession { session, child_exit_rx });
+                    }
+                    Err(e) => {
+                        inner.warm_pool_filling = false;
+                        tracing::warn!(error = %e, "failed to spawn a warm pool shell");
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Set the input policy and redaction rules for sessions inserted from
+    /// now on. Reloading the rules of the policy itself reaches every
+    /// session, existing ones included.
+    pub fn set_policy(&self, policy: crate::policy::Policy) {
+        self.inner.write().policy = policy;
+    }
+
+    /// The input policy and redaction rules.
+    pub fn policy(&self) -> crate::policy::Policy {
+        self.inner.read().policy.clone()
+    }
+
+    /// Set the status line settings sessions inserted from now on start
+    /// with, and the host name status lines show.
+    pub fn set_status_line(&self, config: crate::status_line::StatusLineConfig, hostname: String) {
+        let mut inner = self.inner.write();
+        inner.status_line = config;
+        inner.hostname = hostname;
+    }
+
+    /// The status line settings new sessions start with.
+    pub fn status_line(&self) -> crate::status_line::StatusLineConfig {
+        self.inner.read().status_line.clone()
+    }
+
+    /// The host name status lines show and hooks are told.
+    pub fn hostname(&self) -> String {
+        self.inner.read().hostname.clone()
+    }
+
+    /// Set the hook scripts run around the session lifecycle. See
+    /// [`crate::hooks`].
+    pub fn set_hooks(&self, hooks: crate::hooks::Hooks) {
+        self.inner.write().hooks = hooks;
+    }
+
+    /// The hook scripts run around the session lifecycle.
+    pub fn hooks(&self) -> crate::hooks::Hooks {
+        self.inner.read().hooks.clone()
+    }
+
+    /// Kill `session` because its `on_session_create` hook failed, emitting
+    /// `SessionEvent::Destroyed` with [`DestroyReason::HookRejected`]. The
+    /// session is found by identity, so a rename while the hook ran doesn't
+    /// matter. Returns whether it was still there to kill.
+    pub fn reject(&self, session: &Session) -> bool {
+        let mut inner = self.inner.write();
+        let Some(name) = inner
+            .sessions
+            .iter()
+            .find(|(_, s)| Arc::ptr_eq(&session.client_count, &s.client_count))
+            .map(|(n, _)| n.clone())
+        else {
+            return false;
+        };
+        match Self::remove_locked(&mut inner, &self.events_tx, &name, DestroyReason::HookRejected) {
+            Some(session) => {
+                session.force_kill();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Refresh the status line of the session named `only`, or of every
+    /// session. See [`Session::refresh_status_line`].
+    pub async fn refresh_status_lines(&self, only: Option<&str>) {
+        let (sessions, hostname) = {
+            let inner = self.inner.read();
+            let sessions: Vec<(String, Session)> =
+                inner.sessions.iter().map(|(name, session)| (name.clone(), session.clone())).collect();
+            (sessions, inner.hostname.clone())
+        };
+        let active: Vec<&str> = sessions
+            .iter()
+            .filter(|(_, session)| {
+                session.activity.last_activity_ms() < crate::status_line::ACTIVE_WITHIN.as_millis() as u64
+            })
+            .map(|(name, _)| name.as_str())
+            .collect();
+        let (date, time) = crate::status_line::local_date_time();
+        for (name, session) in &sessions {
+            if only.is_some_and(|only| only != name) {
+                continue;
+            }
+            if !session.status_line.config().enabled && session.status_line.panel().is_none() {
+                continue;
+            }
+            let others: Vec<String> =
+                active.iter().filter(|other| **other != name).map(|other| other.to_string()).collect();
+            let vars = crate::status_line::Vars {
+                session: name,
+                host: &hostname,
+                time: time.clone(),
+                date: date.clone(),
+                clients: session.client_count.load(Ordering::Relaxed),
+                active: &others,
+            };
+            session.refresh_status_line(&vars).await;
+        }
+    }
+
+    /// Spawn a task that refreshes every session's status line each
+    /// [`REFRESH_INTERVAL`](crate::status_line::REFRESH_INTERVAL).
+    pub fn spawn_status_line_refresher(&self) -> tokio::task::JoinHandle<()> {
+        let registry = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(crate::status_line::REFRESH_INTERVAL);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                registry.refresh_status_lines(None).await;
+            }
+        })
+    }
 
-        let _ = self.events_tx.send(SessionEvent::Renamed {
-            old_name: old_name.to_string(),
-            new_name: new_name.to_string(),
-        });
+    /// Set the default idle timeout for [`reap_idle`](Self::reap_idle).
+    pub fn set_idle_kill_after(&self, after: Option<std::time::Duration>) {
+        self.inner.write().idle_kill_after = after;
+    }
 
-        Ok(cloned)
+    /// The default idle timeout, if reaping is enabled.
+    pub fn idle_kill_after(&self) -> Option<std::time::Duration> {
+        self.inner.read().idle_kill_after
     }
 
-    /// Return all session names.
-    pub fn list(&self) -> Vec<String> {
-        let inner = self.inner.read();
-        inner.sessions.keys().cloned().collect()
+    /// Set how long tombstones of exited sessions are kept. Zero keeps none
+    /// and drops those already kept.
+    pub fn set_tombstone_retention(&self, retention: std::time::Duration) {
+        let mut inner = self.inner.write();
+        inner.tombstone_retention = retention;
+        Self::prune_tombstones(&mut inner);
     }
 
-    /// Return the number of sessions.
-    pub fn len(&self) -> usize {
-        let inner = self.inner.read();
-        inner.sessions.len()
+    /// The tombstone of the most recent session named `name` whose child
+    /// exited within the retention window.
+    pub fn tombstone(&self, name: &str) -> Option<Tombstone> {
+        let mut inner = self.inner.write();
+        Self::prune_tombstones(&mut inner);
+        inner.tombstones.iter().rev().find(|t| t.name == name).cloned()
     }
 
-    /// Return true if the registry contains no sessions.
-    pub fn is_empty(&self) -> bool {
-        self.len() == 0
+    /// Drop tombstones past the retention window or over [`MAX_TOMBSTONES`].
+    fn prune_tombstones(inner: &mut RegistryInner) {
+        let cutoff = unix_millis().saturating_sub(inner.tombstone_retention.as_millis() as u64);
+        while inner
+            .tombstones
+            .front()
+            .is_some_and(|t| inner.tombstone_retention.is_zero() || t.ended_at < cutoff)
+        {
+            inner.tombstones.pop_front();
+        }
+        while inner.tombstones.len() > MAX_TOMBSTONES {
+            inner.tombstones.pop_front();
+        }
     }
 
-    /// **Advisory** check for whether a session name is available.
-    ///
-    /// Returns `Ok(())` if `name` is `None` (auto-assign) or the name is free.
-    /// Returns `Err(RegistryError::NameExists)` if the name is taken.
-    ///
-    /// # Important: this is a fast-fail optimization, NOT a correctness guard
-    ///
-    /// Session creation follows a two-phase pattern:
-    ///
-    ///   1. `name_available()` — advisory pre-check (this method)
-    ///   2. `spawn_blocking(Session::spawn_with_options(...))` — expensive fork/exec
-    ///   3. `insert_and_get()` / `insert()` — **authoritative** atomic insert
-    ///
-    /// There is a TOCTOU window between step 1 and step 3: a concurrent request
-    /// can claim the same name after the pre-check passes. This is by design.
-    /// The pre-check exists solely to avoid the expensive fork/exec in step 2
-    /// when the name is *obviously* already taken. The authoritative uniqueness
-    /// check is `insert_and_get()` / `insert()`, which operates under a write
-    /// lock. If the name was claimed between steps 1 and 3, the insert fails
-    /// and the caller shuts down the just-spawned session (`session.shutdown()`).
-    ///
-    /// The consequence of losing the TOCTOU race is one wasted fork/exec that
-    /// is immediately cleaned up — not a resource leak, not a correctness bug.
-    /// This only happens under concurrent creates with the *same* name, which
-    /// is a degenerate case.
-    ///
-    /// **Do not attempt to "fix" this TOCTOU by holding a lock across the
-    /// spawn.** That would block all session operations for the duration of
-    /// fork/exec (potentially hundreds of ms under memory pressure), which is
-    /// far worse than the occasional wasted spawn.
-    pub fn name_available(&self, name: &Option<String>) -> Result<(), RegistryError> {
-        if let Some(n) = name {
-            validate_session_name(n).map_err(RegistryError::InvalidName)?;
-            let inner = self.inner.read();
-            if inner.sessions.contains_key(n) {
-                return Err(RegistryError::NameExists(n.clone()));
+    /// Kill every session that has gone unattached and silent for its idle
+    /// timeout: the session's own `idle_kill_after` if set, otherwise the
+    /// registry default. A zero timeout never reaps. Emits
+    /// `SessionEvent::Destroyed` with [`DestroyReason::Idle`] for each and
+    /// returns their names.
+    pub fn reap_idle(&self) -> Vec<String> {
+        let mut inner = self.inner.write();
+        let default = inner.idle_kill_after;
+        let idle: Vec<String> = inner
+            .sessions
+            .iter()
+            .filter(|(_, session)| {
+                let limit = (*session.idle_kill_after.lock()).or(default);
+                match (limit, session.idle_for()) {
+                    (Some(limit), Some(idle)) => !limit.is_zero() && idle >= limit,
+                    _ => false,
+                }
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in &idle {
+            if let Some(session) =
+                Self::remove_locked(&mut inner, &self.events_tx, name, DestroyReason::Idle)
+            {
+                tracing::info!(session = %name, "killing idle session");
+                session.force_kill();
             }
         }
-        Ok(())
+        idle
     }
 
-    /// Remove all sessions atomically, detaching streaming clients first.
-    ///
-    /// Called during server shutdown to ensure child processes are cleaned up
-    /// promptly. Sends explicit SIGHUP to each child (rather than relying on
-    /// PTY fd closure, which requires all Session Arc clones to be dropped).
-    /// Returns a `JoinHandle` for the SIGKILL escalation task if any sessions
-    /// were drained, so the caller can await it.
-    ///
-    /// Uses a single write lock for the entire operation to prevent in-flight
-    /// session create requests from inserting new sessions between the
-    /// snapshot and the cleanup. Without atomicity, sessions created by
-    /// requests that were already past the HTTP accept layer (but not yet
-    /// completed) could escape drain and leave orphaned child processes.
-    pub fn drain(&self) -> Option<tokio::task::JoinHandle<()>> {
-        let sessions: Vec<Session> = {
-            let mut inner = self.inner.write();
-            let drained: Vec<(String, Session)> = inner.sessions.drain().collect();
-            inner.tags_index.clear();
-            for (name, ref session) in &drained {
-                session.cancelled.cancel();
-                session.detach();
-                session.send_sighup();
-                let _ = self.events_tx.send(SessionEvent::Destroyed {
-                    name: name.clone(),
-                });
+    /// Clean up after MCP session `mcp_session` ended: kill the sessions it
+    /// created with [`DisconnectCleanup::Kill`], emitting
+    /// `SessionEvent::Destroyed` with [`DestroyReason::OwnerDisconnected`],
+    /// and detach the clients of those with [`DisconnectCleanup::Detach`].
+    /// Returns the names of the sessions killed.
+    pub fn release_owner(&self, mcp_session: &str) -> Vec<String> {
+        let mut inner = self.inner.write();
+        let owned: Vec<(String, DisconnectCleanup)> = inner
+            .sessions
+            .iter()
+            .filter_map(|(name, session)| {
+                let owner = session.owner.lock();
+                let owner = owner.as_ref().filter(|o| o.mcp_session.as_deref() == Some(mcp_session))?;
+                Some((name.clone(), owner.cleanup_on_disconnect?))
+            })
+            .collect();
+        let mut killed = Vec::new();
+        for (name, cleanup) in owned {
+            match cleanup {
+                DisconnectCleanup::Kill => {
+                    if let Some(session) = Self::remove_locked(
+                        &mut inner,
+                        &self.events_tx,
+                        &name,
+                        DestroyReason::OwnerDisconnected,
+                    ) {
+                        tracing::info!(session = %name, mcp_session, "killing session of ended MCP session");
+                        session.force_kill();
+                        killed.push(name);
+                    }
+                }
+                DisconnectCleanup::Detach => {
+                    if let Some(session) = inner.sessions.get(&name) {
+                        session.detach();
+                    }
+                }
+                DisconnectCleanup::Keep => {}
             }
-            drained.into_iter().map(|(_, s)| s).collect()
-        };
-        if sessions.is_empty() {
-            return None;
         }
-        // Give children 3 seconds to exit from SIGHUP, then escalate to SIGKILL
-        Some(tokio::spawn(async move {
-            tokio::time::sleep(std::time::Duration::from_secs(3)).await;
-            for session in &sessions {
-                session.kill_child();
+        killed
+    }
+
+    /// Run [`reap_idle`](Self::reap_idle) every `interval` in the
+    /// background.
+    pub fn spawn_idle_reaper(&self, interval: std::time::Duration) -> tokio::task::JoinHandle<()> {
+        let registry = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                registry.reap_idle();
             }
-        }))
+        })
     }
 
     /// Subscribe to session lifecycle events.
@@ -1031,12 +2980,50 @@ impl SessionRegistry {
         child_exit_rx: tokio::sync::oneshot::Receiver<()>,
     ) {
         let registry = self.clone();
+        let found = registry.find_by_identity(&identity);
+        let mut suspended_rx = found.as_ref().map(|(_, session)| session.suspended.subscribe());
+        let pty = found.map(|(_, session)| session.pty);
         tokio::spawn(async move {
-            let _ = child_exit_rx.await;
-            // Mark child as exited BEFORE removing from registry, so that
-            // any concurrent drain/kill_child sees the flag and skips
-            // signaling a potentially-recycled PID.
-            child_exited.store(true, Ordering::Release);
+            let mut child_exit_rx = child_exit_rx;
+            loop {
+                match suspended_rx.as_mut() {
+                    // Report stops and continues until the child exits.
+                    Some(rx) => loop {
+                        tokio::select! {
+                            _ = &mut child_exit_rx => break,
+                            Ok(()) = rx.changed() => {
+                                let suspended = *rx.borrow_and_update();
+                                if let Some((name, _)) = registry.find_by_identity(&identity) {
+                                    let _ = registry.events_tx.send(SessionEvent::Suspended { name, suspended });
+                                }
+                            }
+                        }
+                    },
+                    None => {
+                        let _ = (&mut child_exit_rx).await;
+                    }
+                }
+                // Mark child as exited BEFORE removing from registry, so that
+                // any concurrent drain/kill_child sees the flag and skips
+                // signaling a potentially-recycled PID.
+                child_exited.store(true, Ordering::Release);
+                match registry.restart(&identity).await {
+                    Some(rx) => child_exit_rx = rx,
+                    None => break,
+                }
+            }
+            // Let the reader see EOF, if the terminal was kept open for
+            // restarts.
+            if let Some(pty) = pty {
+                pty.lock().close_slave();
+            }
+            // The final screen is read while the session is still
+            // registered, once the last of its output has been parsed.
+            let keep_tombstone = !registry.inner.read().tombstone_retention.is_zero();
+            let screen = match registry.find_by_identity(&identity) {
+                Some((_, session)) if keep_tombstone => Some(session.final_screen().await),
+                _ => None,
+            };
             // ── Design decision: atomic detach + remove ──────────────
             //
             // The identity lookup, detach, and remove MUST happen under
@@ -1050,20 +3037,63 @@ impl SessionRegistry {
             // See also: the v1→v2→v3 evolution notes above for the
             // identity parameter rationale.
             // ─────────────────────────────────────────────────────────
-            registry.detach_and_remove_by_identity(&identity, &name);
+            registry.detach_and_remove_by_identity(&identity, &name, screen);
         });
     }
 
+    /// Respawn the command of the session with `identity` after its restart
+    /// policy's backoff, and emit [`SessionEvent::Restarted`]. Returns the
+    /// new child's exit receiver, or `None` if the session stays ended or
+    /// was killed or removed meanwhile.
+    async fn restart(&self, identity: &Arc<AtomicUsize>) -> Option<tokio::sync::oneshot::Receiver<()>> {
+        let (_, session) = self.find_by_identity(identity)?;
+        let delay = session.restart_delay()?;
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = session.cancelled.cancelled() => return None,
+        }
+        let (name, session) = self.find_by_identity(identity)?;
+        let exit = session.exit_status.lock().clone();
+        let child_exit_rx = match session.respawn().await {
+            Ok(rx) => rx,
+            Err(e) => {
+                tracing::warn!(session = %name, error = %e, "failed to restart session command");
+                return None;
+            }
+        };
+        // A kill that raced the respawn may have skipped the new child.
+        if session.cancelled.is_cancelled() {
+            session.kill_child();
+        }
+        let restarts = session.restart.as_ref().map_or(0, |r| r.restarts());
+        tracing::info!(session = %name, restarts, ?exit, "restarted session command");
+        let _ = self.events_tx.send(SessionEvent::Restarted { name, restarts, exit });
+        Some(child_exit_rx)
+    }
+
+    /// Find a session and its current name by identity (its `client_count`
+    /// Arc). See [`monitor_child_exit`](Self::monitor_child_exit).
+    fn find_by_identity(&self, identity: &Arc<AtomicUsize>) -> Option<(String, Session)> {
+        self.inner
+            .read()
+            .sessions
+            .iter()
+            .find(|(_, s)| Arc::ptr_eq(identity, &s.client_count))
+            .map(|(n, s)| (n.clone(), s.clone()))
+    }
+
     /// Atomically find, detach, and remove a session by identity.
     ///
     /// Performs identity lookup (Arc::ptr_eq), detach, and remove under a
     /// single write lock to prevent races with concurrent rename() calls.
     /// The `fallback_name` is used only for logging if the session was
     /// already removed (e.g. by drain or kill) before the child exited.
+    /// With a final `screen`, the session leaves a [`Tombstone`].
     fn detach_and_remove_by_identity(
         &self,
         identity: &Arc<AtomicUsize>,
         fallback_name: &str,
+        screen: Option<Vec<String>>,
     ) {
         let mut inner = self.inner.write();
 
@@ -1088,10 +3118,29 @@ impl SessionRegistry {
                             }
                         }
                     }
+                    let exit = session.exit_status.lock().clone();
+                    if let Some(screen) = screen {
+                        let mut tags: Vec<String> = session_tags.iter().cloned().collect();
+                        tags.sort();
+                        inner.tombstones.push_back(Tombstone {
+                            name: name.clone(),
+                            command: session.command.clone(),
+                            tags,
+                            exit: exit.clone().unwrap_or(ExitStatus { exit_code: None, signal: None }),
+                            ended_at: unix_millis(),
+                            screen,
+                        });
+                        Self::prune_tombstones(&mut inner);
+                    }
                     drop(session_tags);
                     session.cancelled.cancel();
                     session.detach();
-                    let _ = self.events_tx.send(SessionEvent::Destroyed { name });
+                    session.usage.log_final(&name);
+                    let _ = self.events_tx.send(SessionEvent::Destroyed {
+                        name,
+                        reason: DestroyReason::Exited,
+                        exit,
+                    });
                 }
             }
             None => {
@@ -1118,13 +3167,35 @@ mod tests {
 
         let session = Session {
             name: name.to_string(),
-            pid: None,
+            pid: Default::default(),
             command: "test".to_string(),
             client_count: Arc::new(AtomicUsize::new(0)),
             tags: Arc::new(RwLock::new(HashSet::new())),
             child_exited: Arc::new(AtomicBool::new(false)),
+            size_arbiter: SizeArbiter::default(),
+            usage: UsageCounters::new(),
+            idle_kill_after: Default::default(),
+            suspended: Default::default(),
+            hibernated: Default::default(),
+            exit_status: Default::default(),
+            restart: Default::default(),
+            scrollback_lines: Default::default(),
+            query_policy: Default::default(),
+            handoff: Default::default(),
+            policy: Default::default(),
+            redactor: Default::default(),
+            resume_tokens: Default::default(),
+            bookmarks: Default::default(),
+            status_line: Default::default(),
+            pipes: Default::default(),
+            approvals: Default::default(),
+            metadata: Default::default(),
+            created_at: Default::default(),
+            run_as: None,
+            safe_mode: None,
+            owner: Default::default(),
             input_tx,
-            output_rx: broker.sender(),
+            output_rx: broker.clone(),
             shutdown: ShutdownCoordinator::new(),
             parser,
             overlays: OverlayStore::new(),
@@ -1157,13 +3228,35 @@ mod tests {
         // Both copies share the same name.
         assert_eq!(cloned.name, "clone-me");
 
-        // The underlying broadcast sender is shared (same channel).
+        // The underlying broker is shared (same ring).
         assert_eq!(
             session.output_rx.receiver_count(),
             cloned.output_rx.receiver_count(),
         );
     }
 
+    #[tokio::test]
+    async fn test_merge_metadata_sets_replaces_and_removes_keys() {
+        let (session, _rx) = create_test_session("meta");
+        let patch = |value: serde_json::Value| value.as_object().unwrap().clone();
+
+        session
+            .merge_metadata(patch(serde_json::json!({"run": "r-1", "attempt": 1})))
+            .unwrap();
+        session
+            .merge_metadata(patch(serde_json::json!({"attempt": 2, "run": null, "owner": {"id": 7}})))
+            .unwrap();
+        assert_eq!(
+            serde_json::Value::Object(session.metadata.read().clone()),
+            serde_json::json!({"attempt": 2, "owner": {"id": 7}})
+        );
+
+        let big = "x".repeat(MAX_METADATA_BYTES);
+        assert!(session.merge_metadata(patch(serde_json::json!({"big": big}))).is_err());
+        assert!(session.merge_metadata(patch(serde_json::json!({"": 1}))).is_err());
+        assert_eq!(session.metadata.read().len(), 2);
+    }
+
     /// Helper: build a minimal Session for registry tests (discards the receiver).
     fn make_test_session(name: &str) -> Session {
         let (session, _rx) = create_test_session(name);
@@ -1345,11 +3438,59 @@ mod tests {
 
         let ev2 = rx.recv().await.expect("should receive Destroyed event");
         assert!(
-            matches!(ev2, SessionEvent::Destroyed { ref name } if name == "evt"),
+            matches!(
+                ev2,
+                SessionEvent::Destroyed { ref name, reason: DestroyReason::Killed, .. } if name == "evt"
+            ),
             "expected Destroyed {{ name: \"evt\" }}, got: {ev2:?}"
         );
     }
 
+    #[tokio::test]
+    async fn maintenance_refuses_new_sessions_and_emits_event() {
+        let registry = SessionRegistry::new();
+        registry
+            .insert(Some("existing".to_string()), make_test_session("x"))
+            .unwrap();
+        let mut rx = registry.subscribe_events();
+
+        registry.set_maintenance(Some("upgrade".to_string()));
+        // Setting the same reason again is not a change.
+        registry.set_maintenance(Some("upgrade".to_string()));
+
+        let ev = rx.recv().await.expect("should receive Maintenance event");
+        assert!(
+            matches!(ev, SessionEvent::Maintenance { reason: Some(ref r) } if r == "upgrade"),
+            "expected Maintenance {{ reason: \"upgrade\" }}, got: {ev:?}"
+        );
+        assert!(matches!(
+            registry.insert(None, make_test_session("y")),
+            Err(RegistryError::Maintenance(ref r)) if r == "upgrade"
+        ));
+        assert!(matches!(
+            registry.name_available(&None),
+            Err(RegistryError::Maintenance(_))
+        ));
+        assert!(registry.get("existing").is_some());
+
+        registry.set_maintenance(None);
+        let ev = rx.recv().await.expect("should receive Maintenance event");
+        assert!(matches!(ev, SessionEvent::Maintenance { reason: None }));
+        assert!(rx.try_recv().is_err());
+        registry.insert(None, make_test_session("y")).unwrap();
+    }
+
+    #[tokio::test]
+    async fn name_available_reports_full_registry() {
+        let registry = SessionRegistry::with_max_sessions(Some(1));
+        assert!(registry.name_available(&None).is_ok());
+        registry.insert(None, make_test_session("x")).unwrap();
+        assert!(matches!(
+            registry.name_available(&Some("other".to_string())),
+            Err(RegistryError::MaxSessionsReached)
+        ));
+    }
+
     #[tokio::test]
     async fn registry_emits_renamed_event() {
         let registry = SessionRegistry::new();
@@ -1371,6 +3512,101 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn reap_idle_kills_unattached_silent_sessions() {
+        use std::time::Duration;
+
+        let registry = SessionRegistry::new();
+        let attached = make_test_session("x");
+        let exempt = make_test_session("x");
+        *exempt.idle_kill_after.lock() = Some(Duration::ZERO);
+        let patient = make_test_session("x");
+        *patient.idle_kill_after.lock() = Some(Duration::from_secs(3600));
+        for (name, session) in [
+            ("idle", make_test_session("x")),
+            ("attached", attached.clone()),
+            ("exempt", exempt),
+            ("patient", patient),
+        ] {
+            registry.insert(Some(name.to_string()), session).unwrap();
+        }
+        let _client = attached.connect().unwrap();
+
+        // Reaping is off until a default or override says otherwise.
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(registry.reap_idle().is_empty());
+
+        registry.set_idle_kill_after(Some(Duration::from_millis(20)));
+        let mut rx = registry.subscribe_events();
+        assert_eq!(registry.reap_idle(), vec!["idle".to_string()]);
+        assert!(registry.get("idle").is_none());
+        assert_eq!(registry.len(), 3);
+        let ev = rx.recv().await.expect("should receive Destroyed event");
+        assert!(
+            matches!(ev, SessionEvent::Destroyed { ref name, reason: DestroyReason::Idle, .. } if name == "idle"),
+            "expected idle Destroyed event, got: {ev:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn release_owner_applies_cleanup_on_disconnect() {
+        let registry = SessionRegistry::new();
+        let owned = |mcp_session: &str, cleanup| {
+            let session = make_test_session("x");
+            *session.owner.lock() = Some(Owner {
+                token: None,
+                mcp_session: Some(mcp_session.to_string()),
+                cleanup_on_disconnect: Some(cleanup),
+            });
+            session
+        };
+        let detached = owned("a", DisconnectCleanup::Detach);
+        let mut detach_rx = detached.detach_signal.subscribe();
+        for (name, session) in [
+            ("killed", owned("a", DisconnectCleanup::Kill)),
+            ("kept", owned("a", DisconnectCleanup::Keep)),
+            ("detached", detached),
+            ("other", owned("b", DisconnectCleanup::Kill)),
+            ("unowned", make_test_session("x")),
+        ] {
+            registry.insert(Some(name.to_string()), session).unwrap();
+        }
+
+        let mut rx = registry.subscribe_events();
+        assert_eq!(registry.release_owner("a"), vec!["killed".to_string()]);
+        let mut left = registry.list();
+        left.sort();
+        assert_eq!(left, vec!["detached", "kept", "other", "unowned"]);
+        assert!(detach_rx.try_recv().is_ok());
+        let ev = rx.recv().await.expect("should receive Destroyed event");
+        assert!(
+            matches!(ev, SessionEvent::Destroyed { ref name, reason: DestroyReason::OwnerDisconnected, .. } if name == "killed"),
+            "expected owner_disconnected Destroyed event, got: {ev:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn claimed_warm_session_starts_fresh() {
+        use std::time::Duration;
+
+        let registry = SessionRegistry::new();
+        let pooled = make_test_session("x");
+        pooled.usage.record_output(100);
+        let (_child_exit_tx, child_exit_rx) = tokio::sync::oneshot::channel();
+        registry.inner.write().warm_pool.push_back(PooledSession { session: pooled, child_exit_rx });
+
+        // Pooled for longer than the idle timeout.
+        registry.set_idle_kill_after(Some(Duration::from_millis(50)));
+        tokio::time::sleep(Duration::from_millis(80)).await;
+
+        let (session, _child_exit_rx) = registry.claim_warm().expect("pool should have a shell");
+        assert_eq!(session.usage.snapshot().output_bytes, 0);
+        assert!(session.idle_for().unwrap() < Duration::from_millis(50));
+        registry.insert(Some("claimed".to_string()), session).unwrap();
+        assert!(registry.reap_idle().is_empty());
+        assert!(registry.get("claimed").is_some());
+    }
+
     #[tokio::test]
     async fn session_spawn_creates_session_with_child_exit() {
         let (session, child_exit_rx) = Session::spawn(
@@ -1397,6 +3633,208 @@ mod tests {
             .expect("oneshot should not be dropped");
     }
 
+    #[test]
+    fn exit_status_from_wait_status() {
+        assert_eq!(
+            ExitStatus::from_wait_status(3 << 8),
+            ExitStatus { exit_code: Some(3), signal: None }
+        );
+        assert_eq!(
+            ExitStatus::from_wait_status(libc::SIGKILL),
+            ExitStatus { exit_code: None, signal: Some("KILL".to_string()) }
+        );
+        assert_eq!(signal_name(libc::SIGTERM), "TERM");
+        assert_eq!(signal_name(40), "40");
+    }
+
+    #[tokio::test]
+    async fn exited_session_leaves_tombstone() {
+        let registry = SessionRegistry::new();
+        let mut events = registry.subscribe_events();
+        let (session, child_exit_rx) = Session::spawn(
+            "done".to_string(),
+            crate::pty::SpawnCommand::Command { command: "echo bye; exit 3".to_string(), interactive: false },
+            24,
+            80,
+        )
+        .expect("Session::spawn should succeed");
+        let identity = session.client_count.clone();
+        let child_exited = session.child_exited.clone();
+        registry.insert(Some("done".to_string()), session).unwrap();
+        registry.monitor_child_exit("done".to_string(), identity, child_exited, child_exit_rx);
+
+        let exit = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                if let Ok(SessionEvent::Destroyed { name, reason: DestroyReason::Exited, exit }) = events.recv().await {
+                    if name == "done" {
+                        break exit;
+                    }
+                }
+            }
+        })
+        .await
+        .expect("session should exit");
+        assert_eq!(exit, Some(ExitStatus { exit_code: Some(3), signal: None }));
+
+        let tombstone = registry.tombstone("done").expect("tombstone should be kept");
+        assert_eq!(tombstone.exit.exit_code, Some(3));
+        assert!(tombstone.screen.iter().any(|line| line.contains("bye")), "screen: {:?}", tombstone.screen);
+        assert!(registry.tombstone("other").is_none());
+
+        registry.set_tombstone_retention(std::time::Duration::ZERO);
+        assert!(registry.tombstone("done").is_none());
+    }
+
+    #[test]
+    fn restart_policy_parse_and_backoff() {
+        assert_eq!(RestartPolicy::parse(None, None, None), Ok(None));
+        assert!(RestartPolicy::parse(None, Some(3), None).is_err());
+        assert!(RestartPolicy::parse(Some(RestartWhen::Always), Some(MAX_RESTART_MAX_RETRIES + 1), None).is_err());
+        assert!(RestartPolicy::parse(Some(RestartWhen::Always), None, Some(10 * 60 * 1000)).is_err());
+
+        let policy = RestartPolicy::parse(Some(RestartWhen::OnFailure), None, None).unwrap().unwrap();
+        assert_eq!(policy.max_retries, DEFAULT_RESTART_MAX_RETRIES);
+        assert_eq!(policy.delay(1), DEFAULT_RESTART_BACKOFF);
+        assert_eq!(policy.delay(3), DEFAULT_RESTART_BACKOFF * 4);
+        assert_eq!(policy.delay(100), MAX_RESTART_BACKOFF);
+
+        let ok = ExitStatus { exit_code: Some(0), signal: None };
+        let failed = ExitStatus { exit_code: Some(1), signal: None };
+        assert!(!policy.applies(Some(&ok)));
+        assert!(policy.applies(Some(&failed)));
+        assert!(policy.applies(None));
+        let always = RestartPolicy { when: RestartWhen::Always, ..policy };
+        assert!(always.applies(Some(&ok)));
+    }
+
+    #[tokio::test]
+    async fn restart_policy_respawns_in_same_terminal() {
+        let registry = SessionRegistry::new();
+        let mut events = registry.subscribe_events();
+        let (session, child_exit_rx) = Session::spawn_with_options(
+            "flaky".to_string(),
+            crate::pty::SpawnCommand::Command { command: "echo run; exit 4".to_string(), interactive: false },
+            24,
+            80,
+            SpawnOptions {
+                restart: Some(RestartPolicy { when: RestartWhen::OnFailure, max_retries: 2, backoff_ms: 10 }),
+                ..Default::default()
+            },
+        )
+        .expect("Session::spawn_with_options should succeed");
+        let first_pid = session.pid();
+        let identity = session.client_count.clone();
+        let child_exited = session.child_exited.clone();
+        registry.insert(Some("flaky".to_string()), session.clone()).unwrap();
+        registry.monitor_child_exit("flaky".to_string(), identity, child_exited, child_exit_rx);
+
+        let mut restarts = Vec::new();
+        let exit = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            loop {
+                match events.recv().await {
+                    Ok(SessionEvent::Restarted { restarts: n, exit, .. }) => {
+                        assert_eq!(exit, Some(ExitStatus { exit_code: Some(4), signal: None }));
+                        restarts.push(n);
+                    }
+                    Ok(SessionEvent::Destroyed { exit, .. }) => break exit,
+                    _ => {}
+                }
+            }
+        })
+        .await
+        .expect("session should end once its retries are used up");
+        assert_eq!(restarts, vec![1, 2]);
+        assert_eq!(exit, Some(ExitStatus { exit_code: Some(4), signal: None }));
+        assert_ne!(session.pid(), first_pid);
+
+        let screen = registry.tombstone("flaky").expect("tombstone should be kept").screen;
+        assert_eq!(screen.iter().filter(|line| *line == "run").count(), 3, "screen: {:?}", screen);
+        assert!(screen.iter().any(|line| line.contains("exited with code 4, restart 2")), "screen: {:?}", screen);
+    }
+
+    #[tokio::test]
+    async fn restart_policy_skips_clean_exit_and_killed_sessions() {
+        let registry = SessionRegistry::new();
+        let mut events = registry.subscribe_events();
+        let policy = RestartPolicy { when: RestartWhen::OnFailure, max_retries: 5, backoff_ms: 10 };
+        for (name, command) in [("clean", "exit 0"), ("killed", "sleep 30")] {
+            let (session, child_exit_rx) = Session::spawn_with_options(
+                name.to_string(),
+                crate::pty::SpawnCommand::Command { command: command.to_string(), interactive: false },
+                24,
+                80,
+                SpawnOptions { restart: Some(policy), ..Default::default() },
+            )
+            .expect("Session::spawn_with_options should succeed");
+            let identity = session.client_count.clone();
+            let child_exited = session.child_exited.clone();
+            registry.insert(Some(name.to_string()), session).unwrap();
+            registry.monitor_child_exit(name.to_string(), identity, child_exited, child_exit_rx);
+        }
+        registry.remove("killed").unwrap().force_kill();
+
+        let mut ended = HashSet::new();
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while ended.len() < 2 {
+                match events.recv().await {
+                    Ok(SessionEvent::Restarted { name, .. }) => panic!("{} should not restart", name),
+                    Ok(SessionEvent::Destroyed { name, .. }) => {
+                        ended.insert(name);
+                    }
+                    _ => {}
+                }
+            }
+        })
+        .await
+        .expect("both sessions should end");
+        // No restart comes after the kill either.
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(!matches!(events.try_recv(), Ok(SessionEvent::Restarted { .. })));
+    }
+
+    #[test]
+    fn format_motd_normalizes_line_endings() {
+        assert_eq!(format_motd("a\nb"), b"a\r\nb\r\n");
+        assert_eq!(format_motd("a\r\nb\r\n"), b"a\r\nb\r\n");
+    }
+
+    #[tokio::test]
+    async fn session_motd_precedes_child_output() {
+        let (session, _child_exit_rx) = Session::spawn_with_options(
+            "motd-test".to_string(),
+            crate::pty::SpawnCommand::default(),
+            24,
+            80,
+            SpawnOptions {
+                motd: Some("Authorized use only.\nSessions are recorded.".to_string()),
+                ..Default::default()
+            },
+        )
+        .expect("Session::spawn_with_options should succeed");
+
+        // The parser consumes input and queries concurrently, so poll until
+        // the MOTD has been processed.
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+        let text = loop {
+            let screen = session
+                .parser
+                .query(crate::parser::state::Query::Screen {
+                    format: crate::parser::state::Format::Plain,
+                    since: None,
+                })
+                .await
+                .expect("screen query should succeed");
+            let text = serde_json::to_string(&screen).unwrap();
+            if text.contains("recorded") || tokio::time::Instant::now() >= deadline {
+                break text;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        };
+        assert!(text.contains("Authorized use only."), "screen: {text}");
+        assert!(text.contains("Sessions are recorded."), "screen: {text}");
+        session.kill_child();
+    }
+
     #[tokio::test]
     async fn session_spawn_with_options_applies_env() {
         let mut env = std::collections::HashMap::new();
@@ -1407,8 +3845,7 @@ mod tests {
             crate::pty::SpawnCommand::default(),
             24,
             80,
-            None,
-            Some(env),
+            SpawnOptions { env: Some(env), ..Default::default() },
         )
         .expect("Session::spawn_with_options should succeed");
 
@@ -1473,6 +3910,38 @@ mod tests {
         assert!(validate_tag("special!char").is_err());
     }
 
+    #[test]
+    fn parse_signal_accepts_names_and_numbers() {
+        assert_eq!(parse_signal("TERM"), Ok(libc::SIGTERM));
+        assert_eq!(parse_signal("SIGINT"), Ok(libc::SIGINT));
+        assert_eq!(parse_signal("usr1"), Ok(libc::SIGUSR1));
+        assert_eq!(parse_signal(" 9 "), Ok(libc::SIGKILL));
+        assert!(parse_signal("BOGUS").is_err());
+        assert!(parse_signal("0").is_err());
+        assert!(parse_signal("").is_err());
+    }
+
+    #[test]
+    fn kill_mode_defaults() {
+        let grace = |ms| std::time::Duration::from_millis(ms);
+        assert_eq!(KillMode::parse(None, None), Ok(KillMode::Immediate));
+        assert_eq!(KillMode::parse(Some("KILL"), Some(1000)), Ok(KillMode::Immediate));
+        assert_eq!(
+            KillMode::parse(Some("INT"), None),
+            Ok(KillMode::Graceful { signal: libc::SIGINT, grace: DEFAULT_KILL_GRACE })
+        );
+        assert_eq!(
+            KillMode::parse(None, Some(250)),
+            Ok(KillMode::Graceful { signal: libc::SIGTERM, grace: grace(250) })
+        );
+        assert!(KillMode::parse(Some("TERM"), Some(60_001)).is_err());
+        assert!(KillMode::parse(Some("NOPE"), None).is_err());
+        assert_eq!(
+            KillMode::Graceful { signal: libc::SIGTERM, grace: grace(1500) }.query(),
+            "signal=TERM&grace_ms=1500"
+        );
+    }
+
     // ---- Tag operations tests ----
 
     #[tokio::test]
@@ -0,0 +1,338 @@
+//! Sessions that are really an ssh connection.
+//!
+//! A create request whose `command` is `ssh://[user@]host[:port]`, or that
+//! sets `remote`, gets a session running `ssh` with a forced TTY and
+//! keepalives instead of a local shell. The ssh client logs to a file rather
+//! than the terminal, which lets the create path tell a failed connection
+//! apart from a slow one and report it as an error instead of a session
+//! that dies a moment after it was created.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::pty::{shell_quote, RunAs, SpawnCommand};
+use crate::usage::UsageCounters;
+
+/// Prefix that marks a `command` as an ssh target.
+pub const SCHEME: &str = "ssh://";
+
+/// Seconds ssh may spend establishing the TCP connection.
+const CONNECT_TIMEOUT_SECS: u64 = 10;
+
+/// How long a create waits to learn whether the connection came up. A bit
+/// over the connect timeout, so an unreachable host still fails the create.
+const CONNECT_WINDOW: Duration = Duration::from_secs(CONNECT_TIMEOUT_SECS + 2);
+
+/// Logged by ssh at `VERBOSE` once authentication succeeds.
+const AUTHENTICATED: &str = "Authenticated to ";
+
+/// Where to connect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshTarget {
+    pub user: Option<String>,
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl SshTarget {
+    /// Parse `[ssh://][user@]host[:port]`. IPv6 hosts go in brackets.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let rest = spec.strip_prefix(SCHEME).unwrap_or(spec);
+        let rest = rest.strip_suffix('/').unwrap_or(rest);
+        if rest.contains('/') {
+            return Err(format!("ssh target {} has a path; only [user@]host[:port] is supported", spec));
+        }
+        let (user, host_port) = match rest.rsplit_once('@') {
+            Some((user, host_port)) => (Some(user), host_port),
+            None => (None, rest),
+        };
+        let (host, port) = match host_port.strip_prefix('[') {
+            Some(bracketed) => {
+                let (host, after) = bracketed
+                    .split_once(']')
+                    .ok_or_else(|| format!("ssh target {} has an unclosed '['", spec))?;
+                match after {
+                    "" => (host, None),
+                    _ => match after.strip_prefix(':') {
+                        Some(port) => (host, Some(port)),
+                        None => return Err(format!("ssh target {} is malformed", spec)),
+                    },
+                }
+            }
+            None => match host_port.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (host_port, None),
+            },
+        };
+        let port = match port {
+            Some(p) => Some(
+                p.parse::<u16>()
+                    .ok()
+                    .filter(|&p| p != 0)
+                    .ok_or_else(|| format!("invalid ssh port: {}", p))?,
+            ),
+            None => None,
+        };
+        // A leading '-' would be read as an ssh option.
+        let valid = |s: &str| {
+            !s.is_empty() && !s.starts_with('-') && !s.chars().any(|c| c.is_whitespace() || c.is_control())
+        };
+        if !valid(host) {
+            return Err(format!("invalid ssh host in {}", spec));
+        }
+        if let Some(user) = user {
+            if !valid(user) {
+                return Err(format!("invalid ssh user in {}", spec));
+            }
+        }
+        Ok(Self {
+            user: user.map(str::to_string),
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+/// An ssh session being created. Dropping it deletes ssh's log file; a
+/// running ssh keeps its handle, so this is safe once the create is done.
+#[derive(Debug)]
+pub struct SshLaunch {
+    pub target: SshTarget,
+    /// Run on the remote host instead of the login shell.
+    pub remote_command: Option<String>,
+    log: PathBuf,
+}
+
+impl SshLaunch {
+    /// Work out whether a create request is for an ssh session: `remote`
+    /// names the target and `command` runs there, or `command` is itself an
+    /// `ssh://` URL. Returns `Ok(None)` for an ordinary session.
+    pub fn from_request(command: Option<&str>, remote: Option<&str>) -> Result<Option<Self>, String> {
+        let (target, remote_command) = match (remote, command) {
+            (Some(remote), command) => (SshTarget::parse(remote)?, command.map(str::to_string)),
+            (None, Some(command)) if command.starts_with(SCHEME) => (SshTarget::parse(command)?, None),
+            (None, _) => return Ok(None),
+        };
+        let log = std::env::temp_dir().join(format!("wsh-ssh-{}.log", uuid::Uuid::new_v4()));
+        Ok(Some(Self { target, remote_command, log }))
+    }
+
+    /// Create ssh's log file, owned by the account ssh will run as. Done by
+    /// the server, with `O_EXCL`, so nothing planted in the shared temp
+    /// directory can redirect what ssh writes.
+    pub fn create_log(&self, run_as: Option<&RunAs>) -> std::io::Result<()> {
+        use std::os::unix::fs::OpenOptionsExt;
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&self.log)?;
+        if let Some(account) = run_as {
+            std::os::unix::fs::chown(&self.log, Some(account.uid), Some(account.gid))?;
+        }
+        Ok(())
+    }
+
+    /// The ssh invocation, as a shell command line.
+    pub fn command_line(&self) -> String {
+        let mut args = vec![
+            "ssh".to_string(),
+            "-tt".to_string(),
+            "-E".to_string(),
+            shell_quote(&self.log.to_string_lossy()),
+            "-o LogLevel=VERBOSE".to_string(),
+            format!("-o ConnectTimeout={}", CONNECT_TIMEOUT_SECS),
+            "-o ServerAliveInterval=15".to_string(),
+            "-o ServerAliveCountMax=3".to_string(),
+        ];
+        if let Some(port) = self.target.port {
+            args.push(format!("-p {}", port));
+        }
+        if let Some(ref user) = self.target.user {
+            args.push(format!("-l {}", shell_quote(user)));
+        }
+        args.push("--".to_string());
+        args.push(shell_quote(&self.target.host));
+        if let Some(ref command) = self.remote_command {
+            args.push(shell_quote(command));
+        }
+        args.join(" ")
+    }
+
+    /// What to spawn for the session.
+    pub fn spawn_command(&self) -> SpawnCommand {
+        SpawnCommand::Command {
+            command: format!("exec {}", self.command_line()),
+            interactive: false,
+        }
+    }
+
+    /// Wait until the connection is up or has failed.
+    ///
+    /// Success is ssh logging that it authenticated, or anything reaching
+    /// the terminal: ssh's own errors go to the log, so output means the
+    /// remote end is talking or ssh is prompting (host key, password) and
+    /// the session needs a human. ssh exiting first is a failure, reported
+    /// with the error it logged. A connection still pending when the window
+    /// closes is left to carry on.
+    pub async fn wait_connected(
+        &self,
+        usage: &UsageCounters,
+        child_exit: &mut tokio::sync::oneshot::Receiver<()>,
+    ) -> Result<(), String> {
+        let deadline = tokio::time::Instant::now() + CONNECT_WINDOW;
+        loop {
+            if self.authenticated() || usage.snapshot().output_bytes > 0 {
+                return Ok(());
+            }
+            tokio::select! {
+                _ = &mut *child_exit => {
+                    return if self.authenticated() { Ok(()) } else { Err(self.failure()) };
+                }
+                _ = tokio::time::sleep(Duration::from_millis(50)) => {}
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Ok(());
+            }
+        }
+    }
+
+    fn log_text(&self) -> String {
+        std::fs::read_to_string(&self.log).unwrap_or_default()
+    }
+
+    fn authenticated(&self) -> bool {
+        self.log_text().contains(AUTHENTICATED)
+    }
+
+    /// The last error ssh logged, without its `ssh: ` prefix.
+    fn failure(&self) -> String {
+        self.log_text()
+            .lines()
+            .map(str::trim)
+            .rfind(|l| !l.is_empty() && !l.starts_with("debug"))
+            .map(|l| l.strip_prefix("ssh: ").unwrap_or(l).to_string())
+            .unwrap_or_else(|| "ssh exited before connecting".to_string())
+    }
+}
+
+impl Drop for SshLaunch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.log);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_targets() {
+        assert_eq!(
+            SshTarget::parse("ssh://deploy@web-1:2222").unwrap(),
+            SshTarget { user: Some("deploy".into()), host: "web-1".into(), port: Some(2222) }
+        );
+        assert_eq!(
+            SshTarget::parse("web-1").unwrap(),
+            SshTarget { user: None, host: "web-1".into(), port: None }
+        );
+        assert_eq!(
+            SshTarget::parse("ssh://[::1]:22/").unwrap(),
+            SshTarget { user: None, host: "::1".into(), port: Some(22) }
+        );
+        for bad in ["ssh://", "ssh://host:0", "ssh://host:x", "ssh://-oProxyCommand=x", "ssh://host/path", "ssh://a b"] {
+            assert!(SshTarget::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn from_request_recognizes_ssh_sessions() {
+        assert!(SshLaunch::from_request(Some("bash"), None).unwrap().is_none());
+        assert!(SshLaunch::from_request(None, None).unwrap().is_none());
+
+        let url = SshLaunch::from_request(Some("ssh://web-1"), None).unwrap().unwrap();
+        assert_eq!(url.target.host, "web-1");
+        assert!(url.remote_command.is_none());
+
+        let remote = SshLaunch::from_request(Some("htop"), Some("ops@web-2")).unwrap().unwrap();
+        assert_eq!(remote.target.user.as_deref(), Some("ops"));
+        assert_eq!(remote.remote_command.as_deref(), Some("htop"));
+
+        assert!(SshLaunch::from_request(None, Some("ssh://")).is_err());
+    }
+
+    #[test]
+    fn command_line_sets_tty_and_keepalives() {
+        let launch = SshLaunch::from_request(Some("tail -f 'a b'"), Some("ops@web:2222")).unwrap().unwrap();
+        let line = launch.command_line();
+        let log = shell_quote(&launch.log.to_string_lossy());
+        assert_eq!(
+            line,
+            format!(
+                "ssh -tt -E {log} -o LogLevel=VERBOSE -o ConnectTimeout=10 -o ServerAliveInterval=15 \
+                 -o ServerAliveCountMax=3 -p 2222 -l 'ops' -- 'web' 'tail -f '\\''a b'\\'''"
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn connection_failure_reports_ssh_error() {
+        if std::process::Command::new("ssh").arg("-V").output().is_err() {
+            eprintln!("skipping: ssh not installed");
+            return;
+        }
+        // Port 1 on loopback refuses connections immediately.
+        let launch = SshLaunch::from_request(Some("ssh://127.0.0.1:1"), None).unwrap().unwrap();
+        launch.create_log(None).unwrap();
+        assert!(launch.create_log(None).is_err(), "log must be created fresh");
+        let (session, mut child_exit_rx) = crate::session::Session::spawn(
+            "ssh-fail".to_string(),
+            launch.spawn_command(),
+            24,
+            80,
+        )
+        .unwrap();
+        let err = launch.wait_connected(&session.usage, &mut child_exit_rx).await.unwrap_err();
+        assert!(err.contains("Connection refused"), "{err}");
+        let log = launch.log.clone();
+        drop(launch);
+        assert!(!log.exists());
+    }
+
+    #[tokio::test]
+    async fn authenticated_connection_succeeds() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Stand-in ssh that logs a successful login and stays quiet.
+        let dir = tempfile::tempdir().unwrap();
+        let fake = dir.path().join("ssh");
+        std::fs::write(
+            &fake,
+            "#!/bin/sh\n\
+             while [ $# -gt 0 ]; do [ \"$1\" = -E ] && log=$2; shift; done\n\
+             echo 'Authenticated to web ([10.0.0.1]:22) using \"publickey\".' >> \"$log\"\n\
+             exec sleep 30\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&fake, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let path = format!("{}:{}", dir.path().display(), std::env::var("PATH").unwrap_or_default());
+
+        let launch = SshLaunch::from_request(Some("ssh://web"), None).unwrap().unwrap();
+        launch.create_log(None).unwrap();
+        let (session, mut child_exit_rx) = crate::session::Session::spawn_with_options(
+            "ssh-ok".to_string(),
+            launch.spawn_command(),
+            24,
+            80,
+            crate::session::SpawnOptions {
+                env: Some([("PATH".to_string(), path)].into()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let started = std::time::Instant::now();
+        launch.wait_connected(&session.usage, &mut child_exit_rx).await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        session.kill_child();
+    }
+}
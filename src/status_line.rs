@@ -0,0 +1,324 @@
+//! Server-rendered status line.
+//!
+//! An optional one-row panel the server keeps up to date on each session,
+//! like tmux's status bar, so clients don't each build their own. Its text
+//! comes from a format string whose `{placeholders}` expand to the session
+//! name, host, time, the other sessions in recent use, and segments
+//! set in the `[status_line]` config section or with
+//! `PATCH /sessions/{name}/status_line`. The server refreshes it every
+//! [`REFRESH_INTERVAL`].
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::overlay::Color;
+use crate::panel::{PanelId, Position};
+
+/// Format used when none is configured.
+pub const DEFAULT_FORMAT: &str = " {session} | {host} | {time} {activity}";
+
+/// How often the server refreshes status lines.
+pub const REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// How recently another session must have had input or output to be
+/// listed by `{activity}`.
+pub const ACTIVE_WITHIN: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Longest format string, in bytes.
+pub const MAX_FORMAT_LEN: usize = 1024;
+
+/// Most segments a status line may have.
+pub const MAX_SEGMENTS: usize = 64;
+
+/// Longest segment value, in bytes.
+pub const MAX_SEGMENT_LEN: usize = 256;
+
+/// Placeholders filled in by the server. Segments may not use these names.
+pub const BUILTINS: &[&str] = &["session", "host", "time", "date", "clients", "activity"];
+
+/// The `[status_line]` config section, which new sessions start with, and
+/// the status line settings of a session.
+///
+/// ```toml
+/// [status_line]
+/// enabled = true
+/// position = "top"
+/// format = " {session} | {task} | {time}"
+/// bg = "blue"
+///
+/// [status_line.segments]
+/// task = "idle"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusLineConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_position")]
+    pub position: Position,
+    #[serde(default = "default_format")]
+    pub format: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fg: Option<Color>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bg: Option<Color>,
+    /// Values for the `{name}` placeholders that aren't built in.
+    #[serde(default)]
+    pub segments: BTreeMap<String, String>,
+}
+
+fn default_position() -> Position {
+    Position::Bottom
+}
+
+fn default_format() -> String {
+    DEFAULT_FORMAT.to_string()
+}
+
+impl Default for StatusLineConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            position: default_position(),
+            format: default_format(),
+            fg: None,
+            bg: None,
+            segments: BTreeMap::new(),
+        }
+    }
+}
+
+impl StatusLineConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.format.len() > MAX_FORMAT_LEN {
+            return Err(format!("format is longer than {} bytes", MAX_FORMAT_LEN));
+        }
+        if self.segments.len() > MAX_SEGMENTS {
+            return Err(format!("more than {} segments", MAX_SEGMENTS));
+        }
+        for (name, value) in &self.segments {
+            if !is_placeholder_name(name) {
+                return Err(format!("segment name '{name}' must be letters, digits and '_'"));
+            }
+            if BUILTINS.contains(&name.as_str()) {
+                return Err(format!("'{name}' is filled in by the server and can't be a segment"));
+            }
+            if value.len() > MAX_SEGMENT_LEN {
+                return Err(format!("segment '{name}' is longer than {} bytes", MAX_SEGMENT_LEN));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Changes to a session's status line settings. Fields left out are
+/// unchanged; a segment set to `null` is removed.
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct StatusLinePatch {
+    pub enabled: Option<bool>,
+    pub position: Option<Position>,
+    pub format: Option<String>,
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    #[serde(default)]
+    pub segments: BTreeMap<String, Option<String>>,
+}
+
+/// Values of the built-in placeholders.
+pub struct Vars<'a> {
+    pub session: &'a str,
+    pub host: &'a str,
+    /// Local time as `HH:MM`.
+    pub time: String,
+    /// Local date as `YYYY-MM-DD`.
+    pub date: String,
+    pub clients: usize,
+    /// Other sessions with input or output within [`ACTIVE_WITHIN`].
+    pub active: &'a [String],
+}
+
+impl Vars<'_> {
+    fn get(&self, name: &str) -> Option<String> {
+        Some(match name {
+            "session" => self.session.to_string(),
+            "host" => self.host.to_string(),
+            "time" => self.time.clone(),
+            "date" => self.date.clone(),
+            "clients" => self.clients.to_string(),
+            "activity" => self.active.iter().map(|name| format!("{name}*")).collect::<Vec<_>>().join(" "),
+            _ => return None,
+        })
+    }
+}
+
+fn is_placeholder_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Expand the `{name}` placeholders in `format`. Unknown names expand to
+/// nothing; braces around anything else are kept as they are.
+pub fn expand(format: &str, vars: &Vars, segments: &BTreeMap<String, String>) -> String {
+    let mut out = String::with_capacity(format.len());
+    let mut rest = format;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        match after.find('}').filter(|&close| is_placeholder_name(&after[..close])) {
+            Some(close) => {
+                let name = &after[..close];
+                match vars.get(name) {
+                    Some(value) => out.push_str(&value),
+                    None => out.push_str(segments.get(name).map_or("", String::as_str)),
+                }
+                rest = &after[close + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Local date and time now, as `(YYYY-MM-DD, HH:MM)`.
+pub fn local_date_time() -> (String, String) {
+    // SAFETY: localtime_r only writes to the tm we pass it.
+    let tm = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        tm
+    };
+    (
+        format!("{:04}-{:02}-{:02}", tm.tm_year + 1900, tm.tm_mon + 1, tm.tm_mday),
+        format!("{:02}:{:02}", tm.tm_hour, tm.tm_min),
+    )
+}
+
+/// A session's status line settings and the panel showing it. Clones
+/// share the same state.
+#[derive(Clone, Default)]
+pub struct StatusLine {
+    inner: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    config: StatusLineConfig,
+    /// The panel showing the status line, once it has been created.
+    panel: Option<PanelId>,
+}
+
+impl StatusLine {
+    pub fn config(&self) -> StatusLineConfig {
+        self.inner.lock().config.clone()
+    }
+
+    pub fn set_config(&self, config: StatusLineConfig) {
+        self.inner.lock().config = config;
+    }
+
+    /// Apply `patch`, returning the new settings, or an error and no
+    /// change if they would be invalid.
+    pub fn patch(&self, patch: StatusLinePatch) -> Result<StatusLineConfig, String> {
+        let mut inner = self.inner.lock();
+        let mut config = inner.config.clone();
+        if let Some(enabled) = patch.enabled {
+            config.enabled = enabled;
+        }
+        if let Some(position) = patch.position {
+            config.position = position;
+        }
+        if let Some(format) = patch.format {
+            config.format = format;
+        }
+        if let Some(fg) = patch.fg {
+            config.fg = Some(fg);
+        }
+        if let Some(bg) = patch.bg {
+            config.bg = Some(bg);
+        }
+        for (name, value) in patch.segments {
+            match value {
+                Some(value) => config.segments.insert(name, value),
+                None => config.segments.remove(&name),
+            };
+        }
+        config.validate()?;
+        inner.config = config.clone();
+        Ok(config)
+    }
+
+    pub fn panel(&self) -> Option<PanelId> {
+        self.inner.lock().panel.clone()
+    }
+
+    pub fn set_panel(&self, panel: Option<PanelId>) {
+        self.inner.lock().panel = panel;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(active: &[String]) -> Vars<'_> {
+        Vars {
+            session: "build",
+            host: "devbox",
+            time: "09:30".into(),
+            date: "2026-10-17".into(),
+            clients: 2,
+            active,
+        }
+    }
+
+    #[test]
+    fn expands_builtins_and_segments() {
+        let active = ["tests".to_string(), "lint".to_string()];
+        let segments = BTreeMap::from([("task".to_string(), "deploying".to_string())]);
+        assert_eq!(
+            expand("[{session}@{host}] {task} {clients} {activity} {date} {time}", &vars(&active), &segments),
+            "[build@devbox] deploying 2 tests* lint* 2026-10-17 09:30"
+        );
+    }
+
+    #[test]
+    fn unknown_placeholders_are_empty_and_other_braces_kept() {
+        let segments = BTreeMap::new();
+        assert_eq!(expand("{missing}|{not a name}|{", &vars(&[]), &segments), "|{not a name}|{");
+    }
+
+    #[test]
+    fn patch_merges_segments_and_validates() {
+        let status_line = StatusLine::default();
+        let patch = |json: serde_json::Value| serde_json::from_value::<StatusLinePatch>(json).unwrap();
+        status_line.patch(patch(serde_json::json!({"enabled": true, "segments": {"a": "1", "b": "2"}}))).unwrap();
+        let config = status_line.patch(patch(serde_json::json!({"segments": {"a": null}}))).unwrap();
+        assert!(config.enabled);
+        assert_eq!(config.segments, BTreeMap::from([("b".to_string(), "2".to_string())]));
+
+        assert!(status_line.patch(patch(serde_json::json!({"segments": {"time": "x"}}))).is_err());
+        assert!(status_line.patch(patch(serde_json::json!({"segments": {"bad name": "x"}}))).is_err());
+        assert_eq!(status_line.config(), config, "a rejected patch changes nothing");
+    }
+
+    #[test]
+    fn parses_the_config_section() {
+        let config: StatusLineConfig = toml::from_str(
+            "enabled = true\nposition = \"top\"\nbg = \"blue\"\n[segments]\ntask = \"idle\"\n",
+        )
+        .unwrap();
+        assert!(config.enabled);
+        assert_eq!(config.position, Position::Top);
+        assert_eq!(config.format, DEFAULT_FORMAT);
+        assert_eq!(config.segments["task"], "idle");
+        config.validate().unwrap();
+    }
+}
@@ -0,0 +1,393 @@
+//! Session templates: reusable session definitions kept on the server.
+//!
+//! A template holds the spawn parameters an orchestrator would otherwise
+//! repeat on every create (command, working directory, environment, size,
+//! tags) plus overlays and panels to put on the new session. Templates are
+//! managed through `/templates` and stored one per file in the `templates`
+//! directory next to the config file, e.g.
+//! `~/.config/wsh/templates/build-env.toml`:
+//!
+//! ```toml
+//! command = "cargo watch -x test"
+//! cwd = "/home/me/src/app"
+//! rows = 40
+//! cols = 120
+//! tags = ["build"]
+//!
+//! [env]
+//! RUST_LOG = "info"
+//!
+//! [[panels]]
+//! position = "top"
+//! height = 1
+//! spans = [{ text = "build-env" }]
+//! ```
+//!
+//! A create request naming a template starts from it; fields the request
+//! sets itself win, `env` is merged key by key and `tags` are combined.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+use crate::overlay::{BackgroundStyle, OverlaySpan, Widget};
+use crate::panel::Position;
+use crate::session::Session;
+
+/// Maximum number of templates a server keeps.
+pub const MAX_TEMPLATES: usize = 256;
+
+/// Maximum length of a template name.
+const MAX_NAME_LEN: usize = 64;
+
+/// A reusable session definition.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct SessionTemplate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<HashMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cols: Option<u16>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Overlays created on each new session.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub overlays: Vec<TemplateOverlay>,
+    /// Panels created on each new session.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub panels: Vec<TemplatePanel>,
+}
+
+/// An overlay in a template, as given to `POST /sessions/:name/overlay`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct TemplateOverlay {
+    pub x: u16,
+    pub y: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub z: Option<i32>,
+    pub width: u16,
+    pub height: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<BackgroundStyle>,
+    #[serde(default)]
+    pub spans: Vec<OverlaySpan>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub focusable: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub widget: Option<Widget>,
+}
+
+/// A panel in a template, as given to `POST /sessions/:name/panel`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemas", derive(schemars::JsonSchema))]
+pub struct TemplatePanel {
+    pub position: Position,
+    pub height: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub z: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<BackgroundStyle>,
+    #[serde(default)]
+    pub spans: Vec<OverlaySpan>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub focusable: bool,
+}
+
+impl SessionTemplate {
+    /// Fill in the create parameters the request left unset from the
+    /// template. `env` is merged with the request's values winning, and the
+    /// template's tags are added to the request's.
+    pub fn fill(
+        &self,
+        command: &mut Option<String>,
+        cwd: &mut Option<String>,
+        env: &mut Option<HashMap<String, String>>,
+        rows: &mut Option<u16>,
+        cols: &mut Option<u16>,
+        tags: &mut Vec<String>,
+    ) {
+        if command.is_none() {
+            command.clone_from(&self.command);
+        }
+        if cwd.is_none() {
+            cwd.clone_from(&self.cwd);
+        }
+        if let Some(ref defaults) = self.env {
+            let merged = env.get_or_insert_with(HashMap::new);
+            for (key, value) in defaults {
+                merged.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+        }
+        *rows = rows.or(self.rows);
+        *cols = cols.or(self.cols);
+        for tag in &self.tags {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+    }
+
+    /// Create the template's overlays and panels on a new session.
+    pub async fn decorate(&self, session: &Session) -> Result<(), String> {
+        if self.overlays.is_empty() && self.panels.is_empty() {
+            return Ok(());
+        }
+        let mode = *session.screen_mode.read();
+        for o in &self.overlays {
+            let id = session
+                .overlays
+                .create(o.x, o.y, o.z, o.width, o.height, o.background.clone(), o.spans.clone(), o.focusable, mode)
+                .map_err(|e| e.to_string())?;
+            if let Some(ref widget) = o.widget {
+                if let Err(e) = session.overlays.set_widget(&id, widget.clone()) {
+                    session.overlays.delete(&id);
+                    return Err(e.to_string());
+                }
+            }
+        }
+        if !self.overlays.is_empty() {
+            let _ = session.visual_update_tx.send(crate::protocol::VisualUpdate::OverlaysChanged);
+        }
+        for p in &self.panels {
+            session
+                .panels
+                .create(p.position.clone(), p.height, p.z, p.background.clone(), p.spans.clone(), p.focusable, mode)
+                .map_err(|e| e.to_string())?;
+        }
+        if !self.panels.is_empty() {
+            crate::panel::reconfigure_layout(&session.panels, &session.terminal_size, &session.pty, &session.parser)
+                .await;
+            let _ = session.visual_update_tx.send(crate::protocol::VisualUpdate::PanelsChanged);
+        }
+        Ok(())
+    }
+
+    /// Check what can be checked before a session is spawned from it.
+    fn validate(&self) -> Result<(), String> {
+        for tag in &self.tags {
+            crate::session::validate_tag(tag)?;
+        }
+        if self.rows == Some(0) || self.cols == Some(0) {
+            return Err("rows and cols must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Why a template couldn't be stored.
+#[derive(Debug)]
+pub enum TemplateError {
+    InvalidName(String),
+    Invalid(String),
+    LimitReached,
+    WriteFailed(String),
+}
+
+impl std::fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidName(msg) => write!(f, "invalid template name: {}", msg),
+            Self::Invalid(msg) => write!(f, "invalid template: {}", msg),
+            Self::LimitReached => write!(f, "maximum number of templates ({}) reached", MAX_TEMPLATES),
+            Self::WriteFailed(msg) => write!(f, "failed to save template: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+/// Template names become file names, so they are kept to a safe alphabet.
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(format!("must be 1 to {} characters", MAX_NAME_LEN));
+    }
+    if name.starts_with('.') {
+        return Err("must not start with '.'".to_string());
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')) {
+        return Err("may only contain letters, digits, '-', '_' and '.'".to_string());
+    }
+    Ok(())
+}
+
+/// The server's templates, backed by a directory once one is set.
+#[derive(Default)]
+pub struct TemplateStore {
+    dir: RwLock<Option<PathBuf>>,
+    templates: RwLock<BTreeMap<String, SessionTemplate>>,
+}
+
+impl TemplateStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep templates in `dir` and load the ones already there. Files that
+    /// fail to load are skipped with a warning. Returns how many loaded.
+    pub fn load_dir(&self, dir: &Path) -> usize {
+        let mut loaded = BTreeMap::new();
+        if let Ok(entries) = std::fs::read_dir(dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("toml") {
+                    continue;
+                }
+                let Some(name) = path.file_stem().and_then(|s| s.to_str()).map(str::to_string) else {
+                    continue;
+                };
+                let result = validate_name(&name).and_then(|_| {
+                    let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+                    let template: SessionTemplate = toml::from_str(&contents).map_err(|e| e.message().to_string())?;
+                    template.validate()?;
+                    Ok(template)
+                });
+                match result {
+                    Ok(template) if loaded.len() < MAX_TEMPLATES => {
+                        loaded.insert(name, template);
+                    }
+                    Ok(_) => tracing::warn!(path = %path.display(), "too many templates, skipping"),
+                    Err(e) => tracing::warn!(path = %path.display(), error = %e, "skipping invalid template"),
+                }
+            }
+        }
+        let count = loaded.len();
+        *self.templates.write() = loaded;
+        *self.dir.write() = Some(dir.to_path_buf());
+        count
+    }
+
+    pub fn list(&self) -> Vec<(String, SessionTemplate)> {
+        self.templates.read().iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<SessionTemplate> {
+        self.templates.read().get(name).cloned()
+    }
+
+    /// Create or replace a template. Returns `true` if it is new.
+    pub fn put(&self, name: &str, template: SessionTemplate) -> Result<bool, TemplateError> {
+        validate_name(name).map_err(TemplateError::InvalidName)?;
+        template.validate().map_err(TemplateError::Invalid)?;
+        let mut templates = self.templates.write();
+        let is_new = !templates.contains_key(name);
+        if is_new && templates.len() >= MAX_TEMPLATES {
+            return Err(TemplateError::LimitReached);
+        }
+        if let Some(ref dir) = *self.dir.read() {
+            let contents = toml::to_string_pretty(&template).map_err(|e| TemplateError::Invalid(e.to_string()))?;
+            std::fs::create_dir_all(dir)
+                .and_then(|_| std::fs::write(dir.join(format!("{}.toml", name)), contents))
+                .map_err(|e| TemplateError::WriteFailed(e.to_string()))?;
+        }
+        templates.insert(name.to_string(), template);
+        Ok(is_new)
+    }
+
+    /// Delete a template. Returns `false` if there was none by that name.
+    pub fn remove(&self, name: &str) -> Result<bool, TemplateError> {
+        let mut templates = self.templates.write();
+        if !templates.contains_key(name) {
+            return Ok(false);
+        }
+        if let Some(ref dir) = *self.dir.read() {
+            match std::fs::remove_file(dir.join(format!("{}.toml", name))) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(TemplateError::WriteFailed(e.to_string())),
+            }
+        }
+        templates.remove(name);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template() -> SessionTemplate {
+        SessionTemplate {
+            command: Some("make".to_string()),
+            cwd: Some("/tmp".to_string()),
+            env: Some(HashMap::from([
+                ("A".to_string(), "template".to_string()),
+                ("B".to_string(), "template".to_string()),
+            ])),
+            rows: Some(40),
+            cols: None,
+            tags: vec!["build".to_string()],
+            overlays: Vec::new(),
+            panels: vec![TemplatePanel {
+                position: Position::Top,
+                height: 1,
+                z: None,
+                background: None,
+                spans: Vec::new(),
+                focusable: false,
+            }],
+        }
+    }
+
+    #[test]
+    fn fill_lets_the_request_win() {
+        let mut command = Some("ls".to_string());
+        let mut cwd = None;
+        let mut env = Some(HashMap::from([("A".to_string(), "request".to_string())]));
+        let (mut rows, mut cols) = (None, Some(100));
+        let mut tags = vec!["mine".to_string(), "build".to_string()];
+        template().fill(&mut command, &mut cwd, &mut env, &mut rows, &mut cols, &mut tags);
+        assert_eq!(command.as_deref(), Some("ls"));
+        assert_eq!(cwd.as_deref(), Some("/tmp"));
+        let env = env.unwrap();
+        assert_eq!((env["A"].as_str(), env["B"].as_str()), ("request", "template"));
+        assert_eq!((rows, cols), (Some(40), Some(100)));
+        assert_eq!(tags, vec!["mine", "build"]);
+    }
+
+    #[test]
+    fn names_are_file_safe() {
+        assert!(validate_name("build-env_2.0").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name(".hidden").is_err());
+        assert!(validate_name("../etc/passwd").is_err());
+        assert!(validate_name(&"x".repeat(65)).is_err());
+    }
+
+    #[test]
+    fn put_rejects_bad_tags_and_sizes() {
+        let store = TemplateStore::new();
+        let bad_tag = SessionTemplate { tags: vec!["has space".to_string()], ..Default::default() };
+        assert!(matches!(store.put("a", bad_tag), Err(TemplateError::Invalid(_))));
+        let bad_size = SessionTemplate { rows: Some(0), ..Default::default() };
+        assert!(matches!(store.put("a", bad_size), Err(TemplateError::Invalid(_))));
+        assert!(matches!(store.put("a/b", SessionTemplate::default()), Err(TemplateError::InvalidName(_))));
+    }
+
+    #[test]
+    fn templates_round_trip_through_the_directory() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = TemplateStore::new();
+        assert_eq!(store.load_dir(dir.path()), 0);
+        assert!(store.put("build-env", template()).unwrap());
+        assert!(!store.put("build-env", template()).unwrap());
+        std::fs::write(dir.path().join("broken.toml"), "command = [").unwrap();
+
+        let reloaded = TemplateStore::new();
+        assert_eq!(reloaded.load_dir(dir.path()), 1);
+        assert_eq!(reloaded.get("build-env"), Some(template()));
+
+        assert!(reloaded.remove("build-env").unwrap());
+        assert!(!reloaded.remove("build-env").unwrap());
+        assert!(!dir.path().join("build-env.toml").exists());
+    }
+}
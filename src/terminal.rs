@@ -1,7 +1,6 @@
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode, size};
 use std::io::Write;
 use std::sync::Arc;
-use parking_lot::RwLock;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -120,26 +119,233 @@ pub fn terminal_size() -> anyhow::Result<(u16, u16)> {
 ///
 /// Tracks the outer terminal's current size so that layout computation
 /// and resize handlers can access it from any thread.
+///
+/// Size changes can be observed via [`TerminalSize::subscribe`].
 #[derive(Clone)]
 pub struct TerminalSize {
-    inner: Arc<RwLock<(u16, u16)>>,
+    inner: Arc<tokio::sync::watch::Sender<(u16, u16)>>,
 }
 
 impl TerminalSize {
     /// Create a new TerminalSize with initial dimensions (rows, cols).
     pub fn new(rows: u16, cols: u16) -> Self {
+        let (tx, _rx) = tokio::sync::watch::channel((rows, cols));
         Self {
-            inner: Arc::new(RwLock::new((rows, cols))),
+            inner: Arc::new(tx),
         }
     }
 
     /// Get the current terminal size as (rows, cols).
     pub fn get(&self) -> (u16, u16) {
-        *self.inner.read()
+        *self.inner.borrow()
     }
 
-    /// Update the terminal size.
+    /// Update the terminal size. Subscribers are only notified if the
+    /// size actually changed.
     pub fn set(&self, rows: u16, cols: u16) {
-        *self.inner.write() = (rows, cols);
+        self.inner.send_if_modified(|size| {
+            if *size == (rows, cols) {
+                return false;
+            }
+            *size = (rows, cols);
+            true
+        });
+    }
+
+    /// Subscribe to size changes. The current size is marked as seen.
+    pub fn subscribe(&self) -> tokio::sync::watch::Receiver<(u16, u16)> {
+        self.inner.subscribe()
+    }
+}
+
+/// How a session reconciles the sizes reported by multiple attached clients.
+///
+/// Only client-reported sizes (socket attach and SIGWINCH-driven `Resize`
+/// frames) go through the policy. Explicit API resizes always apply.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResizePolicy {
+    /// The most recent client resize wins.
+    #[default]
+    #[serde(alias = "latest-client")]
+    LatestClient,
+    /// The session takes the largest rows and cols among attached clients.
+    #[serde(alias = "largest-client")]
+    LargestClient,
+    /// Client resizes are ignored; only explicit API resizes change the size.
+    Fixed,
+}
+
+/// Per-session record of attached client sizes and the active [`ResizePolicy`].
+///
+/// Clones share state. Each method that can change the session size returns
+/// the `(rows, cols)` the caller should apply, or `None` to leave it alone.
+#[derive(Clone, Default)]
+pub struct SizeArbiter {
+    inner: Arc<parking_lot::Mutex<ArbiterState>>,
+}
+
+#[derive(Default)]
+struct ArbiterState {
+    policy: ResizePolicy,
+    next_id: u64,
+    clients: std::collections::HashMap<u64, (u16, u16)>,
+}
+
+impl ArbiterState {
+    fn largest(&self) -> Option<(u16, u16)> {
+        if self.clients.is_empty() {
+            return None;
+        }
+        let rows = self.clients.values().map(|&(r, _)| r).max().unwrap_or(1);
+        let cols = self.clients.values().map(|&(_, c)| c).max().unwrap_or(1);
+        Some((rows, cols))
+    }
+
+    fn target_for(&self, reported: (u16, u16)) -> Option<(u16, u16)> {
+        match self.policy {
+            ResizePolicy::LatestClient => Some(reported),
+            ResizePolicy::LargestClient => self.largest(),
+            ResizePolicy::Fixed => None,
+        }
+    }
+}
+
+impl SizeArbiter {
+    /// Create an arbiter with the given policy and no attached clients.
+    pub fn new(policy: ResizePolicy) -> Self {
+        Self {
+            inner: Arc::new(parking_lot::Mutex::new(ArbiterState {
+                policy,
+                ..Default::default()
+            })),
+        }
+    }
+
+    /// Get the active policy.
+    pub fn policy(&self) -> ResizePolicy {
+        self.inner.lock().policy
+    }
+
+    /// Change the policy. Switching to `LargestClient` while clients are
+    /// attached yields the size they collectively need.
+    pub fn set_policy(&self, policy: ResizePolicy) -> Option<(u16, u16)> {
+        let mut state = self.inner.lock();
+        state.policy = policy;
+        match policy {
+            ResizePolicy::LargestClient => state.largest(),
+            _ => None,
+        }
+    }
+
+    /// Number of clients currently reporting a size.
+    pub fn client_count(&self) -> usize {
+        self.inner.lock().clients.len()
+    }
+
+    /// Register a newly attached client with its terminal size.
+    ///
+    /// Returns the client id for later [`report`](Self::report) and
+    /// [`unregister`](Self::unregister) calls, plus the size to apply.
+    pub fn register(&self, rows: u16, cols: u16) -> (u64, Option<(u16, u16)>) {
+        let mut state = self.inner.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.clients.insert(id, (rows, cols));
+        (id, state.target_for((rows, cols)))
+    }
+
+    /// Record a size change from an attached client.
+    pub fn report(&self, id: u64, rows: u16, cols: u16) -> Option<(u16, u16)> {
+        let mut state = self.inner.lock();
+        state.clients.insert(id, (rows, cols));
+        state.target_for((rows, cols))
+    }
+
+    /// Forget a detached client. Under `LargestClient` the remaining clients
+    /// may now fit in a smaller size, which is returned.
+    pub fn unregister(&self, id: u64) -> Option<(u16, u16)> {
+        let mut state = self.inner.lock();
+        let previous = state.largest();
+        state.clients.remove(&id);
+        match state.policy {
+            ResizePolicy::LargestClient => state.largest().filter(|&s| Some(s) != previous),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latest_client_follows_every_report() {
+        let arbiter = SizeArbiter::new(ResizePolicy::LatestClient);
+        let (a, target) = arbiter.register(24, 80);
+        assert_eq!(target, Some((24, 80)));
+        let (_b, target) = arbiter.register(50, 200);
+        assert_eq!(target, Some((50, 200)));
+        assert_eq!(arbiter.report(a, 30, 100), Some((30, 100)));
+    }
+
+    #[test]
+    fn largest_client_takes_max_of_each_dimension() {
+        let arbiter = SizeArbiter::new(ResizePolicy::LargestClient);
+        let (a, _) = arbiter.register(40, 80);
+        let (b, target) = arbiter.register(24, 120);
+        assert_eq!(target, Some((40, 120)));
+        assert_eq!(arbiter.report(b, 20, 100), Some((40, 100)));
+        assert_eq!(arbiter.unregister(a), Some((20, 100)));
+        assert_eq!(arbiter.client_count(), 1);
+    }
+
+    #[test]
+    fn largest_client_unregister_without_change_returns_none() {
+        let arbiter = SizeArbiter::new(ResizePolicy::LargestClient);
+        let (_a, _) = arbiter.register(40, 120);
+        let (b, _) = arbiter.register(24, 80);
+        assert_eq!(arbiter.unregister(b), None);
+    }
+
+    #[test]
+    fn fixed_ignores_client_sizes() {
+        let arbiter = SizeArbiter::new(ResizePolicy::Fixed);
+        let (a, target) = arbiter.register(24, 80);
+        assert_eq!(target, None);
+        assert_eq!(arbiter.report(a, 50, 200), None);
+        assert_eq!(arbiter.unregister(a), None);
+    }
+
+    #[test]
+    fn switching_to_largest_recomputes_from_attached_clients() {
+        let arbiter = SizeArbiter::default();
+        arbiter.register(24, 80);
+        arbiter.register(30, 60);
+        assert_eq!(arbiter.set_policy(ResizePolicy::LargestClient), Some((30, 80)));
+        assert_eq!(arbiter.set_policy(ResizePolicy::Fixed), None);
+        assert_eq!(arbiter.policy(), ResizePolicy::Fixed);
+    }
+
+    #[test]
+    fn resize_policy_serde() {
+        assert_eq!(
+            serde_json::to_string(&ResizePolicy::LargestClient).unwrap(),
+            "\"largest_client\""
+        );
+        let p: ResizePolicy = serde_json::from_str("\"latest-client\"").unwrap();
+        assert_eq!(p, ResizePolicy::LatestClient);
+    }
+
+    #[test]
+    fn terminal_size_notifies_only_on_change() {
+        let size = TerminalSize::new(24, 80);
+        let mut rx = size.subscribe();
+        size.set(24, 80);
+        assert!(!rx.has_changed().unwrap());
+        size.set(40, 120);
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(*rx.borrow_and_update(), (40, 120));
+        assert_eq!(size.get(), (40, 120));
     }
 }
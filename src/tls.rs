@@ -2,7 +2,7 @@
 //!
 //! Loads PEM-encoded certificate chains and private keys, builds a rustls
 //! `ServerConfig`, and wraps it in a `TlsAcceptor` for use with the manual
-//! accept loop in `run_server()`.
+//! accept loop in [`serve`].
 
 use std::path::Path;
 use std::sync::Arc;
@@ -66,6 +66,72 @@ pub fn load_tls_config(cert_path: &Path, key_path: &Path) -> Result<TlsAcceptor,
     Ok(TlsAcceptor::from(Arc::new(config)))
 }
 
+/// Manual TLS accept loop for HTTPS serving.
+///
+/// `axum::serve()` only accepts `TcpListener` (sealed `Listener` trait), so
+/// TLS requires a manual loop: accept TCP → TLS handshake → hyper-util
+/// `serve_connection`. Each connection is handled in its own spawned task.
+///
+/// Like `into_make_service_with_connect_info`, each request carries the
+/// peer's `ConnectInfo<SocketAddr>`, which ticket IP binding relies on.
+pub async fn serve(
+    listener: tokio::net::TcpListener,
+    acceptor: tokio_rustls::TlsAcceptor,
+    app: axum::Router,
+    cancel: tokio_util::sync::CancellationToken,
+) {
+    use hyper_util::rt::TokioIo;
+
+    loop {
+        let (tcp_stream, peer_addr) = tokio::select! {
+            _ = cancel.cancelled() => break,
+            result = listener.accept() => {
+                match result {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        tracing::debug!(?e, "TCP accept error");
+                        continue;
+                    }
+                }
+            }
+        };
+
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+        let cancel = cancel.clone();
+
+        tokio::spawn(async move {
+            let tls_stream = match acceptor.accept(tcp_stream).await {
+                Ok(s) => s,
+                Err(e) => {
+                    tracing::debug!(?e, %peer_addr, "TLS handshake failed");
+                    return;
+                }
+            };
+
+            let io = TokioIo::new(tls_stream);
+            let app = app.layer(axum::Extension(axum::extract::ConnectInfo(peer_addr)));
+            let service = hyper_util::service::TowerToHyperService::new(app);
+            let builder = hyper_util::server::conn::auto::Builder::new(hyper_util::rt::TokioExecutor::new());
+            let conn = builder.serve_connection_with_upgrades(io, service);
+            tokio::pin!(conn);
+
+            tokio::select! {
+                result = &mut conn => {
+                    if let Err(e) = result {
+                        tracing::debug!(?e, %peer_addr, "connection error");
+                    }
+                }
+                _ = cancel.cancelled() => {
+                    // Graceful shutdown: signal the connection and give it time to finish
+                    conn.as_mut().graceful_shutdown();
+                    let _ = tokio::time::timeout(std::time::Duration::from_secs(5), conn).await;
+                }
+            }
+        });
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
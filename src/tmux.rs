@@ -0,0 +1,143 @@
+//! Adopting existing tmux sessions.
+//!
+//! tmux users already have their work running in tmux sessions. Rather than
+//! moving it, wsh can wrap each one: a new wsh session whose child is a
+//! `tmux attach-session` client. The tmux session keeps running on its own
+//! if the wsh session goes away, and detaching from tmux (`prefix d`) ends
+//! the wsh session.
+
+use serde::Serialize;
+
+use crate::pty::shell_quote;
+
+/// Tag given to every session created by an import.
+pub const IMPORT_TAG: &str = "tmux";
+
+/// A session on a tmux server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TmuxSession {
+    pub name: String,
+    pub windows: u32,
+    /// Number of tmux clients currently attached.
+    pub attached: u32,
+}
+
+/// tmux replaces control characters (tabs included) in its output when not
+/// run from inside tmux, so fields are `:`-separated with the name, which
+/// may contain anything, last.
+const LIST_FORMAT: &str = "#{session_windows}:#{session_attached}:#{session_name}";
+
+/// List the sessions on a tmux server. `socket` selects a server by name,
+/// like `tmux -L`; `None` is the default server.
+///
+/// No tmux server running is an empty list, not an error.
+pub fn list_sessions(socket: Option<&str>) -> Result<Vec<TmuxSession>, String> {
+    let output = command(socket)
+        .args(["list-sessions", "-F", LIST_FORMAT])
+        .output()
+        .map_err(spawn_error)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("no server running") || stderr.contains("error connecting to") {
+            return Ok(Vec::new());
+        }
+        return Err(format!("tmux list-sessions failed: {}", stderr.trim()));
+    }
+    Ok(parse_list(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// A `tmux` invocation for the server selected by `socket`.
+pub(crate) fn command(socket: Option<&str>) -> std::process::Command {
+    let mut cmd = std::process::Command::new("tmux");
+    // -u: report non-ASCII session names as-is instead of as `_`.
+    cmd.arg("-u");
+    if let Some(socket) = socket {
+        cmd.args(["-L", socket]);
+    }
+    cmd.env_remove("TMUX");
+    cmd
+}
+
+pub(crate) fn spawn_error(e: std::io::Error) -> String {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => "tmux is not installed".to_string(),
+        _ => format!("failed to run tmux: {}", e),
+    }
+}
+
+fn parse_list(output: &str) -> Vec<TmuxSession> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, ':');
+            let windows = fields.next()?.parse().ok()?;
+            let attached = fields.next()?.parse().ok()?;
+            let name = fields.next().filter(|n| !n.is_empty())?;
+            Some(TmuxSession { name: name.to_string(), windows, attached })
+        })
+        .collect()
+}
+
+/// The wsh session name for a tmux session: `tmux-` plus the tmux name with
+/// characters wsh doesn't allow replaced by `-`, cut to 64 characters.
+pub fn session_name(tmux_name: &str) -> String {
+    let mut name: String = format!("tmux-{}", tmux_name)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "._-".contains(c) { c } else { '-' })
+        .collect();
+    name.truncate(64);
+    name
+}
+
+/// Shell command that attaches to `tmux_name`. `TMUX` is cleared so a
+/// server started from inside tmux can still attach, and `=` makes tmux
+/// match the name exactly rather than as a prefix.
+pub fn attach_command(tmux_name: &str, socket: Option<&str>) -> String {
+    let flags = match socket {
+        Some(s) => format!("-u -L {}", shell_quote(s)),
+        None => "-u".to_string(),
+    };
+    format!(
+        "unset TMUX; exec tmux {} attach-session -t {}",
+        flags,
+        shell_quote(&format!("={}", tmux_name))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_list_reads_fields() {
+        let out = "3:1:main\n1:0:my: work\nbroken line\n";
+        assert_eq!(
+            parse_list(out),
+            vec![
+                TmuxSession { name: "main".into(), windows: 3, attached: 1 },
+                TmuxSession { name: "my: work".into(), windows: 1, attached: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn session_name_is_valid_for_wsh() {
+        assert_eq!(session_name("main"), "tmux-main");
+        assert_eq!(session_name("my work/α"), "tmux-my-work--");
+        let long = session_name(&"x".repeat(100));
+        assert_eq!(long.len(), 64);
+        assert!(crate::session::validate_session_name(&long).is_ok());
+    }
+
+    #[test]
+    fn attach_command_quotes_names() {
+        assert_eq!(
+            attach_command("it's", None),
+            r"unset TMUX; exec tmux -u attach-session -t '=it'\''s'"
+        );
+        assert_eq!(
+            attach_command("main", Some("work")),
+            "unset TMUX; exec tmux -u -L 'work' attach-session -t '=main'"
+        );
+    }
+}
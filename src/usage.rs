@@ -0,0 +1,237 @@
+//! Per-session usage counters.
+//!
+//! Teams sharing one server want to know who used what. Each session keeps
+//! a [`UsageCounters`] that the PTY reader and writer, the client admission
+//! path, and the MCP tool handlers bump as they work. The totals are exposed
+//! through the stats endpoint and logged when the session ends.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// Point-in-time copy of a session's counters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct UsageSnapshot {
+    /// Lines submitted to the PTY (Enter presses, including pasted newlines).
+    pub commands_run: u64,
+    /// Bytes the child wrote to the terminal.
+    pub output_bytes: u64,
+    /// Bytes written to the PTY as input.
+    pub input_bytes: u64,
+    /// Total time at least one streaming client was attached.
+    pub attached_ms: u64,
+    /// MCP tool calls that targeted this session.
+    pub mcp_tool_calls: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    commands_run: AtomicU64,
+    output_bytes: AtomicU64,
+    input_bytes: AtomicU64,
+    mcp_tool_calls: AtomicU64,
+    /// Whether the last input chunk ended in `\r`, so a `\n` starting the
+    /// next chunk is the second half of a CRLF rather than another line.
+    pending_cr: AtomicBool,
+    attached: parking_lot::Mutex<Attached>,
+}
+
+#[derive(Default)]
+struct Attached {
+    total: Duration,
+    since: Option<Instant>,
+    /// When the last client detached.
+    detached_at: Option<Instant>,
+}
+
+/// Shared, cheaply cloneable usage counters for one session.
+#[derive(Clone, Default)]
+pub struct UsageCounters {
+    inner: Arc<Inner>,
+}
+
+impl UsageCounters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record input written to the PTY. Counts `\r`, and `\n` not preceded
+    /// by `\r`, as one submitted command each.
+    pub fn record_input(&self, data: &[u8]) {
+        self.inner
+            .input_bytes
+            .fetch_add(data.len() as u64, Ordering::Relaxed);
+        let mut prev_cr = self.inner.pending_cr.load(Ordering::Relaxed);
+        let mut lines = 0;
+        for &b in data {
+            match b {
+                b'\r' => lines += 1,
+                b'\n' if !prev_cr => lines += 1,
+                _ => {}
+            }
+            prev_cr = b == b'\r';
+        }
+        if !data.is_empty() {
+            self.inner.pending_cr.store(prev_cr, Ordering::Relaxed);
+        }
+        if lines > 0 {
+            self.inner.commands_run.fetch_add(lines, Ordering::Relaxed);
+        }
+    }
+
+    /// Record output read from the PTY. Safe to call from blocking threads.
+    pub fn record_output(&self, len: usize) {
+        self.inner
+            .output_bytes
+            .fetch_add(len as u64, Ordering::Relaxed);
+    }
+
+    /// Record an MCP tool call against the session.
+    pub fn record_mcp_call(&self) {
+        self.inner.mcp_tool_calls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Start or stop the attached-time clock to match the current client
+    /// count. Called after every connect and disconnect; reading the count
+    /// under the lock means interleaved calls settle on the right state.
+    pub(crate) fn sync_attached(&self, clients: &AtomicUsize) {
+        let mut attached = self.inner.attached.lock();
+        let connected = clients.load(Ordering::Acquire) > 0;
+        match (connected, attached.since) {
+            (true, None) => attached.since = Some(Instant::now()),
+            (false, Some(since)) => {
+                attached.total += since.elapsed();
+                attached.since = None;
+                attached.detached_at = Some(Instant::now());
+            }
+            _ => {}
+        }
+    }
+
+    /// Zero every counter and count the session as unattached from now,
+    /// for a pre-spawned shell handed out as a new session.
+    pub(crate) fn reset(&self) {
+        self.inner.commands_run.store(0, Ordering::Relaxed);
+        self.inner.output_bytes.store(0, Ordering::Relaxed);
+        self.inner.input_bytes.store(0, Ordering::Relaxed);
+        self.inner.mcp_tool_calls.store(0, Ordering::Relaxed);
+        self.inner.pending_cr.store(false, Ordering::Relaxed);
+        *self.inner.attached.lock() = Attached {
+            detached_at: Some(Instant::now()),
+            ..Attached::default()
+        };
+    }
+
+    /// How long the session has gone without a client attached: `None`
+    /// while one is, `Duration::MAX` if none ever was.
+    pub fn unattached_for(&self) -> Option<Duration> {
+        let attached = self.inner.attached.lock();
+        match (attached.since, attached.detached_at) {
+            (Some(_), _) => None,
+            (None, Some(at)) => Some(at.elapsed()),
+            (None, None) => Some(Duration::MAX),
+        }
+    }
+
+    /// Current counters, including any attach span still in progress.
+    pub fn snapshot(&self) -> UsageSnapshot {
+        let attached = {
+            let a = self.inner.attached.lock();
+            a.total + a.since.map(|s| s.elapsed()).unwrap_or_default()
+        };
+        UsageSnapshot {
+            commands_run: self.inner.commands_run.load(Ordering::Relaxed),
+            output_bytes: self.inner.output_bytes.load(Ordering::Relaxed),
+            input_bytes: self.inner.input_bytes.load(Ordering::Relaxed),
+            attached_ms: attached.as_millis() as u64,
+            mcp_tool_calls: self.inner.mcp_tool_calls.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Write the final counters to the log, for usage reports built from
+    /// server logs.
+    pub(crate) fn log_final(&self, session: &str) {
+        let u = self.snapshot();
+        tracing::info!(
+            target: "wsh::usage",
+            session = %session,
+            commands_run = u.commands_run,
+            output_bytes = u.output_bytes,
+            input_bytes = u.input_bytes,
+            attached_ms = u.attached_ms,
+            mcp_tool_calls = u.mcp_tool_calls,
+            "session usage"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_submitted_lines() {
+        let usage = UsageCounters::new();
+        usage.record_input(b"ls -la\r");
+        usage.record_input(b"echo a\r");
+        // CRLF split across writes is still one line.
+        usage.record_input(b"\n");
+        usage.record_input(b"one\ntwo\n");
+        usage.record_input(b"partial");
+        let u = usage.snapshot();
+        assert_eq!(u.commands_run, 4);
+        assert_eq!(u.input_bytes, 7 + 7 + 1 + 8 + 7);
+    }
+
+    #[test]
+    fn attached_time_spans_first_connect_to_last_disconnect() {
+        let usage = UsageCounters::new();
+        let clients = AtomicUsize::new(0);
+        usage.sync_attached(&clients);
+        assert_eq!(usage.snapshot().attached_ms, 0);
+
+        clients.store(1, Ordering::Release);
+        usage.sync_attached(&clients);
+        clients.store(2, Ordering::Release);
+        usage.sync_attached(&clients);
+        std::thread::sleep(Duration::from_millis(30));
+        clients.store(1, Ordering::Release);
+        usage.sync_attached(&clients);
+        // Still attached: the open span counts.
+        assert!(usage.snapshot().attached_ms >= 30);
+
+        clients.store(0, Ordering::Release);
+        usage.sync_attached(&clients);
+        let stopped = usage.snapshot().attached_ms;
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(usage.snapshot().attached_ms, stopped);
+    }
+
+    #[test]
+    fn unattached_for_tracks_last_detach() {
+        let usage = UsageCounters::new();
+        let clients = AtomicUsize::new(0);
+        assert_eq!(usage.unattached_for(), Some(Duration::MAX));
+
+        clients.store(1, Ordering::Release);
+        usage.sync_attached(&clients);
+        assert_eq!(usage.unattached_for(), None);
+
+        clients.store(0, Ordering::Release);
+        usage.sync_attached(&clients);
+        assert!(usage.unattached_for().unwrap() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn counts_output_and_mcp_calls() {
+        let usage = UsageCounters::new();
+        usage.record_output(100);
+        usage.record_output(28);
+        usage.record_mcp_call();
+        let u = usage.snapshot();
+        assert_eq!(u.output_bytes, 128);
+        assert_eq!(u.mcp_tool_calls, 1);
+    }
+}
@@ -15,7 +15,7 @@ use futures::{SinkExt, StreamExt};
 use std::net::SocketAddr;
 use std::time::Duration;
 use tokio::net::TcpListener;
-use tokio::sync::{broadcast, mpsc};
+use tokio::sync::mpsc;
 use tokio_tungstenite::{connect_async, tungstenite::Message};
 use tower::ServiceExt;
 use wsh::api::{router, AppState, RouterConfig};
@@ -26,22 +26,46 @@ use wsh::parser::Parser;
 use wsh::session::{Session, SessionRegistry};
 use wsh::shutdown::ShutdownCoordinator;
 
+mod common;
+
 /// Creates a test application with channels for input/output.
 /// Returns the router, input receiver, and output sender for test verification.
-fn create_test_app() -> (axum::Router, mpsc::Receiver<Bytes>, broadcast::Sender<Bytes>) {
+fn create_test_app() -> (axum::Router, mpsc::Receiver<Bytes>, Broker) {
     let (input_tx, input_rx) = mpsc::channel(64);
     let broker = Broker::new();
     let (_parser_tx, parser_rx) = mpsc::channel(256);
     let parser = Parser::spawn(parser_rx, 80, 24, 1000);
     let session = Session {
         name: "test".to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx,
-        output_rx: broker.sender(),
+        output_rx: broker.clone(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -66,8 +90,11 @@ fn create_test_app() -> (axum::Router, mpsc::Receiver<Bytes>, broadcast::Sender<
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
             backends: wsh::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -75,7 +102,7 @@ fn create_test_app() -> (axum::Router, mpsc::Receiver<Bytes>, broadcast::Sender<
             default_backend_token: None,
             server_id: "test-server-id".to_string(),
     };
-    (router(state, RouterConfig::default()), input_rx, broker.sender())
+    (router(state, RouterConfig::default()), input_rx, broker.clone())
 }
 
 /// Starts the server on a random available port and returns the address.
@@ -148,13 +175,35 @@ async fn test_api_input_multiple_requests() {
     let parser = Parser::spawn(parser_rx, 80, 24, 1000);
     let session = Session {
         name: "test".to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx,
-        output_rx: broker.sender(),
+        output_rx: broker.clone(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -172,7 +221,7 @@ async fn test_api_input_multiple_requests() {
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
-    let state = AppState { sessions: registry, shutdown: ShutdownCoordinator::new(), server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)), server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()), backends: wsh::federation::registry::BackendRegistry::new(), federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())), ip_access: None, hostname: "test".to_string(), federation_config_path: None, local_token: None, default_backend_token: None, server_id: "test-server-id".to_string() };
+    let state = AppState { sessions: registry, shutdown: ShutdownCoordinator::new(), server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)), server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()), tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()), backends: wsh::federation::registry::BackendRegistry::new(), #[cfg(feature = "federation")] federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())), #[cfg(feature = "federation")] ip_access: None, hostname: "test".to_string(), federation_config_path: None, local_token: None, default_backend_token: None, server_id: "test-server-id".to_string() };
     let app = router(state, RouterConfig::default());
 
     let inputs = vec!["first input", "second input", "third input"];
@@ -238,16 +287,38 @@ async fn test_websocket_upgrade_response() {
 async fn test_websocket_receives_pty_output() {
     let (input_tx, _input_rx) = mpsc::channel(64);
     let broker = Broker::new();
-    let output_tx = broker.sender();
+    let output_tx = broker.clone();
     let (_parser_tx, parser_rx) = mpsc::channel(256);
     let parser = Parser::spawn(parser_rx, 80, 24, 1000);
     let session = Session {
         name: "test".to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx,
         output_rx: output_tx.clone(),
         shutdown: ShutdownCoordinator::new(),
@@ -267,7 +338,7 @@ async fn test_websocket_receives_pty_output() {
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
-    let state = AppState { sessions: registry, shutdown: ShutdownCoordinator::new(), server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)), server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()), backends: wsh::federation::registry::BackendRegistry::new(), federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())), ip_access: None, hostname: "test".to_string(), federation_config_path: None, local_token: None, default_backend_token: None, server_id: "test-server-id".to_string() };
+    let state = AppState { sessions: registry, shutdown: ShutdownCoordinator::new(), server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)), server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()), tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()), backends: wsh::federation::registry::BackendRegistry::new(), #[cfg(feature = "federation")] federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())), #[cfg(feature = "federation")] ip_access: None, hostname: "test".to_string(), federation_config_path: None, local_token: None, default_backend_token: None, server_id: "test-server-id".to_string() };
     let app = router(state, RouterConfig::default());
 
     let addr = start_test_server(app).await;
@@ -283,9 +354,7 @@ async fn test_websocket_receives_pty_output() {
 
     // Simulate PTY output by publishing to the broadcast channel
     let test_output = Bytes::from("PTY output test data");
-    output_tx
-        .send(test_output.clone())
-        .expect("Failed to send to broadcast channel");
+    output_tx.publish(&test_output);
 
     // Receive the message on the WebSocket
     let received = tokio::time::timeout(Duration::from_secs(2), ws_stream.next())
@@ -310,13 +379,35 @@ async fn test_websocket_sends_input_to_pty() {
     let parser = Parser::spawn(parser_rx, 80, 24, 1000);
     let session = Session {
         name: "test".to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx,
-        output_rx: broker.sender(),
+        output_rx: broker.clone(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -334,7 +425,7 @@ async fn test_websocket_sends_input_to_pty() {
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
-    let state = AppState { sessions: registry, shutdown: ShutdownCoordinator::new(), server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)), server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()), backends: wsh::federation::registry::BackendRegistry::new(), federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())), ip_access: None, hostname: "test".to_string(), federation_config_path: None, local_token: None, default_backend_token: None, server_id: "test-server-id".to_string() };
+    let state = AppState { sessions: registry, shutdown: ShutdownCoordinator::new(), server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)), server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()), tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()), backends: wsh::federation::registry::BackendRegistry::new(), #[cfg(feature = "federation")] federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())), #[cfg(feature = "federation")] ip_access: None, hostname: "test".to_string(), federation_config_path: None, local_token: None, default_backend_token: None, server_id: "test-server-id".to_string() };
     let app = router(state, RouterConfig::default());
 
     let addr = start_test_server(app).await;
@@ -373,13 +464,35 @@ async fn test_websocket_text_input_to_pty() {
     let parser = Parser::spawn(parser_rx, 80, 24, 1000);
     let session = Session {
         name: "test".to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx,
-        output_rx: broker.sender(),
+        output_rx: broker.clone(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -397,7 +510,7 @@ async fn test_websocket_text_input_to_pty() {
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
-    let state = AppState { sessions: registry, shutdown: ShutdownCoordinator::new(), server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)), server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()), backends: wsh::federation::registry::BackendRegistry::new(), federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())), ip_access: None, hostname: "test".to_string(), federation_config_path: None, local_token: None, default_backend_token: None, server_id: "test-server-id".to_string() };
+    let state = AppState { sessions: registry, shutdown: ShutdownCoordinator::new(), server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)), server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()), tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()), backends: wsh::federation::registry::BackendRegistry::new(), #[cfg(feature = "federation")] federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())), #[cfg(feature = "federation")] ip_access: None, hostname: "test".to_string(), federation_config_path: None, local_token: None, default_backend_token: None, server_id: "test-server-id".to_string() };
     let app = router(state, RouterConfig::default());
 
     let addr = start_test_server(app).await;
@@ -430,16 +543,38 @@ async fn test_websocket_bidirectional_communication() {
     // Test that WebSocket can both send and receive simultaneously
     let (input_tx, mut input_rx) = mpsc::channel(64);
     let broker = Broker::new();
-    let output_tx = broker.sender();
+    let output_tx = broker.clone();
     let (_parser_tx, parser_rx) = mpsc::channel(256);
     let parser = Parser::spawn(parser_rx, 80, 24, 1000);
     let session = Session {
         name: "test".to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx,
         output_rx: output_tx.clone(),
         shutdown: ShutdownCoordinator::new(),
@@ -459,7 +594,7 @@ async fn test_websocket_bidirectional_communication() {
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
-    let state = AppState { sessions: registry, shutdown: ShutdownCoordinator::new(), server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)), server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()), backends: wsh::federation::registry::BackendRegistry::new(), federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())), ip_access: None, hostname: "test".to_string(), federation_config_path: None, local_token: None, default_backend_token: None, server_id: "test-server-id".to_string() };
+    let state = AppState { sessions: registry, shutdown: ShutdownCoordinator::new(), server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)), server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()), tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()), backends: wsh::federation::registry::BackendRegistry::new(), #[cfg(feature = "federation")] federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())), #[cfg(feature = "federation")] ip_access: None, hostname: "test".to_string(), federation_config_path: None, local_token: None, default_backend_token: None, server_id: "test-server-id".to_string() };
     let app = router(state, RouterConfig::default());
 
     let addr = start_test_server(app).await;
@@ -480,9 +615,7 @@ async fn test_websocket_bidirectional_communication() {
 
     // Simulate PTY output
     let test_output = Bytes::from("bidirectional output");
-    output_tx
-        .send(test_output.clone())
-        .expect("Failed to send broadcast");
+    output_tx.publish(&test_output);
 
     // Verify input was received on the channel
     let received_input = tokio::time::timeout(Duration::from_secs(2), input_rx.recv())
@@ -511,16 +644,38 @@ async fn test_websocket_multiple_outputs() {
     // Test that multiple PTY outputs are all received by WebSocket
     let (input_tx, _input_rx) = mpsc::channel(64);
     let broker = Broker::new();
-    let output_tx = broker.sender();
+    let output_tx = broker.clone();
     let (_parser_tx, parser_rx) = mpsc::channel(256);
     let parser = Parser::spawn(parser_rx, 80, 24, 1000);
     let session = Session {
         name: "test".to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx,
         output_rx: output_tx.clone(),
         shutdown: ShutdownCoordinator::new(),
@@ -540,7 +695,7 @@ async fn test_websocket_multiple_outputs() {
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
-    let state = AppState { sessions: registry, shutdown: ShutdownCoordinator::new(), server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)), server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()), backends: wsh::federation::registry::BackendRegistry::new(), federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())), ip_access: None, hostname: "test".to_string(), federation_config_path: None, local_token: None, default_backend_token: None, server_id: "test-server-id".to_string() };
+    let state = AppState { sessions: registry, shutdown: ShutdownCoordinator::new(), server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)), server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()), tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()), backends: wsh::federation::registry::BackendRegistry::new(), #[cfg(feature = "federation")] federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())), #[cfg(feature = "federation")] ip_access: None, hostname: "test".to_string(), federation_config_path: None, local_token: None, default_backend_token: None, server_id: "test-server-id".to_string() };
     let app = router(state, RouterConfig::default());
 
     let addr = start_test_server(app).await;
@@ -560,24 +715,250 @@ async fn test_websocket_multiple_outputs() {
     ];
 
     for output in &outputs {
-        output_tx.send(output.clone()).expect("Failed to send");
+        output_tx.publish(output);
     }
 
-    // Receive all outputs
-    for expected in outputs {
-        let received = tokio::time::timeout(Duration::from_secs(2), ws_stream.next())
+    // Receive all outputs, in order. Output that queues up while the
+    // client is busy may arrive coalesced into fewer frames.
+    let expected: Vec<u8> = outputs.concat();
+    let mut received = Vec::new();
+    while received.len() < expected.len() {
+        let message = tokio::time::timeout(Duration::from_secs(2), ws_stream.next())
             .await
             .expect("timed out waiting for WebSocket message")
             .expect("WebSocket stream ended")
             .expect("WebSocket error");
 
-        match received {
-            Message::Binary(data) => {
-                assert_eq!(data.to_vec(), expected.to_vec());
-            }
+        match message {
+            Message::Binary(data) => received.extend_from_slice(&data),
             other => panic!("Expected binary message, got: {:?}", other),
         }
     }
+    assert_eq!(received, expected);
+}
+
+#[tokio::test]
+async fn test_websocket_text_subprotocol_sends_rendered_screen() {
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+    let (state, _input_rx, output_tx, parser_tx) = common::create_test_state();
+    let addr = start_test_server(router(state, RouterConfig::default())).await;
+
+    let mut request = format!("ws://{}/sessions/test/ws/raw", addr)
+        .into_client_request()
+        .unwrap();
+    request
+        .headers_mut()
+        .insert("sec-websocket-protocol", "wsh-text".parse().unwrap());
+    let (mut ws_stream, response) = connect_async(request)
+        .await
+        .expect("Failed to connect WebSocket");
+    assert_eq!(
+        response.headers().get("sec-websocket-protocol").unwrap(),
+        "wsh-text"
+    );
+
+    // Initial snapshot of the (empty) screen.
+    let first = tokio::time::timeout(Duration::from_secs(2), ws_stream.next())
+        .await
+        .expect("timed out waiting for initial snapshot")
+        .unwrap()
+        .unwrap();
+    assert_eq!(first, Message::Text("".into()));
+
+    // Feed the parser and signal output; the client should receive the
+    // rendered text, not the escape sequences.
+    let data = Bytes::from("\x1b[1;31mhello\x1b[0m\r\nworld");
+    parser_tx.send(data.clone()).await.unwrap();
+    output_tx.publish(&data);
+
+    let received = tokio::time::timeout(Duration::from_secs(2), ws_stream.next())
+        .await
+        .expect("timed out waiting for rendered text")
+        .unwrap()
+        .unwrap();
+    match received {
+        Message::Text(text) => assert_eq!(text.as_str(), "hello\nworld"),
+        other => panic!("Expected text message, got: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_screen_since_generation_returns_only_changed_rows() {
+    let (state, _input_rx, _output_tx, parser_tx) = common::create_test_state();
+    let app = router(state, RouterConfig::default());
+    let screen = |uri: String| {
+        let app = app.clone();
+        async move {
+            let response = app
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        }
+    };
+
+    parser_tx.send(Bytes::from("one\r\ntwo\r\nthree")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let full = screen("/sessions/test/screen?format=plain".into()).await;
+    assert_eq!(full["lines"][1], "two");
+    assert!(full.get("changed_rows").is_none());
+    let generation = full["generation"].as_u64().unwrap();
+
+    parser_tx.send(Bytes::from("\x1b[2;1H\x1b[2KTWO")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let delta = screen(format!(
+        "/sessions/test/screen?format=plain&since_generation={}",
+        generation
+    ))
+    .await;
+    assert_eq!(delta["changed_rows"], serde_json::json!([1]));
+    assert_eq!(delta["lines"], serde_json::json!(["TWO"]));
+    assert!(delta["generation"].as_u64().unwrap() > generation);
+}
+
+#[tokio::test]
+async fn test_inline_images_are_listed_placed_and_downloadable() {
+    use base64::Engine;
+    let (state, _input_rx, _output_tx, parser_tx) = common::create_test_state();
+    let app = router(state, RouterConfig::default());
+    let get = |uri: &'static str| {
+        let app = app.clone();
+        async move {
+            app.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap()
+        }
+    };
+
+    let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x28\0\0\0\x14";
+    let data = base64::engine::general_purpose::STANDARD.encode(png);
+    let name = base64::engine::general_purpose::STANDARD.encode("plot.png");
+    parser_tx
+        .send(Bytes::from(format!(
+            "$ plot\r\n\x1b]1337;File=name={};inline=1:{}\x07\r\n$ ",
+            name, data
+        )))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let response = get("/sessions/test/images").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let list: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let image = &list["images"][0];
+    assert_eq!(image["protocol"], "iterm2");
+    assert_eq!(image["name"], "plot.png");
+    assert_eq!(image["content_type"], "image/png");
+    assert_eq!((image["rows"].as_u64(), image["cols"].as_u64()), (Some(2), Some(5)));
+
+    let response = get("/sessions/test/screen?format=plain").await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let screen: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(screen["images"][0]["id"], image["id"]);
+    assert_eq!(screen["images"][0]["row"], 1);
+    assert_eq!(screen["lines"][3], "$");
+
+    let response = get("/sessions/test/images/1").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "image/png");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(&body[..], png);
+
+    let response = get("/sessions/test/images/9").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_modes_endpoint_reports_negotiated_modes() {
+    let (state, _input_rx, _output_tx, parser_tx) = common::create_test_state();
+    let app = router(state, RouterConfig::default());
+
+    parser_tx
+        .send(Bytes::from("\x1b[?1049h\x1b[>1u\x1b[?2004;1004h\x1b[?1003;1006h"))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let response = app
+        .oneshot(Request::builder().uri("/sessions/test/modes").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let modes: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        modes,
+        serde_json::json!({
+            "alternate_active": true,
+            "application_cursor": false,
+            "application_keypad": false,
+            "kitty_flags": 1,
+            "modify_other_keys": 0,
+            "bracketed_paste": true,
+            "mouse_tracking": "any",
+            "mouse_encoding": "sgr",
+            "focus_events": true,
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_websocket_framed_subprotocol_carries_size_output_and_exit() {
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+    use wsh::api::raw_frames::Frame;
+
+    let (state, _input_rx, output_tx, _parser_tx) = common::create_test_state();
+    let sessions = state.sessions.clone();
+    let addr = start_test_server(router(state, RouterConfig::default())).await;
+
+    let mut request = format!("ws://{}/sessions/test/ws/raw", addr)
+        .into_client_request()
+        .unwrap();
+    request
+        .headers_mut()
+        .insert("sec-websocket-protocol", "wsh-framed".parse().unwrap());
+    let (mut ws_stream, response) = connect_async(request)
+        .await
+        .expect("Failed to connect WebSocket");
+    assert_eq!(
+        response.headers().get("sec-websocket-protocol").unwrap(),
+        "wsh-framed"
+    );
+
+    macro_rules! next_frame {
+        () => {
+            match tokio::time::timeout(Duration::from_secs(2), ws_stream.next())
+                .await
+                .expect("timed out waiting for frame")
+                .unwrap()
+                .unwrap()
+            {
+                Message::Binary(data) => {
+                    let mut frames = Frame::decode_all(&data).unwrap();
+                    assert_eq!(frames.len(), 1);
+                    frames.remove(0)
+                }
+                other => panic!("Expected binary message, got: {:?}", other),
+            }
+        };
+    }
+
+    // The current size comes first.
+    assert_eq!(next_frame!(), Frame::Resize { rows: 24, cols: 80 });
+
+    output_tx.publish(b"hello");
+    assert_eq!(next_frame!(), Frame::Data(Bytes::from("hello")));
+
+    let session = sessions.get("test").unwrap();
+    session.terminal_size.set(40, 120);
+    assert_eq!(next_frame!(), Frame::Resize { rows: 40, cols: 120 });
+
+    sessions.remove("test");
+    assert_eq!(next_frame!(), Frame::Exit("killed".to_string()));
 }
 
 #[tokio::test]
@@ -598,6 +979,7 @@ async fn test_nonexistent_route_returns_404() {
     assert_eq!(response.status(), StatusCode::NOT_FOUND);
 }
 
+#[cfg(feature = "web-ui")]
 #[tokio::test]
 async fn test_web_ui_served_under_ui_prefix() {
     let (app, _input_rx, _output_tx) = create_test_app();
@@ -681,13 +1063,35 @@ async fn test_websocket_line_event_includes_total_lines() {
     let parser = Parser::spawn(parser_rx, 80, 24, 1000);
     let session = Session {
         name: "test".to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx,
-        output_rx: broker.sender(),
+        output_rx: broker.clone(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -705,7 +1109,7 @@ async fn test_websocket_line_event_includes_total_lines() {
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
-    let state = AppState { sessions: registry, shutdown: ShutdownCoordinator::new(), server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)), server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()), backends: wsh::federation::registry::BackendRegistry::new(), federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())), ip_access: None, hostname: "test".to_string(), federation_config_path: None, local_token: None, default_backend_token: None, server_id: "test-server-id".to_string() };
+    let state = AppState { sessions: registry, shutdown: ShutdownCoordinator::new(), server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)), server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()), tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()), backends: wsh::federation::registry::BackendRegistry::new(), #[cfg(feature = "federation")] federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())), #[cfg(feature = "federation")] ip_access: None, hostname: "test".to_string(), federation_config_path: None, local_token: None, default_backend_token: None, server_id: "test-server-id".to_string() };
     let app = router(state, RouterConfig::default());
 
     let addr = start_test_server(app).await;
@@ -735,7 +1139,7 @@ async fn test_websocket_line_event_includes_total_lines() {
 
     // Send text to parser channel and broadcast to reach both parser and subscribers
     _parser_tx.send(bytes::Bytes::from("Hello test\r\n")).await.unwrap();
-    broker.publish(bytes::Bytes::from("Hello test\r\n"));
+    broker.publish(b"Hello test\r\n");
 
     // Look for a line event with total_lines field
     let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
@@ -773,13 +1177,35 @@ async fn test_scrollback_endpoint() {
     let parser = Parser::spawn(parser_rx, 80, 5, 1000); // 5-row screen to get scrollback quickly
     let session = Session {
         name: "test".to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx,
-        output_rx: broker.sender(),
+        output_rx: broker.clone(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -797,7 +1223,7 @@ async fn test_scrollback_endpoint() {
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
-    let state = AppState { sessions: registry, shutdown: ShutdownCoordinator::new(), server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)), server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()), backends: wsh::federation::registry::BackendRegistry::new(), federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())), ip_access: None, hostname: "test".to_string(), federation_config_path: None, local_token: None, default_backend_token: None, server_id: "test-server-id".to_string() };
+    let state = AppState { sessions: registry, shutdown: ShutdownCoordinator::new(), server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)), server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()), tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()), backends: wsh::federation::registry::BackendRegistry::new(), #[cfg(feature = "federation")] federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())), #[cfg(feature = "federation")] ip_access: None, hostname: "test".to_string(), federation_config_path: None, local_token: None, default_backend_token: None, server_id: "test-server-id".to_string() };
     let app = router(state, RouterConfig::default());
 
     // Send enough lines to create scrollback (more than 5 rows)
@@ -835,6 +1261,279 @@ async fn test_scrollback_endpoint() {
     assert!(lines > 0, "Expected lines.len > 0, got {}", lines);
 }
 
+#[tokio::test]
+async fn test_scrollback_since_bookmark() {
+    let (input_tx, _input_rx) = mpsc::channel(64);
+    let broker = Broker::new();
+    let (_parser_tx, parser_rx) = mpsc::channel(256);
+    let parser = Parser::spawn(parser_rx, 80, 5, 1000); // 5-row screen to get scrollback quickly
+    let session = Session {
+        name: "test".to_string(),
+        pid: Default::default(),
+        command: "test".to_string(),
+        client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+        tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
+        child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
+        input_tx,
+        output_rx: broker.clone(),
+        shutdown: ShutdownCoordinator::new(),
+        parser,
+        overlays: OverlayStore::new(),
+        input_mode: InputMode::new(),
+        input_broadcaster: InputBroadcaster::new(),
+        panels: wsh::panel::PanelStore::new(),
+        pty: std::sync::Arc::new(parking_lot::Mutex::new(wsh::pty::Pty::spawn(5, 80, wsh::pty::SpawnCommand::default()).expect("failed to spawn PTY for test"))),
+        terminal_size: wsh::terminal::TerminalSize::new(5, 80),
+        activity: wsh::activity::ActivityTracker::new(),
+        focus: FocusTracker::new(),
+        detach_signal: tokio::sync::broadcast::channel::<()>(1).0,
+        visual_update_tx: tokio::sync::broadcast::channel::<wsh::protocol::VisualUpdate>(16).0,
+        screen_mode: std::sync::Arc::new(parking_lot::RwLock::new(wsh::overlay::ScreenMode::Normal)),
+        cancelled: tokio_util::sync::CancellationToken::new(),
+    };
+    let registry = SessionRegistry::new();
+    registry.insert(Some("test".into()), session).unwrap();
+    let state = AppState { sessions: registry, shutdown: ShutdownCoordinator::new(), server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)), server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()), tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()), backends: wsh::federation::registry::BackendRegistry::new(), #[cfg(feature = "federation")] federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())), #[cfg(feature = "federation")] ip_access: None, hostname: "test".to_string(), federation_config_path: None, local_token: None, default_backend_token: None, server_id: "test-server-id".to_string() };
+    let app = router(state, RouterConfig::default());
+
+    async fn send(app: &axum::Router, method: &str, uri: &str, body: Option<serde_json::Value>) -> (StatusCode, serde_json::Value) {
+        let mut req = Request::builder().method(method).uri(uri);
+        let body = match body {
+            Some(body) => {
+                req = req.header("content-type", "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        let response = app.clone().oneshot(req.body(body).unwrap()).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+    }
+
+    _parser_tx.send(bytes::Bytes::from("$ make\r\n")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let (status, json) = send(&app, "POST", "/sessions/test/bookmarks", Some(serde_json::json!({"name": "build-start", "note": "make"}))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(json["name"], "build-start");
+    assert_eq!(json["offset"], 1);
+
+    for i in 0..3 {
+        _parser_tx.send(bytes::Bytes::from(format!("step {}\r\n", i))).await.unwrap();
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let (status, json) = send(&app, "GET", "/sessions/test/scrollback?format=plain&since_bookmark=build-start&limit=3", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["lines"], serde_json::json!(["step 0", "step 1", "step 2"]));
+
+    let (status, json) = send(&app, "GET", "/sessions/test/bookmarks", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["bookmarks"][0]["note"], "make");
+
+    let (status, json) = send(&app, "POST", "/sessions/test/bookmarks", Some(serde_json::json!({"name": "no spaces"}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(json["error"]["code"], "invalid_request");
+
+    let (status, _) = send(&app, "DELETE", "/sessions/test/bookmarks/build-start", None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, json) = send(&app, "GET", "/sessions/test/scrollback?since_bookmark=build-start", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(json["error"]["code"], "bookmark_not_found");
+}
+
+#[tokio::test]
+async fn test_broadcast_input_reaches_every_tagged_session() {
+    let (state, mut untagged_rx, _output_tx, _parser_tx) = common::create_test_state();
+    let mut workers = Vec::new();
+    // A session may be named "input" even though /sessions/input broadcasts.
+    for name in ["w1", "input"] {
+        let worker = common::create_test_session(name);
+        worker.session.tags.write().insert("workers".to_string());
+        state.sessions.insert(Some(name.into()), worker.session.clone()).unwrap();
+        workers.push(worker);
+    }
+    let app = router(state, RouterConfig::default());
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/sessions/input?tag=workers")
+                .body(Body::from("make\n"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["sent"], 2);
+    assert_eq!(json["failed"], 0);
+    assert_eq!(
+        json["results"],
+        serde_json::json!([{"session": "input", "sent": true}, {"session": "w1", "sent": true}])
+    );
+    for worker in &mut workers {
+        assert_eq!(worker.input_rx.try_recv().unwrap(), Bytes::from("make\n"));
+    }
+    assert!(untagged_rx.try_recv().is_err());
+
+    let response = app
+        .clone()
+        .oneshot(Request::builder().uri("/sessions/input").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .oneshot(Request::builder().method("POST").uri("/sessions/input").body(Body::from("x")).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_pipe_tees_plain_output_into_another_session() {
+    let (state, _input_rx, output_tx, _parser_tx) = common::create_test_state();
+    let mut sink = common::create_test_session("sink");
+    state.sessions.insert(Some("sink".into()), sink.session.clone()).unwrap();
+    let app = router(state, RouterConfig::default());
+
+    let request = |method: &str, uri: &str, body: Option<serde_json::Value>| {
+        let mut req = Request::builder().method(method).uri(uri);
+        if body.is_some() {
+            req = req.header("content-type", "application/json");
+        }
+        let body = body.map_or_else(Body::empty, |b| Body::from(b.to_string()));
+        let app = app.clone();
+        let req = req.body(body).unwrap();
+        async move {
+            let response = app.oneshot(req).await.unwrap();
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice::<serde_json::Value>(&body).unwrap_or_default())
+        }
+    };
+
+    let (status, json) = request("POST", "/sessions/test/pipe", Some(serde_json::json!({"session": "sink", "filter": "plain"}))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(json["target"], serde_json::json!({"kind": "session", "session": "sink"}));
+    let id = json["id"].as_u64().unwrap();
+
+    output_tx.publish(b"\x1b[1;31mFAILED\x1b[0m test_a\r\n");
+    let piped = tokio::time::timeout(Duration::from_secs(2), sink.input_rx.recv())
+        .await
+        .expect("timed out waiting for piped input")
+        .unwrap();
+    assert_eq!(piped, Bytes::from("FAILED test_a\n"));
+
+    let (status, json) = request("GET", "/sessions/test/pipe", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["pipes"][0]["bytes"], 14);
+
+    let (status, json) = request("POST", "/sessions/test/pipe", Some(serde_json::json!({"session": "test"}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(json["error"]["code"], "invalid_request");
+
+    let (status, _) = request("DELETE", &format!("/sessions/test/pipe/{}", id), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, json) = request("DELETE", &format!("/sessions/test/pipe/{}", id), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(json["error"]["code"], "pipe_not_found");
+}
+
+#[tokio::test]
+async fn test_pipe_into_command_closes_its_stdin_when_deleted() {
+    let (state, _input_rx, output_tx, _parser_tx) = common::create_test_state();
+    let app = router(state, RouterConfig::default());
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out.log");
+
+    let body = serde_json::json!({"command": format!("cat > {}", out.display()), "filter": "strip_ansi"});
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/sessions/test/pipe")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let id = serde_json::from_slice::<serde_json::Value>(&body).unwrap()["id"].as_u64().unwrap();
+
+    output_tx.publish(b"\x1b[32mok\x1b[0m\r\n");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("DELETE")
+                .uri(format!("/sessions/test/pipe/{}", id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    // cat writes what it read and exits once its stdin is closed.
+    for _ in 0..50 {
+        if std::fs::read(&out).unwrap_or_default() == b"ok\r\n" {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("command output was {:?}", std::fs::read_to_string(&out));
+}
+
+#[tokio::test]
+async fn test_output_rejects_malformed_cursor() {
+    let (app, _input_rx, _output_tx) = create_test_app();
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/sessions/test/output?since=abc")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"]["code"], "invalid_request");
+}
+
 /// Test that scrollback initially contains the blank screen
 #[tokio::test]
 async fn test_scrollback_initial_state() {
@@ -844,13 +1543,35 @@ async fn test_scrollback_initial_state() {
     let parser = Parser::spawn(parser_rx, 80, 24, 1000);
     let session = Session {
         name: "test".to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx,
-        output_rx: broker.sender(),
+        output_rx: broker.clone(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -868,7 +1589,7 @@ async fn test_scrollback_initial_state() {
     };
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), session).unwrap();
-    let state = AppState { sessions: registry, shutdown: ShutdownCoordinator::new(), server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)), server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()), backends: wsh::federation::registry::BackendRegistry::new(), federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())), ip_access: None, hostname: "test".to_string(), federation_config_path: None, local_token: None, default_backend_token: None, server_id: "test-server-id".to_string() };
+    let state = AppState { sessions: registry, shutdown: ShutdownCoordinator::new(), server_config: std::sync::Arc::new(wsh::api::ServerConfig::new(false)), server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)), ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()), tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()), backends: wsh::federation::registry::BackendRegistry::new(), #[cfg(feature = "federation")] federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())), #[cfg(feature = "federation")] ip_access: None, hostname: "test".to_string(), federation_config_path: None, local_token: None, default_backend_token: None, server_id: "test-server-id".to_string() };
     let app = router(state, RouterConfig::default());
 
     // Query immediately without any output
@@ -918,4 +1639,9 @@ async fn server_info_returns_hostname_and_version() {
     assert_eq!(json["hostname"], "test");
     assert!(json["version"].is_string());
     assert_eq!(json["server_id"], "test-server-id");
+    assert!(json["uptime_secs"].is_u64());
+    assert!(json["persistent"].is_boolean());
+    assert!(json["sessions"].is_u64());
+    assert_eq!(json["federation"]["backends"], 0);
+    assert!(json["resources"]["max_rss_bytes"].as_u64().unwrap() > 0);
 }
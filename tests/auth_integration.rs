@@ -134,7 +134,7 @@ async fn test_ws_ticket_exchange() {
     let response = app
         .oneshot(
             Request::builder()
-                .uri(&format!("/sessions/test/ws/json?ticket={}", ticket))
+                .uri(format!("/sessions/test/ws/json?ticket={}", ticket))
                 .header("upgrade", "websocket")
                 .header("connection", "Upgrade")
                 .header("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ==")
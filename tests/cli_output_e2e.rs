@@ -0,0 +1,120 @@
+//! End-to-end tests for `wsh --output json`.
+//!
+//! These tests spawn a real `wsh server` process and check that subcommands
+//! print their results, and their errors, as JSON documents on stdout.
+
+use std::path::Path;
+use std::time::Duration;
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+const HEALTH_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Waits for wsh to be ready by polling the health endpoint.
+async fn wait_for_ready(port: u16) -> Result<(), &'static str> {
+    let url = format!("http://127.0.0.1:{}/health", port);
+    let client = reqwest::Client::new();
+
+    let deadline = tokio::time::Instant::now() + STARTUP_TIMEOUT;
+    while tokio::time::Instant::now() < deadline {
+        if let Ok(resp) = client.get(&url).send().await {
+            if resp.status().is_success() {
+                return Ok(());
+            }
+        }
+        tokio::time::sleep(HEALTH_POLL_INTERVAL).await;
+    }
+    Err("wsh did not become ready in time")
+}
+
+/// Runs `wsh --output json <args> --socket <socket>` and returns its exit
+/// code and parsed stdout.
+fn wsh_json(socket: &Path, args: &[&str]) -> (i32, serde_json::Value) {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_wsh"))
+        .arg("--output")
+        .arg("json")
+        .args(args)
+        .arg("--socket")
+        .arg(socket)
+        .output()
+        .expect("failed to run wsh");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let value = serde_json::from_str(&stdout).unwrap_or_else(|e| {
+        panic!(
+            "wsh {:?} did not print JSON ({}): stdout: {}, stderr: {}",
+            args,
+            e,
+            stdout,
+            String::from_utf8_lossy(&output.stderr)
+        )
+    });
+    (output.status.code().unwrap_or(-1), value)
+}
+
+#[tokio::test]
+async fn test_subcommands_print_json_results_and_errors() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    drop(listener);
+
+    let socket_dir = tempfile::TempDir::new().unwrap();
+    let socket_path = socket_dir.path().join("output-json.sock");
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_wsh"))
+        .arg("server")
+        .arg("--bind")
+        .arg(format!("127.0.0.1:{}", port))
+        .arg("--socket")
+        .arg(&socket_path)
+        .arg("--server-name")
+        .arg("output-json-e2e-test")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .expect("Failed to spawn wsh server");
+    wait_for_ready(port).await.expect("wsh should become ready");
+
+    let resp = reqwest::Client::new()
+        .post(format!("http://127.0.0.1:{}/sessions", port))
+        .json(&serde_json::json!({"name": "json"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let (code, list) = wsh_json(&socket_path, &["list"]);
+    assert_eq!(code, 0);
+    assert_eq!(list[0]["name"], "json");
+
+    let (code, tags) = wsh_json(&socket_path, &["tag", "json", "--add", "ci"]);
+    assert_eq!(code, 0);
+    assert_eq!(tags, serde_json::json!({"name": "json", "tags": ["ci"]}));
+
+    let (code, persist) = wsh_json(&socket_path, &["persist"]);
+    assert_eq!(code, 0);
+    assert_eq!(persist["persistent"], true);
+
+    let (code, err) = wsh_json(&socket_path, &["kill", "nope"]);
+    assert_eq!(code, 1);
+    assert_eq!(err["exit_code"], 1);
+    assert_eq!(err["error"]["command"], "kill");
+    assert_eq!(err["error"]["code"], "session_not_found");
+
+    let (code, err) = wsh_json(&socket_path, &["kill", "json", "--signal", "BOGUS"]);
+    assert_eq!(code, 1);
+    assert_eq!(err["error"]["code"], "invalid_request");
+
+    let (code, killed) = wsh_json(&socket_path, &["kill", "json", "--signal", "TERM", "--grace", "1s"]);
+    assert_eq!(code, 0);
+    assert_eq!(killed["killed"], true);
+
+    let (code, stopped) = wsh_json(&socket_path, &["stop"]);
+    assert_eq!(code, 0);
+    assert_eq!(stopped["stopped"], true);
+
+    let (code, err) = wsh_json(&socket_path, &["list"]);
+    assert_eq!(code, 1);
+    assert_eq!(err["error"]["code"], "server_unreachable");
+
+    child.kill().ok();
+    child.wait().ok();
+}
@@ -2,7 +2,7 @@
 
 use bytes::Bytes;
 use std::sync::Arc;
-use tokio::sync::{broadcast, mpsc};
+use tokio::sync::mpsc;
 use wsh::activity::ActivityTracker;
 use wsh::broker::Broker;
 use wsh::input::{FocusTracker, InputBroadcaster, InputMode};
@@ -36,13 +36,35 @@ pub fn create_test_session_with_size(name: &str, rows: u16, cols: u16) -> TestSe
     let parser = Parser::spawn(parser_rx, cols as usize, rows as usize, 1000);
     let session = Session {
         name: name.to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx,
-        output_rx: broker.sender(),
+        output_rx: broker.clone(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -70,14 +92,14 @@ pub fn create_test_session_with_size(name: &str, rows: u16, cols: u16) -> TestSe
 }
 
 /// Create a test AppState with a single "test" session.
-pub fn create_test_state() -> (wsh::api::AppState, mpsc::Receiver<Bytes>, broadcast::Sender<Bytes>, mpsc::Sender<Bytes>) {
+pub fn create_test_state() -> (wsh::api::AppState, mpsc::Receiver<Bytes>, Broker, mpsc::Sender<Bytes>) {
     create_test_state_with_size(24, 80)
 }
 
 /// Create a test AppState with a single "test" session of custom dimensions.
-pub fn create_test_state_with_size(rows: u16, cols: u16) -> (wsh::api::AppState, mpsc::Receiver<Bytes>, broadcast::Sender<Bytes>, mpsc::Sender<Bytes>) {
+pub fn create_test_state_with_size(rows: u16, cols: u16) -> (wsh::api::AppState, mpsc::Receiver<Bytes>, Broker, mpsc::Sender<Bytes>) {
     let ts = create_test_session_with_size("test", rows, cols);
-    let output_tx = ts.broker.sender();
+    let output_tx = ts.broker.clone();
     let parser_tx = ts.parser_tx;
     let registry = SessionRegistry::new();
     registry.insert(Some("test".into()), ts.session).unwrap();
@@ -88,8 +110,11 @@ pub fn create_test_state_with_size(rows: u16, cols: u16) -> (wsh::api::AppState,
         server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+        tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
         backends: wsh::federation::registry::BackendRegistry::new(),
+        #[cfg(feature = "federation")]
         federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())),
+        #[cfg(feature = "federation")]
         ip_access: None,
         hostname: "test".to_string(),
         federation_config_path: None,
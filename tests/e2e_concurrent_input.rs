@@ -46,7 +46,7 @@ async fn test_concurrent_input_from_multiple_sources() {
             match pty_reader.read(&mut buf) {
                 Ok(0) => break,
                 Ok(n) => {
-                    broker_clone.publish(Bytes::copy_from_slice(&buf[..n]));
+                    broker_clone.publish(&buf[..n]);
                 }
                 Err(e) => {
                     if e.raw_os_error() != Some(5) {
@@ -69,13 +69,35 @@ async fn test_concurrent_input_from_multiple_sources() {
     let parser = Parser::spawn(parser_rx, 80, 24, 1000);
     let session = Session {
         name: "test".to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx: input_tx.clone(),
-        output_rx: broker.sender(),
+        output_rx: broker.clone(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -100,8 +122,11 @@ async fn test_concurrent_input_from_multiple_sources() {
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
             backends: wsh::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -223,7 +248,7 @@ async fn test_rapid_http_requests() {
         while !stop_flag_reader.load(Ordering::Relaxed) {
             match pty_reader.read(&mut buf) {
                 Ok(0) => break,
-                Ok(n) => broker_clone.publish(Bytes::copy_from_slice(&buf[..n])),
+                Ok(n) => broker_clone.publish(&buf[..n]),
                 Err(e) => {
                     if e.raw_os_error() != Some(5) {
                         eprintln!("Read error: {:?}", e);
@@ -245,13 +270,35 @@ async fn test_rapid_http_requests() {
     let parser = Parser::spawn(parser_rx, 80, 24, 1000);
     let session = Session {
         name: "test".to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx: input_tx.clone(),
-        output_rx: broker.sender(),
+        output_rx: broker.clone(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -276,8 +323,11 @@ async fn test_rapid_http_requests() {
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
             backends: wsh::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
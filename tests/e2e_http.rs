@@ -49,7 +49,7 @@ async fn test_http_post_input_reaches_pty_and_produces_output() {
             match pty_reader.read(&mut buf) {
                 Ok(0) => break,
                 Ok(n) => {
-                    broker_clone.publish(Bytes::copy_from_slice(&buf[..n]));
+                    broker_clone.publish(&buf[..n]);
                 }
                 Err(e) => {
                     // EIO (5) is expected when PTY closes
@@ -77,13 +77,35 @@ async fn test_http_post_input_reaches_pty_and_produces_output() {
     let parser = Parser::spawn(parser_rx, 80, 24, 1000);
     let session = Session {
         name: "test".to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx: input_tx.clone(),
-        output_rx: broker.sender(),
+        output_rx: broker.clone(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -108,8 +130,11 @@ async fn test_http_post_input_reaches_pty_and_produces_output() {
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
             backends: wsh::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -220,7 +245,7 @@ async fn test_scrollback_endpoint_with_real_pty() {
             match pty_reader.read(&mut buf) {
                 Ok(0) => break,
                 Ok(n) => {
-                    broker_clone.publish(Bytes::copy_from_slice(&buf[..n]));
+                    broker_clone.publish(&buf[..n]);
                 }
                 Err(e) => {
                     if e.raw_os_error() != Some(5) {
@@ -247,13 +272,35 @@ async fn test_scrollback_endpoint_with_real_pty() {
     let parser = Parser::spawn(parser_rx, 80, 5, 1000); // 80 cols, 5 rows
     let session = Session {
         name: "test".to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx: input_tx.clone(),
-        output_rx: broker.sender(),
+        output_rx: broker.clone(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -278,8 +325,11 @@ async fn test_scrollback_endpoint_with_real_pty() {
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
             backends: wsh::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -36,7 +36,7 @@ async fn test_async_api_input_reaches_pty() {
             match pty_reader.read(&mut buf) {
                 Ok(0) => break,
                 Ok(n) => {
-                    broker_clone.publish(Bytes::copy_from_slice(&buf[..n]));
+                    broker_clone.publish(&buf[..n]);
                 }
                 Err(e) => {
                     if e.raw_os_error() != Some(5) {
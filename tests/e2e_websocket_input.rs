@@ -47,7 +47,7 @@ async fn test_websocket_input_reaches_pty_and_output_returns() {
             match pty_reader.read(&mut buf) {
                 Ok(0) => break,
                 Ok(n) => {
-                    broker_clone.publish(Bytes::copy_from_slice(&buf[..n]));
+                    broker_clone.publish(&buf[..n]);
                 }
                 Err(e) => {
                     if e.raw_os_error() != Some(5) {
@@ -71,13 +71,35 @@ async fn test_websocket_input_reaches_pty_and_output_returns() {
     let parser = Parser::spawn(parser_rx, 80, 24, 1000);
     let session = Session {
         name: "test".to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx: input_tx.clone(),
-        output_rx: broker.sender(),
+        output_rx: broker.clone(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -102,8 +124,11 @@ async fn test_websocket_input_reaches_pty_and_output_returns() {
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
             backends: wsh::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -204,7 +229,7 @@ async fn test_websocket_text_input_reaches_pty() {
             match pty_reader.read(&mut buf) {
                 Ok(0) => break,
                 Ok(n) => {
-                    broker_clone.publish(Bytes::copy_from_slice(&buf[..n]));
+                    broker_clone.publish(&buf[..n]);
                 }
                 Err(e) => {
                     if e.raw_os_error() != Some(5) {
@@ -227,13 +252,35 @@ async fn test_websocket_text_input_reaches_pty() {
     let parser = Parser::spawn(parser_rx, 80, 24, 1000);
     let session = Session {
         name: "test".to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx: input_tx.clone(),
-        output_rx: broker.sender(),
+        output_rx: broker.clone(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -258,8 +305,11 @@ async fn test_websocket_text_input_reaches_pty() {
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
             backends: wsh::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
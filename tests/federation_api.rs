@@ -10,6 +10,8 @@
 //! - Session listing with no ?server= returns local sessions
 //! - Session creation with server=<self> creates locally
 //! - Session creation with server=<unknown> returns 404
+//! - `server:session` names reach sessions on a backend or locally
+#![cfg(feature = "federation")]
 
 use std::net::SocketAddr;
 use std::time::Duration;
@@ -27,6 +29,11 @@ fn create_test_app() -> axum::Router {
 
 /// Creates a test app and returns the shared BackendRegistry for direct manipulation.
 fn create_test_app_with_registry() -> (axum::Router, wsh::federation::registry::BackendRegistry) {
+    create_named_app("test-host")
+}
+
+/// Creates a test app for a server called `hostname`.
+fn create_named_app(hostname: &str) -> (axum::Router, wsh::federation::registry::BackendRegistry) {
     let registry = SessionRegistry::new();
     let federation_manager = wsh::federation::manager::FederationManager::new();
     let backends = federation_manager.registry().clone();
@@ -37,10 +44,11 @@ fn create_test_app_with_registry() -> (axum::Router, wsh::federation::registry::
         server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+        tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
         backends: backends.clone(),
         federation: std::sync::Arc::new(tokio::sync::Mutex::new(federation_manager)),
         ip_access: None,
-        hostname: "test-host".to_string(),
+        hostname: hostname.to_string(),
         federation_config_path: None,
         local_token: None,
         default_backend_token: None,
@@ -665,3 +673,130 @@ async fn no_server_param_always_local() {
         .unwrap();
     assert_eq!(resp.status(), 200);
 }
+
+// ── Test 23: server:session names are proxied transparently ───────
+
+#[tokio::test]
+async fn qualified_session_names_reach_backend_sessions() {
+    use wsh::federation::registry::{BackendEntry, BackendHealth, BackendRole};
+
+    let (backend_app, _) = create_named_app("backend-host");
+    let backend_addr = start_test_server(backend_app).await;
+    let (hub_app, backends) = create_test_app_with_registry();
+    let hub_addr = start_test_server(hub_app).await;
+    backends
+        .add_unchecked(BackendEntry {
+            address: format!("http://{backend_addr}"),
+            token: None,
+            hostname: Some("backend-host".into()),
+            health: BackendHealth::Healthy,
+            role: BackendRole::Member,
+            server_id: None,
+        })
+        .unwrap();
+    let client = reqwest::Client::new();
+
+    for (addr, name) in [(backend_addr, "remote-sess"), (hub_addr, "local-sess")] {
+        let resp = client
+            .post(format!("http://{addr}/sessions"))
+            .json(&serde_json::json!({"name": name}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 201);
+    }
+
+    // Session-scoped endpoints reach the backend's session.
+    let resp = client
+        .get(format!("http://{hub_addr}/sessions/backend-host:remote-sess"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["name"], "remote-sess");
+    assert_eq!(body["server"], "backend-host");
+
+    let resp = client
+        .get(format!("http://{hub_addr}/sessions/backend-host%3Aremote-sess/screen?format=plain"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert!(body["lines"].is_array());
+
+    let resp = client
+        .post(format!("http://{hub_addr}/sessions/backend-host:remote-sess/input"))
+        .body("echo hi\n")
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success(), "input: {}", resp.status());
+
+    let resp = client
+        .get(format!("http://{hub_addr}/sessions/backend-host:missing"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+
+    // The local hostname qualifies local sessions.
+    let resp = client
+        .get(format!("http://{hub_addr}/sessions/test-host:local-sess"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = client
+        .get(format!("http://{hub_addr}/sessions/nowhere:local-sess"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "server_not_found");
+
+    let resp = client
+        .get(format!("http://{hub_addr}/sessions/backend-host:remote-sess/ws/raw"))
+        .header("connection", "upgrade")
+        .header("upgrade", "websocket")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    // Listings aggregate by default and can be limited to this server.
+    let all: Vec<serde_json::Value> = client
+        .get(format!("http://{hub_addr}/sessions?federated=true"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut entries: Vec<(String, String)> = all
+        .iter()
+        .map(|s| (s["server"].as_str().unwrap().to_string(), s["name"].as_str().unwrap().to_string()))
+        .collect();
+    entries.sort();
+    assert_eq!(
+        entries,
+        vec![
+            ("backend-host".to_string(), "remote-sess".to_string()),
+            ("test-host".to_string(), "local-sess".to_string()),
+        ]
+    );
+
+    let local: Vec<serde_json::Value> = client
+        .get(format!("http://{hub_addr}/sessions?federated=false"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(local.len(), 1);
+    assert_eq!(local[0]["name"], "local-sess");
+}
@@ -7,10 +7,12 @@
 //! 2. Verifying both servers are healthy independently
 //! 3. Testing federation management endpoints on the hub
 //! 4. Cross-server session proxy (via in-process test with `add_unchecked`)
+//! 5. A backend joining the hub through the relay with `--join`
 //!
 //! The cross-server proxy test uses an in-process hub because the SSRF
 //! validation correctly blocks registering localhost backends via the HTTP API.
 //! In production, backends run on non-loopback addresses.
+#![cfg(feature = "federation")]
 
 use std::process::{Child, Command, Stdio};
 use std::time::Duration;
@@ -53,6 +55,11 @@ impl Drop for ServerProcess {
 
 /// Spawns a wsh server with the given instance name on a specific port.
 fn spawn_server(instance_name: &str, port: u16) -> ServerProcess {
+    spawn_server_with_args(instance_name, port, &[])
+}
+
+/// Spawns a wsh server as [`spawn_server`] does, with extra arguments.
+fn spawn_server_with_args(instance_name: &str, port: u16, extra: &[&str]) -> ServerProcess {
     let socket_dir = tempfile::TempDir::new().unwrap();
     let socket_path = socket_dir.path().join("test.sock");
 
@@ -68,6 +75,10 @@ fn spawn_server(instance_name: &str, port: u16) -> ServerProcess {
         .arg(instance_name)
         .arg("--hostname")
         .arg(instance_name)
+        .args(extra)
+        // Servers added at runtime are written back to the config file, so
+        // keep each server's default config out of the real one.
+        .env("XDG_CONFIG_HOME", socket_dir.path())
         .stdout(Stdio::null())
         .stderr(Stdio::null())
         .spawn()
@@ -374,6 +385,7 @@ async fn federation_e2e_cross_server_proxy() {
         server_ws_count: Arc::new(AtomicUsize::new(0)),
         mcp_session_count: Arc::new(AtomicUsize::new(0)),
         ticket_store: Arc::new(wsh::api::ticket::TicketStore::new()),
+        tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
         backends: backends.clone(),
         federation: Arc::new(tokio::sync::Mutex::new(federation_manager)),
         ip_access: None,
@@ -550,3 +562,67 @@ async fn federation_e2e_server_hostname() {
     let servers = body.as_array().unwrap();
     assert_eq!(servers[0]["hostname"], "fed-e2e-custom-host");
 }
+
+// ── Test 6: Relay mode ──────────────────────────────────────────────
+
+#[tokio::test]
+async fn federation_e2e_relay_join() {
+    let hub = spawn_server("fed-e2e-relay-hub", find_available_port());
+    wait_for_ready(&hub.addr()).await.expect("hub should be ready");
+    let join = hub.base_url();
+    let backend = spawn_server_with_args(
+        "fed-e2e-relay-backend",
+        find_available_port(),
+        &["--join", &join],
+    );
+    wait_for_ready(&backend.addr())
+        .await
+        .expect("backend should be ready");
+
+    // The backend dials the hub, which registers it and reaches it back
+    // over the relay for its health check.
+    let client = reqwest::Client::new();
+    let deadline = tokio::time::Instant::now() + STARTUP_TIMEOUT;
+    let joined = loop {
+        let body: serde_json::Value = client
+            .get(format!("{}/servers/fed-e2e-relay-backend", hub.base_url()))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if body["health"] == "healthy" {
+            break body;
+        }
+        assert!(tokio::time::Instant::now() < deadline, "backend never joined: {body}");
+        tokio::time::sleep(HEALTH_POLL_INTERVAL).await;
+    };
+    assert!(joined["address"].as_str().unwrap().starts_with("http://127.0.0.1:"));
+
+    // Requests for the backend are proxied through the relay.
+    let resp = client
+        .get(format!("{}/sessions?server=fed-e2e-relay-backend", hub.base_url()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let sessions: serde_json::Value = resp.json().await.unwrap();
+    assert!(sessions.as_array().unwrap().is_empty());
+
+    // When the backend goes away, so does its registration.
+    drop(backend);
+    let deadline = tokio::time::Instant::now() + STARTUP_TIMEOUT;
+    loop {
+        let resp = client
+            .get(format!("{}/servers/fed-e2e-relay-backend", hub.base_url()))
+            .send()
+            .await
+            .unwrap();
+        if resp.status() == 404 {
+            break;
+        }
+        assert!(tokio::time::Instant::now() < deadline, "relayed backend was not removed");
+        tokio::time::sleep(HEALTH_POLL_INTERVAL).await;
+    }
+}
@@ -2,6 +2,7 @@
 //!
 //! Tests self-loop detection (via server UUID), invalid address rejection,
 //! token leak prevention, and invalid hostname rejection.
+#![cfg(feature = "federation")]
 
 use std::net::SocketAddr;
 use std::time::Duration;
@@ -23,6 +24,7 @@ fn create_test_app_with_registry() -> (axum::Router, wsh::federation::registry::
         server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+        tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
         backends: backends.clone(),
         federation: std::sync::Arc::new(tokio::sync::Mutex::new(federation_manager)),
         ip_access: None,
@@ -0,0 +1,134 @@
+//! End-to-end tests for `wsh gc`.
+//!
+//! These tests spawn a real `wsh server` process with a private instance
+//! directory, record it the way an auto-spawning client does, and verify
+//! that `wsh gc` stops it once its socket is gone.
+
+use std::path::Path;
+use std::time::Duration;
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+const HEALTH_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Waits for wsh to be ready by polling the health endpoint.
+async fn wait_for_ready(port: u16) -> Result<(), &'static str> {
+    let url = format!("http://127.0.0.1:{}/health", port);
+    let client = reqwest::Client::new();
+
+    let deadline = tokio::time::Instant::now() + STARTUP_TIMEOUT;
+    while tokio::time::Instant::now() < deadline {
+        if let Ok(resp) = client.get(&url).send().await {
+            if resp.status().is_success() {
+                return Ok(());
+            }
+        }
+        tokio::time::sleep(HEALTH_POLL_INTERVAL).await;
+    }
+    Err("wsh did not become ready in time")
+}
+
+/// Runs `wsh gc` against the instance directory under `runtime_dir`.
+fn run_gc(runtime_dir: &Path) -> String {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_wsh"))
+        .arg("gc")
+        .env("XDG_RUNTIME_DIR", runtime_dir)
+        .output()
+        .expect("failed to run wsh gc");
+    assert!(output.status.success(), "wsh gc failed: {:?}", output);
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[tokio::test]
+async fn test_gc_stops_daemon_whose_socket_is_gone() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    drop(listener);
+
+    let runtime_dir = tempfile::TempDir::new().unwrap();
+    let instance_dir = runtime_dir.path().join("wsh");
+    let socket_path = instance_dir.join("gc-test.sock");
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_wsh"))
+        .arg("server")
+        .arg("--bind")
+        .arg(format!("127.0.0.1:{}", port))
+        .arg("--socket")
+        .arg(&socket_path)
+        .arg("--server-name")
+        .arg("gc-test")
+        .env("XDG_RUNTIME_DIR", runtime_dir.path())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .expect("Failed to spawn wsh server");
+    wait_for_ready(port).await.expect("wsh should become ready");
+
+    let state_path = instance_dir.join("gc-test.daemon.json");
+    std::fs::write(
+        &state_path,
+        serde_json::json!({
+            "pid": child.id(),
+            "instance": "gc-test",
+            "socket": socket_path,
+            "spawned_at": 0,
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    // While it is reachable, gc leaves it alone.
+    assert!(run_gc(runtime_dir.path()).contains("Nothing to clean up."));
+    assert!(child.try_wait().unwrap().is_none());
+
+    // With the socket gone no client can reach or stop it.
+    std::fs::remove_file(&socket_path).unwrap();
+    let out = run_gc(runtime_dir.path());
+    assert!(out.contains("gc-test: pid"), "unexpected output: {}", out);
+    assert!(out.contains("stopped"), "unexpected output: {}", out);
+
+    let status = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Some(status) = child.try_wait().unwrap() {
+                return status;
+            }
+            tokio::time::sleep(HEALTH_POLL_INTERVAL).await;
+        }
+    })
+    .await
+    .expect("server should have exited");
+    assert!(status.success() || status.code().is_none());
+    assert!(!state_path.exists());
+
+    assert!(run_gc(runtime_dir.path()).contains("Nothing to clean up."));
+}
+
+#[tokio::test]
+async fn test_gc_removes_records_of_dead_daemons() {
+    let runtime_dir = tempfile::TempDir::new().unwrap();
+    let instance_dir = runtime_dir.path().join("wsh");
+    std::fs::create_dir_all(&instance_dir).unwrap();
+
+    let mut sleeper = std::process::Command::new("true").spawn().unwrap();
+    let pid = sleeper.id();
+    sleeper.wait().unwrap();
+
+    let state_path = instance_dir.join("gone.daemon.json");
+    std::fs::write(
+        &state_path,
+        serde_json::json!({
+            "pid": pid,
+            "instance": "gone",
+            "socket": instance_dir.join("gone.sock"),
+            "spawned_at": 0,
+        })
+        .to_string(),
+    )
+    .unwrap();
+    // A socket file nobody listens on.
+    drop(std::os::unix::net::UnixListener::bind(instance_dir.join("gone.sock")).unwrap());
+
+    let out = run_gc(runtime_dir.path());
+    assert!(out.contains("gone: pid"), "unexpected output: {}", out);
+    assert!(!state_path.exists());
+    assert!(!instance_dir.join("gone.sock").exists());
+}
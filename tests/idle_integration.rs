@@ -31,13 +31,35 @@ fn create_test_state() -> (api::AppState, mpsc::Receiver<Bytes>, ActivityTracker
     let activity = ActivityTracker::new();
     let session = Session {
         name: "test".to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx,
-        output_rx: broker.sender(),
+        output_rx: broker.clone(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -65,8 +87,11 @@ fn create_test_state() -> (api::AppState, mpsc::Receiver<Bytes>, ActivityTracker
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
             backends: wsh::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -792,13 +817,35 @@ fn create_multi_session_state() -> (api::AppState, ActivityTracker, ActivityTrac
         let activity = ActivityTracker::new();
         let session = Session {
             name: name.to_string(),
-            pid: None,
+            pid: Default::default(),
             command: "test".to_string(),
             client_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             tags: Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
             child_exited: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            size_arbiter: wsh::terminal::SizeArbiter::default(),
+            usage: wsh::usage::UsageCounters::new(),
+            idle_kill_after: Default::default(),
+            suspended: Default::default(),
+            hibernated: Default::default(),
+            exit_status: Default::default(),
+            restart: Default::default(),
+            scrollback_lines: Default::default(),
+            query_policy: Default::default(),
+            handoff: Default::default(),
+            policy: Default::default(),
+            redactor: Default::default(),
+            resume_tokens: Default::default(),
+            bookmarks: Default::default(),
+            status_line: Default::default(),
+            pipes: Default::default(),
+            approvals: Default::default(),
+            metadata: Default::default(),
+            created_at: Default::default(),
+            run_as: None,
+            safe_mode: None,
+            owner: Default::default(),
             input_tx,
-            output_rx: broker.sender(),
+            output_rx: broker.clone(),
             shutdown: ShutdownCoordinator::new(),
             parser,
             overlays: OverlayStore::new(),
@@ -832,8 +879,11 @@ fn create_multi_session_state() -> (api::AppState, ActivityTracker, ActivityTrac
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
             backends: wsh::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -982,6 +1032,57 @@ async fn test_http_idle_any_fresh_always_waits() {
     );
 }
 
+#[tokio::test]
+async fn test_http_idle_all_waits_for_every_session() {
+    let (state, activity_a, _activity_b, _ptx_a, _ptx_b) = create_multi_session_state();
+    let app = api::router(state, api::RouterConfig::default());
+    let addr = start_server(app).await;
+
+    // Keep alpha busy for a while; beta is idle throughout.
+    let a = activity_a.clone();
+    let touch_handle = tokio::spawn(async move {
+        for _ in 0..30 {
+            a.touch();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    });
+
+    let start = std::time::Instant::now();
+    let (status, json) = http_get(addr, "/idle?timeout_ms=100&mode=all&max_wait_ms=5000").await;
+    let elapsed = start.elapsed();
+    touch_handle.await.unwrap();
+
+    assert_eq!(status, 200);
+    let sessions = json["sessions"].as_array().expect("response should have sessions");
+    let names: Vec<&str> = sessions.iter().map(|s| s["session"].as_str().unwrap()).collect();
+    assert_eq!(names, vec!["alpha", "beta"]);
+    assert_eq!(sessions[0]["generation"], activity_a.generation());
+    // alpha was touched for ~300ms and then needed 100ms of quiet.
+    assert!(elapsed >= Duration::from_millis(350), "returned after {:?}", elapsed);
+}
+
+#[tokio::test]
+async fn test_http_idle_all_returns_408_while_one_is_busy() {
+    let (state, activity_a, _activity_b, _ptx_a, _ptx_b) = create_multi_session_state();
+    let app = api::router(state, api::RouterConfig::default());
+    let addr = start_server(app).await;
+
+    let a = activity_a.clone();
+    let touch_handle = tokio::spawn(async move {
+        loop {
+            a.touch();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(150)).await;
+
+    let (status, json) = http_get(addr, "/idle?timeout_ms=100&mode=all&max_wait_ms=300").await;
+    touch_handle.abort();
+
+    assert_eq!(status, 408);
+    assert_eq!(json["error"]["code"], "idle_timeout");
+}
+
 #[tokio::test]
 async fn test_http_idle_any_no_sessions_returns_404() {
     let state = api::AppState {
@@ -991,8 +1092,11 @@ async fn test_http_idle_any_no_sessions_returns_404() {
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
             backends: wsh::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -1036,6 +1140,14 @@ async fn test_http_session_info_includes_last_activity_ms() {
             "last_activity_ms should be a number, got: {:?}",
             session["last_activity_ms"]
         );
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let at = session["last_activity_at"].as_u64().expect("last_activity_at should be a number");
+        let ago = session["last_activity_ms"].as_u64().unwrap();
+        assert!(at <= now && at + ago + 1000 >= now, "last_activity_at {} is {} ms ago", at, now - at);
+        assert!(session["created_at"].is_number());
     }
 }
 
@@ -670,3 +670,94 @@ async fn test_focus_non_focusable_returns_400() {
     let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
     assert_eq!(json["error"]["code"], "not_focusable");
 }
+
+#[tokio::test]
+async fn test_focus_order_and_next() {
+    let (state, _, _, _ptx) = common::create_test_state();
+    let app = router(state, RouterConfig::default());
+
+    // Create two focusable overlays and one that is not
+    let mut ids = Vec::new();
+    for focusable in [true, true, false] {
+        let create_body = serde_json::json!({
+            "x": 0,
+            "y": 0,
+            "width": 10,
+            "height": 1,
+            "focusable": focusable,
+            "spans": [{ "text": "overlay" }]
+        });
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/sessions/test/overlay")
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_string(&create_body).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        ids.push(json["id"].as_str().unwrap().to_string());
+    }
+
+    // A non-focusable element cannot be put in the order
+    let order_body = serde_json::json!({ "ids": [ids[0], ids[2]] });
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("PUT")
+                .uri("/sessions/test/input/focus/order")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_string(&order_body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Reverse the default order
+    let order_body = serde_json::json!({ "ids": [ids[1], ids[0]] });
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("PUT")
+                .uri("/sessions/test/input/focus/order")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_string(&order_body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    // Focus cycles through the order and wraps around
+    for expected in [&ids[1], &ids[0], &ids[1]] {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/sessions/test/input/focus/next")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["focused"], **expected);
+        assert_eq!(json["order"], serde_json::json!([ids[1], ids[0]]));
+    }
+}
@@ -5,6 +5,7 @@
 //! - Server info and capabilities are returned correctly
 //! - Tool listing works through the MCP protocol
 //! - The endpoint is accessible without authentication (separate from the REST API)
+#![cfg(feature = "mcp")]
 
 use std::net::SocketAddr;
 use std::time::Duration;
@@ -16,16 +17,23 @@ use wsh::shutdown::ShutdownCoordinator;
 
 /// Creates a test app with an empty session registry.
 fn create_test_app() -> axum::Router {
+    create_test_app_with_config(ServerConfig::new(false))
+}
+
+fn create_test_app_with_config(server_config: ServerConfig) -> axum::Router {
     let registry = SessionRegistry::new();
     let state = AppState {
         sessions: registry,
         shutdown: ShutdownCoordinator::new(),
-        server_config: std::sync::Arc::new(ServerConfig::new(false)),
+        server_config: std::sync::Arc::new(server_config),
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
             backends: wsh::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -175,6 +183,10 @@ async fn test_mcp_initialize_returns_server_info() {
         result["capabilities"]["tools"].is_object(),
         "Expected tools capability"
     );
+    assert_eq!(
+        result["capabilities"]["resources"]["subscribe"], true,
+        "Expected resource subscriptions"
+    );
 
     // Verify instructions are present
     assert!(
@@ -279,8 +291,11 @@ async fn test_mcp_endpoint_exempt_from_auth() {
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
             backends: wsh::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -1058,6 +1073,151 @@ async fn test_mcp_tool_run_command() {
     cleanup_session(&client, addr, &mcp_session, sess_name).await;
 }
 
+// ── Test 14b: wsh_run_command streams output as progress ─────────
+
+#[tokio::test]
+async fn test_mcp_tool_run_command_streams_progress() {
+    let app = create_test_app();
+    let addr = start_test_server(app).await;
+    let client = reqwest::Client::new();
+    let mcp_session = setup_mcp_session(&client, addr).await;
+
+    let sess_name = "mcp-runcmd-progress-test";
+    let json = call_tool(
+        &client,
+        addr,
+        &mcp_session,
+        "wsh_create_session",
+        serde_json::json!({"name": sess_name, "command": "sh"}),
+    )
+    .await;
+    assert_not_error(&json);
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    // A progress token asks for output while the command runs.
+    let body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": next_request_id(),
+        "method": "tools/call",
+        "params": {
+            "name": "wsh_run_command",
+            "arguments": {
+                "session": sess_name,
+                "input": "for i in 1 2 3; do echo step$i; sleep 1; done\n",
+                "timeout_ms": 1500,
+                "max_wait_ms": 15000,
+                "format": "plain",
+            },
+            "_meta": {"progressToken": "build-1"},
+        }
+    });
+    let response_body = send_mcp_request_with_session(
+        &client,
+        addr,
+        &serde_json::to_string(&body).unwrap(),
+        &mcp_session,
+    )
+    .await;
+
+    let notifications: Vec<serde_json::Value> = response_body
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .filter_map(|data| serde_json::from_str::<serde_json::Value>(data).ok())
+        .filter(|json| json["method"] == "notifications/progress")
+        .collect();
+    assert!(!notifications.is_empty(), "no progress notifications in:\n{response_body}");
+    assert!(notifications.iter().all(|n| n["params"]["progressToken"] == "build-1"));
+    let streamed: String = notifications
+        .iter()
+        .filter_map(|n| n["params"]["message"].as_str())
+        .collect();
+    assert!(streamed.contains("step1"), "streamed output: {streamed}");
+
+    // The final result still follows.
+    let json = extract_jsonrpc_from_sse(&response_body);
+    assert!(parse_tool_result(&json).get("screen").is_some());
+
+    cleanup_session(&client, addr, &mcp_session, sess_name).await;
+}
+
+#[tokio::test]
+async fn test_mcp_screen_subscription_notifies_on_change() {
+    let app = create_test_app();
+    let addr = start_test_server(app).await;
+    let client = reqwest::Client::new();
+    let mcp_session = setup_mcp_session(&client, addr).await;
+
+    let sess_name = "mcp-subscribe-test";
+    let json = call_tool(
+        &client,
+        addr,
+        &mcp_session,
+        "wsh_create_session",
+        serde_json::json!({"name": sess_name, "command": "sh"}),
+    )
+    .await;
+    assert_not_error(&json);
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let uri = format!("wsh://sessions/{sess_name}/screen");
+    let body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": next_request_id(),
+        "method": "resources/subscribe",
+        "params": {"uri": uri},
+    });
+    let response_body = send_mcp_request_with_session(&client, addr, &body.to_string(), &mcp_session).await;
+    let json = extract_jsonrpc_from_sse(&response_body);
+    assert!(json.get("error").is_none(), "subscribe failed: {json}");
+
+    // Scrollback can't be subscribed to.
+    let body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": next_request_id(),
+        "method": "resources/subscribe",
+        "params": {"uri": format!("wsh://sessions/{sess_name}/scrollback")},
+    });
+    let response_body = send_mcp_request_with_session(&client, addr, &body.to_string(), &mcp_session).await;
+    assert!(extract_jsonrpc_from_sse(&response_body).get("error").is_some());
+
+    // Updates arrive on the standalone stream.
+    let mut stream = client
+        .get(format!("http://{addr}/mcp"))
+        .header("Accept", "text/event-stream")
+        .header("Mcp-Session-Id", &mcp_session)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(stream.status(), 200);
+
+    let json = call_tool(
+        &client,
+        addr,
+        &mcp_session,
+        "wsh_send_input",
+        serde_json::json!({"session": sess_name, "input": "echo changed\n"}),
+    )
+    .await;
+    assert_not_error(&json);
+
+    let mut received = String::new();
+    let found = tokio::time::timeout(Duration::from_secs(5), async {
+        while let Ok(Some(chunk)) = stream.chunk().await {
+            received.push_str(&String::from_utf8_lossy(&chunk));
+            if received.contains("notifications/resources/updated") {
+                return true;
+            }
+        }
+        false
+    })
+    .await
+    .unwrap_or(false);
+    assert!(found, "no resource update notification in:\n{received}");
+    assert!(received.contains(&uri));
+
+    cleanup_session(&client, addr, &mcp_session, sess_name).await;
+}
+
 // ── Test 15: wsh_send_input + wsh_get_screen ─────────────────────
 
 #[tokio::test]
@@ -1507,6 +1667,107 @@ async fn test_mcp_tool_screen_mode() {
     cleanup_session(&client, addr, &mcp_session, sess_name).await;
 }
 
+#[tokio::test]
+async fn test_mcp_file_transfer() {
+    let dir = tempfile::tempdir().unwrap();
+    let work = dir.path().join("work");
+    std::fs::create_dir(&work).unwrap();
+    let config = ServerConfig::new(false);
+    config.set_files_config(wsh::files::FilesConfig { allow: vec![work.clone()] });
+    let addr = start_test_server(create_test_app_with_config(config)).await;
+    let client = reqwest::Client::new();
+    let mcp_session = setup_mcp_session(&client, addr).await;
+
+    let sess_name = "mcp-files-test";
+    let json = call_tool(
+        &client,
+        addr,
+        &mcp_session,
+        "wsh_create_session",
+        serde_json::json!({"name": sess_name, "cwd": work.to_str().unwrap()}),
+    )
+    .await;
+    assert_not_error(&json);
+
+    // Relative paths start at the shell's directory.
+    let json = call_tool(
+        &client,
+        addr,
+        &mcp_session,
+        "wsh_write_file",
+        serde_json::json!({
+            "session": sess_name,
+            "path": "run.sh",
+            "content": "#!/bin/sh\necho hi\n",
+            "mode": "755",
+        }),
+    )
+    .await;
+    assert_not_error(&json);
+    let result = parse_tool_result(&json);
+    let written = work.canonicalize().unwrap().join("run.sh");
+    assert_eq!(result["path"], written.to_str().unwrap());
+    assert_eq!(result["bytes"], 18);
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(std::fs::metadata(&written).unwrap().permissions().mode() & 0o777, 0o755);
+    }
+
+    let json = call_tool(
+        &client,
+        addr,
+        &mcp_session,
+        "wsh_read_file",
+        serde_json::json!({"session": sess_name, "path": written.to_str().unwrap()}),
+    )
+    .await;
+    assert_not_error(&json);
+    let result = parse_tool_result(&json);
+    assert_eq!(result["encoding"], "utf8");
+    assert_eq!(result["content"], "#!/bin/sh\necho hi\n");
+
+    // The HTTP endpoints move raw bytes.
+    let resp = client
+        .put(format!("http://{addr}/sessions/{sess_name}/file"))
+        .query(&[("path", "data.bin")])
+        .body(vec![0u8, 159, 146, 150])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = client
+        .get(format!("http://{addr}/sessions/{sess_name}/file"))
+        .query(&[("path", "data.bin")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.bytes().await.unwrap().as_ref(), &[0u8, 159, 146, 150]);
+
+    // Nothing outside the allowed directory.
+    std::fs::write(dir.path().join("secret"), "x").unwrap();
+    let json = call_tool(
+        &client,
+        addr,
+        &mcp_session,
+        "wsh_read_file",
+        serde_json::json!({"session": sess_name, "path": "../secret"}),
+    )
+    .await;
+    assert!(json["error"].is_object(), "expected an error, got: {json}");
+    let resp = client
+        .get(format!("http://{addr}/sessions/{sess_name}/file"))
+        .query(&[("path", "../secret")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "file_not_allowed");
+
+    cleanup_session(&client, addr, &mcp_session, sess_name).await;
+}
+
 // ── Test 20: Tool call for nonexistent session → error ───────────
 
 #[tokio::test]
@@ -1552,8 +1813,11 @@ async fn test_http_and_mcp_coexist() {
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
             backends: wsh::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -1957,3 +2221,124 @@ async fn test_mcp_tool_send_input_base64() {
     // Cleanup
     cleanup_session(&client, addr, &mcp_session, sess_name).await;
 }
+
+// ── Test 26: result size budget ──────────────────────────────────
+
+#[tokio::test]
+async fn test_mcp_tool_scrollback_truncated_to_budget() {
+    let server_config = ServerConfig::new(false);
+    server_config.set_mcp_config(wsh::config::McpConfig {
+        tool_result_bytes: [("wsh_get_scrollback".to_string(), 2000)].into(),
+        ..Default::default()
+    });
+    let app = create_test_app_with_config(server_config);
+    let addr = start_test_server(app).await;
+    let client = reqwest::Client::new();
+    let mcp_session = setup_mcp_session(&client, addr).await;
+
+    let sess_name = "mcp-budget-test";
+
+    // Fill the scrollback well past the budget.
+    let json = call_tool(
+        &client,
+        addr,
+        &mcp_session,
+        "wsh_create_session",
+        serde_json::json!({"name": sess_name, "command": "seq 1 500; sleep 30"}),
+    )
+    .await;
+    assert_not_error(&json);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let json = call_tool(
+        &client,
+        addr,
+        &mcp_session,
+        "wsh_get_scrollback",
+        serde_json::json!({ "session": sess_name, "limit": 1000, "format": "plain" }),
+    )
+    .await;
+    assert_not_error(&json);
+    assert!(extract_tool_text(&json).len() <= 2000);
+    let result = parse_tool_result(&json);
+    assert_eq!(result["truncated"], true, "expected truncation, got: {}", result);
+    let kept = result["lines"].as_array().unwrap().len() as u64;
+    assert_eq!(result["next_offset"], kept);
+
+    // The cursor continues where the first page stopped.
+    let json = call_tool(
+        &client,
+        addr,
+        &mcp_session,
+        "wsh_get_scrollback",
+        serde_json::json!({ "session": sess_name, "offset": kept, "limit": 1000, "format": "plain" }),
+    )
+    .await;
+    assert_not_error(&json);
+    let result = parse_tool_result(&json);
+    assert_eq!(result["offset"], kept);
+
+    cleanup_session(&client, addr, &mcp_session, sess_name).await;
+}
+
+// ── Sessions follow cleanup_on_disconnect when the MCP session ends ──
+
+#[tokio::test]
+async fn test_mcp_session_end_cleans_up_owned_sessions() {
+    let app = create_test_app();
+    let addr = start_test_server(app).await;
+    let client = reqwest::Client::new();
+    let mcp_session = setup_mcp_session(&client, addr).await;
+
+    for (name, cleanup) in [("mcp-owned-kill", "kill"), ("mcp-owned-keep", "keep")] {
+        let json = call_tool(
+            &client,
+            addr,
+            &mcp_session,
+            "wsh_create_session",
+            serde_json::json!({"name": name, "cleanup_on_disconnect": cleanup}),
+        )
+        .await;
+        assert_not_error(&json);
+    }
+
+    let json = call_tool(
+        &client,
+        addr,
+        &mcp_session,
+        "wsh_list_sessions",
+        serde_json::json!({"session": "mcp-owned-kill"}),
+    )
+    .await;
+    let detail = parse_tool_result(&json);
+    assert_eq!(detail["owner"]["mcp_session"], mcp_session.as_str());
+    assert_eq!(detail["owner"]["cleanup_on_disconnect"], "kill");
+
+    let response = client
+        .delete(format!("http://{addr}/mcp"))
+        .header("Mcp-Session-Id", &mcp_session)
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success(), "DELETE /mcp returned {}", response.status());
+
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    let names = loop {
+        let list: Vec<serde_json::Value> = client
+            .get(format!("http://{addr}/sessions"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let names: Vec<String> = list.iter().map(|s| s["name"].as_str().unwrap().to_string()).collect();
+        if !names.iter().any(|n| n == "mcp-owned-kill") || tokio::time::Instant::now() >= deadline {
+            break names;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    };
+    assert_eq!(names, vec!["mcp-owned-keep".to_string()]);
+
+    let _ = client.delete(format!("http://{addr}/sessions/mcp-owned-keep")).send().await;
+}
@@ -12,6 +12,7 @@
 //! random port, then starts `wsh mcp --bind <addr> --socket <path>` pointing at
 //! that server.  Both processes share a unique Unix socket path so the MCP bridge
 //! connects to the pre-started server rather than spawning its own.
+#![cfg(feature = "mcp")]
 
 use std::io::{BufRead, BufReader, Write};
 use std::net::SocketAddr;
@@ -444,6 +445,76 @@ fn test_mcp_stdio_full_tool_exercise() {
     result.expect("test thread panicked");
 }
 
+// ── Test: resource subscriptions reach the host ───────────────────
+
+#[test]
+fn test_mcp_stdio_resource_subscription() {
+    let mut harness = setup_mcp_test("subscription");
+    let mut stdin = harness.mcp.stdin.take().unwrap();
+    let mut reader = BufReader::new(harness.mcp.stdout.take().unwrap());
+
+    let handle = std::thread::spawn(move || {
+        let response = initialize(&mut stdin, &mut reader);
+        assert!(response["result"].is_object());
+        send_initialized_notification(&mut stdin);
+        std::thread::sleep(Duration::from_millis(200));
+
+        send_jsonrpc(&mut stdin, &serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": {"name": "wsh_create_session", "arguments": {"name": "sub", "command": "sh"}}
+        }));
+        assert_eq!(read_jsonrpc(&mut reader)["id"], 2);
+        std::thread::sleep(Duration::from_millis(300));
+
+        send_jsonrpc(&mut stdin, &serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "resources/subscribe",
+            "params": {"uri": "wsh://sessions/sub/screen"}
+        }));
+        let response = read_jsonrpc(&mut reader);
+        assert!(response.get("error").is_none(), "subscribe failed: {}", response);
+        // Give the bridge time to open the notification stream.
+        std::thread::sleep(Duration::from_millis(300));
+
+        send_jsonrpc(&mut stdin, &serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 4,
+            "method": "tools/call",
+            "params": {"name": "wsh_send_input", "arguments": {"session": "sub", "input": "echo hi\n"}}
+        }));
+
+        // The update arrives on stdout alongside the tool result.
+        loop {
+            let mut line = String::new();
+            assert!(reader.read_line(&mut line).unwrap() > 0, "stdout closed");
+            let Ok(json) = serde_json::from_str::<serde_json::Value>(line.trim()) else {
+                continue;
+            };
+            if json["method"] == "notifications/resources/updated" {
+                assert_eq!(json["params"]["uri"], "wsh://sessions/sub/screen");
+                break;
+            }
+        }
+        drop(stdin);
+    });
+
+    let timeout = Duration::from_secs(30);
+    let start = std::time::Instant::now();
+    while !handle.is_finished() {
+        if start.elapsed() > timeout {
+            drop(harness);
+            panic!("test timed out after {:?}", timeout);
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    let result = handle.join();
+    drop(harness);
+    result.expect("test thread panicked");
+}
+
 // ── Test 3: Clean shutdown when stdin is closed ────────────────────
 
 #[test]
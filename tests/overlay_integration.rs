@@ -638,3 +638,243 @@ async fn test_overlay_region_write() {
     assert_eq!(writes[1]["col"], 0);
     assert_eq!(writes[1]["text"], "Line 2 content");
 }
+
+#[tokio::test]
+async fn test_overlay_widget_create_and_patch() {
+    let (state, _, _, _ptx) = common::create_test_state();
+    let app = router(state, RouterConfig::default());
+
+    // Create a progress bar widget with no spans
+    let create_body = serde_json::json!({
+        "x": 0,
+        "y": 0,
+        "width": 20,
+        "height": 1,
+        "widget": { "type": "progress", "label": "Tests", "value": 0, "max": 10 }
+    });
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/sessions/test/overlay")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_string(&create_body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let overlay_id = json["id"].as_str().unwrap().to_string();
+
+    // Patch only the value
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("PATCH")
+                .uri(format!("/sessions/test/overlay/{}", overlay_id))
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"widget": {"value": 5}}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/sessions/test/overlay/{}", overlay_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["widget"]["type"], "progress");
+    assert_eq!(json["widget"]["value"], 5.0);
+    let text: String = json["spans"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["text"].as_str().unwrap())
+        .collect();
+    assert_eq!(text, "Tests █████░░░░  50%");
+
+    // An invalid widget is rejected
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("PATCH")
+                .uri(format!("/sessions/test/overlay/{}", overlay_id))
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"widget": {"max": 0}}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_overlay_create_with_ttl_and_sticky() {
+    let (state, _, _, _ptx) = common::create_test_state();
+    let app = router(state, RouterConfig::default());
+
+    let create_body = serde_json::json!({
+        "x": 0,
+        "y": 0,
+        "width": 20,
+        "height": 1,
+        "spans": [{ "text": "Saved" }],
+        "ttl_ms": 60000,
+        "sticky": true
+    });
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/sessions/test/overlay")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_string(&create_body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let overlay_id = json["id"].as_str().unwrap().to_string();
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri(format!("/sessions/test/overlay/{}", overlay_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["sticky"], true);
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let expires_at = json["expires_at"].as_u64().unwrap();
+    assert!(expires_at > now && expires_at <= now + 60000);
+}
+
+#[tokio::test]
+async fn test_overlay_animate() {
+    let (state, _, _, _ptx) = common::create_test_state();
+    let app = router(state, RouterConfig::default());
+
+    let create = |x: u16| {
+        let app = app.clone();
+        async move {
+            let body = serde_json::json!({ "x": x, "y": 0, "width": 10, "height": 1 });
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri("/sessions/test/overlay")
+                        .header("content-type", "application/json")
+                        .body(Body::from(body.to_string()))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            json["id"].as_str().unwrap().to_string()
+        }
+    };
+    let animate = |id: String, body: serde_json::Value| {
+        let app = app.clone();
+        async move {
+            app.oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("/sessions/test/overlay/{}/animate", id))
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status()
+        }
+    };
+    let get = |id: String| {
+        let app = app.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri(format!("/sessions/test/overlay/{}", id))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            if response.status() == StatusCode::NOT_FOUND {
+                return None;
+            }
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            Some(serde_json::from_slice::<serde_json::Value>(&body).unwrap())
+        }
+    };
+
+    let slide = create(0).await;
+    let toast = create(40).await;
+    let status = animate(
+        slide.clone(),
+        serde_json::json!({
+            "keyframes": [
+                { "at_ms": 0, "x": 10 },
+                { "at_ms": 100, "x": 20, "spans": [{ "text": "done" }] }
+            ]
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let status = animate(
+        toast.clone(),
+        serde_json::json!({ "keyframes": [{ "at_ms": 100, "y": 5 }], "delete": true }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    let json = get(slide.clone()).await.unwrap();
+    assert_eq!(json["x"], 20);
+    assert_eq!(json["spans"][0]["text"], "done");
+    assert!(get(toast).await.is_none(), "the toast should be deleted at the end");
+
+    let status = animate(slide, serde_json::json!({ "keyframes": [] })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let status = animate("nonexistent".into(), serde_json::json!({ "keyframes": [{ "at_ms": 0 }] })).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
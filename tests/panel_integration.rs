@@ -733,3 +733,203 @@ async fn test_panel_region_write() {
     assert_eq!(writes[1]["text"], "Row 3");
     assert_eq!(writes[1]["bold"], true);
 }
+
+#[tokio::test]
+async fn test_scrollable_panel_append_and_scroll() {
+    let state = create_test_state();
+    let app = router(state, RouterConfig::default());
+
+    // Create a scrollable panel that keeps three lines
+    let create_body = serde_json::json!({
+        "position": "bottom",
+        "height": 2,
+        "max_lines": 3
+    });
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/sessions/test/panel")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_string(&create_body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let json = json_body(response).await;
+    let panel_id = json["id"].as_str().unwrap().to_string();
+
+    // Append four lines; the oldest is dropped
+    for line in ["one", "two", "three", "four"] {
+        let append_body = serde_json::json!({ "spans": [{ "text": line }] });
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("/sessions/test/panel/{}/append", panel_id))
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_string(&append_body).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+
+    // Scroll past the top; the offset is limited to the content
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/sessions/test/panel/{}/scroll", panel_id))
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"offset": 50}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/sessions/test/panel/{}", panel_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let json = json_body(response).await;
+    let text: String = json["spans"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["text"].as_str().unwrap())
+        .collect();
+    assert_eq!(text, "two\nthree\nfour");
+    assert_eq!(json["max_lines"], 3);
+    assert_eq!(json["scroll"], 1);
+
+    // A panel without max_lines cannot be appended to
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/sessions/test/panel")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"position": "top", "height": 1}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let fixed_id = json_body(response).await["id"].as_str().unwrap().to_string();
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/sessions/test/panel/{}/append", fixed_id))
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"spans": [{"text": "x"}]}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_status_line_enable_and_disable() {
+    let state = create_test_state();
+    let app = router(state, RouterConfig::default());
+
+    let patch_body = serde_json::json!({
+        "enabled": true,
+        "format": "{session}: {task}",
+        "segments": { "task": "deploying" }
+    });
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("PATCH")
+                .uri("/sessions/test/status_line")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_string(&patch_body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json = json_body(response).await;
+    assert_eq!(json["text"], "test: deploying");
+    assert_eq!(json["position"], "bottom");
+
+    // The status line is a sticky one-row panel
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/sessions/test/panel")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let json = json_body(response).await;
+    let panels = json.as_array().unwrap();
+    assert_eq!(panels.len(), 1);
+    assert_eq!(panels[0]["height"], 1);
+    assert_eq!(panels[0]["sticky"], true);
+    assert_eq!(panels[0]["spans"][0]["text"], "test: deploying");
+
+    // Built-in names can't be used as segments
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("PATCH")
+                .uri("/sessions/test/status_line")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"segments": {"host": "x"}}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Disabling removes the panel
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("PATCH")
+                .uri("/sessions/test/status_line")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"enabled": false}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let json = json_body(response).await;
+    assert!(json["text"].is_null());
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/sessions/test/panel")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(json_body(response).await.as_array().unwrap().is_empty());
+}
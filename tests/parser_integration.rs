@@ -18,6 +18,7 @@ async fn test_parser_with_ansi_sequences() {
     let response = parser
         .query(Query::Screen {
             format: Format::Styled,
+            since: None,
         })
         .await
         .unwrap();
@@ -102,6 +103,7 @@ async fn test_parser_plain_vs_styled() {
     let plain = parser
         .query(Query::Screen {
             format: Format::Plain,
+            since: None,
         })
         .await
         .unwrap();
@@ -110,6 +112,7 @@ async fn test_parser_plain_vs_styled() {
     let styled = parser
         .query(Query::Screen {
             format: Format::Styled,
+            since: None,
         })
         .await
         .unwrap();
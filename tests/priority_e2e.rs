@@ -0,0 +1,39 @@
+//! End-to-end tests for the `__priority` trampoline that sessions with a
+//! `nice` or `ionice` setting start through.
+
+use std::process::Command;
+
+#[test]
+fn priority_sets_niceness() {
+    let output = Command::new(env!("CARGO_BIN_EXE_wsh"))
+        .args(["__priority", "7", "0", "0", "nice"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "7\n");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn priority_sets_io_class() {
+    if Command::new("ionice").output().is_err() {
+        eprintln!("skipping: ionice not installed");
+        return;
+    }
+    let output = Command::new(env!("CARGO_BIN_EXE_wsh"))
+        .args(["__priority", "-", "3", "0", "ionice"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "idle\n");
+}
+
+#[test]
+fn priority_rejects_malformed_arguments() {
+    let output = Command::new(env!("CARGO_BIN_EXE_wsh"))
+        .args(["__priority", "low", "0", "0", "true"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(127));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("invalid nice value"));
+}
@@ -33,7 +33,7 @@ fn read_pty_and_publish(
                 Ok(n) => {
                     let data = Bytes::copy_from_slice(&buf[..n]);
                     collected.extend_from_slice(&buf[..n]);
-                    broker.publish(data);
+                    broker.publish(&data);
                 }
                 Err(e) => {
                     // EIO is expected when PTY slave is closed
@@ -226,7 +226,7 @@ fn test_multiple_broker_subscribers_receive_pty_output() {
     writer.flush().expect("Flush failed");
 
     // Collect from all subscribers concurrently
-    let collect_from_rx = |mut rx: tokio::sync::broadcast::Receiver<Bytes>,
+    let collect_from_rx = |mut rx: wsh::broker::Subscriber,
                            marker: String|
      -> mpsc::Receiver<Vec<u8>> {
         let (tx, result_rx) = mpsc::channel();
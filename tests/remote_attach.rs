@@ -0,0 +1,90 @@
+//! Integration tests for `wsh attach --url`: the HTTP replay, resize and
+//! `/ws/raw` stream a remote attach uses, against a real API server.
+#![cfg(feature = "remote-attach")]
+
+mod common;
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+use wsh::api::{router, RouterConfig};
+use wsh::protocol::ScrollbackRequest;
+use wsh::remote::RemoteSession;
+
+async fn start_server(app: axum::Router) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    addr
+}
+
+#[tokio::test]
+async fn remote_attach_replays_resizes_and_streams() {
+    let (state, mut input_rx, output_tx, parser_tx) = common::create_test_state();
+    let sessions = state.sessions.clone();
+    let app = router(state, RouterConfig { token: Some("secret".into()), ..Default::default() });
+    let addr = start_server(app).await;
+
+    // Push lines into scrollback and leave a prompt on screen.
+    let mut feed = String::new();
+    for i in 0..30 {
+        feed.push_str(&format!("line {}\r\n", i));
+    }
+    feed.push_str("$ ");
+    parser_tx.send(Bytes::from(feed)).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let remote = RemoteSession::new(&format!("http://{}", addr), "test", Some("secret".into())).unwrap();
+
+    let all = remote.scrollback(ScrollbackRequest::All).await.unwrap();
+    assert!(!all.is_empty());
+    let first = remote.scrollback(ScrollbackRequest::Lines(2)).await.unwrap();
+    assert_eq!(first.len(), 2);
+    assert!(remote.scrollback(ScrollbackRequest::None).await.unwrap().is_empty());
+
+    let screen = String::from_utf8(remote.screen().await.unwrap()).unwrap();
+    assert!(screen.starts_with("\x1b[H\x1b[2J"));
+    assert!(screen.contains("line 29"));
+
+    remote.resize(30, 100).await.unwrap();
+    assert_eq!(sessions.get("test").unwrap().terminal_size.get(), (30, 100));
+
+    let mut ws = remote.connect().await.unwrap();
+    ws.send(Message::Binary(Bytes::from_static(b"ls\r"))).await.unwrap();
+    let input = tokio::time::timeout(Duration::from_secs(2), input_rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(&input[..], b"ls\r");
+
+    output_tx.publish(b"output");
+    let msg = tokio::time::timeout(Duration::from_secs(2), ws.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(msg, Message::Binary(Bytes::from_static(b"output")));
+}
+
+#[tokio::test]
+async fn remote_attach_reports_api_errors() {
+    let (state, _input_rx, _output_tx, _parser_tx) = common::create_test_state();
+    let app = router(state, RouterConfig { token: Some("secret".into()), ..Default::default() });
+    let addr = start_server(app).await;
+    let url = format!("http://{}", addr);
+
+    let wrong_token = RemoteSession::new(&url, "test", Some("nope".into())).unwrap();
+    let err = wrong_token.screen().await.unwrap_err();
+    assert!(err.to_string().starts_with("auth_invalid"), "{}", err);
+    assert!(wrong_token.connect().await.is_err());
+
+    let missing = RemoteSession::new(&url, "missing", Some("secret".into())).unwrap();
+    let err = missing.screen().await.unwrap_err();
+    assert!(err.to_string().starts_with("session_not_found"), "{}", err);
+}
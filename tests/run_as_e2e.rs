@@ -0,0 +1,36 @@
+//! End-to-end tests for the `__run-as` trampoline that sessions spawned as
+//! another user start through.
+//!
+//! Dropping privileges needs root, so the tests that do it are skipped when
+//! the suite runs as an ordinary user.
+
+use std::process::Command;
+
+fn is_root() -> bool {
+    // SAFETY: geteuid cannot fail.
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[test]
+fn run_as_drops_to_target_account() {
+    if !is_root() {
+        eprintln!("skipping: needs root");
+        return;
+    }
+    let output = Command::new(env!("CARGO_BIN_EXE_wsh"))
+        .args(["__run-as", "65534", "65534", "nobody", "sh", "-c", "id -u; id -g"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "65534\n65534\n");
+}
+
+#[test]
+fn run_as_rejects_malformed_arguments() {
+    let output = Command::new(env!("CARGO_BIN_EXE_wsh"))
+        .args(["__run-as", "not-a-uid", "0", "root", "true"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(127));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("expected uid and gid"));
+}
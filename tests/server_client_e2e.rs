@@ -161,6 +161,7 @@ async fn test_attach_to_session() {
         .attach(AttachSessionMsg {
             name: "attach-target".to_string(),
             scrollback: ScrollbackRequest::None,
+            scrollback_format: None,
             rows: 30,
             cols: 120,
         })
@@ -223,6 +224,7 @@ async fn test_detach_and_reattach() {
         .attach(AttachSessionMsg {
             name: "detach-reattach".to_string(),
             scrollback: ScrollbackRequest::None,
+            scrollback_format: None,
             rows: 40,
             cols: 100,
         })
@@ -272,6 +274,7 @@ async fn test_multiple_clients_same_session() {
         &AttachSessionMsg {
             name: "multi-client".to_string(),
             scrollback: ScrollbackRequest::None,
+            scrollback_format: None,
             rows: 24,
             cols: 80,
         },
@@ -425,6 +428,7 @@ async fn test_client_resize() {
         .parser
         .query(Query::Screen {
             format: Format::Plain,
+            since: None,
         })
         .await
         .unwrap();
@@ -450,6 +454,7 @@ async fn test_attach_nonexistent_session() {
         .attach(AttachSessionMsg {
             name: "does-not-exist".to_string(),
             scrollback: ScrollbackRequest::None,
+            scrollback_format: None,
             rows: 24,
             cols: 80,
         })
@@ -506,6 +511,7 @@ async fn test_session_registry_after_client_disconnect() {
         .attach(AttachSessionMsg {
             name: "survive-disconnect".to_string(),
             scrollback: ScrollbackRequest::None,
+            scrollback_format: None,
             rows: 24,
             cols: 80,
         })
@@ -692,7 +698,7 @@ async fn test_ephemeral_server_watches_sessions() {
     // Drain until we see Destroyed.
     let mut saw_destroyed = matches!(
         event2,
-        SessionEvent::Destroyed { ref name } if name == "event-watch"
+        SessionEvent::Destroyed { ref name, .. } if name == "event-watch"
     );
 
     if !saw_destroyed {
@@ -705,7 +711,7 @@ async fn test_ephemeral_server_watches_sessions() {
         {
             saw_destroyed = matches!(
                 event3,
-                SessionEvent::Destroyed { ref name } if name == "event-watch"
+                SessionEvent::Destroyed { ref name, .. } if name == "event-watch"
             );
         }
     }
@@ -717,3 +723,149 @@ async fn test_ephemeral_server_watches_sessions() {
 
     std::fs::remove_file(&path).ok();
 }
+
+// ── Test 15: Read and follow session output ────────────────────────
+
+#[tokio::test]
+async fn test_read_output_via_client() {
+    let (path, _sessions) = start_test_server().await;
+
+    let mut stream = UnixStream::connect(&path).await.unwrap();
+    Frame::control(
+        FrameType::CreateSession,
+        &CreateSessionMsg {
+            name: Some("logs".to_string()),
+            command: Some("bash --norc --noprofile".to_string()),
+            cwd: None,
+            env: None,
+            rows: 24,
+            cols: 80,
+            tags: vec![],
+            server: None,
+        },
+    )
+    .unwrap()
+    .write_to(&mut stream)
+    .await
+    .unwrap();
+    let resp = Frame::read_from(&mut stream).await.unwrap();
+    assert_eq!(resp.frame_type, FrameType::CreateSessionResponse);
+
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    Frame::data(FrameType::StdinInput, Bytes::from("echo first_$((1+1))\n"))
+        .write_to(&mut stream)
+        .await
+        .unwrap();
+
+    // Without follow the server sends what it has and hangs up.
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+    let mut next = 0;
+    loop {
+        let mut lines = Vec::new();
+        let mut reader = Client::connect(&path).await.unwrap();
+        reader
+            .read_output(
+                ReadOutputMsg { name: "logs".to_string(), since: None, last: None, follow: false },
+                |batch| {
+                    lines.extend(batch.lines);
+                    next = batch.next;
+                },
+            )
+            .await
+            .unwrap();
+        if lines.iter().any(|l| l.contains("first_2")) {
+            break;
+        }
+        assert!(tokio::time::Instant::now() < deadline, "first_2 never printed: {:?}", lines);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+
+    // Following picks up lines printed later.
+    let follow_path = path.clone();
+    let (line_tx, mut line_rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut follower = Client::connect(&follow_path).await.unwrap();
+        let _ = follower
+            .read_output(
+                ReadOutputMsg { name: "logs".to_string(), since: Some(next), last: None, follow: true },
+                |batch| {
+                    for line in batch.lines {
+                        let _ = line_tx.send(line);
+                    }
+                },
+            )
+            .await;
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    Frame::data(FrameType::StdinInput, Bytes::from("echo second_$((1+1))\n"))
+        .write_to(&mut stream)
+        .await
+        .unwrap();
+
+    let found = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while let Some(line) = line_rx.recv().await {
+            assert!(!line.contains("first_2"), "followed output repeated an old line: {}", line);
+            if line.contains("second_2") {
+                return true;
+            }
+        }
+        false
+    })
+    .await
+    .unwrap_or(false);
+    assert!(found, "follower should receive second_2");
+
+    std::fs::remove_file(&path).ok();
+}
+
+// ── Test 16: Read output of nonexistent session ────────────────────
+
+#[tokio::test]
+async fn test_read_output_nonexistent_session() {
+    let (path, _sessions) = start_test_server().await;
+
+    let mut client = Client::connect(&path).await.unwrap();
+    let result = client
+        .read_output(
+            ReadOutputMsg { name: "does-not-exist".to_string(), since: None, last: None, follow: false },
+            |_| {},
+        )
+        .await;
+    let err = result.expect_err("reading a nonexistent session should fail");
+    assert!(err.to_string().contains("session_not_found"), "got: {}", err);
+
+    std::fs::remove_file(&path).ok();
+}
+
+// ── Test 17: Rename a session ──────────────────────────────────────
+
+#[tokio::test]
+async fn test_rename_session_via_client() {
+    let (path, sessions) = start_test_server().await;
+
+    let mut creator = Client::connect(&path).await.unwrap();
+    creator
+        .create_session(CreateSessionMsg {
+            name: Some("before".to_string()),
+            command: None,
+            cwd: None,
+            env: None,
+            rows: 24,
+            cols: 80,
+            tags: vec![],
+            server: None,
+        })
+        .await
+        .unwrap();
+
+    let mut renamer = Client::connect(&path).await.unwrap();
+    renamer.rename_session("before", "after").await.unwrap();
+    assert!(sessions.get("before").is_none());
+    assert!(sessions.get("after").is_some());
+
+    let mut renamer = Client::connect(&path).await.unwrap();
+    let err = renamer.rename_session("before", "again").await.unwrap_err();
+    assert!(err.to_string().contains("session_not_found"), "got: {}", err);
+
+    std::fs::remove_file(&path).ok();
+}
@@ -27,8 +27,11 @@ fn create_empty_test_app() -> axum::Router {
         server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+        tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
         backends: wsh::federation::registry::BackendRegistry::new(),
+        #[cfg(feature = "federation")]
         federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())),
+        #[cfg(feature = "federation")]
         ip_access: None,
         hostname: "test".to_string(),
         federation_config_path: None,
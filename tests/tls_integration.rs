@@ -15,7 +15,6 @@ fn create_test_app() -> axum::Router {
 }
 
 fn create_test_app_with_config(config: RouterConfig) -> axum::Router {
-    let federation_manager = wsh::federation::manager::FederationManager::new();
     let state = AppState {
         sessions: SessionRegistry::new(),
         shutdown: ShutdownCoordinator::new(),
@@ -23,8 +22,11 @@ fn create_test_app_with_config(config: RouterConfig) -> axum::Router {
         server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
-        backends: federation_manager.registry().clone(),
-        federation: std::sync::Arc::new(tokio::sync::Mutex::new(federation_manager)),
+        tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
+        backends: wsh::federation::registry::BackendRegistry::new(),
+        #[cfg(feature = "federation")]
+        federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())),
+        #[cfg(feature = "federation")]
         ip_access: None,
         hostname: "test-tls".to_string(),
         federation_config_path: None,
@@ -53,42 +55,7 @@ async fn start_tls_server(
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
     let addr = listener.local_addr().unwrap();
     let cancel = tokio_util::sync::CancellationToken::new();
-
-    tokio::spawn({
-        let cancel = cancel.clone();
-        async move {
-            loop {
-                let (tcp_stream, _peer_addr) = tokio::select! {
-                    _ = cancel.cancelled() => break,
-                    result = listener.accept() => {
-                        match result {
-                            Ok(conn) => conn,
-                            Err(_) => continue,
-                        }
-                    }
-                };
-
-                let acceptor = acceptor.clone();
-                let app = app.clone();
-
-                tokio::spawn(async move {
-                    let tls_stream = match acceptor.accept(tcp_stream).await {
-                        Ok(s) => s,
-                        Err(_) => return,
-                    };
-
-                    let io = hyper_util::rt::TokioIo::new(tls_stream);
-                    let service = hyper_util::service::TowerToHyperService::new(app);
-                    let builder = hyper_util::server::conn::auto::Builder::new(
-                        hyper_util::rt::TokioExecutor::new(),
-                    );
-                    let conn = builder.serve_connection_with_upgrades(io, service);
-                    let _ = conn.await;
-                });
-            }
-        }
-    });
-
+    tokio::spawn(wsh::tls::serve(listener, acceptor, app, cancel));
     tokio::time::sleep(Duration::from_millis(10)).await;
     addr
 }
@@ -166,6 +133,61 @@ async fn plain_http_rejected_by_tls_server() {
     assert!(result.is_err(), "plain HTTP request to TLS server should fail");
 }
 
+#[tokio::test]
+async fn tls_ticket_is_bound_to_the_peer_ip() {
+    let dir = tempfile::tempdir().unwrap();
+    let (cert_path, key_path) = generate_test_cert(dir.path());
+
+    let acceptor = wsh::tls::load_tls_config(&cert_path, &key_path).unwrap();
+    let app = create_test_app_with_config(RouterConfig {
+        token: Some("test-token".to_string()),
+        ..RouterConfig::default()
+    });
+    let addr = start_tls_server(app, acceptor).await;
+
+    // 127.0.0.2 is also loopback, so it reaches the server from another IP.
+    let client_from = |ip: &str| {
+        reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .http1_only()
+            .local_address(ip.parse::<std::net::IpAddr>().unwrap())
+            .build()
+            .unwrap()
+    };
+    let issuer = client_from("127.0.0.1");
+    let other = client_from("127.0.0.2");
+
+    let ticket = || async {
+        let resp = issuer
+            .post(format!("https://127.0.0.1:{}/auth/ws-ticket", addr.port()))
+            .bearer_auth("test-token")
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        body["ticket"].as_str().unwrap().to_string()
+    };
+    let redeem = |client: &reqwest::Client, ticket: String| {
+        client
+            .get(format!("https://127.0.0.1:{}/sessions?ticket={}", addr.port(), ticket))
+            .header("upgrade", "websocket")
+            .header("connection", "Upgrade")
+            .header("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ==")
+            .header("sec-websocket-version", "13")
+            .timeout(Duration::from_secs(5))
+            .send()
+    };
+
+    let resp = redeem(&other, ticket().await).await.unwrap();
+    assert_eq!(resp.status(), 401, "a ticket redeemed from another IP must be rejected");
+
+    let resp = redeem(&issuer, ticket().await).await.unwrap();
+    assert_ne!(resp.status(), 401);
+    assert_ne!(resp.status(), 403);
+}
+
 // --- Combined TLS + base-prefix tests ---
 
 #[tokio::test]
@@ -226,6 +248,7 @@ async fn tls_with_base_prefix_routes_work() {
 
 // --- Combined TLS + federation tests ---
 
+#[cfg(feature = "federation")]
 #[tokio::test]
 async fn tls_federation_servers_endpoint() {
     use wsh::federation::registry::{BackendEntry, BackendHealth, BackendRole};
@@ -254,6 +277,7 @@ async fn tls_federation_servers_endpoint() {
         server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+        tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
         backends,
         federation: std::sync::Arc::new(tokio::sync::Mutex::new(federation_manager)),
         ip_access: None,
@@ -295,6 +319,7 @@ async fn tls_federation_servers_endpoint() {
 
 // --- Combined TLS + base-prefix + federation ---
 
+#[cfg(feature = "federation")]
 #[tokio::test]
 async fn tls_base_prefix_federation_combined() {
     use wsh::federation::registry::{BackendEntry, BackendHealth, BackendRole};
@@ -322,6 +347,7 @@ async fn tls_base_prefix_federation_combined() {
         server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+        tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
         backends,
         federation: std::sync::Arc::new(tokio::sync::Mutex::new(federation_manager)),
         ip_access: None,
@@ -0,0 +1,108 @@
+//! End-to-end tests for the `__tmux-bridge` relay that control-mode tmux
+//! imports run in each session.
+
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+struct TmuxServer {
+    socket: String,
+}
+
+impl TmuxServer {
+    fn tmux(&self, args: &[&str]) -> std::process::Output {
+        Command::new("tmux")
+            .args(["-L", &self.socket])
+            .args(args)
+            .env_remove("TMUX")
+            .output()
+            .unwrap()
+    }
+}
+
+impl Drop for TmuxServer {
+    fn drop(&mut self) {
+        let _ = self.tmux(&["kill-server"]);
+    }
+}
+
+/// Read from `rx` until the collected output contains `needle`.
+fn wait_for(rx: &mpsc::Receiver<Vec<u8>>, output: &mut Vec<u8>, needle: &str) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !String::from_utf8_lossy(output).contains(needle) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match rx.recv_timeout(remaining) {
+            Ok(chunk) => output.extend_from_slice(&chunk),
+            Err(_) => panic!("timed out waiting for {needle:?}; got {:?}", String::from_utf8_lossy(output)),
+        }
+    }
+}
+
+#[test]
+fn bridge_mirrors_a_pane() {
+    let server = TmuxServer { socket: format!("wsh-bridge-e2e-{}", std::process::id()) };
+    let started = Command::new("tmux")
+        .args(["-L", &server.socket, "new-session", "-d", "-s", "work", "-x", "40", "-y", "10"])
+        .arg("echo ready; exec cat")
+        .env_remove("TMUX")
+        .status();
+    if !started.is_ok_and(|s| s.success()) {
+        eprintln!("skipping: tmux unavailable");
+        return;
+    }
+    let pane = String::from_utf8(server.tmux(&["display-message", "-p", "-t", "=work:", "#{pane_id}"]).stdout)
+        .unwrap()
+        .trim()
+        .to_string();
+
+    let mut relay = Command::new(env!("CARGO_BIN_EXE_wsh"))
+        .args(["__tmux-bridge", &pane, &server.socket])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = relay.stdout.take().unwrap();
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+        while let Ok(n) = stdout.read(&mut buf) {
+            if n == 0 || tx.send(buf[..n].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+    let mut output = Vec::new();
+
+    // The pane's existing contents arrive first, as a redraw.
+    wait_for(&rx, &mut output, "ready");
+    assert!(output.starts_with(b"\x1b[0m\x1b[H\x1b[2J"));
+
+    // Input reaches the program in the pane, and its output comes back.
+    let mut stdin = relay.stdin.take().unwrap();
+    stdin.write_all(b"hello bridge\r").unwrap();
+    stdin.flush().unwrap();
+    wait_for(&rx, &mut output, "hello bridge\r\nhello bridge");
+
+    // Closing the pane ends the relay.
+    server.tmux(&["kill-pane", "-t", &pane]);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let status = loop {
+        if let Some(status) = relay.try_wait().unwrap() {
+            break status;
+        }
+        assert!(Instant::now() < deadline, "relay still running after its pane closed");
+        std::thread::sleep(Duration::from_millis(50));
+    };
+    assert!(status.success());
+}
+
+#[test]
+fn bridge_rejects_missing_pane() {
+    let output = Command::new(env!("CARGO_BIN_EXE_wsh"))
+        .args(["__tmux-bridge", "pane"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("expected a pane id"));
+}
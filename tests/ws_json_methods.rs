@@ -24,13 +24,35 @@ fn create_test_state() -> (api::AppState, mpsc::Receiver<Bytes>, mpsc::Sender<By
     let parser = Parser::spawn(parser_rx, 80, 24, 1000);
     let session = Session {
         name: "test".to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx,
-        output_rx: broker.sender(),
+        output_rx: broker.clone(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -55,8 +77,11 @@ fn create_test_state() -> (api::AppState, mpsc::Receiver<Bytes>, mpsc::Sender<By
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
             backends: wsh::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -189,13 +214,35 @@ async fn test_ws_subscribe_then_events() {
     let parser = Parser::spawn(parser_rx, 80, 24, 1000);
     let session = Session {
         name: "test".to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx,
-        output_rx: broker.sender(),
+        output_rx: broker.clone(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -220,8 +267,11 @@ async fn test_ws_subscribe_then_events() {
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
             backends: wsh::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -262,7 +312,7 @@ async fn test_ws_subscribe_then_events() {
 
     // Send to parser channel and broadcast to reach both parser and subscribers
     _parser_tx.send(Bytes::from("Hello\r\n")).await.unwrap();
-    broker.publish(Bytes::from("Hello\r\n"));
+    broker.publish(b"Hello\r\n");
 
     let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
     let mut found_line = false;
@@ -335,13 +385,35 @@ async fn test_ws_methods_interleaved_with_events() {
     let parser = Parser::spawn(parser_rx, 80, 24, 1000);
     let session = Session {
         name: "test".to_string(),
-        pid: None,
+        pid: Default::default(),
         command: "test".to_string(),
         client_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         tags: std::sync::Arc::new(parking_lot::RwLock::new(std::collections::HashSet::new())),
         child_exited: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        size_arbiter: wsh::terminal::SizeArbiter::default(),
+        usage: wsh::usage::UsageCounters::new(),
+        idle_kill_after: Default::default(),
+        suspended: Default::default(),
+        hibernated: Default::default(),
+        exit_status: Default::default(),
+        restart: Default::default(),
+        scrollback_lines: Default::default(),
+        query_policy: Default::default(),
+        handoff: Default::default(),
+        policy: Default::default(),
+        redactor: Default::default(),
+        resume_tokens: Default::default(),
+        bookmarks: Default::default(),
+        status_line: Default::default(),
+        pipes: Default::default(),
+        approvals: Default::default(),
+        metadata: Default::default(),
+        created_at: Default::default(),
+        run_as: None,
+        safe_mode: None,
+        owner: Default::default(),
         input_tx,
-        output_rx: broker.sender(),
+        output_rx: broker.clone(),
         shutdown: ShutdownCoordinator::new(),
         parser,
         overlays: OverlayStore::new(),
@@ -366,8 +438,11 @@ async fn test_ws_methods_interleaved_with_events() {
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
             backends: wsh::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -403,7 +478,7 @@ async fn test_ws_methods_interleaved_with_events() {
     // Now send a method call WHILE events could be flowing
     // Send to parser channel and broadcast to reach both parser and subscribers
     _parser_tx.send(Bytes::from("data\r\n")).await.unwrap();
-    broker.publish(Bytes::from("data\r\n"));
+    broker.publish(b"data\r\n");
     tokio::time::sleep(Duration::from_millis(50)).await;
 
     tx.send(Message::Text(
@@ -434,3 +509,73 @@ async fn test_ws_methods_interleaved_with_events() {
         "should receive method response even while events are streaming"
     );
 }
+
+#[tokio::test]
+async fn test_ws_resume_replays_missed_events() {
+    let (state, _rx, parser_tx) = create_test_state();
+    let app = api::router(state, api::RouterConfig::default());
+    let addr = start_server(app).await;
+
+    let (ws, _) = connect_async(format!("ws://{}/sessions/test/ws/json", addr))
+        .await
+        .unwrap();
+    let (mut tx, mut rx) = ws.split();
+    let connected = recv_json(&mut rx).await;
+    assert_eq!(connected["connected"], true);
+    let token = connected["resume_token"].as_str().unwrap().to_string();
+
+    tx.send(Message::Text(
+        serde_json::json!({"method": "subscribe", "params": {"events": ["lines"], "format": "plain"}})
+            .to_string()
+            .into(),
+    ))
+    .await
+    .unwrap();
+    assert_eq!(recv_json(&mut rx).await["method"], "subscribe");
+    assert_eq!(recv_json(&mut rx).await["event"], "sync");
+
+    // Drop the socket without a close frame, then produce output while
+    // the client is away.
+    drop(tx);
+    drop(rx);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    parser_tx.send(Bytes::from("Missed\r\n")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let (ws, _) = connect_async(format!("ws://{}/sessions/test/ws/json?resume={}", addr, token))
+        .await
+        .unwrap();
+    let (_tx, mut rx) = ws.split();
+    let resumed = recv_json(&mut rx).await;
+    assert_eq!(resumed["connected"], true);
+    assert_eq!(resumed["resumed"], true);
+    assert_eq!(resumed["resume_token"], token.as_str());
+    assert!(resumed["replayed"].as_u64().unwrap() >= 1);
+
+    // The subscription survived: the missed line is replayed without
+    // subscribing again.
+    let mut found = false;
+    for _ in 0..resumed["replayed"].as_u64().unwrap() {
+        let msg = recv_json(&mut rx).await;
+        if msg["event"] == "line" && msg["line"].to_string().contains("Missed") {
+            found = true;
+        }
+    }
+    assert!(found, "missed line should be replayed");
+}
+
+#[tokio::test]
+async fn test_ws_resume_with_unknown_token_starts_fresh() {
+    let (state, _rx, _parser_tx) = create_test_state();
+    let app = api::router(state, api::RouterConfig::default());
+    let addr = start_server(app).await;
+
+    let (ws, _) = connect_async(format!("ws://{}/sessions/test/ws/json?resume=bogus", addr))
+        .await
+        .unwrap();
+    let (_tx, mut rx) = ws.split();
+    let connected = recv_json(&mut rx).await;
+    assert_eq!(connected["connected"], true);
+    assert_eq!(connected["resumed"], false);
+    assert!(connected["resume_token"].as_str().is_some_and(|t| t != "bogus"));
+}
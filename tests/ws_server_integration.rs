@@ -19,8 +19,11 @@ fn create_empty_state() -> api::AppState {
             server_ws_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             mcp_session_count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             ticket_store: std::sync::Arc::new(wsh::api::ticket::TicketStore::new()),
+            tokens: std::sync::Arc::new(wsh::api::tokens::TokenStore::new()),
             backends: wsh::federation::registry::BackendRegistry::new(),
+            #[cfg(feature = "federation")]
             federation: std::sync::Arc::new(tokio::sync::Mutex::new(wsh::federation::manager::FederationManager::new())),
+            #[cfg(feature = "federation")]
             ip_access: None,
             hostname: "test".to_string(),
             federation_config_path: None,
@@ -890,27 +893,212 @@ async fn test_server_ws_subscribe_activity_events() {
     let mut got_idle_after_running = false;
     let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
     while tokio::time::Instant::now() < deadline && !got_idle_after_running {
-        match try_recv_json(&mut rx, Duration::from_millis(200)).await {
-            Some(msg) => {
-                if let Some(event) = msg.get("event").and_then(|e| e.as_str()) {
-                    match event {
-                        "running" => {
-                            got_running = true;
-                            assert_eq!(msg["session"], "act");
-                        }
-                        "idle" if got_running => {
-                            got_idle_after_running = true;
-                            assert_eq!(msg["session"], "act");
-                            assert!(msg.get("screen").is_some(), "Idle event should have screen data");
-                        }
-                        _ => {}
+        if let Some(msg) = try_recv_json(&mut rx, Duration::from_millis(200)).await {
+            if let Some(event) = msg.get("event").and_then(|e| e.as_str()) {
+                match event {
+                    "running" => {
+                        got_running = true;
+                        assert_eq!(msg["session"], "act");
                     }
+                    "idle" if got_running => {
+                        got_idle_after_running = true;
+                        assert_eq!(msg["session"], "act");
+                        assert!(msg.get("screen").is_some(), "Idle event should have screen data");
+                    }
+                    _ => {}
                 }
             }
-            None => {}
         }
     }
 
     assert!(got_running, "Should receive Running event after input");
     assert!(got_idle_after_running, "Should receive Idle event after activity settles");
 }
+
+// ── Test: subscribe to several sessions, then unsubscribe one ──
+
+#[tokio::test]
+async fn test_server_ws_unsubscribe_stops_one_sessions_events() {
+    let state = create_empty_state();
+    let app = api::router(state, api::RouterConfig::default());
+    let addr = start_server(app).await;
+
+    let (mut tx, mut rx) = connect_server_ws(addr).await;
+
+    let mut id = 0;
+    let mut call = |method: &str, session: Option<&str>, params: serde_json::Value| {
+        id += 1;
+        let mut req = serde_json::json!({"id": id, "method": method, "params": params});
+        if let Some(session) = session {
+            req["session"] = serde_json::json!(session);
+        }
+        Message::Text(req.to_string().into())
+    };
+
+    for name in ["left", "right"] {
+        tx.send(call("create_session", None, serde_json::json!({"name": name})))
+            .await
+            .unwrap();
+        tx.send(call(
+            "subscribe",
+            Some(name),
+            serde_json::json!({"events": ["lines"], "format": "plain"}),
+        ))
+        .await
+        .unwrap();
+    }
+    let mut subscribed = 0;
+    while subscribed < 2 {
+        let msg = recv_json(&mut rx).await;
+        if msg.get("method") == Some(&serde_json::json!("subscribe")) {
+            assert!(msg.get("error").is_none(), "subscribe failed: {}", msg);
+            subscribed += 1;
+        }
+    }
+
+    tx.send(call("unsubscribe", Some("left"), serde_json::json!({})))
+        .await
+        .unwrap();
+    loop {
+        let msg = recv_json(&mut rx).await;
+        if msg.get("method") == Some(&serde_json::json!("unsubscribe")) {
+            assert_eq!(msg["result"]["unsubscribed"], true);
+            break;
+        }
+    }
+    // Unsubscribing again, or from a session that doesn't exist, is a no-op.
+    tx.send(call("unsubscribe", Some("nope"), serde_json::json!({})))
+        .await
+        .unwrap();
+    loop {
+        let msg = recv_json(&mut rx).await;
+        if msg.get("method") == Some(&serde_json::json!("unsubscribe")) {
+            assert_eq!(msg["result"]["unsubscribed"], false);
+            break;
+        }
+    }
+
+    for name in ["left", "right"] {
+        tx.send(call(
+            "send_input",
+            Some(name),
+            serde_json::json!({"data": format!("echo from-{}\r", name)}),
+        ))
+        .await
+        .unwrap();
+    }
+
+    let mut right_lines = 0;
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(3);
+    while tokio::time::Instant::now() < deadline {
+        let Some(msg) = try_recv_json(&mut rx, Duration::from_millis(200)).await else {
+            continue;
+        };
+        if msg.get("event") == Some(&serde_json::json!("line")) {
+            assert_eq!(msg["session"], "right", "event after unsubscribe: {}", msg);
+            right_lines += 1;
+        }
+    }
+    assert!(right_lines > 0, "still subscribed session should send lines");
+}
+
+#[tokio::test]
+async fn test_server_ws_attach_streams_tagged_output_until_detach() {
+    use base64::Engine;
+
+    let state = create_empty_state();
+    let app = api::router(state.clone(), api::RouterConfig::default());
+    let addr = start_server(app).await;
+
+    let (mut tx, mut rx) = connect_server_ws(addr).await;
+
+    let mut id = 0;
+    let mut call = |method: &str, session: Option<&str>, params: serde_json::Value| {
+        id += 1;
+        let mut req = serde_json::json!({"id": id, "method": method, "params": params});
+        if let Some(session) = session {
+            req["session"] = serde_json::json!(session);
+        }
+        Message::Text(req.to_string().into())
+    };
+
+    for name in ["left", "right"] {
+        tx.send(call("create_session", None, serde_json::json!({"name": name})))
+            .await
+            .unwrap();
+        tx.send(call("attach", Some(name), serde_json::json!({})))
+            .await
+            .unwrap();
+    }
+    let mut attached = 0;
+    while attached < 2 {
+        let msg = recv_json(&mut rx).await;
+        if msg.get("method") == Some(&serde_json::json!("attach")) {
+            assert!(msg.get("error").is_none(), "attach failed: {}", msg);
+            assert_eq!(msg["result"]["rows"], 24);
+            assert_eq!(msg["result"]["cols"], 80);
+            attached += 1;
+        }
+    }
+    // An attached socket counts as a client of the session.
+    assert_eq!(state.sessions.get("left").unwrap().clients(), 1);
+
+    // Output arrives as base64 frames tagged with the session's name.
+    for name in ["left", "right"] {
+        tx.send(call(
+            "send_input",
+            Some(name),
+            serde_json::json!({"data": format!("echo out-{}\r", name)}),
+        ))
+        .await
+        .unwrap();
+    }
+    let mut output = std::collections::HashMap::<String, String>::new();
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    while tokio::time::Instant::now() < deadline
+        && !["left", "right"].iter().all(|n| {
+            output.get(*n).is_some_and(|o| o.contains(&format!("out-{}", n)))
+        })
+    {
+        let Some(msg) = try_recv_json(&mut rx, Duration::from_millis(200)).await else {
+            continue;
+        };
+        if msg.get("event") == Some(&serde_json::json!("output")) {
+            let data = base64::engine::general_purpose::STANDARD
+                .decode(msg["data"].as_str().unwrap())
+                .unwrap();
+            output
+                .entry(msg["session"].as_str().unwrap().to_string())
+                .or_default()
+                .push_str(&String::from_utf8_lossy(&data));
+        }
+    }
+    assert!(output["left"].contains("out-left"), "left output: {:?}", output.get("left"));
+    assert!(output["right"].contains("out-right"), "right output: {:?}", output.get("right"));
+    assert!(!output["left"].contains("out-right"));
+
+    tx.send(call("detach", Some("left"), serde_json::json!({})))
+        .await
+        .unwrap();
+    loop {
+        let msg = recv_json(&mut rx).await;
+        if msg.get("method") == Some(&serde_json::json!("detach")) {
+            assert_eq!(msg["result"]["detached"], true);
+            break;
+        }
+    }
+    assert_eq!(state.sessions.get("left").unwrap().clients(), 0);
+
+    tx.send(call("send_input", Some("left"), serde_json::json!({"data": "echo gone\r"})))
+        .await
+        .unwrap();
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(1);
+    while tokio::time::Instant::now() < deadline {
+        let Some(msg) = try_recv_json(&mut rx, Duration::from_millis(200)).await else {
+            continue;
+        };
+        if msg.get("event") == Some(&serde_json::json!("output")) {
+            assert_eq!(msg["session"], "right", "output after detach: {}", msg);
+        }
+    }
+}
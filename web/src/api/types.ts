@@ -39,6 +39,25 @@ export interface Cursor {
   visible: boolean;
 }
 
+/** Keyboard modes requested by the application (see src/parser/state.rs). */
+export interface KeyboardModes {
+  application_cursor: boolean;
+  application_keypad: boolean;
+  kitty_flags: number;
+  modify_other_keys: number;
+}
+
+/** How the application wants pastes, mouse and focus input delivered. */
+export interface InputModes {
+  bracketed_paste: boolean;
+  mouse_tracking: "off" | "x10" | "normal" | "button" | "any";
+  mouse_encoding: "default" | "utf8" | "sgr" | "urxvt" | "sgr_pixels";
+  focus_events: boolean;
+}
+
+/** Every input mode the application has negotiated (`get_modes`). */
+export type TerminalModes = { alternate_active: boolean } & KeyboardModes & InputModes;
+
 export interface ScreenResponse {
   epoch: number;
   first_line_index: number;
@@ -48,6 +67,7 @@ export interface ScreenResponse {
   cols: number;
   rows: number;
   alternate_active: boolean;
+  keyboard: KeyboardModes;
 }
 
 export interface ScrollbackResponse {
@@ -62,7 +82,7 @@ export interface ScrollbackResponse {
 export type Event =
   | { event: "line"; seq: number; index: number; total_lines: number; line: FormattedLine }
   | { event: "cursor"; seq: number; row: number; col: number; visible: boolean }
-  | { event: "mode"; seq: number; alternate_active: boolean }
+  | { event: "mode"; seq: number; alternate_active: boolean; keyboard: KeyboardModes; input: InputModes }
   | { event: "reset"; seq: number; reason: string }
   | { event: "sync"; seq: number; screen: ScreenResponse; scrollback_lines: number }
   | { event: "diff"; seq: number; changed_lines: number[]; screen: ScreenResponse }
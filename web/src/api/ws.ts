@@ -1,4 +1,4 @@
-import type { WsRequest, WsResponse, EventType, ScreenResponse, ScrollbackResponse, SessionInfo, ServerInfo } from "./types";
+import type { WsRequest, WsResponse, EventType, ScreenResponse, ScrollbackResponse, SessionInfo, ServerInfo, TerminalModes } from "./types";
 
 type PendingRequest = {
   resolve: (value: unknown) => void;
@@ -453,6 +453,11 @@ export class WshClient {
     return result as ScreenResponse;
   }
 
+  async getModes(session: string): Promise<TerminalModes> {
+    const result = await this.request("get_modes", {}, session);
+    return result as TerminalModes;
+  }
+
   async sendInput(session: string, data: string): Promise<void> {
     // Input clears resize suppression — any subsequent PTY output is a
     // response to this input, not a SIGWINCH redraw.
@@ -528,8 +533,14 @@ export class WshClient {
     // Return unsubscribe function
     return () => {
       set!.delete(callback);
-      if (set!.size === 0) {
-        this.eventCallbacks.delete(session);
+      if (set!.size > 0) return;
+      // Look the session up by its callback set: it may have been renamed
+      // since it was subscribed.
+      for (const [name, callbacks] of this.eventCallbacks) {
+        if (callbacks !== set) continue;
+        this.eventCallbacks.delete(name);
+        // Stop the server sending events nobody is listening to.
+        this.request("unsubscribe", {}, name).catch(() => {});
       }
     };
   }
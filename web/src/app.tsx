@@ -280,11 +280,21 @@ async function setupSession(
   client: WshClient,
   name: string,
 ): Promise<void> {
-  const screen = await client.getScreen(name, "styled");
+  const [screen, modes] = await Promise.all([
+    client.getScreen(name, "styled"),
+    client.getModes(name),
+  ]);
   setFullScreen(name, {
     lines: screen.lines,
     cursor: screen.cursor,
     alternateActive: screen.alternate_active,
+    keyboard: screen.keyboard,
+    input: {
+      bracketed_paste: modes.bracketed_paste,
+      mouse_tracking: modes.mouse_tracking,
+      mouse_encoding: modes.mouse_encoding,
+      focus_events: modes.focus_events,
+    },
     cols: screen.cols,
     rows: screen.rows,
     firstLineIndex: screen.first_line_index,
@@ -436,6 +446,8 @@ function handleEvent(client: WshClient, session: string, raw: any): void {
         lines: screen.lines,
         cursor: screen.cursor,
         alternateActive: screen.alternate_active,
+        keyboard: screen.keyboard,
+        input: current.input,
         cols: screen.cols,
         rows: screen.rows,
         firstLineIndex: screen.first_line_index,
@@ -469,7 +481,7 @@ function handleEvent(client: WshClient, session: string, raw: any): void {
       break;
 
     case "mode":
-      updateScreen(session, { alternateActive: raw.alternate_active });
+      updateScreen(session, { alternateActive: raw.alternate_active, keyboard: raw.keyboard, input: raw.input });
       break;
 
     case "reset":
@@ -479,6 +491,8 @@ function handleEvent(client: WshClient, session: string, raw: any): void {
           lines: screen.lines,
           cursor: screen.cursor,
           alternateActive: screen.alternate_active,
+          keyboard: screen.keyboard,
+          input: getScreen(session).input,
           cols: screen.cols,
           rows: screen.rows,
           firstLineIndex: screen.first_line_index,
@@ -84,7 +84,7 @@ export function InputBar({ session, client }: InputBarProps) {
   };
 
   const handleKeyDown = (e: KeyboardEvent) => {
-    const seq = keyToSequence(e);
+    const seq = keyToSequence(e, getScreen(session).keyboard);
     if (seq !== null) {
       e.preventDefault();
       send(seq);
@@ -1,5 +1,5 @@
 import { useRef, useEffect, useCallback } from "preact/hooks";
-import { getScreenSignal, updateScreen } from "../state/terminal";
+import { getScreen, getScreenSignal, updateScreen } from "../state/terminal";
 import { connectionState, focusedSession, zoomLevel } from "../state/sessions";
 import { spanStyle } from "../utils/terminal";
 import { keyToSequence } from "../utils/keymap";
@@ -141,7 +141,7 @@ export function Terminal({ session, client, captureInput }: TerminalProps) {
     if (!client) return;
     // Let Ctrl+Shift combos bubble up for UI shortcuts
     if (e.ctrlKey && e.shiftKey) return;
-    const seq = keyToSequence(e);
+    const seq = keyToSequence(e, getScreen(session).keyboard);
     if (seq !== null) {
       e.preventDefault();
       client.sendInput(session, seq).catch(() => {});
@@ -160,6 +160,15 @@ export function Terminal({ session, client, captureInput }: TerminalProps) {
     }
   }, [client, session]);
 
+  // Wrap pastes in bracketed paste markers when the application asked for them
+  const handleTextareaPaste = useCallback((e: ClipboardEvent) => {
+    if (!client) return;
+    const text = e.clipboardData?.getData("text/plain");
+    if (!text || !getScreen(session).input.bracketed_paste) return;
+    e.preventDefault();
+    client.sendInput(session, "\x1b[200~" + text + "\x1b[201~").catch(() => {});
+  }, [client, session]);
+
   // Click on terminal container focuses the hidden textarea
   const handleContainerClick = useCallback(() => {
     if (captureInput && textareaRef.current) {
@@ -377,6 +386,7 @@ export function Terminal({ session, client, captureInput }: TerminalProps) {
           class="terminal-hidden-input"
           onKeyDown={handleTextareaKeyDown}
           onInput={handleTextareaInput}
+          onPaste={handleTextareaPaste}
           autocomplete="off"
           autocapitalize="off"
           autocorrect="off"
@@ -1,10 +1,14 @@
 import { signal, type Signal } from "@preact/signals";
-import type { FormattedLine, Cursor } from "../api/types";
+import type { FormattedLine, Cursor, InputModes, KeyboardModes } from "../api/types";
 
 export interface ScreenState {
   lines: FormattedLine[];
   cursor: Cursor;
   alternateActive: boolean;
+  /** Keyboard modes the application has enabled, used to encode keys. */
+  keyboard: KeyboardModes;
+  /** Paste, mouse and focus modes, used to encode pastes. */
+  input: InputModes;
   cols: number;
   rows: number;
   firstLineIndex: number;
@@ -25,6 +29,8 @@ function makeEmptyScreen(): ScreenState {
     lines: [],
     cursor: { row: 0, col: 0, visible: true },
     alternateActive: false,
+    keyboard: { application_cursor: false, application_keypad: false, kitty_flags: 0, modify_other_keys: 0 },
+    input: { bracketed_paste: false, mouse_tracking: "off", mouse_encoding: "default", focus_events: false },
     cols: 80,
     rows: 24,
     firstLineIndex: 0,
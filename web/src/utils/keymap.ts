@@ -1,6 +1,51 @@
-import type { FormattedLine } from "../api/types";
+import type { FormattedLine, KeyboardModes } from "../api/types";
 
-export function keyToSequence(e: KeyboardEvent): string | null {
+const CURSOR_FINALS: Record<string, string> = {
+  ArrowUp: "A",
+  ArrowDown: "B",
+  ArrowRight: "C",
+  ArrowLeft: "D",
+  Home: "H",
+  End: "F",
+};
+
+const KEYPAD_FINALS: Record<string, string> = {
+  Numpad0: "p", Numpad1: "q", Numpad2: "r", Numpad3: "s", Numpad4: "t",
+  Numpad5: "u", Numpad6: "v", Numpad7: "w", Numpad8: "x", Numpad9: "y",
+  NumpadEnter: "M", NumpadAdd: "k", NumpadSubtract: "m",
+  NumpadMultiply: "j", NumpadDivide: "o", NumpadDecimal: "n",
+};
+
+/**
+ * Encode a key event for the terminal. `modes` are the keyboard modes the
+ * application has enabled (application cursor keys, application keypad,
+ * kitty keyboard protocol, modifyOtherKeys); without them, legacy xterm
+ * encodings are used.
+ */
+export function keyToSequence(e: KeyboardEvent, modes?: KeyboardModes): string | null {
+  const plain = !e.ctrlKey && !e.altKey && !e.metaKey;
+  if (modes?.application_keypad && plain && e.code in KEYPAD_FINALS) {
+    return "\x1bO" + KEYPAD_FINALS[e.code];
+  }
+  const disambiguate = ((modes?.kitty_flags ?? 0) & 1) !== 0;
+  if (disambiguate && (e.ctrlKey || e.altKey) && !e.metaKey && e.key.length === 1) {
+    const mod = 1 + (e.shiftKey ? 1 : 0) + (e.altKey ? 2 : 0) + (e.ctrlKey ? 4 : 0);
+    return `\x1b[${e.key.toLowerCase().codePointAt(0)};${mod}u`;
+  }
+  // xterm modifyOtherKeys, when the kitty protocol isn't on: level 2
+  // reports every Ctrl/Alt character, level 1 only those with no
+  // control byte.
+  const otherKeys = (modes?.kitty_flags ?? 0) === 0 ? (modes?.modify_other_keys ?? 0) : 0;
+  if (otherKeys > 0 && (e.ctrlKey || e.altKey) && !e.metaKey && e.key.length === 1) {
+    const hasControlByte = /^[a-zA-Z@ [\\\]^_?2-8/]$/.test(e.key);
+    if (otherKeys >= 2 || (e.ctrlKey && !hasControlByte)) {
+      const mod = 1 + (e.shiftKey ? 1 : 0) + (e.altKey ? 2 : 0) + (e.ctrlKey ? 4 : 0);
+      return `\x1b[27;${mod};${e.key.codePointAt(0)}~`;
+    }
+  }
+  if (e.key in CURSOR_FINALS && plain && !e.shiftKey) {
+    return (modes?.application_cursor ? "\x1bO" : "\x1b[") + CURSOR_FINALS[e.key];
+  }
   if (e.ctrlKey && !e.altKey && !e.metaKey) {
     const key = e.key.toLowerCase();
     if (key.length === 1 && key >= "a" && key <= "z") {
@@ -20,7 +65,7 @@ export function keyToSequence(e: KeyboardEvent): string | null {
     case "Enter": return "\r";
     case "Backspace": return "\x7f";
     case "Tab": return "\t";
-    case "Escape": return "\x1b";
+    case "Escape": return disambiguate ? "\x1b[27u" : "\x1b";
     case "ArrowUp": return "\x1b[A";
     case "ArrowDown": return "\x1b[B";
     case "ArrowRight": return "\x1b[C";